@@ -0,0 +1,99 @@
+//! Throughput and size of file chunks packed by `ChunkCompressor` at
+//! a few zstd levels, for text (this crate's own sources, repeated)
+//! and for already-compressed data. There is no JPEG corpus in the
+//! tree; xorshift noise stands in for one, since a JPEG's entropy-coded
+//! body is just as incompressible. Noise should come out raw at every
+//! level and cost little more than the attempt.
+//!
+//! Run with `cargo bench -p tix-core --bench chunk_compression`.
+
+use std::time::{Duration, Instant};
+
+use tix_core::protocol::ChunkCompressor;
+use tix_core::protocol::file::DEFAULT_CHUNK_SIZE;
+
+const SOURCES: [&str; 4] = [
+    include_str!("../src/protocol/file.rs"),
+    include_str!("../src/protocol/update.rs"),
+    include_str!("../src/network/upload.rs"),
+    include_str!("../src/packet.rs"),
+];
+const CORPUS_BYTES: usize = 16 * 1024 * 1024;
+const LEVELS: [i32; 4] = [0, 1, 3, 6];
+
+fn text() -> Vec<u8> {
+    SOURCES
+        .iter()
+        .flat_map(|s| s.bytes())
+        .cycle()
+        .take(CORPUS_BYTES)
+        .collect()
+}
+
+fn noise() -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..CORPUS_BYTES)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn mib_per_sec(bytes: usize, d: Duration) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0) / d.as_secs_f64()
+}
+
+fn run(name: &str, corpus: &[u8]) {
+    for level in LEVELS {
+        let compressor = ChunkCompressor::new(level);
+        let start = Instant::now();
+        let chunks: Vec<_> = corpus
+            .chunks(DEFAULT_CHUNK_SIZE)
+            .enumerate()
+            .map(|(i, data)| {
+                compressor.pack((i * DEFAULT_CHUNK_SIZE) as u64, i as u64, data.to_vec())
+            })
+            .collect();
+        let pack = start.elapsed();
+
+        let sent: usize = chunks.iter().map(|c| c.data.len()).sum();
+        let compressed = chunks.iter().filter(|c| c.compressed).count();
+
+        let start = Instant::now();
+        let restored: usize = chunks
+            .into_iter()
+            .map(|c| c.unpack(DEFAULT_CHUNK_SIZE as u32).unwrap().data.len())
+            .sum();
+        let unpack = start.elapsed();
+        assert_eq!(restored, corpus.len());
+
+        println!(
+            "{:>5} {:>5} {:>10} {:>6.1}% {:>6}/{:<6} {:>10.0} {:>10.0}",
+            name,
+            level,
+            sent,
+            sent as f64 * 100.0 / corpus.len() as f64,
+            compressed,
+            corpus.len().div_ceil(DEFAULT_CHUNK_SIZE),
+            mib_per_sec(corpus.len(), pack),
+            mib_per_sec(corpus.len(), unpack),
+        );
+    }
+}
+
+fn main() {
+    println!(
+        "{} MiB per corpus in {} KiB chunks",
+        CORPUS_BYTES / (1024 * 1024),
+        DEFAULT_CHUNK_SIZE / 1024
+    );
+    println!(
+        "{:>5} {:>5} {:>10} {:>7} {:>13} {:>10} {:>10}",
+        "data", "level", "sent", "ratio", "compressed", "pack MiB/s", "unpk MiB/s"
+    );
+    run("text", &text());
+    run("noise", &noise());
+}
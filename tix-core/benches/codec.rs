@@ -0,0 +1,99 @@
+//! The control channel's hot path: building a packet (which hashes its
+//! payload with Blake3), checking the hash on arrival, and `TixCodec`
+//! encoding and decoding, for 1 KiB, 64 KiB and 200 KiB payloads. The
+//! partial-buffer decode feeds the frame in 1400-byte segments, as TCP
+//! hands it over, and asks for a packet after each one.
+//!
+//! Run with `cargo bench -p tix-core --bench codec`; `TIX_BENCH_QUICK=1`
+//! for a short smoke run.
+
+mod support;
+
+use std::hint::black_box;
+
+use bytes::BytesMut;
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use tix_core::{Command, Packet, TixCodec};
+use tokio_util::codec::{Decoder, Encoder};
+
+const PAYLOADS: [(&str, usize); 3] = [("1KiB", 1024), ("64KiB", 64 * 1024), ("200KiB", 200 * 1024)];
+
+/// A TCP segment's worth of bytes.
+const SEGMENT: usize = 1400;
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+fn packet(c: &mut Criterion) {
+    let mut group = c.benchmark_group("packet");
+    for (name, len) in PAYLOADS {
+        let data = payload(len);
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_with_input(BenchmarkId::new("build", name), &data, |b, data| {
+            b.iter_batched(
+                || data.clone(),
+                |data| Packet::new_command(1, Command::FileWrite, data).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+        let packet = Packet::new_command(1, Command::FileWrite, data).unwrap();
+        group.bench_with_input(BenchmarkId::new("verify", name), &packet, |b, packet| {
+            b.iter(|| black_box(packet.validate_checksum()))
+        });
+    }
+    group.finish();
+}
+
+fn codec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("codec");
+    for (name, len) in PAYLOADS {
+        let packet = Packet::new_command(1, Command::FileWrite, payload(len)).unwrap();
+        let mut wire = BytesMut::new();
+        TixCodec.encode(packet.clone(), &mut wire).unwrap();
+        group.throughput(Throughput::Bytes(wire.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("encode", name), &packet, |b, packet| {
+            b.iter_batched(
+                || packet.clone(),
+                |packet| {
+                    let mut dst = BytesMut::with_capacity(wire.len());
+                    TixCodec.encode(packet, &mut dst).unwrap();
+                    dst
+                },
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("decode", name), &wire, |b, wire| {
+            b.iter_batched(
+                || wire.clone(),
+                |mut src| TixCodec.decode(&mut src).unwrap().unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(
+            BenchmarkId::new("decode_partial", name),
+            &wire,
+            |b, wire| {
+                b.iter(|| {
+                    let mut src = BytesMut::new();
+                    for segment in wire.chunks(SEGMENT) {
+                        src.extend_from_slice(segment);
+                        if let Some(packet) = TixCodec.decode(&mut src).unwrap() {
+                            return packet;
+                        }
+                    }
+                    unreachable!("the whole frame was fed")
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = support::criterion();
+    targets = packet, codec
+}
+criterion_main!(benches);
@@ -0,0 +1,72 @@
+//! Compressed size of the held-out delta samples in
+//! `tests/fixtures/deltas-eval.bin` with and without the built-in
+//! dictionary, at the levels the adaptive encoder moves between, for
+//! deltas of a few tiles and for all of them. Also times compressing
+//! every sample once.
+//!
+//! Run with `cargo bench -p tix-core --bench dictionary`.
+
+use std::time::{Duration, Instant};
+
+use tix_core::rdp::dictionary::{FrameDictionary, decode_samples};
+
+const HELD_OUT: &[u8] = include_bytes!("../tests/fixtures/deltas-eval.bin");
+const LEVELS: [i32; 3] = [1, 3, 6];
+/// Deltas up to this size count as small.
+const SMALL: usize = 64 * 1024;
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// Compressed bytes of the small samples and of all of them, and the
+/// time taken.
+fn compress(
+    samples: &[Vec<u8>],
+    mut compressor: zstd::bulk::Compressor<'_>,
+) -> (usize, usize, Duration) {
+    let (mut small, mut all) = (0, 0);
+    let start = Instant::now();
+    for sample in samples {
+        let len = compressor.compress(sample).unwrap().len();
+        if sample.len() <= SMALL {
+            small += len;
+        }
+        all += len;
+    }
+    (small, all, start.elapsed())
+}
+
+fn main() {
+    let samples = decode_samples(HELD_OUT).unwrap();
+    let dict = FrameDictionary::builtin();
+    let raw: usize = samples.iter().map(Vec::len).sum();
+    println!(
+        "{} samples, {} small, {raw} bytes raw",
+        samples.len(),
+        samples.iter().filter(|s| s.len() <= SMALL).count()
+    );
+
+    println!(
+        "{:>5} {:>10} {:>10} {:>7} {:>10} {:>10} {:>7} {:>10} {:>10}",
+        "level", "small", "dict", "saved", "all", "dict", "saved", "plain", "dict"
+    );
+    for level in LEVELS {
+        let plain = compress(&samples, zstd::bulk::Compressor::new(level).unwrap());
+        let with_dict = compress(
+            &samples,
+            zstd::bulk::Compressor::with_dictionary(level, dict.bytes()).unwrap(),
+        );
+        println!(
+            "{level:>5} {:>10} {:>10} {:>6.1}% {:>10} {:>10} {:>6.1}% {:>7.2} ms {:>7.2} ms",
+            plain.0,
+            with_dict.0,
+            100.0 * (1.0 - with_dict.0 as f64 / plain.0 as f64),
+            plain.1,
+            with_dict.1,
+            100.0 * (1.0 - with_dict.1 as f64 / plain.1 as f64),
+            ms(plain.2),
+            ms(with_dict.2),
+        );
+    }
+}
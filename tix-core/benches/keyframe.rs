@@ -0,0 +1,104 @@
+//! Payload size of a 1920×1080 frame in which every pixel changed (a
+//! window maximised, a slide advanced), sent as per-tile deltas and as
+//! the full frame the scene-change rule promotes it to, for each
+//! supported block size. Also times both encodings.
+//!
+//! Run with `cargo bench -p tix-core --bench keyframe`.
+
+use std::time::{Duration, Instant};
+
+use tix_core::rdp::delta::{BLOCK_SIZES, Block, DeltaFrame};
+use tix_core::rdp::encoder::AdaptiveEncoder;
+use tix_core::rdp::types::{PixelFormat, RawScreenFrame};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+const ITERATIONS: u32 = 10;
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// A new picture: a window with lines of "text" over a gradient.
+fn scene() -> RawScreenFrame {
+    let stride = WIDTH * 4;
+    let mut data = vec![0u8; (stride * HEIGHT) as usize];
+    for (i, px) in data.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i as u32 % WIDTH, i as u32 / WIDTH);
+        let ink = y % 22 < 13 && (x / 9 + y / 22) % 6 != 0 && (x * 11 + y * 5) % 13 < 5;
+        let px_value = if ink {
+            [0x18, 0x18, 0x18, 0xFF]
+        } else {
+            [(x / 8) as u8, (y / 5) as u8, 0xC0, 0xFF]
+        };
+        px.copy_from_slice(&px_value);
+    }
+    RawScreenFrame {
+        width: WIDTH,
+        height: HEIGHT,
+        stride,
+        format: PixelFormat::Bgra8,
+        data,
+        timestamp: Instant::now(),
+    }
+}
+
+/// A delta listing every tile of the screen.
+fn every_block(block_size: usize, frame: &RawScreenFrame) -> DeltaFrame {
+    let size = block_size as u32;
+    let mut changed_blocks = Vec::new();
+    for y in (0..HEIGHT).step_by(block_size) {
+        for x in (0..WIDTH).step_by(block_size) {
+            changed_blocks.push(Block {
+                x,
+                y,
+                width: size.min(WIDTH - x),
+                height: size.min(HEIGHT - y),
+            });
+        }
+    }
+    DeltaFrame {
+        frame_number: 1,
+        timestamp: frame.timestamp,
+        width: WIDTH,
+        height: HEIGHT,
+        changed_blocks,
+        full_frame: false,
+    }
+}
+
+/// Encoded size and mean encode time of `delta`.
+fn encode(delta: &DeltaFrame, frame: &RawScreenFrame) -> (usize, Duration) {
+    let mut encoder = AdaptiveEncoder::new(100 * 1024 * 1024);
+    let len = encoder.encode(delta, frame).unwrap().data.len();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        encoder.encode(delta, frame).unwrap();
+    }
+    (len, start.elapsed() / ITERATIONS)
+}
+
+fn main() {
+    let frame = scene();
+    let full = DeltaFrame {
+        full_frame: true,
+        ..every_block(BLOCK_SIZES[0], &frame)
+    };
+    let (full_len, full_time) = encode(&full, &frame);
+
+    println!(
+        "{:>5} {:>8} {:>10} {:>10} {:>7} {:>10} {:>10}",
+        "block", "blocks", "tiles", "full", "saved", "tiles", "full"
+    );
+    for block_size in BLOCK_SIZES {
+        let delta = every_block(block_size, &frame);
+        let (tiles_len, tiles_time) = encode(&delta, &frame);
+        println!(
+            "{block_size:>5} {:>8} {tiles_len:>10} {full_len:>10} {:>6.1}% {:>7.2} ms {:>7.2} ms",
+            delta.changed_blocks.len(),
+            100.0 * (1.0 - full_len as f64 / tiles_len as f64),
+            ms(tiles_time),
+            ms(full_time),
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Delta detection by hashing tiles in parallel against the byte-wise
+//! sequential comparison it replaced, at 1080p, 1440p and 4K with 1 %,
+//! 10 % and 100 % of the pixels changing between frames.
+//!
+//! Run with `cargo bench -p tix-core --bench parallel_delta`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use tix_core::rdp::delta::{DeltaDetector, DeltaStrategy};
+use tix_core::rdp::types::{PixelFormat, RawScreenFrame};
+
+const BLOCK_SIZE: usize = 64;
+const RESOLUTIONS: [(&str, u32, u32); 3] = [
+    ("1080p", 1920, 1080),
+    ("1440p", 2560, 1440),
+    ("4K", 3840, 2160),
+];
+const CHANGED_PERCENT: [u32; 3] = [1, 10, 100];
+
+fn frame(width: u32, height: u32) -> RawScreenFrame {
+    let stride = width * 4;
+    let data = (0..(stride * height) as usize)
+        .map(|i| (i * 31 % 251) as u8)
+        .collect();
+    RawScreenFrame {
+        width,
+        height,
+        stride,
+        format: PixelFormat::Bgra8,
+        data,
+        timestamp: Instant::now(),
+    }
+}
+
+/// `base` with the top `percent` of its rows inverted.
+fn changed(base: &RawScreenFrame, percent: u32) -> RawScreenFrame {
+    let mut frame = base.clone();
+    let rows = (base.height * percent).div_ceil(100) as usize;
+    for byte in &mut frame.data[..rows * base.stride as usize] {
+        *byte = !*byte;
+    }
+    frame
+}
+
+fn detect(c: &mut Criterion) {
+    for (name, width, height) in RESOLUTIONS {
+        let mut group = c.benchmark_group(format!("delta/{}", name));
+        group.sample_size(20);
+        let base = frame(width, height);
+        for percent in CHANGED_PERCENT {
+            // Alternating between the two frames changes the same
+            // pixels every time.
+            let frames = [base.clone(), changed(&base, percent)];
+            for (label, strategy) in [
+                ("exact", DeltaStrategy::Exact),
+                ("hashed", DeltaStrategy::Hashed),
+            ] {
+                let mut detector = DeltaDetector::new(BLOCK_SIZE).with_strategy(strategy);
+                detector.detect(&frames[0]);
+                let mut next = 0;
+                group.bench_with_input(
+                    BenchmarkId::new(label, format!("{}%", percent)),
+                    &frames,
+                    |b, frames| {
+                        b.iter(|| {
+                            next ^= 1;
+                            black_box(detector.detect(black_box(&frames[next])))
+                        })
+                    },
+                );
+            }
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, detect);
+criterion_main!(benches);
@@ -0,0 +1,53 @@
+//! Throughput of the reduced-colour pixel conversions on a 1080p frame.
+//!
+//! Run with `cargo bench -p tix-core --bench pixel_convert`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use tix_core::rdp::convert::{bgra_to_gray8, bgra_to_rgb565, gray8_to_bgra, rgb565_to_bgra};
+
+const WIDTH: usize = 1920;
+const HEIGHT: usize = 1080;
+const ITERATIONS: u32 = 50;
+
+fn bench(name: &str, bytes_in: usize, mut f: impl FnMut()) {
+    f(); // warm up
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_iter = start.elapsed() / ITERATIONS;
+    let mpix = (WIDTH * HEIGHT) as f64 / per_iter.as_secs_f64() / 1e6;
+    let gbps = bytes_in as f64 / per_iter.as_secs_f64() / 1e9;
+    println!(
+        "{name:<16} {:>8.3} ms/frame  {mpix:>8.1} Mpix/s  {gbps:>6.2} GB/s in",
+        ms(per_iter)
+    );
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn main() {
+    let pixels = WIDTH * HEIGHT;
+    let bgra: Vec<u8> = (0..pixels * 4).map(|i| (i * 31 % 251) as u8).collect();
+    let mut rgb565 = vec![0u8; pixels * 2];
+    let mut gray = vec![0u8; pixels];
+    let mut back = vec![0u8; pixels * 4];
+
+    bench("bgra -> rgb565", bgra.len(), || {
+        bgra_to_rgb565(black_box(&bgra), &mut rgb565)
+    });
+    bench("rgb565 -> bgra", rgb565.len(), || {
+        rgb565_to_bgra(black_box(&rgb565), &mut back)
+    });
+    bench("bgra -> gray8", bgra.len(), || {
+        bgra_to_gray8(black_box(&bgra), &mut gray)
+    });
+    bench("gray8 -> bgra", gray.len(), || {
+        gray8_to_bgra(black_box(&gray), &mut back)
+    });
+    black_box(&back);
+}
@@ -0,0 +1,111 @@
+//! Payload size of a 1920×1080 delta made of scattered small changes
+//! (a cursor trail, blinking carets, a clock), encoded block by block
+//! and after [`RegionMerger`] has coalesced neighbouring tiles, for each
+//! supported block size. Also times the merge pass itself.
+//!
+//! Run with `cargo bench -p tix-core --bench region_merge`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use tix_core::rdp::delta::{BLOCK_SIZES, DeltaDetector, DeltaFrame};
+use tix_core::rdp::encoder::AdaptiveEncoder;
+use tix_core::rdp::merge::{DEFAULT_MAX_WASTE_PERCENT, RegionMerger};
+use tix_core::rdp::types::{PixelFormat, RawScreenFrame};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+const ITERATIONS: u32 = 200;
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// A mostly flat desktop with a few lines of "text" so the encoder has
+/// something realistic to compress.
+fn desktop() -> RawScreenFrame {
+    let stride = WIDTH * 4;
+    let mut data = vec![0u8; (stride * HEIGHT) as usize];
+    for (i, px) in data.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i as u32 % WIDTH, i as u32 / WIDTH);
+        let ink = y % 20 < 12 && (x / 7 + y / 20) % 5 != 0 && (x * 13 + y * 7) % 11 < 4;
+        let shade = if ink { 0x20 } else { 0xE8 };
+        px.copy_from_slice(&[shade, shade, shade, 0xFF]);
+    }
+    RawScreenFrame {
+        width: WIDTH,
+        height: HEIGHT,
+        stride,
+        format: PixelFormat::Bgra8,
+        data,
+        timestamp: Instant::now(),
+    }
+}
+
+fn fill(frame: &mut RawScreenFrame, x: u32, y: u32, w: u32, h: u32, value: u8) {
+    let stride = frame.stride as usize;
+    for row in y..(y + h).min(frame.height) {
+        let start = row as usize * stride + x as usize * 4;
+        let end = row as usize * stride + (x + w).min(frame.width) as usize * 4;
+        for px in frame.data[start..end].chunks_exact_mut(4) {
+            px.copy_from_slice(&[value, value / 2, 0x40, 0xFF]);
+        }
+    }
+}
+
+/// The changes of one typical idle-ish frame.
+fn scatter(frame: &mut RawScreenFrame) {
+    // Cursor trail: a 12×19 pointer drawn at eight points of a stroke.
+    for i in 0..8 {
+        fill(frame, 400 + i * 21, 300 + i * 9, 12, 19, 0x10);
+    }
+    // Blinking carets in three text fields.
+    for &(x, y) in &[(130, 88), (900, 512), (1500, 960)] {
+        fill(frame, x, y, 2, 18, 0x00);
+    }
+    // Tray clock and a progress bar creeping forward.
+    fill(frame, 1830, 1056, 64, 16, 0x30);
+    fill(frame, 700, 640, 260, 6, 0x60);
+}
+
+fn encoded_len(delta: &DeltaFrame, frame: &RawScreenFrame) -> usize {
+    let mut encoder = AdaptiveEncoder::new(100 * 1024 * 1024);
+    encoder.encode(delta, frame).unwrap().data.len()
+}
+
+fn main() {
+    let base = desktop();
+    let mut changed = base.clone();
+    scatter(&mut changed);
+
+    println!(
+        "{:>5} {:>8} {:>8} {:>10} {:>10} {:>7} {:>10}",
+        "block", "blocks", "merged", "bytes", "merged", "saved", "merge"
+    );
+    for block_size in BLOCK_SIZES {
+        let mut detector = DeltaDetector::new(block_size);
+        detector.detect(&base);
+        let mut delta = detector.detect(&changed);
+        delta.frame_number = 1;
+
+        let merger = RegionMerger::new(block_size, DEFAULT_MAX_WASTE_PERCENT);
+        let mut merged = delta.clone();
+        merger.merge(&mut merged);
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(merger.merge_blocks(black_box(&delta.changed_blocks), WIDTH, HEIGHT));
+        }
+        let per_merge = start.elapsed() / ITERATIONS;
+
+        let plain = encoded_len(&delta, &changed);
+        let coalesced = encoded_len(&merged, &changed);
+        println!(
+            "{block_size:>5} {:>8} {:>8} {plain:>10} {coalesced:>10} {:>6.1}% {:>7.3} ms",
+            delta.changed_blocks.len(),
+            merged.changed_blocks.len(),
+            100.0 * (1.0 - coalesced as f64 / plain as f64),
+            ms(per_merge),
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! The screen stream's hot path on a synthetic 1920×1080 desktop (lines
+//! of "text" over a gradient) with 1 %, 10 % and 100 % of its rows
+//! changed: `AdaptiveEncoder` encoding the delta, `FrameDecoder`
+//! decoding and applying it, and cutting the encoded frame into
+//! datagrams at the default MTU. Also the frame and chunk headers'
+//! encode and decode.
+//!
+//! Run with `cargo bench -p tix-core --bench screen`; `TIX_BENCH_QUICK=1`
+//! for a short smoke run.
+
+mod support;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use tix_core::rdp::FrameDecoder;
+use tix_core::rdp::delta::{DeltaDetector, DeltaFrame};
+use tix_core::rdp::encoder::{AdaptiveEncoder, EncodedFrame};
+use tix_core::rdp::transport::{ChunkHeader, DEFAULT_MTU, FrameHeader, frame_datagrams};
+use tix_core::rdp::types::{PixelFormat, RawScreenFrame};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+const BLOCK_SIZE: usize = 64;
+const CHANGED_PERCENT: [u32; 3] = [1, 10, 100];
+const BANDWIDTH: u64 = 100 * 1024 * 1024;
+
+/// A desktop: a window with lines of "text" over a gradient.
+fn scene() -> RawScreenFrame {
+    let stride = WIDTH * 4;
+    let mut data = vec![0u8; (stride * HEIGHT) as usize];
+    for (i, px) in data.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i as u32 % WIDTH, i as u32 / WIDTH);
+        let ink = y % 22 < 13 && (x / 9 + y / 22) % 6 != 0 && (x * 11 + y * 5) % 13 < 5;
+        let px_value = if ink {
+            [0x18, 0x18, 0x18, 0xFF]
+        } else {
+            [(x / 8) as u8, (y / 5) as u8, 0xC0, 0xFF]
+        };
+        px.copy_from_slice(&px_value);
+    }
+    RawScreenFrame {
+        width: WIDTH,
+        height: HEIGHT,
+        stride,
+        format: PixelFormat::Bgra8,
+        data,
+        timestamp: Instant::now(),
+    }
+}
+
+/// `base` with the top `percent` of its rows inverted.
+fn changed(base: &RawScreenFrame, percent: u32) -> RawScreenFrame {
+    let mut frame = base.clone();
+    let rows = (base.height * percent).div_ceil(100) as usize;
+    for byte in &mut frame.data[..rows * base.stride as usize] {
+        *byte = !*byte;
+    }
+    frame
+}
+
+/// The base frame, and per share changed the changed frame with its
+/// delta against the base.
+fn deltas() -> (RawScreenFrame, Vec<(u32, RawScreenFrame, DeltaFrame)>) {
+    let base = scene();
+    let cases = CHANGED_PERCENT
+        .iter()
+        .map(|&percent| {
+            let next = changed(&base, percent);
+            let mut detector = DeltaDetector::new(BLOCK_SIZE);
+            detector.detect(&base);
+            let delta = detector.detect(&next);
+            (percent, next, delta)
+        })
+        .collect();
+    (base, cases)
+}
+
+fn frame_bytes() -> u64 {
+    WIDTH as u64 * HEIGHT as u64 * 4
+}
+
+fn encoder(c: &mut Criterion) {
+    let (_, cases) = deltas();
+    let mut group = c.benchmark_group("encoder/1080p");
+    group.throughput(Throughput::Bytes(frame_bytes()));
+    for (percent, frame, delta) in &cases {
+        let mut encoder = AdaptiveEncoder::new(BANDWIDTH);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{}%", percent)),
+            &(frame, delta),
+            |b, (frame, delta)| b.iter(|| encoder.encode(delta, frame).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+/// `frame`'s delta encoded, after a full frame of `base`.
+fn encoded(base: &RawScreenFrame, frame: &RawScreenFrame, delta: &DeltaFrame) -> [EncodedFrame; 2] {
+    let mut encoder = AdaptiveEncoder::new(BANDWIDTH);
+    let mut detector = DeltaDetector::new(BLOCK_SIZE);
+    let full = encoder.encode(&detector.detect(base), base).unwrap();
+    [full, encoder.encode(delta, frame).unwrap()]
+}
+
+fn decoder(c: &mut Criterion) {
+    let (base, cases) = deltas();
+    let mut group = c.benchmark_group("decoder/1080p");
+    group.throughput(Throughput::Bytes(frame_bytes()));
+    for (percent, frame, delta) in &cases {
+        let [full, delta] = encoded(&base, frame, delta);
+        let mut decoder = FrameDecoder::new();
+        let decoded = decoder.decode(&full).unwrap();
+        decoder.apply(&decoded, 4).unwrap();
+        // Patching the same tiles in again is the same work every time.
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{}%", percent)),
+            &delta,
+            |b, delta| {
+                b.iter(|| {
+                    let decoded = decoder.decode(delta).unwrap();
+                    black_box(decoder.apply(&decoded, 4).unwrap().len())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn chunking(c: &mut Criterion) {
+    let (base, cases) = deltas();
+    let epoch = Instant::now();
+    let mut group = c.benchmark_group("chunking");
+    for (percent, frame, delta) in &cases {
+        let [_, delta] = encoded(&base, frame, delta);
+        group.throughput(Throughput::Bytes(delta.data.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{}%", percent)),
+            &delta,
+            |b, delta| b.iter(|| frame_datagrams(delta, 1, 0, DEFAULT_MTU, epoch)),
+        );
+    }
+    group.finish();
+}
+
+fn headers(c: &mut Criterion) {
+    let frame = FrameHeader {
+        sequence: 41,
+        frame_number: 1_000,
+        timestamp_us: 1_500,
+        width: WIDTH,
+        height: HEIGHT,
+        is_full_frame: false,
+        session: 1,
+        format: PixelFormat::Bgra8,
+        total_chunks: 300,
+        capture_us: 16_000,
+    };
+    let chunk = ChunkHeader {
+        sequence: 41,
+        chunk_index: 7,
+        chunk_size: (DEFAULT_MTU - ChunkHeader::SIZE) as u32,
+    };
+    let frame_bytes = frame.encode();
+    let chunk_bytes = chunk.encode();
+
+    let mut group = c.benchmark_group("headers");
+    group.bench_function("frame/encode", |b| b.iter(|| black_box(&frame).encode()));
+    group.bench_function("frame/decode", |b| {
+        b.iter(|| FrameHeader::decode(black_box(&frame_bytes)).unwrap())
+    });
+    group.bench_function("chunk/encode", |b| b.iter(|| black_box(&chunk).encode()));
+    group.bench_function("chunk/decode", |b| {
+        b.iter(|| ChunkHeader::decode(black_box(&chunk_bytes)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = support::criterion();
+    targets = headers, encoder, decoder, chunking
+}
+criterion_main!(benches);
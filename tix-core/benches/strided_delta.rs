@@ -0,0 +1,97 @@
+//! Per-frame cost of delta detection and block encoding on a padded
+//! 3840×2160 capture, next to the whole-frame copies the pipeline no
+//! longer makes (cloning the reference, de-striding into tight rows).
+//!
+//! Run with `cargo bench -p tix-core --bench strided_delta`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use tix_core::rdp::delta::DeltaDetector;
+use tix_core::rdp::encoder::AdaptiveEncoder;
+use tix_core::rdp::types::{PixelFormat, RawScreenFrame};
+
+const WIDTH: u32 = 3840;
+const HEIGHT: u32 = 2160;
+/// Row pitch as a driver that aligns rows past the visible width
+/// might report it.
+const STRIDE: u32 = WIDTH * 4 + 256;
+const BLOCK_SIZE: usize = 64;
+const ITERATIONS: u32 = 20;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    f(); // warm up
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_iter = start.elapsed() / ITERATIONS;
+    println!("{name:<28} {:>8.3} ms/frame", ms(per_iter));
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn frame() -> RawScreenFrame {
+    let data = (0..(STRIDE * HEIGHT) as usize)
+        .map(|i| (i * 31 % 251) as u8)
+        .collect();
+    RawScreenFrame {
+        width: WIDTH,
+        height: HEIGHT,
+        stride: STRIDE,
+        format: PixelFormat::Bgra8,
+        data,
+        timestamp: Instant::now(),
+    }
+}
+
+/// Touch one pixel in each of `blocks` tiles along the diagonal.
+fn scribble(frame: &mut RawScreenFrame, blocks: usize, value: u8) {
+    for i in 0..blocks {
+        let x = (i * BLOCK_SIZE) % WIDTH as usize;
+        let y = (i * BLOCK_SIZE) % HEIGHT as usize;
+        frame.data[y * STRIDE as usize + x * 4] = value;
+    }
+}
+
+fn main() {
+    let mut current = frame();
+    let row = WIDTH as usize * 4;
+
+    // What every frame used to pay before any comparison happened.
+    bench("clone reference (removed)", || {
+        black_box(current.clone());
+    });
+    let mut tight = Vec::with_capacity(row * HEIGHT as usize);
+    bench("de-stride copy (avoided)", || {
+        tight.clear();
+        for y in 0..HEIGHT as usize {
+            tight.extend_from_slice(&current.data[y * STRIDE as usize..][..row]);
+        }
+        black_box(&tight);
+    });
+
+    let mut detector = DeltaDetector::new(BLOCK_SIZE);
+    detector.detect(&current);
+    bench("detect, static", || {
+        black_box(detector.detect(black_box(&current)));
+    });
+
+    let mut flip = 0u8;
+    bench("detect, 30 dirty blocks", || {
+        flip = flip.wrapping_add(1);
+        scribble(&mut current, 30, flip);
+        black_box(detector.detect(&current));
+    });
+
+    flip = flip.wrapping_add(1);
+    scribble(&mut current, 30, flip);
+    let mut delta = detector.detect(&current);
+    delta.frame_number = 1;
+    let mut encoder = AdaptiveEncoder::new(100 * 1024 * 1024);
+    bench("encode 30 dirty blocks", || {
+        black_box(encoder.encode(&delta, &current).unwrap());
+    });
+}
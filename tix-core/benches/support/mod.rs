@@ -0,0 +1,21 @@
+//! Criterion settings shared by the `codec` and `screen` suites.
+//!
+//! `TIX_BENCH_QUICK=1` cuts the samples and the time spent on each
+//! benchmark, for a smoke run in CI; the numbers are noisier but every
+//! benchmark still runs.
+
+use std::time::Duration;
+
+use criterion::Criterion;
+
+pub fn criterion() -> Criterion {
+    let quick = std::env::var_os("TIX_BENCH_QUICK").is_some_and(|v| v != "0");
+    if !quick {
+        return Criterion::default();
+    }
+    Criterion::default()
+        .sample_size(10)
+        .warm_up_time(Duration::from_millis(200))
+        .measurement_time(Duration::from_secs(1))
+        .without_plots()
+}
@@ -0,0 +1,728 @@
+//! Record sample deltas and train a screen-stream zstd dictionary.
+//!
+//! ```text
+//! cargo run --release -p tix-core --example train_dictionary -- \
+//!     sample tests/fixtures/deltas-train.bin --frames 500 --seed 1
+//! cargo run --release -p tix-core --example train_dictionary -- \
+//!     sample tests/fixtures/deltas-eval.bin --frames 150 --seed 2
+//! cargo run --release -p tix-core --example train_dictionary -- \
+//!     train tests/fixtures/deltas-train.bin -o src/rdp/builtin.dict
+//! ```
+//!
+//! `sample` plays a synthetic desktop session (typing, a blinking
+//! caret, a ticking clock, hover highlights, menus and scrolling) through
+//! the same delta detector, region merger and encoder the slave uses,
+//! and keeps the uncompressed payloads of the delta frames. The scenes
+//! are made up: to tune the dictionary for real desktops, record
+//! payloads from a live slave into a sample file and train on that.
+//!
+//! `train` builds a dictionary from one or more sample files and prints
+//! how much it saves on them at level 1.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use tix_core::rdp::delta::DeltaDetector;
+use tix_core::rdp::dictionary::{
+    DEFAULT_DICTIONARY_SIZE, FrameDictionary, decode_samples, encode_samples,
+};
+use tix_core::rdp::encoder::AdaptiveEncoder;
+use tix_core::rdp::merge::{DEFAULT_MAX_WASTE_PERCENT, RegionMerger};
+use tix_core::rdp::types::{PixelFormat, RawScreenFrame};
+
+const USAGE: &str = "usage:
+  train_dictionary sample <out> [--frames N] [--seed S]
+  train_dictionary train <samples>... -o <dict> [--size BYTES]";
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+const BLOCK_SIZE: usize = 64;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("sample") => sample(&args[1..]),
+        Some("train") => train(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Value following `flag` in `args`, removed from it along with the flag.
+fn take_option(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, String> {
+    let Some(at) = args.iter().position(|a| a == flag) else {
+        return Ok(None);
+    };
+    if at + 1 >= args.len() {
+        return Err(format!("{flag} needs a value"));
+    }
+    let value = args.remove(at + 1);
+    args.remove(at);
+    Ok(Some(value))
+}
+
+fn parse_number<T: std::str::FromStr>(
+    value: Option<String>,
+    flag: &str,
+    default: T,
+) -> Result<T, String> {
+    match value {
+        Some(v) => v
+            .parse()
+            .map_err(|_| format!("{flag}: '{v}' is not a number")),
+        None => Ok(default),
+    }
+}
+
+// ── sample ───────────────────────────────────────────────────────
+
+fn sample(args: &[String]) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let frames = parse_number(take_option(&mut args, "--frames")?, "--frames", 500usize)?;
+    let seed = parse_number(take_option(&mut args, "--seed")?, "--seed", 1u64)?;
+    let [out] = args.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+
+    let mut desktop = Desktop::new(seed);
+    let mut detector = DeltaDetector::new(BLOCK_SIZE);
+    let merger = RegionMerger::new(BLOCK_SIZE, DEFAULT_MAX_WASTE_PERCENT);
+    let mut encoder = AdaptiveEncoder::new(100 * 1024 * 1024);
+    let mut samples = Vec::new();
+
+    while samples.len() < frames {
+        desktop.step();
+        let frame = desktop.frame();
+        let mut delta = detector.detect(&frame);
+        merger.merge(&mut delta);
+        if delta.changed_blocks.is_empty() {
+            continue;
+        }
+        let encoded = encoder.encode(&delta, &frame).map_err(|e| e.to_string())?;
+        if encoded.is_full_frame {
+            continue;
+        }
+        let raw = zstd::decode_all(encoded.data.as_slice()).map_err(|e| e.to_string())?;
+        samples.push(raw);
+    }
+
+    let file = encode_samples(&samples).map_err(|e| e.to_string())?;
+    std::fs::write(out, &file).map_err(|e| format!("{out}: {e}"))?;
+    let raw: usize = samples.iter().map(Vec::len).sum();
+    println!(
+        "{} deltas, {} KiB raw, {} KiB written to {out}",
+        samples.len(),
+        raw / 1024,
+        file.len() / 1024
+    );
+    Ok(())
+}
+
+// ── train ────────────────────────────────────────────────────────
+
+fn train(args: &[String]) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let out = take_option(&mut args, "-o")?.ok_or(USAGE)?;
+    let size = parse_number(
+        take_option(&mut args, "--size")?,
+        "--size",
+        DEFAULT_DICTIONARY_SIZE,
+    )?;
+    if args.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let mut samples = Vec::new();
+    for path in &args {
+        let file = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+        samples.extend(decode_samples(&file).map_err(|e| format!("{path}: {e}"))?);
+    }
+
+    let started = Instant::now();
+    let dictionary = FrameDictionary::train(&samples, size).map_err(|e| e.to_string())?;
+    let elapsed = started.elapsed();
+    std::fs::write(&out, dictionary.bytes()).map_err(|e| format!("{out}: {e}"))?;
+
+    let mut compressor = zstd::bulk::Compressor::with_dictionary(1, dictionary.bytes())
+        .map_err(|e| e.to_string())?;
+    let (mut plain, mut with_dict) = (0, 0);
+    for sample in &samples {
+        plain += zstd::bulk::compress(sample, 1)
+            .map_err(|e| e.to_string())?
+            .len();
+        with_dict += compressor
+            .compress(sample)
+            .map_err(|e| e.to_string())?
+            .len();
+    }
+    println!(
+        "{} bytes, id {}, hash {:016x}, trained on {} samples in {:.1} s",
+        dictionary.bytes().len(),
+        dictionary.id(),
+        dictionary.hash(),
+        samples.len(),
+        elapsed.as_secs_f64()
+    );
+    println!(
+        "level 1 on the samples: {plain} bytes plain, {with_dict} with the dictionary ({:.0} %)",
+        with_dict as f64 * 100.0 / plain as f64
+    );
+    println!("wrote {}", PathBuf::from(out).display());
+    Ok(())
+}
+
+// ── Synthetic desktop ────────────────────────────────────────────
+
+type Bgra = [u8; 4];
+
+const DESKTOP: Bgra = [0x70, 0x50, 0x30, 0xFF];
+const TASKBAR: Bgra = [0x28, 0x24, 0x20, 0xFF];
+const TITLE: Bgra = [0xC0, 0x78, 0x00, 0xFF];
+const TITLE_IDLE: Bgra = [0xE0, 0xE0, 0xE0, 0xFF];
+const WINDOW: Bgra = [0xFF, 0xFF, 0xFF, 0xFF];
+const MENU: Bgra = [0xF2, 0xF2, 0xF2, 0xFF];
+const BUTTON: Bgra = [0xE1, 0xE1, 0xE1, 0xFF];
+const HOVER: Bgra = [0xFB, 0xE5, 0xCC, 0xFF];
+const INK: Bgra = [0x1E, 0x1E, 0x1E, 0xFF];
+const LIGHT_INK: Bgra = [0xFF, 0xFF, 0xFF, 0xFF];
+
+const CELL_W: u32 = 7;
+const LINE_H: u32 = 16;
+
+/// xorshift64*, so sample files are reproducible from their seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: u32) -> u32 {
+        (self.next() % n as u64) as u32
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+struct Window {
+    frame: Rect,
+    /// Text lines shown in the body, top first.
+    lines: Vec<String>,
+    /// Caret position: line and column.
+    caret: (usize, usize),
+}
+
+impl Window {
+    fn body(&self) -> Rect {
+        Rect {
+            x: self.frame.x + 1,
+            y: self.frame.y + 30,
+            w: self.frame.w - 2,
+            h: self.frame.h - 70,
+        }
+    }
+
+    fn visible_lines(&self) -> usize {
+        (self.body().h / LINE_H) as usize
+    }
+
+    fn button(&self, i: u32) -> Rect {
+        Rect {
+            x: self.frame.x + self.frame.w - 90 * (i + 1),
+            y: self.frame.y + self.frame.h - 34,
+            w: 80,
+            h: 26,
+        }
+    }
+}
+
+struct Desktop {
+    rng: Rng,
+    pixels: Vec<u8>,
+    windows: Vec<Window>,
+    focused: usize,
+    caret_on: bool,
+    clock: u32,
+    hovered: Option<(usize, u32)>,
+    menu: Option<Rect>,
+    tick: u64,
+}
+
+const WORDS: &[&str] = &[
+    "the",
+    "screen",
+    "delta",
+    "frame",
+    "remote",
+    "session",
+    "config",
+    "update",
+    "file",
+    "error",
+    "master",
+    "slave",
+    "window",
+    "value",
+    "return",
+    "let",
+    "fn",
+    "match",
+    "Some",
+    "None",
+    "ok",
+    "report",
+    "build",
+    "test",
+    "path",
+    "C:\\Users",
+    "docs",
+    "2024",
+    "total",
+    "pending",
+];
+
+impl Desktop {
+    fn new(seed: u64) -> Self {
+        let mut rng = Rng(seed.max(1).wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1);
+        let windows = [
+            Rect {
+                x: 60,
+                y: 40,
+                w: 700,
+                h: 520,
+            },
+            Rect {
+                x: 520,
+                y: 120,
+                w: 640,
+                h: 480,
+            },
+        ]
+        .into_iter()
+        .map(|frame| {
+            let mut window = Window {
+                frame,
+                lines: Vec::new(),
+                caret: (0, 0),
+            };
+            for _ in 0..window.visible_lines() - 3 {
+                window.lines.push(sentence(&mut rng, 70));
+            }
+            window.caret = (
+                window.lines.len() - 1,
+                window.lines.last().map_or(0, String::len),
+            );
+            window
+        })
+        .collect();
+        let mut desktop = Self {
+            rng,
+            pixels: vec![0; (WIDTH * HEIGHT * 4) as usize],
+            windows,
+            focused: 1,
+            caret_on: true,
+            clock: 9 * 60,
+            hovered: None,
+            menu: None,
+            tick: 0,
+        };
+        desktop.redraw();
+        desktop
+    }
+
+    fn frame(&self) -> RawScreenFrame {
+        RawScreenFrame {
+            width: WIDTH,
+            height: HEIGHT,
+            stride: WIDTH * 4,
+            format: PixelFormat::Bgra8,
+            data: self.pixels.clone(),
+            timestamp: Instant::now(),
+        }
+    }
+
+    /// One event's worth of change, as a user would cause it.
+    fn step(&mut self) {
+        self.tick += 1;
+        // Weighted towards the small changes that dominate a desktop
+        // in use; redrawing a whole window is comparatively rare.
+        match self.rng.below(40) {
+            0..=15 => self.type_text(),
+            16..=25 => self.blink(),
+            26..=29 => self.hover(),
+            30..=35 => self.tick_clock(),
+            36 => self.toggle_menu(),
+            37 => self.new_line(),
+            38 => self.scroll(),
+            _ => self.switch_focus(),
+        }
+    }
+
+    fn type_text(&mut self) {
+        let word = WORDS[self.rng.below(WORDS.len() as u32) as usize];
+        let window = &mut self.windows[self.focused];
+        let (line, _) = window.caret;
+        if window.lines[line].len() + word.len() + 1 > 80 {
+            return self.new_line();
+        }
+        if !window.lines[line].is_empty() {
+            window.lines[line].push(' ');
+        }
+        window.lines[line].push_str(word);
+        window.caret.1 = window.lines[line].len();
+        self.draw_line(self.focused, line);
+        self.draw_caret(true);
+    }
+
+    fn new_line(&mut self) {
+        let window = &mut self.windows[self.focused];
+        if window.lines.len() >= window.visible_lines() {
+            window.lines.remove(0);
+            window.lines.push(String::new());
+            window.caret = (window.lines.len() - 1, 0);
+            self.draw_window(self.focused);
+        } else {
+            window.lines.push(String::new());
+            window.caret = (window.lines.len() - 1, 0);
+            self.draw_caret(true);
+        }
+    }
+
+    fn scroll(&mut self) {
+        let lines = 1 + self.rng.below(3) as usize;
+        for _ in 0..lines {
+            let text = sentence(&mut self.rng, 75);
+            let window = &mut self.windows[self.focused];
+            window.lines.remove(0);
+            window.lines.insert(window.lines.len() - 1, text);
+        }
+        self.draw_window(self.focused);
+    }
+
+    fn blink(&mut self) {
+        self.caret_on = !self.caret_on;
+        self.draw_caret(self.caret_on);
+    }
+
+    fn hover(&mut self) {
+        if let Some((window, button)) = self.hovered.take() {
+            self.draw_button(window, button, false);
+        }
+        let window = self.rng.below(self.windows.len() as u32) as usize;
+        let button = self.rng.below(2);
+        self.hovered = Some((window, button));
+        self.draw_button(window, button, true);
+    }
+
+    fn toggle_menu(&mut self) {
+        match self.menu.take() {
+            Some(_) => self.redraw(),
+            None => {
+                let anchor = self.windows[self.focused].frame;
+                let menu = Rect {
+                    x: anchor.x + 10 + self.rng.below(200),
+                    y: anchor.y + 30,
+                    w: 180,
+                    h: 8 * LINE_H + 8,
+                };
+                fill(&mut self.pixels, menu, MENU);
+                frame_rect(&mut self.pixels, menu, [0xA0, 0xA0, 0xA0, 0xFF]);
+                for i in 0..8 {
+                    let item = WORDS[self.rng.below(WORDS.len() as u32) as usize];
+                    text(
+                        &mut self.pixels,
+                        menu.x + 24,
+                        menu.y + 4 + i * LINE_H,
+                        item,
+                        INK,
+                        MENU,
+                    );
+                }
+                self.menu = Some(menu);
+            }
+        }
+    }
+
+    fn switch_focus(&mut self) {
+        self.draw_caret(false);
+        self.focused = (self.focused + 1) % self.windows.len();
+        // The focused window comes to the front.
+        let window = self.windows.remove(self.focused);
+        self.windows.push(window);
+        self.focused = self.windows.len() - 1;
+        self.hovered = None;
+        self.menu = None;
+        self.redraw();
+    }
+
+    fn tick_clock(&mut self) {
+        self.clock = (self.clock + 1) % (24 * 60);
+        self.draw_taskbar();
+    }
+
+    // ── Drawing ──────────────────────────────────────────────────
+
+    fn redraw(&mut self) {
+        fill(
+            &mut self.pixels,
+            Rect {
+                x: 0,
+                y: 0,
+                w: WIDTH,
+                h: HEIGHT,
+            },
+            DESKTOP,
+        );
+        for i in 0..self.windows.len() {
+            self.draw_window(i);
+        }
+        self.draw_taskbar();
+    }
+
+    fn draw_taskbar(&mut self) {
+        let bar = Rect {
+            x: 0,
+            y: HEIGHT - 40,
+            w: WIDTH,
+            h: 40,
+        };
+        fill(&mut self.pixels, bar, TASKBAR);
+        for (i, name) in ["Explorer", "Terminal", "Editor", "Browser"]
+            .iter()
+            .enumerate()
+        {
+            let x = 60 + i as u32 * 130;
+            fill(
+                &mut self.pixels,
+                Rect {
+                    x,
+                    y: bar.y + 6,
+                    w: 120,
+                    h: 28,
+                },
+                [0x40, 0x3A, 0x34, 0xFF],
+            );
+            text(
+                &mut self.pixels,
+                x + 8,
+                bar.y + 12,
+                name,
+                LIGHT_INK,
+                [0x40, 0x3A, 0x34, 0xFF],
+            );
+        }
+        let clock = format!("{:02}:{:02}", self.clock / 60, self.clock % 60);
+        text(
+            &mut self.pixels,
+            WIDTH - 60,
+            bar.y + 12,
+            &clock,
+            LIGHT_INK,
+            TASKBAR,
+        );
+    }
+
+    fn draw_window(&mut self, i: usize) {
+        let window = &self.windows[i];
+        let frame = window.frame;
+        let title = if i == self.focused { TITLE } else { TITLE_IDLE };
+        let title_ink = if i == self.focused { LIGHT_INK } else { INK };
+        fill(&mut self.pixels, frame, WINDOW);
+        frame_rect(&mut self.pixels, frame, [0x80, 0x80, 0x80, 0xFF]);
+        fill(
+            &mut self.pixels,
+            Rect {
+                x: frame.x,
+                y: frame.y,
+                w: frame.w,
+                h: 30,
+            },
+            title,
+        );
+        text(
+            &mut self.pixels,
+            frame.x + 10,
+            frame.y + 8,
+            "notes.txt - Editor",
+            title_ink,
+            title,
+        );
+        for line in 0..window.lines.len() {
+            self.draw_line(i, line);
+        }
+        for button in 0..2 {
+            self.draw_button(i, button, false);
+        }
+        if i == self.focused {
+            self.draw_caret(self.caret_on);
+        }
+    }
+
+    fn draw_line(&mut self, i: usize, line: usize) {
+        let window = &self.windows[i];
+        let body = window.body();
+        let y = body.y + line as u32 * LINE_H;
+        let row = Rect {
+            x: body.x,
+            y,
+            w: body.w,
+            h: LINE_H,
+        };
+        fill(&mut self.pixels, row, WINDOW);
+        let shown: String = window.lines[line]
+            .chars()
+            .take((body.w / CELL_W) as usize - 2)
+            .collect();
+        text(&mut self.pixels, body.x + 6, y + 2, &shown, INK, WINDOW);
+    }
+
+    fn draw_button(&mut self, i: usize, button: u32, hovered: bool) {
+        let rect = self.windows[i].button(button);
+        let face = if hovered { HOVER } else { BUTTON };
+        fill(&mut self.pixels, rect, face);
+        frame_rect(&mut self.pixels, rect, [0xAD, 0xAD, 0xAD, 0xFF]);
+        let label = if button == 0 { "Cancel" } else { "Save" };
+        text(&mut self.pixels, rect.x + 18, rect.y + 6, label, INK, face);
+    }
+
+    fn draw_caret(&mut self, on: bool) {
+        let window = &self.windows[self.focused];
+        let body = window.body();
+        let (line, col) = window.caret;
+        let col = col.min((body.w / CELL_W) as usize - 2) as u32;
+        let caret = Rect {
+            x: body.x + 6 + col * CELL_W,
+            y: body.y + line as u32 * LINE_H + 1,
+            w: 1,
+            h: LINE_H - 2,
+        };
+        fill(&mut self.pixels, caret, if on { INK } else { WINDOW });
+    }
+}
+
+fn sentence(rng: &mut Rng, max_len: usize) -> String {
+    let mut line = String::new();
+    loop {
+        let word = WORDS[rng.below(WORDS.len() as u32) as usize];
+        if line.len() + word.len() + 1 > max_len {
+            return line;
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+}
+
+fn fill(pixels: &mut [u8], rect: Rect, color: Bgra) {
+    let right = (rect.x + rect.w).min(WIDTH);
+    let bottom = (rect.y + rect.h).min(HEIGHT);
+    for y in rect.y.min(bottom)..bottom {
+        let row = (y * WIDTH) as usize * 4;
+        for x in rect.x.min(right)..right {
+            let at = row + x as usize * 4;
+            pixels[at..at + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+fn frame_rect(pixels: &mut [u8], rect: Rect, color: Bgra) {
+    fill(pixels, Rect { h: 1, ..rect }, color);
+    fill(
+        pixels,
+        Rect {
+            y: rect.y + rect.h - 1,
+            h: 1,
+            ..rect
+        },
+        color,
+    );
+    fill(pixels, Rect { w: 1, ..rect }, color);
+    fill(
+        pixels,
+        Rect {
+            x: rect.x + rect.w - 1,
+            w: 1,
+            ..rect
+        },
+        color,
+    );
+}
+
+/// Draw `s` in a made-up 5×9 font with smoothed edges, the way
+/// anti-aliased text mixes ink and background into a few shades.
+fn text(pixels: &mut [u8], x: u32, y: u32, s: &str, ink: Bgra, paper: Bgra) {
+    let edge: Bgra = std::array::from_fn(|i| ((ink[i] as u16 + paper[i] as u16 * 2) / 3) as u8);
+    for (n, c) in s.chars().enumerate() {
+        if c == ' ' {
+            continue;
+        }
+        let glyph = glyph(c);
+        let cx = x + n as u32 * CELL_W;
+        for gy in 0..9u32 {
+            for gx in 0..5u32 {
+                if glyph >> (gy * 5 + gx) & 1 == 0 {
+                    continue;
+                }
+                fill(
+                    pixels,
+                    Rect {
+                        x: cx + gx,
+                        y: y + gy,
+                        w: 1,
+                        h: 1,
+                    },
+                    ink,
+                );
+                if gx < 4 && glyph >> (gy * 5 + gx + 1) & 1 == 0 {
+                    fill(
+                        pixels,
+                        Rect {
+                            x: cx + gx + 1,
+                            y: y + gy,
+                            w: 1,
+                            h: 1,
+                        },
+                        edge,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// A stable 5×9 bitmap per character: a few strokes picked by its code.
+fn glyph(c: char) -> u64 {
+    let mut h = (c as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let mut bits = 0u64;
+    // Most glyphs have a vertical stem and two or three bars.
+    let stem = (h % 5) as u32;
+    for gy in 1..9 {
+        bits |= 1 << (gy * 5 + stem);
+    }
+    for _ in 0..3 {
+        h = h.rotate_left(17) ^ 0xA5A5_5A5A_C3C3_3C3C;
+        let gy = (h % 9) as u32;
+        let (from, to) = (((h >> 8) % 3) as u32, 2 + ((h >> 12) % 3) as u32);
+        for gx in from..=to {
+            bits |= 1 << (gy * 5 + gx);
+        }
+    }
+    bits
+}
@@ -0,0 +1,304 @@
+//! Turn Criterion's latest results into the baseline table of
+//! `BENCHMARKS.md`.
+//!
+//! ```text
+//! bench-report                Markdown on stdout
+//! bench-report --write [FILE] into FILE (BENCHMARKS.md)
+//! ```
+//!
+//! Results are read from `target/criterion`, or `$CRITERION_HOME` when
+//! set, so run it from the workspace root after
+//! `cargo bench -p tix-core --bench codec --bench screen`.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tix_core::format::format_rate;
+
+const USAGE: &str = "usage: bench-report [--write [FILE]]";
+
+const PREAMBLE: &str = "\
+# Benchmarks
+
+Baseline numbers for tix-core's hot paths: packet building and hashing,
+the TCP codec, the screen frame and chunk headers, the encoder and
+decoder on a synthetic 1080p desktop, and cutting frames into
+datagrams. Regenerate after a change to any of them:
+
+```bash
+cargo bench -p tix-core --bench codec --bench screen
+cargo run -q -p tix-core --bin bench-report -- --write
+```
+
+`TIX_BENCH_QUICK=1` in front of `cargo bench` runs every benchmark
+briefly, for a CI smoke run; its numbers are too noisy to commit.
+Compare like with like: the figures below only mean something next to
+another run on the same machine.
+";
+
+/// `benchmark.json`: what was measured.
+#[derive(Debug, Deserialize)]
+struct Benchmark {
+    group_id: String,
+    function_id: Option<String>,
+    value_str: Option<String>,
+    throughput: Option<Throughput>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum Throughput {
+    Bytes(u64),
+    BytesDecimal(u64),
+    Elements(u64),
+}
+
+/// `estimates.json`, the part used: nanoseconds per iteration.
+#[derive(Debug, Deserialize)]
+struct Estimates {
+    mean: Estimate,
+}
+
+#[derive(Debug, Deserialize)]
+struct Estimate {
+    point_estimate: f64,
+    confidence_interval: Interval,
+}
+
+#[derive(Debug, Deserialize)]
+struct Interval {
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+/// One benchmark's result.
+#[derive(Debug)]
+struct Row {
+    bench: Benchmark,
+    mean: Estimate,
+}
+
+impl Row {
+    /// The benchmark within its group, e.g. `decode/64KiB`.
+    fn name(&self) -> String {
+        let parts: Vec<&str> = [&self.bench.function_id, &self.bench.value_str]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        parts.join("/")
+    }
+
+    fn throughput(&self) -> String {
+        let per_iter = Duration::from_secs_f64(self.mean.point_estimate / 1e9);
+        match self.bench.throughput {
+            Some(Throughput::Bytes(n) | Throughput::BytesDecimal(n)) => format_rate(n, per_iter),
+            Some(Throughput::Elements(n)) => {
+                format!("{:.0} elem/s", n as f64 / per_iter.as_secs_f64())
+            }
+            None => "-".to_string(),
+        }
+    }
+
+    /// Order within a group: by function, then by amount of work.
+    fn sort_key(&self) -> (Option<&str>, u64, Option<&str>) {
+        let amount = match self.bench.throughput {
+            Some(Throughput::Bytes(n) | Throughput::BytesDecimal(n) | Throughput::Elements(n)) => n,
+            None => 0,
+        };
+        (
+            self.bench.function_id.as_deref(),
+            amount,
+            self.bench.value_str.as_deref(),
+        )
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let out = match args.as_slice() {
+        [] => None,
+        ["--write"] => Some(PathBuf::from("BENCHMARKS.md")),
+        ["--write", file] => Some(PathBuf::from(file)),
+        ["-h" | "--help"] => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let dir = std::env::var_os("CRITERION_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target/criterion"));
+    let rows = match collect(&dir) {
+        Ok(rows) if rows.is_empty() => {
+            eprintln!("{}: no results; run cargo bench first", dir.display());
+            return ExitCode::FAILURE;
+        }
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("{}: {e}", dir.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let report = render(rows);
+    match out {
+        None => print!("{report}"),
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, report) {
+                eprintln!("{}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+            println!("wrote {}", path.display());
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Every `new/` result under `dir`, Criterion's latest run of each
+/// benchmark.
+fn collect(dir: &Path) -> Result<Vec<Row>, String> {
+    let mut rows = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir).map_err(|e| e.to_string())?;
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            if path.file_name().is_some_and(|name| name == "new") {
+                rows.push(read_row(&path)?);
+            } else if path.file_name().is_some_and(|name| name != "report") {
+                pending.push(path);
+            }
+        }
+    }
+    Ok(rows)
+}
+
+fn read_row(dir: &Path) -> Result<Row, String> {
+    let read = |name: &str| {
+        let path = dir.join(name);
+        std::fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))
+    };
+    let bench: Benchmark = serde_json::from_str(&read("benchmark.json")?)
+        .map_err(|e| format!("{}: {e}", dir.display()))?;
+    let estimates: Estimates = serde_json::from_str(&read("estimates.json")?)
+        .map_err(|e| format!("{}: {e}", dir.display()))?;
+    Ok(Row {
+        bench,
+        mean: estimates.mean,
+    })
+}
+
+/// `BENCHMARKS.md`: the preamble, the machine, then a table per group.
+fn render(mut rows: Vec<Row>) -> String {
+    rows.sort_by(|a, b| {
+        (a.bench.group_id.as_str(), a.sort_key()).cmp(&(b.bench.group_id.as_str(), b.sort_key()))
+    });
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut out = format!(
+        "{PREAMBLE}\nMachine: {} {}, available parallelism {}.\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        threads
+    );
+    let mut group = None;
+    for row in &rows {
+        if group != Some(&row.bench.group_id) {
+            group = Some(&row.bench.group_id);
+            out.push_str(&format!(
+                "\n## {}\n\n| Benchmark | Mean | 95% interval | Throughput |\n|---|---:|---:|---:|\n",
+                row.bench.group_id
+            ));
+        }
+        out.push_str(&format!(
+            "| {} | {} | {} – {} | {} |\n",
+            row.name(),
+            format_time(row.mean.point_estimate),
+            format_time(row.mean.confidence_interval.lower_bound),
+            format_time(row.mean.confidence_interval.upper_bound),
+            row.throughput(),
+        ));
+    }
+    out
+}
+
+/// `nanos` with three significant digits in the largest unit under
+/// which it is at least 1: `7.66 ns`, `1.03 ms`.
+fn format_time(nanos: f64) -> String {
+    let (value, unit) = if nanos < 1e3 {
+        (nanos, "ns")
+    } else if nanos < 1e6 {
+        (nanos / 1e3, "µs")
+    } else if nanos < 1e9 {
+        (nanos / 1e6, "ms")
+    } else {
+        (nanos / 1e9, "s")
+    };
+    let decimals = if value < 10.0 {
+        2
+    } else if value < 100.0 {
+        1
+    } else {
+        0
+    };
+    format!("{value:.decimals$} {unit}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(group: &str, function: Option<&str>, value: &str, bytes: u64, nanos: f64) -> Row {
+        Row {
+            bench: Benchmark {
+                group_id: group.to_string(),
+                function_id: function.map(str::to_string),
+                value_str: Some(value.to_string()),
+                throughput: Some(Throughput::Bytes(bytes)),
+            },
+            mean: Estimate {
+                point_estimate: nanos,
+                confidence_interval: Interval {
+                    lower_bound: nanos * 0.9,
+                    upper_bound: nanos * 1.1,
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn times_keep_three_digits() {
+        assert_eq!(format_time(7.657), "7.66 ns");
+        assert_eq!(format_time(1_026_500.0), "1.03 ms");
+        assert_eq!(format_time(54_270.0), "54.3 µs");
+        assert_eq!(format_time(123_456.0), "123 µs");
+        assert_eq!(format_time(2.5e9), "2.50 s");
+    }
+
+    #[test]
+    fn groups_become_tables_ordered_by_work() {
+        let report = render(vec![
+            row("codec", Some("decode"), "200KiB", 200 * 1024, 57_267.0),
+            row("codec", Some("decode"), "64KiB", 64 * 1024, 19_455.0),
+            row("chunking", None, "1%", 45_000, 3_187.4),
+        ]);
+        let tables = report.split_once("## ").unwrap().1;
+        assert_eq!(
+            tables,
+            "chunking\n\n\
+             | Benchmark | Mean | 95% interval | Throughput |\n|---|---:|---:|---:|\n\
+             | 1% | 3.19 µs | 2.87 µs – 3.51 µs | 13.2 GiB/s |\n\
+             \n## codec\n\n\
+             | Benchmark | Mean | 95% interval | Throughput |\n|---|---:|---:|---:|\n\
+             | decode/64KiB | 19.5 µs | 17.5 µs – 21.4 µs | 3.1 GiB/s |\n\
+             | decode/200KiB | 57.3 µs | 51.5 µs – 63.0 µs | 3.3 GiB/s |\n"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Print the wire protocol reference generated from the code.
+//!
+//! ```text
+//! protocol-doc               JSON on stdout
+//! protocol-doc --markdown    Markdown on stdout
+//! protocol-doc --write [DIR] protocol.json and protocol.md in DIR (docs/)
+//! ```
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use tix_core::protocol::schema::ProtocolDoc;
+
+const USAGE: &str = "usage: protocol-doc [--markdown | --write [DIR]]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let doc = ProtocolDoc::generate();
+    match args.as_slice() {
+        [] => print!("{}", doc.to_json()),
+        ["--markdown"] => print!("{}", doc.to_markdown()),
+        ["--write"] => return write(&doc, PathBuf::from("docs")),
+        ["--write", dir] => return write(&doc, PathBuf::from(dir)),
+        ["-h" | "--help"] => println!("{USAGE}"),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn write(doc: &ProtocolDoc, dir: PathBuf) -> ExitCode {
+    let files = [
+        ("protocol.json", doc.to_json()),
+        ("protocol.md", doc.to_markdown()),
+    ];
+    for (name, contents) in files {
+        let path = dir.join(name);
+        if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, contents))
+        {
+            eprintln!("{}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+        println!("wrote {}", path.display());
+    }
+    ExitCode::SUCCESS
+}
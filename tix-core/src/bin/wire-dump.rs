@@ -0,0 +1,96 @@
+//! Print a wire tap capture file, one packet per entry.
+//!
+//! ```text
+//! wire-dump FILE             dump the first 64 payload bytes of each
+//! wire-dump --bytes N FILE   dump the first N instead (0: headers only)
+//! ```
+
+use std::process::ExitCode;
+use std::time::Duration;
+
+use tix_core::network::wiretap::{CaptureReader, CaptureRecord, DEFAULT_DUMP_BYTES, hex_dump};
+
+const USAGE: &str = "usage: wire-dump [--bytes N] FILE";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (path, bytes) = match args.as_slice() {
+        ["-h" | "--help"] => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        [path] => (*path, DEFAULT_DUMP_BYTES),
+        ["--bytes", n, path] | [path, "--bytes", n] => match n.parse() {
+            Ok(n) => (*path, n),
+            Err(_) => {
+                eprintln!("--bytes: not a number: {n}");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let reader = match CaptureReader::open(path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut first = None;
+    let mut count = 0usize;
+    for record in reader {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("{path}: record {}: {e}", count + 1);
+                return ExitCode::FAILURE;
+            }
+        };
+        let start = *first.get_or_insert(record.timestamp);
+        let offset = record
+            .timestamp
+            .duration_since(start)
+            .unwrap_or(Duration::ZERO);
+        print_record(&record, offset, bytes);
+        count += 1;
+    }
+    println!("{count} packets");
+    ExitCode::SUCCESS
+}
+
+fn print_record(record: &CaptureRecord, offset: Duration, bytes: usize) {
+    let packet = match record.packet() {
+        Ok(packet) => packet,
+        Err(e) => {
+            println!(
+                "{:>12.6}s {:<8} unparsable ({e})",
+                offset.as_secs_f64(),
+                record.direction
+            );
+            println!("    {}", hex_dump(&record.bytes, bytes));
+            return;
+        }
+    };
+    let command = match packet.command() {
+        Ok(command) => command.to_string(),
+        Err(_) => "unknown".to_string(),
+    };
+    println!(
+        "{:>12.6}s {:<8} {command} {:?} id={} flags={:?} len={} {}",
+        offset.as_secs_f64(),
+        record.direction,
+        packet.message_type(),
+        packet.request_id(),
+        packet.flags(),
+        packet.payload().len(),
+        packet.wire_version(),
+    );
+    if bytes > 0 {
+        println!("    {}", hex_dump(packet.payload(), bytes));
+    }
+}
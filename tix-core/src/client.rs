@@ -0,0 +1,752 @@
+//! Headless client for driving a slave from code.
+//!
+//! [`TixClient`] wraps a [`Connection`] to one slave and turns the
+//! request/response traffic into plain async calls: run a shell command,
+//! list a directory, move a file either way, measure the round trip.
+//! Every call gets its own request ID, counted from a random offset, and
+//! a private channel; a background task routes each incoming packet to
+//! the call waiting on its ID, so any number of calls can be in flight at
+//! once on a shared `&TixClient`.
+//! Outstanding requests are tracked in a [`MasterState`].
+//!
+//! Slaves dial the master, not the other way round, so "connecting" a
+//! client means accepting a slave: [`TixClient::listen`] binds the
+//! address a slave is configured to reach and waits for it.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use tix_core::ConnectionInfo;
+//! use tix_core::client::TixClient;
+//!
+//! # async fn example() -> Result<(), tix_core::TixError> {
+//! let client = TixClient::listen(&ConnectionInfo::new("0.0.0.0".into(), 4321)).await?;
+//! println!("round trip {:?}", client.ping().await?);
+//!
+//! let mut output = client.shell("ipconfig").await?;
+//! while let Some(chunk) = output.next().await {
+//!     print!("{}", String::from_utf8_lossy(&chunk.data));
+//! }
+//! let status = output.wait().await?;
+//! println!("exit code {}", status.exit_code);
+//!
+//! // Both run at once over the same connection.
+//! let (listing, summary) = tokio::join!(
+//!     client.list_dir(r"C:\Logs"),
+//!     client.download(r"C:\Logs\app.log", "app.log"),
+//! );
+//! println!("{} entries, {} bytes copied", listing?.len(), summary?.bytes);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures::Stream;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::Sleep;
+
+use crate::error::TixError;
+use crate::flags::ProtocolFlags;
+use crate::message::Command;
+use crate::network::upload::{AckWindow, send_file_windowed};
+use crate::network::{Connection, ConnectionInfo, ConnectionSender};
+use crate::packet::Packet;
+use crate::protocol::file::{DEFAULT_CHUNK_SIZE, DEFAULT_UPLOAD_WINDOW};
+use crate::protocol::{
+    ChunkCompressor, DeltaSyncRequest, FileAttributes, FileChunk, FileDigest,
+    FileHashVerification, FileMetadata, FileTransferAck, FileWriteProgress, LimitExceeded,
+    ShellExitStatus, ShellOutputChunk, TaskFailure,
+};
+use crate::state::{MasterState, request_id};
+
+/// How long a call waits for the next packet of its reply before it
+/// fails with [`TixError::Timeout`].
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// ── TransferSummary ──────────────────────────────────────────────
+
+/// Outcome of a completed [`download`](TixClient::download) or
+/// [`upload`](TixClient::upload).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferSummary {
+    /// Where the file ended up: the local path for a download, the path
+    /// the slave reports for an upload.
+    pub path: String,
+    /// Bytes transferred.
+    pub bytes: u64,
+    /// Blake3 hash of the contents, verified by the receiving side.
+    pub hash: [u8; 32],
+    /// Wall time from request to completion.
+    pub elapsed: Duration,
+}
+
+// ── Routing ──────────────────────────────────────────────────────
+
+/// Requests in flight and where their replies go.
+#[derive(Default)]
+struct Router {
+    state: MasterState,
+    routes: HashMap<u64, mpsc::UnboundedSender<Packet>>,
+}
+
+type SharedRouter = Arc<Mutex<Router>>;
+
+/// Deliver every packet from `conn` to the call waiting on its request
+/// ID. Packets nobody waits for (unsolicited warnings, late replies to
+/// abandoned calls) are dropped. When the connection closes,
+/// every pending call sees its channel end.
+async fn route(mut conn: Connection, router: SharedRouter) {
+    while let Some(packet) = conn.recv().await {
+        let router = router.lock().unwrap();
+        if let Some(route) = router.routes.get(&packet.request_id()) {
+            let _ = route.send(packet);
+        }
+    }
+    router.lock().unwrap().routes.clear();
+}
+
+/// The receiving end of one call. Dropping it forgets the request.
+struct PendingRequest {
+    id: u64,
+    rx: mpsc::UnboundedReceiver<Packet>,
+    router: SharedRouter,
+    timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+}
+
+impl PendingRequest {
+    /// The next packet of the reply, waiting at most the request timeout
+    /// since the previous one.
+    async fn next(&mut self) -> Result<Packet, TixError> {
+        std::future::poll_fn(|cx| self.poll_packet(cx)).await
+    }
+
+    /// The next packet with no deadline, for while the caller itself is
+    /// still busy sending.
+    async fn recv(&mut self) -> Result<Packet, TixError> {
+        let packet = self.rx.recv().await.ok_or(TixError::ChannelClosed)?;
+        self.reset_deadline();
+        check_refusal(packet)
+    }
+
+    fn poll_packet(&mut self, cx: &mut Context<'_>) -> Poll<Result<Packet, TixError>> {
+        match self.rx.poll_recv(cx) {
+            Poll::Ready(Some(packet)) => {
+                self.reset_deadline();
+                Poll::Ready(check_refusal(packet))
+            }
+            Poll::Ready(None) => Poll::Ready(Err(TixError::ChannelClosed)),
+            Poll::Pending => match self.deadline.as_mut().poll(cx) {
+                Poll::Ready(()) => Poll::Ready(Err(TixError::Timeout(self.timeout))),
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+
+    fn reset_deadline(&mut self) {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        self.deadline.as_mut().reset(deadline);
+    }
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        let mut router = self.router.lock().unwrap();
+        router.routes.remove(&self.id);
+        router.state.resolve(self.id);
+    }
+}
+
+/// A slave over its traffic budget answers with `LimitExceeded` instead
+/// of the reply the call expects, and one whose task failed with an
+/// `ERROR` packet.
+fn check_refusal(packet: Packet) -> Result<Packet, TixError> {
+    if packet.command().ok() == Some(Command::LimitExceeded) {
+        let reason = LimitExceeded::from_bytes(packet.payload())
+            .map(|refusal| refusal.to_string())
+            .unwrap_or_else(|_| "slave refused the request".to_string());
+        return Err(TixError::Other(reason));
+    }
+    if packet.flags().contains(ProtocolFlags::ERROR) {
+        let failure = TaskFailure::from_payload(packet.payload());
+        return Err(TixError::Other(failure.to_string()));
+    }
+    Ok(packet)
+}
+
+// ── TixClient ────────────────────────────────────────────────────
+
+/// An async, headless handle on one slave. See the [module docs](self).
+pub struct TixClient {
+    tx: ConnectionSender,
+    router: SharedRouter,
+    next_id: AtomicU64,
+    timeout: Duration,
+    peer_addr: Option<SocketAddr>,
+    reader: tokio::task::JoinHandle<()>,
+}
+
+impl TixClient {
+    /// Drive the slave on the other end of `conn`.
+    pub fn new(conn: Connection) -> Self {
+        let router = SharedRouter::default();
+        Self {
+            tx: conn.sender(),
+            peer_addr: conn.peer_addr(),
+            router: Arc::clone(&router),
+            next_id: AtomicU64::new(request_id::random_offset()),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            reader: tokio::spawn(route(conn, router)),
+        }
+    }
+
+    /// Bind `info` and wait for a slave to connect.
+    pub async fn listen(info: &ConnectionInfo) -> Result<Self, TixError> {
+        let listener = TcpListener::bind(info.to_socket_string()).await?;
+        Self::accept(&listener).await
+    }
+
+    /// Wait for the next slave on an existing listener.
+    pub async fn accept(listener: &TcpListener) -> Result<Self, TixError> {
+        let (stream, _) = listener.accept().await?;
+        Ok(Self::new(Connection::new(stream)))
+    }
+
+    /// Fail a call that waits longer than `timeout` for the next packet
+    /// of its reply (default [`DEFAULT_REQUEST_TIMEOUT`]).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The slave's address.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Number of calls currently awaiting a reply.
+    pub fn pending(&self) -> usize {
+        self.router.lock().unwrap().state.pending_count()
+    }
+
+    /// Round-trip time of a `Ping`.
+    pub async fn ping(&self) -> Result<Duration, TixError> {
+        let start = Instant::now();
+        let mut request = self
+            .send(|id| Packet::new_command(id, Command::Ping, Vec::new()))
+            .await?;
+        request.next().await?;
+        Ok(start.elapsed())
+    }
+
+    /// Run `command` in the slave's shell. The returned stream yields
+    /// its output as it arrives; [`ShellOutput::wait`] gives the exit
+    /// status.
+    pub async fn shell(&self, command: &str) -> Result<ShellOutput, TixError> {
+        let payload = command.as_bytes().to_vec();
+        let request = self
+            .send(|id| Packet::new_command(id, Command::ShellExecute, payload))
+            .await?;
+        Ok(ShellOutput::new(request))
+    }
+
+    /// The entries of the directory `path` on the slave. A directory
+    /// that does not exist or cannot be read lists as empty.
+    pub async fn list_dir(&self, path: &str) -> Result<Vec<FileMetadata>, TixError> {
+        let payload = path.as_bytes().to_vec();
+        let mut request = self
+            .send(|id| Packet::new_command(id, Command::ListDir, payload))
+            .await?;
+        let reply = request.next().await?;
+        Ok(parse_listing(&String::from_utf8_lossy(reply.payload())))
+    }
+
+    /// Copy the slave's `remote` file to `local`, replacing it. The
+    /// contents are checked against the slave's hash; a mismatch
+    /// removes `local` and fails with [`TixError::FileIntegrityFailed`].
+    pub async fn download(
+        &self,
+        remote: &str,
+        local: impl AsRef<Path>,
+    ) -> Result<TransferSummary, TixError> {
+        let local = local.as_ref();
+        let start = Instant::now();
+        // Asking for the chunks that differ from an empty file gets them all.
+        let nothing = FileDigest::of(&[], DEFAULT_CHUNK_SIZE as u32);
+        let mut request = self
+            .send(|id| DeltaSyncRequest::new(remote, &nothing).into_packet(id))
+            .await?;
+
+        let local_err = |e: std::io::Error| TixError::Other(format!("{}: {e}", local.display()));
+        let mut file = tokio::fs::File::create(local).await.map_err(local_err)?;
+        let mut hasher = blake3::Hasher::new();
+        let verification = loop {
+            let packet = request.next().await?;
+            let flags = packet.flags();
+            if flags.contains(ProtocolFlags::FINAL_FRAGMENT) {
+                break FileHashVerification::from_bytes(packet.payload())?;
+            }
+            if !flags.contains(ProtocolFlags::STREAMING) {
+                // The slave could not read the file; the reply says why.
+                drop(file);
+                let _ = tokio::fs::remove_file(local).await;
+                let reason = String::from_utf8_lossy(packet.payload()).into_owned();
+                return Err(TixError::Other(reason));
+            }
+            let chunk = FileChunk::from_bytes(packet.payload())?.unpack(nothing.chunk_size)?;
+            hasher.update(&chunk.data);
+            file.seek(std::io::SeekFrom::Start(chunk.offset))
+                .await
+                .map_err(local_err)?;
+            file.write_all(&chunk.data).await.map_err(local_err)?;
+        };
+        file.flush().await.map_err(local_err)?;
+        drop(file);
+
+        let hash = *hasher.finalize().as_bytes();
+        let size = tokio::fs::metadata(local).await.map_err(local_err)?.len();
+        if hash != verification.blake3_hash || size != verification.total_bytes {
+            let _ = tokio::fs::remove_file(local).await;
+            return Err(TixError::FileIntegrityFailed);
+        }
+        Ok(TransferSummary {
+            path: local.display().to_string(),
+            bytes: size,
+            hash,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// Copy `local` to the slave as `remote`. A relative `remote` lands
+    /// in the slave's upload folder under its file name. An existing file
+    /// is never replaced: the slave writes a numbered copy beside it, and
+    /// the summary has the path actually written. At most
+    /// [`DEFAULT_UPLOAD_WINDOW`] chunks go out ahead of what the slave
+    /// has reported written.
+    pub async fn upload(
+        &self,
+        local: impl AsRef<Path>,
+        remote: &str,
+    ) -> Result<TransferSummary, TixError> {
+        let local = local.as_ref();
+        let start = Instant::now();
+        let id = request_id::wrap(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let first = FileTransferAck {
+            path: remote.to_string(),
+            bytes_written: 0,
+            error: None,
+        };
+        // Tracked under the ID before anything is sent; the packets
+        // themselves go out through `send_file`.
+        let mut request = self.register(id, first.into_packet(id)?);
+
+        let sent = AtomicU64::new(0);
+        let (acks, mut window) = AckWindow::channel(DEFAULT_UPLOAD_WINDOW);
+        // The client never says `Hello`, so the slave cannot know it
+        // takes compressed chunks; it gets raw ones.
+        let sending = send_file_windowed(
+            &self.tx,
+            id,
+            local,
+            remote.to_string(),
+            &sent,
+            ChunkCompressor::raw(),
+            &mut window,
+        );
+        tokio::pin!(sending);
+        // The slave may refuse the upload before all of it is sent, in
+        // which case there is no hash to report. Until it reports
+        // progress there is no telling how long it may stay quiet; once
+        // it does, silence means it stalled.
+        let mut hash = None;
+        let mut reporting = false;
+        let reply = loop {
+            let deadline = hash.is_some() || reporting;
+            tokio::select! {
+                sent = &mut sending, if hash.is_none() => hash = Some(sent?),
+                reply = async {
+                    if deadline { request.next().await } else { request.recv().await }
+                } => {
+                    let reply = reply?;
+                    if !reply.flags().contains(ProtocolFlags::STREAMING) {
+                        break reply;
+                    }
+                    let _ = acks.send(FileWriteProgress::from_bytes(reply.payload())?);
+                    reporting = true;
+                }
+            }
+        };
+        let hash = hash.unwrap_or([0; 32]);
+        let ack = FileTransferAck::from_bytes(reply.payload())?;
+        if let Some(error) = ack.error {
+            return Err(TixError::Other(format!("{}: {error}", ack.path)));
+        }
+        Ok(TransferSummary {
+            path: ack.path,
+            bytes: ack.bytes_written,
+            hash,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    // ── Internal ─────────────────────────────────────────────────
+
+    /// Send the packet `build` makes for a fresh request ID, with its
+    /// reply channel already in place.
+    async fn send(
+        &self,
+        build: impl FnOnce(u64) -> Result<Packet, TixError>,
+    ) -> Result<PendingRequest, TixError> {
+        let id = request_id::wrap(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let packet = build(id)?;
+        let request = self.register(id, packet.clone());
+        self.tx
+            .send(packet)
+            .await
+            .map_err(|_| TixError::ChannelClosed)?;
+        Ok(request)
+    }
+
+    /// Track `id` and open its reply channel.
+    fn register(&self, id: u64, packet: Packet) -> PendingRequest {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut router = self.router.lock().unwrap();
+        router
+            .state
+            .track_with_deadline(id, packet, Some(self.timeout));
+        router.routes.insert(id, tx);
+        PendingRequest {
+            id,
+            rx,
+            router: Arc::clone(&self.router),
+            timeout: self.timeout,
+            deadline: Box::pin(tokio::time::sleep(self.timeout)),
+        }
+    }
+}
+
+impl Drop for TixClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Parse a `ListDir` reply: `PATH|<dir>;<name>|<0 or 1>|<size>|<attributes>;…`.
+fn parse_listing(text: &str) -> Vec<FileMetadata> {
+    let mut entries = text.split(';').filter(|e| !e.is_empty()).peekable();
+    let dir = entries
+        .next_if(|e| e.starts_with("PATH|"))
+        .map(|e| Path::new(&e[5..]).to_path_buf())
+        .unwrap_or_default();
+    entries
+        .filter_map(|entry| {
+            let mut parts = entry.split('|');
+            let name = parts.next()?.to_string();
+            let is_directory = parts.next()? == "1";
+            let size = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+            let attributes = FileAttributes::from_field(parts.next());
+            Some(FileMetadata {
+                path: dir.join(&name).display().to_string(),
+                name,
+                size,
+                modified: 0,
+                is_directory,
+                hash: None,
+                attributes,
+            })
+        })
+        .collect()
+}
+
+// ── ShellOutput ──────────────────────────────────────────────────
+
+/// Output of a running [`shell`](TixClient::shell) command.
+///
+/// A [`Stream`] of output chunks in arrival order. It ends when the
+/// command exits or the call fails; [`wait`](Self::wait) then returns
+/// the exit status or the error.
+pub struct ShellOutput {
+    request: PendingRequest,
+    buffered: VecDeque<ShellOutputChunk>,
+    exit: Option<Result<ShellExitStatus, TixError>>,
+}
+
+impl ShellOutput {
+    fn new(request: PendingRequest) -> Self {
+        Self {
+            request,
+            buffered: VecDeque::new(),
+            exit: None,
+        }
+    }
+
+    /// Wait for the command to exit, discarding output not yet read.
+    pub async fn wait(mut self) -> Result<ShellExitStatus, TixError> {
+        while self.exit.is_none() {
+            let packet = self.request.next().await;
+            self.accept(packet);
+        }
+        self.buffered.clear();
+        self.exit.take().unwrap()
+    }
+
+    /// Fold one reply packet into the buffered output or exit status.
+    fn accept(&mut self, packet: Result<Packet, TixError>) {
+        let packet = match packet {
+            Ok(packet) => packet,
+            Err(e) => {
+                self.exit = Some(Err(e));
+                return;
+            }
+        };
+        let flags = packet.flags();
+        if flags.contains(ProtocolFlags::STREAMING) {
+            match ShellOutputChunk::from_bytes(packet.payload()) {
+                Ok(chunk) => self.buffered.push_back(chunk),
+                Err(e) => self.exit = Some(Err(e)),
+            }
+        } else if flags.contains(ProtocolFlags::FINAL_FRAGMENT) {
+            self.exit = Some(ShellExitStatus::from_bytes(packet.payload()));
+        } else {
+            let (chunks, status) = parse_legacy_shell(packet.payload());
+            self.buffered.extend(chunks);
+            self.exit = Some(Ok(status));
+        }
+    }
+}
+
+impl Stream for ShellOutput {
+    type Item = ShellOutputChunk;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(chunk) = this.buffered.pop_front() {
+                return Poll::Ready(Some(chunk));
+            }
+            if this.exit.is_some() {
+                return Poll::Ready(None);
+            }
+            match this.request.poll_packet(cx) {
+                Poll::Ready(packet) => this.accept(packet),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Split a single-reply shell result, `stdout: …\nstderr: …\nExit Code: n`,
+/// into output chunks and an exit status.
+fn parse_legacy_shell(payload: &[u8]) -> (Vec<ShellOutputChunk>, ShellExitStatus) {
+    let text = String::from_utf8_lossy(payload);
+    let Some((output, code)) = text.rsplit_once("\nExit Code: ") else {
+        // Not the usual shape: pass it through as output.
+        let chunk = ShellOutputChunk::stdout(0, payload.to_vec());
+        return (vec![chunk], ShellExitStatus::success(0, 1));
+    };
+    let exit_code = code.trim().parse().unwrap_or(-1);
+    let output = output.strip_prefix("stdout: ").unwrap_or(output);
+    let (stdout, stderr) = output.rsplit_once("\nstderr: ").unwrap_or((output, ""));
+    let mut chunks = Vec::new();
+    for (data, is_stdout) in [(stdout, true), (stderr, false)] {
+        if !data.is_empty() {
+            chunks.push(ShellOutputChunk {
+                chunk_number: chunks.len() as u64,
+                data: data.as_bytes().to_vec(),
+                is_stdout,
+            });
+        }
+    }
+    let total = chunks.len() as u64;
+    (chunks, ShellExitStatus::success(exit_code, total))
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    /// A client and the raw slave end of its connection.
+    async fn pair() -> (TixClient, Connection) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let info = ConnectionInfo::new("127.0.0.1".into(), listener.local_addr().unwrap().port());
+        let (client, slave) =
+            tokio::join!(TixClient::accept(&listener), Connection::connect(&info));
+        (client.unwrap(), slave.unwrap())
+    }
+
+    /// The next packet the slave side receives.
+    async fn next_command(conn: &mut Connection) -> Packet {
+        conn.recv().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn replies_reach_their_own_call() {
+        let (client, mut slave) = pair().await;
+        let peer = tokio::spawn(async move {
+            let first = next_command(&mut slave).await;
+            let second = next_command(&mut slave).await;
+            let (listing, ping) = if first.command().unwrap() == Command::ListDir {
+                (first, second)
+            } else {
+                (second, first)
+            };
+            // Answer in the opposite order, with noise in between.
+            slave
+                .send(
+                    Packet::new_response(ping.request_id(), Command::Ping, b"Pong".to_vec())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            slave
+                .send(Packet::new_response(999, Command::Ping, Vec::new()).unwrap())
+                .await
+                .unwrap();
+            // `logs` comes from an older slave without attributes.
+            let reply = "PATH|/srv;logs|1|0;a.txt|0|12|3";
+            slave
+                .send(
+                    Packet::new_response(listing.request_id(), Command::ListDir, reply.into())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            slave
+        });
+
+        let (listing, rtt) = tokio::join!(client.list_dir("/srv"), client.ping());
+        assert!(rtt.is_ok());
+        let listing = listing.unwrap();
+        assert_eq!(listing.len(), 2);
+        assert!(listing[0].is_directory);
+        assert_eq!(listing[0].attributes, FileAttributes::empty());
+        assert_eq!((listing[1].name.as_str(), listing[1].size), ("a.txt", 12));
+        assert_eq!(
+            listing[1].attributes,
+            FileAttributes::READONLY | FileAttributes::HIDDEN
+        );
+        assert_eq!(Path::new(&listing[1].path), Path::new("/srv").join("a.txt"));
+        assert_eq!(client.pending(), 0);
+        drop(peer.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn silence_times_out_and_hangup_fails_calls() {
+        let (client, slave) = pair().await;
+        let client = client.with_timeout(Duration::from_millis(50));
+        assert!(matches!(client.ping().await, Err(TixError::Timeout(_))));
+        assert_eq!(client.pending(), 0);
+
+        drop(slave);
+        let mut output = client.shell("dir").await.unwrap();
+        assert_eq!(output.next().await, None);
+        assert!(output.wait().await.is_err());
+    }
+
+    /// A file of `chunks` upload chunks.
+    fn chunked_file(name: &str, chunks: usize) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("tix-client-{}-{}", std::process::id(), name));
+        std::fs::write(&path, vec![5u8; DEFAULT_CHUNK_SIZE * chunks]).unwrap();
+        path
+    }
+
+    /// Upload chunks that arrive within `quiet` of each other.
+    async fn drain_chunks(slave: &mut Connection, quiet: Duration) -> u64 {
+        let mut chunks = 0;
+        while let Ok(Some(packet)) = tokio::time::timeout(quiet, slave.recv()).await {
+            if packet.command().ok() == Some(Command::FileWrite) {
+                chunks += 1;
+            }
+        }
+        chunks
+    }
+
+    #[tokio::test]
+    async fn upload_waits_for_the_window_to_open() {
+        let (client, mut slave) = pair().await;
+        let path = chunked_file("window.bin", DEFAULT_UPLOAD_WINDOW as usize + 8);
+        let peer = tokio::spawn(async move {
+            let header = next_command(&mut slave).await;
+            let id = header.request_id();
+            assert!(header.flags().contains(ProtocolFlags::ACK_REQUESTED));
+            let progress = FileWriteProgress::new(0, 0).into_packet(id).unwrap();
+            slave.send(progress).await.unwrap();
+
+            // Nothing more is acked: the sender stops a window ahead.
+            let quiet = Duration::from_millis(200);
+            assert_eq!(drain_chunks(&mut slave, quiet).await, DEFAULT_UPLOAD_WINDOW);
+
+            let progress = FileWriteProgress::new(DEFAULT_UPLOAD_WINDOW, 0);
+            slave.send(progress.into_packet(id).unwrap()).await.unwrap();
+            // The rest of the chunks and the verification.
+            assert_eq!(drain_chunks(&mut slave, quiet).await, 9);
+            let ack = FileTransferAck {
+                path: "window.bin".into(),
+                bytes_written: (DEFAULT_CHUNK_SIZE * (DEFAULT_UPLOAD_WINDOW as usize + 8)) as u64,
+                error: None,
+            };
+            slave.send(ack.into_packet(id).unwrap()).await.unwrap();
+            slave
+        });
+
+        let summary = client.upload(&path, "window.bin").await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(summary.path, "window.bin");
+        assert_ne!(summary.hash, [0; 32]);
+        assert_eq!(client.pending(), 0);
+        drop(peer.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn upload_stops_at_an_error_ack() {
+        let (client, mut slave) = pair().await;
+        let total = DEFAULT_UPLOAD_WINDOW as usize * 4;
+        let path = chunked_file("refused.bin", total);
+        let peer = tokio::spawn(async move {
+            let id = next_command(&mut slave).await.request_id();
+            let progress = FileWriteProgress::new(0, 0).into_packet(id).unwrap();
+            slave.send(progress).await.unwrap();
+            let ack = FileTransferAck {
+                path: "refused.bin".into(),
+                bytes_written: 0,
+                error: Some("disk full".into()),
+            };
+            slave.send(ack.into_packet(id).unwrap()).await.unwrap();
+            // Whatever was already on its way, but not the whole file.
+            let sent = drain_chunks(&mut slave, Duration::from_millis(200)).await;
+            assert!(sent <= DEFAULT_UPLOAD_WINDOW, "{sent} chunks sent");
+            slave
+        });
+
+        let err = client.upload(&path, "refused.bin").await.unwrap_err();
+        assert!(err.to_string().contains("disk full"), "{err}");
+        drop(peer.await.unwrap());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn legacy_shell_reply_splits_streams() {
+        let (chunks, status) = parse_legacy_shell(b"stdout: hi\n\nstderr: oops\n\nExit Code: 3");
+        assert_eq!(status.exit_code, 3);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0], ShellOutputChunk::stdout(0, b"hi\n".to_vec()));
+        assert_eq!(chunks[1], ShellOutputChunk::stderr(1, b"oops\n".to_vec()));
+
+        let (chunks, status) = parse_legacy_shell(b"stdout: \nstderr: \nExit Code: 0");
+        assert!(chunks.is_empty());
+        assert_eq!(status.exit_code, 0);
+    }
+}
@@ -1,14 +1,20 @@
 //! TIX wire codec — Decoder / Encoder for `tokio_util::codec::Framed`.
 //!
 //! The codec reads/writes complete `Packet` values from a TCP stream.
-//! Framing is done by first reading the fixed 64-byte header, extracting
-//! the payload length, then waiting for the full payload before yielding.
+//! Framing is done by first reading the 64-byte minimum header, whose
+//! magic tells the header version and size, then the rest of the
+//! header for the payload length, then waiting for the full payload
+//! before yielding.
+//!
+//! Both TIX1 and TIX2 are decoded on any connection, so a peer may
+//! switch versions between two packets. Packets are encoded in the
+//! version they carry (see [`Packet::set_wire_version`]).
 
 use bytes::BytesMut;
 use tokio_util::codec::{Decoder, Encoder};
 
 use crate::error::TixError;
-use crate::header::HEADER_SIZE;
+use crate::header::{HEADER_SIZE, PacketHeader, WireVersion};
 use crate::packet::{MAX_FRAME_SIZE, MAX_PAYLOAD_SIZE, Packet};
 
 /// Stateless codec for TIX packets.
@@ -19,21 +25,18 @@ impl Decoder for TixCodec {
     type Error = TixError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        // Guard: total buffered data must not exceed the frame limit.
-        if src.len() > MAX_FRAME_SIZE {
-            return Err(TixError::FrameTooLarge {
-                size: src.len(),
-                max: MAX_FRAME_SIZE,
-            });
-        }
-
-        // Need at least a full header to proceed.
+        // Need at least the smallest header; its magic then tells how
+        // long the header really is.
         if src.len() < HEADER_SIZE {
             return Ok(None);
         }
+        let header_size = WireVersion::from_magic(&src[..4])?.header_size();
+        if src.len() < header_size {
+            return Ok(None);
+        }
 
         // Peek at the header to learn the payload length.
-        let header = crate::header::PacketHeader::from_bytes(&src[..HEADER_SIZE])?;
+        let header = PacketHeader::from_bytes(&src[..header_size])?;
         let payload_len = header.payload_length() as usize;
 
         if payload_len > MAX_PAYLOAD_SIZE {
@@ -50,7 +53,15 @@ impl Decoder for TixCodec {
             ));
         }
 
-        let total = HEADER_SIZE + payload_len;
+        // The buffer may hold several frames once the reader falls
+        // behind; only the one being decoded is bounded.
+        let total = header_size + payload_len;
+        if total > MAX_FRAME_SIZE {
+            return Err(TixError::FrameTooLarge {
+                size: total,
+                max: MAX_FRAME_SIZE,
+            });
+        }
         if src.len() < total {
             // Reserve capacity to avoid repeated allocations.
             src.reserve(total - src.len());
@@ -118,4 +129,72 @@ mod tests {
         assert_eq!(decoded.payload(), payload.as_slice());
         assert!(decoded.validate_checksum());
     }
+
+    #[test]
+    fn decodes_mixed_versions_from_one_stream() {
+        let mut codec = TixCodec;
+        let mut buf = BytesMut::new();
+        for (i, version) in [WireVersion::V1, WireVersion::V2, WireVersion::V1]
+            .into_iter()
+            .enumerate()
+        {
+            let mut pkt =
+                Packet::new_response(i as u64, Command::ShellExecute, vec![i as u8; 100]).unwrap();
+            pkt.set_wire_version(version);
+            codec.encode(pkt, &mut buf).unwrap();
+        }
+
+        // Feed it a byte at a time to exercise every partial state.
+        let mut stream = BytesMut::new();
+        let mut decoded = Vec::new();
+        for byte in buf {
+            stream.extend_from_slice(&[byte]);
+            while let Some(pkt) = codec.decode(&mut stream).unwrap() {
+                decoded.push(pkt);
+            }
+        }
+        let versions: Vec<_> = decoded.iter().map(Packet::wire_version).collect();
+        assert_eq!(
+            versions,
+            [WireVersion::V1, WireVersion::V2, WireVersion::V1]
+        );
+        for (i, pkt) in decoded.iter().enumerate() {
+            assert_eq!(pkt.request_id(), i as u64);
+            assert_eq!(pkt.payload(), vec![i as u8; 100].as_slice());
+        }
+    }
+
+    #[test]
+    fn decodes_a_backlog_of_large_frames() {
+        let mut codec = TixCodec;
+        let mut buf = BytesMut::new();
+        for id in 1..=3 {
+            let pkt =
+                Packet::new_command(id, Command::FileWrite, vec![0xAB; MAX_PAYLOAD_SIZE]).unwrap();
+            codec.encode(pkt, &mut buf).unwrap();
+        }
+        assert!(buf.len() > MAX_FRAME_SIZE);
+        for id in 1..=3 {
+            let pkt = codec.decode(&mut buf).unwrap().unwrap();
+            assert_eq!(pkt.request_id(), id);
+        }
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn unknown_version_is_a_typed_error() {
+        let mut codec = TixCodec;
+        let mut buf = BytesMut::new();
+        codec
+            .encode(
+                Packet::new_command(1, Command::Ping, Vec::new()).unwrap(),
+                &mut buf,
+            )
+            .unwrap();
+        buf[0..4].copy_from_slice(b"TIX7");
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(TixError::UnsupportedVersion(7))
+        ));
+    }
 }
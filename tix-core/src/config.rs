@@ -0,0 +1,358 @@
+//! Reading and writing the TOML files the tix binaries are set up with.
+//!
+//! A missing file is fine, since every setting has a default, but a file
+//! that is there and wrong is not: [`load`] reports syntax errors with
+//! their line and column, unknown keys (naming the closest known one when
+//! it looks like a typo) and values that cannot work, so a typo never
+//! turns silently into default behaviour. The binaries stop on these
+//! unless started with [`IGNORE_ERRORS_FLAG`].
+//!
+//! [`write_atomic`] is how config and state files are written back: into
+//! a temporary file beside the target that is then renamed over it, so a
+//! crash mid-write leaves the old file rather than a truncated one.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+/// Start with the defaults instead of stopping on a bad config file.
+pub const IGNORE_ERRORS_FLAG: &str = "--ignore-config-errors";
+
+/// Exit code of a binary that refused its config file.
+pub const EXIT_INVALID_CONFIG: i32 = 2;
+
+/// Checks on a parsed config that its types cannot express.
+pub trait Validate {
+    /// One message per setting that cannot work, naming it as
+    /// `section.key`; empty when all is well.
+    fn problems(&self) -> Vec<String>;
+}
+
+/// Why a config file was refused.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("cannot read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// Not TOML, or not the shape of the config; `message` points at
+    /// the line and column.
+    #[error("invalid config {}: {message}", path.display())]
+    Parse { path: PathBuf, message: String },
+
+    #[error("invalid config {}:\n  {}", path.display(), problems.join("\n  "))]
+    Invalid {
+        path: PathBuf,
+        problems: Vec<String>,
+    },
+}
+
+/// Read the config at `path`. `Ok(None)` means there is no file, and the
+/// defaults apply.
+pub fn load<T: DeserializeOwned + Validate>(path: &Path) -> Result<Option<T>, ConfigError> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => parse(path, &text).map(Some),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// For a binary's `main`: the config at `path`, or the defaults when
+/// there is no file. A bad file is reported on stderr and ends the
+/// process with [`EXIT_INVALID_CONFIG`], or, with `ignore_errors`, is
+/// replaced by the defaults. The second value is a note for the log
+/// once it is set up, when the defaults are used because there is no
+/// file.
+pub fn load_or_exit<T>(path: &Path, ignore_errors: bool) -> (T, Option<String>)
+where
+    T: DeserializeOwned + Validate + Default,
+{
+    match load(path) {
+        Ok(Some(config)) => (config, None),
+        Ok(None) => (
+            T::default(),
+            Some(format!("no config at {}; using defaults", path.display())),
+        ),
+        Err(e) if ignore_errors => {
+            eprintln!("{}\nignored ({}); using defaults", e, IGNORE_ERRORS_FLAG);
+            (T::default(), None)
+        }
+        Err(e) => {
+            eprintln!(
+                "{}\nfix the file, or start with {} to use the defaults",
+                e, IGNORE_ERRORS_FLAG
+            );
+            std::process::exit(EXIT_INVALID_CONFIG);
+        }
+    }
+}
+
+/// Parse and check `text`, read from `path`.
+pub fn parse<T: DeserializeOwned + Validate>(path: &Path, text: &str) -> Result<T, ConfigError> {
+    let config: T = toml::from_str(text).map_err(|e| {
+        let mut message = e.to_string().trim_end().to_string();
+        if let Some(hint) = unknown_field_hint(e.message()) {
+            message.push_str(&format!("\nhelp: {}", hint));
+        }
+        ConfigError::Parse {
+            path: path.to_path_buf(),
+            message,
+        }
+    })?;
+    let problems = config.problems();
+    if !problems.is_empty() {
+        return Err(ConfigError::Invalid {
+            path: path.to_path_buf(),
+            problems,
+        });
+    }
+    Ok(config)
+}
+
+/// "did you mean `fps`?" for serde's "unknown field `fsp`, expected one
+/// of `fps`, ..." when one of the expected keys is close enough.
+fn unknown_field_hint(message: &str) -> Option<String> {
+    let rest = message.strip_prefix("unknown field ")?;
+    // Keys are quoted in backticks: the unknown one, then the expected.
+    let mut keys = rest.split('`').skip(1).step_by(2);
+    let unknown = keys.next()?;
+    let expected: Vec<&str> = keys.collect();
+    did_you_mean(unknown, &expected).map(|key| format!("did you mean `{}`?", key))
+}
+
+/// The candidate closest to `word`, if it is close enough to be what was
+/// meant: at most one edit for every three characters (and at least
+/// one), or `word` cut short, as `timeout` for `timeout_secs`.
+pub fn did_you_mean<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let word = word.to_lowercase();
+    let limit = (word.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .map(|c| {
+            let candidate = c.to_lowercase();
+            let mut distance = edit_distance(&word, &candidate);
+            if word.len() >= 3 && candidate.starts_with(&word) {
+                distance = distance.min(limit);
+            }
+            (distance, *c)
+        })
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c)
+}
+
+/// Insertions, deletions, substitutions and swaps of neighbouring
+/// characters needed to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Three rows of the table: two back, one back, current.
+    let mut before: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut prev, row);
+    }
+    prev[b.len()]
+}
+
+/// What is wrong with `address` as a `host:port` for the setting `key`.
+pub fn address_problem(key: &str, address: &str) -> Option<String> {
+    let Some((host, port)) = address.rsplit_once(':') else {
+        return Some(format!("{} = \"{}\" has no :port", key, address));
+    };
+    if host.is_empty() {
+        return Some(format!("{} = \"{}\" has no host", key, address));
+    }
+    match port.parse::<u16>() {
+        Ok(0) => Some(format!("{} = \"{}\": port must not be 0", key, address)),
+        Ok(_) => None,
+        Err(_) => Some(format!(
+            "{} = \"{}\": port must be a number from 1 to 65535",
+            key, address
+        )),
+    }
+}
+
+/// Replace the file at `path` with `contents` in one step. The data goes
+/// to a temporary file in the same directory first, is flushed to disk,
+/// and is then renamed over `path`.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", std::process::id()));
+    let tmp = path.with_file_name(name);
+    let written = std::fs::File::create(&tmp).and_then(|mut file| {
+        file.write_all(contents.as_ref())?;
+        file.sync_all()
+    });
+    match written.and_then(|()| std::fs::rename(&tmp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    struct Sample {
+        screen: Screen,
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    struct Screen {
+        fps: u8,
+        monitor_index: u32,
+    }
+
+    impl Validate for Sample {
+        fn problems(&self) -> Vec<String> {
+            if self.screen.fps == 0 {
+                vec!["screen.fps must be at least 1".into()]
+            } else {
+                Vec::new()
+            }
+        }
+    }
+
+    fn parse_sample(text: &str) -> Result<Sample, ConfigError> {
+        parse(Path::new("sample.toml"), text)
+    }
+
+    #[test]
+    fn edit_distance_counts_swaps_as_one() {
+        assert_eq!(edit_distance("fps", "fps"), 0);
+        assert_eq!(edit_distance("fsp", "fps"), 1);
+        assert_eq!(edit_distance("scren", "screen"), 1);
+        assert_eq!(edit_distance("", "mtu"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn suggests_only_close_keys() {
+        let keys = ["fps", "monitor_index", "block_size"];
+        assert_eq!(did_you_mean("fsp", &keys), Some("fps"));
+        assert_eq!(did_you_mean("Monitor_Idx", &keys), Some("monitor_index"));
+        assert_eq!(did_you_mean("block", &keys), Some("block_size"));
+        assert_eq!(did_you_mean("colour", &keys), None);
+        assert_eq!(did_you_mean("fp", &keys), Some("fps"));
+        assert_eq!(did_you_mean("x", &[]), None);
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_line() {
+        let err = parse_sample("[screen]\nfps = = 3\n").unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { .. }));
+        assert!(err.to_string().contains("line 2, column"), "{}", err);
+        // A port that does not fit its type is a parse error too.
+        let err = parse_sample("[screen]\nfps = 300\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_keys_are_refused_with_a_suggestion() {
+        let err = parse_sample("[screen]\nfsp = 30\n").unwrap_err();
+        let text = err.to_string();
+        assert!(text.contains("unknown field `fsp`"), "{}", text);
+        assert!(text.ends_with("help: did you mean `fps`?"), "{}", text);
+
+        let err = parse_sample("[scren]\nfps = 30\n").unwrap_err();
+        assert!(
+            err.to_string().ends_with("did you mean `screen`?"),
+            "{}",
+            err
+        );
+
+        let err = parse_sample("[screen]\nwallpaper = true\n").unwrap_err();
+        assert!(!err.to_string().contains("did you mean"), "{}", err);
+    }
+
+    #[test]
+    fn semantic_problems_are_listed() {
+        let err = parse_sample("[screen]\nfps = 0\n").unwrap_err();
+        match &err {
+            ConfigError::Invalid { problems, .. } => {
+                assert_eq!(problems, &["screen.fps must be at least 1"])
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(
+            err.to_string(),
+            "invalid config sample.toml:\n  screen.fps must be at least 1"
+        );
+        assert_eq!(parse_sample("[screen]\nfps = 30").unwrap().screen.fps, 30);
+    }
+
+    #[test]
+    fn missing_files_mean_defaults() {
+        let dir = std::env::temp_dir().join(format!("tix-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("absent.toml");
+        assert!(load::<Sample>(&path).unwrap().is_none());
+
+        std::fs::write(&path, "[screen]\nfps = 0\n").unwrap();
+        assert!(load::<Sample>(&path).is_err());
+        // A directory is there but cannot be read as a file.
+        assert!(matches!(
+            load::<Sample>(&dir),
+            Err(ConfigError::Read { .. })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn addresses_need_a_usable_port() {
+        assert_eq!(address_problem("slave", "10.0.0.5:7332"), None);
+        assert_eq!(address_problem("slave", "[::1]:7332"), None);
+        for bad in [
+            "10.0.0.5",
+            "10.0.0.5:0",
+            "10.0.0.5:http",
+            ":7332",
+            "h:70000",
+        ] {
+            let problem = address_problem("slave", bad).unwrap();
+            assert!(problem.starts_with("slave = "), "{}", problem);
+        }
+    }
+
+    #[test]
+    fn atomic_writes_replace_the_whole_file() {
+        let dir = std::env::temp_dir().join(format!("tix-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        write_atomic(&path, "first version, rather long").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+        // No temporary file is left behind.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        // A failed write leaves nothing behind either.
+        assert!(write_atomic(&dir.join("missing").join("x"), "x").is_err());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
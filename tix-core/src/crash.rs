@@ -0,0 +1,207 @@
+//! Crash reporting for the long-running binaries.
+//!
+//! A slave runs as a service, where nobody reads stderr: a panic there
+//! used to leave no trace. [`install`] adds a panic hook that logs the
+//! panic through `tracing` under [`TARGET`] and appends a
+//! [`CrashRecord`] to a crash log, one JSON object per line:
+//!
+//! ```text
+//! {"ts_ms":1760600000000,"app":"tix-slave","version":"0.1.0","thread":"tokio-runtime-worker","message":"index out of bounds: the len is 3 but the index is 7","location":"tix-slave/src/search.rs:88:17","backtrace":["0: ..."]}
+//! ```
+//!
+//! The previous hook still runs afterwards, so a console sees the usual
+//! message too. A panic inside a pooled task is also caught by the
+//! [`TaskPool`](crate::TaskPool), which answers the request with
+//! [`TaskError::Panicked`](crate::TaskError::Panicked) and keeps the
+//! process serving; the hook only records it.
+
+use std::any::Any;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// `tracing` target of the panic reports.
+pub const TARGET: &str = "tix::crash";
+
+/// Backtrace lines kept in a record; the rest are dropped.
+pub const MAX_BACKTRACE_LINES: usize = 64;
+
+/// One panic, as written to the crash log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashRecord {
+    /// When it happened, in milliseconds since the Unix epoch.
+    pub ts_ms: u64,
+    /// Binary that panicked.
+    pub app: String,
+    /// Its version.
+    pub version: String,
+    /// Name of the panicking thread, `"<unnamed>"` without one.
+    pub thread: String,
+    /// The panic message.
+    pub message: String,
+    /// `file:line:column` of the panic, when known.
+    pub location: Option<String>,
+    /// The backtrace, one frame line per entry, at most
+    /// [`MAX_BACKTRACE_LINES`].
+    pub backtrace: Vec<String>,
+}
+
+impl CrashRecord {
+    /// Describe the panic `info` in `app` at `version`, with the current
+    /// thread and a fresh backtrace.
+    pub fn capture(app: &str, version: &str, info: &PanicHookInfo<'_>) -> Self {
+        let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+        Self {
+            ts_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+            app: app.to_string(),
+            version: version.to_string(),
+            thread: std::thread::current()
+                .name()
+                .unwrap_or("<unnamed>")
+                .to_string(),
+            message: panic_message(info.payload()),
+            location: info.location().map(ToString::to_string),
+            backtrace: truncate_backtrace(&backtrace),
+        }
+    }
+
+    /// Append the record as one JSON line to `path`, creating the file.
+    pub fn append(&self, path: &Path) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(self).map_err(std::io::Error::other)?;
+        line.push('\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(line.as_bytes())
+    }
+}
+
+/// Read back the records in crash log `path`, skipping lines that are
+/// not one (a record cut short by the process dying, say).
+pub fn read_log(path: &Path) -> std::io::Result<Vec<CrashRecord>> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// The text of a panic payload: what `panic!` was given when it was a
+/// string, a placeholder otherwise.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "panic with a non-string payload".to_string()
+    }
+}
+
+/// The first [`MAX_BACKTRACE_LINES`] non-empty lines of `backtrace`,
+/// trimmed, with a last line saying how many were dropped.
+fn truncate_backtrace(backtrace: &str) -> Vec<String> {
+    let lines: Vec<&str> = backtrace
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    let mut kept: Vec<String> = lines
+        .iter()
+        .take(MAX_BACKTRACE_LINES)
+        .map(|l| l.to_string())
+        .collect();
+    if lines.len() > MAX_BACKTRACE_LINES {
+        kept.push(format!("… {} more", lines.len() - MAX_BACKTRACE_LINES));
+    }
+    kept
+}
+
+/// Report every panic in this process: log it under [`TARGET`] and, with
+/// a `crash_log`, append a [`CrashRecord`] to it, then run the hook that
+/// was installed before.
+pub fn install(app: &'static str, version: &'static str, crash_log: Option<PathBuf>) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let record = CrashRecord::capture(app, version, info);
+        tracing::error!(
+            target: TARGET,
+            thread = %record.thread,
+            location = record.location.as_deref().unwrap_or("unknown"),
+            "panic: {}\n{}",
+            record.message,
+            record.backtrace.join("\n")
+        );
+        if let Some(path) = &crash_log
+            && let Err(e) = record.append(path)
+        {
+            tracing::error!(target: TARGET, "cannot write {}: {e}", path.display());
+        }
+        previous(info);
+    }));
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads_are_read_as_text() {
+        let caught = std::panic::catch_unwind(|| panic!("index {} out of range", 7)).unwrap_err();
+        assert_eq!(panic_message(caught.as_ref()), "index 7 out of range");
+        let caught = std::panic::catch_unwind(|| panic!("plain")).unwrap_err();
+        assert_eq!(panic_message(caught.as_ref()), "plain");
+        let caught = std::panic::catch_unwind(|| std::panic::panic_any(42u8)).unwrap_err();
+        assert_eq!(
+            panic_message(caught.as_ref()),
+            "panic with a non-string payload"
+        );
+    }
+
+    #[test]
+    fn long_backtraces_are_cut() {
+        let backtrace: String = (0..100).map(|i| format!("  {i}: frame\n\n")).collect();
+        let kept = truncate_backtrace(&backtrace);
+        assert_eq!(kept.len(), MAX_BACKTRACE_LINES + 1);
+        assert_eq!(kept[0], "0: frame");
+        assert_eq!(kept[MAX_BACKTRACE_LINES], "… 36 more");
+        assert_eq!(truncate_backtrace("0: main\n").len(), 1);
+    }
+
+    #[test]
+    fn records_append_and_read_back() {
+        let path = std::env::temp_dir().join(format!("tix-crash-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let record = CrashRecord {
+            ts_ms: 1_760_600_000_000,
+            app: "tix-slave".into(),
+            version: "0.1.0".into(),
+            thread: "tokio-runtime-worker".into(),
+            message: "called `Option::unwrap()` on a `None` value".into(),
+            location: Some("tix-slave/src/slave.rs:10:5".into()),
+            backtrace: vec!["0: main".into()],
+        };
+        record.append(&path).unwrap();
+        // A record cut short by the process dying is skipped.
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"ts_ms\":17\n")
+            .unwrap();
+        let second = CrashRecord {
+            message: "second".into(),
+            ..record.clone()
+        };
+        second.append(&path).unwrap();
+
+        assert_eq!(read_log(&path).unwrap(), [record, second]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -11,7 +11,7 @@ use thiserror::Error;
 pub enum TixError {
     // ── Protocol Errors ──────────────────────────────────────────
     /// Received bytes that do not start with a valid TIX magic sequence.
-    #[error("invalid magic bytes: expected TIX0 or TIX1")]
+    #[error("invalid magic bytes: expected TIX0, TIX1 or TIX2")]
     InvalidMagic,
 
     /// A field in the packet header could not be parsed.
@@ -74,10 +74,59 @@ pub enum TixError {
     #[error("invalid command: {0}")]
     InvalidCommand(String),
 
+    /// A command typed by the user is malformed or names something that
+    /// does not exist; the text is the explanation shown to them.
+    #[error("{0}")]
+    InvalidCommandSyntax(String),
+
+    /// The command needs a slave and none is connected.
+    #[error("no slave connected")]
+    NotConnected,
+
+    /// The operator's role does not allow the command; it was not sent.
+    #[error("{command} not permitted for role {role}")]
+    NotPermitted { command: String, role: String },
+
+    /// The connected slave said in its `Hello` that it lacks the feature
+    /// the command needs; it was not sent.
+    #[error("{command} not supported by connected slave (v{version})")]
+    NotSupported { command: String, version: String },
+
     /// File integrity check failed after transfer.
     #[error("file integrity check failed")]
     FileIntegrityFailed,
 
+    /// A download still did not match the sender's hash after every
+    /// repair round; `chunks` are the ones the last round brought in.
+    #[error("{path}: chunk(s) {chunks:?} still corrupt after {rounds} repair round(s)")]
+    RepairFailed {
+        path: String,
+        rounds: u32,
+        chunks: Vec<u64>,
+    },
+
+    // ── Capture Errors ──────────────────────────────────────────
+    // Only raised by `rdp`, but they carry text rather than the Windows
+    // error so the enum is the same with and without that feature.
+    /// The screen capture pipeline became invalid (display mode change,
+    /// desktop switch, GPU reset) and must be reinitialised.
+    #[error("capture lost: {0}")]
+    CaptureLost(String),
+
+    // ── Input Errors ────────────────────────────────────────────
+    /// Input was dropped without reaching `SendInput` because recent
+    /// injections kept failing; the text says why.
+    #[error("input blocked: {0}")]
+    InputBlocked(String),
+
+    // ── TLS Errors ──────────────────────────────────────────────
+    // Only raised by `tls`, but they carry text rather than the rustls
+    // error so the enum is the same with and without that feature.
+    /// The TLS setup could not be built: a certificate, key, CA bundle
+    /// or pin that is missing or unreadable.
+    #[error("tls: {0}")]
+    Tls(String),
+
     // ── Task Errors ─────────────────────────────────────────────
     /// A spawned task failed.
     #[error("task error: {0}")]
@@ -108,9 +157,22 @@ pub enum TaskError {
     #[error("task I/O error: {0}")]
     Io(#[from] std::io::Error),
 
+    /// A path the task needs stayed locked by another task for too long.
+    #[error("resource busy: {path} still locked after {waited:?}")]
+    ResourceBusy { path: String, waited: Duration },
+
     /// Generic task failure with a human-readable message.
     #[error("task failed: {0}")]
     Failed(String),
+
+    /// A task is already running under this request ID; the new one
+    /// was not started.
+    #[error("request {0} is already in flight")]
+    DuplicateRequest(u64),
+
+    /// The task's body panicked; the message is the panic's.
+    #[error("task panicked: {0}")]
+    Panicked(String),
 }
 
 // ── Convenient From implementations ──────────────────────────────
@@ -0,0 +1,265 @@
+//! Optional features a slave may or may not offer, exchanged in the
+//! `Hello` so a master can tell what is missing before it asks.
+//!
+//! A feature is a group of commands (or a shell backend) that a slave
+//! build only has on some platforms, or that its config turns off: the
+//! Windows service and event log APIs, the Recycle Bin, PowerShell, the
+//! screen stack. The slave probes for each at startup and lists the ones
+//! it has by name ([`HelloInfo::features`](crate::protocol::HelloInfo::features)).
+//! Names are strings so a build can add features without breaking older
+//! peers: a name this build does not know is ignored.
+//!
+//! ```
+//! use tix_core::Command;
+//! use tix_core::features::{Feature, FeatureSet};
+//!
+//! let set = FeatureSet::from_names(["services", "teleport"]);
+//! assert!(set.contains(Feature::Services));
+//! assert_eq!(set.missing_for(Command::ServiceList), None);
+//! assert_eq!(set.missing_for(Command::EventLogQuery), Some(Feature::EventLog));
+//! assert_eq!(set.missing_for(Command::ListDir), None);
+//! ```
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::message::Command;
+use crate::protocol::ShellKind;
+
+/// An optional feature of a slave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Feature {
+    /// Windows PowerShell as a shell backend.
+    PowerShell,
+    /// PowerShell 7+ (`pwsh`) as a shell backend.
+    Pwsh,
+    /// Listing and controlling Windows services.
+    Services,
+    /// Querying the Windows event log.
+    EventLog,
+    /// Reading the registry, and the startup entries kept there.
+    Registry,
+    /// Restoring files from the Recycle Bin.
+    Trash,
+    /// Screen capture and input injection.
+    Screen,
+    /// Raising the secure attention sequence (Ctrl+Alt+Del).
+    Sas,
+}
+
+impl Feature {
+    /// Every feature, in the order they are listed.
+    pub const ALL: [Feature; 8] = [
+        Feature::PowerShell,
+        Feature::Pwsh,
+        Feature::Services,
+        Feature::EventLog,
+        Feature::Registry,
+        Feature::Trash,
+        Feature::Screen,
+        Feature::Sas,
+    ];
+
+    /// The name sent on the wire.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Feature::PowerShell => "powershell",
+            Feature::Pwsh => "pwsh",
+            Feature::Services => "services",
+            Feature::EventLog => "eventlog",
+            Feature::Registry => "registry",
+            Feature::Trash => "trash",
+            Feature::Screen => "screen",
+            Feature::Sas => "sas",
+        }
+    }
+
+    /// The commands that need this feature. The shell backends have
+    /// none: any shell runs through `ShellExecute`.
+    pub fn commands(self) -> &'static [Command] {
+        match self {
+            Feature::PowerShell | Feature::Pwsh => &[],
+            Feature::Services => &[Command::ServiceList, Command::ServiceControl],
+            Feature::EventLog => &[Command::EventLogQuery],
+            Feature::Registry => &[Command::RegistryQuery, Command::StartupList],
+            Feature::Trash => &[Command::TrashRestore],
+            Feature::Screen => &[
+                Command::ScreenStart,
+                Command::ScreenMode,
+                Command::InputMouse,
+                Command::InputKeyboard,
+            ],
+            Feature::Sas => &[Command::SendSas],
+        }
+    }
+
+    /// The feature `cmd` needs, if any.
+    pub fn for_command(cmd: Command) -> Option<Feature> {
+        Feature::ALL
+            .into_iter()
+            .find(|feature| feature.commands().contains(&cmd))
+    }
+
+    /// The feature running a command in `shell` needs, if any.
+    pub fn for_shell(shell: &ShellKind) -> Option<Feature> {
+        match shell {
+            ShellKind::PowerShell => Some(Feature::PowerShell),
+            ShellKind::PwshCore => Some(Feature::Pwsh),
+            ShellKind::Cmd | ShellKind::Custom(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Feature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Feature::ALL
+            .into_iter()
+            .find(|feature| feature.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown feature `{}`", s))
+    }
+}
+
+/// The features a slave offers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureSet {
+    features: BTreeSet<Feature>,
+}
+
+impl FeatureSet {
+    /// The features `names` name. Names this build does not know, from a
+    /// newer peer, are skipped.
+    pub fn from_names<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            features: names
+                .into_iter()
+                .filter_map(|name| name.as_ref().parse().ok())
+                .collect(),
+        }
+    }
+
+    /// Every feature.
+    pub fn all() -> Self {
+        Feature::ALL.into_iter().collect()
+    }
+
+    /// The wire names, in [`Feature::ALL`] order.
+    pub fn names(&self) -> Vec<String> {
+        self.iter()
+            .map(|feature| feature.as_str().to_string())
+            .collect()
+    }
+
+    pub fn contains(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    pub fn insert(&mut self, feature: Feature) {
+        self.features.insert(feature);
+    }
+
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Feature> + '_ {
+        self.features.iter().copied()
+    }
+
+    /// The features both sets have.
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.features
+            .intersection(&other.features)
+            .copied()
+            .collect()
+    }
+
+    /// The feature `cmd` needs that this set lacks, if any.
+    pub fn missing_for(&self, cmd: Command) -> Option<Feature> {
+        Feature::for_command(cmd).filter(|feature| !self.contains(*feature))
+    }
+
+    /// The feature a command in `shell` needs that this set lacks, if
+    /// any.
+    pub fn missing_for_shell(&self, shell: &ShellKind) -> Option<Feature> {
+        Feature::for_shell(shell).filter(|feature| !self.contains(*feature))
+    }
+}
+
+impl FromIterator<Feature> for FeatureSet {
+    fn from_iter<I: IntoIterator<Item = Feature>>(iter: I) -> Self {
+        Self {
+            features: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl fmt::Display for FeatureSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("none");
+        }
+        f.write_str(&self.names().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip_and_unknown_ones_are_skipped() {
+        for feature in Feature::ALL {
+            assert_eq!(feature.as_str().parse(), Ok(feature));
+        }
+        let set = FeatureSet::from_names(["Trash", "sas", "holograms", ""]);
+        assert_eq!(set, [Feature::Trash, Feature::Sas].into_iter().collect());
+        assert_eq!(set.names(), ["trash", "sas"]);
+        assert_eq!(set.to_string(), "trash, sas");
+        assert_eq!(FeatureSet::default().to_string(), "none");
+        assert_eq!(
+            FeatureSet::from_names(FeatureSet::all().names()),
+            FeatureSet::all()
+        );
+    }
+
+    #[test]
+    fn commands_and_shells_name_what_they_need() {
+        let set = FeatureSet::from_names(["registry", "pwsh"]);
+        assert_eq!(set.missing_for(Command::StartupList), None);
+        assert_eq!(set.missing_for(Command::TrashRestore), Some(Feature::Trash));
+        assert_eq!(set.missing_for(Command::SendSas), Some(Feature::Sas));
+        assert_eq!(set.missing_for(Command::ScreenStop), None);
+        assert_eq!(set.missing_for(Command::ShellExecute), None);
+        assert_eq!(set.missing_for_shell(&ShellKind::PwshCore), None);
+        assert_eq!(
+            set.missing_for_shell(&ShellKind::PowerShell),
+            Some(Feature::PowerShell)
+        );
+        assert_eq!(set.missing_for_shell(&ShellKind::Cmd), None);
+        assert_eq!(
+            set.missing_for_shell(&ShellKind::Custom("bash".into())),
+            None
+        );
+        // No command is claimed by two features.
+        for cmd in Command::ALL {
+            let owners = Feature::ALL
+                .into_iter()
+                .filter(|feature| feature.commands().contains(&cmd))
+                .count();
+            assert!(owners <= 1, "{cmd} needs {owners} features");
+        }
+    }
+}
@@ -21,6 +21,12 @@ bitflags! {
         const ACK_REQUESTED = 0x0000_0000_0000_0008;
         /// This packet is a streaming chunk (shell output, file chunk).
         const STREAMING     = 0x0000_0000_0000_0010;
+        /// This response reports that the request failed; the payload
+        /// is a `TaskFailure` instead of the command's normal response.
+        const ERROR         = 0x0000_0000_0000_0020;
+        /// One piece of a payload too large for a single packet; the
+        /// payload starts with a fragment prefix (see `fragment`).
+        const FRAGMENT      = 0x0000_0000_0000_0040;
     }
 }
 
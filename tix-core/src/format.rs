@@ -0,0 +1,145 @@
+//! Human-readable sizes, durations and rates for the consoles and the
+//! viewer.
+//!
+//! Sizes use binary units with one decimal (`1.5 KiB`); exact byte
+//! counts are kept below 1 KiB. A value that would round up to 1024 of
+//! a unit is shown in the next one instead, so `1048575` reads
+//! `1.0 MiB` rather than `1024.0 KiB`.
+
+use std::time::Duration;
+
+const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Compact binary-prefixed byte count (`512 B`, `1.5 MiB`).
+pub fn format_bytes(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    // Compare the value as it will be printed, so rounding never shows
+    // `1024.0` of a unit.
+    while (value * 10.0).round() >= 10_240.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Short duration for tables and status lines: `350ms`, `4.2s`,
+/// `5m 02s`, `1h 03m`, `2d 04h`.
+pub fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if millis < 1000 {
+        return format!("{millis}ms");
+    }
+    // Rounded rather than truncated, so 59.96s becomes `1m 00s`.
+    let tenths = (millis + 50) / 100;
+    if tenths < 600 {
+        return format!("{}.{}s", tenths / 10, tenths % 10);
+    }
+    let secs = u64::try_from((millis + 500) / 1000).unwrap_or(u64::MAX);
+    if secs < 3600 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else if secs < 86_400 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else {
+        format!("{}d {:02}h", secs / 86_400, secs % 86_400 / 3600)
+    }
+}
+
+/// Throughput of `bytes` moved in `duration` (`1.5 MiB/s`); `-` when no
+/// time has passed and there is nothing to divide by.
+pub fn format_rate(bytes: u64, duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs <= 0.0 {
+        return "-".to_string();
+    }
+    // `as` saturates, so absurd rates still format.
+    format!("{}/s", format_bytes((bytes as f64 / secs) as u64))
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIB: u64 = 1024;
+    const MIB: u64 = KIB * 1024;
+    const GIB: u64 = MIB * 1024;
+    const TIB: u64 = GIB * 1024;
+    const PIB: u64 = TIB * 1024;
+    const EIB: u64 = PIB * 1024;
+
+    #[test]
+    fn bytes_below_a_kibibyte_are_exact() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1), "1 B");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+    }
+
+    #[test]
+    fn bytes_use_one_decimal_of_the_largest_unit() {
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(10 * KIB + 102), "10.1 KiB");
+        assert_eq!(format_bytes(MIB), "1.0 MiB");
+        assert_eq!(format_bytes(5 * MIB / 2), "2.5 MiB");
+        assert_eq!(format_bytes(GIB), "1.0 GiB");
+        assert_eq!(format_bytes(3 * TIB), "3.0 TiB");
+        assert_eq!(format_bytes(PIB), "1.0 PiB");
+        assert_eq!(format_bytes(7 * PIB + PIB / 2), "7.5 PiB");
+        assert_eq!(format_bytes(EIB), "1.0 EiB");
+    }
+
+    #[test]
+    fn bytes_just_under_a_boundary_roll_over() {
+        assert_eq!(format_bytes(MIB - 1), "1.0 MiB");
+        assert_eq!(format_bytes(GIB - 1), "1.0 GiB");
+        assert_eq!(format_bytes(TIB - 1), "1.0 TiB");
+        assert_eq!(format_bytes(PIB - 1), "1.0 PiB");
+        assert_eq!(format_bytes(EIB - 1), "1.0 EiB");
+        // Far enough below to stay in the smaller unit.
+        assert_eq!(format_bytes(1023 * KIB), "1023.0 KiB");
+        assert_eq!(format_bytes(MIB - 52), "1023.9 KiB");
+    }
+
+    #[test]
+    fn largest_values_do_not_overflow() {
+        assert_eq!(format_bytes(u64::MAX), "16.0 EiB");
+        assert_eq!(format_bytes(u64::MAX / 2), "8.0 EiB");
+    }
+
+    #[test]
+    fn durations_pick_a_readable_unit() {
+        assert_eq!(format_duration(Duration::ZERO), "0ms");
+        assert_eq!(format_duration(Duration::from_micros(999)), "0ms");
+        assert_eq!(format_duration(Duration::from_millis(350)), "350ms");
+        assert_eq!(format_duration(Duration::from_millis(999)), "999ms");
+        assert_eq!(format_duration(Duration::from_secs(1)), "1.0s");
+        assert_eq!(format_duration(Duration::from_millis(4200)), "4.2s");
+        assert_eq!(format_duration(Duration::from_millis(4249)), "4.2s");
+        assert_eq!(format_duration(Duration::from_millis(4250)), "4.3s");
+        assert_eq!(format_duration(Duration::from_millis(59_949)), "59.9s");
+        assert_eq!(format_duration(Duration::from_millis(59_950)), "1m 00s");
+        assert_eq!(format_duration(Duration::from_secs(302)), "5m 02s");
+        assert_eq!(format_duration(Duration::from_secs(3599)), "59m 59s");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h 00m");
+        assert_eq!(format_duration(Duration::from_secs(3780)), "1h 03m");
+        assert_eq!(format_duration(Duration::from_secs(86_399)), "23h 59m");
+        assert_eq!(format_duration(Duration::from_secs(187_200)), "2d 04h");
+        assert_eq!(format_duration(Duration::MAX), "213503982334601d 07h");
+    }
+
+    #[test]
+    fn rates_divide_by_elapsed_time() {
+        assert_eq!(format_rate(0, Duration::from_secs(1)), "0 B/s");
+        assert_eq!(format_rate(3 * MIB, Duration::from_secs(2)), "1.5 MiB/s");
+        assert_eq!(format_rate(512, Duration::from_millis(500)), "1.0 KiB/s");
+        assert_eq!(format_rate(100, Duration::from_secs(1000)), "0 B/s");
+        assert_eq!(format_rate(1024, Duration::ZERO), "-");
+        assert_eq!(format_rate(u64::MAX, Duration::from_nanos(1)), "16.0 EiB/s");
+    }
+}
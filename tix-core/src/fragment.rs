@@ -0,0 +1,455 @@
+//! Payloads larger than one packet.
+//!
+//! A packet carries at most [`MAX_PAYLOAD_SIZE`] bytes. A response that
+//! can grow past that (the listing of a huge directory) is cut by
+//! [`Packet::new_fragmented`] into `FRAGMENT`-flagged packets, and the
+//! receiver feeds them to a [`FragmentReassembler`] until the whole
+//! payload is back.
+//!
+//! Each fragment's payload starts with a 16-byte prefix, all little
+//! endian:
+//!
+//! ```text
+//! Offset  Size  Field
+//!   0      4    fragment_index (0-based)
+//!   4      4    total_fragments
+//!   8      8    total_size of the reassembled payload
+//! ```
+//!
+//! Fragments may arrive in any order; all of them share the request ID,
+//! command and message type of the payload they make up.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::error::TixError;
+use crate::flags::ProtocolFlags;
+use crate::message::{Command, MessageType};
+use crate::packet::{MAX_PAYLOAD_SIZE, Packet};
+
+/// Bytes of the prefix in front of every fragment.
+pub const FRAGMENT_PREFIX_SIZE: usize = 16;
+
+/// Payload bytes a single fragment carries.
+pub const FRAGMENT_DATA_SIZE: usize = MAX_PAYLOAD_SIZE - FRAGMENT_PREFIX_SIZE;
+
+/// Largest payload a reassembler rebuilds unless configured otherwise.
+pub const DEFAULT_MAX_REASSEMBLED_SIZE: usize = 16 * 1024 * 1024;
+
+/// How long a reassembly may go without a new fragment before
+/// [`FragmentReassembler::expire`] drops it, unless configured otherwise.
+pub const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The prefix of one fragment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FragmentHeader {
+    pub index: u32,
+    pub total: u32,
+    pub total_size: u64,
+}
+
+impl FragmentHeader {
+    fn to_bytes(self) -> [u8; FRAGMENT_PREFIX_SIZE] {
+        let mut out = [0u8; FRAGMENT_PREFIX_SIZE];
+        out[..4].copy_from_slice(&self.index.to_le_bytes());
+        out[4..8].copy_from_slice(&self.total.to_le_bytes());
+        out[8..].copy_from_slice(&self.total_size.to_le_bytes());
+        out
+    }
+
+    /// Split a fragment's payload into its prefix and data.
+    pub fn parse(payload: &[u8]) -> Result<(Self, &[u8]), TixError> {
+        if payload.len() < FRAGMENT_PREFIX_SIZE {
+            return Err(TixError::ProtocolViolation(
+                "fragment shorter than its prefix",
+            ));
+        }
+        let (prefix, data) = payload.split_at(FRAGMENT_PREFIX_SIZE);
+        let header = Self {
+            index: u32::from_le_bytes(prefix[..4].try_into().unwrap_or_default()),
+            total: u32::from_le_bytes(prefix[4..8].try_into().unwrap_or_default()),
+            total_size: u64::from_le_bytes(prefix[8..].try_into().unwrap_or_default()),
+        };
+        if header.total == 0 || header.index >= header.total {
+            return Err(TixError::ProtocolViolation("fragment index out of range"));
+        }
+        Ok((header, data))
+    }
+}
+
+impl Packet {
+    /// Build the response packets that carry `payload`. A payload that
+    /// fits one packet is sent as a plain response; a larger one as
+    /// `FRAGMENT` packets for a [`FragmentReassembler`].
+    pub fn new_fragmented(
+        request_id: u64,
+        command: Command,
+        payload: Vec<u8>,
+    ) -> Result<Vec<Packet>, TixError> {
+        if payload.len() <= MAX_PAYLOAD_SIZE {
+            return Ok(vec![Packet::new_response(request_id, command, payload)?]);
+        }
+        let total = u32::try_from(payload.len().div_ceil(FRAGMENT_DATA_SIZE)).map_err(|_| {
+            TixError::PayloadTooLarge {
+                size: payload.len(),
+                max: FRAGMENT_DATA_SIZE * u32::MAX as usize,
+            }
+        })?;
+        payload
+            .chunks(FRAGMENT_DATA_SIZE)
+            .zip(0..)
+            .map(|(data, index)| {
+                let header = FragmentHeader {
+                    index,
+                    total,
+                    total_size: payload.len() as u64,
+                };
+                let mut fragment = Vec::with_capacity(FRAGMENT_PREFIX_SIZE + data.len());
+                fragment.extend_from_slice(&header.to_bytes());
+                fragment.extend_from_slice(data);
+                Packet::new_response_with_flags(
+                    request_id,
+                    command,
+                    fragment,
+                    ProtocolFlags::FRAGMENT,
+                )
+            })
+            .collect()
+    }
+
+    /// Whether this packet is one fragment of a larger payload.
+    pub fn is_fragment(&self) -> bool {
+        self.flags().contains(ProtocolFlags::FRAGMENT)
+    }
+}
+
+/// A payload put back together from its fragments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reassembled {
+    pub request_id: u64,
+    pub command: Command,
+    pub message_type: MessageType,
+    pub payload: Vec<u8>,
+}
+
+/// Fragments received so far for one request.
+#[derive(Debug)]
+struct Partial {
+    command: Command,
+    message_type: MessageType,
+    total_size: usize,
+    fragments: Vec<Option<Vec<u8>>>,
+    received: usize,
+    bytes: usize,
+    last_seen: Instant,
+}
+
+/// Collects fragments per request ID and hands back whole payloads.
+#[derive(Debug)]
+pub struct FragmentReassembler {
+    max_size: usize,
+    timeout: Duration,
+    pending: HashMap<u64, Partial>,
+}
+
+impl Default for FragmentReassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FragmentReassembler {
+    pub fn new() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_REASSEMBLED_SIZE,
+            timeout: DEFAULT_REASSEMBLY_TIMEOUT,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Refuse payloads announced larger than `bytes`.
+    pub fn with_max_size(mut self, bytes: usize) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    /// Drop reassemblies that went `timeout` without a fragment.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Take in one `FRAGMENT` packet. Returns the payload once its last
+    /// fragment is in. A fragment that breaks the rules (too large,
+    /// inconsistent with the earlier ones) drops the whole reassembly
+    /// and is an error.
+    pub fn push(&mut self, packet: &Packet) -> Result<Option<Reassembled>, TixError> {
+        self.push_at(packet, Instant::now())
+    }
+
+    fn push_at(&mut self, packet: &Packet, now: Instant) -> Result<Option<Reassembled>, TixError> {
+        let request_id = packet.request_id();
+        let result = self.accept(packet, now);
+        if result.is_err() {
+            self.pending.remove(&request_id);
+        }
+        result
+    }
+
+    fn accept(&mut self, packet: &Packet, now: Instant) -> Result<Option<Reassembled>, TixError> {
+        let (header, data) = FragmentHeader::parse(packet.payload())?;
+        let total_size = usize::try_from(header.total_size).unwrap_or(usize::MAX);
+        if total_size > self.max_size {
+            return Err(TixError::PayloadTooLarge {
+                size: total_size,
+                max: self.max_size,
+            });
+        }
+        // The count is on the wire too; it must match the size before it
+        // sizes an allocation.
+        if header.total as usize != total_size.div_ceil(FRAGMENT_DATA_SIZE) {
+            return Err(TixError::ProtocolViolation(
+                "fragment count disagrees with the announced size",
+            ));
+        }
+        let request_id = packet.request_id();
+        let command = packet.command()?;
+        let partial = self.pending.entry(request_id).or_insert_with(|| Partial {
+            command,
+            message_type: packet.message_type(),
+            total_size,
+            fragments: vec![None; header.total as usize],
+            received: 0,
+            bytes: 0,
+            last_seen: now,
+        });
+        if partial.fragments.len() != header.total as usize || partial.total_size != total_size {
+            return Err(TixError::ProtocolViolation(
+                "fragment disagrees with the earlier ones",
+            ));
+        }
+        partial.last_seen = now;
+        let slot = &mut partial.fragments[header.index as usize];
+        if slot.is_some() {
+            // A repeat; the first copy stands.
+            return Ok(None);
+        }
+        partial.bytes += data.len();
+        if partial.bytes > partial.total_size {
+            return Err(TixError::ProtocolViolation(
+                "fragments exceed their announced size",
+            ));
+        }
+        *slot = Some(data.to_vec());
+        partial.received += 1;
+        if partial.received < partial.fragments.len() {
+            return Ok(None);
+        }
+
+        let Some(partial) = self.pending.remove(&request_id) else {
+            return Ok(None);
+        };
+        if partial.bytes != partial.total_size {
+            return Err(TixError::ProtocolViolation(
+                "fragments fall short of their announced size",
+            ));
+        }
+        let mut payload = Vec::with_capacity(partial.total_size);
+        for fragment in partial.fragments.into_iter().flatten() {
+            payload.extend_from_slice(&fragment);
+        }
+        Ok(Some(Reassembled {
+            request_id,
+            command: partial.command,
+            message_type: partial.message_type,
+            payload,
+        }))
+    }
+
+    /// Forget the fragments of `request_id`, e.g. once it was cancelled.
+    pub fn discard(&mut self, request_id: u64) {
+        self.pending.remove(&request_id);
+    }
+
+    /// Drop reassemblies that have waited too long for their next
+    /// fragment. Returns their request IDs.
+    pub fn expire(&mut self) -> Vec<u64> {
+        self.expire_at(Instant::now())
+    }
+
+    fn expire_at(&mut self, now: Instant) -> Vec<u64> {
+        let timeout = self.timeout;
+        let mut expired: Vec<u64> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| now.saturating_duration_since(p.last_seen) >= timeout)
+            .map(|(id, _)| *id)
+            .collect();
+        expired.sort_unstable();
+        for id in &expired {
+            self.pending.remove(id);
+        }
+        expired
+    }
+
+    /// Reassemblies still waiting for fragments.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn small_payloads_stay_one_plain_packet() {
+        let packets = Packet::new_fragmented(3, Command::ListDir, vec![1; 100]).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert!(!packets[0].is_fragment());
+        assert_eq!(packets[0].payload(), &[1; 100][..]);
+    }
+
+    #[test]
+    fn five_megabytes_round_trip() {
+        let payload = sample(5 * 1024 * 1024);
+        let packets = Packet::new_fragmented(9, Command::ListDir, payload.clone()).unwrap();
+        assert_eq!(packets.len(), payload.len().div_ceil(FRAGMENT_DATA_SIZE));
+        assert!(packets.iter().all(|p| p.is_fragment()));
+        assert!(
+            packets
+                .iter()
+                .all(|p| p.payload().len() <= MAX_PAYLOAD_SIZE)
+        );
+
+        let mut reassembler = FragmentReassembler::new();
+        let (last, rest) = packets.split_last().unwrap();
+        for packet in rest {
+            // Through the wire format, checksums and all.
+            let packet = Packet::from_bytes(&packet.to_bytes().unwrap()).unwrap();
+            assert_eq!(reassembler.push(&packet).unwrap(), None);
+        }
+        let whole = reassembler.push(last).unwrap().unwrap();
+        assert_eq!(whole.request_id, 9);
+        assert_eq!(whole.command, Command::ListDir);
+        assert_eq!(whole.message_type, MessageType::Response);
+        assert_eq!(whole.payload, payload);
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn fragments_may_arrive_out_of_order_and_interleaved() {
+        let a = sample(3 * FRAGMENT_DATA_SIZE + 17);
+        let b = vec![7u8; 2 * FRAGMENT_DATA_SIZE];
+        let mut first = Packet::new_fragmented(1, Command::ListDir, a.clone()).unwrap();
+        let mut second = Packet::new_fragmented(2, Command::ListDir, b.clone()).unwrap();
+        first.reverse();
+        second.swap(0, 1);
+
+        let mut reassembler = FragmentReassembler::new();
+        let mut done = Vec::new();
+        let mut order = vec![&first[0], &second[0], &first[2], &first[0]];
+        order.extend([&first[1], &second[1], &first[3]]);
+        for packet in order {
+            if let Some(whole) = reassembler.push(packet).unwrap() {
+                done.push(whole);
+            }
+        }
+        assert_eq!(done.len(), 2);
+        assert_eq!((done[0].request_id, &done[0].payload), (2, &b));
+        assert_eq!((done[1].request_id, &done[1].payload), (1, &a));
+    }
+
+    #[test]
+    fn payloads_over_the_cap_are_refused() {
+        let packets = Packet::new_fragmented(4, Command::ListDir, sample(1024 * 1024)).unwrap();
+        let mut reassembler = FragmentReassembler::new().with_max_size(512 * 1024);
+        let err = reassembler.push(&packets[0]).unwrap_err();
+        assert!(matches!(
+            err,
+            TixError::PayloadTooLarge {
+                size: 1_048_576,
+                max: 524_288
+            }
+        ));
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn inconsistent_fragments_drop_the_reassembly() {
+        let mut reassembler = FragmentReassembler::new();
+        let a = Packet::new_fragmented(5, Command::ListDir, sample(2 * MAX_PAYLOAD_SIZE)).unwrap();
+        let b = Packet::new_fragmented(5, Command::ListDir, sample(4 * MAX_PAYLOAD_SIZE)).unwrap();
+        assert_eq!(reassembler.push(&a[0]).unwrap(), None);
+        assert!(reassembler.push(&b[1]).is_err());
+        assert_eq!(reassembler.pending(), 0);
+
+        let short = Packet::new_response_with_flags(
+            6,
+            Command::ListDir,
+            vec![0; 8],
+            ProtocolFlags::FRAGMENT,
+        )
+        .unwrap();
+        assert!(reassembler.push(&short).is_err());
+    }
+
+    #[test]
+    fn fragment_counts_must_match_the_announced_size() {
+        let header = FragmentHeader {
+            index: 0,
+            total: u32::MAX,
+            total_size: 1024,
+        };
+        let mut payload = header.to_bytes().to_vec();
+        payload.extend_from_slice(&[0; 1024]);
+        let packet =
+            Packet::new_response_with_flags(9, Command::ListDir, payload, ProtocolFlags::FRAGMENT)
+                .unwrap();
+
+        let mut reassembler = FragmentReassembler::new();
+        let err = reassembler.push(&packet).unwrap_err();
+        assert!(matches!(err, TixError::ProtocolViolation(_)));
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn stale_reassemblies_expire() {
+        let mut reassembler = FragmentReassembler::new().with_timeout(Duration::from_secs(10));
+        let t0 = Instant::now();
+        let slow =
+            Packet::new_fragmented(7, Command::ListDir, sample(3 * MAX_PAYLOAD_SIZE)).unwrap();
+        let busy =
+            Packet::new_fragmented(8, Command::ListDir, sample(3 * MAX_PAYLOAD_SIZE)).unwrap();
+        reassembler.push_at(&slow[0], t0).unwrap();
+        reassembler.push_at(&busy[0], t0).unwrap();
+        reassembler
+            .push_at(&busy[1], t0 + Duration::from_secs(6))
+            .unwrap();
+
+        assert!(
+            reassembler
+                .expire_at(t0 + Duration::from_secs(9))
+                .is_empty()
+        );
+        assert_eq!(reassembler.expire_at(t0 + Duration::from_secs(10)), vec![7]);
+        assert_eq!(reassembler.pending(), 1);
+        assert_eq!(reassembler.expire_at(t0 + Duration::from_secs(16)), vec![8]);
+
+        // A late fragment of an expired payload starts over, and never
+        // completes on its own.
+        assert_eq!(
+            reassembler
+                .push_at(&slow[1], t0 + Duration::from_secs(20))
+                .unwrap(),
+            None
+        );
+        assert_eq!(reassembler.pending(), 1);
+    }
+}
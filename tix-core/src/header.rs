@@ -1,54 +1,182 @@
-//! TIX Packet Header — 64 bytes, little-endian, C-compatible layout.
+//! TIX Packet Header, little-endian, in one of two wire versions.
+//!
+//! TIX1, spoken by every peer, is 64 bytes. The command shares a field
+//! with nothing else, and bit 63 of `flags` marks responses:
 //!
 //! ```text
 //! Offset  Size   Field
 //! ──────  ─────  ──────────────
-//!   0       4    magic           b"TIX1"
+//!   0       4    magic           b"TIX1" (b"TIX0" is read as TIX1)
 //!   4      32    checksum        Blake3 hash of payload
-//!  36       4    message_type    Command | Response
-//!  40       8    flags           ProtocolFlags bitmask
+//!  36       4    command         Command discriminant
+//!  40       8    flags           ProtocolFlags bitmask, bit 63 = response
 //!  48       8    request_id      Unique per-command identifier
 //!  56       8    payload_length  Byte count of following payload
 //! ──────  ─────  ──────────────
 //! Total:  64 bytes
 //! ```
+//!
+//! TIX2 is 80 bytes, with an explicit version byte, the message type in
+//! a field of its own and room for later additions:
+//!
+//! ```text
+//! Offset  Size   Field
+//! ──────  ─────  ──────────────
+//!   0       4    magic           b"TIX2"
+//!   4       1    version         2
+//!   5       1    message_type    1 = Command, 2 = Response
+//!   6       2    reserved
+//!   8       4    command         Command discriminant
+//!  12       4    flags           ProtocolFlags bitmask
+//!  16       8    request_id      Unique per-command identifier
+//!  24       8    payload_length  Byte count of following payload
+//!  32      32    checksum        Blake3 hash of payload
+//!  64       4    session         Master's session nonce, 0 = none
+//!  68      12    reserved        Sequence numbers, encryption metadata
+//! ──────  ─────  ──────────────
+//! Total:  80 bytes
+//! ```
+//!
+//! Reserved bytes are sent as zero and ignored on receipt. TIX1 has no
+//! room for the session: it reads as 0 and is dropped when written (see
+//! [`RequestIds`](crate::RequestIds)).
+//!
+//! A [`PacketHeader`] holds the fields, not the bytes: it is read from
+//! either version and written in whichever [`WireVersion`] it carries,
+//! and the accessors give the same answers for both. A connection writes
+//! TIX1 until both ends have said in their `Hello` that they speak TIX2
+//! (see [`HelloInfo`](crate::protocol::HelloInfo)); peers that never send
+//! `Hello` keep TIX1.
 
 use crate::error::TixError;
 use crate::flags::ProtocolFlags;
 use crate::message::{Command, MessageType};
 
-/// Fixed size of the on-wire header.
+/// Size of a TIX1 header, the smallest a header can be.
 pub const HEADER_SIZE: usize = 64;
 
-/// Type alias for the exact byte array that can hold one header.
+/// Size of a TIX2 header.
+pub const HEADER_SIZE_V2: usize = 80;
+
+/// Size of the largest header of any supported version.
+pub const MAX_HEADER_SIZE: usize = HEADER_SIZE_V2;
+
+/// Type alias for the exact byte array that can hold one TIX1 header.
 pub type HeaderBytes = [u8; HEADER_SIZE];
 
-/// Protocol magic for the current version.
+/// Protocol magic of TIX1.
 pub const MAGIC: [u8; 4] = *b"TIX1";
 
-/// TIX Protocol Header — 64 bytes.
-///
-/// All multi-byte fields are stored **little-endian** on the wire.
+/// Protocol magic of TIX2.
+pub const MAGIC_V2: [u8; 4] = *b"TIX2";
+
+/// Bit of TIX1 `flags` that marks a response.
+const RESPONSE_BIT: u64 = 1 << 63;
+
+// Every flag must survive the 32-bit TIX2 field.
+const _: () = assert!(ProtocolFlags::all().bits() <= u32::MAX as u64);
+
+// ── WireVersion ──────────────────────────────────────────────────
+
+/// Header layout a packet is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WireVersion {
+    /// The original 64-byte header.
+    #[default]
+    V1 = 1,
+    /// The 80-byte header with an explicit version and message type.
+    V2 = 2,
+}
+
+impl WireVersion {
+    /// Newest version this build reads and writes.
+    pub const LATEST: Self = Self::V2;
+
+    /// The version number carried in `Hello` and the TIX2 version byte.
+    pub fn number(self) -> u8 {
+        self as u8
+    }
+
+    /// Version for `number`, or [`TixError::UnsupportedVersion`].
+    pub fn from_number(number: u8) -> Result<Self, TixError> {
+        match number {
+            1 => Ok(Self::V1),
+            2 => Ok(Self::V2),
+            n => Err(TixError::UnsupportedVersion(u32::from(n))),
+        }
+    }
+
+    /// Highest version both ends speak, given the peer's latest.
+    /// Anything newer than ours settles on ours.
+    pub fn negotiate(peer_latest: u8) -> Self {
+        Self::from_number(peer_latest.min(Self::LATEST.number())).unwrap_or(Self::V1)
+    }
+
+    /// Version announced by the first four bytes of a header. `TIX`
+    /// followed by a digit this build does not know is an
+    /// [`UnsupportedVersion`](TixError::UnsupportedVersion); anything
+    /// else is not TIX at all.
+    pub fn from_magic(magic: &[u8]) -> Result<Self, TixError> {
+        match magic {
+            b"TIX0" | b"TIX1" => Ok(Self::V1),
+            b"TIX2" => Ok(Self::V2),
+            [b'T', b'I', b'X', digit @ b'0'..=b'9'] => {
+                Err(TixError::UnsupportedVersion(u32::from(digit - b'0')))
+            }
+            _ => Err(TixError::InvalidMagic),
+        }
+    }
+
+    /// Magic written at the start of the header.
+    pub fn magic(self) -> [u8; 4] {
+        match self {
+            Self::V1 => MAGIC,
+            Self::V2 => MAGIC_V2,
+        }
+    }
+
+    /// Size of a header in this version.
+    pub fn header_size(self) -> usize {
+        match self {
+            Self::V1 => HEADER_SIZE,
+            Self::V2 => HEADER_SIZE_V2,
+        }
+    }
+}
+
+impl std::fmt::Display for WireVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TIX{}", self.number())
+    }
+}
+
+// ── PacketHeader ─────────────────────────────────────────────────
+
+/// TIX protocol header, independent of its wire version.
 #[derive(Clone)]
 pub struct PacketHeader {
-    /// Magic bytes identifying the protocol version.
-    magic: [u8; 4],
+    /// Layout used by [`to_bytes`](Self::to_bytes).
+    version: WireVersion,
     /// Blake3 hash of the payload (full 32 bytes).
     checksum: [u8; 32],
     /// Whether this is a Command or Response.
-    message_type: u32,
+    message_type: MessageType,
+    /// Raw command discriminant, kept even when unknown.
+    command: u32,
     /// Protocol flags bitmask.
-    flags: u64,
+    flags: ProtocolFlags,
     /// Unique identifier tying a command to its response.
     request_id: u64,
     /// Length of the payload that follows this header.
     payload_length: u64,
+    /// Session nonce, TIX2 only; 0 when there is none.
+    session: u32,
 }
 
 impl PacketHeader {
     // ── Construction ─────────────────────────────────────────────
 
-    /// Create a new header with the given fields.
+    /// Create a TIX1 header with the given fields.
     ///
     /// The checksum should be set separately after computing the
     /// Blake3 hash of the payload. It defaults to all zeros.
@@ -59,90 +187,15 @@ impl PacketHeader {
         request_id: u64,
         payload_length: u64,
     ) -> Self {
-        // We store the command in the message_type field's upper bits?
-        // No — keep it simple: message_type is the MessageType discriminant.
-        // The command is encoded in the request semantic. However, looking at
-        // the old code, command_id was a separate field. The PLAN.md header
-        // spec only has message_type (u32), not a separate command_id.
-        //
-        // Re-reading PLAN.md header spec carefully:
-        //   magic(4) + checksum(32) + message_type(u32) + flags(u64)
-        //   + request_id(u64) + payload_length(u64) + _reserved(8) = 64+8?
-        //
-        // Actually our layout has exactly 64 bytes without reserved:
-        //   4 + 32 + 4 + 8 + 8 + 8 = 64 ✓
-        //
-        // But we need a command field! Let's repurpose message_type
-        // to carry the command discriminant, and use the high bit to
-        // distinguish commands from responses. Or better: keep the old
-        // approach with a command_id field and shrink something else.
-        //
-        // Let me adjust: use the full 64 bytes more practically:
-        //   magic(4) + checksum(32) + message_type(4) + command(4)
-        //   + flags(8) + request_id(8) + payload_length(8) = 68 ... too much.
-        //
-        // Simplest backward-compatible fix: encode the command as a u32
-        // (truncated from u64). Redefine layout:
-        //   magic(4) + checksum(32) + message_type(u16) + command(u16)
-        //    + flags(u64) + request_id(u64) + payload_length(u64) = 4+32+2+2+8+8+8 = 64 ✓
-        //
-        // But that limits command to u16 (65535 values — more than enough).
-        // Actually the old code had command_id as u64 in a 44-byte header.
-        // Let's keep it expandable. Real layout:
-        //
-        //   magic:          4
-        //   checksum:      32
-        //   message_type:   4   (u32: Command=1, Response=2)
-        //   command_id:     4   (u32: the Command discriminant)
-        //   flags:          8   (u64)
-        //   request_id:     8   (u64)
-        //   payload_length: 8   (u64)
-        //   ─────────────────
-        //   Total:         68   ← 4 bytes over budget
-        //
-        // To keep 64: drop 4 reserved bytes or shrink something.
-        // Option: make flags u32 → saves 4 → total 64.
-        //   magic(4)+checksum(32)+msg_type(4)+cmd(4)+flags(4)+req_id(8)+len(8) = 64 ✓
-        //
-        // But PLAN.md says flags is u64. Let me just make the header 68 bytes.
-        // Actually, let me re-read the AGENTS.md spec more carefully:
-        //   magic: [u8; 4]       = 4
-        //   checksum: [u8; 32]   = 32
-        //   message_type: u32    = 4
-        //   flags: u64           = 8
-        //   request_id: u64      = 8
-        //   payload_length: u64  = 8
-        //                        = 64
-        //
-        // There's no separate command field! The AGENTS.md uses message_type
-        // to hold "Command or Response" but the old code had a separate
-        // command_id. The PLAN.md command table uses 0x0001-style IDs.
-        //
-        // Resolution: Combine message_type + command into a single u32:
-        //   High 16 bits = MessageType (0x0001=Cmd, 0x0002=Resp)
-        //   Low 16 bits  = Command discriminant
-        // This way: message_type u32 carries both.
-        //
-        // Actually simpler: just keep message_type for the command enum value
-        // and add a separate bit to flags for "is_response".
-        //
-        // SIMPLEST: flags already has 64 bits. Use bit 63 as "is_response".
-        // message_type field == command discriminant (u32).
-        //
-        // Let's go with that approach for cleanliness.
-        let mut effective_flags = flags;
-        if message_type == MessageType::Response {
-            // Use the highest bit of flags to signal "this is a response"
-            effective_flags |= ProtocolFlags::from_bits_retain(1 << 63);
-        }
-
         Self {
-            magic: MAGIC,
+            version: WireVersion::V1,
             checksum: [0u8; 32],
-            message_type: command as u32,
-            flags: effective_flags.bits(),
+            message_type,
+            command: command as u32,
+            flags,
             request_id,
             payload_length,
+            session: 0,
         }
     }
 
@@ -151,8 +204,28 @@ impl PacketHeader {
         self.checksum = checksum;
     }
 
+    /// Set the session nonce. Only TIX2 carries it.
+    pub fn set_session(&mut self, session: u32) {
+        self.session = session;
+    }
+
+    /// Write this header in `version` from now on.
+    pub fn set_version(&mut self, version: WireVersion) {
+        self.version = version;
+    }
+
     // ── Accessors ────────────────────────────────────────────────
 
+    /// Wire version the header was read in or will be written in.
+    pub fn version(&self) -> WireVersion {
+        self.version
+    }
+
+    /// Size of the header on the wire.
+    pub fn size(&self) -> usize {
+        self.version.header_size()
+    }
+
     /// Returns the 32-byte Blake3 checksum.
     pub fn checksum(&self) -> &[u8; 32] {
         &self.checksum
@@ -160,21 +233,17 @@ impl PacketHeader {
 
     /// Returns the command encoded in this header.
     pub fn command(&self) -> Result<Command, TixError> {
-        Command::try_from(self.message_type as u64)
+        Command::try_from(u64::from(self.command))
     }
 
     /// Returns whether this is a Command or Response.
     pub fn message_type(&self) -> MessageType {
-        if self.flags & (1 << 63) != 0 {
-            MessageType::Response
-        } else {
-            MessageType::Command
-        }
+        self.message_type
     }
 
-    /// Returns the protocol flags (without the internal response bit).
+    /// Returns the protocol flags.
     pub fn flags(&self) -> ProtocolFlags {
-        ProtocolFlags::from(self.flags & !(1 << 63))
+        self.flags
     }
 
     /// Returns the request ID used to correlate responses.
@@ -187,83 +256,126 @@ impl PacketHeader {
         self.payload_length
     }
 
+    /// Returns the session nonce, 0 when the header has none.
+    pub fn session(&self) -> u32 {
+        self.session
+    }
+
     // ── Serialization ────────────────────────────────────────────
 
-    /// Serialize the header to exactly [`HEADER_SIZE`] bytes (little-endian).
-    pub fn to_bytes(&self) -> HeaderBytes {
-        let mut buf = [0u8; HEADER_SIZE];
-        buf[0..4].copy_from_slice(&self.magic);
-        buf[4..36].copy_from_slice(&self.checksum);
-        buf[36..40].copy_from_slice(&self.message_type.to_le_bytes());
-        buf[40..48].copy_from_slice(&self.flags.to_le_bytes());
-        buf[48..56].copy_from_slice(&self.request_id.to_le_bytes());
-        buf[56..64].copy_from_slice(&self.payload_length.to_le_bytes());
+    /// Serialize the header in its wire version: [`HEADER_SIZE`] bytes
+    /// for TIX1, [`HEADER_SIZE_V2`] for TIX2.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; self.size()];
+        match self.version {
+            WireVersion::V1 => {
+                let mut flags = self.flags.bits();
+                if self.message_type == MessageType::Response {
+                    flags |= RESPONSE_BIT;
+                }
+                buf[0..4].copy_from_slice(&MAGIC);
+                buf[4..36].copy_from_slice(&self.checksum);
+                buf[36..40].copy_from_slice(&self.command.to_le_bytes());
+                buf[40..48].copy_from_slice(&flags.to_le_bytes());
+                buf[48..56].copy_from_slice(&self.request_id.to_le_bytes());
+                buf[56..64].copy_from_slice(&self.payload_length.to_le_bytes());
+            }
+            WireVersion::V2 => {
+                buf[0..4].copy_from_slice(&MAGIC_V2);
+                buf[4] = WireVersion::V2.number();
+                buf[5] = self.message_type as u8;
+                buf[8..12].copy_from_slice(&self.command.to_le_bytes());
+                buf[12..16].copy_from_slice(&(self.flags.bits() as u32).to_le_bytes());
+                buf[16..24].copy_from_slice(&self.request_id.to_le_bytes());
+                buf[24..32].copy_from_slice(&self.payload_length.to_le_bytes());
+                buf[32..64].copy_from_slice(&self.checksum);
+                buf[64..68].copy_from_slice(&self.session.to_le_bytes());
+            }
+        }
         buf
     }
 
-    /// Deserialize a header from exactly [`HEADER_SIZE`] bytes.
+    /// Deserialize a header of either version from the start of `bytes`,
+    /// which must hold at least the whole header.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
-        if bytes.len() < HEADER_SIZE {
+        if bytes.len() < 4 {
             return Err(TixError::InvalidHeader("buffer too short for header"));
         }
-
-        let magic: [u8; 4] = bytes[0..4]
-            .try_into()
-            .map_err(|_| TixError::InvalidHeader("magic slice"))?;
-
-        // Accept both TIX0 (legacy) and TIX1 (current)
-        if &magic != b"TIX0" && &magic != b"TIX1" {
-            return Err(TixError::InvalidMagic);
+        let version = WireVersion::from_magic(&bytes[0..4])?;
+        if bytes.len() < version.header_size() {
+            return Err(TixError::InvalidHeader("buffer too short for header"));
         }
+        match version {
+            WireVersion::V1 => Self::from_v1(bytes),
+            WireVersion::V2 => Self::from_v2(bytes),
+        }
+    }
 
-        let checksum: [u8; 32] = bytes[4..36]
-            .try_into()
-            .map_err(|_| TixError::InvalidHeader("checksum slice"))?;
-
-        let message_type = u32::from_le_bytes(
-            bytes[36..40]
-                .try_into()
-                .map_err(|_| TixError::InvalidHeader("message_type slice"))?,
-        );
-
-        let flags = u64::from_le_bytes(
-            bytes[40..48]
-                .try_into()
-                .map_err(|_| TixError::InvalidHeader("flags slice"))?,
-        );
-
-        let request_id = u64::from_le_bytes(
-            bytes[48..56]
-                .try_into()
-                .map_err(|_| TixError::InvalidHeader("request_id slice"))?,
-        );
-
-        let payload_length = u64::from_le_bytes(
-            bytes[56..64]
-                .try_into()
-                .map_err(|_| TixError::InvalidHeader("payload_length slice"))?,
-        );
+    fn from_v1(bytes: &[u8]) -> Result<Self, TixError> {
+        let flags = read_u64(bytes, 40, "flags slice")?;
+        Ok(Self {
+            version: WireVersion::V1,
+            checksum: read_checksum(bytes, 4)?,
+            message_type: if flags & RESPONSE_BIT != 0 {
+                MessageType::Response
+            } else {
+                MessageType::Command
+            },
+            command: read_u32(bytes, 36, "command slice")?,
+            flags: ProtocolFlags::from(flags & !RESPONSE_BIT),
+            request_id: read_u64(bytes, 48, "request_id slice")?,
+            payload_length: read_u64(bytes, 56, "payload_length slice")?,
+            session: 0,
+        })
+    }
 
+    fn from_v2(bytes: &[u8]) -> Result<Self, TixError> {
+        if bytes[4] != WireVersion::V2.number() {
+            return Err(TixError::UnsupportedVersion(u32::from(bytes[4])));
+        }
         Ok(Self {
-            magic,
-            checksum,
-            message_type,
-            flags,
-            request_id,
-            payload_length,
+            version: WireVersion::V2,
+            checksum: read_checksum(bytes, 32)?,
+            message_type: MessageType::try_from(u32::from(bytes[5]))?,
+            command: read_u32(bytes, 8, "command slice")?,
+            flags: ProtocolFlags::from(u64::from(read_u32(bytes, 12, "flags slice")?)),
+            request_id: read_u64(bytes, 16, "request_id slice")?,
+            payload_length: read_u64(bytes, 24, "payload_length slice")?,
+            session: read_u32(bytes, 64, "session slice")?,
         })
     }
 }
 
+fn read_u32(bytes: &[u8], at: usize, what: &'static str) -> Result<u32, TixError> {
+    bytes[at..at + 4]
+        .try_into()
+        .map(u32::from_le_bytes)
+        .map_err(|_| TixError::InvalidHeader(what))
+}
+
+fn read_u64(bytes: &[u8], at: usize, what: &'static str) -> Result<u64, TixError> {
+    bytes[at..at + 8]
+        .try_into()
+        .map(u64::from_le_bytes)
+        .map_err(|_| TixError::InvalidHeader(what))
+}
+
+fn read_checksum(bytes: &[u8], at: usize) -> Result<[u8; 32], TixError> {
+    bytes[at..at + 32]
+        .try_into()
+        .map_err(|_| TixError::InvalidHeader("checksum slice"))
+}
+
 impl std::fmt::Debug for PacketHeader {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("PacketHeader")
-            .field("magic", &String::from_utf8_lossy(&self.magic))
+            .field("version", &self.version)
             .field("message_type", &self.message_type())
             .field("command", &self.command())
             .field("flags", &self.flags())
             .field("request_id", &self.request_id)
             .field("payload_length", &self.payload_length)
+            .field("session", &self.session)
             .finish()
     }
 }
@@ -323,5 +435,136 @@ mod tests {
     fn too_short_rejected() {
         let bytes = [0u8; 10];
         assert!(PacketHeader::from_bytes(&bytes).is_err());
+
+        let mut v2 = sample(MessageType::Command, WireVersion::V2).to_bytes();
+        v2.truncate(HEADER_SIZE);
+        assert!(PacketHeader::from_bytes(&v2).is_err());
+    }
+
+    fn sample(message_type: MessageType, version: WireVersion) -> PacketHeader {
+        let mut header = PacketHeader::new(
+            message_type,
+            Command::FileWrite,
+            ProtocolFlags::STREAMING | ProtocolFlags::FINAL_FRAGMENT,
+            0xDEAD_BEEF_0000_0001,
+            4096,
+        );
+        header.set_checksum([0xA5; 32]);
+        header.set_version(version);
+        header
+    }
+
+    fn assert_same_fields(a: &PacketHeader, b: &PacketHeader) {
+        assert_eq!(a.command().unwrap(), b.command().unwrap());
+        assert_eq!(a.message_type(), b.message_type());
+        assert_eq!(a.flags(), b.flags());
+        assert_eq!(a.request_id(), b.request_id());
+        assert_eq!(a.payload_length(), b.payload_length());
+        assert_eq!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn both_versions_carry_the_same_fields() {
+        for message_type in [MessageType::Command, MessageType::Response] {
+            let v1 = sample(message_type, WireVersion::V1);
+            let v2 = sample(message_type, WireVersion::V2);
+            let v1_bytes = v1.to_bytes();
+            let v2_bytes = v2.to_bytes();
+            assert_eq!(v1_bytes.len(), HEADER_SIZE);
+            assert_eq!(v2_bytes.len(), HEADER_SIZE_V2);
+            assert_eq!(&v2_bytes[0..4], b"TIX2");
+            assert_eq!(v2_bytes[4], 2);
+
+            let from_v1 = PacketHeader::from_bytes(&v1_bytes).unwrap();
+            let from_v2 = PacketHeader::from_bytes(&v2_bytes).unwrap();
+            assert_eq!(from_v1.version(), WireVersion::V1);
+            assert_eq!(from_v2.version(), WireVersion::V2);
+            assert_same_fields(&from_v1, &v1);
+            assert_same_fields(&from_v2, &v1);
+
+            // Re-encoding a decoded header in the other version is lossless.
+            let mut converted = from_v1.clone();
+            converted.set_version(WireVersion::V2);
+            assert_eq!(converted.to_bytes(), v2_bytes);
+            let mut converted = from_v2;
+            converted.set_version(WireVersion::V1);
+            assert_eq!(converted.to_bytes(), v1_bytes);
+        }
+    }
+
+    #[test]
+    fn legacy_tix0_reads_as_tix1() {
+        let mut bytes = sample(MessageType::Response, WireVersion::V1).to_bytes();
+        bytes[0..4].copy_from_slice(b"TIX0");
+        let header = PacketHeader::from_bytes(&bytes).unwrap();
+        assert_eq!(header.version(), WireVersion::V1);
+        assert_eq!(header.message_type(), MessageType::Response);
+    }
+
+    #[test]
+    fn v2_ignores_reserved_bytes_and_keeps_unknown_commands() {
+        let mut bytes = sample(MessageType::Command, WireVersion::V2).to_bytes();
+        bytes[6] = 0xFF;
+        bytes[70] = 0xFF;
+        bytes[8..12].copy_from_slice(&0x7777u32.to_le_bytes());
+        let header = PacketHeader::from_bytes(&bytes).unwrap();
+        assert!(matches!(
+            header.command(),
+            Err(TixError::UnknownVariant { value: 0x7777, .. })
+        ));
+        assert_eq!(header.request_id(), 0xDEAD_BEEF_0000_0001);
+    }
+
+    #[test]
+    fn only_v2_carries_the_session() {
+        let mut header = sample(MessageType::Response, WireVersion::V2);
+        header.set_session(0xC0FF_EE01);
+        let bytes = header.to_bytes();
+        assert_eq!(&bytes[64..68], &0xC0FF_EE01u32.to_le_bytes());
+        assert_eq!(
+            PacketHeader::from_bytes(&bytes).unwrap().session(),
+            0xC0FF_EE01
+        );
+
+        header.set_version(WireVersion::V1);
+        assert_eq!(
+            PacketHeader::from_bytes(&header.to_bytes())
+                .unwrap()
+                .session(),
+            0
+        );
+    }
+
+    #[test]
+    fn future_versions_are_rejected_with_a_typed_error() {
+        let mut bytes = sample(MessageType::Command, WireVersion::V2).to_bytes();
+        bytes[0..4].copy_from_slice(b"TIX3");
+        assert!(matches!(
+            PacketHeader::from_bytes(&bytes),
+            Err(TixError::UnsupportedVersion(3))
+        ));
+
+        let mut bytes = sample(MessageType::Command, WireVersion::V2).to_bytes();
+        bytes[4] = 9;
+        assert!(matches!(
+            PacketHeader::from_bytes(&bytes),
+            Err(TixError::UnsupportedVersion(9))
+        ));
+
+        let mut bytes = sample(MessageType::Command, WireVersion::V2).to_bytes();
+        bytes[5] = 7;
+        assert!(matches!(
+            PacketHeader::from_bytes(&bytes),
+            Err(TixError::UnknownVariant { .. })
+        ));
+    }
+
+    #[test]
+    fn negotiation_settles_on_the_lower_version() {
+        assert_eq!(WireVersion::negotiate(1), WireVersion::V1);
+        assert_eq!(WireVersion::negotiate(2), WireVersion::V2);
+        assert_eq!(WireVersion::negotiate(200), WireVersion::LATEST);
+        assert_eq!(WireVersion::negotiate(0), WireVersion::V1);
+        assert_eq!(WireVersion::V2.to_string(), "TIX2");
     }
 }
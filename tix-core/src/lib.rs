@@ -6,36 +6,76 @@
 //! - **Protocol types**: `PacketHeader`, `Packet`, `Command`, `MessageType`, `ProtocolFlags`
 //! - **Protocol payloads**: Structured request/response types for shell, file, and screen
 //! - **Codec**: `TixCodec` for framed TCP I/O via `tokio_util`
-//! - **Network**: `Connection` for managed TCP connections with heartbeat
-//! - **State**: Connection state machines for master and slave
+//! - **Network**: `Connection` for managed TCP connections with heartbeats
+//!   and peer liveness, round-trip estimates, traffic accounting, send
+//!   rates and bandwidth caps
+//! - **State**: Connection state machines for master and slave, and
+//!   request IDs that start at a random offset in every session
 //! - **Task**: `TaskPool` for tracking spawned async work with cancellation
 //! - **Error**: `TixError` — typed, `thiserror`-based error hierarchy
+//! - **Config**: loading, checking and atomically saving TOML config files
+//! - **Crash**: a panic hook that logs panics and keeps a crash log
+//! - **Format**: human-readable sizes, durations and rates for the UIs
+//! - **Policy**: `CommandSet`, the commands a list of name patterns covers
+//! - **Features**: `FeatureSet`, the optional features a slave offers
+//! - **Paste guard**: `PasteGuard`, how large a transfer to the slave may
+//!   get before someone is asked, and before it is refused
+//! - **Client**: `TixClient` — async, headless API for scripting a slave
+//! - **Testing** (`test-util` feature): loopback harness pieces — a
+//!   headless master, a synthetic frame source and scratch directories
+//! - **RDP** (`rdp` feature, on by default): the screen stack; with
+//!   `win-capture` (also default) its DXGI capturer and input injector
+//!   are real on Windows. `default-features = false` drops it, and
+//!   zstd and the Windows APIs with it; the rest builds on its own.
+//! - **Chunk compression** (`compression` feature, on by default): zstd
+//!   for file transfer chunks; without it they all go raw.
 
+pub mod client;
 pub mod codec;
+pub mod config;
+pub mod crash;
 pub mod error;
+pub mod features;
 pub mod flags;
+pub mod format;
+pub mod fragment;
 pub mod header;
 pub mod message;
 pub mod network;
 pub mod packet;
+pub mod paste_guard;
+pub mod policy;
 pub mod protocol;
+#[cfg(feature = "rdp")]
 pub mod rdp;
 pub mod state;
 pub mod task;
+#[cfg(feature = "test-util")]
+pub mod testing;
 
 // ── Re-exports for ergonomic usage ───────────────────────────────
 
+pub use client::{ShellOutput, TixClient, TransferSummary};
 pub use codec::TixCodec;
 pub use error::{TaskError, TixError};
+pub use features::{Feature, FeatureSet};
 pub use flags::ProtocolFlags;
-pub use header::{HEADER_SIZE, PacketHeader};
+pub use fragment::{FragmentReassembler, Reassembled};
+pub use header::{HEADER_SIZE, PacketHeader, WireVersion};
 pub use message::{Command, MessageType};
-pub use network::{Connection, ConnectionInfo, ConnectionSender};
+pub use network::{
+    BandwidthWindow, Connection, ConnectionInfo, ConnectionOptions, ConnectionSender,
+    ConnectionStats, Liveness, RttEstimator, SendPriority,
+};
 pub use packet::{MAX_FRAME_SIZE, MAX_PAYLOAD_SIZE, Packet};
-pub use state::{ConnectionPhase, MasterState, PeerCapabilities, SlaveState, TrackedRequest};
+pub use state::{
+    ConnectionPhase, FinishedRequest, MasterState, PeerCapabilities, RequestEnd, RequestIds,
+    SlaveState, TrackedRequest,
+};
 pub use task::{Task, TaskEvent, TaskEventSender, TaskOptions, TaskPool};
 
 // ── RDP (Phase 7) re-exports ─────────────────────────────────────
+#[cfg(feature = "rdp")]
 pub use rdp::{
     BandwidthEstimator, DeltaDetector, DxgiCapturer, FrameDecoder, InputInjector,
     ScreenClient, ScreenService, ScreenServiceConfig, ScreenTransport,
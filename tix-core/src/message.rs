@@ -88,6 +88,17 @@ pub enum Command {
     Upload = 0x0206,
     /// Download file (remote → local).
     Download = 0x0207,
+    /// Delete files, to the Recycle Bin unless asked otherwise.
+    FileDelete = 0x0208,
+    /// Restore a recycled item to its original path.
+    TrashRestore = 0x0209,
+    /// Hash a remote file, whole and per chunk.
+    FileHash = 0x020A,
+    /// Find files by name under a remote directory, streaming matches.
+    FileSearch = 0x020B,
+    /// Run several file operations as one, undoing them all if one
+    /// fails.
+    FileTransaction = 0x020C,
 
     // ── System (0x03xx) ──────────────────────────────────────────
     /// Query system information (OS, CPU, RAM, etc.).
@@ -96,6 +107,38 @@ pub enum Command {
     SystemAction = 0x0302,
     /// List running processes.
     ProcessList = 0x0303,
+    /// Read a registry key or value.
+    RegistryQuery = 0x0304,
+    /// List programs launched at logon (Run keys, Startup folders).
+    StartupList = 0x0305,
+    /// A request was refused because the slave is over its bandwidth cap.
+    LimitExceeded = 0x0306,
+    /// List the tasks the slave is running. Also sent unsolicited, with
+    /// request ID 0, when a task runs past the slow-task threshold.
+    TaskList = 0x0307,
+    /// List the services installed on the slave.
+    ServiceList = 0x0308,
+    /// Start, stop or restart a service and wait for it to settle.
+    ServiceControl = 0x0309,
+    /// Read recent entries from a Windows event log, streamed in batches.
+    EventLogQuery = 0x030A,
+    /// Add a command the slave runs on its own at a fixed interval.
+    ScheduleCreate = 0x030B,
+    /// List the slave's schedules.
+    ScheduleList = 0x030C,
+    /// Remove a schedule, stopping a run in progress.
+    ScheduleDelete = 0x030D,
+    /// Read the kept results of a schedule. Also sent unsolicited, with
+    /// request ID 0, after each run while a master is connected.
+    ScheduleResults = 0x030E,
+    /// Network diagnostics: interfaces, routes, name resolution and TCP
+    /// port checks, run on the slave.
+    NetDiag = 0x030F,
+    /// Switch the slave's packet logging and capture on or off.
+    WireTap = 0x0310,
+    /// Check that the slave can capture, inject input, bind its screen
+    /// socket and write its files.
+    SelfTest = 0x0311,
 
     // ── Screen / Remote Desktop (0x04xx) ─────────────────────────
     /// Start screen capture session.
@@ -108,6 +151,20 @@ pub enum Command {
     InputMouse = 0x0404,
     /// Keyboard input event (master → slave).
     InputKeyboard = 0x0405,
+    /// Switch a running screen session between control and view-only.
+    ScreenMode = 0x0406,
+    /// Raise the secure attention sequence (Ctrl+Alt+Del) on the slave,
+    /// which input injection cannot produce.
+    SendSas = 0x0407,
+    /// Switch a screen session's frames to the zstd dictionary offered
+    /// at `ScreenStart`, once the viewer holds it.
+    ScreenDictionary = 0x0408,
+    /// The viewer's side of the UDP preflight run before a
+    /// `ScreenStart` ack.
+    ScreenPreflight = 0x0409,
+    /// Switch a screen session between frames and low-bandwidth
+    /// thumbnails.
+    ScreenSharing = 0x040A,
 
     // ── Update (0x05xx) ──────────────────────────────────────────
     /// Check for updates.
@@ -139,16 +196,40 @@ impl TryFrom<u64> for Command {
             0x0205 => Ok(Command::Copy),
             0x0206 => Ok(Command::Upload),
             0x0207 => Ok(Command::Download),
+            0x0208 => Ok(Command::FileDelete),
+            0x0209 => Ok(Command::TrashRestore),
+            0x020A => Ok(Command::FileHash),
+            0x020B => Ok(Command::FileSearch),
+            0x020C => Ok(Command::FileTransaction),
 
             0x0301 => Ok(Command::SystemInfo),
             0x0302 => Ok(Command::SystemAction),
             0x0303 => Ok(Command::ProcessList),
+            0x0304 => Ok(Command::RegistryQuery),
+            0x0305 => Ok(Command::StartupList),
+            0x0306 => Ok(Command::LimitExceeded),
+            0x0307 => Ok(Command::TaskList),
+            0x0308 => Ok(Command::ServiceList),
+            0x0309 => Ok(Command::ServiceControl),
+            0x030A => Ok(Command::EventLogQuery),
+            0x030B => Ok(Command::ScheduleCreate),
+            0x030C => Ok(Command::ScheduleList),
+            0x030D => Ok(Command::ScheduleDelete),
+            0x030E => Ok(Command::ScheduleResults),
+            0x030F => Ok(Command::NetDiag),
+            0x0310 => Ok(Command::WireTap),
+            0x0311 => Ok(Command::SelfTest),
 
             0x0401 => Ok(Command::ScreenStart),
             0x0402 => Ok(Command::ScreenStop),
             0x0403 => Ok(Command::ScreenFrame),
             0x0404 => Ok(Command::InputMouse),
             0x0405 => Ok(Command::InputKeyboard),
+            0x0406 => Ok(Command::ScreenMode),
+            0x0407 => Ok(Command::SendSas),
+            0x0408 => Ok(Command::ScreenDictionary),
+            0x0409 => Ok(Command::ScreenPreflight),
+            0x040A => Ok(Command::ScreenSharing),
 
             0x0501 => Ok(Command::UpdateCheck),
             0x0502 => Ok(Command::UpdatePush),
@@ -169,6 +250,59 @@ impl fmt::Display for Command {
 }
 
 impl Command {
+    /// Every command, in ID order.
+    pub const ALL: [Command; 49] = [
+        Command::Ping,
+        Command::Hello,
+        Command::Goodbye,
+        Command::Heartbeat,
+        Command::ShellExecute,
+        Command::ShellCancel,
+        Command::ShellResize,
+        Command::ListDir,
+        Command::FileRead,
+        Command::FileWrite,
+        Command::ListDrives,
+        Command::Copy,
+        Command::Upload,
+        Command::Download,
+        Command::FileDelete,
+        Command::TrashRestore,
+        Command::FileHash,
+        Command::FileSearch,
+        Command::FileTransaction,
+        Command::SystemInfo,
+        Command::SystemAction,
+        Command::ProcessList,
+        Command::RegistryQuery,
+        Command::StartupList,
+        Command::LimitExceeded,
+        Command::TaskList,
+        Command::ServiceList,
+        Command::ServiceControl,
+        Command::EventLogQuery,
+        Command::ScheduleCreate,
+        Command::ScheduleList,
+        Command::ScheduleDelete,
+        Command::ScheduleResults,
+        Command::NetDiag,
+        Command::WireTap,
+        Command::SelfTest,
+        Command::ScreenStart,
+        Command::ScreenStop,
+        Command::ScreenFrame,
+        Command::InputMouse,
+        Command::InputKeyboard,
+        Command::ScreenMode,
+        Command::SendSas,
+        Command::ScreenDictionary,
+        Command::ScreenPreflight,
+        Command::ScreenSharing,
+        Command::UpdateCheck,
+        Command::UpdatePush,
+        Command::UpdateApply,
+    ];
+
     /// Returns `true` if this command expects a response from the peer.
     pub fn expects_response(&self) -> bool {
         !matches!(self, Command::Heartbeat | Command::Goodbye)
@@ -198,38 +332,19 @@ mod tests {
 
     #[test]
     fn command_roundtrip() {
-        let cmds = [
-            Command::Ping,
-            Command::Hello,
-            Command::Goodbye,
-            Command::Heartbeat,
-            Command::ShellExecute,
-            Command::ShellCancel,
-            Command::ShellResize,
-            Command::ListDir,
-            Command::FileRead,
-            Command::FileWrite,
-            Command::ListDrives,
-            Command::Copy,
-            Command::Upload,
-            Command::Download,
-            Command::SystemInfo,
-            Command::SystemAction,
-            Command::ProcessList,
-            Command::ScreenStart,
-            Command::ScreenStop,
-            Command::ScreenFrame,
-            Command::InputMouse,
-            Command::InputKeyboard,
-            Command::UpdateCheck,
-            Command::UpdatePush,
-            Command::UpdateApply,
-        ];
-        for cmd in cmds {
+        for cmd in Command::ALL {
             assert_eq!(Command::try_from(cmd as u64).unwrap(), cmd);
         }
     }
 
+    #[test]
+    fn all_lists_every_command() {
+        let known: Vec<Command> = (0..=0xFFFF)
+            .filter_map(|id| Command::try_from(id).ok())
+            .collect();
+        assert_eq!(known, Command::ALL);
+    }
+
     #[test]
     fn command_invalid() {
         assert!(Command::try_from(0xDEAD).is_err());
@@ -1,53 +1,162 @@
 //! TCP connection management with background reader/writer tasks.
 //!
-//! `Connection` wraps a `TcpStream` and splits it into two independent
-//! background tasks communicating over mpsc channels. This avoids holding
-//! a borrow across await points and gives natural back-pressure.
+//! `Connection` wraps a `TcpStream`, or any other byte stream such as a
+//! TLS session over one, and splits it into two independent background
+//! tasks communicating over mpsc channels. This avoids holding
+//! a borrow across await points and gives natural back-pressure. Outgoing
+//! packets wait in one of two lanes, control ahead of bulk; see
+//! [`sender`](super::sender).
+//!
+//! Outgoing packets are written in the connection's wire version, TIX1
+//! until [`Connection::set_wire_version`] is called after the `Hello`
+//! exchange. Incoming packets may use either version. Packets that
+//! carry no session nonce of their own get the connection's, set with
+//! [`Connection::set_session`], when they are queued. What the peer said it can do in its
+//! `Hello` is kept with [`Connection::set_peer_capabilities`].
+//!
+//! Heartbeats are the connection's own business: it sends them, drops
+//! the peer's before [`Connection::recv`] and reports what their absence
+//! means through [`Connection::on_liveness_change`]; see
+//! [`heartbeat`](super::heartbeat). Their echoes also measure the
+//! round-trip time, kept in the connection's [`ConnectionStats`].
+//!
+//! [`Connection::accept_tls`] and [`Connection::connect_tls`] run the
+//! connection over a TLS session; see [`tls`](super::tls).
+//!
+//! Every packet passes the connection's [`WireTap`] on its way in or
+//! out; see [`wiretap`](super::wiretap).
 
 use futures::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
 use tokio_util::codec::Framed;
 
 use crate::codec::TixCodec;
 use crate::error::TixError;
+use crate::header::WireVersion;
 use crate::packet::Packet;
+use crate::state::PeerCapabilities;
 
-/// Sender half — cheaply cloneable, used to enqueue packets for the
-/// background writer task.
-pub type ConnectionSender = mpsc::Sender<Packet>;
+use super::heartbeat::{ConnectionOptions, Heartbeat, Liveness};
+use super::sender::{BULK_QUEUE, CONTROL_QUEUE, ConnectionSender};
+use super::stats::ConnectionStats;
+use super::tls::{TlsAcceptor, TlsConnector};
+use super::wiretap::{Direction, WireTap};
 
 /// A managed TIX connection to a single peer.
 ///
-/// Internally spawns two Tokio tasks:
-/// - **Writer**: drains `tx` and writes packets to the TCP stream.
-/// - **Reader**: reads packets from the TCP stream and pushes them to `rx`.
-/// - **Heartbeat**: periodically sends a heartbeat packet (reuses a static
-///   instance — no cloning per tick).
+/// Internally spawns three Tokio tasks:
+/// - **Writer**: drains the control lane, then the bulk lane, and writes
+///   packets to the TCP stream.
+/// - **Reader**: reads packets from the TCP stream, notes the peer's
+///   activity and pushes everything but heartbeats to `rx`.
+/// - **Heartbeat**: sends a heartbeat every interval and re-judges the
+///   peer's liveness; not started when heartbeats are disabled.
+///
+/// Dropping the connection stops the reader and the heartbeat; the
+/// writer finishes once no [`ConnectionSender`] is left.
 #[derive(Debug)]
 pub struct Connection {
     /// Send packets to the background writer.
-    tx: mpsc::Sender<Packet>,
+    tx: ConnectionSender,
     /// Receive packets from the background reader.
     rx: mpsc::Receiver<Packet>,
+    /// Local socket address, captured before the stream was split.
+    local_addr: Option<SocketAddr>,
+    /// Remote socket address, captured before the stream was split.
+    peer_addr: Option<SocketAddr>,
+    /// Byte counters maintained by the reader and writer tasks.
+    stats: Arc<ConnectionStats>,
+    /// Header version the writer stamps on every packet.
+    wire_version: Arc<AtomicU8>,
+    /// What the peer's `Hello` advertised, once it arrived.
+    peer_capabilities: RwLock<Option<PeerCapabilities>>,
+    /// The peer's liveness, kept by the reader and heartbeat tasks.
+    heartbeat: Arc<Heartbeat>,
+    heartbeat_task: Option<JoinHandle<()>>,
+    /// Packet logging, off until switched on.
+    tap: WireTap,
 }
 
 impl Connection {
     /// Wrap an already-connected `TcpStream`.
     pub fn new(stream: TcpStream) -> Self {
+        Self::with_options(stream, ConnectionOptions::default())
+    }
+
+    /// Wrap an already-connected `TcpStream`, with heartbeats as
+    /// `options` says.
+    pub fn with_options(stream: TcpStream, options: ConnectionOptions) -> Self {
         // Apply low-latency socket options.
         let _ = stream.set_nodelay(true);
+        let local_addr = stream.local_addr().ok();
+        let peer_addr = stream.peer_addr().ok();
+        Self::from_stream_with_options(stream, local_addr, peer_addr, options)
+    }
 
+    /// Wrap a stream layered over a socket, such as a TLS session, whose
+    /// addresses the caller read from the socket beforehand.
+    pub fn from_stream<S>(
+        stream: S,
+        local_addr: Option<SocketAddr>,
+        peer_addr: Option<SocketAddr>,
+    ) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        Self::from_stream_with_options(stream, local_addr, peer_addr, ConnectionOptions::default())
+    }
+
+    /// [`from_stream`](Self::from_stream), with heartbeats as `options`
+    /// says.
+    pub fn from_stream_with_options<S>(
+        stream: S,
+        local_addr: Option<SocketAddr>,
+        peer_addr: Option<SocketAddr>,
+        options: ConnectionOptions,
+    ) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
         let (mut net_writer, mut net_reader) = Framed::new(stream, TixCodec).split();
 
-        // User → Network
-        let (user_tx, mut network_rx) = mpsc::channel::<Packet>(128);
+        // User → Network, in two lanes
+        let (control_tx, mut control_rx) = mpsc::channel::<Packet>(CONTROL_QUEUE);
+        let (bulk_tx, mut bulk_rx) = mpsc::channel::<Packet>(BULK_QUEUE);
+        let (user_tx, backlog) = ConnectionSender::lanes(control_tx, bulk_tx);
         // Network → User
         let (network_tx, user_rx) = mpsc::channel::<Packet>(128);
 
+        let stats = Arc::new(ConnectionStats::default());
+        let wire_version = Arc::new(AtomicU8::new(WireVersion::V1.number()));
+        let tap = WireTap::default();
+
         // Writer task
+        let writer_stats = Arc::clone(&stats);
+        let writer_version = Arc::clone(&wire_version);
+        let writer_tap = tap.clone();
         tokio::spawn(async move {
-            while let Some(packet) = network_rx.recv().await {
+            loop {
+                let mut packet = tokio::select! {
+                    biased;
+                    Some(packet) = control_rx.recv() => packet,
+                    Some(packet) = bulk_rx.recv() => {
+                        backlog.remove(packet.request_id());
+                        packet
+                    }
+                    else => break,
+                };
+                let version = WireVersion::from_number(writer_version.load(Ordering::Relaxed))
+                    .unwrap_or_default();
+                packet.set_wire_version(version);
+                writer_stats.record_sent(&packet);
+                writer_tap.observe(Direction::Sent, &packet);
                 if let Err(e) = net_writer.send(packet).await {
                     eprintln!("[NET] write error: {e}");
                     break;
@@ -56,12 +165,28 @@ impl Connection {
         });
 
         // Reader task
+        let reader_stats = Arc::clone(&stats);
+        let heartbeat = Arc::new(Heartbeat::new(options, Arc::clone(&stats)));
+        let reader_heartbeat = Arc::clone(&heartbeat);
+        let reader_tap = tap.clone();
         tokio::spawn(async move {
-            while let Some(result) = net_reader.next().await {
+            loop {
+                let result = tokio::select! {
+                    result = net_reader.next() => match result {
+                        Some(result) => result,
+                        None => break,
+                    },
+                    _ = network_tx.closed() => break, // user_rx dropped
+                };
                 match result {
                     Ok(packet) => {
+                        reader_stats.record_received(&packet);
+                        reader_tap.observe(Direction::Received, &packet);
+                        if reader_heartbeat.observe(&packet) {
+                            continue;
+                        }
                         if network_tx.send(packet).await.is_err() {
-                            break; // user_rx dropped
+                            break;
                         }
                     }
                     Err(e) => {
@@ -70,25 +195,26 @@ impl Connection {
                     }
                 }
             }
+            reader_heartbeat.close();
         });
 
-        // Heartbeat task — sends a static heartbeat every 5 seconds.
-        let heartbeat_tx = user_tx.clone();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
-            loop {
-                interval.tick().await;
-                // Build a fresh heartbeat each tick — it's a tiny packet with
-                // zero payload and no allocation.
-                if heartbeat_tx.send(Packet::heartbeat()).await.is_err() {
-                    break;
-                }
-            }
+        let heartbeat_task = options.enabled.then(|| {
+            let heartbeat = Arc::clone(&heartbeat);
+            let tx = user_tx.clone();
+            tokio::spawn(async move { heartbeat.run(tx).await })
         });
 
         Self {
             tx: user_tx,
             rx: user_rx,
+            local_addr,
+            peer_addr,
+            stats,
+            wire_version,
+            peer_capabilities: RwLock::new(None),
+            heartbeat,
+            heartbeat_task,
+            tap,
         }
     }
 
@@ -101,21 +227,147 @@ impl Connection {
     }
 
     /// Receive the next packet from the peer, or `None` if the
-    /// connection was closed.
+    /// connection was closed. Heartbeats never come out of here.
     pub async fn recv(&mut self) -> Option<Packet> {
         self.rx.recv().await
     }
 
+    /// Take the next packet if one is already queued, without waiting.
+    pub fn try_recv(&mut self) -> Option<Packet> {
+        self.rx.try_recv().ok()
+    }
+
     /// Obtain a cloneable sender handle for use in spawned tasks.
     pub fn sender(&self) -> ConnectionSender {
         self.tx.clone()
     }
 
+    /// Local address of the underlying TCP socket.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Remote address of the underlying TCP socket.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Header version outgoing packets are written in.
+    pub fn wire_version(&self) -> WireVersion {
+        WireVersion::from_number(self.wire_version.load(Ordering::Relaxed)).unwrap_or_default()
+    }
+
+    /// Write packets in `version` from now on, including those already
+    /// queued. Call it once the peer's `Hello` shows it reads `version`.
+    pub fn set_wire_version(&self, version: WireVersion) {
+        self.wire_version.store(version.number(), Ordering::Relaxed);
+    }
+
+    /// Session nonce stamped on outgoing packets that have none; 0 for
+    /// none.
+    pub fn session(&self) -> u32 {
+        self.tx.session()
+    }
+
+    /// Stamp `session` on outgoing packets that have none from now on.
+    /// Packets already queued keep the nonce they were queued with, and
+    /// so do senders taken under an earlier session; senders taken
+    /// before the first one pick it up.
+    pub fn set_session(&mut self, session: u32) {
+        self.tx.set_session(session);
+    }
+
+    /// What the peer advertised in its `Hello`; `None` until it
+    /// arrives.
+    pub fn peer_capabilities(&self) -> Option<PeerCapabilities> {
+        self.peer_capabilities
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Keep what the peer advertised in its `Hello`.
+    pub fn set_peer_capabilities(&self, capabilities: PeerCapabilities) {
+        *self
+            .peer_capabilities
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some(capabilities);
+    }
+
+    /// The heartbeat settings the connection runs with.
+    pub fn options(&self) -> ConnectionOptions {
+        self.heartbeat.options()
+    }
+
+    /// When the peer last sent anything, heartbeats included.
+    pub fn last_peer_activity(&self) -> Instant {
+        self.heartbeat.last_activity()
+    }
+
+    /// Follow the peer's liveness: the receiver wakes on every change
+    /// between alive, suspect and dead.
+    pub fn on_liveness_change(&self) -> watch::Receiver<Liveness> {
+        self.heartbeat.subscribe()
+    }
+
+    /// How long the peer may stay silent before it is dead: the miss
+    /// threshold in intervals, plus the link's RTO once measured.
+    pub fn miss_deadline(&self) -> Duration {
+        let options = self.options();
+        options.interval * options.miss_threshold.max(1) + self.heartbeat.slack()
+    }
+
+    /// Shared traffic counters and round-trip estimate for this
+    /// connection.
+    pub fn stats(&self) -> Arc<ConnectionStats> {
+        Arc::clone(&self.stats)
+    }
+
+    /// Switch for logging this connection's packets; clones share it.
+    pub fn wire_tap(&self) -> WireTap {
+        self.tap.clone()
+    }
+
     /// Connect to a remote peer described by `ConnectionInfo`.
     pub async fn connect(info: &ConnectionInfo) -> Result<Self, std::io::Error> {
         let stream = TcpStream::connect(info.to_socket_string()).await?;
         Ok(Self::new(stream))
     }
+
+    /// Connect to `info` and run the connection over TLS, checking the
+    /// peer's certificate as `connector` says.
+    pub async fn connect_tls(
+        info: &ConnectionInfo,
+        connector: &TlsConnector,
+    ) -> Result<Self, std::io::Error> {
+        let stream = TcpStream::connect(info.to_socket_string()).await?;
+        let _ = stream.set_nodelay(true);
+        let local_addr = stream.local_addr().ok();
+        let peer_addr = stream.peer_addr().ok();
+        let stream = connector.connect(info.ip(), stream).await?;
+        Ok(Self::from_stream(stream, local_addr, peer_addr))
+    }
+
+    /// Run the TLS handshake on an accepted `stream` and wrap the
+    /// session. A peer that does not speak TLS fails here.
+    pub async fn accept_tls(
+        stream: TcpStream,
+        acceptor: &TlsAcceptor,
+    ) -> Result<Self, std::io::Error> {
+        let _ = stream.set_nodelay(true);
+        let local_addr = stream.local_addr().ok();
+        let peer_addr = stream.peer_addr().ok();
+        let stream = acceptor.accept(stream).await?;
+        Ok(Self::from_stream(stream, local_addr, peer_addr))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(task) = &self.heartbeat_task {
+            task.abort();
+        }
+    }
 }
 
 // ── ConnectionInfo ──────────────────────────────────────────────
@@ -0,0 +1,435 @@
+//! Heartbeats and peer liveness for a [`Connection`](super::Connection).
+//!
+//! Each side sends an empty `Heartbeat` packet (request ID 0) on the
+//! control lane every [`ConnectionOptions::interval`]. The reader counts
+//! any packet from the peer as a sign of life and drops heartbeats
+//! before they reach `recv`, so application code never sees them.
+//!
+//! Silence is measured in intervals: one without a packet makes the
+//! peer [`Liveness::Suspect`], [`ConnectionOptions::miss_threshold`]
+//! make it [`Liveness::Dead`]. The next packet makes it
+//! [`Liveness::Alive`] again; a closed connection stays dead.
+//! Subscribers follow the state through a watch channel (see
+//! [`Connection::on_liveness_change`](super::Connection::on_liveness_change)).
+//!
+//! A heartbeat carries a [`HeartbeatStamp`]: the sender's clock, and the
+//! last stamp it heard from the peer with how long it held it. The peer
+//! subtracts both from its own clock and has a round-trip sample for
+//! the connection's [`RttEstimator`](super::rtt::RttEstimator). Silence
+//! is judged only after that estimate's RTO has passed as well, so a
+//! slow link is not taken for a dead one. Heartbeats without a stamp,
+//! from older peers, still count as signs of life.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+
+use crate::message::Command;
+use crate::packet::Packet;
+
+use super::sender::{ConnectionSender, SendPriority};
+use super::stats::ConnectionStats;
+
+/// How often a heartbeat goes out by default.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Intervals of silence after which the peer is dead by default.
+pub const DEFAULT_MISS_THRESHOLD: u32 = 3;
+
+/// Heartbeat settings of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    /// Time between heartbeats, and the unit silence is counted in.
+    pub interval: Duration,
+    /// Intervals without a packet from the peer before it is dead.
+    pub miss_threshold: u32,
+    /// Send heartbeats and track liveness. Off, the peer always counts
+    /// as alive; its heartbeats are still dropped.
+    pub enabled: bool,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_HEARTBEAT_INTERVAL,
+            miss_threshold: DEFAULT_MISS_THRESHOLD,
+            enabled: true,
+        }
+    }
+}
+
+impl ConnectionOptions {
+    /// What `silence` since the peer's last packet says about it.
+    pub fn liveness(&self, silence: Duration) -> Liveness {
+        if !self.enabled {
+            return Liveness::Alive;
+        }
+        let missed = silence.as_nanos() / self.interval.as_nanos().max(1);
+        if missed >= u128::from(self.miss_threshold.max(1)) {
+            Liveness::Dead
+        } else if missed >= 1 {
+            Liveness::Suspect
+        } else {
+            Liveness::Alive
+        }
+    }
+}
+
+/// Whether the peer is still there, judged by what it has sent lately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liveness {
+    /// Heard from within the last interval.
+    Alive,
+    /// Silent for at least one interval.
+    Suspect,
+    /// Silent for the miss threshold, or the connection closed.
+    Dead,
+}
+
+impl fmt::Display for Liveness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Alive => "alive",
+            Self::Suspect => "suspect",
+            Self::Dead => "dead",
+        })
+    }
+}
+
+// ── HeartbeatStamp ──────────────────────────────────────────────
+
+/// The payload of a heartbeat: 8 bytes with no echo, 24 with one, all
+/// little-endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatStamp {
+    /// The sender's clock, in microseconds since its end of the
+    /// connection opened.
+    pub sent_us: u64,
+    /// The receiver's last stamp, if the sender has heard one since its
+    /// previous heartbeat.
+    pub echo: Option<Echo>,
+}
+
+/// A stamp sent back to where it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Echo {
+    /// The stamp as it arrived.
+    pub sent_us: u64,
+    /// How long the echoing side held it before sending it back.
+    pub held_us: u64,
+}
+
+impl HeartbeatStamp {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.sent_us.to_le_bytes().to_vec();
+        if let Some(echo) = self.echo {
+            out.extend_from_slice(&echo.sent_us.to_le_bytes());
+            out.extend_from_slice(&echo.held_us.to_le_bytes());
+        }
+        out
+    }
+
+    /// `None` for an empty heartbeat, or one this version cannot read.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let word = |i: usize| {
+            let bytes: [u8; 8] = bytes.get(i * 8..i * 8 + 8)?.try_into().ok()?;
+            Some(u64::from_le_bytes(bytes))
+        };
+        let echo = match bytes.len() {
+            8 => None,
+            24 => Some(Echo {
+                sent_us: word(1)?,
+                held_us: word(2)?,
+            }),
+            _ => return None,
+        };
+        Some(Self {
+            sent_us: word(0)?,
+            echo,
+        })
+    }
+}
+
+// ── Heartbeat ────────────────────────────────────────────────────
+
+/// Liveness state shared by a connection's reader and heartbeat tasks.
+#[derive(Debug)]
+pub(crate) struct Heartbeat {
+    options: ConnectionOptions,
+    last_activity: Mutex<Instant>,
+    state: watch::Sender<Liveness>,
+    closed: AtomicBool,
+    /// What this side's stamps count from.
+    epoch: Instant,
+    /// The peer's last stamp and when it arrived, for the next heartbeat
+    /// to echo.
+    peer_stamp: Mutex<Option<(u64, Instant)>>,
+    /// Where round-trip samples go.
+    stats: Arc<ConnectionStats>,
+}
+
+impl Heartbeat {
+    pub(crate) fn new(options: ConnectionOptions, stats: Arc<ConnectionStats>) -> Self {
+        let now = Instant::now();
+        Self {
+            options,
+            last_activity: Mutex::new(now),
+            state: watch::Sender::new(Liveness::Alive),
+            closed: AtomicBool::new(false),
+            epoch: now,
+            peer_stamp: Mutex::new(None),
+            stats,
+        }
+    }
+
+    pub(crate) fn options(&self) -> ConnectionOptions {
+        self.options
+    }
+
+    /// Note `packet` from the peer. Returns whether it is a heartbeat,
+    /// which goes no further.
+    pub(crate) fn observe(&self, packet: &Packet) -> bool {
+        let now = Instant::now();
+        *self.last_activity.lock().unwrap() = now;
+        self.set(Liveness::Alive);
+        if packet.command().ok() != Some(Command::Heartbeat) {
+            return false;
+        }
+        if let Some(stamp) = HeartbeatStamp::from_bytes(packet.payload()) {
+            *self.peer_stamp.lock().unwrap() = Some((stamp.sent_us, now));
+            if let Some(rtt) = stamp.echo.and_then(|echo| self.round_trip(echo, now)) {
+                self.stats.record_rtt(rtt);
+            }
+        }
+        true
+    }
+
+    /// The round trip an echo of our stamp measures, less the time the
+    /// peer sat on it; `None` for a stamp from our future.
+    fn round_trip(&self, echo: Echo, now: Instant) -> Option<Duration> {
+        let elapsed = self.micros(now).checked_sub(echo.sent_us)?;
+        Some(Duration::from_micros(elapsed.saturating_sub(echo.held_us)))
+    }
+
+    fn micros(&self, at: Instant) -> u64 {
+        at.saturating_duration_since(self.epoch).as_micros() as u64
+    }
+
+    /// The heartbeat to send at `now`, echoing the peer's last stamp
+    /// once.
+    pub(crate) fn stamp(&self, now: Instant) -> HeartbeatStamp {
+        let echo = self
+            .peer_stamp
+            .lock()
+            .unwrap()
+            .take()
+            .map(|(sent_us, at)| Echo {
+                sent_us,
+                held_us: now.saturating_duration_since(at).as_micros() as u64,
+            });
+        HeartbeatStamp {
+            sent_us: self.micros(now),
+            echo,
+        }
+    }
+
+    /// Extra silence allowed for the link's round trip: the RTO, capped
+    /// at the miss threshold so a peer that is gone is still found out.
+    pub(crate) fn slack(&self) -> Duration {
+        let cap = self.options.interval * self.options.miss_threshold.max(1);
+        self.stats.rtt().rto().min(cap)
+    }
+
+    /// When the peer last sent anything.
+    pub(crate) fn last_activity(&self) -> Instant {
+        *self.last_activity.lock().unwrap()
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<Liveness> {
+        self.state.subscribe()
+    }
+
+    /// Re-judge the peer as of `now`.
+    pub(crate) fn check(&self, now: Instant) {
+        let silence = now.saturating_duration_since(self.last_activity());
+        self.set(self.options.liveness(silence.saturating_sub(self.slack())));
+    }
+
+    /// The connection is gone: the peer is dead for good.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.state
+            .send_if_modified(|state| std::mem::replace(state, Liveness::Dead) != Liveness::Dead);
+    }
+
+    fn set(&self, liveness: Liveness) {
+        if self.closed.load(Ordering::Relaxed) {
+            return;
+        }
+        self.state
+            .send_if_modified(|state| std::mem::replace(state, liveness) != liveness);
+    }
+
+    /// Send a heartbeat through `tx` every interval and re-judge the
+    /// peer after each, until the writer is gone.
+    pub(crate) async fn run(&self, tx: ConnectionSender) {
+        let mut interval = tokio::time::interval(self.options.interval);
+        loop {
+            interval.tick().await;
+            let now = Instant::now();
+            self.check(now);
+            let stamp = self.stamp(now).to_bytes();
+            let packet = Packet::new_command(0, Command::Heartbeat, stamp)
+                .expect("a stamp is 24 bytes at most");
+            if tx
+                .send_with_priority(packet, SendPriority::Control)
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(miss_threshold: u32) -> ConnectionOptions {
+        ConnectionOptions {
+            interval: Duration::from_secs(5),
+            miss_threshold,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn silence_turns_suspect_then_dead_at_the_threshold() {
+        let opts = options(3);
+        let secs = Duration::from_secs;
+        assert_eq!(opts.liveness(secs(0)), Liveness::Alive);
+        assert_eq!(opts.liveness(Duration::from_millis(4999)), Liveness::Alive);
+        assert_eq!(opts.liveness(secs(5)), Liveness::Suspect);
+        assert_eq!(
+            opts.liveness(Duration::from_millis(14_999)),
+            Liveness::Suspect
+        );
+        assert_eq!(opts.liveness(secs(15)), Liveness::Dead);
+        assert_eq!(opts.liveness(secs(600)), Liveness::Dead);
+
+        // A threshold of one (or zero) skips suspicion.
+        assert_eq!(options(1).liveness(secs(5)), Liveness::Dead);
+        assert_eq!(options(0).liveness(secs(5)), Liveness::Dead);
+        let off = ConnectionOptions {
+            enabled: false,
+            ..opts
+        };
+        assert_eq!(off.liveness(secs(600)), Liveness::Alive);
+    }
+
+    #[test]
+    fn packets_revive_the_peer_until_the_connection_closes() {
+        let heartbeat = Heartbeat::new(options(3), Arc::default());
+        let mut rx = heartbeat.subscribe();
+        let start = heartbeat.last_activity();
+
+        heartbeat.check(start + Duration::from_secs(6));
+        assert_eq!(*rx.borrow_and_update(), Liveness::Suspect);
+        heartbeat.check(start + Duration::from_secs(16));
+        assert_eq!(*rx.borrow_and_update(), Liveness::Dead);
+
+        assert!(heartbeat.observe(&Packet::heartbeat()));
+        assert_eq!(*rx.borrow_and_update(), Liveness::Alive);
+        let ping = Packet::new_command(1, Command::Ping, Vec::new()).unwrap();
+        assert!(!heartbeat.observe(&ping));
+        // No change, no wake-up.
+        assert!(!rx.has_changed().unwrap());
+
+        heartbeat.close();
+        assert_eq!(*rx.borrow_and_update(), Liveness::Dead);
+        heartbeat.observe(&ping);
+        assert_eq!(*rx.borrow(), Liveness::Dead);
+    }
+
+    #[test]
+    fn stamps_survive_the_wire() {
+        let bare = HeartbeatStamp {
+            sent_us: 42,
+            echo: None,
+        };
+        assert_eq!(bare.to_bytes().len(), 8);
+        assert_eq!(HeartbeatStamp::from_bytes(&bare.to_bytes()), Some(bare));
+        let echoed = HeartbeatStamp {
+            sent_us: 7,
+            echo: Some(Echo {
+                sent_us: 1_000_000,
+                held_us: 250,
+            }),
+        };
+        assert_eq!(HeartbeatStamp::from_bytes(&echoed.to_bytes()), Some(echoed));
+        // Older peers send nothing; odd lengths are not guessed at.
+        assert_eq!(HeartbeatStamp::from_bytes(&[]), None);
+        assert_eq!(HeartbeatStamp::from_bytes(&[0; 12]), None);
+    }
+
+    #[test]
+    fn echoes_measure_the_round_trip_less_the_hold() {
+        let ours = Heartbeat::new(options(3), Arc::default());
+        let theirs = Heartbeat::new(options(3), Arc::default());
+        let now = Instant::now() + Duration::from_secs(1);
+        let echo = Echo {
+            sent_us: ours.micros(now) - 300_000,
+            held_us: 100_000,
+        };
+        assert_eq!(ours.round_trip(echo, now), Some(Duration::from_millis(200)));
+        let future = Echo {
+            sent_us: ours.micros(now) + 1,
+            held_us: 0,
+        };
+        assert_eq!(ours.round_trip(future, now), None);
+
+        // One stamp there and back gives one sample, and is echoed once.
+        let heartbeat = |stamp: HeartbeatStamp| {
+            Packet::new_command(0, Command::Heartbeat, stamp.to_bytes()).unwrap()
+        };
+        assert!(theirs.observe(&heartbeat(ours.stamp(Instant::now()))));
+        let reply = theirs.stamp(Instant::now());
+        assert!(reply.echo.is_some());
+        assert_eq!(theirs.stamp(Instant::now()).echo, None);
+        assert!(ours.observe(&heartbeat(reply)));
+        let rtt = ours.stats.rtt();
+        assert_eq!(rtt.samples(), 1);
+        assert!(rtt.srtt().unwrap() < Duration::from_secs(1));
+        assert_eq!(theirs.stats.rtt().samples(), 0);
+        // An unstamped heartbeat is still a heartbeat.
+        assert!(ours.observe(&Packet::heartbeat()));
+    }
+
+    #[test]
+    fn a_slow_link_gets_more_time_before_suspicion() {
+        let stats = Arc::new(ConnectionStats::default());
+        // SRTT 2 s, RTTVAR 1 s: an RTO of 6 s.
+        stats.record_rtt(Duration::from_secs(2));
+        let heartbeat = Heartbeat::new(options(3), Arc::clone(&stats));
+        assert_eq!(heartbeat.slack(), Duration::from_secs(6));
+        let mut rx = heartbeat.subscribe();
+        let start = heartbeat.last_activity();
+
+        heartbeat.check(start + Duration::from_secs(10));
+        assert_eq!(*rx.borrow_and_update(), Liveness::Alive);
+        heartbeat.check(start + Duration::from_secs(12));
+        assert_eq!(*rx.borrow_and_update(), Liveness::Suspect);
+        heartbeat.check(start + Duration::from_secs(21));
+        assert_eq!(*rx.borrow_and_update(), Liveness::Dead);
+
+        // However bad the link, the slack stops at the miss threshold.
+        stats.record_rtt(Duration::from_secs(120));
+        assert_eq!(heartbeat.slack(), Duration::from_secs(15));
+    }
+}
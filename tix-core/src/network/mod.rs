@@ -1,5 +1,16 @@
 mod connection;
+pub mod heartbeat;
+pub mod rtt;
+pub mod sender;
+pub mod stats;
+pub mod tls;
+pub mod upload;
+pub mod wiretap;
 
 pub use connection::Connection;
 pub use connection::ConnectionInfo;
-pub use connection::ConnectionSender;
+pub use heartbeat::{ConnectionOptions, Liveness};
+pub use rtt::RttEstimator;
+pub use sender::{ConnectionSender, SendPriority};
+pub use stats::{BandwidthWindow, Clock, ConnectionStats, SendRate, SystemClock};
+pub use wiretap::WireTap;
@@ -0,0 +1,176 @@
+//! Round-trip time estimation and the timeouts derived from it.
+//!
+//! Heartbeats carry the sender's clock and echo the last stamp heard
+//! from the peer (see [`heartbeat`](super::heartbeat)), so each one that
+//! comes back is a round-trip sample at no extra traffic. Samples feed
+//! an [`RttEstimator`], which smooths them the way TCP does (RFC 6298):
+//!
+//! ```text
+//! SRTT   ← 7/8 · SRTT + 1/8 · R
+//! RTTVAR ← 3/4 · RTTVAR + 1/4 · |SRTT − R|
+//! RTO    = SRTT + 4 · RTTVAR
+//! ```
+//!
+//! A request's timeout is then `max(floor, RTO × multiplier)`, the
+//! multiplier depending on how long the command may take to answer
+//! (see [`timeout_multiplier`]): a LAN keeps the configured floor, a slow
+//! or jittery link stretches it instead of failing requests that were
+//! only late.
+
+use std::time::Duration;
+
+use crate::message::Command;
+
+/// Weight of a new sample in the smoothed RTT (1/8).
+const ALPHA: f64 = 0.125;
+
+/// Weight of a new deviation in the RTT variance (1/4).
+const BETA: f64 = 0.25;
+
+/// Smoothed round-trip time and its variance.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RttEstimator {
+    srtt: Option<Duration>,
+    rttvar: Duration,
+    samples: u64,
+}
+
+impl RttEstimator {
+    /// Fold in one measured round trip.
+    pub fn observe(&mut self, sample: Duration) {
+        self.samples += 1;
+        let Some(srtt) = self.srtt else {
+            self.srtt = Some(sample);
+            self.rttvar = sample / 2;
+            return;
+        };
+        let deviation = srtt.abs_diff(sample);
+        self.rttvar = self.rttvar.mul_f64(1.0 - BETA) + deviation.mul_f64(BETA);
+        self.srtt = Some(srtt.mul_f64(1.0 - ALPHA) + sample.mul_f64(ALPHA));
+    }
+
+    /// Smoothed round-trip time; `None` before the first sample.
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt
+    }
+
+    /// How far samples stray from [`srtt`](Self::srtt).
+    pub fn rttvar(&self) -> Duration {
+        self.rttvar
+    }
+
+    /// Samples seen so far.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Retransmission-style timeout, `SRTT + 4 · RTTVAR`; zero before
+    /// the first sample.
+    pub fn rto(&self) -> Duration {
+        self.srtt
+            .map_or(Duration::ZERO, |srtt| srtt + self.rttvar * 4)
+    }
+
+    /// How long to wait for the answer to `cmd`: `floor`, or more when
+    /// the link is slow.
+    pub fn request_timeout(&self, floor: Duration, cmd: Command) -> Duration {
+        floor.max(self.rto() * timeout_multiplier(cmd))
+    }
+}
+
+/// How many RTOs `cmd` may take to answer. Commands the slave answers
+/// at once stay tight; ones that run a program or walk a disk first get
+/// a generous budget.
+pub fn timeout_multiplier(cmd: Command) -> u32 {
+    match cmd {
+        Command::Ping | Command::Hello | Command::FileHash => 4,
+        Command::ShellExecute
+        | Command::Copy
+        | Command::FileDelete
+        | Command::FileSearch
+        | Command::FileTransaction
+        | Command::EventLogQuery
+        | Command::ServiceControl
+        | Command::SelfTest
+        | Command::UpdateApply => 120,
+        _ => 20,
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    fn feed(samples: &[u64]) -> RttEstimator {
+        let mut rtt = RttEstimator::default();
+        for &sample in samples {
+            rtt.observe(ms(sample));
+        }
+        rtt
+    }
+
+    #[test]
+    fn the_first_sample_sets_the_estimate() {
+        let rtt = RttEstimator::default();
+        assert_eq!(rtt.srtt(), None);
+        assert_eq!(rtt.rto(), Duration::ZERO);
+
+        let rtt = feed(&[100]);
+        assert_eq!(rtt.srtt(), Some(ms(100)));
+        assert_eq!(rtt.rttvar(), ms(50));
+        assert_eq!(rtt.rto(), ms(300));
+        assert_eq!(rtt.samples(), 1);
+    }
+
+    #[test]
+    fn a_steady_link_settles_on_its_rtt() {
+        let rtt = feed(&[20; 100]);
+        assert_eq!(rtt.srtt(), Some(ms(20)));
+        // The variance decays towards zero, and the RTO with it.
+        assert!(rtt.rttvar() < ms(1), "{:?}", rtt.rttvar());
+        assert!(rtt.rto() < ms(21), "{:?}", rtt.rto());
+    }
+
+    #[test]
+    fn a_spike_widens_the_timeout_more_than_the_mean() {
+        let mut rtt = feed(&[300; 50]);
+        let calm = rtt.rto();
+        rtt.observe(ms(2000));
+        // SRTT moves by an eighth of the jump, RTTVAR by a quarter of it.
+        let srtt = rtt.srtt().unwrap();
+        assert!(srtt > ms(510) && srtt < ms(515), "{:?}", srtt);
+        assert!(rtt.rttvar() > ms(420), "{:?}", rtt.rttvar());
+        assert!(rtt.rto() > calm + ms(1500), "{:?} vs {:?}", rtt.rto(), calm);
+
+        // Calm again, it shrinks back.
+        for _ in 0..50 {
+            rtt.observe(ms(300));
+        }
+        assert!(rtt.rto() < ms(320), "{:?}", rtt.rto());
+    }
+
+    #[test]
+    fn timeouts_keep_the_floor_until_the_link_needs_more() {
+        let floor = Duration::from_secs(10);
+        let lan = feed(&[1, 2, 1, 1]);
+        assert_eq!(lan.request_timeout(floor, Command::ListDir), floor);
+        assert_eq!(lan.request_timeout(floor, Command::ShellExecute), floor);
+
+        // 300 ms with 2 s spikes: RTO around 3 s.
+        let vpn = feed(&[300, 320, 2000, 310, 300, 2100, 290]);
+        let rto = vpn.rto();
+        assert!(rto > ms(2500) && rto < ms(3500), "{:?}", rto);
+        assert_eq!(vpn.request_timeout(floor, Command::Ping), rto * 4);
+        assert_eq!(vpn.request_timeout(floor, Command::ListDir), rto * 20);
+        assert_eq!(vpn.request_timeout(floor, Command::ShellExecute), rto * 120);
+        // Ping stays within seconds while a shell command gets minutes.
+        assert!(rto * 4 < Duration::from_secs(15));
+        assert!(rto * 120 > Duration::from_secs(300));
+    }
+}
@@ -0,0 +1,278 @@
+//! The two outgoing queues of a [`Connection`](super::Connection).
+//!
+//! Every task of a peer writes through the same socket. Were they all to
+//! share one queue, a file transfer would park heartbeats and Ping
+//! replies behind a few megabytes of chunks and the other end would
+//! declare the link dead. So the writer drains two lanes:
+//!
+//! - **control**: heartbeats, Pings, acks, errors and other small packets;
+//!   always emptied first.
+//! - **bulk**: file chunks, frames and large responses. It is short, so a
+//!   sender that outpaces the socket waits in `send` instead of queueing
+//!   without limit.
+//!
+//! [`ConnectionSender::send`] picks the lane from the payload size. A
+//! small packet for a request that still has bulk packets queued follows
+//! them on the bulk lane, so a stream's final status or error never
+//! overtakes its chunks.
+//!
+//! A packet without a session nonce gets the sender's as it is queued,
+//! not as it is written: a sender taken during one session keeps that
+//! session's nonce after [`Connection::set_session`] moves on, so what a
+//! task of the old session still sends reads as the old session's.
+//!
+//! [`Connection::set_session`]: super::Connection::set_session
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+
+use crate::packet::Packet;
+
+/// Payloads larger than this go to the bulk lane.
+pub const BULK_THRESHOLD: usize = 8 * 1024;
+
+/// Packets the control lane holds before its senders wait.
+pub const CONTROL_QUEUE: usize = 128;
+
+/// Packets the bulk lane holds before its senders wait: a few megabytes
+/// of 200 KB chunks.
+pub const BULK_QUEUE: usize = 16;
+
+/// Which queue a packet waits in before the writer takes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendPriority {
+    /// Written before anything on the bulk lane.
+    Control,
+    /// Written when the control lane is empty; bounded.
+    Bulk,
+}
+
+/// Sender half of a connection. Cheaply cloneable, used to enqueue
+/// packets for the background writer task.
+#[derive(Debug, Clone)]
+pub struct ConnectionSender {
+    control: mpsc::Sender<Packet>,
+    /// `None` for a single-queue sender (see the `From` impl).
+    bulk: Option<mpsc::Sender<Packet>>,
+    backlog: Arc<BulkBacklog>,
+    /// Nonce stamped on packets that have none; shared with the clones
+    /// taken in the same session.
+    session: Arc<AtomicU32>,
+}
+
+impl ConnectionSender {
+    /// A sender over two lanes, and the backlog the writer must update
+    /// as it takes packets off the bulk one.
+    pub(crate) fn lanes(
+        control: mpsc::Sender<Packet>,
+        bulk: mpsc::Sender<Packet>,
+    ) -> (Self, Arc<BulkBacklog>) {
+        let backlog = Arc::new(BulkBacklog::default());
+        let sender = Self {
+            control,
+            bulk: Some(bulk),
+            backlog: Arc::clone(&backlog),
+            session: Arc::default(),
+        };
+        (sender, backlog)
+    }
+
+    /// Session nonce stamped on queued packets that have none; 0 for
+    /// none.
+    pub fn session(&self) -> u32 {
+        self.session.load(Ordering::Relaxed)
+    }
+
+    /// Stamp `session` from now on. Clones that already share this
+    /// sender's nonce follow only while it is still 0, so senders taken
+    /// before the first session learn it; once a session is set, the
+    /// clones taken under it keep it.
+    pub(crate) fn set_session(&mut self, session: u32) {
+        if self.session() == 0 {
+            self.session.store(session, Ordering::Relaxed);
+        } else {
+            self.session = Arc::new(AtomicU32::new(session));
+        }
+    }
+
+    /// Enqueue `packet` on the lane [`priority_of`](Self::priority_of)
+    /// picks. Waits while that lane is full.
+    pub async fn send(&self, packet: Packet) -> Result<(), SendError<Packet>> {
+        let priority = self.priority_of(&packet);
+        self.send_with_priority(packet, priority).await
+    }
+
+    /// Enqueue `packet` on the bulk lane, waiting for room.
+    pub async fn send_bulk(&self, packet: Packet) -> Result<(), SendError<Packet>> {
+        self.send_with_priority(packet, SendPriority::Bulk).await
+    }
+
+    /// Enqueue `packet` on the given lane, waiting for room. A control
+    /// packet does not wait for the bulk lane, and may overtake packets
+    /// queued there.
+    pub async fn send_with_priority(
+        &self,
+        packet: Packet,
+        priority: SendPriority,
+    ) -> Result<(), SendError<Packet>> {
+        let packet = self.stamp(packet);
+        match (&self.bulk, priority) {
+            (Some(bulk), SendPriority::Bulk) => {
+                let id = packet.request_id();
+                self.backlog.add(id);
+                let result = bulk.send(packet).await;
+                if result.is_err() {
+                    self.backlog.remove(id);
+                }
+                result
+            }
+            _ => self.control.send(packet).await,
+        }
+    }
+
+    /// Enqueue `packet` without waiting; fails when its lane is full.
+    pub fn try_send(&self, packet: Packet) -> Result<(), TrySendError<Packet>> {
+        let packet = self.stamp(packet);
+        match (&self.bulk, self.priority_of(&packet)) {
+            (Some(bulk), SendPriority::Bulk) => {
+                let id = packet.request_id();
+                self.backlog.add(id);
+                let result = bulk.try_send(packet);
+                if result.is_err() {
+                    self.backlog.remove(id);
+                }
+                result
+            }
+            _ => self.control.try_send(packet),
+        }
+    }
+
+    /// The lane [`send`](Self::send) uses for `packet`.
+    pub fn priority_of(&self, packet: &Packet) -> SendPriority {
+        if packet.payload().len() > BULK_THRESHOLD || self.backlog.holds(packet.request_id()) {
+            SendPriority::Bulk
+        } else {
+            SendPriority::Control
+        }
+    }
+
+    /// Whether the writer task has stopped.
+    pub fn is_closed(&self) -> bool {
+        self.control.is_closed()
+    }
+
+    fn stamp(&self, mut packet: Packet) -> Packet {
+        if packet.session() == 0 {
+            packet.set_session(self.session());
+        }
+        packet
+    }
+}
+
+/// A sender whose packets all share one queue, for tests and in-process
+/// plumbing with no writer to prioritize.
+impl From<mpsc::Sender<Packet>> for ConnectionSender {
+    fn from(tx: mpsc::Sender<Packet>) -> Self {
+        Self {
+            control: tx,
+            bulk: None,
+            backlog: Arc::default(),
+            session: Arc::default(),
+        }
+    }
+}
+
+/// Bulk packets queued but not yet taken by the writer, per request ID.
+#[derive(Debug, Default)]
+pub(crate) struct BulkBacklog(Mutex<HashMap<u64, usize>>);
+
+impl BulkBacklog {
+    fn add(&self, request_id: u64) {
+        *self.lock().entry(request_id).or_default() += 1;
+    }
+
+    /// The writer took a bulk packet of `request_id`.
+    pub(crate) fn remove(&self, request_id: u64) {
+        let mut queued = self.lock();
+        if let Some(count) = queued.get_mut(&request_id) {
+            *count -= 1;
+            if *count == 0 {
+                queued.remove(&request_id);
+            }
+        }
+    }
+
+    fn holds(&self, request_id: u64) -> bool {
+        self.lock().contains_key(&request_id)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, usize>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Command;
+
+    fn packet(id: u64, len: usize) -> Packet {
+        Packet::new_response(id, Command::FileRead, vec![0; len]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn small_packets_skip_the_bulk_lane_unless_their_request_is_on_it() {
+        let (control_tx, mut control) = mpsc::channel(8);
+        let (bulk_tx, mut bulk) = mpsc::channel(8);
+        let (tx, backlog) = ConnectionSender::lanes(control_tx, bulk_tx);
+
+        tx.send(packet(5, BULK_THRESHOLD + 1)).await.unwrap();
+        tx.send(packet(5, 10)).await.unwrap();
+        tx.send(packet(6, 10)).await.unwrap();
+        assert_eq!(control.try_recv().unwrap().request_id(), 6);
+        assert!(control.try_recv().is_err());
+        assert_eq!(bulk.try_recv().unwrap().payload().len(), BULK_THRESHOLD + 1);
+        backlog.remove(5);
+        assert_eq!(bulk.try_recv().unwrap().payload().len(), 10);
+        backlog.remove(5);
+
+        // Once the writer took them, request 5 is small again.
+        tx.send(packet(5, 10)).await.unwrap();
+        assert_eq!(control.try_recv().unwrap().request_id(), 5);
+    }
+
+    #[tokio::test]
+    async fn senders_keep_the_session_they_were_taken_in() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let mut current = ConnectionSender::from(tx);
+        let early = current.clone();
+        current.set_session(1);
+        let first = current.clone();
+        current.set_session(2);
+
+        early.send(packet(1, 10)).await.unwrap();
+        first.send(packet(2, 10)).await.unwrap();
+        current.send(packet(3, 10)).await.unwrap();
+        let mut stamped = packet(4, 10);
+        stamped.set_session(7);
+        current.send(stamped).await.unwrap();
+        let sessions: Vec<u32> = (0..4).map(|_| rx.try_recv().unwrap().session()).collect();
+        assert_eq!(sessions, [1, 1, 2, 7]);
+    }
+
+    #[tokio::test]
+    async fn a_single_queue_sender_uses_it_for_everything() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let tx = ConnectionSender::from(tx);
+        tx.send(packet(1, BULK_THRESHOLD * 4)).await.unwrap();
+        tx.send_bulk(packet(2, 1)).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap().request_id(), 1);
+        assert_eq!(rx.try_recv().unwrap().request_id(), 2);
+    }
+}
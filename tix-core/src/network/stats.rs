@@ -0,0 +1,436 @@
+//! Traffic accounting and bandwidth caps.
+//!
+//! [`ConnectionStats`] is updated by the background reader and writer
+//! tasks of a [`Connection`](super::Connection) and can be read from any
+//! task. [`BandwidthWindow`] turns a stream of byte counts into a
+//! sliding-window total and decides whether a configured cap is hit;
+//! its notion of time comes from a [`Clock`] so tests can drive it.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::message::Command;
+use crate::packet::Packet;
+use crate::protocol::CommandTraffic;
+
+use super::rtt::RttEstimator;
+
+// ── ConnectionStats ──────────────────────────────────────────────
+
+/// Byte counters for one control connection, overall and per command,
+/// its measured round-trip time and the rate it sends at.
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    per_command: Mutex<HashMap<Command, CommandTraffic>>,
+    rtt: Mutex<RttEstimator>,
+    send_rate: Mutex<SendRate>,
+}
+
+impl ConnectionStats {
+    /// Account for a packet written to the peer.
+    pub fn record_sent(&self, packet: &Packet) {
+        let len = Self::wire_len(packet);
+        self.bytes_sent.fetch_add(len, Ordering::Relaxed);
+        self.update(packet, |t| t.bytes_sent += len);
+        self.send_rate
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(Instant::now(), len);
+    }
+
+    /// Account for a packet read from the peer.
+    pub fn record_received(&self, packet: &Packet) {
+        let len = Self::wire_len(packet);
+        self.bytes_received.fetch_add(len, Ordering::Relaxed);
+        self.update(packet, |t| t.bytes_received += len);
+    }
+
+    /// Total bytes written, headers included.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Total bytes read, headers included.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Per-command breakdown, largest total first.
+    pub fn per_command(&self) -> Vec<CommandTraffic> {
+        let map = self.per_command.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out: Vec<CommandTraffic> = map.values().cloned().collect();
+        out.sort_by(|a, b| {
+            b.total_bytes()
+                .cmp(&a.total_bytes())
+                .then_with(|| a.command.cmp(&b.command))
+        });
+        out
+    }
+
+    /// Account for a round trip measured from a heartbeat echo.
+    pub fn record_rtt(&self, sample: Duration) {
+        self.rtt
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .observe(sample);
+    }
+
+    /// The round-trip estimate so far; empty until the peer echoes a
+    /// heartbeat.
+    pub fn rtt(&self) -> RttEstimator {
+        *self.rtt.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Bytes a second the connection sends at when it has plenty to
+    /// send; `None` until it has been busy for a while.
+    pub fn send_rate(&self) -> Option<u64> {
+        self.send_rate
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .bytes_per_sec()
+    }
+
+    fn wire_len(packet: &Packet) -> u64 {
+        packet.wire_size() as u64
+    }
+
+    fn update(&self, packet: &Packet, f: impl FnOnce(&mut CommandTraffic)) {
+        // Packets with an unknown command still count towards the totals.
+        let Ok(cmd) = packet.command() else {
+            return;
+        };
+        let mut map = self.per_command.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = map.entry(cmd).or_insert_with(|| CommandTraffic {
+            command: format!("{:?}", cmd),
+            ..Default::default()
+        });
+        entry.packets += 1;
+        f(entry);
+    }
+}
+
+// ── SendRate ─────────────────────────────────────────────────────
+
+/// Width of the windows a [`SendRate`] measures over.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Least a window must carry to say anything about the link: heartbeats
+/// and small replies trickle out far below its speed.
+const BUSY_BYTES: u64 = 64 * 1024;
+
+/// Weight of a new window in the smoothed rate.
+const RATE_WEIGHT: f64 = 0.25;
+
+/// Throughput of a sender, smoothed over the one-second windows in which
+/// it was busy. Quiet windows are skipped, so an idle connection keeps
+/// the rate it last managed.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SendRate {
+    /// Start of the current window and the bytes sent in it.
+    window: Option<(Instant, u64)>,
+    rate: Option<f64>,
+}
+
+impl SendRate {
+    /// Account for `bytes` going out at `now`.
+    pub fn record(&mut self, now: Instant, bytes: u64) {
+        let Some((start, sent)) = self.window.as_mut() else {
+            self.window = Some((now, bytes));
+            return;
+        };
+        let elapsed = now.saturating_duration_since(*start);
+        if elapsed < RATE_WINDOW {
+            *sent += bytes;
+            return;
+        }
+        // A window that ran long sat idle for part of it.
+        if *sent >= BUSY_BYTES && elapsed < RATE_WINDOW * 2 {
+            let sample = *sent as f64 / elapsed.as_secs_f64();
+            self.rate = Some(match self.rate {
+                Some(rate) => rate * (1.0 - RATE_WEIGHT) + sample * RATE_WEIGHT,
+                None => sample,
+            });
+        }
+        self.window = Some((now, bytes));
+    }
+
+    /// The smoothed rate in bytes a second.
+    pub fn bytes_per_sec(&self) -> Option<u64> {
+        self.rate.map(|rate| rate as u64)
+    }
+}
+
+// ── Clock ────────────────────────────────────────────────────────
+
+/// Source of the current time for [`BandwidthWindow`].
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// ── BandwidthWindow ──────────────────────────────────────────────
+
+/// Width of the buckets the window is made of. Traffic ages out one
+/// bucket at a time, so this is also the resolution of `retry_after`.
+const BUCKET: Duration = Duration::from_secs(60);
+
+/// Sliding-window byte total with an optional cap.
+///
+/// Feed it with [`record`](Self::record) (a delta) or
+/// [`observe_total`](Self::observe_total) (a monotonically growing
+/// counter such as [`ConnectionStats::bytes_sent`]).
+pub struct BandwidthWindow<C: Clock = SystemClock> {
+    clock: C,
+    window: Duration,
+    limit: Option<u64>,
+    /// `(bucket start, bytes)`, oldest first.
+    buckets: VecDeque<(Instant, u64)>,
+    last_total: u64,
+}
+
+impl BandwidthWindow<SystemClock> {
+    /// A one-hour window capped at `limit` bytes (`None` = accounting
+    /// only).
+    pub fn per_hour(limit: Option<u64>) -> Self {
+        Self::with_clock(Duration::from_secs(3600), limit, SystemClock)
+    }
+}
+
+impl<C: Clock> BandwidthWindow<C> {
+    /// A window of the given width driven by `clock`.
+    pub fn with_clock(window: Duration, limit: Option<u64>, clock: C) -> Self {
+        Self {
+            clock,
+            window,
+            limit,
+            buckets: VecDeque::new(),
+            last_total: 0,
+        }
+    }
+
+    /// The configured cap.
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// Add `bytes` at the current time.
+    pub fn record(&mut self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        let now = self.clock.now();
+        self.expire(now);
+        match self.buckets.back_mut() {
+            Some((start, total)) if now.duration_since(*start) < BUCKET => *total += bytes,
+            _ => self.buckets.push_back((now, bytes)),
+        }
+    }
+
+    /// Record whatever a cumulative counter gained since the last call.
+    /// A counter that went backwards (new connection) starts over.
+    pub fn observe_total(&mut self, total: u64) {
+        let delta = total.checked_sub(self.last_total).unwrap_or(total);
+        self.last_total = total;
+        self.record(delta);
+    }
+
+    /// Bytes currently inside the window.
+    pub fn used(&mut self) -> u64 {
+        let now = self.clock.now();
+        self.expire(now);
+        self.buckets.iter().map(|(_, b)| b).sum()
+    }
+
+    /// Whether the cap is reached.
+    pub fn is_exceeded(&mut self) -> bool {
+        match self.limit {
+            Some(limit) => self.used() >= limit,
+            None => false,
+        }
+    }
+
+    /// How long until enough traffic ages out to drop below the cap.
+    /// `None` when the cap is not exceeded.
+    pub fn retry_after(&mut self) -> Option<Duration> {
+        let limit = self.limit?;
+        let now = self.clock.now();
+        let mut used = self.used();
+        if used < limit {
+            return None;
+        }
+        for &(start, bytes) in &self.buckets {
+            used -= bytes;
+            if used < limit {
+                return Some((start + self.window).saturating_duration_since(now));
+            }
+        }
+        Some(self.window)
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(&(start, _)) = self.buckets.front() {
+            if now.duration_since(start) >= self.window {
+                self.buckets.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{HEADER_SIZE, HEADER_SIZE_V2, WireVersion};
+    use std::sync::Arc;
+
+    /// A clock that only moves when told to.
+    #[derive(Clone)]
+    struct ManualClock(Arc<Mutex<Instant>>);
+
+    impl ManualClock {
+        fn new() -> Self {
+            Self(Arc::new(Mutex::new(Instant::now())))
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    const HOUR: Duration = Duration::from_secs(3600);
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn window_slides() {
+        let clock = ManualClock::new();
+        let mut win = BandwidthWindow::with_clock(HOUR, None, clock.clone());
+
+        win.record(100);
+        clock.advance(30 * MINUTE);
+        win.record(50);
+        assert_eq!(win.used(), 150);
+
+        clock.advance(31 * MINUTE);
+        assert_eq!(win.used(), 50);
+        clock.advance(30 * MINUTE);
+        assert_eq!(win.used(), 0);
+    }
+
+    #[test]
+    fn cap_trips_and_releases() {
+        let clock = ManualClock::new();
+        let mut win = BandwidthWindow::with_clock(HOUR, Some(1000), clock.clone());
+
+        win.record(600);
+        clock.advance(10 * MINUTE);
+        win.record(500);
+        assert!(win.is_exceeded());
+        // The first 600 bytes leave the window 50 minutes from now.
+        assert_eq!(win.retry_after(), Some(50 * MINUTE));
+
+        clock.advance(49 * MINUTE);
+        assert!(win.is_exceeded());
+        clock.advance(MINUTE);
+        assert!(!win.is_exceeded());
+        assert_eq!(win.retry_after(), None);
+    }
+
+    #[test]
+    fn observe_total_tracks_deltas() {
+        let clock = ManualClock::new();
+        let mut win = BandwidthWindow::with_clock(HOUR, Some(10), clock);
+
+        win.observe_total(4);
+        win.observe_total(9);
+        assert_eq!(win.used(), 9);
+        // Counter reset (reconnect): the new total is all fresh traffic.
+        win.observe_total(3);
+        assert_eq!(win.used(), 12);
+        assert!(win.is_exceeded());
+    }
+
+    #[test]
+    fn uncapped_window_never_trips() {
+        let mut win = BandwidthWindow::with_clock(HOUR, None, ManualClock::new());
+        win.record(u32::MAX as u64);
+        assert!(!win.is_exceeded());
+        assert_eq!(win.retry_after(), None);
+    }
+
+    #[test]
+    fn connection_stats_per_command() {
+        let stats = ConnectionStats::default();
+        let big = Packet::new_response(1, Command::Download, vec![0; 1000]).unwrap();
+        let small = Packet::new_command(2, Command::Ping, Vec::new()).unwrap();
+
+        stats.record_sent(&big);
+        stats.record_received(&small);
+        stats.record_received(&small);
+
+        assert_eq!(stats.bytes_sent(), (HEADER_SIZE + 1000) as u64);
+        assert_eq!(stats.bytes_received(), 2 * HEADER_SIZE as u64);
+
+        let per = stats.per_command();
+        assert_eq!(per[0].command, "Download");
+        assert_eq!(per[1].command, "Ping");
+        assert_eq!(per[1].packets, 2);
+
+        let mut v2 = Packet::new_command(3, Command::Ping, Vec::new()).unwrap();
+        v2.set_wire_version(WireVersion::V2);
+        stats.record_sent(&v2);
+        assert_eq!(
+            stats.bytes_sent(),
+            (HEADER_SIZE + 1000 + HEADER_SIZE_V2) as u64
+        );
+    }
+
+    #[test]
+    fn send_rate_counts_busy_windows_only() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut rate = SendRate::default();
+        assert_eq!(rate.bytes_per_sec(), None);
+
+        // A trickle of heartbeats says nothing.
+        for s in 0..10 {
+            rate.record(at(s * 1000), 40);
+        }
+        assert_eq!(rate.bytes_per_sec(), None);
+
+        // 100 KiB every 100 ms: 1000 KiB/s.
+        let mut t = 20_000;
+        for _ in 0..30 {
+            rate.record(at(t), 100 * 1024);
+            t += 100;
+        }
+        let busy = rate.bytes_per_sec().unwrap();
+        assert!((990 * 1024..=1010 * 1024).contains(&busy), "{}", busy);
+
+        // A long pause and a small reply leave it alone.
+        rate.record(at(t + 60_000), 40);
+        rate.record(at(t + 62_000), 40);
+        assert_eq!(rate.bytes_per_sec(), Some(busy));
+    }
+}
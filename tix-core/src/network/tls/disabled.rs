@@ -0,0 +1,74 @@
+//! Stand-ins for a build without the `tls` feature. The types exist so
+//! code that mentions them builds either way, but none can be made:
+//! every way to get one fails.
+
+use tokio::net::TcpStream;
+
+use super::{TlsConfig, disabled_problem};
+use crate::error::TixError;
+
+/// The accepting side's stream; there is never a session to wrap.
+pub type ServerStream = TcpStream;
+
+/// The dialling side's stream; there is never a session to wrap.
+pub type ClientStream = TcpStream;
+
+impl TlsConfig {
+    /// Always fails: this build has no TLS.
+    pub fn acceptor(&self) -> Result<TlsAcceptor, TixError> {
+        Err(TixError::Tls(disabled_problem()))
+    }
+
+    /// Always fails: this build has no TLS.
+    pub fn connector(&self) -> Result<TlsConnector, TixError> {
+        Err(TixError::Tls(disabled_problem()))
+    }
+}
+
+/// The accepting side's TLS setup, of which there is none.
+#[derive(Debug, Clone)]
+pub enum TlsAcceptor {}
+
+impl TlsAcceptor {
+    /// SHA-256 fingerprint of the certificate presented.
+    pub fn fingerprint(&self) -> &str {
+        match *self {}
+    }
+
+    /// Run the server half of the handshake on `stream`.
+    pub async fn accept(&self, _stream: TcpStream) -> std::io::Result<ServerStream> {
+        match *self {}
+    }
+}
+
+/// The dialling side's TLS setup, of which there is none.
+#[derive(Debug, Clone)]
+pub enum TlsConnector {}
+
+impl TlsConnector {
+    /// Run the client half of the handshake on `stream`, dialled at
+    /// `host`.
+    pub async fn connect(&self, _host: &str, _stream: TcpStream) -> std::io::Result<ClientStream> {
+        match *self {}
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enabled_tls_is_refused() {
+        let config = TlsConfig {
+            enabled: true,
+            pin_sha256: "ab".repeat(32),
+            ..TlsConfig::default()
+        };
+        assert_eq!(config.connect_problems(), [disabled_problem()]);
+        assert!(matches!(config.acceptor(), Err(TixError::Tls(_))));
+        assert!(matches!(config.connector(), Err(TixError::Tls(_))));
+        assert!(TlsConfig::default().connect_problems().is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+//! TLS for the control connection.
+//!
+//! With `tls.enabled` set, the accepting side (the master, in the usual
+//! topology) presents a certificate and the dialling side checks it
+//! before a single TIX packet is exchanged; [`Connection`] then runs over
+//! the TLS session exactly as it would over bare TCP. There is no
+//! upgrade in the middle of a connection: both ends are configured for
+//! TLS or neither is, and a plaintext peer on the other end fails the
+//! handshake.
+//!
+//! The certificate comes from `tls.cert` and `tls.key`. When neither
+//! file exists yet, [`TlsConfig::acceptor`] writes a self-signed pair
+//! there, so a fresh install has one to pin. The dialling side trusts
+//! either that one certificate, by its SHA-256 fingerprint in
+//! `tls.pin_sha256`, or any certificate a CA in `tls.ca` issued for the
+//! host it dials (or `tls.server_name`).
+//!
+//! Without the `tls` feature the `[tls]` section still parses, but a
+//! config that enables it is refused: [`TlsConfig::connect_problems`]
+//! reports it, and [`TlsConfig::acceptor`] and
+//! [`TlsConfig::connector`] fail.
+//!
+//! ```toml
+//! [tls]
+//! enabled = true
+//! cert = "tix-tls.crt"
+//! key = "tix-tls.key"
+//! pin_sha256 = "3f9a…"   # dialling side; or ca = "ca.pem"
+//! ```
+//!
+//! [`Connection`]: super::Connection
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "tls"))]
+mod disabled;
+#[cfg(feature = "tls")]
+mod session;
+
+#[cfg(not(feature = "tls"))]
+pub use disabled::{ClientStream, ServerStream, TlsAcceptor, TlsConnector};
+#[cfg(feature = "tls")]
+pub use session::{
+    ClientStream, ServerStream, TlsAcceptor, TlsConnector, ensure_certificate, fingerprint,
+    generate_self_signed,
+};
+
+/// How long either side waits for the TLS handshake to finish.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default for `tls.cert`.
+pub const DEFAULT_CERT_PATH: &str = "tix-tls.crt";
+
+/// Default for `tls.key`.
+pub const DEFAULT_KEY_PATH: &str = "tix-tls.key";
+
+// ── Config ───────────────────────────────────────────────────────
+
+/// The `[tls]` section shared by the master's and the slave's config.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Run the control connection over TLS. Both ends must agree.
+    pub enabled: bool,
+    /// Certificate chain (PEM) the accepting side presents.
+    pub cert: PathBuf,
+    /// Private key (PEM) of `cert`.
+    pub key: PathBuf,
+    /// SHA-256 fingerprint of the one certificate the dialling side
+    /// accepts, in hex; colons are allowed.
+    pub pin_sha256: String,
+    /// CA bundle (PEM) the dialling side checks the certificate against
+    /// when nothing is pinned.
+    pub ca: Option<PathBuf>,
+    /// Name the certificate must carry when checked against `ca`;
+    /// defaults to the host dialled.
+    pub server_name: Option<String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert: PathBuf::from(DEFAULT_CERT_PATH),
+            key: PathBuf::from(DEFAULT_KEY_PATH),
+            pin_sha256: String::new(),
+            ca: None,
+            server_name: None,
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Problems with the settings the dialling side uses, named as
+    /// `tls.key`; empty when all is well or TLS is off. A build without
+    /// the `tls` feature has a problem with any config that turns it on.
+    pub fn connect_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !self.enabled {
+            return problems;
+        }
+        if !cfg!(feature = "tls") {
+            problems.push(disabled_problem());
+            return problems;
+        }
+        if !self.pin_sha256.is_empty() {
+            if let Err(e) = parse_fingerprint(&self.pin_sha256) {
+                problems.push(format!("tls.pin_sha256: {e}"));
+            }
+        } else if self.ca.is_none() {
+            problems.push("tls.pin_sha256 or tls.ca must be set to check the peer".to_string());
+        }
+        problems
+    }
+}
+
+/// The 32 bytes of a hex SHA-256 fingerprint, ignoring colons, spaces
+/// and case.
+pub fn parse_fingerprint(text: &str) -> Result<[u8; 32], String> {
+    let digits: Vec<u8> = text.bytes().filter(|b| !matches!(b, b':' | b' ')).collect();
+    if digits.len() != 64 {
+        return Err(format!(
+            "expected 64 hex digits of a SHA-256 fingerprint, got {}",
+            digits.len()
+        ));
+    }
+    let mut out = [0u8; 32];
+    for (byte, pair) in out.iter_mut().zip(digits.chunks_exact(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| "not hex".to_string())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| format!("'{pair}' is not hex"))?;
+    }
+    Ok(out)
+}
+
+/// What is wrong with `tls.enabled` in a build without TLS.
+fn disabled_problem() -> String {
+    "tls.enabled: this build was compiled without TLS support".to_string()
+}
@@ -0,0 +1,349 @@
+//! The TLS sessions themselves, on rustls with the ring provider.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme};
+use tokio::net::TcpStream;
+
+use super::{HANDSHAKE_TIMEOUT, TlsConfig, parse_fingerprint};
+use crate::error::TixError;
+
+/// A TLS session over the accepting side's socket.
+pub type ServerStream = tokio_rustls::server::TlsStream<TcpStream>;
+
+/// A TLS session over the dialling side's socket.
+pub type ClientStream = tokio_rustls::client::TlsStream<TcpStream>;
+
+impl TlsConfig {
+    /// What the accepting side hands each connection to. Writes a
+    /// self-signed certificate to `cert` and `key` if neither exists.
+    pub fn acceptor(&self) -> Result<TlsAcceptor, TixError> {
+        ensure_certificate(&self.cert, &self.key)?;
+        let chain = CertificateDer::pem_file_iter(&self.cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| TixError::Tls(format!("{}: {e}", self.cert.display())))?;
+        if chain.is_empty() {
+            return Err(TixError::Tls(format!(
+                "{}: no certificate in the file",
+                self.cert.display()
+            )));
+        }
+        let key = PrivateKeyDer::from_pem_file(&self.key)
+            .map_err(|e| TixError::Tls(format!("{}: {e}", self.key.display())))?;
+        let fingerprint = fingerprint(&chain[0]);
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| TixError::Tls(e.to_string()))?
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .map_err(|e| TixError::Tls(format!("{}: {e}", self.key.display())))?;
+        Ok(TlsAcceptor {
+            inner: tokio_rustls::TlsAcceptor::from(Arc::new(config)),
+            fingerprint,
+        })
+    }
+
+    /// What the dialling side wraps its socket with: pinned to
+    /// `pin_sha256` if set, else checked against `ca`.
+    pub fn connector(&self) -> Result<TlsConnector, TixError> {
+        let provider = provider();
+        let verifier: Arc<dyn ServerCertVerifier> = if !self.pin_sha256.is_empty() {
+            let pin = parse_fingerprint(&self.pin_sha256)
+                .map_err(|e| TixError::Tls(format!("tls.pin_sha256: {e}")))?;
+            Arc::new(PinnedCert {
+                pin,
+                algorithms: provider.signature_verification_algorithms,
+            })
+        } else if let Some(ca) = &self.ca {
+            let mut roots = RootCertStore::empty();
+            let certs = CertificateDer::pem_file_iter(ca)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .map_err(|e| TixError::Tls(format!("{}: {e}", ca.display())))?;
+            let (added, _) = roots.add_parsable_certificates(certs);
+            if added == 0 {
+                return Err(TixError::Tls(format!(
+                    "{}: no usable CA certificate",
+                    ca.display()
+                )));
+            }
+            WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .map_err(|e| TixError::Tls(format!("{}: {e}", ca.display())))?
+        } else {
+            return Err(TixError::Tls(
+                "tls.pin_sha256 or tls.ca must be set to check the peer".to_string(),
+            ));
+        };
+        let config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| TixError::Tls(e.to_string()))?
+            .dangerous()
+            .with_custom_certificate_verifier(verifier)
+            .with_no_client_auth();
+        Ok(TlsConnector {
+            inner: tokio_rustls::TlsConnector::from(Arc::new(config)),
+            server_name: self.server_name.clone(),
+        })
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+// ── Both ends ────────────────────────────────────────────────────
+
+/// The accepting side's TLS setup. Cloning is cheap.
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    inner: tokio_rustls::TlsAcceptor,
+    fingerprint: String,
+}
+
+impl TlsAcceptor {
+    /// SHA-256 fingerprint of the certificate presented, for the
+    /// dialling side's `tls.pin_sha256`.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// Run the server half of the handshake on `stream`.
+    pub async fn accept(&self, stream: TcpStream) -> std::io::Result<ServerStream> {
+        handshake(self.inner.accept(stream)).await
+    }
+}
+
+impl std::fmt::Debug for TlsAcceptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsAcceptor")
+            .field("fingerprint", &self.fingerprint)
+            .finish()
+    }
+}
+
+/// The dialling side's TLS setup. Cloning is cheap.
+#[derive(Clone)]
+pub struct TlsConnector {
+    inner: tokio_rustls::TlsConnector,
+    server_name: Option<String>,
+}
+
+impl TlsConnector {
+    /// Run the client half of the handshake on `stream`, dialled at
+    /// `host`.
+    pub async fn connect(&self, host: &str, stream: TcpStream) -> std::io::Result<ClientStream> {
+        let name = self.server_name.as_deref().unwrap_or(host);
+        let name = ServerName::try_from(name.to_string()).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{name}: {e}"))
+        })?;
+        handshake(self.inner.connect(name, stream)).await
+    }
+}
+
+impl std::fmt::Debug for TlsConnector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsConnector")
+            .field("server_name", &self.server_name)
+            .finish()
+    }
+}
+
+/// A handshake that gives up after [`HANDSHAKE_TIMEOUT`], so a peer
+/// that connects and says nothing cannot hold the other side.
+async fn handshake<T>(handshake: impl Future<Output = std::io::Result<T>>) -> std::io::Result<T> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake)
+        .await
+        .map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("TLS handshake not done after {HANDSHAKE_TIMEOUT:?}"),
+            )
+        })?
+}
+
+// ── Certificates ─────────────────────────────────────────────────
+
+/// A fresh self-signed certificate for `names`, with its private key,
+/// both in PEM.
+pub fn generate_self_signed(names: &[String]) -> Result<(String, String), TixError> {
+    let certified = rcgen::generate_simple_self_signed(names.to_vec())
+        .map_err(|e| TixError::Tls(format!("cannot generate a certificate: {e}")))?;
+    Ok((certified.cert.pem(), certified.signing_key.serialize_pem()))
+}
+
+/// Write a self-signed certificate to `cert` and its key to `key`
+/// unless one of them already exists. Returns whether it wrote them.
+pub fn ensure_certificate(cert: &Path, key: &Path) -> Result<bool, TixError> {
+    if cert.exists() || key.exists() {
+        return Ok(false);
+    }
+    let host = hostname();
+    let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+    if !host.is_empty() && !names.contains(&host) {
+        names.push(host);
+    }
+    let (cert_pem, key_pem) = generate_self_signed(&names)?;
+    write_private(key, key_pem.as_bytes())
+        .map_err(|e| TixError::Tls(format!("{}: {e}", key.display())))?;
+    std::fs::write(cert, cert_pem)
+        .map_err(|e| TixError::Tls(format!("{}: {e}", cert.display())))?;
+    Ok(true)
+}
+
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_default()
+}
+
+/// Create `path` readable by its owner only, where the platform allows.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents)
+}
+
+/// Lowercase hex SHA-256 of a DER certificate, as `tls.pin_sha256`
+/// takes it.
+pub fn fingerprint(cert: &[u8]) -> String {
+    hex(ring::digest::digest(&ring::digest::SHA256, cert).as_ref())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// ── Pinning ──────────────────────────────────────────────────────
+
+/// Accepts exactly the certificate whose SHA-256 is `pin`, whoever
+/// issued it and whatever names it carries; the handshake signature is
+/// still checked against it.
+#[derive(Debug)]
+struct PinnedCert {
+    pin: [u8; 32],
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for PinnedCert {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let actual = ring::digest::digest(&ring::digest::SHA256, end_entity);
+        if actual.as_ref() == self.pin {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(format!(
+                "certificate fingerprint {} is not the pinned {}",
+                hex(actual.as_ref()),
+                hex(&self.pin)
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprints_parse_with_or_without_colons() {
+        let der = b"not really a certificate";
+        let plain = fingerprint(der);
+        assert_eq!(plain.len(), 64);
+        let colons: Vec<String> = plain
+            .as_bytes()
+            .chunks(2)
+            .map(|p| String::from_utf8(p.to_vec()).unwrap().to_uppercase())
+            .collect();
+        let colons = colons.join(":");
+        assert_eq!(parse_fingerprint(&plain), parse_fingerprint(&colons));
+        assert_eq!(hex(&parse_fingerprint(&plain).unwrap()), plain);
+        assert!(parse_fingerprint("abcd").is_err());
+        assert!(parse_fingerprint(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn dialling_side_needs_a_pin_or_a_ca() {
+        let mut config = TlsConfig {
+            enabled: true,
+            ..TlsConfig::default()
+        };
+        assert_eq!(config.connect_problems().len(), 1);
+        assert!(matches!(config.connector(), Err(TixError::Tls(_))));
+        config.pin_sha256 = "12:34".to_string();
+        assert!(config.connect_problems()[0].starts_with("tls.pin_sha256"));
+        config.pin_sha256 = "ab".repeat(32);
+        assert!(config.connect_problems().is_empty());
+        assert!(config.connector().is_ok());
+    }
+    #[test]
+    fn first_use_writes_a_self_signed_pair_once() {
+        let dir = std::env::temp_dir().join(format!("tix-tls-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = TlsConfig {
+            enabled: true,
+            cert: dir.join("tix.crt"),
+            key: dir.join("tix.key"),
+            ..TlsConfig::default()
+        };
+
+        let first = config.acceptor().unwrap();
+        assert!(config.cert.exists() && config.key.exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&config.key).unwrap().permissions().mode();
+            assert_eq!(mode & 0o077, 0);
+        }
+        // The second start reuses the pair, so a pin stays valid.
+        let second = config.acceptor().unwrap();
+        assert_eq!(first.fingerprint(), second.fingerprint());
+        assert!(!ensure_certificate(&config.cert, &config.key).unwrap());
+
+        std::fs::write(&config.key, "garbage").unwrap();
+        assert!(matches!(config.acceptor(), Err(TixError::Tls(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
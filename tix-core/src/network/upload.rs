@@ -0,0 +1,465 @@
+//! Streaming a local file to the peer as a chunked `FileWrite` upload.
+//!
+//! Both the master console and the remote desktop viewer push files
+//! this way; see `protocol::file` for the packet sequence.
+//!
+//! [`send_file_windowed`] also asks the slave for progress and, once the
+//! first report is in, stops to wait whenever a whole [`AckWindow`] of
+//! chunks is unconfirmed. Whoever reads the replies passes the progress
+//! reports in through the window's channel, and drops the channel when
+//! the slave gives up, which ends the upload at the next chunk.
+//!
+//! A window made [`with_flow`](AckWindow::with_flow) also answers to a
+//! [`Flow`] signal: paused, it sends nothing until resumed; cancelled,
+//! it closes the upload with [`upload_cancel_packet`] so the slave
+//! deletes what it has written.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender, error::TryRecvError};
+use tokio::sync::watch;
+
+use crate::error::TixError;
+use crate::protocol::file::{
+    ACK_EVERY_CHUNKS, ChunkCompressor, DEFAULT_CHUNK_SIZE, FileHashVerification,
+    FileTransferHeader, FileWriteProgress, upload_cancel_packet,
+};
+
+use super::ConnectionSender;
+
+// ── Flow ─────────────────────────────────────────────────────────
+
+/// What the owner of an upload wants it to do next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Flow {
+    #[default]
+    Run,
+    /// Send nothing more until set back to `Run`.
+    Pause,
+    /// Give up and have the slave delete the partial file.
+    Cancel,
+}
+
+// ── Ack window ───────────────────────────────────────────────────
+
+/// How many chunks of a windowed upload may be unconfirmed.
+#[derive(Debug)]
+pub struct AckWindow {
+    size: u64,
+    acks: UnboundedReceiver<FileWriteProgress>,
+    /// Chunks the slave has reported written; `None` until it reports.
+    acked: Option<u64>,
+    flow: Option<watch::Receiver<Flow>>,
+}
+
+impl AckWindow {
+    /// A window of `size` chunks and the sender its progress reports go
+    /// into. The window never drops below the slave's reporting cadence,
+    /// which would stall the upload between reports.
+    pub fn channel(size: u64) -> (UnboundedSender<FileWriteProgress>, Self) {
+        let (tx, acks) = mpsc::unbounded_channel();
+        let window = Self {
+            size: size.max(ACK_EVERY_CHUNKS),
+            acks,
+            acked: None,
+            flow: None,
+        };
+        (tx, window)
+    }
+
+    /// Hold every chunk until `flow` says `Run`, and give up once it
+    /// says `Cancel` or its sender is dropped.
+    pub fn with_flow(mut self, flow: watch::Receiver<Flow>) -> Self {
+        self.flow = Some(flow);
+        self
+    }
+
+    /// Chunks the slave has confirmed so far.
+    pub fn acked(&self) -> Option<u64> {
+        self.acked
+    }
+
+    /// Wait until chunk `index` may be sent.
+    async fn admit(&mut self, index: u64) -> Result<(), TixError> {
+        if let Some(flow) = self.flow.as_mut() {
+            let flow = flow
+                .wait_for(|flow| *flow != Flow::Pause)
+                .await
+                .map_err(|_| cancelled())?;
+            if *flow == Flow::Cancel {
+                return Err(cancelled());
+            }
+        }
+        loop {
+            match self.acks.try_recv() {
+                Ok(progress) => self.record(progress)?,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(ended()),
+            }
+        }
+        while self.acked.is_some_and(|acked| index >= acked + self.size) {
+            let progress = self.acks.recv().await.ok_or_else(ended)?;
+            self.record(progress)?;
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, progress: FileWriteProgress) -> Result<(), TixError> {
+        if progress.has_gaps() {
+            return Err(TixError::Other(format!(
+                "slave is missing chunks {:?}",
+                progress.missing_chunks()
+            )));
+        }
+        self.acked = Some(self.acked.unwrap_or(0).max(progress.chunks_written));
+        Ok(())
+    }
+}
+
+fn ended() -> TixError {
+    TixError::Other("slave ended the upload".to_string())
+}
+
+fn cancelled() -> TixError {
+    TixError::Other("upload cancelled".to_string())
+}
+
+// ── Sending ──────────────────────────────────────────────────────
+
+/// Stream `local` to the peer as `remote`: header, chunks packed by
+/// `compressor`, then the hash. `sent` is bumped by the raw bytes of
+/// every chunk so callers can show progress. Returns the Blake3 hash of
+/// what was sent, uncompressed.
+pub async fn send_file(
+    tx: &ConnectionSender,
+    request_id: u64,
+    local: &Path,
+    remote: String,
+    sent: &AtomicU64,
+    compressor: ChunkCompressor,
+) -> Result<[u8; 32], TixError> {
+    stream_file(tx, request_id, local, remote, sent, compressor, None).await
+}
+
+/// [`send_file`] with progress reports from the slave, holding back
+/// whenever `window` is full.
+pub async fn send_file_windowed(
+    tx: &ConnectionSender,
+    request_id: u64,
+    local: &Path,
+    remote: String,
+    sent: &AtomicU64,
+    compressor: ChunkCompressor,
+    window: &mut AckWindow,
+) -> Result<[u8; 32], TixError> {
+    stream_file(tx, request_id, local, remote, sent, compressor, Some(window)).await
+}
+
+async fn stream_file(
+    tx: &ConnectionSender,
+    request_id: u64,
+    local: &Path,
+    remote: String,
+    sent: &AtomicU64,
+    compressor: ChunkCompressor,
+    mut window: Option<&mut AckWindow>,
+) -> Result<[u8; 32], TixError> {
+    let local_err = |e: std::io::Error| TixError::Other(format!("{}: {e}", local.display()));
+    let mut file = tokio::fs::File::open(local).await.map_err(local_err)?;
+    let size = file.metadata().await.map_err(local_err)?.len();
+    let chunk_size = DEFAULT_CHUNK_SIZE as u32;
+
+    let header = FileTransferHeader {
+        path: remote,
+        size,
+        modified: 0,
+        permissions: 0,
+        is_directory: false,
+        total_chunks: FileTransferHeader::compute_total_chunks(size, chunk_size),
+        chunk_size,
+    };
+    let header = match window {
+        Some(_) => header.into_windowed_upload_packet(request_id)?,
+        None => header.into_upload_packet(request_id)?,
+    };
+    tx.send(header).await?;
+
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0u8; DEFAULT_CHUNK_SIZE];
+    let mut offset = 0u64;
+    let mut index = 0u64;
+    loop {
+        let n = file.read(&mut buf).await.map_err(local_err)?;
+        if n == 0 {
+            break;
+        }
+        if let Some(window) = window.as_deref_mut()
+            && let Err(e) = window.admit(index).await
+        {
+            if window.flow.is_some() {
+                // Whatever ended it, nothing more is coming.
+                tx.send(upload_cancel_packet(request_id)?).await?;
+            }
+            return Err(e);
+        }
+        hasher.update(&buf[..n]);
+        let chunk = compressor.pack(offset, index, buf[..n].to_vec());
+        tx.send(chunk.into_upload_packet(request_id)?).await?;
+        offset += n as u64;
+        index += 1;
+        sent.store(offset, Ordering::Relaxed);
+    }
+
+    let hash = *hasher.finalize().as_bytes();
+    let verification = FileHashVerification::new(hash, offset, index);
+    tx.send(verification.into_upload_packet(request_id)?)
+        .await?;
+    Ok(hash)
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flags::ProtocolFlags;
+    use crate::protocol::file::DEFAULT_UPLOAD_WINDOW;
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn streams_header_chunks_and_hash() {
+        use crate::protocol::file::FileChunk;
+
+        let contents = vec![7u8; DEFAULT_CHUNK_SIZE + 10];
+        let path = std::env::temp_dir().join(format!("tix-send-file-{}.bin", std::process::id()));
+        std::fs::write(&path, &contents).unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let tx = ConnectionSender::from(tx);
+        let sent = AtomicU64::new(0);
+
+        let zstd = ChunkCompressor::new(3);
+        let hash = send_file(&tx, 4, &path, "big.bin".into(), &sent, zstd)
+            .await
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let mut packets = Vec::new();
+        while let Ok(p) = rx.try_recv() {
+            packets.push(p);
+        }
+        assert_eq!(packets.len(), 4);
+        assert!(packets.iter().all(|p| p.request_id() == 4));
+
+        let header = FileTransferHeader::from_bytes(packets[0].payload()).unwrap();
+        assert_eq!(header.total_chunks, 2);
+        let second = FileChunk::from_bytes(packets[2].payload()).unwrap();
+        assert_eq!(second.offset, DEFAULT_CHUNK_SIZE as u64);
+        // Ten bytes do not pay for a zstd frame; the full chunk does.
+        let first = FileChunk::from_bytes(packets[1].payload()).unwrap();
+        assert!(first.compressed && !second.compressed);
+        assert_eq!(first.unpack(header.chunk_size).unwrap().data.len(), DEFAULT_CHUNK_SIZE);
+
+        assert!(packets[3].flags().contains(ProtocolFlags::FINAL_FRAGMENT));
+        let verify = FileHashVerification::from_bytes(packets[3].payload()).unwrap();
+        assert_eq!(verify.blake3_hash, *blake3::hash(&contents).as_bytes());
+        assert_eq!(verify.blake3_hash, hash);
+        assert_eq!(sent.load(Ordering::Relaxed), contents.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn empty_file_is_header_then_hash() {
+        let path = std::env::temp_dir().join(format!("tix-send-empty-{}.bin", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let tx = ConnectionSender::from(tx);
+
+        send_file(
+            &tx,
+            9,
+            &path,
+            "empty.bin".into(),
+            &AtomicU64::new(0),
+            ChunkCompressor::raw(),
+        )
+            .await
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let header = FileTransferHeader::from_bytes(rx.try_recv().unwrap().payload()).unwrap();
+        assert_eq!((header.size, header.total_chunks), (0, 0));
+        let last = rx.try_recv().unwrap();
+        assert!(last.flags().contains(ProtocolFlags::FINAL_FRAGMENT));
+        let verify = FileHashVerification::from_bytes(last.payload()).unwrap();
+        assert_eq!((verify.total_bytes, verify.total_chunks), (0, 0));
+        assert_eq!(verify.blake3_hash, *blake3::hash(b"").as_bytes());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn full_window_waits_for_progress() {
+        let chunks = ACK_EVERY_CHUNKS + 4;
+        let path = std::env::temp_dir().join(format!("tix-send-window-{}.bin", std::process::id()));
+        std::fs::write(&path, vec![1u8; DEFAULT_CHUNK_SIZE * chunks as usize]).unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        let tx = ConnectionSender::from(tx);
+        let (acks, mut window) = AckWindow::channel(1);
+        acks.send(FileWriteProgress::new(0, 0)).unwrap();
+
+        let sent = AtomicU64::new(0);
+        let mut sending = Box::pin(send_file_windowed(
+            &tx,
+            3,
+            &path,
+            "w.bin".into(),
+            &sent,
+            ChunkCompressor::raw(),
+            &mut window,
+        ));
+        // Header plus one window of chunks, then nothing until an ack.
+        let mut packets = Vec::new();
+        while packets.len() < 1 + ACK_EVERY_CHUNKS as usize {
+            tokio::select! {
+                _ = &mut sending => panic!("finished without acks"),
+                p = rx.recv() => packets.push(p.unwrap()),
+            }
+        }
+        assert!(packets[0].flags().contains(ProtocolFlags::ACK_REQUESTED));
+        tokio::select! {
+            _ = &mut sending => panic!("finished without acks"),
+            _ = rx.recv() => panic!("sent past the window"),
+            _ = tokio::time::sleep(std::time::Duration::from_millis(50)) => {}
+        }
+
+        acks.send(FileWriteProgress::new(ACK_EVERY_CHUNKS, 0))
+            .unwrap();
+        let hash = sending.await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        while let Ok(p) = rx.try_recv() {
+            packets.push(p);
+        }
+        assert_eq!(packets.len() as u64, chunks + 2);
+        assert_eq!(
+            FileHashVerification::from_bytes(packets.last().unwrap().payload())
+                .unwrap()
+                .blake3_hash,
+            hash
+        );
+        assert_eq!(window.acked(), Some(ACK_EVERY_CHUNKS));
+    }
+
+    #[tokio::test]
+    async fn ended_or_gappy_uploads_stop_early() {
+        let (acks, mut window) = AckWindow::channel(ACK_EVERY_CHUNKS);
+        acks.send(FileWriteProgress::new(4, 0)).unwrap();
+        window.admit(5).await.unwrap();
+        drop(acks);
+        let err = window.admit(6).await.unwrap_err();
+        assert!(err.to_string().contains("ended"), "{err}");
+
+        let (acks, mut window) = AckWindow::channel(ACK_EVERY_CHUNKS);
+        acks.send(FileWriteProgress::new(4, 0).with_missing([6]))
+            .unwrap();
+        let err = window.admit(7).await.unwrap_err();
+        assert!(err.to_string().contains("[6]"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn paused_uploads_hold_until_resumed() {
+        let path = std::env::temp_dir().join(format!("tix-send-pause-{}.bin", std::process::id()));
+        std::fs::write(&path, vec![2u8; DEFAULT_CHUNK_SIZE * 2]).unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let tx = ConnectionSender::from(tx);
+        let (flow, flow_rx) = watch::channel(Flow::Pause);
+        let (_acks, window) = AckWindow::channel(DEFAULT_UPLOAD_WINDOW);
+        let mut window = window.with_flow(flow_rx);
+
+        let sent = AtomicU64::new(0);
+        let mut sending = Box::pin(send_file_windowed(
+            &tx,
+            5,
+            &path,
+            "f.bin".into(),
+            &sent,
+            ChunkCompressor::raw(),
+            &mut window,
+        ));
+        // The header goes out; no chunk does while paused.
+        tokio::select! {
+            _ = &mut sending => panic!("finished while paused"),
+            p = rx.recv() => assert!(FileTransferHeader::from_bytes(p.unwrap().payload()).is_ok()),
+        }
+        tokio::select! {
+            _ = &mut sending => panic!("finished while paused"),
+            _ = rx.recv() => panic!("sent a chunk while paused"),
+            _ = tokio::time::sleep(std::time::Duration::from_millis(50)) => {}
+        }
+        assert_eq!(sent.load(Ordering::Relaxed), 0);
+
+        flow.send(Flow::Run).unwrap();
+        sending.await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        let mut packets = Vec::new();
+        while let Ok(p) = rx.try_recv() {
+            packets.push(p);
+        }
+        assert_eq!(packets.len(), 3);
+        assert_eq!(sent.load(Ordering::Relaxed), DEFAULT_CHUNK_SIZE as u64 * 2);
+    }
+
+    #[tokio::test]
+    async fn cancelled_uploads_close_with_an_empty_packet() {
+        let path = std::env::temp_dir().join(format!("tix-send-cancel-{}.bin", std::process::id()));
+        std::fs::write(&path, vec![3u8; DEFAULT_CHUNK_SIZE]).unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let tx = ConnectionSender::from(tx);
+        let (_flow, flow_rx) = watch::channel(Flow::Cancel);
+        let (_acks, window) = AckWindow::channel(DEFAULT_UPLOAD_WINDOW);
+        let mut window = window.with_flow(flow_rx);
+
+        let err = send_file_windowed(
+            &tx,
+            6,
+            &path,
+            "f.bin".into(),
+            &AtomicU64::new(0),
+            ChunkCompressor::raw(),
+            &mut window,
+        )
+        .await
+        .unwrap_err();
+        let _ = std::fs::remove_file(&path);
+        assert!(err.to_string().contains("cancelled"), "{err}");
+
+        assert!(FileTransferHeader::from_bytes(rx.try_recv().unwrap().payload()).is_ok());
+        let last = rx.try_recv().unwrap();
+        assert!(last.flags().contains(ProtocolFlags::FINAL_FRAGMENT));
+        assert!(last.payload().is_empty());
+        assert!(rx.try_recv().is_err());
+
+        // A dropped signal gives up the same way.
+        let (flow, flow_rx) = watch::channel(Flow::Pause);
+        let (_acks, window) = AckWindow::channel(DEFAULT_UPLOAD_WINDOW);
+        let mut window = window.with_flow(flow_rx);
+        drop(flow);
+        assert!(window.admit(0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn missing_file_names_the_path() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let tx = ConnectionSender::from(tx);
+        let err = send_file(
+            &tx,
+            1,
+            Path::new("no-such-file.bin"),
+            "x".into(),
+            &AtomicU64::new(0),
+            ChunkCompressor::raw(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("no-such-file.bin"), "{err}");
+    }
+}
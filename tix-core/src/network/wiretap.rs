@@ -0,0 +1,548 @@
+//! Packet-level debug view of a [`Connection`](super::Connection).
+//!
+//! A [`WireTap`] sees every packet the connection writes and reads,
+//! heartbeats included. While it is on, each packet is logged through
+//! `tracing` at debug level under the [`TARGET`] target: direction,
+//! command, request ID, flags, payload length and a hex + ASCII dump of
+//! the first [`dump_bytes`](WireTap::dump_bytes) bytes of the payload.
+//! The header fields are already spelled out, so the dump skips them.
+//!
+//! The tap can also mirror the full packets to a capture file, which
+//! `wire-dump` prints offline:
+//!
+//! ```text
+//! "TIXWIRE1"                                      file magic, once
+//! ┌──────────────┬───────────┬────────────┬─────────────────────────┐
+//! │ micros (u64) │ dir (u8)  │ len (u32)  │ packet bytes (len)      │
+//! └──────────────┴───────────┴────────────┴─────────────────────────┘
+//!   since the Unix epoch, little-endian; dir 0 = sent, 1 = received
+//! ```
+//!
+//! Off, the tap costs the connection one relaxed atomic load per packet.
+
+use std::fmt::{self, Write as _};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::TixError;
+use crate::packet::Packet;
+use crate::protocol::WireTapSettings;
+
+/// `tracing` target of the per-packet lines.
+pub const TARGET: &str = "tix::wire";
+
+/// Payload bytes dumped per packet unless configured otherwise.
+pub const DEFAULT_DUMP_BYTES: usize = 64;
+
+/// First bytes of a capture file.
+pub const CAPTURE_MAGIC: &[u8; 8] = b"TIXWIRE1";
+
+/// Which way a packet went, from the tapped side's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl Direction {
+    fn to_byte(self) -> u8 {
+        match self {
+            Direction::Sent => 0,
+            Direction::Received => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, TixError> {
+        match byte {
+            0 => Ok(Direction::Sent),
+            1 => Ok(Direction::Received),
+            other => Err(TixError::UnknownVariant {
+                type_name: "Direction",
+                value: other as u64,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Direction::Sent => "sent",
+            Direction::Received => "received",
+        })
+    }
+}
+
+// ── WireTap ──────────────────────────────────────────────────────
+
+/// Runtime switch for packet logging and capture on one connection.
+///
+/// Clones share the same switch, so a handle taken from the connection
+/// can be flipped from anywhere while the reader and writer tasks run.
+#[derive(Debug, Clone, Default)]
+pub struct WireTap {
+    inner: Arc<TapState>,
+}
+
+#[derive(Debug)]
+struct TapState {
+    enabled: AtomicBool,
+    dump_bytes: AtomicUsize,
+    capture: Mutex<Option<CaptureWriter>>,
+}
+
+impl Default for TapState {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            dump_bytes: AtomicUsize::new(DEFAULT_DUMP_BYTES),
+            capture: Mutex::new(None),
+        }
+    }
+}
+
+impl WireTap {
+    /// Whether packets are being logged (and captured).
+    pub fn is_enabled(&self) -> bool {
+        self.inner.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Turn the tap on or off. An open capture file stays open but is
+    /// only written while the tap is on.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.inner.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Payload bytes dumped per logged packet.
+    pub fn dump_bytes(&self) -> usize {
+        self.inner.dump_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Dump at most `bytes` payload bytes per logged packet.
+    pub fn set_dump_bytes(&self, bytes: usize) {
+        self.inner.dump_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Mirror packets to the capture file at `path`, replacing any
+    /// capture already running. An existing file is appended to.
+    pub fn start_capture(&self, path: impl AsRef<Path>) -> Result<(), TixError> {
+        let writer = CaptureWriter::open(path)?;
+        *self.capture() = Some(writer);
+        Ok(())
+    }
+
+    /// Close the capture file, if one is open, and return its path.
+    pub fn stop_capture(&self) -> Option<PathBuf> {
+        self.capture().take().map(|writer| writer.path)
+    }
+
+    /// Path of the capture file being written, if any.
+    pub fn capture_path(&self) -> Option<PathBuf> {
+        self.capture().as_ref().map(|writer| writer.path.clone())
+    }
+
+    /// Take on `settings`. The capture file is opened first, so one that
+    /// cannot be opened leaves the tap as it was.
+    pub fn apply(&self, settings: &WireTapSettings) -> Result<(), TixError> {
+        match &settings.capture {
+            Some(path) if self.capture_path().as_deref() != Some(Path::new(path)) => {
+                self.start_capture(path)?;
+            }
+            Some(_) => {}
+            None => {
+                self.stop_capture();
+            }
+        }
+        self.set_dump_bytes(settings.dump_bytes as usize);
+        self.set_enabled(settings.enabled);
+        Ok(())
+    }
+
+    /// The tap's current state, in the form [`apply`](Self::apply) takes.
+    pub fn settings(&self) -> WireTapSettings {
+        WireTapSettings {
+            enabled: self.is_enabled(),
+            dump_bytes: self.dump_bytes().try_into().unwrap_or(u32::MAX),
+            capture: self.capture_path().map(|p| p.display().to_string()),
+        }
+    }
+
+    /// Log `packet` and append it to the capture file, if the tap is on.
+    pub fn observe(&self, direction: Direction, packet: &Packet) {
+        if !self.is_enabled() {
+            return;
+        }
+        if tracing::enabled!(target: TARGET, tracing::Level::DEBUG) {
+            let command = match packet.command() {
+                Ok(command) => command.to_string(),
+                Err(_) => "unknown".to_string(),
+            };
+            tracing::debug!(
+                target: TARGET,
+                %direction,
+                %command,
+                kind = ?packet.message_type(),
+                request_id = packet.request_id(),
+                flags = ?packet.flags(),
+                len = packet.payload().len(),
+                dump = %hex_dump(packet.payload(), self.dump_bytes()),
+            );
+        }
+        let mut capture = self.capture();
+        if let Some(writer) = capture.as_mut()
+            && let Err(e) = writer.write(direction, packet)
+        {
+            tracing::warn!(target: TARGET, "capture to {} stopped: {e}", writer.path.display());
+            *capture = None;
+        }
+    }
+
+    fn capture(&self) -> std::sync::MutexGuard<'_, Option<CaptureWriter>> {
+        self.inner.capture.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// `[wire]` section of the master and slave configs: how every
+/// connection's tap starts out.
+///
+/// ```toml
+/// [wire]
+/// enabled = false
+/// dump_bytes = 64
+/// capture = ""
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WireConfig {
+    /// Log packets from the moment a connection opens.
+    pub enabled: bool,
+    /// Payload bytes dumped per logged packet.
+    pub dump_bytes: u32,
+    /// Capture file to mirror packets to; empty for none.
+    pub capture: String,
+}
+
+impl Default for WireConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dump_bytes: DEFAULT_DUMP_BYTES as u32,
+            capture: String::new(),
+        }
+    }
+}
+
+impl WireConfig {
+    /// The settings to [`apply`](WireTap::apply) to a new connection.
+    pub fn settings(&self) -> WireTapSettings {
+        WireTapSettings {
+            enabled: self.enabled,
+            dump_bytes: self.dump_bytes,
+            capture: (!self.capture.is_empty()).then(|| self.capture.clone()),
+        }
+    }
+}
+
+/// One line of hex followed by the printable ASCII of the same bytes,
+/// at most `limit` of them: `47 45 54 0a |GET.|`. A cut-off dump ends
+/// in `(+N bytes)`.
+pub fn hex_dump(bytes: &[u8], limit: usize) -> String {
+    let shown = &bytes[..bytes.len().min(limit)];
+    let mut out = String::with_capacity(shown.len() * 4 + 16);
+    for byte in shown {
+        let _ = write!(out, "{byte:02x} ");
+    }
+    out.push('|');
+    out.extend(shown.iter().map(|&b| {
+        if b.is_ascii_graphic() || b == b' ' {
+            b as char
+        } else {
+            '.'
+        }
+    }));
+    out.push('|');
+    if bytes.len() > shown.len() {
+        let _ = write!(out, " (+{} bytes)", bytes.len() - shown.len());
+    }
+    out
+}
+
+// ── Capture files ────────────────────────────────────────────────
+
+/// Appends packets to a capture file.
+#[derive(Debug)]
+pub struct CaptureWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+}
+
+impl CaptureWriter {
+    /// Open the file at `path` for appending, creating it with the
+    /// magic if it is new or empty. Reconnects thus add to one file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, TixError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| file_err(&path, e))?;
+        let empty = file.metadata().map_err(|e| file_err(&path, e))?.len() == 0;
+        let mut writer = Self {
+            path,
+            file: BufWriter::new(file),
+        };
+        if empty {
+            writer.append(|file| file.write_all(CAPTURE_MAGIC))?;
+        }
+        Ok(writer)
+    }
+
+    /// Append one record stamped with the current time. Each record is
+    /// flushed, so the file is readable while the connection runs.
+    pub fn write(&mut self, direction: Direction, packet: &Packet) -> Result<(), TixError> {
+        let bytes = packet.to_bytes()?;
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        self.append(|file| {
+            file.write_all(&micros.to_le_bytes())?;
+            file.write_all(&[direction.to_byte()])?;
+            file.write_all(&(bytes.len() as u32).to_le_bytes())?;
+            file.write_all(&bytes)
+        })
+    }
+
+    fn append(
+        &mut self,
+        write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+    ) -> Result<(), TixError> {
+        write(&mut self.file)
+            .and_then(|_| self.file.flush())
+            .map_err(|e| file_err(&self.path, e))
+    }
+}
+
+/// One packet read back from a capture file.
+#[derive(Debug, Clone)]
+pub struct CaptureRecord {
+    /// When the tap saw the packet.
+    pub timestamp: SystemTime,
+    pub direction: Direction,
+    /// The packet as it was on the wire, header and payload.
+    pub bytes: Vec<u8>,
+}
+
+impl CaptureRecord {
+    /// Parse the recorded bytes back into a packet.
+    pub fn packet(&self) -> Result<Packet, TixError> {
+        Packet::from_bytes(&self.bytes)
+    }
+}
+
+/// Reads the records of a capture file in order.
+#[derive(Debug)]
+pub struct CaptureReader<R> {
+    reader: R,
+}
+
+impl CaptureReader<BufReader<File>> {
+    /// Open the capture file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, TixError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| file_err(path, e))?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Read capture records from `reader`, which must start with the
+    /// capture magic.
+    pub fn new(mut reader: R) -> Result<Self, TixError> {
+        let mut magic = [0u8; CAPTURE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != CAPTURE_MAGIC {
+            return Err(TixError::InvalidMagic);
+        }
+        Ok(Self { reader })
+    }
+
+    fn read_record(&mut self) -> Result<Option<CaptureRecord>, TixError> {
+        let mut head = [0u8; 13];
+        match self.reader.read(&mut head[..1])? {
+            0 => return Ok(None),
+            _ => self.reader.read_exact(&mut head[1..])?,
+        }
+        let micros = u64::from_le_bytes(head[..8].try_into().expect("8 bytes"));
+        let direction = Direction::from_byte(head[8])?;
+        let len = u32::from_le_bytes(head[9..].try_into().expect("4 bytes")) as usize;
+        let mut bytes = vec![0u8; len];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(Some(CaptureRecord {
+            timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+            direction,
+            bytes,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<CaptureRecord, TixError>;
+
+    /// The next record; a file cut off mid-record ends in an error.
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+fn file_err(path: &Path, e: std::io::Error) -> TixError {
+    TixError::Other(format!("{}: {e}", path.display()))
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flags::ProtocolFlags;
+    use crate::message::Command;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("tix-wiretap-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn hex_dump_shows_hex_then_ascii() {
+        assert_eq!(hex_dump(b"GET /\n", 64), "47 45 54 20 2f 0a |GET /.|");
+        assert_eq!(hex_dump(&[], 64), "||");
+    }
+
+    #[test]
+    fn hex_dump_stops_at_the_limit() {
+        let dump = hex_dump(&[0x41; 100], 4);
+        assert_eq!(dump, "41 41 41 41 |AAAA| (+96 bytes)");
+        assert_eq!(hex_dump(b"abc", 0), "|| (+3 bytes)");
+    }
+
+    #[test]
+    fn capture_round_trips_through_the_reader() {
+        let path = temp_path("round-trip.cap");
+        let sent = Packet::new_command(7, Command::Ping, b"hello".to_vec()).unwrap();
+        let received = Packet::new_response_with_flags(
+            7,
+            Command::ShellExecute,
+            vec![0xAB; 300],
+            ProtocolFlags::STREAMING,
+        )
+        .unwrap();
+
+        let tap = WireTap::default();
+        tap.set_enabled(true);
+        tap.start_capture(&path).unwrap();
+        tap.observe(Direction::Sent, &sent);
+        tap.observe(Direction::Received, &received);
+        assert_eq!(tap.stop_capture(), Some(path.clone()));
+        // Not captured: the file is closed.
+        tap.observe(Direction::Sent, &sent);
+
+        let records: Vec<CaptureRecord> = CaptureReader::open(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, Direction::Sent);
+        assert_eq!(records[0].bytes, sent.to_bytes().unwrap());
+        assert_eq!(records[1].direction, Direction::Received);
+        let packet = records[1].packet().unwrap();
+        assert_eq!(packet.command().unwrap(), Command::ShellExecute);
+        assert_eq!(packet.flags(), ProtocolFlags::STREAMING);
+        assert_eq!(packet.payload(), received.payload());
+        assert!(records[0].timestamp <= records[1].timestamp);
+    }
+
+    #[test]
+    fn disabled_tap_captures_nothing() {
+        let path = temp_path("disabled.cap");
+        let tap = WireTap::default();
+        tap.start_capture(&path).unwrap();
+        tap.observe(Direction::Sent, &Packet::heartbeat());
+        tap.stop_capture();
+
+        let records = CaptureReader::open(&path).unwrap().count();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records, 0);
+    }
+
+    #[test]
+    fn reopened_capture_appends() {
+        let path = temp_path("append.cap");
+        let tap = WireTap::default();
+        tap.set_enabled(true);
+        for id in 1..=2 {
+            tap.start_capture(&path).unwrap();
+            tap.observe(
+                Direction::Sent,
+                &Packet::new_command(id, Command::Ping, vec![]).unwrap(),
+            );
+            tap.stop_capture();
+        }
+
+        let ids: Vec<u64> = CaptureReader::open(&path)
+            .unwrap()
+            .map(|record| record.unwrap().packet().unwrap().request_id())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(ids, [1, 2]);
+    }
+
+    #[test]
+    fn applying_settings_opens_and_closes_the_capture() {
+        let path = temp_path("apply.cap");
+        let tap = WireTap::default();
+        let settings = WireTapSettings {
+            enabled: true,
+            dump_bytes: 8,
+            capture: Some(path.display().to_string()),
+        };
+        tap.apply(&settings).unwrap();
+        assert_eq!(tap.settings(), settings);
+
+        let off = WireConfig::default().settings();
+        tap.apply(&off).unwrap();
+        assert_eq!(tap.settings(), off);
+        assert_eq!(tap.dump_bytes(), DEFAULT_DUMP_BYTES);
+        std::fs::remove_file(&path).unwrap();
+
+        let missing = WireTapSettings {
+            capture: Some(temp_path("no/such/dir.cap").display().to_string()),
+            ..settings
+        };
+        assert!(tap.apply(&missing).is_err());
+        assert_eq!(tap.settings(), off);
+    }
+
+    #[test]
+    fn truncated_capture_ends_in_an_error() {
+        let mut bytes = CAPTURE_MAGIC.to_vec();
+        bytes.extend_from_slice(&[0u8; 9]);
+        bytes.extend_from_slice(&10u32.to_le_bytes());
+        bytes.extend_from_slice(b"short");
+        let mut reader = CaptureReader::new(bytes.as_slice()).unwrap();
+        assert!(reader.next().unwrap().is_err());
+
+        assert!(matches!(
+            CaptureReader::new(&b"NOTWIRE!"[..]),
+            Err(TixError::InvalidMagic)
+        ));
+    }
+}
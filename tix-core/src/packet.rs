@@ -5,14 +5,14 @@
 
 use crate::error::TixError;
 use crate::flags::ProtocolFlags;
-use crate::header::{HEADER_SIZE, PacketHeader};
+use crate::header::{HEADER_SIZE, MAX_HEADER_SIZE, PacketHeader, WireVersion};
 use crate::message::{Command, MessageType};
 
 /// Maximum payload size (256 KiB).
 pub const MAX_PAYLOAD_SIZE: usize = 256 * 1024;
 
-/// Maximum total frame size (header + payload).
-pub const MAX_FRAME_SIZE: usize = HEADER_SIZE + MAX_PAYLOAD_SIZE;
+/// Maximum total frame size (header of any version + payload).
+pub const MAX_FRAME_SIZE: usize = MAX_HEADER_SIZE + MAX_PAYLOAD_SIZE;
 
 /// A fully assembled TIX packet (header + payload).
 #[derive(Clone)]
@@ -157,9 +157,39 @@ impl Packet {
         self.header.checksum()
     }
 
+    /// Returns the session nonce the packet carries, 0 for none.
+    pub fn session(&self) -> u32 {
+        self.header.session()
+    }
+
+    /// Stamp the packet with session nonce `session`; only a TIX2
+    /// header keeps it.
+    pub fn set_session(&mut self, session: u32) {
+        self.header.set_session(session);
+    }
+
+    /// Returns the header version the packet was read in or will be
+    /// written in.
+    pub fn wire_version(&self) -> WireVersion {
+        self.header.version()
+    }
+
+    /// Returns the size of the packet on the wire, header included.
+    pub fn wire_size(&self) -> usize {
+        self.header.size() + self.payload.len()
+    }
+
+    /// Write the packet with a `version` header. Packets are built as
+    /// TIX1; a connection restamps them once a newer version has been
+    /// negotiated.
+    pub fn set_wire_version(&mut self, version: WireVersion) {
+        self.header.set_version(version);
+    }
+
     // ── Serialization ────────────────────────────────────────────
 
-    /// Serialize the full packet (header + payload) to bytes.
+    /// Serialize the full packet (header + payload) to bytes, in the
+    /// packet's wire version.
     pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
         if self.payload.len() > MAX_PAYLOAD_SIZE {
             return Err(TixError::PayloadTooLarge {
@@ -167,13 +197,14 @@ impl Packet {
                 max: MAX_PAYLOAD_SIZE,
             });
         }
-        let mut buf = Vec::with_capacity(HEADER_SIZE + self.payload.len());
+        let mut buf = Vec::with_capacity(self.wire_size());
         buf.extend_from_slice(&self.header.to_bytes());
         buf.extend_from_slice(&self.payload);
         Ok(buf)
     }
 
-    /// Deserialize a packet from raw bytes (header + payload).
+    /// Deserialize a packet from raw bytes (header + payload), taking
+    /// the wire version from the magic.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
         if bytes.len() < HEADER_SIZE {
             return Err(TixError::InvalidPacketLength {
@@ -182,9 +213,10 @@ impl Packet {
             });
         }
 
-        let header = PacketHeader::from_bytes(&bytes[..HEADER_SIZE])?;
+        let header = PacketHeader::from_bytes(bytes)?;
+        let header_size = header.size();
 
-        let expected_total = HEADER_SIZE + header.payload_length() as usize;
+        let expected_total = header_size + header.payload_length() as usize;
         if bytes.len() != expected_total {
             return Err(TixError::InvalidPacketLength {
                 expected: expected_total,
@@ -199,7 +231,7 @@ impl Packet {
             });
         }
 
-        let payload = bytes[HEADER_SIZE..].to_vec();
+        let payload = bytes[header_size..].to_vec();
 
         Ok(Self { header, payload })
     }
@@ -288,6 +320,30 @@ mod tests {
         assert!(!decoded.validate_checksum());
     }
 
+    #[test]
+    fn v2_roundtrip_matches_v1() {
+        let mut pkt = Packet::new_response_with_flags(
+            9,
+            Command::ShellExecute,
+            b"chunk".to_vec(),
+            ProtocolFlags::STREAMING,
+        )
+        .unwrap();
+        let v1 = Packet::from_bytes(&pkt.to_bytes().unwrap()).unwrap();
+        pkt.set_wire_version(WireVersion::V2);
+        let bytes = pkt.to_bytes().unwrap();
+        assert_eq!(bytes.len(), pkt.wire_size());
+        let v2 = Packet::from_bytes(&bytes).unwrap();
+
+        assert_eq!(v2.wire_version(), WireVersion::V2);
+        assert_eq!(v2.message_type(), v1.message_type());
+        assert_eq!(v2.command().unwrap(), v1.command().unwrap());
+        assert_eq!(v2.flags(), v1.flags());
+        assert_eq!(v2.request_id(), v1.request_id());
+        assert_eq!(v2.payload(), v1.payload());
+        assert!(v2.validate_checksum());
+    }
+
     #[test]
     fn empty_payload_command() {
         let pkt = Packet::new_command(5, Command::Ping, Vec::new()).unwrap();
@@ -0,0 +1,272 @@
+//! Size limits on bulk data sent to the slave: files dropped on the
+//! viewer, pasted in the console's file explorer or named by `Upload`.
+//!
+//! Below [`confirm_bytes`](PasteGuardConfig::confirm_bytes) a transfer
+//! goes ahead without a word. Up to
+//! [`refuse_bytes`](PasteGuardConfig::refuse_bytes) someone has to agree
+//! to it first, told how large it is and how long it should take at the
+//! rate the connection has been sending at (see
+//! [`ConnectionStats::send_rate`](crate::network::ConnectionStats::send_rate)).
+//! Anything larger is refused: a transfer that dies halfway through
+//! starts over, so multi-gigabyte files are better fetched by the slave
+//! with a restartable copy.
+//!
+//! ```
+//! use tix_core::paste_guard::{PasteGuard, PasteGuardConfig, Verdict};
+//!
+//! let guard = PasteGuard::new(&PasteGuardConfig::default());
+//! assert_eq!(guard.check("notes.txt", 4096, None), Verdict::Proceed);
+//! assert!(matches!(guard.check("disk.iso", 4 << 30, None), Verdict::Refuse(_)));
+//! ```
+//!
+//! The consoles only put the question to someone; what to ask and when
+//! is decided here.
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::format::{format_bytes, format_duration};
+
+/// Default size above which a transfer needs confirming (10 MiB).
+pub const DEFAULT_CONFIRM_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Default size above which a transfer is refused (2 GiB).
+pub const DEFAULT_REFUSE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// `[paste_guard]` section of the master and viewer configs.
+///
+/// ```toml
+/// [paste_guard]
+/// confirm_bytes = 10485760
+/// refuse_bytes = 2147483648
+/// ```
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasteGuardConfig {
+    /// Ask before sending more than this many bytes.
+    pub confirm_bytes: u64,
+    /// Refuse to send more than this many bytes.
+    pub refuse_bytes: u64,
+}
+
+impl Default for PasteGuardConfig {
+    fn default() -> Self {
+        Self {
+            confirm_bytes: DEFAULT_CONFIRM_BYTES,
+            refuse_bytes: DEFAULT_REFUSE_BYTES,
+        }
+    }
+}
+
+impl PasteGuardConfig {
+    /// What is wrong with the section, for the config's own checks.
+    pub fn problems(&self) -> Vec<String> {
+        if self.refuse_bytes < self.confirm_bytes {
+            vec![format!(
+                "paste_guard.refuse_bytes ({}) must not be below paste_guard.confirm_bytes ({})",
+                self.refuse_bytes, self.confirm_bytes
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// What to do with a transfer of a given size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Small enough to send at once.
+    Proceed,
+    /// Ask this question first.
+    Confirm(String),
+    /// Do not send it, for this reason.
+    Refuse(String),
+}
+
+/// The thresholds of a [`PasteGuardConfig`], applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasteGuard {
+    confirm_bytes: u64,
+    refuse_bytes: u64,
+}
+
+impl Default for PasteGuard {
+    fn default() -> Self {
+        Self::new(&PasteGuardConfig::default())
+    }
+}
+
+impl PasteGuard {
+    pub fn new(config: &PasteGuardConfig) -> Self {
+        Self {
+            confirm_bytes: config.confirm_bytes,
+            refuse_bytes: config.refuse_bytes,
+        }
+    }
+
+    /// Whether `bytes` is more than may be sent at all.
+    pub fn refuses(&self, bytes: u64) -> bool {
+        bytes > self.refuse_bytes
+    }
+
+    /// Whether `bytes` is more than may be sent without asking.
+    pub fn needs_confirmation(&self, bytes: u64) -> bool {
+        bytes > self.confirm_bytes && !self.refuses(bytes)
+    }
+
+    /// The verdict on sending `what`, `bytes` long, over a connection
+    /// that has been sending at `rate` bytes a second (`None` when it
+    /// has not carried enough to tell).
+    pub fn check(&self, what: &str, bytes: u64, rate: Option<u64>) -> Verdict {
+        if self.refuses(bytes) {
+            Verdict::Refuse(format!(
+                "{} is {}, over the {} limit for sending to the slave; have the slave fetch \
+                 it with a restartable copy instead (ShellExecute robocopy <share> <dir> \
+                 <file> /Z)",
+                what,
+                format_bytes(bytes),
+                format_bytes(self.refuse_bytes)
+            ))
+        } else if self.needs_confirmation(bytes) {
+            Verdict::Confirm(format!(
+                "{} is {}, {}. Send it?",
+                what,
+                format_bytes(bytes),
+                estimate(bytes, rate)
+            ))
+        } else {
+            Verdict::Proceed
+        }
+    }
+}
+
+/// How long `bytes` should take at `rate` bytes a second, for a
+/// prompt: `about 5m 50s at 1.0 MiB/s`.
+pub fn estimate(bytes: u64, rate: Option<u64>) -> String {
+    match rate.filter(|&rate| rate > 0) {
+        Some(rate) => {
+            let secs = bytes as f64 / rate as f64;
+            format!(
+                "about {} at {}/s",
+                format_duration(Duration::from_secs_f64(secs.min(u32::MAX as f64))),
+                format_bytes(rate)
+            )
+        }
+        None => "time unknown until the link has carried a larger transfer".to_string(),
+    }
+}
+
+/// Bytes a transfer of `path` sends: the file's length, or the total of
+/// the files under a folder. What cannot be read counts as nothing.
+pub fn path_size(path: &Path) -> u64 {
+    let Ok(meta) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| path_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    #[test]
+    fn thresholds_split_three_ways() {
+        let guard = PasteGuard::default();
+        assert_eq!(guard.check("a", 0, None), Verdict::Proceed);
+        assert_eq!(
+            guard.check("a", DEFAULT_CONFIRM_BYTES, None),
+            Verdict::Proceed
+        );
+        assert!(matches!(
+            guard.check("a", DEFAULT_CONFIRM_BYTES + 1, None),
+            Verdict::Confirm(_)
+        ));
+        assert!(matches!(
+            guard.check("a", DEFAULT_REFUSE_BYTES, None),
+            Verdict::Confirm(_)
+        ));
+        assert!(matches!(
+            guard.check("a", DEFAULT_REFUSE_BYTES + 1, None),
+            Verdict::Refuse(_)
+        ));
+        assert!(!guard.needs_confirmation(DEFAULT_REFUSE_BYTES + 1));
+    }
+
+    #[test]
+    fn questions_give_the_size_and_the_time_it_takes() {
+        let guard = PasteGuard::default();
+        assert_eq!(
+            guard.check("dump.bin", 350 * MIB, Some(MIB)),
+            Verdict::Confirm("dump.bin is 350.0 MiB, about 5m 50s at 1.0 MiB/s. Send it?".into())
+        );
+        assert_eq!(
+            guard.check("dump.bin", 350 * MIB, None),
+            Verdict::Confirm(
+                "dump.bin is 350.0 MiB, time unknown until the link has carried a larger \
+                 transfer. Send it?"
+                    .into()
+            )
+        );
+        let Verdict::Refuse(msg) = guard.check("disk.iso", 3 << 30, Some(MIB)) else {
+            panic!("a 3 GiB file went through");
+        };
+        assert!(
+            msg.starts_with("disk.iso is 3.0 GiB, over the 2.0 GiB limit"),
+            "{}",
+            msg
+        );
+        assert!(msg.contains("robocopy"), "{}", msg);
+    }
+
+    #[test]
+    fn estimates_survive_odd_rates() {
+        assert_eq!(estimate(MIB, Some(2 * MIB)), "about 500ms at 2.0 MiB/s");
+        assert_eq!(estimate(MIB, Some(0)), estimate(MIB, None));
+        assert!(estimate(u64::MAX, Some(1)).starts_with("about "));
+    }
+
+    #[test]
+    fn thresholds_come_from_the_config() {
+        let config = PasteGuardConfig {
+            confirm_bytes: 0,
+            refuse_bytes: MIB,
+        };
+        assert!(config.problems().is_empty());
+        let guard = PasteGuard::new(&config);
+        assert!(guard.needs_confirmation(1));
+        assert!(guard.refuses(MIB + 1));
+        let backwards = PasteGuardConfig {
+            confirm_bytes: MIB,
+            refuse_bytes: 1,
+        };
+        assert_eq!(backwards.problems().len(), 1);
+    }
+
+    #[test]
+    fn folders_count_the_files_inside() {
+        let dir = std::env::temp_dir().join(format!("tix-paste-guard-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a"), [0u8; 100]).unwrap();
+        std::fs::write(dir.join("sub").join("b"), [0u8; 20]).unwrap();
+        assert_eq!(path_size(&dir.join("a")), 100);
+        assert_eq!(path_size(&dir), 120);
+        assert_eq!(path_size(&dir.join("missing")), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
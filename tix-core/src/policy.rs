@@ -0,0 +1,156 @@
+//! Sets of commands named by pattern, for the config settings that say
+//! which commands they cover: the slave's `audit.commands` and the
+//! master's operator roles.
+//!
+//! A pattern is a command name (`ShellExecute`) or a glob over the names
+//! with `*` and `?` (`File*`, `Screen*`), compared ignoring case. One
+//! starting with `!` takes what it matches back out, wherever it stands
+//! in the list:
+//!
+//! ```
+//! use tix_core::Command;
+//! use tix_core::policy::CommandSet;
+//!
+//! let set = CommandSet::new(&["*", "!Shell*"]);
+//! assert!(set.contains(Command::ListDir));
+//! assert!(!set.contains(Command::ShellExecute));
+//! ```
+
+use std::collections::HashSet;
+
+use crate::config::did_you_mean;
+use crate::message::Command;
+use crate::protocol::search::name_matches;
+
+/// The commands a list of patterns names.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandSet {
+    commands: HashSet<Command>,
+}
+
+impl CommandSet {
+    /// The commands `patterns` name. A pattern that names none adds
+    /// nothing; [`problems`](Self::problems) reports it.
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Self {
+        let (excluded, included): (Vec<&str>, Vec<&str>) = patterns
+            .iter()
+            .map(AsRef::as_ref)
+            .partition(|p| p.starts_with('!'));
+        let commands = Command::ALL
+            .into_iter()
+            .filter(|cmd| included.iter().any(|p| pattern_matches(p, *cmd)))
+            .filter(|cmd| !excluded.iter().any(|p| pattern_matches(&p[1..], *cmd)))
+            .collect();
+        Self { commands }
+    }
+
+    /// Every command.
+    pub fn all() -> Self {
+        Self {
+            commands: Command::ALL.into_iter().collect(),
+        }
+    }
+
+    pub fn contains(&self, cmd: Command) -> bool {
+        self.commands.contains(&cmd)
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// The commands in the set, in ID order.
+    pub fn iter(&self) -> impl Iterator<Item = Command> + '_ {
+        Command::ALL
+            .into_iter()
+            .filter(|cmd| self.commands.contains(cmd))
+    }
+
+    /// One message per pattern that names no command, suggesting the
+    /// name it was probably meant to be.
+    pub fn problems<S: AsRef<str>>(patterns: &[S]) -> Vec<String> {
+        let names: Vec<String> = Command::ALL.iter().map(Command::to_string).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        patterns
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|pattern| {
+                let bare = pattern.strip_prefix('!').unwrap_or(pattern);
+                !Command::ALL
+                    .into_iter()
+                    .any(|cmd| pattern_matches(bare, cmd))
+            })
+            .map(|pattern| {
+                let bare = pattern.strip_prefix('!').unwrap_or(pattern);
+                if bare.contains(['*', '?']) {
+                    return format!("`{}` matches no command", pattern);
+                }
+                match did_you_mean(bare, &names) {
+                    Some(name) => format!("unknown command `{}`; did you mean `{}`?", bare, name),
+                    None => format!("unknown command `{}`", bare),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Whether `pattern`, without its `!`, names `cmd`.
+fn pattern_matches(pattern: &str, cmd: Command) -> bool {
+    let (pattern, name) = (pattern.to_lowercase(), cmd.to_string().to_lowercase());
+    if pattern.contains(['*', '?']) {
+        name_matches(&pattern, &name)
+    } else {
+        pattern == name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_globs_ignore_case() {
+        let set = CommandSet::new(&["ping", "File*", "Screen?tart"]);
+        assert!(set.contains(Command::Ping));
+        assert!(set.contains(Command::FileRead) && set.contains(Command::FileSearch));
+        assert!(set.contains(Command::ScreenStart));
+        assert!(!set.contains(Command::ScreenStop));
+        assert!(!set.contains(Command::ListDir));
+        // A name is the whole name, not a part of it.
+        assert!(!CommandSet::new(&["File"]).contains(Command::FileRead));
+    }
+
+    #[test]
+    fn exclusions_win_wherever_they_stand() {
+        let set = CommandSet::new(&["!ShellExecute", "*", "!System*"]);
+        assert_eq!(set.len(), Command::ALL.len() - 3);
+        assert!(!set.contains(Command::ShellExecute));
+        assert!(!set.contains(Command::SystemAction));
+        assert!(set.contains(Command::ShellCancel));
+        assert!(CommandSet::new(&["!Ping"]).is_empty());
+        assert_eq!(CommandSet::new(&["*"]), CommandSet::all());
+        assert_eq!(
+            CommandSet::new(&["ListDir", "Ping"])
+                .iter()
+                .collect::<Vec<_>>(),
+            [Command::Ping, Command::ListDir]
+        );
+    }
+
+    #[test]
+    fn patterns_naming_nothing_are_problems() {
+        assert!(CommandSet::problems(&["*", "!Shell*", "listdir"]).is_empty());
+        assert_eq!(
+            CommandSet::problems(&["ShellExec", "!Bogus", "Disk*"]),
+            [
+                "unknown command `ShellExec`; did you mean `ShellExecute`?",
+                "unknown command `Bogus`",
+                "`Disk*` matches no command",
+            ]
+        );
+    }
+}
@@ -0,0 +1,452 @@
+//! Event log protocol — recent entries from a Windows event log.
+//!
+//! # Wire Protocol
+//!
+//! ```text
+//! Master ──[EventLogQuery]────────────────────► Slave
+//!   Payload: EventLogQueryRequest (bincode)
+//!
+//! Slave  ──[EventLogQuery + STREAMING]────────► Master   (repeated)
+//!   Payload: EventLogBatch (bincode)
+//!
+//! Slave  ──[EventLogQuery + FINAL_FRAGMENT]───► Master
+//!   Payload: EventLogSummary (bincode)
+//! ```
+//!
+//! Entries come newest first. A query that cannot run at all (no such
+//! log, no right to read it) sends no batches, only a summary carrying
+//! an [`EventLogError`].
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::TixError;
+use crate::flags::ProtocolFlags;
+use crate::message::Command;
+use crate::packet::Packet;
+use crate::protocol::schema::wire_schema;
+
+/// Entries per [`EventLogBatch`].
+pub const EVENTLOG_BATCH_SIZE: usize = 32;
+
+/// Default for [`EventLogQueryRequest::max_entries`].
+pub const DEFAULT_MAX_ENTRIES: u32 = 50;
+
+/// Most entries one query may ask for.
+pub const MAX_ENTRIES_LIMIT: u32 = 1000;
+
+/// Longest message kept per entry, in characters; the rest is cut off
+/// and marked with `…`.
+pub const MAX_MESSAGE_CHARS: usize = 512;
+
+// ── Level ─────────────────────────────────────────────────────────
+
+/// Severity of an event, as the `Level` field of its system section
+/// gives it. Lower is more severe.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventLevel {
+    Critical = 1,
+    Error = 2,
+    Warning = 3,
+    Information = 4,
+    Verbose = 5,
+}
+
+impl EventLevel {
+    /// The level for a raw `Level` value. Providers that log with level
+    /// 0 ("log always") or a custom level are shown as information.
+    pub fn from_raw(level: u8) -> Self {
+        match level {
+            1 => Self::Critical,
+            2 => Self::Error,
+            3 => Self::Warning,
+            5 => Self::Verbose,
+            _ => Self::Information,
+        }
+    }
+}
+
+impl fmt::Display for EventLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Critical => write!(f, "critical"),
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+            Self::Information => write!(f, "info"),
+            Self::Verbose => write!(f, "verbose"),
+        }
+    }
+}
+
+// ── Request ───────────────────────────────────────────────────────
+
+/// Request payload for `Command::EventLogQuery`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EventLogQueryRequest {
+    /// Log (channel) to read, e.g. `System` or `Application`.
+    pub log: String,
+
+    /// Only entries at this level or more severe; `None` for all.
+    pub level: Option<EventLevel>,
+
+    /// Stop after this many entries, at most [`MAX_ENTRIES_LIMIT`].
+    pub max_entries: u32,
+
+    /// Only entries logged at or after this time (Unix milliseconds).
+    pub since_ms: Option<u64>,
+}
+
+impl EventLogQueryRequest {
+    /// The newest [`DEFAULT_MAX_ENTRIES`] entries of `log`, any level.
+    pub fn new(log: impl Into<String>) -> Self {
+        Self {
+            log: log.into(),
+            level: None,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            since_ms: None,
+        }
+    }
+
+    pub fn with_level(mut self, level: Option<EventLevel>) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn with_max_entries(mut self, max_entries: u32) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    pub fn with_since_ms(mut self, since_ms: Option<u64>) -> Self {
+        self.since_ms = since_ms;
+        self
+    }
+
+    /// `max_entries`, kept within 1..=[`MAX_ENTRIES_LIMIT`].
+    pub fn entry_limit(&self) -> usize {
+        self.max_entries.clamp(1, MAX_ENTRIES_LIMIT) as usize
+    }
+
+    /// The XPath filter the Event Log API takes for this request, with
+    /// `since_ms` measured back from `now_ms`.
+    pub fn xpath(&self, now_ms: u64) -> String {
+        let mut conditions = Vec::new();
+        if let Some(level) = self.level {
+            let levels: Vec<String> = (1..=level as u8).map(|l| format!("Level={}", l)).collect();
+            conditions.push(format!("({})", levels.join(" or ")));
+        }
+        if let Some(since) = self.since_ms {
+            conditions.push(format!(
+                "TimeCreated[timediff(@SystemTime) <= {}]",
+                now_ms.saturating_sub(since)
+            ));
+        }
+        if conditions.is_empty() {
+            "*".to_string()
+        } else {
+            format!("*[System[{}]]", conditions.join(" and "))
+        }
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet` carrying this request.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::EventLogQuery, payload)
+    }
+}
+
+// ── Results ───────────────────────────────────────────────────────
+
+/// One event log entry.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EventLogEntry {
+    /// When the event was logged (Unix milliseconds).
+    pub time_ms: u64,
+    pub level: EventLevel,
+    /// Name of the provider (source) that logged it.
+    pub provider: String,
+    pub event_id: u32,
+    /// Rendered message, at most [`MAX_MESSAGE_CHARS`] characters.
+    pub message: String,
+}
+
+/// `message` cut to [`MAX_MESSAGE_CHARS`] characters, with runs of
+/// whitespace (line breaks included) folded to single spaces.
+pub fn truncate_message(message: &str) -> String {
+    let mut out = String::new();
+    let mut chars = 0;
+    for word in message.split_whitespace() {
+        if chars > 0 {
+            out.push(' ');
+            chars += 1;
+        }
+        for c in word.chars() {
+            if chars >= MAX_MESSAGE_CHARS {
+                out.pop();
+                out.push('…');
+                return out;
+            }
+            out.push(c);
+            chars += 1;
+        }
+    }
+    out
+}
+
+/// A batch of entries, streamed from slave to master.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EventLogBatch {
+    /// Sequential batch number (0-based).
+    pub batch_number: u64,
+
+    /// Newest first, continuing from the previous batch.
+    pub entries: Vec<EventLogEntry>,
+}
+
+impl EventLogBatch {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a streaming response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response_with_flags(
+            request_id,
+            Command::EventLogQuery,
+            payload,
+            ProtocolFlags::STREAMING,
+        )
+    }
+}
+
+/// Why an event log query failed on the slave.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum EventLogErrorKind {
+    /// No log has that name.
+    NotFound,
+    /// The slave process may not read the log (Security needs admin).
+    AccessDenied,
+    /// The log service rejected the query filter.
+    InvalidQuery,
+    /// The slave platform has no Windows event log.
+    Unsupported,
+    /// Any other OS error.
+    Other,
+}
+
+impl fmt::Display for EventLogErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "not found"),
+            Self::AccessDenied => write!(f, "access denied"),
+            Self::InvalidQuery => write!(f, "invalid query"),
+            Self::Unsupported => write!(f, "unsupported"),
+            Self::Other => write!(f, "failed"),
+        }
+    }
+}
+
+/// A failed query: what went wrong and the OS's words for it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EventLogError {
+    pub kind: EventLogErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for EventLogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+/// Final message of a query.
+///
+/// Carried in a packet with `FINAL_FRAGMENT` flag set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EventLogSummary {
+    /// Entries sent across all batches.
+    pub entries: u64,
+
+    /// The query stopped at `max_entries`; older matches exist.
+    pub truncated: bool,
+
+    /// Wall time of the query in milliseconds.
+    pub elapsed_ms: u64,
+
+    /// Why the query could not run, or stopped part way.
+    pub error: Option<EventLogError>,
+}
+
+impl EventLogSummary {
+    /// A query that could not start.
+    pub fn failed(kind: EventLogErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            error: Some(EventLogError {
+                kind,
+                message: message.into(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build the final response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response_with_flags(
+            request_id,
+            Command::EventLogQuery,
+            payload,
+            ProtocolFlags::FINAL_FRAGMENT,
+        )
+    }
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    enum EventLevel { Critical, Error, Warning, Information, Verbose }
+}
+wire_schema! {
+    struct EventLogQueryRequest {
+        log: String,
+        level: Option<EventLevel>,
+        max_entries: u32,
+        since_ms: Option<u64>,
+    }
+}
+wire_schema! {
+    struct EventLogEntry {
+        time_ms: u64,
+        level: EventLevel,
+        provider: String,
+        event_id: u32,
+        message: String,
+    }
+}
+wire_schema! {
+    struct EventLogBatch { batch_number: u64, entries: Vec<EventLogEntry> }
+}
+wire_schema! {
+    enum EventLogErrorKind { NotFound, AccessDenied, InvalidQuery, Unsupported, Other }
+}
+wire_schema! {
+    struct EventLogError { kind: EventLogErrorKind, message: String }
+}
+wire_schema! {
+    struct EventLogSummary {
+        entries: u64,
+        truncated: bool,
+        elapsed_ms: u64,
+        error: Option<EventLogError>,
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_roundtrip_and_xpath() {
+        let req = EventLogQueryRequest::new("System")
+            .with_level(Some(EventLevel::Error))
+            .with_max_entries(20)
+            .with_since_ms(Some(1_000));
+        let decoded = EventLogQueryRequest::from_bytes(&req.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded, req);
+        assert_eq!(
+            req.xpath(3_601_000),
+            "*[System[(Level=1 or Level=2) and TimeCreated[timediff(@SystemTime) <= 3600000]]]"
+        );
+
+        let all = EventLogQueryRequest::new("Application");
+        assert_eq!(all.xpath(0), "*");
+        assert_eq!(all.entry_limit(), DEFAULT_MAX_ENTRIES as usize);
+        assert_eq!(all.with_max_entries(0).entry_limit(), 1);
+        assert_eq!(
+            EventLogQueryRequest::new("System")
+                .with_max_entries(u32::MAX)
+                .entry_limit(),
+            MAX_ENTRIES_LIMIT as usize
+        );
+    }
+
+    #[test]
+    fn levels_map_from_raw_values() {
+        assert_eq!(EventLevel::from_raw(1), EventLevel::Critical);
+        assert_eq!(EventLevel::from_raw(3), EventLevel::Warning);
+        assert_eq!(EventLevel::from_raw(0), EventLevel::Information);
+        assert_eq!(EventLevel::from_raw(200), EventLevel::Information);
+        assert!(EventLevel::Critical < EventLevel::Warning);
+    }
+
+    #[test]
+    fn messages_are_folded_and_capped() {
+        assert_eq!(
+            truncate_message("  The service\r\n\r\nstarted.  "),
+            "The service started."
+        );
+        let long = "x".repeat(MAX_MESSAGE_CHARS + 10);
+        let cut = truncate_message(&long);
+        assert_eq!(cut.chars().count(), MAX_MESSAGE_CHARS);
+        assert!(cut.ends_with('…'));
+        let exact = "é".repeat(MAX_MESSAGE_CHARS);
+        assert_eq!(truncate_message(&exact), exact);
+    }
+
+    #[test]
+    fn results_use_streaming_then_final_flags() {
+        let batch = EventLogBatch {
+            batch_number: 0,
+            entries: vec![EventLogEntry {
+                time_ms: 1_700_000_000_000,
+                level: EventLevel::Warning,
+                provider: "Service Control Manager".into(),
+                event_id: 7031,
+                message: "The Print Spooler service terminated unexpectedly.".into(),
+            }],
+        };
+        let pkt = batch.clone().into_packet(9).unwrap();
+        assert!(pkt.flags().contains(ProtocolFlags::STREAMING));
+        assert_eq!(pkt.command().unwrap(), Command::EventLogQuery);
+        assert_eq!(EventLogBatch::from_bytes(pkt.payload()).unwrap(), batch);
+
+        let summary = EventLogSummary::failed(EventLogErrorKind::AccessDenied, "Access is denied.");
+        let pkt = summary.clone().into_packet(9).unwrap();
+        assert!(pkt.flags().contains(ProtocolFlags::FINAL_FRAGMENT));
+        let decoded = EventLogSummary::from_bytes(pkt.payload()).unwrap();
+        assert_eq!(decoded, summary);
+        assert_eq!(
+            decoded.error.unwrap().to_string(),
+            "access denied: Access is denied."
+        );
+    }
+}
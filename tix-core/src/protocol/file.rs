@@ -32,21 +32,124 @@
 //!   Payload: FileTransferAck (bincode)
 //! ```
 //!
+//! A sender that sets `ACK_REQUESTED` on the header asks for progress
+//! while the upload runs:
+//! ```text
+//! Slave  ──[FileWrite + STREAMING]──────────► Master   (repeated)
+//!   Payload: FileWriteProgress (bincode)
+//! ```
+//!
+//! The slave answers the header with a progress of zero, then reports
+//! again every [`ACK_EVERY_CHUNKS`] chunks or [`ACK_INTERVAL`],
+//! whichever comes first. Once the first progress arrives the sender
+//! keeps at most [`DEFAULT_UPLOAD_WINDOW`] chunks beyond the last one
+//! reported in flight; a sender that hears nothing streams freely, as
+//! older slaves never report. A failure is still the one
+//! `FileTransferAck`, sent as soon as the slave gives up, so the sender
+//! can stop without pushing the rest of the file.
+//!
+//! A sender that gives up part way closes the upload with an empty
+//! packet instead of the verification:
+//! ```text
+//! Master ──[FileWrite + FINAL_FRAGMENT]─────► Slave
+//!   Payload: empty
+//! ```
+//!
+//! The slave deletes what it has written so far and answers with a
+//! failed `FileTransferAck` (see [`upload_cancel_packet`]).
+//!
+//! Each `FileChunk` says whether its data is the chunk itself or a zstd
+//! frame of it. A sender compresses only for a peer whose `Hello` said
+//! it takes compressed chunks, and keeps the raw form of any chunk that
+//! compression does not shrink by [`MIN_COMPRESSION_SAVING`] percent, so
+//! one transfer can mix both (see [`ChunkCompressor`]). The receiver
+//! inflates a chunk no further than the transfer's chunk size, and the
+//! `FileHashVerification` is always over the raw bytes. A build without
+//! the `compression` feature sends every chunk raw, says so in its
+//! `Hello`, and refuses a compressed chunk.
+//!
+//! ## File Hash
+//! ```text
+//! Master ──[FileHash]───────────────────────► Slave
+//!   Payload: FileHashRequest (bincode)
+//!
+//! Slave  ──[FileHash]───────────────────────► Master
+//!   Payload: FileHashResponse (bincode)
+//! ```
+//!
 //! ## Delta Sync
 //! ```text
 //! Master ──[FileRead + ACK_REQUESTED]───────► Slave
 //!   Payload: DeltaSyncRequest (bincode)
 //!
-//! Slave  ──[FileRead + STREAMING]───────────► Master
-//!   Payload: DeltaChunkInfo[] (only changed chunks)
+//! Slave  ──[FileRead + STREAMING]───────────► Master   (changed chunks only)
+//!   Payload: FileChunk (bincode)
+//!
+//! Slave  ──[FileRead + FINAL_FRAGMENT]──────► Master
+//!   Payload: FileHashVerification (bincode)
+//! ```
+//!
+//! The master sends the chunk hashes of the copy it already has. The
+//! verification carries the new size and hash: the master resizes its
+//! copy to `total_bytes`, overlays the chunks with [`apply_delta`] and
+//! checks the result. A failure is a plain `FileRead` response carrying
+//! the error text.
+//!
+//! The same exchange repairs a download whose rebuilt copy fails its
+//! hash check: the downloader sends the chunk hashes of its bad copy and
+//! gets back only the chunks that differ (see [`ChunkRepair`]).
+//!
+//! ## Delete / Restore
+//! ```text
+//! Master ──[FileDelete]─────────────────────► Slave
+//!   Payload: FileDeleteRequest (bincode)
+//!
+//! Slave  ──[FileDelete]─────────────────────► Master
+//!   Payload: FileDeleteResponse (bincode)
+//!
+//! Master ──[TrashRestore]───────────────────► Slave
+//!   Payload: TrashRestoreRequest (bincode)
+//!
+//! Slave  ──[TrashRestore]───────────────────► Master
+//!   Payload: TrashRestoreResponse (bincode)
+//! ```
+//!
+//! ## Copy (on the slave)
+//! ```text
+//! Master ──[Copy]───────────────────────────► Slave
+//!   Payload: CopyRequest (bincode)
+//!
+//! Slave  ──[Copy]───────────────────────────► Master
+//!   Payload: UTF-8 result line
+//! ```
+//!
+//! Older masters sent `Copy` as the text `<src> <dest>`; slaves still
+//! accept that when the payload is not a `CopyRequest`. A recursive copy
+//! does not descend into directory links (see [`is_reparse_point`]).
+//!
+//! ## List Dir
+//! ```text
+//! Master ──[ListDir]────────────────────────► Slave
+//!   Payload: UTF-8 path
+//!
+//! Slave  ──[ListDir]────────────────────────► Master
+//!   Payload: PATH|<dir>;<name>|<0 or 1>|<size>|<attributes>;…
 //! ```
+//!
+//! `<attributes>` is the decimal [`FileAttributes`] of the entry. Older
+//! slaves leave it out, which reads as no attributes.
 
-use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use bitflags::bitflags;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::error::TixError;
 use crate::flags::ProtocolFlags;
 use crate::message::Command;
 use crate::packet::Packet;
+use crate::protocol::schema::wire_schema;
+use crate::state::PeerCapabilities;
 
 /// Default chunk size for file transfers (64 KiB).
 pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
@@ -54,6 +157,15 @@ pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
 /// Maximum chunk size (256 KiB — matches MAX_PAYLOAD_SIZE minus overhead).
 pub const MAX_CHUNK_SIZE: usize = 200 * 1024;
 
+/// Upload chunks a sender may have beyond the slave's last progress.
+pub const DEFAULT_UPLOAD_WINDOW: u64 = 64;
+
+/// The slave reports upload progress after this many chunks...
+pub const ACK_EVERY_CHUNKS: u64 = 16;
+
+/// ...or once this long has passed since the last report.
+pub const ACK_INTERVAL: Duration = Duration::from_millis(250);
+
 // ── File Transfer Request ─────────────────────────────────────────
 
 /// Request to read/download a file from the remote.
@@ -157,6 +269,29 @@ impl FileTransferHeader {
         Packet::new_response_with_flags(request_id, command, payload, ProtocolFlags::STREAMING)
     }
 
+    /// Build the first `FileWrite` command packet of an upload.
+    pub fn into_upload_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command_with_flags(
+            request_id,
+            Command::FileWrite,
+            payload,
+            ProtocolFlags::STREAMING,
+        )
+    }
+
+    /// Like [`into_upload_packet`](Self::into_upload_packet), asking the
+    /// slave for [`FileWriteProgress`] as the chunks land.
+    pub fn into_windowed_upload_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command_with_flags(
+            request_id,
+            Command::FileWrite,
+            payload,
+            ProtocolFlags::STREAMING | ProtocolFlags::ACK_REQUESTED,
+        )
+    }
+
     /// Compute the expected number of chunks for a file of given size.
     pub fn compute_total_chunks(file_size: u64, chunk_size: u32) -> u64 {
         if file_size == 0 {
@@ -180,6 +315,20 @@ pub struct FileChunk {
 
     /// The data for this chunk.
     pub data: Vec<u8>,
+
+    /// `data` is a zstd frame of the chunk rather than the chunk itself
+    /// (see [`ChunkCompressor`]). Missing, and so `false`, in a chunk
+    /// from a build that predates it.
+    pub compressed: bool,
+}
+
+/// `FileChunk` as sent before `compressed` was added. Builds that old
+/// ignore the trailing field in ours, and only ever get raw chunks.
+#[derive(Deserialize)]
+struct LegacyFileChunk {
+    offset: u64,
+    chunk_index: u64,
+    data: Vec<u8>,
 }
 
 impl FileChunk {
@@ -189,6 +338,36 @@ impl FileChunk {
             offset,
             chunk_index,
             data,
+            compressed: false,
+        }
+    }
+
+    /// The raw chunk: `data` inflated if it was sent compressed. A
+    /// chunk that would inflate past `chunk_size` bytes (0 = default)
+    /// is refused rather than decompressed, as is every compressed one
+    /// without the `compression` feature.
+    pub fn unpack(self, chunk_size: u32) -> Result<Self, TixError> {
+        if !self.compressed {
+            return Ok(self);
+        }
+        #[cfg(feature = "compression")]
+        {
+            let limit = effective_chunk_size(chunk_size);
+            let data = zstd::bulk::decompress(&self.data, limit).map_err(|e| {
+                TixError::Encoding(format!(
+                    "chunk {} does not inflate to at most {} bytes: {}",
+                    self.chunk_index, limit, e
+                ))
+            })?;
+            Ok(Self::new(self.offset, self.chunk_index, data))
+        }
+        #[cfg(not(feature = "compression"))]
+        {
+            let _ = chunk_size;
+            Err(TixError::Encoding(format!(
+                "chunk {} is compressed, and this build takes raw chunks only",
+                self.chunk_index
+            )))
         }
     }
 
@@ -197,9 +376,16 @@ impl FileChunk {
         bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
     }
 
-    /// Deserialize from bytes.
+    /// Deserialize from bytes, including a chunk from a build that
+    /// predates `compressed`.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
-        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+        let current = match bincode::deserialize(bytes) {
+            Ok(chunk) => return Ok(chunk),
+            Err(e) => e,
+        };
+        let legacy: LegacyFileChunk =
+            bincode::deserialize(bytes).map_err(|_| TixError::Encoding(current.to_string()))?;
+        Ok(Self::new(legacy.offset, legacy.chunk_index, legacy.data))
     }
 
     /// Build a streaming response `Packet`.
@@ -207,6 +393,144 @@ impl FileChunk {
         let payload = self.to_bytes()?;
         Packet::new_response_with_flags(request_id, command, payload, ProtocolFlags::STREAMING)
     }
+
+    /// Build a `FileWrite` data packet for an upload.
+    pub fn into_upload_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command_with_flags(
+            request_id,
+            Command::FileWrite,
+            payload,
+            ProtocolFlags::STREAMING,
+        )
+    }
+}
+
+// ── Chunk Compression ─────────────────────────────────────────────
+
+/// zstd level file chunks are compressed at by default.
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
+/// Highest zstd level a config may ask for.
+pub const MAX_COMPRESSION_LEVEL: i32 = 19;
+
+/// A compressed chunk is only sent when it is at least this many
+/// percent smaller than the raw one.
+pub const MIN_COMPRESSION_SAVING: usize = 5;
+
+/// `[transfer]` section of the master and slave configs.
+///
+/// ```toml
+/// [transfer]
+/// compression_level = 3   # 0 sends every chunk raw
+/// resumable = false       # keep partial downloads to continue later
+/// ```
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransferConfig {
+    /// zstd level chunks are compressed at, 1 to
+    /// [`MAX_COMPRESSION_LEVEL`]; 0 turns compression off.
+    pub compression_level: i32,
+    /// Keep the `.part` file of a cancelled or failed download, and
+    /// continue from it on retry. Only the master downloads this way.
+    pub resumable: bool,
+}
+
+impl Default for TransferConfig {
+    fn default() -> Self {
+        Self {
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            resumable: false,
+        }
+    }
+}
+
+impl TransferConfig {
+    /// What is wrong with the section, for the config's own checks.
+    pub fn problems(&self) -> Vec<String> {
+        if (0..=MAX_COMPRESSION_LEVEL).contains(&self.compression_level) {
+            Vec::new()
+        } else {
+            vec![format!(
+                "transfer.compression_level ({}) must be between 0 and {}",
+                self.compression_level, MAX_COMPRESSION_LEVEL
+            )]
+        }
+    }
+
+    /// The compressor the section asks for.
+    pub fn compressor(&self) -> ChunkCompressor {
+        ChunkCompressor::new(self.compression_level)
+    }
+}
+
+/// How a sender packs file chunks.
+///
+/// Each chunk is compressed on its own and sent compressed only when
+/// that saves at least [`MIN_COMPRESSION_SAVING`] percent, so a text
+/// file goes over small while a zip or a video goes over raw, without
+/// the receiver needing to know which to expect: every [`FileChunk`]
+/// says which form it is in. Compress only for a peer whose `Hello`
+/// said it takes compressed chunks ([`for_peer`](Self::for_peer)).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChunkCompressor {
+    /// `None` sends every chunk raw.
+    level: Option<i32>,
+}
+
+impl ChunkCompressor {
+    /// Compress at zstd `level`; 0 or less sends every chunk raw, as
+    /// does any level without the `compression` feature.
+    pub fn new(level: i32) -> Self {
+        Self {
+            level: (cfg!(feature = "compression") && level > 0)
+                .then_some(level.min(MAX_COMPRESSION_LEVEL)),
+        }
+    }
+
+    /// Send every chunk raw.
+    pub fn raw() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.level.is_some()
+    }
+
+    /// This compressor if the negotiated `capabilities` include
+    /// compression, else [`raw`](Self::raw). No handshake yet counts as
+    /// no compression.
+    pub fn for_peer(self, capabilities: Option<&PeerCapabilities>) -> Self {
+        match capabilities {
+            Some(caps) if caps.compression => self,
+            _ => Self::raw(),
+        }
+    }
+
+    /// The chunk of `data` at `offset`, compressed if that pays off.
+    #[cfg(feature = "compression")]
+    pub fn pack(&self, offset: u64, chunk_index: u64, data: Vec<u8>) -> FileChunk {
+        let Some(level) = self.level else {
+            return FileChunk::new(offset, chunk_index, data);
+        };
+        match zstd::bulk::compress(&data, level) {
+            Ok(packed) if packed.len() * 100 <= data.len() * (100 - MIN_COMPRESSION_SAVING) => {
+                FileChunk {
+                    offset,
+                    chunk_index,
+                    data: packed,
+                    compressed: true,
+                }
+            }
+            _ => FileChunk::new(offset, chunk_index, data),
+        }
+    }
+
+    /// The chunk of `data` at `offset`; always raw in this build.
+    #[cfg(not(feature = "compression"))]
+    pub fn pack(&self, offset: u64, chunk_index: u64, data: Vec<u8>) -> FileChunk {
+        FileChunk::new(offset, chunk_index, data)
+    }
 }
 
 // ── File Metadata ─────────────────────────────────────────────────
@@ -231,6 +555,9 @@ pub struct FileMetadata {
 
     /// Optional Blake3 hash of contents.
     pub hash: Option<[u8; 32]>,
+
+    /// Hidden, system, read-only and link flags of the entry.
+    pub attributes: FileAttributes,
 }
 
 impl FileMetadata {
@@ -245,6 +572,75 @@ impl FileMetadata {
     }
 }
 
+// ── File Attributes ───────────────────────────────────────────────
+
+bitflags! {
+    /// Windows attributes of a listed entry, with the values of the
+    /// matching `FILE_ATTRIBUTE_*` constants. Entries on other platforms
+    /// carry none.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct FileAttributes: u32 {
+        const READONLY      = 0x0000_0001;
+        const HIDDEN        = 0x0000_0002;
+        const SYSTEM        = 0x0000_0004;
+        /// A junction, symbolic link, mount point or cloud placeholder.
+        const REPARSE_POINT = 0x0000_0400;
+        const COMPRESSED    = 0x0000_0800;
+    }
+}
+
+impl FileAttributes {
+    /// The attributes recorded in `meta`. Take it from
+    /// `symlink_metadata` or a directory entry so a link reports itself
+    /// rather than its target.
+    pub fn of(meta: &std::fs::Metadata) -> Self {
+        #[cfg(windows)]
+        {
+            use std::os::windows::fs::MetadataExt;
+            Self::from_bits_truncate(meta.file_attributes())
+        }
+        #[cfg(not(windows))]
+        {
+            let _ = meta;
+            Self::empty()
+        }
+    }
+
+    /// Parse the decimal field of a `ListDir` entry. Anything else,
+    /// including a missing field, reads as no attributes.
+    pub fn from_field(field: Option<&str>) -> Self {
+        field
+            .and_then(|f| f.parse().ok())
+            .map_or_else(Self::empty, Self::from_bits_truncate)
+    }
+
+    /// Whether the entry is hidden from normal listings.
+    pub fn is_hidden(self) -> bool {
+        self.contains(Self::HIDDEN)
+    }
+}
+
+impl Serialize for FileAttributes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.bits().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileAttributes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(Self::from_bits_truncate)
+    }
+}
+
+/// Whether a recursive walk must step over the entry `meta` describes
+/// instead of descending into it: a symbolic link, junction or other
+/// reparse point may lead back up the tree. `meta` must not follow
+/// links, as with [`FileAttributes::of`].
+pub fn is_reparse_point(meta: &std::fs::Metadata) -> bool {
+    meta.file_type().is_symlink()
+        || FileAttributes::of(meta).contains(FileAttributes::REPARSE_POINT)
+}
+
 // ── File Hash Verification ────────────────────────────────────────
 
 /// Final verification payload sent after all file chunks.
@@ -287,6 +683,149 @@ impl FileHashVerification {
         let payload = self.to_bytes()?;
         Packet::new_response_with_flags(request_id, command, payload, ProtocolFlags::FINAL_FRAGMENT)
     }
+
+    /// Build the closing `FileWrite` packet of an upload.
+    pub fn into_upload_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command_with_flags(
+            request_id,
+            Command::FileWrite,
+            payload,
+            ProtocolFlags::FINAL_FRAGMENT,
+        )
+    }
+}
+
+/// Build the packet that abandons upload `request_id`: a `FileWrite`
+/// with `FINAL_FRAGMENT` and no payload, in place of the verification.
+pub fn upload_cancel_packet(request_id: u64) -> Result<Packet, TixError> {
+    Packet::new_command_with_flags(
+        request_id,
+        Command::FileWrite,
+        Vec::new(),
+        ProtocolFlags::FINAL_FRAGMENT,
+    )
+}
+
+// ── File Transfer Ack ─────────────────────────────────────────────
+
+/// The slave's verdict on a finished (or aborted) upload.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileTransferAck {
+    /// Where the file was written on the slave.
+    pub path: String,
+
+    /// Bytes written to disk.
+    pub bytes_written: u64,
+
+    /// `None` on success, otherwise why the upload was rejected. A
+    /// partially written file is removed before a failure is reported.
+    pub error: Option<String>,
+}
+
+impl FileTransferAck {
+    /// Whether the upload landed intact.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build the `FileWrite` response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::FileWrite, payload)
+    }
+}
+
+// ── File Write Progress ───────────────────────────────────────────
+
+/// How far an upload has got on the slave: a cumulative ack.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileWriteProgress {
+    /// Chunks written without a gap, from chunk 0.
+    pub chunks_written: u64,
+
+    /// Bytes those chunks hold.
+    pub bytes_written: u64,
+
+    /// Chunks after the contiguous run that have not arrived: bit `i`
+    /// (least significant first) stands for chunk `chunks_written + i`.
+    /// Empty when nothing after the run has arrived either.
+    pub missing: Vec<u8>,
+}
+
+impl FileWriteProgress {
+    pub fn new(chunks_written: u64, bytes_written: u64) -> Self {
+        Self {
+            chunks_written,
+            bytes_written,
+            missing: Vec::new(),
+        }
+    }
+
+    /// Mark `chunks` as missing; indices inside the contiguous run are
+    /// ignored.
+    pub fn with_missing(mut self, chunks: impl IntoIterator<Item = u64>) -> Self {
+        for chunk in chunks {
+            let Some(bit) = chunk.checked_sub(self.chunks_written) else {
+                continue;
+            };
+            let byte = (bit / 8) as usize;
+            if self.missing.len() <= byte {
+                self.missing.resize(byte + 1, 0);
+            }
+            self.missing[byte] |= 1 << (bit % 8);
+        }
+        self
+    }
+
+    /// Whether the slave is waiting on chunks it has passed over.
+    pub fn has_gaps(&self) -> bool {
+        self.missing.iter().any(|b| *b != 0)
+    }
+
+    /// The chunk indices [`missing`](Self::missing) stands for, in order.
+    pub fn missing_chunks(&self) -> Vec<u64> {
+        self.missing
+            .iter()
+            .enumerate()
+            .flat_map(|(byte, bits)| {
+                (0..8)
+                    .filter(move |bit| bits & (1 << bit) != 0)
+                    .map(move |bit| self.chunks_written + byte as u64 * 8 + bit)
+            })
+            .collect()
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build the streaming `FileWrite` response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response_with_flags(
+            request_id,
+            Command::FileWrite,
+            payload,
+            ProtocolFlags::STREAMING,
+        )
+    }
 }
 
 // ── Delta Sync ────────────────────────────────────────────────────
@@ -307,6 +846,41 @@ pub struct DeltaSyncRequest {
 }
 
 impl DeltaSyncRequest {
+    /// Ask for the chunks of `path` that differ from `digest`.
+    pub fn new(path: impl Into<String>, digest: &FileDigest) -> Self {
+        Self {
+            path: path.into(),
+            chunk_size: digest.chunk_size,
+            chunk_hashes: digest.chunks.clone(),
+        }
+    }
+
+    /// The chunks of `data` the requester does not already have.
+    pub fn changed_chunks(&self, data: &[u8]) -> Vec<FileChunk> {
+        differing_chunks(&self.chunk_hashes, data, self.chunk_size)
+    }
+
+    /// The verification that closes the reply for the current `data`.
+    pub fn verification(&self, data: &[u8]) -> FileHashVerification {
+        let chunk_size = effective_chunk_size(self.chunk_size);
+        FileHashVerification::new(
+            *blake3::hash(data).as_bytes(),
+            data.len() as u64,
+            data.len().div_ceil(chunk_size) as u64,
+        )
+    }
+
+    /// Build the `FileRead` command `Packet` with `ACK_REQUESTED` set.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command_with_flags(
+            request_id,
+            Command::FileRead,
+            payload,
+            ProtocolFlags::ACK_REQUESTED,
+        )
+    }
+
     /// Serialize to bytes.
     pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
         bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
@@ -345,71 +919,642 @@ impl DeltaChunkInfo {
     }
 }
 
-// ── Helpers ───────────────────────────────────────────────────────
-
-/// Classify a file transfer response packet by its flags.
-pub fn classify_file_response(packet: &Packet) -> FileResponseKind {
-    let flags = packet.flags();
-    if flags.contains(ProtocolFlags::FINAL_FRAGMENT) {
-        FileResponseKind::HashVerification
-    } else if flags.contains(ProtocolFlags::STREAMING) {
-        FileResponseKind::StreamingChunk
-    } else {
-        FileResponseKind::SingleResponse
+/// Rebuild a file from the previous copy `base` and the changed
+/// `chunks`, resized to `total_bytes`.
+pub fn apply_delta(base: &[u8], chunks: &[FileChunk], total_bytes: u64) -> Vec<u8> {
+    let total = total_bytes as usize;
+    let mut data = base[..base.len().min(total)].to_vec();
+    data.resize(total, 0);
+    for chunk in chunks {
+        let start = (chunk.offset as usize).min(total);
+        let end = (start + chunk.data.len()).min(total);
+        data[start..end].copy_from_slice(&chunk.data[..end - start]);
     }
+    data
 }
 
-/// Classification of a file transfer response.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum FileResponseKind {
-    /// A streaming chunk (header or data chunk).
-    StreamingChunk,
-    /// The final hash verification packet.
-    HashVerification,
-    /// A single non-streaming response (ack, error, etc.).
-    SingleResponse,
+/// The chunks of `data`, cut every `chunk_size` bytes (0 = default),
+/// that differ from `known`, the chunk hashes of another copy cut the
+/// same way: by hash, by length, or by lying past the end of that copy.
+pub fn differing_chunks(known: &[DeltaChunkInfo], data: &[u8], chunk_size: u32) -> Vec<FileChunk> {
+    let chunk_size = effective_chunk_size(chunk_size);
+    data.chunks(chunk_size)
+        .enumerate()
+        .filter(|(index, chunk)| {
+            known.get(*index).is_none_or(|known| {
+                known.length as usize != chunk.len()
+                    || known.hash != *blake3::hash(chunk).as_bytes()
+            })
+        })
+        .map(|(index, chunk)| {
+            FileChunk::new((index * chunk_size) as u64, index as u64, chunk.to_vec())
+        })
+        .collect()
 }
 
-// ── Tests ─────────────────────────────────────────────────────────
+fn effective_chunk_size(chunk_size: u32) -> usize {
+    match chunk_size as usize {
+        0 => DEFAULT_CHUNK_SIZE,
+        n => n.min(MAX_CHUNK_SIZE),
+    }
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// ── Repair ────────────────────────────────────────────────────────
 
-    #[test]
-    fn file_transfer_request_roundtrip() {
-        let req = FileTransferRequest::download("/home/user/file.txt").with_chunk_size(32768);
+/// Repair rounds a download gets by default once its hash mismatches.
+pub const DEFAULT_REPAIR_ROUNDS: u32 = 2;
 
-        let bytes = req.to_bytes().unwrap();
-        let decoded = FileTransferRequest::from_bytes(&bytes).unwrap();
-        assert_eq!(req, decoded);
-        assert_eq!(decoded.path, "/home/user/file.txt");
-        assert_eq!(decoded.chunk_size, 32768);
-        assert!(!decoded.delta_sync);
+/// Repair of a downloaded copy that does not match the sender's
+/// [`FileHashVerification`].
+///
+/// Usually only a chunk or two went bad, so instead of fetching the file
+/// again each round is a delta sync with the bad copy as its base: the
+/// [`DeltaSyncRequest`] carries the chunk hashes of the copy as it
+/// stands, the sender returns the chunks that differ and a fresh
+/// verification, and [`apply_delta`] patches them in. Once `max_rounds`
+/// rounds have still ended in a mismatch, [`check`](Self::check) gives
+/// up with [`TixError::RepairFailed`], naming the chunks the last round
+/// brought in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkRepair {
+    path: String,
+    chunk_size: u32,
+    max_rounds: u32,
+    rounds: u32,
+    /// Chunk indices the transfer or latest round brought in.
+    last: Vec<u64>,
+}
+
+impl ChunkRepair {
+    /// Repair of `path`, downloaded in chunks of `chunk_size` bytes.
+    pub fn new(path: impl Into<String>, chunk_size: u32, max_rounds: u32) -> Self {
+        Self {
+            path: path.into(),
+            chunk_size,
+            max_rounds,
+            rounds: 0,
+            last: Vec::new(),
+        }
     }
 
-    #[test]
-    fn file_transfer_request_with_delta() {
-        let hash = [0xABu8; 32];
-        let req = FileTransferRequest::download("file.bin").with_delta_sync(hash);
+    /// Size of the chunks the file is downloaded in.
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
 
-        let bytes = req.to_bytes().unwrap();
-        let decoded = FileTransferRequest::from_bytes(&bytes).unwrap();
-        assert!(decoded.delta_sync);
-        assert_eq!(decoded.local_hash.unwrap(), hash);
+    /// Repair rounds started so far.
+    pub fn rounds(&self) -> u32 {
+        self.rounds
     }
 
-    #[test]
-    fn file_transfer_header_roundtrip() {
-        let header = FileTransferHeader {
-            path: "C:\\data\\report.pdf".to_string(),
-            size: 1_048_576,
-            modified: 1700000000,
-            permissions: 0o644,
-            is_directory: false,
-            total_chunks: 16,
-            chunk_size: DEFAULT_CHUNK_SIZE as u32,
-        };
+    /// Note the chunks the transfer or a repair round brought in.
+    pub fn received(&mut self, chunks: &[FileChunk]) {
+        self.last = chunks.iter().map(|c| c.chunk_index).collect();
+    }
+
+    /// Chunk indices the latest round brought in: after a repair, the
+    /// chunks it replaced.
+    pub fn last_chunks(&self) -> &[u64] {
+        &self.last
+    }
+
+    /// Judge `data`, the copy rebuilt so far, against `verification`:
+    /// `None` when it matches, the request for the next round while
+    /// rounds remain, and an error once they are used up.
+    pub fn check(
+        &mut self,
+        data: &[u8],
+        verification: &FileHashVerification,
+    ) -> Result<Option<DeltaSyncRequest>, TixError> {
+        if data.len() as u64 == verification.total_bytes
+            && *blake3::hash(data).as_bytes() == verification.blake3_hash
+        {
+            return Ok(None);
+        }
+        if self.rounds >= self.max_rounds {
+            return Err(TixError::RepairFailed {
+                path: self.path.clone(),
+                rounds: self.rounds,
+                chunks: self.last.clone(),
+            });
+        }
+        self.rounds += 1;
+        let digest = FileDigest::of(data, self.chunk_size);
+        Ok(Some(DeltaSyncRequest::new(self.path.clone(), &digest)))
+    }
+}
+
+// ── File Hash ─────────────────────────────────────────────────────
+
+/// Ask the slave for the [`FileDigest`] of a file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileHashRequest {
+    /// Remote path to hash.
+    pub path: String,
+
+    /// Chunk size for the per-chunk hashes (0 = use default).
+    pub chunk_size: u32,
+}
+
+impl FileHashRequest {
+    /// Hash `path` in default-sized chunks.
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            chunk_size: DEFAULT_CHUNK_SIZE as u32,
+        }
+    }
+
+    /// Set custom chunk size.
+    pub fn with_chunk_size(mut self, size: u32) -> Self {
+        self.chunk_size = size;
+        self
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::FileHash, payload)
+    }
+}
+
+/// Blake3 hashes of a file, whole and per chunk.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileDigest {
+    /// File size in bytes.
+    pub size: u64,
+
+    /// Blake3 hash of the complete contents.
+    pub hash: [u8; 32],
+
+    /// Chunk size the chunk hashes were computed with.
+    pub chunk_size: u32,
+
+    /// One entry per chunk, in order.
+    pub chunks: Vec<DeltaChunkInfo>,
+}
+
+impl FileDigest {
+    /// Hash `data` in chunks of `chunk_size` bytes (0 = use default).
+    pub fn of(data: &[u8], chunk_size: u32) -> Self {
+        let size = effective_chunk_size(chunk_size);
+        let chunks = data
+            .chunks(size)
+            .enumerate()
+            .map(|(index, chunk)| {
+                DeltaChunkInfo::new(
+                    index as u64,
+                    (index * size) as u64,
+                    chunk.len() as u32,
+                    *blake3::hash(chunk).as_bytes(),
+                )
+            })
+            .collect();
+        Self {
+            size: data.len() as u64,
+            hash: *blake3::hash(data).as_bytes(),
+            chunk_size: size as u32,
+            chunks,
+        }
+    }
+}
+
+/// Reply to [`FileHashRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum FileHashResponse {
+    /// The file was read and hashed.
+    Hashed(FileDigest),
+    /// The file could not be read.
+    Failed(String),
+}
+
+impl FileHashResponse {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::FileHash, payload)
+    }
+}
+
+// ── Delete / Trash ────────────────────────────────────────────────
+
+/// How a [`FileDeleteRequest`] disposes of its targets.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Move to the Recycle Bin so the delete can be undone.
+    #[default]
+    Recycle,
+    /// Remove immediately; not recoverable.
+    Permanent,
+}
+
+impl DeleteMode {
+    /// Short phrase for confirmations and logs, e.g. "to the Recycle Bin".
+    pub fn describe(self) -> &'static str {
+        match self {
+            DeleteMode::Recycle => "to the Recycle Bin",
+            DeleteMode::Permanent => "permanently",
+        }
+    }
+}
+
+/// Delete one or more remote paths (directories recursively).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileDeleteRequest {
+    /// Paths to delete.
+    pub paths: Vec<String>,
+    /// Recycle (default) or permanent delete.
+    pub mode: DeleteMode,
+}
+
+impl FileDeleteRequest {
+    /// Recycle `paths`.
+    pub fn new(paths: Vec<String>) -> Self {
+        Self {
+            paths,
+            mode: DeleteMode::Recycle,
+        }
+    }
+
+    /// Set the delete mode.
+    pub fn with_mode(mut self, mode: DeleteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::FileDelete, payload)
+    }
+}
+
+/// What happened to a single path in a [`FileDeleteResponse`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum DeleteOutcome {
+    /// Moved to the Recycle Bin.
+    Recycled,
+    /// Removed permanently.
+    Deleted,
+    /// Left in place.
+    Failed(String),
+}
+
+/// Per-path result of a delete.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeletedItem {
+    pub path: String,
+    pub outcome: DeleteOutcome,
+}
+
+/// Reply to [`FileDeleteRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileDeleteResponse {
+    /// Mode the master asked for.
+    pub requested: DeleteMode,
+    /// One entry per requested path, in request order.
+    pub items: Vec<DeletedItem>,
+    /// Set when the slave could not honour the requested mode (e.g. no
+    /// Recycle Bin on this platform) and explains what it did instead.
+    pub note: Option<String>,
+}
+
+impl FileDeleteResponse {
+    /// Whether a recycle request ended up deleting anything permanently.
+    pub fn fell_back_to_permanent(&self) -> bool {
+        self.requested == DeleteMode::Recycle
+            && self
+                .items
+                .iter()
+                .any(|i| i.outcome == DeleteOutcome::Deleted)
+    }
+
+    /// Number of paths that were not removed.
+    pub fn failed_count(&self) -> usize {
+        self.items
+            .iter()
+            .filter(|i| matches!(i.outcome, DeleteOutcome::Failed(_)))
+            .count()
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::FileDelete, payload)
+    }
+}
+
+/// Restore the most recently recycled item that used to live at
+/// `original_path`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrashRestoreRequest {
+    pub original_path: String,
+}
+
+impl TrashRestoreRequest {
+    pub fn new(original_path: impl Into<String>) -> Self {
+        Self {
+            original_path: original_path.into(),
+        }
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::TrashRestore, payload)
+    }
+}
+
+/// Reply to [`TrashRestoreRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum TrashRestoreResponse {
+    /// The item is back at its original path.
+    Restored { path: String },
+    /// Nothing in the Recycle Bin came from that path.
+    NotFound(String),
+    /// The slave has no Recycle Bin.
+    Unsupported(String),
+    /// Found, but could not be moved back.
+    Failed(String),
+}
+
+impl TrashRestoreResponse {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::TrashRestore, payload)
+    }
+}
+
+// ── Copy ──────────────────────────────────────────────────────────
+
+/// Copy a path to another place on the slave.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CopyRequest {
+    /// File or directory to copy.
+    pub src: String,
+    /// Target path, or an existing directory to copy into.
+    pub dest: String,
+    /// Replace files that already exist at the destination.
+    pub overwrite: bool,
+    /// Allow `src` to be a directory.
+    pub recursive: bool,
+}
+
+impl CopyRequest {
+    /// Copy a single file without replacing anything.
+    pub fn new(src: impl Into<String>, dest: impl Into<String>) -> Self {
+        Self {
+            src: src.into(),
+            dest: dest.into(),
+            overwrite: false,
+            recursive: false,
+        }
+    }
+
+    /// Replace existing files at the destination.
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Allow copying directories.
+    pub fn with_recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::Copy, payload)
+    }
+}
+
+// ── Helpers ───────────────────────────────────────────────────────
+
+/// Classify a file transfer response packet by its flags.
+pub fn classify_file_response(packet: &Packet) -> FileResponseKind {
+    let flags = packet.flags();
+    if flags.contains(ProtocolFlags::FINAL_FRAGMENT) {
+        FileResponseKind::HashVerification
+    } else if flags.contains(ProtocolFlags::STREAMING) {
+        FileResponseKind::StreamingChunk
+    } else {
+        FileResponseKind::SingleResponse
+    }
+}
+
+/// Classification of a file transfer response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileResponseKind {
+    /// A streaming chunk (header or data chunk).
+    StreamingChunk,
+    /// The final hash verification packet.
+    HashVerification,
+    /// A single non-streaming response (ack, error, etc.).
+    SingleResponse,
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    struct FileTransferRequest {
+        path: String,
+        chunk_size: u32,
+        delta_sync: bool,
+        local_hash: Option<[u8; 32]>,
+    }
+}
+wire_schema! {
+    struct FileTransferHeader {
+        path: String,
+        size: u64,
+        modified: u64,
+        permissions: u32,
+        is_directory: bool,
+        total_chunks: u64,
+        chunk_size: u32,
+    }
+}
+wire_schema! {
+    struct FileChunk { offset: u64, chunk_index: u64, data: Vec<u8>, compressed: bool }
+}
+wire_schema! {
+    struct FileMetadata {
+        name: String,
+        path: String,
+        size: u64,
+        modified: u64,
+        is_directory: bool,
+        hash: Option<[u8; 32]>,
+        attributes: FileAttributes,
+    }
+}
+wire_schema! {
+    struct FileHashVerification { blake3_hash: [u8; 32], total_bytes: u64, total_chunks: u64 }
+}
+wire_schema! {
+    struct FileTransferAck { path: String, bytes_written: u64, error: Option<String> }
+}
+wire_schema! {
+    struct FileWriteProgress { chunks_written: u64, bytes_written: u64, missing: Vec<u8> }
+}
+wire_schema! {
+    struct DeltaSyncRequest { path: String, chunk_size: u32, chunk_hashes: Vec<DeltaChunkInfo> }
+}
+wire_schema! {
+    struct DeltaChunkInfo { index: u64, offset: u64, length: u32, hash: [u8; 32] }
+}
+wire_schema! {
+    struct FileHashRequest { path: String, chunk_size: u32 }
+}
+wire_schema! {
+    struct FileDigest { size: u64, hash: [u8; 32], chunk_size: u32, chunks: Vec<DeltaChunkInfo> }
+}
+wire_schema! {
+    enum FileHashResponse { Hashed { 0: FileDigest }, Failed { 0: String } }
+}
+wire_schema! {
+    enum DeleteMode { Recycle, Permanent }
+}
+wire_schema! {
+    struct FileDeleteRequest { paths: Vec<String>, mode: DeleteMode }
+}
+wire_schema! {
+    enum DeleteOutcome { Recycled, Deleted, Failed { 0: String } }
+}
+wire_schema! {
+    struct DeletedItem { path: String, outcome: DeleteOutcome }
+}
+wire_schema! {
+    struct FileDeleteResponse { requested: DeleteMode, items: Vec<DeletedItem>, note: Option<String> }
+}
+wire_schema! {
+    struct TrashRestoreRequest { original_path: String }
+}
+wire_schema! {
+    enum TrashRestoreResponse {
+        Restored { path: String },
+        NotFound { 0: String },
+        Unsupported { 0: String },
+        Failed { 0: String },
+    }
+}
+wire_schema! {
+    struct CopyRequest { src: String, dest: String, overwrite: bool, recursive: bool }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_transfer_request_roundtrip() {
+        let req = FileTransferRequest::download("/home/user/file.txt").with_chunk_size(32768);
+
+        let bytes = req.to_bytes().unwrap();
+        let decoded = FileTransferRequest::from_bytes(&bytes).unwrap();
+        assert_eq!(req, decoded);
+        assert_eq!(decoded.path, "/home/user/file.txt");
+        assert_eq!(decoded.chunk_size, 32768);
+        assert!(!decoded.delta_sync);
+    }
+
+    #[test]
+    fn file_transfer_request_with_delta() {
+        let hash = [0xABu8; 32];
+        let req = FileTransferRequest::download("file.bin").with_delta_sync(hash);
+
+        let bytes = req.to_bytes().unwrap();
+        let decoded = FileTransferRequest::from_bytes(&bytes).unwrap();
+        assert!(decoded.delta_sync);
+        assert_eq!(decoded.local_hash.unwrap(), hash);
+    }
+
+    #[test]
+    fn file_transfer_header_roundtrip() {
+        let header = FileTransferHeader {
+            path: "C:\\data\\report.pdf".to_string(),
+            size: 1_048_576,
+            modified: 1700000000,
+            permissions: 0o644,
+            is_directory: false,
+            total_chunks: 16,
+            chunk_size: DEFAULT_CHUNK_SIZE as u32,
+        };
 
         let bytes = header.to_bytes().unwrap();
         let decoded = FileTransferHeader::from_bytes(&bytes).unwrap();
@@ -427,6 +1572,151 @@ mod tests {
         assert_eq!(decoded.offset, 0);
     }
 
+    #[cfg(feature = "compression")]
+    /// `len` bytes of log-like text.
+    fn text(len: usize) -> Vec<u8> {
+        (0..)
+            .map(|i| format!("2024-05-01 12:00:{:02} INFO request {} served\n", i % 60, i))
+            .flat_map(String::into_bytes)
+            .take(len)
+            .collect()
+    }
+
+    #[cfg(feature = "compression")]
+    /// `len` bytes that do not compress, as in a JPEG or a zip.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn only_chunks_that_shrink_go_compressed() {
+        let zstd = ChunkCompressor::new(DEFAULT_COMPRESSION_LEVEL);
+        let packed = zstd.pack(0, 0, text(DEFAULT_CHUNK_SIZE));
+        assert!(packed.compressed);
+        assert!(
+            packed.data.len() < DEFAULT_CHUNK_SIZE / 4,
+            "{}",
+            packed.data.len()
+        );
+
+        let packed = zstd.pack(0, 0, noise(DEFAULT_CHUNK_SIZE));
+        assert!(!packed.compressed);
+        assert_eq!(packed.data, noise(DEFAULT_CHUNK_SIZE));
+
+        for raw in [ChunkCompressor::raw(), ChunkCompressor::new(0)] {
+            assert!(!raw.is_enabled());
+            assert!(!raw.pack(0, 0, text(1000)).compressed);
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn mixed_chunk_streams_rebuild_the_file() {
+        let chunk_size = 4096;
+        let mut original = text(3 * chunk_size);
+        original.extend(noise(2 * chunk_size));
+        original.extend(text(chunk_size));
+        original.extend(noise(100));
+
+        let zstd = ChunkCompressor::new(DEFAULT_COMPRESSION_LEVEL);
+        let received: Vec<FileChunk> = original
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(i, data)| zstd.pack((i * chunk_size) as u64, i as u64, data.to_vec()))
+            .map(|chunk| FileChunk::from_bytes(&chunk.to_bytes().unwrap()).unwrap())
+            .collect();
+        let forms: Vec<bool> = received.iter().map(|chunk| chunk.compressed).collect();
+        assert_eq!(forms, [true, true, true, false, false, true, false]);
+
+        let unpacked: Vec<FileChunk> = received
+            .into_iter()
+            .map(|chunk| chunk.unpack(chunk_size as u32).unwrap())
+            .collect();
+        assert!(unpacked.iter().all(|chunk| !chunk.compressed));
+        let rebuilt = apply_delta(&[], &unpacked, original.len() as u64);
+        assert_eq!(rebuilt, original);
+        assert_eq!(blake3::hash(&rebuilt), blake3::hash(&original));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn chunks_never_inflate_past_the_chunk_size() {
+        let bomb = FileChunk {
+            compressed: true,
+            ..FileChunk::new(0, 3, zstd::bulk::compress(&[0u8; 8192], 3).unwrap())
+        };
+        assert!(bomb.data.len() < 100);
+        let err = bomb.clone().unpack(4096).unwrap_err();
+        assert!(err.to_string().contains("chunk 3"), "{}", err);
+        assert_eq!(bomb.unpack(8192).unwrap().data, [0u8; 8192]);
+
+        let garbage = FileChunk {
+            compressed: true,
+            ..FileChunk::new(0, 0, b"not zstd".to_vec())
+        };
+        assert!(garbage.unpack(0).is_err());
+    }
+
+    #[test]
+    fn old_peers_get_and_send_raw_chunks() {
+        // A chunk from a build without the flag reads as raw.
+        let bytes = bincode::serialize(&(8u64, 1u64, b"data".to_vec())).unwrap();
+        let chunk = FileChunk::from_bytes(&bytes).unwrap();
+        assert_eq!(chunk, FileChunk::new(8, 1, b"data".to_vec()));
+
+        let zstd = ChunkCompressor::new(DEFAULT_COMPRESSION_LEVEL);
+        let old = PeerCapabilities {
+            compression: false,
+            ..PeerCapabilities::default()
+        };
+        assert_eq!(zstd.for_peer(Some(&old)), ChunkCompressor::raw());
+        assert_eq!(zstd.for_peer(None), ChunkCompressor::raw());
+        assert_eq!(zstd.for_peer(Some(&PeerCapabilities::default())), zstd);
+    }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn without_compression_chunks_go_and_come_raw() {
+        let compressor = TransferConfig::default().compressor();
+        assert!(!compressor.is_enabled());
+        assert!(!compressor.pack(0, 0, vec![0; 8192]).compressed);
+
+        let packed = FileChunk {
+            compressed: true,
+            ..FileChunk::new(0, 3, vec![0x28, 0xB5, 0x2F, 0xFD])
+        };
+        let err = packed.unpack(0).unwrap_err();
+        assert!(err.to_string().contains("chunk 3"), "{}", err);
+    }
+
+    #[test]
+    fn transfer_config_bounds_the_level() {
+        assert!(TransferConfig::default().problems().is_empty());
+        assert_eq!(
+            TransferConfig::default().compressor().is_enabled(),
+            cfg!(feature = "compression")
+        );
+        let off = TransferConfig {
+            compression_level: 0,
+            ..TransferConfig::default()
+        };
+        assert!(off.problems().is_empty() && !off.compressor().is_enabled());
+        let wild = TransferConfig {
+            compression_level: 40,
+            ..TransferConfig::default()
+        };
+        assert_eq!(wild.problems().len(), 1);
+    }
+
     #[test]
     fn file_metadata_roundtrip() {
         let meta = FileMetadata {
@@ -436,6 +1726,7 @@ mod tests {
             modified: 1700000000,
             is_directory: false,
             hash: Some([0x42; 32]),
+            attributes: FileAttributes::HIDDEN | FileAttributes::READONLY,
         };
 
         let bytes = meta.to_bytes().unwrap();
@@ -443,6 +1734,48 @@ mod tests {
         assert_eq!(meta, decoded);
     }
 
+    #[test]
+    fn attribute_fields_parse_leniently() {
+        let attrs = FileAttributes::HIDDEN | FileAttributes::REPARSE_POINT;
+        let field = attrs.bits().to_string();
+        assert_eq!(FileAttributes::from_field(Some(&field)), attrs);
+        assert!(FileAttributes::from_field(Some(&field)).is_hidden());
+        assert_eq!(FileAttributes::from_field(None), FileAttributes::empty());
+        assert_eq!(
+            FileAttributes::from_field(Some("junk")),
+            FileAttributes::empty()
+        );
+        // Bits this version does not know about are dropped.
+        assert_eq!(
+            FileAttributes::from_field(Some("8194")),
+            FileAttributes::HIDDEN
+        );
+    }
+
+    #[test]
+    fn links_are_not_walked_into() {
+        let root = std::env::temp_dir().join(format!("tix-reparse-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("real")).unwrap();
+        let meta = std::fs::symlink_metadata(root.join("real")).unwrap();
+        assert!(!is_reparse_point(&meta));
+
+        #[cfg(unix)]
+        let linked = std::os::unix::fs::symlink(&root, root.join("loop")).is_ok();
+        #[cfg(windows)]
+        let linked = std::process::Command::new("cmd")
+            .args(["/c", "mklink", "/J"])
+            .arg(root.join("loop"))
+            .arg(&root)
+            .status()
+            .is_ok_and(|s| s.success());
+        if linked {
+            let meta = std::fs::symlink_metadata(root.join("loop")).unwrap();
+            assert!(is_reparse_point(&meta));
+        }
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
     #[test]
     fn file_hash_verification_roundtrip() {
         let hash = blake3::hash(b"test content");
@@ -470,6 +1803,112 @@ mod tests {
         assert_eq!(decoded.chunk_hashes.len(), 2);
     }
 
+    #[test]
+    fn delta_sync_sends_only_changed_chunks() {
+        let old: Vec<u8> = (0..250u32).map(|i| i as u8).collect();
+        let digest = FileDigest::of(&old, 100);
+        assert_eq!(digest.chunks.len(), 3);
+        assert_eq!(digest.chunks[2].length, 50);
+
+        // Change the middle chunk and grow the file by a fourth chunk.
+        let mut new = old.clone();
+        new[150] ^= 0xFF;
+        new.extend_from_slice(&[9u8; 120]);
+        let req = DeltaSyncRequest::new("log.txt", &digest);
+        let packet = req.clone().into_packet(4).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::FileRead);
+        assert!(packet.flags().contains(ProtocolFlags::ACK_REQUESTED));
+
+        let chunks = req.changed_chunks(&new);
+        let indices: Vec<u64> = chunks.iter().map(|c| c.chunk_index).collect();
+        // The old partial last chunk is now full, so it is resent too.
+        assert_eq!(indices, [1, 2, 3]);
+        assert_eq!(apply_delta(&old, &chunks, new.len() as u64), new);
+        let verify = req.verification(&new);
+        assert_eq!(verify.total_chunks, 4);
+        assert_eq!(verify.blake3_hash, FileDigest::of(&new, 100).hash);
+
+        // Shrinking needs no chunks at all when the prefix is unchanged.
+        let shorter = &old[..200];
+        let digest = FileDigest::of(&old, 100);
+        let req = DeltaSyncRequest::new("log.txt", &digest);
+        let chunks = req.changed_chunks(shorter);
+        assert!(chunks.is_empty());
+        assert_eq!(apply_delta(&old, &chunks, 200), shorter);
+    }
+
+    #[test]
+    fn repair_refetches_only_the_corrupt_chunk() {
+        let original: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let sent = DeltaSyncRequest::new("big.bin", &FileDigest::of(&[], 100));
+        let verification = sent.verification(&original);
+        let mut transferred = sent.changed_chunks(&original);
+        transferred[4].data[10] ^= 0x01;
+
+        let mut repair = ChunkRepair::new("big.bin", 100, DEFAULT_REPAIR_ROUNDS);
+        repair.received(&transferred);
+        let copy = apply_delta(&[], &transferred, verification.total_bytes);
+        let request = repair.check(&copy, &verification).unwrap().unwrap();
+        assert_eq!(repair.rounds(), 1);
+
+        // The sender answers as it would any delta sync.
+        let fresh = request.changed_chunks(&original);
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[0].chunk_index, 4);
+        repair.received(&fresh);
+        let copy = apply_delta(&copy, &fresh, verification.total_bytes);
+        let verification = request.verification(&original);
+        assert!(repair.check(&copy, &verification).unwrap().is_none());
+        assert_eq!(repair.last_chunks(), [4]);
+    }
+
+    #[test]
+    fn repair_gives_up_after_its_rounds() {
+        let data = vec![1u8; 300];
+        // A sender whose file keeps changing never verifies.
+        let moving = FileHashVerification::new([0; 32], 300, 3);
+        let mut repair = ChunkRepair::new("busy.log", 100, 2);
+        assert!(repair.check(&data, &moving).unwrap().is_some());
+        repair.received(&[FileChunk::new(200, 2, vec![2; 100])]);
+        assert!(repair.check(&data, &moving).unwrap().is_some());
+        repair.received(&[FileChunk::new(200, 2, vec![3; 100])]);
+        match repair.check(&data, &moving) {
+            Err(TixError::RepairFailed {
+                path,
+                rounds,
+                chunks,
+            }) => {
+                assert_eq!((path.as_str(), rounds), ("busy.log", 2));
+                assert_eq!(chunks, [2]);
+            }
+            other => panic!("expected RepairFailed, got {:?}", other),
+        }
+
+        assert!(ChunkRepair::new("x", 100, 0).check(&data, &moving).is_err());
+        let digest = FileDigest::of(&data, 100);
+        assert!(differing_chunks(&digest.chunks, &data, 100).is_empty());
+    }
+
+    #[test]
+    fn file_hash_roundtrip() {
+        let packet = FileHashRequest::new("/var/log/app.log")
+            .into_packet(8)
+            .unwrap();
+        assert_eq!(packet.command().unwrap(), Command::FileHash);
+        let req = FileHashRequest::from_bytes(packet.payload()).unwrap();
+        assert_eq!(req.chunk_size, DEFAULT_CHUNK_SIZE as u32);
+
+        let resp = FileHashResponse::Hashed(FileDigest::of(b"hello", 0));
+        let pkt = resp.clone().into_packet(8).unwrap();
+        let decoded = FileHashResponse::from_bytes(pkt.payload()).unwrap();
+        assert_eq!(decoded, resp);
+        let FileHashResponse::Hashed(digest) = decoded else {
+            panic!("expected a digest");
+        };
+        assert_eq!(digest.hash, *blake3::hash(b"hello").as_bytes());
+        assert!(FileDigest::of(b"", 0).chunks.is_empty());
+    }
+
     #[test]
     fn compute_total_chunks() {
         assert_eq!(FileTransferHeader::compute_total_chunks(0, 65536), 0);
@@ -493,4 +1932,149 @@ mod tests {
         let decoded = FileTransferRequest::from_bytes(packet.payload()).unwrap();
         assert_eq!(decoded.path, "test.txt");
     }
+
+    #[test]
+    fn upload_packets_use_file_write_flags() {
+        let header = FileTransferHeader {
+            path: "notes.txt".to_string(),
+            size: 4,
+            modified: 0,
+            permissions: 0o644,
+            is_directory: false,
+            total_chunks: 1,
+            chunk_size: 65536,
+        };
+        let first = header.into_upload_packet(9).unwrap();
+        assert_eq!(first.message_type(), crate::message::MessageType::Command);
+        assert_eq!(first.command().unwrap(), Command::FileWrite);
+        assert!(first.flags().contains(ProtocolFlags::STREAMING));
+
+        let chunk = FileChunk::new(0, 0, b"data".to_vec());
+        let data = chunk.into_upload_packet(9).unwrap();
+        assert!(data.flags().contains(ProtocolFlags::STREAMING));
+
+        let verify = FileHashVerification::new(*blake3::hash(b"data").as_bytes(), 4, 1);
+        let last = verify.into_upload_packet(9).unwrap();
+        assert!(last.flags().contains(ProtocolFlags::FINAL_FRAGMENT));
+        assert_eq!(last.request_id(), 9);
+    }
+
+    #[test]
+    fn copy_request_roundtrip_and_legacy_text_rejected() {
+        let req = CopyRequest::new("C:\\Program Files\\app", "D:\\backup dir")
+            .with_overwrite(true)
+            .with_recursive(true);
+        let packet = req.clone().into_packet(3).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::Copy);
+        assert_eq!(CopyRequest::from_bytes(packet.payload()).unwrap(), req);
+
+        // What an old master sends must not decode as a request.
+        assert!(CopyRequest::from_bytes(b"C:\\a.txt C:\\b.txt").is_err());
+    }
+
+    #[test]
+    fn file_transfer_ack_roundtrip() {
+        let ack = FileTransferAck {
+            path: "C:\\Users\\me\\Downloads\\notes.txt".to_string(),
+            bytes_written: 4,
+            error: Some("hash mismatch".to_string()),
+        };
+        let packet = ack.clone().into_packet(9).unwrap();
+        assert_eq!(packet.message_type(), crate::message::MessageType::Response);
+        let decoded = FileTransferAck::from_bytes(packet.payload()).unwrap();
+        assert_eq!(decoded, ack);
+        assert!(!decoded.is_ok());
+    }
+
+    #[test]
+    fn write_progress_is_streamed_with_its_gaps() {
+        let progress = FileWriteProgress::new(16, 16 * 4096).with_missing([3, 16, 18, 25]);
+        assert!(progress.has_gaps());
+        assert_eq!(progress.missing_chunks(), vec![16, 18, 25]);
+        assert_eq!(progress.missing, vec![0b0000_0101, 0b0000_0010]);
+
+        let packet = progress.clone().into_packet(5).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::FileWrite);
+        assert!(packet.flags().contains(ProtocolFlags::STREAMING));
+        assert_eq!(
+            FileWriteProgress::from_bytes(packet.payload()).unwrap(),
+            progress
+        );
+        assert!(!FileWriteProgress::new(2, 10).has_gaps());
+
+        let header = FileTransferHeader {
+            path: "a".into(),
+            size: 0,
+            modified: 0,
+            permissions: 0,
+            is_directory: false,
+            total_chunks: 0,
+            chunk_size: 4,
+        };
+        let flags = header.into_windowed_upload_packet(5).unwrap().flags();
+        assert!(flags.contains(ProtocolFlags::STREAMING | ProtocolFlags::ACK_REQUESTED));
+    }
+
+    #[test]
+    fn file_delete_request_defaults_to_recycle() {
+        let req = FileDeleteRequest::new(vec!["C:\\tmp\\a.txt".to_string()]);
+        assert_eq!(req.mode, DeleteMode::Recycle);
+
+        let packet = req.with_mode(DeleteMode::Permanent).into_packet(7).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::FileDelete);
+        let decoded = FileDeleteRequest::from_bytes(packet.payload()).unwrap();
+        assert_eq!(decoded.mode, DeleteMode::Permanent);
+        assert_eq!(decoded.paths, vec!["C:\\tmp\\a.txt".to_string()]);
+    }
+
+    #[test]
+    fn file_delete_response_reports_fallback() {
+        let resp = FileDeleteResponse {
+            requested: DeleteMode::Recycle,
+            items: vec![
+                DeletedItem {
+                    path: "/tmp/a".to_string(),
+                    outcome: DeleteOutcome::Deleted,
+                },
+                DeletedItem {
+                    path: "/tmp/b".to_string(),
+                    outcome: DeleteOutcome::Failed("busy".to_string()),
+                },
+            ],
+            note: Some("no Recycle Bin on this platform".to_string()),
+        };
+        let decoded = FileDeleteResponse::from_bytes(&resp.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded, resp);
+        assert!(decoded.fell_back_to_permanent());
+        assert_eq!(decoded.failed_count(), 1);
+
+        let recycled = FileDeleteResponse {
+            requested: DeleteMode::Recycle,
+            items: vec![DeletedItem {
+                path: "C:\\a".to_string(),
+                outcome: DeleteOutcome::Recycled,
+            }],
+            note: None,
+        };
+        assert!(!recycled.fell_back_to_permanent());
+    }
+
+    #[test]
+    fn trash_restore_roundtrip() {
+        let packet = TrashRestoreRequest::new("C:\\docs\\report.pdf")
+            .into_packet(3)
+            .unwrap();
+        assert_eq!(packet.command().unwrap(), Command::TrashRestore);
+        let req = TrashRestoreRequest::from_bytes(packet.payload()).unwrap();
+        assert_eq!(req.original_path, "C:\\docs\\report.pdf");
+
+        let resp = TrashRestoreResponse::Restored {
+            path: req.original_path.clone(),
+        };
+        let pkt = resp.clone().into_packet(3).unwrap();
+        assert_eq!(
+            TrashRestoreResponse::from_bytes(pkt.payload()).unwrap(),
+            resp
+        );
+    }
 }
@@ -1,23 +1,73 @@
 //! High-level protocol payload definitions for TIX services.
 //!
 //! Each sub-module defines the structured request/response payloads for a
-//! specific protocol domain (shell, file transfer, remote desktop). Payloads
-//! are serialized with `serde` + `bincode` and carried inside [`Packet`]
+//! specific protocol domain (shell, file transfer, file search, file
+//! transactions, remote desktop, system inspection, service management, network diagnostics,
+//! self-tests, event logs, scheduled commands, self-update). Payloads are
+//! serialized with `serde` + `bincode` and carried inside [`Packet`]
 //! bodies.
 //!
 //! [`Packet`]: crate::packet::Packet
 
+pub mod eventlog;
 pub mod file;
+pub mod netdiag;
+pub mod schedule;
+pub mod schema;
 pub mod screen;
+pub mod search;
+pub mod selftest;
+pub mod service;
 pub mod shell;
+pub mod system;
+pub mod transaction;
+pub mod update;
 
 // Re-export the most commonly used types at the protocol level.
+pub use eventlog::{
+    EventLevel, EventLogBatch, EventLogEntry, EventLogError, EventLogErrorKind,
+    EventLogQueryRequest, EventLogSummary,
+};
 pub use file::{
-    DeltaChunkInfo, DeltaSyncRequest, FileChunk, FileHashVerification, FileMetadata,
-    FileTransferHeader, FileTransferRequest,
+    ChunkCompressor, ChunkRepair, CopyRequest, DeleteMode, DeleteOutcome, DeletedItem,
+    DeltaChunkInfo, DeltaSyncRequest, FileAttributes, FileChunk, FileDeleteRequest,
+    FileDeleteResponse, FileDigest, FileHashRequest, FileHashResponse, FileHashVerification,
+    FileMetadata, FileTransferAck, FileTransferHeader, FileTransferRequest, FileWriteProgress,
+    TransferConfig, TrashRestoreRequest, TrashRestoreResponse, apply_delta, differing_chunks,
+    is_reparse_point, upload_cancel_packet,
+};
+pub use netdiag::{
+    DnsResult, InterfaceAddress, LinkState, NetDiagErrorKind, NetDiagRequest, NetDiagResponse,
+    NetInterface, PortCheckOutcome, PortCheckResult, RouteEntry,
 };
 pub use screen::{
-    KeyAction, KeyEvent, MouseButton, MouseEvent, MouseEventKind, ScreenConfig, ScreenFrame,
-    ScreenStartRequest, ScreenStopRequest,
+    DictionaryOffer, InputRejection, KeyAction, KeyEvent, MouseButton, MouseEvent, MouseEventKind,
+    PreflightReport, SasRefusal, SasResponse, ScreenConfig, ScreenDictionaryRequest,
+    ScreenDictionaryResponse, ScreenFrame, ScreenModeRequest, ScreenModeResponse,
+    ScreenPreflightRequest, ScreenSharingRequest, ScreenStartRequest, ScreenStartResponse,
+    ScreenStopRequest, TimedKeyEvent, TimedMouseEvent,
+};
+pub use schedule::{
+    ScheduleCreateRequest, ScheduleInfo, ScheduleNameRequest, ScheduleResponse, ScheduleRunReport,
+    ScheduleRunResult,
+};
+pub use search::{FileSearchBatch, FileSearchRequest, FileSearchSummary};
+pub use selftest::{CheckResult, CheckStatus, SelfTestReport};
+pub use service::{
+    ServiceAction, ServiceControlRequest, ServiceControlResponse, ServiceErrorKind, ServiceInfo,
+    ServiceListRequest, ServiceListResponse, ServiceStartType, ServiceState,
+};
+pub use shell::{
+    ShellExecuteRequest, ShellExitStatus, ShellKind, ShellOutputChunk, ShellResizeRequest,
+};
+pub use system::{
+    CommandTraffic, LimitExceeded, LockAccess, PathLockInfo, RegistryEntry, RegistryErrorKind,
+    RegistryHive, RegistryQueryRequest, RegistryQueryResponse, RegistryValue, SessionStats,
+    StartupEntry, StartupListResponse, StartupSource, SystemInfoResponse, TaskFailure, TaskInfo,
+    TaskListResponse, WireTapResponse, WireTapSettings,
+};
+pub use transaction::{
+    FileTransactionRequest, FileTransactionResponse, MAX_TRANSACTION_OPS, StepOutcome, StepResult,
+    TransactionOp,
 };
-pub use shell::{ShellExecuteRequest, ShellExitStatus, ShellOutputChunk, ShellResizeRequest};
+pub use update::{HelloInfo, UpdateApplyRequest, UpdateApplyResponse, UpdateError};
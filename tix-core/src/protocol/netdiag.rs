@@ -0,0 +1,469 @@
+//! Network diagnostics protocol — the slave's interfaces, routes, name
+//! resolution and TCP reachability, as structured data rather than the
+//! localized text of `ipconfig` and `route print`.
+//!
+//! # Wire Protocol
+//!
+//! ```text
+//! Master ──[NetDiag]──────────────────────────► Slave
+//!   Payload: NetDiagRequest (bincode)
+//!
+//! Slave  ──[NetDiag]──────────────────────────► Master
+//!   Payload: NetDiagResponse (bincode), the variant matching the
+//!   request, or Error
+//! ```
+//!
+//! Interfaces and routes come from the IP Helper API and are only
+//! available on Windows slaves; name resolution and port checks work
+//! everywhere. A port check is a plain TCP connect from the slave, which
+//! makes it a cheap preflight before anything that needs the slave to
+//! reach the master (the screen stream, say).
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use crate::error::TixError;
+use crate::message::Command;
+use crate::packet::Packet;
+use crate::protocol::schema::wire_schema;
+
+/// How long a port check waits for the connection by default.
+pub const DEFAULT_PORT_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+// ── Request ───────────────────────────────────────────────────────
+
+/// Request payload for `Command::NetDiag`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum NetDiagRequest {
+    /// Network adapters with their addresses, gateways and link state.
+    InterfaceList,
+    /// The IPv4 and IPv6 routing tables.
+    RouteTable,
+    /// Resolve `name` with the slave's resolver.
+    DnsResolve { name: String },
+    /// Open a TCP connection to `host:port` and close it again.
+    PortCheck {
+        host: String,
+        port: u16,
+        /// Give up after this many milliseconds, resolution included.
+        timeout_ms: u64,
+    },
+}
+
+impl NetDiagRequest {
+    /// A port check waiting up to [`DEFAULT_PORT_CHECK_TIMEOUT`].
+    pub fn port_check(host: impl Into<String>, port: u16) -> Self {
+        Self::PortCheck {
+            host: host.into(),
+            port,
+            timeout_ms: DEFAULT_PORT_CHECK_TIMEOUT.as_millis() as u64,
+        }
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+}
+
+// ── Interfaces and Routes ─────────────────────────────────────────
+
+/// Whether an interface can pass traffic.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LinkState {
+    Up,
+    Down,
+    /// Present but waiting on something, e.g. a dial-up connection.
+    Dormant,
+    /// Missing hardware or any state this version does not know.
+    Unknown,
+}
+
+impl fmt::Display for LinkState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Up => write!(f, "up"),
+            Self::Down => write!(f, "down"),
+            Self::Dormant => write!(f, "dormant"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// An address assigned to an interface, with its on-link prefix.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InterfaceAddress {
+    pub address: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl fmt::Display for InterfaceAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+/// One network adapter of the slave.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NetInterface {
+    /// Interface index, the one [`RouteEntry::interface_index`] refers to.
+    pub index: u32,
+    /// Friendly name, e.g. `Ethernet` or `Wi-Fi`.
+    pub name: String,
+    /// Adapter description, usually the hardware model.
+    pub description: String,
+    pub state: LinkState,
+    /// Hardware address as `aa:bb:cc:dd:ee:ff`, when the adapter has one.
+    pub mac: Option<String>,
+    pub mtu: u32,
+    /// Transmit speed in bits per second, when known.
+    pub speed_bps: Option<u64>,
+    pub addresses: Vec<InterfaceAddress>,
+    pub gateways: Vec<IpAddr>,
+    pub dns_servers: Vec<IpAddr>,
+}
+
+/// One entry of the slave's routing table.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RouteEntry {
+    pub destination: IpAddr,
+    pub prefix_len: u8,
+    /// `None` for on-link routes.
+    pub next_hop: Option<IpAddr>,
+    pub interface_index: u32,
+    /// Name of the interface, when it is still there.
+    pub interface: Option<String>,
+    /// Route metric; Windows adds the interface metric on top when it
+    /// picks between routes.
+    pub metric: u32,
+}
+
+impl RouteEntry {
+    /// Whether this is a default route (`0.0.0.0/0` or `::/0`).
+    pub fn is_default(&self) -> bool {
+        self.prefix_len == 0
+    }
+}
+
+// ── Resolution and Port Checks ────────────────────────────────────
+
+/// Addresses the slave's resolver returned for a name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DnsResult {
+    pub name: String,
+    /// In the order the resolver returned them, without duplicates.
+    pub addresses: Vec<IpAddr>,
+    /// Time the lookup took, in microseconds.
+    pub elapsed_us: u64,
+}
+
+/// How a port check ended.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PortCheckOutcome {
+    /// The connection was accepted.
+    Open,
+    /// The peer answered with a reset: nothing listens there.
+    Refused,
+    /// No answer before the timeout; often a firewall dropping packets.
+    TimedOut,
+    /// The name did not resolve.
+    Unresolved(String),
+    /// Any other connect error, e.g. no route to the host.
+    Failed(String),
+}
+
+impl PortCheckOutcome {
+    pub fn is_open(&self) -> bool {
+        matches!(self, Self::Open)
+    }
+}
+
+impl fmt::Display for PortCheckOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Open => write!(f, "open"),
+            Self::Refused => write!(f, "refused"),
+            Self::TimedOut => write!(f, "timed out"),
+            Self::Unresolved(message) => write!(f, "unresolved: {}", message),
+            Self::Failed(message) => write!(f, "failed: {}", message),
+        }
+    }
+}
+
+/// Result of a port check.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PortCheckResult {
+    pub host: String,
+    pub port: u16,
+    /// The address tried last; the one connected to when open. `None`
+    /// when the name did not resolve.
+    pub address: Option<SocketAddr>,
+    pub outcome: PortCheckOutcome,
+    /// Time until the outcome was known, in microseconds.
+    pub elapsed_us: u64,
+}
+
+impl PortCheckResult {
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_micros(self.elapsed_us)
+    }
+}
+
+// ── Response ──────────────────────────────────────────────────────
+
+/// Why a diagnostics request failed on the slave.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum NetDiagErrorKind {
+    /// The name did not resolve.
+    Resolve,
+    /// The slave platform cannot answer this request.
+    Unsupported,
+    /// Any other OS error.
+    Other,
+}
+
+impl fmt::Display for NetDiagErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resolve => write!(f, "resolution failed"),
+            Self::Unsupported => write!(f, "unsupported"),
+            Self::Other => write!(f, "failed"),
+        }
+    }
+}
+
+/// Response payload for `Command::NetDiag`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum NetDiagResponse {
+    /// Answers [`NetDiagRequest::InterfaceList`], ordered by index.
+    Interfaces(Vec<NetInterface>),
+    /// Answers [`NetDiagRequest::RouteTable`], IPv4 first, each family
+    /// by destination.
+    Routes(Vec<RouteEntry>),
+    /// Answers [`NetDiagRequest::DnsResolve`].
+    Resolved(DnsResult),
+    /// Answers [`NetDiagRequest::PortCheck`], whether or not the port
+    /// was open.
+    PortCheck(PortCheckResult),
+    /// The request could not be answered.
+    Error {
+        kind: NetDiagErrorKind,
+        message: String,
+    },
+}
+
+impl NetDiagResponse {
+    /// Build an error response.
+    pub fn error(kind: NetDiagErrorKind, message: impl Into<String>) -> Self {
+        Self::Error {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::NetDiag, payload)
+    }
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    enum NetDiagRequest {
+        InterfaceList,
+        RouteTable,
+        DnsResolve { name: String },
+        PortCheck { host: String, port: u16, timeout_ms: u64 },
+    }
+}
+wire_schema! {
+    enum LinkState { Up, Down, Dormant, Unknown }
+}
+wire_schema! {
+    struct InterfaceAddress { address: IpAddr, prefix_len: u8 }
+}
+wire_schema! {
+    struct NetInterface {
+        index: u32,
+        name: String,
+        description: String,
+        state: LinkState,
+        mac: Option<String>,
+        mtu: u32,
+        speed_bps: Option<u64>,
+        addresses: Vec<InterfaceAddress>,
+        gateways: Vec<IpAddr>,
+        dns_servers: Vec<IpAddr>,
+    }
+}
+wire_schema! {
+    struct RouteEntry {
+        destination: IpAddr,
+        prefix_len: u8,
+        next_hop: Option<IpAddr>,
+        interface_index: u32,
+        interface: Option<String>,
+        metric: u32,
+    }
+}
+wire_schema! {
+    struct DnsResult { name: String, addresses: Vec<IpAddr>, elapsed_us: u64 }
+}
+wire_schema! {
+    enum PortCheckOutcome {
+        Open,
+        Refused,
+        TimedOut,
+        Unresolved { 0: String },
+        Failed { 0: String },
+    }
+}
+wire_schema! {
+    struct PortCheckResult {
+        host: String,
+        port: u16,
+        address: Option<SocketAddr>,
+        outcome: PortCheckOutcome,
+        elapsed_us: u64,
+    }
+}
+wire_schema! {
+    enum NetDiagErrorKind { Resolve, Unsupported, Other }
+}
+wire_schema! {
+    enum NetDiagResponse {
+        Interfaces { 0: Vec<NetInterface> },
+        Routes { 0: Vec<RouteEntry> },
+        Resolved { 0: DnsResult },
+        PortCheck { 0: PortCheckResult },
+        Error { kind: NetDiagErrorKind, message: String },
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn ethernet() -> NetInterface {
+        NetInterface {
+            index: 12,
+            name: "Ethernet".into(),
+            description: "Intel(R) Ethernet Connection I219-V".into(),
+            state: LinkState::Up,
+            mac: Some("00:1b:21:3a:4f:5c".into()),
+            mtu: 1500,
+            speed_bps: Some(1_000_000_000),
+            addresses: vec![
+                InterfaceAddress {
+                    address: Ipv4Addr::new(192, 168, 1, 20).into(),
+                    prefix_len: 24,
+                },
+                InterfaceAddress {
+                    address: "fe80::1c2b:3d4e:5f60:7182".parse().unwrap(),
+                    prefix_len: 64,
+                },
+            ],
+            gateways: vec![Ipv4Addr::new(192, 168, 1, 1).into()],
+            dns_servers: vec![Ipv4Addr::new(192, 168, 1, 1).into()],
+        }
+    }
+
+    #[test]
+    fn requests_roundtrip() {
+        let requests = [
+            NetDiagRequest::InterfaceList,
+            NetDiagRequest::RouteTable,
+            NetDiagRequest::DnsResolve {
+                name: "example.com".into(),
+            },
+            NetDiagRequest::port_check("10.0.0.5", 3389),
+        ];
+        for req in requests {
+            assert_eq!(
+                NetDiagRequest::from_bytes(&req.to_bytes().unwrap()).unwrap(),
+                req
+            );
+        }
+        let NetDiagRequest::PortCheck { timeout_ms, .. } = NetDiagRequest::port_check("h", 1)
+        else {
+            unreachable!()
+        };
+        assert_eq!(timeout_ms, 3000);
+    }
+
+    #[test]
+    fn responses_roundtrip() {
+        let responses = [
+            NetDiagResponse::Interfaces(vec![ethernet()]),
+            NetDiagResponse::Routes(vec![
+                RouteEntry {
+                    destination: Ipv4Addr::UNSPECIFIED.into(),
+                    prefix_len: 0,
+                    next_hop: Some(Ipv4Addr::new(192, 168, 1, 1).into()),
+                    interface_index: 12,
+                    interface: Some("Ethernet".into()),
+                    metric: 35,
+                },
+                RouteEntry {
+                    destination: Ipv6Addr::LOCALHOST.into(),
+                    prefix_len: 128,
+                    next_hop: None,
+                    interface_index: 1,
+                    interface: None,
+                    metric: 331,
+                },
+            ]),
+            NetDiagResponse::Resolved(DnsResult {
+                name: "localhost".into(),
+                addresses: vec![Ipv6Addr::LOCALHOST.into(), Ipv4Addr::LOCALHOST.into()],
+                elapsed_us: 412,
+            }),
+            NetDiagResponse::PortCheck(PortCheckResult {
+                host: "master".into(),
+                port: 7331,
+                address: Some("192.168.1.2:7331".parse().unwrap()),
+                outcome: PortCheckOutcome::Failed("network unreachable".into()),
+                elapsed_us: 1_250,
+            }),
+            NetDiagResponse::error(NetDiagErrorKind::Unsupported, "Windows only"),
+        ];
+        for resp in responses {
+            let packet = resp.clone().into_packet(9).unwrap();
+            assert_eq!(packet.command().unwrap(), Command::NetDiag);
+            assert_eq!(NetDiagResponse::from_bytes(packet.payload()).unwrap(), resp);
+        }
+    }
+
+    #[test]
+    fn outcomes_and_addresses_display() {
+        assert_eq!(PortCheckOutcome::TimedOut.to_string(), "timed out");
+        assert_eq!(
+            PortCheckOutcome::Unresolved("no such host".into()).to_string(),
+            "unresolved: no such host"
+        );
+        assert_eq!(ethernet().addresses[0].to_string(), "192.168.1.20/24");
+        assert!(PortCheckOutcome::Open.is_open());
+    }
+}
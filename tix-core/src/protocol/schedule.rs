@@ -0,0 +1,379 @@
+//! Scheduled commands — shell commands the slave runs on its own at a
+//! fixed interval, with their recent results kept for the master.
+//!
+//! # Wire Protocol
+//!
+//! ```text
+//! Master ──[ScheduleCreate]───────────────────► Slave
+//!   Payload: ScheduleCreateRequest (bincode)
+//!
+//! Master ──[ScheduleList]─────────────────────► Slave
+//!   Payload: empty
+//!
+//! Master ──[ScheduleDelete | ScheduleResults]─► Slave
+//!   Payload: ScheduleNameRequest (bincode)
+//!
+//! Slave  ──[same command]─────────────────────► Master
+//!   Payload: ScheduleResponse (bincode)
+//!
+//! Slave  ──[ScheduleResults + STREAMING]──────► Master   (request ID 0)
+//!   Payload: ScheduleRunReport (bincode), after every run while a
+//!   master is connected
+//! ```
+//!
+//! Schedules belong to the slave, not to a connection: they keep running
+//! while no master is connected and across slave restarts.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::error::TixError;
+use crate::flags::ProtocolFlags;
+use crate::message::Command;
+use crate::packet::Packet;
+use crate::protocol::schema::wire_schema;
+
+/// Shortest interval a schedule may have.
+pub const MIN_INTERVAL_SECS: u64 = 10;
+
+/// Longest schedule name.
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Output kept from one run, in characters; the rest is cut.
+pub const MAX_OUTPUT_CHARS: usize = 4096;
+
+// ── Requests ──────────────────────────────────────────────────────
+
+/// Request payload for `Command::ScheduleCreate`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScheduleCreateRequest {
+    /// Unique name: letters, digits, `-`, `_` and `.`.
+    pub name: String,
+    /// Shell command line, run like `ShellExecute` runs one.
+    pub command: String,
+    /// Seconds between runs; the first run comes one interval after
+    /// the schedule is created.
+    pub interval_secs: u64,
+    /// Stop after this many runs; `None` runs until deleted.
+    pub max_runs: Option<u32>,
+}
+
+impl ScheduleCreateRequest {
+    pub fn new(name: impl Into<String>, command: impl Into<String>, interval: Duration) -> Self {
+        Self {
+            name: name.into(),
+            command: command.into(),
+            interval_secs: interval.as_secs(),
+            max_runs: None,
+        }
+    }
+
+    pub fn with_max_runs(mut self, max_runs: u32) -> Self {
+        self.max_runs = Some(max_runs);
+        self
+    }
+
+    /// Time between runs.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+
+    /// Why the slave would refuse this schedule, if it would.
+    pub fn validate(&self) -> Result<(), String> {
+        validate_name(&self.name)?;
+        if self.command.trim().is_empty() {
+            return Err("a schedule needs a command".to_string());
+        }
+        if self.interval_secs < MIN_INTERVAL_SECS {
+            return Err(format!(
+                "interval must be at least {} seconds",
+                MIN_INTERVAL_SECS
+            ));
+        }
+        if self.max_runs == Some(0) {
+            return Err("max runs must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+}
+
+/// Whether `name` can name a schedule.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!(
+            "schedule names are 1 to {} characters long",
+            MAX_NAME_LEN
+        ));
+    }
+    match name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        Some(c) => Err(format!(
+            "'{}' is not allowed in a schedule name (letters, digits, '-', '_', '.')",
+            c
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Request payload for `Command::ScheduleDelete` and
+/// `Command::ScheduleResults`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScheduleNameRequest {
+    pub name: String,
+}
+
+impl ScheduleNameRequest {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+}
+
+// ── Results ───────────────────────────────────────────────────────
+
+/// What one run of a schedule did.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScheduleRunResult {
+    /// 1 for the schedule's first run, counting across slave restarts.
+    pub run: u32,
+    /// Start time, Unix milliseconds.
+    pub started_ms: u64,
+    pub elapsed_ms: u64,
+    /// Exit code, when the command ran to the end.
+    pub exit_code: Option<i32>,
+    /// Standard output followed by standard error, lossily decoded and
+    /// cut to [`MAX_OUTPUT_CHARS`].
+    pub output: String,
+    /// Why the run did not finish: the command could not start, timed
+    /// out or was cancelled.
+    pub error: Option<String>,
+}
+
+impl ScheduleRunResult {
+    /// Whether the command ran and exited with 0.
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none() && self.exit_code == Some(0)
+    }
+}
+
+/// `output` cut to [`MAX_OUTPUT_CHARS`] characters, ending in `…` when
+/// something was left out.
+pub fn truncate_output(output: &str) -> String {
+    match output.char_indices().nth(MAX_OUTPUT_CHARS) {
+        Some((end, _)) => format!("{}…", &output[..end]),
+        None => output.to_string(),
+    }
+}
+
+/// A schedule as the slave holds it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScheduleInfo {
+    pub name: String,
+    pub command: String,
+    pub interval_secs: u64,
+    pub max_runs: Option<u32>,
+    /// Runs started so far.
+    pub runs: u32,
+    /// Runs skipped because the one before was still going.
+    pub skipped: u32,
+    /// Time until the next run, in milliseconds; `None` once the
+    /// schedule has used up its runs.
+    pub next_run_ms: Option<u64>,
+    /// Most recent result, if any run has finished.
+    pub last: Option<ScheduleRunResult>,
+}
+
+/// Response payload for every schedule command but the pushed results.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ScheduleResponse {
+    /// `ScheduleCreate`: the new schedule.
+    Created(ScheduleInfo),
+    /// `ScheduleList`: every schedule, sorted by name.
+    Schedules(Vec<ScheduleInfo>),
+    /// `ScheduleDelete`: the schedule with this name is gone.
+    Deleted(String),
+    /// `ScheduleResults`: the kept results of `name`, oldest first.
+    Results {
+        name: String,
+        results: Vec<ScheduleRunResult>,
+    },
+    /// The request was refused.
+    Error(String),
+}
+
+impl ScheduleResponse {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build the response `Packet` to a `command` request.
+    pub fn into_packet(self, request_id: u64, command: Command) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, command, payload)
+    }
+}
+
+/// A run that just finished, pushed to the connected master.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScheduleRunReport {
+    pub name: String,
+    pub result: ScheduleRunResult,
+    /// This was the schedule's last run.
+    pub finished: bool,
+}
+
+impl ScheduleRunReport {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build the unsolicited `Packet`: request ID 0, streaming.
+    pub fn into_packet(self) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response_with_flags(
+            0,
+            Command::ScheduleResults,
+            payload,
+            ProtocolFlags::STREAMING,
+        )
+    }
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    struct ScheduleCreateRequest {
+        name: String,
+        command: String,
+        interval_secs: u64,
+        max_runs: Option<u32>,
+    }
+}
+wire_schema! {
+    struct ScheduleNameRequest { name: String }
+}
+wire_schema! {
+    struct ScheduleRunResult {
+        run: u32,
+        started_ms: u64,
+        elapsed_ms: u64,
+        exit_code: Option<i32>,
+        output: String,
+        error: Option<String>,
+    }
+}
+wire_schema! {
+    struct ScheduleInfo {
+        name: String,
+        command: String,
+        interval_secs: u64,
+        max_runs: Option<u32>,
+        runs: u32,
+        skipped: u32,
+        next_run_ms: Option<u64>,
+        last: Option<ScheduleRunResult>,
+    }
+}
+wire_schema! {
+    enum ScheduleResponse {
+        Created { 0: ScheduleInfo },
+        Schedules { 0: Vec<ScheduleInfo> },
+        Deleted { 0: String },
+        Results { name: String, results: Vec<ScheduleRunResult> },
+        Error { 0: String },
+    }
+}
+wire_schema! {
+    struct ScheduleRunReport { name: String, result: ScheduleRunResult, finished: bool }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_request_is_validated() {
+        let ok = ScheduleCreateRequest::new("disk-free", "dir C:\\", Duration::from_secs(300));
+        assert_eq!(ok.validate(), Ok(()));
+        assert_eq!(
+            ScheduleCreateRequest::from_bytes(&ok.to_bytes().unwrap()).unwrap(),
+            ok
+        );
+
+        let short = ScheduleCreateRequest::new("x", "ver", Duration::from_secs(5));
+        assert!(short.validate().unwrap_err().contains("at least 10"));
+        assert!(ok.clone().with_max_runs(0).validate().is_err());
+        let blank = ScheduleCreateRequest::new("x", "  ", Duration::from_secs(60));
+        assert!(blank.validate().is_err());
+        assert!(validate_name("rotate logs").unwrap_err().contains("' '"));
+        assert!(validate_name("").is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn output_is_cut_on_a_character() {
+        assert_eq!(truncate_output("short"), "short");
+        let long = "é".repeat(MAX_OUTPUT_CHARS + 3);
+        let cut = truncate_output(&long);
+        assert_eq!(cut.chars().count(), MAX_OUTPUT_CHARS + 1);
+        assert!(cut.ends_with('…'));
+    }
+
+    #[test]
+    fn run_reports_are_unsolicited_and_streaming() {
+        let report = ScheduleRunReport {
+            name: "metrics".into(),
+            result: ScheduleRunResult {
+                run: 3,
+                exit_code: Some(0),
+                output: "ok".into(),
+                ..Default::default()
+            },
+            finished: false,
+        };
+        assert!(report.result.succeeded());
+        let pkt = report.clone().into_packet().unwrap();
+        assert_eq!(pkt.request_id(), 0);
+        assert_eq!(pkt.command().unwrap(), Command::ScheduleResults);
+        assert!(pkt.flags().contains(ProtocolFlags::STREAMING));
+        assert_eq!(
+            ScheduleRunReport::from_bytes(pkt.payload()).unwrap(),
+            report
+        );
+    }
+}
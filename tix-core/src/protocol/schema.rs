@@ -0,0 +1,946 @@
+//! Machine-readable reference of the wire protocol, generated from the
+//! code.
+//!
+//! Every [`Command`] is described by the messages exchanged under it:
+//! who sends each one, with which [`ProtocolFlags`], and what its
+//! payload holds. Each bincode payload type implements [`WireSchema`],
+//! which lays out its fields (or its variants, for an enum) in the order
+//! bincode writes them. [`ProtocolDoc::generate`] walks the command
+//! table and collects every type it reaches; `protocol-doc` prints the
+//! result as JSON or Markdown:
+//!
+//! ```text
+//! cargo run -p tix-core --bin protocol-doc              # JSON
+//! cargo run -p tix-core --bin protocol-doc -- --markdown
+//! cargo run -p tix-core --bin protocol-doc -- --write docs
+//! ```
+//!
+//! The checked-in copies under `docs/` are compared with a fresh
+//! generation by a test, so they cannot go stale unnoticed. Drift in
+//! the code is caught earlier still: the command table is an exhaustive
+//! `match`, so a new [`Command`] does not compile without an entry, and
+//! [`wire_schema!`] destructures every type it describes, so a field or
+//! variant added, removed or retyped without updating the schema does
+//! not compile either.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::net::{IpAddr, SocketAddr};
+
+use serde::Serialize;
+
+use crate::flags::ProtocolFlags;
+use crate::header::{HEADER_SIZE, HEADER_SIZE_V2};
+use crate::message::Command;
+use crate::packet::MAX_PAYLOAD_SIZE;
+use crate::protocol::*;
+
+// ── Schema traits ─────────────────────────────────────────────────
+
+/// A type that can appear in a bincode payload.
+pub trait WireType {
+    /// The type as the reference names it: a primitive (`u32`,
+    /// `string`), a container (`vec<u8>`, `option<string>`) or the name
+    /// of a payload type.
+    fn wire_type() -> String;
+
+    /// Add the payload types this type refers to, itself included.
+    fn register(_types: &mut TypeRegistry) {}
+}
+
+/// A protocol payload type whose layout the reference spells out.
+///
+/// Implement it with [`wire_schema!`], which checks the description
+/// against the type.
+pub trait WireSchema: WireType {
+    /// Name of the type in the reference.
+    const NAME: &'static str;
+
+    /// Fields or variants, in wire order.
+    fn shape() -> Shape;
+}
+
+/// Layout of a payload type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Shape {
+    /// Fields one after the other.
+    Struct { fields: Vec<Field> },
+    /// A `u32` variant index, then that variant's fields.
+    Enum { variants: Vec<Variant> },
+}
+
+/// One field of a struct or enum variant. Tuple fields are named by
+/// position (`0`, `1`, ...).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Field {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// One variant of an enum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Variant {
+    /// What bincode writes for it: its position in the declaration,
+    /// whatever discriminant the Rust enum gives it.
+    pub index: u32,
+    pub name: &'static str,
+    pub fields: Vec<Field>,
+}
+
+/// Payload types reached so far, by name.
+#[derive(Debug, Default)]
+pub struct TypeRegistry {
+    types: BTreeMap<&'static str, Shape>,
+}
+
+impl TypeRegistry {
+    /// Record `T`; `false` when it already was, so recursion stops.
+    pub fn insert<T: WireSchema>(&mut self) -> bool {
+        if self.types.contains_key(T::NAME) {
+            return false;
+        }
+        self.types.insert(T::NAME, T::shape());
+        true
+    }
+}
+
+/// Implement [`WireSchema`] and [`WireType`] for a protocol type from a
+/// copy of its definition:
+///
+/// ```ignore
+/// wire_schema! {
+///     struct FileHashRequest { path: String, chunk_size: u32 }
+/// }
+/// wire_schema! {
+///     enum FileHashResponse { Hashed { 0: FileDigest }, Failed { 0: String } }
+/// }
+/// ```
+///
+/// Every variant is written with braces, tuple fields by position and
+/// unit variants without any. The copy is checked against the real
+/// type: a missing, extra or retyped field or variant fails to compile.
+/// Variant order is not checked; keep it that of the declaration.
+macro_rules! wire_schema {
+    (struct $name:ident { $($field:tt : $ty:ty),* $(,)? }) => {
+        impl $crate::protocol::schema::WireSchema for $name {
+            const NAME: &'static str = stringify!($name);
+
+            fn shape() -> $crate::protocol::schema::Shape {
+                let _same_fields = |value: &$name| {
+                    let $name { $($field: _),* } = value;
+                    $(let _: &$ty = &value.$field;)*
+                };
+                $crate::protocol::schema::Shape::Struct {
+                    fields: vec![$($crate::protocol::schema::Field {
+                        name: stringify!($field),
+                        ty: <$ty as $crate::protocol::schema::WireType>::wire_type(),
+                    }),*],
+                }
+            }
+        }
+
+        impl $crate::protocol::schema::WireType for $name {
+            fn wire_type() -> String {
+                stringify!($name).to_string()
+            }
+
+            fn register(types: &mut $crate::protocol::schema::TypeRegistry) {
+                if types.insert::<Self>() {
+                    $(<$ty as $crate::protocol::schema::WireType>::register(types);)*
+                }
+            }
+        }
+    };
+    (enum $name:ident {
+        $($variant:ident $({ $($field:tt : $ty:ty),* $(,)? })?),* $(,)?
+    }) => {
+        impl $crate::protocol::schema::WireSchema for $name {
+            const NAME: &'static str = stringify!($name);
+
+            fn shape() -> $crate::protocol::schema::Shape {
+                let _same_variants = |value: &$name| {
+                    match value {
+                        $($name::$variant { $($($field: _),*)? } => {})*
+                    }
+                    $($($(
+                        if let $name::$variant { $field: field, .. } = value {
+                            let _: &$ty = field;
+                        }
+                    )*)?)*
+                };
+                let variants: Vec<(&'static str, Vec<$crate::protocol::schema::Field>)> = vec![
+                    $((stringify!($variant), vec![$($($crate::protocol::schema::Field {
+                        name: stringify!($field),
+                        ty: <$ty as $crate::protocol::schema::WireType>::wire_type(),
+                    }),*)?])),*
+                ];
+                $crate::protocol::schema::Shape::Enum {
+                    variants: variants
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, (name, fields))| $crate::protocol::schema::Variant {
+                            index: index as u32,
+                            name,
+                            fields,
+                        })
+                        .collect(),
+                }
+            }
+        }
+
+        impl $crate::protocol::schema::WireType for $name {
+            fn wire_type() -> String {
+                stringify!($name).to_string()
+            }
+
+            fn register(types: &mut $crate::protocol::schema::TypeRegistry) {
+                if types.insert::<Self>() {
+                    $($($(<$ty as $crate::protocol::schema::WireType>::register(types);)*)?)*
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use wire_schema;
+
+// ── Primitives and containers ─────────────────────────────────────
+
+macro_rules! primitives {
+    ($($ty:ty => $name:literal),* $(,)?) => {
+        $(impl WireType for $ty {
+            fn wire_type() -> String {
+                $name.to_string()
+            }
+        })*
+    };
+}
+
+primitives! {
+    bool => "bool",
+    u8 => "u8",
+    u16 => "u16",
+    u32 => "u32",
+    u64 => "u64",
+    i16 => "i16",
+    i32 => "i32",
+    i64 => "i64",
+    String => "string",
+    IpAddr => "ip_addr",
+    SocketAddr => "socket_addr",
+}
+
+impl<T: WireType> WireType for Vec<T> {
+    fn wire_type() -> String {
+        format!("vec<{}>", T::wire_type())
+    }
+
+    fn register(types: &mut TypeRegistry) {
+        T::register(types);
+    }
+}
+
+impl<T: WireType> WireType for Option<T> {
+    fn wire_type() -> String {
+        format!("option<{}>", T::wire_type())
+    }
+
+    fn register(types: &mut TypeRegistry) {
+        T::register(types);
+    }
+}
+
+impl<T: WireType, const N: usize> WireType for [T; N] {
+    fn wire_type() -> String {
+        format!("[{}; {N}]", T::wire_type())
+    }
+
+    fn register(types: &mut TypeRegistry) {
+        T::register(types);
+    }
+}
+
+impl<A: WireType, B: WireType> WireType for (A, B) {
+    fn wire_type() -> String {
+        format!("({}, {})", A::wire_type(), B::wire_type())
+    }
+
+    fn register(types: &mut TypeRegistry) {
+        A::register(types);
+        B::register(types);
+    }
+}
+
+impl<K: WireType, V: WireType> WireType for HashMap<K, V> {
+    fn wire_type() -> String {
+        format!("map<{}, {}>", K::wire_type(), V::wire_type())
+    }
+
+    fn register(types: &mut TypeRegistry) {
+        K::register(types);
+        V::register(types);
+    }
+}
+
+/// Sent as its bits.
+impl WireType for FileAttributes {
+    fn wire_type() -> String {
+        "u32".to_string()
+    }
+}
+
+// ── Messages ──────────────────────────────────────────────────────
+
+/// Who sends a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    MasterToSlave,
+    SlaveToMaster,
+}
+
+impl Direction {
+    fn arrow(self) -> &'static str {
+        match self {
+            Direction::MasterToSlave => "master → slave",
+            Direction::SlaveToMaster => "slave → master",
+        }
+    }
+}
+
+/// What a message's payload holds.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "encoding", rename_all = "snake_case")]
+pub enum Payload {
+    /// No payload bytes.
+    Empty,
+    /// A bincode-serialized payload type.
+    Bincode {
+        #[serde(rename = "type")]
+        ty: &'static str,
+        #[serde(skip)]
+        register: fn(&mut TypeRegistry),
+    },
+    /// UTF-8 text in the format described.
+    Utf8 { format: &'static str },
+    /// Bytes in the format described.
+    Raw { format: &'static str },
+}
+
+impl Payload {
+    /// A bincode `T`.
+    pub fn of<T: WireSchema>() -> Self {
+        Payload::Bincode {
+            ty: T::NAME,
+            register: T::register,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Payload::Empty => "empty".to_string(),
+            Payload::Bincode { ty, .. } => format!("[`{ty}`](#{})", ty.to_lowercase()),
+            Payload::Utf8 { format } => format!("UTF-8: {format}"),
+            Payload::Raw { format } => format!("raw: {format}"),
+        }
+    }
+}
+
+/// One kind of packet sent under a command.
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    pub direction: Direction,
+    /// Flags set on the packet, by name.
+    pub flags: Vec<&'static str>,
+    pub payload: Payload,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<&'static str>,
+}
+
+impl Message {
+    /// A command packet from the master.
+    fn request(payload: Payload) -> Self {
+        Self {
+            direction: Direction::MasterToSlave,
+            flags: Vec::new(),
+            payload,
+            note: None,
+        }
+    }
+
+    /// A response packet from the slave.
+    fn reply(payload: Payload) -> Self {
+        Self {
+            direction: Direction::SlaveToMaster,
+            ..Self::request(payload)
+        }
+    }
+
+    fn flags(mut self, flags: ProtocolFlags) -> Self {
+        self.flags = flags.iter_names().map(|(name, _)| name).collect();
+        self
+    }
+
+    fn note(mut self, note: &'static str) -> Self {
+        self.note = Some(note);
+        self
+    }
+}
+
+/// Free text of the slave's answers that carry only a result line.
+const RESULT_LINE: &str = "a human-readable result line";
+
+/// The packets exchanged under `command`; empty when it is reserved.
+fn messages(command: Command) -> Vec<Message> {
+    use Message as M;
+
+    let streaming = ProtocolFlags::STREAMING;
+    let last = ProtocolFlags::FINAL_FRAGMENT;
+    match command {
+        Command::Ping => vec![
+            M::request(Payload::Empty),
+            M::reply(Payload::Utf8 { format: "`Pong`" }),
+        ],
+        Command::Hello => vec![
+            M::request(Payload::of::<HelloInfo>()).note("describes the master"),
+            M::reply(Payload::of::<HelloInfo>()).note("describes the slave"),
+        ],
+        Command::Heartbeat => vec![
+            M::request(Payload::Raw {
+                format: "sender clock in µs (u64), then optionally the receiver's last stamp \
+                         and how many µs it was held (u64 each), little-endian; empty from \
+                         older peers",
+            })
+            .note("sent by both sides every 5 s with request ID 0; never answered"),
+        ],
+        Command::ShellExecute => vec![
+            M::request(Payload::of::<ShellExecuteRequest>()),
+            M::reply(Payload::of::<ShellOutputChunk>()).flags(streaming),
+            M::reply(Payload::of::<ShellExitStatus>()).flags(last),
+        ],
+        Command::ShellCancel => vec![
+            M::request(Payload::Raw {
+                format: "request ID of the task to cancel, u64 little-endian",
+            })
+            .note("cancels any running task, not only shell commands"),
+            M::reply(Payload::Utf8 {
+                format: RESULT_LINE,
+            }),
+        ],
+        Command::ShellResize => vec![M::request(Payload::of::<ShellResizeRequest>())],
+        Command::ListDir => vec![
+            M::request(Payload::Utf8 {
+                format: "directory path",
+            }),
+            M::reply(Payload::Utf8 {
+                format: "`PATH|<dir>`, then one `name|is_dir|size|attributes` per entry, \
+                         joined by `;`",
+            })
+            .note("split into FRAGMENT packets when larger than one payload"),
+        ],
+        Command::FileRead => vec![
+            M::request(Payload::of::<FileTransferRequest>()),
+            M::reply(Payload::of::<FileTransferHeader>())
+                .flags(streaming)
+                .note("once, first"),
+            M::reply(Payload::of::<FileChunk>()).flags(streaming),
+            M::reply(Payload::of::<FileHashVerification>()).flags(last),
+            M::request(Payload::of::<DeltaSyncRequest>())
+                .flags(ProtocolFlags::ACK_REQUESTED)
+                .note("delta sync: only chunks whose hash differs come back"),
+        ],
+        Command::FileWrite => vec![
+            M::request(Payload::of::<FileTransferHeader>())
+                .flags(streaming)
+                .note("once, first; with ACK_REQUESTED the slave reports progress"),
+            M::request(Payload::of::<FileChunk>()).flags(streaming),
+            M::request(Payload::of::<FileHashVerification>()).flags(last),
+            M::request(Payload::Empty)
+                .flags(last)
+                .note("instead of the verification: the sender gave up, delete the partial file"),
+            M::reply(Payload::of::<FileWriteProgress>())
+                .flags(streaming)
+                .note("only when the header asked for it"),
+            M::reply(Payload::of::<FileTransferAck>())
+                .note("once, at the end or as soon as the write fails"),
+        ],
+        Command::ListDrives => vec![
+            M::request(Payload::Empty),
+            M::reply(Payload::Utf8 {
+                format: "drive roots joined by `,`",
+            }),
+        ],
+        Command::Copy => vec![
+            M::request(Payload::of::<CopyRequest>()),
+            M::reply(Payload::Utf8 {
+                format: RESULT_LINE,
+            }),
+        ],
+        Command::Upload | Command::Download => vec![
+            M::request(Payload::Utf8 {
+                format: "`<source>|<destination>`",
+            })
+            .note("a recursive, overwriting copy on the slave"),
+            M::reply(Payload::Utf8 {
+                format: RESULT_LINE,
+            }),
+        ],
+        Command::FileDelete => vec![
+            M::request(Payload::of::<FileDeleteRequest>()),
+            M::reply(Payload::of::<FileDeleteResponse>()),
+        ],
+        Command::TrashRestore => vec![
+            M::request(Payload::of::<TrashRestoreRequest>()),
+            M::reply(Payload::of::<TrashRestoreResponse>()),
+        ],
+        Command::FileHash => vec![
+            M::request(Payload::of::<FileHashRequest>()),
+            M::reply(Payload::of::<FileHashResponse>()),
+        ],
+        Command::FileSearch => vec![
+            M::request(Payload::of::<FileSearchRequest>()),
+            M::reply(Payload::of::<FileSearchBatch>()).flags(streaming),
+            M::reply(Payload::of::<FileSearchSummary>())
+                .flags(last)
+                .note("not sent when the search is cancelled"),
+        ],
+        Command::FileTransaction => vec![
+            M::request(Payload::of::<FileTransactionRequest>()),
+            M::reply(Payload::of::<FileTransactionResponse>())
+                .note("once every step ran, or once the failed ones were undone"),
+        ],
+        Command::SystemInfo => vec![
+            M::request(Payload::Empty),
+            M::reply(Payload::of::<SystemInfoResponse>()),
+        ],
+        Command::SystemAction => vec![
+            M::request(Payload::Utf8 {
+                format: "`shutdown`, `reboot` or `sleep`",
+            }),
+            M::reply(Payload::Utf8 {
+                format: RESULT_LINE,
+            }),
+        ],
+        Command::RegistryQuery => vec![
+            M::request(Payload::of::<RegistryQueryRequest>()),
+            M::reply(Payload::of::<RegistryQueryResponse>()),
+        ],
+        Command::StartupList => vec![
+            M::request(Payload::Empty),
+            M::reply(Payload::of::<StartupListResponse>()),
+        ],
+        Command::LimitExceeded => vec![
+            M::reply(Payload::of::<LimitExceeded>())
+                .note("instead of the normal response, when the bandwidth cap refuses a request"),
+        ],
+        Command::TaskList => vec![
+            M::request(Payload::Empty),
+            M::reply(Payload::of::<TaskListResponse>())
+                .note("also unsolicited, with request ID 0, for tasks that just turned slow"),
+        ],
+        Command::ServiceList => vec![
+            M::request(Payload::of::<ServiceListRequest>()),
+            M::reply(Payload::of::<ServiceListResponse>()),
+        ],
+        Command::ServiceControl => vec![
+            M::request(Payload::of::<ServiceControlRequest>()),
+            M::reply(Payload::of::<ServiceControlResponse>()),
+        ],
+        Command::EventLogQuery => vec![
+            M::request(Payload::of::<EventLogQueryRequest>()),
+            M::reply(Payload::of::<EventLogBatch>()).flags(streaming),
+            M::reply(Payload::of::<EventLogSummary>()).flags(last),
+        ],
+        Command::ScheduleCreate => vec![
+            M::request(Payload::of::<ScheduleCreateRequest>()),
+            M::reply(Payload::of::<ScheduleResponse>()),
+        ],
+        Command::ScheduleList => vec![
+            M::request(Payload::Empty),
+            M::reply(Payload::of::<ScheduleResponse>()),
+        ],
+        Command::ScheduleDelete => vec![
+            M::request(Payload::of::<ScheduleNameRequest>()),
+            M::reply(Payload::of::<ScheduleResponse>()),
+        ],
+        Command::ScheduleResults => vec![
+            M::request(Payload::of::<ScheduleNameRequest>()),
+            M::reply(Payload::of::<ScheduleResponse>()),
+            M::reply(Payload::of::<ScheduleRunReport>())
+                .flags(streaming)
+                .note("unsolicited, with request ID 0, after every run"),
+        ],
+        Command::NetDiag => vec![
+            M::request(Payload::of::<NetDiagRequest>()),
+            M::reply(Payload::of::<NetDiagResponse>()),
+        ],
+        Command::WireTap => vec![
+            M::request(Payload::of::<WireTapSettings>()),
+            M::reply(Payload::of::<WireTapResponse>()),
+        ],
+        Command::SelfTest => vec![
+            M::request(Payload::Empty),
+            M::reply(Payload::of::<SelfTestReport>()),
+        ],
+        Command::ScreenStart => vec![
+            M::request(Payload::of::<ScreenStartRequest>()),
+            M::reply(Payload::of::<ScreenStartResponse>()),
+        ],
+        Command::ScreenStop => vec![
+            M::request(Payload::Raw {
+                format: "empty to stop every session, or one byte naming the session",
+            }),
+            M::reply(Payload::Utf8 {
+                format: RESULT_LINE,
+            }),
+        ],
+        Command::ScreenFrame => vec![M::reply(Payload::of::<ScreenFrame>()).flags(streaming)],
+        Command::InputMouse => vec![
+            M::request(Payload::of::<MouseEvent>()),
+            M::request(Payload::of::<TimedMouseEvent>())
+                .note("instead, once the ScreenStart ack set timed_input"),
+            M::reply(Payload::of::<InputRejection>()).note("only when the event is refused"),
+        ],
+        Command::InputKeyboard => vec![
+            M::request(Payload::of::<KeyEvent>()),
+            M::request(Payload::of::<TimedKeyEvent>())
+                .note("instead, once the ScreenStart ack set timed_input"),
+            M::reply(Payload::of::<InputRejection>()).note("only when the event is refused"),
+        ],
+        Command::ScreenMode => vec![
+            M::request(Payload::of::<ScreenModeRequest>()),
+            M::reply(Payload::of::<ScreenModeResponse>()),
+        ],
+        Command::SendSas => vec![
+            M::request(Payload::Empty),
+            M::reply(Payload::of::<SasResponse>()),
+        ],
+        Command::ScreenDictionary => vec![
+            M::request(Payload::of::<ScreenDictionaryRequest>()),
+            M::reply(Payload::of::<ScreenDictionaryResponse>()),
+        ],
+        Command::ScreenPreflight => vec![M::request(Payload::of::<ScreenPreflightRequest>())],
+        Command::ScreenSharing => vec![M::request(Payload::of::<ScreenSharingRequest>())],
+        Command::UpdateApply => vec![
+            M::request(Payload::of::<UpdateApplyRequest>())
+                .note("the new binary is uploaded with FileWrite first"),
+            M::reply(Payload::of::<UpdateApplyResponse>()).note("sent before the slave restarts"),
+        ],
+        Command::Goodbye | Command::ProcessList | Command::UpdateCheck | Command::UpdatePush => {
+            Vec::new()
+        }
+    }
+}
+
+// ── Document ──────────────────────────────────────────────────────
+
+/// One command and its messages.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandDoc {
+    pub name: String,
+    pub id: u64,
+    /// Assigned an ID but not implemented by either side.
+    pub reserved: bool,
+    pub messages: Vec<Message>,
+}
+
+/// One bit of the packet flags.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlagDoc {
+    pub name: &'static str,
+    pub bit: u64,
+}
+
+/// The whole reference.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtocolDoc {
+    pub header_sizes: [usize; 2],
+    pub max_payload_size: usize,
+    pub flags: Vec<FlagDoc>,
+    pub commands: Vec<CommandDoc>,
+    /// What replaces any response when the task serving the request
+    /// fails.
+    pub failure: Message,
+    /// Every payload type reached from the commands, by name.
+    pub types: BTreeMap<&'static str, Shape>,
+}
+
+impl ProtocolDoc {
+    /// Describe the protocol as this build speaks it.
+    pub fn generate() -> Self {
+        let commands: Vec<CommandDoc> = Command::ALL
+            .into_iter()
+            .map(|command| {
+                let messages = messages(command);
+                CommandDoc {
+                    name: command.to_string(),
+                    id: command as u64,
+                    reserved: messages.is_empty(),
+                    messages,
+                }
+            })
+            .collect();
+        let failure = Message::reply(Payload::of::<TaskFailure>())
+            .flags(ProtocolFlags::ERROR)
+            .note("under the request's own command");
+
+        let mut types = TypeRegistry::default();
+        let payloads = commands
+            .iter()
+            .flat_map(|c| &c.messages)
+            .chain([&failure])
+            .map(|m| &m.payload);
+        for payload in payloads {
+            if let Payload::Bincode { register, .. } = payload {
+                register(&mut types);
+            }
+        }
+
+        Self {
+            header_sizes: [HEADER_SIZE, HEADER_SIZE_V2],
+            max_payload_size: MAX_PAYLOAD_SIZE,
+            flags: ProtocolFlags::all()
+                .iter_names()
+                .map(|(name, flag)| FlagDoc {
+                    name,
+                    bit: flag.bits(),
+                })
+                .collect(),
+            commands,
+            failure,
+            types: types.types,
+        }
+    }
+
+    /// The reference as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("reference is serializable");
+        json.push('\n');
+        json
+    }
+
+    /// The reference as Markdown.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        self.write_markdown(&mut md).expect("writing to a String");
+        md
+    }
+
+    fn write_markdown(&self, md: &mut String) -> std::fmt::Result {
+        writeln!(md, "# TIX wire protocol")?;
+        writeln!(md)?;
+        writeln!(
+            md,
+            "Generated by `cargo run -p tix-core --bin protocol-doc -- --write docs`; \
+             do not edit by hand."
+        )?;
+        writeln!(md)?;
+        writeln!(
+            md,
+            "Every packet is a header ({} bytes for TIX1, {} for TIX2; see \
+             `tix-core/src/header.rs`) followed by at most {} payload bytes. \
+             Bincode payloads use bincode 1 defaults: little-endian fixed-width \
+             integers, a `u64` length before every string, `vec` and `map`, a `u8` \
+             tag (0 = none, 1 = some) before an `option`, a `u32` variant index \
+             before an enum's fields, and no length before a fixed array. A \
+             `socket_addr` is an enum of `V4([u8; 4], u16)` and `V6([u8; 16], u16)`.",
+            self.header_sizes[0], self.header_sizes[1], self.max_payload_size
+        )?;
+
+        writeln!(md)?;
+        writeln!(md, "## Flags")?;
+        writeln!(md)?;
+        writeln!(md, "| Flag | Bit |")?;
+        writeln!(md, "|------|-----|")?;
+        for flag in &self.flags {
+            writeln!(md, "| `{}` | `0x{:X}` |", flag.name, flag.bit)?;
+        }
+
+        writeln!(md)?;
+        writeln!(md, "## Commands")?;
+        writeln!(md)?;
+        writeln!(md, "| ID | Command | Direction | Flags | Payload | Notes |")?;
+        writeln!(md, "|----|---------|-----------|-------|---------|-------|")?;
+        for command in &self.commands {
+            let id = format!("`0x{:04X}`", command.id);
+            if command.reserved {
+                writeln!(md, "| {id} | {} | | | | reserved |", command.name)?;
+            }
+            for (i, message) in command.messages.iter().enumerate() {
+                let (id, name) = if i == 0 {
+                    (id.as_str(), command.name.as_str())
+                } else {
+                    ("", "")
+                };
+                write_message_row(md, id, name, message)?;
+            }
+        }
+        write_message_row(md, "any", "", &self.failure)?;
+
+        writeln!(md)?;
+        writeln!(md, "## Payload types")?;
+        for (name, shape) in &self.types {
+            writeln!(md)?;
+            writeln!(md, "### {name}")?;
+            writeln!(md)?;
+            match shape {
+                Shape::Struct { fields } => write_fields(md, fields)?,
+                Shape::Enum { variants } => {
+                    writeln!(md, "| Index | Variant | Fields |")?;
+                    writeln!(md, "|-------|---------|--------|")?;
+                    for variant in variants {
+                        let fields: Vec<String> = variant
+                            .fields
+                            .iter()
+                            .map(|f| format!("`{}: {}`", f.name, f.ty))
+                            .collect();
+                        writeln!(
+                            md,
+                            "| {} | `{}` | {} |",
+                            variant.index,
+                            variant.name,
+                            fields.join(", ")
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_message_row(md: &mut String, id: &str, name: &str, message: &Message) -> std::fmt::Result {
+    let flags: Vec<String> = message.flags.iter().map(|f| format!("`{f}`")).collect();
+    writeln!(
+        md,
+        "| {id} | {name} | {} | {} | {} | {} |",
+        message.direction.arrow(),
+        flags.join(" "),
+        cell(&message.payload.describe()),
+        cell(message.note.unwrap_or_default())
+    )
+}
+
+/// `text` safe inside a table cell, code spans included.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn write_fields(md: &mut String, fields: &[Field]) -> std::fmt::Result {
+    writeln!(md, "| Field | Type |")?;
+    writeln!(md, "|-------|------|")?;
+    for field in fields {
+        writeln!(md, "| `{}` | `{}` |", field.name, field.ty)?;
+    }
+    Ok(())
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where `protocol-doc --write docs` puts the reference.
+    fn checked_in(file: &str) -> String {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../docs")
+            .join(file);
+        std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+            .replace("\r\n", "\n")
+    }
+
+    #[test]
+    fn checked_in_reference_is_current() {
+        let doc = ProtocolDoc::generate();
+        let hint = "docs/ is stale; run `cargo run -p tix-core --bin protocol-doc -- --write docs`";
+        assert!(checked_in("protocol.json") == doc.to_json(), "{hint}");
+        assert!(checked_in("protocol.md") == doc.to_markdown(), "{hint}");
+    }
+
+    #[test]
+    fn every_command_and_reached_type_is_described() {
+        let doc = ProtocolDoc::generate();
+        assert_eq!(doc.commands.len(), Command::ALL.len());
+        let reserved: Vec<&str> = doc
+            .commands
+            .iter()
+            .filter(|c| c.reserved)
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(
+            reserved,
+            ["Goodbye", "ProcessList", "UpdateCheck", "UpdatePush"]
+        );
+
+        // Types are reached through fields, not only named by commands.
+        for nested in [
+            "FileDigest",
+            "DeltaChunkInfo",
+            "SessionStats",
+            "ScreenConfig",
+        ] {
+            assert!(doc.types.contains_key(nested), "{nested} missing");
+        }
+        for shape in doc.types.values() {
+            let fields: Vec<&Field> = match shape {
+                Shape::Struct { fields } => fields.iter().collect(),
+                Shape::Enum { variants } => variants.iter().flat_map(|v| &v.fields).collect(),
+            };
+            for field in fields {
+                let named = field
+                    .ty
+                    .split(|c: char| !c.is_alphanumeric() && c != '_')
+                    .filter(|word| word.starts_with(char::is_uppercase));
+                for name in named {
+                    assert!(doc.types.contains_key(name), "{name} not described");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn layouts_follow_bincode() {
+        let doc = ProtocolDoc::generate();
+        // Discriminants 1..=5 in Rust, indices 0..=4 on the wire.
+        let Shape::Enum { variants } = &doc.types["EventLevel"] else {
+            panic!("EventLevel is an enum");
+        };
+        assert_eq!((variants[0].index, variants[0].name), (0, "Critical"));
+        let critical = bincode::serialize(&EventLevel::Critical).unwrap();
+        assert_eq!(critical, 0u32.to_le_bytes());
+
+        let Shape::Struct { fields } = &doc.types["ShellResizeRequest"] else {
+            panic!("ShellResizeRequest is a struct");
+        };
+        let layout: Vec<(&str, &str)> = fields.iter().map(|f| (f.name, f.ty.as_str())).collect();
+        assert_eq!(
+            layout,
+            [
+                ("target_request_id", "u64"),
+                ("cols", "u16"),
+                ("rows", "u16")
+            ]
+        );
+        let bytes = ShellResizeRequest {
+            target_request_id: 1,
+            cols: 2,
+            rows: 3,
+        }
+        .to_bytes()
+        .unwrap();
+        assert_eq!(bytes, [1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 3, 0]);
+
+        let Shape::Enum { variants } = &doc.types["FileHashResponse"] else {
+            panic!("FileHashResponse is an enum");
+        };
+        assert_eq!(variants[1].fields[0].ty, "string");
+        assert!(
+            doc.to_markdown()
+                .contains("| `0x0101` | ShellExecute | master → slave |")
+        );
+    }
+}
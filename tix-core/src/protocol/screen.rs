@@ -8,9 +8,35 @@
 //!   Payload: ScreenStartRequest (bincode)
 //!
 //! Slave  ──[ScreenStart]─────────────────────► Master   (ack)
-//!   Payload: ScreenConfig (bincode)
+//!   Payload: ScreenStartResponse (bincode)
 //! ```
 //!
+//! The request carries the UDP port the master listens on for frames;
+//! the ack carries the slave's UDP `ip:port`, so the screen stream can
+//! be negotiated over an ordinary tix-core connection. When the request
+//! asks for an MTU probe, the master must answer probe datagrams (see
+//! [`crate::rdp::mtu`]) on that port until the ack arrives; the ack
+//! carries the MTU the stream will use.
+//!
+//! A viewer showing several monitors sends one `ScreenStart` per
+//! monitor, each with its own `session` ID and UDP port; every ack
+//! carries that session's endpoint and the monitor's position on the
+//! slave's virtual desktop, which the viewer adds to pointer
+//! coordinates. A `ScreenStart` for a session that is already running
+//! replaces it.
+//!
+//! A viewer that sets `udp_input` offers to send pointer moves as
+//! datagrams to the session's UDP endpoint (see
+//! [`crate::rdp::pointer`]); the ack's `udp_input` says whether the
+//! slave listens for them. Without it, moves stay on the control
+//! connection like every other input event.
+//!
+//! A viewer that sets `timed_input` offers to stamp every input event
+//! with when it took it; once an ack sets `timed_input` too,
+//! `InputMouse` and `InputKeyboard` carry a [`TimedMouseEvent`] or
+//! [`TimedKeyEvent`] instead, and the slave drops pointer moves a burst
+//! of later input has overtaken (see [`crate::rdp::burst`]).
+//!
 //! ## Screen Frames (continuous)
 //! ```text
 //! Slave  ──[ScreenFrame + STREAMING]─────────► Master   (repeated)
@@ -20,7 +46,7 @@
 //! ## Screen Stop
 //! ```text
 //! Master ──[ScreenStop]──────────────────────► Slave
-//!   Payload: empty
+//!   Payload: empty (every session) or one byte (that session)
 //! ```
 //!
 //! ## Input Injection
@@ -30,14 +56,92 @@
 //!
 //! Master ──[InputKeyboard]───────────────────► Slave
 //!   Payload: KeyEvent (bincode)
+//!
+//! Slave  ──[InputMouse | InputKeyboard]──────► Master   (only on refusal)
+//!   Payload: InputRejection (bincode)
+//! ```
+//!
+//! ## Session Mode
+//! ```text
+//! Master ──[ScreenMode]──────────────────────► Slave
+//!   Payload: ScreenModeRequest (bincode)
+//!
+//! Slave  ──[ScreenMode]──────────────────────► Master
+//!   Payload: ScreenModeResponse (bincode)
+//! ```
+//!
+//! A session started with `control: false`, or switched to it later,
+//! is view-only: the slave refuses every input event itself rather than
+//! relying on the viewer to stop sending them.
+//!
+//! ## Secure Attention Sequence
+//! ```text
+//! Master ──[SendSas]─────────────────────────► Slave
+//!   Payload: empty
+//!
+//! Slave  ──[SendSas]─────────────────────────► Master
+//!   Payload: SasResponse (bincode)
 //! ```
+//!
+//! Windows ignores an injected Ctrl+Alt+Del, so the viewer asks for it
+//! by name and the slave raises it with `SendSAS`. That only works from
+//! a service and only where policy allows it, so the answer says why
+//! when it could not be done.
+//!
+//! ## Frame Dictionary
+//! ```text
+//! Master ──[ScreenDictionary]────────────────► Slave
+//!   Payload: ScreenDictionaryRequest (bincode)
+//!
+//! Slave  ──[ScreenDictionary]────────────────► Master
+//!   Payload: ScreenDictionaryResponse (bincode)
+//! ```
+//!
+//! A viewer that sets `dictionary` in its `ScreenStart` can decode
+//! frames compressed against a shared zstd dictionary (see
+//! [`crate::rdp::dictionary`]). If the slave has one, the ack's
+//! `dictionary` offers it by hash, with the bytes unless the hash was
+//! among the request's `known_dictionaries`. Frames stay plain until
+//! the viewer has loaded it and confirmed with `ScreenDictionary`, so
+//! nothing is ever compressed against a dictionary the viewer lacks.
+//!
+//! ## UDP Preflight
+//! ```text
+//! Master ──[ScreenPreflight]─────────────────► Slave
+//!   Payload: ScreenPreflightRequest (bincode)
+//! ```
+//!
+//! A viewer that sets `preflight` in its `ScreenStart` echoes probe
+//! datagrams on its UDP port until the ack arrives (see
+//! [`crate::rdp::preflight`]). The ack's `preflight` reports how many
+//! of the slave's probes were echoed; the viewer answers with how many
+//! it received. There is no reply, but a slave told that none arrived
+//! stops the session rather than stream into a firewall.
+//!
+//! ## Low-Bandwidth Sharing
+//! ```text
+//! Master ──[ScreenSharing]───────────────────► Slave
+//!   Payload: ScreenSharingRequest (bincode)
+//! ```
+//!
+//! A viewer that sets `thumbnails` in its `ScreenStart` can show the
+//! periodic thumbnails a slave sends instead of frames on a slow link
+//! (see [`crate::rdp::thumbnail`]); with `low_bandwidth` as well the
+//! session starts that way. The ack repeats both as the slave applied
+//! them. `ScreenSharing` switches a running session either way and
+//! says whether the slave may fall back to thumbnails by itself. There
+//! is no reply: the stream itself shows the switch.
 
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 
 use crate::error::TixError;
 use crate::flags::ProtocolFlags;
 use crate::message::Command;
 use crate::packet::Packet;
+use crate::protocol::schema::wire_schema;
+#[cfg(feature = "rdp")]
+use crate::rdp::types::PixelFormat;
 
 // ── Screen Start ──────────────────────────────────────────────────
 
@@ -61,6 +165,47 @@ pub struct ScreenStartRequest {
 
     /// Monitor index to capture (0 = primary).
     pub monitor: u8,
+
+    /// UDP port the master receives frames on (`None` = not negotiated
+    /// over this channel).
+    pub udp_port: Option<u16>,
+
+    /// Whether input events are accepted (`false` = view-only).
+    pub control: bool,
+
+    /// Stream MTU in UDP payload bytes (0 = transport default). When
+    /// probing, the largest size tried.
+    pub mtu: u16,
+
+    /// Measure the path MTU before streaming.
+    pub probe_mtu: bool,
+
+    /// Screen session to start or replace, one per monitor (see
+    /// [`MAX_SESSIONS`](crate::rdp::transport::MAX_SESSIONS)).
+    pub session: u8,
+
+    /// Offer to send pointer moves over the screen socket.
+    pub udp_input: bool,
+
+    /// The viewer can decode frames compressed against a dictionary.
+    pub dictionary: bool,
+
+    /// Hashes of the dictionaries the viewer already holds, so the
+    /// slave can offer one without sending its bytes.
+    pub known_dictionaries: Vec<u64>,
+
+    /// Check that UDP gets through before the ack.
+    pub preflight: bool,
+
+    /// Offer to stamp input events with the viewer's clock.
+    pub timed_input: bool,
+
+    /// The viewer can show thumbnails in place of frames, so the slave
+    /// may fall back to them on a slow link.
+    pub thumbnails: bool,
+
+    /// Start with thumbnails rather than frames.
+    pub low_bandwidth: bool,
 }
 
 impl Default for ScreenStartRequest {
@@ -72,6 +217,18 @@ impl Default for ScreenStartRequest {
             format: ImageFormat::Jpeg,
             include_cursor: true,
             monitor: 0,
+            udp_port: None,
+            control: true,
+            mtu: 0,
+            probe_mtu: false,
+            session: 0,
+            udp_input: false,
+            dictionary: false,
+            known_dictionaries: Vec::new(),
+            preflight: false,
+            timed_input: false,
+            thumbnails: false,
+            low_bandwidth: false,
         }
     }
 }
@@ -106,6 +263,82 @@ impl ScreenStartRequest {
         self
     }
 
+    /// Set the UDP port the master will receive frames on.
+    pub fn with_udp_port(mut self, port: u16) -> Self {
+        self.udp_port = Some(port);
+        self
+    }
+
+    /// Accept input events (`true`) or start view-only (`false`).
+    pub fn with_control(mut self, control: bool) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Set the stream MTU (0 = transport default).
+    pub fn with_mtu(mut self, mtu: u16) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Ask the slave to probe the path MTU before streaming.
+    pub fn with_mtu_probe(mut self, probe: bool) -> Self {
+        self.probe_mtu = probe;
+        self
+    }
+
+    /// Capture monitor `monitor` (0 = primary).
+    pub fn with_monitor(mut self, monitor: u8) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// Run as screen session `session`.
+    pub fn with_session(mut self, session: u8) -> Self {
+        self.session = session;
+        self
+    }
+
+    /// Offer pointer moves over UDP (`true`) or keep all input on the
+    /// control connection.
+    pub fn with_udp_input(mut self, udp_input: bool) -> Self {
+        self.udp_input = udp_input;
+        self
+    }
+
+    /// Accept a frame dictionary, naming the ones already held by hash.
+    pub fn with_dictionary(mut self, known: Vec<u64>) -> Self {
+        self.dictionary = true;
+        self.known_dictionaries = known;
+        self
+    }
+
+    /// Ask the slave to probe the UDP path before the ack.
+    pub fn with_preflight(mut self, preflight: bool) -> Self {
+        self.preflight = preflight;
+        self
+    }
+
+    /// Offer timestamped input (`true`) or send the plain events.
+    pub fn with_timed_input(mut self, timed_input: bool) -> Self {
+        self.timed_input = timed_input;
+        self
+    }
+
+    /// Accept thumbnails in place of frames when the link is too slow.
+    pub fn with_thumbnails(mut self, thumbnails: bool) -> Self {
+        self.thumbnails = thumbnails;
+        self
+    }
+
+    /// Start with thumbnails rather than frames; implies
+    /// [`with_thumbnails`](Self::with_thumbnails).
+    pub fn with_low_bandwidth(mut self, low_bandwidth: bool) -> Self {
+        self.low_bandwidth = low_bandwidth;
+        self.thumbnails |= low_bandwidth;
+        self
+    }
+
     /// Serialize to bytes.
     pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
         bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
@@ -145,6 +378,83 @@ pub struct ScreenConfig {
 
     /// Monitor name/description.
     pub monitor_name: String,
+
+    /// Slave UDP endpoint frames are sent from (`None` when the stream
+    /// was negotiated out of band).
+    pub udp_endpoint: Option<SocketAddr>,
+
+    /// MTU the stream is sent with; the receiver sizes its buffer from
+    /// it.
+    pub mtu: u16,
+
+    /// Session the stream runs as; its frame headers carry it.
+    pub session: u8,
+
+    /// Top-left corner of the monitor on the slave's virtual desktop.
+    /// Pointer positions in the frame are offset by it before they are
+    /// sent back as input.
+    pub origin: (i32, i32),
+
+    /// Tile size of the delta stream in pixels. Merged regions are
+    /// multiples of it.
+    pub block_size: u16,
+
+    /// The slave takes pointer moves on `udp_endpoint`; only ever set
+    /// when the request offered them.
+    pub udp_input: bool,
+
+    /// Dictionary the slave would compress frames against once the
+    /// viewer confirms it; only ever set when the request accepted one.
+    pub dictionary: Option<DictionaryOffer>,
+
+    /// The slave's side of the UDP preflight, when the request asked
+    /// for one.
+    pub preflight: Option<PreflightReport>,
+
+    /// Input events are to come stamped; only ever set when the request
+    /// offered it.
+    pub timed_input: bool,
+
+    /// The slave may fall back to thumbnails; only ever set when the
+    /// request offered to show them.
+    pub thumbnails: bool,
+
+    /// The session starts with thumbnails.
+    pub low_bandwidth: bool,
+}
+
+/// A frame dictionary offered in the `ScreenStart` ack.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DictionaryOffer {
+    /// xxh64 of the dictionary bytes.
+    pub hash: u64,
+    /// The dictionary, or empty when the request listed `hash` as
+    /// already known.
+    pub bytes: Vec<u8>,
+}
+
+impl std::fmt::Debug for DictionaryOffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DictionaryOffer")
+            .field("hash", &format_args!("{:016x}", self.hash))
+            .field("len", &self.bytes.len())
+            .finish()
+    }
+}
+
+/// What the slave saw of the UDP preflight.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PreflightReport {
+    /// Probes sent to the viewer's UDP port.
+    pub sent: u16,
+    /// Probes the viewer echoed back.
+    pub echoed: u16,
+    /// Where the viewer saw the probes come from, as its echoes quote
+    /// it; not `udp_endpoint` when NAT is on the slave's side.
+    pub seen_as: Option<SocketAddr>,
+    /// Where the echoes came from; not the viewer's address and port
+    /// when NAT is on its side.
+    pub echoed_from: Option<SocketAddr>,
 }
 
 impl ScreenConfig {
@@ -157,6 +467,27 @@ impl ScreenConfig {
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
         bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
     }
+}
+
+/// Ack payload for `Command::ScreenStart`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ScreenStartResponse {
+    /// Capture is running with the given configuration.
+    Started(ScreenConfig),
+    /// Capture could not be started (no display, already running, …).
+    Failed(String),
+}
+
+impl ScreenStartResponse {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
 
     /// Build a response `Packet`.
     pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
@@ -165,17 +496,307 @@ impl ScreenConfig {
     }
 }
 
+// ── Session Mode ──────────────────────────────────────────────────
+
+/// Request payload for `Command::ScreenMode`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScreenModeRequest {
+    /// `true` for control, `false` for view-only.
+    pub control: bool,
+}
+
+impl ScreenModeRequest {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::ScreenMode, payload)
+    }
+}
+
+/// Response payload for `Command::ScreenMode`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ScreenModeResponse {
+    /// The session now runs in the given mode.
+    Applied { control: bool },
+    /// No screen session is running.
+    NoSession,
+}
+
+impl ScreenModeResponse {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::ScreenMode, payload)
+    }
+}
+
+/// Why the slave refused an input event.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum InputRejection {
+    /// The session is view-only.
+    ViewOnly,
+}
+
+impl InputRejection {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build the response to the refused `InputMouse` or
+    /// `InputKeyboard` command.
+    pub fn into_packet(self, request_id: u64, command: Command) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, command, payload)
+    }
+}
+
+impl std::fmt::Display for InputRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ViewOnly => f.write_str("view-only session"),
+        }
+    }
+}
+
+/// Response payload for `Command::SendSas`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SasResponse {
+    /// The sequence was raised on the slave.
+    Sent,
+    /// The slave could not or would not raise it.
+    Refused(SasRefusal),
+}
+
+impl SasResponse {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::SendSas, payload)
+    }
+}
+
+/// Why the slave did not raise the secure attention sequence.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SasRefusal {
+    /// `screen.allow_sas` is off in the slave's config.
+    Disabled,
+    /// No screen session is running.
+    NoSession,
+    /// The session is view-only.
+    ViewOnly,
+    /// The slave is not running as a service.
+    NotAService,
+    /// The `SoftwareSASGeneration` policy does not let services raise it.
+    PolicyDenied,
+    /// The slave's platform has no secure attention sequence.
+    Unsupported,
+    /// `SendSAS` itself failed.
+    Failed(String),
+}
+
+impl std::fmt::Display for SasRefusal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Disabled => f.write_str("Ctrl+Alt+Del is disabled on the slave"),
+            Self::NoSession => f.write_str("no screen session"),
+            Self::ViewOnly => f.write_str("view-only session"),
+            Self::NotAService => f.write_str("the slave is not running as a service"),
+            Self::PolicyDenied => f.write_str("policy does not let services send Ctrl+Alt+Del"),
+            Self::Unsupported => f.write_str("the slave's platform has no Ctrl+Alt+Del"),
+            Self::Failed(e) => write!(f, "SendSAS failed: {}", e),
+        }
+    }
+}
+
+// ── Frame Dictionary ──────────────────────────────────────────────
+
+/// Request payload for `Command::ScreenDictionary`: the viewer holds
+/// the offered dictionary and can decode frames compressed with it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScreenDictionaryRequest {
+    /// Session whose stream should switch.
+    pub session: u8,
+    /// Hash of the dictionary loaded, as offered.
+    pub hash: u64,
+}
+
+impl ScreenDictionaryRequest {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::ScreenDictionary, payload)
+    }
+}
+
+/// Response payload for `Command::ScreenDictionary`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ScreenDictionaryResponse {
+    /// Frames from the next one on are compressed with the dictionary.
+    Enabled,
+    /// The stream stays plain: no such session, or a different
+    /// dictionary than the one offered.
+    Refused(String),
+}
+
+impl ScreenDictionaryResponse {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::ScreenDictionary, payload)
+    }
+}
+
+// ── UDP Preflight ─────────────────────────────────────────────────
+
+/// Request payload for `Command::ScreenPreflight`: the viewer's side of
+/// the UDP preflight.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScreenPreflightRequest {
+    /// Session the preflight ran for.
+    pub session: u8,
+    /// Distinct probes that arrived.
+    pub received: u16,
+    /// Where they came from.
+    pub source: Option<SocketAddr>,
+}
+
+impl ScreenPreflightRequest {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::ScreenPreflight, payload)
+    }
+}
+
+// ── Low-Bandwidth Sharing ─────────────────────────────────────────
+
+/// Request payload for `Command::ScreenSharing`: switch a session
+/// between frames and thumbnails.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScreenSharingRequest {
+    /// Session to switch.
+    pub session: u8,
+    /// Send thumbnails (`true`) or frames.
+    pub low_bandwidth: bool,
+    /// Let the slave fall back to thumbnails by itself while frames are
+    /// sent.
+    pub fallback: bool,
+}
+
+impl ScreenSharingRequest {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::ScreenSharing, payload)
+    }
+}
+
 // ── Screen Stop ───────────────────────────────────────────────────
 
-/// Request to stop screen capture. Payload is empty, but we define a
-/// type for consistency and future extensibility.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
-pub struct ScreenStopRequest;
+/// Request to stop screen capture: every session, or just one. The
+/// payload is empty or the session byte, so a bare `ScreenStop` from an
+/// older viewer still stops everything.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct ScreenStopRequest {
+    /// The session to stop (`None` = all of them).
+    pub session: Option<u8>,
+}
 
 impl ScreenStopRequest {
+    /// Stop only session `session`.
+    pub fn session(session: u8) -> Self {
+        Self {
+            session: Some(session),
+        }
+    }
+
+    /// Decode a `ScreenStop` payload.
+    pub fn from_payload(payload: &[u8]) -> Self {
+        Self {
+            session: payload.first().copied(),
+        }
+    }
+
     /// Build a command `Packet`.
     pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
-        Packet::new_command(request_id, Command::ScreenStop, Vec::new())
+        let payload = self.session.map(|s| vec![s]).unwrap_or_default();
+        Packet::new_command(request_id, Command::ScreenStop, payload)
     }
 }
 
@@ -278,6 +899,36 @@ pub enum ImageFormat {
     RawBgra,
     /// Raw RGB pixels.
     RawRgb,
+    /// 16-bit RGB565 pixels, half the size of BGRA before compression.
+    /// For slow links; colours lose their low bits.
+    Rgb565,
+    /// 8-bit grayscale, a quarter the size of BGRA.
+    Gray8,
+}
+
+#[cfg(feature = "rdp")]
+impl ImageFormat {
+    /// Pixel layout the RDP pipeline sends for this format. Formats the
+    /// pipeline does not implement map to full-colour BGRA8.
+    pub fn pixel_format(self) -> PixelFormat {
+        match self {
+            ImageFormat::Rgb565 => PixelFormat::Rgb565,
+            ImageFormat::Gray8 => PixelFormat::Gray8,
+            _ => PixelFormat::Bgra8,
+        }
+    }
+}
+
+#[cfg(feature = "rdp")]
+impl From<PixelFormat> for ImageFormat {
+    fn from(format: PixelFormat) -> Self {
+        match format {
+            PixelFormat::Rgb565 => ImageFormat::Rgb565,
+            PixelFormat::Gray8 => ImageFormat::Gray8,
+            PixelFormat::Rgb8 => ImageFormat::RawRgb,
+            PixelFormat::Bgra8 | PixelFormat::Rgba8 => ImageFormat::RawBgra,
+        }
+    }
 }
 
 impl std::fmt::Display for ImageFormat {
@@ -287,6 +938,8 @@ impl std::fmt::Display for ImageFormat {
             ImageFormat::Png => write!(f, "png"),
             ImageFormat::RawBgra => write!(f, "raw_bgra"),
             ImageFormat::RawRgb => write!(f, "raw_rgb"),
+            ImageFormat::Rgb565 => write!(f, "rgb565"),
+            ImageFormat::Gray8 => write!(f, "gray8"),
         }
     }
 }
@@ -487,6 +1140,206 @@ impl KeyEvent {
     }
 }
 
+// ── Timed Input ───────────────────────────────────────────────────
+
+/// A [`MouseEvent`] stamped with when the viewer took it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TimedMouseEvent {
+    /// Milliseconds on the viewer's monotonic clock. Only compared with
+    /// the viewer's other stamps, so the two machines' clocks never
+    /// need to agree.
+    pub at_ms: u64,
+    pub event: MouseEvent,
+}
+
+impl TimedMouseEvent {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::InputMouse, payload)
+    }
+}
+
+/// A [`KeyEvent`] stamped with when the viewer took it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TimedKeyEvent {
+    /// Milliseconds on the viewer's monotonic clock, as for
+    /// [`TimedMouseEvent::at_ms`].
+    pub at_ms: u64,
+    pub event: KeyEvent,
+}
+
+impl TimedKeyEvent {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::InputKeyboard, payload)
+    }
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    struct ScreenStartRequest {
+        quality: u8,
+        fps: u8,
+        region: Option<CaptureRegion>,
+        format: ImageFormat,
+        include_cursor: bool,
+        monitor: u8,
+        udp_port: Option<u16>,
+        control: bool,
+        mtu: u16,
+        probe_mtu: bool,
+        session: u8,
+        udp_input: bool,
+        dictionary: bool,
+        known_dictionaries: Vec<u64>,
+        preflight: bool,
+        timed_input: bool,
+        thumbnails: bool,
+        low_bandwidth: bool,
+    }
+}
+wire_schema! {
+    struct ScreenConfig {
+        width: u32,
+        height: u32,
+        quality: u8,
+        fps: u8,
+        format: ImageFormat,
+        monitor_name: String,
+        udp_endpoint: Option<SocketAddr>,
+        mtu: u16,
+        session: u8,
+        origin: (i32, i32),
+        block_size: u16,
+        udp_input: bool,
+        dictionary: Option<DictionaryOffer>,
+        preflight: Option<PreflightReport>,
+        timed_input: bool,
+        thumbnails: bool,
+        low_bandwidth: bool,
+    }
+}
+wire_schema! {
+    struct DictionaryOffer { hash: u64, bytes: Vec<u8> }
+}
+wire_schema! {
+    struct PreflightReport {
+        sent: u16,
+        echoed: u16,
+        seen_as: Option<SocketAddr>,
+        echoed_from: Option<SocketAddr>,
+    }
+}
+wire_schema! {
+    enum ScreenStartResponse { Started { 0: ScreenConfig }, Failed { 0: String } }
+}
+wire_schema! {
+    struct ScreenModeRequest { control: bool }
+}
+wire_schema! {
+    enum ScreenModeResponse { Applied { control: bool }, NoSession }
+}
+wire_schema! {
+    struct ScreenDictionaryRequest { session: u8, hash: u64 }
+}
+wire_schema! {
+    enum ScreenDictionaryResponse { Enabled, Refused { 0: String } }
+}
+wire_schema! {
+    struct ScreenPreflightRequest { session: u8, received: u16, source: Option<SocketAddr> }
+}
+wire_schema! {
+    struct ScreenSharingRequest { session: u8, low_bandwidth: bool, fallback: bool }
+}
+wire_schema! {
+    enum InputRejection { ViewOnly }
+}
+wire_schema! {
+    enum SasResponse { Sent, Refused { 0: SasRefusal } }
+}
+wire_schema! {
+    enum SasRefusal {
+        Disabled,
+        NoSession,
+        ViewOnly,
+        NotAService,
+        PolicyDenied,
+        Unsupported,
+        Failed { 0: String },
+    }
+}
+wire_schema! {
+    struct ScreenFrame {
+        frame_number: u64,
+        timestamp_us: u64,
+        width: u32,
+        height: u32,
+        format: ImageFormat,
+        data: Vec<u8>,
+        cursor: Option<CursorInfo>,
+        is_delta: bool,
+    }
+}
+wire_schema! {
+    struct CaptureRegion { x: u32, y: u32, width: u32, height: u32 }
+}
+wire_schema! {
+    enum ImageFormat { Jpeg, Png, RawBgra, RawRgb, Rgb565, Gray8 }
+}
+wire_schema! {
+    struct CursorInfo { x: i32, y: i32, visible: bool }
+}
+wire_schema! {
+    struct MouseEvent {
+        x: i32,
+        y: i32,
+        kind: MouseEventKind,
+        button: MouseButton,
+        scroll_delta: i16,
+    }
+}
+wire_schema! {
+    enum MouseEventKind { Move, Press, Release, Scroll, DoubleClick }
+}
+wire_schema! {
+    enum MouseButton { None, Left, Right, Middle, X1, X2 }
+}
+wire_schema! {
+    struct KeyEvent { virtual_key: u16, scan_code: u16, action: KeyAction, modifiers: u8 }
+}
+wire_schema! {
+    enum KeyAction { Press, Release }
+}
+wire_schema! {
+    struct TimedMouseEvent { at_ms: u64, event: MouseEvent }
+}
+wire_schema! {
+    struct TimedKeyEvent { at_ms: u64, event: KeyEvent }
+}
+
 // ── Tests ─────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -498,7 +1351,12 @@ mod tests {
         let req = ScreenStartRequest::new()
             .with_quality(90)
             .with_fps(60)
-            .with_format(ImageFormat::Png);
+            .with_format(ImageFormat::Png)
+            .with_udp_port(50000)
+            .with_mtu(1472)
+            .with_mtu_probe(true)
+            .with_udp_input(true)
+            .with_timed_input(true);
 
         let bytes = req.to_bytes().unwrap();
         let decoded = ScreenStartRequest::from_bytes(&bytes).unwrap();
@@ -506,6 +1364,127 @@ mod tests {
         assert_eq!(decoded.quality, 90);
         assert_eq!(decoded.fps, 60);
         assert_eq!(decoded.format, ImageFormat::Png);
+        assert_eq!(decoded.udp_port, Some(50000));
+        assert_eq!(decoded.mtu, 1472);
+        assert!(decoded.probe_mtu);
+        assert!(decoded.udp_input);
+        assert!(!ScreenStartRequest::new().udp_input);
+        assert!(decoded.timed_input);
+        assert!(!ScreenStartRequest::new().timed_input);
+    }
+
+    #[test]
+    fn control_defaults_on_and_mode_roundtrip() {
+        assert!(ScreenStartRequest::new().control);
+        let view = ScreenStartRequest::new().with_control(false);
+        let decoded = ScreenStartRequest::from_bytes(&view.to_bytes().unwrap()).unwrap();
+        assert!(!decoded.control);
+
+        let pkt = ScreenModeRequest { control: false }.into_packet(8).unwrap();
+        assert_eq!(pkt.command().unwrap(), Command::ScreenMode);
+        assert!(
+            !ScreenModeRequest::from_bytes(pkt.payload())
+                .unwrap()
+                .control
+        );
+
+        let applied = ScreenModeResponse::Applied { control: true };
+        let pkt = applied.into_packet(8).unwrap();
+        assert_eq!(
+            ScreenModeResponse::from_bytes(pkt.payload()).unwrap(),
+            applied
+        );
+
+        let pkt = InputRejection::ViewOnly
+            .into_packet(9, Command::InputMouse)
+            .unwrap();
+        assert_eq!(pkt.command().unwrap(), Command::InputMouse);
+        assert_eq!(pkt.message_type(), crate::message::MessageType::Response);
+        let rejection = InputRejection::from_bytes(pkt.payload()).unwrap();
+        assert_eq!(rejection.to_string(), "view-only session");
+    }
+
+    #[test]
+    fn sas_response_roundtrip() {
+        for response in [
+            SasResponse::Sent,
+            SasResponse::Refused(SasRefusal::NotAService),
+            SasResponse::Refused(SasRefusal::Failed("access denied".into())),
+        ] {
+            let pkt = response.clone().into_packet(3).unwrap();
+            assert_eq!(pkt.command().unwrap(), Command::SendSas);
+            assert_eq!(SasResponse::from_bytes(pkt.payload()).unwrap(), response);
+        }
+        assert_eq!(
+            SasRefusal::Failed("access denied".into()).to_string(),
+            "SendSAS failed: access denied"
+        );
+    }
+
+    #[test]
+    fn dictionary_is_offered_by_hash_and_confirmed() {
+        let req = ScreenStartRequest::new().with_dictionary(vec![7, 9]);
+        let decoded = ScreenStartRequest::from_bytes(&req.to_bytes().unwrap()).unwrap();
+        assert!(decoded.dictionary);
+        assert_eq!(decoded.known_dictionaries, [7, 9]);
+        assert!(!ScreenStartRequest::new().dictionary);
+
+        let confirm = ScreenDictionaryRequest { session: 1, hash: 9 };
+        let pkt = confirm.into_packet(4).unwrap();
+        assert_eq!(pkt.command().unwrap(), Command::ScreenDictionary);
+        assert_eq!(
+            ScreenDictionaryRequest::from_bytes(pkt.payload()).unwrap(),
+            confirm
+        );
+
+        for response in [
+            ScreenDictionaryResponse::Enabled,
+            ScreenDictionaryResponse::Refused("no screen session 1".into()),
+        ] {
+            let pkt = response.clone().into_packet(4).unwrap();
+            assert_eq!(pkt.message_type(), crate::message::MessageType::Response);
+            assert_eq!(
+                ScreenDictionaryResponse::from_bytes(pkt.payload()).unwrap(),
+                response
+            );
+        }
+    }
+
+    #[test]
+    fn preflight_is_requested_and_reported() {
+        let req = ScreenStartRequest::new().with_preflight(true);
+        assert!(ScreenStartRequest::from_bytes(&req.to_bytes().unwrap()).unwrap().preflight);
+        assert!(!ScreenStartRequest::new().preflight);
+
+        let report = ScreenPreflightRequest {
+            session: 1,
+            received: 0,
+            source: None,
+        };
+        let pkt = report.into_packet(5).unwrap();
+        assert_eq!(pkt.command().unwrap(), Command::ScreenPreflight);
+        assert_eq!(
+            ScreenPreflightRequest::from_bytes(pkt.payload()).unwrap(),
+            report
+        );
+    }
+
+    #[test]
+    fn low_bandwidth_is_requested_and_switched() {
+        let req = ScreenStartRequest::new().with_low_bandwidth(true);
+        let decoded = ScreenStartRequest::from_bytes(&req.to_bytes().unwrap()).unwrap();
+        assert!(decoded.low_bandwidth && decoded.thumbnails);
+        let offered = ScreenStartRequest::new().with_thumbnails(true);
+        assert!(offered.thumbnails && !offered.low_bandwidth);
+
+        let switch = ScreenSharingRequest {
+            session: 2,
+            low_bandwidth: false,
+            fallback: true,
+        };
+        let pkt = switch.into_packet(6).unwrap();
+        assert_eq!(pkt.command().unwrap(), Command::ScreenSharing);
+        assert_eq!(ScreenSharingRequest::from_bytes(pkt.payload()).unwrap(), switch);
     }
 
     #[test]
@@ -530,6 +1509,25 @@ mod tests {
             fps: 30,
             format: ImageFormat::Jpeg,
             monitor_name: "Primary".to_string(),
+            udp_endpoint: None,
+            mtu: 1400,
+            session: 1,
+            origin: (-1920, 0),
+            block_size: 32,
+            udp_input: true,
+            dictionary: Some(DictionaryOffer {
+                hash: 0x0123_4567_89ab_cdef,
+                bytes: vec![0x37, 0xa4, 0x30, 0xec],
+            }),
+            preflight: Some(PreflightReport {
+                sent: 8,
+                echoed: 7,
+                seen_as: Some("203.0.113.9:61000".parse().unwrap()),
+                echoed_from: None,
+            }),
+            timed_input: true,
+            thumbnails: true,
+            low_bandwidth: false,
         };
 
         let bytes = config.to_bytes().unwrap();
@@ -537,6 +1535,53 @@ mod tests {
         assert_eq!(config, decoded);
     }
 
+    #[test]
+    fn screen_start_response_roundtrip() {
+        let started = ScreenStartResponse::Started(ScreenConfig {
+            width: 2560,
+            height: 1440,
+            quality: 75,
+            fps: 60,
+            format: ImageFormat::RawBgra,
+            monitor_name: "Monitor 0".to_string(),
+            udp_endpoint: Some("10.0.0.10:40123".parse().unwrap()),
+            mtu: 1472,
+            session: 0,
+            origin: (0, 0),
+            block_size: 64,
+            udp_input: false,
+            dictionary: None,
+            preflight: None,
+            timed_input: false,
+            thumbnails: false,
+            low_bandwidth: false,
+        });
+        let packet = started.clone().into_packet(3).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::ScreenStart);
+        assert_eq!(packet.message_type(), crate::message::MessageType::Response);
+        assert_eq!(
+            ScreenStartResponse::from_bytes(packet.payload()).unwrap(),
+            started
+        );
+
+        let failed = ScreenStartResponse::Failed("no display".to_string());
+        let bytes = failed.to_bytes().unwrap();
+        assert_eq!(ScreenStartResponse::from_bytes(&bytes).unwrap(), failed);
+    }
+
+    #[test]
+    fn screen_stop_names_one_session_or_all() {
+        let all = ScreenStopRequest::default().into_packet(1).unwrap();
+        assert!(all.payload().is_empty());
+        assert_eq!(ScreenStopRequest::from_payload(all.payload()).session, None);
+
+        let one = ScreenStopRequest::session(2).into_packet(2).unwrap();
+        assert_eq!(
+            ScreenStopRequest::from_payload(one.payload()),
+            ScreenStopRequest::session(2)
+        );
+    }
+
     #[test]
     fn screen_frame_roundtrip() {
         let frame = ScreenFrame {
@@ -617,6 +1662,25 @@ mod tests {
         assert_eq!(packet.command().unwrap(), Command::InputKeyboard);
     }
 
+    #[test]
+    fn timed_input_rides_the_plain_commands() {
+        let mouse = TimedMouseEvent {
+            at_ms: 90_061_042,
+            event: MouseEvent::move_to(-5, 9),
+        };
+        let packet = mouse.into_packet(12).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::InputMouse);
+        assert_eq!(TimedMouseEvent::from_bytes(packet.payload()).unwrap(), mouse);
+
+        let key = TimedKeyEvent {
+            at_ms: 90_061_050,
+            event: KeyEvent::release(0x0D, 0x1C, key_modifiers::NONE),
+        };
+        let packet = key.into_packet(13).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::InputKeyboard);
+        assert_eq!(TimedKeyEvent::from_bytes(packet.payload()).unwrap(), key);
+    }
+
     #[test]
     fn fps_clamped() {
         let req = ScreenStartRequest::new().with_fps(200);
@@ -637,5 +1701,19 @@ mod tests {
         assert_eq!(ImageFormat::Jpeg.to_string(), "jpeg");
         assert_eq!(ImageFormat::Png.to_string(), "png");
         assert_eq!(ImageFormat::RawBgra.to_string(), "raw_bgra");
+        assert_eq!(ImageFormat::Rgb565.to_string(), "rgb565");
+    }
+
+    #[test]
+    #[cfg(feature = "rdp")]
+    fn image_format_maps_to_pixel_format() {
+        assert_eq!(ImageFormat::Jpeg.pixel_format(), PixelFormat::Bgra8);
+        assert_eq!(ImageFormat::Rgb565.pixel_format(), PixelFormat::Rgb565);
+        assert_eq!(ImageFormat::Gray8.pixel_format(), PixelFormat::Gray8);
+        for format in [PixelFormat::Bgra8, PixelFormat::Rgb565, PixelFormat::Gray8] {
+            assert_eq!(ImageFormat::from(format).pixel_format(), format);
+        }
+        assert_eq!("grey".parse::<PixelFormat>(), Ok(PixelFormat::Gray8));
+        assert!("cmyk".parse::<PixelFormat>().is_err());
     }
 }
@@ -0,0 +1,325 @@
+//! File search protocol — find entries by name under a remote directory.
+//!
+//! # Wire Protocol
+//!
+//! ```text
+//! Master ──[FileSearch]───────────────────────► Slave
+//!   Payload: FileSearchRequest (bincode)
+//!
+//! Slave  ──[FileSearch + STREAMING]───────────► Master   (repeated)
+//!   Payload: FileSearchBatch (bincode)
+//!
+//! Slave  ──[FileSearch + FINAL_FRAGMENT]──────► Master
+//!   Payload: FileSearchSummary (bincode)
+//!
+//! Master ──[ShellCancel]──────────────────────► Slave
+//!   Payload: request_id of the search to stop (u64 LE)
+//! ```
+//!
+//! Matches are sent in batches as the walk finds them, so the master can
+//! show results long before a large tree has been covered. A cancelled
+//! search sends no summary.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::TixError;
+use crate::flags::ProtocolFlags;
+use crate::message::Command;
+use crate::packet::Packet;
+use crate::protocol::file::FileMetadata;
+use crate::protocol::schema::wire_schema;
+
+/// Matches per [`FileSearchBatch`].
+pub const SEARCH_BATCH_SIZE: usize = 64;
+
+/// Default for [`FileSearchRequest::max_results`].
+pub const DEFAULT_MAX_RESULTS: u32 = 1000;
+
+// ── Request ───────────────────────────────────────────────────────
+
+/// Request payload for `Command::FileSearch`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileSearchRequest {
+    /// Directory to search under.
+    pub root: String,
+
+    /// Name to look for: a glob with `*` and `?`, or else a substring.
+    pub pattern: String,
+
+    /// Compare names case-sensitively.
+    pub case_sensitive: bool,
+
+    /// Stop after this many matches (0 = no cap).
+    pub max_results: u32,
+
+    /// Levels below `root` to descend (0 = no limit, 1 = `root` only).
+    pub max_depth: u32,
+
+    /// Report directories whose names match, not just files.
+    pub include_dirs: bool,
+}
+
+impl FileSearchRequest {
+    /// Case-insensitive search for files, capped at
+    /// [`DEFAULT_MAX_RESULTS`].
+    pub fn new(root: impl Into<String>, pattern: impl Into<String>) -> Self {
+        Self {
+            root: root.into(),
+            pattern: pattern.into(),
+            case_sensitive: false,
+            max_results: DEFAULT_MAX_RESULTS,
+            max_depth: 0,
+            include_dirs: false,
+        }
+    }
+
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    pub fn with_max_results(mut self, max_results: u32) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_include_dirs(mut self, include_dirs: bool) -> Self {
+        self.include_dirs = include_dirs;
+        self
+    }
+
+    /// Whether the entry called `name` is a match.
+    pub fn matches(&self, name: &str) -> bool {
+        if self.case_sensitive {
+            name_matches(&self.pattern, name)
+        } else {
+            name_matches(&self.pattern.to_lowercase(), &name.to_lowercase())
+        }
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet` carrying this request.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::FileSearch, payload)
+    }
+}
+
+/// `pattern` against `name`: a glob when it holds `*` or `?`, else a
+/// substring test.
+pub(crate) fn name_matches(pattern: &str, name: &str) -> bool {
+    if !pattern.contains(['*', '?']) {
+        return name.contains(pattern);
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Greedy match with backtracking to the last `*`.
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// ── Results ───────────────────────────────────────────────────────
+
+/// A batch of matches, streamed from slave to master.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileSearchBatch {
+    /// Sequential batch number (0-based).
+    pub batch_number: u64,
+
+    pub matches: Vec<FileMetadata>,
+}
+
+impl FileSearchBatch {
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a streaming response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response_with_flags(
+            request_id,
+            Command::FileSearch,
+            payload,
+            ProtocolFlags::STREAMING,
+        )
+    }
+}
+
+/// Final message of a search.
+///
+/// Carried in a packet with `FINAL_FRAGMENT` flag set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileSearchSummary {
+    /// Matches sent across all batches.
+    pub matches: u64,
+
+    /// Directories read, `root` included.
+    pub directories_scanned: u64,
+
+    /// Wall time of the walk in milliseconds.
+    pub elapsed_ms: u64,
+
+    /// The walk stopped at `max_results`.
+    pub truncated: bool,
+
+    /// Why the search could not run at all, e.g. a missing root.
+    pub error: Option<String>,
+}
+
+impl FileSearchSummary {
+    /// A search that could not start.
+    pub fn failed(error: impl Into<String>) -> Self {
+        Self {
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build the final response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response_with_flags(
+            request_id,
+            Command::FileSearch,
+            payload,
+            ProtocolFlags::FINAL_FRAGMENT,
+        )
+    }
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    struct FileSearchRequest {
+        root: String,
+        pattern: String,
+        case_sensitive: bool,
+        max_results: u32,
+        max_depth: u32,
+        include_dirs: bool,
+    }
+}
+wire_schema! {
+    struct FileSearchBatch { batch_number: u64, matches: Vec<FileMetadata> }
+}
+wire_schema! {
+    struct FileSearchSummary {
+        matches: u64,
+        directories_scanned: u64,
+        elapsed_ms: u64,
+        truncated: bool,
+        error: Option<String>,
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substring_and_glob_patterns() {
+        let req = FileSearchRequest::new("C:\\", "report");
+        assert!(req.matches("Q3 Report.docx"));
+        assert!(!req.matches("notes.txt"));
+        assert!(
+            !req.clone()
+                .with_case_sensitive(true)
+                .matches("Q3 Report.docx")
+        );
+
+        let glob = FileSearchRequest::new("/", "*.log");
+        assert!(glob.matches("app.log"));
+        assert!(glob.matches("APP.LOG"));
+        assert!(!glob.matches("app.log.1"));
+        assert!(FileSearchRequest::new("/", "a?c*").matches("abcdef"));
+        assert!(FileSearchRequest::new("/", "*a*b*").matches("xxaxxbxx"));
+        assert!(!FileSearchRequest::new("/", "*a*b").matches("xxaxxbxx"));
+    }
+
+    #[test]
+    fn request_roundtrip() {
+        let req = FileSearchRequest::new("/srv", "*.conf")
+            .with_max_results(10)
+            .with_max_depth(3)
+            .with_include_dirs(true);
+        let decoded = FileSearchRequest::from_bytes(&req.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded, req);
+    }
+
+    #[test]
+    fn results_use_streaming_then_final_flags() {
+        let batch = FileSearchBatch {
+            batch_number: 0,
+            matches: Vec::new(),
+        };
+        let pkt = batch.into_packet(4).unwrap();
+        assert!(pkt.flags().contains(ProtocolFlags::STREAMING));
+        assert_eq!(pkt.command().unwrap(), Command::FileSearch);
+
+        let summary = FileSearchSummary {
+            matches: 3,
+            directories_scanned: 2,
+            ..Default::default()
+        };
+        let pkt = summary.clone().into_packet(4).unwrap();
+        assert!(pkt.flags().contains(ProtocolFlags::FINAL_FRAGMENT));
+        assert_eq!(
+            FileSearchSummary::from_bytes(pkt.payload()).unwrap(),
+            summary
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Self-test protocol — whether a slave machine can actually capture,
+//! inject input, open its screen socket and write its files.
+//!
+//! # Wire Protocol
+//!
+//! ```text
+//! Master ──[SelfTest]─────────────────────────► Slave
+//!   Payload: empty
+//!
+//! Slave  ──[SelfTest]─────────────────────────► Master
+//!   Payload: SelfTestReport (bincode)
+//! ```
+//!
+//! The same report is what `tix-rdp-slave --doctor` prints locally. The
+//! checks themselves live in [`rdp::doctor`](crate::rdp::doctor); each
+//! produces one [`CheckResult`], so a new probe is a new function and
+//! nothing here changes.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::TixError;
+use crate::message::Command;
+use crate::packet::Packet;
+use crate::protocol::schema::wire_schema;
+
+// ── Check Results ─────────────────────────────────────────────────
+
+/// Verdict of one check, from best to worst.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CheckStatus {
+    Pass,
+    /// Works, but something is likely to get in the way later.
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pass => write!(f, "pass"),
+            Self::Warn => write!(f, "warn"),
+            Self::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+/// Outcome of one check.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CheckResult {
+    /// Short name of what was checked, e.g. `capture monitor 0`.
+    pub name: String,
+    pub status: CheckStatus,
+    /// What was found, e.g. `1920x1080, first frame in 16 ms`.
+    pub detail: String,
+    /// What to do about a warning or failure.
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    /// A passed check.
+    pub fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    /// A warning, with what to do about it.
+    pub fn warn(
+        name: impl Into<String>,
+        detail: impl Into<String>,
+        hint: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    /// A failed check, with what to do about it.
+    pub fn fail(
+        name: impl Into<String>,
+        detail: impl Into<String>,
+        hint: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+// ── Report ────────────────────────────────────────────────────────
+
+/// Response payload for `Command::SelfTest`: every check, in the order
+/// it ran.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// Host name of the machine the checks ran on.
+    pub hostname: String,
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    /// The worst status of any check; `Pass` for an empty report.
+    pub fn status(&self) -> CheckStatus {
+        self.checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(CheckStatus::Pass)
+    }
+
+    /// How many checks ended with `status`.
+    pub fn count(&self, status: CheckStatus) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == status)
+            .count()
+    }
+
+    /// One line for logs: `5 checks: 3 pass, 1 warn, 1 fail`.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} checks: {} pass, {} warn, {} fail",
+            self.checks.len(),
+            self.count(CheckStatus::Pass),
+            self.count(CheckStatus::Warn),
+            self.count(CheckStatus::Fail)
+        )
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::SelfTest, payload)
+    }
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    enum CheckStatus { Pass, Warn, Fail }
+}
+wire_schema! {
+    struct CheckResult { name: String, status: CheckStatus, detail: String, hint: Option<String> }
+}
+wire_schema! {
+    struct SelfTestReport { hostname: String, checks: Vec<CheckResult> }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> SelfTestReport {
+        SelfTestReport {
+            hostname: "DESK-07".into(),
+            checks: vec![
+                CheckResult::pass("capture monitor 0", "1920x1080, first frame in 16 ms"),
+                CheckResult::warn(
+                    "session",
+                    "RDP session 2",
+                    "capture stops when the RDP client is minimized",
+                ),
+                CheckResult::fail(
+                    "UDP port 7332",
+                    "address in use",
+                    "stop the other listener or pick another network.listen_port",
+                ),
+                CheckResult::pass("log directory", "C:\\ProgramData\\tix is writable"),
+            ],
+        }
+    }
+
+    #[test]
+    fn report_roundtrips() {
+        let report = report();
+        let packet = report.clone().into_packet(4).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::SelfTest);
+        assert_eq!(
+            SelfTestReport::from_bytes(packet.payload()).unwrap(),
+            report
+        );
+    }
+
+    #[test]
+    fn report_status_is_the_worst_check() {
+        let mut report = report();
+        assert_eq!(report.status(), CheckStatus::Fail);
+        assert_eq!(report.summary(), "4 checks: 2 pass, 1 warn, 1 fail");
+
+        report
+            .checks
+            .retain(|check| check.status != CheckStatus::Fail);
+        assert_eq!(report.status(), CheckStatus::Warn);
+        assert_eq!(SelfTestReport::default().status(), CheckStatus::Pass);
+    }
+}
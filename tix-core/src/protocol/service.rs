@@ -0,0 +1,565 @@
+//! Windows service management protocol — listing services and starting,
+//! stopping or restarting one.
+//!
+//! # Wire Protocol
+//!
+//! ```text
+//! Master ──[ServiceList]──────────────────────► Slave
+//!   Payload: ServiceListRequest (bincode)
+//!
+//! Slave  ──[ServiceList]──────────────────────► Master
+//!   Payload: ServiceListResponse (bincode)
+//!
+//! Master ──[ServiceControl]───────────────────► Slave
+//!   Payload: ServiceControlRequest (bincode)
+//!
+//! Slave  ──[ServiceControl]───────────────────► Master
+//!   Payload: ServiceControlResponse (bincode), once the service has
+//!   reached the state the action asked for or the wait timed out
+//! ```
+//!
+//! Control requests run as slave tasks, so `cancel <id>` and the slave's
+//! task timeout apply to the wait as well.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::error::TixError;
+use crate::message::Command;
+use crate::packet::Packet;
+use crate::protocol::schema::wire_schema;
+use crate::protocol::search::name_matches;
+
+/// How long the slave waits for a service to settle by default.
+pub const DEFAULT_CONTROL_TIMEOUT: Duration = Duration::from_secs(30);
+
+// ── Service State ─────────────────────────────────────────────────
+
+/// Current state of a service, as the Service Control Manager reports
+/// it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ServiceState {
+    Stopped,
+    StartPending,
+    StopPending,
+    Running,
+    ContinuePending,
+    PausePending,
+    Paused,
+    /// A state code this version does not know.
+    Unknown(u32),
+}
+
+impl ServiceState {
+    /// Map a raw `SERVICE_*` state code (winsvc.h).
+    pub fn from_raw(code: u32) -> Self {
+        match code {
+            1 => Self::Stopped,
+            2 => Self::StartPending,
+            3 => Self::StopPending,
+            4 => Self::Running,
+            5 => Self::ContinuePending,
+            6 => Self::PausePending,
+            7 => Self::Paused,
+            other => Self::Unknown(other),
+        }
+    }
+
+    /// Whether the service is on its way to another state.
+    pub fn is_pending(&self) -> bool {
+        matches!(
+            self,
+            Self::StartPending | Self::StopPending | Self::ContinuePending | Self::PausePending
+        )
+    }
+}
+
+impl fmt::Display for ServiceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stopped => write!(f, "stopped"),
+            Self::StartPending => write!(f, "starting"),
+            Self::StopPending => write!(f, "stopping"),
+            Self::Running => write!(f, "running"),
+            Self::ContinuePending => write!(f, "resuming"),
+            Self::PausePending => write!(f, "pausing"),
+            Self::Paused => write!(f, "paused"),
+            Self::Unknown(code) => write!(f, "state {}", code),
+        }
+    }
+}
+
+/// When a service is started.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ServiceStartType {
+    /// Loaded by the boot loader (drivers).
+    Boot,
+    /// Loaded during kernel initialization (drivers).
+    System,
+    /// Started by the SCM at boot.
+    Automatic,
+    /// Started on demand.
+    Manual,
+    Disabled,
+    /// A start type code this version does not know.
+    Unknown(u32),
+}
+
+impl ServiceStartType {
+    /// Map a raw `SERVICE_*_START` code (winnt.h).
+    pub fn from_raw(code: u32) -> Self {
+        match code {
+            0 => Self::Boot,
+            1 => Self::System,
+            2 => Self::Automatic,
+            3 => Self::Manual,
+            4 => Self::Disabled,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl fmt::Display for ServiceStartType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Boot => write!(f, "boot"),
+            Self::System => write!(f, "system"),
+            Self::Automatic => write!(f, "auto"),
+            Self::Manual => write!(f, "manual"),
+            Self::Disabled => write!(f, "disabled"),
+            Self::Unknown(code) => write!(f, "type {}", code),
+        }
+    }
+}
+
+/// One service installed on the slave.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ServiceInfo {
+    /// Key name, the one `sc` and [`ServiceControlRequest`] take.
+    pub name: String,
+    /// Name shown in the Services console.
+    pub display_name: String,
+    pub state: ServiceState,
+    /// `None` when the slave could not read the service's configuration.
+    pub start_type: Option<ServiceStartType>,
+}
+
+/// Why a service request failed on the slave.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ServiceErrorKind {
+    /// No service has that name.
+    NotFound,
+    /// The slave process lacks the rights for the request.
+    AccessDenied,
+    /// The service did not reach the requested state in time.
+    Timeout,
+    /// The slave platform has no Service Control Manager.
+    Unsupported,
+    /// Any other OS error.
+    Other,
+}
+
+impl fmt::Display for ServiceErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "not found"),
+            Self::AccessDenied => write!(f, "access denied"),
+            Self::Timeout => write!(f, "timed out"),
+            Self::Unsupported => write!(f, "unsupported"),
+            Self::Other => write!(f, "failed"),
+        }
+    }
+}
+
+/// Whether service `name` is the one `pattern` names: equal ignoring
+/// case, or matching it as a glob when it holds `*` or `?`.
+pub fn service_name_matches(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.to_lowercase(), name.to_lowercase());
+    if pattern.contains(['*', '?']) {
+        name_matches(&pattern, &name)
+    } else {
+        pattern == name
+    }
+}
+
+// ── Service List ──────────────────────────────────────────────────
+
+/// Request payload for `Command::ServiceList`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ServiceListRequest {
+    /// Only services whose name or display name contains this text, or
+    /// matches it as a glob; case-insensitive. `None` lists them all.
+    pub filter: Option<String>,
+}
+
+impl ServiceListRequest {
+    pub fn new(filter: Option<String>) -> Self {
+        Self {
+            filter: filter.filter(|f| !f.is_empty()),
+        }
+    }
+
+    /// Whether `service` passes the filter.
+    pub fn matches(&self, service: &ServiceInfo) -> bool {
+        let Some(filter) = &self.filter else {
+            return true;
+        };
+        let filter = filter.to_lowercase();
+        [&service.name, &service.display_name]
+            .iter()
+            .any(|name| name_matches(&filter, &name.to_lowercase()))
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+}
+
+/// Response payload for `Command::ServiceList`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ServiceListResponse {
+    /// Services passing the filter, sorted by name.
+    Services(Vec<ServiceInfo>),
+    /// The services could not be enumerated.
+    Error {
+        kind: ServiceErrorKind,
+        message: String,
+    },
+}
+
+impl ServiceListResponse {
+    /// Build an error response.
+    pub fn error(kind: ServiceErrorKind, message: impl Into<String>) -> Self {
+        Self::Error {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::ServiceList, payload)
+    }
+}
+
+// ── Service Control ───────────────────────────────────────────────
+
+/// What to do with a service.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ServiceAction {
+    Start,
+    Stop,
+    /// Stop, wait until stopped, then start.
+    Restart,
+}
+
+impl ServiceAction {
+    /// The state the service is in once the action is done.
+    pub fn target_state(&self) -> ServiceState {
+        match self {
+            Self::Start | Self::Restart => ServiceState::Running,
+            Self::Stop => ServiceState::Stopped,
+        }
+    }
+
+    /// Whether the action takes a running service down, if only for a
+    /// moment.
+    pub fn interrupts(&self) -> bool {
+        matches!(self, Self::Stop | Self::Restart)
+    }
+}
+
+impl FromStr for ServiceAction {
+    type Err = TixError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "start" => Ok(Self::Start),
+            "stop" => Ok(Self::Stop),
+            "restart" => Ok(Self::Restart),
+            _ => Err(TixError::InvalidCommand(format!(
+                "unknown service action '{}' (start, stop or restart)",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ServiceAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Start => write!(f, "start"),
+            Self::Stop => write!(f, "stop"),
+            Self::Restart => write!(f, "restart"),
+        }
+    }
+}
+
+/// Request payload for `Command::ServiceControl`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceControlRequest {
+    /// Key name of the service.
+    pub name: String,
+    pub action: ServiceAction,
+    /// How long to wait for the service to settle, in milliseconds.
+    pub timeout_ms: u64,
+}
+
+impl ServiceControlRequest {
+    /// Apply `action` to service `name`, waiting up to
+    /// [`DEFAULT_CONTROL_TIMEOUT`].
+    pub fn new(name: impl Into<String>, action: ServiceAction) -> Self {
+        Self {
+            name: name.into(),
+            action,
+            timeout_ms: DEFAULT_CONTROL_TIMEOUT.as_millis() as u64,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_ms = timeout.as_millis() as u64;
+        self
+    }
+
+    /// How long to wait for the service to settle.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+}
+
+/// Response payload for `Command::ServiceControl`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ServiceControlResponse {
+    /// The action is done; `service` is as it is now.
+    Done {
+        action: ServiceAction,
+        service: ServiceInfo,
+    },
+    /// The action failed. `state` is the service's state afterwards,
+    /// when it could still be read.
+    Error {
+        kind: ServiceErrorKind,
+        message: String,
+        state: Option<ServiceState>,
+    },
+}
+
+impl ServiceControlResponse {
+    /// Build an error response.
+    pub fn error(
+        kind: ServiceErrorKind,
+        message: impl Into<String>,
+        state: Option<ServiceState>,
+    ) -> Self {
+        Self::Error {
+            kind,
+            message: message.into(),
+            state,
+        }
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::ServiceControl, payload)
+    }
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    enum ServiceState {
+        Stopped,
+        StartPending,
+        StopPending,
+        Running,
+        ContinuePending,
+        PausePending,
+        Paused,
+        Unknown { 0: u32 },
+    }
+}
+wire_schema! {
+    enum ServiceStartType { Boot, System, Automatic, Manual, Disabled, Unknown { 0: u32 } }
+}
+wire_schema! {
+    struct ServiceInfo {
+        name: String,
+        display_name: String,
+        state: ServiceState,
+        start_type: Option<ServiceStartType>,
+    }
+}
+wire_schema! {
+    enum ServiceErrorKind { NotFound, AccessDenied, Timeout, Unsupported, Other }
+}
+wire_schema! {
+    struct ServiceListRequest { filter: Option<String> }
+}
+wire_schema! {
+    enum ServiceListResponse {
+        Services { 0: Vec<ServiceInfo> },
+        Error { kind: ServiceErrorKind, message: String },
+    }
+}
+wire_schema! {
+    enum ServiceAction { Start, Stop, Restart }
+}
+wire_schema! {
+    struct ServiceControlRequest { name: String, action: ServiceAction, timeout_ms: u64 }
+}
+wire_schema! {
+    enum ServiceControlResponse {
+        Done { action: ServiceAction, service: ServiceInfo },
+        Error { kind: ServiceErrorKind, message: String, state: Option<ServiceState> },
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spooler() -> ServiceInfo {
+        ServiceInfo {
+            name: "Spooler".into(),
+            display_name: "Print Spooler".into(),
+            state: ServiceState::Running,
+            start_type: Some(ServiceStartType::Automatic),
+        }
+    }
+
+    #[test]
+    fn service_list_roundtrip() {
+        let req = ServiceListRequest::new(Some("spool".into()));
+        assert_eq!(
+            ServiceListRequest::from_bytes(&req.to_bytes().unwrap()).unwrap(),
+            req
+        );
+        assert_eq!(ServiceListRequest::new(Some(String::new())).filter, None);
+
+        let responses = [
+            ServiceListResponse::Services(vec![
+                spooler(),
+                ServiceInfo {
+                    name: "wuauserv".into(),
+                    display_name: "Windows Update".into(),
+                    state: ServiceState::Unknown(42),
+                    start_type: None,
+                },
+            ]),
+            ServiceListResponse::error(ServiceErrorKind::AccessDenied, "OpenSCManager: denied"),
+        ];
+        for resp in responses {
+            let packet = resp.clone().into_packet(5).unwrap();
+            assert_eq!(packet.command().unwrap(), Command::ServiceList);
+            assert_eq!(
+                ServiceListResponse::from_bytes(packet.payload()).unwrap(),
+                resp
+            );
+        }
+    }
+
+    #[test]
+    fn service_control_roundtrip() {
+        let req = ServiceControlRequest::new("Spooler", ServiceAction::Restart)
+            .with_timeout(Duration::from_secs(5));
+        let decoded = ServiceControlRequest::from_bytes(&req.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded, req);
+        assert_eq!(decoded.timeout(), Duration::from_secs(5));
+
+        let responses = [
+            ServiceControlResponse::Done {
+                action: ServiceAction::Stop,
+                service: spooler(),
+            },
+            ServiceControlResponse::error(
+                ServiceErrorKind::Timeout,
+                "Spooler still stopping after 30 s",
+                Some(ServiceState::StopPending),
+            ),
+            ServiceControlResponse::error(ServiceErrorKind::NotFound, "no such service", None),
+        ];
+        for resp in responses {
+            let packet = resp.clone().into_packet(8).unwrap();
+            assert_eq!(packet.command().unwrap(), Command::ServiceControl);
+            assert_eq!(
+                ServiceControlResponse::from_bytes(packet.payload()).unwrap(),
+                resp
+            );
+        }
+    }
+
+    #[test]
+    fn actions_parse_and_know_their_target() {
+        assert_eq!(
+            "Stop".parse::<ServiceAction>().unwrap(),
+            ServiceAction::Stop
+        );
+        assert!("pause".parse::<ServiceAction>().is_err());
+        assert_eq!(ServiceAction::Restart.target_state(), ServiceState::Running);
+        assert!(ServiceAction::Restart.interrupts());
+        assert!(!ServiceAction::Start.interrupts());
+    }
+
+    #[test]
+    fn raw_codes_map_to_states() {
+        assert_eq!(ServiceState::from_raw(4), ServiceState::Running);
+        assert!(ServiceState::from_raw(3).is_pending());
+        assert_eq!(ServiceState::from_raw(9).to_string(), "state 9");
+        assert_eq!(ServiceStartType::from_raw(2), ServiceStartType::Automatic);
+        assert_eq!(ServiceStartType::from_raw(4).to_string(), "disabled");
+    }
+
+    #[test]
+    fn filters_match_name_or_display_name() {
+        assert!(ServiceListRequest::default().matches(&spooler()));
+        assert!(ServiceListRequest::new(Some("PRINT".into())).matches(&spooler()));
+        assert!(ServiceListRequest::new(Some("sp*er".into())).matches(&spooler()));
+        assert!(!ServiceListRequest::new(Some("update".into())).matches(&spooler()));
+
+        assert!(service_name_matches("spooler", "Spooler"));
+        assert!(!service_name_matches("spool", "Spooler"));
+        assert!(service_name_matches("Win*", "WinDefend"));
+    }
+}
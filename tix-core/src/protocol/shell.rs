@@ -20,15 +20,67 @@
 //! ```
 //!
 //! Output is streamed in chunks so the master can display partial results
-//! immediately without waiting for the command to finish.
+//! immediately without waiting for the command to finish. Chunks carry
+//! the bytes the command wrote, untouched; the exit status names the code
+//! page to read them in when they are not UTF-8.
+//!
+//! The request names the interpreter to run the command in
+//! ([`ShellKind`]); the exit status names the one the slave resolved it
+//! to.
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 use crate::error::TixError;
 use crate::flags::ProtocolFlags;
 use crate::message::Command;
 use crate::packet::Packet;
+use crate::protocol::schema::wire_schema;
+
+// ── Shell Kind ────────────────────────────────────────────────────
+
+/// The interpreter a [`ShellExecuteRequest`] runs its command in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellKind {
+    /// The system shell: `cmd /c` on Windows, `sh -c` elsewhere.
+    #[default]
+    Cmd,
+    /// Windows PowerShell (`powershell.exe`).
+    PowerShell,
+    /// PowerShell 7+ (`pwsh`).
+    #[serde(rename = "pwsh")]
+    PwshCore,
+    /// A shell the slave's config allows by name.
+    Custom(String),
+}
+
+impl FromStr for ShellKind {
+    type Err = TixError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "" => Err(TixError::InvalidCommand("empty shell name".to_string())),
+            "cmd" => Ok(Self::Cmd),
+            "powershell" => Ok(Self::PowerShell),
+            "pwsh" => Ok(Self::PwshCore),
+            _ => Ok(Self::Custom(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ShellKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cmd => write!(f, "cmd"),
+            Self::PowerShell => write!(f, "powershell"),
+            Self::PwshCore => write!(f, "pwsh"),
+            Self::Custom(name) => write!(f, "{name}"),
+        }
+    }
+}
 
 // ── Shell Execute ─────────────────────────────────────────────────
 
@@ -49,6 +101,19 @@ pub struct ShellExecuteRequest {
 
     /// Optional working directory.
     pub working_dir: Option<String>,
+
+    /// The interpreter to run `command` in.
+    pub shell: ShellKind,
+}
+
+/// [`ShellExecuteRequest`] as sent by builds that predate `shell`.
+#[derive(Deserialize)]
+struct LegacyShellExecuteRequest {
+    command: String,
+    pty: bool,
+    timeout_ms: u64,
+    env: HashMap<String, String>,
+    working_dir: Option<String>,
 }
 
 impl ShellExecuteRequest {
@@ -60,6 +125,7 @@ impl ShellExecuteRequest {
             timeout_ms: 30_000, // 30s default
             env: HashMap::new(),
             working_dir: None,
+            shell: ShellKind::Cmd,
         }
     }
 
@@ -87,14 +153,34 @@ impl ShellExecuteRequest {
         self
     }
 
+    /// Run the command in `shell` instead of the system shell.
+    pub fn with_shell(mut self, shell: ShellKind) -> Self {
+        self.shell = shell;
+        self
+    }
+
     /// Serialize to bytes for packet payload.
     pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
         bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
     }
 
-    /// Deserialize from packet payload bytes.
+    /// Deserialize from packet payload bytes, including a request from a
+    /// build that predates `shell` (run in the system shell).
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
-        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+        let current = match bincode::deserialize(bytes) {
+            Ok(request) => return Ok(request),
+            Err(e) => e,
+        };
+        let legacy: LegacyShellExecuteRequest =
+            bincode::deserialize(bytes).map_err(|_| TixError::Encoding(current.to_string()))?;
+        Ok(Self {
+            command: legacy.command,
+            pty: legacy.pty,
+            timeout_ms: legacy.timeout_ms,
+            env: legacy.env,
+            working_dir: legacy.working_dir,
+            shell: ShellKind::Cmd,
+        })
     }
 
     /// Build a command `Packet` carrying this request.
@@ -115,7 +201,8 @@ pub struct ShellOutputChunk {
     /// Sequential chunk number (0-based).
     pub chunk_number: u64,
 
-    /// The output data (UTF-8 text or raw bytes).
+    /// The output exactly as the command wrote it: UTF-8, text in the
+    /// slave's code page, or binary.
     pub data: Vec<u8>,
 
     /// `true` if this is from stdout, `false` if from stderr.
@@ -178,6 +265,24 @@ pub struct ShellExitStatus {
 
     /// Optional error message if the command failed to start.
     pub error: Option<String>,
+
+    /// Windows code page of output that is not UTF-8 (the slave's OEM
+    /// code page, e.g. 850); 0 when unknown.
+    pub codepage: u32,
+
+    /// The interpreter the command ran in, as the slave resolved it
+    /// (e.g. `"powershell -NoProfile -NonInteractive -Command"`); empty
+    /// when unknown.
+    pub shell: String,
+}
+
+/// [`ShellExitStatus`] as sent by builds that predate `shell`.
+#[derive(Deserialize)]
+struct LegacyShellExitStatus {
+    exit_code: i32,
+    total_chunks: u64,
+    error: Option<String>,
+    codepage: u32,
 }
 
 impl ShellExitStatus {
@@ -187,15 +292,31 @@ impl ShellExitStatus {
             exit_code,
             total_chunks,
             error: None,
+            codepage: 0,
+            shell: String::new(),
         }
     }
 
+    /// Name the code page the output was written in.
+    pub fn with_codepage(mut self, codepage: u32) -> Self {
+        self.codepage = codepage;
+        self
+    }
+
+    /// Name the interpreter the command ran in.
+    pub fn with_shell(mut self, shell: impl Into<String>) -> Self {
+        self.shell = shell.into();
+        self
+    }
+
     /// Failed to start the process.
     pub fn failed(error: impl Into<String>) -> Self {
         Self {
             exit_code: -1,
             total_chunks: 0,
             error: Some(error.into()),
+            codepage: 0,
+            shell: String::new(),
         }
     }
 
@@ -204,9 +325,22 @@ impl ShellExitStatus {
         bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
     }
 
-    /// Deserialize from bytes.
+    /// Deserialize from bytes, including a status from a build that
+    /// predates `shell`.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
-        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+        let current = match bincode::deserialize(bytes) {
+            Ok(status) => return Ok(status),
+            Err(e) => e,
+        };
+        let legacy: LegacyShellExitStatus =
+            bincode::deserialize(bytes).map_err(|_| TixError::Encoding(current.to_string()))?;
+        Ok(Self {
+            exit_code: legacy.exit_code,
+            total_chunks: legacy.total_chunks,
+            error: legacy.error,
+            codepage: legacy.codepage,
+            shell: String::new(),
+        })
     }
 
     /// Build the final response `Packet`.
@@ -289,6 +423,37 @@ pub enum ShellResponseKind {
     LegacySingle,
 }
 
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    enum ShellKind { Cmd, PowerShell, PwshCore, Custom { 0: String } }
+}
+wire_schema! {
+    struct ShellExecuteRequest {
+        command: String,
+        pty: bool,
+        timeout_ms: u64,
+        env: HashMap<String, String>,
+        working_dir: Option<String>,
+        shell: ShellKind,
+    }
+}
+wire_schema! {
+    struct ShellOutputChunk { chunk_number: u64, data: Vec<u8>, is_stdout: bool }
+}
+wire_schema! {
+    struct ShellExitStatus {
+        exit_code: i32,
+        total_chunks: u64,
+        error: Option<String>,
+        codepage: u32,
+        shell: String,
+    }
+}
+wire_schema! {
+    struct ShellResizeRequest { target_request_id: u64, cols: u16, rows: u16 }
+}
+
 // ── Tests ─────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -325,10 +490,52 @@ mod tests {
 
     #[test]
     fn shell_exit_status_roundtrip() {
-        let exit = ShellExitStatus::success(0, 42);
+        let exit = ShellExitStatus::success(0, 42)
+            .with_codepage(850)
+            .with_shell("sh -c");
         let bytes = exit.to_bytes().unwrap();
         let decoded = ShellExitStatus::from_bytes(&bytes).unwrap();
         assert_eq!(exit, decoded);
+        assert_eq!(decoded.codepage, 850);
+    }
+
+    #[test]
+    fn shell_kind_names() {
+        for (name, kind) in [
+            ("cmd", ShellKind::Cmd),
+            ("PowerShell", ShellKind::PowerShell),
+            ("pwsh", ShellKind::PwshCore),
+            ("bash", ShellKind::Custom("bash".into())),
+        ] {
+            assert_eq!(name.parse::<ShellKind>().unwrap(), kind);
+            assert_eq!(kind.to_string(), name.to_ascii_lowercase());
+        }
+        assert!("".parse::<ShellKind>().is_err());
+    }
+
+    #[test]
+    fn shell_fields_from_older_builds() {
+        let old_request = bincode::serialize(&(
+            "dir",
+            false,
+            5_000u64,
+            HashMap::<String, String>::new(),
+            Some("C:\\"),
+        ))
+        .unwrap();
+        let request = ShellExecuteRequest::from_bytes(&old_request).unwrap();
+        assert_eq!(request.command, "dir");
+        assert_eq!(request.working_dir.as_deref(), Some("C:\\"));
+        assert_eq!(request.shell, ShellKind::Cmd);
+
+        let old_status = bincode::serialize(&(3i32, 2u64, None::<String>, 850u32)).unwrap();
+        let status = ShellExitStatus::from_bytes(&old_status).unwrap();
+        assert_eq!((status.exit_code, status.codepage), (3, 850));
+        assert_eq!(status.shell, "");
+
+        let request = ShellExecuteRequest::new("Get-Date").with_shell(ShellKind::PowerShell);
+        let decoded = ShellExecuteRequest::from_bytes(&request.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.shell, ShellKind::PowerShell);
     }
 
     #[test]
@@ -0,0 +1,1154 @@
+//! System inspection protocol — registry queries, startup programs,
+//! session statistics and the slave's wire tap.
+//!
+//! # Wire Protocol
+//!
+//! ```text
+//! Master ──[RegistryQuery]────────────────────► Slave
+//!   Payload: RegistryQueryRequest (bincode)
+//!
+//! Slave  ──[RegistryQuery]────────────────────► Master
+//!   Payload: RegistryQueryResponse (bincode)
+//!
+//! Master ──[StartupList]──────────────────────► Slave
+//!   Payload: empty
+//!
+//! Slave  ──[StartupList]──────────────────────► Master
+//!   Payload: StartupListResponse (bincode)
+//!
+//! Master ──[SystemInfo]───────────────────────► Slave
+//!   Payload: empty
+//!
+//! Slave  ──[SystemInfo]───────────────────────► Master
+//!   Payload: SystemInfoResponse (bincode)
+//!
+//! Slave  ──[LimitExceeded]────────────────────► Master
+//!   Payload: LimitExceeded (bincode), sent instead of the normal
+//!   response when a request is refused by the bandwidth cap
+//!
+//! Master ──[TaskList]─────────────────────────► Slave
+//!   Payload: empty
+//!
+//! Slave  ──[TaskList]─────────────────────────► Master
+//!   Payload: TaskListResponse (bincode). Also sent unsolicited with
+//!   request ID 0, listing only the tasks that just crossed the
+//!   slow-task threshold
+//!
+//! Master ──[WireTap]──────────────────────────► Slave
+//!   Payload: WireTapSettings (bincode)
+//!
+//! Slave  ──[WireTap]──────────────────────────► Master
+//!   Payload: WireTapResponse (bincode)
+//!
+//! Slave  ──[any command, ERROR flag]──────────► Master
+//!   Payload: TaskFailure (bincode), sent instead of the normal
+//!   response when the task serving the request failed, timed out or
+//!   was cancelled
+//! ```
+//!
+//! The registry and startup commands are read-only: the protocol has no
+//! way to modify the registry or the startup folders.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+use crate::error::{TaskError, TixError};
+use crate::flags::ProtocolFlags;
+use crate::format::format_bytes;
+use crate::message::Command;
+use crate::packet::Packet;
+use crate::protocol::schema::wire_schema;
+
+// ── Registry Hive ─────────────────────────────────────────────────
+
+/// Root hive a registry path is resolved against.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum RegistryHive {
+    ClassesRoot,
+    CurrentUser,
+    LocalMachine,
+    Users,
+    CurrentConfig,
+}
+
+impl RegistryHive {
+    /// Parse a hive from its short (`HKLM`) or long
+    /// (`HKEY_LOCAL_MACHINE`) name, case-insensitively.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "HKCR" | "HKEY_CLASSES_ROOT" => Some(Self::ClassesRoot),
+            "HKCU" | "HKEY_CURRENT_USER" => Some(Self::CurrentUser),
+            "HKLM" | "HKEY_LOCAL_MACHINE" => Some(Self::LocalMachine),
+            "HKU" | "HKEY_USERS" => Some(Self::Users),
+            "HKCC" | "HKEY_CURRENT_CONFIG" => Some(Self::CurrentConfig),
+            _ => None,
+        }
+    }
+
+    /// Short display name (e.g. `HKLM`).
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Self::ClassesRoot => "HKCR",
+            Self::CurrentUser => "HKCU",
+            Self::LocalMachine => "HKLM",
+            Self::Users => "HKU",
+            Self::CurrentConfig => "HKCC",
+        }
+    }
+}
+
+impl fmt::Display for RegistryHive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.short_name())
+    }
+}
+
+// ── Registry Query ────────────────────────────────────────────────
+
+/// Request payload for `Command::RegistryQuery`.
+///
+/// With `value_name` set, the slave returns that single value. Without
+/// it, the slave enumerates the key's subkeys and values.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegistryQueryRequest {
+    /// Root hive.
+    pub hive: RegistryHive,
+
+    /// Key path below the hive, backslash-separated.
+    pub key_path: String,
+
+    /// Optional value to read (`""` is the key's default value).
+    pub value_name: Option<String>,
+}
+
+impl RegistryQueryRequest {
+    /// Create a request enumerating the given key.
+    pub fn new(hive: RegistryHive, key_path: impl Into<String>) -> Self {
+        Self {
+            hive,
+            key_path: key_path.into(),
+            value_name: None,
+        }
+    }
+
+    /// Parse a full path such as `HKLM\SOFTWARE\Microsoft`.
+    pub fn from_path(path: &str) -> Result<Self, TixError> {
+        let path = path.trim().trim_matches('\\');
+        let (hive, key) = match path.split_once('\\') {
+            Some((hive, key)) => (hive, key),
+            None => (path, ""),
+        };
+        let hive = RegistryHive::parse(hive)
+            .ok_or_else(|| TixError::InvalidCommand(format!("unknown registry hive '{hive}'")))?;
+        Ok(Self::new(hive, key.trim_matches('\\')))
+    }
+
+    /// Query a single value instead of enumerating the key.
+    pub fn with_value(mut self, name: impl Into<String>) -> Self {
+        self.value_name = Some(name.into());
+        self
+    }
+
+    /// Full display path (e.g. `HKLM\SOFTWARE\Microsoft`).
+    pub fn full_path(&self) -> String {
+        if self.key_path.is_empty() {
+            self.hive.to_string()
+        } else {
+            format!("{}\\{}", self.hive, self.key_path)
+        }
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet` carrying this request.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::RegistryQuery, payload)
+    }
+}
+
+// Raw `REG_*` type codes (winnt.h).
+const REG_SZ: u32 = 1;
+const REG_EXPAND_SZ: u32 = 2;
+const REG_BINARY: u32 = 3;
+const REG_DWORD: u32 = 4;
+const REG_MULTI_SZ: u32 = 7;
+const REG_QWORD: u32 = 11;
+
+/// A typed registry value.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum RegistryValue {
+    /// `REG_SZ`
+    String(String),
+    /// `REG_EXPAND_SZ` (unexpanded).
+    ExpandString(String),
+    /// `REG_MULTI_SZ`
+    MultiString(Vec<String>),
+    /// `REG_DWORD`
+    Dword(u32),
+    /// `REG_QWORD`
+    Qword(u64),
+    /// `REG_BINARY`
+    Binary(Vec<u8>),
+    /// Any other type, carried as raw bytes.
+    Other { kind: u32, data: Vec<u8> },
+}
+
+impl RegistryValue {
+    /// Decode raw value data as returned by `RegQueryValueExW`.
+    ///
+    /// `kind` is the `REG_*` type code. Strings are UTF-16LE and may or
+    /// may not carry their terminating NUL; malformed numeric data falls
+    /// back to [`RegistryValue::Other`].
+    pub fn from_raw(kind: u32, data: Vec<u8>) -> Self {
+        fn utf16(data: &[u8]) -> Vec<u16> {
+            data.chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect()
+        }
+        fn string(data: &[u8]) -> String {
+            let wide = utf16(data);
+            let end = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+            String::from_utf16_lossy(&wide[..end])
+        }
+
+        match kind {
+            REG_SZ => Self::String(string(&data)),
+            REG_EXPAND_SZ => Self::ExpandString(string(&data)),
+            REG_BINARY => Self::Binary(data),
+            REG_DWORD if data.len() == 4 => {
+                Self::Dword(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+            }
+            REG_MULTI_SZ => Self::MultiString(
+                utf16(&data)
+                    .split(|&c| c == 0)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from_utf16_lossy)
+                    .collect(),
+            ),
+            REG_QWORD if data.len() == 8 => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&data);
+                Self::Qword(u64::from_le_bytes(bytes))
+            }
+            _ => Self::Other { kind, data },
+        }
+    }
+
+    /// Registry type name (e.g. `REG_SZ`).
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "REG_SZ",
+            Self::ExpandString(_) => "REG_EXPAND_SZ",
+            Self::MultiString(_) => "REG_MULTI_SZ",
+            Self::Dword(_) => "REG_DWORD",
+            Self::Qword(_) => "REG_QWORD",
+            Self::Binary(_) => "REG_BINARY",
+            Self::Other { .. } => "REG_UNKNOWN",
+        }
+    }
+}
+
+impl fmt::Display for RegistryValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(s) | Self::ExpandString(s) => f.write_str(s),
+            Self::MultiString(items) => f.write_str(&items.join(" | ")),
+            Self::Dword(v) => write!(f, "0x{v:08x} ({v})"),
+            Self::Qword(v) => write!(f, "0x{v:016x} ({v})"),
+            Self::Binary(data) | Self::Other { data, .. } => {
+                for (i, b) in data.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{b:02x}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A named value inside a registry key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegistryEntry {
+    /// Value name (`""` for the default value).
+    pub name: String,
+    /// The typed value.
+    pub value: RegistryValue,
+}
+
+/// Why a registry query failed on the slave.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RegistryErrorKind {
+    /// The key or value does not exist.
+    NotFound,
+    /// The slave process lacks the rights to open the key.
+    AccessDenied,
+    /// The slave platform has no registry.
+    Unsupported,
+    /// Any other OS error.
+    Other,
+}
+
+/// Response payload for `Command::RegistryQuery`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum RegistryQueryResponse {
+    /// A single value was requested and found.
+    Value(RegistryEntry),
+    /// The key was enumerated.
+    Key {
+        subkeys: Vec<String>,
+        values: Vec<RegistryEntry>,
+    },
+    /// The query failed.
+    Error {
+        kind: RegistryErrorKind,
+        message: String,
+    },
+}
+
+impl RegistryQueryResponse {
+    /// Build an error response.
+    pub fn error(kind: RegistryErrorKind, message: impl Into<String>) -> Self {
+        Self::Error {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::RegistryQuery, payload)
+    }
+}
+
+// ── Startup List ──────────────────────────────────────────────────
+
+/// Where a startup entry was found.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum StartupSource {
+    /// A value under a `Run` / `RunOnce` key, e.g. `HKCU\...\Run`.
+    RegistryKey(String),
+    /// A file inside a Startup folder.
+    StartupFolder(String),
+}
+
+impl fmt::Display for StartupSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RegistryKey(path) | Self::StartupFolder(path) => f.write_str(path),
+        }
+    }
+}
+
+/// A single program launched at logon.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StartupEntry {
+    /// Value name or file name.
+    pub name: String,
+    /// Command line or target path.
+    pub command: String,
+    /// Where the entry lives.
+    pub source: StartupSource,
+}
+
+/// Response payload for `Command::StartupList`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct StartupListResponse {
+    /// Every entry found, in source order.
+    pub entries: Vec<StartupEntry>,
+    /// Locations that could not be read (access denied, etc.).
+    pub errors: Vec<String>,
+}
+
+impl StartupListResponse {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::StartupList, payload)
+    }
+}
+
+// ── Session Statistics ────────────────────────────────────────────
+
+/// Traffic attributed to one command on the control connection.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CommandTraffic {
+    /// Command name (e.g. `Download`).
+    pub command: String,
+    /// Packets sent and received.
+    pub packets: u64,
+    /// Bytes written to the peer, headers included.
+    pub bytes_sent: u64,
+    /// Bytes read from the peer, headers included.
+    pub bytes_received: u64,
+}
+
+impl CommandTraffic {
+    /// Bytes in both directions.
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes_sent + self.bytes_received
+    }
+}
+
+/// Where the bytes of a session went, and how close it is to its cap.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SessionStats {
+    /// Bytes sent on the TCP control connection.
+    pub control_sent: u64,
+    /// Bytes received on the TCP control connection.
+    pub control_received: u64,
+    /// Bytes sent on the UDP screen stream.
+    pub screen_sent: u64,
+    /// Bytes received on the UDP screen stream.
+    pub screen_received: u64,
+    /// Control traffic per command, largest first.
+    pub commands: Vec<CommandTraffic>,
+    /// Bytes counted in the current one-hour window.
+    pub window_bytes: u64,
+    /// Configured `limits.max_bytes_per_hour`, if any.
+    pub limit_per_hour: Option<u64>,
+    /// Whether the cap is currently exceeded.
+    pub throttled: bool,
+}
+
+impl SessionStats {
+    /// All bytes in both directions on both channels.
+    pub fn total_bytes(&self) -> u64 {
+        self.control_sent + self.control_received + self.screen_sent + self.screen_received
+    }
+
+    /// One-line summary for status bars.
+    pub fn summary(&self) -> String {
+        let mut line = format!(
+            "ctl {} up / {} down, screen {} up / {} down",
+            format_bytes(self.control_sent),
+            format_bytes(self.control_received),
+            format_bytes(self.screen_sent),
+            format_bytes(self.screen_received),
+        );
+        if let Some(limit) = self.limit_per_hour {
+            line.push_str(&format!(
+                ", {} of {}/h",
+                format_bytes(self.window_bytes),
+                format_bytes(limit)
+            ));
+            if self.throttled {
+                line.push_str(" (throttled)");
+            }
+        }
+        line
+    }
+}
+
+/// How a file operation holds a path.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LockAccess {
+    /// Readers; any number at once.
+    Shared,
+    /// A writer, deleter or move; alone.
+    Exclusive,
+}
+
+impl fmt::Display for LockAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockAccess::Shared => write!(f, "shared"),
+            LockAccess::Exclusive => write!(f, "exclusive"),
+        }
+    }
+}
+
+/// A path lock currently held by a slave task.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PathLockInfo {
+    /// Normalized absolute path.
+    pub path: String,
+    pub access: LockAccess,
+    /// Request that holds the lock.
+    pub request_id: u64,
+    /// How long it has been held, in milliseconds.
+    pub held_ms: u64,
+}
+
+/// Response payload for `Command::SystemInfo`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SystemInfoResponse {
+    /// Machine name.
+    pub hostname: String,
+    /// Operating system family (`windows`, `linux`, ...).
+    pub os: String,
+    /// Traffic accounting for the current connection.
+    pub session: SessionStats,
+    /// Paths locked by running file operations.
+    pub path_locks: Vec<PathLockInfo>,
+}
+
+impl SystemInfoResponse {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::SystemInfo, payload)
+    }
+}
+
+/// Refusal sent in place of a normal response while the slave is over
+/// its bandwidth cap.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LimitExceeded {
+    /// The command that was refused.
+    pub command: String,
+    /// Bytes counted in the current window.
+    pub used: u64,
+    /// The configured cap for the window.
+    pub limit: u64,
+    /// Seconds until enough traffic ages out of the window.
+    pub retry_after_secs: u64,
+}
+
+impl LimitExceeded {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet` answering `request_id`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::LimitExceeded, payload)
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} refused: bandwidth limit reached ({} of {} per hour), retry in {}s",
+            self.command,
+            format_bytes(self.used),
+            format_bytes(self.limit),
+            self.retry_after_secs
+        )
+    }
+}
+
+// ── Task List ─────────────────────────────────────────────────────
+
+/// A task running in the slave's task pool.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TaskInfo {
+    /// Request the task is serving.
+    pub request_id: u64,
+    /// Name given when the task was spawned, e.g. `Copy a -> b`.
+    pub name: Option<String>,
+    /// Time since the task was spawned, in milliseconds.
+    pub elapsed_ms: u64,
+    /// Automatic cancellation deadline, if the task has one.
+    pub timeout_ms: Option<u64>,
+}
+
+impl TaskInfo {
+    /// Time since the task was spawned.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_millis(self.elapsed_ms)
+    }
+}
+
+/// Response payload for `Command::TaskList`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaskListResponse {
+    /// Running tasks, oldest request first.
+    pub tasks: Vec<TaskInfo>,
+    /// The slave's slow-task threshold, in milliseconds.
+    pub slow_after_ms: u64,
+}
+
+impl TaskListResponse {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`. Request ID 0 marks a slow-task
+    /// warning nobody asked for.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::TaskList, payload)
+    }
+
+    /// Whether `task` has run past the slow-task threshold.
+    pub fn is_slow(&self, task: &TaskInfo) -> bool {
+        self.slow_after_ms > 0 && task.elapsed_ms >= self.slow_after_ms
+    }
+}
+
+// ── Wire Tap ──────────────────────────────────────────────────────
+
+/// How the slave should tap its end of the control connection; see
+/// [`WireTap`](crate::network::WireTap). Request payload for
+/// `Command::WireTap`, and echoed back once applied.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WireTapSettings {
+    /// Log every packet at debug level.
+    pub enabled: bool,
+    /// Payload bytes dumped per logged packet.
+    pub dump_bytes: u32,
+    /// Capture file on the slave to mirror packets to. `None` closes
+    /// the capture the slave has open, if any.
+    pub capture: Option<String>,
+}
+
+impl WireTapSettings {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet` carrying these settings.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::WireTap, payload)
+    }
+}
+
+/// Response payload for `Command::WireTap`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WireTapResponse {
+    /// The tap now runs with these settings.
+    Applied(WireTapSettings),
+    /// The capture file could not be opened; the tap is unchanged.
+    Failed(String),
+}
+
+impl WireTapResponse {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::WireTap, payload)
+    }
+}
+
+// ── Task Failure ──────────────────────────────────────────────────
+
+/// Why a slave task ended without answering its request.
+///
+/// Carried with [`ProtocolFlags::ERROR`] under the command of the failed
+/// request, in place of that command's normal response.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TaskFailure {
+    /// The task ran past its deadline.
+    Timeout { after_ms: u64 },
+    /// The task was cancelled (`cancel <id>`).
+    Cancelled,
+    /// An I/O error, as text.
+    Io(String),
+    /// A path stayed locked by another task.
+    ResourceBusy { path: String, waited_ms: u64 },
+    /// Anything else.
+    Failed(String),
+    /// The request reused the ID of one still in flight and was not
+    /// run; the earlier request carries on.
+    DuplicateRequest,
+    /// The task's body panicked, with this message. The slave carries on
+    /// and has its crash log entry.
+    Panicked(String),
+}
+
+impl TaskFailure {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Read the payload of an `ERROR` packet. A payload that is not a
+    /// `TaskFailure` is taken as the error text itself.
+    pub fn from_payload(bytes: &[u8]) -> Self {
+        Self::from_bytes(bytes)
+            .unwrap_or_else(|_| TaskFailure::Failed(String::from_utf8_lossy(bytes).into_owned()))
+    }
+
+    /// Build the error response to request `request_id`, sent under the
+    /// request's own `command`.
+    pub fn into_packet(self, request_id: u64, command: Command) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response_with_flags(request_id, command, payload, ProtocolFlags::ERROR)
+    }
+}
+
+impl From<&TaskError> for TaskFailure {
+    fn from(err: &TaskError) -> Self {
+        match err {
+            TaskError::Timeout(after) => TaskFailure::Timeout {
+                after_ms: after.as_millis() as u64,
+            },
+            TaskError::Cancelled => TaskFailure::Cancelled,
+            TaskError::Io(e) => TaskFailure::Io(e.to_string()),
+            TaskError::ResourceBusy { path, waited } => TaskFailure::ResourceBusy {
+                path: path.clone(),
+                waited_ms: waited.as_millis() as u64,
+            },
+            TaskError::Failed(msg) => TaskFailure::Failed(msg.clone()),
+            TaskError::DuplicateRequest(_) => TaskFailure::DuplicateRequest,
+            TaskError::Panicked(msg) => TaskFailure::Panicked(msg.clone()),
+        }
+    }
+}
+
+impl fmt::Display for TaskFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskFailure::Timeout { after_ms } => write!(f, "task timed out after {} ms", after_ms),
+            TaskFailure::Cancelled => write!(f, "task was cancelled"),
+            TaskFailure::Io(e) => write!(f, "I/O error: {}", e),
+            TaskFailure::ResourceBusy { path, waited_ms } => write!(
+                f,
+                "resource busy: {} still locked after {} ms",
+                path, waited_ms
+            ),
+            TaskFailure::Failed(msg) => write!(f, "{}", msg),
+            TaskFailure::DuplicateRequest => {
+                write!(f, "request ID already in use by a running request")
+            }
+            TaskFailure::Panicked(msg) => write!(f, "task panicked: {}", msg),
+        }
+    }
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    enum RegistryHive { ClassesRoot, CurrentUser, LocalMachine, Users, CurrentConfig }
+}
+wire_schema! {
+    struct RegistryQueryRequest {
+        hive: RegistryHive,
+        key_path: String,
+        value_name: Option<String>,
+    }
+}
+wire_schema! {
+    enum RegistryValue {
+        String { 0: String },
+        ExpandString { 0: String },
+        MultiString { 0: Vec<String> },
+        Dword { 0: u32 },
+        Qword { 0: u64 },
+        Binary { 0: Vec<u8> },
+        Other { kind: u32, data: Vec<u8> },
+    }
+}
+wire_schema! {
+    struct RegistryEntry { name: String, value: RegistryValue }
+}
+wire_schema! {
+    enum RegistryErrorKind { NotFound, AccessDenied, Unsupported, Other }
+}
+wire_schema! {
+    enum RegistryQueryResponse {
+        Value { 0: RegistryEntry },
+        Key { subkeys: Vec<String>, values: Vec<RegistryEntry> },
+        Error { kind: RegistryErrorKind, message: String },
+    }
+}
+wire_schema! {
+    enum StartupSource { RegistryKey { 0: String }, StartupFolder { 0: String } }
+}
+wire_schema! {
+    struct StartupEntry { name: String, command: String, source: StartupSource }
+}
+wire_schema! {
+    struct StartupListResponse { entries: Vec<StartupEntry>, errors: Vec<String> }
+}
+wire_schema! {
+    struct CommandTraffic {
+        command: String,
+        packets: u64,
+        bytes_sent: u64,
+        bytes_received: u64,
+    }
+}
+wire_schema! {
+    struct SessionStats {
+        control_sent: u64,
+        control_received: u64,
+        screen_sent: u64,
+        screen_received: u64,
+        commands: Vec<CommandTraffic>,
+        window_bytes: u64,
+        limit_per_hour: Option<u64>,
+        throttled: bool,
+    }
+}
+wire_schema! {
+    enum LockAccess { Shared, Exclusive }
+}
+wire_schema! {
+    struct PathLockInfo { path: String, access: LockAccess, request_id: u64, held_ms: u64 }
+}
+wire_schema! {
+    struct SystemInfoResponse {
+        hostname: String,
+        os: String,
+        session: SessionStats,
+        path_locks: Vec<PathLockInfo>,
+    }
+}
+wire_schema! {
+    struct LimitExceeded { command: String, used: u64, limit: u64, retry_after_secs: u64 }
+}
+wire_schema! {
+    struct TaskInfo {
+        request_id: u64,
+        name: Option<String>,
+        elapsed_ms: u64,
+        timeout_ms: Option<u64>,
+    }
+}
+wire_schema! {
+    struct TaskListResponse { tasks: Vec<TaskInfo>, slow_after_ms: u64 }
+}
+wire_schema! {
+    struct WireTapSettings { enabled: bool, dump_bytes: u32, capture: Option<String> }
+}
+wire_schema! {
+    enum WireTapResponse { Applied { 0: WireTapSettings }, Failed { 0: String } }
+}
+wire_schema! {
+    enum TaskFailure {
+        Timeout { after_ms: u64 },
+        Cancelled,
+        Io { 0: String },
+        ResourceBusy { path: String, waited_ms: u64 },
+        Failed { 0: String },
+        DuplicateRequest,
+        Panicked { 0: String },
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_query_request_roundtrip() {
+        let req = RegistryQueryRequest::new(RegistryHive::LocalMachine, "SOFTWARE\\Microsoft")
+            .with_value("ProductName");
+        let bytes = req.to_bytes().unwrap();
+        let decoded = RegistryQueryRequest::from_bytes(&bytes).unwrap();
+        assert_eq!(req, decoded);
+    }
+
+    #[test]
+    fn registry_query_request_from_path() {
+        let req = RegistryQueryRequest::from_path(
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\",
+        )
+        .unwrap();
+        assert_eq!(req.hive, RegistryHive::LocalMachine);
+        assert_eq!(
+            req.key_path,
+            "SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion"
+        );
+        assert_eq!(
+            req.full_path(),
+            "HKLM\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion"
+        );
+
+        let root = RegistryQueryRequest::from_path("hkcu").unwrap();
+        assert_eq!(root.hive, RegistryHive::CurrentUser);
+        assert!(root.key_path.is_empty());
+
+        assert!(RegistryQueryRequest::from_path("HKXX\\Foo").is_err());
+    }
+
+    #[test]
+    fn registry_query_response_roundtrip() {
+        let responses = [
+            RegistryQueryResponse::Value(RegistryEntry {
+                name: "ProductName".into(),
+                value: RegistryValue::String("Windows 11 Pro".into()),
+            }),
+            RegistryQueryResponse::Key {
+                subkeys: vec!["Fonts".into(), "Winlogon".into()],
+                values: vec![
+                    RegistryEntry {
+                        name: "CurrentBuild".into(),
+                        value: RegistryValue::Dword(22631),
+                    },
+                    RegistryEntry {
+                        name: "DigitalProductId".into(),
+                        value: RegistryValue::Binary(vec![0xA4, 0x00, 0x00]),
+                    },
+                ],
+            },
+            RegistryQueryResponse::error(RegistryErrorKind::AccessDenied, "access denied"),
+        ];
+        for resp in responses {
+            let bytes = resp.to_bytes().unwrap();
+            assert_eq!(RegistryQueryResponse::from_bytes(&bytes).unwrap(), resp);
+        }
+    }
+
+    #[test]
+    fn registry_value_display() {
+        assert_eq!(RegistryValue::Dword(16).to_string(), "0x00000010 (16)");
+        assert_eq!(RegistryValue::Binary(vec![1, 0xff]).to_string(), "01 ff");
+        assert_eq!(RegistryValue::Dword(0).type_name(), "REG_DWORD");
+    }
+
+    #[test]
+    fn registry_value_from_raw() {
+        let wide = |s: &str| -> Vec<u8> {
+            s.encode_utf16()
+                .chain(std::iter::once(0))
+                .flat_map(u16::to_le_bytes)
+                .collect()
+        };
+
+        assert_eq!(
+            RegistryValue::from_raw(REG_SZ, wide("Windows")),
+            RegistryValue::String("Windows".into())
+        );
+        assert_eq!(
+            RegistryValue::from_raw(REG_DWORD, 22631u32.to_le_bytes().to_vec()),
+            RegistryValue::Dword(22631)
+        );
+        assert_eq!(
+            RegistryValue::from_raw(REG_QWORD, 7u64.to_le_bytes().to_vec()),
+            RegistryValue::Qword(7)
+        );
+
+        let mut multi = wide("a");
+        multi.extend(wide("b"));
+        multi.extend([0, 0]);
+        assert_eq!(
+            RegistryValue::from_raw(REG_MULTI_SZ, multi),
+            RegistryValue::MultiString(vec!["a".into(), "b".into()])
+        );
+
+        // Truncated DWORD is preserved as raw bytes
+        assert_eq!(
+            RegistryValue::from_raw(REG_DWORD, vec![1, 2]),
+            RegistryValue::Other {
+                kind: REG_DWORD,
+                data: vec![1, 2]
+            }
+        );
+    }
+
+    #[test]
+    fn startup_list_roundtrip() {
+        let resp = StartupListResponse {
+            entries: vec![
+                StartupEntry {
+                    name: "OneDrive".into(),
+                    command: "\"C:\\Program Files\\OneDrive.exe\" /background".into(),
+                    source: StartupSource::RegistryKey(
+                        "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Run".into(),
+                    ),
+                },
+                StartupEntry {
+                    name: "notes.lnk".into(),
+                    command: "C:\\Startup\\notes.lnk".into(),
+                    source: StartupSource::StartupFolder("C:\\Startup".into()),
+                },
+            ],
+            errors: vec!["HKLM\\...\\RunOnce: access denied".into()],
+        };
+        let packet = resp.clone().into_packet(7).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::StartupList);
+        let decoded = StartupListResponse::from_bytes(packet.payload()).unwrap();
+        assert_eq!(resp, decoded);
+    }
+
+    #[test]
+    fn system_info_roundtrip() {
+        let resp = SystemInfoResponse {
+            hostname: "WS-01".into(),
+            os: "windows".into(),
+            session: SessionStats {
+                control_sent: 2048,
+                screen_sent: 3 * 1024 * 1024,
+                commands: vec![CommandTraffic {
+                    command: "Download".into(),
+                    packets: 2,
+                    bytes_sent: 1500,
+                    bytes_received: 48,
+                }],
+                window_bytes: 3 * 1024 * 1024,
+                limit_per_hour: Some(2 * 1024 * 1024),
+                throttled: true,
+                ..Default::default()
+            },
+            path_locks: vec![PathLockInfo {
+                path: "C:\\data\\a.bin".into(),
+                access: LockAccess::Exclusive,
+                request_id: 12,
+                held_ms: 1500,
+            }],
+        };
+        let packet = resp.clone().into_packet(3).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::SystemInfo);
+        assert_eq!(
+            SystemInfoResponse::from_bytes(packet.payload()).unwrap(),
+            resp
+        );
+
+        let summary = resp.session.summary();
+        assert!(summary.contains("3.0 MiB of 2.0 MiB/h"));
+        assert!(summary.ends_with("(throttled)"));
+    }
+
+    #[test]
+    fn limit_exceeded_roundtrip() {
+        let err = LimitExceeded {
+            command: "Upload".into(),
+            used: 5000,
+            limit: 4096,
+            retry_after_secs: 90,
+        };
+        let packet = err.clone().into_packet(11).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::LimitExceeded);
+        assert_eq!(packet.request_id(), 11);
+        assert_eq!(LimitExceeded::from_bytes(packet.payload()).unwrap(), err);
+        assert!(err.to_string().contains("retry in 90s"));
+    }
+
+    #[test]
+    fn task_list_roundtrip() {
+        let resp = TaskListResponse {
+            tasks: vec![
+                TaskInfo {
+                    request_id: 4,
+                    name: Some("Copy C:\\big.iso -> D:\\".into()),
+                    elapsed_ms: 400_000,
+                    timeout_ms: None,
+                },
+                TaskInfo {
+                    request_id: 9,
+                    name: None,
+                    elapsed_ms: 20,
+                    timeout_ms: Some(60_000),
+                },
+            ],
+            slow_after_ms: 300_000,
+        };
+        let packet = resp.clone().into_packet(0).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::TaskList);
+        assert_eq!(packet.request_id(), 0);
+        let decoded = TaskListResponse::from_bytes(packet.payload()).unwrap();
+        assert_eq!(decoded, resp);
+        assert!(decoded.is_slow(&decoded.tasks[0]));
+        assert!(!decoded.is_slow(&decoded.tasks[1]));
+        assert_eq!(decoded.tasks[0].elapsed(), Duration::from_secs(400));
+    }
+
+    #[test]
+    fn wire_tap_roundtrip() {
+        let settings = WireTapSettings {
+            enabled: true,
+            dump_bytes: 128,
+            capture: Some("C:\\tix\\wire.cap".into()),
+        };
+        let packet = settings.clone().into_packet(4).unwrap();
+        assert_eq!(packet.command().unwrap(), Command::WireTap);
+        assert_eq!(
+            WireTapSettings::from_bytes(packet.payload()).unwrap(),
+            settings
+        );
+
+        let resp = WireTapResponse::Applied(settings);
+        let packet = resp.clone().into_packet(4).unwrap();
+        assert_eq!(WireTapResponse::from_bytes(packet.payload()).unwrap(), resp);
+    }
+
+    #[test]
+    fn task_failure_packet_roundtrip() {
+        let err = TaskError::Timeout(Duration::from_millis(1500));
+        let pkt = TaskFailure::from(&err)
+            .into_packet(9, Command::ShellExecute)
+            .unwrap();
+        assert!(pkt.flags().contains(ProtocolFlags::ERROR));
+        assert_eq!(pkt.command().unwrap(), Command::ShellExecute);
+        let failure = TaskFailure::from_payload(pkt.payload());
+        assert_eq!(failure, TaskFailure::Timeout { after_ms: 1500 });
+        assert_eq!(failure.to_string(), "task timed out after 1500 ms");
+
+        // An older slave's plain-text error.
+        let failure = TaskFailure::from_payload(b"disk full");
+        assert_eq!(failure, TaskFailure::Failed("disk full".to_string()));
+    }
+}
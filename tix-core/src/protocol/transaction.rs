@@ -0,0 +1,368 @@
+//! File transactions — several file operations on the slave that stand
+//! or fall together.
+//!
+//! # Wire Protocol
+//!
+//! ```text
+//! Master ──[FileTransaction]──────────────────► Slave
+//!   Payload: FileTransactionRequest (bincode)
+//!
+//! Slave  ──[FileTransaction]──────────────────► Master
+//!   Payload: FileTransactionResponse (bincode)
+//! ```
+//!
+//! The slave locks every path the operations touch before the first one
+//! runs, then runs them in order while keeping a journal of how to undo
+//! each. When one fails it undoes the ones before it, last first, and
+//! the response says what became of every step. At most
+//! [`MAX_TRANSACTION_OPS`] operations go in one transaction.
+//!
+//! Not everything can be undone. A delete that bypassed the Recycle Bin,
+//! because it asked to or because the slave has none, is gone for good;
+//! its step comes back [`StepOutcome::Irreversible`] instead of rolled
+//! back. An undo that fails leaves its step in effect and says why.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::TixError;
+use crate::message::Command;
+use crate::packet::Packet;
+use crate::protocol::file::DeleteMode;
+use crate::protocol::schema::wire_schema;
+use crate::protocol::system::LockAccess;
+
+/// Most operations one transaction may hold.
+pub const MAX_TRANSACTION_OPS: usize = 64;
+
+// ── Request ───────────────────────────────────────────────────────
+
+/// One step of a [`FileTransactionRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TransactionOp {
+    /// Create a directory and any missing parents. One that already
+    /// exists is left alone.
+    Mkdir { path: String },
+    /// Copy a file or directory tree to `dest`, which must not exist.
+    Copy { src: String, dest: String },
+    /// Move `src` to `dest`, which must not exist.
+    Move { src: String, dest: String },
+    /// Delete a path, to the Recycle Bin unless `mode` says otherwise.
+    Delete { path: String, mode: DeleteMode },
+}
+
+impl TransactionOp {
+    /// The paths this step touches and the lock each needs.
+    pub fn paths(&self) -> Vec<(&str, LockAccess)> {
+        match self {
+            TransactionOp::Mkdir { path } | TransactionOp::Delete { path, .. } => {
+                vec![(path, LockAccess::Exclusive)]
+            }
+            TransactionOp::Copy { src, dest } => {
+                vec![(src, LockAccess::Shared), (dest, LockAccess::Exclusive)]
+            }
+            TransactionOp::Move { src, dest } => {
+                vec![(src, LockAccess::Exclusive), (dest, LockAccess::Exclusive)]
+            }
+        }
+    }
+}
+
+impl fmt::Display for TransactionOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionOp::Mkdir { path } => write!(f, "mkdir {}", path),
+            TransactionOp::Copy { src, dest } => write!(f, "copy {} -> {}", src, dest),
+            TransactionOp::Move { src, dest } => write!(f, "move {} -> {}", src, dest),
+            TransactionOp::Delete {
+                path,
+                mode: DeleteMode::Recycle,
+            } => write!(f, "delete {}", path),
+            TransactionOp::Delete {
+                path,
+                mode: DeleteMode::Permanent,
+            } => write!(f, "delete --permanent {}", path),
+        }
+    }
+}
+
+/// Request payload for `Command::FileTransaction`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileTransactionRequest {
+    /// The steps, run in this order.
+    pub ops: Vec<TransactionOp>,
+}
+
+impl FileTransactionRequest {
+    pub fn new(ops: Vec<TransactionOp>) -> Self {
+        Self { ops }
+    }
+
+    /// Check the request is one a slave will run: at least one step and
+    /// no more than [`MAX_TRANSACTION_OPS`].
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ops.is_empty() {
+            return Err("a transaction needs at least one step".to_string());
+        }
+        if self.ops.len() > MAX_TRANSACTION_OPS {
+            return Err(format!(
+                "a transaction takes at most {} steps, not {}",
+                MAX_TRANSACTION_OPS,
+                self.ops.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet` carrying this request.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::FileTransaction, payload)
+    }
+}
+
+// ── Response ──────────────────────────────────────────────────────
+
+/// What became of one step of a transaction.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// Ran, and stays done.
+    Done,
+    /// Ran, then was undone because a later step failed.
+    RolledBack,
+    /// Ran, and a later step failed, but there was no undoing it: a
+    /// delete that bypassed the Recycle Bin.
+    Irreversible,
+    /// Ran, and undoing it failed; it is still in effect.
+    RollbackFailed(String),
+    /// The step that failed. What it had started was cleaned up.
+    Failed(String),
+    /// Never ran, because an earlier step failed.
+    Skipped,
+}
+
+impl StepOutcome {
+    /// Whether the step's change is still in effect.
+    pub fn in_effect(&self) -> bool {
+        matches!(
+            self,
+            StepOutcome::Done | StepOutcome::Irreversible | StepOutcome::RollbackFailed(_)
+        )
+    }
+}
+
+impl fmt::Display for StepOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepOutcome::Done => f.write_str("done"),
+            StepOutcome::RolledBack => f.write_str("rolled back"),
+            StepOutcome::Irreversible => f.write_str("not undone: deleted permanently"),
+            StepOutcome::RollbackFailed(e) => write!(f, "not undone: {}", e),
+            StepOutcome::Failed(e) => write!(f, "failed: {}", e),
+            StepOutcome::Skipped => f.write_str("skipped"),
+        }
+    }
+}
+
+/// One step of a [`FileTransactionResponse`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StepResult {
+    pub op: TransactionOp,
+    pub outcome: StepOutcome,
+}
+
+/// Reply to [`FileTransactionRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileTransactionResponse {
+    /// One entry per requested step, in request order.
+    pub steps: Vec<StepResult>,
+    /// Why no step ran at all: the request was invalid or its paths
+    /// stayed busy.
+    pub error: Option<String>,
+}
+
+impl FileTransactionResponse {
+    /// A transaction turned away before its first step.
+    pub fn refused(ops: &[TransactionOp], error: impl Into<String>) -> Self {
+        Self {
+            steps: ops
+                .iter()
+                .map(|op| StepResult {
+                    op: op.clone(),
+                    outcome: StepOutcome::Skipped,
+                })
+                .collect(),
+            error: Some(error.into()),
+        }
+    }
+
+    /// Whether every step ran and stays done.
+    pub fn committed(&self) -> bool {
+        self.error.is_none()
+            && self
+                .steps
+                .iter()
+                .all(|step| step.outcome == StepOutcome::Done)
+    }
+
+    /// The index and error of the step that failed, if one did.
+    pub fn failed_step(&self) -> Option<(usize, &str)> {
+        self.steps
+            .iter()
+            .enumerate()
+            .find_map(|(i, step)| match &step.outcome {
+                StepOutcome::Failed(e) => Some((i, e.as_str())),
+                _ => None,
+            })
+    }
+
+    /// The steps still in effect after a failed transaction: the ones
+    /// that could not be undone.
+    pub fn left_in_effect(&self) -> usize {
+        if self.committed() {
+            return 0;
+        }
+        self.steps
+            .iter()
+            .filter(|step| step.outcome.in_effect())
+            .count()
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::FileTransaction, payload)
+    }
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    enum TransactionOp {
+        Mkdir { path: String },
+        Copy { src: String, dest: String },
+        Move { src: String, dest: String },
+        Delete { path: String, mode: DeleteMode },
+    }
+}
+wire_schema! {
+    struct FileTransactionRequest { ops: Vec<TransactionOp> }
+}
+wire_schema! {
+    enum StepOutcome {
+        Done,
+        RolledBack,
+        Irreversible,
+        RollbackFailed { 0: String },
+        Failed { 0: String },
+        Skipped,
+    }
+}
+wire_schema! {
+    struct StepResult { op: TransactionOp, outcome: StepOutcome }
+}
+wire_schema! {
+    struct FileTransactionResponse { steps: Vec<StepResult>, error: Option<String> }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(src: &str, dest: &str) -> TransactionOp {
+        TransactionOp::Move {
+            src: src.to_string(),
+            dest: dest.to_string(),
+        }
+    }
+
+    #[test]
+    fn request_roundtrip_and_limits() {
+        let req = FileTransactionRequest::new(vec![
+            TransactionOp::Mkdir {
+                path: "/srv/new".to_string(),
+            },
+            mv("/srv/a", "/srv/new/a"),
+            TransactionOp::Delete {
+                path: "/srv/old".to_string(),
+                mode: DeleteMode::Permanent,
+            },
+        ]);
+        assert_eq!(req.validate(), Ok(()));
+        let pkt = req.clone().into_packet(3).unwrap();
+        assert_eq!(pkt.command().unwrap(), Command::FileTransaction);
+        assert_eq!(
+            FileTransactionRequest::from_bytes(pkt.payload()).unwrap(),
+            req
+        );
+        assert_eq!(req.ops[2].to_string(), "delete --permanent /srv/old");
+
+        assert!(FileTransactionRequest::new(Vec::new()).validate().is_err());
+        let full = vec![mv("a", "b"); MAX_TRANSACTION_OPS];
+        assert!(FileTransactionRequest::new(full.clone()).validate().is_ok());
+        let over = [full, vec![mv("a", "b")]].concat();
+        let err = FileTransactionRequest::new(over).validate().unwrap_err();
+        assert_eq!(err, "a transaction takes at most 64 steps, not 65");
+    }
+
+    #[test]
+    fn response_says_what_was_left_behind() {
+        let step = |outcome| StepResult {
+            op: mv("a", "b"),
+            outcome,
+        };
+        let committed = FileTransactionResponse {
+            steps: vec![step(StepOutcome::Done), step(StepOutcome::Done)],
+            error: None,
+        };
+        assert!(committed.committed());
+        assert_eq!(committed.failed_step(), None);
+        assert_eq!(committed.left_in_effect(), 0);
+
+        let failed = FileTransactionResponse {
+            steps: vec![
+                step(StepOutcome::RolledBack),
+                step(StepOutcome::Irreversible),
+                step(StepOutcome::Failed("gone".to_string())),
+                step(StepOutcome::Skipped),
+            ],
+            error: None,
+        };
+        assert!(!failed.committed());
+        assert_eq!(failed.failed_step(), Some((2, "gone")));
+        assert_eq!(failed.left_in_effect(), 1);
+        let pkt = failed.clone().into_packet(9).unwrap();
+        assert_eq!(
+            FileTransactionResponse::from_bytes(pkt.payload()).unwrap(),
+            failed
+        );
+
+        let refused = FileTransactionResponse::refused(&[mv("a", "b")], "busy");
+        assert!(!refused.committed());
+        assert_eq!(refused.steps[0].outcome, StepOutcome::Skipped);
+        assert_eq!(refused.left_in_effect(), 0);
+    }
+}
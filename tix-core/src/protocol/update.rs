@@ -0,0 +1,529 @@
+//! Version exchange and slave self-update protocol.
+//!
+//! # Wire Protocol
+//!
+//! ```text
+//! Master ──[Hello]────────────────────────────► Slave
+//!   Payload: HelloInfo (bincode) describing the master
+//!
+//! Slave  ──[Hello]────────────────────────────► Master
+//!   Payload: HelloInfo (bincode) describing the slave
+//!
+//! Master ──[FileWrite ...]────────────────────► Slave
+//!   The new executable, uploaded to a staging path as a normal
+//!   chunked upload (see `protocol::file`)
+//!
+//! Master ──[UpdateApply]──────────────────────► Slave
+//!   Payload: UpdateApplyRequest (bincode)
+//!
+//! Slave  ──[UpdateApply]──────────────────────► Master
+//!   Payload: UpdateApplyResponse (bincode), sent before the slave
+//!   restarts into the new binary
+//! ```
+//!
+//! Each `Hello` also names the newest packet header version its sender
+//! speaks ([`HelloInfo::wire_version`]). Once a side has seen the
+//! other's, it writes every later packet in the version both speak, so
+//! two current peers move to TIX2 while a peer that predates it keeps
+//! TIX1. Either version is accepted on receipt, so it does not matter
+//! which side switches first.
+//!
+//! It also says whether its sender takes compressed file chunks
+//! ([`HelloInfo::compression`]); chunks go compressed only when both
+//! sides said so.
+//!
+//! A slave lists the optional features it has ([`HelloInfo::features`],
+//! see [`features`](crate::features)), so the master can refuse a
+//! command the slave cannot run before sending it. The master's `Hello`
+//! has no list and says nothing either way.
+//!
+//! The master's `Hello` carries its session nonce
+//! ([`HelloInfo::session`]). It goes out before TIX2 is agreed on, in a
+//! header with no room for one, and the slave must stamp the nonce on
+//! everything it sends once TIX2 is: the master drops a TIX2 response
+//! without it as another session's.
+//!
+//! Fields are only ever added to the end of a `Hello`. A build reads
+//! the fields it knows and ignores any that follow; a field missing
+//! from the end of an older build's `Hello` takes its default.
+//!
+//! The slave refuses an update whose staged file does not hash to the
+//! expected value, or whose version is not newer than the running one
+//! (unless the master explicitly allows a downgrade). A refused update
+//! leaves the running binary untouched.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::error::TixError;
+use crate::features::FeatureSet;
+use crate::header::WireVersion;
+use crate::message::Command;
+use crate::packet::Packet;
+use crate::protocol::schema::wire_schema;
+use crate::state::PeerCapabilities;
+
+// ── Hello ─────────────────────────────────────────────────────────
+
+/// Identity exchanged in the `Hello` handshake.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HelloInfo {
+    /// Program name (`tix-master`, `tix-slave`).
+    pub product: String,
+    /// Program version, `major.minor.patch`.
+    pub version: String,
+    /// Machine name.
+    pub hostname: String,
+    /// Operating system family (`windows`, `linux`, ...).
+    pub os: String,
+    /// Stable ID of a slave installation (a UUID), kept across restarts
+    /// and reconnects so the master can tell it is the same machine.
+    /// Empty from the master.
+    pub slave_id: String,
+    /// Newest packet header version the sender speaks.
+    pub wire_version: u8,
+    /// The sender takes and sends zstd-compressed file chunks (see
+    /// [`ChunkCompressor`](crate::protocol::ChunkCompressor)).
+    pub compression: bool,
+    /// Names of the optional features the sender has (see
+    /// [`FeatureSet`]). `None` from the master.
+    pub features: Option<Vec<String>>,
+    /// Session nonce the sender stamps on its packets (see
+    /// [`request_id`](crate::state::request_id)). 0 from the slave.
+    pub session: u32,
+}
+
+impl HelloInfo {
+    /// Describe the calling program on this machine.
+    pub fn local(product: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            product: product.into(),
+            version: version.into(),
+            hostname: std::env::var("COMPUTERNAME")
+                .or_else(|_| std::env::var("HOSTNAME"))
+                .unwrap_or_default(),
+            os: std::env::consts::OS.to_string(),
+            slave_id: String::new(),
+            wire_version: WireVersion::LATEST.number(),
+            compression: true,
+            features: None,
+            session: 0,
+        }
+    }
+
+    /// Say whether compressed file chunks are welcome.
+    pub fn with_compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// List the optional features the sender has.
+    pub fn with_features(mut self, features: &FeatureSet) -> Self {
+        self.features = Some(features.names());
+        self
+    }
+
+    /// The capabilities this `Hello` advertises.
+    pub fn capabilities(&self) -> PeerCapabilities {
+        PeerCapabilities {
+            compression: self.compression,
+            features: self.features.as_ref().map(FeatureSet::from_names),
+            ..PeerCapabilities::default()
+        }
+    }
+
+    /// Announce the session nonce the sender stamps on its packets.
+    pub fn with_session(mut self, session: u32) -> Self {
+        self.session = session;
+        self
+    }
+
+    /// Present the slave installation `id`.
+    pub fn with_slave_id(mut self, id: impl Into<String>) -> Self {
+        self.slave_id = id.into();
+        self
+    }
+
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes. Fields missing from the
+    /// end, in a `Hello` from an older build, take their defaults;
+    /// fields a newer build added after ours are ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        if bytes.is_empty() {
+            return Err(TixError::Encoding("empty Hello".to_string()));
+        }
+        let mut body = bytes;
+        Ok(Self {
+            product: hello_field(&mut body)?,
+            version: hello_field(&mut body)?,
+            hostname: hello_field(&mut body)?,
+            os: hello_field(&mut body)?,
+            slave_id: hello_field(&mut body)?,
+            wire_version: hello_field(&mut body)?,
+            compression: hello_field(&mut body)?,
+            features: hello_field(&mut body)?,
+            session: hello_field(&mut body)?,
+        })
+    }
+
+    /// Header version to write to the sender of this `Hello`: the newer
+    /// of the versions both sides speak.
+    pub fn negotiated_version(&self) -> WireVersion {
+        WireVersion::negotiate(self.wire_version)
+    }
+
+    /// Build the master's `Hello` command packet.
+    pub fn into_command_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::Hello, payload)
+    }
+
+    /// Build the slave's `Hello` response packet.
+    pub fn into_response_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::Hello, payload)
+    }
+}
+
+/// The next field of a `Hello`, or its default once the body has run
+/// out.
+fn hello_field<T: DeserializeOwned + Default>(body: &mut &[u8]) -> Result<T, TixError> {
+    if body.is_empty() {
+        return Ok(T::default());
+    }
+    bincode::deserialize_from(body).map_err(|e| TixError::Encoding(e.to_string()))
+}
+
+// ── Version Gate ──────────────────────────────────────────────────
+
+/// Parse `major.minor.patch` into numbers. A leading `v` and any
+/// pre-release or build suffix (`-rc1`, `+abc`) are ignored; missing
+/// components count as zero.
+pub fn parse_version(version: &str) -> Option<[u64; 3]> {
+    let core = version.trim().trim_start_matches('v');
+    let core = core.split(['-', '+']).next().unwrap_or("");
+    if core.is_empty() {
+        return None;
+    }
+    let mut parts = [0u64; 3];
+    for (i, part) in core.split('.').enumerate() {
+        if i >= 3 {
+            return None;
+        }
+        parts[i] = part.parse().ok()?;
+    }
+    Some(parts)
+}
+
+/// Compare two version strings numerically.
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    Some(parse_version(a)?.cmp(&parse_version(b)?))
+}
+
+/// Decide whether `offered` may replace `current`.
+pub fn check_version(
+    current: &str,
+    offered: &str,
+    allow_downgrade: bool,
+) -> Result<(), UpdateError> {
+    let ordering = compare_versions(current, offered)
+        .ok_or_else(|| UpdateError::BadVersion(offered.to_string()))?;
+    match ordering {
+        Ordering::Less => Ok(()),
+        _ if allow_downgrade => Ok(()),
+        Ordering::Equal => Err(UpdateError::AlreadyInstalled(current.to_string())),
+        Ordering::Greater => Err(UpdateError::Downgrade {
+            current: current.to_string(),
+            offered: offered.to_string(),
+        }),
+    }
+}
+
+// ── Update Apply ──────────────────────────────────────────────────
+
+/// Request payload for `Command::UpdateApply`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UpdateApplyRequest {
+    /// Where the new executable was uploaded, as reported by the
+    /// slave's `FileTransferAck`.
+    pub staged_path: String,
+    /// Expected Blake3 hash of the staged file.
+    pub blake3_hash: [u8; 32],
+    /// Version of the staged build.
+    pub version: String,
+    /// Accept a version that is not newer than the running one.
+    pub allow_downgrade: bool,
+    /// Restart into the new binary once it is in place.
+    pub restart: bool,
+}
+
+impl UpdateApplyRequest {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a command `Packet` carrying this request.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_command(request_id, Command::UpdateApply, payload)
+    }
+}
+
+/// Why the slave refused an update. In every case the running binary
+/// is left in place.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum UpdateError {
+    /// The staged file does not hash to the expected value.
+    HashMismatch { expected: String, actual: String },
+    /// The offered version is older than the running one.
+    Downgrade { current: String, offered: String },
+    /// The offered version is already running.
+    AlreadyInstalled(String),
+    /// The offered version string could not be parsed.
+    BadVersion(String),
+    /// The staged file does not exist.
+    StagedMissing(String),
+    /// The running executable could not be moved aside.
+    Locked(String),
+    /// Any other I/O failure while swapping binaries.
+    Io(String),
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HashMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "hash mismatch: expected {expected}, staged file is {actual}"
+                )
+            }
+            Self::Downgrade { current, offered } => {
+                write!(f, "refusing downgrade from {current} to {offered}")
+            }
+            Self::AlreadyInstalled(v) => write!(f, "version {v} is already installed"),
+            Self::BadVersion(v) => write!(f, "unparseable version '{v}'"),
+            Self::StagedMissing(p) => write!(f, "staged file {p} not found"),
+            Self::Locked(e) => write!(f, "running executable is locked: {e}"),
+            Self::Io(e) => write!(f, "update failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for UpdateError {}
+
+/// Response payload for `Command::UpdateApply`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum UpdateApplyResponse {
+    /// The new binary is in place.
+    Accepted {
+        previous_version: String,
+        new_version: String,
+        /// The slave is about to restart and drop the connection.
+        restarting: bool,
+    },
+    /// The update was refused; nothing changed.
+    Rejected(UpdateError),
+}
+
+impl UpdateApplyResponse {
+    /// Serialize to bytes for packet payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from packet payload bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Build a response `Packet`.
+    pub fn into_packet(self, request_id: u64) -> Result<Packet, TixError> {
+        let payload = self.to_bytes()?;
+        Packet::new_response(request_id, Command::UpdateApply, payload)
+    }
+}
+
+/// Lowercase hex rendering of a hash, for error messages.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// ── Wire Schema ───────────────────────────────────────────────────
+
+wire_schema! {
+    struct HelloInfo {
+        product: String,
+        version: String,
+        hostname: String,
+        os: String,
+        slave_id: String,
+        wire_version: u8,
+        compression: bool,
+        features: Option<Vec<String>>,
+        session: u32,
+    }
+}
+wire_schema! {
+    struct UpdateApplyRequest {
+        staged_path: String,
+        blake3_hash: [u8; 32],
+        version: String,
+        allow_downgrade: bool,
+        restart: bool,
+    }
+}
+wire_schema! {
+    enum UpdateError {
+        HashMismatch { expected: String, actual: String },
+        Downgrade { current: String, offered: String },
+        AlreadyInstalled { 0: String },
+        BadVersion { 0: String },
+        StagedMissing { 0: String },
+        Locked { 0: String },
+        Io { 0: String },
+    }
+}
+wire_schema! {
+    enum UpdateApplyResponse {
+        Accepted { previous_version: String, new_version: String, restarting: bool },
+        Rejected { 0: UpdateError },
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(parse_version("1.2.3"), Some([1, 2, 3]));
+        assert_eq!(parse_version("v0.4"), Some([0, 4, 0]));
+        assert_eq!(parse_version("2.0.0-rc1"), Some([2, 0, 0]));
+        assert_eq!(parse_version("1.2.3.4"), None);
+        assert_eq!(parse_version("latest"), None);
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn version_gate() {
+        assert!(check_version("0.1.0", "0.2.0", false).is_ok());
+        assert!(check_version("0.9.0", "0.10.0", false).is_ok());
+        assert_eq!(
+            check_version("0.2.0", "0.2.0", false),
+            Err(UpdateError::AlreadyInstalled("0.2.0".into()))
+        );
+        assert!(matches!(
+            check_version("0.3.0", "0.2.9", false),
+            Err(UpdateError::Downgrade { .. })
+        ));
+        assert!(check_version("0.3.0", "0.2.9", true).is_ok());
+        assert_eq!(
+            check_version("0.3.0", "nightly", true),
+            Err(UpdateError::BadVersion("nightly".into()))
+        );
+    }
+
+    #[test]
+    fn hello_and_apply_roundtrip() {
+        let hello = HelloInfo::local("tix-slave", "0.1.0")
+            .with_slave_id("6f1c2b9e-3d4a-4c1b-9a8e-2f7d5e0c1b3a");
+        let pkt = hello.clone().into_response_packet(1).unwrap();
+        assert_eq!(pkt.command().unwrap(), Command::Hello);
+        assert_eq!(HelloInfo::from_bytes(pkt.payload()).unwrap(), hello);
+
+        let req = UpdateApplyRequest {
+            staged_path: "C:\\Users\\me\\Downloads\\tix-slave.exe".into(),
+            blake3_hash: [7; 32],
+            version: "0.2.0".into(),
+            allow_downgrade: false,
+            restart: true,
+        };
+        let pkt = req.clone().into_packet(2).unwrap();
+        assert_eq!(pkt.command().unwrap(), Command::UpdateApply);
+        assert_eq!(UpdateApplyRequest::from_bytes(pkt.payload()).unwrap(), req);
+
+        let resp = UpdateApplyResponse::Rejected(UpdateError::Locked("in use".into()));
+        let bytes = resp.to_bytes().unwrap();
+        assert_eq!(UpdateApplyResponse::from_bytes(&bytes).unwrap(), resp);
+    }
+
+    #[test]
+    fn hello_negotiates_with_newer_builds() {
+        let hello = HelloInfo::local("tix-master", "0.3.0");
+        assert_eq!(hello.negotiated_version(), WireVersion::LATEST);
+        assert!(hello.capabilities().compression);
+        assert!(!hello.clone().with_compression(false).capabilities().compression);
+
+        // A newer build than this one settles on our latest.
+        let future = HelloInfo {
+            wire_version: 9,
+            ..hello
+        };
+        let info = HelloInfo::from_bytes(&future.to_bytes().unwrap()).unwrap();
+        assert_eq!(info.negotiated_version(), WireVersion::LATEST);
+
+        assert!(HelloInfo::from_bytes(b"junk").is_err());
+        assert!(HelloInfo::from_bytes(b"").is_err());
+    }
+
+    #[test]
+    fn hello_features_and_session_round_trip() {
+        let features = FeatureSet::from_names(["services", "trash"]);
+        let hello = HelloInfo::local("tix-slave", "0.4.0")
+            .with_features(&features)
+            .with_session(0xC0FF_EE01);
+        let info = HelloInfo::from_bytes(&hello.to_bytes().unwrap()).unwrap();
+        assert_eq!(info.capabilities().features, Some(features));
+        assert_eq!(info.session, 0xC0FF_EE01);
+
+        // One from a newer build names features this one has not heard
+        // of; they are skipped.
+        let future = HelloInfo {
+            features: Some(vec!["trash".into(), "holodeck".into()]),
+            ..hello
+        };
+        let info = HelloInfo::from_bytes(&future.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            info.capabilities().features,
+            Some(FeatureSet::from_names(["trash"]))
+        );
+    }
+
+    #[test]
+    fn hello_fields_are_added_at_the_end() {
+        let hello = HelloInfo::local("tix-slave", "0.5.0").with_slave_id("id");
+
+        // A newer build's Hello with a field after ours.
+        let mut bytes = hello.to_bytes().unwrap();
+        bytes.extend(bincode::serialize(&"later").unwrap());
+        assert_eq!(HelloInfo::from_bytes(&bytes).unwrap(), hello);
+
+        // An older build's that stops before `features` and `session`.
+        let older = ("tix-slave", "0.4.0", "desk", "windows", "id", 2u8, true);
+        let info = HelloInfo::from_bytes(&bincode::serialize(&older).unwrap()).unwrap();
+        assert_eq!(info.slave_id, "id");
+        assert_eq!(info.negotiated_version(), WireVersion::V2);
+        assert!(info.compression);
+        assert_eq!(info.features, None);
+        assert_eq!(info.session, 0);
+
+        // A field cut off halfway is an error, not a default.
+        let bytes = hello.to_bytes().unwrap();
+        assert!(HelloInfo::from_bytes(&bytes[..bytes.len() - 2]).is_err());
+    }
+}
@@ -140,7 +140,7 @@ mod tests {
         est.record_at(t0 + Duration::from_secs(1), 1_000_000);
         let bps = est.estimate_bps();
         // 2 MB over 1 second ≈ 2 MB/s.
-        assert!(bps >= 1_900_000 && bps <= 2_100_000, "bps = {bps}");
+        assert!((1_900_000..=2_100_000).contains(&bps), "bps = {bps}");
     }
 
     #[test]
@@ -0,0 +1,281 @@
+//! Dropping pointer moves that later input has overtaken.
+//!
+//! When the input path backs up, the slave receives queued events in a
+//! burst. Replaying every pointer move in it drags the remote cursor
+//! through positions the viewer left long ago, and a drag ends up
+//! wherever the replay happened to be. A viewer that negotiated
+//! `timed_input` (see [`ScreenStartRequest::timed_input`]) stamps every
+//! event with its own monotonic clock, so the slave can tell how far
+//! behind a move is.
+//!
+//! The slave collects the events that are already waiting into an
+//! [`InputBurst`] and applies them in order, minus the moves that are
+//! both *stale* — stamped more than `stale_after` before the newest
+//! event seen so far — and *superseded* by a later move. Buttons, the
+//! wheel and keys are never dropped, and neither is the last move
+//! before one of them: Windows presses the button wherever the pointer
+//! is, so that move is where the click lands.
+//!
+//! Only the viewer's stamps are compared with each other, so the two
+//! machines' clocks never need to agree.
+//!
+//! [`ScreenStartRequest::timed_input`]: crate::protocol::ScreenStartRequest::timed_input
+
+use std::time::Duration;
+
+use crate::protocol::screen::{MouseEventKind, TimedKeyEvent, TimedMouseEvent};
+
+/// How far behind the newest event a move may be before it is dropped.
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_millis(100);
+
+/// Events held before a burst is applied regardless of what is still
+/// waiting, so a flood cannot hold input back indefinitely.
+pub const MAX_BURST: usize = 256;
+
+// ── TimedInput ───────────────────────────────────────────────────
+
+/// One stamped input event, as the slave receives it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimedInput {
+    Mouse(TimedMouseEvent),
+    Key(TimedKeyEvent),
+}
+
+impl TimedInput {
+    /// The viewer's stamp, in milliseconds.
+    pub fn at_ms(&self) -> u64 {
+        match self {
+            Self::Mouse(mouse) => mouse.at_ms,
+            Self::Key(key) => key.at_ms,
+        }
+    }
+
+    /// Whether this is a pointer move, the only kind ever dropped.
+    fn is_move(&self) -> bool {
+        matches!(self, Self::Mouse(mouse) if mouse.event.kind == MouseEventKind::Move)
+    }
+}
+
+// ── InputBurst ───────────────────────────────────────────────────
+
+/// Stamped input waiting to be applied, and what earlier bursts left
+/// behind: the newest stamp seen and the newest move applied.
+#[derive(Debug)]
+pub struct InputBurst {
+    stale_after_ms: u64,
+    pending: Vec<TimedInput>,
+    newest: Option<u64>,
+    last_move: Option<u64>,
+    dropped: u64,
+}
+
+impl Default for InputBurst {
+    fn default() -> Self {
+        Self::new(DEFAULT_STALE_AFTER)
+    }
+}
+
+impl InputBurst {
+    /// Drop moves more than `stale_after` behind the newest event;
+    /// zero drops none.
+    pub fn new(stale_after: Duration) -> Self {
+        Self {
+            stale_after_ms: stale_after.as_millis() as u64,
+            pending: Vec::new(),
+            newest: None,
+            last_move: None,
+            dropped: 0,
+        }
+    }
+
+    /// Queue `input` behind what is already waiting.
+    pub fn push(&mut self, input: TimedInput) {
+        self.newest = self.newest.max(Some(input.at_ms()));
+        self.pending.push(input);
+    }
+
+    /// Events waiting to be applied.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Whether the burst should be applied before taking more.
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= MAX_BURST
+    }
+
+    /// The waiting events to apply, in the order they arrived, without
+    /// the moves later input has overtaken.
+    pub fn take(&mut self) -> Vec<TimedInput> {
+        let pending = std::mem::take(&mut self.pending);
+        if self.stale_after_ms == 0 {
+            self.note_moves(&pending);
+            return pending;
+        }
+        let cutoff = self.newest.unwrap_or(0).saturating_sub(self.stale_after_ms);
+
+        // Walk backwards, so each move knows what the next pointer
+        // event after it is: another move supersedes it, a button or
+        // the wheel needs it.
+        let mut keep = vec![true; pending.len()];
+        let mut next_is_move = None;
+        for (i, input) in pending.iter().enumerate().rev() {
+            let TimedInput::Mouse(_) = input else {
+                continue;
+            };
+            if input.is_move() {
+                let superseded = match next_is_move {
+                    Some(next_is_move) => next_is_move,
+                    // The last move of the burst only loses to one an
+                    // earlier burst already applied.
+                    None => self.last_move.is_some_and(|last| last > input.at_ms()),
+                };
+                keep[i] = !(superseded && input.at_ms() < cutoff);
+                next_is_move = Some(true);
+            } else {
+                next_is_move = Some(false);
+            }
+        }
+
+        let before = pending.len();
+        let kept: Vec<TimedInput> = pending
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(input, keep)| keep.then_some(input))
+            .collect();
+        self.dropped += (before - kept.len()) as u64;
+        self.note_moves(&kept);
+        kept
+    }
+
+    /// Moves dropped so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn note_moves(&mut self, applied: &[TimedInput]) {
+        for input in applied.iter().filter(|input| input.is_move()) {
+            self.last_move = self.last_move.max(Some(input.at_ms()));
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::screen::{KeyEvent, MouseButton, MouseEvent, key_modifiers};
+
+    fn mouse(at_ms: u64, event: MouseEvent) -> TimedInput {
+        TimedInput::Mouse(TimedMouseEvent { at_ms, event })
+    }
+
+    fn moved(at_ms: u64, x: i32) -> TimedInput {
+        mouse(at_ms, MouseEvent::move_to(x, 0))
+    }
+
+    fn key(at_ms: u64) -> TimedInput {
+        TimedInput::Key(TimedKeyEvent {
+            at_ms,
+            event: KeyEvent::press(0x41, 0x1E, key_modifiers::NONE),
+        })
+    }
+
+    fn burst(inputs: &[TimedInput]) -> InputBurst {
+        let mut burst = InputBurst::default();
+        for input in inputs {
+            burst.push(*input);
+        }
+        burst
+    }
+
+    #[test]
+    fn a_backlog_of_moves_collapses_to_the_recent_ones() {
+        let mut burst = burst(&[
+            moved(0, 0),
+            moved(40, 1),
+            moved(80, 2),
+            moved(120, 3),
+            moved(160, 4),
+            moved(200, 5),
+        ]);
+        // Newest is 200: 0, 40 and 80 are more than 100 ms behind it.
+        assert_eq!(burst.take(), [moved(120, 3), moved(160, 4), moved(200, 5)]);
+        assert_eq!(burst.dropped(), 3);
+        assert!(burst.is_empty());
+    }
+
+    #[test]
+    fn buttons_wheel_and_keys_survive_in_order() {
+        let press = mouse(10, MouseEvent::press(0, 0, MouseButton::Left));
+        let wheel = mouse(30, MouseEvent::scroll(0, 0, -120));
+        let release = mouse(50, MouseEvent::release(0, 0, MouseButton::Left));
+        let mut burst = burst(&[
+            press,
+            moved(20, 1),
+            wheel,
+            key(40),
+            release,
+            moved(60, 2),
+            moved(400, 3),
+        ]);
+        assert_eq!(
+            burst.take(),
+            [press, moved(20, 1), wheel, key(40), release, moved(400, 3)]
+        );
+        assert_eq!(burst.dropped(), 1);
+    }
+
+    #[test]
+    fn the_move_a_release_lands_on_is_kept() {
+        // A drag that backed up: press, a trail of moves, release.
+        let press = mouse(0, MouseEvent::press(0, 0, MouseButton::Left));
+        let release = mouse(300, MouseEvent::release(0, 0, MouseButton::Left));
+        let mut burst = burst(&[
+            press,
+            moved(50, 1),
+            moved(100, 2),
+            moved(150, 3),
+            release,
+            key(500),
+        ]);
+        // Only the last move before the release decides where the drop
+        // happens; the trail before it goes.
+        assert_eq!(burst.take(), [press, moved(150, 3), release, key(500)]);
+        assert_eq!(burst.dropped(), 2);
+    }
+
+    #[test]
+    fn a_late_move_loses_to_one_already_applied() {
+        let mut burst = burst(&[moved(300, 1)]);
+        assert_eq!(burst.take(), [moved(300, 1)]);
+
+        // Reordered on the way: older than the move already applied,
+        // and more than 100 ms behind the newest stamp.
+        burst.push(moved(150, 0));
+        assert!(burst.take().is_empty());
+
+        // Old, but nothing newer moved the pointer: it is where the
+        // pointer should be.
+        burst.push(key(600));
+        burst.take();
+        burst.push(moved(350, 2));
+        assert_eq!(burst.take(), [moved(350, 2)]);
+        assert_eq!(burst.dropped(), 1);
+    }
+
+    #[test]
+    fn zero_threshold_drops_nothing() {
+        let mut burst = InputBurst::new(Duration::ZERO);
+        for input in [moved(0, 0), moved(1_000, 1), moved(2_000, 2)] {
+            burst.push(input);
+        }
+        assert_eq!(burst.take().len(), 3);
+        assert_eq!(burst.dropped(), 0);
+    }
+}
@@ -7,14 +7,63 @@
 //!
 //! This module is **Windows-only**. On other platforms the types are
 //! still defined but construction will fail at runtime.
-
-use std::time::Instant;
+//!
+//! # Mode changes
+//!
+//! A resolution change, monitor hot-plug or desktop switch invalidates
+//! the duplication interface. [`capture_frame`](DxgiCapturer::capture_frame)
+//! reports this as [`TixError::CaptureLost`]; the owner is expected to
+//! call [`FrameSource::reinitialize`] and treat the next frame as a
+//! keyframe, since its dimensions may differ.
+//!
+//! # GPU preparation
+//!
+//! With [`with_gpu_prep`](DxgiCapturer::with_gpu_prep) the capturer
+//! diffs and packs frames on the device (see [`gpu`](crate::rdp::gpu))
+//! and reads back only the changed tiles. Frames then arrive in the
+//! stream's pixel format, and [`FrameSource::take_prepared_tiles`] says
+//! which tiles changed.
 
 use crate::error::TixError;
+use crate::rdp::gpu::{GpuPrepMode, PreparedTiles};
 use crate::rdp::types::{PixelFormat, RawScreenFrame};
 
+// ── FrameSource ──────────────────────────────────────────────────
+
+/// A producer of raw desktop frames.
+///
+/// [`DxgiCapturer`] is the production implementation. The trait lets
+/// [`ScreenService`](crate::rdp::service::ScreenService) run against
+/// synthetic sources in tests.
+pub trait FrameSource: Send {
+    /// Wait up to `timeout_ms` for the next frame.
+    ///
+    /// Returns [`TixError::Timeout`] when nothing changed and
+    /// [`TixError::CaptureLost`] when the source must be reinitialised.
+    fn capture_frame(&mut self, timeout_ms: u32) -> Result<RawScreenFrame, TixError>;
+
+    /// Rebuild the capture pipeline for the current display mode.
+    fn reinitialize(&mut self) -> Result<(), TixError>;
+
+    /// Current frame width in pixels.
+    fn width(&self) -> u32;
+
+    /// Current frame height in pixels.
+    fn height(&self) -> u32;
+
+    /// The changed tiles of the frame just captured, when the source
+    /// found them itself; `None` leaves it to the delta detector.
+    fn take_prepared_tiles(&mut self) -> Option<PreparedTiles> {
+        None
+    }
+}
+
 // ── Platform gate ────────────────────────────────────────────────
 
+/// Whether [`DxgiCapturer`] can capture anything in this build: only on
+/// Windows with the `win-capture` feature. Elsewhere it always fails.
+pub const CAPTURE_AVAILABLE: bool = cfg!(all(target_os = "windows", feature = "win-capture"));
+
 /// DXGI-based screen capturer.
 ///
 /// Wraps the `IDXGIOutputDuplication` pipeline:
@@ -31,29 +80,50 @@ use crate::rdp::types::{PixelFormat, RawScreenFrame};
 ///
 /// All unsafe FFI calls are confined to this struct.
 pub struct DxgiCapturer {
+    /// Monitor being duplicated, kept for reinitialisation.
+    monitor_index: u32,
     /// Screen width in pixels.
     width: u32,
     /// Screen height in pixels.
     height: u32,
+    /// Top-left corner of the monitor on the virtual desktop.
+    origin: (i32, i32),
     /// Row pitch of the staging texture.
+    #[cfg(all(target_os = "windows", feature = "win-capture"))]
     stride: u32,
 
     // ── Platform handles (Windows only) ──────────────────────
-    #[cfg(target_os = "windows")]
+    #[cfg(all(target_os = "windows", feature = "win-capture"))]
     device: windows::Win32::Graphics::Direct3D11::ID3D11Device,
-    #[cfg(target_os = "windows")]
+    #[cfg(all(target_os = "windows", feature = "win-capture"))]
     context: windows::Win32::Graphics::Direct3D11::ID3D11DeviceContext,
-    #[cfg(target_os = "windows")]
-    duplication: windows::Win32::Graphics::Dxgi::IDXGIOutputDuplication,
-    #[cfg(target_os = "windows")]
+    /// `None` between losing the duplication and a successful
+    /// [`reinitialize`](FrameSource::reinitialize).
+    #[cfg(all(target_os = "windows", feature = "win-capture"))]
+    duplication: Option<windows::Win32::Graphics::Dxgi::IDXGIOutputDuplication>,
+    #[cfg(all(target_os = "windows", feature = "win-capture"))]
     staging_texture: windows::Win32::Graphics::Direct3D11::ID3D11Texture2D,
+    /// GPU preparation as asked for: mode, block size and pixel format.
+    /// Kept so [`reinitialize`](FrameSource::reinitialize) can set it up
+    /// again on the new device.
+    #[cfg(all(target_os = "windows", feature = "win-capture"))]
+    gpu_settings: (GpuPrepMode, usize, PixelFormat),
+    /// `None` while frames are read back whole.
+    #[cfg(all(target_os = "windows", feature = "win-capture"))]
+    gpu_prep: Option<crate::rdp::gpu::GpuFramePrep>,
+    /// What the last prepared frame changed, until taken.
+    #[cfg(all(target_os = "windows", feature = "win-capture"))]
+    prepared: Option<PreparedTiles>,
 }
 
 // ── Windows implementation ───────────────────────────────────────
 
-#[cfg(target_os = "windows")]
+#[cfg(all(target_os = "windows", feature = "win-capture"))]
 mod platform {
+    use std::time::Instant;
+
     use super::*;
+    use crate::rdp::gpu::GpuFramePrep;
     use windows::{
         core::Interface,
         Win32::Graphics::{
@@ -107,6 +177,10 @@ mod platform {
                     .map_err(|e| TixError::Other(format!("EnumOutputs({monitor_index}) failed: {e}")))?
             };
 
+            let origin = unsafe { output.GetDesc() }
+                .map(|desc| (desc.DesktopCoordinates.left, desc.DesktopCoordinates.top))
+                .unwrap_or_default();
+
             // 3. Duplicate the output.
             let output1: IDXGIOutput1 = output.cast().map_err(|e| {
                 TixError::Other(format!("Cast to IDXGIOutput1 failed: {e}"))
@@ -153,16 +227,83 @@ mod platform {
             let stride = width * 4;
 
             Ok(Self {
+                monitor_index,
                 width,
                 height,
+                origin,
                 stride,
                 device,
                 context,
-                duplication,
+                duplication: Some(duplication),
                 staging_texture,
+                gpu_settings: (GpuPrepMode::Off, 0, PixelFormat::Bgra8),
+                gpu_prep: None,
+                prepared: None,
             })
         }
 
+        /// Prepare frames on the GPU as `mode` says, in tiles of
+        /// `block_size` packed in `format`.
+        ///
+        /// With [`GpuPrepMode::Auto`] a device that cannot do it is
+        /// logged and left on the CPU path; with [`GpuPrepMode::On`] it
+        /// is an error.
+        pub fn with_gpu_prep(
+            mut self,
+            mode: GpuPrepMode,
+            block_size: usize,
+            format: PixelFormat,
+        ) -> Result<Self, TixError> {
+            self.gpu_settings = (mode, block_size, format);
+            self.start_gpu_prep()?;
+            Ok(self)
+        }
+
+        fn start_gpu_prep(&mut self) -> Result<(), TixError> {
+            let (mode, block_size, format) = self.gpu_settings;
+            self.gpu_prep = None;
+            self.prepared = None;
+            if mode == GpuPrepMode::Off {
+                return Ok(());
+            }
+            let prep = GpuFramePrep::new(
+                &self.device,
+                &self.context,
+                self.width,
+                self.height,
+                block_size,
+                format,
+            );
+            match prep {
+                Ok(prep) => {
+                    tracing::info!(block_size, %format, "preparing frames on the GPU");
+                    self.gpu_prep = Some(prep);
+                    Ok(())
+                }
+                Err(e) if mode == GpuPrepMode::Auto => {
+                    tracing::warn!(error = %e, "GPU frame prep unavailable, using the CPU");
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        }
+
+        /// Drop the current duplication and rebuild the whole pipeline
+        /// for the monitor's current mode: a new D3D11 device (the old
+        /// one may have been removed by a driver reset), a fresh output
+        /// enumeration and a new duplication.
+        ///
+        /// The old duplication must be released first: DXGI refuses a
+        /// second `DuplicateOutput` on the same output while one is live.
+        pub fn reinitialize(&mut self) -> Result<(), TixError> {
+            self.duplication = None;
+            self.gpu_prep = None;
+            let settings = self.gpu_settings;
+            *self = unsafe { Self::init_dxgi(self.monitor_index)? };
+            self.gpu_settings = settings;
+            self.start_gpu_prep()
+        }
+
         /// Capture the next desktop frame.
         ///
         /// Blocks for up to `timeout_ms` milliseconds waiting for a new
@@ -173,14 +314,20 @@ mod platform {
         }
 
         unsafe fn capture_inner(&mut self, timeout_ms: u32) -> Result<RawScreenFrame, TixError> {
-            use windows::Win32::Graphics::Dxgi::DXGI_ERROR_WAIT_TIMEOUT;
+            use windows::Win32::Graphics::Dxgi::{
+                DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_WAIT_TIMEOUT,
+            };
+
+            let duplication = self
+                .duplication
+                .clone()
+                .ok_or_else(|| TixError::CaptureLost("duplication not initialised".into()))?;
 
             let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
             let mut resource = None;
 
             match unsafe {
-                self.duplication
-                    .AcquireNextFrame(timeout_ms, &mut frame_info, &mut resource)
+                duplication.AcquireNextFrame(timeout_ms, &mut frame_info, &mut resource)
             } {
                 Ok(()) => {}
                 Err(e) if e.code() == DXGI_ERROR_WAIT_TIMEOUT => {
@@ -188,6 +335,23 @@ mod platform {
                         timeout_ms as u64,
                     )));
                 }
+                // Mode change, desktop switch (UAC, lock screen) or GPU
+                // reset: the duplication is dead and must be rebuilt.
+                Err(e) if e.code() == DXGI_ERROR_ACCESS_LOST
+                    || e.code() == DXGI_ERROR_DEVICE_REMOVED =>
+                {
+                    self.duplication = None;
+                    // After a driver reset the device is gone too; its
+                    // removal reason is the HRESULT worth logging.
+                    let reason = match unsafe { self.device.GetDeviceRemovedReason() } {
+                        Err(r) => format!(", device removed: 0x{:08X}", r.code().0),
+                        Ok(()) => String::new(),
+                    };
+                    return Err(TixError::CaptureLost(format!(
+                        "AcquireNextFrame: {e} (0x{:08X}{reason})",
+                        e.code().0
+                    )));
+                }
                 Err(e) => {
                     return Err(TixError::Other(format!("AcquireNextFrame failed: {e}")));
                 }
@@ -197,10 +361,45 @@ mod platform {
                 resource.ok_or_else(|| TixError::Other("Acquired resource is None".into()))?;
 
             let texture: ID3D11Texture2D = resource.cast().map_err(|e| {
-                let _ = unsafe { self.duplication.ReleaseFrame() };
+                let _ = unsafe { duplication.ReleaseFrame() };
                 TixError::Other(format!("Cast to ID3D11Texture2D failed: {e}"))
             })?;
 
+            // The desktop can change size without ACCESS_LOST on some
+            // drivers; copying into a mismatched staging texture would
+            // silently produce garbage, so treat it the same way.
+            let mut desc = D3D11_TEXTURE2D_DESC::default();
+            unsafe { texture.GetDesc(&mut desc) };
+            if desc.Width != self.width || desc.Height != self.height {
+                let _ = unsafe { duplication.ReleaseFrame() };
+                self.duplication = None;
+                return Err(TixError::CaptureLost(format!(
+                    "desktop resized from {}x{} to {}x{}",
+                    self.width, self.height, desc.Width, desc.Height
+                )));
+            }
+
+            if let Some(prep) = self.gpu_prep.as_mut() {
+                match prep.prepare(&texture, Instant::now()) {
+                    Ok((frame, tiles)) => {
+                        let _ = unsafe { duplication.ReleaseFrame() };
+                        self.prepared = Some(tiles);
+                        return Ok(frame);
+                    }
+                    // `on` was asked for: better a visible failure than
+                    // a silent slowdown.
+                    Err(e) if self.gpu_settings.0 == GpuPrepMode::On => {
+                        let _ = unsafe { duplication.ReleaseFrame() };
+                        return Err(e);
+                    }
+                    Err(e) => {
+                        tracing::warn!(error = %e, "GPU frame prep failed, using the CPU");
+                        self.gpu_prep = None;
+                    }
+                }
+            }
+            self.prepared = None;
+
             // Copy GPU texture → staging texture.
             unsafe {
                 self.context
@@ -208,7 +407,7 @@ mod platform {
             }
 
             // Release the DXGI frame as early as possible.
-            let _ = unsafe { self.duplication.ReleaseFrame() };
+            let _ = unsafe { duplication.ReleaseFrame() };
 
             // Map the staging texture for CPU read.
             let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
@@ -254,12 +453,24 @@ mod platform {
         pub fn height(&self) -> u32 {
             self.height
         }
+
+        /// Top-left corner of the monitor on the virtual desktop; the
+        /// primary monitor is at `(0, 0)`.
+        pub fn origin(&self) -> (i32, i32) {
+            self.origin
+        }
+
+        /// What the last prepared frame changed; `None` after a frame
+        /// read back whole.
+        pub fn take_prepared_tiles(&mut self) -> Option<PreparedTiles> {
+            self.prepared.take()
+        }
     }
 }
 
 // ── Non-Windows stub ─────────────────────────────────────────────
 
-#[cfg(not(target_os = "windows"))]
+#[cfg(not(all(target_os = "windows", feature = "win-capture")))]
 impl DxgiCapturer {
     /// DXGI is only available on Windows.
     pub fn new(_monitor_index: u32) -> Result<Self, TixError> {
@@ -272,6 +483,30 @@ impl DxgiCapturer {
         Err(TixError::Other("Not supported on this platform".into()))
     }
 
+    pub fn reinitialize(&mut self) -> Result<(), TixError> {
+        Self::new(self.monitor_index).map(|_| ())
+    }
+
+    /// There is no GPU path here: `on` is an error, anything else
+    /// stays on the CPU.
+    pub fn with_gpu_prep(
+        self,
+        mode: GpuPrepMode,
+        _block_size: usize,
+        _format: PixelFormat,
+    ) -> Result<Self, TixError> {
+        match mode {
+            GpuPrepMode::On => Err(TixError::Other(
+                "GPU frame prep is only available on Windows".into(),
+            )),
+            _ => Ok(self),
+        }
+    }
+
+    pub fn take_prepared_tiles(&mut self) -> Option<PreparedTiles> {
+        None
+    }
+
     pub fn width(&self) -> u32 {
         self.width
     }
@@ -279,4 +514,30 @@ impl DxgiCapturer {
     pub fn height(&self) -> u32 {
         self.height
     }
+
+    pub fn origin(&self) -> (i32, i32) {
+        self.origin
+    }
+}
+
+impl FrameSource for DxgiCapturer {
+    fn capture_frame(&mut self, timeout_ms: u32) -> Result<RawScreenFrame, TixError> {
+        DxgiCapturer::capture_frame(self, timeout_ms)
+    }
+
+    fn reinitialize(&mut self) -> Result<(), TixError> {
+        DxgiCapturer::reinitialize(self)
+    }
+
+    fn width(&self) -> u32 {
+        DxgiCapturer::width(self)
+    }
+
+    fn height(&self) -> u32 {
+        DxgiCapturer::height(self)
+    }
+
+    fn take_prepared_tiles(&mut self) -> Option<PreparedTiles> {
+        DxgiCapturer::take_prepared_tiles(self)
+    }
 }
@@ -3,18 +3,59 @@
 //! Receives encoded frames from the [`ScreenTransport`], decodes them
 //! via [`FrameDecoder`], and provides the latest frame buffer to the
 //! display layer.
+//!
+//! Status datagrams from the slave (a secure desktop holding the
+//! screen) are published on a separate channel; any frame clears them.
+//!
+//! In [low-bandwidth mode](crate::rdp::thumbnail) each thumbnail is
+//! scaled up to the screen's size and published like a full frame, so
+//! the renderer and pointer mapping need not know. The foreground window
+//! sent with it goes out on its own channel, which any frame clears.
+//!
+//! Frames are published as [`TimedFrame`]s carrying the slave's capture
+//! time next to the local arrival time, so the renderer can pace them
+//! evenly instead of showing each the instant it lands. Each also lists
+//! the areas that changed since the frame published before it, so the
+//! renderer can redraw only those.
+//!
+//! A client built [`with_dictionary`](ScreenClient::with_dictionary)
+//! also decodes frames the slave compressed against that dictionary;
+//! plain frames decode either way.
+//!
+//! [`ScreenClient::snapshot`] (or a [`SnapshotHandle`] once the client
+//! has moved into its task) copies the latest frame out for saving.
+//!
+//! A frame that fails to decode or apply is counted in [`FrameStats`]
+//! and skipped; the next one that decodes is shown, redrawn whole. Only
+//! a run of failures (see
+//! [`with_max_stream_errors`](ScreenClient::with_max_stream_errors))
+//! ends the receive loop, since by then the stream itself is broken.
+//!
+//! Each frame is traced through `receive`, `decode` and `apply` spans
+//! and reported to [`telemetry`](crate::rdp::telemetry) under the
+//! `viewer` role, including its age once it is ready to display.
 
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use tokio::sync::watch;
+use tracing::{Instrument, debug_span, field, warn};
 
 use crate::error::TixError;
-use crate::rdp::decoder::FrameDecoder;
-use crate::rdp::transport::ScreenTransport;
+use crate::rdp::convert;
+use crate::rdp::decoder::{DirtyRect, FrameDecoder};
+use crate::rdp::desktop::ScreenStatus;
+use crate::rdp::dictionary::FrameDictionary;
+use crate::rdp::telemetry::{self, Role};
+use crate::rdp::thumbnail::{ForegroundInfo, Thumbnail};
+use crate::rdp::transport::{ScreenMessage, ScreenTransport, TrafficCounter};
 use crate::rdp::types::PixelFormat;
 
+/// Frames in a row that may fail to decode before the client gives up,
+/// unless configured otherwise.
+pub const DEFAULT_MAX_STREAM_ERRORS: u32 = 30;
+
 // ── FrameStats ───────────────────────────────────────────────────
 
 /// Per-frame statistics exposed to the UI.
@@ -30,6 +71,147 @@ pub struct FrameStats {
     pub width: u32,
     /// Last frame height.
     pub height: u32,
+    /// Number of the last frame, as stamped by the slave.
+    pub frame_number: u64,
+    /// Frames skipped because they failed to decode or apply.
+    pub stream_errors: u64,
+    /// Why the last skipped frame failed.
+    pub last_error: Option<String>,
+}
+
+// ── TimedFrame ───────────────────────────────────────────────────
+
+/// A decoded frame and when it was captured and received.
+#[derive(Debug, Clone)]
+pub struct TimedFrame {
+    /// The whole screen, in the client's pixel format.
+    pub buffer: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// Number of the frame, as stamped by the slave.
+    pub frame_number: u64,
+    /// Capture time on the slave's stream clock, if the slave stamps
+    /// it. Comparable only with other frames of the same stream.
+    pub capture_ts: Option<Duration>,
+    /// When the frame was decoded and ready to show.
+    pub arrival_ts: Instant,
+    /// Position in the client's output, counting from 1 without gaps
+    /// (unlike `frame_number`, which skips frames the slave dropped).
+    pub seq: u64,
+    /// What changed since frame `seq - 1`, in frame pixels; `None` when
+    /// the whole frame did (a full frame, a new size, a skipped frame).
+    pub dirty: Option<Vec<DirtyRect>>,
+}
+
+impl TimedFrame {
+    /// What changed since the frame numbered `seq`, if this frame comes
+    /// right after it and says; `None` asks for a full redraw.
+    pub fn dirty_since(&self, seq: Option<u64>) -> Option<&[DirtyRect]> {
+        if seq? + 1 != self.seq {
+            return None;
+        }
+        self.dirty.as_deref()
+    }
+}
+
+impl Default for TimedFrame {
+    fn default() -> Self {
+        Self {
+            buffer: Vec::new(),
+            width: 0,
+            height: 0,
+            frame_number: 0,
+            capture_ts: None,
+            arrival_ts: Instant::now(),
+            seq: 0,
+            dirty: None,
+        }
+    }
+}
+
+// ── Snapshots ────────────────────────────────────────────────────
+
+/// A copy of the whole remote screen as last decoded, independent of
+/// how the viewer scales it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotFrame {
+    pub width: u32,
+    pub height: u32,
+    /// Layout of `data`.
+    pub format: PixelFormat,
+    /// Number of the frame, as stamped by the slave.
+    pub frame_number: u64,
+    /// Tightly packed rows, `width × height` pixels.
+    pub data: Vec<u8>,
+}
+
+impl SnapshotFrame {
+    /// Copy `frame`, or `None` before anything has been decoded.
+    pub fn of(frame: &TimedFrame, format: PixelFormat) -> Option<Self> {
+        if frame.width == 0 || frame.height == 0 || frame.buffer.is_empty() {
+            return None;
+        }
+        Some(Self {
+            width: frame.width,
+            height: frame.height,
+            format,
+            frame_number: frame.frame_number,
+            data: frame.buffer.clone(),
+        })
+    }
+
+    /// The pixels as RGBA8, fully opaque: a screen has no transparency,
+    /// whatever the capture left in the alpha bytes.
+    pub fn to_rgba8(&self) -> Result<Vec<u8>, TixError> {
+        let pixels = self.width as usize * self.height as usize;
+        let needed = pixels * self.format.bytes_per_pixel();
+        if self.data.len() < needed {
+            return Err(TixError::Other(format!(
+                "{}x{} {} snapshot needs {} bytes, has {}",
+                self.width,
+                self.height,
+                self.format,
+                needed,
+                self.data.len()
+            )));
+        }
+        let data = &self.data[..needed];
+        let mut rgba = Vec::with_capacity(pixels * 4);
+        match self.format {
+            PixelFormat::Rgba8 => {
+                for px in data.chunks_exact(4) {
+                    rgba.extend_from_slice(&[px[0], px[1], px[2], 0xFF]);
+                }
+            }
+            PixelFormat::Rgb8 => {
+                for px in data.chunks_exact(3) {
+                    rgba.extend_from_slice(&[px[0], px[1], px[2], 0xFF]);
+                }
+            }
+            _ => {
+                let mut bgra = Vec::with_capacity(pixels * 4);
+                convert::unpack_to_bgra(data, self.format, &mut bgra)?;
+                for px in bgra.chunks_exact(4) {
+                    rgba.extend_from_slice(&[px[2], px[1], px[0], 0xFF]);
+                }
+            }
+        }
+        Ok(rgba)
+    }
+}
+
+/// Takes snapshots of a [`ScreenClient`]'s frames from another task.
+#[derive(Debug, Clone)]
+pub struct SnapshotHandle {
+    frame_rx: watch::Receiver<TimedFrame>,
+    pixel_format: PixelFormat,
+}
+
+impl SnapshotHandle {
+    /// Copy of the latest decoded frame; `None` until one has arrived.
+    pub fn snapshot(&self) -> Option<SnapshotFrame> {
+        SnapshotFrame::of(&self.frame_rx.borrow(), self.pixel_format)
+    }
 }
 
 // ── ScreenClient ─────────────────────────────────────────────────
@@ -44,13 +226,22 @@ pub struct ScreenClient {
     decoder: FrameDecoder,
     running: Arc<AtomicBool>,
     pixel_format: PixelFormat,
-    /// Sender half of the frame-buffer watch channel.
-    frame_tx: watch::Sender<Vec<u8>>,
+    /// Sender half of the frame watch channel.
+    frame_tx: watch::Sender<TimedFrame>,
     /// Receiver half — clone this to get frames in the renderer.
-    frame_rx: watch::Receiver<Vec<u8>>,
+    frame_rx: watch::Receiver<TimedFrame>,
     /// Stats channel.
     stats_tx: watch::Sender<FrameStats>,
     stats_rx: watch::Receiver<FrameStats>,
+    /// Stream status channel.
+    status_tx: watch::Sender<ScreenStatus>,
+    status_rx: watch::Receiver<ScreenStatus>,
+    /// Low-bandwidth channel: the foreground window while thumbnails
+    /// are shown, `None` while frames are.
+    low_bandwidth_tx: watch::Sender<Option<ForegroundInfo>>,
+    low_bandwidth_rx: watch::Receiver<Option<ForegroundInfo>>,
+    /// Failed frames in a row that end the loop; 0 never does.
+    max_stream_errors: u32,
 }
 
 impl ScreenClient {
@@ -59,8 +250,10 @@ impl ScreenClient {
     /// `pixel_format` describes the expected pixel layout (typically
     /// [`PixelFormat::Bgra8`] from DXGI capture).
     pub fn new(transport: ScreenTransport, pixel_format: PixelFormat) -> Self {
-        let (frame_tx, frame_rx) = watch::channel(Vec::new());
+        let (frame_tx, frame_rx) = watch::channel(TimedFrame::default());
         let (stats_tx, stats_rx) = watch::channel(FrameStats::default());
+        let (status_tx, status_rx) = watch::channel(ScreenStatus::Live);
+        let (low_bandwidth_tx, low_bandwidth_rx) = watch::channel(None);
         Self {
             transport: Arc::new(transport),
             decoder: FrameDecoder::new(),
@@ -70,12 +263,37 @@ impl ScreenClient {
             frame_rx,
             stats_tx,
             stats_rx,
+            status_tx,
+            status_rx,
+            low_bandwidth_tx,
+            low_bandwidth_rx,
+            max_stream_errors: DEFAULT_MAX_STREAM_ERRORS,
         }
     }
 
+    /// Also decode frames compressed against `dictionary`. Load it
+    /// before confirming it to the slave, so every frame that uses it
+    /// finds it here.
+    pub fn with_dictionary(mut self, dictionary: &FrameDictionary) -> Self {
+        self.decoder.set_dictionary(Some(dictionary));
+        self
+    }
+
+    /// Give up once `max` frames in a row failed to decode or apply;
+    /// 0 keeps skipping them forever.
+    pub fn with_max_stream_errors(mut self, max: u32) -> Self {
+        self.max_stream_errors = max;
+        self
+    }
+
+    /// Byte counters of the transport the client receives on.
+    pub fn traffic(&self) -> TrafficCounter {
+        self.transport.traffic()
+    }
+
     /// Obtain a `watch::Receiver` that yields the latest decoded
-    /// frame buffer whenever a new frame arrives.
-    pub fn frame_receiver(&self) -> watch::Receiver<Vec<u8>> {
+    /// frame whenever a new frame arrives.
+    pub fn frame_receiver(&self) -> watch::Receiver<TimedFrame> {
         self.frame_rx.clone()
     }
 
@@ -84,6 +302,33 @@ impl ScreenClient {
         self.stats_rx.clone()
     }
 
+    /// Obtain a `watch::Receiver` for the stream status, e.g. to show
+    /// that the slave is on a secure desktop.
+    pub fn status_receiver(&self) -> watch::Receiver<ScreenStatus> {
+        self.status_rx.clone()
+    }
+
+    /// Obtain a `watch::Receiver` that holds the foreground window
+    /// while the slave sends thumbnails instead of frames, and `None`
+    /// otherwise.
+    pub fn low_bandwidth_receiver(&self) -> watch::Receiver<Option<ForegroundInfo>> {
+        self.low_bandwidth_rx.clone()
+    }
+
+    /// Copy of the latest decoded frame; `None` until one has arrived.
+    pub fn snapshot(&self) -> Option<SnapshotFrame> {
+        SnapshotFrame::of(&self.frame_rx.borrow(), self.pixel_format)
+    }
+
+    /// A handle for [`snapshot`](Self::snapshot)s that outlives the
+    /// borrow of the client, e.g. once it runs in a task.
+    pub fn snapshot_handle(&self) -> SnapshotHandle {
+        SnapshotHandle {
+            frame_rx: self.frame_rx.clone(),
+            pixel_format: self.pixel_format,
+        }
+    }
+
     /// A cloneable stop handle.
     pub fn stop_handle(&self) -> Arc<AtomicBool> {
         Arc::clone(&self.running)
@@ -91,8 +336,9 @@ impl ScreenClient {
 
     /// Run the receive loop.
     ///
-    /// Blocks the calling task until [`stop`](Self::stop) is invoked or
-    /// the transport encounters an unrecoverable error.
+    /// Blocks the calling task until [`stop`](Self::stop) is invoked,
+    /// the transport encounters an unrecoverable error or too many
+    /// frames in a row fail to decode.
     pub async fn run(&mut self) -> Result<(), TixError> {
         self.running.store(true, Ordering::SeqCst);
 
@@ -101,24 +347,124 @@ impl ScreenClient {
         let mut last_frame_time = Instant::now();
         let mut total_frames: u64 = 0;
         let mut total_bytes: u64 = 0;
+        let mut published: u64 = 0;
+        let mut size = (0, 0);
+        let mut stream_errors: u64 = 0;
+        let mut failed_in_a_row: u32 = 0;
+        let mut last_error: Option<String> = None;
 
         while self.running.load(Ordering::SeqCst) {
-            let encoded = match self.transport.receive_frame().await {
-                Ok(f) => f,
+            let receive_span = debug_span!("receive", frame_number = field::Empty);
+            let received = self.transport.receive().instrument(receive_span.clone()).await;
+            let encoded = match received {
+                Ok(ScreenMessage::Frame(f)) => f,
+                Ok(ScreenMessage::Status(status)) => {
+                    self.status_tx.send_if_modified(|current| {
+                        let changed = *current != status;
+                        *current = status;
+                        changed
+                    });
+                    continue;
+                }
+                Ok(ScreenMessage::Thumbnail(thumbnail)) => {
+                    total_bytes += thumbnail.jpeg.len() as u64;
+                    match self.publish_thumbnail(&thumbnail, published + 1) {
+                        Ok(()) => {
+                            published += 1;
+                            // The next frame redraws whole.
+                            size = (0, 0);
+                            self.stats_tx.send_modify(|stats| {
+                                stats.total_bytes = total_bytes;
+                                stats.width = thumbnail.screen_width;
+                                stats.height = thumbnail.screen_height;
+                            });
+                        }
+                        Err(e) => warn!("skipping a thumbnail that failed to decode: {e}"),
+                    }
+                    continue;
+                }
+                Ok(ScreenMessage::Foreground(info)) => {
+                    self.low_bandwidth_tx.send_replace(Some(info));
+                    continue;
+                }
                 Err(TixError::Timeout(_)) => continue,
                 Err(e) => return Err(e),
             };
+            // Frames flowing means live, even if the slave's own notice
+            // was lost, and no longer thumbnails.
+            self.status_tx.send_if_modified(|current| {
+                let stale = !current.is_live();
+                *current = ScreenStatus::Live;
+                stale
+            });
+            self.low_bandwidth_tx.send_if_modified(|current| current.take().is_some());
+            let frame_number = encoded.frame_number;
+            receive_span.record("frame_number", frame_number);
 
             total_bytes += encoded.data.len() as u64;
             total_frames += 1;
+            telemetry::frame(Role::Viewer, "received");
+            telemetry::bytes(Role::Viewer, encoded.data.len() as u64);
 
             // Decode.
-            let decoded = self.decoder.decode(&encoded)?;
-            let _ = self.decoder.apply(&decoded, bpp);
+            let decode_start = Instant::now();
+            let decoded =
+                debug_span!("decode", frame_number).in_scope(|| self.decoder.decode(&encoded));
+            telemetry::stage_duration(Role::Viewer, "decode", decode_start.elapsed());
+            let applied = decoded.and_then(|decoded| {
+                let apply_start = Instant::now();
+                let applied = debug_span!("apply", frame_number)
+                    .in_scope(|| self.decoder.apply(&decoded, bpp).map(|_| ()));
+                telemetry::stage_duration(Role::Viewer, "apply", apply_start.elapsed());
+                applied.map(|()| decoded)
+            });
+            let decoded = match applied {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    // A half-applied delta may have touched anything, and
+                    // a later frame onto the same screen would not say so.
+                    telemetry::frame(Role::Viewer, "dropped");
+                    size = (0, 0);
+                    stream_errors += 1;
+                    failed_in_a_row += 1;
+                    warn!(frame_number, "skipping a frame that failed to decode: {e}");
+                    if self.max_stream_errors > 0 && failed_in_a_row >= self.max_stream_errors {
+                        return Err(TixError::Other(format!(
+                            "{failed_in_a_row} frames in a row failed to decode, the last with: {e}"
+                        )));
+                    }
+                    let error = e.to_string();
+                    last_error = Some(error.clone());
+                    self.stats_tx.send_modify(|stats| {
+                        stats.stream_errors = stream_errors;
+                        stats.last_error = Some(error);
+                    });
+                    continue;
+                }
+            };
+            failed_in_a_row = 0;
+            telemetry::frame(Role::Viewer, "decoded");
+            // A delta onto the same screen says what it touched; anything
+            // else may have changed every pixel.
+            let same_size = std::mem::replace(&mut size, (decoded.width, decoded.height))
+                == (decoded.width, decoded.height);
+            let dirty = (same_size && !decoded.is_full_frame)
+                .then(|| FrameDecoder::block_rects(&decoded.data, bpp).ok())
+                .flatten();
 
             // Publish.
-            let buf = self.decoder.frame_buffer().to_vec();
-            let _ = self.frame_tx.send(buf);
+            published += 1;
+            let _ = self.frame_tx.send(TimedFrame {
+                buffer: self.decoder.frame_buffer().to_vec(),
+                width: decoded.width,
+                height: decoded.height,
+                frame_number,
+                capture_ts: encoded.capture_clock,
+                arrival_ts: Instant::now(),
+                seq: published,
+                dirty,
+            });
+            telemetry::frame_latency(Role::Viewer, encoded.timestamp.elapsed());
 
             // FPS tracking.
             let now = Instant::now();
@@ -137,12 +483,38 @@ impl ScreenClient {
                 total_bytes,
                 width: decoded.width,
                 height: decoded.height,
+                frame_number,
+                stream_errors,
+                last_error: last_error.clone(),
             });
         }
 
         Ok(())
     }
 
+    /// Scale `thumbnail` up to the screen and publish it as frame `seq`.
+    fn publish_thumbnail(&self, thumbnail: &Thumbnail, seq: u64) -> Result<(), TixError> {
+        let bgra = thumbnail.to_screen_bgra()?;
+        let mut buffer = Vec::with_capacity(bgra.len() / 4 * self.pixel_format.bytes_per_pixel());
+        convert::pack_bgra(&bgra, self.pixel_format, &mut buffer)?;
+        self.low_bandwidth_tx.send_if_modified(|current| {
+            let entering = current.is_none();
+            current.get_or_insert_with(ForegroundInfo::default);
+            entering
+        });
+        let _ = self.frame_tx.send(TimedFrame {
+            buffer,
+            width: thumbnail.screen_width,
+            height: thumbnail.screen_height,
+            frame_number: 0,
+            capture_ts: None,
+            arrival_ts: Instant::now(),
+            seq,
+            dirty: None,
+        });
+        Ok(())
+    }
+
     /// Signal the client to stop.
     pub fn stop(&self) {
         self.running.store(false, Ordering::SeqCst);
@@ -153,3 +525,181 @@ impl ScreenClient {
         self.running.load(Ordering::SeqCst)
     }
 }
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdp::encoder::EncodedFrame;
+    use tokio::net::UdpSocket;
+
+    /// A 4×4 BGRA full frame, or one whose payload is not zstd at all.
+    fn frame(frame_number: u64, corrupt: bool) -> EncodedFrame {
+        let pixels = [frame_number as u8; 64];
+        EncodedFrame {
+            frame_number,
+            timestamp: Instant::now(),
+            width: 4,
+            height: 4,
+            data: if corrupt { vec![0xA5; 40] } else { zstd::encode_all(&pixels[..], 1).unwrap() },
+            is_full_frame: true,
+            block_count: 0,
+            format: PixelFormat::Bgra8,
+            capture_clock: None,
+        }
+    }
+
+    /// A client on loopback and the transport that feeds it.
+    async fn pair(max_stream_errors: u32) -> (ScreenClient, ScreenTransport) {
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let (sender_addr, receiver_addr) =
+            (sender.local_addr().unwrap(), receiver.local_addr().unwrap());
+        let transport = ScreenTransport::new(receiver, sender_addr);
+        let client = ScreenClient::new(transport, PixelFormat::Bgra8)
+            .with_max_stream_errors(max_stream_errors);
+        (client, ScreenTransport::new(sender, receiver_addr))
+    }
+
+    #[tokio::test]
+    async fn bad_frames_are_skipped_and_counted() {
+        let (mut client, sender) = pair(DEFAULT_MAX_STREAM_ERRORS).await;
+        let mut frames = client.frame_receiver();
+        let stats = client.stats_receiver();
+        let stop = client.stop_handle();
+        let run = tokio::spawn(async move { client.run().await });
+
+        for (n, corrupt) in [(1, false), (2, true), (3, false)] {
+            sender.send_frame(&frame(n, corrupt)).await.unwrap();
+        }
+        let shown = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                frames.changed().await.unwrap();
+                let frame = frames.borrow_and_update().clone();
+                if frame.frame_number == 3 {
+                    return frame;
+                }
+            }
+        })
+        .await
+        .expect("the frame after the bad one never arrived");
+        // Shown as the second frame, with nothing to say what changed.
+        assert_eq!(shown.seq, 2);
+        assert_eq!(shown.dirty, None);
+        assert_eq!(&shown.buffer[..4], &[3; 4]);
+        let stats = stats.borrow().clone();
+        assert_eq!(stats.stream_errors, 1);
+        assert!(stats.last_error.is_some());
+
+        stop.store(false, Ordering::SeqCst);
+        sender.send_frame(&frame(4, false)).await.unwrap();
+        assert!(run.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn a_run_of_bad_frames_ends_the_stream() {
+        let (mut client, sender) = pair(3).await;
+        let run = tokio::spawn(async move { client.run().await });
+        for n in 1..=3 {
+            sender.send_frame(&frame(n, true)).await.unwrap();
+        }
+        let result = tokio::time::timeout(Duration::from_secs(5), run).await.unwrap().unwrap();
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("3 frames in a row"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn thumbnails_show_as_frames_until_frames_return() {
+        let (mut client, sender) = pair(DEFAULT_MAX_STREAM_ERRORS).await;
+        let mut frames = client.frame_receiver();
+        let mut low_bandwidth = client.low_bandwidth_receiver();
+        let stop = client.stop_handle();
+        let run = tokio::spawn(async move { client.run().await });
+
+        // A 16×8 screen, left half white, sent as an 8×4 thumbnail.
+        let mut data = vec![0u8; 16 * 8 * 4];
+        for (i, px) in data.chunks_exact_mut(4).enumerate() {
+            if i % 16 < 8 {
+                px.copy_from_slice(&[255; 4]);
+            }
+        }
+        let screen = crate::rdp::types::RawScreenFrame {
+            width: 16,
+            height: 8,
+            stride: 64,
+            format: PixelFormat::Bgra8,
+            data,
+            timestamp: Instant::now(),
+        };
+        let thumbnail = Thumbnail::capture(&screen, 8, 90).unwrap();
+        let info = ForegroundInfo { title: "Inbox".into(), ..ForegroundInfo::default() };
+        sender.send_thumbnail(&thumbnail).await.unwrap();
+        sender.send_foreground(&info).await.unwrap();
+
+        let wait = Duration::from_secs(5);
+        tokio::time::timeout(wait, frames.changed()).await.unwrap().unwrap();
+        let shown = frames.borrow_and_update().clone();
+        assert_eq!((shown.width, shown.height, shown.seq), (16, 8, 1));
+        assert_eq!(shown.buffer.len(), 16 * 8 * 4);
+        assert!(shown.buffer[0] > 200 && shown.buffer[15 * 4] < 60);
+        tokio::time::timeout(wait, low_bandwidth.wait_for(|view| view.as_ref() == Some(&info)))
+            .await
+            .unwrap()
+            .unwrap();
+
+        sender.send_frame(&frame(5, false)).await.unwrap();
+        tokio::time::timeout(wait, frames.changed()).await.unwrap().unwrap();
+        assert_eq!(frames.borrow().seq, 2);
+        assert_eq!(*low_bandwidth.borrow(), None);
+
+        stop.store(false, Ordering::SeqCst);
+        sender.send_frame(&frame(6, false)).await.unwrap();
+        assert!(run.await.unwrap().is_ok());
+    }
+
+    #[test]
+    fn snapshot_waits_for_a_frame() {
+        assert_eq!(SnapshotFrame::of(&TimedFrame::default(), PixelFormat::Bgra8), None);
+    }
+
+    #[test]
+    fn snapshot_swizzles_to_opaque_rgba() {
+        let frame = TimedFrame {
+            buffer: vec![1, 2, 3, 0, 10, 20, 30, 128],
+            width: 2,
+            height: 1,
+            frame_number: 7,
+            ..TimedFrame::default()
+        };
+        let snapshot = SnapshotFrame::of(&frame, PixelFormat::Bgra8).unwrap();
+        assert_eq!(snapshot.frame_number, 7);
+        assert_eq!(snapshot.to_rgba8().unwrap(), [3, 2, 1, 255, 30, 20, 10, 255]);
+
+        let gray = SnapshotFrame {
+            format: PixelFormat::Gray8,
+            data: vec![0x40, 0x80],
+            ..snapshot.clone()
+        };
+        assert_eq!(gray.to_rgba8().unwrap(), [0x40, 0x40, 0x40, 255, 0x80, 0x80, 0x80, 255]);
+
+        let short = SnapshotFrame { data: vec![0; 4], ..snapshot };
+        assert!(short.to_rgba8().is_err());
+    }
+
+    #[test]
+    fn dirty_regions_only_follow_the_previous_frame() {
+        let rect = DirtyRect { x: 16, y: 32, width: 16, height: 16 };
+        let frame = TimedFrame { seq: 8, dirty: Some(vec![rect]), ..TimedFrame::default() };
+        assert_eq!(frame.dirty_since(Some(7)), Some(&[rect][..]));
+        // A frame was skipped, or none shown yet: redraw everything.
+        assert_eq!(frame.dirty_since(Some(6)), None);
+        assert_eq!(frame.dirty_since(Some(8)), None);
+        assert_eq!(frame.dirty_since(None), None);
+
+        let full = TimedFrame { seq: 8, dirty: None, ..TimedFrame::default() };
+        assert_eq!(full.dirty_since(Some(7)), None);
+        let still = TimedFrame { seq: 8, dirty: Some(Vec::new()), ..TimedFrame::default() };
+        assert_eq!(still.dirty_since(Some(7)), Some(&[][..]));
+    }
+}
@@ -0,0 +1,275 @@
+//! Pixel format conversion for reduced-colour streaming.
+//!
+//! Captures are always BGRA8. On slow links the encoder packs dirty
+//! pixels into [`PixelFormat::Rgb565`] (half the bytes) or
+//! [`PixelFormat::Gray8`] (a quarter) before compression, and the
+//! decoder expands them back to BGRA8 for the renderer. Neither round
+//! trip is exact:
+//!
+//! | Format   | Max error per channel               |
+//! |----------|-------------------------------------|
+//! | `Rgb565` | 7 (red, blue), 3 (green)            |
+//! | `Gray8`  | colour is lost; grey inputs within 1 |
+//!
+//! The inner loops handle four pixels per iteration over fixed-size
+//! chunks, which the compiler turns into SIMD code without any
+//! platform-specific intrinsics; a scalar tail handles the remainder.
+
+use crate::error::TixError;
+use crate::rdp::types::PixelFormat;
+
+// ── Public entry points ──────────────────────────────────────────
+
+/// Append the BGRA8 pixels in `src` to `dst` converted to `format`.
+///
+/// `src.len()` must be a multiple of 4 and `format` one the encoder
+/// can produce (see [`PixelFormat::is_encodable`]).
+pub fn pack_bgra(src: &[u8], format: PixelFormat, dst: &mut Vec<u8>) -> Result<(), TixError> {
+    if !src.len().is_multiple_of(4) {
+        return Err(TixError::Other(format!(
+            "BGRA input of {} bytes is not whole pixels",
+            src.len()
+        )));
+    }
+    let pixels = src.len() / 4;
+    let start = dst.len();
+    match format {
+        PixelFormat::Bgra8 => dst.extend_from_slice(src),
+        PixelFormat::Rgb565 => {
+            dst.resize(start + pixels * 2, 0);
+            bgra_to_rgb565(src, &mut dst[start..]);
+        }
+        PixelFormat::Gray8 => {
+            dst.resize(start + pixels, 0);
+            bgra_to_gray8(src, &mut dst[start..]);
+        }
+        other => {
+            return Err(TixError::Other(format!("cannot encode frames as {other}")));
+        }
+    }
+    Ok(())
+}
+
+/// Append the `format` pixels in `src` to `dst` expanded to BGRA8.
+pub fn unpack_to_bgra(src: &[u8], format: PixelFormat, dst: &mut Vec<u8>) -> Result<(), TixError> {
+    let bpp = format.bytes_per_pixel();
+    if !src.len().is_multiple_of(bpp) {
+        return Err(TixError::Other(format!(
+            "{format} input of {} bytes is not whole pixels",
+            src.len()
+        )));
+    }
+    let pixels = src.len() / bpp;
+    let start = dst.len();
+    match format {
+        PixelFormat::Bgra8 => dst.extend_from_slice(src),
+        PixelFormat::Rgb565 => {
+            dst.resize(start + pixels * 4, 0);
+            rgb565_to_bgra(src, &mut dst[start..]);
+        }
+        PixelFormat::Gray8 => {
+            dst.resize(start + pixels * 4, 0);
+            gray8_to_bgra(src, &mut dst[start..]);
+        }
+        other => {
+            return Err(TixError::Other(format!("cannot decode {other} frames to BGRA")));
+        }
+    }
+    Ok(())
+}
+
+// ── RGB565 ───────────────────────────────────────────────────────
+
+#[inline(always)]
+fn pack565(b: u8, g: u8, r: u8) -> [u8; 2] {
+    let v = ((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3);
+    v.to_le_bytes()
+}
+
+#[inline(always)]
+fn unpack565(lo: u8, hi: u8) -> [u8; 4] {
+    let v = u16::from_le_bytes([lo, hi]);
+    let r = (v >> 11) as u8 & 0x1F;
+    let g = (v >> 5) as u8 & 0x3F;
+    let b = v as u8 & 0x1F;
+    // Replicate the high bits into the low ones so 0x1F maps to 0xFF.
+    [(b << 3) | (b >> 2), (g << 2) | (g >> 4), (r << 3) | (r >> 2), 0xFF]
+}
+
+/// BGRA8 → RGB565. `dst` must hold exactly half as many bytes as `src`.
+pub fn bgra_to_rgb565(src: &[u8], dst: &mut [u8]) {
+    debug_assert_eq!(src.len() / 2, dst.len());
+    let mut s = src.chunks_exact(16);
+    let mut d = dst.chunks_exact_mut(8);
+    for (s, d) in (&mut s).zip(&mut d) {
+        for i in 0..4 {
+            let p = pack565(s[i * 4], s[i * 4 + 1], s[i * 4 + 2]);
+            d[i * 2] = p[0];
+            d[i * 2 + 1] = p[1];
+        }
+    }
+    for (s, d) in s
+        .remainder()
+        .chunks_exact(4)
+        .zip(d.into_remainder().chunks_exact_mut(2))
+    {
+        d.copy_from_slice(&pack565(s[0], s[1], s[2]));
+    }
+}
+
+/// RGB565 → BGRA8. `dst` must hold exactly twice as many bytes as `src`.
+pub fn rgb565_to_bgra(src: &[u8], dst: &mut [u8]) {
+    debug_assert_eq!(src.len() * 2, dst.len());
+    let mut s = src.chunks_exact(8);
+    let mut d = dst.chunks_exact_mut(16);
+    for (s, d) in (&mut s).zip(&mut d) {
+        for i in 0..4 {
+            d[i * 4..i * 4 + 4].copy_from_slice(&unpack565(s[i * 2], s[i * 2 + 1]));
+        }
+    }
+    for (s, d) in s
+        .remainder()
+        .chunks_exact(2)
+        .zip(d.into_remainder().chunks_exact_mut(4))
+    {
+        d.copy_from_slice(&unpack565(s[0], s[1]));
+    }
+}
+
+// ── Grayscale ────────────────────────────────────────────────────
+
+/// BT.601 luma in 8.8 fixed point.
+#[inline(always)]
+fn luma(b: u8, g: u8, r: u8) -> u8 {
+    ((77 * r as u32 + 150 * g as u32 + 29 * b as u32 + 128) >> 8) as u8
+}
+
+/// BGRA8 → Gray8. `dst` must hold a quarter as many bytes as `src`.
+pub fn bgra_to_gray8(src: &[u8], dst: &mut [u8]) {
+    debug_assert_eq!(src.len() / 4, dst.len());
+    let mut s = src.chunks_exact(16);
+    let mut d = dst.chunks_exact_mut(4);
+    for (s, d) in (&mut s).zip(&mut d) {
+        for i in 0..4 {
+            d[i] = luma(s[i * 4], s[i * 4 + 1], s[i * 4 + 2]);
+        }
+    }
+    for (s, d) in s
+        .remainder()
+        .chunks_exact(4)
+        .zip(d.into_remainder().iter_mut())
+    {
+        *d = luma(s[0], s[1], s[2]);
+    }
+}
+
+/// Gray8 → BGRA8. `dst` must hold four times as many bytes as `src`.
+pub fn gray8_to_bgra(src: &[u8], dst: &mut [u8]) {
+    debug_assert_eq!(src.len() * 4, dst.len());
+    let mut s = src.chunks_exact(4);
+    let mut d = dst.chunks_exact_mut(16);
+    for (s, d) in (&mut s).zip(&mut d) {
+        for i in 0..4 {
+            d[i * 4..i * 4 + 4].copy_from_slice(&[s[i], s[i], s[i], 0xFF]);
+        }
+    }
+    for (&y, d) in s
+        .remainder()
+        .iter()
+        .zip(d.into_remainder().chunks_exact_mut(4))
+    {
+        d.copy_from_slice(&[y, y, y, 0xFF]);
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A spread of colours plus the extremes, with a pixel count that is
+    /// not a multiple of four so the scalar tail runs too.
+    fn sample_pixels() -> Vec<u8> {
+        let mut out = Vec::new();
+        for r in (0..=255).step_by(17) {
+            for g in (0..=255).step_by(15) {
+                for b in (0..=255).step_by(51) {
+                    out.extend_from_slice(&[b as u8, g as u8, r as u8, 0xFF]);
+                }
+            }
+        }
+        out.extend_from_slice(&[255, 255, 255, 255, 0, 0, 0, 255, 1, 2, 3, 255]);
+        assert_ne!((out.len() / 4) % 4, 0);
+        out
+    }
+
+    fn max_channel_error(a: &[u8], b: &[u8]) -> [u8; 3] {
+        let mut worst = [0u8; 3];
+        for (pa, pb) in a.chunks_exact(4).zip(b.chunks_exact(4)) {
+            for c in 0..3 {
+                worst[c] = worst[c].max(pa[c].abs_diff(pb[c]));
+            }
+        }
+        worst
+    }
+
+    #[test]
+    fn rgb565_round_trip_error_is_bounded() {
+        let src = sample_pixels();
+        let mut packed = Vec::new();
+        pack_bgra(&src, PixelFormat::Rgb565, &mut packed).unwrap();
+        assert_eq!(packed.len(), src.len() / 2);
+
+        let mut back = Vec::new();
+        unpack_to_bgra(&packed, PixelFormat::Rgb565, &mut back).unwrap();
+        assert_eq!(back.len(), src.len());
+
+        let [b, g, r] = max_channel_error(&src, &back);
+        assert!(b <= 7 && r <= 7, "red/blue error {r}/{b}");
+        assert!(g <= 3, "green error {g}");
+        // Extremes survive exactly and alpha is opaque.
+        assert_eq!(&back[back.len() - 12..back.len() - 4], &[255, 255, 255, 255, 0, 0, 0, 255]);
+        assert!(back.chunks_exact(4).all(|p| p[3] == 0xFF));
+    }
+
+    #[test]
+    fn gray8_round_trip() {
+        let src = sample_pixels();
+        let mut packed = Vec::new();
+        pack_bgra(&src, PixelFormat::Gray8, &mut packed).unwrap();
+        assert_eq!(packed.len(), src.len() / 4);
+
+        let mut back = Vec::new();
+        unpack_to_bgra(&packed, PixelFormat::Gray8, &mut back).unwrap();
+        for (s, d) in src.chunks_exact(4).zip(back.chunks_exact(4)) {
+            assert_eq!(d[0], d[1]);
+            assert_eq!(d[1], d[2]);
+            assert_eq!(d[0], luma(s[0], s[1], s[2]));
+            // A grey input comes back within one step.
+            if s[0] == s[1] && s[1] == s[2] {
+                assert!(d[0].abs_diff(s[0]) <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn pack_appends_and_bgra_is_passthrough() {
+        let src = [1, 2, 3, 4, 5, 6, 7, 8];
+        let mut dst = vec![0xEE];
+        pack_bgra(&src, PixelFormat::Bgra8, &mut dst).unwrap();
+        assert_eq!(dst, [0xEE, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+        pack_bgra(&src, PixelFormat::Rgb565, &mut dst).unwrap();
+        assert_eq!(dst.len(), 9 + 4);
+    }
+
+    #[test]
+    fn partial_pixels_are_rejected() {
+        let mut dst = Vec::new();
+        assert!(pack_bgra(&[0; 6], PixelFormat::Rgb565, &mut dst).is_err());
+        assert!(unpack_to_bgra(&[0; 3], PixelFormat::Rgb565, &mut dst).is_err());
+        assert!(pack_bgra(&[0; 4], PixelFormat::Rgb8, &mut dst).is_err());
+        assert!(dst.is_empty());
+    }
+}
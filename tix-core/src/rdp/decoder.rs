@@ -1,10 +1,22 @@
 //! Frame decoder / decompressor.
 //!
 //! Takes [`EncodedFrame`]s received from the network and reconstructs
-//! pixel data that can be rendered on the master display.
+//! pixel data that can be rendered on the master display. Frames sent
+//! in a reduced-colour format are expanded back to BGRA8 here, so
+//! everything downstream of [`FrameDecoder::decode`] sees BGRA8.
+//!
+//! Frames compressed against a [`FrameDictionary`] name its ID in their
+//! zstd header; the decoder needs the same dictionary for those, set
+//! with [`FrameDecoder::set_dictionary`], and decodes plain frames
+//! either way.
+
+use std::io::Read;
 
 use crate::error::TixError;
+use crate::rdp::convert;
+use crate::rdp::dictionary::FrameDictionary;
 use crate::rdp::encoder::EncodedFrame;
+use crate::rdp::types::PixelFormat;
 
 // ── DecodedFrame ─────────────────────────────────────────────────
 
@@ -21,9 +33,15 @@ pub struct DecodedFrame {
     ///
     /// - **Full frame**: tightly-packed BGRA rows (`width * 4 * height` bytes).
     /// - **Delta frame**: block-encoded payload (see [`AdaptiveEncoder`]).
+    ///
+    /// [`AdaptiveEncoder`]: crate::rdp::encoder::AdaptiveEncoder
     pub data: Vec<u8>,
     /// Number of dirty blocks (0 for full frames).
     pub block_count: u32,
+    /// Format the frame travelled in. `data` has already been expanded
+    /// to BGRA8 unless this is a layout the decoder passes through
+    /// (`Rgba8`, `Rgb8`).
+    pub wire_format: PixelFormat,
 }
 
 // ── DecodedBlock ─────────────────────────────────────────────────
@@ -39,6 +57,56 @@ pub struct DecodedBlock {
     pub data: Vec<u8>,
 }
 
+impl DecodedBlock {
+    /// The screen area the block covers.
+    pub fn rect(&self) -> DirtyRect {
+        DirtyRect { x: self.x, y: self.y, width: self.width, height: self.height }
+    }
+}
+
+// ── DirtyRect ────────────────────────────────────────────────────
+
+/// A screen area that changed between two frames, in frame pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DirtyRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl DirtyRect {
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// The smallest rectangle covering both. An empty rectangle adds
+    /// nothing, wherever it sits.
+    pub fn union(&self, other: &Self) -> Self {
+        if other.is_empty() {
+            return *self;
+        }
+        if self.is_empty() {
+            return *other;
+        }
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Self { x, y, width: right - x, height: bottom - y }
+    }
+
+    /// The smallest rectangle covering all of `rects`; `None` when
+    /// none of them covers anything.
+    pub fn bounding(rects: &[Self]) -> Option<Self> {
+        let bounds = rects.iter().fold(Self::default(), |acc, r| acc.union(r));
+        (!bounds.is_empty()).then_some(bounds)
+    }
+}
+
 // ── FrameDecoder ─────────────────────────────────────────────────
 
 /// Stateless decoder that decompresses zstd-encoded frames.
@@ -48,6 +116,8 @@ pub struct FrameDecoder {
     /// Dimensions of the current frame buffer.
     buf_width: u32,
     buf_height: u32,
+    /// Dictionary for frames that name one, with its ID.
+    dictionary: Option<(u32, zstd::dict::DecoderDictionary<'static>)>,
 }
 
 impl FrameDecoder {
@@ -57,23 +127,127 @@ impl FrameDecoder {
             frame_buffer: Vec::new(),
             buf_width: 0,
             buf_height: 0,
+            dictionary: None,
         }
     }
 
+    /// Create a decoder that also accepts frames compressed against
+    /// `dictionary`.
+    pub fn with_dictionary(dictionary: &FrameDictionary) -> Self {
+        let mut decoder = Self::new();
+        decoder.set_dictionary(Some(dictionary));
+        decoder
+    }
+
+    /// Accept frames compressed against `dictionary` from now on, or
+    /// only plain frames with `None`.
+    pub fn set_dictionary(&mut self, dictionary: Option<&FrameDictionary>) {
+        self.dictionary = dictionary.map(|dictionary| {
+            (dictionary.id(), zstd::dict::DecoderDictionary::copy(dictionary.bytes()))
+        });
+    }
+
+    /// ID of the dictionary the decoder holds, if any.
+    pub fn dictionary_id(&self) -> Option<u32> {
+        self.dictionary.as_ref().map(|(id, _)| *id)
+    }
+
     /// Decompress an encoded frame and return the decoded payload.
     pub fn decode(&mut self, encoded: &EncodedFrame) -> Result<DecodedFrame, TixError> {
-        let decompressed = zstd::decode_all(encoded.data.as_slice())
-            .map_err(|e| TixError::Other(format!("zstd decode failed: {e}")))?;
+        let decompressed = self.decompress(&encoded.data)?;
+
+        let data = match encoded.format {
+            PixelFormat::Rgb565 | PixelFormat::Gray8 if encoded.is_full_frame => {
+                Self::expand_full_frame(&decompressed, encoded)?
+            }
+            PixelFormat::Rgb565 | PixelFormat::Gray8 => {
+                Self::expand_delta_frame(&decompressed, encoded.format)?
+            }
+            _ => decompressed,
+        };
 
         Ok(DecodedFrame {
             width: encoded.width,
             height: encoded.height,
             is_full_frame: encoded.is_full_frame,
-            data: decompressed,
+            data,
             block_count: encoded.block_count,
+            wire_format: encoded.format,
         })
     }
 
+    /// Expand a reduced-colour full frame to BGRA8.
+    /// Undo the zstd compression, with the dictionary if the frame
+    /// names one.
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, TixError> {
+        let failed = |e: std::io::Error| TixError::Other(format!("zstd decode failed: {e}"));
+        let Some(wanted) = zstd::zstd_safe::get_dict_id_from_frame(data) else {
+            return zstd::decode_all(data).map_err(failed);
+        };
+        match &self.dictionary {
+            Some((id, dictionary)) if *id == wanted.get() => {
+                let mut out = Vec::new();
+                zstd::stream::read::Decoder::with_prepared_dictionary(data, dictionary)
+                    .and_then(|mut decoder| decoder.read_to_end(&mut out))
+                    .map_err(failed)?;
+                Ok(out)
+            }
+            _ => Err(TixError::Other(format!(
+                "frame needs zstd dictionary {wanted}, which this decoder does not hold"
+            ))),
+        }
+    }
+
+    fn expand_full_frame(data: &[u8], encoded: &EncodedFrame) -> Result<Vec<u8>, TixError> {
+        let pixels = encoded.width as usize * encoded.height as usize;
+        let expected = pixels * encoded.format.bytes_per_pixel();
+        if data.len() < expected {
+            return Err(TixError::Other(format!(
+                "{} full frame too short: {} < {}",
+                encoded.format,
+                data.len(),
+                expected
+            )));
+        }
+        let mut out = Vec::with_capacity(pixels * 4);
+        convert::unpack_to_bgra(&data[..expected], encoded.format, &mut out)?;
+        Ok(out)
+    }
+
+    /// Rewrite a reduced-colour delta payload with BGRA8 block pixels,
+    /// keeping the block count and headers as they are.
+    fn expand_delta_frame(data: &[u8], format: PixelFormat) -> Result<Vec<u8>, TixError> {
+        let bpp = format.bytes_per_pixel();
+        if data.len() < 4 {
+            return Err(TixError::Other("delta frame too short for block count".into()));
+        }
+
+        let count = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+        let mut out = Vec::with_capacity(data.len() * 4 / bpp);
+        out.extend_from_slice(&data[0..4]);
+        let mut offset = 4;
+
+        for _ in 0..count {
+            if offset + 16 > data.len() {
+                return Err(TixError::Other("delta frame truncated (block header)".into()));
+            }
+            let header = &data[offset..offset + 16];
+            let w = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+            let h = u32::from_le_bytes(header[12..16].try_into().unwrap()) as usize;
+            out.extend_from_slice(header);
+            offset += 16;
+
+            let block_bytes = w * h * bpp;
+            if offset + block_bytes > data.len() {
+                return Err(TixError::Other("delta frame truncated (block data)".into()));
+            }
+            convert::unpack_to_bgra(&data[offset..offset + block_bytes], format, &mut out)?;
+            offset += block_bytes;
+        }
+
+        Ok(out)
+    }
+
     /// Apply a decoded frame to the internal frame buffer and return
     /// a reference to the complete, up-to-date screen image.
     ///
@@ -161,6 +335,37 @@ impl FrameDecoder {
         Ok(())
     }
 
+    /// The areas a delta payload changes: what [`extract_blocks`]
+    /// would return, without copying the pixels out.
+    ///
+    /// [`extract_blocks`]: Self::extract_blocks
+    pub fn block_rects(data: &[u8], bpp: usize) -> Result<Vec<DirtyRect>, TixError> {
+        if data.len() < 4 {
+            return Err(TixError::Other("delta too short".into()));
+        }
+
+        let count = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+        let mut offset = 4;
+        let mut rects = Vec::with_capacity(count.min(data.len() / 16));
+
+        for _ in 0..count {
+            if offset + 16 > data.len() {
+                return Err(TixError::Other("truncated block header".into()));
+            }
+            let field = |i: usize| {
+                u32::from_le_bytes(data[offset + i * 4..offset + i * 4 + 4].try_into().unwrap())
+            };
+            let rect = DirtyRect { x: field(0), y: field(1), width: field(2), height: field(3) };
+            offset += 16 + rect.area() as usize * bpp;
+            if offset > data.len() {
+                return Err(TixError::Other("truncated block data".into()));
+            }
+            rects.push(rect);
+        }
+
+        Ok(rects)
+    }
+
     /// Parse a delta payload into individual [`DecodedBlock`]s.
     ///
     /// Useful when the renderer wants to blit blocks individually
@@ -217,6 +422,7 @@ mod tests {
     use super::*;
     use crate::rdp::delta::{Block, DeltaFrame};
     use crate::rdp::encoder::AdaptiveEncoder;
+    use crate::rdp::merge::RegionMerger;
     use crate::rdp::types::{PixelFormat, RawScreenFrame};
     use std::time::Instant;
 
@@ -289,6 +495,171 @@ mod tests {
         }
     }
 
+    /// A frame whose pixels vary, so conversion errors would show.
+    fn gradient_frame(w: u32, h: u32) -> RawScreenFrame {
+        let mut frame = test_frame(w, h, 0);
+        for (i, px) in frame.data.chunks_exact_mut(4).enumerate() {
+            px.copy_from_slice(&[(i * 3) as u8, (i * 5) as u8, (i * 7) as u8, 0xFF]);
+        }
+        frame
+    }
+
+    fn small_delta(frame_number: u64, x: u32) -> DeltaFrame {
+        DeltaFrame {
+            frame_number,
+            timestamp: Instant::now(),
+            width: 64,
+            height: 64,
+            changed_blocks: vec![Block { x, y: 0, width: 16, height: 16 }],
+            full_frame: false,
+        }
+    }
+
+    #[test]
+    fn dictionary_frames_round_trip() {
+        let dict = FrameDictionary::builtin();
+        let source = gradient_frame(64, 64);
+        let delta = small_delta(1, 16);
+
+        let plain = AdaptiveEncoder::new(100_000_000).encode(&delta, &source).unwrap();
+        let mut enc = AdaptiveEncoder::new(100_000_000).with_dictionary(dict.clone());
+        let packed = enc.encode(&delta, &source).unwrap();
+        assert_ne!(packed.data, plain.data);
+
+        let expected = FrameDecoder::new().decode(&plain).unwrap().data;
+        let mut dec = FrameDecoder::with_dictionary(&dict);
+        assert_eq!(dec.dictionary_id(), Some(dict.id()));
+        assert_eq!(dec.decode(&packed).unwrap().data, expected);
+        // Plain frames still decode with a dictionary loaded.
+        assert_eq!(dec.decode(&plain).unwrap().data, expected);
+
+        let err = FrameDecoder::new().decode(&packed).unwrap_err();
+        assert!(err.to_string().contains("dictionary"), "{err}");
+    }
+
+    #[test]
+    fn dictionary_can_be_enabled_mid_stream() {
+        let dict = FrameDictionary::builtin();
+        let source = gradient_frame(64, 64);
+        let mut enc = AdaptiveEncoder::new(100_000_000);
+        let mut dec = FrameDecoder::new();
+        let mut reference = FrameDecoder::new();
+
+        for n in 0..8u64 {
+            if n == 4 {
+                // The viewer loads the dictionary first, then the
+                // encoder switches; frames in between are plain.
+                dec.set_dictionary(Some(&dict));
+            }
+            if n == 5 {
+                enc.set_dictionary(Some(dict.clone()));
+            }
+            if n == 6 {
+                // A level change keeps the dictionary.
+                enc.adjust_quality(u64::MAX);
+                assert_eq!(enc.compression_level(), 2);
+            }
+            let delta = small_delta(n, (n as u32 % 4) * 16);
+            let encoded = enc.encode(&delta, &source).unwrap();
+            let dict_id = zstd::zstd_safe::get_dict_id_from_frame(&encoded.data);
+            assert_eq!(dict_id.map(|id| id.get()), (n >= 5).then_some(dict.id()), "frame {n}");
+
+            let expected = AdaptiveEncoder::new(100_000_000).encode(&delta, &source).unwrap();
+
+            let decoded = dec.decode(&encoded).unwrap();
+            assert_eq!(decoded.data, reference.decode(&expected).unwrap().data);
+            dec.apply(&decoded, 4).unwrap();
+        }
+        assert_eq!(enc.dictionary(), Some(&dict));
+    }
+
+    #[test]
+    fn rgb565_frames_come_back_as_bgra() {
+        let source = gradient_frame(64, 64);
+        let mut enc = AdaptiveEncoder::new(100_000_000).with_format(PixelFormat::Rgb565);
+        let mut dec = FrameDecoder::new();
+
+        let full = DeltaFrame {
+            frame_number: 0,
+            timestamp: Instant::now(),
+            width: 64,
+            height: 64,
+            changed_blocks: vec![Block { x: 0, y: 0, width: 64, height: 64 }],
+            full_frame: true,
+        };
+        let decoded = dec.decode(&enc.encode(&full, &source).unwrap()).unwrap();
+        assert_eq!(decoded.wire_format, PixelFormat::Rgb565);
+        assert_eq!(decoded.data.len(), 64 * 64 * 4);
+        let buf = dec.apply(&decoded, 4).unwrap();
+        for (a, b) in source.data.chunks_exact(4).zip(buf.chunks_exact(4)) {
+            assert!(a[0].abs_diff(b[0]) <= 7 && a[2].abs_diff(b[2]) <= 7);
+            assert!(a[1].abs_diff(b[1]) <= 3);
+            assert_eq!(b[3], 0xFF);
+        }
+
+        // A delta block expands to width * height * 4 bytes after its header.
+        let delta = DeltaFrame {
+            frame_number: 1,
+            timestamp: Instant::now(),
+            width: 64,
+            height: 64,
+            changed_blocks: vec![Block { x: 16, y: 8, width: 10, height: 3 }],
+            full_frame: false,
+        };
+        let decoded = dec.decode(&enc.encode(&delta, &source).unwrap()).unwrap();
+        assert_eq!(decoded.data.len(), 4 + 16 + 10 * 3 * 4);
+        let blocks = FrameDecoder::extract_blocks(&decoded.data, 4).unwrap();
+        assert_eq!(blocks[0].data.len(), 10 * 3 * 4);
+        dec.apply(&decoded, 4).unwrap();
+    }
+
+    #[test]
+    fn gray_delta_is_expanded() {
+        let source = test_frame(32, 32, 0x80);
+        let mut enc = AdaptiveEncoder::new(100_000_000).with_format(PixelFormat::Gray8);
+        let delta = DeltaFrame {
+            frame_number: 0,
+            timestamp: Instant::now(),
+            width: 32,
+            height: 32,
+            changed_blocks: vec![Block { x: 0, y: 0, width: 8, height: 8 }],
+            full_frame: false,
+        };
+        let mut dec = FrameDecoder::new();
+        let decoded = dec.decode(&enc.encode(&delta, &source).unwrap()).unwrap();
+        let buf = dec.apply(&decoded, 4).unwrap();
+        assert_eq!(&buf[0..4], &[0x80, 0x80, 0x80, 0xFF]);
+    }
+
+    #[test]
+    fn truncated_reduced_frames_are_rejected() {
+        let encoded = |data: Vec<u8>, full: bool| EncodedFrame {
+            frame_number: 0,
+            timestamp: Instant::now(),
+            width: 4,
+            height: 4,
+            data: zstd::encode_all(data.as_slice(), 1).unwrap(),
+            is_full_frame: full,
+            block_count: 1,
+            format: PixelFormat::Rgb565,
+            capture_clock: None,
+        };
+        let mut dec = FrameDecoder::new();
+        // 4×4 RGB565 needs 32 bytes.
+        assert!(dec.decode(&encoded(vec![0; 31], true)).is_err());
+        assert!(dec.decode(&encoded(vec![0; 32], true)).is_ok());
+
+        // One 2×2 block needs 8 pixel bytes after its header.
+        let mut delta = 1u32.to_le_bytes().to_vec();
+        for v in [0u32, 0, 2, 2] {
+            delta.extend_from_slice(&v.to_le_bytes());
+        }
+        delta.extend_from_slice(&[0; 7]);
+        assert!(dec.decode(&encoded(delta.clone(), false)).is_err());
+        delta.push(0);
+        assert!(dec.decode(&encoded(delta, false)).is_ok());
+    }
+
     #[test]
     fn extract_blocks_works() {
         let source = test_frame(128, 128, 0xFF);
@@ -315,4 +686,66 @@ mod tests {
         assert_eq!(blocks[0].width, 16);
         assert_eq!(blocks[1].x, 64);
     }
+
+    #[test]
+    fn extract_blocks_returns_merged_regions() {
+        let source = gradient_frame(128, 128);
+        // Three touching 16px tiles in a row and one on its own.
+        let mut delta = DeltaFrame {
+            frame_number: 1,
+            timestamp: Instant::now(),
+            width: 128,
+            height: 128,
+            changed_blocks: vec![
+                Block { x: 0, y: 0, width: 16, height: 16 },
+                Block { x: 16, y: 0, width: 16, height: 16 },
+                Block { x: 32, y: 0, width: 16, height: 16 },
+                Block { x: 96, y: 96, width: 16, height: 16 },
+            ],
+            full_frame: false,
+        };
+        RegionMerger::new(16, 30).merge(&mut delta);
+        assert_eq!(delta.changed_blocks.len(), 2);
+
+        let mut enc = AdaptiveEncoder::new(100_000_000);
+        let encoded = enc.encode(&delta, &source).unwrap();
+        assert_eq!(encoded.block_count, 2);
+
+        let decoded = FrameDecoder::new().decode(&encoded).unwrap();
+        let blocks = FrameDecoder::extract_blocks(&decoded.data, 4).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!((blocks[0].x, blocks[0].y, blocks[0].width, blocks[0].height), (0, 0, 48, 16));
+        assert_eq!((blocks[1].x, blocks[1].y, blocks[1].width, blocks[1].height), (96, 96, 16, 16));
+
+        let rects = FrameDecoder::block_rects(&decoded.data, 4).unwrap();
+        assert_eq!(rects, blocks.iter().map(DecodedBlock::rect).collect::<Vec<_>>());
+        assert!(FrameDecoder::block_rects(&decoded.data[..decoded.data.len() - 1], 4).is_err());
+
+        let stride = source.stride as usize;
+        for block in &blocks {
+            let row_len = block.width as usize * 4;
+            for (row, got) in block.data.chunks_exact(row_len).enumerate() {
+                let off = (block.y as usize + row) * stride + block.x as usize * 4;
+                assert_eq!(got, &source.data[off..off + row_len]);
+            }
+        }
+    }
+
+    #[test]
+    fn dirty_rects_union_to_their_bounds() {
+        let a = DirtyRect { x: 10, y: 20, width: 16, height: 16 };
+        let b = DirtyRect { x: 100, y: 4, width: 8, height: 8 };
+        assert_eq!(a.union(&b), DirtyRect { x: 10, y: 4, width: 98, height: 32 });
+        assert_eq!(a.union(&b), b.union(&a));
+        assert_eq!(a.union(&a), a);
+
+        // Empty rectangles are ignored, wherever they claim to be.
+        let empty = DirtyRect { x: 500, y: 500, width: 0, height: 7 };
+        assert_eq!(a.union(&empty), a);
+        assert_eq!(empty.union(&a), a);
+        assert_eq!(DirtyRect::bounding(&[empty, a, b]), Some(a.union(&b)));
+        assert_eq!(DirtyRect::bounding(&[empty]), None);
+        assert_eq!(DirtyRect::bounding(&[]), None);
+        assert_eq!(a.union(&b).area(), 98 * 32);
+    }
 }
@@ -1,14 +1,29 @@
 //! Block-level delta detection between consecutive frames.
 //!
 //! Divides the screen into `block_size × block_size` tiles and compares
-//! each tile byte-for-byte against the previous frame. Only tiles that
-//! differ are included in the [`DeltaFrame`] output, dramatically
-//! reducing bandwidth when the screen is mostly static.
+//! each tile against the previous frame. Only tiles that differ are
+//! included in the [`DeltaFrame`] output, dramatically reducing
+//! bandwidth when the screen is mostly static.
+//!
+//! Frames are compared in their strided capture layout; row padding is
+//! never read. By default ([`DeltaStrategy::Hashed`]) every tile is
+//! hashed with xxHash64 on the rayon pool, one row of tiles per task, and
+//! compared against the hashes kept from the last frame: a 4K frame is
+//! read once, by every core, and no copy of it is kept. The
+//! single-threaded byte comparison against a reference copy
+//! ([`DeltaStrategy::Exact`]) remains as the reference implementation.
+//!
+//! A capturer that diffs frames on the GPU (see [`gpu`](crate::rdp::gpu))
+//! hands its changed tiles over with [`DeltaDetector::adopt`] instead.
 
 use std::cmp;
 use std::time::Instant;
 
-use crate::rdp::types::RawScreenFrame;
+use rayon::prelude::*;
+use xxhash_rust::xxh64::xxh64;
+
+use crate::rdp::gpu::PreparedTiles;
+use crate::rdp::types::{PixelFormat, RawScreenFrame};
 
 // ── Block ────────────────────────────────────────────────────────
 
@@ -66,6 +81,47 @@ impl DeltaFrame {
     }
 }
 
+// ── DeltaStrategy ────────────────────────────────────────────────
+
+/// How [`DeltaDetector`] tells that a tile changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeltaStrategy {
+    /// Compare a 64-bit xxHash of each tile with the tile's hash in
+    /// the last frame, hashing rows of tiles in parallel. Two different
+    /// tiles hashing alike is possible but far less likely than a bit
+    /// flipping on the wire.
+    #[default]
+    Hashed,
+    /// Compare each tile byte for byte with a copy of the last frame,
+    /// on the calling thread.
+    Exact,
+}
+
+/// Tile hashes of the last frame seen by a hashing detector.
+struct TileHashes {
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    /// Row-major, one per tile.
+    hashes: Vec<u64>,
+}
+
+// ── Block sizes ──────────────────────────────────────────────────
+
+/// Tile sizes a screen stream may be configured with, smallest first.
+pub const BLOCK_SIZES: [usize; 4] = [8, 16, 32, 64];
+
+/// The largest supported tile size not above `requested`, or the
+/// smallest one when `requested` is below all of them.
+pub fn supported_block_size(requested: usize) -> usize {
+    BLOCK_SIZES
+        .iter()
+        .rev()
+        .copied()
+        .find(|&size| size <= requested)
+        .unwrap_or(BLOCK_SIZES[0])
+}
+
 // ── DeltaDetector ────────────────────────────────────────────────
 
 /// Stateful detector that remembers the previous frame and emits
@@ -75,10 +131,16 @@ impl DeltaFrame {
 ///
 /// A block size of **64** offers a good trade-off: large enough to
 /// amortise the per-block overhead, small enough to skip unchanged
-/// regions on a typical desktop.
+/// regions on a typical desktop. Smaller tiles find changes more
+/// tightly; pair them with a [`RegionMerger`](crate::rdp::merge::RegionMerger)
+/// so neighbouring dirty tiles do not each pay for a block header.
 pub struct DeltaDetector {
+    /// Last frame, kept by [`DeltaStrategy::Exact`].
     previous_frame: Option<RawScreenFrame>,
+    /// Tiles of the last frame, kept by [`DeltaStrategy::Hashed`].
+    previous_hashes: Option<TileHashes>,
     block_size: usize,
+    strategy: DeltaStrategy,
 }
 
 impl DeltaDetector {
@@ -87,75 +149,219 @@ impl DeltaDetector {
         assert!(block_size > 0, "block_size must be > 0");
         Self {
             previous_frame: None,
+            previous_hashes: None,
             block_size,
+            strategy: DeltaStrategy::default(),
         }
     }
 
+    /// Use `strategy` to find changed tiles. The next frame is a full
+    /// frame.
+    pub fn with_strategy(mut self, strategy: DeltaStrategy) -> Self {
+        self.strategy = strategy;
+        self.reset();
+        self
+    }
+
     /// Reset the detector, forcing the next frame to be a full frame.
     pub fn reset(&mut self) {
         self.previous_frame = None;
+        self.previous_hashes = None;
     }
 
     /// Compare `current` against the stored previous frame.
     ///
     /// The first call (or the call after [`reset`](Self::reset))
-    /// always produces a full-frame delta.
+    /// always produces a full-frame delta, as does a change of size or
+    /// pixel format. A change of stride alone does not.
     pub fn detect(&mut self, current: &RawScreenFrame) -> DeltaFrame {
-        let delta = match &self.previous_frame {
-            Some(prev) if prev.width == current.width && prev.height == current.height => {
-                self.detect_blocks(current, prev)
-            }
-            _ => {
-                // First frame or resolution change → full frame.
-                DeltaFrame {
-                    frame_number: 0,
-                    timestamp: current.timestamp,
-                    width: current.width,
-                    height: current.height,
-                    changed_blocks: vec![Block {
-                        x: 0,
-                        y: 0,
-                        width: current.width,
-                        height: current.height,
-                    }],
-                    full_frame: true,
-                }
-            }
+        let changed = match self.strategy {
+            DeltaStrategy::Hashed => self.detect_hashed(current),
+            DeltaStrategy::Exact => self.detect_exact(current),
         };
+        match changed {
+            Some(changed) => self.build_delta(current, changed),
+            // First frame or resolution change → full frame.
+            None => Self::full_frame(current),
+        }
+    }
 
-        self.previous_frame = Some(current.clone());
-        delta
+    /// Take the changed tiles of `current` from whoever already found
+    /// them, rather than comparing frames here. The detector's own
+    /// reference is dropped: it has not seen these frames, so going
+    /// back to [`detect`](Self::detect) starts with a full frame.
+    pub fn adopt(&mut self, current: &RawScreenFrame, tiles: PreparedTiles) -> DeltaFrame {
+        self.reset();
+        match tiles {
+            PreparedTiles::Full => Self::full_frame(current),
+            PreparedTiles::Changed(changed) => self.build_delta(current, changed),
+        }
     }
 
     // ── Internal ─────────────────────────────────────────────────
 
-    fn detect_blocks(&self, current: &RawScreenFrame, previous: &RawScreenFrame) -> DeltaFrame {
+    fn full_frame(current: &RawScreenFrame) -> DeltaFrame {
+        DeltaFrame {
+            frame_number: 0,
+            timestamp: current.timestamp,
+            width: current.width,
+            height: current.height,
+            changed_blocks: vec![Block {
+                x: 0,
+                y: 0,
+                width: current.width,
+                height: current.height,
+            }],
+            full_frame: true,
+        }
+    }
+
+    /// Changed tiles by hash, or `None` when there is nothing of the
+    /// same size and format to compare with.
+    fn detect_hashed(&mut self, current: &RawScreenFrame) -> Option<Vec<Block>> {
+        let bs = self.block_size;
+        let blocks_x = (current.width as usize).div_ceil(bs);
+        let hashes = Self::tile_hashes(bs, current);
+        let changed = self
+            .previous_hashes
+            .as_ref()
+            .filter(|prev| {
+                prev.width == current.width
+                    && prev.height == current.height
+                    && prev.format == current.format
+            })
+            .map(|prev| {
+                (0..hashes.len())
+                    .filter(|&i| hashes[i] != prev.hashes[i])
+                    .map(|i| Self::tile(bs, current, i % blocks_x, i / blocks_x))
+                    .collect()
+            });
+        self.previous_hashes = Some(TileHashes {
+            width: current.width,
+            height: current.height,
+            format: current.format,
+            hashes,
+        });
+        changed
+    }
+
+    /// Hash of every tile of `frame`, row-major. Each row of tiles is
+    /// one rayon task.
+    fn tile_hashes(bs: usize, frame: &RawScreenFrame) -> Vec<u64> {
+        let blocks_x = (frame.width as usize).div_ceil(bs);
+        let blocks_y = (frame.height as usize).div_ceil(bs);
+        let mut hashes = vec![0; blocks_x * blocks_y];
+        if blocks_x == 0 {
+            return hashes;
+        }
+        hashes
+            .par_chunks_mut(blocks_x)
+            .enumerate()
+            .for_each(|(by, row)| {
+                for (bx, hash) in row.iter_mut().enumerate() {
+                    // Each row's hash seeds the next row's.
+                    let block = Self::tile(bs, frame, bx, by);
+                    *hash = frame
+                        .region_rows(block.x, block.y, block.width, block.height)
+                        .fold(0, |seed, line| xxh64(line, seed));
+                }
+            });
+        hashes
+    }
+
+    /// Changed tiles by byte comparison, or `None` when there is nothing
+    /// of the same size and format to compare with.
+    fn detect_exact(&mut self, current: &RawScreenFrame) -> Option<Vec<Block>> {
+        let reference = self.previous_frame.as_mut().filter(|prev| {
+            prev.width == current.width
+                && prev.height == current.height
+                && prev.format == current.format
+        });
+        let Some(previous) = reference else {
+            self.store_reference(current);
+            return None;
+        };
+
+        let changed = Self::changed_blocks(self.block_size, current, previous);
+        for block in &changed {
+            Self::copy_block(previous, current, block);
+        }
+        Some(changed)
+    }
+
+    /// Tile `(bx, by)` of `frame`, clipped at its right and bottom
+    /// edges.
+    pub(crate) fn tile(bs: usize, frame: &RawScreenFrame, bx: usize, by: usize) -> Block {
+        let start_x = bx * bs;
+        let start_y = by * bs;
+        let end_x = cmp::min(start_x + bs, frame.width as usize);
+        let end_y = cmp::min(start_y + bs, frame.height as usize);
+        Block {
+            x: start_x as u32,
+            y: start_y as u32,
+            width: (end_x - start_x) as u32,
+            height: (end_y - start_y) as u32,
+        }
+    }
+
+    /// Replace the reference with a copy of `current`, reusing the old
+    /// buffer when it is big enough.
+    fn store_reference(&mut self, current: &RawScreenFrame) {
+        match &mut self.previous_frame {
+            Some(prev) => {
+                prev.width = current.width;
+                prev.height = current.height;
+                prev.stride = current.stride;
+                prev.format = current.format;
+                prev.timestamp = current.timestamp;
+                prev.data.clone_from(&current.data);
+            }
+            None => self.previous_frame = Some(current.clone()),
+        }
+    }
+
+    /// Patch one changed tile of `current` into the reference. Each
+    /// frame is addressed with its own stride.
+    fn copy_block(reference: &mut RawScreenFrame, current: &RawScreenFrame, block: &Block) {
+        let bpp = current.format.bytes_per_pixel();
+        let left = block.x as usize * bpp;
+        let stride = reference.stride as usize;
+        let rows = current.region_rows(block.x, block.y, block.width, block.height);
+        for (y, row) in (block.y as usize..).zip(rows) {
+            let start = y * stride + left;
+            reference.data[start..start + row.len()].copy_from_slice(row);
+        }
+    }
+
+    /// Tiles that differ between the two frames, row-major.
+    pub(crate) fn changed_blocks(
+        bs: usize,
+        current: &RawScreenFrame,
+        previous: &RawScreenFrame,
+    ) -> Vec<Block> {
         let w = current.width as usize;
         let h = current.height as usize;
-        let bs = self.block_size;
 
-        let blocks_x = (w + bs - 1) / bs;
-        let blocks_y = (h + bs - 1) / bs;
+        let blocks_x = w.div_ceil(bs);
+        let blocks_y = h.div_ceil(bs);
 
         let mut changed = Vec::new();
 
         for by in 0..blocks_y {
             for bx in 0..blocks_x {
-                let start_x = bx * bs;
-                let start_y = by * bs;
-                let end_x = cmp::min(start_x + bs, w);
-                let end_y = cmp::min(start_y + bs, h);
-
-                if Self::block_differs(current, previous, start_x, start_y, end_x, end_y) {
-                    changed.push(Block {
-                        x: start_x as u32,
-                        y: start_y as u32,
-                        width: (end_x - start_x) as u32,
-                        height: (end_y - start_y) as u32,
-                    });
+                let block = Self::tile(bs, current, bx, by);
+                if Self::block_differs(current, previous, &block) {
+                    changed.push(block);
                 }
             }
         }
+        changed
+    }
+
+    fn build_delta(&self, current: &RawScreenFrame, changed: Vec<Block>) -> DeltaFrame {
+        let bs = self.block_size;
+        let blocks_x = (current.width as usize).div_ceil(bs);
+        let blocks_y = (current.height as usize).div_ceil(bs);
 
         // If more than 80 % of blocks changed it's cheaper to send a full frame.
         let total_blocks = blocks_x * blocks_y;
@@ -182,30 +388,12 @@ impl DeltaDetector {
     }
 
     /// Row-by-row byte comparison for a rectangular tile.
-    fn block_differs(
-        current: &RawScreenFrame,
-        previous: &RawScreenFrame,
-        start_x: usize,
-        start_y: usize,
-        end_x: usize,
-        end_y: usize,
-    ) -> bool {
-        let bpp = current.format.bytes_per_pixel();
-        let stride = current.stride as usize;
-
-        for y in start_y..end_y {
-            let row_offset = y * stride;
-            let left = start_x * bpp;
-            let right = end_x * bpp;
-
-            let cur_slice = &current.data[row_offset + left..row_offset + right];
-            let prev_slice = &previous.data[row_offset + left..row_offset + right];
-
-            if cur_slice != prev_slice {
-                return true;
-            }
-        }
-        false
+    fn block_differs(current: &RawScreenFrame, previous: &RawScreenFrame, block: &Block) -> bool {
+        let (x, y, w, h) = (block.x, block.y, block.width, block.height);
+        current
+            .region_rows(x, y, w, h)
+            .zip(previous.region_rows(x, y, w, h))
+            .any(|(cur, prev)| cur != prev)
     }
 }
 
@@ -228,6 +416,23 @@ mod tests {
         }
     }
 
+    /// A frame with `pad` bytes of `padding` after every row, as DXGI
+    /// hands out when the row pitch is aligned.
+    fn make_strided(w: u32, h: u32, pad: u32, fill: u8, padding: u8) -> RawScreenFrame {
+        let row = (w * 4) as usize;
+        let stride = w * 4 + pad;
+        let mut data = vec![padding; (stride * h) as usize];
+        for y in 0..h as usize {
+            let start = y * stride as usize;
+            data[start..start + row].fill(fill);
+        }
+        RawScreenFrame {
+            stride,
+            data,
+            ..make_frame(w, h, fill)
+        }
+    }
+
     #[test]
     fn first_frame_is_full() {
         let mut det = DeltaDetector::new(64);
@@ -297,6 +502,150 @@ mod tests {
         assert!((ratio - 0.25).abs() < 1e-6);
     }
 
+    #[test]
+    fn row_padding_is_ignored() {
+        let mut det = DeltaDetector::new(64);
+        let _ = det.detect(&make_strided(100, 80, 48, 0x11, 0x00));
+        let delta = det.detect(&make_strided(100, 80, 48, 0x11, 0xFF));
+        assert!(!delta.full_frame);
+        assert!(delta.changed_blocks.is_empty());
+    }
+
+    #[test]
+    fn stride_change_compares_pixels() {
+        let mut det = DeltaDetector::new(64);
+        let _ = det.detect(&make_strided(128, 128, 64, 0, 0xEE));
+
+        // Same picture, tightly packed, one pixel changed at (70, 5).
+        let mut tight = make_frame(128, 128, 0);
+        tight.data[(5 * 128 + 70) * 4] = 0xFF;
+        let delta = det.detect(&tight);
+        assert!(!delta.full_frame);
+        assert_eq!(
+            delta.changed_blocks,
+            [Block {
+                x: 64,
+                y: 0,
+                width: 64,
+                height: 64
+            }]
+        );
+    }
+
+    #[test]
+    fn reference_tracks_changed_blocks() {
+        let mut det = DeltaDetector::new(32);
+        let before = make_strided(96, 64, 16, 0, 0);
+        let mut after = before.clone();
+        let offset = 40 * after.stride as usize + 50 * 4;
+        after.data[offset] = 0x80;
+
+        let _ = det.detect(&before);
+        assert_eq!(det.detect(&after).changed_blocks.len(), 1);
+        // The reference now holds `after`...
+        assert!(det.detect(&after).changed_blocks.is_empty());
+        // ...and going back is a change again.
+        let back = det.detect(&before);
+        assert_eq!((back.changed_blocks[0].x, back.changed_blocks[0].y), (32, 32));
+    }
+
+    /// Pseudo-random bytes, the same for the same `seed`.
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hashed_matches_exact_reference() {
+        // Odd sizes leave clipped tiles on the right and bottom edges.
+        let (w, h, pad) = (333, 217, 12);
+        let mut frame = make_strided(w, h, pad, 0, 0);
+        frame.data = noise(frame.data.len(), 7);
+
+        let mut hashed = DeltaDetector::new(32);
+        let mut exact = DeltaDetector::new(32).with_strategy(DeltaStrategy::Exact);
+        assert!(hashed.detect(&frame).full_frame);
+        assert!(exact.detect(&frame).full_frame);
+
+        let stride = frame.stride as usize;
+        for step in 1..40u64 {
+            // A handful of scattered pixels, a rectangle, and now and
+            // then a bare padding change.
+            for (i, byte) in noise(6, step).into_iter().enumerate() {
+                let x = (byte as usize * 7 + i * 53) % w as usize;
+                let y = (byte as usize * 3 + step as usize * 11) % h as usize;
+                frame.data[y * stride + x * 4 + i % 4] ^= 0x5A;
+            }
+            if step % 3 == 0 {
+                let y0 = (step as usize * 17) % (h as usize - 40);
+                for y in y0..y0 + 40 {
+                    frame.data[y * stride + 100..y * stride + 260].fill(step as u8);
+                }
+            }
+            if step % 5 == 0 {
+                frame.data[stride - 1] ^= 0xFF;
+            }
+
+            let a = hashed.detect(&frame);
+            let b = exact.detect(&frame);
+            assert_eq!(a.full_frame, b.full_frame, "step {}", step);
+            assert_eq!(a.changed_blocks, b.changed_blocks, "step {}", step);
+            assert!(!a.changed_blocks.is_empty() || step % 5 == 0);
+        }
+    }
+
+    #[test]
+    fn hashed_detector_sees_format_changes() {
+        let mut det = DeltaDetector::new(64);
+        let frame = make_frame(128, 64, 0);
+        let _ = det.detect(&frame);
+        let gray = RawScreenFrame {
+            format: crate::rdp::types::PixelFormat::Gray8,
+            stride: 128,
+            data: vec![0; 128 * 64],
+            ..frame.clone()
+        };
+        assert!(det.detect(&gray).full_frame);
+        assert!(det.detect(&gray).changed_blocks.is_empty());
+    }
+
+    #[test]
+    fn block_sizes_round_down_to_supported() {
+        assert_eq!(supported_block_size(64), 64);
+        assert_eq!(supported_block_size(48), 32);
+        assert_eq!(supported_block_size(1000), 64);
+        assert_eq!(supported_block_size(0), 8);
+    }
+
+    #[test]
+    fn adopted_tiles_replace_detection() {
+        let mut det = DeltaDetector::new(64);
+        let frame = make_frame(256, 128, 0);
+        det.detect(&frame);
+
+        let block = Block {
+            x: 64,
+            y: 0,
+            width: 64,
+            height: 64,
+        };
+        let delta = det.adopt(&frame, PreparedTiles::Changed(vec![block]));
+        assert!(!delta.full_frame);
+        assert_eq!(delta.changed_blocks, vec![block]);
+        assert!(det.adopt(&frame, PreparedTiles::Full).full_frame);
+
+        // The same frame again, but the detector never saw the adopted
+        // ones: back on its own it starts over.
+        assert!(det.detect(&frame).full_frame);
+    }
+
     #[test]
     fn reset_forces_full_frame() {
         let mut det = DeltaDetector::new(64);
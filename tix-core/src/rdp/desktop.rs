@@ -0,0 +1,443 @@
+//! Secure-desktop detection for the screen stream.
+//!
+//! A UAC consent prompt, the lock screen and Ctrl+Alt+Del all run on a
+//! separate desktop (`Winlogon`) that duplication of the user's
+//! `Default` desktop cannot see. While it is up, DXGI either times out
+//! on every acquire or reports the capture as lost, and input injected
+//! from the service lands on a desktop nobody is looking at. Without
+//! help the viewer just shows the last frame and the session looks hung.
+//!
+//! [`SecureDesktopDetector`] watches for that pattern: after a run of
+//! stalled captures it asks a [`DesktopProbe`] which desktop currently
+//! receives input, and anything other than `Default` is reported as a
+//! [`ScreenStatus::SecureDesktop`]. The service sends the status to the
+//! viewer as a status datagram on the screen socket, repeats it while
+//! the secure desktop stays up, and sends [`ScreenStatus::Live`] once
+//! frames flow again.
+//!
+//! ```text
+//! status: magic "TXST" (4) | bincode(ScreenStatus)
+//! ```
+//!
+//! A service running as `SYSTEM` may instead follow the switch by
+//! attaching to the input desktop before rebuilding the capturer; see
+//! [`ScreenServiceConfig`](crate::rdp::ScreenServiceConfig).
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::TixError;
+
+/// Consecutive stalled captures before the input desktop is queried.
+/// A static screen also times out, so a single stall means nothing.
+pub const STALLS_BEFORE_PROBE: u32 = 5;
+
+/// Minimum time between desktop queries, and how often the status is
+/// repeated while the secure desktop stays up.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Name of the interactive user desktop.
+pub const DEFAULT_DESKTOP: &str = "Default";
+
+const STATUS_MAGIC: &[u8; 4] = b"TXST";
+
+// ── ScreenStatus ─────────────────────────────────────────────────
+
+/// Out-of-band state of the stream, sent between frames.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ScreenStatus {
+    /// Frames are flowing.
+    #[default]
+    Live,
+    /// Input goes to a desktop the capturer cannot see. `desktop` is
+    /// its name, or `None` if the service may not even open it.
+    SecureDesktop { desktop: Option<String> },
+    /// Capture keeps failing even after rebuilding the capturer; the
+    /// service is still retrying. `error` is the last failure.
+    CaptureFailed { error: String },
+    /// Frames are flowing but input is not reaching the remote desktop,
+    /// e.g. because an elevated window has the focus and the slave is
+    /// not elevated. `reason` is for the viewer's banner.
+    InputBlocked { reason: String },
+}
+
+impl ScreenStatus {
+    /// Text for the viewer to show instead of the frozen frame.
+    pub fn message(&self) -> String {
+        match self {
+            Self::Live => "live".into(),
+            Self::SecureDesktop {
+                desktop: Some(name),
+            } => {
+                format!("secure desktop ({name}) active — interaction required at the machine")
+            }
+            Self::SecureDesktop { desktop: None } => {
+                "secure desktop active — interaction required at the machine".into()
+            }
+            Self::CaptureFailed { error } => format!("screen capture failed, retrying ({error})"),
+            Self::InputBlocked { reason } => reason.clone(),
+        }
+    }
+
+    /// Whether frames are flowing, whether or not input gets through.
+    pub fn is_live(&self) -> bool {
+        matches!(self, Self::Live | Self::InputBlocked { .. })
+    }
+
+    /// Why input is not reaching the remote desktop, if it is not.
+    pub fn input_blocked(&self) -> Option<&str> {
+        match self {
+            Self::InputBlocked { reason } => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// Whether this is the secure-desktop state.
+    pub fn is_secure(&self) -> bool {
+        matches!(self, Self::SecureDesktop { .. })
+    }
+}
+
+// ── Datagrams ────────────────────────────────────────────────────
+
+/// Encode `status` as a datagram for the screen socket.
+pub fn status_datagram(status: &ScreenStatus) -> Result<Vec<u8>, TixError> {
+    let body = bincode::serialize(status).map_err(|e| TixError::Encoding(e.to_string()))?;
+    let mut data = Vec::with_capacity(STATUS_MAGIC.len() + body.len());
+    data.extend_from_slice(STATUS_MAGIC);
+    data.extend_from_slice(&body);
+    Ok(data)
+}
+
+/// Whether `data` is a status datagram rather than screen data.
+pub fn is_status(data: &[u8]) -> bool {
+    data.len() > STATUS_MAGIC.len() && data[..4] == *STATUS_MAGIC
+}
+
+/// Decode a status datagram; `None` if `data` is not a valid one.
+pub fn parse_status(data: &[u8]) -> Option<ScreenStatus> {
+    if !is_status(data) {
+        return None;
+    }
+    bincode::deserialize(&data[STATUS_MAGIC.len()..]).ok()
+}
+
+// ── DesktopProbe ─────────────────────────────────────────────────
+
+/// Access to the window station's input desktop.
+///
+/// [`InputDesktop`] is the production implementation; tests substitute
+/// a scripted one.
+pub trait DesktopProbe: Send {
+    /// Name of the desktop currently receiving input, or `None` if this
+    /// process may not open it (an unprivileged service cannot open
+    /// `Winlogon`).
+    fn input_desktop(&mut self) -> Option<String>;
+
+    /// Move the calling thread onto the input desktop, so capture and
+    /// injection from this thread target it.
+    fn attach(&mut self) -> Result<(), TixError>;
+}
+
+/// The real input desktop, via `OpenInputDesktop`. On other platforms
+/// there is only ever the default desktop.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InputDesktop;
+
+#[cfg(all(target_os = "windows", feature = "win-capture"))]
+mod platform {
+    use windows::Win32::Foundation::{GENERIC_ALL, HANDLE};
+    use windows::Win32::System::StationsAndDesktops::*;
+    use windows::Win32::System::Threading::GetCurrentThreadId;
+
+    use super::*;
+
+    /// Name of a desktop or other user object.
+    unsafe fn object_name(handle: HANDLE) -> Option<String> {
+        let mut name = [0u16; 256];
+        let mut needed = 0u32;
+        unsafe {
+            GetUserObjectInformationW(
+                handle,
+                UOI_NAME,
+                Some(name.as_mut_ptr().cast()),
+                (name.len() * 2) as u32,
+                Some(&mut needed),
+            )
+            .ok()?;
+        }
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        Some(String::from_utf16_lossy(&name[..len]))
+    }
+
+    unsafe fn open_input_desktop() -> Option<HDESK> {
+        unsafe {
+            OpenInputDesktop(
+                DF_ALLOWOTHERACCOUNTHOOK,
+                false,
+                DESKTOP_ACCESS_FLAGS(GENERIC_ALL.0),
+            )
+            .ok()
+        }
+    }
+
+    /// Session of this process.
+    pub fn session_id() -> Option<u32> {
+        use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+        use windows::Win32::System::Threading::GetCurrentProcessId;
+
+        let mut session = 0u32;
+        // SAFETY: `session` outlives the call.
+        unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session).ok()? };
+        Some(session)
+    }
+
+    /// Session attached to the physical console, if any.
+    pub fn console_session_id() -> Option<u32> {
+        use windows::Win32::System::RemoteDesktop::WTSGetActiveConsoleSessionId;
+
+        // SAFETY: no arguments, no handles.
+        let session = unsafe { WTSGetActiveConsoleSessionId() };
+        (session != u32::MAX).then_some(session)
+    }
+
+    impl DesktopProbe for InputDesktop {
+        fn input_desktop(&mut self) -> Option<String> {
+            // SAFETY: the handle is closed before returning.
+            unsafe {
+                let desk = open_input_desktop()?;
+                let name = object_name(HANDLE(desk.0));
+                let _ = CloseDesktop(desk);
+                name
+            }
+        }
+
+        fn attach(&mut self) -> Result<(), TixError> {
+            // SAFETY: a desktop successfully set on the thread stays
+            // open for as long as the thread uses it; every other
+            // handle is closed.
+            unsafe {
+                let desk = open_input_desktop().ok_or_else(|| {
+                    TixError::Other("cannot open the input desktop (not SYSTEM?)".into())
+                })?;
+                let target = object_name(HANDLE(desk.0));
+                let current = GetThreadDesktop(GetCurrentThreadId())
+                    .ok()
+                    .and_then(|d| object_name(HANDLE(d.0)));
+                if target.is_some() && target == current {
+                    let _ = CloseDesktop(desk);
+                    return Ok(());
+                }
+                SetThreadDesktop(desk).map_err(|e| {
+                    let _ = CloseDesktop(desk);
+                    TixError::Other(format!("SetThreadDesktop: {e}"))
+                })
+            }
+        }
+    }
+}
+
+#[cfg(all(target_os = "windows", feature = "win-capture"))]
+pub use platform::{console_session_id, session_id};
+
+/// Terminal Services session of this process; `None` off Windows.
+#[cfg(not(all(target_os = "windows", feature = "win-capture")))]
+pub fn session_id() -> Option<u32> {
+    None
+}
+
+/// Session attached to the physical console; `None` off Windows, or
+/// while no session is.
+#[cfg(not(all(target_os = "windows", feature = "win-capture")))]
+pub fn console_session_id() -> Option<u32> {
+    None
+}
+
+#[cfg(not(all(target_os = "windows", feature = "win-capture")))]
+impl DesktopProbe for InputDesktop {
+    fn input_desktop(&mut self) -> Option<String> {
+        Some(DEFAULT_DESKTOP.into())
+    }
+
+    fn attach(&mut self) -> Result<(), TixError> {
+        Ok(())
+    }
+}
+
+// ── SecureDesktopDetector ────────────────────────────────────────
+
+/// Turns capture stalls and desktop queries into status changes.
+///
+/// Feed it every capture outcome: [`on_stall`](Self::on_stall) for a
+/// timeout or lost capture, [`on_frame`](Self::on_frame) for a frame.
+/// Each returns the status to send to the viewer, if any.
+pub struct SecureDesktopDetector {
+    probe: Box<dyn DesktopProbe>,
+    stalls: u32,
+    last_probe: Option<Instant>,
+    secure: Option<ScreenStatus>,
+}
+
+impl SecureDesktopDetector {
+    /// A detector querying `probe`.
+    pub fn new(probe: impl DesktopProbe + 'static) -> Self {
+        Self {
+            probe: Box::new(probe),
+            stalls: 0,
+            last_probe: None,
+            secure: None,
+        }
+    }
+
+    /// Record a capture that produced no frame at `now`.
+    ///
+    /// Returns the secure-desktop status when the switch is first seen
+    /// and again every [`PROBE_INTERVAL`] while it lasts, and
+    /// [`ScreenStatus::Live`] if the input desktop went back to
+    /// `Default` before frames did.
+    pub fn on_stall(&mut self, now: Instant) -> Option<ScreenStatus> {
+        self.stalls = self.stalls.saturating_add(1);
+        if self.stalls < STALLS_BEFORE_PROBE {
+            return None;
+        }
+        if self
+            .last_probe
+            .is_some_and(|last| now.duration_since(last) < PROBE_INTERVAL)
+        {
+            return None;
+        }
+        self.last_probe = Some(now);
+
+        match self.probe.input_desktop() {
+            Some(name) if name.eq_ignore_ascii_case(DEFAULT_DESKTOP) => {
+                self.secure.take().map(|_| ScreenStatus::Live)
+            }
+            desktop => {
+                let status = ScreenStatus::SecureDesktop { desktop };
+                self.secure = Some(status.clone());
+                Some(status)
+            }
+        }
+    }
+
+    /// Record a captured frame. Returns [`ScreenStatus::Live`] if the
+    /// viewer was last told about a secure desktop.
+    pub fn on_frame(&mut self) -> Option<ScreenStatus> {
+        self.stalls = 0;
+        self.last_probe = None;
+        self.secure.take().map(|_| ScreenStatus::Live)
+    }
+
+    /// Whether the secure desktop was up at the last query.
+    pub fn is_secure(&self) -> bool {
+        self.secure.is_some()
+    }
+
+    /// Attach the calling thread to the input desktop.
+    pub fn attach(&mut self) -> Result<(), TixError> {
+        self.probe.attach()
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Reports whatever desktop the test last set.
+    #[derive(Clone)]
+    struct ScriptedDesktop(Arc<Mutex<Option<String>>>);
+
+    impl ScriptedDesktop {
+        fn new(name: &str) -> Self {
+            Self(Arc::new(Mutex::new(Some(name.into()))))
+        }
+
+        fn switch(&self, name: Option<&str>) {
+            *self.0.lock().unwrap() = name.map(Into::into);
+        }
+    }
+
+    impl DesktopProbe for ScriptedDesktop {
+        fn input_desktop(&mut self) -> Option<String> {
+            self.0.lock().unwrap().clone()
+        }
+
+        fn attach(&mut self) -> Result<(), TixError> {
+            Ok(())
+        }
+    }
+
+    fn stall_times(
+        detector: &mut SecureDesktopDetector,
+        start: Instant,
+        n: u32,
+    ) -> Vec<ScreenStatus> {
+        (0..n)
+            .filter_map(|i| detector.on_stall(start + Duration::from_millis(100) * i))
+            .collect()
+    }
+
+    #[test]
+    fn status_datagram_roundtrip() {
+        let status = ScreenStatus::SecureDesktop {
+            desktop: Some("Winlogon".into()),
+        };
+        let data = status_datagram(&status).unwrap();
+        assert!(is_status(&data));
+        assert_eq!(parse_status(&data), Some(status));
+        assert_eq!(
+            parse_status(&status_datagram(&ScreenStatus::Live).unwrap()),
+            Some(ScreenStatus::Live)
+        );
+        assert!(parse_status(b"TXST\xff").is_none());
+        assert!(!is_status(b"TXMP\0\0\0\0"));
+    }
+
+    #[test]
+    fn static_screen_on_default_desktop_stays_quiet() {
+        let mut detector = SecureDesktopDetector::new(ScriptedDesktop::new("Default"));
+        assert!(stall_times(&mut detector, Instant::now(), 50).is_empty());
+        assert!(!detector.is_secure());
+        assert_eq!(detector.on_frame(), None);
+    }
+
+    #[test]
+    fn reports_switch_repeats_and_recovers() {
+        let desktop = ScriptedDesktop::new("Winlogon");
+        let mut detector = SecureDesktopDetector::new(desktop.clone());
+        let start = Instant::now();
+
+        // Probed after the fifth stall, then once a second: 3 s of stalls.
+        let reports = stall_times(&mut detector, start, 30);
+        let secure = ScreenStatus::SecureDesktop {
+            desktop: Some("Winlogon".into()),
+        };
+        assert_eq!(reports, vec![secure.clone(); 3]);
+        assert!(detector.is_secure());
+        assert!(
+            secure
+                .message()
+                .contains("interaction required at the machine")
+        );
+
+        // The prompt is dismissed and frames come back.
+        assert_eq!(detector.on_frame(), Some(ScreenStatus::Live));
+        assert_eq!(detector.on_frame(), None);
+
+        // An unprivileged service cannot open Winlogon at all.
+        desktop.switch(None);
+        let reports = stall_times(&mut detector, start, 5);
+        assert_eq!(reports, [ScreenStatus::SecureDesktop { desktop: None }]);
+
+        // Back on Default while the screen is static: live, no frame needed.
+        desktop.switch(Some("default"));
+        let later = start + PROBE_INTERVAL * 2;
+        assert_eq!(detector.on_stall(later), Some(ScreenStatus::Live));
+        assert!(!detector.is_secure());
+    }
+}
@@ -0,0 +1,223 @@
+//! Zstd dictionaries for the screen stream.
+//!
+//! Most deltas are a few tiles of window chrome, text and flat
+//! background. Compressed on their own, each frame pays again for the
+//! entropy tables and for patterns every other frame also contains; a
+//! dictionary trained on typical deltas carries those instead, which
+//! shrinks small payloads the most.
+//!
+//! A [`FrameDictionary`] is identified twice:
+//!
+//! - by its zstd dictionary ID, which zstd stamps into every frame
+//!   compressed with it, so the [`FrameDecoder`] can tell dictionary
+//!   frames from plain ones without any extra header;
+//! - by the xxh64 [`hash`](FrameDictionary::hash) of its bytes, which
+//!   the viewer quotes at `ScreenStart` so the slave can skip sending a
+//!   dictionary it already has.
+//!
+//! The slave uses the [`builtin`](FrameDictionary::builtin) dictionary
+//! unless configured otherwise. It was trained with
+//! `cargo run -p tix-core --example train_dictionary` from the sample
+//! deltas in `tests/fixtures`; the same example trains one from other
+//! samples. Sample files are a zstd stream of payloads, each preceded
+//! by its length as a little-endian `u32` (see [`encode_samples`]).
+//!
+//! [`FrameDecoder`]: crate::rdp::decoder::FrameDecoder
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::{Arc, LazyLock};
+
+use crate::error::TixError;
+
+/// Size the built-in dictionary was trained to, and the default for
+/// [`FrameDictionary::train`] callers.
+pub const DEFAULT_DICTIONARY_SIZE: usize = 16 * 1024;
+
+/// Bytes of the dictionary compiled into every tix build.
+const BUILTIN: &[u8] = include_bytes!("builtin.dict");
+
+static BUILTIN_DICTIONARY: LazyLock<FrameDictionary> = LazyLock::new(|| {
+    FrameDictionary::new(BUILTIN.to_vec()).expect("the built-in dictionary is a zstd dictionary")
+});
+
+// ── FrameDictionary ──────────────────────────────────────────────
+
+/// A trained zstd dictionary. Cheap to clone.
+#[derive(Clone, PartialEq, Eq)]
+pub struct FrameDictionary {
+    bytes: Arc<[u8]>,
+    id: u32,
+    hash: u64,
+}
+
+impl FrameDictionary {
+    /// Wrap trained dictionary bytes. Raw content without a zstd
+    /// dictionary header is refused: frames compressed with it would
+    /// not carry an ID to recognise them by.
+    pub fn new(bytes: Vec<u8>) -> Result<Self, TixError> {
+        let id = zstd::zstd_safe::get_dict_id_from_dict(&bytes)
+            .ok_or_else(|| TixError::Encoding("not a zstd dictionary".into()))?
+            .get();
+        let hash = xxhash_rust::xxh64::xxh64(&bytes, 0);
+        Ok(Self {
+            bytes: bytes.into(),
+            id,
+            hash,
+        })
+    }
+
+    /// The dictionary shipped with tix.
+    pub fn builtin() -> Self {
+        BUILTIN_DICTIONARY.clone()
+    }
+
+    /// Train a dictionary of at most `max_size` bytes from sample
+    /// payloads (uncompressed, as the encoder produces them).
+    pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> Result<Self, TixError> {
+        let bytes = zstd::dict::from_samples(samples, max_size)
+            .map_err(|e| TixError::Other(format!("dictionary training failed: {e}")))?;
+        Self::new(bytes)
+    }
+
+    /// The ID zstd stamps into frames compressed with this dictionary.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// xxh64 of the dictionary bytes.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Debug for FrameDictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameDictionary")
+            .field("id", &self.id)
+            .field("hash", &format_args!("{:016x}", self.hash))
+            .field("len", &self.bytes.len())
+            .finish()
+    }
+}
+
+// ── Sample files ─────────────────────────────────────────────────
+
+/// Pack sample payloads into a sample file.
+pub fn encode_samples<S: AsRef<[u8]>>(samples: &[S]) -> Result<Vec<u8>, TixError> {
+    let mut encoder = zstd::Encoder::new(Vec::new(), 19).map_err(sample_error)?;
+    for sample in samples {
+        let sample = sample.as_ref();
+        let len = u32::try_from(sample.len())
+            .map_err(|_| TixError::Encoding("sample over 4 GiB".into()))?;
+        encoder.write_all(&len.to_le_bytes()).map_err(sample_error)?;
+        encoder.write_all(sample).map_err(sample_error)?;
+    }
+    encoder.finish().map_err(sample_error)
+}
+
+/// Unpack a sample file written by [`encode_samples`].
+pub fn decode_samples(file: &[u8]) -> Result<Vec<Vec<u8>>, TixError> {
+    let mut data = Vec::new();
+    zstd::Decoder::new(file)
+        .and_then(|mut decoder| decoder.read_to_end(&mut data))
+        .map_err(sample_error)?;
+    let mut samples = Vec::new();
+    let mut rest = data.as_slice();
+    while !rest.is_empty() {
+        let (len, tail) = rest
+            .split_first_chunk::<4>()
+            .ok_or_else(|| TixError::Encoding("sample file truncated (length)".into()))?;
+        let len = u32::from_le_bytes(*len) as usize;
+        if tail.len() < len {
+            return Err(TixError::Encoding("sample file truncated (payload)".into()));
+        }
+        samples.push(tail[..len].to_vec());
+        rest = &tail[len..];
+    }
+    Ok(samples)
+}
+
+fn sample_error(e: std::io::Error) -> TixError {
+    TixError::Encoding(format!("sample file: {e}"))
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Delta payloads recorded from synthetic desktop scenes: the
+    /// built-in dictionary was trained on the first file and never saw
+    /// the second.
+    const TRAINING: &[u8] = include_bytes!("../../tests/fixtures/deltas-train.bin");
+    const HELD_OUT: &[u8] = include_bytes!("../../tests/fixtures/deltas-eval.bin");
+
+    #[test]
+    fn builtin_dictionary_is_valid() {
+        let dict = FrameDictionary::builtin();
+        assert_ne!(dict.id(), 0);
+        assert!(dict.bytes().len() <= DEFAULT_DICTIONARY_SIZE);
+        assert_eq!(dict.hash(), xxhash_rust::xxh64::xxh64(BUILTIN, 0));
+        assert_eq!(FrameDictionary::new(BUILTIN.to_vec()).unwrap(), dict);
+    }
+
+    #[test]
+    fn raw_content_is_not_a_dictionary() {
+        assert!(FrameDictionary::new(vec![0xAB; 4096]).is_err());
+        assert!(FrameDictionary::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn sample_files_round_trip() {
+        let samples = vec![vec![1u8, 2, 3], Vec::new(), vec![9; 1000]];
+        let file = encode_samples(&samples).unwrap();
+        assert_eq!(decode_samples(&file).unwrap(), samples);
+
+        let mut truncated = Vec::new();
+        zstd::Decoder::new(file.as_slice())
+            .unwrap()
+            .read_to_end(&mut truncated)
+            .unwrap();
+        truncated.pop();
+        let file = zstd::encode_all(truncated.as_slice(), 1).unwrap();
+        assert!(decode_samples(&file).is_err());
+    }
+
+    #[test]
+    fn training_reproduces_the_builtin_dictionary() {
+        let samples = decode_samples(TRAINING).unwrap();
+        let trained = FrameDictionary::train(&samples, DEFAULT_DICTIONARY_SIZE).unwrap();
+        assert_eq!(trained, FrameDictionary::builtin());
+    }
+
+    #[test]
+    fn builtin_dictionary_shrinks_small_held_out_deltas() {
+        let dict = FrameDictionary::builtin();
+        let samples = decode_samples(HELD_OUT).unwrap();
+        assert!(samples.len() >= 100);
+
+        // (plain, with dictionary) for deltas of a few tiles, and for all.
+        let mut compressor = zstd::bulk::Compressor::with_dictionary(1, dict.bytes()).unwrap();
+        let (mut small, mut all) = ((0, 0), (0, 0));
+        for sample in &samples {
+            let plain = zstd::bulk::compress(sample, 1).unwrap().len();
+            let with_dict = compressor.compress(sample).unwrap().len();
+            if sample.len() <= 64 * 1024 {
+                small.0 += plain;
+                small.1 += with_dict;
+            }
+            all.0 += plain;
+            all.1 += with_dict;
+        }
+        // See `benches/dictionary.rs` for the figures per level. The
+        // gain is in the small frames; large ones barely change.
+        assert!(small.1 * 100 < small.0 * 92, "small deltas: {small:?}");
+        assert!(all.1 < all.0, "all deltas: {all:?}");
+    }
+}
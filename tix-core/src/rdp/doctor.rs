@@ -0,0 +1,416 @@
+//! Self-test of what the screen stack needs from the machine it runs on.
+//!
+//! A slave that starts cleanly can still be useless: in an RDP session
+//! with no DXGI output, in session 0 without a desktop, behind a port
+//! another program holds, or unable to write its logs. [`run`] goes
+//! through those prerequisites and returns a [`SelfTestReport`] with
+//! one [`CheckResult`] per check, each with a hint for whatever did not
+//! pass. `tix-rdp-slave --doctor` prints it; a `SelfTest` request gets
+//! it back as a payload.
+//!
+//! Every check is a function of its own returning a `CheckResult`, so
+//! adding one is adding a function and a line to [`run`]. The checks
+//! block (the capture waits for a frame), so async callers should run
+//! them on a blocking thread.
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::error::TixError;
+use crate::protocol::selftest::{CheckResult, SelfTestReport};
+use crate::rdp::capture::{DxgiCapturer, FrameSource};
+use crate::rdp::desktop::{self, DEFAULT_DESKTOP, DesktopProbe, InputDesktop};
+use crate::rdp::input::InputInjector;
+
+/// Monitors tried by default; the capture check stops at the first one
+/// DXGI does not have.
+pub const DEFAULT_MAX_MONITORS: u32 = 8;
+
+/// How long the capture check waits for a first frame by default.
+pub const DEFAULT_CAPTURE_TIMEOUT_MS: u32 = 1000;
+
+/// What [`run`] checks.
+#[derive(Debug, Clone)]
+pub struct DoctorOptions {
+    /// Most monitors to capture from.
+    pub max_monitors: u32,
+    /// Wait for a first frame from each monitor this long.
+    pub capture_timeout_ms: u32,
+    /// Send a harmless input event.
+    pub input: bool,
+    /// UDP port the screen stream goes out from; `0` checks that any
+    /// port can be bound.
+    pub udp_port: u16,
+    /// Directories the slave writes to, each with what it keeps there.
+    pub directories: Vec<(String, PathBuf)>,
+}
+
+impl Default for DoctorOptions {
+    fn default() -> Self {
+        Self {
+            max_monitors: DEFAULT_MAX_MONITORS,
+            capture_timeout_ms: DEFAULT_CAPTURE_TIMEOUT_MS,
+            input: true,
+            udp_port: 0,
+            directories: Vec::new(),
+        }
+    }
+}
+
+/// Run every check `options` asks for.
+pub fn run(options: &DoctorOptions) -> SelfTestReport {
+    let mut checks = vec![check_session(
+        desktop::session_id(),
+        desktop::console_session_id(),
+        &mut InputDesktop,
+    )];
+    checks.extend(check_capture(
+        options.max_monitors,
+        options.capture_timeout_ms,
+    ));
+    if options.input {
+        checks.push(check_input(&InputInjector::new()));
+    }
+    checks.push(check_udp_port(options.udp_port));
+    for (what, dir) in &options.directories {
+        checks.push(check_writable(what, dir));
+    }
+    SelfTestReport {
+        hostname: std::env::var("COMPUTERNAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| "unknown".to_string()),
+        checks,
+    }
+}
+
+// ── Checks ───────────────────────────────────────────────────────
+
+/// Which session and desktop this process is attached to. `session` and
+/// `console` are Terminal Services session IDs, `None` where there are
+/// none.
+pub fn check_session(
+    session: Option<u32>,
+    console: Option<u32>,
+    desktop: &mut dyn DesktopProbe,
+) -> CheckResult {
+    const NAME: &str = "session";
+    let input = desktop.input_desktop();
+    let place = match session {
+        Some(id) => format!("session {id}"),
+        None => "no Windows sessions".to_string(),
+    };
+
+    if session == Some(0) {
+        return CheckResult::fail(
+            NAME,
+            format!("{place} (services)"),
+            "session 0 has no interactive desktop to capture or inject into; \
+             start the slave in the user's session, e.g. from a logon task",
+        );
+    }
+    let Some(input) = input else {
+        return CheckResult::warn(
+            NAME,
+            format!("{place}, input desktop not accessible"),
+            "a UAC prompt or the lock screen is up and this process may not open it; \
+             run as SYSTEM with screen.secure_desktop_capture to follow it",
+        );
+    };
+    if !input.eq_ignore_ascii_case(DEFAULT_DESKTOP) {
+        return CheckResult::warn(
+            NAME,
+            format!("{place}, input on the {input} desktop"),
+            "a UAC prompt or the lock screen is up; answer it at the machine",
+        );
+    }
+    match (session, console) {
+        (Some(id), Some(console)) if id != console => CheckResult::warn(
+            NAME,
+            format!("RDP session {id} (console is session {console}), desktop {input}"),
+            "DXGI stops delivering frames while the RDP client is minimized or disconnected; \
+             keep it open, or run the slave on the console session",
+        ),
+        _ => CheckResult::pass(NAME, format!("{place}, desktop {input}")),
+    }
+}
+
+/// Open a capturer on each monitor in turn and take one frame from it,
+/// stopping at the first monitor that does not exist. Monitor 0 failing
+/// is the one failure: there is nothing to capture at all.
+pub fn check_capture(max_monitors: u32, timeout_ms: u32) -> Vec<CheckResult> {
+    let mut checks = Vec::new();
+    for monitor in 0..max_monitors.max(1) {
+        let name = format!("capture monitor {monitor}");
+        match DxgiCapturer::new(monitor) {
+            Ok(mut capturer) => {
+                let origin = capturer.origin();
+                let mut check = check_frame(&name, &mut capturer, timeout_ms);
+                check.detail = format!("{} at {},{}", check.detail, origin.0, origin.1);
+                checks.push(check);
+            }
+            Err(e) if monitor == 0 => {
+                checks.push(CheckResult::fail(
+                    name,
+                    e.to_string(),
+                    "no DXGI output: a disconnected or minimized RDP session, session 0, \
+                     or no display adapter; run on the console session with a display attached",
+                ));
+                break;
+            }
+            Err(_) => break,
+        }
+    }
+    checks
+}
+
+/// Take one frame from `source`.
+pub fn check_frame(name: &str, source: &mut dyn FrameSource, timeout_ms: u32) -> CheckResult {
+    let size = format!("{}x{}", source.width(), source.height());
+    let started = Instant::now();
+    match source.capture_frame(timeout_ms) {
+        Ok(_) => CheckResult::pass(
+            name,
+            format!(
+                "{size}, first frame in {} ms",
+                started.elapsed().as_millis()
+            ),
+        ),
+        Err(TixError::Timeout(_)) => CheckResult::warn(
+            name,
+            format!("{size}, no frame within {timeout_ms} ms"),
+            "frames only come when the screen changes; a locked or disconnected \
+             session never changes, so check nobody has locked it",
+        ),
+        Err(e) => CheckResult::fail(
+            name,
+            format!("{size}, {e}"),
+            "the display mode may be changing or another program holds exclusive \
+             fullscreen; run the check again",
+        ),
+    }
+}
+
+/// Inject a pointer move of zero pixels, and say whether input will reach
+/// elevated windows too.
+pub fn check_input(injector: &InputInjector) -> CheckResult {
+    const NAME: &str = "input injection";
+    match injector.probe() {
+        Ok(()) if injector.elevated() => CheckResult::pass(NAME, "SendInput accepted, elevated"),
+        Ok(()) => CheckResult::warn(
+            NAME,
+            "SendInput accepted, not elevated",
+            "input will not reach elevated windows (UIPI); run the slave as administrator",
+        ),
+        Err(e) => CheckResult::fail(
+            NAME,
+            e.to_string(),
+            "SendInput only reaches the desktop of an interactive session; \
+             run the slave in the logged-on user's session",
+        ),
+    }
+}
+
+/// Bind the screen stream's UDP port, and let it go again.
+pub fn check_udp_port(port: u16) -> CheckResult {
+    let name = match port {
+        0 => "UDP socket".to_string(),
+        port => format!("UDP port {port}"),
+    };
+    match UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))) {
+        Ok(socket) => {
+            let bound = socket.local_addr().map(|a| a.port()).unwrap_or(port);
+            CheckResult::pass(name, format!("bound 0.0.0.0:{bound}"))
+        }
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => CheckResult::fail(
+            name,
+            e.to_string(),
+            "another program holds the port, perhaps a slave already running; \
+             stop it or change network.listen_port",
+        ),
+        Err(e) => CheckResult::fail(
+            name,
+            e.to_string(),
+            "a firewall or security policy refuses the bind; allow the slave's \
+             executable for UDP on the private network",
+        ),
+    }
+}
+
+/// Create and remove a file in `dir`, which holds `what`.
+pub fn check_writable(what: &str, dir: &Path) -> CheckResult {
+    let name = format!("{what} directory");
+    let shown = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    if !shown.is_dir() {
+        return CheckResult::fail(
+            name,
+            format!("{} does not exist", shown.display()),
+            "create it, or point the config at a directory that exists",
+        );
+    }
+    let probe = shown.join(format!(".tix-doctor-{}", std::process::id()));
+    let written = std::fs::write(&probe, b"tix").and_then(|()| std::fs::remove_file(&probe));
+    match written {
+        Ok(()) => CheckResult::pass(name, format!("{} is writable", shown.display())),
+        Err(e) => {
+            let _ = std::fs::remove_file(&probe);
+            CheckResult::fail(
+                name,
+                format!("{}: {e}", shown.display()),
+                "grant the account the slave runs as write access, \
+                 or point the config somewhere it has it",
+            )
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::protocol::selftest::CheckStatus;
+    use crate::rdp::types::{PixelFormat, RawScreenFrame};
+
+    struct Desktop(Option<&'static str>);
+
+    impl DesktopProbe for Desktop {
+        fn input_desktop(&mut self) -> Option<String> {
+            self.0.map(Into::into)
+        }
+
+        fn attach(&mut self) -> Result<(), TixError> {
+            Ok(())
+        }
+    }
+
+    /// Answers every capture with `result`.
+    struct Source(fn() -> Result<RawScreenFrame, TixError>);
+
+    impl FrameSource for Source {
+        fn capture_frame(&mut self, _timeout_ms: u32) -> Result<RawScreenFrame, TixError> {
+            (self.0)()
+        }
+
+        fn reinitialize(&mut self) -> Result<(), TixError> {
+            Ok(())
+        }
+
+        fn width(&self) -> u32 {
+            640
+        }
+
+        fn height(&self) -> u32 {
+            480
+        }
+    }
+
+    #[test]
+    fn session_zero_and_secure_desktops_are_reported() {
+        let console = Some(1);
+        let check = check_session(Some(1), console, &mut Desktop(Some("Default")));
+        assert_eq!(check.status, CheckStatus::Pass, "{check:?}");
+        assert_eq!(check.detail, "session 1, desktop Default");
+
+        let check = check_session(Some(0), console, &mut Desktop(Some("Default")));
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.hint.unwrap().contains("session 0"));
+
+        let check = check_session(Some(1), console, &mut Desktop(Some("Winlogon")));
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.detail.contains("Winlogon"));
+        let check = check_session(Some(1), console, &mut Desktop(None));
+        assert_eq!(check.status, CheckStatus::Warn);
+
+        let check = check_session(Some(3), console, &mut Desktop(Some("Default")));
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.detail.starts_with("RDP session 3"));
+
+        let check = check_session(None, None, &mut Desktop(Some("Default")));
+        assert_eq!(check.status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn frame_check_tells_idle_from_broken() {
+        let frame = || {
+            Ok(RawScreenFrame {
+                width: 640,
+                height: 480,
+                stride: 640 * 4,
+                format: PixelFormat::Bgra8,
+                data: vec![0; 640 * 480 * 4],
+                timestamp: std::time::Instant::now(),
+            })
+        };
+        let check = check_frame("capture monitor 0", &mut Source(frame), 100);
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(check.detail.starts_with("640x480, first frame in"));
+
+        let idle = || Err(TixError::Timeout(Duration::from_millis(100)));
+        let check = check_frame("capture monitor 0", &mut Source(idle), 100);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert_eq!(check.detail, "640x480, no frame within 100 ms");
+
+        let lost = || Err(TixError::CaptureLost("mode change".into()));
+        let check = check_frame("capture monitor 0", &mut Source(lost), 100);
+        assert_eq!(check.status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn udp_port_in_use_fails() {
+        let held = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let port = held.local_addr().unwrap().port();
+        let check = check_udp_port(port);
+        assert_eq!(check.status, CheckStatus::Fail, "{check:?}");
+        assert!(check.hint.unwrap().contains("listen_port"));
+
+        drop(held);
+        let check = check_udp_port(0);
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert_eq!(check.name, "UDP socket");
+    }
+
+    #[test]
+    fn writable_directories_pass_and_missing_ones_fail() {
+        let dir = std::env::temp_dir().join(format!("tix-doctor-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let check = check_writable("log", &dir);
+        assert_eq!(check.status, CheckStatus::Pass, "{check:?}");
+        assert_eq!(check.name, "log directory");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let check = check_writable("config", &dir.join("missing"));
+        assert_eq!(check.status, CheckStatus::Fail);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(not(all(target_os = "windows", feature = "win-capture")))]
+    #[test]
+    fn capture_and_input_fail_off_windows() {
+        let checks = check_capture(4, 10);
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].status, CheckStatus::Fail);
+        assert_eq!(check_input(&InputInjector::new()).status, CheckStatus::Fail);
+    }
+
+    #[cfg(all(target_os = "windows", feature = "win-capture"))]
+    #[test]
+    fn primary_monitor_is_checked_first() {
+        let checks = check_capture(1, 500);
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].name, "capture monitor 0");
+        let session = check_session(
+            desktop::session_id(),
+            desktop::console_session_id(),
+            &mut InputDesktop,
+        );
+        assert!(session.detail.contains("session"), "{session:?}");
+    }
+}
@@ -8,12 +8,140 @@
 //!
 //! Quality is adjusted dynamically via [`adjust_quality`](AdaptiveEncoder::adjust_quality)
 //! based on measured bandwidth reported by the transport layer.
+//!
+//! Pixels go out in the encoder's [`format`](AdaptiveEncoder::format):
+//! BGRA8 as captured, or packed to RGB565 / Gray8 by
+//! [`convert`](crate::rdp::convert) before compression. When allowed,
+//! sustained bandwidth pressure that maximum compression cannot absorb
+//! drops a BGRA8 stream to RGB565; it returns to the negotiated format
+//! once the link has had headroom for a while.
+//!
+//! A [`KeyframePolicy`] promotes deltas to full frames: periodically, so
+//! a viewer that joined late or lost datagrams catches up, and when most
+//! of the screen changed at once (a window maximised, a video started),
+//! where one full frame compresses better than thousands of blocks.
+//!
+//! Once the viewer has confirmed it holds the same
+//! [`FrameDictionary`], [`set_dictionary`](AdaptiveEncoder::set_dictionary)
+//! makes every following frame compress against it. Frames before that
+//! stay plain, and the decoder tells the two apart by the dictionary ID
+//! zstd writes into the frame header.
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::error::TixError;
+use crate::rdp::convert;
 use crate::rdp::delta::{DeltaFrame, Block};
-use crate::rdp::types::RawScreenFrame;
+use crate::rdp::dictionary::FrameDictionary;
+use crate::rdp::types::{PixelFormat, RawScreenFrame};
+
+/// Highest zstd level the adaptive controller will use.
+pub const MAX_COMPRESSION_LEVEL: i32 = 9;
+
+/// Consecutive over-budget adjustments at maximum compression before the
+/// format is downgraded.
+const DOWNGRADE_AFTER: u32 = 3;
+
+/// Consecutive adjustments with headroom before a downgraded format is
+/// restored.
+const RESTORE_AFTER: u32 = 10;
+
+/// Frames between periodic keyframes, unless configured otherwise.
+pub const DEFAULT_KEYFRAME_FRAMES: u64 = 300;
+
+/// Longest time between periodic keyframes, unless configured otherwise.
+pub const DEFAULT_KEYFRAME_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Share of the screen, in percent, that must change at once for a
+/// delta to go out as a full frame, unless configured otherwise.
+pub const DEFAULT_SCENE_CHANGE_PERCENT: u8 = 60;
+
+// ── KeyframePolicy ───────────────────────────────────────────────
+
+/// Why a frame went out whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyframeReason {
+    /// The delta detector asked for it: first frame, new size, reset.
+    Requested,
+    /// The periodic keyframe was due.
+    Periodic,
+    /// Most of the screen changed.
+    SceneChange,
+}
+
+/// When a delta is sent as a full frame instead.
+///
+/// Time is read from the frames' capture timestamps, so a policy can be
+/// driven with made-up ones.
+#[derive(Debug, Clone, Default)]
+pub struct KeyframePolicy {
+    /// A keyframe at least every this many frames.
+    frames: Option<u64>,
+    /// A keyframe at least this often.
+    interval: Option<Duration>,
+    /// Promote deltas covering at least this share of the screen.
+    scene_change_percent: Option<u8>,
+    /// Deltas sent since the last keyframe.
+    since_keyframe: u64,
+    last_keyframe: Option<Instant>,
+}
+
+impl KeyframePolicy {
+    /// A keyframe every `frames` frames or `interval`, whichever comes
+    /// first, and for deltas covering `scene_change_percent` of the
+    /// screen. `None` turns a rule off; the default policy has none.
+    pub fn new(
+        frames: Option<u64>,
+        interval: Option<Duration>,
+        scene_change_percent: Option<u8>,
+    ) -> Self {
+        Self {
+            frames: frames.filter(|&n| n > 0),
+            interval,
+            scene_change_percent: scene_change_percent.map(|p| p.min(100)),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the periodic keyframe is due at `now`, changes or not.
+    pub fn is_due(&self, now: Instant) -> bool {
+        let by_count = self.frames.is_some_and(|n| self.since_keyframe + 1 >= n);
+        let by_time = self.interval.is_some_and(|interval| match self.last_keyframe {
+            Some(last) => now.saturating_duration_since(last) >= interval,
+            None => true,
+        });
+        by_count || by_time
+    }
+
+    /// Whether `delta` should go out as a full frame, and why.
+    pub fn reason(&self, delta: &DeltaFrame) -> Option<KeyframeReason> {
+        if delta.full_frame {
+            return Some(KeyframeReason::Requested);
+        }
+        if self.is_due(delta.timestamp) {
+            return Some(KeyframeReason::Periodic);
+        }
+        let percent = self.scene_change_percent?;
+        let total = delta.width as u64 * delta.height as u64;
+        let changed: u64 = delta
+            .changed_blocks
+            .iter()
+            .map(|b| b.width as u64 * b.height as u64)
+            .sum();
+        (total > 0 && changed.min(total) * 100 >= total * percent as u64)
+            .then_some(KeyframeReason::SceneChange)
+    }
+
+    /// A frame captured at `now` was sent, whole or not.
+    pub fn record(&mut self, full_frame: bool, now: Instant) {
+        if full_frame {
+            self.since_keyframe = 0;
+            self.last_keyframe = Some(now);
+        } else {
+            self.since_keyframe += 1;
+        }
+    }
+}
 
 // ── EncodedFrame ─────────────────────────────────────────────────
 
@@ -34,6 +162,12 @@ pub struct EncodedFrame {
     pub is_full_frame: bool,
     /// Number of dirty blocks (informational).
     pub block_count: u32,
+    /// Layout of the pixels inside `data` once decompressed.
+    pub format: PixelFormat,
+    /// On received frames, when the slave captured the frame by its own
+    /// stream clock (if it says). Only differences between frames of
+    /// one stream mean anything; `None` on frames encoded locally.
+    pub capture_clock: Option<Duration>,
 }
 
 // ── AdaptiveEncoder ──────────────────────────────────────────────
@@ -57,6 +191,25 @@ pub struct AdaptiveEncoder {
     measured_bandwidth: u64,
     /// Number of frames encoded so far.
     frame_count: u64,
+    /// Format the stream was negotiated at; never exceeded.
+    negotiated_format: PixelFormat,
+    /// Format frames are currently packed into.
+    format: PixelFormat,
+    /// Whether bandwidth pressure may lower `format`.
+    allow_downgrade: bool,
+    /// Consecutive adjustments that were over budget at maximum
+    /// compression.
+    pressure_streak: u32,
+    /// Consecutive adjustments comfortably under budget.
+    headroom_streak: u32,
+    /// Highest level the controller may pick; lowered to save CPU.
+    level_cap: i32,
+    /// When deltas are sent whole.
+    keyframes: KeyframePolicy,
+    /// Dictionary frames are compressed against, once both ends have it.
+    dictionary: Option<FrameDictionary>,
+    /// Compression context holding `dictionary`, built on first use.
+    compressor: Option<zstd::bulk::Compressor<'static>>,
 }
 
 impl AdaptiveEncoder {
@@ -70,25 +223,96 @@ impl AdaptiveEncoder {
             target_bandwidth,
             measured_bandwidth: target_bandwidth,
             frame_count: 0,
+            negotiated_format: PixelFormat::Bgra8,
+            format: PixelFormat::Bgra8,
+            allow_downgrade: false,
+            pressure_streak: 0,
+            headroom_streak: 0,
+            level_cap: MAX_COMPRESSION_LEVEL,
+            keyframes: KeyframePolicy::default(),
+            dictionary: None,
+            compressor: None,
         }
     }
 
-    /// Encode a delta frame using pixel data from `source`.
+    /// Pack pixels into `format` (falls back to BGRA8 for formats the
+    /// encoder cannot produce).
+    pub fn with_format(mut self, format: PixelFormat) -> Self {
+        let format = if format.is_encodable() {
+            format
+        } else {
+            PixelFormat::Bgra8
+        };
+        self.negotiated_format = format;
+        self.format = format;
+        self
+    }
+
+    /// Allow the controller to drop to RGB565 under sustained pressure.
+    pub fn with_format_downgrade(mut self, allow: bool) -> Self {
+        self.allow_downgrade = allow;
+        self
+    }
+
+    /// Promote deltas to full frames following `policy`.
+    pub fn with_keyframes(mut self, policy: KeyframePolicy) -> Self {
+        self.keyframes = policy;
+        self
+    }
+
+    /// Compress every frame against `dictionary` from the start. Only
+    /// for streams whose viewer is known to hold it; see
+    /// [`set_dictionary`](Self::set_dictionary) otherwise.
+    pub fn with_dictionary(mut self, dictionary: FrameDictionary) -> Self {
+        self.set_dictionary(Some(dictionary));
+        self
+    }
+
+    /// Compress frames from the next one on against `dictionary`, or
+    /// plain again with `None`.
+    pub fn set_dictionary(&mut self, dictionary: Option<FrameDictionary>) {
+        self.dictionary = dictionary;
+        self.compressor = None;
+    }
+
+    /// The dictionary frames are compressed against, if any.
+    pub fn dictionary(&self) -> Option<&FrameDictionary> {
+        self.dictionary.as_ref()
+    }
+
+    /// Whether the next frame goes out whole even if nothing changed,
+    /// so a static screen still gets its periodic keyframe.
+    pub fn keyframe_due(&self, now: Instant) -> bool {
+        self.keyframes.is_due(now)
+    }
+
+    /// Encode a delta frame using pixel data from `source`. The frame
+    /// goes out whole if the delta asks for it or the keyframe policy
+    /// promotes it.
     pub fn encode(
         &mut self,
         delta: &DeltaFrame,
         source: &RawScreenFrame,
     ) -> Result<EncodedFrame, TixError> {
-        let raw = if delta.full_frame {
-            self.encode_full_frame(source)?
+        // Only BGRA captures can be repacked; anything else goes as is.
+        let format = if source.format == PixelFormat::Bgra8 {
+            self.format
+        } else {
+            source.format
+        };
+        let full_frame = self.keyframes.reason(delta).is_some();
+        let raw = if full_frame {
+            self.encode_full_frame(source, format)?
         } else {
-            self.encode_delta_blocks(&delta.changed_blocks, source)?
+            self.encode_delta_blocks(&delta.changed_blocks, source, format)?
         };
 
-        let compressed = zstd::encode_all(raw.as_slice(), self.compression_level)
+        let compressed = self
+            .compress(&raw)
             .map_err(|e| TixError::Other(format!("zstd encode failed: {e}")))?;
 
         self.frame_count += 1;
+        self.keyframes.record(full_frame, delta.timestamp);
 
         Ok(EncodedFrame {
             frame_number: delta.frame_number,
@@ -96,29 +320,81 @@ impl AdaptiveEncoder {
             width: delta.width,
             height: delta.height,
             data: compressed,
-            is_full_frame: delta.full_frame,
-            block_count: delta.changed_blocks.len() as u32,
+            is_full_frame: full_frame,
+            block_count: if full_frame { 1 } else { delta.changed_blocks.len() as u32 },
+            format,
+            capture_clock: None,
         })
     }
 
     /// Adjust quality based on measured network throughput.
     ///
     /// Called periodically by the service loop after the transport
-    /// layer reports actual bandwidth usage.
+    /// layer reports actual bandwidth usage. May change
+    /// [`format`](Self::format); callers should send a keyframe when it
+    /// does so the whole picture is at the new depth.
     pub fn adjust_quality(&mut self, measured_bandwidth: u64) {
         self.measured_bandwidth = measured_bandwidth;
 
         if measured_bandwidth > self.target_bandwidth {
             // Over budget — increase compression (slower but smaller).
             self.quality = self.quality.saturating_sub(5);
-            self.compression_level = (self.compression_level + 1).min(9);
+            self.headroom_streak = 0;
+            if self.compression_level >= self.level_cap {
+                self.pressure_streak += 1;
+            }
+            self.compression_level = (self.compression_level + 1).min(self.level_cap);
+
+            if self.allow_downgrade
+                && self.pressure_streak >= DOWNGRADE_AFTER
+                && self.format == PixelFormat::Bgra8
+            {
+                self.format = PixelFormat::Rgb565;
+                self.pressure_streak = 0;
+            }
         } else if measured_bandwidth < self.target_bandwidth * 8 / 10 {
             // Under 80 % — decrease compression (faster, larger).
             self.quality = (self.quality + 5).min(100);
             self.compression_level = (self.compression_level - 1).max(1);
+            self.pressure_streak = 0;
+            self.headroom_streak += 1;
+
+            if self.format != self.negotiated_format && self.headroom_streak >= RESTORE_AFTER {
+                self.format = self.negotiated_format;
+                self.headroom_streak = 0;
+            }
+        } else {
+            self.pressure_streak = 0;
+            self.headroom_streak = 0;
         }
     }
 
+    /// Keep the zstd level at or below `cap` (clamped to
+    /// `1..=MAX_COMPRESSION_LEVEL`), trading bandwidth for CPU time.
+    /// Under bandwidth pressure at the cap, the format downgrade still
+    /// applies.
+    pub fn set_level_cap(&mut self, cap: i32) {
+        self.level_cap = cap.clamp(1, MAX_COMPRESSION_LEVEL);
+        self.compression_level = self.compression_level.min(self.level_cap);
+    }
+
+    /// Whether frames cannot get any smaller: the level is at its cap
+    /// and the format is as reduced as it is allowed to go.
+    pub fn is_exhausted(&self) -> bool {
+        self.compression_level >= self.level_cap
+            && (self.format != PixelFormat::Bgra8 || !self.allow_downgrade)
+    }
+
+    /// Current zstd compression level.
+    pub fn compression_level(&self) -> i32 {
+        self.compression_level
+    }
+
+    /// Pixel format frames are currently packed into.
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
     /// Current quality slider value (0..100).
     pub fn quality(&self) -> u8 {
         self.quality
@@ -131,21 +407,63 @@ impl AdaptiveEncoder {
 
     // ── Internal encoding helpers ────────────────────────────────
 
-    /// Full frame: emit all rows packed tightly (no padding).
-    fn encode_full_frame(&self, source: &RawScreenFrame) -> Result<Vec<u8>, TixError> {
-        let bpp = source.format.bytes_per_pixel();
-        let row_len = source.width as usize * bpp;
-        let mut out = Vec::with_capacity(row_len * source.height as usize);
+    /// Compress a raw payload at the current level, against the
+    /// dictionary if one is set.
+    fn compress(&mut self, raw: &[u8]) -> std::io::Result<Vec<u8>> {
+        let Some(dictionary) = &self.dictionary else {
+            return zstd::encode_all(raw, self.compression_level);
+        };
+        let compressor = match &mut self.compressor {
+            Some(compressor) => compressor,
+            empty => empty.insert(zstd::bulk::Compressor::with_dictionary(
+                self.compression_level,
+                dictionary.bytes(),
+            )?),
+        };
+        // Not `set_compression_level`, which drops the dictionary.
+        compressor.set_parameter(zstd::zstd_safe::CParameter::CompressionLevel(
+            self.compression_level,
+        ))?;
+        compressor.compress(raw)
+    }
 
-        for y in 0..source.height {
-            let row_start = y as usize * source.stride as usize;
-            out.extend_from_slice(&source.data[row_start..row_start + row_len]);
+    /// Full frame: emit all rows packed tightly (no padding) in `format`.
+    fn encode_full_frame(
+        &self,
+        source: &RawScreenFrame,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, TixError> {
+        let row_len = source.width as usize * source.format.bytes_per_pixel();
+        let out_row_len = source.width as usize * format.bytes_per_pixel();
+        // Unpadded and unconverted: the capture buffer already is the payload.
+        if format == source.format && source.stride as usize == row_len {
+            return Ok(source.data[..source.byte_len()].to_vec());
         }
 
+        let mut out = Vec::with_capacity(out_row_len * source.height as usize);
+        for row in source.region_rows(0, 0, source.width, source.height) {
+            Self::push_row(&mut out, row, source, format)?;
+        }
         Ok(out)
     }
 
-    /// Delta: emit a sequence of `[block_header | block_pixels]`.
+    /// Append one row of `source` pixels, converted to `format` if needed.
+    fn push_row(
+        out: &mut Vec<u8>,
+        row: &[u8],
+        source: &RawScreenFrame,
+        format: PixelFormat,
+    ) -> Result<(), TixError> {
+        if format == source.format {
+            out.extend_from_slice(row);
+            Ok(())
+        } else {
+            convert::pack_bgra(row, format, out)
+        }
+    }
+
+    /// Delta: emit a sequence of `[block_header | block_pixels]`, with
+    /// pixels in `format` (`width * height * bpp` bytes per block).
     ///
     /// Block header (16 bytes, little-endian):
     /// ```text
@@ -158,9 +476,11 @@ impl AdaptiveEncoder {
         &self,
         blocks: &[Block],
         source: &RawScreenFrame,
+        format: PixelFormat,
     ) -> Result<Vec<u8>, TixError> {
-        let bpp = source.format.bytes_per_pixel();
-        let mut out = Vec::new();
+        let out_bpp = format.bytes_per_pixel();
+        let pixels: usize = blocks.iter().map(|b| b.width as usize * b.height as usize).sum();
+        let mut out = Vec::with_capacity(4 + blocks.len() * 16 + pixels * out_bpp);
 
         // Leading u32: number of blocks.
         out.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
@@ -172,14 +492,10 @@ impl AdaptiveEncoder {
             out.extend_from_slice(&block.width.to_le_bytes());
             out.extend_from_slice(&block.height.to_le_bytes());
 
-            // Pixel data for this block.
-            let start_x_bytes = block.x as usize * bpp;
-            let row_bytes = block.width as usize * bpp;
-
-            for row in 0..block.height {
-                let y = (block.y + row) as usize;
-                let offset = y * source.stride as usize + start_x_bytes;
-                out.extend_from_slice(&source.data[offset..offset + row_bytes]);
+            // Pixel data for this block, read straight from the strided
+            // capture buffer.
+            for row in source.region_rows(block.x, block.y, block.width, block.height) {
+                Self::push_row(&mut out, row, source, format)?;
             }
         }
 
@@ -272,6 +588,235 @@ mod tests {
         assert!(enc.quality() < initial);
     }
 
+    #[test]
+    fn reduced_formats_shrink_full_and_delta_payloads() {
+        let frame = test_frame(128, 128);
+        for (format, bpp) in [
+            (PixelFormat::Bgra8, 4),
+            (PixelFormat::Rgb565, 2),
+            (PixelFormat::Gray8, 1),
+        ] {
+            let mut enc = AdaptiveEncoder::new(100 * 1024 * 1024).with_format(format);
+
+            let full = enc.encode(&full_delta(128, 128), &frame).unwrap();
+            assert_eq!(full.format, format);
+            let raw = zstd::decode_all(full.data.as_slice()).unwrap();
+            assert_eq!(raw.len(), 128 * 128 * bpp);
+
+            // Count + one 16-byte header + a 64×64 block.
+            let partial = enc.encode(&partial_delta(128, 128), &frame).unwrap();
+            let raw = zstd::decode_all(partial.data.as_slice()).unwrap();
+            assert_eq!(raw.len(), 4 + 16 + 64 * 64 * bpp);
+        }
+    }
+
+    #[test]
+    fn strided_source_packs_like_tight() {
+        let (w, h) = (40, 24);
+        let mut tight = test_frame(w, h);
+        for (i, byte) in tight.data.iter_mut().enumerate() {
+            *byte = (i * 7 % 251) as u8;
+        }
+        // The same pixels behind 24 bytes of junk padding per row.
+        let stride = w * 4 + 24;
+        let mut data = vec![0xEE; (stride * h) as usize];
+        for y in 0..h as usize {
+            let row = &tight.data[y * w as usize * 4..(y + 1) * w as usize * 4];
+            data[y * stride as usize..][..row.len()].copy_from_slice(row);
+        }
+        let strided = RawScreenFrame {
+            stride,
+            data,
+            ..tight.clone()
+        };
+
+        let mut blocks = partial_delta(w, h);
+        blocks.changed_blocks = vec![Block {
+            x: 13,
+            y: 5,
+            width: 20,
+            height: 11,
+        }];
+        for format in [PixelFormat::Bgra8, PixelFormat::Rgb565] {
+            for delta in [full_delta(w, h), blocks.clone()] {
+                let mut enc = AdaptiveEncoder::new(1_000_000).with_format(format);
+                let a = enc.encode(&delta, &tight).unwrap();
+                let b = enc.encode(&delta, &strided).unwrap();
+                assert_eq!(
+                    zstd::decode_all(a.data.as_slice()).unwrap(),
+                    zstd::decode_all(b.data.as_slice()).unwrap(),
+                    "{format} full={}",
+                    delta.full_frame
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn non_bgra_sources_are_sent_unchanged() {
+        let mut frame = test_frame(64, 64);
+        frame.format = PixelFormat::Rgba8;
+        let mut enc = AdaptiveEncoder::new(1_000_000).with_format(PixelFormat::Rgb565);
+        let encoded = enc.encode(&full_delta(64, 64), &frame).unwrap();
+        assert_eq!(encoded.format, PixelFormat::Rgba8);
+    }
+
+    #[test]
+    fn sustained_pressure_downgrades_then_restores() {
+        let mut enc = AdaptiveEncoder::new(1_000_000).with_format_downgrade(true);
+        // Climb to maximum compression first; no downgrade yet.
+        for _ in 0..MAX_COMPRESSION_LEVEL {
+            enc.adjust_quality(2_000_000);
+        }
+        assert_eq!(enc.format(), PixelFormat::Bgra8);
+        for _ in 0..DOWNGRADE_AFTER {
+            enc.adjust_quality(2_000_000);
+        }
+        assert_eq!(enc.format(), PixelFormat::Rgb565);
+
+        for _ in 0..RESTORE_AFTER - 1 {
+            enc.adjust_quality(100_000);
+        }
+        assert_eq!(enc.format(), PixelFormat::Rgb565);
+        enc.adjust_quality(100_000);
+        assert_eq!(enc.format(), PixelFormat::Bgra8);
+    }
+
+    #[test]
+    fn exhausted_once_level_and_format_are_spent() {
+        let mut enc = AdaptiveEncoder::new(1_000_000).with_format_downgrade(true);
+        for _ in 0..MAX_COMPRESSION_LEVEL {
+            enc.adjust_quality(2_000_000);
+        }
+        // Still room: the downgrade to RGB565 is to come.
+        assert!(!enc.is_exhausted());
+        for _ in 0..DOWNGRADE_AFTER {
+            enc.adjust_quality(2_000_000);
+        }
+        assert!(enc.is_exhausted());
+
+        let mut enc = AdaptiveEncoder::new(1_000_000);
+        enc.set_level_cap(3);
+        for _ in 0..3 {
+            enc.adjust_quality(2_000_000);
+        }
+        assert!(enc.is_exhausted());
+    }
+
+    #[test]
+    fn downgrade_needs_permission() {
+        let mut enc = AdaptiveEncoder::new(1_000_000);
+        for _ in 0..50 {
+            enc.adjust_quality(2_000_000);
+        }
+        assert_eq!(enc.format(), PixelFormat::Bgra8);
+
+        // An explicitly negotiated grayscale stream stays grayscale.
+        let mut enc = AdaptiveEncoder::new(1_000_000)
+            .with_format(PixelFormat::Gray8)
+            .with_format_downgrade(true);
+        for _ in 0..50 {
+            enc.adjust_quality(2_000_000);
+        }
+        assert_eq!(enc.format(), PixelFormat::Gray8);
+    }
+
+    /// A delta on a 160×100 screen whose changed blocks cover
+    /// `rows` full-width 160×1 strips, captured at `at`.
+    fn strips(rows: u32, at: Instant) -> DeltaFrame {
+        DeltaFrame {
+            frame_number: 0,
+            timestamp: at,
+            width: 160,
+            height: 100,
+            changed_blocks: (0..rows)
+                .map(|y| Block {
+                    x: 0,
+                    y,
+                    width: 160,
+                    height: 1,
+                })
+                .collect(),
+            full_frame: false,
+        }
+    }
+
+    #[test]
+    fn scene_changes_are_promoted_from_the_threshold_up() {
+        let t0 = Instant::now();
+        let policy = KeyframePolicy::new(None, None, Some(60));
+        assert_eq!(policy.reason(&strips(59, t0)), None);
+        assert_eq!(
+            policy.reason(&strips(60, t0)),
+            Some(KeyframeReason::SceneChange)
+        );
+        assert_eq!(
+            policy.reason(&strips(100, t0)),
+            Some(KeyframeReason::SceneChange)
+        );
+        assert_eq!(
+            policy.reason(&full_delta(160, 100)),
+            Some(KeyframeReason::Requested)
+        );
+        assert_eq!(KeyframePolicy::default().reason(&strips(100, t0)), None);
+
+        // The encoder sends the promoted frame whole.
+        let mut enc = AdaptiveEncoder::new(1_000_000)
+            .with_keyframes(KeyframePolicy::new(None, None, Some(60)));
+        let frame = test_frame(160, 100);
+        let below = enc.encode(&strips(59, t0), &frame).unwrap();
+        assert!(!below.is_full_frame);
+        assert_eq!(below.block_count, 59);
+        let at = enc.encode(&strips(60, t0), &frame).unwrap();
+        assert!(at.is_full_frame);
+        assert_eq!(at.block_count, 1);
+        let raw = zstd::decode_all(at.data.as_slice()).unwrap();
+        assert_eq!(raw.len(), 160 * 100 * 4);
+    }
+
+    #[test]
+    fn periodic_keyframes_come_by_count() {
+        let t0 = Instant::now();
+        let mut enc = AdaptiveEncoder::new(1_000_000)
+            .with_keyframes(KeyframePolicy::new(Some(4), None, None));
+        let frame = test_frame(160, 100);
+        let full: Vec<bool> = (0..9)
+            .map(|i| {
+                let delta = if i == 0 { full_delta(160, 100) } else { strips(1, t0) };
+                enc.encode(&delta, &frame).unwrap().is_full_frame
+            })
+            .collect();
+        assert_eq!(
+            full,
+            [true, false, false, false, true, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn periodic_keyframes_come_by_time() {
+        let t0 = Instant::now();
+        let second = Duration::from_secs(1);
+        let mut enc = AdaptiveEncoder::new(1_000_000)
+            .with_keyframes(KeyframePolicy::new(Some(300), Some(10 * second), None));
+        let frame = test_frame(160, 100);
+        let mut first = full_delta(160, 100);
+        first.timestamp = t0;
+        assert!(enc.encode(&first, &frame).unwrap().is_full_frame);
+
+        // A frame every 3 s: the one at 12 s is the first 10 s on.
+        let full: Vec<bool> = (1..=5)
+            .map(|i| {
+                let delta = strips(1, t0 + 3 * i * second);
+                enc.encode(&delta, &frame).unwrap().is_full_frame
+            })
+            .collect();
+        assert_eq!(full, [false, false, false, true, false]);
+
+        // Due with nothing changed, so a static screen is repainted too.
+        assert!(!enc.keyframe_due(t0 + 21 * second));
+        assert!(enc.keyframe_due(t0 + 22 * second));
+    }
+
     #[test]
     fn quality_increases_when_under_budget() {
         let mut enc = AdaptiveEncoder::new(10_000_000);
@@ -0,0 +1,847 @@
+//! Frame preparation on the GPU: tile diffing and pixel packing before
+//! the one readback.
+//!
+//! The CPU path copies the whole desktop texture into a staging texture,
+//! reads every byte of it back and then hashes every tile to find what
+//! changed; at 4K that is 33 MB over the bus a frame, changes or not.
+//! With a [`GpuFramePrep`] the captured texture stays on the device. A
+//! compute shader compares it tile by tile with the previous frame, a
+//! second one packs only the changed tiles, already converted to the
+//! stream's pixel format, into a compact buffer, and only that buffer is
+//! read back. The tiles are written into a CPU copy of the screen, which
+//! goes down the pipeline as an ordinary [`RawScreenFrame`] together
+//! with the changed tiles ([`PreparedTiles`]), so the delta detector is
+//! skipped and the encoder is none the wiser.
+//!
+//! # Packed layout
+//!
+//! Tile `i` of a readback takes [`tile_len`] bytes from `i × tile_len`:
+//! `block_size` rows of `block_size` pixels, of which only the part
+//! inside the frame is meaningful; pixels past the right or bottom edge
+//! are zero. [`pack_tiles`] builds the same bytes on the CPU and is what
+//! the shaders are checked against.
+//!
+//! # Fallback
+//!
+//! `screen.gpu_prep` chooses ([`GpuPrepMode`]). With `auto`, a device
+//! below feature level 11.0, a shader that does not compile or a
+//! readback that fails leaves the capturer on the CPU path; `on` makes
+//! those errors, and `off` never tries.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::TixError;
+use crate::rdp::convert::pack_bgra;
+use crate::rdp::delta::{Block, DeltaDetector};
+use crate::rdp::types::{PixelFormat, RawScreenFrame};
+
+#[cfg(all(target_os = "windows", feature = "win-capture"))]
+pub use platform::GpuFramePrep;
+
+/// The compute shaders, HLSL for `cs_5_0`: entry points `diff` and
+/// `pack`.
+pub const SHADER_SOURCE: &str = include_str!("gpu_prep.hlsl");
+
+// ── Mode ─────────────────────────────────────────────────────────
+
+/// Whether the capturer prepares frames on the GPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GpuPrepMode {
+    /// On the GPU when the device can, on the CPU otherwise.
+    #[default]
+    Auto,
+    /// On the GPU or not at all: failing to set it up fails the capture.
+    On,
+    /// Always on the CPU.
+    Off,
+}
+
+impl fmt::Display for GpuPrepMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GpuPrepMode::Auto => "auto",
+            GpuPrepMode::On => "on",
+            GpuPrepMode::Off => "off",
+        })
+    }
+}
+
+impl FromStr for GpuPrepMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(GpuPrepMode::Auto),
+            "on" | "true" => Ok(GpuPrepMode::On),
+            "off" | "false" => Ok(GpuPrepMode::Off),
+            other => Err(format!("unknown gpu_prep '{other}' (auto, on or off)")),
+        }
+    }
+}
+
+// ── Tiles ────────────────────────────────────────────────────────
+
+/// What a prepared frame says changed, in place of delta detection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreparedTiles {
+    /// There was nothing to compare with; the whole frame is new.
+    Full,
+    /// These tiles changed and nothing else did.
+    Changed(Vec<Block>),
+}
+
+/// Every tile of `frame`, row-major, clipped at the edges.
+pub fn all_tiles(frame: &RawScreenFrame, block_size: u32) -> Vec<Block> {
+    let bs = block_size as usize;
+    let columns = (frame.width as usize).div_ceil(bs);
+    let rows = (frame.height as usize).div_ceil(bs);
+    (0..rows)
+        .flat_map(|by| (0..columns).map(move |bx| (bx, by)))
+        .map(|(bx, by)| DeltaDetector::tile(bs, frame, bx, by))
+        .collect()
+}
+
+/// The tiles that differ between `previous` and `current`, row-major:
+/// what the `diff` pass marks.
+pub fn changed_tiles(
+    previous: &RawScreenFrame,
+    current: &RawScreenFrame,
+    block_size: u32,
+) -> Vec<Block> {
+    DeltaDetector::changed_blocks(block_size as usize, current, previous)
+}
+
+/// Bytes one packed tile takes in `format`.
+pub fn tile_len(block_size: u32, format: PixelFormat) -> usize {
+    (block_size * block_size) as usize * format.bytes_per_pixel()
+}
+
+/// `tiles` of the BGRA8 `frame` in `format`, laid out as the `pack` pass
+/// writes them.
+pub fn pack_tiles(
+    frame: &RawScreenFrame,
+    tiles: &[Block],
+    block_size: u32,
+    format: PixelFormat,
+) -> Result<Vec<u8>, TixError> {
+    let len = tile_len(block_size, format);
+    let row_len = block_size as usize * format.bytes_per_pixel();
+    let mut packed = vec![0; tiles.len() * len];
+    let mut row = Vec::with_capacity(row_len);
+    for (tile, out) in tiles.iter().zip(packed.chunks_exact_mut(len)) {
+        let rows = frame.region_rows(tile.x, tile.y, tile.width, tile.height);
+        for (src, dst) in rows.zip(out.chunks_exact_mut(row_len)) {
+            row.clear();
+            pack_bgra(src, format, &mut row)?;
+            dst[..row.len()].copy_from_slice(&row);
+        }
+    }
+    Ok(packed)
+}
+
+/// Write `packed` tiles into `frame`, whose pixels are in the packed
+/// format. Only the part of each tile inside the frame is copied.
+pub fn unpack_tiles(frame: &mut RawScreenFrame, tiles: &[Block], packed: &[u8], block_size: u32) {
+    let bpp = frame.format.bytes_per_pixel();
+    let len = tile_len(block_size, frame.format);
+    let row_len = block_size as usize * bpp;
+    let stride = frame.stride as usize;
+    for (tile, src) in tiles.iter().zip(packed.chunks_exact(len)) {
+        let width = tile.width as usize * bpp;
+        for (y, src) in (tile.y as usize..).zip(src.chunks_exact(row_len).take(tile.height as usize)) {
+            let start = y * stride + tile.x as usize * bpp;
+            frame.data[start..start + width].copy_from_slice(&src[..width]);
+        }
+    }
+}
+
+/// Shader constant for `format`; `None` for formats the encoder cannot
+/// send.
+#[cfg_attr(not(all(target_os = "windows", feature = "win-capture")), allow(dead_code))]
+fn format_id(format: PixelFormat) -> Option<u32> {
+    match format {
+        PixelFormat::Bgra8 => Some(0),
+        PixelFormat::Rgb565 => Some(1),
+        PixelFormat::Gray8 => Some(2),
+        _ => None,
+    }
+}
+
+// ── Windows implementation ───────────────────────────────────────
+
+#[cfg(all(target_os = "windows", feature = "win-capture"))]
+mod platform {
+    use std::time::Instant;
+
+    use windows::Win32::Graphics::{
+        Direct3D::{
+            D3D_FEATURE_LEVEL_11_0,
+            Fxc::{D3DCOMPILE_OPTIMIZATION_LEVEL3, D3DCompile},
+            ID3DBlob,
+        },
+        Direct3D11::*,
+        Dxgi::Common::*,
+    };
+    use windows::core::{PCSTR, Param, s};
+
+    use super::*;
+
+    /// Largest dispatch along one axis; matches `MAX_GROUPS_X`.
+    const MAX_GROUPS_X: u32 = 65535;
+
+    /// The shader's `Params` constant buffer.
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct Params {
+        width: u32,
+        height: u32,
+        block_size: u32,
+        format: u32,
+        columns: u32,
+        tile_count: u32,
+        padding: [u32; 2],
+    }
+
+    /// Diffs and packs captured frames on the capturer's D3D11 device.
+    pub struct GpuFramePrep {
+        block_size: u32,
+        params: Params,
+        /// The screen as last read back, in the stream's pixel format.
+        mirror: RawScreenFrame,
+        /// Whether `previous` holds a frame to compare with.
+        primed: bool,
+        context: ID3D11DeviceContext,
+        diff: ID3D11ComputeShader,
+        pack: ID3D11ComputeShader,
+        constants: ID3D11Buffer,
+        /// The frame being prepared and the one before it; swapped after
+        /// every frame instead of copied.
+        current: (ID3D11Texture2D, ID3D11ShaderResourceView),
+        previous: (ID3D11Texture2D, ID3D11ShaderResourceView),
+        /// One flag per tile, and where it is read back from.
+        dirty: (ID3D11Buffer, ID3D11UnorderedAccessView),
+        dirty_readback: ID3D11Buffer,
+        /// Origins of the tiles to pack.
+        tiles: (ID3D11Buffer, ID3D11ShaderResourceView),
+        /// Packed tiles, and where they are read back from.
+        packed: (ID3D11Buffer, ID3D11UnorderedAccessView),
+        packed_readback: ID3D11Buffer,
+    }
+
+    impl GpuFramePrep {
+        /// Set up the passes for `width × height` BGRA frames from
+        /// `device`, packed in `format` by tiles of `block_size`.
+        pub fn new(
+            device: &ID3D11Device,
+            context: &ID3D11DeviceContext,
+            width: u32,
+            height: u32,
+            block_size: usize,
+            format: PixelFormat,
+        ) -> Result<Self, TixError> {
+            let format_id = format_id(format)
+                .ok_or_else(|| TixError::Other(format!("cannot prepare {format} frames")))?;
+            let level = unsafe { device.GetFeatureLevel() };
+            if level.0 < D3D_FEATURE_LEVEL_11_0.0 {
+                return Err(TixError::Other(format!(
+                    "feature level 0x{:X} has no compute shaders; 11.0 is needed",
+                    level.0
+                )));
+            }
+            let block_size = block_size as u32;
+            let tile_count = width.div_ceil(block_size) * height.div_ceil(block_size);
+            let packed_len = tile_count * tile_len(block_size, format) as u32;
+
+            unsafe {
+                let diff = compute_shader(device, s!("diff"))?;
+                let pack = compute_shader(device, s!("pack"))?;
+                let constants = buffer(
+                    device,
+                    std::mem::size_of::<Params>() as u32,
+                    D3D11_USAGE_DEFAULT,
+                    D3D11_BIND_CONSTANT_BUFFER.0 as u32,
+                    0,
+                    0,
+                )?;
+
+                let dirty = buffer(
+                    device,
+                    tile_count * 4,
+                    D3D11_USAGE_DEFAULT,
+                    D3D11_BIND_UNORDERED_ACCESS.0 as u32,
+                    D3D11_RESOURCE_MISC_BUFFER_STRUCTURED.0 as u32,
+                    4,
+                )?;
+                let dirty_view = uav(device, &dirty, None)?;
+                let tiles = buffer(
+                    device,
+                    tile_count * 8,
+                    D3D11_USAGE_DEFAULT,
+                    D3D11_BIND_SHADER_RESOURCE.0 as u32,
+                    D3D11_RESOURCE_MISC_BUFFER_STRUCTURED.0 as u32,
+                    8,
+                )?;
+                let tiles_view = srv(device, &tiles, None)?;
+                let packed = buffer(
+                    device,
+                    packed_len,
+                    D3D11_USAGE_DEFAULT,
+                    D3D11_BIND_UNORDERED_ACCESS.0 as u32,
+                    D3D11_RESOURCE_MISC_BUFFER_ALLOW_RAW_VIEWS.0 as u32,
+                    0,
+                )?;
+                let raw_view = D3D11_UNORDERED_ACCESS_VIEW_DESC {
+                    Format: DXGI_FORMAT_R32_TYPELESS,
+                    ViewDimension: D3D11_UAV_DIMENSION_BUFFER,
+                    Anonymous: D3D11_UNORDERED_ACCESS_VIEW_DESC_0 {
+                        Buffer: D3D11_BUFFER_UAV {
+                            FirstElement: 0,
+                            NumElements: packed_len / 4,
+                            Flags: D3D11_BUFFER_UAV_FLAG_RAW.0 as u32,
+                        },
+                    },
+                };
+                let packed_view = uav(device, &packed, Some(&raw_view))?;
+
+                let bpp = format.bytes_per_pixel() as u32;
+                Ok(Self {
+                    block_size,
+                    params: Params {
+                        width,
+                        height,
+                        block_size,
+                        format: format_id,
+                        columns: width.div_ceil(block_size),
+                        tile_count,
+                        padding: [0; 2],
+                    },
+                    mirror: RawScreenFrame {
+                        width,
+                        height,
+                        stride: width * bpp,
+                        format,
+                        data: vec![0; (width * bpp * height) as usize],
+                        timestamp: Instant::now(),
+                    },
+                    primed: false,
+                    context: context.clone(),
+                    diff,
+                    pack,
+                    constants,
+                    current: frame_texture(device, width, height)?,
+                    previous: frame_texture(device, width, height)?,
+                    dirty: (dirty, dirty_view),
+                    dirty_readback: readback(device, tile_count * 4)?,
+                    tiles: (tiles, tiles_view),
+                    packed: (packed, packed_view),
+                    packed_readback: readback(device, packed_len)?,
+                })
+            }
+        }
+
+        /// Prepare the captured `texture`: the frame as the CPU now has
+        /// it, and which tiles of it changed.
+        ///
+        /// The texture must be the size given to [`new`](Self::new) and
+        /// is not used after this returns, so the duplication frame can
+        /// be released straight away.
+        pub fn prepare(
+            &mut self,
+            texture: &ID3D11Texture2D,
+            timestamp: Instant,
+        ) -> Result<(RawScreenFrame, PreparedTiles), TixError> {
+            unsafe { self.context.CopyResource(&self.current.0, texture) };
+            let (tiles, list) = if self.primed {
+                let changed = unsafe { self.diff()? };
+                (PreparedTiles::Changed(changed.clone()), changed)
+            } else {
+                (PreparedTiles::Full, all_tiles(&self.mirror, self.block_size))
+            };
+            let packed = unsafe { self.pack(&list)? };
+            unpack_tiles(&mut self.mirror, &list, &packed, self.block_size);
+            std::mem::swap(&mut self.current, &mut self.previous);
+            self.primed = true;
+
+            let mut frame = self.mirror.clone();
+            frame.timestamp = timestamp;
+            Ok((frame, tiles))
+        }
+
+        /// Run the `diff` pass and read back the changed tiles.
+        unsafe fn diff(&mut self) -> Result<Vec<Block>, TixError> {
+            let params = Params {
+                tile_count: self.params.columns * self.params.height.div_ceil(self.block_size),
+                ..self.params
+            };
+            let ctx = &self.context;
+            unsafe {
+                ctx.UpdateSubresource(
+                    &self.constants,
+                    0,
+                    None,
+                    &params as *const Params as _,
+                    0,
+                    0,
+                );
+                ctx.CSSetShader(&self.diff, None);
+                ctx.CSSetConstantBuffers(0, Some(&[Some(self.constants.clone())]));
+                ctx.CSSetShaderResources(
+                    0,
+                    Some(&[Some(self.current.1.clone()), Some(self.previous.1.clone())]),
+                );
+                ctx.CSSetUnorderedAccessViews(0, 1, Some(&Some(self.dirty.1.clone())), None);
+                ctx.Dispatch(params.columns, params.tile_count / params.columns, 1);
+                unbind(ctx);
+                ctx.CopyResource(&self.dirty_readback, &self.dirty.0);
+            }
+
+            let flags = unsafe { read_back(ctx, &self.dirty_readback, params.tile_count as usize * 4)? };
+            let tiles = all_tiles(&self.mirror, self.block_size);
+            Ok(flags
+                .chunks_exact(4)
+                .zip(tiles)
+                .filter(|(flag, _)| flag.iter().any(|b| *b != 0))
+                .map(|(_, tile)| tile)
+                .collect())
+        }
+
+        /// Run the `pack` pass over `tiles` and read back what it wrote.
+        unsafe fn pack(&mut self, tiles: &[Block]) -> Result<Vec<u8>, TixError> {
+            if tiles.is_empty() {
+                return Ok(Vec::new());
+            }
+            let count = tiles.len() as u32;
+            let origins: Vec<[u32; 2]> = tiles.iter().map(|t| [t.x, t.y]).collect();
+            let params = Params {
+                tile_count: count,
+                ..self.params
+            };
+            let len = count * tile_len(self.block_size, self.mirror.format) as u32;
+            let ctx = &self.context;
+            unsafe {
+                let list = D3D11_BOX {
+                    left: 0,
+                    right: count * 8,
+                    top: 0,
+                    bottom: 1,
+                    front: 0,
+                    back: 1,
+                };
+                ctx.UpdateSubresource(&self.tiles.0, 0, Some(&list), origins.as_ptr() as _, 0, 0);
+                ctx.UpdateSubresource(
+                    &self.constants,
+                    0,
+                    None,
+                    &params as *const Params as _,
+                    0,
+                    0,
+                );
+                ctx.CSSetShader(&self.pack, None);
+                ctx.CSSetConstantBuffers(0, Some(&[Some(self.constants.clone())]));
+                ctx.CSSetShaderResources(
+                    0,
+                    Some(&[Some(self.current.1.clone()), None, Some(self.tiles.1.clone())]),
+                );
+                ctx.CSSetUnorderedAccessViews(1, 1, Some(&Some(self.packed.1.clone())), None);
+                ctx.Dispatch(count.min(MAX_GROUPS_X), count.div_ceil(MAX_GROUPS_X), 1);
+                unbind(ctx);
+
+                let used = D3D11_BOX {
+                    left: 0,
+                    right: len,
+                    top: 0,
+                    bottom: 1,
+                    front: 0,
+                    back: 1,
+                };
+                ctx.CopySubresourceRegion(
+                    &self.packed_readback,
+                    0,
+                    0,
+                    0,
+                    0,
+                    &self.packed.0,
+                    0,
+                    Some(&used),
+                );
+                read_back(ctx, &self.packed_readback, len as usize)
+            }
+        }
+    }
+
+    /// Detach the passes' views so the textures can be copied into.
+    unsafe fn unbind(ctx: &ID3D11DeviceContext) {
+        unsafe {
+            ctx.CSSetShaderResources(0, Some(&[None, None, None]));
+            ctx.CSSetUnorderedAccessViews(0, 2, Some([None, None].as_ptr()), None);
+        }
+    }
+
+    /// The first `len` bytes of the readback buffer `staging`.
+    unsafe fn read_back(
+        ctx: &ID3D11DeviceContext,
+        staging: &ID3D11Buffer,
+        len: usize,
+    ) -> Result<Vec<u8>, TixError> {
+        let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+        unsafe {
+            ctx.Map(staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))
+                .map_err(|e| TixError::Other(format!("Map (readback) failed: {e}")))?;
+            let data = std::slice::from_raw_parts(mapped.pData as *const u8, len).to_vec();
+            ctx.Unmap(staging, 0);
+            Ok(data)
+        }
+    }
+
+    unsafe fn compute_shader(
+        device: &ID3D11Device,
+        entry: PCSTR,
+    ) -> Result<ID3D11ComputeShader, TixError> {
+        let mut code: Option<ID3DBlob> = None;
+        let mut errors: Option<ID3DBlob> = None;
+        let compiled = unsafe {
+            D3DCompile(
+                SHADER_SOURCE.as_ptr() as _,
+                SHADER_SOURCE.len(),
+                s!("gpu_prep.hlsl"),
+                None,
+                None,
+                entry,
+                s!("cs_5_0"),
+                D3DCOMPILE_OPTIMIZATION_LEVEL3,
+                0,
+                &mut code,
+                Some(&mut errors),
+            )
+        };
+        if let Err(e) = compiled {
+            let detail = errors
+                .map(|blob| String::from_utf8_lossy(unsafe { blob_bytes(&blob) }).into_owned())
+                .unwrap_or_default();
+            return Err(TixError::Other(format!(
+                "compiling {}: {e} {}",
+                unsafe { entry.display() },
+                detail.trim()
+            )));
+        }
+        let code = code.ok_or_else(|| TixError::Other("D3DCompile returned no code".into()))?;
+        let mut shader = None;
+        unsafe {
+            device
+                .CreateComputeShader(blob_bytes(&code), None, Some(&mut shader))
+                .map_err(|e| TixError::Other(format!("CreateComputeShader failed: {e}")))?;
+        }
+        shader.ok_or_else(|| TixError::Other("compute shader is None".into()))
+    }
+
+    unsafe fn blob_bytes(blob: &ID3DBlob) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize())
+        }
+    }
+
+    unsafe fn buffer(
+        device: &ID3D11Device,
+        len: u32,
+        usage: D3D11_USAGE,
+        bind: u32,
+        misc: u32,
+        stride: u32,
+    ) -> Result<ID3D11Buffer, TixError> {
+        let desc = D3D11_BUFFER_DESC {
+            ByteWidth: len.max(16),
+            Usage: usage,
+            BindFlags: bind,
+            CPUAccessFlags: if usage == D3D11_USAGE_STAGING {
+                D3D11_CPU_ACCESS_READ.0 as u32
+            } else {
+                0
+            },
+            MiscFlags: misc,
+            StructureByteStride: stride,
+        };
+        let mut buffer = None;
+        unsafe {
+            device
+                .CreateBuffer(&desc, None, Some(&mut buffer))
+                .map_err(|e| TixError::Other(format!("CreateBuffer ({len} bytes) failed: {e}")))?;
+        }
+        buffer.ok_or_else(|| TixError::Other("buffer is None".into()))
+    }
+
+    unsafe fn readback(device: &ID3D11Device, len: u32) -> Result<ID3D11Buffer, TixError> {
+        unsafe { buffer(device, len, D3D11_USAGE_STAGING, 0, 0, 0) }
+    }
+
+    unsafe fn uav(
+        device: &ID3D11Device,
+        buffer: impl Param<ID3D11Resource>,
+        desc: Option<&D3D11_UNORDERED_ACCESS_VIEW_DESC>,
+    ) -> Result<ID3D11UnorderedAccessView, TixError> {
+        let mut view = None;
+        unsafe {
+            device
+                .CreateUnorderedAccessView(buffer, desc.map(|d| d as *const _), Some(&mut view))
+                .map_err(|e| TixError::Other(format!("CreateUnorderedAccessView failed: {e}")))?;
+        }
+        view.ok_or_else(|| TixError::Other("UAV is None".into()))
+    }
+
+    unsafe fn srv(
+        device: &ID3D11Device,
+        resource: impl Param<ID3D11Resource>,
+        desc: Option<&D3D11_SHADER_RESOURCE_VIEW_DESC>,
+    ) -> Result<ID3D11ShaderResourceView, TixError> {
+        let mut view = None;
+        unsafe {
+            device
+                .CreateShaderResourceView(resource, desc.map(|d| d as *const _), Some(&mut view))
+                .map_err(|e| TixError::Other(format!("CreateShaderResourceView failed: {e}")))?;
+        }
+        view.ok_or_else(|| TixError::Other("SRV is None".into()))
+    }
+
+    /// A BGRA texture the shaders can read, the size of the desktop.
+    unsafe fn frame_texture(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+    ) -> Result<(ID3D11Texture2D, ID3D11ShaderResourceView), TixError> {
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+        };
+        let mut texture = None;
+        unsafe {
+            device
+                .CreateTexture2D(&desc, None, Some(&mut texture))
+                .map_err(|e| TixError::Other(format!("CreateTexture2D (prep) failed: {e}")))?;
+        }
+        let texture: ID3D11Texture2D =
+            texture.ok_or_else(|| TixError::Other("prep texture is None".into()))?;
+        let view = unsafe { srv(device, &texture, None)? };
+        Ok((texture, view))
+    }
+
+    // ── Tests ────────────────────────────────────────────────────
+
+    #[cfg(test)]
+    mod tests {
+        use windows::Win32::Graphics::Direct3D::D3D_DRIVER_TYPE_HARDWARE;
+
+        use super::*;
+
+        /// A BGRA texture holding `frame`.
+        fn upload(device: &ID3D11Device, frame: &RawScreenFrame) -> ID3D11Texture2D {
+            let desc = D3D11_TEXTURE2D_DESC {
+                Width: frame.width,
+                Height: frame.height,
+                MipLevels: 1,
+                ArraySize: 1,
+                Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Usage: D3D11_USAGE_DEFAULT,
+                BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
+                CPUAccessFlags: 0,
+                MiscFlags: 0,
+            };
+            let data = D3D11_SUBRESOURCE_DATA {
+                pSysMem: frame.data.as_ptr() as _,
+                SysMemPitch: frame.stride,
+                SysMemSlicePitch: 0,
+            };
+            let mut texture = None;
+            unsafe {
+                device
+                    .CreateTexture2D(&desc, Some(&data), Some(&mut texture))
+                    .unwrap();
+            }
+            texture.unwrap()
+        }
+
+        /// Skipped on machines without a hardware D3D11 device, such as
+        /// most CI runners.
+        #[test]
+        fn gpu_output_matches_the_cpu_reference() {
+            let mut device = None;
+            let mut context = None;
+            let created = unsafe {
+                D3D11CreateDevice(
+                    None,
+                    D3D_DRIVER_TYPE_HARDWARE,
+                    None,
+                    D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+                    None,
+                    D3D11_SDK_VERSION,
+                    Some(&mut device),
+                    None,
+                    Some(&mut context),
+                )
+            };
+            if created.is_err() {
+                eprintln!("no hardware D3D11 device; skipped");
+                return;
+            }
+            let (device, context) = (device.unwrap(), context.unwrap());
+
+            let first = super::super::tests::synthetic(100, 70, 0);
+            let mut second = first.clone();
+            super::super::tests::scribble(&mut second);
+            for format in [PixelFormat::Bgra8, PixelFormat::Rgb565, PixelFormat::Gray8] {
+                let mut prep =
+                    GpuFramePrep::new(&device, &context, 100, 70, 16, format).unwrap();
+                let (frame, tiles) = prep.prepare(&upload(&device, &first), Instant::now()).unwrap();
+                assert_eq!(tiles, PreparedTiles::Full);
+                let mut expected = first.clone();
+                super::super::tests::convert_frame(&mut expected, format);
+                assert_eq!(frame.data, expected.data, "{format}");
+
+                let (frame, tiles) = prep.prepare(&upload(&device, &second), Instant::now()).unwrap();
+                assert_eq!(
+                    tiles,
+                    PreparedTiles::Changed(changed_tiles(&first, &second, 16)),
+                    "{format}"
+                );
+                let mut expected = second.clone();
+                super::super::tests::convert_frame(&mut expected, format);
+                assert_eq!(frame.data, expected.data, "{format}");
+            }
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// A BGRA frame with a gradient and 12 bytes of row padding.
+    pub(super) fn synthetic(width: u32, height: u32, seed: u8) -> RawScreenFrame {
+        let stride = width * 4 + 12;
+        let mut data = vec![0xEE; (stride * height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                let at = (y * stride + x * 4) as usize;
+                let v = (x * 7 + y * 3) as u8;
+                data[at..at + 4].copy_from_slice(&[v, v ^ seed, v.wrapping_mul(5), 0xFF]);
+            }
+        }
+        RawScreenFrame {
+            width,
+            height,
+            stride,
+            format: PixelFormat::Bgra8,
+            data,
+            timestamp: Instant::now(),
+        }
+    }
+
+    /// Change a few pixels: one in the middle, one on the ragged
+    /// bottom-right tile.
+    pub(super) fn scribble(frame: &mut RawScreenFrame) {
+        for (x, y) in [(20, 20), (frame.width - 1, frame.height - 1)] {
+            let at = (y * frame.stride + x * 4) as usize;
+            frame.data[at..at + 3].copy_from_slice(&[1, 2, 3]);
+        }
+    }
+
+    /// `frame` converted to `format`, tightly packed.
+    pub(super) fn convert_frame(frame: &mut RawScreenFrame, format: PixelFormat) {
+        let mut data = Vec::new();
+        for row in frame.region_rows(0, 0, frame.width, frame.height) {
+            pack_bgra(row, format, &mut data).unwrap();
+        }
+        frame.stride = frame.width * format.bytes_per_pixel() as u32;
+        frame.format = format;
+        frame.data = data;
+    }
+
+    #[test]
+    fn modes_parse() {
+        assert_eq!("AUTO".parse(), Ok(GpuPrepMode::Auto));
+        assert_eq!(" on".parse(), Ok(GpuPrepMode::On));
+        assert_eq!("off".parse(), Ok(GpuPrepMode::Off));
+        assert!("sometimes".parse::<GpuPrepMode>().is_err());
+        assert_eq!(GpuPrepMode::default().to_string(), "auto");
+    }
+
+    #[test]
+    fn changed_tiles_are_the_ones_touched() {
+        let first = synthetic(100, 70, 0);
+        let mut second = first.clone();
+        scribble(&mut second);
+
+        let all = all_tiles(&first, 16);
+        assert_eq!(all.len(), 7 * 5);
+        assert_eq!(
+            all.last(),
+            Some(&Block {
+                x: 96,
+                y: 64,
+                width: 4,
+                height: 6
+            })
+        );
+        let changed = changed_tiles(&first, &second, 16);
+        assert_eq!(changed, vec![all[8], all[34]]);
+        assert!(changed_tiles(&first, &first, 16).is_empty());
+    }
+
+    #[test]
+    fn packed_tiles_rebuild_the_frame_in_every_format() {
+        let first = synthetic(100, 70, 9);
+        let mut second = first.clone();
+        scribble(&mut second);
+        for format in [PixelFormat::Bgra8, PixelFormat::Rgb565, PixelFormat::Gray8] {
+            // The whole first frame, then only what changed.
+            let mut mirror = first.clone();
+            convert_frame(&mut mirror, format);
+            mirror.data.fill(0);
+            let all = all_tiles(&first, 16);
+            let packed = pack_tiles(&first, &all, 16, format).unwrap();
+            assert_eq!(packed.len(), all.len() * tile_len(16, format));
+            unpack_tiles(&mut mirror, &all, &packed, 16);
+
+            let changed = changed_tiles(&first, &second, 16);
+            let packed = pack_tiles(&second, &changed, 16, format).unwrap();
+            assert_eq!(packed.len(), 2 * tile_len(16, format));
+            unpack_tiles(&mut mirror, &changed, &packed, 16);
+
+            let mut expected = second.clone();
+            convert_frame(&mut expected, format);
+            assert_eq!(mirror.data, expected.data, "{format}");
+
+            // Past the frame's edge a packed tile is zero.
+            let bpp = format.bytes_per_pixel();
+            let edge = &packed[tile_len(16, format)..];
+            assert!(edge[4 * bpp..16 * bpp].iter().all(|b| *b == 0));
+            assert!(edge[6 * 16 * bpp..].iter().all(|b| *b == 0));
+        }
+    }
+
+    #[test]
+    fn only_sendable_formats_are_prepared() {
+        assert_eq!(format_id(PixelFormat::Rgb565), Some(1));
+        assert_eq!(format_id(PixelFormat::Rgb8), None);
+        assert!(SHADER_SOURCE.contains("void diff("));
+        assert!(SHADER_SOURCE.contains("void pack("));
+    }
+}
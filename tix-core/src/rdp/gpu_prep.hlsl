@@ -0,0 +1,105 @@
+// Frame preparation on the GPU for the slave's screen capture; see
+// rdp/gpu.rs for how the passes are driven and read back.
+//
+// `diff` runs one group per tile of the frame and sets Dirty[tile] when
+// any pixel of it differs from the previous frame. `pack` runs one
+// group per tile listed in Tiles and writes it to Packed in the stream's
+// pixel format: BlockSize rows of BlockSize pixels, zero past the right
+// and bottom edges of the frame.
+
+Texture2D<float4> Current : register(t0);
+Texture2D<float4> Previous : register(t1);
+StructuredBuffer<uint2> Tiles : register(t2);
+RWStructuredBuffer<uint> Dirty : register(u0);
+RWByteAddressBuffer Packed : register(u1);
+
+cbuffer Params : register(b0)
+{
+    uint Width;
+    uint Height;
+    uint BlockSize;
+    uint Format;    // 0 = BGRA8, 1 = RGB565, 2 = Gray8
+    uint Columns;   // tiles per row of the frame
+    uint TileCount; // tiles listed in Tiles
+    uint2 Padding;
+};
+
+#define GROUP 8
+// Dispatch dimensions are capped; `pack` spreads long lists over rows.
+#define MAX_GROUPS_X 65535
+
+groupshared uint Changed;
+
+[numthreads(GROUP, GROUP, 1)]
+void diff(uint3 tile : SV_GroupID, uint3 local : SV_GroupThreadID, uint index : SV_GroupIndex)
+{
+    if (index == 0)
+        Changed = 0;
+    GroupMemoryBarrierWithGroupSync();
+
+    uint2 origin = tile.xy * BlockSize;
+    for (uint y = local.y; y < BlockSize; y += GROUP)
+    {
+        for (uint x = local.x; x < BlockSize; x += GROUP)
+        {
+            uint2 p = origin + uint2(x, y);
+            if (p.x < Width && p.y < Height
+                && any(Current.Load(int3(p, 0)) != Previous.Load(int3(p, 0))))
+                InterlockedOr(Changed, 1);
+        }
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    if (index == 0)
+        Dirty[tile.y * Columns + tile.x] = Changed;
+}
+
+// The pixel as the capture stores it: x = blue, y = green, z = red,
+// w = alpha, 0-255.
+uint4 Bgra(float4 c)
+{
+    return uint4(round(saturate(c.bgra) * 255.0));
+}
+
+// One pixel in Format, in the low bits. Must match rdp::convert
+// bit for bit: the CPU path produces the same bytes.
+uint Encode(float4 c)
+{
+    uint4 p = Bgra(c);
+    if (Format == 1)
+        return ((p.z >> 3) << 11) | ((p.y >> 2) << 5) | (p.x >> 3);
+    if (Format == 2)
+        return (77 * p.z + 150 * p.y + 29 * p.x + 128) >> 8;
+    return p.x | (p.y << 8) | (p.z << 16) | (p.w << 24);
+}
+
+[numthreads(GROUP, GROUP, 1)]
+void pack(uint3 group : SV_GroupID, uint3 local : SV_GroupThreadID)
+{
+    uint slot = group.y * MAX_GROUPS_X + group.x;
+    if (slot >= TileCount)
+        return;
+
+    uint bits = Format == 0 ? 32 : (Format == 1 ? 16 : 8);
+    uint perWord = 32 / bits;
+    uint wordsPerRow = BlockSize / perWord;
+    uint tileBytes = BlockSize * BlockSize * (bits / 8);
+    uint2 origin = Tiles[slot];
+
+    for (uint y = local.y; y < BlockSize; y += GROUP)
+    {
+        for (uint w = local.x; w < wordsPerRow; w += GROUP)
+        {
+            // Pixels fill a word from its low bits, which is their
+            // order in memory on a little-endian readback.
+            uint word = 0;
+            for (uint k = 0; k < perWord; k++)
+            {
+                uint2 p = origin + uint2(w * perWord + k, y);
+                if (p.x < Width && p.y < Height)
+                    word |= Encode(Current.Load(int3(p, 0))) << (k * bits);
+            }
+            Packed.Store(slot * tileBytes + (y * wordsPerRow + w) * 4, word);
+        }
+    }
+}
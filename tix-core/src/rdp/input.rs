@@ -5,9 +5,211 @@
 //! # Platform
 //!
 //! Windows-only. On other platforms the injector is defined but all
-//! methods return an error.
+//! methods return an error. [`InputGate`] and [`InjectionBreaker`] are
+//! platform-independent.
+//!
+//! # Blocked input
+//!
+//! `SendInput` fails outright now and then, and fails silently when the
+//! foreground window belongs to an elevated process and the slave does
+//! not run elevated (UIPI). Either way the viewer keeps clicking with
+//! nothing happening. The injector counts such failures in a row, the
+//! silent kind detected by asking whether the foreground window's
+//! process is elevated, and after [`BREAKER_THRESHOLD`] of them opens
+//! an [`InjectionBreaker`]: input is dropped for [`BREAKER_COOLDOWN`]
+//! instead of being thrown at a window that will not take it, or until
+//! the foreground window changes. [`InputInjector::blocked`] says why,
+//! for the screen service to tell the viewer.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
 use crate::error::TixError;
+use crate::protocol::screen::InputRejection;
+
+// ── InputGate ────────────────────────────────────────────────────
+
+/// Control / view-only switch for one screen session.
+///
+/// Every input event goes through [`admit`](Self::admit) before it
+/// reaches the injector, so a view-only session stays view-only no
+/// matter what the viewer sends.
+#[derive(Debug)]
+pub struct InputGate {
+    control: AtomicBool,
+    rejected: AtomicU64,
+}
+
+impl InputGate {
+    /// A gate that starts in control (`true`) or view-only mode.
+    pub fn new(control: bool) -> Self {
+        Self {
+            control: AtomicBool::new(control),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Switch modes. Returns the previous mode.
+    pub fn set_control(&self, control: bool) -> bool {
+        self.control.swap(control, Ordering::SeqCst)
+    }
+
+    /// Whether input is currently accepted.
+    pub fn allows_control(&self) -> bool {
+        self.control.load(Ordering::SeqCst)
+    }
+
+    /// Let one input event through, or say why not.
+    pub fn admit(&self) -> Result<(), InputRejection> {
+        if self.allows_control() {
+            Ok(())
+        } else {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            Err(InputRejection::ViewOnly)
+        }
+    }
+
+    /// Input events refused so far.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for InputGate {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+// ── InjectionBreaker ─────────────────────────────────────────────
+
+/// Failed or blocked injections in a row that open the breaker.
+pub const BREAKER_THRESHOLD: u32 = 8;
+
+/// How long an open breaker drops input before trying again.
+pub const BREAKER_COOLDOWN: Duration = Duration::from_secs(5);
+
+/// What the viewer is told when UIPI looks to be eating the input.
+pub const ELEVATED_FOREGROUND: &str =
+    "input blocked by elevated window on remote — slave needs to run elevated";
+
+/// How one injection went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Injection {
+    /// `SendInput` took the event.
+    Sent,
+    /// `SendInput` refused the event; the text says why.
+    Failed(String),
+    /// `SendInput` took the event, but the foreground window belongs to
+    /// an elevated process and this one is not: UIPI drops it unseen.
+    Blocked,
+}
+
+/// Circuit breaker between the viewer's input and `SendInput`.
+///
+/// Feed each injection through [`admit`](Self::admit) and, if it was
+/// attempted, [`record`](Self::record) its outcome. Windows are told
+/// apart by an opaque ID (the `HWND` on Windows).
+#[derive(Debug)]
+pub struct InjectionBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    /// Failed or blocked injections since the last one that worked.
+    failures: u32,
+    /// Some failure of the current run looked like UIPI.
+    blocked: bool,
+    last_error: Option<String>,
+    /// While open: when to try again, and the foreground window it
+    /// opened on.
+    open: Option<(Instant, Option<isize>)>,
+    /// Why input is not getting through, until an injection does.
+    reason: Option<String>,
+}
+
+impl InjectionBreaker {
+    /// A breaker that opens after `threshold` failures in a row and
+    /// stays open for `cooldown`.
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            failures: 0,
+            blocked: false,
+            last_error: None,
+            open: None,
+            reason: None,
+        }
+    }
+
+    /// Whether to attempt an injection at `now` with `foreground` in
+    /// front. An open breaker closes early when the foreground window
+    /// changes: the new one may well take input.
+    pub fn admit(&mut self, now: Instant, foreground: Option<isize>) -> bool {
+        let Some((until, window)) = self.open else {
+            return true;
+        };
+        if foreground != window {
+            self.reset();
+            return true;
+        }
+        if now < until {
+            return false;
+        }
+        // Cooled down: try again, and open again after as many failures.
+        self.open = None;
+        true
+    }
+
+    /// Record how an admitted injection went. Returns `true` when this
+    /// opened the breaker.
+    pub fn record(&mut self, outcome: &Injection, now: Instant, foreground: Option<isize>) -> bool {
+        match outcome {
+            Injection::Sent => {
+                self.reset();
+                return false;
+            }
+            Injection::Failed(error) => self.last_error = Some(error.clone()),
+            Injection::Blocked => self.blocked = true,
+        }
+        self.failures += 1;
+        if self.failures < self.threshold {
+            return false;
+        }
+        self.failures = 0;
+        self.open = Some((now + self.cooldown, foreground));
+        self.reason = Some(match (&self.last_error, self.blocked) {
+            (Some(error), false) => format!("input injection keeps failing on remote: {error}"),
+            _ => ELEVATED_FOREGROUND.into(),
+        });
+        true
+    }
+
+    /// Whether input is being dropped.
+    pub fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    /// Why input is not getting through, from the moment the breaker
+    /// opens until an injection works or the foreground window changes.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    fn reset(&mut self) {
+        self.failures = 0;
+        self.blocked = false;
+        self.last_error = None;
+        self.open = None;
+        self.reason = None;
+    }
+}
+
+impl Default for InjectionBreaker {
+    fn default() -> Self {
+        Self::new(BREAKER_THRESHOLD, BREAKER_COOLDOWN)
+    }
+}
 
 // ── InputInjector ────────────────────────────────────────────────
 
@@ -16,12 +218,47 @@ use crate::error::TixError;
 /// On Windows this uses `SendInput` which requires the calling
 /// process to be running in the same desktop session (or with
 /// `UIAccess` privileges).
-pub struct InputInjector;
+///
+/// Clones share one [`InjectionBreaker`], so every path input arrives
+/// on trips the same one and the screen service sees its state.
+#[derive(Debug, Clone)]
+pub struct InputInjector {
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    breaker: InjectionBreaker,
+    /// The last foreground window seen and whether it is elevated, so
+    /// the process is only queried when the window changes.
+    #[cfg_attr(not(all(target_os = "windows", feature = "win-capture")), allow(dead_code))]
+    foreground: Option<(isize, bool)>,
+}
 
 impl InputInjector {
     /// Create a new injector (no initialisation cost).
     pub fn new() -> Self {
-        Self
+        Self::with_breaker(InjectionBreaker::default())
+    }
+
+    /// An injector guarded by `breaker` instead of the default one.
+    pub fn with_breaker(breaker: InjectionBreaker) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                breaker,
+                foreground: None,
+            })),
+        }
+    }
+
+    /// Why input is not reaching the remote desktop, while the breaker
+    /// has something to say.
+    pub fn blocked(&self) -> Option<String> {
+        self.lock().breaker.reason().map(str::to_owned)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
     }
 }
 
@@ -33,29 +270,36 @@ impl Default for InputInjector {
 
 // ── Windows implementation ───────────────────────────────────────
 
-#[cfg(target_os = "windows")]
+#[cfg(all(target_os = "windows", feature = "win-capture"))]
 mod platform {
     use super::*;
     use crate::protocol::screen::{KeyAction, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
     use windows::Win32::UI::Input::KeyboardAndMouse::*;
 
     impl InputInjector {
         /// Inject a mouse event from the TixRP protocol.
         pub fn inject_mouse(&self, event: &MouseEvent) -> Result<(), TixError> {
-            // Convert to absolute coordinates (0..65535).
-            let (screen_w, screen_h) = unsafe {
+            // Convert to absolute coordinates (0..65535) across the whole
+            // virtual desktop, so a session on a secondary monitor (which
+            // adds that monitor's origin) lands on the right screen. With
+            // one monitor this is the same as the primary screen.
+            let (left, top, screen_w, screen_h) = unsafe {
                 use windows::Win32::UI::WindowsAndMessaging::*;
-                let w = GetSystemMetrics(SM_CXSCREEN);
-                let h = GetSystemMetrics(SM_CYSCREEN);
-                (w, h)
+                (
+                    GetSystemMetrics(SM_XVIRTUALSCREEN),
+                    GetSystemMetrics(SM_YVIRTUALSCREEN),
+                    GetSystemMetrics(SM_CXVIRTUALSCREEN),
+                    GetSystemMetrics(SM_CYVIRTUALSCREEN),
+                )
             };
 
             if screen_w == 0 || screen_h == 0 {
                 return Err(TixError::Other("GetSystemMetrics returned 0".into()));
             }
 
-            let abs_x = (event.x as i64 * 65535 / screen_w as i64) as i32;
-            let abs_y = (event.y as i64 * 65535 / screen_h as i64) as i32;
+            let abs_x = ((event.x - left) as i64 * 65535 / screen_w as i64) as i32;
+            let abs_y = ((event.y - top) as i64 * 65535 / screen_h as i64) as i32;
 
             let mut flags = MOUSE_EVENT_FLAGS(0);
             let mut mouse_data: u32 = 0;
@@ -113,6 +357,7 @@ mod platform {
                     return Ok(());
                 }
             }
+            flags |= MOUSEEVENTF_VIRTUALDESK;
 
             let input = INPUT {
                 r#type: INPUT_MOUSE,
@@ -128,12 +373,7 @@ mod platform {
                 },
             };
 
-            let sent = unsafe { SendInput(&[input], std::mem::size_of::<INPUT>() as i32) };
-            if sent == 0 {
-                return Err(TixError::Other("SendInput (mouse) returned 0".into()));
-            }
-
-            Ok(())
+            self.send(input, "mouse")
         }
 
         /// Inject a keyboard event from the TixRP protocol.
@@ -168,19 +408,153 @@ mod platform {
                 },
             };
 
+            self.send(input, "keyboard")
+        }
+
+        /// `SendInput` a pointer move of zero pixels, past the breaker.
+        /// Nothing moves, but it fails the way real input would from a
+        /// process that cannot reach the input desktop.
+        pub fn probe(&self) -> Result<(), TixError> {
+            let input = INPUT {
+                r#type: INPUT_MOUSE,
+                Anonymous: INPUT_0 {
+                    mi: MOUSEINPUT {
+                        dx: 0,
+                        dy: 0,
+                        mouseData: 0,
+                        dwFlags: MOUSEEVENTF_MOVE,
+                        time: 0,
+                        dwExtraInfo: 0,
+                    },
+                },
+            };
             let sent = unsafe { SendInput(&[input], std::mem::size_of::<INPUT>() as i32) };
             if sent == 0 {
-                return Err(TixError::Other("SendInput (keyboard) returned 0".into()));
+                let error = windows::core::Error::from_win32();
+                return Err(TixError::Other(format!("SendInput failed: {error}")));
             }
-
             Ok(())
         }
+
+        /// Whether this process runs elevated. Without it UIPI keeps
+        /// input away from elevated windows.
+        pub fn elevated(&self) -> bool {
+            process_elevated()
+        }
+
+        /// `SendInput` one event past the breaker, and tell the breaker
+        /// how it went.
+        fn send(&self, input: INPUT, what: &str) -> Result<(), TixError> {
+            let now = Instant::now();
+            let mut shared = self.lock();
+            let foreground = foreground_window(&mut shared.foreground);
+            let window = foreground.map(|(id, _)| id);
+            if !shared.breaker.admit(now, window) {
+                let reason = shared.breaker.reason().unwrap_or("recent injections failed");
+                return Err(TixError::InputBlocked(reason.into()));
+            }
+
+            let sent = unsafe { SendInput(&[input], std::mem::size_of::<INPUT>() as i32) };
+            let outcome = if sent == 0 {
+                let error = windows::core::Error::from_win32();
+                Injection::Failed(format!("SendInput ({what}) failed: {error}"))
+            } else if foreground.is_some_and(|(_, elevated)| elevated) && !process_elevated() {
+                Injection::Blocked
+            } else {
+                Injection::Sent
+            };
+            if shared.breaker.record(&outcome, now, window)
+                && let Some(reason) = shared.breaker.reason()
+            {
+                tracing::warn!("pausing input injection for {BREAKER_COOLDOWN:?}: {reason}");
+            }
+            match outcome {
+                Injection::Failed(error) => Err(TixError::Other(error)),
+                Injection::Sent | Injection::Blocked => Ok(()),
+            }
+        }
+    }
+
+    /// The foreground window and whether its process runs elevated.
+    /// `cache` holds the last answer, so the process is only queried
+    /// when the foreground window changes.
+    fn foreground_window(cache: &mut Option<(isize, bool)>) -> Option<(isize, bool)> {
+        use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+        use windows::Win32::UI::WindowsAndMessaging::{
+            GetForegroundWindow, GetWindowThreadProcessId,
+        };
+
+        // SAFETY: the process handle is closed before returning.
+        unsafe {
+            let hwnd = GetForegroundWindow();
+            if hwnd.is_invalid() {
+                return None;
+            }
+            let id = hwnd.0 as isize;
+            if let Some(known) = cache.filter(|(cached, _)| *cached == id) {
+                return Some(known);
+            }
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, Some(&mut pid));
+            // A process whose token we may not even read is taken to be
+            // above us.
+            let elevated = match OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
+                Ok(process) => {
+                    let elevated = token_elevated(process).unwrap_or(true);
+                    let _ = CloseHandle(process);
+                    elevated
+                }
+                Err(_) => true,
+            };
+            *cache = Some((id, elevated));
+            *cache
+        }
+    }
+
+    /// Whether this process runs elevated, so UIPI lets it reach any
+    /// window. Asked once.
+    fn process_elevated() -> bool {
+        use windows::Win32::System::Threading::GetCurrentProcess;
+
+        static ELEVATED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+        // SAFETY: the pseudo-handle needs no closing.
+        *ELEVATED.get_or_init(|| unsafe {
+            token_elevated(GetCurrentProcess()).unwrap_or(false)
+        })
+    }
+
+    /// Whether the token of `process` is elevated; `None` if it cannot
+    /// be read.
+    unsafe fn token_elevated(process: HANDLE) -> Option<bool> {
+        use windows::Win32::Security::{
+            GetTokenInformation, TOKEN_ELEVATION, TOKEN_QUERY, TokenElevation,
+        };
+        use windows::Win32::System::Threading::OpenProcessToken;
+
+        let mut token = HANDLE::default();
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut len = 0u32;
+        // SAFETY: `elevation` is the size passed in; the token is
+        // closed before returning.
+        unsafe {
+            OpenProcessToken(process, TOKEN_QUERY, &mut token).ok()?;
+            let read = GetTokenInformation(
+                token,
+                TokenElevation,
+                Some((&mut elevation as *mut TOKEN_ELEVATION).cast()),
+                std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+                &mut len,
+            );
+            let _ = CloseHandle(token);
+            read.ok()?;
+        }
+        Some(elevation.TokenIsElevated != 0)
     }
 }
 
 // ── Non-Windows stub ─────────────────────────────────────────────
 
-#[cfg(not(target_os = "windows"))]
+#[cfg(not(all(target_os = "windows", feature = "win-capture")))]
 mod platform {
     use super::*;
     use crate::protocol::screen::{KeyEvent, MouseEvent};
@@ -197,6 +571,16 @@ mod platform {
                 "Input injection is only available on Windows".into(),
             ))
         }
+
+        pub fn probe(&self) -> Result<(), TixError> {
+            Err(TixError::Other(
+                "Input injection is only available on Windows".into(),
+            ))
+        }
+
+        pub fn elevated(&self) -> bool {
+            false
+        }
     }
 }
 
@@ -210,4 +594,92 @@ mod tests {
     fn injector_creates_without_error() {
         let _inj = InputInjector::new();
     }
+
+    #[test]
+    fn view_only_gate_rejects_until_switched_back() {
+        let gate = InputGate::new(false);
+        assert_eq!(gate.admit(), Err(InputRejection::ViewOnly));
+        assert_eq!(gate.admit(), Err(InputRejection::ViewOnly));
+        assert_eq!(gate.rejected(), 2);
+
+        assert!(!gate.set_control(true));
+        assert!(gate.admit().is_ok());
+        assert!(gate.set_control(false));
+        assert!(gate.admit().is_err());
+        assert_eq!(gate.rejected(), 3);
+    }
+
+    const COOLDOWN: Duration = Duration::from_secs(5);
+    const ELEVATED_WINDOW: Option<isize> = Some(0x1234);
+
+    /// Record `count` outcomes at `now`; whether the last one opened
+    /// the breaker.
+    fn fail(breaker: &mut InjectionBreaker, outcome: Injection, count: u32, now: Instant) -> bool {
+        (0..count).fold(false, |_, _| {
+            assert!(breaker.admit(now, ELEVATED_WINDOW));
+            breaker.record(&outcome, now, ELEVATED_WINDOW)
+        })
+    }
+
+    #[test]
+    fn breaker_opens_after_a_run_of_blocked_input_and_cools_down() {
+        let t0 = Instant::now();
+        let mut breaker = InjectionBreaker::new(3, COOLDOWN);
+        assert!(!fail(&mut breaker, Injection::Blocked, 2, t0));
+        assert!(breaker.reason().is_none());
+        assert!(fail(&mut breaker, Injection::Blocked, 1, t0));
+        assert!(breaker.is_open());
+        assert_eq!(breaker.reason(), Some(ELEVATED_FOREGROUND));
+
+        // Dropped while open, then tried again as many times.
+        assert!(!breaker.admit(t0 + COOLDOWN / 2, ELEVATED_WINDOW));
+        let later = t0 + COOLDOWN;
+        assert!(!fail(&mut breaker, Injection::Blocked, 2, later));
+        assert_eq!(breaker.reason(), Some(ELEVATED_FOREGROUND));
+        assert!(fail(&mut breaker, Injection::Blocked, 1, later));
+        assert!(!breaker.admit(later, ELEVATED_WINDOW));
+    }
+
+    #[test]
+    fn one_injection_that_works_resets_the_count() {
+        let t0 = Instant::now();
+        let mut breaker = InjectionBreaker::new(3, COOLDOWN);
+        for _ in 0..4 {
+            assert!(!fail(&mut breaker, Injection::Failed("denied".into()), 2, t0));
+            assert!(!breaker.record(&Injection::Sent, t0, ELEVATED_WINDOW));
+        }
+        assert!(!breaker.is_open());
+
+        assert!(fail(&mut breaker, Injection::Failed("denied".into()), 3, t0));
+        assert_eq!(
+            breaker.reason(),
+            Some("input injection keeps failing on remote: denied")
+        );
+        let after = t0 + COOLDOWN;
+        assert!(!fail(&mut breaker, Injection::Sent, 1, after));
+        assert_eq!(breaker.reason(), None);
+    }
+
+    #[test]
+    fn a_new_foreground_window_closes_the_breaker() {
+        let t0 = Instant::now();
+        let mut breaker = InjectionBreaker::new(2, COOLDOWN);
+        assert!(fail(&mut breaker, Injection::Blocked, 2, t0));
+        assert!(!breaker.admit(t0, ELEVATED_WINDOW));
+
+        assert!(breaker.admit(t0, Some(0x5678)));
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.reason(), None);
+        // The count starts over too.
+        assert!(!breaker.record(&Injection::Blocked, t0, Some(0x5678)));
+    }
+
+    #[test]
+    fn injector_clones_share_the_breaker() {
+        let injector = InputInjector::with_breaker(InjectionBreaker::new(1, COOLDOWN));
+        let clone = injector.clone();
+        let now = Instant::now();
+        assert!(clone.lock().breaker.record(&Injection::Blocked, now, None));
+        assert_eq!(injector.blocked().as_deref(), Some(ELEVATED_FOREGROUND));
+    }
 }
@@ -0,0 +1,402 @@
+//! Coalescing of dirty tiles into larger rectangles.
+//!
+//! [`DeltaDetector`](crate::rdp::delta::DeltaDetector) reports every
+//! changed tile as its own [`Block`], and each block costs a 16-byte
+//! header in the delta payload. Scattered small changes (a cursor trail,
+//! a blinking caret, a clock) at a small block size turn into many
+//! headers around a few pixels each. [`RegionMerger`] walks the tile grid
+//! and grows rectangles over neighbouring dirty tiles, accepting clean
+//! tiles into a rectangle as long as they stay within a waste budget:
+//! the share of the rectangle's pixels that did not change.
+//!
+//! Merged rectangles never overlap and always cover every dirty tile, so
+//! the decoder patches them like any other block.
+
+use crate::rdp::delta::{Block, DeltaFrame};
+
+/// Default share of clean pixels a merged rectangle may include, in
+/// percent.
+pub const DEFAULT_MAX_WASTE_PERCENT: u8 = 30;
+
+/// Whether a rectangle of `area` pixels, `dirty` of which changed, is
+/// within `max_percent` percent of waste.
+pub fn within_waste(area: u64, dirty: u64, max_percent: u8) -> bool {
+    let clean = area.saturating_sub(dirty);
+    clean * 100 <= area * max_percent.min(100) as u64
+}
+
+/// Merges the dirty tiles of a [`DeltaFrame`] into larger rectangles.
+///
+/// Greedy: starting from the first unmerged dirty tile in row-major
+/// order, a rectangle widens along its row and then grows downwards for
+/// as long as it stays within the waste budget, and is trimmed back to
+/// the last column and row that had a dirty tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionMerger {
+    block_size: usize,
+    max_waste_percent: u8,
+}
+
+impl RegionMerger {
+    /// A merger for tiles of `block_size` pixels that lets up to
+    /// `max_waste_percent` percent of a rectangle be clean (clamped to
+    /// 100). `0` merges only runs of dirty tiles.
+    pub fn new(block_size: usize, max_waste_percent: u8) -> Self {
+        assert!(block_size > 0, "block_size must be > 0");
+        Self {
+            block_size,
+            max_waste_percent: max_waste_percent.min(100),
+        }
+    }
+
+    /// The configured waste budget in percent.
+    pub fn max_waste_percent(&self) -> u8 {
+        self.max_waste_percent
+    }
+
+    /// Replace the changed blocks of `delta` with merged rectangles.
+    /// Full frames are left alone.
+    pub fn merge(&self, delta: &mut DeltaFrame) {
+        if delta.full_frame {
+            return;
+        }
+        delta.changed_blocks = self.merge_blocks(&delta.changed_blocks, delta.width, delta.height);
+    }
+
+    /// Merge `blocks` of a `width × height` frame.
+    ///
+    /// A block marks every tile it touches as dirty, so blocks that are
+    /// not tile-aligned (or overlap) come out covered too. The result is
+    /// in row-major order of each rectangle's top-left tile.
+    pub fn merge_blocks(&self, blocks: &[Block], width: u32, height: u32) -> Vec<Block> {
+        let grid = TileGrid::new(self.block_size, width, height);
+        if blocks.len() < 2 || grid.cols == 0 || grid.rows == 0 {
+            return blocks.to_vec();
+        }
+
+        let mut dirty = vec![false; grid.cols * grid.rows];
+        for block in blocks.iter().filter(|b| b.width > 0 && b.height > 0) {
+            let (tx0, ty0) = grid.tile_of(block.x, block.y);
+            let (tx1, ty1) = grid.tile_of(block.x + block.width - 1, block.y + block.height - 1);
+            for ty in ty0..=ty1 {
+                dirty[ty * grid.cols + tx0..=ty * grid.cols + tx1].fill(true);
+            }
+        }
+
+        let mut taken = vec![false; dirty.len()];
+        let mut merged = Vec::new();
+        for start in 0..dirty.len() {
+            if !dirty[start] || taken[start] {
+                continue;
+            }
+            let (tx0, ty0) = (start % grid.cols, start / grid.cols);
+
+            // Widen along the first row, ending on a dirty tile.
+            let mut area = grid.tile_area(tx0, ty0);
+            let mut changed = area;
+            let mut end = tx0 + 1;
+            let mut best = (end, area, changed);
+            while end < grid.cols && !taken[ty0 * grid.cols + end] {
+                let tile = grid.tile_area(end, ty0);
+                area += tile;
+                if dirty[ty0 * grid.cols + end] {
+                    changed += tile;
+                }
+                end += 1;
+                if !within_waste(area, changed, self.max_waste_percent) {
+                    break;
+                }
+                if dirty[ty0 * grid.cols + end - 1] {
+                    best = (end, area, changed);
+                }
+            }
+            let (tx1, mut area, mut changed) = best;
+
+            // Then grow downwards, ending on a row with a dirty tile.
+            let mut end = ty0 + 1;
+            let mut bottom = end;
+            while end < grid.rows && (tx0..tx1).all(|tx| !taken[end * grid.cols + tx]) {
+                let mut row_dirty = false;
+                let (mut row_area, mut row_changed) = (0, 0);
+                for tx in tx0..tx1 {
+                    let tile = grid.tile_area(tx, end);
+                    row_area += tile;
+                    if dirty[end * grid.cols + tx] {
+                        row_changed += tile;
+                        row_dirty = true;
+                    }
+                }
+                if !within_waste(
+                    area + row_area,
+                    changed + row_changed,
+                    self.max_waste_percent,
+                ) {
+                    break;
+                }
+                area += row_area;
+                changed += row_changed;
+                end += 1;
+                if row_dirty {
+                    bottom = end;
+                }
+            }
+
+            for ty in ty0..bottom {
+                taken[ty * grid.cols + tx0..ty * grid.cols + tx1].fill(true);
+            }
+            merged.push(grid.rect(tx0, ty0, tx1, bottom));
+        }
+        merged
+    }
+}
+
+/// Tile geometry of one frame.
+struct TileGrid {
+    block_size: usize,
+    width: usize,
+    height: usize,
+    cols: usize,
+    rows: usize,
+}
+
+impl TileGrid {
+    fn new(block_size: usize, width: u32, height: u32) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        Self {
+            block_size,
+            width,
+            height,
+            cols: width.div_ceil(block_size),
+            rows: height.div_ceil(block_size),
+        }
+    }
+
+    /// Tile holding pixel `(x, y)`, clamped to the grid.
+    fn tile_of(&self, x: u32, y: u32) -> (usize, usize) {
+        (
+            (x as usize / self.block_size).min(self.cols - 1),
+            (y as usize / self.block_size).min(self.rows - 1),
+        )
+    }
+
+    /// Pixels in tile `(tx, ty)`, clipped at the frame edges.
+    fn tile_area(&self, tx: usize, ty: usize) -> u64 {
+        let rect = self.rect(tx, ty, tx + 1, ty + 1);
+        rect.width as u64 * rect.height as u64
+    }
+
+    /// Pixel rectangle covering tiles `[tx0, tx1) × [ty0, ty1)`.
+    fn rect(&self, tx0: usize, ty0: usize, tx1: usize, ty1: usize) -> Block {
+        let bs = self.block_size;
+        let (x0, y0) = (tx0 * bs, ty0 * bs);
+        let (x1, y1) = ((tx1 * bs).min(self.width), (ty1 * bs).min(self.height));
+        Block {
+            x: x0 as u32,
+            y: y0 as u32,
+            width: (x1 - x0) as u32,
+            height: (y1 - y0) as u32,
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn tile(bs: u32, tx: u32, ty: u32) -> Block {
+        Block {
+            x: tx * bs,
+            y: ty * bs,
+            width: bs,
+            height: bs,
+        }
+    }
+
+    fn area(blocks: &[Block]) -> u64 {
+        blocks
+            .iter()
+            .map(|b| b.width as u64 * b.height as u64)
+            .sum()
+    }
+
+    #[test]
+    fn waste_math() {
+        // 7 of 10 pixels dirty: exactly 30 % waste.
+        assert!(within_waste(10, 7, 30));
+        assert!(!within_waste(10, 6, 30));
+        assert!(within_waste(10, 10, 0));
+        assert!(!within_waste(10, 9, 0));
+        assert!(within_waste(0, 0, 0));
+        // Over 100 % behaves like 100 %: anything goes.
+        assert!(within_waste(10, 0, 255));
+    }
+
+    #[test]
+    fn runs_of_dirty_tiles_become_one_rectangle() {
+        let merger = RegionMerger::new(16, 0);
+        let blocks: Vec<Block> = (0..4).map(|tx| tile(16, tx, 2)).collect();
+        let merged = merger.merge_blocks(&blocks, 128, 128);
+        assert_eq!(
+            merged,
+            [Block {
+                x: 0,
+                y: 32,
+                width: 64,
+                height: 16
+            }]
+        );
+
+        // A 2×2 square.
+        let square = [
+            tile(16, 3, 3),
+            tile(16, 4, 3),
+            tile(16, 3, 4),
+            tile(16, 4, 4),
+        ];
+        let merged = merger.merge_blocks(&square, 128, 128);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(area(&merged), 4 * 16 * 16);
+    }
+
+    #[test]
+    fn gaps_within_budget_are_bridged() {
+        // D D D . D: 1 clean tile in 5 is 20 % waste.
+        let blocks = [tile(8, 0, 0), tile(8, 1, 0), tile(8, 2, 0), tile(8, 4, 0)];
+        let merged = RegionMerger::new(8, 30).merge_blocks(&blocks, 64, 64);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].width, 40);
+
+        // With a 10 % budget the gap splits them.
+        let merged = RegionMerger::new(8, 10).merge_blocks(&blocks, 64, 64);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(area(&merged), 4 * 8 * 8);
+    }
+
+    #[test]
+    fn far_apart_tiles_stay_separate() {
+        let blocks = [tile(16, 0, 0), tile(16, 7, 7)];
+        let merged =
+            RegionMerger::new(16, DEFAULT_MAX_WASTE_PERCENT).merge_blocks(&blocks, 128, 128);
+        assert_eq!(merged, blocks);
+    }
+
+    #[test]
+    fn trailing_clean_rows_and_columns_are_trimmed() {
+        // Dirty tiles at (0,0) and (0,2) with nothing in between: a
+        // 3-row rectangle would be 33 % clean, over budget.
+        let blocks = [tile(8, 0, 0), tile(8, 0, 2)];
+        let merged = RegionMerger::new(8, 30).merge_blocks(&blocks, 64, 64);
+        assert_eq!(merged, blocks);
+
+        // At 50 % the gap is bridged (the rectangle is 50 % clean while
+        // it spans the gap) but still ends on the last dirty row.
+        let merged = RegionMerger::new(8, 50).merge_blocks(&blocks, 64, 64);
+        assert_eq!(
+            merged,
+            [Block {
+                x: 0,
+                y: 0,
+                width: 8,
+                height: 24
+            }]
+        );
+    }
+
+    #[test]
+    fn edge_tiles_are_clipped() {
+        // 50×20 frame with 16 px tiles: the right column is 2 px wide
+        // and the bottom row 4 px tall.
+        let blocks = [
+            Block {
+                x: 32,
+                y: 16,
+                width: 16,
+                height: 4,
+            },
+            Block {
+                x: 48,
+                y: 16,
+                width: 2,
+                height: 4,
+            },
+        ];
+        let merged = RegionMerger::new(16, 0).merge_blocks(&blocks, 50, 20);
+        assert_eq!(
+            merged,
+            [Block {
+                x: 32,
+                y: 16,
+                width: 18,
+                height: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn merged_rectangles_cover_every_dirty_tile_once() {
+        let bs = 8;
+        let (w, h) = (200u32, 120u32);
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut blocks = Vec::new();
+        for ty in 0..h.div_ceil(bs) {
+            for tx in 0..w.div_ceil(bs) {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                if state.is_multiple_of(3) {
+                    let mut b = tile(bs, tx, ty);
+                    b.width = b.width.min(w - b.x);
+                    b.height = b.height.min(h - b.y);
+                    blocks.push(b);
+                }
+            }
+        }
+
+        for waste in [0, 30, 60, 100] {
+            let merged = RegionMerger::new(bs as usize, waste).merge_blocks(&blocks, w, h);
+            assert!(merged.len() <= blocks.len());
+            let mut cover = vec![0u8; (w * h) as usize];
+            for m in &merged {
+                for y in m.y..m.y + m.height {
+                    for x in m.x..m.x + m.width {
+                        cover[(y * w + x) as usize] += 1;
+                    }
+                }
+                let dirty: u64 = blocks
+                    .iter()
+                    .filter(|b| b.x >= m.x && b.y >= m.y)
+                    .filter(|b| b.x < m.x + m.width && b.y < m.y + m.height)
+                    .map(|b| b.width as u64 * b.height as u64)
+                    .sum();
+                let total = m.width as u64 * m.height as u64;
+                assert!(within_waste(total, dirty, waste), "{waste}%: {m:?}");
+            }
+            assert!(cover.iter().all(|&c| c <= 1), "{waste}%: overlap");
+            for b in &blocks {
+                assert_eq!(cover[(b.y * w + b.x) as usize], 1, "{waste}%: {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn full_frames_are_untouched() {
+        let mut delta = DeltaFrame {
+            frame_number: 0,
+            timestamp: Instant::now(),
+            width: 64,
+            height: 64,
+            changed_blocks: vec![tile(64, 0, 0)],
+            full_frame: true,
+        };
+        RegionMerger::new(8, 100).merge(&mut delta);
+        assert_eq!(delta.changed_blocks, [tile(64, 0, 0)]);
+
+        delta.full_frame = false;
+        delta.changed_blocks = vec![tile(8, 0, 0), tile(8, 1, 0)];
+        RegionMerger::new(8, 0).merge(&mut delta);
+        assert_eq!(delta.changed_blocks.len(), 1);
+    }
+}
@@ -18,6 +18,7 @@
 //! └─────────────────────────┘                └──────────────────────┘
 //!
 //! Input: Master ──[MouseEvent/KeyEvent]──► Slave InputInjector
+//!        Master ──[PointerMove over UDP]──► Slave InputInjector (opt-in)
 //! ```
 //!
 //! ## Sub-modules
@@ -27,34 +28,70 @@
 //! | `types`      | Shared frame / pixel types used across the pipeline |
 //! | `capture`    | DXGI Desktop Duplication screen capture (Windows) |
 //! | `delta`      | Block-level change detection between frames       |
+//! | `gpu`        | Tile diffing and packing on the GPU before readback |
+//! | `merge`      | Coalesces dirty tiles into larger rectangles      |
+//! | `convert`    | BGRA ↔ RGB565 / grayscale for reduced-colour links |
 //! | `encoder`    | Adaptive zstd-based frame encoder                 |
 //! | `decoder`    | Frame decoder / decompressor                      |
+//! | `dictionary` | Trained zstd dictionaries shared by both ends     |
 //! | `transport`  | UDP transport with chunked framing                |
+//! | `mtu`        | Path-MTU probing at session start                 |
+//! | `preflight`  | Checks UDP gets through before a session goes live |
+//! | `desktop`    | Secure-desktop (UAC) detection and stream status  |
+//! | `doctor`     | Self-test of capture, input, UDP and disk prerequisites |
 //! | `input`      | Win32 `SendInput` mouse / keyboard injection      |
+//! | `pointer`    | Latest-wins pointer moves over the screen socket  |
+//! | `burst`      | Drops pointer moves that later input has overtaken |
 //! | `bandwidth`  | Bandwidth estimator for adaptive quality           |
 //! | `service`    | Slave-side capture service orchestrator            |
+//! | `watchdog`   | Rebuilds a capturer that stopped producing frames  |
+//! | `throttle`   | CPU budget and background priority for the slave   |
+//! | `thumbnail`  | Periodic JPEG thumbnails for low-bandwidth links   |
+//! | `telemetry`  | Frame pipeline metrics (Prometheus, feature `metrics`) |
 //! | `client`     | Master-side frame consumer                        |
 
 pub mod bandwidth;
+pub mod burst;
 pub mod capture;
 pub mod client;
+pub mod convert;
 pub mod decoder;
 pub mod delta;
+pub mod desktop;
+pub mod dictionary;
+pub mod doctor;
 pub mod encoder;
+pub mod gpu;
 pub mod input;
+pub mod merge;
+pub mod mtu;
+pub mod pointer;
+pub mod preflight;
 pub mod service;
+pub mod telemetry;
+pub mod throttle;
+pub mod thumbnail;
 pub mod transport;
 pub mod types;
+pub mod watchdog;
 
 // ── Re-exports ───────────────────────────────────────────────────
 
 pub use bandwidth::BandwidthEstimator;
-pub use capture::DxgiCapturer;
-pub use client::ScreenClient;
-pub use decoder::FrameDecoder;
-pub use delta::{Block, DeltaDetector, DeltaFrame};
+pub use burst::{InputBurst, TimedInput};
+pub use capture::{DxgiCapturer, FrameSource};
+pub use client::{ScreenClient, SnapshotFrame, SnapshotHandle, TimedFrame};
+pub use decoder::{DirtyRect, FrameDecoder};
+pub use delta::{Block, DeltaDetector, DeltaFrame, DeltaStrategy};
+pub use desktop::{DesktopProbe, InputDesktop, ScreenStatus, SecureDesktopDetector};
 pub use encoder::{AdaptiveEncoder, EncodedFrame};
-pub use input::InputInjector;
+pub use gpu::{GpuPrepMode, PreparedTiles};
+pub use input::{InjectionBreaker, InputGate, InputInjector};
+pub use merge::RegionMerger;
+pub use pointer::{PointerFilter, PointerMove, PointerSender};
 pub use service::{ScreenService, ScreenServiceConfig};
-pub use transport::{ChunkHeader, FrameHeader, ScreenTransport};
+pub use throttle::{CpuThrottle, Priority, ThrottleState};
+pub use thumbnail::{ForegroundInfo, SharingSwitch, Thumbnail};
+pub use transport::{ChunkHeader, FrameHeader, ScreenMessage, ScreenTransport, TrafficCounter};
 pub use types::{PixelFormat, RawScreenFrame};
+pub use watchdog::CaptureWatchdog;
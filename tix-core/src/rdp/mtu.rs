@@ -0,0 +1,326 @@
+//! Path-MTU probing for the screen stream.
+//!
+//! Frames are cut into datagrams of at most the transport MTU and the
+//! receiver sizes its buffer from the same number, so both ends must
+//! agree on it before the first frame. At session start the sender may
+//! measure it: padded probe datagrams go out largest first with the
+//! don't-fragment bit set, the receiver echoes a short ack for each one
+//! that arrives, and the largest acked size wins. If nothing is acked
+//! within [`PROBE_TIMEOUT`] the stream uses [`DEFAULT_MTU`].
+//!
+//! ```text
+//! probe: magic "TXMP" (4) | size u32 (4) | zero padding up to `size`
+//! ack:   magic "TXMA" (4) | size u32 (4)
+//! ```
+//!
+//! Sizes are UDP payload bytes, like the transport MTU itself.
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use crate::rdp::transport::DEFAULT_MTU;
+
+/// Candidate sizes, largest first: a 9000-byte jumbo frame, Ethernet,
+/// the historical default, a typical VPN/PPPoE path and the IPv6
+/// minimum link MTU, each minus the IP and UDP headers.
+pub const PROBE_SIZES: [usize; 5] = [8972, 1472, DEFAULT_MTU, 1372, 1252];
+
+/// How long the prober waits for acks.
+pub const PROBE_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Each size is sent this many times so one lost datagram does not
+/// rule it out.
+const PROBE_ROUNDS: usize = 2;
+
+const PROBE_MAGIC: &[u8; 4] = b"TXMP";
+const ACK_MAGIC: &[u8; 4] = b"TXMA";
+const PREFIX_LEN: usize = 8;
+
+// ── Datagrams ────────────────────────────────────────────────────
+
+/// A probe datagram of exactly `size` bytes.
+pub fn probe_datagram(size: usize) -> Vec<u8> {
+    let mut data = vec![0u8; size.max(PREFIX_LEN)];
+    data[..4].copy_from_slice(PROBE_MAGIC);
+    data[4..8].copy_from_slice(&(size as u32).to_le_bytes());
+    data
+}
+
+/// Whether `data` is a probe rather than screen data.
+pub fn is_probe(data: &[u8]) -> bool {
+    data.len() >= PREFIX_LEN && data[..4] == *PROBE_MAGIC
+}
+
+/// The ack to send back for a probe, or `None` if `data` is not one.
+/// A truncated probe is not acked: its size did not make it through.
+pub fn probe_ack(data: &[u8]) -> Option<[u8; PREFIX_LEN]> {
+    if !is_probe(data) {
+        return None;
+    }
+    let size = u32::from_le_bytes(data[4..8].try_into().unwrap());
+    if data.len() != size as usize {
+        return None;
+    }
+    let mut ack = [0u8; PREFIX_LEN];
+    ack[..4].copy_from_slice(ACK_MAGIC);
+    ack[4..].copy_from_slice(&size.to_le_bytes());
+    Some(ack)
+}
+
+fn acked_size(data: &[u8]) -> Option<usize> {
+    if data.len() != PREFIX_LEN || data[..4] != *ACK_MAGIC {
+        return None;
+    }
+    Some(u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize)
+}
+
+// ── MtuProbe ─────────────────────────────────────────────────────
+
+/// Prober state: the sizes to try and the largest one acked so far.
+#[derive(Debug, Clone)]
+pub struct MtuProbe {
+    sizes: Vec<usize>,
+    best: Option<usize>,
+}
+
+impl MtuProbe {
+    /// Probe every [`PROBE_SIZES`] entry up to `max`.
+    pub fn new(max: usize) -> Self {
+        Self::with_sizes(PROBE_SIZES.iter().copied().filter(|&s| s <= max))
+    }
+
+    /// Probe the given sizes.
+    pub fn with_sizes(sizes: impl IntoIterator<Item = usize>) -> Self {
+        let mut sizes: Vec<usize> = sizes.into_iter().filter(|&s| s >= PREFIX_LEN).collect();
+        sizes.sort_unstable_by(|a, b| b.cmp(a));
+        sizes.dedup();
+        Self { sizes, best: None }
+    }
+
+    /// Sizes in send order, largest first.
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    /// Feed a datagram received from the peer. Returns `true` once the
+    /// largest size is acked and there is nothing left to wait for.
+    pub fn on_datagram(&mut self, data: &[u8]) -> bool {
+        if let Some(size) = acked_size(data)
+            && self.sizes.contains(&size)
+        {
+            self.best = self.best.max(Some(size));
+        }
+        self.is_done()
+    }
+
+    /// Whether the largest candidate has been acked.
+    pub fn is_done(&self) -> bool {
+        self.best.is_some() && self.best == self.sizes.first().copied()
+    }
+
+    /// The largest acked size, or [`DEFAULT_MTU`] if none was.
+    pub fn result(&self) -> usize {
+        self.best.unwrap_or(DEFAULT_MTU)
+    }
+}
+
+// ── Async drivers ────────────────────────────────────────────────
+
+/// Measure the path MTU towards `peer`, trying sizes up to `max`. The
+/// peer must be running [`answer_probes`] on the socket it receives
+/// screen data on.
+pub async fn probe(socket: &UdpSocket, peer: SocketAddr, max: usize) -> usize {
+    if let Err(e) = set_dont_fragment(socket) {
+        tracing::debug!("cannot set don't-fragment, probing anyway: {e}");
+    }
+    let mut state = MtuProbe::new(max);
+    for _ in 0..PROBE_ROUNDS {
+        for &size in state.sizes() {
+            // Too big for the local interface: the send fails outright.
+            let _ = socket.send_to(&probe_datagram(size), peer).await;
+        }
+    }
+
+    let mut buf = [0u8; 64];
+    let wait = async {
+        loop {
+            match socket.recv_from(&mut buf).await {
+                Ok((len, from)) if from == peer => {
+                    if state.on_datagram(&buf[..len]) {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::debug!("MTU probe receive error: {e}");
+                    return;
+                }
+            }
+        }
+    };
+    let _ = tokio::time::timeout(PROBE_TIMEOUT, wait).await;
+    state.result()
+}
+
+/// Ack every probe arriving on `socket` until an I/O error. Run this
+/// on the receiving socket while the peer negotiates the session.
+pub async fn answer_probes(socket: &UdpSocket) -> io::Result<()> {
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).await?;
+        if let Some(ack) = probe_ack(&buf[..len]) {
+            socket.send_to(&ack, from).await?;
+        }
+    }
+}
+
+/// Ask the OS not to fragment datagrams sent from `socket`, so an
+/// oversized probe is dropped instead of reassembled. Only IPv4 sockets
+/// on Linux and Windows are touched; elsewhere this is a no-op.
+pub fn set_dont_fragment(socket: &UdpSocket) -> io::Result<()> {
+    if !socket.local_addr()?.is_ipv4() {
+        return Ok(());
+    }
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        let value: libc::c_int = libc::IP_PMTUDISC_DO;
+        // SAFETY: valid socket descriptor and a c_int option value of
+        // the advertised size.
+        let rc = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_IP,
+                libc::IP_MTU_DISCOVER,
+                &value as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::io::AsRawSocket;
+        use windows::Win32::Networking::WinSock::{
+            IP_DONTFRAGMENT, IPPROTO_IP, SOCKET, setsockopt,
+        };
+        let value = 1u32.to_ne_bytes();
+        let socket = SOCKET(socket.as_raw_socket() as usize);
+        // SAFETY: valid socket handle and a DWORD option value.
+        let rc = unsafe { setsockopt(socket, IPPROTO_IP.0, IP_DONTFRAGMENT, Some(&value)) };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A path that silently drops datagrams above `mtu` and the first
+    /// `lost_acks` acks coming back.
+    struct LossyPath {
+        mtu: usize,
+        lost_acks: usize,
+    }
+
+    impl LossyPath {
+        /// Deliver `datagram`; returns the ack that makes it back.
+        fn send(&mut self, datagram: &[u8]) -> Option<[u8; PREFIX_LEN]> {
+            if datagram.len() > self.mtu {
+                return None;
+            }
+            let ack = probe_ack(datagram)?;
+            if self.lost_acks > 0 {
+                self.lost_acks -= 1;
+                return None;
+            }
+            Some(ack)
+        }
+    }
+
+    fn run(state: &mut MtuProbe, path: &mut LossyPath) {
+        for _ in 0..PROBE_ROUNDS {
+            for size in state.sizes().to_vec() {
+                if let Some(ack) = path.send(&probe_datagram(size)) {
+                    state.on_datagram(&ack);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn datagrams_roundtrip() {
+        let probe = probe_datagram(1472);
+        assert_eq!(probe.len(), 1472);
+        assert!(is_probe(&probe));
+        let ack = probe_ack(&probe).unwrap();
+        assert_eq!(acked_size(&ack), Some(1472));
+        // Truncated on the way: not acked.
+        assert!(probe_ack(&probe[..1400]).is_none());
+        assert!(!is_probe(b"TXM"));
+    }
+
+    #[test]
+    fn picks_largest_size_the_path_carries() {
+        let mut state = MtuProbe::new(usize::MAX);
+        let mut path = LossyPath { mtu: 1472, lost_acks: 0 };
+        run(&mut state, &mut path);
+        assert_eq!(state.result(), 1472);
+        assert!(!state.is_done());
+
+        let mut state = MtuProbe::new(usize::MAX);
+        let mut path = LossyPath { mtu: 9000, lost_acks: 0 };
+        run(&mut state, &mut path);
+        assert_eq!(state.result(), 8972);
+        assert!(state.is_done());
+    }
+
+    #[test]
+    fn survives_lost_acks() {
+        // The whole first round of acks is lost; the second gets through.
+        let mut state = MtuProbe::new(usize::MAX);
+        let mut path = LossyPath { mtu: 1380, lost_acks: 2 };
+        run(&mut state, &mut path);
+        assert_eq!(state.result(), 1372);
+    }
+
+    #[test]
+    fn falls_back_when_nothing_is_acked() {
+        let mut state = MtuProbe::new(usize::MAX);
+        let mut path = LossyPath { mtu: 1000, lost_acks: 0 };
+        run(&mut state, &mut path);
+        assert_eq!(state.result(), DEFAULT_MTU);
+        // Acks for sizes never probed are ignored.
+        let mut capped = MtuProbe::new(1472);
+        assert_eq!(capped.sizes(), [1472, 1400, 1372, 1252]);
+        let bogus = probe_ack(&probe_datagram(8972)).unwrap();
+        capped.on_datagram(&bogus);
+        assert_eq!(capped.result(), DEFAULT_MTU);
+    }
+
+    #[tokio::test]
+    async fn probes_over_loopback() {
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let peer = receiver.local_addr().unwrap();
+        let responder = tokio::spawn(async move { answer_probes(&receiver).await });
+
+        assert_eq!(probe(&sender, peer, 1472).await, 1472);
+        responder.abort();
+
+        // Nobody answering: the default after the timeout.
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let silent_addr = silent.local_addr().unwrap();
+        assert_eq!(probe(&sender, silent_addr, usize::MAX).await, DEFAULT_MTU);
+    }
+}
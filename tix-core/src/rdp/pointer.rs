@@ -0,0 +1,205 @@
+//! Pointer moves over the screen socket.
+//!
+//! A viewer that negotiated it at `ScreenStart` (see
+//! [`ScreenStartRequest::udp_input`](crate::protocol::ScreenStartRequest::udp_input))
+//! sends mouse moves as datagrams to the UDP endpoint frames come from,
+//! instead of queueing them behind everything else on the control
+//! connection. Moves are the only input that travels this way: a lost
+//! or late position is made obsolete by the next one, so the slave
+//! keeps the newest and drops anything older ([`PointerFilter`]).
+//! Buttons, the wheel and keys stay on the control connection, where
+//! their order is guaranteed.
+//!
+//! ## Wire format
+//!
+//! **Pointer datagram** (17 bytes, little-endian):
+//! ```text
+//! magic:     [u8; 4]  "TXPM"
+//! session:   u8       screen session the move belongs to
+//! sequence:  u32      per-session counter, wrapping
+//! x:         i32      position on the slave's virtual desktop
+//! y:         i32
+//! ```
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use tokio::net::UdpSocket;
+
+use crate::error::TixError;
+use crate::rdp::transport::TrafficCounter;
+
+const POINTER_MAGIC: &[u8; 4] = b"TXPM";
+
+// ── PointerMove ──────────────────────────────────────────────────
+
+/// One pointer position sent viewer → slave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerMove {
+    pub session: u8,
+    pub sequence: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl PointerMove {
+    /// Encoded size on the wire.
+    pub const SIZE: usize = 17;
+
+    /// Serialize to a datagram.
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        buf[0..4].copy_from_slice(POINTER_MAGIC);
+        buf[4] = self.session;
+        buf[5..9].copy_from_slice(&self.sequence.to_le_bytes());
+        buf[9..13].copy_from_slice(&self.x.to_le_bytes());
+        buf[13..17].copy_from_slice(&self.y.to_le_bytes());
+        buf
+    }
+
+    /// Decode a pointer datagram; `None` if `data` is not one.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if !is_pointer(data) {
+            return None;
+        }
+        Some(Self {
+            session: data[4],
+            sequence: u32::from_le_bytes(data[5..9].try_into().unwrap()),
+            x: i32::from_le_bytes(data[9..13].try_into().unwrap()),
+            y: i32::from_le_bytes(data[13..17].try_into().unwrap()),
+        })
+    }
+}
+
+/// Whether `data` is a pointer datagram rather than screen data.
+pub fn is_pointer(data: &[u8]) -> bool {
+    data.len() == PointerMove::SIZE && data[..4] == *POINTER_MAGIC
+}
+
+// ── PointerFilter ────────────────────────────────────────────────
+
+/// Latest-wins filter for one session's pointer datagrams.
+///
+/// Sequence numbers are compared with wraparound, so a stream that runs
+/// past `u32::MAX` keeps working.
+#[derive(Debug, Default)]
+pub struct PointerFilter {
+    last: Option<u32>,
+    stale: u64,
+}
+
+impl PointerFilter {
+    /// Whether the move numbered `sequence` is newer than everything
+    /// seen so far. Duplicates and late arrivals are counted and
+    /// refused.
+    pub fn admit(&mut self, sequence: u32) -> bool {
+        let newer = match self.last {
+            None => true,
+            Some(last) => (sequence.wrapping_sub(last) as i32) > 0,
+        };
+        if newer {
+            self.last = Some(sequence);
+        } else {
+            self.stale += 1;
+        }
+        newer
+    }
+
+    /// Moves refused for being out of date.
+    pub fn stale(&self) -> u64 {
+        self.stale
+    }
+}
+
+// ── PointerSender ────────────────────────────────────────────────
+
+/// Viewer-side handle that sends pointer moves for one session.
+///
+/// Obtained from [`ScreenTransport::pointer_sender`](crate::rdp::ScreenTransport::pointer_sender);
+/// it shares the transport's socket and traffic counters, so it keeps
+/// working after the transport has moved into a
+/// [`ScreenClient`](crate::rdp::ScreenClient).
+#[derive(Debug)]
+pub struct PointerSender {
+    socket: Arc<UdpSocket>,
+    remote_addr: SocketAddr,
+    session: u8,
+    sequence: AtomicU32,
+    traffic: TrafficCounter,
+}
+
+impl PointerSender {
+    pub(crate) fn new(
+        socket: Arc<UdpSocket>,
+        remote_addr: SocketAddr,
+        session: u8,
+        traffic: TrafficCounter,
+    ) -> Self {
+        Self {
+            socket,
+            remote_addr,
+            session,
+            sequence: AtomicU32::new(0),
+            traffic,
+        }
+    }
+
+    /// Send the pointer position `(x, y)`.
+    pub async fn send(&self, x: i32, y: i32) -> Result<(), TixError> {
+        let datagram = PointerMove {
+            session: self.session,
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+            x,
+            y,
+        }
+        .encode();
+        self.socket
+            .send_to(&datagram, self.remote_addr)
+            .await
+            .map_err(|e| TixError::Other(format!("UDP send pointer: {e}")))?;
+        self.traffic.record_sent(datagram.len());
+        Ok(())
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn datagram_roundtrip() {
+        let pointer = PointerMove {
+            session: 3,
+            sequence: 0xDEAD_BEEF,
+            x: -1920,
+            y: 1079,
+        };
+        let data = pointer.encode();
+        assert!(is_pointer(&data));
+        assert_eq!(PointerMove::decode(&data), Some(pointer));
+
+        assert!(PointerMove::decode(&data[..16]).is_none());
+        assert!(PointerMove::decode(b"TXST\0\0\0\0\0\0\0\0\0\0\0\0\0").is_none());
+    }
+
+    #[test]
+    fn stale_positions_are_dropped() {
+        let mut filter = PointerFilter::default();
+        assert!(filter.admit(5));
+        assert!(filter.admit(7));
+        // 6 was overtaken by 7, and 7 is already applied.
+        assert!(!filter.admit(6));
+        assert!(!filter.admit(7));
+        assert!(filter.admit(8));
+        assert_eq!(filter.stale(), 2);
+
+        // Wraparound: u32::MAX is older than 0.
+        let mut filter = PointerFilter::default();
+        assert!(filter.admit(u32::MAX - 1));
+        assert!(filter.admit(1));
+        assert!(!filter.admit(u32::MAX));
+    }
+}
@@ -0,0 +1,497 @@
+//! UDP preflight for the screen stream.
+//!
+//! A working control connection says nothing about UDP: a firewall or
+//! NAT that drops the slave's datagrams leaves the viewer waiting on a
+//! black window. So before a session goes live the slave sends
+//! [`PREFLIGHT_PROBES`] small probes to the viewer's UDP port and the
+//! viewer echoes each one, quoting the address it saw it come from.
+//! The slave reports its side in the `ScreenStart` ack, the viewer
+//! its own in `ScreenPreflight`, and [`assess`] turns the two into a
+//! verdict: the stream works, works but input over UDP will not, or
+//! never arrives.
+//!
+//! ```text
+//! probe: magic "TXPF" (4) | seq u16 (2) | count u16 (2)
+//! echo:  magic "TXPE" (4) | seq u16 (2) | port u16 (2) | source IP (4 or 16)
+//! ```
+//!
+//! Probes are shorter than any frame or chunk header, so a receiver
+//! that gets one late drops it like any other stray datagram.
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use crate::protocol::screen::PreflightReport;
+use crate::rdp::mtu;
+
+/// Probes the slave sends per session.
+pub const PREFLIGHT_PROBES: u16 = 8;
+
+/// Gap between two probes, so a burst limit or a queue that is briefly
+/// full does not take all of them.
+pub const PROBE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long the slave waits for echoes after the last probe.
+pub const ECHO_TIMEOUT: Duration = Duration::from_millis(400);
+
+/// Most probes one preflight can tell apart.
+const MAX_PROBES: u16 = 64;
+
+const PROBE_MAGIC: &[u8; 4] = b"TXPF";
+const ECHO_MAGIC: &[u8; 4] = b"TXPE";
+const PROBE_LEN: usize = 8;
+
+// ── Datagrams ────────────────────────────────────────────────────
+
+/// Probe `seq` of `count`.
+pub fn probe_datagram(seq: u16, count: u16) -> [u8; PROBE_LEN] {
+    let mut data = [0u8; PROBE_LEN];
+    data[..4].copy_from_slice(PROBE_MAGIC);
+    data[4..6].copy_from_slice(&seq.to_le_bytes());
+    data[6..8].copy_from_slice(&count.to_le_bytes());
+    data
+}
+
+fn parse_probe(data: &[u8]) -> Option<u16> {
+    if data.len() != PROBE_LEN || data[..4] != *PROBE_MAGIC {
+        return None;
+    }
+    let seq = u16::from_le_bytes([data[4], data[5]]);
+    (seq < MAX_PROBES).then_some(seq)
+}
+
+/// Whether `data` is a preflight probe.
+pub fn is_probe(data: &[u8]) -> bool {
+    parse_probe(data).is_some()
+}
+
+/// The echo for a probe that arrived from `from`, or `None` if `data`
+/// is not a probe.
+pub fn echo_datagram(data: &[u8], from: SocketAddr) -> Option<Vec<u8>> {
+    let seq = parse_probe(data)?;
+    let mut echo = Vec::with_capacity(24);
+    echo.extend_from_slice(ECHO_MAGIC);
+    echo.extend_from_slice(&seq.to_le_bytes());
+    echo.extend_from_slice(&from.port().to_le_bytes());
+    match from.ip() {
+        IpAddr::V4(ip) => echo.extend_from_slice(&ip.octets()),
+        IpAddr::V6(ip) => echo.extend_from_slice(&ip.octets()),
+    }
+    Some(echo)
+}
+
+/// The probe an echo answers and the address the probe was seen from.
+fn parse_echo(data: &[u8]) -> Option<(u16, SocketAddr)> {
+    if data.len() < 8 || data[..4] != *ECHO_MAGIC {
+        return None;
+    }
+    let seq = u16::from_le_bytes([data[4], data[5]]);
+    let port = u16::from_le_bytes([data[6], data[7]]);
+    let ip = match &data[8..] {
+        &[a, b, c, d] => IpAddr::V4(Ipv4Addr::new(a, b, c, d)),
+        v6 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(v6).ok()?)),
+    };
+    (seq < MAX_PROBES).then_some((seq, SocketAddr::new(ip, port)))
+}
+
+// ── Viewer side ──────────────────────────────────────────────────
+
+/// The probes the viewer has seen, each counted once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProbeTally {
+    seen: u64,
+    /// Where the last probe came from.
+    pub source: Option<SocketAddr>,
+}
+
+impl ProbeTally {
+    /// Count `data` if it is a probe and return the echo to send back.
+    pub fn on_datagram(&mut self, data: &[u8], from: SocketAddr) -> Option<Vec<u8>> {
+        let seq = parse_probe(data)?;
+        self.seen |= 1 << seq;
+        self.source = Some(from);
+        echo_datagram(data, from)
+    }
+
+    /// Distinct probes seen.
+    pub fn received(&self) -> u16 {
+        self.seen.count_ones() as u16
+    }
+}
+
+/// Answer preflight and MTU probes arriving on `socket` until an I/O
+/// error, counting the preflight ones into `tally`. Run this on the
+/// screen socket while waiting for the `ScreenStart` ack.
+pub async fn answer_probes(socket: &UdpSocket, tally: &mut ProbeTally) -> io::Result<()> {
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).await?;
+        if let Some(echo) = tally.on_datagram(&buf[..len], from) {
+            socket.send_to(&echo, from).await?;
+        } else if let Some(ack) = mtu::probe_ack(&buf[..len]) {
+            socket.send_to(&ack, from).await?;
+        }
+    }
+}
+
+// ── Slave side ───────────────────────────────────────────────────
+
+/// Prober state: which probes have been echoed, and from where.
+#[derive(Debug, Clone)]
+pub struct Preflight {
+    count: u16,
+    echoed: u64,
+    seen_as: Option<SocketAddr>,
+    echoed_from: Option<SocketAddr>,
+}
+
+impl Preflight {
+    /// Send `count` probes (at most 64).
+    pub fn new(count: u16) -> Self {
+        Self {
+            count: count.min(MAX_PROBES),
+            echoed: 0,
+            seen_as: None,
+            echoed_from: None,
+        }
+    }
+
+    /// Probes to send.
+    pub fn count(&self) -> u16 {
+        self.count
+    }
+
+    /// Feed a datagram received from `from`. Returns `true` once every
+    /// probe has been echoed.
+    pub fn on_datagram(&mut self, data: &[u8], from: SocketAddr) -> bool {
+        if let Some((seq, seen_as)) = parse_echo(data)
+            && seq < self.count
+        {
+            self.echoed |= 1 << seq;
+            self.seen_as = Some(seen_as);
+            self.echoed_from = Some(from);
+        }
+        self.is_done()
+    }
+
+    /// Whether every probe has been echoed.
+    pub fn is_done(&self) -> bool {
+        self.echoed.count_ones() == u32::from(self.count)
+    }
+
+    /// What the slave saw.
+    pub fn report(&self) -> PreflightReport {
+        PreflightReport {
+            sent: self.count,
+            echoed: self.echoed.count_ones() as u16,
+            seen_as: self.seen_as,
+            echoed_from: self.echoed_from,
+        }
+    }
+}
+
+/// Probe the viewer at `peer` and collect its echoes. The viewer must
+/// be running [`answer_probes`] on the socket it receives screen data
+/// on. Send errors count as lost probes.
+pub async fn run(socket: &UdpSocket, peer: SocketAddr, count: u16) -> PreflightReport {
+    let mut state = Preflight::new(count);
+    for seq in 0..state.count() {
+        let _ = socket.send_to(&probe_datagram(seq, state.count()), peer).await;
+        let _ = tokio::time::timeout(PROBE_INTERVAL, collect_echoes(socket, &mut state)).await;
+    }
+    if !state.is_done() {
+        let _ = tokio::time::timeout(ECHO_TIMEOUT, collect_echoes(socket, &mut state)).await;
+    }
+    state.report()
+}
+
+/// Feed datagrams into `state` until every probe is echoed.
+async fn collect_echoes(socket: &UdpSocket, state: &mut Preflight) {
+    let mut buf = [0u8; 64];
+    loop {
+        match socket.recv_from(&mut buf).await {
+            Ok((len, from)) => {
+                if state.on_datagram(&buf[..len], from) {
+                    return;
+                }
+            }
+            // ICMP port unreachable surfaces here on some systems.
+            Err(e) => tracing::debug!("preflight receive error: {e}"),
+        }
+    }
+}
+
+// ── Assessment ───────────────────────────────────────────────────
+
+/// What the preflight says about the screen stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreflightVerdict {
+    /// Probes got through both ways.
+    Clear,
+    /// Both ways work, but a quarter or more of the probes were lost.
+    Lossy,
+    /// The slave's datagrams arrive but nothing gets back to it: frames
+    /// will show, pointer moves over UDP and MTU probes will not work.
+    OneWay,
+    /// None of the slave's datagrams arrived: no frame ever would.
+    Blocked,
+}
+
+/// Both sides of one preflight, judged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightAssessment {
+    pub verdict: PreflightVerdict,
+    /// Probes the slave sent.
+    pub sent: u16,
+    /// Probes that reached the viewer.
+    pub received: u16,
+    /// Echoes that reached the slave.
+    pub echoed: u16,
+    /// The slave's datagrams arrived from another address than the one
+    /// it advertised: there is NAT on its side of the path.
+    pub translated_from: Option<SocketAddr>,
+    /// Viewer UDP port the probes were sent to.
+    pub port: u16,
+}
+
+/// Judge the slave's `report` against the viewer's `tally`. `advertised`
+/// is the slave's UDP endpoint from the ack, with any wildcard IP
+/// already replaced; `port` the viewer's UDP port.
+pub fn assess(
+    report: &PreflightReport,
+    tally: &ProbeTally,
+    advertised: SocketAddr,
+    port: u16,
+) -> PreflightAssessment {
+    let received = tally.received();
+    let verdict = if received == 0 {
+        PreflightVerdict::Blocked
+    } else if report.echoed == 0 {
+        PreflightVerdict::OneWay
+    } else if u32::from(report.echoed) * 4 < u32::from(report.sent) * 3 {
+        PreflightVerdict::Lossy
+    } else {
+        PreflightVerdict::Clear
+    };
+    PreflightAssessment {
+        verdict,
+        sent: report.sent,
+        received,
+        echoed: report.echoed,
+        translated_from: tally.source.filter(|source| *source != advertised),
+        port,
+    }
+}
+
+impl fmt::Display for PreflightAssessment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.verdict {
+            PreflightVerdict::Clear => write!(
+                f,
+                "UDP clear: {}/{} probes arrived, {} echoed",
+                self.received, self.sent, self.echoed
+            )?,
+            PreflightVerdict::Lossy => write!(
+                f,
+                "UDP lossy: {}/{} probes arrived, {} echoed; expect dropped frames",
+                self.received, self.sent, self.echoed
+            )?,
+            PreflightVerdict::OneWay => write!(
+                f,
+                "UDP one-way: {}/{} probes arrived but no echo reached the slave; \
+                 allow inbound UDP on the slave to use pointer moves over UDP",
+                self.received, self.sent
+            )?,
+            PreflightVerdict::Blocked => write!(
+                f,
+                "UDP blocked: none of {} probes from the slave arrived. Allow inbound \
+                 UDP on port {} in this machine's firewall, and outbound UDP on the slave",
+                self.sent, self.port
+            )?,
+        }
+        if let Some(source) = self.translated_from {
+            write!(f, " (NAT: slave seen as {})", source)?;
+        }
+        Ok(())
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(sent: u16, echoed: u16) -> PreflightReport {
+        PreflightReport {
+            sent,
+            echoed,
+            seen_as: None,
+            echoed_from: None,
+        }
+    }
+
+    fn tally(received: u16, source: SocketAddr) -> ProbeTally {
+        let mut tally = ProbeTally::default();
+        for seq in 0..received {
+            tally.on_datagram(&probe_datagram(seq, received), source);
+        }
+        tally
+    }
+
+    /// Forwards datagrams to `to` and drops everything `to` sends back,
+    /// like a firewall that lets the slave's stream out but nothing in.
+    async fn one_way_relay(relay: UdpSocket, to: SocketAddr) {
+        let mut buf = [0u8; 2048];
+        while let Ok((len, from)) = relay.recv_from(&mut buf).await {
+            if from != to {
+                let _ = relay.send_to(&buf[..len], to).await;
+            }
+        }
+    }
+
+    #[test]
+    fn datagrams_roundtrip() {
+        let probe = probe_datagram(3, 8);
+        assert!(is_probe(&probe));
+        assert!(!mtu::is_probe(&probe));
+        assert!(!is_probe(&probe_datagram(MAX_PROBES, 8)));
+
+        for from in ["192.168.1.20:50123", "[fe80::1]:7332"] {
+            let from: SocketAddr = from.parse().unwrap();
+            let echo = echo_datagram(&probe, from).unwrap();
+            assert_eq!(parse_echo(&echo), Some((3, from)));
+        }
+        assert!(echo_datagram(b"TXMP\x00\x00\x00\x00", "127.0.0.1:1".parse().unwrap()).is_none());
+        assert!(parse_echo(b"TXPE\x03\x00\x01\x00\x7f").is_none());
+    }
+
+    #[test]
+    fn duplicates_are_counted_once() {
+        let from: SocketAddr = "10.0.0.5:50000".parse().unwrap();
+        let mut tally = ProbeTally::default();
+        tally.on_datagram(&probe_datagram(1, 8), from);
+        tally.on_datagram(&probe_datagram(1, 8), from);
+        assert!(tally.on_datagram(b"hello", from).is_none());
+        assert_eq!(tally.received(), 1);
+
+        let mut state = Preflight::new(2);
+        let echo = echo_datagram(&probe_datagram(0, 2), from).unwrap();
+        assert!(!state.on_datagram(&echo, from));
+        assert!(!state.on_datagram(&echo, from));
+        let echo = echo_datagram(&probe_datagram(1, 2), from).unwrap();
+        assert!(state.on_datagram(&echo, from));
+        assert_eq!(state.report().echoed, 2);
+        assert_eq!(state.report().seen_as, Some(from));
+    }
+
+    #[test]
+    fn verdicts() {
+        let slave: SocketAddr = "10.0.0.5:50000".parse().unwrap();
+        let judge = |echoed, received| {
+            assess(&report(8, echoed), &tally(received, slave), slave, 7332).verdict
+        };
+        assert_eq!(judge(8, 8), PreflightVerdict::Clear);
+        assert_eq!(judge(6, 7), PreflightVerdict::Clear);
+        assert_eq!(judge(5, 8), PreflightVerdict::Lossy);
+        assert_eq!(judge(0, 8), PreflightVerdict::OneWay);
+        assert_eq!(judge(0, 0), PreflightVerdict::Blocked);
+
+        let blocked = assess(&report(8, 0), &ProbeTally::default(), slave, 7332);
+        let text = blocked.to_string();
+        assert!(text.starts_with("UDP blocked"), "{text}");
+        assert!(text.contains("port 7332"), "{text}");
+
+        let public: SocketAddr = "203.0.113.9:61000".parse().unwrap();
+        let natted = assess(&report(8, 8), &tally(8, public), slave, 7332);
+        assert_eq!(natted.verdict, PreflightVerdict::Clear);
+        assert_eq!(natted.translated_from, Some(public));
+        assert!(natted.to_string().ends_with("(NAT: slave seen as 203.0.113.9:61000)"));
+    }
+
+    #[tokio::test]
+    async fn preflight_over_loopback() {
+        let slave = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let viewer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let (slave_addr, viewer_addr) = (slave.local_addr().unwrap(), viewer.local_addr().unwrap());
+
+        let mut tally = ProbeTally::default();
+        let report = tokio::select! {
+            report = run(&slave, viewer_addr, PREFLIGHT_PROBES) => report,
+            Err(e) = answer_probes(&viewer, &mut tally) => panic!("viewer socket: {e}"),
+        };
+        assert_eq!(report.sent, PREFLIGHT_PROBES);
+        assert_eq!(report.echoed, PREFLIGHT_PROBES);
+        assert_eq!(report.seen_as, Some(slave_addr));
+        assert_eq!(report.echoed_from, Some(viewer_addr));
+
+        let verdict = assess(&report, &tally, slave_addr, viewer_addr.port());
+        assert_eq!(verdict.verdict, PreflightVerdict::Clear);
+        assert_eq!(verdict.received, PREFLIGHT_PROBES);
+        assert_eq!(verdict.translated_from, None);
+    }
+
+    #[tokio::test]
+    async fn one_way_path_over_loopback() {
+        let slave = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let viewer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let relay = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let (slave_addr, viewer_addr) = (slave.local_addr().unwrap(), viewer.local_addr().unwrap());
+        let relay_addr = relay.local_addr().unwrap();
+        let relay = tokio::spawn(one_way_relay(relay, viewer_addr));
+
+        let mut tally = ProbeTally::default();
+        let report = tokio::select! {
+            report = run(&slave, relay_addr, 4) => report,
+            Err(e) = answer_probes(&viewer, &mut tally) => panic!("viewer socket: {e}"),
+        };
+        relay.abort();
+        assert_eq!(report.echoed, 0);
+        assert_eq!(tally.received(), 4);
+
+        let verdict = assess(&report, &tally, slave_addr, viewer_addr.port());
+        assert_eq!(verdict.verdict, PreflightVerdict::OneWay);
+        // The relay rewrote the source, as NAT would.
+        assert_eq!(verdict.translated_from, Some(relay_addr));
+    }
+
+    #[tokio::test]
+    async fn blocked_path_over_loopback() {
+        let slave = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let viewer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let (slave_addr, viewer_addr) = (slave.local_addr().unwrap(), viewer.local_addr().unwrap());
+        // Nothing listens where the slave sends.
+        let void = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let void_addr = void.local_addr().unwrap();
+        drop(void);
+
+        let mut tally = ProbeTally::default();
+        let report = tokio::select! {
+            report = run(&slave, void_addr, 4) => report,
+            Err(e) = answer_probes(&viewer, &mut tally) => panic!("viewer socket: {e}"),
+        };
+        assert_eq!(report, PreflightReport { sent: 4, echoed: 0, seen_as: None, echoed_from: None });
+
+        let verdict = assess(&report, &tally, slave_addr, viewer_addr.port());
+        assert_eq!(verdict.verdict, PreflightVerdict::Blocked);
+        assert_eq!(verdict.received, 0);
+    }
+
+    #[tokio::test]
+    async fn mtu_probes_are_still_answered() {
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let viewer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let viewer_addr = viewer.local_addr().unwrap();
+        let mut tally = ProbeTally::default();
+        let mtu = tokio::select! {
+            mtu = mtu::probe(&sender, viewer_addr, 1472) => mtu,
+            Err(e) = answer_probes(&viewer, &mut tally) => panic!("viewer socket: {e}"),
+        };
+        assert_eq!(mtu, 1472);
+        assert_eq!(tally.received(), 0);
+    }
+}
@@ -3,24 +3,119 @@
 //! Orchestrates the full capture pipeline:
 //!
 //! 1. [`DxgiCapturer`] acquires raw frames from the desktop.
-//! 2. [`DeltaDetector`] identifies changed blocks.
+//! 2. [`DeltaDetector`] identifies changed blocks, which a
+//!    [`RegionMerger`] coalesces into larger rectangles.
 //! 3. [`AdaptiveEncoder`] compresses the delta.
 //! 4. [`ScreenTransport`] sends UDP datagrams to the master.
 //!
 //! The service runs in a Tokio task and respects a
 //! `CancellationToken`-style shutdown via its `running` flag.
+//!
+//! When the capturer reports [`TixError::CaptureLost`] (resolution
+//! change, monitor hot-plug) the service rebuilds it in place and sends
+//! a keyframe at the new size; the UDP transport is left untouched so
+//! the viewer keeps receiving on the same socket.
+//!
+//! Stalled captures are checked against the input desktop (see
+//! [`desktop`](crate::rdp::desktop)): while a UAC prompt or other
+//! secure desktop holds the screen, the viewer is sent a status
+//! datagram instead of being left on a frozen frame.
+//!
+//! A [`CaptureWatchdog`] covers the failures DXGI does not report: if
+//! no frame is captured for the stall timeout (outside a pause or a
+//! secure desktop), the capturer is rebuilt from a fresh D3D device and
+//! a keyframe follows. Repeated fruitless rebuilds back off and are
+//! reported to the viewer as [`ScreenStatus::CaptureFailed`]. Capture
+//! errors other than a timeout or lost duplication count as stalls
+//! rather than ending the session.
+//!
+//! A paused service keeps its transport and capturer but sends nothing;
+//! the first frame after resuming is a keyframe. Keyframes also go out
+//! every `keyframe_frames` frames sent, after `keyframe_interval` even
+//! when the screen is still, and in place of a delta covering
+//! `scene_change_percent` of the screen (see [`KeyframePolicy`]).
+//!
+//! With `max_cpu_percent` set, a [`CpuThrottle`] checks the process CPU
+//! time every second and caps the zstd level, then lengthens the frame
+//! interval, until usage is back under the budget.
+//!
+//! With [`with_pointer_input`](ScreenService::with_pointer_input) the
+//! service also takes [`pointer`](crate::rdp::pointer) moves from the
+//! viewer on its UDP socket and injects the newest of them, as long as
+//! the session's [`InputGate`] lets input through.
+//!
+//! While the injector's breaker holds input back (see
+//! [`input`](crate::rdp::input)), the viewer is sent a
+//! [`ScreenStatus::InputBlocked`] every second, and
+//! [`ScreenStatus::Live`] once input gets through again. Hand the
+//! service the injector the control connection uses
+//! ([`with_injector`](ScreenService::with_injector)) so its failures
+//! count too.
+//!
+//! A service given a [`FrameDictionary`] with
+//! [`with_dictionary`](ScreenService::with_dictionary) keeps sending
+//! plain frames until its
+//! [`dictionary_handle`](ScreenService::dictionary_handle) is set, which
+//! the slave does once the viewer confirms it holds the dictionary; the
+//! next frame and every one after it is compressed against it.
+//!
+//! In [low-bandwidth mode](crate::rdp::thumbnail) the service sends a
+//! thumbnail and the foreground window every `thumbnails.interval`
+//! instead of frames, spaced further apart when the target bandwidth
+//! cannot carry them that often. It starts there with `low_bandwidth`,
+//! switches on the viewer's request through its
+//! [`sharing_switch`](ScreenService::sharing_switch), and with
+//! `low_bandwidth_fallback` falls back there by itself once the stream
+//! stays over budget with the encoder out of options, trying frames
+//! again now and then. Frames resume with a keyframe.
+//!
+//! Each stage runs inside a `debug`-level `tracing` span carrying the
+//! frame number (`capture`, `delta`, `encode`, `send`) and reports to
+//! [`telemetry`](crate::rdp::telemetry) under the `slave` role.
 
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use tracing::{Instrument, debug, debug_span, error, info, warn};
+
 use crate::error::TixError;
+use crate::protocol::screen::MouseEvent;
 use crate::rdp::bandwidth::BandwidthEstimator;
-use crate::rdp::capture::DxgiCapturer;
-use crate::rdp::delta::DeltaDetector;
-use crate::rdp::encoder::AdaptiveEncoder;
-use crate::rdp::input::InputInjector;
+use crate::rdp::capture::{DxgiCapturer, FrameSource};
+use crate::rdp::delta::{DeltaDetector, supported_block_size};
+use crate::rdp::dictionary::FrameDictionary;
+use crate::rdp::merge::{DEFAULT_MAX_WASTE_PERCENT, RegionMerger};
+use crate::rdp::desktop::{DesktopProbe, InputDesktop, ScreenStatus, SecureDesktopDetector};
+use crate::rdp::encoder::{
+    AdaptiveEncoder, DEFAULT_KEYFRAME_FRAMES, DEFAULT_KEYFRAME_INTERVAL,
+    DEFAULT_SCENE_CHANGE_PERCENT, KeyframePolicy,
+};
+use crate::rdp::gpu::GpuPrepMode;
+use crate::rdp::input::{InputGate, InputInjector};
+use crate::rdp::pointer::PointerFilter;
+use crate::rdp::telemetry::{self, Role};
+use crate::rdp::throttle::{self, CpuThrottle, Priority, ThrottleState};
+use crate::rdp::thumbnail::{
+    ForegroundInfo, SharingController, SharingSwitch, Thumbnail, ThumbnailConfig, ThumbnailPacer,
+};
 use crate::rdp::transport::ScreenTransport;
+use crate::rdp::types::PixelFormat;
+use crate::rdp::watchdog::{CaptureWatchdog, DEFAULT_MAX_FAILURES, DEFAULT_STALL_TIMEOUT};
+
+/// Attempts to rebuild a lost capturer before giving up. A mode switch
+/// typically settles within a second or two.
+const MAX_REINIT_ATTEMPTS: u32 = 20;
+
+/// Delay between reinitialisation attempts.
+const REINIT_BACKOFF: Duration = Duration::from_millis(250);
+
+/// How often a paused service checks whether it may resume.
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+/// How often blocked input is reported again, in case a datagram is
+/// lost.
+const INPUT_STATUS_REPEAT: Duration = Duration::from_secs(1);
 
 // ── ScreenServiceConfig ──────────────────────────────────────────
 
@@ -29,14 +124,57 @@ use crate::rdp::transport::ScreenTransport;
 pub struct ScreenServiceConfig {
     /// Target frames per second (1..=60).
     pub target_fps: u8,
-    /// Delta detection block size in pixels.
+    /// Delta detection block size in pixels, one of
+    /// [`BLOCK_SIZES`](crate::rdp::delta::BLOCK_SIZES); other values are
+    /// rounded down to one.
     pub block_size: usize,
+    /// Merge neighbouring dirty blocks into rectangles up to this many
+    /// percent clean; `None` sends every block on its own.
+    pub merge_waste_percent: Option<u8>,
     /// Target bandwidth in bytes/second for adaptive quality.
     pub target_bandwidth: u64,
     /// Monitor index (0 = primary).
     pub monitor_index: u32,
     /// DXGI frame acquire timeout in milliseconds.
     pub capture_timeout_ms: u32,
+    /// Pixel format frames are sent in (`Bgra8`, `Rgb565` or `Gray8`).
+    pub pixel_format: PixelFormat,
+    /// Whether the capturer diffs and packs frames on the GPU.
+    pub gpu_prep: GpuPrepMode,
+    /// Let the encoder drop a BGRA8 stream to RGB565 when the link
+    /// cannot keep up even at maximum compression.
+    pub allow_format_downgrade: bool,
+    /// Follow a switch to the secure desktop by attaching to it and
+    /// rebuilding the capturer, instead of only reporting it. Needs the
+    /// service to run as `SYSTEM`.
+    pub secure_desktop_capture: bool,
+    /// Rebuild the capturer after this long without a frame; `None`
+    /// turns the watchdog off.
+    pub stall_timeout: Option<Duration>,
+    /// Fruitless rebuilds in a row before the viewer is told.
+    pub max_rebuild_failures: u32,
+    /// CPU budget in percent of the machine; `None` leaves usage
+    /// unbounded.
+    pub max_cpu_percent: Option<u8>,
+    /// Process priority applied when the service starts.
+    pub priority: Priority,
+    /// Send a keyframe at least every this many frames; `None` only
+    /// counts time.
+    pub keyframe_frames: Option<u64>,
+    /// Send a keyframe at least this often, changes or not; `None` only
+    /// counts frames.
+    pub keyframe_interval: Option<Duration>,
+    /// Send a delta covering at least this many percent of the screen
+    /// as a full frame; `None` never promotes.
+    pub scene_change_percent: Option<u8>,
+    /// Size, quality and cadence of thumbnails in low-bandwidth mode.
+    pub thumbnails: ThumbnailConfig,
+    /// Start in low-bandwidth mode, sending thumbnails.
+    pub low_bandwidth: bool,
+    /// Fall back to low-bandwidth mode when even the smallest frames
+    /// overrun `target_bandwidth`. Only for viewers that show
+    /// thumbnails.
+    pub low_bandwidth_fallback: bool,
 }
 
 impl Default for ScreenServiceConfig {
@@ -44,9 +182,24 @@ impl Default for ScreenServiceConfig {
         Self {
             target_fps: 60,
             block_size: 64,
+            merge_waste_percent: Some(DEFAULT_MAX_WASTE_PERCENT),
             target_bandwidth: 100 * 1024 * 1024, // 100 MB/s
             monitor_index: 0,
             capture_timeout_ms: 100,
+            pixel_format: PixelFormat::Bgra8,
+            gpu_prep: GpuPrepMode::Auto,
+            allow_format_downgrade: true,
+            secure_desktop_capture: false,
+            stall_timeout: Some(DEFAULT_STALL_TIMEOUT),
+            max_rebuild_failures: DEFAULT_MAX_FAILURES,
+            max_cpu_percent: None,
+            priority: Priority::Normal,
+            keyframe_frames: Some(DEFAULT_KEYFRAME_FRAMES),
+            keyframe_interval: Some(DEFAULT_KEYFRAME_INTERVAL),
+            scene_change_percent: Some(DEFAULT_SCENE_CHANGE_PERCENT),
+            thumbnails: ThumbnailConfig::default(),
+            low_bandwidth: false,
+            low_bandwidth_fallback: false,
         }
     }
 }
@@ -60,13 +213,33 @@ impl Default for ScreenServiceConfig {
 /// Call [`run`](Self::run) to start the capture loop. It runs until
 /// [`stop`](Self::stop) is called or an unrecoverable error occurs.
 pub struct ScreenService {
-    capturer: DxgiCapturer,
+    capturer: Box<dyn FrameSource>,
     delta: DeltaDetector,
+    merger: Option<RegionMerger>,
     encoder: AdaptiveEncoder,
     transport: Arc<ScreenTransport>,
     injector: InputInjector,
     bandwidth: BandwidthEstimator,
+    desktop: SecureDesktopDetector,
+    watchdog: Option<CaptureWatchdog>,
+    throttle: Option<CpuThrottle>,
+    /// Gate of the session whose pointer moves arrive on the transport;
+    /// `None` when input only comes over the control connection.
+    pointer_gate: Option<Arc<InputGate>>,
+    /// Why input was last reported blocked, and when.
+    input_blocked: Option<(String, Instant)>,
     running: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    /// Dictionary offered to the viewer, used once `use_dictionary` is
+    /// set.
+    dictionary: Option<FrameDictionary>,
+    use_dictionary: Arc<AtomicBool>,
+    /// The viewer's mode requests, and whether it allows a fallback.
+    switch: Arc<SharingSwitch>,
+    sharing: SharingController,
+    pacer: ThumbnailPacer,
+    /// Sent again when the screen has not changed since.
+    last_thumbnail: Option<Thumbnail>,
     config: ScreenServiceConfig,
 }
 
@@ -81,22 +254,119 @@ impl ScreenService {
         transport: ScreenTransport,
         config: ScreenServiceConfig,
     ) -> Result<Self, TixError> {
-        let capturer = DxgiCapturer::new(config.monitor_index)?;
-        let delta = DeltaDetector::new(config.block_size);
-        let encoder = AdaptiveEncoder::new(config.target_bandwidth);
+        let capturer = DxgiCapturer::new(config.monitor_index)?.with_gpu_prep(
+            config.gpu_prep,
+            supported_block_size(config.block_size),
+            config.pixel_format,
+        )?;
+        Ok(Self::with_source(transport, config, capturer))
+    }
+
+    /// Create a service that pulls frames from an arbitrary
+    /// [`FrameSource`] instead of DXGI.
+    pub fn with_source(
+        transport: ScreenTransport,
+        config: ScreenServiceConfig,
+        source: impl FrameSource + 'static,
+    ) -> Self {
+        let block_size = supported_block_size(config.block_size);
+        let delta = DeltaDetector::new(block_size);
+        let merger = config
+            .merge_waste_percent
+            .map(|waste| RegionMerger::new(block_size, waste));
+        let keyframes = KeyframePolicy::new(
+            config.keyframe_frames,
+            config.keyframe_interval,
+            config.scene_change_percent,
+        );
+        let encoder = AdaptiveEncoder::new(config.target_bandwidth)
+            .with_format(config.pixel_format)
+            .with_format_downgrade(config.allow_format_downgrade)
+            .with_keyframes(keyframes);
         let injector = InputInjector::new();
         let bandwidth = BandwidthEstimator::new();
+        let watchdog = config.stall_timeout.map(|timeout| {
+            CaptureWatchdog::new(timeout, config.max_rebuild_failures, Instant::now())
+        });
+        let throttle = config.max_cpu_percent.map(CpuThrottle::new);
+        let switch = Arc::new(SharingSwitch::new(config.low_bandwidth_fallback));
+        let sharing = SharingController::new(config.low_bandwidth);
+        let pacer = ThumbnailPacer::new(config.thumbnails.interval, config.target_bandwidth);
 
-        Ok(Self {
-            capturer,
+        Self {
+            capturer: Box::new(source),
             delta,
+            merger,
             encoder,
             transport: Arc::new(transport),
             injector,
             bandwidth,
+            desktop: SecureDesktopDetector::new(InputDesktop),
+            watchdog,
+            throttle,
+            pointer_gate: None,
+            input_blocked: None,
             running: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+            dictionary: None,
+            use_dictionary: Arc::new(AtomicBool::new(false)),
+            switch,
+            sharing,
+            pacer,
+            last_thumbnail: None,
             config,
-        })
+        }
+    }
+
+    /// Query `probe` for the input desktop instead of the system.
+    pub fn with_desktop_probe(mut self, probe: impl DesktopProbe + 'static) -> Self {
+        self.desktop = SecureDesktopDetector::new(probe);
+        self
+    }
+
+    /// Replace the CPU throttle, e.g. with one on a mocked clock.
+    /// Overrides `max_cpu_percent`.
+    pub fn with_throttle(mut self, throttle: CpuThrottle) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// Inject pointer moves the viewer sends to the transport's socket,
+    /// while `gate` admits input.
+    pub fn with_pointer_input(mut self, gate: Arc<InputGate>) -> Self {
+        self.pointer_gate = Some(gate);
+        self
+    }
+
+    /// Inject through `injector`, a clone of the one input from the
+    /// control connection goes through, so both trip the same breaker.
+    pub fn with_injector(mut self, injector: InputInjector) -> Self {
+        self.injector = injector;
+        self
+    }
+
+    /// Hold `dictionary` for the stream; frames use it once the
+    /// [`dictionary_handle`](Self::dictionary_handle) is set.
+    pub fn with_dictionary(mut self, dictionary: FrameDictionary) -> Self {
+        self.dictionary = Some(dictionary);
+        self
+    }
+
+    /// A cloneable handle that switches frames to the dictionary when
+    /// set to `true`. Without a dictionary it does nothing.
+    pub fn dictionary_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.use_dictionary)
+    }
+
+    /// A handle that switches between frames and thumbnails on the
+    /// viewer's request.
+    pub fn sharing_switch(&self) -> Arc<SharingSwitch> {
+        Arc::clone(&self.switch)
+    }
+
+    /// Whether thumbnails are being sent instead of frames.
+    pub fn is_low_bandwidth(&self) -> bool {
+        self.sharing.is_low_bandwidth()
     }
 
     /// A cloneable handle that can be used to stop the service from
@@ -105,17 +375,33 @@ impl ScreenService {
         Arc::clone(&self.running)
     }
 
+    /// A cloneable handle that pauses the stream while set to `true`.
+    pub fn pause_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.paused)
+    }
+
     /// Reference to the input injector (for handling incoming input
     /// events from the master).
     pub fn injector(&self) -> &InputInjector {
         &self.injector
     }
 
+    /// Rebuilds since the last captured frame.
+    pub fn failed_rebuilds(&self) -> u32 {
+        self.watchdog.as_ref().map_or(0, CaptureWatchdog::failures)
+    }
+
     /// Current estimated bandwidth in bytes/second.
     pub fn estimated_bandwidth(&self) -> u64 {
         self.bandwidth.estimate_bps()
     }
 
+    /// Latest CPU measurement and throttle settings, when a budget is
+    /// set.
+    pub fn throttle_state(&self) -> Option<ThrottleState> {
+        self.throttle.as_ref().map(CpuThrottle::state)
+    }
+
     /// Run the capture loop.
     ///
     /// This is intended to be spawned on the Tokio runtime:
@@ -130,41 +416,158 @@ impl ScreenService {
     /// # }
     /// ```
     pub async fn run(&mut self) -> Result<(), TixError> {
+        let pointer = self.pointer_gate.clone().map(|gate| {
+            let transport = Arc::clone(&self.transport);
+            tokio::spawn(Self::forward_pointer(transport, gate, self.injector.clone()))
+        });
+        let result = self.capture_loop().await;
+        if let Some(task) = pointer {
+            task.abort();
+        }
+        result
+    }
+
+    async fn capture_loop(&mut self) -> Result<(), TixError> {
         self.running.store(true, Ordering::SeqCst);
-        let frame_interval = Duration::from_secs_f64(1.0 / self.config.target_fps as f64);
+        let base_interval = Duration::from_secs_f64(1.0 / self.config.target_fps as f64);
+        let mut frame_interval = base_interval;
         let mut frame_number: u64 = 0;
         let mut last_bandwidth_check = Instant::now();
+        if self.config.secure_desktop_capture {
+            warn!(
+                "secure desktop capture enabled: needs SYSTEM with UI access; the desktop \
+                 is attached per thread, so capture may fall back to reporting when the \
+                 runtime moves the task, and input is only delivered by injectors that \
+                 attach too"
+            );
+        }
+
+        if let Err(e) = throttle::set_priority(self.config.priority) {
+            warn!("cannot lower the process priority: {e}");
+        }
+
+        self.rearm_watchdog();
 
         while self.running.load(Ordering::SeqCst) {
             let loop_start = Instant::now();
+            if let Some(extra) = self.check_throttle(loop_start) {
+                frame_interval = base_interval + extra;
+            }
+
+            if self.paused.load(Ordering::SeqCst) {
+                // The viewer's picture is stale by the time we resume.
+                self.delta.reset();
+                tokio::time::sleep(PAUSE_POLL).await;
+                self.rearm_watchdog();
+                continue;
+            }
+            self.check_input(loop_start).await;
+
+            if self.check_sharing(loop_start) {
+                self.share_thumbnail().await?;
+                continue;
+            }
 
             // 1. Capture.
-            let raw = match self.capturer.capture_frame(self.config.capture_timeout_ms) {
+            let timeout_ms = self.config.capture_timeout_ms;
+            let captured = debug_span!("capture", frame_number)
+                .in_scope(|| self.capturer.capture_frame(timeout_ms));
+            let raw = match captured {
                 Ok(f) => f,
                 Err(TixError::Timeout(_)) => {
-                    // No new desktop frame within the deadline — skip.
+                    // No new desktop frame within the deadline — skip,
+                    // unless the stall is a secure desktop.
+                    if let Some(status) = self.desktop.on_stall(Instant::now()) {
+                        Self::report(&self.transport, &status).await;
+                        if status.is_secure() && self.config.secure_desktop_capture {
+                            self.follow_input_desktop();
+                        }
+                    }
+                    self.check_watchdog().await;
                     tokio::task::yield_now().await;
                     continue;
                 }
-                Err(e) => return Err(e),
+                Err(TixError::CaptureLost(_)) => {
+                    self.recover_capturer().await?;
+                    self.rearm_watchdog();
+                    continue;
+                }
+                Err(e) => {
+                    // Possibly a wedged duplication; the watchdog decides
+                    // when to rebuild it.
+                    if let Some(watchdog) = &mut self.watchdog {
+                        if watchdog.last_error().is_none() {
+                            warn!("capture failed: {e}");
+                        } else {
+                            debug!("capture failed: {e}");
+                        }
+                        watchdog.on_error(&e);
+                    } else {
+                        return Err(e);
+                    }
+                    self.check_watchdog().await;
+                    Self::pace(loop_start, frame_interval).await;
+                    continue;
+                }
             };
+            telemetry::frame(Role::Slave, "captured");
+            telemetry::stage_duration(Role::Slave, "capture", loop_start.elapsed());
+            if let Some(status) = self.desktop.on_frame() {
+                Self::report(&self.transport, &status).await;
+            }
+            if let Some(status) = self.watchdog.as_mut().and_then(|w| w.on_frame(Instant::now())) {
+                info!("capture recovered");
+                Self::report(&self.transport, &status).await;
+            }
 
             // 2. Delta detection.
-            let mut delta = self.delta.detect(&raw);
+            let delta_start = Instant::now();
+            let mut delta = debug_span!("delta", frame_number).in_scope(|| {
+                match self.capturer.take_prepared_tiles() {
+                    Some(tiles) => self.delta.adopt(&raw, tiles),
+                    None => self.delta.detect(&raw),
+                }
+            });
             delta.frame_number = frame_number;
+            if let Some(merger) = &self.merger {
+                merger.merge(&mut delta);
+            }
+            telemetry::stage_duration(Role::Slave, "delta", delta_start.elapsed());
 
-            // Skip sending if nothing changed.
-            if !delta.full_frame && delta.changed_blocks.is_empty() {
+            // Skip sending if nothing changed, unless a keyframe is due.
+            if !delta.full_frame
+                && delta.changed_blocks.is_empty()
+                && !self.encoder.keyframe_due(delta.timestamp)
+            {
+                telemetry::frame(Role::Slave, "dropped");
                 Self::pace(loop_start, frame_interval).await;
                 continue;
             }
 
             // 3. Encode.
-            let encoded = self.encoder.encode(&delta, &raw)?;
+            if self.encoder.dictionary().is_none()
+                && self.use_dictionary.load(Ordering::SeqCst)
+                && let Some(dictionary) = self.dictionary.clone()
+            {
+                info!("compressing frames with dictionary {:016x}", dictionary.hash());
+                self.encoder.set_dictionary(Some(dictionary));
+            }
+            let encode_start = Instant::now();
+            let encoded =
+                debug_span!("encode", frame_number).in_scope(|| self.encoder.encode(&delta, &raw))?;
             let encoded_size = encoded.data.len() as u64;
+            telemetry::frame(Role::Slave, "encoded");
+            telemetry::stage_duration(Role::Slave, "encode", encode_start.elapsed());
 
             // 4. Send.
-            self.transport.send_frame(&encoded).await?;
+            let send_start = Instant::now();
+            self.transport
+                .send_frame(&encoded)
+                .instrument(debug_span!("send", frame_number))
+                .await?;
+            telemetry::frame(Role::Slave, "sent");
+            telemetry::bytes(Role::Slave, encoded_size);
+            telemetry::stage_duration(Role::Slave, "send", send_start.elapsed());
 
             // 5. Bandwidth tracking.
             self.bandwidth.record(encoded_size);
@@ -173,7 +576,23 @@ impl ScreenService {
             // Adjust quality every second.
             if last_bandwidth_check.elapsed() > Duration::from_secs(1) {
                 let bps = self.bandwidth.estimate_bps();
+                let format = self.encoder.format();
                 self.encoder.adjust_quality(bps);
+                if self.encoder.format() != format {
+                    // Repaint everything at the new colour depth.
+                    self.delta.reset();
+                }
+                let over_budget = bps > self.config.target_bandwidth;
+                let exhausted = self.encoder.is_exhausted();
+                let fallback = self.switch.fallback();
+                if self.sharing.on_check(Instant::now(), over_budget, exhausted, fallback) {
+                    warn!(
+                        "{bps} B/s is over the {} B/s budget at maximum compression; \
+                         falling back to thumbnails",
+                        self.config.target_bandwidth
+                    );
+                    self.pacer.reset();
+                }
                 last_bandwidth_check = Instant::now();
             }
 
@@ -184,6 +603,115 @@ impl ScreenService {
         Ok(())
     }
 
+    /// Apply the viewer's mode requests and try frames again after a
+    /// fallback when due. Returns whether to send thumbnails.
+    fn check_sharing(&mut self, now: Instant) -> bool {
+        let was_low = self.sharing.is_low_bandwidth();
+        if let Some(low_bandwidth) = self.switch.take() {
+            self.sharing.request(low_bandwidth);
+        }
+        if self.sharing.probe_due(now) {
+            info!("trying frames again after the low-bandwidth fallback");
+        }
+        let low = self.sharing.is_low_bandwidth();
+        match (was_low, low) {
+            (false, true) => {
+                info!("low-bandwidth mode: sending thumbnails instead of frames");
+                self.pacer.reset();
+            }
+            (true, false) => {
+                info!("leaving low-bandwidth mode; sending a keyframe");
+                self.delta.reset();
+                self.bandwidth = BandwidthEstimator::new();
+                self.rearm_watchdog();
+            }
+            _ => {}
+        }
+        low
+    }
+
+    /// Send a thumbnail and the foreground window if the pacer lets
+    /// one go out, else wait a little for it to.
+    async fn share_thumbnail(&mut self) -> Result<(), TixError> {
+        let now = Instant::now();
+        // Stalls cannot be told from a still screen here, and frames
+        // resume with a rebuilt-or-not capturer either way.
+        self.rearm_watchdog();
+        if !self.pacer.is_due(now) {
+            tokio::time::sleep(self.pacer.until_due(now).min(PAUSE_POLL)).await;
+            return Ok(());
+        }
+
+        let ThumbnailConfig { max_dimension, quality, .. } = self.config.thumbnails;
+        let thumbnail = match self.capturer.capture_frame(self.config.capture_timeout_ms) {
+            Ok(raw) => {
+                // Tiles prepared for a delta nobody will compute.
+                let _ = self.capturer.take_prepared_tiles();
+                match Thumbnail::capture(&raw, max_dimension, quality) {
+                    Ok(thumbnail) => self.last_thumbnail.insert(thumbnail).clone(),
+                    Err(e) => {
+                        warn!("cannot make a thumbnail: {e}");
+                        self.pacer.sent(now, 0);
+                        return Ok(());
+                    }
+                }
+            }
+            // Nothing changed: send the last one again, in case it was
+            // lost.
+            Err(TixError::Timeout(_)) => match &self.last_thumbnail {
+                Some(thumbnail) => thumbnail.clone(),
+                None => return Ok(()),
+            },
+            Err(TixError::CaptureLost(_)) => return self.recover_capturer().await,
+            Err(e) => {
+                debug!("capture failed: {e}");
+                self.pacer.sent(now, 0);
+                return Ok(());
+            }
+        };
+
+        let mut sent = self
+            .transport
+            .send_thumbnail(&thumbnail)
+            .instrument(debug_span!("send_thumbnail"))
+            .await?;
+        sent += self.transport.send_foreground(&ForegroundInfo::current()).await?;
+        self.pacer.sent(Instant::now(), sent);
+        self.bandwidth.record(sent as u64);
+        telemetry::bytes(Role::Slave, sent as u64);
+        Ok(())
+    }
+
+    /// Inject the pointer moves arriving on `transport` until it fails
+    /// or the task is aborted. Moves overtaken by a newer one are
+    /// dropped.
+    async fn forward_pointer(
+        transport: Arc<ScreenTransport>,
+        gate: Arc<InputGate>,
+        injector: InputInjector,
+    ) {
+        let mut filter = PointerFilter::default();
+        loop {
+            let pointer = match transport.receive_pointer().await {
+                Ok(pointer) => pointer,
+                Err(e) => {
+                    warn!("pointer input stopped: {e}");
+                    return;
+                }
+            };
+            if !filter.admit(pointer.sequence) {
+                debug!("dropped stale pointer move #{}", pointer.sequence);
+                continue;
+            }
+            if gate.admit().is_err() {
+                continue;
+            }
+            if let Err(e) = injector.inject_mouse(&MouseEvent::move_to(pointer.x, pointer.y)) {
+                debug!("inject pointer move: {e}");
+            }
+        }
+    }
+
     /// Signal the service to stop.
     pub fn stop(&self) {
         self.running.store(false, Ordering::SeqCst);
@@ -194,6 +722,168 @@ impl ScreenService {
         self.running.load(Ordering::SeqCst)
     }
 
+    /// Rebuild the capturer after it reported [`TixError::CaptureLost`].
+    ///
+    /// The delta detector is reset so the next frame goes out as a full
+    /// keyframe carrying the (possibly new) dimensions. Failures while
+    /// a secure desktop is up do not count towards giving up: the
+    /// duplication comes back once the prompt is dismissed.
+    async fn recover_capturer(&mut self) -> Result<(), TixError> {
+        let mut attempts = 0;
+        loop {
+            if self.config.secure_desktop_capture {
+                self.attach_input_desktop();
+            }
+            match self.capturer.reinitialize() {
+                Ok(()) => {
+                    self.delta.reset();
+                    return Ok(());
+                }
+                Err(e) => {
+                    if let Some(status) = self.desktop.on_stall(Instant::now()) {
+                        Self::report(&self.transport, &status).await;
+                    }
+                    if !self.desktop.is_secure() {
+                        attempts += 1;
+                    }
+                    if attempts >= MAX_REINIT_ATTEMPTS {
+                        return Err(e);
+                    }
+                    if !self.running.load(Ordering::SeqCst) {
+                        return Ok(());
+                    }
+                    tokio::time::sleep(REINIT_BACKOFF).await;
+                }
+            }
+        }
+    }
+
+    /// Rebuild the capturer if the watchdog says it has stalled for too
+    /// long. A secure desktop explains the stall, so it only pushes the
+    /// deadline back.
+    async fn check_watchdog(&mut self) {
+        let Some(watchdog) = &mut self.watchdog else {
+            return;
+        };
+        let now = Instant::now();
+        if self.desktop.is_secure() {
+            watchdog.rearm(now);
+            return;
+        }
+        if !watchdog.is_due(now) {
+            return;
+        }
+
+        warn!(
+            "no frame captured for {:?} (last error: {}); rebuilding capturer, attempt {}",
+            watchdog.backoff(),
+            watchdog.last_error().unwrap_or("none, acquire timed out"),
+            watchdog.failures() + 1
+        );
+        let result = self.capturer.reinitialize();
+        match &result {
+            Ok(()) => {
+                info!("capturer rebuilt; sending a keyframe");
+                self.delta.reset();
+            }
+            Err(e) => warn!("rebuilding capturer failed: {e}"),
+        }
+        let status = watchdog.on_rebuild(Instant::now(), result.map_err(|e| e.to_string()));
+        info!("next capturer rebuild in {:?} without a frame", watchdog.backoff());
+        if let Some(status) = status {
+            error!(
+                "capture still failing after {} rebuilds; telling the viewer",
+                watchdog.failures()
+            );
+            Self::report(&self.transport, &status).await;
+        }
+    }
+
+    /// Give the capturer a full stall timeout from now, e.g. after a
+    /// pause or a rebuild for a mode change.
+    fn rearm_watchdog(&mut self) {
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.rearm(Instant::now());
+        }
+    }
+
+    /// Tell the viewer about a status change. The stream carries on if
+    /// the datagram cannot be sent; the next report repeats it.
+    async fn report(transport: &ScreenTransport, status: &ScreenStatus) {
+        info!("screen status: {}", status.message());
+        if let Err(e) = transport.send_status(status).await {
+            warn!("failed to send screen status: {e}");
+        }
+    }
+
+    /// Report blocked input when the injector's breaker opens, again
+    /// every [`INPUT_STATUS_REPEAT`] while it has a reason, and
+    /// [`ScreenStatus::Live`] once it clears.
+    async fn check_input(&mut self, now: Instant) {
+        let blocked = self.injector.blocked();
+        let status = match (&blocked, &self.input_blocked) {
+            (None, None) => return,
+            (Some(reason), Some((last, at)))
+                if reason == last && now.duration_since(*at) < INPUT_STATUS_REPEAT =>
+            {
+                return;
+            }
+            (Some(reason), _) => ScreenStatus::InputBlocked { reason: reason.clone() },
+            (None, Some(_)) => ScreenStatus::Live,
+        };
+        if self.input_blocked.as_ref().map(|(last, _)| last) != blocked.as_ref() {
+            Self::report(&self.transport, &status).await;
+        } else if let Err(e) = self.transport.send_status(&status).await {
+            debug!("failed to repeat the input status: {e}");
+        }
+        self.input_blocked = blocked.map(|reason| (reason, now));
+    }
+
+    /// Attach to the input desktop, logging (but otherwise ignoring) a
+    /// failure: the secure desktop is still reported either way.
+    fn attach_input_desktop(&mut self) {
+        if let Err(e) = self.desktop.attach() {
+            warn!("cannot attach to the input desktop: {e}");
+        }
+    }
+
+    /// Move capture onto the secure desktop that took over the screen.
+    /// If the capturer cannot be rebuilt there, the next stall reports
+    /// the secure desktop again and retries.
+    fn follow_input_desktop(&mut self) {
+        self.attach_input_desktop();
+        match self.capturer.reinitialize() {
+            Ok(()) => self.delta.reset(),
+            Err(e) => warn!("cannot capture the secure desktop: {e}"),
+        }
+    }
+
+    /// Measure CPU usage if due and apply the throttle to the encoder.
+    /// Returns the extra frame delay after a measurement.
+    fn check_throttle(&mut self, now: Instant) -> Option<Duration> {
+        let throttle = self.throttle.as_mut()?;
+        let was_throttling = throttle.state().is_throttling();
+        let state = throttle.tick(now, self.encoder.compression_level())?;
+        self.encoder.set_level_cap(state.level_cap);
+        telemetry::throttle(Role::Slave, &state);
+        debug!(
+            cpu_percent = state.cpu_percent,
+            extra_delay_ms = state.extra_delay.as_millis() as u64,
+            level_cap = state.level_cap,
+            "cpu throttle"
+        );
+        match (was_throttling, state.is_throttling()) {
+            (false, true) => info!(
+                "CPU at {:.0}% is over the {}% budget; throttling the stream",
+                state.cpu_percent,
+                self.config.max_cpu_percent.unwrap_or(100)
+            ),
+            (true, false) => info!("CPU back under budget; throttle released"),
+            _ => {}
+        }
+        Some(state.extra_delay)
+    }
+
     /// Sleep for the remainder of the frame interval.
     async fn pace(loop_start: Instant, interval: Duration) {
         let elapsed = loop_start.elapsed();
@@ -202,3 +892,529 @@ impl ScreenService {
         }
     }
 }
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdp::input::{ELEVATED_FOREGROUND, Injection, InjectionBreaker};
+    use crate::rdp::decoder::FrameDecoder;
+    use crate::rdp::transport::ScreenMessage;
+    use crate::rdp::types::{PixelFormat, RawScreenFrame};
+    use tokio::net::UdpSocket;
+
+    /// Synthetic source that emits `frames_before_lost` frames, reports
+    /// the capture as lost, then comes back at `resized` dimensions.
+    struct ResizingSource {
+        width: u32,
+        height: u32,
+        resized: (u32, u32),
+        frames_before_lost: u32,
+        lost_pending: bool,
+        counter: u8,
+    }
+
+    impl FrameSource for ResizingSource {
+        fn capture_frame(&mut self, _timeout_ms: u32) -> Result<RawScreenFrame, TixError> {
+            if self.frames_before_lost == 0 && self.lost_pending {
+                return Err(TixError::CaptureLost("mode change".into()));
+            }
+            self.frames_before_lost = self.frames_before_lost.saturating_sub(1);
+            // Touch one pixel per frame: a small, never-empty delta.
+            self.counter = self.counter.wrapping_add(1);
+            let mut data = vec![0u8; (self.width * self.height * 4) as usize];
+            data[0] = self.counter;
+            Ok(RawScreenFrame {
+                width: self.width,
+                height: self.height,
+                stride: self.width * 4,
+                format: PixelFormat::Bgra8,
+                data,
+                timestamp: Instant::now(),
+            })
+        }
+
+        fn reinitialize(&mut self) -> Result<(), TixError> {
+            (self.width, self.height) = self.resized;
+            self.lost_pending = false;
+            Ok(())
+        }
+
+        fn width(&self) -> u32 {
+            self.width
+        }
+
+        fn height(&self) -> u32 {
+            self.height
+        }
+    }
+
+    #[tokio::test]
+    async fn service_survives_resolution_change() {
+        let send_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let recv_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let send_addr = send_sock.local_addr().unwrap();
+        let recv_addr = recv_sock.local_addr().unwrap();
+
+        let source = ResizingSource {
+            width: 64,
+            height: 48,
+            resized: (96, 64),
+            frames_before_lost: 3,
+            lost_pending: true,
+            counter: 0,
+        };
+        let config = ScreenServiceConfig {
+            block_size: 16,
+            ..ScreenServiceConfig::default()
+        };
+        let mut service =
+            ScreenService::with_source(ScreenTransport::new(send_sock, recv_addr), config, source);
+        let stop = service.stop_handle();
+        let handle = tokio::spawn(async move { service.run().await });
+
+        let receiver = ScreenTransport::new(recv_sock, send_addr);
+        let mut decoder = FrameDecoder::new();
+        let mut frames = Vec::new();
+        for _ in 0..6 {
+            let encoded = tokio::time::timeout(Duration::from_secs(5), receiver.receive_frame())
+                .await
+                .expect("stream stalled")
+                .unwrap();
+            let decoded = decoder.decode(&encoded).unwrap();
+            let buf_len = decoder.apply(&decoded, 4).unwrap().len();
+            frames.push((
+                encoded.frame_number,
+                decoded.width,
+                decoded.height,
+                decoded.is_full_frame,
+                buf_len,
+            ));
+        }
+
+        stop.store(false, Ordering::SeqCst);
+        handle.await.unwrap().unwrap();
+
+        // Frame numbers keep counting across the transition.
+        let numbers: Vec<u64> = frames.iter().map(|f| f.0).collect();
+        assert_eq!(numbers, (0..6).collect::<Vec<u64>>());
+
+        for (i, &(_, w, h, full, buf_len)) in frames.iter().enumerate() {
+            let (ew, eh) = if i < 3 { (64, 48) } else { (96, 64) };
+            assert_eq!((w, h), (ew, eh), "frame {i}");
+            assert_eq!(buf_len, (ew * eh * 4) as usize, "frame {i}");
+            // Keyframes open each resolution, deltas follow.
+            assert_eq!(full, i == 0 || i == 3, "frame {i}");
+        }
+    }
+
+    #[tokio::test]
+    async fn dictionary_is_switched_on_mid_stream() {
+        let send_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let recv_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let send_addr = send_sock.local_addr().unwrap();
+        let recv_addr = recv_sock.local_addr().unwrap();
+
+        let source = ResizingSource {
+            width: 64,
+            height: 48,
+            resized: (64, 48),
+            frames_before_lost: u32::MAX,
+            lost_pending: false,
+            counter: 0,
+        };
+        let config = ScreenServiceConfig {
+            block_size: 16,
+            ..ScreenServiceConfig::default()
+        };
+        let dictionary = FrameDictionary::builtin();
+        let mut service =
+            ScreenService::with_source(ScreenTransport::new(send_sock, recv_addr), config, source)
+                .with_dictionary(dictionary.clone());
+        let stop = service.stop_handle();
+        let enable = service.dictionary_handle();
+        let handle = tokio::spawn(async move { service.run().await });
+
+        // The viewer loads the dictionary before confirming it, so it
+        // decodes both kinds of frame throughout.
+        let receiver = ScreenTransport::new(recv_sock, send_addr);
+        let mut decoder = FrameDecoder::with_dictionary(&dictionary);
+        let mut with_dictionary = Vec::new();
+        for n in 0..12 {
+            if n == 4 {
+                enable.store(true, Ordering::SeqCst);
+            }
+            let encoded = tokio::time::timeout(Duration::from_secs(5), receiver.receive_frame())
+                .await
+                .expect("stream stalled")
+                .unwrap();
+            let decoded = decoder.decode(&encoded).unwrap();
+            decoder.apply(&decoded, 4).unwrap();
+            let id = zstd::zstd_safe::get_dict_id_from_frame(&encoded.data);
+            with_dictionary.push(id.is_some_and(|id| id.get() == dictionary.id()));
+        }
+
+        stop.store(false, Ordering::SeqCst);
+        handle.await.unwrap().unwrap();
+
+        // Plain until enabled, then the dictionary for good.
+        let first = with_dictionary.iter().position(|&d| d).expect("never switched");
+        assert!(first >= 4, "{with_dictionary:?}");
+        assert!(with_dictionary[first..].iter().all(|&d| d), "{with_dictionary:?}");
+    }
+
+    /// The same picture, over and over.
+    struct StillSource;
+
+    impl FrameSource for StillSource {
+        fn capture_frame(&mut self, _timeout_ms: u32) -> Result<RawScreenFrame, TixError> {
+            Ok(RawScreenFrame {
+                width: 32,
+                height: 32,
+                stride: 128,
+                format: PixelFormat::Bgra8,
+                data: vec![7u8; 32 * 32 * 4],
+                timestamp: Instant::now(),
+            })
+        }
+
+        fn reinitialize(&mut self) -> Result<(), TixError> {
+            Ok(())
+        }
+
+        fn width(&self) -> u32 {
+            32
+        }
+
+        fn height(&self) -> u32 {
+            32
+        }
+    }
+
+    #[tokio::test]
+    async fn a_still_screen_gets_periodic_keyframes() {
+        let send_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let recv_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let send_addr = send_sock.local_addr().unwrap();
+        let recv_addr = recv_sock.local_addr().unwrap();
+
+        let config = ScreenServiceConfig {
+            block_size: 16,
+            keyframe_frames: None,
+            keyframe_interval: Some(Duration::from_millis(100)),
+            ..ScreenServiceConfig::default()
+        };
+        let mut service = ScreenService::with_source(
+            ScreenTransport::new(send_sock, recv_addr),
+            config,
+            StillSource,
+        );
+        let stop = service.stop_handle();
+        let handle = tokio::spawn(async move { service.run().await });
+
+        let receiver = ScreenTransport::new(recv_sock, send_addr);
+        let mut sent = Vec::new();
+        for _ in 0..3 {
+            let encoded = tokio::time::timeout(Duration::from_secs(5), receiver.receive_frame())
+                .await
+                .expect("no keyframe on a still screen")
+                .unwrap();
+            sent.push((encoded.frame_number, encoded.is_full_frame, Instant::now()));
+        }
+
+        stop.store(false, Ordering::SeqCst);
+        handle.await.unwrap().unwrap();
+
+        // Nothing changed, so only keyframes went out, about 100 ms apart
+        // with the ~16 ms frames in between skipped.
+        let numbers: Vec<u64> = sent.iter().map(|f| f.0).collect();
+        assert_eq!(numbers, [0, 1, 2]);
+        assert!(sent.iter().all(|&(_, full, _)| full));
+        assert!(sent[2].2 - sent[1].2 >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn low_bandwidth_mode_sends_thumbnails_until_switched_back() {
+        let send_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let recv_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let send_addr = send_sock.local_addr().unwrap();
+        let recv_addr = recv_sock.local_addr().unwrap();
+
+        let config = ScreenServiceConfig {
+            block_size: 16,
+            thumbnails: ThumbnailConfig {
+                max_dimension: 16,
+                interval: Duration::from_millis(50),
+                ..ThumbnailConfig::default()
+            },
+            low_bandwidth: true,
+            ..ScreenServiceConfig::default()
+        };
+        let mut service = ScreenService::with_source(
+            ScreenTransport::new(send_sock, recv_addr),
+            config,
+            StillSource,
+        );
+        assert!(service.is_low_bandwidth());
+        let switch = service.sharing_switch();
+        let stop = service.stop_handle();
+        let handle = tokio::spawn(async move { service.run().await });
+
+        let receiver = ScreenTransport::new(recv_sock, send_addr);
+        let next = || async {
+            tokio::time::timeout(Duration::from_secs(5), receiver.receive())
+                .await
+                .expect("stream stalled")
+                .unwrap()
+        };
+        let mut thumbnails = Vec::new();
+        while thumbnails.len() < 2 {
+            match next().await {
+                ScreenMessage::Thumbnail(thumbnail) => thumbnails.push((thumbnail, Instant::now())),
+                ScreenMessage::Foreground(_) => {}
+                other => panic!("expected thumbnails only, got {other:?}"),
+            }
+        }
+        let (thumbnail, first) = &thumbnails[0];
+        assert_eq!((thumbnail.width, thumbnail.height), (16, 16));
+        assert_eq!((thumbnail.screen_width, thumbnail.screen_height), (32, 32));
+        assert!(thumbnails[1].1 - *first >= Duration::from_millis(30));
+
+        switch.request(false);
+        let frame = loop {
+            if let ScreenMessage::Frame(frame) = next().await {
+                break frame;
+            }
+        };
+        assert!(frame.is_full_frame);
+        assert_eq!((frame.width, frame.height), (32, 32));
+
+        stop.store(false, Ordering::SeqCst);
+        handle.await.unwrap().unwrap();
+    }
+
+    /// A screen that freezes after one frame, as under a UAC prompt.
+    struct FrozenSource {
+        frames: u32,
+    }
+
+    impl FrameSource for FrozenSource {
+        fn capture_frame(&mut self, _timeout_ms: u32) -> Result<RawScreenFrame, TixError> {
+            if self.frames == 0 {
+                return Err(TixError::Timeout(Duration::from_millis(100)));
+            }
+            self.frames -= 1;
+            Ok(RawScreenFrame {
+                width: 16,
+                height: 16,
+                stride: 64,
+                format: PixelFormat::Bgra8,
+                data: vec![0u8; 16 * 16 * 4],
+                timestamp: Instant::now(),
+            })
+        }
+
+        fn reinitialize(&mut self) -> Result<(), TixError> {
+            Ok(())
+        }
+
+        fn width(&self) -> u32 {
+            16
+        }
+
+        fn height(&self) -> u32 {
+            16
+        }
+    }
+
+    struct WinlogonDesktop;
+
+    impl DesktopProbe for WinlogonDesktop {
+        fn input_desktop(&mut self) -> Option<String> {
+            Some("Winlogon".into())
+        }
+
+        fn attach(&mut self) -> Result<(), TixError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn service_reports_secure_desktop() {
+        let send_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let recv_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let send_addr = send_sock.local_addr().unwrap();
+        let recv_addr = recv_sock.local_addr().unwrap();
+
+        let transport = ScreenTransport::new(send_sock, recv_addr);
+        let config = ScreenServiceConfig::default();
+        let mut service = ScreenService::with_source(transport, config, FrozenSource { frames: 1 })
+            .with_desktop_probe(WinlogonDesktop);
+        let stop = service.stop_handle();
+        let handle = tokio::spawn(async move { service.run().await });
+
+        let receiver = ScreenTransport::new(recv_sock, send_addr);
+        let mut messages = Vec::new();
+        for _ in 0..2 {
+            let message = tokio::time::timeout(Duration::from_secs(5), receiver.receive())
+                .await
+                .expect("stream stalled")
+                .unwrap();
+            messages.push(message);
+        }
+
+        stop.store(false, Ordering::SeqCst);
+        handle.await.unwrap().unwrap();
+
+        assert!(matches!(messages[0], ScreenMessage::Frame(_)));
+        let ScreenMessage::Status(status) = &messages[1] else {
+            panic!("expected a status, got {:?}", messages[1]);
+        };
+        assert_eq!(status, &ScreenStatus::SecureDesktop { desktop: Some("Winlogon".into()) });
+    }
+
+    #[tokio::test]
+    async fn service_repeats_blocked_input() {
+        let send_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let recv_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let send_addr = send_sock.local_addr().unwrap();
+        let recv_addr = recv_sock.local_addr().unwrap();
+
+        let mut breaker = InjectionBreaker::new(1, Duration::from_secs(60));
+        breaker.record(&Injection::Blocked, Instant::now(), None);
+        let injector = InputInjector::with_breaker(breaker);
+        let transport = ScreenTransport::new(send_sock, recv_addr);
+        let config = ScreenServiceConfig::default();
+        let mut service = ScreenService::with_source(transport, config, FrozenSource { frames: 1 })
+            .with_injector(injector.clone());
+        let stop = service.stop_handle();
+        let handle = tokio::spawn(async move { service.run().await });
+
+        let receiver = ScreenTransport::new(recv_sock, send_addr);
+        let mut statuses = Vec::new();
+        while statuses.len() < 2 {
+            let message = tokio::time::timeout(Duration::from_secs(5), receiver.receive())
+                .await
+                .expect("stream stalled")
+                .unwrap();
+            if let ScreenMessage::Status(status) = message {
+                statuses.push(status);
+            }
+        }
+
+        stop.store(false, Ordering::SeqCst);
+        handle.await.unwrap().unwrap();
+
+        // Reported, then repeated while the breaker stays open.
+        let blocked = ScreenStatus::InputBlocked { reason: ELEVATED_FOREGROUND.into() };
+        assert_eq!(statuses, [blocked.clone(), blocked]);
+    }
+
+    /// A duplication wedged after a driver reset: one frame, then every
+    /// acquire fails, and rebuilds fail until `rebuilds_to_recover`.
+    struct WedgedSource {
+        frames: u32,
+        rebuilds: Arc<std::sync::Mutex<Vec<Instant>>>,
+        rebuilds_to_recover: usize,
+    }
+
+    impl FrameSource for WedgedSource {
+        fn capture_frame(&mut self, _timeout_ms: u32) -> Result<RawScreenFrame, TixError> {
+            if self.frames == 0 {
+                return Err(TixError::Other("AcquireNextFrame failed (0x887A0005)".into()));
+            }
+            self.frames -= 1;
+            Ok(RawScreenFrame {
+                width: 16,
+                height: 16,
+                stride: 64,
+                format: PixelFormat::Bgra8,
+                data: vec![0u8; 16 * 16 * 4],
+                timestamp: Instant::now(),
+            })
+        }
+
+        fn reinitialize(&mut self) -> Result<(), TixError> {
+            let mut rebuilds = self.rebuilds.lock().unwrap();
+            rebuilds.push(Instant::now());
+            if rebuilds.len() < self.rebuilds_to_recover {
+                return Err(TixError::Other("DuplicateOutput failed (0x887A0004)".into()));
+            }
+            self.frames = u32::MAX;
+            Ok(())
+        }
+
+        fn width(&self) -> u32 {
+            16
+        }
+
+        fn height(&self) -> u32 {
+            16
+        }
+    }
+
+    #[tokio::test]
+    async fn watchdog_rebuilds_wedged_capturer() {
+        let send_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let recv_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let send_addr = send_sock.local_addr().unwrap();
+        let recv_addr = recv_sock.local_addr().unwrap();
+
+        let rebuilds = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let source = WedgedSource {
+            frames: 1,
+            rebuilds: Arc::clone(&rebuilds),
+            rebuilds_to_recover: 3,
+        };
+        let config = ScreenServiceConfig {
+            stall_timeout: Some(Duration::from_millis(50)),
+            max_rebuild_failures: 2,
+            ..ScreenServiceConfig::default()
+        };
+        let transport = ScreenTransport::new(send_sock, recv_addr);
+        let mut service = ScreenService::with_source(transport, config, source);
+        let stop = service.stop_handle();
+        let handle = tokio::spawn(async move {
+            let result = service.run().await;
+            (service, result)
+        });
+
+        let receiver = ScreenTransport::new(recv_sock, send_addr);
+        let mut messages = Vec::new();
+        for _ in 0..4 {
+            let message = tokio::time::timeout(Duration::from_secs(5), receiver.receive())
+                .await
+                .expect("stream stalled")
+                .unwrap();
+            messages.push(message);
+        }
+
+        stop.store(false, Ordering::SeqCst);
+        let (service, result) = handle.await.unwrap();
+        result.unwrap();
+
+        let ScreenMessage::Frame(first) = &messages[0] else {
+            panic!("expected the first frame, got {:?}", messages[0]);
+        };
+        assert!(first.is_full_frame);
+        // Two failed rebuilds tell the viewer; the third brings frames
+        // back, announced as live and repainted with a keyframe.
+        let failed = ScreenStatus::CaptureFailed {
+            error: "DuplicateOutput failed (0x887A0004)".into(),
+        };
+        assert!(matches!(&messages[1], ScreenMessage::Status(s) if *s == failed));
+        assert!(matches!(&messages[2], ScreenMessage::Status(ScreenStatus::Live)));
+        let ScreenMessage::Frame(repaint) = &messages[3] else {
+            panic!("expected a keyframe, got {:?}", messages[3]);
+        };
+        assert!(repaint.is_full_frame);
+
+        let rebuilds = rebuilds.lock().unwrap();
+        assert_eq!(rebuilds.len(), 3);
+        // Each failure doubles the wait before the next attempt.
+        assert!(rebuilds[1] - rebuilds[0] >= Duration::from_millis(50));
+        assert!(rebuilds[2] - rebuilds[1] >= Duration::from_millis(100));
+        assert_eq!(service.failed_rebuilds(), 0);
+    }
+}
@@ -0,0 +1,180 @@
+//! Frame pipeline metrics.
+//!
+//! The slave ([`ScreenService`](crate::rdp::service::ScreenService)) and
+//! the viewer ([`ScreenClient`](crate::rdp::client::ScreenClient)) report
+//! under the same metric names and tell themselves apart with a `role`
+//! label, so one dashboard can chart both ends of a session:
+//!
+//! | Metric | Kind | Labels |
+//! |--------|------|--------|
+//! | `tix_rdp_frames_total` | counter | `role`, `stage` |
+//! | `tix_rdp_bytes_total` | counter | `role` |
+//! | `tix_rdp_stage_seconds` | histogram | `role`, `stage` |
+//! | `tix_rdp_frame_latency_seconds` | histogram | `role` |
+//!
+//! Frame stages are `captured`, `encoded`, `sent` and `dropped` (nothing
+//! changed since the last frame) on the slave, and `received`, `decoded`
+//! and `dropped` (could not be applied) on the viewer. Timed stages are
+//! `capture`, `delta`, `encode` and `send` on the slave and `decode`,
+//! `apply` and `render` on the viewer. Bytes count what went onto
+//! (slave) or came off (viewer) the wire.
+//!
+//! While a CPU budget is set the slave also reports
+//! `tix_rdp_cpu_percent`, `tix_rdp_throttle_delay_seconds` and
+//! `tix_rdp_compression_level_cap` (gauges, `role` label), so a dashboard
+//! shows when and how hard the stream is being held back.
+//!
+//! The recording functions compile to nothing unless the `metrics`
+//! feature is enabled; [`serve`] then exposes the values in Prometheus
+//! text format. The per-stage `tracing` spans are always emitted and do
+//! not depend on the feature.
+
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+pub use metrics_exporter_prometheus::PrometheusHandle;
+
+/// Counter of frames passing each pipeline stage.
+pub const FRAMES_TOTAL: &str = "tix_rdp_frames_total";
+/// Counter of screen bytes sent or received.
+pub const BYTES_TOTAL: &str = "tix_rdp_bytes_total";
+/// Histogram of time spent in a pipeline stage.
+pub const STAGE_SECONDS: &str = "tix_rdp_stage_seconds";
+/// Histogram of capture-to-display latency.
+pub const FRAME_LATENCY_SECONDS: &str = "tix_rdp_frame_latency_seconds";
+
+/// Gauge of process CPU usage in percent of the machine.
+pub const CPU_PERCENT: &str = "tix_rdp_cpu_percent";
+/// Gauge of the delay the CPU throttle adds between frames.
+pub const THROTTLE_DELAY_SECONDS: &str = "tix_rdp_throttle_delay_seconds";
+/// Gauge of the highest zstd level the CPU throttle allows.
+pub const COMPRESSION_LEVEL_CAP: &str = "tix_rdp_compression_level_cap";
+
+/// Which end of the stream is reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Slave,
+    Viewer,
+}
+
+impl Role {
+    /// Value of the `role` label.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Slave => "slave",
+            Self::Viewer => "viewer",
+        }
+    }
+}
+
+/// Count one frame through `stage`.
+pub fn frame(role: Role, stage: &'static str) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(FRAMES_TOTAL, "role" => role.as_str(), "stage" => stage).increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (role, stage);
+}
+
+/// Add `bytes` to the traffic counter.
+pub fn bytes(role: Role, bytes: u64) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(BYTES_TOTAL, "role" => role.as_str()).increment(bytes);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (role, bytes);
+}
+
+/// Record how long `stage` took for one frame.
+pub fn stage_duration(role: Role, stage: &'static str, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!(STAGE_SECONDS, "role" => role.as_str(), "stage" => stage)
+        .record(elapsed.as_secs_f64());
+    #[cfg(not(feature = "metrics"))]
+    let _ = (role, stage, elapsed);
+}
+
+/// Record the age of a frame since capture.
+pub fn frame_latency(role: Role, age: Duration) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!(FRAME_LATENCY_SECONDS, "role" => role.as_str()).record(age.as_secs_f64());
+    #[cfg(not(feature = "metrics"))]
+    let _ = (role, age);
+}
+
+/// Publish the CPU throttle's latest measurement.
+pub fn throttle(role: Role, state: &crate::rdp::throttle::ThrottleState) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::gauge!(CPU_PERCENT, "role" => role.as_str()).set(state.cpu_percent);
+        metrics::gauge!(THROTTLE_DELAY_SECONDS, "role" => role.as_str())
+            .set(state.extra_delay.as_secs_f64());
+        metrics::gauge!(COMPRESSION_LEVEL_CAP, "role" => role.as_str())
+            .set(f64::from(state.level_cap));
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (role, state);
+}
+
+/// Install the Prometheus recorder without an HTTP listener and return
+/// a handle that renders the current values. Meant for tests and for
+/// callers that expose the text some other way.
+#[cfg(feature = "metrics")]
+pub fn install_recorder() -> Result<PrometheusHandle, crate::error::TixError> {
+    let handle = metrics_exporter_prometheus::PrometheusBuilder::new()
+        .install_recorder()
+        .map_err(|e| crate::error::TixError::Other(format!("metrics recorder: {e}")))?;
+    describe();
+    Ok(handle)
+}
+
+/// Install the Prometheus recorder and serve `/metrics` on
+/// `127.0.0.1:port`. Must be called from within a Tokio runtime. Fails
+/// when the crate was built without the `metrics` feature.
+pub fn serve(port: u16) -> Result<(), crate::error::TixError> {
+    #[cfg(feature = "metrics")]
+    {
+        let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+        metrics_exporter_prometheus::PrometheusBuilder::new()
+            .with_http_listener(addr)
+            .install()
+            .map_err(|e| crate::error::TixError::Other(format!("metrics endpoint {addr}: {e}")))?;
+        describe();
+        Ok(())
+    }
+    #[cfg(not(feature = "metrics"))]
+    {
+        let _ = port;
+        Err(crate::error::TixError::Other(
+            "built without the `metrics` feature".into(),
+        ))
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn describe() {
+    metrics::describe_counter!(FRAMES_TOTAL, "Frames passing each pipeline stage");
+    metrics::describe_counter!(
+        BYTES_TOTAL,
+        metrics::Unit::Bytes,
+        "Screen bytes on the wire"
+    );
+    metrics::describe_histogram!(
+        STAGE_SECONDS,
+        metrics::Unit::Seconds,
+        "Time spent in a pipeline stage per frame"
+    );
+    metrics::describe_histogram!(
+        FRAME_LATENCY_SECONDS,
+        metrics::Unit::Seconds,
+        "Frame age since capture, excluding network transit"
+    );
+    metrics::describe_gauge!(CPU_PERCENT, "Process CPU usage, percent of the machine");
+    metrics::describe_gauge!(
+        THROTTLE_DELAY_SECONDS,
+        metrics::Unit::Seconds,
+        "Extra delay between frames to stay under the CPU budget"
+    );
+    metrics::describe_gauge!(
+        COMPRESSION_LEVEL_CAP,
+        "Highest zstd level allowed under the CPU budget"
+    );
+}
@@ -0,0 +1,428 @@
+//! CPU budget for the capture service.
+//!
+//! A slave that is someone's workstation should not lose a core to the
+//! screen stream. [`CpuThrottle`] samples the process CPU time once a
+//! second and, while usage is over `max_cpu_percent`, first caps the zstd
+//! level one step at a time and then stretches the sleep between frames.
+//! Once usage falls well below the budget the extra delay shrinks and the
+//! cap is raised again, in the reverse order.
+//!
+//! Percentages are of the whole machine, as Task Manager shows them: 25 on
+//! a four-core host is one core's worth.
+//!
+//! [`set_priority`] puts the process in background mode so that when the
+//! budget is not enough the scheduler still favours the interactive user.
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::error::TixError;
+use crate::rdp::encoder::MAX_COMPRESSION_LEVEL;
+
+/// How often usage is measured and the throttle adjusted.
+pub const MEASURE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// First step of extra inter-frame delay.
+const MIN_EXTRA_DELAY: Duration = Duration::from_millis(10);
+
+/// Longest extra delay; the stream stays at one frame a second or so.
+const MAX_EXTRA_DELAY: Duration = Duration::from_secs(1);
+
+/// Usage below this share of the budget counts as headroom.
+const HEADROOM: f64 = 0.8;
+
+// ── CPU clocks ───────────────────────────────────────────────────
+
+/// Source of the CPU time the process has used so far.
+pub trait CpuClock: Send {
+    /// Total user + kernel time across all threads, or `None` when the
+    /// platform cannot tell.
+    fn cpu_time(&mut self) -> Option<Duration>;
+}
+
+/// Reads the CPU time of the current process from the OS.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProcessCpuClock;
+
+impl CpuClock for ProcessCpuClock {
+    #[cfg(target_os = "windows")]
+    fn cpu_time(&mut self) -> Option<Duration> {
+        use windows::Win32::Foundation::FILETIME;
+        use windows::Win32::System::Threading::{GetCurrentProcess, GetProcessTimes};
+
+        let mut creation = FILETIME::default();
+        let mut exit = FILETIME::default();
+        let mut kernel = FILETIME::default();
+        let mut user = FILETIME::default();
+        // SAFETY: the pseudo-handle is always valid and the four out
+        // pointers live for the duration of the call.
+        unsafe {
+            GetProcessTimes(
+                GetCurrentProcess(),
+                &mut creation,
+                &mut exit,
+                &mut kernel,
+                &mut user,
+            )
+            .ok()?;
+        }
+        let ticks = |t: FILETIME| (u64::from(t.dwHighDateTime) << 32) | u64::from(t.dwLowDateTime);
+        // FILETIME durations count 100 ns intervals.
+        Some(Duration::from_nanos((ticks(kernel) + ticks(user)) * 100))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn cpu_time(&mut self) -> Option<Duration> {
+        let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+        // The command name may contain spaces; fields resume after it.
+        let rest = &stat[stat.rfind(')')? + 1..];
+        let mut fields = rest.split_whitespace().skip(11);
+        let utime: u64 = fields.next()?.parse().ok()?;
+        let stime: u64 = fields.next()?.parse().ok()?;
+        // SAFETY: sysconf has no preconditions.
+        let hz = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        if hz <= 0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64((utime + stime) as f64 / hz as f64))
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    fn cpu_time(&mut self) -> Option<Duration> {
+        None
+    }
+}
+
+// ── Throttle ─────────────────────────────────────────────────────
+
+/// Outcome of the latest measurement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThrottleState {
+    /// Process CPU usage over the last interval, in percent of the
+    /// whole machine.
+    pub cpu_percent: f64,
+    /// Added to the frame interval on top of the target FPS.
+    pub extra_delay: Duration,
+    /// Highest zstd level the encoder may use.
+    pub level_cap: i32,
+}
+
+impl ThrottleState {
+    /// Whether the stream is being held back.
+    pub fn is_throttling(&self) -> bool {
+        !self.extra_delay.is_zero() || self.level_cap < MAX_COMPRESSION_LEVEL
+    }
+}
+
+impl Default for ThrottleState {
+    fn default() -> Self {
+        Self {
+            cpu_percent: 0.0,
+            extra_delay: Duration::ZERO,
+            level_cap: MAX_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+/// Keeps the process under a CPU budget.
+pub struct CpuThrottle {
+    max_percent: f64,
+    cores: u32,
+    clock: Box<dyn CpuClock>,
+    /// Wall and CPU time of the previous sample.
+    last_sample: Option<(Instant, Duration)>,
+    state: ThrottleState,
+}
+
+impl std::fmt::Debug for CpuThrottle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CpuThrottle")
+            .field("max_percent", &self.max_percent)
+            .field("cores", &self.cores)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CpuThrottle {
+    /// Budget of `max_percent` of the machine (1..=100), measured with
+    /// [`ProcessCpuClock`].
+    pub fn new(max_percent: u8) -> Self {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get() as u32);
+        Self {
+            max_percent: f64::from(max_percent.clamp(1, 100)),
+            cores,
+            clock: Box::new(ProcessCpuClock),
+            last_sample: None,
+            state: ThrottleState::default(),
+        }
+    }
+
+    /// Measure with `clock` instead of the OS.
+    pub fn with_clock(mut self, clock: impl CpuClock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self.last_sample = None;
+        self
+    }
+
+    /// Convert CPU time to percentages as if the machine had `cores`
+    /// logical processors.
+    pub fn with_cores(mut self, cores: u32) -> Self {
+        self.cores = cores.max(1);
+        self
+    }
+
+    /// Current throttle settings.
+    pub fn state(&self) -> ThrottleState {
+        self.state
+    }
+
+    /// Measure if a [`MEASURE_INTERVAL`] has passed since the last sample
+    /// and adjust to it. `level` is the encoder's current zstd level.
+    /// Returns the new state after a measurement.
+    pub fn tick(&mut self, now: Instant, level: i32) -> Option<ThrottleState> {
+        if let Some((at, _)) = self.last_sample
+            && now.saturating_duration_since(at) < MEASURE_INTERVAL
+        {
+            return None;
+        }
+        let cpu = self.clock.cpu_time()?;
+        // The first sample is only a baseline.
+        let (at, last_cpu) = self.last_sample.replace((now, cpu))?;
+        let wall = now.saturating_duration_since(at).as_secs_f64();
+        let used = cpu.saturating_sub(last_cpu).as_secs_f64();
+        self.state.cpu_percent = used / wall * 100.0 / f64::from(self.cores);
+        self.adjust(level);
+        Some(self.state)
+    }
+
+    fn adjust(&mut self, level: i32) {
+        let state = &mut self.state;
+        if state.cpu_percent > self.max_percent {
+            if level > 1 {
+                // Cheaper compression first: it costs bandwidth, not FPS.
+                state.level_cap = level.min(state.level_cap) - 1;
+            } else {
+                state.extra_delay = (state.extra_delay * 5 / 4)
+                    .max(MIN_EXTRA_DELAY)
+                    .min(MAX_EXTRA_DELAY);
+            }
+        } else if state.cpu_percent < self.max_percent * HEADROOM {
+            if !state.extra_delay.is_zero() {
+                state.extra_delay = state.extra_delay * 4 / 5;
+                if state.extra_delay < MIN_EXTRA_DELAY {
+                    state.extra_delay = Duration::ZERO;
+                }
+            } else if state.level_cap < MAX_COMPRESSION_LEVEL {
+                state.level_cap += 1;
+            }
+        }
+    }
+}
+
+// ── Priority ─────────────────────────────────────────────────────
+
+/// Scheduling priority of the slave process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// Leave the priority alone.
+    #[default]
+    Normal,
+    /// Background mode on Windows (lower CPU, I/O and memory priority);
+    /// nice 10 elsewhere.
+    Background,
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "normal" => Ok(Self::Normal),
+            "background" => Ok(Self::Background),
+            other => Err(format!(
+                "unknown priority '{other}' (expected normal or background)"
+            )),
+        }
+    }
+}
+
+/// Apply `priority` to the whole process. Capture and encode run on the
+/// Tokio worker threads rather than a dedicated one, so a per-thread
+/// priority would not follow them.
+pub fn set_priority(priority: Priority) -> Result<(), TixError> {
+    if priority == Priority::Normal {
+        return Ok(());
+    }
+    set_background()
+}
+
+#[cfg(target_os = "windows")]
+fn set_background() -> Result<(), TixError> {
+    use windows::Win32::System::Threading::{
+        GetCurrentProcess, PROCESS_MODE_BACKGROUND_BEGIN, SetPriorityClass,
+    };
+
+    // SAFETY: the pseudo-handle is always valid for the current process.
+    unsafe { SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) }
+        .map_err(|e| TixError::Other(format!("SetPriorityClass: {e}")))
+}
+
+#[cfg(target_os = "linux")]
+fn set_background() -> Result<(), TixError> {
+    const NICE: libc::c_int = 10;
+    // Linux keeps a nice value per thread, so renice every existing one.
+    let tasks = std::fs::read_dir("/proc/self/task")
+        .map_err(|e| TixError::Other(format!("/proc/self/task: {e}")))?;
+    for task in tasks.flatten() {
+        let Some(tid) = task.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        // SAFETY: setpriority has no memory-safety preconditions.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, NICE) } != 0 {
+            return Err(TixError::Other(format!(
+                "setpriority: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn set_background() -> Result<(), TixError> {
+    Err(TixError::Other(
+        "background priority is not supported on this platform".into(),
+    ))
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// CPU time advanced by hand, in microseconds.
+    #[derive(Clone, Default)]
+    struct MockClock(Arc<AtomicU64>);
+
+    impl MockClock {
+        fn spend(&self, cpu: Duration) {
+            self.0.fetch_add(cpu.as_micros() as u64, Ordering::SeqCst);
+        }
+    }
+
+    impl CpuClock for MockClock {
+        fn cpu_time(&mut self) -> Option<Duration> {
+            Some(Duration::from_micros(self.0.load(Ordering::SeqCst)))
+        }
+    }
+
+    fn throttle(max_percent: u8, clock: &MockClock) -> CpuThrottle {
+        CpuThrottle::new(max_percent)
+            .with_clock(clock.clone())
+            .with_cores(1)
+    }
+
+    #[test]
+    fn measures_once_per_interval() {
+        let clock = MockClock::default();
+        let mut throttle = throttle(50, &clock).with_cores(4);
+        let start = Instant::now();
+        assert_eq!(throttle.tick(start, 1), None, "first sample is a baseline");
+
+        clock.spend(Duration::from_millis(400));
+        assert_eq!(throttle.tick(start + Duration::from_millis(500), 1), None);
+        let state = throttle.tick(start + Duration::from_secs(1), 1).unwrap();
+        assert!((state.cpu_percent - 10.0).abs() < 1e-9, "{state:?}");
+        assert!(!state.is_throttling());
+    }
+
+    #[test]
+    fn caps_the_level_before_slowing_down() {
+        let clock = MockClock::default();
+        let mut throttle = throttle(25, &clock);
+        let mut now = Instant::now();
+        throttle.tick(now, 4);
+
+        let mut step = |level| {
+            clock.spend(Duration::from_millis(600));
+            now += MEASURE_INTERVAL;
+            throttle.tick(now, level).unwrap()
+        };
+        assert_eq!(step(4).level_cap, 3);
+        assert_eq!(step(3).level_cap, 2);
+        let state = step(2);
+        assert_eq!((state.level_cap, state.extra_delay), (1, Duration::ZERO));
+        assert_eq!(step(1).extra_delay, MIN_EXTRA_DELAY);
+        assert!(step(1).extra_delay > MIN_EXTRA_DELAY);
+    }
+
+    #[test]
+    fn recovers_delay_then_level_when_idle() {
+        let clock = MockClock::default();
+        let mut throttle = throttle(25, &clock);
+        throttle.state = ThrottleState {
+            cpu_percent: 0.0,
+            extra_delay: Duration::from_millis(12),
+            level_cap: MAX_COMPRESSION_LEVEL - 1,
+        };
+        let mut now = Instant::now();
+        throttle.tick(now, 1);
+
+        now += MEASURE_INTERVAL;
+        let state = throttle.tick(now, 1).unwrap();
+        assert_eq!(state.extra_delay, Duration::ZERO);
+        assert_eq!(state.level_cap, MAX_COMPRESSION_LEVEL - 1);
+        now += MEASURE_INTERVAL;
+        let state = throttle.tick(now, 1).unwrap();
+        assert_eq!(state.level_cap, MAX_COMPRESSION_LEVEL);
+        assert!(!state.is_throttling());
+    }
+
+    /// A stream whose frames cost 5 ms of CPU plus 1 ms per zstd level,
+    /// at 60 FPS: about 80 % of a core uncapped.
+    #[test]
+    fn closed_loop_settles_under_budget() {
+        let clock = MockClock::default();
+        let mut throttle = throttle(25, &clock);
+        let base_interval = Duration::from_secs(1) / 60;
+        let mut level = 5;
+        let mut now = Instant::now();
+        throttle.tick(now, level);
+
+        let mut history = Vec::new();
+        for _ in 0..60 {
+            let state = throttle.state();
+            level = level.min(state.level_cap);
+            let per_frame = Duration::from_millis(5 + level as u64);
+            let interval = (base_interval + state.extra_delay).max(per_frame);
+            let frames = MEASURE_INTERVAL.as_secs_f64() / interval.as_secs_f64();
+            clock.spend(per_frame.mul_f64(frames));
+            now += MEASURE_INTERVAL;
+            history.push(throttle.tick(now, level).unwrap());
+        }
+
+        let settled = &history[30..];
+        assert!(
+            settled.iter().all(|s| s.cpu_percent <= 25.0 * 1.05),
+            "{settled:?}"
+        );
+        assert!(
+            settled
+                .iter()
+                .all(|s| s.cpu_percent >= 25.0 * HEADROOM * 0.8),
+            "should not overshoot into idling: {settled:?}"
+        );
+        assert_eq!(settled.last().unwrap().level_cap, 1);
+    }
+
+    #[test]
+    fn parses_priority() {
+        assert_eq!("Background".parse(), Ok(Priority::Background));
+        assert_eq!("normal".parse(), Ok(Priority::Normal));
+        assert!("idle".parse::<Priority>().is_err());
+    }
+}
@@ -0,0 +1,919 @@
+//! Low-bandwidth sharing: periodic thumbnails and the foreground window
+//! instead of frames.
+//!
+//! Some links cannot carry the frame stream even at its smallest, e.g.
+//! a cellular fallback at 256 kbit/s. There the slave stops sending
+//! frames and, every few seconds, sends a small JPEG [`Thumbnail`] of
+//! the screen, at most `max_dimension` pixels along its longer side,
+//! and a [`ForegroundInfo`] naming the window in front and where the
+//! cursor is. The viewer scales the thumbnail back up to the screen's
+//! size, so pointer positions map as they do for frames and input is
+//! forwarded as usual.
+//!
+//! A session enters the mode when the viewer asks for it, in its
+//! `ScreenStart` or later with `ScreenSharing`, or as a last resort when
+//! the viewer has offered `thumbnails` and [`SharingController`] sees
+//! the stream stay over budget with the encoder out of options. It
+//! returns to frames when the viewer asks, or when a probe finds the
+//! frame stream fits the budget again: a controller that fell back
+//! tries frames again after [`FIRST_PROBE`], waiting twice as long
+//! after each probe that falls straight back, up to [`MAX_PROBE`].
+//!
+//! ```text
+//! thumbnail chunk: magic "TXTN" (4) | id u32 | index u16 | count u16 | part of bincode(Thumbnail)
+//! foreground:      magic "TXFG" (4) | bincode(ForegroundInfo)
+//! ```
+//!
+//! A thumbnail rarely fits one datagram, so it is cut into chunks that
+//! [`ThumbnailAssembler`] puts back together; a chunk of a newer
+//! thumbnail drops whatever is left of an older one. [`ThumbnailPacer`]
+//! spaces thumbnails at least `interval` apart, and further when the
+//! last one took longer than that to send at the service's target
+//! bandwidth.
+
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{ExtendedColorType, ImageFormat, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use crate::error::TixError;
+use crate::rdp::types::{PixelFormat, RawScreenFrame};
+
+/// Longer side of a thumbnail in pixels, unless configured otherwise.
+pub const DEFAULT_MAX_DIMENSION: u32 = 480;
+
+/// Time between thumbnails, unless configured otherwise.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// JPEG quality of thumbnails (1-100), unless configured otherwise.
+pub const DEFAULT_QUALITY: u8 = 60;
+
+/// Seconds in a row over budget, with the encoder at its smallest,
+/// before the controller falls back to thumbnails.
+pub const FALLBACK_AFTER: u32 = 5;
+
+/// Time in the fallback before the first try at frames again.
+pub const FIRST_PROBE: Duration = Duration::from_secs(30);
+
+/// Longest wait between tries at frames.
+pub const MAX_PROBE: Duration = Duration::from_secs(300);
+
+/// Longest window title sent, in characters, so the foreground
+/// datagram stays well inside the MTU.
+pub const MAX_TITLE_CHARS: usize = 200;
+
+const THUMBNAIL_MAGIC: &[u8; 4] = b"TXTN";
+const FOREGROUND_MAGIC: &[u8; 4] = b"TXFG";
+
+/// Size of a thumbnail chunk's header.
+pub const CHUNK_HEADER_SIZE: usize = 12;
+
+// ── ThumbnailConfig ──────────────────────────────────────────────
+
+/// How thumbnails are made and how often they are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThumbnailConfig {
+    /// Longer side of a thumbnail in pixels.
+    pub max_dimension: u32,
+    /// Shortest time between thumbnails.
+    pub interval: Duration,
+    /// JPEG quality (1-100).
+    pub quality: u8,
+}
+
+impl Default for ThumbnailConfig {
+    fn default() -> Self {
+        Self {
+            max_dimension: DEFAULT_MAX_DIMENSION,
+            interval: DEFAULT_INTERVAL,
+            quality: DEFAULT_QUALITY,
+        }
+    }
+}
+
+// ── Thumbnail ────────────────────────────────────────────────────
+
+/// A downscaled JPEG of the whole screen.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Thumbnail {
+    /// Size of the JPEG in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// Size of the screen it shows; the viewer scales back up to it.
+    pub screen_width: u32,
+    pub screen_height: u32,
+    pub jpeg: Vec<u8>,
+}
+
+impl std::fmt::Debug for Thumbnail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Thumbnail")
+            .field("size", &(self.width, self.height))
+            .field("screen", &(self.screen_width, self.screen_height))
+            .field("len", &self.jpeg.len())
+            .finish()
+    }
+}
+
+impl Thumbnail {
+    /// Downscale `frame` to at most `max_dimension` pixels along its
+    /// longer side and encode it at JPEG `quality`.
+    pub fn capture(
+        frame: &RawScreenFrame,
+        max_dimension: u32,
+        quality: u8,
+    ) -> Result<Self, TixError> {
+        let (width, height, rgb) = downscale(frame, max_dimension)?;
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, quality.clamp(1, 100))
+            .encode(&rgb, width, height, ExtendedColorType::Rgb8)
+            .map_err(|e| TixError::Encoding(format!("thumbnail JPEG: {e}")))?;
+        Ok(Self {
+            width,
+            height,
+            screen_width: frame.width,
+            screen_height: frame.height,
+            jpeg,
+        })
+    }
+
+    /// The thumbnail's own pixels, RGB8.
+    pub fn decode(&self) -> Result<RgbImage, TixError> {
+        let image = image::load_from_memory_with_format(&self.jpeg, ImageFormat::Jpeg)
+            .map_err(|e| TixError::Encoding(format!("thumbnail JPEG: {e}")))?;
+        Ok(image.to_rgb8())
+    }
+
+    /// The thumbnail scaled up to the screen's size, as tightly packed
+    /// BGRA8 rows.
+    pub fn to_screen_bgra(&self) -> Result<Vec<u8>, TixError> {
+        let small = self.decode()?;
+        let (width, height) = (self.screen_width.max(1), self.screen_height.max(1));
+        let screen = if small.dimensions() == (width, height) {
+            small
+        } else {
+            image::imageops::resize(&small, width, height, FilterType::Triangle)
+        };
+        let mut bgra = Vec::with_capacity(width as usize * height as usize * 4);
+        for px in screen.pixels() {
+            bgra.extend_from_slice(&[px[2], px[1], px[0], 0xFF]);
+        }
+        Ok(bgra)
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TixError> {
+        bincode::serialize(self).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TixError> {
+        bincode::deserialize(bytes).map_err(|e| TixError::Encoding(e.to_string()))
+    }
+}
+
+/// Size of the thumbnail of a `width × height` screen: the longer side
+/// brought down to `max_dimension`, aspect kept. Smaller screens keep
+/// their size.
+pub fn thumbnail_size(width: u32, height: u32, max_dimension: u32) -> (u32, u32) {
+    let longer = width.max(height);
+    let max_dimension = max_dimension.max(1);
+    if longer <= max_dimension {
+        return (width.max(1), height.max(1));
+    }
+    let scale = |side: u32| {
+        ((side as u64 * max_dimension as u64 + longer as u64 / 2) / longer as u64).max(1) as u32
+    };
+    (scale(width), scale(height))
+}
+
+/// Average `frame` down to its thumbnail size (see [`thumbnail_size`]),
+/// each thumbnail pixel the mean of the screen pixels it covers.
+/// Returns the size and tightly packed RGB8 rows.
+pub fn downscale(
+    frame: &RawScreenFrame,
+    max_dimension: u32,
+) -> Result<(u32, u32, Vec<u8>), TixError> {
+    let (red, blue) = match frame.format {
+        PixelFormat::Bgra8 => (2, 0),
+        PixelFormat::Rgba8 => (0, 2),
+        other => {
+            return Err(TixError::Other(format!(
+                "cannot make a thumbnail of a {other} capture"
+            )));
+        }
+    };
+    if frame.width == 0 || frame.height == 0 || frame.data.len() < frame.byte_len() {
+        return Err(TixError::Other(format!(
+            "{}x{} capture of {} bytes cannot be downscaled",
+            frame.width,
+            frame.height,
+            frame.data.len()
+        )));
+    }
+    let (width, height) = thumbnail_size(frame.width, frame.height, max_dimension);
+    // Screen columns each thumbnail column covers, worked out once.
+    let span = |i: u32, out: u32, src: u32| {
+        let start = (i as u64 * src as u64 / out as u64) as u32;
+        let end = (((i + 1) as u64 * src as u64 / out as u64) as u32).max(start + 1);
+        start..end
+    };
+    let columns: Vec<_> = (0..width).map(|x| span(x, width, frame.width)).collect();
+
+    let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+    let mut sums = vec![[0u32; 3]; width as usize];
+    for y in 0..height {
+        let rows = span(y, height, frame.height);
+        let row_count = rows.len() as u32;
+        sums.iter_mut().for_each(|sum| *sum = [0; 3]);
+        for row in rows {
+            let pixels = frame.row(row);
+            for (sum, cols) in sums.iter_mut().zip(&columns) {
+                for px in pixels[cols.start as usize * 4..cols.end as usize * 4].chunks_exact(4) {
+                    sum[0] += px[red] as u32;
+                    sum[1] += px[1] as u32;
+                    sum[2] += px[blue] as u32;
+                }
+            }
+        }
+        for (sum, cols) in sums.iter().zip(&columns) {
+            let count = cols.len() as u32 * row_count;
+            rgb.extend(
+                sum.iter()
+                    .map(|&channel| ((channel + count / 2) / count) as u8),
+            );
+        }
+    }
+    Ok((width, height, rgb))
+}
+
+// ── Datagrams ────────────────────────────────────────────────────
+
+/// Cut `thumbnail` into datagrams of at most `mtu` bytes, numbered
+/// `id`.
+pub fn thumbnail_datagrams(
+    thumbnail: &Thumbnail,
+    id: u32,
+    mtu: usize,
+) -> Result<Vec<Vec<u8>>, TixError> {
+    let body = thumbnail.to_bytes()?;
+    let part = mtu.saturating_sub(CHUNK_HEADER_SIZE).max(1);
+    let count = body.len().div_ceil(part);
+    let count = u16::try_from(count).map_err(|_| {
+        TixError::Encoding(format!(
+            "thumbnail of {} bytes needs too many chunks",
+            body.len()
+        ))
+    })?;
+    Ok(body
+        .chunks(part)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut data = Vec::with_capacity(CHUNK_HEADER_SIZE + chunk.len());
+            data.extend_from_slice(THUMBNAIL_MAGIC);
+            data.extend_from_slice(&id.to_le_bytes());
+            data.extend_from_slice(&(index as u16).to_le_bytes());
+            data.extend_from_slice(&count.to_le_bytes());
+            data.extend_from_slice(chunk);
+            data
+        })
+        .collect())
+}
+
+/// Whether `data` is a thumbnail chunk rather than screen data.
+pub fn is_thumbnail(data: &[u8]) -> bool {
+    data.len() > CHUNK_HEADER_SIZE && data[..4] == *THUMBNAIL_MAGIC
+}
+
+/// Encode `info` as a datagram for the screen socket.
+pub fn foreground_datagram(info: &ForegroundInfo) -> Result<Vec<u8>, TixError> {
+    let body = bincode::serialize(info).map_err(|e| TixError::Encoding(e.to_string()))?;
+    let mut data = Vec::with_capacity(FOREGROUND_MAGIC.len() + body.len());
+    data.extend_from_slice(FOREGROUND_MAGIC);
+    data.extend_from_slice(&body);
+    Ok(data)
+}
+
+/// Decode a foreground datagram; `None` if `data` is not a valid one.
+pub fn parse_foreground(data: &[u8]) -> Option<ForegroundInfo> {
+    if data.len() <= FOREGROUND_MAGIC.len() || data[..4] != *FOREGROUND_MAGIC {
+        return None;
+    }
+    bincode::deserialize(&data[FOREGROUND_MAGIC.len()..]).ok()
+}
+
+/// Puts thumbnail chunks back together.
+#[derive(Debug, Default)]
+pub struct ThumbnailAssembler {
+    id: u32,
+    parts: Vec<Option<Vec<u8>>>,
+    missing: usize,
+}
+
+impl ThumbnailAssembler {
+    /// Take a thumbnail chunk; returns the thumbnail once its last
+    /// chunk is in. Malformed chunks and those of older thumbnails are
+    /// ignored.
+    pub fn push(&mut self, data: &[u8]) -> Option<Thumbnail> {
+        if !is_thumbnail(data) {
+            return None;
+        }
+        let id = u32::from_le_bytes(data[4..8].try_into().unwrap());
+        let index = u16::from_le_bytes(data[8..10].try_into().unwrap()) as usize;
+        let count = u16::from_le_bytes(data[10..12].try_into().unwrap()) as usize;
+        if index >= count {
+            return None;
+        }
+        if self.parts.is_empty() || id != self.id {
+            // Wrapping comparison, so ids keep working past u32::MAX.
+            if !self.parts.is_empty() && id.wrapping_sub(self.id) > u32::MAX / 2 {
+                return None;
+            }
+            self.id = id;
+            self.parts = vec![None; count];
+            self.missing = count;
+        }
+        if self.parts.len() != count || self.parts[index].is_some() {
+            return None;
+        }
+        self.parts[index] = Some(data[CHUNK_HEADER_SIZE..].to_vec());
+        self.missing -= 1;
+        if self.missing > 0 {
+            return None;
+        }
+        let body: Vec<u8> = std::mem::take(&mut self.parts)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect();
+        Thumbnail::from_bytes(&body).ok()
+    }
+}
+
+// ── ForegroundInfo ───────────────────────────────────────────────
+
+/// What the remote user is looking at, sent next to each thumbnail.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForegroundInfo {
+    /// Title of the foreground window; empty if there is none.
+    pub title: String,
+    /// File name of the process owning it, e.g. `EXCEL.EXE`; empty if
+    /// it cannot be read.
+    pub process: String,
+    /// Cursor position on the virtual desktop.
+    pub cursor: Option<(i32, i32)>,
+}
+
+impl ForegroundInfo {
+    /// The foreground window and cursor right now. Empty on platforms
+    /// without a desktop to ask.
+    pub fn current() -> Self {
+        let mut info = platform::foreground();
+        if let Some((cut, _)) = info.title.char_indices().nth(MAX_TITLE_CHARS) {
+            info.title.truncate(cut);
+        }
+        info
+    }
+
+    /// One line for the viewer's overlay.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        match (self.title.is_empty(), self.process.is_empty()) {
+            (false, false) => parts.push(format!("{} ({})", self.title, self.process)),
+            (false, true) => parts.push(self.title.clone()),
+            (true, false) => parts.push(self.process.clone()),
+            (true, true) => parts.push("no foreground window".into()),
+        }
+        if let Some((x, y)) = self.cursor {
+            parts.push(format!("cursor {x},{y}"));
+        }
+        parts.join(" · ")
+    }
+}
+
+#[cfg(all(target_os = "windows", feature = "win-capture"))]
+mod platform {
+    use super::ForegroundInfo;
+
+    pub fn foreground() -> ForegroundInfo {
+        use windows::Win32::Foundation::{CloseHandle, POINT};
+        use windows::Win32::System::Threading::{
+            OpenProcess, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+            QueryFullProcessImageNameW,
+        };
+        use windows::Win32::UI::WindowsAndMessaging::{
+            GetCursorPos, GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId,
+        };
+        use windows::core::PWSTR;
+
+        let mut info = ForegroundInfo::default();
+        // SAFETY: the buffers outlive the calls that fill them, and the
+        // process handle is closed before returning.
+        unsafe {
+            let mut point = POINT::default();
+            if GetCursorPos(&mut point).is_ok() {
+                info.cursor = Some((point.x, point.y));
+            }
+            let hwnd = GetForegroundWindow();
+            if hwnd.is_invalid() {
+                return info;
+            }
+            let mut title = [0u16; 512];
+            let len = GetWindowTextW(hwnd, &mut title).max(0) as usize;
+            info.title = String::from_utf16_lossy(&title[..len]);
+
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, Some(&mut pid));
+            if let Ok(process) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
+                let mut path = [0u16; 1024];
+                let mut len = path.len() as u32;
+                let named = QueryFullProcessImageNameW(
+                    process,
+                    PROCESS_NAME_WIN32,
+                    PWSTR(path.as_mut_ptr()),
+                    &mut len,
+                );
+                if named.is_ok() {
+                    let path = String::from_utf16_lossy(&path[..len as usize]);
+                    info.process = path.rsplit('\\').next().unwrap_or_default().to_string();
+                }
+                let _ = CloseHandle(process);
+            }
+        }
+        info
+    }
+}
+
+#[cfg(not(all(target_os = "windows", feature = "win-capture")))]
+mod platform {
+    use super::ForegroundInfo;
+
+    pub fn foreground() -> ForegroundInfo {
+        ForegroundInfo::default()
+    }
+}
+
+// ── ThumbnailPacer ───────────────────────────────────────────────
+
+/// When the next thumbnail may go out: `interval` after the last one,
+/// or later if sending it at the target bandwidth took longer.
+#[derive(Debug, Clone)]
+pub struct ThumbnailPacer {
+    interval: Duration,
+    bytes_per_second: u64,
+    next: Option<Instant>,
+}
+
+impl ThumbnailPacer {
+    /// Pace thumbnails `interval` apart, within `bytes_per_second`
+    /// (0 for no limit).
+    pub fn new(interval: Duration, bytes_per_second: u64) -> Self {
+        Self {
+            interval,
+            bytes_per_second,
+            next: None,
+        }
+    }
+
+    /// Whether a thumbnail may go out at `now`.
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.next.is_none_or(|next| now >= next)
+    }
+
+    /// Time left until one may go out.
+    pub fn until_due(&self, now: Instant) -> Duration {
+        self.next
+            .map_or(Duration::ZERO, |next| next.saturating_duration_since(now))
+    }
+
+    /// Note that `bytes` went out at `now`.
+    pub fn sent(&mut self, now: Instant, bytes: usize) {
+        let on_the_wire = match self.bytes_per_second {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(bytes as f64 / rate as f64),
+        };
+        self.next = Some(now + self.interval.max(on_the_wire));
+    }
+
+    /// Let the next thumbnail go out straight away, e.g. on entering
+    /// the mode.
+    pub fn reset(&mut self) {
+        self.next = None;
+    }
+}
+
+// ── Switching ────────────────────────────────────────────────────
+
+const NO_REQUEST: u8 = 0;
+const REQUEST_FULL: u8 = 1;
+const REQUEST_LOW: u8 = 2;
+
+/// The viewer's side of the switch, shared with the control connection:
+/// requests to change mode, and whether the service may fall back to
+/// thumbnails by itself.
+#[derive(Debug)]
+pub struct SharingSwitch {
+    requested: AtomicU8,
+    fallback: AtomicBool,
+}
+
+impl SharingSwitch {
+    pub fn new(fallback: bool) -> Self {
+        Self {
+            requested: AtomicU8::new(NO_REQUEST),
+            fallback: AtomicBool::new(fallback),
+        }
+    }
+
+    /// Ask for low-bandwidth mode (`true`) or frames. The newest
+    /// request wins.
+    pub fn request(&self, low_bandwidth: bool) {
+        let request = if low_bandwidth {
+            REQUEST_LOW
+        } else {
+            REQUEST_FULL
+        };
+        self.requested.store(request, Ordering::SeqCst);
+    }
+
+    /// Let the service fall back to thumbnails by itself, or not.
+    pub fn set_fallback(&self, fallback: bool) {
+        self.fallback.store(fallback, Ordering::SeqCst);
+    }
+
+    pub fn fallback(&self) -> bool {
+        self.fallback.load(Ordering::SeqCst)
+    }
+
+    /// The request not yet acted on, if any.
+    pub fn take(&self) -> Option<bool> {
+        match self.requested.swap(NO_REQUEST, Ordering::SeqCst) {
+            REQUEST_LOW => Some(true),
+            REQUEST_FULL => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl Default for SharingSwitch {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+/// Why a session is in low-bandwidth mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Engaged {
+    /// The viewer asked; only the viewer ends it.
+    Requested,
+    /// The controller fell back; frames are tried again at `probe_at`.
+    Fallback { probe_at: Instant },
+}
+
+/// Decides between frames and thumbnails for one session.
+#[derive(Debug, Clone)]
+pub struct SharingController {
+    engaged: Option<Engaged>,
+    /// Checks in a row over budget at the encoder's smallest.
+    pressure: u32,
+    /// Checks in a row within budget since the last probe began.
+    calm: u32,
+    /// Frames are being tried again after a fallback.
+    probing: bool,
+    probe_after: Duration,
+}
+
+impl SharingController {
+    /// A controller starting in low-bandwidth mode if `low_bandwidth`.
+    pub fn new(low_bandwidth: bool) -> Self {
+        Self {
+            engaged: low_bandwidth.then_some(Engaged::Requested),
+            pressure: 0,
+            calm: 0,
+            probing: false,
+            probe_after: FIRST_PROBE,
+        }
+    }
+
+    /// Whether thumbnails are being sent instead of frames.
+    pub fn is_low_bandwidth(&self) -> bool {
+        self.engaged.is_some()
+    }
+
+    /// Whether the controller switched by itself, rather than on request.
+    pub fn is_fallback(&self) -> bool {
+        matches!(self.engaged, Some(Engaged::Fallback { .. }))
+    }
+
+    /// Apply the viewer's request; it overrides a fallback either way.
+    pub fn request(&mut self, low_bandwidth: bool) {
+        self.engaged = low_bandwidth.then_some(Engaged::Requested);
+        self.pressure = 0;
+        self.calm = 0;
+        self.probing = false;
+        self.probe_after = FIRST_PROBE;
+    }
+
+    /// Take the once-a-second measurement of the frame stream: whether
+    /// it used more than its budget, and whether the encoder could not
+    /// shrink it further. With `fallback` allowed, returns `true` when
+    /// that has gone on long enough to switch to thumbnails.
+    pub fn on_check(
+        &mut self,
+        now: Instant,
+        over_budget: bool,
+        exhausted: bool,
+        fallback: bool,
+    ) -> bool {
+        if self.engaged.is_some() {
+            return false;
+        }
+        if !over_budget {
+            self.pressure = 0;
+            self.calm += 1;
+            if self.probing && self.calm >= FALLBACK_AFTER {
+                // Frames fit again: the next fallback starts afresh.
+                self.probing = false;
+                self.probe_after = FIRST_PROBE;
+            }
+            return false;
+        }
+        self.calm = 0;
+        if !exhausted || !fallback {
+            self.pressure = 0;
+            return false;
+        }
+        self.pressure += 1;
+        if self.pressure < FALLBACK_AFTER {
+            return false;
+        }
+        if std::mem::take(&mut self.probing) {
+            self.probe_after = (self.probe_after * 2).min(MAX_PROBE);
+        }
+        self.pressure = 0;
+        self.engaged = Some(Engaged::Fallback {
+            probe_at: now + self.probe_after,
+        });
+        true
+    }
+
+    /// Whether a fallback has lasted long enough to try frames again;
+    /// if so, the controller switches back to them.
+    pub fn probe_due(&mut self, now: Instant) -> bool {
+        match self.engaged {
+            Some(Engaged::Fallback { probe_at }) if now >= probe_at => {
+                self.engaged = None;
+                self.probing = true;
+                self.calm = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width × height` BGRA capture, left half red and right half
+    /// blue, rows padded to a multiple of 64 bytes.
+    fn halves(width: u32, height: u32) -> RawScreenFrame {
+        let stride = (width * 4).div_ceil(64) * 64;
+        let mut data = vec![0u8; (stride * height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                let at = (y * stride + x * 4) as usize;
+                let px = if x < width / 2 {
+                    [0, 0, 255, 255]
+                } else {
+                    [255, 0, 0, 255]
+                };
+                data[at..at + 4].copy_from_slice(&px);
+            }
+        }
+        RawScreenFrame {
+            width,
+            height,
+            stride,
+            format: PixelFormat::Bgra8,
+            data,
+            timestamp: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn thumbnails_keep_the_aspect_within_the_limit() {
+        assert_eq!(thumbnail_size(1920, 1080, 480), (480, 270));
+        assert_eq!(thumbnail_size(1080, 1920, 480), (270, 480));
+        assert_eq!(thumbnail_size(320, 200, 480), (320, 200));
+        assert_eq!(thumbnail_size(5000, 3, 480), (480, 1));
+    }
+
+    #[test]
+    fn downscaling_averages_what_each_pixel_covers() {
+        let (width, height, rgb) = downscale(&halves(64, 32), 16).unwrap();
+        assert_eq!((width, height), (16, 8));
+        assert_eq!(rgb.len(), 16 * 8 * 3);
+        // Each thumbnail pixel covers 4×4 screen pixels of one colour.
+        assert_eq!(&rgb[..3], &[255, 0, 0]);
+        assert_eq!(&rgb[rgb.len() - 3..], &[0, 0, 255]);
+
+        // 3 screen columns per thumbnail column: the middle one mixes.
+        let (_, _, rgb) = downscale(&halves(6, 2), 2).unwrap();
+        assert_eq!(rgb.len(), 2 * 3);
+        assert_eq!(&rgb[..3], &[255, 0, 0]);
+
+        let mut packed = halves(8, 8);
+        packed.format = PixelFormat::Rgb565;
+        assert!(downscale(&packed, 4).is_err());
+    }
+
+    #[test]
+    fn thumbnail_round_trips_through_jpeg() {
+        let frame = halves(1920, 1080);
+        let thumbnail = Thumbnail::capture(&frame, DEFAULT_MAX_DIMENSION, DEFAULT_QUALITY).unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (480, 270));
+        assert_eq!(
+            (thumbnail.screen_width, thumbnail.screen_height),
+            (1920, 1080)
+        );
+        assert_eq!(&thumbnail.jpeg[..2], &[0xFF, 0xD8], "not a JPEG");
+        // Flat colours compress to next to nothing; far below a frame.
+        assert!(
+            thumbnail.jpeg.len() < 20_000,
+            "{} bytes",
+            thumbnail.jpeg.len()
+        );
+
+        let small = thumbnail.decode().unwrap();
+        assert_eq!(small.dimensions(), (480, 270));
+        let red = small.get_pixel(100, 100);
+        assert!(red[0] > 200 && red[2] < 60, "{red:?}");
+
+        let screen = thumbnail.to_screen_bgra().unwrap();
+        assert_eq!(screen.len(), 1920 * 1080 * 4);
+        let at = |x: usize, y: usize| &screen[(y * 1920 + x) * 4..(y * 1920 + x) * 4 + 4];
+        assert!(
+            at(200, 500)[2] > 200 && at(200, 500)[0] < 60,
+            "{:?}",
+            at(200, 500)
+        );
+        assert!(
+            at(1700, 500)[0] > 200 && at(1700, 500)[2] < 60,
+            "{:?}",
+            at(1700, 500)
+        );
+        assert_eq!(at(1700, 500)[3], 0xFF);
+    }
+
+    #[test]
+    fn chunks_reassemble_and_a_newer_thumbnail_wins() {
+        let thumbnail = Thumbnail::capture(&halves(640, 480), 320, 90).unwrap();
+        let chunks = thumbnail_datagrams(&thumbnail, 7, 200).unwrap();
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.len() <= 200 && is_thumbnail(c)));
+
+        let mut assembler = ThumbnailAssembler::default();
+        let (last, rest) = chunks.split_last().unwrap();
+        // Out of order, with a duplicate.
+        for chunk in rest.iter().rev().chain(&rest[..1]) {
+            assert_eq!(assembler.push(chunk), None);
+        }
+        assert_eq!(assembler.push(last), Some(thumbnail.clone()));
+
+        // Half of #8, then all of #9: #8 is abandoned, and its late
+        // chunks are ignored.
+        let eight = thumbnail_datagrams(&thumbnail, 8, 200).unwrap();
+        let nine = thumbnail_datagrams(&thumbnail, 9, 200).unwrap();
+        assembler.push(&eight[0]);
+        let done: Vec<_> = nine.iter().filter_map(|c| assembler.push(c)).collect();
+        assert_eq!(done, [thumbnail]);
+        assert!(eight[1..].iter().all(|c| assembler.push(c).is_none()));
+    }
+
+    #[test]
+    fn foreground_datagrams_round_trip() {
+        let info = ForegroundInfo {
+            title: "Quarterly report.xlsx - Excel".into(),
+            process: "EXCEL.EXE".into(),
+            cursor: Some((812, 433)),
+        };
+        let data = foreground_datagram(&info).unwrap();
+        assert_eq!(parse_foreground(&data), Some(info.clone()));
+        assert_eq!(parse_foreground(b"TXSTxx"), None);
+        assert!(!is_thumbnail(&data));
+        assert_eq!(
+            info.summary(),
+            "Quarterly report.xlsx - Excel (EXCEL.EXE) · cursor 812,433"
+        );
+        assert_eq!(ForegroundInfo::default().summary(), "no foreground window");
+    }
+
+    #[test]
+    fn pacer_waits_out_the_interval_or_the_bandwidth() {
+        let start = Instant::now();
+        let mut pacer = ThumbnailPacer::new(Duration::from_secs(3), 4_000);
+        assert!(pacer.is_due(start));
+
+        pacer.sent(start, 6_000);
+        assert!(!pacer.is_due(start + Duration::from_secs(2)));
+        assert!(pacer.is_due(start + Duration::from_secs(3)));
+
+        // 20 kB at 4 kB/s is 5 s on the wire: longer than the interval.
+        pacer.sent(start, 20_000);
+        assert_eq!(
+            pacer.until_due(start + Duration::from_secs(3)),
+            Duration::from_secs(2)
+        );
+        assert!(pacer.is_due(start + Duration::from_secs(5)));
+
+        pacer.reset();
+        assert!(pacer.is_due(start));
+    }
+
+    #[test]
+    fn switch_hands_over_the_newest_request() {
+        let switch = SharingSwitch::new(true);
+        assert_eq!(switch.take(), None);
+        switch.request(true);
+        switch.request(false);
+        assert_eq!(switch.take(), Some(false));
+        assert_eq!(switch.take(), None);
+        switch.set_fallback(false);
+        assert!(!switch.fallback());
+    }
+
+    #[test]
+    fn controller_falls_back_only_when_the_encoder_is_spent() {
+        let start = Instant::now();
+        let mut controller = SharingController::new(false);
+        let second = |n: u32| start + Duration::from_secs(n as u64);
+
+        // Over budget, but the encoder still has room: no switch.
+        for n in 0..10 {
+            assert!(!controller.on_check(second(n), true, false, true));
+        }
+        // Spent, but the viewer did not offer thumbnails.
+        for n in 0..10 {
+            assert!(!controller.on_check(second(n), true, true, false));
+        }
+        // Spent and allowed: after FALLBACK_AFTER seconds.
+        for n in 1..FALLBACK_AFTER {
+            assert!(!controller.on_check(second(n), true, true, true));
+        }
+        assert!(controller.on_check(second(FALLBACK_AFTER), true, true, true));
+        assert!(controller.is_low_bandwidth() && controller.is_fallback());
+    }
+
+    #[test]
+    fn failed_probes_back_off_and_a_good_one_resets() {
+        let start = Instant::now();
+        let mut controller = SharingController::new(false);
+        let fall_back = |controller: &mut SharingController, at: Instant| {
+            (0..FALLBACK_AFTER).any(|_| controller.on_check(at, true, true, true))
+        };
+
+        assert!(fall_back(&mut controller, start));
+        assert!(!controller.probe_due(start + FIRST_PROBE - Duration::from_secs(1)));
+        let probe = start + FIRST_PROBE;
+        assert!(controller.probe_due(probe));
+        assert!(!controller.is_low_bandwidth());
+
+        // Straight back over: the next probe waits twice as long.
+        assert!(fall_back(&mut controller, probe));
+        assert!(!controller.probe_due(probe + FIRST_PROBE));
+        let probe = probe + FIRST_PROBE * 2;
+        assert!(controller.probe_due(probe));
+
+        // This time frames fit; a later fallback starts from scratch.
+        for _ in 0..FALLBACK_AFTER {
+            controller.on_check(probe, false, true, true);
+        }
+        assert!(fall_back(&mut controller, probe));
+        assert!(controller.probe_due(probe + FIRST_PROBE));
+    }
+
+    #[test]
+    fn requests_override_the_controller() {
+        let start = Instant::now();
+        let mut controller = SharingController::new(true);
+        assert!(controller.is_low_bandwidth() && !controller.is_fallback());
+        // A requested mode is never probed out of.
+        assert!(!controller.probe_due(start + MAX_PROBE * 2));
+
+        controller.request(false);
+        assert!(!controller.is_low_bandwidth());
+        for _ in 0..FALLBACK_AFTER {
+            controller.on_check(start, true, true, true);
+        }
+        assert!(controller.is_fallback());
+        controller.request(false);
+        assert!(!controller.is_low_bandwidth());
+    }
+}
@@ -6,17 +6,33 @@
 //!
 //! ## Wire format
 //!
-//! **Frame header packet** (33 bytes):
+//! **Frame header packet** (41 bytes):
 //! ```text
 //! sequence:       u32  (4)
 //! frame_number:   u64  (8)
-//! timestamp_us:   u64  (8)
+//! timestamp_us:   u64  (8)   time the frame spent on the sender
 //! width:          u32  (4)
 //! height:         u32  (4)
-//! is_full_frame:  u8   (1)
+//! flags:          u8   (1)   bit 0 = full frame, bits 1-3 = session,
+//!                            bits 4-7 = pixel format
 //! total_chunks:   u32  (4)
+//! capture_us:     u64  (8)   capture time on the sender's stream clock
 //! ```
 //!
+//! `capture_us` lets the viewer pace frames by when they were captured
+//! rather than when they happened to arrive. Senders that predate it
+//! send 33-byte headers, which decode with `capture_us` 0.
+//!
+//! The pixel format is [`PixelFormat::wire_id`]; 0 is BGRA8, so headers
+//! from senders that predate reduced-colour frames still decode.
+//!
+//! The session ID tells concurrent streams from one slave apart (one
+//! per monitor, see [`MAX_SESSIONS`]). Each session has its own pair of
+//! UDP ports, so the ID is a guard rather than a demultiplexer: a
+//! receiver drops frames stamped for another session, and only takes
+//! chunks from the address its frame header came from. Senders that
+//! predate sessions stamp 0.
+//!
 //! **Chunk packet** (12 byte header + payload):
 //! ```text
 //! sequence:       u32  (4)
@@ -24,20 +40,51 @@
 //! chunk_size:     u32  (4)
 //! data:           [u8] (variable, ≤ MTU − 12)
 //! ```
+//!
+//! [`frame_datagrams`] cuts a frame into these packets without touching
+//! a socket; `send_frame` sends what it returns.
+//!
+//! Between frames the slave may also send a [`ScreenStatus`] datagram
+//! (see [`desktop`](crate::rdp::desktop)); [`ScreenTransport::receive`]
+//! surfaces it as a [`ScreenMessage::Status`].
+//!
+//! In [low-bandwidth mode](crate::rdp::thumbnail) the slave sends
+//! thumbnail chunks and foreground datagrams instead of frames;
+//! `receive` puts the chunks back together and surfaces them as a
+//! [`ScreenMessage::Thumbnail`] and a [`ScreenMessage::Foreground`]. A
+//! thumbnail completing while a frame is being collected abandons the
+//! frame: the slave has stopped sending its chunks.
+//!
+//! In the other direction the viewer may send [`pointer`] datagrams;
+//! the slave picks them up with [`ScreenTransport::receive_pointer`] and
+//! frame reassembly skips them.
 
 use std::net::SocketAddr;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::time::Instant;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use tokio::net::UdpSocket;
 
 use crate::error::TixError;
+use crate::rdp::desktop::{self, ScreenStatus};
 use crate::rdp::encoder::EncodedFrame;
+use crate::rdp::mtu;
+use crate::rdp::pointer::{self, PointerMove, PointerSender};
+use crate::rdp::thumbnail::{self, ForegroundInfo, Thumbnail, ThumbnailAssembler};
+use crate::rdp::types::PixelFormat;
 
 // ── Constants ────────────────────────────────────────────────────
 
 /// Maximum transmission unit minus IP (20) + UDP (8) headers.
-const DEFAULT_MTU: usize = 1400;
+pub const DEFAULT_MTU: usize = 1400;
+
+/// Smallest `mtu` the configs accept; below it the frame and chunk
+/// headers leave next to no room for pixels.
+pub const MIN_MTU: usize = 100;
+
+/// Concurrent screen sessions the frame header can tell apart.
+pub const MAX_SESSIONS: u8 = 8;
 
 // ── FrameHeader ──────────────────────────────────────────────────
 
@@ -50,12 +97,21 @@ pub struct FrameHeader {
     pub width: u32,
     pub height: u32,
     pub is_full_frame: bool,
+    /// Screen session the frame belongs to (`< MAX_SESSIONS`).
+    pub session: u8,
+    pub format: PixelFormat,
     pub total_chunks: u32,
+    /// Microseconds since the sending transport was created; 0 if the
+    /// sender does not say.
+    pub capture_us: u64,
 }
 
 impl FrameHeader {
     /// Encoded size on the wire.
-    pub const SIZE: usize = 33;
+    pub const SIZE: usize = 41;
+
+    /// Size of a header without `capture_us`.
+    pub const MIN_SIZE: usize = 33;
 
     /// Serialize to bytes (little-endian).
     pub fn encode(&self) -> [u8; Self::SIZE] {
@@ -65,28 +121,39 @@ impl FrameHeader {
         buf[12..20].copy_from_slice(&self.timestamp_us.to_le_bytes());
         buf[20..24].copy_from_slice(&self.width.to_le_bytes());
         buf[24..28].copy_from_slice(&self.height.to_le_bytes());
-        buf[28] = self.is_full_frame as u8;
+        buf[28] = self.is_full_frame as u8
+            | ((self.session % MAX_SESSIONS) << 1)
+            | (self.format.wire_id() << 4);
         buf[29..33].copy_from_slice(&self.total_chunks.to_le_bytes());
+        buf[33..41].copy_from_slice(&self.capture_us.to_le_bytes());
         buf
     }
 
     /// Deserialize from bytes.
     pub fn decode(data: &[u8]) -> Result<Self, TixError> {
-        if data.len() < Self::SIZE {
+        if data.len() < Self::MIN_SIZE {
             return Err(TixError::Other(format!(
                 "FrameHeader too short: {} < {}",
                 data.len(),
-                Self::SIZE,
+                Self::MIN_SIZE,
             )));
         }
+        let format = PixelFormat::from_wire_id(data[28] >> 4).ok_or_else(|| {
+            TixError::Other(format!("FrameHeader has unknown pixel format {}", data[28] >> 4))
+        })?;
         Ok(Self {
             sequence: u32::from_le_bytes(data[0..4].try_into().unwrap()),
             frame_number: u64::from_le_bytes(data[4..12].try_into().unwrap()),
             timestamp_us: u64::from_le_bytes(data[12..20].try_into().unwrap()),
             width: u32::from_le_bytes(data[20..24].try_into().unwrap()),
             height: u32::from_le_bytes(data[24..28].try_into().unwrap()),
-            is_full_frame: data[28] != 0,
+            is_full_frame: data[28] & 1 != 0,
+            session: (data[28] >> 1) & (MAX_SESSIONS - 1),
+            format,
             total_chunks: u32::from_le_bytes(data[29..33].try_into().unwrap()),
+            capture_us: data
+                .get(33..41)
+                .map_or(0, |b| u64::from_le_bytes(b.try_into().unwrap())),
         })
     }
 }
@@ -131,6 +198,95 @@ impl ChunkHeader {
     }
 }
 
+// ── Chunking ─────────────────────────────────────────────────────
+
+/// Cut `frame` into the datagrams [`ScreenTransport::send_frame`] sends:
+/// the [`FrameHeader`] of sequence number `sequence` in `session`, then
+/// the data in chunks of at most `mtu` bytes, each behind its
+/// [`ChunkHeader`]. `capture_us` counts from `epoch`.
+pub fn frame_datagrams(
+    frame: &EncodedFrame,
+    sequence: u32,
+    session: u8,
+    mtu: usize,
+    epoch: Instant,
+) -> Vec<Vec<u8>> {
+    let chunk_payload_max = mtu - ChunkHeader::SIZE;
+    let total_chunks = frame.data.len().div_ceil(chunk_payload_max);
+
+    let header = FrameHeader {
+        sequence,
+        frame_number: frame.frame_number,
+        timestamp_us: frame.timestamp.elapsed().as_micros() as u64,
+        width: frame.width,
+        height: frame.height,
+        is_full_frame: frame.is_full_frame,
+        session,
+        format: frame.format,
+        total_chunks: total_chunks as u32,
+        capture_us: frame.timestamp.saturating_duration_since(epoch).as_micros() as u64,
+    };
+    let mut datagrams = Vec::with_capacity(1 + total_chunks);
+    datagrams.push(header.encode().to_vec());
+
+    for (idx, chunk_data) in frame.data.chunks(chunk_payload_max).enumerate() {
+        let ch = ChunkHeader {
+            sequence,
+            chunk_index: idx as u32,
+            chunk_size: chunk_data.len() as u32,
+        };
+        let mut pkt = Vec::with_capacity(ChunkHeader::SIZE + chunk_data.len());
+        pkt.extend_from_slice(&ch.encode());
+        pkt.extend_from_slice(chunk_data);
+        datagrams.push(pkt);
+    }
+    datagrams
+}
+
+// ── TrafficCounter ───────────────────────────────────────────────
+
+/// Shared sent/received byte counters for a [`ScreenTransport`].
+///
+/// Cloning yields another handle to the same counters, so a caller can
+/// keep reading them after the transport has been moved into a
+/// [`ScreenService`](crate::rdp::ScreenService).
+#[derive(Debug, Clone, Default)]
+pub struct TrafficCounter {
+    sent: Arc<AtomicU64>,
+    received: Arc<AtomicU64>,
+}
+
+impl TrafficCounter {
+    /// Total bytes sent, datagram headers included.
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Total bytes received, datagram headers included.
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_sent(&self, bytes: usize) {
+        self.sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+// ── ScreenMessage ────────────────────────────────────────────────
+
+/// One item received from the screen socket.
+#[derive(Debug)]
+pub enum ScreenMessage {
+    /// A complete frame.
+    Frame(EncodedFrame),
+    /// A change in the stream's state, e.g. a secure desktop coming up.
+    Status(ScreenStatus),
+    /// A complete thumbnail, sent instead of frames on a slow link.
+    Thumbnail(Thumbnail),
+    /// The window in front, sent next to each thumbnail.
+    Foreground(ForegroundInfo),
+}
+
 // ── ScreenTransport ──────────────────────────────────────────────
 
 /// Bidirectional UDP transport for screen frames.
@@ -139,26 +295,49 @@ impl ChunkHeader {
 /// transmits them. The receiver reassembles frames using sequence
 /// numbers.
 pub struct ScreenTransport {
-    socket: UdpSocket,
+    socket: Arc<UdpSocket>,
     remote_addr: SocketAddr,
     sequence: AtomicU32,
     mtu: usize,
-    /// Total bytes sent since construction (for bandwidth estimation).
-    bytes_sent: std::sync::atomic::AtomicU64,
+    /// Session stamped on sent frames and expected on received ones.
+    session: u8,
+    /// Bytes sent and received (for bandwidth estimation and accounting).
+    traffic: TrafficCounter,
+    /// A status that arrived while a frame was being collected, handed
+    /// out by the next [`receive`](Self::receive).
+    pending_status: Mutex<Option<ScreenStatus>>,
+    /// Likewise for the foreground window.
+    pending_foreground: Mutex<Option<ForegroundInfo>>,
+    /// Thumbnail chunks received so far.
+    thumbnails: Mutex<ThumbnailAssembler>,
+    /// Zero of the stream clock stamped into `capture_us`.
+    epoch: Instant,
 }
 
 impl ScreenTransport {
     /// Wrap an already-bound `UdpSocket` targeting `remote_addr`.
     pub fn new(socket: UdpSocket, remote_addr: SocketAddr) -> Self {
         Self {
-            socket,
+            socket: Arc::new(socket),
             remote_addr,
             sequence: AtomicU32::new(0),
             mtu: DEFAULT_MTU,
-            bytes_sent: std::sync::atomic::AtomicU64::new(0),
+            session: 0,
+            traffic: TrafficCounter::default(),
+            pending_status: Mutex::new(None),
+            pending_foreground: Mutex::new(None),
+            thumbnails: Mutex::new(ThumbnailAssembler::default()),
+            epoch: Instant::now(),
         }
     }
 
+    /// Count traffic into existing counters, e.g. ones that outlive a
+    /// single screen session.
+    pub fn with_traffic(mut self, traffic: TrafficCounter) -> Self {
+        self.traffic = traffic;
+        self
+    }
+
     /// Override the effective MTU (must be > [`ChunkHeader::SIZE`]).
     pub fn with_mtu(mut self, mtu: usize) -> Self {
         assert!(mtu > ChunkHeader::SIZE + 1);
@@ -166,79 +345,194 @@ impl ScreenTransport {
         self
     }
 
+    /// Carry session `session` (`< MAX_SESSIONS`); frames of other
+    /// sessions are dropped on receive.
+    pub fn with_session(mut self, session: u8) -> Self {
+        assert!(session < MAX_SESSIONS);
+        self.session = session;
+        self
+    }
+
+    /// The session this transport carries.
+    pub fn session(&self) -> u8 {
+        self.session
+    }
+
     /// Total bytes sent across all frames.
     pub fn bytes_sent(&self) -> u64 {
-        self.bytes_sent.load(Ordering::Relaxed)
+        self.traffic.sent()
+    }
+
+    /// Total bytes received, including datagrams that were dropped.
+    pub fn bytes_received(&self) -> u64 {
+        self.traffic.received()
+    }
+
+    /// A handle to this transport's counters.
+    pub fn traffic(&self) -> TrafficCounter {
+        self.traffic.clone()
     }
 
     /// Send an encoded frame as a sequence of UDP datagrams.
     pub async fn send_frame(&self, frame: &EncodedFrame) -> Result<(), TixError> {
         let seq = self.sequence.fetch_add(1, Ordering::SeqCst);
-        let chunk_payload_max = self.mtu - ChunkHeader::SIZE;
-        let total_chunks = (frame.data.len() + chunk_payload_max - 1) / chunk_payload_max;
+        let datagrams = frame_datagrams(frame, seq, self.session, self.mtu, self.epoch);
 
-        // 1. Frame header datagram.
-        let header = FrameHeader {
-            sequence: seq,
-            frame_number: frame.frame_number,
-            timestamp_us: frame.timestamp.elapsed().as_micros() as u64,
-            width: frame.width,
-            height: frame.height,
-            is_full_frame: frame.is_full_frame,
-            total_chunks: total_chunks as u32,
-        };
-        let header_bytes = header.encode();
+        let mut sent_total = 0;
+        for (idx, data) in datagrams.iter().enumerate() {
+            self.socket
+                .send_to(data, self.remote_addr)
+                .await
+                .map_err(|e| match idx {
+                    0 => TixError::Other(format!("UDP send header: {e}")),
+                    _ => TixError::Other(format!("UDP send chunk {}: {e}", idx - 1)),
+                })?;
+            sent_total += data.len();
+        }
+
+        self.traffic
+            .sent
+            .fetch_add(sent_total as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Send a status datagram between frames.
+    pub async fn send_status(&self, status: &ScreenStatus) -> Result<(), TixError> {
+        let data = desktop::status_datagram(status)?;
         self.socket
-            .send_to(&header_bytes, self.remote_addr)
+            .send_to(&data, self.remote_addr)
             .await
-            .map_err(|e| TixError::Other(format!("UDP send header: {e}")))?;
-
-        // 2. Data chunk datagrams.
-        let mut sent_total = header_bytes.len();
-        for (idx, chunk_data) in frame.data.chunks(chunk_payload_max).enumerate() {
-            let ch = ChunkHeader {
-                sequence: seq,
-                chunk_index: idx as u32,
-                chunk_size: chunk_data.len() as u32,
-            };
-
-            let mut pkt = Vec::with_capacity(ChunkHeader::SIZE + chunk_data.len());
-            pkt.extend_from_slice(&ch.encode());
-            pkt.extend_from_slice(chunk_data);
+            .map_err(|e| TixError::Other(format!("UDP send status: {e}")))?;
+        self.traffic
+            .sent
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
 
+    /// Send a thumbnail in place of a frame; returns the bytes sent.
+    pub async fn send_thumbnail(&self, thumbnail: &Thumbnail) -> Result<usize, TixError> {
+        let id = self.sequence.fetch_add(1, Ordering::SeqCst);
+        let mut sent_total = 0;
+        for data in thumbnail::thumbnail_datagrams(thumbnail, id, self.mtu)? {
             self.socket
-                .send_to(&pkt, self.remote_addr)
+                .send_to(&data, self.remote_addr)
                 .await
-                .map_err(|e| TixError::Other(format!("UDP send chunk {idx}: {e}")))?;
+                .map_err(|e| TixError::Other(format!("UDP send thumbnail: {e}")))?;
+            sent_total += data.len();
+        }
+        self.traffic
+            .sent
+            .fetch_add(sent_total as u64, Ordering::Relaxed);
+        Ok(sent_total)
+    }
+
+    /// Send the foreground window next to a thumbnail; returns the
+    /// bytes sent.
+    pub async fn send_foreground(&self, info: &ForegroundInfo) -> Result<usize, TixError> {
+        let data = thumbnail::foreground_datagram(info)?;
+        self.socket
+            .send_to(&data, self.remote_addr)
+            .await
+            .map_err(|e| TixError::Other(format!("UDP send foreground: {e}")))?;
+        self.traffic
+            .sent
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        Ok(data.len())
+    }
 
-            sent_total += pkt.len();
+    /// A handle that sends pointer moves for this session to the remote
+    /// end, on this transport's socket.
+    pub fn pointer_sender(&self) -> PointerSender {
+        PointerSender::new(
+            Arc::clone(&self.socket),
+            self.remote_addr,
+            self.session,
+            self.traffic.clone(),
+        )
+    }
+
+    /// Receive the next pointer move of this transport's session from
+    /// the remote end. Everything else arriving on the socket is
+    /// dropped, so only call this where no frames are expected, i.e. on
+    /// the sending side of the stream.
+    pub async fn receive_pointer(&self) -> Result<PointerMove, TixError> {
+        let mut buf = [0u8; PointerMove::SIZE + 1];
+        loop {
+            let (len, from) = self
+                .socket
+                .recv_from(&mut buf)
+                .await
+                .map_err(|e| TixError::Other(format!("UDP recv pointer: {e}")))?;
+            self.traffic
+                .received
+                .fetch_add(len as u64, Ordering::Relaxed);
+            if from != self.remote_addr {
+                continue;
+            }
+            if let Some(pointer) = PointerMove::decode(&buf[..len])
+                && pointer.session == self.session
+            {
+                return Ok(pointer);
+            }
         }
+    }
 
-        self.bytes_sent
-            .fetch_add(sent_total as u64, Ordering::Relaxed);
-        Ok(())
+    /// Receive the next complete frame, skipping everything else.
+    pub async fn receive_frame(&self) -> Result<EncodedFrame, TixError> {
+        loop {
+            if let ScreenMessage::Frame(frame) = self.receive().await? {
+                return Ok(frame);
+            }
+        }
     }
 
-    /// Receive the next complete frame.
+    /// Receive the next complete frame, status, thumbnail or foreground
+    /// message.
     ///
-    /// Waits for a frame header and then collects all chunks belonging
-    /// to that sequence number. Out-of-sequence datagrams are silently
-    /// dropped.
-    pub async fn receive_frame(&self) -> Result<EncodedFrame, TixError> {
+    /// Waits for a frame header of this transport's session and then
+    /// collects the chunks belonging to that sequence number from the
+    /// same sender. Out-of-sequence datagrams, and everything from other
+    /// sessions, are silently dropped. A status or foreground arriving
+    /// in the middle of a frame is returned by the following call; a
+    /// thumbnail completing there is returned instead of the frame.
+    pub async fn receive(&self) -> Result<ScreenMessage, TixError> {
+        if let Some(status) = self.pending_status.lock().unwrap().take() {
+            return Ok(ScreenMessage::Status(status));
+        }
+        if let Some(info) = self.pending_foreground.lock().unwrap().take() {
+            return Ok(ScreenMessage::Foreground(info));
+        }
         let mut buf = vec![0u8; self.mtu + FrameHeader::SIZE];
 
         // Wait for a frame header.
-        let header = loop {
-            let (len, _) = self
+        let (header, sender) = loop {
+            let (len, from) = self
                 .socket
                 .recv_from(&mut buf)
                 .await
                 .map_err(|e| TixError::Other(format!("UDP recv: {e}")))?;
+            self.traffic
+                .received
+                .fetch_add(len as u64, Ordering::Relaxed);
 
-            if len >= FrameHeader::SIZE {
-                if let Ok(h) = FrameHeader::decode(&buf[..len]) {
-                    break h;
+            if let Some(status) = desktop::parse_status(&buf[..len]) {
+                return Ok(ScreenMessage::Status(status));
+            }
+            if let Some(info) = thumbnail::parse_foreground(&buf[..len]) {
+                return Ok(ScreenMessage::Foreground(info));
+            }
+            if thumbnail::is_thumbnail(&buf[..len]) {
+                if let Some(thumbnail) = self.thumbnails.lock().unwrap().push(&buf[..len]) {
+                    return Ok(ScreenMessage::Thumbnail(thumbnail));
                 }
+                continue;
+            }
+            if len >= FrameHeader::MIN_SIZE
+                && !mtu::is_probe(&buf[..len])
+                && let Ok(h) = FrameHeader::decode(&buf[..len])
+                && h.session == self.session
+            {
+                break (h, from);
             }
         };
 
@@ -248,13 +542,34 @@ impl ScreenTransport {
         let mut received = 0usize;
 
         while received < total {
-            let (len, _) = self
+            let (len, from) = self
                 .socket
                 .recv_from(&mut buf)
                 .await
                 .map_err(|e| TixError::Other(format!("UDP recv chunk: {e}")))?;
+            self.traffic
+                .received
+                .fetch_add(len as u64, Ordering::Relaxed);
 
-            if len < ChunkHeader::SIZE {
+            if let Some(status) = desktop::parse_status(&buf[..len]) {
+                *self.pending_status.lock().unwrap() = Some(status);
+                continue;
+            }
+            if let Some(info) = thumbnail::parse_foreground(&buf[..len]) {
+                *self.pending_foreground.lock().unwrap() = Some(info);
+                continue;
+            }
+            if thumbnail::is_thumbnail(&buf[..len]) {
+                if let Some(thumbnail) = self.thumbnails.lock().unwrap().push(&buf[..len]) {
+                    return Ok(ScreenMessage::Thumbnail(thumbnail));
+                }
+                continue;
+            }
+            if len < ChunkHeader::SIZE
+                || from != sender
+                || mtu::is_probe(&buf[..len])
+                || pointer::is_pointer(&buf[..len])
+            {
                 continue;
             }
 
@@ -287,15 +602,21 @@ impl ScreenTransport {
             data.extend_from_slice(&chunk);
         }
 
-        Ok(EncodedFrame {
+        // Back-date the timestamp by the time the frame spent on the
+        // slave, so its age covers capture to display minus the wire.
+        let on_slave = Duration::from_micros(header.timestamp_us);
+        Ok(ScreenMessage::Frame(EncodedFrame {
             frame_number: header.frame_number,
-            timestamp: Instant::now(),
+            timestamp: Instant::now().checked_sub(on_slave).unwrap_or_else(Instant::now),
             width: header.width,
             height: header.height,
             data,
             is_full_frame: header.is_full_frame,
             block_count: 0,
-        })
+            format: header.format,
+            capture_clock: (header.capture_us != 0)
+                .then(|| Duration::from_micros(header.capture_us)),
+        }))
     }
 
     /// Returns a reference to the underlying socket.
@@ -307,6 +628,70 @@ impl ScreenTransport {
     pub fn remote_addr(&self) -> SocketAddr {
         self.remote_addr
     }
+
+    /// The effective MTU.
+    pub fn mtu(&self) -> usize {
+        self.mtu
+    }
+}
+
+// ── TransportConfig ──────────────────────────────────────────────
+
+/// Socket tuning for a screen stream, applied before the socket is
+/// handed to tokio.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportConfig {
+    /// MTU to use when not probing, or the upper bound when probing.
+    pub mtu: usize,
+    /// Measure the path MTU at session start (see [`mtu`]).
+    pub probe_mtu: bool,
+    /// `SO_RCVBUF` size; `None` keeps the OS default.
+    pub recv_buffer: Option<usize>,
+    /// `SO_SNDBUF` size; `None` keeps the OS default.
+    pub send_buffer: Option<usize>,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            mtu: DEFAULT_MTU,
+            probe_mtu: false,
+            recv_buffer: None,
+            send_buffer: None,
+        }
+    }
+}
+
+impl TransportConfig {
+    /// Bind a UDP socket on `addr` with the configured buffer sizes.
+    /// The OS may round the sizes (Linux doubles them) or cap them.
+    pub fn bind(&self, addr: SocketAddr) -> Result<UdpSocket, TixError> {
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        let err = |what: &str, e: std::io::Error| TixError::Other(format!("UDP {what}: {e}"));
+        let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))
+            .map_err(|e| err("socket", e))?;
+        if let Some(size) = self.recv_buffer {
+            socket.set_recv_buffer_size(size).map_err(|e| err("SO_RCVBUF", e))?;
+        }
+        if let Some(size) = self.send_buffer {
+            socket.set_send_buffer_size(size).map_err(|e| err("SO_SNDBUF", e))?;
+        }
+        socket.set_nonblocking(true).map_err(|e| err("nonblocking", e))?;
+        socket.bind(&addr.into()).map_err(|e| err(&format!("bind {addr}"), e))?;
+        UdpSocket::from_std(socket.into()).map_err(|e| err("register", e))
+    }
+
+    /// The MTU to stream to `peer` with: probed if enabled, otherwise
+    /// the configured value. Never smaller than a chunk header allows.
+    pub async fn negotiate_mtu(&self, socket: &UdpSocket, peer: SocketAddr) -> usize {
+        let mtu = if self.probe_mtu {
+            mtu::probe(socket, peer, self.mtu).await
+        } else {
+            self.mtu
+        };
+        mtu.max(ChunkHeader::SIZE + 2)
+    }
 }
 
 // ── Tests ────────────────────────────────────────────────────────
@@ -324,7 +709,10 @@ mod tests {
             width: 1920,
             height: 1080,
             is_full_frame: true,
+            session: 5,
+            format: PixelFormat::Rgb565,
             total_chunks: 8,
+            capture_us: 987_654,
         };
 
         let encoded = hdr.encode();
@@ -336,7 +724,45 @@ mod tests {
         assert_eq!(decoded.width, 1920);
         assert_eq!(decoded.height, 1080);
         assert!(decoded.is_full_frame);
+        assert_eq!(decoded.session, 5);
+        assert_eq!(decoded.format, PixelFormat::Rgb565);
         assert_eq!(decoded.total_chunks, 8);
+        assert_eq!(decoded.capture_us, 987_654);
+
+        // Senders that predate `capture_us` leave it off.
+        let legacy = FrameHeader::decode(&encoded[..FrameHeader::MIN_SIZE]).unwrap();
+        assert_eq!((legacy.frame_number, legacy.capture_us), (100, 0));
+        assert!(FrameHeader::decode(&encoded[..FrameHeader::MIN_SIZE - 1]).is_err());
+    }
+
+    #[test]
+    fn frame_header_flags_byte() {
+        let mut hdr = FrameHeader {
+            sequence: 1,
+            frame_number: 1,
+            timestamp_us: 0,
+            width: 8,
+            height: 8,
+            is_full_frame: false,
+            session: 0,
+            format: PixelFormat::Gray8,
+            total_chunks: 1,
+            capture_us: 0,
+        };
+        let decoded = FrameHeader::decode(&hdr.encode()).unwrap();
+        assert!(!decoded.is_full_frame);
+        assert_eq!(decoded.format, PixelFormat::Gray8);
+
+        // A legacy header (flags byte 0 or 1) is BGRA8.
+        hdr.format = PixelFormat::Bgra8;
+        hdr.is_full_frame = true;
+        let bytes = hdr.encode();
+        assert_eq!(bytes[28], 1);
+        assert_eq!(FrameHeader::decode(&bytes).unwrap().format, PixelFormat::Bgra8);
+
+        let mut bad = bytes;
+        bad[28] = 0xF1;
+        assert!(FrameHeader::decode(&bad).is_err());
     }
 
     #[test]
@@ -367,6 +793,50 @@ mod tests {
         assert!(ChunkHeader::decode(&short).is_err());
     }
 
+    #[test]
+    fn frames_are_cut_into_mtu_sized_datagrams() {
+        let epoch = Instant::now();
+        let frame = EncodedFrame {
+            frame_number: 7,
+            timestamp: epoch + Duration::from_millis(3),
+            width: 64,
+            height: 32,
+            data: (0..5000u32).map(|i| i as u8).collect(),
+            is_full_frame: false,
+            block_count: 2,
+            format: PixelFormat::Bgra8,
+            capture_clock: None,
+        };
+        let datagrams = frame_datagrams(&frame, 41, 2, DEFAULT_MTU, epoch);
+
+        let header = FrameHeader::decode(&datagrams[0]).unwrap();
+        assert_eq!(datagrams[0].len(), FrameHeader::SIZE);
+        assert_eq!((header.sequence, header.session), (41, 2));
+        assert_eq!(header.frame_number, 7);
+        assert_eq!(header.capture_us, 3000);
+        // 5000 bytes in chunks of 1388: three full and one of 836.
+        assert_eq!(header.total_chunks, 4);
+        assert_eq!(datagrams.len(), 5);
+
+        let mut data = Vec::new();
+        for (idx, datagram) in datagrams[1..].iter().enumerate() {
+            assert!(datagram.len() <= DEFAULT_MTU);
+            let chunk = ChunkHeader::decode(datagram).unwrap();
+            assert_eq!((chunk.sequence, chunk.chunk_index), (41, idx as u32));
+            assert_eq!(chunk.chunk_size as usize, datagram.len() - ChunkHeader::SIZE);
+            data.extend_from_slice(&datagram[ChunkHeader::SIZE..]);
+        }
+        assert_eq!(data, frame.data);
+
+        let empty = EncodedFrame {
+            data: Vec::new(),
+            ..frame
+        };
+        let datagrams = frame_datagrams(&empty, 0, 0, DEFAULT_MTU, epoch);
+        assert_eq!(datagrams.len(), 1);
+        assert_eq!(FrameHeader::decode(&datagrams[0]).unwrap().total_chunks, 0);
+    }
+
     #[tokio::test]
     async fn udp_transport_send_receive() {
         // Bind two sockets on localhost.
@@ -379,14 +849,17 @@ mod tests {
         let transport_send = ScreenTransport::new(sender_sock, receiver_addr);
         let transport_recv = ScreenTransport::new(receiver_sock, sender_addr);
 
+        // Captured 5 ms into the sender's stream clock.
         let frame = EncodedFrame {
             frame_number: 99,
-            timestamp: Instant::now(),
+            timestamp: Instant::now() + Duration::from_millis(5),
             width: 320,
             height: 240,
             data: vec![0xAB; 5000], // will need several chunks
             is_full_frame: true,
             block_count: 0,
+            format: PixelFormat::Rgb565,
+            capture_clock: None,
         };
 
         let send_handle = tokio::spawn(async move {
@@ -404,7 +877,249 @@ mod tests {
         assert_eq!(received.width, 320);
         assert_eq!(received.height, 240);
         assert!(received.is_full_frame);
+        assert_eq!(received.format, PixelFormat::Rgb565);
         assert_eq!(received.data.len(), 5000);
         assert!(received.data.iter().all(|&b| b == 0xAB));
+        assert!(received.capture_clock.unwrap() >= Duration::from_millis(5));
+    }
+
+    #[tokio::test]
+    async fn bind_applies_buffer_sizes() {
+        let config = TransportConfig {
+            recv_buffer: Some(64 * 1024),
+            send_buffer: Some(32 * 1024),
+            ..TransportConfig::default()
+        };
+        let socket = config.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        // Linux reports double the request; sizes this small are under
+        // every OS cap.
+        let sock = socket2::SockRef::from(&socket);
+        assert!(sock.recv_buffer_size().unwrap() >= 64 * 1024);
+        assert!(sock.send_buffer_size().unwrap() >= 32 * 1024);
+
+        // The socket is usable from tokio.
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.send_to(b"hi", peer.local_addr().unwrap()).await.unwrap();
+        let mut buf = [0u8; 2];
+        assert_eq!(peer.recv(&mut buf).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn status_between_chunks_follows_the_frame() {
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver_addr = receiver_sock.local_addr().unwrap();
+        let receiver = ScreenTransport::new(receiver_sock, sender.local_addr().unwrap());
+
+        let header = FrameHeader {
+            sequence: 3,
+            frame_number: 7,
+            timestamp_us: 0,
+            width: 2,
+            height: 1,
+            is_full_frame: true,
+            session: 0,
+            format: PixelFormat::Bgra8,
+            total_chunks: 1,
+            capture_us: 0,
+        };
+        let chunk = ChunkHeader { sequence: 3, chunk_index: 0, chunk_size: 8 };
+        let status = ScreenStatus::SecureDesktop { desktop: None };
+        let mut chunk_pkt = chunk.encode().to_vec();
+        chunk_pkt.extend_from_slice(&[1; 8]);
+        for datagram in [
+            header.encode().to_vec(),
+            desktop::status_datagram(&status).unwrap(),
+            chunk_pkt,
+        ] {
+            sender.send_to(&datagram, receiver_addr).await.unwrap();
+        }
+
+        let ScreenMessage::Frame(frame) = receiver.receive().await.unwrap() else {
+            panic!("expected the frame first");
+        };
+        assert_eq!(frame.frame_number, 7);
+        assert_eq!(frame.data, [1; 8]);
+        let next = receiver.receive().await.unwrap();
+        assert!(matches!(next, ScreenMessage::Status(s) if s == status));
+    }
+
+    #[tokio::test]
+    async fn thumbnail_replaces_a_frame_left_unfinished() {
+        let sender_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver_addr = receiver_sock.local_addr().unwrap();
+        let sender_addr = sender_sock.local_addr().unwrap();
+        let sender = ScreenTransport::new(sender_sock, receiver_addr).with_mtu(200);
+        let receiver = ScreenTransport::new(receiver_sock, sender_addr);
+
+        // The header of a frame whose chunks never come: the slave
+        // switched to thumbnails after sending it.
+        let header = FrameHeader {
+            sequence: 40,
+            frame_number: 1,
+            timestamp_us: 0,
+            width: 2,
+            height: 1,
+            is_full_frame: true,
+            session: 0,
+            format: PixelFormat::Bgra8,
+            total_chunks: 1,
+            capture_us: 0,
+        };
+        sender.socket().send_to(&header.encode(), receiver_addr).await.unwrap();
+
+        let thumbnail = Thumbnail {
+            width: 4,
+            height: 2,
+            screen_width: 8,
+            screen_height: 4,
+            jpeg: (0..=255).cycle().take(700).collect(),
+        };
+        let info = ForegroundInfo {
+            title: "Inbox".into(),
+            process: "outlook.exe".into(),
+            cursor: Some((3, 4)),
+        };
+        let sent = sender.send_thumbnail(&thumbnail).await.unwrap();
+        assert!(sent > 700);
+        sender.send_foreground(&info).await.unwrap();
+        let foreground = thumbnail::foreground_datagram(&info).unwrap().len();
+        assert_eq!(sender.bytes_sent(), (sent + foreground) as u64);
+
+        let ScreenMessage::Thumbnail(received) = receiver.receive().await.unwrap() else {
+            panic!("expected the thumbnail");
+        };
+        assert_eq!(received, thumbnail);
+        let next = receiver.receive().await.unwrap();
+        assert!(matches!(next, ScreenMessage::Foreground(f) if f == info));
+    }
+
+    #[tokio::test]
+    async fn receiver_keeps_to_its_own_session() {
+        // Two streams that both start at sequence 0, landing on one port.
+        let first = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let second = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver_addr = receiver_sock.local_addr().unwrap();
+        let receiver =
+            ScreenTransport::new(receiver_sock, second.local_addr().unwrap()).with_session(1);
+
+        let datagrams = |session: u8, fill: u8| {
+            let header = FrameHeader {
+                sequence: 0,
+                frame_number: 10 + session as u64,
+                timestamp_us: 0,
+                width: 2,
+                height: 1,
+                is_full_frame: true,
+                session,
+                format: PixelFormat::Bgra8,
+                total_chunks: 1,
+                capture_us: 0,
+            };
+            let mut chunk = ChunkHeader { sequence: 0, chunk_index: 0, chunk_size: 8 }
+                .encode()
+                .to_vec();
+            chunk.extend_from_slice(&[fill; 8]);
+            (header.encode().to_vec(), chunk)
+        };
+        let (first_header, first_chunk) = datagrams(0, 0xAA);
+        let (second_header, second_chunk) = datagrams(1, 0xBB);
+        first.send_to(&first_header, receiver_addr).await.unwrap();
+        second.send_to(&second_header, receiver_addr).await.unwrap();
+        first.send_to(&first_chunk, receiver_addr).await.unwrap();
+        second.send_to(&second_chunk, receiver_addr).await.unwrap();
+
+        let frame = receiver.receive_frame().await.unwrap();
+        assert_eq!(frame.frame_number, 11);
+        assert_eq!(frame.data, [0xBB; 8]);
+    }
+
+    #[tokio::test]
+    async fn sent_frames_carry_the_session() {
+        let sender_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sender =
+            ScreenTransport::new(sender_sock, receiver.local_addr().unwrap()).with_session(3);
+        assert_eq!(sender.session(), 3);
+
+        let frame = EncodedFrame {
+            frame_number: 1,
+            timestamp: Instant::now(),
+            width: 1,
+            height: 1,
+            data: vec![0; 4],
+            is_full_frame: true,
+            block_count: 0,
+            format: PixelFormat::Bgra8,
+            capture_clock: None,
+        };
+        sender.send_frame(&frame).await.unwrap();
+        let mut buf = [0u8; 64];
+        let len = receiver.recv(&mut buf).await.unwrap();
+        assert_eq!(FrameHeader::decode(&buf[..len]).unwrap().session, 3);
+    }
+
+    #[tokio::test]
+    async fn pointer_moves_reach_the_sender_side() {
+        let slave_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let viewer_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let stranger = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let slave_addr = slave_sock.local_addr().unwrap();
+        let slave =
+            ScreenTransport::new(slave_sock, viewer_sock.local_addr().unwrap()).with_session(2);
+        let viewer = ScreenTransport::new(viewer_sock, slave_addr).with_session(2);
+
+        // Moves from another host, or for another session, are ignored.
+        let foreign = PointerMove { session: 2, sequence: 9, x: 1, y: 1 };
+        stranger.send_to(&foreign.encode(), slave_addr).await.unwrap();
+        let other_session = PointerMove { session: 0, ..foreign };
+        viewer.socket().send_to(&other_session.encode(), slave_addr).await.unwrap();
+
+        let pointer = viewer.pointer_sender();
+        pointer.send(100, -20).await.unwrap();
+        pointer.send(101, -21).await.unwrap();
+
+        let first = slave.receive_pointer().await.unwrap();
+        assert_eq!((first.session, first.sequence, first.x, first.y), (2, 0, 100, -20));
+        let second = slave.receive_pointer().await.unwrap();
+        assert_eq!((second.sequence, second.x, second.y), (1, 101, -21));
+        assert_eq!(viewer.bytes_sent(), 2 * PointerMove::SIZE as u64);
+    }
+
+    #[tokio::test]
+    async fn pointer_datagrams_are_not_taken_for_chunks() {
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver_addr = receiver_sock.local_addr().unwrap();
+        let receiver = ScreenTransport::new(receiver_sock, sender.local_addr().unwrap());
+
+        // A pointer datagram of session 0, move 0 reads as chunk 0 of a
+        // frame whose sequence happens to spell the magic.
+        let sequence = u32::from_le_bytes(*b"TXPM");
+        let header = FrameHeader {
+            sequence,
+            frame_number: 4,
+            timestamp_us: 0,
+            width: 2,
+            height: 1,
+            is_full_frame: true,
+            session: 0,
+            format: PixelFormat::Bgra8,
+            total_chunks: 1,
+            capture_us: 0,
+        };
+        let mut chunk = ChunkHeader { sequence, chunk_index: 0, chunk_size: 8 }
+            .encode()
+            .to_vec();
+        chunk.extend_from_slice(&[7; 8]);
+        let pointer = PointerMove { session: 0, sequence: 0, x: 0, y: 0 };
+        for datagram in [header.encode().to_vec(), pointer.encode().to_vec(), chunk] {
+            sender.send_to(&datagram, receiver_addr).await.unwrap();
+        }
+
+        let frame = receiver.receive_frame().await.unwrap();
+        assert_eq!(frame.data, [7; 8]);
     }
 }
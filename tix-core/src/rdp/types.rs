@@ -8,7 +8,8 @@ use std::time::Instant;
 
 // ── PixelFormat ──────────────────────────────────────────────────
 
-/// Pixel layout for raw captured frames.
+/// Pixel layout for raw captured frames and for frame payloads on the
+/// wire.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PixelFormat {
     /// 4 bytes per pixel: Blue, Green, Red, Alpha (DXGI default).
@@ -17,6 +18,11 @@ pub enum PixelFormat {
     Rgba8,
     /// 3 bytes per pixel: Red, Green, Blue.
     Rgb8,
+    /// 2 bytes per pixel, little-endian `RRRRRGGG GGGBBBBB`. Reduced
+    /// colour for slow links; see [`convert`](crate::rdp::convert).
+    Rgb565,
+    /// 1 byte per pixel of luma.
+    Gray8,
 }
 
 impl PixelFormat {
@@ -25,6 +31,68 @@ impl PixelFormat {
         match self {
             PixelFormat::Bgra8 | PixelFormat::Rgba8 => 4,
             PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgb565 => 2,
+            PixelFormat::Gray8 => 1,
+        }
+    }
+
+    /// Identifier carried in the frame header (4 bits).
+    pub const fn wire_id(self) -> u8 {
+        match self {
+            PixelFormat::Bgra8 => 0,
+            PixelFormat::Rgba8 => 1,
+            PixelFormat::Rgb8 => 2,
+            PixelFormat::Rgb565 => 3,
+            PixelFormat::Gray8 => 4,
+        }
+    }
+
+    /// Inverse of [`wire_id`](Self::wire_id).
+    pub const fn from_wire_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(PixelFormat::Bgra8),
+            1 => Some(PixelFormat::Rgba8),
+            2 => Some(PixelFormat::Rgb8),
+            3 => Some(PixelFormat::Rgb565),
+            4 => Some(PixelFormat::Gray8),
+            _ => None,
+        }
+    }
+
+    /// Whether the encoder can produce this format from a BGRA capture.
+    pub const fn is_encodable(self) -> bool {
+        matches!(
+            self,
+            PixelFormat::Bgra8 | PixelFormat::Rgb565 | PixelFormat::Gray8
+        )
+    }
+}
+
+impl std::fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PixelFormat::Bgra8 => "bgra8",
+            PixelFormat::Rgba8 => "rgba8",
+            PixelFormat::Rgb8 => "rgb8",
+            PixelFormat::Rgb565 => "rgb565",
+            PixelFormat::Gray8 => "gray8",
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for PixelFormat {
+    type Err = String;
+
+    /// Parse a config value such as `"rgb565"` or `"gray"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "bgra" | "bgra8" => Ok(PixelFormat::Bgra8),
+            "rgba" | "rgba8" => Ok(PixelFormat::Rgba8),
+            "rgb" | "rgb8" => Ok(PixelFormat::Rgb8),
+            "rgb565" | "565" => Ok(PixelFormat::Rgb565),
+            "gray" | "gray8" | "grey" | "grayscale" => Ok(PixelFormat::Gray8),
+            other => Err(format!("unknown pixel format '{other}'")),
         }
     }
 }
@@ -37,6 +105,10 @@ impl PixelFormat {
 /// `stride` may be larger than `width * bytes_per_pixel` due to
 /// GPU row-alignment requirements (e.g. DXGI may pad rows to 256-byte
 /// boundaries).
+///
+/// The capture buffer is used as is through delta detection and
+/// encoding; padding is only dropped when the encoder packs the changed
+/// blocks, so encoded frames on the wire are always tightly packed.
 #[derive(Debug, Clone)]
 pub struct RawScreenFrame {
     /// Frame width in pixels.
@@ -66,6 +138,29 @@ impl RawScreenFrame {
         &self.data[start..end]
     }
 
+    /// The pixels of each row of the `width × height` rectangle at
+    /// `(x, y)`, padding excluded. Lets later stages read straight from
+    /// the strided capture buffer instead of a tightly packed copy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rectangle is out of bounds.
+    pub fn region_rows(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> impl Iterator<Item = &[u8]> + '_ {
+        let bpp = self.format.bytes_per_pixel();
+        let left = x as usize * bpp;
+        let len = width as usize * bpp;
+        (y..y + height).map(move |row| {
+            let start = row as usize * self.stride as usize + left;
+            &self.data[start..start + len]
+        })
+    }
+
     /// Returns the pixel bytes at `(x, y)`.
     ///
     /// # Panics
@@ -0,0 +1,174 @@
+//! Capture watchdog for the screen service.
+//!
+//! A GPU driver reset can leave desktop duplication wedged: every
+//! acquire times out or fails, yet nothing reports the capture as lost,
+//! so the viewer sits on the last frame while the control channel looks
+//! healthy. The same timeouts are also what a static desktop produces,
+//! so a single one proves nothing; only a long run of them does.
+//!
+//! [`CaptureWatchdog`] tracks when the last frame was captured. Once no
+//! frame has arrived for the stall timeout the service rebuilds the
+//! capturer (device, output enumeration and duplication) and repaints
+//! with a keyframe. A rebuild that is not followed by a frame counts as
+//! failed; each failure doubles the wait before the next attempt, up to
+//! [`MAX_BACKOFF`], and after `max_failures` in a row the viewer is told
+//! with a [`ScreenStatus::CaptureFailed`]. The watchdog keeps retrying
+//! after that; the first frame resets it.
+
+use std::time::{Duration, Instant};
+
+use crate::rdp::desktop::ScreenStatus;
+
+/// How long capture may go without a frame before it is rebuilt.
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Rebuilds without a frame before the viewer is told.
+pub const DEFAULT_MAX_FAILURES: u32 = 3;
+
+/// Longest wait between rebuild attempts.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// ── CaptureWatchdog ──────────────────────────────────────────────
+
+/// Decides when a stalled capturer gets rebuilt.
+///
+/// Call [`on_frame`](Self::on_frame) for every captured frame and
+/// [`is_due`](Self::is_due) when capture comes back empty; when it is
+/// due, rebuild and report the outcome with
+/// [`on_rebuild`](Self::on_rebuild).
+#[derive(Debug, Clone)]
+pub struct CaptureWatchdog {
+    stall_timeout: Duration,
+    max_failures: u32,
+    deadline: Instant,
+    failures: u32,
+    last_error: Option<String>,
+    reported: bool,
+}
+
+impl CaptureWatchdog {
+    /// A watchdog that fires after `stall_timeout` without a frame and
+    /// reports after `max_failures` fruitless rebuilds.
+    pub fn new(stall_timeout: Duration, max_failures: u32, now: Instant) -> Self {
+        Self {
+            stall_timeout,
+            max_failures: max_failures.max(1),
+            deadline: now + stall_timeout,
+            failures: 0,
+            last_error: None,
+            reported: false,
+        }
+    }
+
+    /// Record a captured frame. Returns [`ScreenStatus::Live`] if the
+    /// viewer was told capture had failed.
+    pub fn on_frame(&mut self, now: Instant) -> Option<ScreenStatus> {
+        self.deadline = now + self.stall_timeout;
+        self.failures = 0;
+        self.last_error = None;
+        std::mem::take(&mut self.reported).then_some(ScreenStatus::Live)
+    }
+
+    /// Restart the clock without counting anything, e.g. after a pause
+    /// or while another stall (a secure desktop) is being handled.
+    pub fn rearm(&mut self, now: Instant) {
+        self.deadline = self.deadline.max(now + self.stall_timeout);
+    }
+
+    /// Remember the most recent capture error, for the log line when the
+    /// watchdog fires.
+    pub fn on_error(&mut self, error: impl ToString) {
+        self.last_error = Some(error.to_string());
+    }
+
+    /// The last capture error since the last frame.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Whether the capturer should be rebuilt at `now`.
+    pub fn is_due(&self, now: Instant) -> bool {
+        now >= self.deadline
+    }
+
+    /// Record a rebuild attempt made at `now`; `result` is its error,
+    /// if any. Returns the status to send to the viewer, if any.
+    ///
+    /// Even a successful rebuild counts as a failure until a frame
+    /// proves otherwise; [`on_frame`](Self::on_frame) clears it.
+    pub fn on_rebuild(&mut self, now: Instant, result: Result<(), String>) -> Option<ScreenStatus> {
+        self.failures = self.failures.saturating_add(1);
+        if let Err(e) = result {
+            self.last_error = Some(e);
+        }
+        self.deadline = now + self.backoff();
+        if self.failures < self.max_failures || self.reported {
+            return None;
+        }
+        self.reported = true;
+        let error = self.last_error.clone().unwrap_or_else(|| "no frames".into());
+        Some(ScreenStatus::CaptureFailed { error })
+    }
+
+    /// Wait before the next rebuild: the stall timeout, doubled for
+    /// every failed attempt so far.
+    pub fn backoff(&self) -> Duration {
+        let doublings = self.failures.saturating_sub(1).min(16);
+        self.stall_timeout
+            .saturating_mul(1 << doublings)
+            .min(MAX_BACKOFF.max(self.stall_timeout))
+    }
+
+    /// Rebuilds since the last frame.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_only_after_the_stall_timeout() {
+        let start = Instant::now();
+        let mut dog = CaptureWatchdog::new(Duration::from_secs(5), 3, start);
+        assert!(!dog.is_due(start + Duration::from_secs(4)));
+        assert!(dog.is_due(start + Duration::from_secs(5)));
+
+        assert_eq!(dog.on_frame(start + Duration::from_secs(4)), None);
+        assert!(!dog.is_due(start + Duration::from_secs(8)));
+        dog.rearm(start + Duration::from_secs(8));
+        assert!(!dog.is_due(start + Duration::from_secs(12)));
+    }
+
+    #[test]
+    fn backs_off_and_reports_once() {
+        let mut now = Instant::now();
+        let mut dog = CaptureWatchdog::new(Duration::from_secs(5), 3, now);
+        dog.on_error("AcquireNextFrame failed (0x887A0005)");
+
+        let mut reports = Vec::new();
+        let mut waits = Vec::new();
+        for _ in 0..6 {
+            now += dog.backoff();
+            assert!(dog.is_due(now));
+            reports.extend(dog.on_rebuild(now, Err("DuplicateOutput failed".into())));
+            waits.push(dog.backoff().as_secs());
+        }
+        assert_eq!(waits, [5, 10, 20, 40, 60, 60]);
+        assert_eq!(dog.failures(), 6);
+        assert_eq!(
+            reports,
+            [ScreenStatus::CaptureFailed {
+                error: "DuplicateOutput failed".into()
+            }]
+        );
+
+        assert_eq!(dog.on_frame(now), Some(ScreenStatus::Live));
+        assert_eq!(dog.failures(), 0);
+        assert_eq!(dog.backoff(), Duration::from_secs(5));
+    }
+}
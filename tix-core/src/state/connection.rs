@@ -7,6 +7,8 @@
 use std::time::Instant;
 
 use crate::error::TixError;
+use crate::features::{Feature, FeatureSet};
+use crate::message::Command;
 
 // ── ConnectionPhase ──────────────────────────────────────────────
 
@@ -184,6 +186,10 @@ pub struct PeerCapabilities {
 
     /// Maximum payload size the peer will accept.
     pub max_payload_size: u64,
+
+    /// Optional features the peer has, when it listed them. `None`
+    /// says nothing either way, and so lets every command through.
+    pub features: Option<FeatureSet>,
 }
 
 impl Default for PeerCapabilities {
@@ -194,12 +200,15 @@ impl Default for PeerCapabilities {
             screen_capture: true,
             compression: true,
             max_payload_size: crate::packet::MAX_PAYLOAD_SIZE as u64,
+            features: None,
         }
     }
 }
 
 impl PeerCapabilities {
     /// Negotiate capabilities by taking the intersection of both peers.
+    /// A side that did not list its features leaves the other's list as
+    /// it is.
     pub fn negotiate(&self, remote: &Self) -> Self {
         Self {
             shell_streaming: self.shell_streaming && remote.shell_streaming,
@@ -207,8 +216,17 @@ impl PeerCapabilities {
             screen_capture: self.screen_capture && remote.screen_capture,
             compression: self.compression && remote.compression,
             max_payload_size: self.max_payload_size.min(remote.max_payload_size),
+            features: match (&self.features, &remote.features) {
+                (Some(ours), Some(theirs)) => Some(ours.intersection(theirs)),
+                (ours, theirs) => ours.clone().or_else(|| theirs.clone()),
+            },
         }
     }
+
+    /// The feature `cmd` needs that the peer said it lacks, if any.
+    pub fn missing_for(&self, cmd: Command) -> Option<Feature> {
+        self.features.as_ref()?.missing_for(cmd)
+    }
 }
 
 // ── Tests ────────────────────────────────────────────────────────
@@ -316,6 +334,41 @@ mod tests {
         assert!(!negotiated.compression);
         assert_eq!(negotiated.max_payload_size, 1024);
         assert!(negotiated.shell_streaming);
+        assert_eq!(negotiated.features, None);
+        assert_eq!(negotiated.missing_for(Command::SendSas), None);
+    }
+
+    #[test]
+    fn features_negotiate() {
+        let listed = |names: &[&str]| PeerCapabilities {
+            features: Some(FeatureSet::from_names(names)),
+            ..Default::default()
+        };
+        // The master lists nothing: the slave's list stands.
+        let slave = listed(&["services", "eventlog", "quantum"]);
+        let negotiated = PeerCapabilities::default().negotiate(&slave);
+        assert_eq!(negotiated.features, slave.features);
+        assert_eq!(negotiated.missing_for(Command::ServiceControl), None);
+        assert_eq!(
+            negotiated.missing_for(Command::TrashRestore),
+            Some(Feature::Trash)
+        );
+        assert_eq!(negotiated.missing_for(Command::ListDir), None);
+
+        // Both listed: only what both have.
+        let negotiated = listed(&["eventlog", "trash"]).negotiate(&slave);
+        assert_eq!(
+            negotiated.features,
+            Some(FeatureSet::from_names(["eventlog"]))
+        );
+        assert_eq!(
+            negotiated.missing_for(Command::ServiceList),
+            Some(Feature::Services)
+        );
+
+        // An empty list is a list: everything optional is missing.
+        let negotiated = slave.negotiate(&listed(&[]));
+        assert_eq!(negotiated.missing_for(Command::SendSas), Some(Feature::Sas));
     }
 
     #[test]
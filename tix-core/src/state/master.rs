@@ -1,13 +1,23 @@
 //! Master-side state tracking.
 //!
 //! Tracks the connection phase, negotiated capabilities, and outstanding
-//! requests with optional timeout support.
+//! requests with optional timeout support. The last few requests that
+//! stopped being tracked are remembered too, so a response arriving after
+//! its request was answered, timed out or given up on can be told apart
+//! from one nobody asked for.
+//!
+//! Each `MasterState` is one connection session: it hands out request
+//! IDs from a random offset and owns the session nonce responses must
+//! echo (see [`request_id`](super::request_id)).
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::time::{Duration, Instant};
 
+use crate::header::WireVersion;
+use crate::message::{Command, MessageType};
 use crate::packet::Packet;
 use crate::state::connection::{ConnectionPhase, PeerCapabilities};
+use crate::state::request_id::RequestIds;
 
 // ── TrackedRequest ────────────────────────────────────────────────
 
@@ -37,6 +47,33 @@ impl TrackedRequest {
     }
 }
 
+// ── FinishedRequest ──────────────────────────────────────────────
+
+/// Requests remembered after they stop being tracked.
+pub const RECENTLY_FINISHED: usize = 256;
+
+/// Why a request stopped being tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestEnd {
+    /// Its response arrived ([`MasterState::resolve`]).
+    Answered,
+    /// Its deadline passed ([`MasterState::drain_expired`]).
+    TimedOut,
+    /// The master stopped waiting, e.g. a cancelled search
+    /// ([`MasterState::abandon`]).
+    Abandoned,
+}
+
+/// A request that is no longer pending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FinishedRequest {
+    /// Command of the original request.
+    pub command: Option<Command>,
+    pub end: RequestEnd,
+    /// When it stopped being tracked.
+    pub at: Instant,
+}
+
 // ── MasterState ──────────────────────────────────────────────────
 
 /// Tracks outstanding requests and connection state on the master side.
@@ -56,6 +93,16 @@ pub struct MasterState {
 
     /// Default deadline applied to requests when none is specified.
     default_timeout: Option<Duration>,
+
+    /// The last [`RECENTLY_FINISHED`] requests to leave `requests`,
+    /// oldest first.
+    finished: VecDeque<(u64, FinishedRequest)>,
+
+    /// Request IDs and nonce of this session.
+    ids: RequestIds,
+
+    /// Header version agreed on in the `Hello` exchange.
+    wire_version: WireVersion,
 }
 
 impl MasterState {
@@ -66,6 +113,61 @@ impl MasterState {
             local_capabilities: PeerCapabilities::default(),
             requests: HashMap::new(),
             default_timeout: None,
+            finished: VecDeque::new(),
+            ids: RequestIds::random(),
+            wire_version: WireVersion::V1,
+        }
+    }
+
+    /// Hand out request IDs from `ids` instead of a random session.
+    pub fn with_request_ids(mut self, ids: RequestIds) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    // ── Session ───────────────────────────────────────────────────
+
+    /// Nonce of this session, to stamp on outgoing packets.
+    pub fn session(&self) -> u32 {
+        self.ids.session()
+    }
+
+    /// A fresh request ID: neither pending nor one of the last
+    /// [`RECENTLY_FINISHED`], so a late answer to an old request can
+    /// never resolve the new one.
+    pub fn next_request_id(&mut self) -> u64 {
+        loop {
+            let id = self.ids.next_id();
+            if !self.requests.contains_key(&id) && self.recently_finished(id).is_none() {
+                return id;
+            }
+        }
+    }
+
+    /// Header version agreed on with the slave; TIX1 until
+    /// [`set_wire_version`](Self::set_wire_version).
+    pub fn wire_version(&self) -> WireVersion {
+        self.wire_version
+    }
+
+    /// Note the header version the `Hello` exchange settled on.
+    pub fn set_wire_version(&mut self, version: WireVersion) {
+        self.wire_version = version;
+    }
+
+    /// Whether `packet` carries the nonce of another session: a
+    /// straggler from before a reconnect. A TIX1 packet has no room for
+    /// one and is this session's, as is anything before TIX2 is agreed
+    /// on. After that the slave knows the nonce from the `Hello`, so a
+    /// TIX2 response without one comes from a slave that does not.
+    pub fn is_from_other_session(&self, packet: &Packet) -> bool {
+        match packet.session() {
+            0 => {
+                self.wire_version >= WireVersion::V2
+                    && packet.wire_version() >= WireVersion::V2
+                    && packet.message_type() == MessageType::Response
+            }
+            session => session != self.session(),
         }
     }
 
@@ -147,7 +249,40 @@ impl MasterState {
 
     /// Resolve (complete) a request, returning its `Packet` if present.
     pub fn resolve(&mut self, request_id: u64) -> Option<Packet> {
-        self.requests.remove(&request_id).map(|r| r.packet)
+        self.finish(request_id, RequestEnd::Answered)
+    }
+
+    /// Stop waiting for a request without an answer.
+    pub fn abandon(&mut self, request_id: u64) -> Option<Packet> {
+        self.finish(request_id, RequestEnd::Abandoned)
+    }
+
+    fn finish(&mut self, request_id: u64, end: RequestEnd) -> Option<Packet> {
+        let req = self.requests.remove(&request_id)?;
+        self.remember(request_id, &req, end);
+        Some(req.packet)
+    }
+
+    fn remember(&mut self, request_id: u64, req: &TrackedRequest, end: RequestEnd) {
+        if self.finished.len() == RECENTLY_FINISHED {
+            self.finished.pop_front();
+        }
+        let finished = FinishedRequest {
+            command: req.packet.command().ok(),
+            end,
+            at: Instant::now(),
+        };
+        self.finished.push_back((request_id, finished));
+    }
+
+    /// How a recent request stopped being tracked, if it is one of the
+    /// last [`RECENTLY_FINISHED`].
+    pub fn recently_finished(&self, request_id: u64) -> Option<&FinishedRequest> {
+        self.finished
+            .iter()
+            .rev()
+            .find(|(id, _)| *id == request_id)
+            .map(|(_, finished)| finished)
     }
 
     /// Number of in-flight requests.
@@ -180,10 +315,14 @@ impl MasterState {
     /// Remove and return all expired requests.
     pub fn drain_expired(&mut self) -> Vec<(u64, TrackedRequest)> {
         let expired_ids: Vec<u64> = self.check_timeouts();
-        expired_ids
+        let expired: Vec<(u64, TrackedRequest)> = expired_ids
             .into_iter()
             .filter_map(|id| self.requests.remove(&id).map(|r| (id, r)))
-            .collect()
+            .collect();
+        for (id, req) in &expired {
+            self.remember(*id, req, RequestEnd::TimedOut);
+        }
+        expired
     }
 }
 
@@ -198,7 +337,6 @@ impl Default for MasterState {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::message::Command;
 
     fn dummy_packet() -> Packet {
         Packet::new_command(1, Command::Ping, Vec::new()).unwrap()
@@ -264,6 +402,38 @@ mod tests {
         assert_eq!(state.pending_count(), 1); // request 2 still alive
     }
 
+    #[test]
+    fn finished_requests_are_remembered_with_their_end() {
+        let mut state = MasterState::new();
+        state.track(1, dummy_packet());
+        state.track_with_deadline(2, dummy_packet(), Some(Duration::ZERO));
+        state.track(3, dummy_packet());
+        state.resolve(1);
+        std::thread::sleep(Duration::from_millis(1));
+        state.drain_expired();
+        state.abandon(3);
+
+        let end = |id| state.recently_finished(id).map(|f| f.end);
+        assert_eq!(end(1), Some(RequestEnd::Answered));
+        assert_eq!(end(2), Some(RequestEnd::TimedOut));
+        assert_eq!(end(3), Some(RequestEnd::Abandoned));
+        assert_eq!(end(4), None);
+        assert_eq!(
+            state.recently_finished(1).unwrap().command,
+            Some(Command::Ping)
+        );
+
+        for id in 10..10 + RECENTLY_FINISHED as u64 {
+            state.track(id, dummy_packet());
+            state.resolve(id);
+        }
+        assert!(
+            state.recently_finished(1).is_none(),
+            "oldest entries are forgotten"
+        );
+        assert!(state.recently_finished(10).is_some());
+    }
+
     #[test]
     fn phase_starts_disconnected() {
         let state = MasterState::new();
@@ -299,4 +469,43 @@ mod tests {
         assert!(req.deadline.is_none());
         assert!(req.elapsed() < Duration::from_secs(1));
     }
+
+    #[test]
+    fn new_ids_skip_pending_and_recent_requests() {
+        let mut state = MasterState::new().with_request_ids(RequestIds::starting_at(5, 9));
+        state.track(5, dummy_packet());
+        state.track(6, dummy_packet());
+        state.resolve(6);
+        assert_eq!(state.next_request_id(), 7);
+        assert_eq!(state.next_request_id(), 8);
+    }
+
+    #[test]
+    fn stragglers_of_another_session_are_told_apart() {
+        let state = MasterState::new().with_request_ids(RequestIds::starting_at(1, 9));
+        let mut packet = Packet::new_response(1, Command::Ping, Vec::new()).unwrap();
+        assert!(!state.is_from_other_session(&packet));
+        packet.set_session(9);
+        assert!(!state.is_from_other_session(&packet));
+        packet.set_session(8);
+        assert!(state.is_from_other_session(&packet));
+        assert_ne!(MasterState::new().session(), MasterState::new().session());
+    }
+
+    #[test]
+    fn unstamped_tix2_responses_are_foreign_once_tix2_is_agreed() {
+        let mut state = MasterState::new().with_request_ids(RequestIds::starting_at(1, 9));
+        let mut packet = Packet::new_response(1, Command::Ping, Vec::new()).unwrap();
+        packet.set_wire_version(WireVersion::V2);
+        assert!(!state.is_from_other_session(&packet));
+
+        state.set_wire_version(WireVersion::V2);
+        assert!(state.is_from_other_session(&packet));
+        packet.set_session(9);
+        assert!(!state.is_from_other_session(&packet));
+
+        // TIX1 has no nonce to carry.
+        let tix1 = Packet::new_response(1, Command::Ping, Vec::new()).unwrap();
+        assert!(!state.is_from_other_session(&tix1));
+    }
 }
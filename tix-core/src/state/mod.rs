@@ -1,7 +1,9 @@
 pub mod connection;
 mod master;
+pub mod request_id;
 mod slave;
 
 pub use connection::{ConnectionPhase, PeerCapabilities};
-pub use master::{MasterState, TrackedRequest};
+pub use master::{FinishedRequest, MasterState, RECENTLY_FINISHED, RequestEnd, TrackedRequest};
+pub use request_id::RequestIds;
 pub use slave::SlaveState;
@@ -0,0 +1,108 @@
+//! Request IDs and session nonces.
+//!
+//! IDs used to start at 1 on every master start and after every
+//! reconnect, so a slave still finishing request 5 of the previous
+//! session could answer the new session's request 5. [`RequestIds`]
+//! instead starts each session at a random offset in the low
+//! [`REQUEST_ID_BITS`] bits, leaving the top 16 free for multiplexing
+//! several slaves later, and draws a random session nonce with it. The
+//! master stamps the nonce on its TIX2 headers, the slave echoes it on
+//! everything it sends, and a response carrying another session's nonce
+//! is dropped (see [`MasterState::is_from_other_session`]).
+//!
+//! [`MasterState::is_from_other_session`]: super::MasterState::is_from_other_session
+
+use uuid::Uuid;
+
+/// Bits of a request ID the generator uses.
+pub const REQUEST_ID_BITS: u32 = 48;
+
+/// Mask of the bits a generated request ID may set.
+pub const REQUEST_ID_MASK: u64 = (1 << REQUEST_ID_BITS) - 1;
+
+/// `counter` folded into the ID space: its low [`REQUEST_ID_BITS`]
+/// bits, with 0 (unsolicited slave packets) skipped.
+pub fn wrap(counter: u64) -> u64 {
+    match counter & REQUEST_ID_MASK {
+        0 => 1,
+        id => id,
+    }
+}
+
+/// A random starting point for request IDs.
+pub fn random_offset() -> u64 {
+    wrap(Uuid::new_v4().as_u64_pair().0)
+}
+
+/// Hands out the request IDs of one connection session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestIds {
+    next: u64,
+    session: u32,
+}
+
+impl RequestIds {
+    /// A session with a random first ID and a random non-zero nonce.
+    pub fn random() -> Self {
+        let (high, low) = Uuid::new_v4().as_u64_pair();
+        Self::starting_at(high, (low as u32).max(1))
+    }
+
+    /// A session whose first ID is `first` (folded into the ID space),
+    /// with nonce `session`.
+    pub fn starting_at(first: u64, session: u32) -> Self {
+        Self {
+            next: wrap(first),
+            session,
+        }
+    }
+
+    /// The session nonce; 0 stands for none.
+    pub fn session(&self) -> u32 {
+        self.session
+    }
+
+    /// The next ID, wrapping within [`REQUEST_ID_BITS`] bits.
+    pub fn next_id(&mut self) -> u64 {
+        let id = self.next;
+        self.next = wrap(id.wrapping_add(1));
+        id
+    }
+}
+
+impl Default for RequestIds {
+    fn default() -> Self {
+        Self::random()
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_stay_in_48_bits_and_skip_zero() {
+        let mut ids = RequestIds::starting_at(REQUEST_ID_MASK - 1, 7);
+        assert_eq!(ids.next_id(), REQUEST_ID_MASK - 1);
+        assert_eq!(ids.next_id(), REQUEST_ID_MASK);
+        assert_eq!(ids.next_id(), 1);
+        assert_eq!(ids.next_id(), 2);
+        assert_eq!(ids.session(), 7);
+        assert_eq!(RequestIds::starting_at(1 << 60, 7).next_id(), 1);
+    }
+
+    #[test]
+    fn sessions_start_apart() {
+        let a = RequestIds::random();
+        let b = RequestIds::random();
+        assert_ne!(a, b);
+        assert_ne!(a.session(), 0);
+        for ids in [a, b] {
+            let mut ids = ids;
+            assert_eq!(ids.next_id() & !REQUEST_ID_MASK, 0);
+        }
+        assert_ne!(random_offset(), 0);
+    }
+}
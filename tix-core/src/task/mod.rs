@@ -8,14 +8,28 @@
 //! - **CancellationToken**: every task receives a token; `cancel_task()`
 //!   or `cancel_all()` signal cooperative cancellation.
 //! - **Per-task timeout**: optionally auto-cancel after a deadline.
-//! - **Typed errors**: `TaskEvent::Error` carries a [`TaskError`] enum.
+//! - **Typed errors**: `TaskEvent::Error` carries a [`TaskError`] enum,
+//!   handed back by [`TaskPool::process_event`] so the owner can answer
+//!   the request the task was serving.
+//! - **Panic safety**: a task body that panics ends with
+//!   [`TaskError::Panicked`] rather than vanishing with its request
+//!   unanswered; the pool and its other tasks carry on. This needs a
+//!   build with `panic = "unwind"`; under `abort` a panic still ends the
+//!   process.
+//! - **One task per request**: spawning under an ID that is still
+//!   tracked fails with [`TaskError::DuplicateRequest`] and leaves the
+//!   running task alone.
 //! - **Metadata**: spawned time, optional name, active count.
+//! - **Snapshots**: [`TaskPool::snapshot`] lists the running tasks as
+//!   [`TaskInfo`] for the `TaskList` command.
 
 use std::collections::HashMap;
 use std::future::Future;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
 use std::time::{Duration, Instant};
 
+use futures::FutureExt;
 use tokio_util::sync::CancellationToken;
 
 /// A boxed async task factory: takes a connection sender, request ID, and
@@ -25,8 +39,10 @@ pub type BoxedTaskFn = Box<
     dyn FnOnce(ConnectionSender, u64, Vec<u8>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send,
 >;
 
+use crate::crash;
 use crate::error::TaskError;
 use crate::network::ConnectionSender;
+use crate::protocol::TaskInfo;
 
 // ── TaskEvent ────────────────────────────────────────────────────
 
@@ -38,10 +54,23 @@ pub type TaskEventSender = tokio::sync::mpsc::Sender<TaskEvent>;
 pub enum TaskEvent {
     /// The task completed successfully.
     Finished(u64),
-    /// The task failed with a typed error.
+    /// The task failed without answering its request: it timed out, was
+    /// cancelled, panicked, or gave up with a typed error. A task body that has
+    /// already sent its own error response must not report one. A body that
+    /// reports one itself is still followed by `Finished` when it returns;
+    /// the pool resolves a task on its first event and ignores the rest.
     Error(u64, TaskError),
 }
 
+impl TaskEvent {
+    /// Request ID of the task the event is about.
+    pub fn request_id(&self) -> u64 {
+        match self {
+            TaskEvent::Finished(id) | TaskEvent::Error(id, _) => *id,
+        }
+    }
+}
+
 // ── TaskOptions ──────────────────────────────────────────────────
 
 /// Configuration for a spawned task.
@@ -84,13 +113,17 @@ pub struct Task {
     spawned_at: Instant,
     /// Optional human-readable name.
     name: Option<String>,
+    /// Automatic cancellation deadline, if any.
+    timeout: Option<Duration>,
 }
 
 impl Task {
     /// Spawn a new task from an async closure.
     ///
     /// The closure runs inside a `tokio::select!` against the
-    /// cancellation token so it can be stopped cooperatively.
+    /// cancellation token so it can be stopped cooperatively. On timeout
+    /// or cancellation the closure's future is dropped, so it cannot
+    /// send anything after the `Error` event.
     pub fn spawn<F, Fut>(
         tx: ConnectionSender,
         req_id: u64,
@@ -108,7 +141,7 @@ impl Task {
         let timeout = options.timeout;
 
         let handle = tokio::spawn(async move {
-            let work = f(tx, req_id, payload);
+            let work = guarded(async move { f(tx, req_id, payload).await });
 
             match timeout {
                 Some(dur) => {
@@ -122,7 +155,10 @@ impl Task {
                             let _ = event_tx.send(TaskEvent::Error(req_id, TaskError::Timeout(dur))).await;
                             return;
                         }
-                        () = work => {}
+                        result = work => if let Err(err) = result {
+                            let _ = event_tx.send(TaskEvent::Error(req_id, err)).await;
+                            return;
+                        }
                     }
                 }
                 None => {
@@ -132,7 +168,10 @@ impl Task {
                             let _ = event_tx.send(TaskEvent::Error(req_id, TaskError::Cancelled)).await;
                             return;
                         }
-                        () = work => {}
+                        result = work => if let Err(err) = result {
+                            let _ = event_tx.send(TaskEvent::Error(req_id, err)).await;
+                            return;
+                        }
                     }
                 }
             }
@@ -146,6 +185,7 @@ impl Task {
             token,
             spawned_at: Instant::now(),
             name: options.name,
+            timeout,
         }
     }
 
@@ -163,7 +203,7 @@ impl Task {
         let timeout = options.timeout;
 
         let handle = tokio::spawn(async move {
-            let work = f(tx, req_id, payload);
+            let work = guarded(async move { f(tx, req_id, payload).await });
 
             match timeout {
                 Some(dur) => {
@@ -177,7 +217,10 @@ impl Task {
                             let _ = event_tx.send(TaskEvent::Error(req_id, TaskError::Timeout(dur))).await;
                             return;
                         }
-                        () = work => {}
+                        result = work => if let Err(err) = result {
+                            let _ = event_tx.send(TaskEvent::Error(req_id, err)).await;
+                            return;
+                        }
                     }
                 }
                 None => {
@@ -187,7 +230,10 @@ impl Task {
                             let _ = event_tx.send(TaskEvent::Error(req_id, TaskError::Cancelled)).await;
                             return;
                         }
-                        () = work => {}
+                        result = work => if let Err(err) = result {
+                            let _ = event_tx.send(TaskEvent::Error(req_id, err)).await;
+                            return;
+                        }
                     }
                 }
             }
@@ -201,6 +247,7 @@ impl Task {
             token,
             spawned_at: Instant::now(),
             name: options.name,
+            timeout,
         }
     }
 
@@ -219,6 +266,21 @@ impl Task {
         self.name.as_deref()
     }
 
+    /// The timeout the task was spawned with.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Wire description of this task as of now.
+    pub fn info(&self, req_id: u64) -> TaskInfo {
+        TaskInfo {
+            request_id: req_id,
+            name: self.name.clone(),
+            elapsed_ms: self.spawned_at.elapsed().as_millis() as u64,
+            timeout_ms: self.timeout.map(|t| t.as_millis() as u64),
+        }
+    }
+
     /// Get a child token that downstream work can use to check for
     /// cancellation.
     pub fn cancellation_token(&self) -> CancellationToken {
@@ -226,6 +288,18 @@ impl Task {
     }
 }
 
+/// Run `work`, turning a panic inside it into [`TaskError::Panicked`]
+/// so the pool hears of it instead of losing the task.
+async fn guarded<Fut>(work: Fut) -> Result<(), TaskError>
+where
+    Fut: Future<Output = ()>,
+{
+    AssertUnwindSafe(work)
+        .catch_unwind()
+        .await
+        .map_err(|payload| TaskError::Panicked(crash::panic_message(payload.as_ref())))
+}
+
 // ── TaskPool ─────────────────────────────────────────────────────
 
 /// Pool that tracks in-flight tasks and dispatches events.
@@ -251,15 +325,23 @@ impl TaskPool {
     /// Spawn a task with a generic async function (backward-compatible).
     ///
     /// Uses default options (no timeout, no name).
-    pub fn spawn<F, Fut>(&mut self, tx: ConnectionSender, req_id: u64, payload: Vec<u8>, f: F)
+    pub fn spawn<F, Fut>(
+        &mut self,
+        tx: ConnectionSender,
+        req_id: u64,
+        payload: Vec<u8>,
+        f: F,
+    ) -> Result<(), TaskError>
     where
         F: FnOnce(ConnectionSender, u64, Vec<u8>) -> Fut + Send + 'static,
         Fut: Future<Output = ()> + Send + 'static,
     {
-        self.spawn_with_options(tx, req_id, payload, f, TaskOptions::default());
+        self.spawn_with_options(tx, req_id, payload, f, TaskOptions::default())
     }
 
     /// Spawn a task with explicit options (name, timeout).
+    ///
+    /// Nothing is started if a task is already tracked under `req_id`.
     pub fn spawn_with_options<F, Fut>(
         &mut self,
         tx: ConnectionSender,
@@ -267,12 +349,15 @@ impl TaskPool {
         payload: Vec<u8>,
         f: F,
         options: TaskOptions,
-    ) where
+    ) -> Result<(), TaskError>
+    where
         F: FnOnce(ConnectionSender, u64, Vec<u8>) -> Fut + Send + 'static,
         Fut: Future<Output = ()> + Send + 'static,
     {
+        self.check_free(req_id)?;
         let task = Task::spawn(tx, req_id, payload, f, self.pool_tx.clone(), options);
         self.tasks.insert(req_id, task);
+        Ok(())
     }
 
     /// Spawn with a boxed future (backward-compatible).
@@ -282,16 +367,8 @@ impl TaskPool {
         req_id: u64,
         payload: Vec<u8>,
         f: BoxedTaskFn,
-    ) {
-        let task = Task::spawn_boxed(
-            tx,
-            req_id,
-            payload,
-            f,
-            self.pool_tx.clone(),
-            TaskOptions::default(),
-        );
-        self.tasks.insert(req_id, task);
+    ) -> Result<(), TaskError> {
+        self.spawn_boxed_with_options(tx, req_id, payload, f, TaskOptions::default())
     }
 
     /// Spawn boxed with explicit options.
@@ -302,9 +379,27 @@ impl TaskPool {
         payload: Vec<u8>,
         f: BoxedTaskFn,
         options: TaskOptions,
-    ) {
+    ) -> Result<(), TaskError> {
+        self.check_free(req_id)?;
         let task = Task::spawn_boxed(tx, req_id, payload, f, self.pool_tx.clone(), options);
         self.tasks.insert(req_id, task);
+        Ok(())
+    }
+
+    /// Refuse `req_id` while a task is tracked under it: replacing the
+    /// entry would orphan the running task and its cancellation token.
+    fn check_free(&self, req_id: u64) -> Result<(), TaskError> {
+        match self.tasks.get(&req_id) {
+            Some(running) => {
+                eprintln!(
+                    "[TASK] ReqID {} already running ({}); not spawning another",
+                    req_id,
+                    running.name().unwrap_or("unnamed")
+                );
+                Err(TaskError::DuplicateRequest(req_id))
+            }
+            None => Ok(()),
+        }
     }
 
     // ── Cancellation ──────────────────────────────────────────────
@@ -345,6 +440,19 @@ impl TaskPool {
         self.tasks.get(&req_id)
     }
 
+    /// Iterate over the tracked tasks and their request IDs, in no
+    /// particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &Task)> {
+        self.tasks.iter().map(|(id, task)| (*id, task))
+    }
+
+    /// Describe every tracked task, ordered by request ID.
+    pub fn snapshot(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<TaskInfo> = self.iter().map(|(id, task)| task.info(id)).collect();
+        tasks.sort_by_key(|t| t.request_id);
+        tasks
+    }
+
     // ── Callbacks & Events ────────────────────────────────────────
 
     /// Register a callback invoked when any task finishes.
@@ -360,26 +468,31 @@ impl TaskPool {
         self.pool_rx.recv().await
     }
 
-    /// Process a single task event.
-    pub async fn process_event(&mut self, event: TaskEvent) {
-        match &event {
-            TaskEvent::Finished(id) | TaskEvent::Error(id, _) => {
-                self.tasks.remove(id);
-                for cb in &self.finished_callbacks {
-                    cb(*id);
-                }
-                if let TaskEvent::Error(id, err) = &event {
-                    eprintln!("[TASK] {id} failed: {err}");
-                }
-            }
+    /// Process a single task event, returning the error of a failed
+    /// task so the caller can report it to whoever is waiting. Only the
+    /// first event for a task counts: a later one (the `Finished` that
+    /// follows an error the body reported itself) is dropped.
+    pub async fn process_event(&mut self, event: TaskEvent) -> Option<TaskError> {
+        let id = event.request_id();
+        self.tasks.remove(&id)?;
+        for cb in &self.finished_callbacks {
+            cb(id);
+        }
+        match event {
+            TaskEvent::Finished(_) => None,
+            TaskEvent::Error(_, err) => Some(err),
         }
     }
 
-    /// Consume the pool into a background processing loop.
+    /// Consume the pool into a background processing loop. Nobody is
+    /// left to answer for failed tasks, so their errors are only logged.
     pub fn start(mut self) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
             while let Some(event) = self.pool_rx.recv().await {
-                self.process_event(event).await;
+                let id = event.request_id();
+                if let Some(err) = self.process_event(event).await {
+                    eprintln!("[TASK] {id} failed: {err}");
+                }
             }
         })
     }
@@ -401,12 +514,14 @@ impl Default for TaskPool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use tokio::sync::mpsc;
 
     /// Helper: create a dummy ConnectionSender.
     fn dummy_sender() -> ConnectionSender {
         let (tx, _rx) = mpsc::channel(1);
-        tx
+        tx.into()
     }
 
     #[tokio::test]
@@ -414,7 +529,8 @@ mod tests {
         let mut pool = TaskPool::new();
         let tx = dummy_sender();
 
-        pool.spawn(tx, 1, Vec::new(), |_tx, _req, _payload| async {});
+        pool.spawn(tx, 1, Vec::new(), |_tx, _req, _payload| async {})
+            .unwrap();
 
         assert_eq!(pool.active_count(), 1);
         assert!(pool.is_active(1));
@@ -434,7 +550,8 @@ mod tests {
         pool.spawn(tx, 42, Vec::new(), |_tx, _req, _payload| async {
             // Long-running task
             tokio::time::sleep(Duration::from_secs(60)).await;
-        });
+        })
+        .unwrap();
 
         assert!(pool.cancel_task(42));
 
@@ -456,7 +573,8 @@ mod tests {
             let tx = dummy_sender();
             pool.spawn(tx, i, Vec::new(), |_tx, _req, _payload| async {
                 tokio::time::sleep(Duration::from_secs(60)).await;
-            });
+            })
+            .unwrap();
         }
 
         assert_eq!(pool.active_count(), 3);
@@ -485,7 +603,8 @@ mod tests {
                 tokio::time::sleep(Duration::from_secs(60)).await;
             },
             opts,
-        );
+        )
+        .unwrap();
 
         let event = pool.recv().await.unwrap();
         match event {
@@ -497,6 +616,36 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn timed_out_task_sends_nothing_afterwards() {
+        let mut pool = TaskPool::new();
+        let (tx, mut rx) = mpsc::channel(8);
+        let tx = ConnectionSender::from(tx);
+        let opts = TaskOptions::new().with_timeout(Duration::from_millis(1));
+
+        pool.spawn_with_options(
+            tx,
+            11,
+            Vec::new(),
+            |tx, req_id, _payload| async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let reply = crate::Packet::new_response(req_id, crate::Command::Ping, Vec::new());
+                let _ = tx.send(reply.unwrap()).await;
+            },
+            opts,
+        )
+        .unwrap();
+
+        let event = pool.recv().await.unwrap();
+        let err = pool.process_event(event).await;
+        assert!(matches!(err, Some(TaskError::Timeout(_))));
+
+        // Well past the point where the body would have answered.
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(rx.try_recv().is_err());
+        assert!(pool.pool_rx.try_recv().is_err());
+    }
+
     #[tokio::test]
     async fn task_name_metadata() {
         let mut pool = TaskPool::new();
@@ -511,7 +660,8 @@ mod tests {
                 tokio::time::sleep(Duration::from_secs(60)).await;
             },
             opts,
-        );
+        )
+        .unwrap();
 
         let task = pool.get_task(7).unwrap();
         assert_eq!(task.name(), Some("shell-exec"));
@@ -522,6 +672,101 @@ mod tests {
         let _ = pool.recv().await;
     }
 
+    #[tokio::test]
+    async fn duplicate_id_keeps_the_running_task() {
+        let mut pool = TaskPool::new();
+        let ran = Arc::new(AtomicUsize::new(0));
+        let sleep = |ran: Arc<AtomicUsize>| {
+            move |_tx, _req, _payload| async move {
+                ran.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+        };
+        let options = TaskOptions::new().with_name("first");
+        pool.spawn_with_options(dummy_sender(), 7, Vec::new(), sleep(ran.clone()), options)
+            .unwrap();
+        let err = pool
+            .spawn(dummy_sender(), 7, Vec::new(), sleep(ran.clone()))
+            .unwrap_err();
+        assert!(matches!(err, TaskError::DuplicateRequest(7)));
+        assert_eq!(pool.active_count(), 1);
+        assert_eq!(pool.get_task(7).unwrap().name(), Some("first"));
+
+        // The first task's token is still the one cancel reaches.
+        assert!(pool.cancel_task(7));
+        let event = pool.recv().await.unwrap();
+        assert!(matches!(event, TaskEvent::Error(7, TaskError::Cancelled)));
+        assert!(ran.load(Ordering::SeqCst) <= 1);
+    }
+
+    #[tokio::test]
+    async fn panicking_task_reports_and_the_pool_carries_on() {
+        let mut pool = TaskPool::new();
+        pool.spawn(dummy_sender(), 4, Vec::new(), |_tx, _req, payload| async move {
+            let _ = payload[3];
+        })
+        .unwrap();
+
+        let event = pool.recv().await.unwrap();
+        match pool.process_event(event).await {
+            Some(TaskError::Panicked(msg)) => assert!(msg.contains("out of bounds"), "{msg}"),
+            other => panic!("expected Panicked, got {other:?}"),
+        }
+        assert_eq!(pool.active_count(), 0);
+
+        // A closure that panics before its future exists is caught too.
+        let opts = TaskOptions::new().with_timeout(Duration::from_secs(5));
+        pool.spawn_boxed_with_options(
+            dummy_sender(),
+            5,
+            Vec::new(),
+            Box::new(|_tx, _req, _payload| panic!("bad request")),
+            opts,
+        )
+        .unwrap();
+        let event = pool.recv().await.unwrap();
+        assert!(matches!(event, TaskEvent::Error(5, TaskError::Panicked(ref m)) if m == "bad request"));
+        pool.process_event(event).await;
+
+        pool.spawn(dummy_sender(), 6, Vec::new(), |_tx, _req, _payload| async {})
+            .unwrap();
+        assert!(matches!(pool.recv().await.unwrap(), TaskEvent::Finished(6)));
+    }
+
+    #[tokio::test]
+    async fn finished_after_reported_error_is_ignored() {
+        let mut pool = TaskPool::new();
+        let finished = Arc::new(AtomicUsize::new(0));
+        let counter = finished.clone();
+        pool.on_finished(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let events = pool.event_sender();
+        pool.spawn(
+            dummy_sender(),
+            8,
+            Vec::new(),
+            move |_tx, req_id, _payload| async move {
+                let _ = events
+                    .send(TaskEvent::Error(
+                        req_id,
+                        TaskError::Failed("no shell".into()),
+                    ))
+                    .await;
+            },
+        )
+        .unwrap();
+
+        let event = pool.recv().await.unwrap();
+        assert!(matches!(event, TaskEvent::Error(8, TaskError::Failed(_))));
+        assert!(pool.process_event(event).await.is_some());
+
+        let event = pool.recv().await.unwrap();
+        assert!(matches!(event, TaskEvent::Finished(8)));
+        assert!(pool.process_event(event).await.is_none());
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn cancel_unknown_returns_false() {
         let pool = TaskPool::new();
@@ -538,7 +783,8 @@ mod tests {
         });
 
         let tx = dummy_sender();
-        pool.spawn(tx, 5, Vec::new(), |_tx, _req, _payload| async {});
+        pool.spawn(tx, 5, Vec::new(), |_tx, _req, _payload| async {})
+            .unwrap();
 
         let event = pool.recv().await.unwrap();
         pool.process_event(event).await;
@@ -546,4 +792,42 @@ mod tests {
         let finished_id = cb_rx.recv().await.unwrap();
         assert_eq!(finished_id, 5);
     }
+
+    #[tokio::test]
+    async fn snapshot_lists_running_tasks_in_id_order() {
+        let mut pool = TaskPool::new();
+        assert!(pool.snapshot().is_empty());
+
+        let sleep = |_tx, _req, _payload| async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        };
+        pool.spawn_with_options(
+            dummy_sender(),
+            8,
+            Vec::new(),
+            sleep,
+            TaskOptions::new()
+                .with_name("copy")
+                .with_timeout(Duration::from_secs(90)),
+        )
+        .unwrap();
+        pool.spawn(dummy_sender(), 3, Vec::new(), sleep).unwrap();
+
+        let snapshot = pool.snapshot();
+        let ids: Vec<u64> = snapshot.iter().map(|t| t.request_id).collect();
+        assert_eq!(ids, [3, 8]);
+        assert_eq!(snapshot[0].name, None);
+        assert_eq!(snapshot[0].timeout_ms, None);
+        assert_eq!(snapshot[1].name.as_deref(), Some("copy"));
+        assert_eq!(snapshot[1].timeout_ms, Some(90_000));
+        assert!(snapshot[1].elapsed_ms < 1000);
+        assert_eq!(pool.iter().count(), 2);
+
+        pool.cancel_all();
+        for _ in 0..2 {
+            let event = pool.recv().await.unwrap();
+            pool.process_event(event).await;
+        }
+        assert!(pool.snapshot().is_empty());
+    }
 }
@@ -0,0 +1,126 @@
+//! Deterministic frames for running the screen pipeline headless.
+
+use std::time::{Duration, Instant};
+
+use crate::error::TixError;
+use crate::rdp::capture::FrameSource;
+use crate::rdp::types::{PixelFormat, RawScreenFrame};
+
+/// Side of the square that moves across [`SyntheticSource`] frames.
+const SQUARE: u32 = 16;
+
+/// A [`FrameSource`] that draws its frames instead of capturing them.
+///
+/// Frame `n` is a fixed background of flat grey bands, compressing about
+/// as well as a real desktop, with a square whose position and colour
+/// depend on `n`. Consecutive frames therefore differ in a few tiles, and
+/// any frame can be redrawn with [`SyntheticSource::frame`] to check what
+/// a viewer decoded. Past its frame limit the source acts like an idle
+/// desktop: each capture waits out its timeout and reports
+/// [`TixError::Timeout`].
+#[derive(Debug, Clone)]
+pub struct SyntheticSource {
+    width: u32,
+    height: u32,
+    captured: u64,
+    limit: Option<u64>,
+}
+
+impl SyntheticSource {
+    /// A source of `width`×`height` BGRA frames, each at least as large
+    /// as the moving square.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width: width.max(SQUARE),
+            height: height.max(SQUARE),
+            captured: 0,
+            limit: None,
+        }
+    }
+
+    /// Produce `frames` frames, then go idle.
+    pub fn with_limit(mut self, frames: u64) -> Self {
+        self.limit = Some(frames);
+        self
+    }
+
+    /// Frames handed out so far.
+    pub fn captured(&self) -> u64 {
+        self.captured
+    }
+
+    /// Frame `n` of a `width`×`height` source, with tightly packed rows.
+    pub fn frame(width: u32, height: u32, n: u64) -> RawScreenFrame {
+        let (width, height) = (width.max(SQUARE), height.max(SQUARE));
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let shade = 0xC0 + (y / SQUARE % 4) as u8 * 0x10;
+            for _ in 0..width {
+                data.extend_from_slice(&[shade, shade, shade, 0xFF]);
+            }
+        }
+
+        let left = (n * 7 % u64::from(width - SQUARE + 1)) as u32;
+        let top = (n * 3 % u64::from(height - SQUARE + 1)) as u32;
+        let colour = [n as u8, !(n as u8), 0x40, 0xFF];
+        for y in top..top + SQUARE {
+            let row = (y * width + left) as usize * 4;
+            for px in data[row..row + SQUARE as usize * 4].chunks_exact_mut(4) {
+                px.copy_from_slice(&colour);
+            }
+        }
+
+        RawScreenFrame {
+            width,
+            height,
+            stride: width * 4,
+            format: PixelFormat::Bgra8,
+            data,
+            timestamp: Instant::now(),
+        }
+    }
+}
+
+impl FrameSource for SyntheticSource {
+    fn capture_frame(&mut self, timeout_ms: u32) -> Result<RawScreenFrame, TixError> {
+        if self.limit.is_some_and(|limit| self.captured >= limit) {
+            let timeout = Duration::from_millis(u64::from(timeout_ms));
+            std::thread::sleep(timeout);
+            return Err(TixError::Timeout(timeout));
+        }
+        let frame = Self::frame(self.width, self.height, self.captured);
+        self.captured += 1;
+        Ok(frame)
+    }
+
+    fn reinitialize(&mut self) -> Result<(), TixError> {
+        Ok(())
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_reproducible_and_distinct() {
+        let mut source = SyntheticSource::new(64, 48).with_limit(2);
+        let first = source.capture_frame(0).unwrap();
+        let second = source.capture_frame(0).unwrap();
+        assert_eq!(first.data, SyntheticSource::frame(64, 48, 0).data);
+        assert_eq!(second.data, SyntheticSource::frame(64, 48, 1).data);
+        assert_ne!(first.data, second.data);
+        assert_eq!(first.data.len(), 64 * 48 * 4);
+
+        assert!(matches!(source.capture_frame(0), Err(TixError::Timeout(_))));
+        assert_eq!(source.captured(), 2);
+    }
+}
@@ -0,0 +1,13 @@
+//! Scratch space for tests that touch the file system.
+
+use std::path::PathBuf;
+
+/// An empty directory named after the test, under the system temp
+/// directory and this process, so parallel runs of the same test don't
+/// collide. Whatever a previous run left there is removed first.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tix-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
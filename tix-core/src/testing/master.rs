@@ -0,0 +1,227 @@
+//! The master end of a control connection, driven from test code.
+
+use std::collections::VecDeque;
+use std::net::Shutdown;
+use std::time::Duration;
+
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::error::TixError;
+use crate::flags::ProtocolFlags;
+use crate::message::Command;
+use crate::network::{Connection, ConnectionInfo};
+use crate::packet::Packet;
+
+/// How long a [`HeadlessMaster`] waits for a slave or a reply unless
+/// told otherwise.
+pub const DEFAULT_WAIT: Duration = Duration::from_secs(10);
+
+/// Bind a listener on an OS-assigned loopback port. The returned info is
+/// the address a slave should dial.
+pub async fn ephemeral_listener() -> Result<(TcpListener, ConnectionInfo), TixError> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let info = ConnectionInfo::new(addr.ip().to_string(), addr.port());
+    Ok((listener, info))
+}
+
+/// A master that accepts slaves on a loopback port and exchanges raw
+/// packets with them.
+///
+/// Replies are matched to requests by ID. Packets for other requests
+/// that arrive in the meantime are kept for a later
+/// [`reply`](Self::reply).
+pub struct HeadlessMaster {
+    listener: TcpListener,
+    info: ConnectionInfo,
+    conn: Option<Connection>,
+    /// Second handle on the accepted socket, to cut it on
+    /// [`disconnect`](Self::disconnect).
+    socket: Option<std::net::TcpStream>,
+    stash: VecDeque<Packet>,
+    next_id: u64,
+    wait: Duration,
+}
+
+impl HeadlessMaster {
+    /// Listen on a free loopback port.
+    pub async fn bind() -> Result<Self, TixError> {
+        let (listener, info) = ephemeral_listener().await?;
+        Ok(Self {
+            listener,
+            info,
+            conn: None,
+            socket: None,
+            stash: VecDeque::new(),
+            next_id: 1,
+            wait: DEFAULT_WAIT,
+        })
+    }
+
+    /// Give up on a slave or a reply after `wait` (default
+    /// [`DEFAULT_WAIT`]).
+    pub fn with_wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// The address slaves should dial.
+    pub fn info(&self) -> &ConnectionInfo {
+        &self.info
+    }
+
+    /// Whether a slave is currently connected.
+    pub fn is_connected(&self) -> bool {
+        self.conn.is_some()
+    }
+
+    /// Wait for the next slave to dial in. It replaces the current one.
+    pub async fn accept(&mut self) -> Result<(), TixError> {
+        let accepted = tokio::time::timeout(self.wait, self.listener.accept()).await;
+        let (stream, _) = accepted.map_err(|_| TixError::Timeout(self.wait))??;
+        let std_stream = stream.into_std()?;
+        self.socket = Some(std_stream.try_clone()?);
+        self.conn = Some(Connection::new(TcpStream::from_std(std_stream)?));
+        self.stash.clear();
+        Ok(())
+    }
+
+    /// Cut the connection as a crashed master or a dead link would: the
+    /// slave sees the socket close.
+    pub fn disconnect(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            let _ = socket.shutdown(Shutdown::Both);
+        }
+        self.conn = None;
+        self.stash.clear();
+    }
+
+    /// A request ID not used by this master yet.
+    pub fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Send `packet` to the connected slave.
+    pub async fn send(&mut self, packet: Packet) -> Result<(), TixError> {
+        self.conn
+            .as_ref()
+            .ok_or(TixError::ChannelClosed)?
+            .send(packet)
+            .await
+    }
+
+    /// Send `command` under a fresh request ID and return the ID.
+    pub async fn send_command(
+        &mut self,
+        command: Command,
+        payload: Vec<u8>,
+    ) -> Result<u64, TixError> {
+        let id = self.next_id();
+        self.send(Packet::new_command(id, command, payload)?)
+            .await?;
+        Ok(id)
+    }
+
+    /// Send `command` and wait for the first packet answering it.
+    pub async fn request(
+        &mut self,
+        command: Command,
+        payload: Vec<u8>,
+    ) -> Result<Packet, TixError> {
+        let id = self.send_command(command, payload).await?;
+        self.reply(id).await
+    }
+
+    /// The next packet answering request `id`.
+    pub async fn reply(&mut self, id: u64) -> Result<Packet, TixError> {
+        if let Some(pos) = self.stash.iter().position(|p| p.request_id() == id) {
+            return Ok(self.stash.remove(pos).expect("position is in range"));
+        }
+        let wait = self.wait;
+        let conn = self.conn.as_mut().ok_or(TixError::ChannelClosed)?;
+        let stash = &mut self.stash;
+        let next = async {
+            loop {
+                let packet = conn.recv().await.ok_or(TixError::ChannelClosed)?;
+                if packet.request_id() == id {
+                    return Ok(packet);
+                }
+                stash.push_back(packet);
+            }
+        };
+        tokio::time::timeout(wait, next)
+            .await
+            .map_err(|_| TixError::Timeout(wait))?
+    }
+
+    /// Every packet of a streamed reply to `id`, up to and including the
+    /// one flagged `FINAL_FRAGMENT` or the first that is not `STREAMING`.
+    pub async fn stream(&mut self, id: u64) -> Result<Vec<Packet>, TixError> {
+        let mut packets = Vec::new();
+        loop {
+            let packet = self.reply(id).await?;
+            let flags = packet.flags();
+            let done = flags.contains(ProtocolFlags::FINAL_FRAGMENT)
+                || !flags.contains(ProtocolFlags::STREAMING);
+            packets.push(packet);
+            if done {
+                return Ok(packets);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replies_are_matched_by_id() {
+        let mut master = HeadlessMaster::bind().await.unwrap();
+        let info = master.info().clone();
+        let slave = tokio::spawn(async move {
+            let mut conn = Connection::connect(&info).await.unwrap();
+            let mut ids = Vec::new();
+            while ids.len() < 2 {
+                let packet = conn.recv().await.unwrap();
+                if packet.command().ok() == Some(Command::Ping) {
+                    ids.push(packet.request_id());
+                }
+            }
+            // Answer the second request first.
+            for id in ids.into_iter().rev() {
+                let reply = Packet::new_response(id, Command::Ping, id.to_le_bytes().to_vec());
+                conn.send(reply.unwrap()).await.unwrap();
+            }
+            conn
+        });
+        master.accept().await.unwrap();
+
+        let first = master
+            .send_command(Command::Ping, Vec::new())
+            .await
+            .unwrap();
+        let second = master
+            .send_command(Command::Ping, Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            master.reply(first).await.unwrap().payload(),
+            first.to_le_bytes()
+        );
+        assert_eq!(
+            master.reply(second).await.unwrap().payload(),
+            second.to_le_bytes()
+        );
+
+        let mut conn = slave.await.unwrap();
+        master.disconnect();
+        assert!(!master.is_connected());
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            while conn.recv().await.is_some() {}
+        });
+        assert!(closed.await.is_ok(), "slave did not see the disconnect");
+    }
+}
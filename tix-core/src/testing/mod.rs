@@ -0,0 +1,22 @@
+//! Building blocks for tests that run the real stack over loopback.
+//!
+//! Only compiled with the `test-util` feature, which is meant for
+//! `[dev-dependencies]`:
+//!
+//! - [`ephemeral_listener`] — a master listener on a free local port.
+//! - [`HeadlessMaster`] — the master end of a control connection,
+//!   scripted packet by packet.
+//! - `SyntheticSource` — a `FrameSource` drawing deterministic frames,
+//!   so a `ScreenService` runs without a display. Needs the `rdp`
+//!   feature too.
+//! - [`temp_dir`] — an empty scratch directory for one test.
+
+#[cfg(feature = "rdp")]
+mod capture;
+mod fs;
+mod master;
+
+#[cfg(feature = "rdp")]
+pub use capture::SyntheticSource;
+pub use fs::temp_dir;
+pub use master::{DEFAULT_WAIT, HeadlessMaster, ephemeral_listener};
@@ -1,12 +1,20 @@
 //! Integration tests — full connection lifecycle, command round-trips,
 //! and error scenarios over a real TCP connection on localhost.
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Duration;
 
+use tix_core::network::wiretap::{CaptureReader, Direction};
+use tix_core::protocol::HelloInfo;
 use tix_core::{
-    Command, Connection, ConnectionInfo, ConnectionPhase, MasterState, Packet, SlaveState,
+    Command, Connection, ConnectionInfo, ConnectionOptions, ConnectionPhase, HEADER_SIZE, Liveness,
+    MasterState, Packet, RequestIds, SlaveState, TixCodec, WireVersion,
 };
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
+use tokio::time::Instant;
+use tokio_util::codec::Decoder;
 
 // ── Helpers ──────────────────────────────────────────────────────
 
@@ -19,18 +27,6 @@ async fn ephemeral_listener() -> (TcpListener, ConnectionInfo) {
     (listener, info)
 }
 
-/// Receive the next non-heartbeat packet, skipping any heartbeats
-/// that arrive first.
-async fn recv_skip_heartbeat(conn: &mut Connection) -> Option<Packet> {
-    loop {
-        let pkt = conn.recv().await?;
-        if pkt.request_id() != 0 {
-            return Some(pkt);
-        }
-        // heartbeat — skip
-    }
-}
-
 // ── Connection lifecycle ─────────────────────────────────────────
 
 #[tokio::test]
@@ -51,8 +47,8 @@ async fn test_connection_lifecycle() {
     let ping = Packet::new_command(1, Command::Ping, Vec::new()).unwrap();
     master_conn.send(ping).await.unwrap();
 
-    // Slave receives it (skip any heartbeats)
-    let pkt = tokio::time::timeout(Duration::from_secs(5), recv_skip_heartbeat(&mut slave_conn))
+    // Slave receives it
+    let pkt = tokio::time::timeout(Duration::from_secs(5), slave_conn.recv())
         .await
         .expect("timeout")
         .expect("recv returned None");
@@ -65,13 +61,10 @@ async fn test_connection_lifecycle() {
     slave_conn.send(pong).await.unwrap();
 
     // Master receives response
-    let resp = tokio::time::timeout(
-        Duration::from_secs(5),
-        recv_skip_heartbeat(&mut master_conn),
-    )
-    .await
-    .expect("timeout")
-    .expect("recv returned None");
+    let resp = tokio::time::timeout(Duration::from_secs(5), master_conn.recv())
+        .await
+        .expect("timeout")
+        .expect("recv returned None");
 
     assert_eq!(resp.request_id(), 1);
     assert_eq!(resp.payload(), b"Pong");
@@ -97,15 +90,199 @@ async fn test_bidirectional_packets() {
     }
 
     for i in 1u64..=5 {
-        let pkt =
-            tokio::time::timeout(Duration::from_secs(5), recv_skip_heartbeat(&mut slave_conn))
-                .await
-                .expect("timeout")
-                .expect("recv returned None");
+        let pkt = tokio::time::timeout(Duration::from_secs(5), slave_conn.recv())
+            .await
+            .expect("timeout")
+            .expect("recv returned None");
         assert_eq!(pkt.request_id(), i);
     }
 }
 
+#[tokio::test]
+async fn test_connection_over_any_stream() {
+    // Any byte stream, like the TLS session `accept_tls` wraps, with no
+    // socket of its own.
+    let (a, b) = tokio::io::duplex(64 * 1024);
+    let addr = "127.0.0.1:4321".parse().ok();
+    let master_conn = Connection::from_stream(a, None, addr);
+    let mut slave_conn = Connection::from_stream(b, addr, None);
+    assert_eq!(master_conn.peer_addr(), addr);
+
+    let payload = vec![7u8; 20_000];
+    let cmd = Packet::new_command(9, Command::ShellExecute, payload.clone()).unwrap();
+    master_conn.send(cmd).await.unwrap();
+    let pkt = tokio::time::timeout(Duration::from_secs(5), slave_conn.recv())
+        .await
+        .expect("timeout")
+        .expect("recv returned None");
+    assert_eq!(pkt.request_id(), 9);
+    assert_eq!(pkt.payload(), &payload[..]);
+}
+
+// ── Wire versions ────────────────────────────────────────────────
+
+/// Connect two `Connection`s over loopback.
+async fn connected_pair() -> (Connection, Connection) {
+    let (listener, info) = ephemeral_listener().await;
+    let slave_handle = tokio::spawn(async move { Connection::connect(&info).await.unwrap() });
+    let (stream, _) = listener.accept().await.unwrap();
+    (Connection::new(stream), slave_handle.await.unwrap())
+}
+
+async fn recv_within(conn: &mut Connection) -> Packet {
+    tokio::time::timeout(Duration::from_secs(5), conn.recv())
+        .await
+        .expect("timeout")
+        .expect("recv returned None")
+}
+
+#[tokio::test]
+async fn test_hello_moves_both_sides_to_tix2() {
+    let (mut master, mut slave) = connected_pair().await;
+
+    // Before Hello, everything is TIX1.
+    master
+        .send(Packet::new_command(1, Command::Ping, Vec::new()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        recv_within(&mut slave).await.wire_version(),
+        WireVersion::V1
+    );
+
+    let hello = HelloInfo::local("tix-master", "0.3.0");
+    master
+        .send(hello.into_command_packet(2).unwrap())
+        .await
+        .unwrap();
+    let pkt = recv_within(&mut slave).await;
+    assert_eq!(pkt.wire_version(), WireVersion::V1);
+    let master_hello = HelloInfo::from_bytes(pkt.payload()).unwrap();
+    slave.set_wire_version(master_hello.negotiated_version());
+    let reply = HelloInfo::local("tix-slave", "0.3.0")
+        .into_response_packet(2)
+        .unwrap();
+    slave.send(reply).await.unwrap();
+
+    // The master may still be writing TIX1 while the slave has
+    // switched; both are read.
+    master
+        .send(Packet::new_command(3, Command::ShellExecute, b"dir".to_vec()).unwrap())
+        .await
+        .unwrap();
+    let pkt = recv_within(&mut master).await;
+    assert_eq!(pkt.wire_version(), WireVersion::V2);
+    assert_eq!(pkt.command().unwrap(), Command::Hello);
+    let slave_hello = HelloInfo::from_bytes(pkt.payload()).unwrap();
+    master.set_wire_version(slave_hello.negotiated_version());
+    let pkt = recv_within(&mut slave).await;
+    assert_eq!(pkt.wire_version(), WireVersion::V1);
+    assert_eq!(pkt.payload(), b"dir");
+
+    master
+        .send(Packet::new_command(4, Command::ShellExecute, b"ver".to_vec()).unwrap())
+        .await
+        .unwrap();
+    let pkt = recv_within(&mut slave).await;
+    assert_eq!(pkt.wire_version(), WireVersion::V2);
+    assert_eq!(pkt.request_id(), 4);
+    assert_eq!(pkt.command().unwrap(), Command::ShellExecute);
+    assert_eq!(pkt.payload(), b"ver");
+    assert_eq!(master.wire_version(), WireVersion::V2);
+}
+
+/// A response queued under one session and written after the next one
+/// began still carries the old nonce, so the new session drops it.
+#[tokio::test]
+async fn test_queued_response_keeps_its_session() {
+    let (mut master, mut slave) = connected_pair().await;
+    master.set_wire_version(WireVersion::V2);
+    slave.set_wire_version(WireVersion::V2);
+    slave.set_session(0xA);
+    let task = slave.sender();
+
+    // The test runtime has one thread: nothing is written before the
+    // next await, so the session changes while the response is queued.
+    let late = Packet::new_response(7, Command::ShellExecute, b"old".to_vec()).unwrap();
+    task.try_send(late).unwrap();
+    slave.set_session(0xB);
+    let fresh = Packet::new_response(8, Command::ShellExecute, b"new".to_vec()).unwrap();
+    slave.send(fresh).await.unwrap();
+
+    let mut state = MasterState::new().with_request_ids(RequestIds::starting_at(1, 0xB));
+    state.set_wire_version(WireVersion::V2);
+    let late = recv_within(&mut master).await;
+    assert_eq!((late.request_id(), late.session()), (7, 0xA));
+    assert!(state.is_from_other_session(&late));
+    let fresh = recv_within(&mut master).await;
+    assert_eq!((fresh.request_id(), fresh.session()), (8, 0xB));
+    assert!(!state.is_from_other_session(&fresh));
+}
+
+/// A peer that predates TIX2 never sends `Hello` and only parses
+/// 64-byte TIX1 headers.
+#[tokio::test]
+async fn test_peer_without_hello_keeps_tix1() {
+    let (listener, info) = ephemeral_listener().await;
+    let old_peer = tokio::spawn(async move {
+        let mut stream = tokio::net::TcpStream::connect(info.to_socket_string())
+            .await
+            .unwrap();
+        let ping = Packet::new_command(1, Command::Ping, Vec::new()).unwrap();
+        stream.write_all(&ping.to_bytes().unwrap()).await.unwrap();
+        let mut header = [0u8; HEADER_SIZE];
+        loop {
+            stream.read_exact(&mut header).await.unwrap();
+            let len = u64::from_le_bytes(header[56..64].try_into().unwrap()) as usize;
+            let mut payload = vec![0u8; len];
+            stream.read_exact(&mut payload).await.unwrap();
+            let mut frame = bytes::BytesMut::from(&header[..]);
+            frame.extend_from_slice(&payload);
+            let pkt = TixCodec.decode(&mut frame).unwrap().unwrap();
+            if pkt.request_id() != 0 {
+                return (header, pkt);
+            }
+        }
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut conn = Connection::new(stream);
+    let ping = recv_within(&mut conn).await;
+    assert_eq!(ping.wire_version(), WireVersion::V1);
+    conn.send(Packet::new_response(1, Command::Ping, b"Pong".to_vec()).unwrap())
+        .await
+        .unwrap();
+
+    let (header, pong) = old_peer.await.unwrap();
+    assert_eq!(&header[0..4], b"TIX1");
+    assert_eq!(pong.payload(), b"Pong");
+    assert_eq!(conn.wire_version(), WireVersion::V1);
+}
+
+#[tokio::test]
+async fn test_unknown_version_closes_the_connection() {
+    let (listener, info) = ephemeral_listener().await;
+    let sender = tokio::spawn(async move {
+        let mut stream = tokio::net::TcpStream::connect(info.to_socket_string())
+            .await
+            .unwrap();
+        let mut bytes = Packet::new_command(1, Command::Ping, Vec::new())
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        bytes[0..4].copy_from_slice(b"TIX5");
+        stream.write_all(&bytes).await.unwrap();
+        stream
+    });
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut conn = Connection::new(stream);
+    let _keep_open = sender.await.unwrap();
+    let next = tokio::time::timeout(Duration::from_secs(5), conn.recv())
+        .await
+        .expect("timeout");
+    assert!(next.is_none(), "a TIX5 packet must not be delivered");
+}
+
 // ── State machine ────────────────────────────────────────────────
 
 #[tokio::test]
@@ -213,7 +390,7 @@ async fn test_shell_execute_round_trip() {
     master_conn.send(cmd).await.unwrap();
 
     // Slave receives
-    let pkt = tokio::time::timeout(Duration::from_secs(5), recv_skip_heartbeat(&mut slave_conn))
+    let pkt = tokio::time::timeout(Duration::from_secs(5), slave_conn.recv())
         .await
         .expect("timeout")
         .expect("recv returned None");
@@ -226,16 +403,89 @@ async fn test_shell_execute_round_trip() {
     slave_conn.send(resp).await.unwrap();
 
     // Master receives response
-    let resp = tokio::time::timeout(
-        Duration::from_secs(5),
-        recv_skip_heartbeat(&mut master_conn),
-    )
-    .await
-    .expect("timeout")
-    .expect("recv returned None");
+    let resp = tokio::time::timeout(Duration::from_secs(5), master_conn.recv())
+        .await
+        .expect("timeout")
+        .expect("recv returned None");
     assert_eq!(resp.payload(), &output[..]);
 }
 
+// ── Screen negotiation over the control channel ──────────────────
+
+#[tokio::test]
+async fn test_screen_start_negotiation_round_trip() {
+    use tix_core::protocol::{ScreenConfig, ScreenStartRequest, ScreenStartResponse};
+
+    let (listener, info) = ephemeral_listener().await;
+
+    let slave_handle = tokio::spawn({
+        let info = info.clone();
+        async move { Connection::connect(&info).await.unwrap() }
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let master_conn = Connection::new(stream);
+    let mut slave_conn = slave_handle.await.unwrap();
+
+    // The master side knows where the slave connected from
+    assert!(master_conn.peer_addr().is_some());
+    assert_eq!(
+        slave_conn.peer_addr().unwrap().port(),
+        master_conn.local_addr().unwrap().port()
+    );
+
+    // Master (or a bridged viewer) announces its UDP receive port
+    let req = ScreenStartRequest::new().with_fps(30).with_udp_port(41000);
+    master_conn.send(req.into_packet(9).unwrap()).await.unwrap();
+
+    let pkt = tokio::time::timeout(Duration::from_secs(5), slave_conn.recv())
+        .await
+        .expect("timeout")
+        .expect("recv returned None");
+    assert_eq!(pkt.command().unwrap(), Command::ScreenStart);
+    let decoded = ScreenStartRequest::from_bytes(pkt.payload()).unwrap();
+    assert_eq!(decoded.udp_port, Some(41000));
+
+    // Slave acks with its own UDP endpoint
+    let slave_ip = slave_conn.local_addr().unwrap().ip();
+    let ack = ScreenStartResponse::Started(ScreenConfig {
+        width: 1920,
+        height: 1080,
+        quality: decoded.quality,
+        fps: decoded.fps,
+        format: decoded.format,
+        monitor_name: "Monitor 0".to_string(),
+        udp_endpoint: Some(std::net::SocketAddr::new(slave_ip, 42000)),
+        mtu: 1400,
+        session: decoded.session,
+        origin: (0, 0),
+        block_size: 64,
+        udp_input: decoded.udp_input,
+        dictionary: None,
+        preflight: None,
+        timed_input: decoded.timed_input,
+        thumbnails: decoded.thumbnails,
+        low_bandwidth: decoded.low_bandwidth,
+    });
+    slave_conn.send(ack.into_packet(9).unwrap()).await.unwrap();
+
+    let mut master_conn = master_conn;
+    let resp = tokio::time::timeout(Duration::from_secs(5), master_conn.recv())
+        .await
+        .expect("timeout")
+        .expect("recv returned None");
+    assert_eq!(resp.request_id(), 9);
+    match ScreenStartResponse::from_bytes(resp.payload()).unwrap() {
+        ScreenStartResponse::Started(config) => {
+            let endpoint = config.udp_endpoint.unwrap();
+            assert_eq!(endpoint.ip(), slave_ip);
+            assert_eq!(endpoint.port(), 42000);
+            assert_eq!(config.fps, 30);
+        }
+        ScreenStartResponse::Failed(e) => panic!("unexpected failure: {e}"),
+    }
+}
+
 // ── Large payload ────────────────────────────────────────────────
 
 #[tokio::test]
@@ -256,17 +506,321 @@ async fn test_large_payload_transfer() {
     let cmd = Packet::new_command(1, Command::Copy, large_payload.clone()).unwrap();
     master_conn.send(cmd).await.unwrap();
 
-    let pkt = tokio::time::timeout(
-        Duration::from_secs(10),
-        recv_skip_heartbeat(&mut slave_conn),
-    )
-    .await
-    .expect("timeout")
-    .expect("recv returned None");
+    let pkt = tokio::time::timeout(Duration::from_secs(10), slave_conn.recv())
+        .await
+        .expect("timeout")
+        .expect("recv returned None");
     assert_eq!(pkt.payload().len(), 200 * 1024);
     assert_eq!(pkt.payload(), &large_payload[..]);
 }
 
+// ── Send lanes ───────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_ping_overtakes_a_bulk_flood() {
+    const CHUNKS: usize = 500;
+    let (mut master_conn, mut slave_conn) = connected_pair().await;
+
+    // The slave answers Pings at once but takes 2 ms per chunk, so the
+    // master's bulk lane stays full for the whole flood.
+    tokio::spawn(async move {
+        while let Some(pkt) = slave_conn.recv().await {
+            match pkt.command() {
+                Ok(Command::Ping) => {
+                    let pong = Packet::new_response(pkt.request_id(), Command::Ping, Vec::new());
+                    slave_conn.send(pong.unwrap()).await.unwrap();
+                }
+                Ok(Command::FileWrite) => tokio::time::sleep(Duration::from_millis(2)).await,
+                _ => {}
+            }
+        }
+    });
+    let tx = master_conn.sender();
+    let flood = tokio::spawn(async move {
+        for _ in 0..CHUNKS {
+            let chunk = Packet::new_command(1, Command::FileWrite, vec![0xAB; 200 * 1024]);
+            tx.send(chunk.unwrap()).await.unwrap();
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let started = std::time::Instant::now();
+    let ping = Packet::new_command(2, Command::Ping, Vec::new()).unwrap();
+    master_conn.send(ping).await.unwrap();
+    let pong = recv_within(&mut master_conn).await;
+    assert_eq!(pong.request_id(), 2);
+    // Well within the heartbeat interval, and ahead of the chunks.
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(!flood.is_finished(), "the flood ended before the Pong");
+}
+
+#[tokio::test]
+async fn test_bulk_lane_pushes_back_on_a_stalled_peer() {
+    let (listener, info) = ephemeral_listener().await;
+    // A peer that never reads.
+    let peer = tokio::spawn(async move {
+        tokio::net::TcpStream::connect(info.to_socket_string())
+            .await
+            .unwrap()
+    });
+    let (stream, _) = listener.accept().await.unwrap();
+    let conn = Connection::new(stream);
+    let _peer = peer.await.unwrap();
+
+    let tx = conn.sender();
+    let mut queued = 0;
+    while queued < 1000 {
+        let chunk = Packet::new_command(1, Command::FileWrite, vec![0xAB; 200 * 1024]).unwrap();
+        match tokio::time::timeout(Duration::from_millis(200), tx.send_bulk(chunk)).await {
+            Ok(sent) => {
+                sent.unwrap();
+                queued += 1;
+            }
+            Err(_) => break,
+        }
+    }
+    // The socket buffers hold some chunks and the lane a few more; the
+    // sender waits after that instead of queueing 200 MB.
+    assert!(queued < 200, "{queued} chunks queued without blocking");
+
+    // The control lane is not stuck behind them.
+    let ping = Packet::new_command(2, Command::Ping, Vec::new()).unwrap();
+    tokio::time::timeout(Duration::from_millis(100), tx.send(ping))
+        .await
+        .expect("control lane blocked")
+        .unwrap();
+}
+
+// ── Heartbeats ───────────────────────────────────────────────────
+
+/// Heartbeats every 20 ms, the peer dead after three silent intervals.
+const FAST: ConnectionOptions = ConnectionOptions {
+    interval: Duration::from_millis(20),
+    miss_threshold: 3,
+    enabled: true,
+};
+
+#[tokio::test]
+async fn test_recv_never_yields_heartbeats() {
+    let (listener, info) = ephemeral_listener().await;
+    let slave_handle = tokio::spawn(async move {
+        let stream = tokio::net::TcpStream::connect(info.to_socket_string())
+            .await
+            .unwrap();
+        Connection::with_options(stream, FAST)
+    });
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut master_conn = Connection::new(stream);
+    let slave_conn = slave_handle.await.unwrap();
+    assert_eq!(slave_conn.options(), FAST);
+
+    // A dozen heartbeats go by before the first real packet.
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(master_conn.try_recv().is_none());
+    assert!(master_conn.stats().bytes_received() >= 10 * HEADER_SIZE as u64);
+    assert!(master_conn.last_peer_activity().elapsed() < Duration::from_millis(200));
+
+    let ping = Packet::new_command(7, Command::Ping, Vec::new()).unwrap();
+    slave_conn.send(ping).await.unwrap();
+    let pkt = recv_within(&mut master_conn).await;
+    assert_eq!(pkt.command().unwrap(), Command::Ping);
+    assert_eq!(pkt.request_id(), 7);
+}
+
+#[tokio::test]
+async fn test_silent_peer_turns_suspect_then_dead() {
+    let (listener, info) = ephemeral_listener().await;
+    // A peer that connects and then says nothing at all.
+    let silent = tokio::spawn(async move {
+        tokio::net::TcpStream::connect(info.to_socket_string())
+            .await
+            .unwrap()
+    });
+    let (stream, _) = listener.accept().await.unwrap();
+    let conn = Connection::with_options(stream, FAST);
+    let mut peer = silent.await.unwrap();
+    let mut liveness = conn.on_liveness_change();
+    assert_eq!(*liveness.borrow_and_update(), Liveness::Alive);
+
+    assert_eq!(next_liveness(&mut liveness).await, Liveness::Suspect);
+    assert_eq!(next_liveness(&mut liveness).await, Liveness::Dead);
+
+    // Any packet brings it back.
+    let heartbeat = Packet::heartbeat().to_bytes().unwrap();
+    peer.write_all(&heartbeat).await.unwrap();
+    assert_eq!(next_liveness(&mut liveness).await, Liveness::Alive);
+}
+
+async fn next_liveness(rx: &mut tokio::sync::watch::Receiver<Liveness>) -> Liveness {
+    tokio::time::timeout(Duration::from_secs(5), rx.changed())
+        .await
+        .expect("no change")
+        .unwrap();
+    *rx.borrow_and_update()
+}
+
+/// One direction of a link that holds every byte for the current
+/// `delay` (in ms) before passing it on, in order.
+fn delay_bytes<R, W>(mut from: R, mut to: W, delay: Arc<AtomicU64>)
+where
+    R: tokio::io::AsyncRead + Send + Unpin + 'static,
+    W: tokio::io::AsyncWrite + Send + Unpin + 'static,
+{
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; 16 * 1024];
+        while let Ok(n @ 1..) = from.read(&mut buf).await {
+            let due = Instant::now() + Duration::from_millis(delay.load(Ordering::Relaxed));
+            if tx.send((due, buf[..n].to_vec())).is_err() {
+                break;
+            }
+        }
+    });
+    tokio::spawn(async move {
+        while let Some((due, bytes)) = rx.recv().await {
+            tokio::time::sleep_until(due).await;
+            if to.write_all(&bytes).await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Two connections joined by a link with `delay` ms each way.
+fn delayed_pair(options: ConnectionOptions, delay: &Arc<AtomicU64>) -> (Connection, Connection) {
+    let (a, a_link) = tokio::io::duplex(64 * 1024);
+    let (b, b_link) = tokio::io::duplex(64 * 1024);
+    let (a_read, a_write) = tokio::io::split(a_link);
+    let (b_read, b_write) = tokio::io::split(b_link);
+    delay_bytes(a_read, b_write, Arc::clone(delay));
+    delay_bytes(b_read, a_write, Arc::clone(delay));
+    (
+        Connection::from_stream_with_options(a, None, None, options),
+        Connection::from_stream_with_options(b, None, None, options),
+    )
+}
+
+#[tokio::test]
+async fn test_timeouts_stretch_with_a_slow_link() {
+    let options = ConnectionOptions {
+        interval: Duration::from_millis(50),
+        miss_threshold: 3,
+        enabled: true,
+    };
+    let delay = Arc::new(AtomicU64::new(2));
+    let (mut master_conn, mut slave_conn) = delayed_pair(options, &delay);
+    let mut liveness = master_conn.on_liveness_change();
+    let died = Arc::new(AtomicBool::new(false));
+    let watcher_died = Arc::clone(&died);
+    tokio::spawn(async move {
+        while liveness.changed().await.is_ok() {
+            if *liveness.borrow_and_update() == Liveness::Dead {
+                watcher_died.store(true, Ordering::Relaxed);
+            }
+        }
+    });
+    // The slave answers pings as they come.
+    tokio::spawn(async move {
+        while let Some(packet) = slave_conn.recv().await {
+            let pong = Packet::new_response(packet.request_id(), Command::Ping, b"Pong".to_vec());
+            if slave_conn.send(pong.unwrap()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // A fast link keeps the floor.
+    let floor = Duration::from_millis(200);
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    let fast = master_conn.stats().rtt();
+    assert!(fast.samples() >= 3, "{:?}", fast);
+    assert!(
+        fast.srtt().unwrap() < Duration::from_millis(100),
+        "{:?}",
+        fast
+    );
+    assert_eq!(fast.request_timeout(floor, Command::Ping), floor);
+
+    // The link slows down step by step to 250 ms each way.
+    for ms in [50, 100, 150, 200, 250] {
+        delay.store(ms, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(400)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let slow = master_conn.stats().rtt();
+    let srtt = slow.srtt().unwrap();
+    assert!(srtt > Duration::from_millis(400), "{:?}", slow);
+    let timeout = slow.request_timeout(floor, Command::Ping);
+    assert!(timeout > Duration::from_secs(1), "{:?}", timeout);
+    // Silence is allowed up to twice the miss threshold, no more.
+    assert_eq!(master_conn.miss_deadline(), Duration::from_millis(300));
+
+    // A ping now takes longer than the floor, and well within the
+    // stretched timeout.
+    let sent = Instant::now();
+    let ping = Packet::new_command(1, Command::Ping, Vec::new()).unwrap();
+    master_conn.send(ping).await.unwrap();
+    let pong = tokio::time::timeout(timeout, master_conn.recv())
+        .await
+        .expect("the stretched timeout was too short")
+        .unwrap();
+    assert_eq!(pong.request_id(), 1);
+    assert!(sent.elapsed() > floor);
+
+    // Slowing down never looked like a dead slave.
+    assert!(!died.load(Ordering::Relaxed));
+}
+
+// ── Wire tap ─────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_wire_tap_leaves_packets_untouched() {
+    // Debug lines go to the test output, so the formatting path runs too.
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_test_writer()
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let capture = std::env::temp_dir().join(format!("tix-tap-{}.cap", std::process::id()));
+    let (mut master_conn, slave_conn) = connected_pair().await;
+    for tap in [master_conn.wire_tap(), slave_conn.wire_tap()] {
+        tap.set_enabled(true);
+        tap.set_dump_bytes(16);
+    }
+    master_conn.wire_tap().start_capture(&capture).unwrap();
+
+    let payloads = [
+        Vec::new(),
+        b"tapped".to_vec(),
+        (0..=255).cycle().take(100_000).collect(),
+    ];
+    for (id, payload) in payloads.iter().enumerate() {
+        let packet = Packet::new_response(id as u64, Command::FileRead, payload.clone()).unwrap();
+        slave_conn.send(packet).await.unwrap();
+    }
+    for (id, payload) in payloads.iter().enumerate() {
+        let packet = recv_within(&mut master_conn).await;
+        assert_eq!(packet.request_id(), id as u64);
+        assert_eq!(packet.payload(), payload.as_slice());
+        assert!(packet.validate_checksum());
+    }
+
+    master_conn.wire_tap().stop_capture();
+    // Heartbeats both ways are in the capture too.
+    let received: Vec<_> = CaptureReader::open(&capture)
+        .unwrap()
+        .map(Result::unwrap)
+        .filter(|record| record.direction == Direction::Received)
+        .filter(|record| record.packet().unwrap().command().unwrap() != Command::Heartbeat)
+        .collect();
+    std::fs::remove_file(&capture).unwrap();
+    assert_eq!(received.len(), payloads.len());
+    for (record, payload) in received.iter().zip(&payloads) {
+        assert_eq!(record.packet().unwrap().payload(), payload.as_slice());
+    }
+}
+
 // ── Error scenarios ──────────────────────────────────────────────
 
 #[tokio::test]
@@ -288,16 +842,13 @@ async fn test_connection_drop_detected() {
     // Give the background tasks time to notice
     tokio::time::sleep(Duration::from_millis(200)).await;
 
+    let mut liveness = master_conn.on_liveness_change();
     let result = tokio::time::timeout(Duration::from_secs(5), master_conn.recv())
         .await
         .expect("timeout");
-    // After dropping the peer, recv should eventually return None
-    // (may take a moment for TCP FIN to propagate)
-    // We accept either None or a heartbeat packet
-    if let Some(pkt) = result {
-        // If we got something, it must be a heartbeat from master's own writer
-        assert_eq!(pkt.request_id(), 0);
-    }
+    // Dropping the connection closes the socket; heartbeats never show.
+    assert!(result.is_none(), "got {result:?}");
+    assert_eq!(*liveness.borrow_and_update(), Liveness::Dead);
 }
 
 #[test]
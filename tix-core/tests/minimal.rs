@@ -0,0 +1,48 @@
+//! The crate without its default features: no `rdp` module, no zstd
+//! (file chunks go raw), rayon or Windows APIs, but the codec and the
+//! protocol payloads — screen ones included — work as before.
+//!
+//! Only built by `cargo test-core-minimal`; with `rdp` on, the rest of
+//! the suite covers the same ground.
+
+#![cfg(not(feature = "rdp"))]
+
+use bytes::BytesMut;
+use tix_core::protocol::screen::{ImageFormat, ScreenStartRequest};
+use tix_core::protocol::shell::ShellExecuteRequest;
+use tix_core::{Command, Packet, TixCodec};
+use tokio_util::codec::{Decoder, Encoder};
+
+fn round_trip(packets: Vec<Packet>) -> Vec<Packet> {
+    let mut buf = BytesMut::new();
+    for packet in packets {
+        TixCodec.encode(packet, &mut buf).unwrap();
+    }
+    let mut decoded = Vec::new();
+    while let Some(packet) = TixCodec.decode(&mut buf).unwrap() {
+        decoded.push(packet);
+    }
+    assert!(buf.is_empty());
+    decoded
+}
+
+#[test]
+fn codec_round_trips_packets() {
+    let shell = ShellExecuteRequest::new("uname -a");
+    let screen = ScreenStartRequest::new().with_format(ImageFormat::Gray8);
+    let packets = vec![
+        Packet::new_command(1, Command::Ping, Vec::new()).unwrap(),
+        shell.clone().into_packet(2).unwrap(),
+        screen.clone().into_packet(3).unwrap(),
+        Packet::new_response(2, Command::ShellExecute, vec![7; 70_000]).unwrap(),
+    ];
+
+    let decoded = round_trip(packets);
+    assert_eq!(decoded.len(), 4);
+    assert_eq!(decoded[0].command().unwrap(), Command::Ping);
+    assert_eq!(ShellExecuteRequest::from_bytes(decoded[1].payload()).unwrap(), shell);
+    assert_eq!(decoded[2].request_id(), 3);
+    let start = ScreenStartRequest::from_bytes(decoded[2].payload()).unwrap();
+    assert_eq!(start.format, screen.format);
+    assert_eq!(decoded[3].payload(), &[7; 70_000][..]);
+}
@@ -0,0 +1,165 @@
+//! The control connection over TLS on localhost: pinned and CA-checked
+//! certificates, and the peers that must be turned away.
+#![cfg(feature = "tls")]
+
+use std::time::Duration;
+
+use tix_core::network::tls::{TlsAcceptor, TlsConfig};
+use tix_core::testing::{ephemeral_listener, temp_dir};
+use tix_core::{Command, Connection, Packet};
+use tokio::net::TcpListener;
+
+// ── Helpers ──────────────────────────────────────────────────────
+
+/// The accepting side's config, with its certificate kept in `dir`.
+fn server_config(dir: &std::path::Path) -> TlsConfig {
+    TlsConfig {
+        enabled: true,
+        cert: dir.join("server.crt"),
+        key: dir.join("server.key"),
+        ..TlsConfig::default()
+    }
+}
+
+/// Accept one connection on `listener` over TLS.
+async fn accept(listener: &TcpListener, acceptor: &TlsAcceptor) -> std::io::Result<Connection> {
+    let (stream, _) = listener.accept().await?;
+    Connection::accept_tls(stream, acceptor).await
+}
+
+async fn recv(conn: &mut Connection) -> Option<Packet> {
+    tokio::time::timeout(Duration::from_secs(5), conn.recv())
+        .await
+        .expect("timeout")
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn pinned_round_trip_over_loopback() {
+    let dir = temp_dir("tls-pinned");
+    let acceptor = server_config(&dir).acceptor().unwrap();
+    let connector = TlsConfig {
+        enabled: true,
+        pin_sha256: acceptor.fingerprint().to_string(),
+        ..TlsConfig::default()
+    }
+    .connector()
+    .unwrap();
+    let (listener, info) = ephemeral_listener().await.unwrap();
+
+    let (master, slave) = tokio::join!(
+        accept(&listener, &acceptor),
+        Connection::connect_tls(&info, &connector)
+    );
+    let (mut master, mut slave) = (master.unwrap(), slave.unwrap());
+    assert_eq!(
+        master.peer_addr().map(|a| a.port()),
+        slave.local_addr().map(|a| a.port())
+    );
+
+    let payload = vec![0x5Au8; 100_000];
+    let cmd = Packet::new_command(3, Command::ShellExecute, payload.clone()).unwrap();
+    master.send(cmd).await.unwrap();
+    let got = recv(&mut slave).await.expect("slave saw nothing");
+    assert_eq!(got.request_id(), 3);
+    assert_eq!(got.payload(), &payload[..]);
+
+    let reply = Packet::new_response(3, Command::ShellExecute, b"done".to_vec()).unwrap();
+    slave.send(reply).await.unwrap();
+    let got = recv(&mut master).await.expect("master saw nothing");
+    assert_eq!(got.payload(), b"done");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn certificate_that_is_not_the_pinned_one_is_refused() {
+    let dir = temp_dir("tls-mismatch");
+    let acceptor = server_config(&dir).acceptor().unwrap();
+    let wrong = "00".repeat(32);
+    let connector = TlsConfig {
+        enabled: true,
+        pin_sha256: wrong.clone(),
+        ..TlsConfig::default()
+    }
+    .connector()
+    .unwrap();
+    let (listener, info) = ephemeral_listener().await.unwrap();
+
+    let (master, slave) = tokio::join!(
+        accept(&listener, &acceptor),
+        Connection::connect_tls(&info, &connector)
+    );
+    let err = slave.unwrap_err().to_string();
+    assert!(err.contains(acceptor.fingerprint()), "{err}");
+    assert!(err.contains(&wrong), "{err}");
+    // The accepting side hears the refusal rather than waiting it out.
+    assert!(master.is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn plaintext_client_fails_cleanly_against_a_tls_listener() {
+    let dir = temp_dir("tls-plaintext");
+    let acceptor = server_config(&dir).acceptor().unwrap();
+    let (listener, info) = ephemeral_listener().await.unwrap();
+
+    let (master, slave) = tokio::join!(accept(&listener, &acceptor), async {
+        let mut slave = Connection::connect(&info).await.unwrap();
+        let hello = Packet::new_command(1, Command::Hello, Vec::new()).unwrap();
+        slave.send(hello).await.unwrap();
+        recv(&mut slave).await
+    });
+    // Refused on the first bytes, well before the handshake timeout,
+    // and the plaintext side sees the connection close.
+    assert!(master.is_err());
+    assert!(slave.is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn ca_bundle_checks_the_issuer_and_the_name() {
+    let dir = temp_dir("tls-ca");
+    let ca_key = rcgen::KeyPair::generate().unwrap();
+    let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+    ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    let ca = rcgen::CertifiedIssuer::self_signed(ca_params, ca_key).unwrap();
+    let leaf_key = rcgen::KeyPair::generate().unwrap();
+    let leaf = rcgen::CertificateParams::new(vec!["tix.example".to_string()])
+        .unwrap()
+        .signed_by(&leaf_key, &ca)
+        .unwrap();
+    std::fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+    std::fs::write(dir.join("server.crt"), leaf.pem()).unwrap();
+    std::fs::write(dir.join("server.key"), leaf_key.serialize_pem()).unwrap();
+    let acceptor = server_config(&dir).acceptor().unwrap();
+    let (listener, info) = ephemeral_listener().await.unwrap();
+
+    let client = |server_name: Option<&str>| TlsConfig {
+        enabled: true,
+        ca: Some(dir.join("ca.pem")),
+        server_name: server_name.map(String::from),
+        ..TlsConfig::default()
+    };
+
+    // Dialled as 127.0.0.1, which the certificate does not name.
+    let connector = client(None).connector().unwrap();
+    let (_, slave) = tokio::join!(
+        accept(&listener, &acceptor),
+        Connection::connect_tls(&info, &connector)
+    );
+    assert!(slave.is_err());
+
+    let connector = client(Some("tix.example")).connector().unwrap();
+    let (master, slave) = tokio::join!(
+        accept(&listener, &acceptor),
+        Connection::connect_tls(&info, &connector)
+    );
+    master.unwrap();
+    slave.unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
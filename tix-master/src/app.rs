@@ -2,17 +2,81 @@ use ratatui::{
     Frame,
     buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
-    symbols::border,
+    style::Style,
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Widget},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Widget, Wrap},
 };
+use std::collections::VecDeque;
 use std::path::{Path, PathBuf};
+use tix_core::format::{format_bytes, format_duration};
+use tix_core::paste_guard::{self, Verdict};
+use tix_core::policy::CommandSet;
+use tix_core::protocol::{DeleteMode, EventLevel, FileAttributes};
+use tix_core::{Command, Feature, FeatureSet, TixError};
+
+use crate::commands::{self, ConfirmGate};
+use crate::eventlog;
+use crate::inventory;
+use crate::late::LATE_MARKER;
+use crate::localfs::LocalFsOps;
+use crate::localops::{self, CopyProgress, LocalListing, LocalOp, LocalOpsWorker};
+use crate::notify::{self, Notifier};
+use crate::pager::{self, LogBuffer, LogEntry, LogRow, Page, Pager};
+use crate::palette::{self, Palette, PaletteAction, Source};
+use crate::remote_path::{self, Lookup, RemoteCompletions};
+use crate::retention::HistoryFile;
+use crate::roles;
+use crate::search::SEARCH_STATUS_PREFIX;
+use crate::services::ServiceGuard;
+use crate::theme::{Theme, ThemeName};
+use crate::tasks::{TaskId, TaskList, TaskPanel};
+use crate::transfers::{self, TransferCommand, TransferPanel, TransferState, TransferUpdate};
+use crate::watch;
+
+/// Tasks panel ID of the copy running on the host.
+const LOCAL_COPY_TASK: &str = "host copy";
+
+/// Smallest terminal the console draws itself in.
+pub const MIN_WIDTH: u16 = 60;
+pub const MIN_HEIGHT: u16 = 20;
+/// Below this width the sidebar goes under the logs and the tabs lose
+/// their names.
+const WIDE_WIDTH: u16 = 100;
+
+/// How the console arranges itself for the terminal's size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutMode {
+    /// Logs and sidebar side by side, full tab titles.
+    Wide,
+    /// Sidebar stacked under the logs, tab keys only.
+    Narrow,
+    /// Only a message asking for a bigger terminal.
+    TooSmall,
+}
+
+impl LayoutMode {
+    pub fn for_area(area: Rect) -> Self {
+        if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+            LayoutMode::TooSmall
+        } else if area.width < WIDE_WIDTH {
+            LayoutMode::Narrow
+        } else {
+            LayoutMode::Wide
+        }
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct SlaveInfo {
     pub ip: String,
+    /// Friendly or host name from the inventory; empty until the slave
+    /// identifies itself.
+    pub name: String,
     pub ram_usage: String,
+    /// Last / min / avg / max round-trip time.
+    pub ping: String,
+    /// Smoothed heartbeat round trip and its variance.
+    pub rtt: String,
     pub other: Vec<String>,
 }
 
@@ -29,11 +93,28 @@ pub enum UiEvent {
 
 #[derive(Debug, Clone)]
 pub enum MasterEvent {
-    Log(String),
+    /// A console message, stamped where it was produced.
+    Log(LogEntry),
+    /// A slave's answer to request `id`; paged when it is long.
+    Response {
+        id: u64,
+        text: String,
+    },
     SlaveConnected(String),
+    /// The slave, by name, went away.
+    SlaveDisconnected(String),
+    /// The connected slave's name from the inventory.
+    SlaveNamed(String),
     SlaveInfo {
         ram_usage: String,
     },
+    /// Request `id` went out as `command` (`ShellExecute`) with
+    /// `summary` (`dir`); its Tasks row times it from now.
+    TaskStarted {
+        id: u64,
+        command: String,
+        summary: String,
+    },
     TaskUpdate {
         id: u64,
         status: String,
@@ -46,6 +127,122 @@ pub enum MasterEvent {
     RefreshTree {
         is_slave: bool,
     },
+    /// Updated round-trip statistics line for the Slave PC panel.
+    PingStats(String),
+    /// New heartbeat round-trip line for the Slave PC panel.
+    LinkRtt(String),
+    /// Bytes a second the connection to the slave sends at, for upload
+    /// estimates; `None` until measured.
+    LinkRate(Option<u64>),
+    /// A host directory read by the local-ops worker.
+    LocalListing(LocalListing),
+    /// Progress, or the end, of a copy on the host.
+    LocalCopy(CopyProgress),
+    /// The connected slave's directory for relative paths; `None` until
+    /// `cd` sets one.
+    RemoteCwd(Option<String>),
+    /// A slave directory listed for path completion: the `ListDir`
+    /// reply, empty when the listing failed.
+    RemoteListing {
+        dir: String,
+        data: String,
+    },
+    /// The operator's role changed; `None` when there is none.
+    Role {
+        name: Option<String>,
+        allowed: CommandSet,
+    },
+    /// The connected slave's version and the optional features it
+    /// listed in its `Hello`; `None` when it listed none.
+    SlaveFeatures {
+        version: String,
+        features: Option<FeatureSet>,
+    },
+    /// A transfer item was queued, moved or finished.
+    Transfer(TransferUpdate),
+}
+
+impl MasterEvent {
+    /// A [`Log`](Self::Log) of `text`, stamped now.
+    pub fn log(text: impl Into<String>) -> Self {
+        Self::Log(LogEntry::new(text))
+    }
+
+    /// Variant name, for diagnostics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Log(_) => "Log",
+            Self::Response { .. } => "Response",
+            Self::SlaveConnected(_) => "SlaveConnected",
+            Self::SlaveDisconnected(_) => "SlaveDisconnected",
+            Self::SlaveNamed(_) => "SlaveNamed",
+            Self::SlaveInfo { .. } => "SlaveInfo",
+            Self::TaskStarted { .. } => "TaskStarted",
+            Self::TaskUpdate { .. } => "TaskUpdate",
+            Self::TreeData { .. } => "TreeData",
+            Self::RefreshTree { .. } => "RefreshTree",
+            Self::PingStats(_) => "PingStats",
+            Self::LinkRtt(_) => "LinkRtt",
+            Self::LinkRate(_) => "LinkRate",
+            Self::LocalListing(_) => "LocalListing",
+            Self::LocalCopy(_) => "LocalCopy",
+            Self::RemoteCwd(_) => "RemoteCwd",
+            Self::RemoteListing { .. } => "RemoteListing",
+            Self::Role { .. } => "Role",
+            Self::SlaveFeatures { .. } => "SlaveFeatures",
+            Self::Transfer(_) => "Transfer",
+        }
+    }
+}
+
+/// How loudly the log reports a failed command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorSeverity {
+    /// The user can fix it: a typo, or no slave to send to. Yellow.
+    Hint,
+    /// Anything else that went wrong. Red.
+    Error,
+    /// Data arrived damaged. Bold red.
+    Corruption,
+    /// A slave task panicked. Reversed red.
+    Crash,
+}
+
+impl ErrorSeverity {
+    const PREFIXES: [(Self, &'static str); 4] = [
+        (Self::Hint, "Hint: "),
+        (Self::Corruption, "Data corruption: "),
+        (Self::Crash, "Slave crash: "),
+        (Self::Error, "Error: "),
+    ];
+
+    pub fn of(err: &TixError) -> Self {
+        match err {
+            TixError::NotConnected | TixError::InvalidCommandSyntax(_) => Self::Hint,
+            TixError::ChecksumMismatch | TixError::FileIntegrityFailed => Self::Corruption,
+            _ => Self::Error,
+        }
+    }
+
+    /// The severity a log line was written with, if it reports an error.
+    pub fn of_line(line: &str) -> Option<Self> {
+        Self::PREFIXES
+            .iter()
+            .find(|(_, prefix)| line.starts_with(prefix))
+            .map(|(severity, _)| *severity)
+    }
+
+    fn prefix(self) -> &'static str {
+        Self::PREFIXES
+            .iter()
+            .find(|(severity, _)| *severity == self)
+            .map_or("Error: ", |(_, prefix)| prefix)
+    }
+}
+
+/// The log line for a command that failed with `err`.
+pub fn error_line(err: &TixError) -> String {
+    format!("{}{}", ErrorSeverity::of(err).prefix(), err)
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -68,6 +265,10 @@ pub struct CompletionState {
     pub active: bool,
     pub trigger_type: Option<CompletionType>,
     pub last_input: String,
+    /// Byte offset in the input where a slave path's last component
+    /// starts; the chosen option replaces everything from there. `None`
+    /// replaces the last word.
+    pub anchor: Option<usize>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -85,6 +286,19 @@ pub struct FileNode {
     pub is_expanded: bool,
     pub children: Option<Vec<FileNode>>,
     pub is_selected: bool,
+    /// Hidden, system and link flags, as listed.
+    pub attributes: FileAttributes,
+    /// Length of a file, when the listing gave one. Directories have
+    /// none.
+    pub size: Option<u64>,
+}
+
+impl FileNode {
+    /// Whether the node gets a row in a panel that does or does not
+    /// show hidden entries.
+    fn is_shown(&self, show_hidden: bool) -> bool {
+        show_hidden || !self.attributes.is_hidden()
+    }
 }
 
 #[derive(Debug, Default)]
@@ -92,6 +306,8 @@ pub struct TreeViewState {
     pub root_nodes: Vec<FileNode>,
     pub cursor_index: usize,
     pub scroll_offset: usize,
+    /// List hidden entries too (toggled with `h`).
+    pub show_hidden: bool,
 }
 
 #[derive(Debug, Default)]
@@ -101,16 +317,124 @@ pub struct TreeExplorerState {
     pub active_side: bool, // false = local, true = slave
     pub clipboard: Vec<PathBuf>,
     pub is_cut_operation: bool,
+    /// Delete waiting for the user to answer the confirmation popup.
+    pub pending_delete: Option<PendingDelete>,
+    /// Slave paths sent to the Recycle Bin by the last confirmed delete.
+    pub last_recycled: Vec<PathBuf>,
+    /// Host-side name being typed for a new folder or a rename.
+    pub pending_name: Option<NameInput>,
+}
+
+/// A console command held back until the user confirms it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingCommand {
+    pub question: String,
+    pub command: String,
+    /// Commands confirmed along with it, e.g. the rest of a paste.
+    pub also: Vec<String>,
+}
+
+/// A delete that has been requested but not yet confirmed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingDelete {
+    pub paths: Vec<PathBuf>,
+    pub mode: DeleteMode,
+    /// The paths are on the host rather than the slave.
+    pub local: bool,
+    /// Why the last attempt at a host delete failed.
+    pub error: Option<String>,
+}
+
+impl PendingDelete {
+    /// Confirmation question shown in the popup.
+    pub fn prompt(&self) -> String {
+        let target = if self.paths.len() == 1 {
+            format!("'{}'", self.paths[0].display())
+        } else {
+            format!("{} items", self.paths.len())
+        };
+        let side = if self.local { "this PC" } else { "the slave" };
+        match self.mode {
+            DeleteMode::Recycle => format!("Move {} on {} to the Recycle Bin?", target, side),
+            DeleteMode::Permanent => format!(
+                "PERMANENTLY delete {} on {}? This cannot be undone.",
+                target, side
+            ),
+        }
+    }
+
+    /// Console command that performs the delete.
+    pub fn to_command(&self) -> String {
+        let mut cmd = "Delete".to_string();
+        if self.mode == DeleteMode::Permanent {
+            cmd.push_str(" --permanent");
+        }
+        for path in &self.paths {
+            cmd.push_str(&format!(" \"{}\"", path.display()));
+        }
+        cmd
+    }
+}
+
+/// What a confirmed [`NameInput`] does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NameAction {
+    /// Create a directory inside this one.
+    NewFolder(PathBuf),
+    /// Rename this file or directory.
+    Rename(PathBuf),
+}
+
+/// A host-side name being typed into the tree popup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameInput {
+    pub action: NameAction,
+    pub name: String,
+    /// Why the last attempt failed; shown under the input.
+    pub error: Option<String>,
+}
+
+impl NameInput {
+    /// Popup title.
+    pub fn title(&self) -> &'static str {
+        match self.action {
+            NameAction::NewFolder(_) => " New Folder ",
+            NameAction::Rename(_) => " Rename ",
+        }
+    }
+
+    /// Line above the input.
+    pub fn prompt(&self) -> String {
+        match &self.action {
+            NameAction::NewFolder(parent) => format!("New folder in {}:", parent.display()),
+            NameAction::Rename(path) => format!("Rename '{}' to:", path.display()),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct App {
     pub master_info: MasterInfo,
     pub slave_info: SlaveInfo,
-    pub tasks: Vec<String>,
+    pub tasks: TaskList,
+    /// Request IDs of `find` commands still running, oldest first.
+    pub running_searches: Vec<u64>,
     pub command_to_execute: String,
-    pub logs: Vec<String>,
+    pub logs: LogBuffer,
+    /// Rows the log pane is scrolled up from the bottom.
     pub log_scroll: usize,
+    /// [`LogBuffer::rows_added`] when the scroll last caught up with it.
+    log_rows_seen: usize,
+    /// Whether log lines start with the time they were logged.
+    pub log_times: bool,
+    /// Lines in the log pane at the last draw.
+    pub log_view_height: usize,
+    /// Responses longer than this many lines are paged.
+    pub page_threshold: usize,
+    /// Paged responses, oldest first.
+    pub pages: VecDeque<Page>,
+    /// Open full-screen pager, if any.
+    pub pager: Option<Pager>,
     pub autoscroll: bool,
     pub completion: CompletionState,
     pub exit: bool,
@@ -119,6 +443,55 @@ pub struct App {
     pub needs_completion_update: bool,
     pub active_tab: Tab,
     pub tree_explorer: TreeExplorerState,
+    pub theme: Theme,
+    /// Suggestions only open on Tab instead of following the input.
+    pub reduced_motion: bool,
+    /// Runs host listings and copies; without one they run inline.
+    pub local_ops: Option<LocalOpsWorker>,
+    /// Bell, banner and toast alerts; none without one.
+    pub notifier: Option<Notifier>,
+    pub slave_connected: bool,
+    /// The slave directory set with `cd`, shown in the prompt.
+    pub remote_cwd: Option<String>,
+    /// Slave listings fetched for path completion.
+    pub remote_listings: RemoteCompletions,
+    /// A listing completion needs from the slave, for the master task.
+    remote_request: Option<String>,
+    /// Decides which `service` commands need confirming.
+    pub service_guard: ServiceGuard,
+    /// Decides which other commands need confirming.
+    pub confirm_gate: ConfirmGate,
+    /// Command waiting for the user to answer the confirmation popup.
+    pub pending_command: Option<PendingCommand>,
+    /// Rate the master last measured uploads at, in bytes a second.
+    pub link_rate: Option<u64>,
+    /// Uploads and downloads, shown under the trees with `T`.
+    pub transfer_panel: TransferPanel,
+    /// Master events that arrived while the popup was open; they are
+    /// applied once it is answered, so it keeps describing the slave
+    /// the command would go to.
+    held_events: VecDeque<MasterEvent>,
+    /// Open command palette (`Ctrl+P`), if any.
+    pub palette: Option<Palette>,
+    /// Arguments the command picked in the palette still needs, drawn
+    /// after the input until it changes.
+    pub input_hint: Option<String>,
+    /// Commands sent, newest first.
+    pub history: VecDeque<String>,
+    /// Where `history` is also written, redacted, if anywhere.
+    history_file: Option<HistoryFile>,
+    /// Slave folders visited, newest first.
+    pub recent_paths: VecDeque<String>,
+    /// Slave folder the tree explorer is opening its way to.
+    pending_reveal: Option<String>,
+    /// The operator's role, if one is active.
+    pub role: Option<String>,
+    /// Commands the role allows; key bindings for the others are dimmed.
+    pub allowed: CommandSet,
+    /// The connected slave's version and the optional features its
+    /// `Hello` listed; commands needing any other are dimmed and
+    /// refused. `None` when it listed none.
+    pub slave_features: Option<(String, FeatureSet)>,
 }
 
 impl Default for App {
@@ -129,40 +502,163 @@ impl Default for App {
 
 impl App {
     pub fn new() -> Self {
-        Self {
+        let mut app = Self {
             master_info: MasterInfo {
                 ip: "10.0.0.1".to_string(),
             },
             slave_info: SlaveInfo {
                 ip: "Not Connected".to_string(),
+                name: String::new(),
                 ram_usage: "N/A".to_string(),
+                ping: "N/A".to_string(),
+                rtt: "N/A".to_string(),
                 other: Vec::new(),
             },
-            tasks: Vec::new(),
+            tasks: TaskList::default(),
+            running_searches: Vec::new(),
             command_to_execute: String::new(),
-            logs: vec![
-                "Welcome to Tix Master".to_string(),
-                "Waiting for connections...".to_string(),
-            ],
+            logs: LogBuffer::default(),
             log_scroll: 0,
+            log_rows_seen: 0,
+            log_times: false,
+            log_view_height: 0,
+            page_threshold: pager::DEFAULT_PAGE_THRESHOLD,
+            pages: VecDeque::new(),
+            pager: None,
             autoscroll: true,
             completion: CompletionState::default(),
             exit: false,
-            available_commands: vec![
-                "Ping".to_string(),
-                "ShellExecute".to_string(),
-                "Copy".to_string(),
-                "ListDrives".to_string(),
-                "ListDir".to_string(),
-                "Upload".to_string(),
-                "Download".to_string(),
-                "SystemAction".to_string(),
-                "Exit".to_string(),
-            ],
+            available_commands: commands::names(),
             last_input_time: std::time::Instant::now(),
             needs_completion_update: false,
             active_tab: Tab::Main,
             tree_explorer: TreeExplorerState::default(),
+            theme: Theme::default(),
+            reduced_motion: false,
+            local_ops: None,
+            notifier: None,
+            slave_connected: false,
+            remote_cwd: None,
+            remote_listings: RemoteCompletions::new(),
+            remote_request: None,
+            service_guard: ServiceGuard::default(),
+            confirm_gate: ConfirmGate::default(),
+            pending_command: None,
+            link_rate: None,
+            transfer_panel: TransferPanel::default(),
+            held_events: VecDeque::new(),
+            palette: None,
+            input_hint: None,
+            history: VecDeque::new(),
+            history_file: None,
+            recent_paths: VecDeque::new(),
+            pending_reveal: None,
+            role: None,
+            allowed: CommandSet::all(),
+            slave_features: None,
+        };
+        app.logs.push("Welcome to Tix Master");
+        app.logs.push("Waiting for connections...");
+        app
+    }
+
+    /// Page responses longer than `lines` and keep at most `capacity`
+    /// console lines.
+    pub fn with_paging(mut self, lines: usize, capacity: usize) -> Self {
+        self.page_threshold = lines;
+        self.logs.set_capacity(capacity);
+        self
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Keep the suggestion popup still: typing closes it and only Tab
+    /// opens it again.
+    pub fn with_reduced_motion(mut self, reduced_motion: bool) -> Self {
+        self.reduced_motion = reduced_motion;
+        self
+    }
+
+    /// Keep `history` finished rows in the Tasks sidebar.
+    pub fn with_task_history(mut self, history: usize) -> Self {
+        self.tasks = TaskList::with_history(history);
+        self
+    }
+
+    /// Run host listings and copies on `worker` instead of the UI task.
+    pub fn with_local_ops(mut self, worker: LocalOpsWorker) -> Self {
+        self.local_ops = Some(worker);
+        self
+    }
+
+    /// Alert on finished tasks and slaves coming and going.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Ask before stopping the services `guard` protects.
+    pub fn with_service_guard(mut self, guard: ServiceGuard) -> Self {
+        self.service_guard = guard;
+        self
+    }
+
+    pub fn with_confirm_gate(mut self, gate: ConfirmGate) -> Self {
+        self.confirm_gate = gate;
+        self
+    }
+
+    /// Start from the commands in `file` and add each one sent to it.
+    pub fn with_history_file(mut self, file: HistoryFile) -> Self {
+        match file.load() {
+            Ok(commands) => {
+                for command in commands {
+                    palette::remember(&mut self.history, &command, palette::MAX_HISTORY);
+                }
+            }
+            Err(e) => self.logs.push(format!(
+                "[DATA] Cannot read history {}: {}",
+                file.path().display(),
+                e
+            )),
+        }
+        self.history_file = Some(file);
+        self
+    }
+
+    /// Add `cmd` to the history, and to the history file with its
+    /// secrets masked. The caller sends `cmd` itself, as typed.
+    fn remember(&mut self, cmd: &str) {
+        palette::remember(&mut self.history, &roles::redact(cmd), palette::MAX_HISTORY);
+        let Some(file) = &self.history_file else {
+            return;
+        };
+        if let Err(e) = file.append(cmd, inventory::unix_now()) {
+            let path = file.path().display().to_string();
+            self.history_file = None;
+            self.logs.push(format!(
+                "[DATA] Cannot write history {}: {}; it is kept for this session only",
+                path, e
+            ));
+        }
+    }
+
+    /// Hand `op` to the worker, or run it here when there is none.
+    fn run_local(&mut self, op: LocalOp) {
+        let op = match &self.local_ops {
+            Some(worker) => match worker.submit(op) {
+                Ok(()) => return,
+                Err(op) => op,
+            },
+            None => op,
+        };
+        let mut events = Vec::new();
+        localops::run(op, &mut |event| events.push(event));
+        for event in events {
+            self.update(event);
         }
     }
 
@@ -191,6 +687,8 @@ impl App {
                     is_expanded: false,
                     children: None,
                     is_selected: false,
+                    attributes: FileAttributes::empty(),
+                    size: None,
                 });
             }
         }
@@ -223,26 +721,28 @@ impl App {
             tree.cursor_index,
             &mut current_idx,
             &mut current_path,
+            tree.show_hidden,
         );
 
         if let Some(path) = current_path {
             if !active_side {
                 // Local refresh
-                if let Some(node) = Self::find_node_mut(&mut tree.root_nodes, &path) {
-                    if node.is_dir && node.is_expanded {
-                        Self::load_node_children_static(node);
-                        self.logs
-                            .push(format!("Refreshed local directory: {}", path.display()));
-                    } else if let Some(parent_path) = path.parent()
-                        && let Some(parent_node) =
-                            Self::find_node_mut(&mut tree.root_nodes, parent_path)
-                    {
-                        Self::load_node_children_static(parent_node);
-                        self.logs.push(format!(
-                            "Refreshed local parent directory: {}",
-                            parent_path.display()
-                        ));
-                    }
+                let dir = match Self::find_node_at_path_static(&tree.root_nodes, &path) {
+                    Some(node) if node.is_dir && node.is_expanded => Some(path.clone()),
+                    Some(_) => path
+                        .parent()
+                        .filter(|p| Self::find_node_at_path_static(&tree.root_nodes, p).is_some())
+                        .map(Path::to_path_buf),
+                    None => None,
+                };
+                if let Some(dir) = dir {
+                    self.logs
+                        .push(format!("Refreshing local directory: {}", dir.display()));
+                    self.run_local(LocalOp::List {
+                        dir,
+                        open: false,
+                        select: None,
+                    });
                 }
             } else {
                 // Slave refresh
@@ -299,22 +799,15 @@ impl App {
 
     pub fn tree_cursor_down(&mut self) {
         let active_side = self.tree_explorer.active_side;
-        let (root_nodes, cursor_index, _) = if !active_side {
-            (
-                &self.tree_explorer.local_tree.root_nodes,
-                &mut self.tree_explorer.local_tree.cursor_index,
-                &mut self.tree_explorer.local_tree.scroll_offset,
-            )
+        let tree = if !active_side {
+            &mut self.tree_explorer.local_tree
         } else {
-            (
-                &self.tree_explorer.slave_tree.root_nodes,
-                &mut self.tree_explorer.slave_tree.cursor_index,
-                &mut self.tree_explorer.slave_tree.scroll_offset,
-            )
+            &mut self.tree_explorer.slave_tree
         };
+        let cursor_index = &mut tree.cursor_index;
 
         let mut count = 0;
-        Self::count_visible_static(root_nodes, &mut count);
+        Self::count_visible_static(&tree.root_nodes, &mut count, tree.show_hidden);
         if *cursor_index + 1 < count {
             *cursor_index += 1;
         }
@@ -335,15 +828,17 @@ impl App {
 
     pub fn tree_toggle_expand(&mut self) -> Option<String> {
         let active_side = self.tree_explorer.active_side;
-        let (root_nodes, cursor_index) = if !active_side {
+        let (root_nodes, cursor_index, show_hidden) = if !active_side {
             (
                 &mut self.tree_explorer.local_tree.root_nodes,
                 self.tree_explorer.local_tree.cursor_index,
+                self.tree_explorer.local_tree.show_hidden,
             )
         } else {
             (
                 &mut self.tree_explorer.slave_tree.root_nodes,
                 self.tree_explorer.slave_tree.cursor_index,
+                self.tree_explorer.slave_tree.show_hidden,
             )
         };
 
@@ -355,14 +850,16 @@ impl App {
             cursor_index,
             &mut current_idx,
             &mut node_to_load,
+            show_hidden,
         );
 
         if let Some(path) = node_to_load {
             if !active_side {
-                // Find node again to load children (to satisfy borrow checker)
-                if let Some(node) = Self::find_node_mut(root_nodes, &path) {
-                    Self::load_node_children_static(node);
-                }
+                self.run_local(LocalOp::List {
+                    dir: path,
+                    open: false,
+                    select: None,
+                });
             } else {
                 let path_str = path.to_string_lossy().to_string();
                 self.logs.push(format!(
@@ -375,34 +872,14 @@ impl App {
         None
     }
 
-    fn load_node_children_static(node: &mut FileNode) {
-        if let Ok(entries) = std::fs::read_dir(&node.path) {
-            let mut children = Vec::new();
-            for entry in entries.flatten() {
-                let path = entry.path();
-                let name = entry.file_name().to_string_lossy().to_string();
-                let is_dir = path.is_dir();
-                children.push(FileNode {
-                    name,
-                    path,
-                    is_dir,
-                    is_expanded: false,
-                    children: None,
-                    is_selected: false,
-                });
-            }
-            children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
-            node.children = Some(children);
-        }
-    }
-
     fn toggle_node_at_static(
-        nodes: &mut Vec<FileNode>,
+        nodes: &mut [FileNode],
         target_idx: usize,
         current_idx: &mut usize,
         node_to_load: &mut Option<PathBuf>,
+        show_hidden: bool,
     ) -> bool {
-        for node in nodes {
+        for node in nodes.iter_mut().filter(|n| n.is_shown(show_hidden)) {
             if *current_idx == target_idx {
                 if node.is_dir {
                     node.is_expanded = !node.is_expanded;
@@ -415,7 +892,13 @@ impl App {
             *current_idx += 1;
             if node.is_expanded
                 && let Some(children) = &mut node.children
-                && Self::toggle_node_at_static(children, target_idx, current_idx, node_to_load)
+                && Self::toggle_node_at_static(
+                    children,
+                    target_idx,
+                    current_idx,
+                    node_to_load,
+                    show_hidden,
+                )
             {
                 return true;
             }
@@ -437,41 +920,44 @@ impl App {
         None
     }
 
-    fn count_visible_static(nodes: &[FileNode], count: &mut usize) {
-        for node in nodes {
+    fn count_visible_static(nodes: &[FileNode], count: &mut usize, show_hidden: bool) {
+        for node in nodes.iter().filter(|n| n.is_shown(show_hidden)) {
             *count += 1;
             if node.is_expanded
                 && let Some(children) = &node.children
             {
-                Self::count_visible_static(children, count);
+                Self::count_visible_static(children, count, show_hidden);
             }
         }
     }
 
     pub fn tree_toggle_select(&mut self) {
         let active_side = self.tree_explorer.active_side;
-        let (root_nodes, cursor_index) = if !active_side {
+        let (root_nodes, cursor_index, show_hidden) = if !active_side {
             (
                 &mut self.tree_explorer.local_tree.root_nodes,
                 self.tree_explorer.local_tree.cursor_index,
+                self.tree_explorer.local_tree.show_hidden,
             )
         } else {
             (
                 &mut self.tree_explorer.slave_tree.root_nodes,
                 self.tree_explorer.slave_tree.cursor_index,
+                self.tree_explorer.slave_tree.show_hidden,
             )
         };
 
         let mut current_idx = 0;
-        Self::select_node_at_static(root_nodes, cursor_index, &mut current_idx);
+        Self::select_node_at_static(root_nodes, cursor_index, &mut current_idx, show_hidden);
     }
 
     fn select_node_at_static(
-        nodes: &mut Vec<FileNode>,
+        nodes: &mut [FileNode],
         target_idx: usize,
         current_idx: &mut usize,
+        show_hidden: bool,
     ) -> bool {
-        for node in nodes {
+        for node in nodes.iter_mut().filter(|n| n.is_shown(show_hidden)) {
             if *current_idx == target_idx {
                 node.is_selected = !node.is_selected;
                 return true;
@@ -479,7 +965,7 @@ impl App {
             *current_idx += 1;
             if node.is_expanded
                 && let Some(children) = &mut node.children
-                && Self::select_node_at_static(children, target_idx, current_idx)
+                && Self::select_node_at_static(children, target_idx, current_idx, show_hidden)
             {
                 return true;
             }
@@ -540,163 +1026,644 @@ impl App {
         }
     }
 
-    pub fn tree_switch_side(&mut self) {
-        self.tree_explorer.active_side = !self.tree_explorer.active_side;
-    }
-
-    pub fn tree_paste(&mut self) -> Vec<String> {
-        let mut commands = Vec::new();
-        if self.tree_explorer.clipboard.is_empty() {
-            self.logs.push("Clipboard is empty".to_string());
-            return commands;
-        }
-
-        let active_side = self.tree_explorer.active_side;
-        let dest_tree = if !active_side {
+    /// Ask for confirmation before deleting the selected items of the
+    /// active tree (or the one under the cursor when nothing is selected).
+    pub fn tree_request_delete(&mut self, mode: DeleteMode) {
+        let local = !self.tree_explorer.active_side;
+        let tree = if local {
             &self.tree_explorer.local_tree
         } else {
             &self.tree_explorer.slave_tree
         };
-
-        // Find the current directory at cursor or use root
-        let mut current_idx = 0;
-        let mut dest_path = None;
-        Self::get_path_at_cursor_static(
-            &dest_tree.root_nodes,
-            dest_tree.cursor_index,
-            &mut current_idx,
-            &mut dest_path,
-        );
-
-        let dest_dir = if let Some(path) = dest_path {
-            if path.is_dir() {
-                path
-            } else {
-                path.parent().unwrap_or(Path::new("")).to_path_buf()
-            }
-        } else if !dest_tree.root_nodes.is_empty() {
-            dest_tree.root_nodes[0].path.clone()
-        } else {
-            self.logs
-                .push("Error: Could not determine destination directory".to_string());
-            return commands;
-        };
-
-        let dest_dir_str = dest_dir.to_string_lossy().to_string();
-        let _is_upload = !self.tree_explorer.active_side; // False if pasting INTO local (download), True if pasting INTO slave (upload)
-        // Wait, active_side: false = local, true = slave.
-        // If active_side is true, we are on slave side, so we want to paste INTO slave (Upload).
-        // If active_side is false, we are on local side, so we want to paste INTO local (Download).
-        let is_paste_to_slave = active_side;
-
-        // Determine if source is also on the same side
-        // For simplicity, we assume if we are on local side, we only paste local paths if they are local
-        // and if we are on slave side, we only paste slave paths if they are slave.
-        // But the clipboard doesn't currently store which side the paths came from.
-        // Let's assume for now:
-        // - If dest is local and all paths are absolute windows paths, it's a local copy.
-        // - If dest is slave, we always use Upload for now (since we don't know if src was slave).
-
-        let mut local_copy_count = 0;
-
-        for src_path in &self.tree_explorer.clipboard {
-            let src_path_str = src_path.to_string_lossy().to_string();
-
-            if is_paste_to_slave {
-                // Upload: Local -> Slave
-                self.logs
-                    .push(format!("Uploading {} to {}", src_path_str, dest_dir_str));
-                commands.push(format!("Upload {}|{}", src_path_str, dest_dir_str));
-            } else {
-                // Dest is Local.
-                // If it's a local-to-local copy:
-                if src_path.exists() {
-                    let mut dest_file = dest_dir.clone();
-                    if let Some(file_name) = src_path.file_name() {
-                        dest_file.push(file_name);
-                        self.logs.push(format!(
-                            "Copying local {} to {}",
-                            src_path_str,
-                            dest_file.display()
-                        ));
-                        if src_path.is_dir() {
-                            // Simplified directory copy
-                            let _ = self.copy_dir_all(src_path, &dest_file);
-                        } else {
-                            let _ = std::fs::copy(src_path, &dest_file);
-                        }
-                        local_copy_count += 1;
-                    }
-                } else {
-                    // Download: Slave -> Local
-                    self.logs
-                        .push(format!("Downloading {} to {}", src_path_str, dest_dir_str));
-                    commands.push(format!("Download {}|{}", src_path_str, dest_dir_str));
-                }
-            }
+        let mut paths = Vec::new();
+        self.get_selected_paths(&tree.root_nodes, &mut paths);
+        if paths.is_empty() {
+            let mut current_idx = 0;
+            let mut cursor_path = None;
+            Self::get_path_at_cursor_static(
+                &tree.root_nodes,
+                tree.cursor_index,
+                &mut current_idx,
+                &mut cursor_path,
+                tree.show_hidden,
+            );
+            paths.extend(cursor_path);
         }
 
-        if local_copy_count > 0 {
-            self.tree_refresh();
+        if paths.is_empty() {
+            self.logs.push("Nothing to delete".to_string());
+            return;
         }
+        self.tree_explorer.pending_delete = Some(PendingDelete {
+            paths,
+            mode,
+            local,
+            error: None,
+        });
+    }
 
-        if self.tree_explorer.is_cut_operation {
-            // In a real app, we'd delete after successful copy. For now just clear.
-            self.tree_explorer.clipboard.clear();
+    /// Accept the pending delete. Slave deletes return the command to
+    /// send; host deletes run here and keep the popup open on failure.
+    pub fn confirm_delete(&mut self) -> Option<String> {
+        let pending = self.tree_explorer.pending_delete.take()?;
+        if pending.local {
+            self.delete_local(pending);
+            return None;
         }
+        self.logs.push(format!(
+            "Deleting {} item(s) {}",
+            pending.paths.len(),
+            pending.mode.describe()
+        ));
+        if pending.mode == DeleteMode::Recycle {
+            self.tree_explorer.last_recycled = pending.paths.clone();
+        }
+        Some(commands::confirmed(&pending.to_command()))
+    }
 
-        commands
+    /// Drop the pending delete.
+    pub fn cancel_delete(&mut self) {
+        if self.tree_explorer.pending_delete.take().is_some() {
+            self.logs.push("Delete cancelled".to_string());
+        }
     }
 
-    fn get_path_at_cursor_static(
-        nodes: &[FileNode],
-        target_idx: usize,
-        current_idx: &mut usize,
-        found_path: &mut Option<PathBuf>,
-    ) -> bool {
-        for node in nodes {
-            if *current_idx == target_idx {
-                *found_path = Some(node.path.clone());
-                return true;
-            }
-            *current_idx += 1;
-            if node.is_expanded
-                && let Some(children) = &node.children
-                && Self::get_path_at_cursor_static(children, target_idx, current_idx, found_path)
-            {
-                return true;
-            }
+    /// Restore everything the last recycle delete removed.
+    pub fn tree_undo_delete(&mut self) -> Vec<String> {
+        if let Some(note) = self.unsupported(Some(Feature::Trash)) {
+            self.logs.push(format!("Error: TrashRestore {}", note));
+            return Vec::new();
         }
-        false
+        let paths = std::mem::take(&mut self.tree_explorer.last_recycled);
+        if paths.is_empty() {
+            self.logs.push("Nothing to restore".to_string());
+        }
+        paths
+            .iter()
+            .map(|p| format!("TrashRestore \"{}\"", p.display()))
+            .collect()
     }
 
-    fn copy_dir_all(&self, src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
-        std::fs::create_dir_all(&dst)?;
-        for entry in std::fs::read_dir(src)? {
-            let entry = entry?;
-            let ty = entry.file_type()?;
-            if ty.is_dir() {
-                self.copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name()))?;
-            } else {
-                std::fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
+    /// Run a confirmed host delete and refresh the parents of whatever
+    /// went away. Failed paths stay in the popup with the error.
+    fn delete_local(&mut self, mut pending: PendingDelete) {
+        let mut failed = Vec::new();
+        let mut errors = Vec::new();
+        let mut parents = Vec::new();
+        for path in &pending.paths {
+            match LocalFsOps.delete(path, pending.mode) {
+                Ok(()) => {
+                    self.logs.push(format!(
+                        "Deleted {} {}",
+                        path.display(),
+                        pending.mode.describe()
+                    ));
+                    parents.extend(path.parent().map(Path::to_path_buf));
+                }
+                Err(e) => {
+                    self.logs.push(format!("Delete failed: {}", e));
+                    failed.push(path.clone());
+                    errors.push(e);
+                }
             }
         }
-        Ok(())
-    }
+        parents.sort();
+        parents.dedup();
+        for parent in &parents {
+            self.reload_local_dir(parent, None);
+        }
 
-    pub fn on_input_change(&mut self) {
-        self.last_input_time = std::time::Instant::now();
-        self.needs_completion_update = true;
+        if let Some(first) = errors.first() {
+            pending.error = Some(if errors.len() == 1 {
+                first.clone()
+            } else {
+                format!("{} items were not deleted. {}", errors.len(), first)
+            });
+            pending.paths = failed;
+            self.tree_explorer.pending_delete = Some(pending);
+        }
     }
 
-    pub fn update_completion(&mut self) {
-        if !self.needs_completion_update {
+    /// Start typing the name of a new host folder inside the directory
+    /// under the cursor, or next to the file under it.
+    pub fn tree_request_new_folder(&mut self) {
+        if self.tree_explorer.active_side {
+            self.logs
+                .push("New folder is only available on the host tree".to_string());
             return;
         }
-        self.needs_completion_update = false;
-        self.trigger_completion();
-    }
-
+        let Some(node) = self.local_cursor_node() else {
+            return;
+        };
+        let parent = if node.is_dir {
+            node.path.clone()
+        } else if let Some(parent) = node.path.parent() {
+            parent.to_path_buf()
+        } else {
+            return;
+        };
+        self.tree_explorer.pending_name = Some(NameInput {
+            action: NameAction::NewFolder(parent),
+            name: String::new(),
+            error: None,
+        });
+    }
+
+    /// Start editing the name of the host item under the cursor.
+    pub fn tree_request_rename(&mut self) {
+        if self.tree_explorer.active_side {
+            self.logs
+                .push("Rename is only available on the host tree".to_string());
+            return;
+        }
+        let Some(node) = self.local_cursor_node() else {
+            return;
+        };
+        let is_root = self
+            .tree_explorer
+            .local_tree
+            .root_nodes
+            .iter()
+            .any(|root| root.path == node.path);
+        if is_root {
+            self.logs.push("Drives cannot be renamed".to_string());
+            return;
+        }
+        self.tree_explorer.pending_name = Some(NameInput {
+            action: NameAction::Rename(node.path.clone()),
+            name: node.name.clone(),
+            error: None,
+        });
+    }
+
+    pub fn name_input_push(&mut self, c: char) {
+        if let Some(input) = &mut self.tree_explorer.pending_name {
+            input.name.push(c);
+            input.error = None;
+        }
+    }
+
+    pub fn name_input_pop(&mut self) {
+        if let Some(input) = &mut self.tree_explorer.pending_name {
+            input.name.pop();
+            input.error = None;
+        }
+    }
+
+    pub fn cancel_name_input(&mut self) {
+        self.tree_explorer.pending_name = None;
+    }
+
+    /// Create or rename with the typed name. On success the affected
+    /// directory is re-read and the cursor moves to the result; on
+    /// failure the popup stays open with the error.
+    pub fn confirm_name_input(&mut self) {
+        let Some(mut input) = self.tree_explorer.pending_name.take() else {
+            return;
+        };
+        let result = match &input.action {
+            NameAction::NewFolder(parent) => LocalFsOps.create_dir(parent, &input.name),
+            NameAction::Rename(path) => LocalFsOps.rename(path, &input.name),
+        };
+        match result {
+            Ok(target) => {
+                let dir = match &input.action {
+                    NameAction::NewFolder(parent) => {
+                        self.logs.push(format!("Created {}", target.display()));
+                        parent.clone()
+                    }
+                    NameAction::Rename(old) => {
+                        self.logs.push(format!(
+                            "Renamed {} to {}",
+                            old.display(),
+                            target.display()
+                        ));
+                        old.parent().unwrap_or(Path::new("")).to_path_buf()
+                    }
+                };
+                // The cursor follows the result.
+                self.reload_local_dir(&dir, Some(target));
+            }
+            Err(e) => {
+                input.error = Some(e);
+                self.tree_explorer.pending_name = Some(input);
+            }
+        }
+    }
+
+    /// Host node under the cursor.
+    fn local_cursor_node(&self) -> Option<&FileNode> {
+        let tree = &self.tree_explorer.local_tree;
+        let mut current_idx = 0;
+        let mut path = None;
+        Self::get_path_at_cursor_static(
+            &tree.root_nodes,
+            tree.cursor_index,
+            &mut current_idx,
+            &mut path,
+            tree.show_hidden,
+        );
+        Self::find_node_at_path_static(&tree.root_nodes, &path?)
+    }
+
+    /// Re-read `dir` in the host tree and open it, then put the cursor
+    /// on `select` if given.
+    fn reload_local_dir(&mut self, dir: &Path, select: Option<PathBuf>) {
+        self.run_local(LocalOp::List {
+            dir: dir.to_path_buf(),
+            open: true,
+            select,
+        });
+    }
+
+    /// Put a worker's listing into the host tree, keeping the cursor on
+    /// a visible row.
+    fn apply_local_listing(&mut self, listing: LocalListing) {
+        let tree = &mut self.tree_explorer.local_tree;
+        let Some(node) = Self::find_node_mut(&mut tree.root_nodes, &listing.dir) else {
+            return;
+        };
+        if let Some(children) = listing.children {
+            node.children = Some(children);
+        }
+        node.is_expanded |= listing.open;
+        let mut count = 0;
+        Self::count_visible_static(&tree.root_nodes, &mut count, tree.show_hidden);
+        tree.cursor_index = tree.cursor_index.min(count.saturating_sub(1));
+        if let Some(target) = listing.select {
+            let mut current_idx = 0;
+            if let Some(index) = Self::visible_index_static(
+                &tree.root_nodes,
+                &target,
+                &mut current_idx,
+                tree.show_hidden,
+            ) {
+                tree.cursor_index = index;
+            }
+        }
+    }
+
+    /// Task line and, once it ends, log lines for a host copy.
+    fn on_local_copy(&mut self, progress: CopyProgress) {
+        let dest = progress.dest_dir.display();
+        let copied = format!("{} files, {}", progress.files, format_bytes(progress.bytes));
+        let status = if !progress.done {
+            format!("Copying to {}: {}", dest, copied)
+        } else if progress.errors.is_empty() {
+            format!("Copied {} to {}", copied, dest)
+        } else {
+            format!(
+                "Copied {} to {}, {} failed",
+                copied,
+                dest,
+                progress.errors.len()
+            )
+        };
+        self.tasks.update(
+            TaskId::Local(LOCAL_COPY_TASK),
+            &status,
+            std::time::Instant::now(),
+        );
+        if !progress.done {
+            return;
+        }
+        for error in &progress.errors {
+            self.logs.push(format!("Copy failed: {}", error));
+        }
+        self.logs.push(status);
+        let tree = &self.tree_explorer.local_tree;
+        if Self::find_node_at_path_static(&tree.root_nodes, &progress.dest_dir).is_some() {
+            self.run_local(LocalOp::List {
+                dir: progress.dest_dir,
+                open: false,
+                select: None,
+            });
+        }
+    }
+
+    /// Cursor index of the visible row showing `path`.
+    fn visible_index_static(
+        nodes: &[FileNode],
+        path: &Path,
+        current_idx: &mut usize,
+        show_hidden: bool,
+    ) -> Option<usize> {
+        for node in nodes.iter().filter(|n| n.is_shown(show_hidden)) {
+            if node.path == path {
+                return Some(*current_idx);
+            }
+            *current_idx += 1;
+            if node.is_expanded
+                && let Some(children) = &node.children
+                && let Some(found) =
+                    Self::visible_index_static(children, path, current_idx, show_hidden)
+            {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// Open the slave tree at `path`, listing the folders on the way
+    /// as their turn comes.
+    pub fn tree_reveal(&mut self, path: String) {
+        self.set_tab(Tab::TreeExplorer);
+        self.tree_explorer.active_side = true;
+        self.logs
+            .push(format!("Opening {} in the tree explorer", path));
+        self.pending_reveal = Some(path);
+        self.continue_reveal();
+    }
+
+    /// Expand the slave tree towards the folder being revealed and put
+    /// the cursor on the deepest one loaded, asking for the next
+    /// listing while the way is not yet known.
+    fn continue_reveal(&mut self) {
+        let Some(target) = self.pending_reveal.clone() else {
+            return;
+        };
+        let tree = &mut self.tree_explorer.slave_tree;
+        if tree.root_nodes.is_empty() {
+            self.remote_request = Some("ListDrives".to_string());
+            return;
+        }
+        let Some((reached, needs_listing)) = Self::reveal_static(&mut tree.root_nodes, &target)
+        else {
+            self.pending_reveal = None;
+            self.logs
+                .push(format!("{} is not on the slave's drives", target));
+            return;
+        };
+        let mut current_idx = 0;
+        if let Some(index) = Self::visible_index_static(
+            &tree.root_nodes,
+            &reached,
+            &mut current_idx,
+            tree.show_hidden,
+        ) {
+            tree.cursor_index = index;
+        }
+        if needs_listing {
+            self.remote_request = Some(format!("ListDir {}", reached.to_string_lossy()));
+        } else {
+            self.pending_reveal = None;
+        }
+    }
+
+    /// Expand the folders holding `target`. Returns the deepest one
+    /// and whether its contents still have to be fetched.
+    fn reveal_static(nodes: &mut [FileNode], target: &str) -> Option<(PathBuf, bool)> {
+        let node = nodes
+            .iter_mut()
+            .find(|n| n.is_dir && remote_path::contains(&n.path.to_string_lossy(), target))?;
+        node.is_expanded = true;
+        match &mut node.children {
+            Some(children) => Some(
+                Self::reveal_static(children, target).unwrap_or_else(|| (node.path.clone(), false)),
+            ),
+            None => Some((node.path.clone(), true)),
+        }
+    }
+
+    /// Show or hide hidden entries in the active panel, keeping the
+    /// cursor on the same entry when it stays visible.
+    pub fn tree_toggle_hidden(&mut self) {
+        let active_side = self.tree_explorer.active_side;
+        let tree = if !active_side {
+            &mut self.tree_explorer.local_tree
+        } else {
+            &mut self.tree_explorer.slave_tree
+        };
+        let mut current_idx = 0;
+        let mut cursor_path = None;
+        Self::get_path_at_cursor_static(
+            &tree.root_nodes,
+            tree.cursor_index,
+            &mut current_idx,
+            &mut cursor_path,
+            tree.show_hidden,
+        );
+        tree.show_hidden = !tree.show_hidden;
+
+        let mut current_idx = 0;
+        let index = cursor_path.and_then(|path| {
+            Self::visible_index_static(&tree.root_nodes, &path, &mut current_idx, tree.show_hidden)
+        });
+        let mut count = 0;
+        Self::count_visible_static(&tree.root_nodes, &mut count, tree.show_hidden);
+        tree.cursor_index = index.unwrap_or(tree.cursor_index.min(count.saturating_sub(1)));
+        self.logs.push(format!(
+            "Hidden entries {}",
+            if tree.show_hidden { "shown" } else { "hidden" }
+        ));
+    }
+
+    pub fn tree_switch_side(&mut self) {
+        self.tree_explorer.active_side = !self.tree_explorer.active_side;
+    }
+
+    /// What pasting the file explorer's clipboard would send, for a
+    /// prompt: how to name it, and its total size.
+    fn paste_size(&self) -> (String, u64) {
+        let clipboard = &self.tree_explorer.clipboard;
+        let bytes = clipboard.iter().map(|p| paste_guard::path_size(p)).sum();
+        let what = match clipboard.as_slice() {
+            [one] => one.file_name().map_or_else(
+                || one.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            many => format!("This paste of {} items", many.len()),
+        };
+        (what, bytes)
+    }
+
+    pub fn tree_paste(&mut self) -> Vec<String> {
+        let mut commands = Vec::new();
+        if self.tree_explorer.clipboard.is_empty() {
+            self.logs.push("Clipboard is empty".to_string());
+            return commands;
+        }
+
+        let active_side = self.tree_explorer.active_side;
+        let dest_tree = if !active_side {
+            &self.tree_explorer.local_tree
+        } else {
+            &self.tree_explorer.slave_tree
+        };
+
+        // Find the current directory at cursor or use root
+        let mut current_idx = 0;
+        let mut dest_path = None;
+        Self::get_path_at_cursor_static(
+            &dest_tree.root_nodes,
+            dest_tree.cursor_index,
+            &mut current_idx,
+            &mut dest_path,
+            dest_tree.show_hidden,
+        );
+
+        let dest_dir = if let Some(path) = dest_path {
+            if path.is_dir() {
+                path
+            } else {
+                path.parent().unwrap_or(Path::new("")).to_path_buf()
+            }
+        } else if !dest_tree.root_nodes.is_empty() {
+            dest_tree.root_nodes[0].path.clone()
+        } else {
+            self.logs
+                .push("Error: Could not determine destination directory".to_string());
+            return commands;
+        };
+
+        let dest_dir_str = dest_dir.to_string_lossy().to_string();
+        // Uploads go into the folder, not over it.
+        let upload_dest = format!(
+            "{}{}",
+            dest_dir_str.trim_end_matches(['\\', '/']),
+            remote_path::separator(&dest_dir_str)
+        );
+        let _is_upload = !self.tree_explorer.active_side; // False if pasting INTO local (download), True if pasting INTO slave (upload)
+        // Wait, active_side: false = local, true = slave.
+        // If active_side is true, we are on slave side, so we want to paste INTO slave (Upload).
+        // If active_side is false, we are on local side, so we want to paste INTO local (Download).
+        let is_paste_to_slave = active_side;
+
+        // Determine if source is also on the same side
+        // For simplicity, we assume if we are on local side, we only paste local paths if they are local
+        // and if we are on slave side, we only paste slave paths if they are slave.
+        // But the clipboard doesn't currently store which side the paths came from.
+        // Let's assume for now:
+        // - If dest is local and all paths are absolute windows paths, it's a local copy.
+        // - If dest is slave, we always use Upload for now (since we don't know if src was slave).
+
+        // Everything pasted to the slave is weighed at once.
+        if is_paste_to_slave {
+            let (what, bytes) = self.paste_size();
+            let verdict = self
+                .confirm_gate
+                .paste_guard()
+                .check(&what, bytes, self.link_rate);
+            match verdict {
+                Verdict::Proceed => {}
+                Verdict::Confirm(question) => {
+                    let mut uploads = self
+                        .tree_explorer
+                        .clipboard
+                        .iter()
+                        .map(|src| format!("Upload {}|{}", src.to_string_lossy(), upload_dest));
+                    if let Some(command) = uploads.next() {
+                        self.pending_command = Some(PendingCommand {
+                            question,
+                            command,
+                            also: uploads.collect(),
+                        });
+                    }
+                    return commands;
+                }
+                Verdict::Refuse(reason) => {
+                    self.logs.push(format!("Error: {}", reason));
+                    return commands;
+                }
+            }
+        }
+
+        let mut local_sources = Vec::new();
+
+        for src_path in &self.tree_explorer.clipboard {
+            let src_path_str = src_path.to_string_lossy().to_string();
+
+            if is_paste_to_slave {
+                // Upload: Local -> Slave
+                self.logs
+                    .push(format!("Uploading {} to {}", src_path_str, dest_dir_str));
+                commands.push(format!("Upload {}|{}", src_path_str, upload_dest));
+            } else {
+                // Dest is Local.
+                // If it's a local-to-local copy:
+                if src_path.exists() {
+                    self.logs.push(format!(
+                        "Copying local {} to {}",
+                        src_path_str, dest_dir_str
+                    ));
+                    local_sources.push(src_path.clone());
+                } else {
+                    // Download: Slave -> Local
+                    self.logs
+                        .push(format!("Downloading {} to {}", src_path_str, dest_dir_str));
+                    commands.push(format!("Download {}|{}", src_path_str, dest_dir_str));
+                }
+            }
+        }
+
+        // Copied on the worker; the destination is re-read when it ends.
+        if !local_sources.is_empty() {
+            self.tasks.update(
+                TaskId::Local(LOCAL_COPY_TASK),
+                &format!("Copying to {}", dest_dir_str),
+                std::time::Instant::now(),
+            );
+            self.run_local(LocalOp::Copy {
+                sources: local_sources,
+                dest_dir,
+            });
+        }
+
+        if self.tree_explorer.is_cut_operation {
+            // In a real app, we'd delete after successful copy. For now just clear.
+            self.tree_explorer.clipboard.clear();
+        }
+
+        commands
+    }
+
+    fn get_path_at_cursor_static(
+        nodes: &[FileNode],
+        target_idx: usize,
+        current_idx: &mut usize,
+        found_path: &mut Option<PathBuf>,
+        show_hidden: bool,
+    ) -> bool {
+        for node in nodes.iter().filter(|n| n.is_shown(show_hidden)) {
+            if *current_idx == target_idx {
+                *found_path = Some(node.path.clone());
+                return true;
+            }
+            *current_idx += 1;
+            if node.is_expanded
+                && let Some(children) = &node.children
+                && Self::get_path_at_cursor_static(
+                    children,
+                    target_idx,
+                    current_idx,
+                    found_path,
+                    show_hidden,
+                )
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn on_input_change(&mut self) {
+        self.last_input_time = std::time::Instant::now();
+        self.input_hint = None;
+        if self.reduced_motion {
+            self.completion.active = false;
+        } else {
+            self.needs_completion_update = true;
+        }
+    }
+
+    pub fn update_completion(&mut self) {
+        if !self.needs_completion_update {
+            return;
+        }
+        self.needs_completion_update = false;
+        self.trigger_completion();
+    }
+
     pub fn handle_tab(&mut self) {
         if self.completion.active && !self.completion.options.is_empty() {
             self.completion.selected_index =
@@ -714,7 +1681,7 @@ impl App {
                 self.completion.selected_index -= 1;
             }
         } else {
-            self.log_scroll = (self.log_scroll + 1).min(self.logs.len().saturating_sub(1));
+            self.log_scroll = (self.log_scroll + 1).min(self.logs.rows().saturating_sub(1));
             self.autoscroll = false;
         }
     }
@@ -734,6 +1701,7 @@ impl App {
     }
 
     pub fn handle_enter(&mut self) -> Option<String> {
+        self.input_hint = None;
         if self.completion.active && !self.completion.options.is_empty() {
             self.apply_completion();
             self.completion.active = false;
@@ -742,18 +1710,254 @@ impl App {
             let cmd = self.command_to_execute.clone();
             self.command_to_execute.clear();
             self.completion.active = false;
+            if let Some(args) = cmd.strip_prefix("theme")
+                && (args.is_empty() || args.starts_with(' '))
+            {
+                self.logs.push(format!("> {}", cmd));
+                self.set_theme(args.trim());
+                return None;
+            }
+            if let Some(spec) = commands::lookup(&cmd)
+                && let Some(note) = self.unsupported(spec.feature)
+            {
+                self.logs.push(format!("> {}", cmd));
+                self.logs.push(format!("Error: {} {}", spec.name, note));
+                return None;
+            }
+            let question = match self.confirm_gate.upload_confirmation(&cmd, self.link_rate) {
+                Ok(question) => question,
+                Err(reason) => {
+                    self.logs.push(format!("> {}", cmd));
+                    self.logs.push(format!("Error: {}", reason));
+                    return None;
+                }
+            };
+            let question = question
+                .or_else(|| self.service_guard.confirmation(&cmd))
+                .or_else(|| self.confirm_gate.confirmation(&cmd, &self.slave_label()));
+            if let Some(question) = question {
+                self.pending_command = Some(PendingCommand {
+                    question,
+                    command: cmd,
+                    also: Vec::new(),
+                });
+                return None;
+            }
+            self.remember(&cmd);
             Some(cmd)
         } else {
+            self.open_visible_page();
             None
         }
     }
 
-    pub fn handle_esc(&mut self) {
+    /// Accept the pending command, returning it, and any confirmed with
+    /// it, with the flag that tells the master it was confirmed.
+    pub fn confirm_command(&mut self) -> Vec<String> {
+        let Some(pending) = self.pending_command.take() else {
+            return Vec::new();
+        };
+        self.logs.push(format!("> {}", pending.command));
+        self.remember(&pending.command);
+        self.release_held_events();
+        std::iter::once(&pending.command)
+            .chain(&pending.also)
+            .map(|cmd| commands::confirmed(cmd))
+            .collect()
+    }
+
+    /// Drop the pending command.
+    pub fn cancel_command(&mut self) {
+        if let Some(pending) = self.pending_command.take() {
+            match pending.also.len() {
+                0 => self.logs.push(format!("Cancelled: {}", pending.command)),
+                more => self
+                    .logs
+                    .push(format!("Cancelled: {} and {} more", pending.command, more)),
+            }
+        }
+        self.release_held_events();
+    }
+
+    /// Open the command palette over the current tab.
+    pub fn open_palette(&mut self) {
+        let slave = self.slave_connected.then(|| {
+            if self.slave_info.name.is_empty() {
+                self.slave_info.ip.clone()
+            } else {
+                format!("{} ({})", self.slave_info.name, self.slave_info.ip)
+            }
+        });
+        let entries = palette::gather(
+            commands::COMMANDS,
+            slave.as_deref(),
+            &self.recent_paths,
+            &self.history,
+        )
+        .into_iter()
+        .map(|entry| {
+            let note = (entry.source == Source::Command)
+                .then(|| commands::lookup(&entry.label))
+                .flatten()
+                .and_then(|spec| self.unsupported(spec.feature));
+            match note {
+                Some(note) => entry.unavailable(note),
+                None => entry,
+            }
+        })
+        .collect();
+        self.completion.active = false;
+        self.palette = Some(Palette::new(entries));
+    }
+
+    /// Apply a key to the open palette, which keeps the focus until
+    /// `Enter` or `Esc`. Returns a command to send when the chosen entry
+    /// runs one.
+    pub fn handle_palette_key(&mut self, key: crossterm::event::KeyEvent) -> Option<String> {
+        let palette = self.palette.as_mut()?;
+        if key.code == crossterm::event::KeyCode::Esc {
+            self.palette = None;
+            return None;
+        }
+        let action = palette.handle_key(key)?;
+        self.palette = None;
+        match action {
+            PaletteAction::Run(cmd) => {
+                self.set_tab(Tab::Main);
+                self.command_to_execute = cmd;
+                self.handle_enter()
+            }
+            PaletteAction::Insert { text, hint } => {
+                self.set_tab(Tab::Main);
+                self.command_to_execute = text;
+                self.input_hint = (!hint.is_empty()).then_some(hint);
+                None
+            }
+            PaletteAction::Reveal(path) => {
+                self.tree_reveal(path);
+                None
+            }
+            PaletteAction::ShowSlave => {
+                self.set_tab(Tab::SystemSettings);
+                None
+            }
+        }
+    }
+
+    fn release_held_events(&mut self) {
+        while let Some(event) = self.held_events.pop_front() {
+            self.update(event);
+        }
+    }
+
+    /// The connected slave as the confirmation popup names it.
+    fn slave_label(&self) -> String {
+        if self.slave_info.name.is_empty() {
+            format!("the slave at {}", self.slave_info.ip)
+        } else {
+            format!("{} ({})", self.slave_info.name, self.slave_info.ip)
+        }
+    }
+
+    /// `theme <name>`: switch themes; without a name, list them. Drawn
+    /// by the console itself, so the master never sees it.
+    fn set_theme(&mut self, name: &str) {
+        if name.is_empty() {
+            let names: Vec<&str> = ThemeName::ALL.iter().map(|n| n.as_str()).collect();
+            self.logs.push(format!(
+                "Theme: {} (available: {})",
+                self.theme.name,
+                names.join(", ")
+            ));
+            return;
+        }
+        match name.parse::<ThemeName>() {
+            Ok(name) => {
+                self.theme = Theme::named(name);
+                self.logs.push(format!("Theme set to {}", name));
+            }
+            Err(e) => self.logs.push(format!("Error: {}", e)),
+        }
+    }
+
+    /// Open the newest paged response whose collapsed line is on screen.
+    pub fn open_visible_page(&mut self) {
+        self.follow_log();
+        let Some(id) = self
+            .visible_log()
+            .iter()
+            .rev()
+            .find_map(|row| pager::page_reference(row.line))
+        else {
+            return;
+        };
+        match self.pages.iter().find(|page| page.request_id == id) {
+            Some(page) => self.pager = Some(Pager::new(page.clone())),
+            None => self
+                .logs
+                .push(format!("Response to ReqID {} is no longer kept", id)),
+        }
+    }
+
+    /// Feed a key to the open pager, closing it on `q` / Esc.
+    pub fn handle_pager_key(&mut self, key: crossterm::event::KeyEvent) {
+        if let Some(pager) = &mut self.pager
+            && !pager.handle_key(key)
+        {
+            self.pager = None;
+        }
+    }
+
+    /// Log a slave response, paging it when it is too long to scroll.
+    fn log_response(&mut self, id: u64, text: &str) {
+        let line_count = text.lines().count();
+        if line_count <= self.page_threshold {
+            // One entry, so nothing logged meanwhile lands inside it.
+            self.logs
+                .push(format!("- Slave: {}", text.trim_end_matches('\n')));
+            return;
+        }
+        let page = Page::new(id, text);
+        self.logs.push(page.collapsed_line());
+        if self.pages.len() == pager::MAX_PAGES {
+            self.pages.pop_front();
+        }
+        self.pages.push_back(page);
+    }
+
+    /// The log rows on screen.
+    fn visible_log(&self) -> Vec<LogRow<'_>> {
+        self.logs.visible(self.log_scroll, self.log_view_height)
+    }
+
+    /// Account for rows logged since the last call: at the bottom the
+    /// view follows them, scrolled up it stays on the rows it shows.
+    fn follow_log(&mut self) {
+        let added = self.logs.rows_added().wrapping_sub(self.log_rows_seen);
+        self.log_rows_seen = self.logs.rows_added();
+        if self.autoscroll {
+            self.log_scroll = 0;
+        } else {
+            self.log_scroll = self.log_scroll.saturating_add(added);
+        }
+    }
+
+    /// Show or hide the time in front of each log entry.
+    pub fn toggle_log_times(&mut self) {
+        self.log_times = !self.log_times;
+    }
+
+    /// Close the suggestions, else cancel the newest running search,
+    /// else quit. Returns the command to send, if any.
+    pub fn handle_esc(&mut self) -> Option<String> {
         if self.completion.active {
             self.completion.active = false;
+        } else if let Some(id) = self.running_searches.last() {
+            return Some(format!("cancel {}", id));
         } else {
             self.exit = true;
         }
+        None
     }
 
     fn trigger_completion(&mut self) {
@@ -780,10 +1984,20 @@ impl App {
             }
         }
 
+        // Slave paths complete from listings fetched in the background.
+        if self.slave_connected
+            && let Some((start, word)) = remote_path::remote_word(input)
+        {
+            let word = word.to_string();
+            self.complete_remote(start, &word);
+            return;
+        }
+
         // Path autocomplete
         let parts: Vec<&str> = input.split_whitespace().collect();
         if parts.len() > 1 || (parts.len() == 1 && input.ends_with(' ')) {
             self.completion.trigger_type = Some(CompletionType::Path);
+            self.completion.anchor = None;
             let last_word = if input.ends_with(' ') {
                 ""
             } else {
@@ -842,12 +2056,72 @@ impl App {
         }
     }
 
+    /// Complete `word`, the slave path being typed from byte `start` of
+    /// the input. Without a recent listing of its directory, ask the
+    /// master for one and complete once it is in.
+    fn complete_remote(&mut self, start: usize, word: &str) {
+        self.completion.trigger_type = Some(CompletionType::Path);
+        let split = word.rfind(['/', '\\']).map_or(0, |i| i + 1);
+        let (typed_dir, prefix) = word.split_at(split);
+        let cwd = self.remote_cwd.as_deref();
+        let dir = match (typed_dir, cwd) {
+            ("", Some(cwd)) => cwd.to_string(),
+            ("", None) => ".".to_string(),
+            _ => match remote_path::resolve(cwd, typed_dir) {
+                dir if remote_path::is_absolute(&dir) => remote_path::normalize(&dir),
+                dir => dir,
+            },
+        };
+        let sep = if typed_dir.is_empty() {
+            remote_path::separator(cwd.unwrap_or_default())
+        } else {
+            remote_path::separator(typed_dir)
+        };
+
+        let prefix = prefix.to_lowercase();
+        let mut options: Vec<CompletionOption> =
+            match self.remote_listings.lookup(&dir, std::time::Instant::now()) {
+                Lookup::Ready(entries) => entries
+                    .iter()
+                    .filter(|e| e.name.to_lowercase().starts_with(&prefix))
+                    .map(|e| CompletionOption {
+                        display: e.name.clone(),
+                        value: if e.is_dir {
+                            format!("{}{}", e.name, sep)
+                        } else {
+                            e.name.clone()
+                        },
+                        is_dir: e.is_dir,
+                    })
+                    .collect(),
+                Lookup::Fetch => {
+                    self.remote_request = Some(format!("complete {}", dir));
+                    Vec::new()
+                }
+                Lookup::Waiting => Vec::new(),
+            };
+        options.sort_by(|a, b| a.display.cmp(&b.display));
+        self.completion.active = !options.is_empty();
+        self.completion.options = options;
+        self.completion.selected_index = 0;
+        self.completion.anchor = Some(start + split);
+    }
+
+    /// The listing path completion is waiting for, to send to the
+    /// master as a `complete <dir>` command.
+    pub fn take_remote_request(&mut self) -> Option<String> {
+        self.remote_request.take()
+    }
+
     fn apply_completion(&mut self) {
         if let Some(choice) = self.completion.options.get(self.completion.selected_index) {
             let input = &self.command_to_execute;
 
             if self.completion.trigger_type == Some(CompletionType::Command) {
                 self.command_to_execute = choice.value.clone();
+            } else if let Some(anchor) = self.completion.anchor {
+                let kept = input.get(..anchor).unwrap_or(input);
+                self.command_to_execute = format!("{}{}", kept, choice.value);
             } else {
                 let parts: Vec<&str> = input.split_whitespace().collect();
                 let mut new_cmd = if input.ends_with(' ') {
@@ -885,34 +2159,105 @@ impl App {
     }
 
     pub fn update(&mut self, event: MasterEvent) {
+        if self.pending_command.is_some() {
+            self.held_events.push_back(event);
+            return;
+        }
+        if let Some(notifier) = &mut self.notifier {
+            notifier.observe(&event);
+        }
         match event {
-            MasterEvent::Log(msg) => {
-                // Split multi-line messages into individual lines
-                for line in msg.lines() {
-                    self.logs.push(line.to_string());
-                }
-                if self.autoscroll {
-                    self.log_scroll = 0; // Reset scroll to show latest (bottom)
-                }
+            MasterEvent::Log(entry) => {
+                self.logs.push(entry);
+                self.follow_log();
+            }
+            MasterEvent::Transfer(update) => {
+                self.transfer_panel
+                    .apply_at(update, std::time::Instant::now());
+            }
+            MasterEvent::Response { id, text } => {
+                self.log_response(id, &text);
+                self.follow_log();
             }
             MasterEvent::SlaveConnected(ip) => {
+                self.slave_connected = true;
+                self.slave_features = None;
+                self.remote_listings.clear();
                 self.slave_info.ip = ip;
+                self.slave_info.name.clear();
+                self.slave_info.ping = "N/A".to_string();
+                self.slave_info.rtt = "N/A".to_string();
                 self.logs
                     .push(format!("Slave connected: {}", self.slave_info.ip));
             }
+            MasterEvent::SlaveDisconnected(name) => {
+                self.slave_connected = false;
+                self.slave_features = None;
+                self.remote_listings.clear();
+                self.slave_info.ip = "Not Connected".to_string();
+                self.slave_info.name.clear();
+                self.slave_info.ping = "N/A".to_string();
+                self.slave_info.rtt = "N/A".to_string();
+                self.link_rate = None;
+                self.logs.push(format!("{} disconnected", name));
+            }
+            MasterEvent::SlaveNamed(name) => {
+                self.slave_info.name = name;
+            }
             MasterEvent::SlaveInfo { ram_usage } => {
                 self.slave_info.ram_usage = ram_usage;
             }
+            MasterEvent::PingStats(line) => {
+                self.slave_info.ping = line;
+            }
+            MasterEvent::LinkRtt(line) => {
+                self.slave_info.rtt = line;
+            }
+            MasterEvent::LinkRate(rate) => {
+                self.link_rate = rate;
+            }
             MasterEvent::TaskUpdate { id, status } => {
-                let id_str = format!("{}", id);
-                if let Some(task) = self
-                    .tasks
-                    .iter_mut()
-                    .find(|t| t.contains(&format!("< {} >", id_str)))
-                {
-                    *task = format!("< {} > {}", id_str, status);
-                } else {
-                    self.tasks.push(format!("< {} > {}", id_str, status));
+                self.running_searches.retain(|&s| s != id);
+                if status.starts_with(SEARCH_STATUS_PREFIX) {
+                    self.running_searches.push(id);
+                }
+                self.tasks
+                    .update(TaskId::Request(id), &status, std::time::Instant::now());
+            }
+            MasterEvent::TaskStarted {
+                id,
+                command,
+                summary,
+            } => {
+                self.tasks.start(
+                    TaskId::Request(id),
+                    &command,
+                    &summary,
+                    std::time::Instant::now(),
+                );
+            }
+            MasterEvent::Role { name, allowed } => {
+                self.role = name;
+                self.allowed = allowed;
+            }
+            MasterEvent::SlaveFeatures { version, features } => {
+                self.slave_features = features.map(|features| (version, features));
+            }
+            MasterEvent::LocalListing(listing) => self.apply_local_listing(listing),
+            MasterEvent::LocalCopy(progress) => self.on_local_copy(progress),
+            MasterEvent::RemoteCwd(dir) => {
+                if let Some(dir) = &dir {
+                    palette::remember(&mut self.recent_paths, dir, palette::MAX_RECENT_PATHS);
+                }
+                self.remote_cwd = dir;
+            }
+            MasterEvent::RemoteListing { dir, data } => {
+                let entries = remote_path::parse_listing(&data);
+                self.remote_listings
+                    .store(&dir, entries, std::time::Instant::now());
+                // Finish the completion that asked for it.
+                if !self.reduced_motion || self.completion.active {
+                    self.needs_completion_update = true;
                 }
             }
             MasterEvent::TreeData {
@@ -932,11 +2277,14 @@ impl App {
                                 is_expanded: false,
                                 children: None,
                                 is_selected: false,
+                                attributes: FileAttributes::empty(),
+                                size: None,
                             })
                             .collect();
                         self.tree_explorer.slave_tree.root_nodes = drives;
                     } else if path == "dir_listing" {
-                        // Parse data: "PATH|/some/path;name1|0|123;name2|1|0"
+                        // Parse data: "PATH|/some/path;name1|0|123|2;name2|1|0|0"; the
+                        // attributes field is missing from older slaves.
                         let entries: Vec<&str> = data.split(';').collect();
                         if entries.is_empty() {
                             return;
@@ -945,9 +2293,14 @@ impl App {
                         let mut target_path = PathBuf::new();
                         let mut start_index = 0;
 
-                        if entries[0].starts_with("PATH|") {
-                            target_path = PathBuf::from(&entries[0][5..]);
+                        if let Some(dir) = entries[0].strip_prefix("PATH|") {
+                            target_path = PathBuf::from(dir);
                             start_index = 1;
+                            palette::remember(
+                                &mut self.recent_paths,
+                                dir,
+                                palette::MAX_RECENT_PATHS,
+                            );
                         }
 
                         let children: Vec<FileNode> = entries[start_index..]
@@ -958,6 +2311,12 @@ impl App {
                                 if parts.len() >= 2 {
                                     let name = parts[0].to_string();
                                     let is_dir = parts[1] == "1";
+                                    let attributes =
+                                        FileAttributes::from_field(parts.get(3).copied());
+                                    let size = parts
+                                        .get(2)
+                                        .filter(|_| !is_dir)
+                                        .and_then(|s| s.parse().ok());
                                     let mut full_path = target_path.clone();
                                     full_path.push(&name);
                                     Some(FileNode {
@@ -967,6 +2326,8 @@ impl App {
                                         is_expanded: false,
                                         children: None,
                                         is_selected: false,
+                                        attributes,
+                                        size,
                                     })
                                 } else {
                                     None
@@ -997,6 +2358,7 @@ impl App {
                             );
                         }
                     }
+                    self.continue_reveal();
                 }
             }
             MasterEvent::RefreshTree { is_slave } => {
@@ -1049,7 +2411,41 @@ impl App {
 
     pub fn draw(&mut self, frame: &mut Frame) {
         let area = frame.area();
-        let buf = frame.buffer_mut();
+        self.render_all(area, frame.buffer_mut());
+    }
+
+    /// Draw the whole console into `buf`.
+    fn render_all(&mut self, area: Rect, buf: &mut Buffer) {
+        let theme = self.theme;
+        let mode = LayoutMode::for_area(area);
+        if mode == LayoutMode::TooSmall {
+            let message = format!("terminal too small (need {MIN_WIDTH}×{MIN_HEIGHT})");
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Fill(1),
+                    Constraint::Length(1),
+                    Constraint::Fill(1),
+                ])
+                .split(area);
+            Paragraph::new(Span::styled(message, theme.warning))
+                .centered()
+                .render(rows[1], buf);
+            return;
+        }
+
+        // 0. An alert banner pushes everything down a row while it lasts
+        let area = match self.notifier.as_ref().and_then(Notifier::banner) {
+            Some(banner) => {
+                let rows = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(1), Constraint::Min(0)])
+                    .split(area);
+                notify::render_banner(banner, &theme, rows[0], buf);
+                rows[1]
+            }
+            None => area,
+        };
 
         // 1. Render Tab Bar (Top)
         let layout = Layout::default()
@@ -1060,11 +2456,14 @@ impl App {
         let tab_area = layout[0];
         let content_area = layout[1];
 
-        let tab_titles = [
-            " [F1] Main Console ",
-            " [F2] Tree Explorer ",
-            " [F3] System & Settings ",
-        ];
+        let tab_titles = match mode {
+            LayoutMode::Wide => [
+                " [F1] Main Console ",
+                " [F2] Tree Explorer ",
+                " [F3] System & Settings ",
+            ],
+            _ => ["[F1]", "[F2]", "[F3]"],
+        };
         let tab_spans: Vec<Span> = tab_titles
             .iter()
             .enumerate()
@@ -1073,38 +2472,47 @@ impl App {
                     || (i == 1 && self.active_tab == Tab::TreeExplorer)
                     || (i == 2 && self.active_tab == Tab::SystemSettings)
                 {
-                    Style::default()
-                        .bg(Color::Cyan)
-                        .fg(Color::Black)
-                        .add_modifier(Modifier::BOLD)
+                    theme.selected
                 } else {
-                    Style::default().fg(Color::Gray)
+                    theme.text
                 };
                 Span::styled(*title, style)
             })
             .collect();
 
         Paragraph::new(Line::from(tab_spans))
-            .block(Block::bordered().border_style(Style::default().fg(Color::DarkGray)))
+            .block(self.block().border_style(theme.border))
             .render(tab_area, buf);
 
         // 2. Render Active Tab Content
         match self.active_tab {
-            Tab::Main => self.render_main_tab(content_area, buf),
+            Tab::Main => self.render_main_tab(content_area, mode, buf),
             Tab::TreeExplorer => self.render_tree_tab(content_area, buf),
             Tab::SystemSettings => self.render_system_tab(content_area, buf),
         }
+
+        // 3. The command palette floats over the tab
+        if let Some(palette) = &self.palette {
+            self.render_palette(palette, content_area, buf);
+        }
+
+        // 4. A paged response covers everything
+        if let Some(pager) = &mut self.pager {
+            pager.render(area, buf, &theme);
+        }
+    }
+
+    /// A bordered block in the theme's border set.
+    fn block(&self) -> Block<'static> {
+        Block::bordered().border_set(self.theme.border_set)
     }
 
     fn render_system_tab(&self, area: Rect, buf: &mut Buffer) {
-        let block = Block::bordered()
-            .title(Span::styled(
-                " System Actions & Settings ",
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            ))
-            .border_style(Style::default().fg(Color::DarkGray));
+        let theme = &self.theme;
+        let block = self
+            .block()
+            .title(Span::styled(" System Actions & Settings ", theme.title))
+            .border_style(theme.border);
 
         let inner = block.inner(area);
         block.render(area, buf);
@@ -1119,87 +2527,83 @@ impl App {
             .split(inner);
 
         // --- System Actions ---
-        let actions_block = Block::bordered()
-            .title(Span::styled(
-                " Remote System Actions ",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            ))
-            .border_style(Style::default().fg(Color::DarkGray));
+        let actions_block = self
+            .block()
+            .title(Span::styled(" Remote System Actions ", theme.heading))
+            .border_style(theme.border);
         let actions_inner = actions_block.inner(layout[0]);
         actions_block.render(layout[0], buf);
 
+        let system_action = |style| self.binding_style(&[Command::SystemAction], style);
         let actions = vec![
             Line::from(vec![
-                Span::styled("[1] Shutdown", Style::default().fg(Color::Red)),
+                Span::styled("[1] Shutdown", system_action(theme.danger)),
                 Span::raw(" - Power off the remote slave"),
             ]),
             Line::from(vec![
-                Span::styled("[2] Reboot", Style::default().fg(Color::Yellow)),
+                Span::styled("[2] Reboot", system_action(theme.warning)),
                 Span::raw(" - Restart the remote slave"),
             ]),
             Line::from(vec![
-                Span::styled("[3] Sleep", Style::default().fg(Color::Blue)),
+                Span::styled("[3] Sleep", system_action(theme.info)),
                 Span::raw(" - Put remote slave to sleep"),
             ]),
             Line::from(vec![
-                Span::styled("[4] Wake Up", Style::default().fg(Color::Green)),
+                Span::styled("[4] Wake Up", theme.success),
                 Span::raw(" - Send Wake-on-LAN (if supported)"),
             ]),
         ];
         Paragraph::new(actions).render(actions_inner, buf);
 
         // --- Settings ---
-        let settings_block = Block::bordered()
-            .title(Span::styled(
-                " Deployment & Settings ",
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            ))
-            .border_style(Style::default().fg(Color::DarkGray));
+        let settings_block = self
+            .block()
+            .title(Span::styled(" Deployment & Settings ", theme.title))
+            .border_style(theme.border);
         let settings_inner = settings_block.inner(layout[1]);
         settings_block.render(layout[1], buf);
 
         let settings = vec![
             Line::from(vec![
-                Span::styled(
-                    "[S] Install as System Service",
-                    Style::default().fg(Color::Gray),
-                ),
+                Span::styled("[S] Install as System Service", theme.text),
                 Span::raw(" (Not implemented)"),
             ]),
             Line::from(vec![
-                Span::styled("[A] Auto-start on boot", Style::default().fg(Color::Gray)),
+                Span::styled("[A] Auto-start on boot", theme.text),
                 Span::raw(" (Not implemented)"),
             ]),
             Line::from(vec![
-                Span::styled("[L] Log Level: ", Style::default().fg(Color::Gray)),
-                Span::styled("INFO", Style::default().fg(Color::Green)),
+                Span::styled("[L] Log Level: ", theme.text),
+                Span::styled("INFO", theme.success),
+            ]),
+            Line::from(vec![
+                Span::styled("Theme: ", theme.text),
+                Span::styled(theme.name.as_str(), theme.value),
+                Span::raw(" (theme <name> to change)"),
+            ]),
+            Line::from(vec![
+                Span::styled("Role: ", theme.text),
+                Span::styled(self.role.as_deref().unwrap_or("none"), theme.value),
+                Span::raw(" (role <name> to switch)"),
             ]),
         ];
         Paragraph::new(settings).render(settings_inner, buf);
     }
 
-    fn render_main_tab(&self, area: Rect, buf: &mut Buffer) {
+    fn render_main_tab(&mut self, area: Rect, mode: LayoutMode, buf: &mut Buffer) {
+        let theme = self.theme;
         // Outer block
         let outer_block = Block::bordered()
             .title(
                 Line::from(vec![
                     Span::raw(" Tix-Master-V0.1---"),
-                    Span::styled(
-                        "YuTech Labs",
-                        Style::default()
-                            .fg(Color::Cyan)
-                            .add_modifier(Modifier::BOLD),
-                    ),
+                    Span::styled("YuTech Labs", theme.title),
                     Span::raw(" "),
                 ])
                 .centered(),
             )
-            .border_set(border::THICK)
-            .border_style(Style::default().fg(Color::DarkGray));
+            .border_set(theme.frame_set)
+            .border_style(theme.border);
 
         let inner_area = outer_block.inner(area);
         outer_block.render(area, buf);
@@ -1213,236 +2617,185 @@ impl App {
         let top_area = main_layout[0];
         let input_area = main_layout[1];
 
-        // Split Top area into Logs (Left) and Sidebar (Right)
-        let top_layout = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
-            .split(top_area);
+        // Split Top area into Logs (Left) and Sidebar (Right), or on a
+        // narrow terminal Logs (Top) and Sidebar (Bottom)
+        let top_layout = match mode {
+            LayoutMode::Wide => Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+                .split(top_area),
+            _ => Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(top_area.height / 2)])
+                .split(top_area),
+        };
 
         let logs_area = top_layout[0];
         let sidebar_area = top_layout[1];
 
         // --- Render Logs ---
-        let logs_block = Block::bordered()
+        let logs_block = self
+            .block()
             .title(Line::from(vec![
-                Span::styled(
-                    " Master Logs ",
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
-                ),
+                Span::styled(" Master Logs ", theme.heading),
                 if self.autoscroll {
-                    Span::styled(
-                        "[Autoscroll]",
-                        Style::default()
-                            .fg(Color::Green)
-                            .add_modifier(Modifier::DIM),
-                    )
+                    Span::styled("[Autoscroll]", theme.faint(theme.success))
                 } else {
-                    Span::styled(
-                        "[Manual]",
-                        Style::default()
-                            .fg(Color::Yellow)
-                            .add_modifier(Modifier::DIM),
-                    )
+                    Span::styled("[Manual]", theme.faint(theme.warning))
                 },
             ]))
-            .border_style(Style::default().fg(Color::DarkGray))
+            .border_style(theme.border)
             .padding(ratatui::widgets::Padding::horizontal(1));
 
         let logs_inner = logs_block.inner(logs_area);
         logs_block.render(logs_area, buf);
 
-        let visible_height = logs_inner.height as usize;
-        let total_logs = self.logs.len();
-
-        // Calculate which logs to show based on scroll
-        let log_items: Vec<ListItem> = if total_logs <= visible_height {
-            // If we have fewer logs than space, just show them all
-            self.logs.iter()
-        } else {
-            // Calculate start index based on scroll from the bottom
-            // scroll 0 = last `visible_height` logs
-            let start = total_logs
-                .saturating_sub(visible_height)
-                .saturating_sub(self.log_scroll);
-            let end = (start + visible_height).min(total_logs);
-            self.logs[start..end].iter()
-        }
-        .map(|log| {
-            if log.starts_with(">") {
-                ListItem::new(Line::from(vec![
-                    Span::styled("> ", Style::default().fg(Color::Green)),
-                    Span::raw(&log[2..]),
-                ]))
-            } else if log.starts_with("-") {
-                ListItem::new(Line::from(vec![
-                    Span::styled("- ", Style::default().fg(Color::Blue)),
-                    Span::styled(
-                        &log[2..],
-                        Style::default()
-                            .fg(Color::Gray)
-                            .add_modifier(Modifier::ITALIC),
-                    ),
-                ]))
-            } else if log.starts_with("[SEND]") {
-                ListItem::new(Line::from(vec![
-                    Span::styled("→ ", Style::default().fg(Color::Cyan)),
-                    Span::styled(log, Style::default().fg(Color::DarkGray)),
-                ]))
-            } else if log.starts_with("[RECV]") || log.starts_with("[DONE]") {
-                ListItem::new(Line::from(vec![
-                    Span::styled("← ", Style::default().fg(Color::Green)),
-                    Span::styled(log, Style::default().fg(Color::DarkGray)),
-                ]))
-            } else if log.contains("stdout:") || log.contains("stderr:") {
-                // Format shell output lines specifically if needed,
-                // but for now let's just clean them up
-                ListItem::new(Line::from(log.as_str()))
-            } else {
-                ListItem::new(Line::from(log.as_str()))
-            }
-        })
-        .collect();
+        // Only the lines on screen are styled, however long the log is.
+        self.log_view_height = logs_inner.height as usize;
+        self.follow_log();
+        let log_items: Vec<ListItem> = self
+            .visible_log()
+            .into_iter()
+            .map(|row| {
+                let mut spans = vec![Span::styled(row.prefix(self.log_times), theme.muted)];
+                spans.extend(log_line_spans(row.line, row.first, &theme));
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
 
         let logs_list = List::new(log_items);
         logs_list.render(logs_inner, buf);
 
         // --- Render Sidebar ---
-        let sidebar_layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(10), // Info box
-                Constraint::Min(0),     // Tasks box
-            ])
-            .split(sidebar_area);
+        let icons = theme.icons;
+        let sidebar_layout = match mode {
+            LayoutMode::Wide => Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(11), // Info box
+                    Constraint::Min(0),     // Tasks box
+                ])
+                .split(sidebar_area),
+            // Under the logs there is width to spare but few rows.
+            _ => Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(sidebar_area),
+        };
 
         let info_area = sidebar_layout[0];
         let tasks_area = sidebar_layout[1];
 
         // Info Box (Slave + Master)
-        let info_block = Block::bordered()
-            .border_style(Style::default().fg(Color::DarkGray))
-            .padding(ratatui::widgets::Padding::uniform(1));
+        let padding = match mode {
+            LayoutMode::Wide => ratatui::widgets::Padding::uniform(1),
+            _ => ratatui::widgets::Padding::horizontal(1),
+        };
+        let info_block = self.block().border_style(theme.border).padding(padding);
         let info_inner = info_block.inner(info_area);
         info_block.render(info_area, buf);
 
         let mut info_text = vec![
-            Line::from(vec![Span::styled(
-                "Slave PC :",
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            )]),
             Line::from(vec![
-                Span::styled("IP    : ", Style::default().fg(Color::Gray)),
-                Span::styled(&self.slave_info.ip, Style::default().fg(Color::Yellow)),
+                Span::styled("Slave PC : ", theme.title),
+                Span::styled(&self.slave_info.name, theme.value),
             ]),
             Line::from(vec![
-                Span::styled("Ram   : ", Style::default().fg(Color::Gray)),
-                Span::styled(
-                    &self.slave_info.ram_usage,
-                    Style::default().fg(Color::Magenta),
-                ),
+                Span::styled("IP    : ", theme.text),
+                Span::styled(&self.slave_info.ip, theme.address),
+            ]),
+            Line::from(vec![
+                Span::styled("Ram   : ", theme.text),
+                Span::styled(&self.slave_info.ram_usage, theme.accent),
+            ]),
+            Line::from(vec![
+                Span::styled("Ping  : ", theme.text),
+                Span::styled(&self.slave_info.ping, theme.success),
+            ]),
+            Line::from(vec![
+                Span::styled("RTT   : ", theme.text),
+                Span::styled(&self.slave_info.rtt, theme.success),
             ]),
         ];
         for other in &self.slave_info.other {
-            info_text.push(Line::from(vec![Span::styled(
-                other,
-                Style::default().fg(Color::DarkGray),
-            )]));
+            info_text.push(Line::from(vec![Span::styled(other, theme.muted)]));
         }
         info_text.push(Line::from(""));
         info_text.push(Line::from(vec![Span::styled(
             "Master PC (this):",
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
+            theme.title,
         )]));
         info_text.push(Line::from(vec![
-            Span::styled("IP    : ", Style::default().fg(Color::Gray)),
-            Span::styled(&self.master_info.ip, Style::default().fg(Color::Yellow)),
+            Span::styled("IP    : ", theme.text),
+            Span::styled(&self.master_info.ip, theme.address),
         ]));
 
         Paragraph::new(info_text).render(info_inner, buf);
 
         // Tasks Box
-        let tasks_block = Block::bordered()
-            .title(Span::styled(
-                " Tasks : ",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            ))
-            .border_style(Style::default().fg(Color::DarkGray))
+        let tasks_block = self
+            .block()
+            .title(Span::styled(" Tasks : ", theme.heading))
+            .border_style(theme.border)
             .padding(ratatui::widgets::Padding::horizontal(1));
         let tasks_inner = tasks_block.inner(tasks_area);
         tasks_block.render(tasks_area, buf);
 
-        let task_items: Vec<ListItem> = self
-            .tasks
-            .iter()
-            .map(|task| {
-                let color = if task.contains("Running") || task.contains("Solved") {
-                    Color::Green
-                } else if task.contains("Waiting") {
-                    Color::Yellow
-                } else if task.contains("Failed") {
-                    Color::Red
-                } else {
-                    Color::Gray
-                };
-                ListItem::new(Line::from(vec![Span::styled(
-                    task,
-                    Style::default().fg(color),
-                )]))
-            })
-            .collect();
-        List::new(task_items).render(tasks_inner, buf);
+        TaskPanel {
+            tasks: &self.tasks,
+            theme: &theme,
+            now: std::time::Instant::now(),
+            animate: !self.reduced_motion,
+        }
+        .render(tasks_inner, buf);
 
         // --- Render Input ---
         let input_block = Block::default()
             .borders(Borders::TOP)
-            .border_style(Style::default().fg(Color::DarkGray));
+            .border_set(theme.border_set)
+            .border_style(theme.border);
         let input_inner = input_block.inner(input_area);
         input_block.render(input_area, buf);
 
+        let prompt = match &self.remote_cwd {
+            Some(dir) => format!(" {} > ", dir),
+            None => " > ".to_string(),
+        };
+        let prompt_width = prompt.chars().count() as u16;
         let input_text = Line::from(vec![
+            Span::styled(prompt, theme.bold(theme.success)),
+            Span::raw(roles::redact(&self.command_to_execute)),
             Span::styled(
-                " > ",
-                Style::default()
-                    .fg(Color::Green)
-                    .add_modifier(Modifier::BOLD),
+                self.input_hint.as_deref().unwrap_or_default(),
+                theme.matched,
             ),
-            Span::raw(&self.command_to_execute),
         ]);
         Paragraph::new(input_text).render(input_inner, buf);
 
         // --- Render Autocomplete Dropdown ---
         if self.completion.active && !self.completion.options.is_empty() {
             let num_options = self.completion.options.len().min(10);
-            let dropdown_height = (num_options + 2) as u16;
-            let dropdown_width = 40.min(inner_area.width - 4);
+            let dropdown_height =
+                ((num_options + 2) as u16).min(input_area.y.saturating_sub(inner_area.y));
+            let dropdown_width = 40.min(inner_area.width.saturating_sub(4));
 
             // Position above the input bar
             let dropdown_area = Rect {
-                x: input_inner.x + 3, // Offset by " > "
+                x: (input_inner.x + prompt_width)
+                    .min(input_inner.right().saturating_sub(dropdown_width)),
                 y: input_area.y.saturating_sub(dropdown_height),
                 width: dropdown_width,
                 height: dropdown_height,
-            };
+            }
+            .intersection(inner_area);
 
             // Clear the area under the dropdown
             Clear.render(dropdown_area, buf);
 
-            let dropdown_block = Block::bordered()
-                .border_style(Style::default().fg(Color::Cyan))
-                .title(Span::styled(
-                    " Suggestions ",
-                    Style::default()
-                        .fg(Color::Cyan)
-                        .add_modifier(Modifier::BOLD),
-                ));
+            let dropdown_block = self
+                .block()
+                .border_style(theme.focus)
+                .title(Span::styled(" Suggestions ", theme.title));
 
             let list_items: Vec<ListItem> = self
                 .completion
@@ -1450,19 +2803,22 @@ impl App {
                 .iter()
                 .enumerate()
                 .map(|(i, opt)| {
+                    let unavailable = self.completion.trigger_type
+                        == Some(CompletionType::Command)
+                        && commands::lookup(&opt.value)
+                            .is_some_and(|spec| self.unsupported(spec.feature).is_some());
                     let style = if i == self.completion.selected_index {
-                        Style::default()
-                            .bg(Color::Cyan)
-                            .fg(Color::Black)
-                            .add_modifier(Modifier::BOLD)
+                        theme.selected
+                    } else if unavailable {
+                        theme.muted
                     } else {
                         Style::default()
                     };
 
                     let icon = if opt.is_dir {
-                        Span::styled("📁 ", Style::default().fg(Color::Yellow))
+                        Span::styled(icons.dir, theme.warning)
                     } else {
-                        Span::styled("📄 ", Style::default().fg(Color::Blue))
+                        Span::styled(icons.file, theme.info)
                     };
 
                     ListItem::new(Line::from(vec![icon, Span::styled(&opt.display, style)]))
@@ -1475,6 +2831,14 @@ impl App {
 
             list.render(dropdown_area, buf);
         }
+
+        if let Some(pending) = &self.pending_command {
+            let lines = vec![
+                Line::from(pending.question.as_str()),
+                Line::from(Span::styled("[Y] Yes   [any other key] Cancel", theme.text)),
+            ];
+            self.render_popup(" Confirm ", theme.danger, lines, area, buf);
+        }
     }
 
     fn render_tree_tab(&mut self, area: Rect, buf: &mut Buffer) {
@@ -1505,16 +2869,174 @@ impl App {
             !active_side,
         );
 
-        // Slave tree
-        self.render_tree_panel(
-            " Slave Tree (Remote) ",
-            true,
-            tree_layout[1],
-            buf,
-            active_side,
-        );
+        // Slave tree
+        let slave_title = if self.slave_info.name.is_empty() {
+            " Slave Tree (Remote) ".to_string()
+        } else {
+            format!(" Slave Tree ({}) ", self.slave_info.name)
+        };
+        self.render_tree_panel(&slave_title, true, tree_layout[1], buf, active_side);
+
+        if self.transfer_panel.is_visible() {
+            self.render_transfers(action_area, buf);
+        } else {
+            self.render_action_bar(action_area, buf);
+        }
+
+        if let Some(pending) = &self.tree_explorer.pending_delete {
+            self.render_delete_confirmation(pending, area, buf);
+        }
+        if let Some(input) = &self.tree_explorer.pending_name {
+            self.render_name_input(input, area, buf);
+        }
+    }
+
+    fn render_delete_confirmation(&self, pending: &PendingDelete, area: Rect, buf: &mut Buffer) {
+        let theme = &self.theme;
+        let style = match pending.mode {
+            DeleteMode::Recycle => theme.warning,
+            DeleteMode::Permanent => theme.danger,
+        };
+        let mut lines = vec![Line::from(pending.prompt())];
+        let keys = match &pending.error {
+            Some(error) => {
+                lines.push(Line::from(Span::styled(error.clone(), theme.danger)));
+                "[Y] Retry   [any other key] Cancel"
+            }
+            None => "[Y] Yes   [any other key] Cancel",
+        };
+        lines.push(Line::from(Span::styled(keys, theme.text)));
+        self.render_popup(" Confirm Delete ", style, lines, area, buf);
+    }
+
+    fn render_name_input(&self, input: &NameInput, area: Rect, buf: &mut Buffer) {
+        let theme = &self.theme;
+        let mut lines = vec![
+            Line::from(input.prompt()),
+            Line::from(vec![
+                Span::styled("> ", theme.focus),
+                Span::styled(format!("{}_", input.name), theme.text),
+            ]),
+        ];
+        if let Some(error) = &input.error {
+            lines.push(Line::from(Span::styled(error.clone(), theme.danger)));
+        }
+        lines.push(Line::from(Span::styled(
+            "[Enter] OK   [Esc] Cancel",
+            theme.text,
+        )));
+        self.render_popup(input.title(), theme.focus, lines, area, buf);
+    }
+
+    /// Centred bordered popup for `lines`, with room for long ones to
+    /// wrap.
+    fn render_popup(
+        &self,
+        title: &str,
+        style: Style,
+        lines: Vec<Line<'_>>,
+        area: Rect,
+        buf: &mut Buffer,
+    ) {
+        let width = 70.min(area.width.saturating_sub(4));
+        let height = (lines.len() as u16 + 4).min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width.saturating_sub(width)) / 2,
+            y: area.y + area.height.saturating_sub(height) / 2,
+            width,
+            height,
+        };
+        Clear.render(popup, buf);
+
+        let block = self
+            .block()
+            .title(Span::styled(title.to_string(), self.theme.bold(style)))
+            .border_style(style);
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: true })
+            .block(block)
+            .render(popup, buf);
+    }
+
+    fn render_palette(&self, palette: &Palette, area: Rect, buf: &mut Buffer) {
+        let theme = &self.theme;
+        let width = 90.min(area.width.saturating_sub(4));
+        let height = 20.min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width.saturating_sub(width)) / 2,
+            y: area.y + area.height.saturating_sub(height) / 2,
+            width,
+            height,
+        };
+        Clear.render(popup, buf);
+
+        let block = self
+            .block()
+            .title(Span::styled(" Command Palette ", theme.bold(theme.focus)))
+            .border_style(theme.focus);
+        let inner = block.inner(popup);
+        block.render(popup, buf);
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ])
+            .split(inner);
+
+        Paragraph::new(Line::from(vec![
+            Span::styled("> ", theme.bold(theme.success)),
+            Span::raw(palette.query()),
+            Span::styled("_", theme.muted),
+        ]))
+        .render(rows[0], buf);
+
+        // Scroll just far enough to keep the selection in view.
+        let visible = rows[1].height as usize;
+        let selected = palette.selected_index();
+        let items: Vec<ListItem> = palette
+            .matches()
+            .enumerate()
+            .skip((selected + 1).saturating_sub(visible))
+            .take(visible)
+            .map(|(i, entry)| {
+                let mut spans = vec![
+                    Span::styled(
+                        format!("{:<9}", format!("[{}]", entry.source.tag())),
+                        theme.muted,
+                    ),
+                    Span::styled(
+                        entry.label.as_str(),
+                        if entry.unavailable {
+                            theme.muted
+                        } else {
+                            theme.text
+                        },
+                    ),
+                ];
+                if !entry.detail.is_empty() {
+                    spans.push(Span::styled(format!("  {}", entry.detail), theme.muted));
+                }
+                let item = ListItem::new(Line::from(spans));
+                if i == selected {
+                    item.style(theme.selected)
+                } else {
+                    item
+                }
+            })
+            .collect();
+        if items.is_empty() {
+            Paragraph::new(Span::styled("No matches", theme.muted)).render(rows[1], buf);
+        } else {
+            List::new(items).render(rows[1], buf);
+        }
 
-        self.render_action_bar(action_area, buf);
+        Paragraph::new(Span::styled(
+            "Enter: use  Up/Down: move  Esc: close",
+            theme.muted,
+        ))
+        .render(rows[2], buf);
     }
 
     fn render_tree_panel(
@@ -1525,47 +3047,52 @@ impl App {
         buf: &mut Buffer,
         is_active: bool,
     ) {
-        let border_color = if is_active {
-            Color::Cyan
+        let theme = self.theme;
+        let border_style = if is_active { theme.focus } else { theme.border };
+        let shows_hidden = if is_slave {
+            self.tree_explorer.slave_tree.show_hidden
         } else {
-            Color::DarkGray
+            self.tree_explorer.local_tree.show_hidden
         };
-        let block = Block::bordered()
-            .title(Span::styled(
-                title,
-                Style::default()
-                    .fg(border_color)
-                    .add_modifier(Modifier::BOLD),
-            ))
-            .border_style(Style::default().fg(border_color));
+        let title = if shows_hidden {
+            format!("{}+ hidden ", title)
+        } else {
+            title.to_string()
+        };
+        let block = self
+            .block()
+            .title(Span::styled(title, theme.bold(border_style)))
+            .border_style(border_style);
 
         let inner = block.inner(area);
         block.render(area, buf);
 
         let mut items = Vec::new();
-        let (root_nodes, cursor_index, scroll_offset) = if !is_slave {
-            (
-                &self.tree_explorer.local_tree.root_nodes,
-                self.tree_explorer.local_tree.cursor_index,
-                &mut self.tree_explorer.local_tree.scroll_offset,
-            )
+        let tree = if !is_slave {
+            &mut self.tree_explorer.local_tree
         } else {
-            (
-                &self.tree_explorer.slave_tree.root_nodes,
-                self.tree_explorer.slave_tree.cursor_index,
-                &mut self.tree_explorer.slave_tree.scroll_offset,
-            )
+            &mut self.tree_explorer.slave_tree
         };
+        let (cursor_index, scroll_offset) = (tree.cursor_index, &mut tree.scroll_offset);
 
-        Self::flatten_tree_static(root_nodes, 0, &mut items);
+        Self::flatten_tree_static(&tree.root_nodes, 0, &mut items, tree.show_hidden);
+
+        // Placeholder rows cannot hold the cursor, so find the row of the
+        // `cursor_index`-th node.
+        let cursor_row = items
+            .iter()
+            .enumerate()
+            .filter(|(_, (node, _))| node.is_some())
+            .nth(cursor_index)
+            .map_or(cursor_index, |(row, _)| row);
 
         // Adjust scroll offset to follow cursor
         let height = inner.height as usize;
         if height > 0 {
-            if cursor_index < *scroll_offset {
-                *scroll_offset = cursor_index;
-            } else if cursor_index >= *scroll_offset + height {
-                *scroll_offset = cursor_index - height + 1;
+            if cursor_row < *scroll_offset {
+                *scroll_offset = cursor_row;
+            } else if cursor_row >= *scroll_offset + height {
+                *scroll_offset = cursor_row - height + 1;
             }
         }
 
@@ -1576,75 +3103,242 @@ impl App {
             .take(height)
             .map(|(i, (node, depth))| {
                 let indent = "  ".repeat(*depth);
-                let icon = if node.is_dir {
-                    if node.is_expanded { "📂 " } else { "📁 " }
+                let Some(node) = node else {
+                    return ListItem::new(Line::from(vec![
+                        Span::raw(indent),
+                        Span::styled("(empty)", theme.faint(theme.muted)),
+                    ]));
+                };
+                let icon = if node.attributes.contains(FileAttributes::REPARSE_POINT) {
+                    theme.icons.link
+                } else if node.is_dir {
+                    if node.is_expanded {
+                        theme.icons.dir_open
+                    } else {
+                        theme.icons.dir
+                    }
                 } else {
-                    "📄 "
+                    theme.icons.file
                 };
 
                 let selection_mark = if node.is_selected { "[x] " } else { "[ ] " };
-                let style = if is_active && i == cursor_index {
-                    Style::default().bg(Color::Cyan).fg(Color::Black)
+                let style = if is_active && i == cursor_row {
+                    theme.cursor
+                } else if node.attributes.is_hidden() {
+                    theme.faint(theme.muted)
                 } else {
                     Style::default()
                 };
 
-                ListItem::new(Line::from(vec![
+                let mut line = Line::from(vec![
                     Span::raw(indent),
-                    Span::styled(selection_mark, Style::default().fg(Color::Yellow)),
+                    Span::styled(selection_mark, theme.warning),
                     Span::raw(icon),
                     Span::styled(&node.name, style),
-                ]))
+                ]);
+                // Sizes sit against the right border, where there is room.
+                if let Some(size) = node.size {
+                    let size = format_bytes(size);
+                    let gap = (inner.width as usize).saturating_sub(line.width() + size.len());
+                    if gap > 0 {
+                        line.push_span(Span::raw(" ".repeat(gap)));
+                        line.push_span(Span::styled(size, theme.muted));
+                    }
+                }
+                ListItem::new(line)
             })
             .collect();
 
         List::new(list_items).render(inner, buf);
     }
 
+    /// Visible rows with their depth. An open directory known to be
+    /// empty gets a `None` row, drawn as "(empty)".
     fn flatten_tree_static<'a>(
         nodes: &'a [FileNode],
         depth: usize,
-        out: &mut Vec<(&'a FileNode, usize)>,
+        out: &mut Vec<(Option<&'a FileNode>, usize)>,
+        show_hidden: bool,
     ) {
-        for node in nodes {
-            out.push((node, depth));
+        for node in nodes.iter().filter(|n| n.is_shown(show_hidden)) {
+            out.push((Some(node), depth));
             if node.is_expanded
                 && let Some(children) = &node.children
             {
-                Self::flatten_tree_static(children, depth + 1, out);
+                if !children.iter().any(|c| c.is_shown(show_hidden)) {
+                    out.push((None, depth + 1));
+                }
+                Self::flatten_tree_static(children, depth + 1, out, show_hidden);
             }
         }
     }
 
+    /// `style` for a key binding that sends `commands`, dimmed when the
+    /// role allows none of them.
+    fn binding_style(&self, commands: &[Command], style: Style) -> Style {
+        if commands.is_empty() || commands.iter().any(|cmd| self.allowed.contains(*cmd)) {
+            style
+        } else {
+            self.theme.muted
+        }
+    }
+
+    /// Why a command needing `feature` cannot go to the connected slave:
+    /// its `Hello` left the feature out. `None` when it can, or when the
+    /// slave listed nothing.
+    fn unsupported(&self, feature: Option<Feature>) -> Option<String> {
+        let (version, features) = self.slave_features.as_ref()?;
+        feature.filter(|feature| !features.contains(*feature))?;
+        Some(format!("not supported by connected slave (v{})", version))
+    }
+
+    /// Whether the connected slave said it can run none of `commands`;
+    /// key bindings that only send those are not offered.
+    fn slave_lacks(&self, commands: &[Command]) -> bool {
+        !commands.is_empty()
+            && commands
+                .iter()
+                .all(|cmd| self.unsupported(Feature::for_command(*cmd)).is_some())
+    }
+
     fn render_action_bar(&self, area: Rect, buf: &mut Buffer) {
-        let block = Block::bordered()
-            .title(Span::styled(
-                " Actions ",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            ))
-            .border_style(Style::default().fg(Color::DarkGray));
+        let block = self
+            .block()
+            .title(Span::styled(" Actions ", self.theme.heading))
+            .border_style(self.theme.border);
 
         let inner = block.inner(area);
         block.render(area, buf);
 
-        let actions = [
-            "[Space] Select",
-            "[Enter] Open/Close",
-            "[C] Copy",
-            "[X] Cut",
-            "[V] Paste",
-            "[F5] Refresh",
-            "[Del] Delete",
+        // With the slave commands each one sends.
+        let actions: [(&str, &[Command]); 13] = [
+            ("[Space] Select", &[]),
+            ("[Enter] Open/Close", &[Command::ListDir]),
+            ("[C] Copy", &[]),
+            ("[X] Cut", &[]),
+            (
+                "[V] Paste",
+                &[Command::Copy, Command::Upload, Command::Download],
+            ),
+            ("[F5] Refresh", &[Command::ListDir]),
+            ("[H] Show/hide hidden", &[]),
+            ("[N] New folder (host)", &[]),
+            ("[F2] Rename (host)", &[]),
+            ("[Del] Recycle", &[Command::FileDelete]),
+            ("[Shift+Del] Delete permanently", &[Command::FileDelete]),
+            ("[U] Undo last slave recycle", &[Command::TrashRestore]),
+            ("[T] Transfers", &[]),
         ];
 
         let action_spans: Vec<Line> = actions
             .iter()
-            .map(|a| Line::from(Span::styled(*a, Style::default().fg(Color::Gray))))
+            .filter(|(_, cmds)| !self.slave_lacks(cmds))
+            .map(|(a, cmds)| {
+                Line::from(Span::styled(*a, self.binding_style(cmds, self.theme.text)))
+            })
             .collect();
         Paragraph::new(action_spans).render(inner, buf);
     }
+
+    /// The transfers panel: one row per item, newest last, with the
+    /// selected one highlighted.
+    fn render_transfers(&self, area: Rect, buf: &mut Buffer) {
+        let theme = &self.theme;
+        let block = self
+            .block()
+            .title(Span::styled(
+                " Transfers  [[/]] Select  [P] Pause/resume  [X] Cancel  [R] Retry  [T] Actions ",
+                theme.heading,
+            ))
+            .border_style(theme.border);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let rows = self.transfer_panel.rows();
+        if rows.is_empty() {
+            Paragraph::new(Line::from(Span::styled(
+                "No transfers yet: paste onto the other tree to start one",
+                theme.muted,
+            )))
+            .render(inner, buf);
+            return;
+        }
+
+        let columns = transfers::PanelColumns::for_width(inner.width as usize);
+        let mut lines = vec![Line::from(Span::styled(columns.header(), theme.heading))];
+        // Keep the selected row in view.
+        let room = (inner.height as usize).saturating_sub(1).max(1);
+        let selected = self.transfer_panel.selected_index();
+        let first = (selected + 1).saturating_sub(room);
+        for (index, row) in rows.iter().enumerate().skip(first).take(room) {
+            let update = &row.update;
+            let bar = match update.fraction() {
+                Some(fraction) => format!(
+                    "{} {:>3.0}%",
+                    transfers::progress_bar(
+                        fraction,
+                        columns.bar,
+                        theme.icons.bar_full,
+                        theme.icons.bar_empty
+                    ),
+                    fraction * 100.0
+                ),
+                None => format!(
+                    "{:<width$}",
+                    format_bytes(update.bytes),
+                    width = columns.bar + 5
+                ),
+            };
+            let rate = row
+                .rate
+                .map(|rate| format!("{}/s", format_bytes(rate as u64)))
+                .unwrap_or_default();
+            let eta = row.eta().map(format_duration).unwrap_or_default();
+            let text = columns.row(update, &bar, &rate, &eta);
+            let style = match update.state {
+                TransferState::Failed => theme.danger,
+                TransferState::Paused => theme.warning,
+                TransferState::Done => theme.success,
+                TransferState::Cancelled | TransferState::Queued => theme.muted,
+                TransferState::Active => theme.text,
+            };
+            let style = if index == selected {
+                theme.cursor
+            } else {
+                style
+            };
+            lines.push(Line::from(Span::styled(text, style)));
+        }
+        Paragraph::new(lines).render(inner, buf);
+    }
+
+    /// `transfer pause` or `resume` for the selected item, whichever
+    /// applies.
+    pub fn transfer_toggle_pause(&mut self) -> Option<String> {
+        let update = &self.transfer_panel.selected()?.update;
+        let command = match update.state {
+            TransferState::Queued | TransferState::Active => TransferCommand::Pause(update.id),
+            TransferState::Paused => TransferCommand::Resume(update.id),
+            state => {
+                self.logs
+                    .push(format!("Transfer {} is {}", update.id, state));
+                return None;
+            }
+        };
+        Some(command.to_command())
+    }
+
+    /// `transfer cancel` for the selected item.
+    pub fn transfer_cancel(&mut self) -> Option<String> {
+        let update = &self.transfer_panel.selected()?.update;
+        Some(TransferCommand::Cancel(update.id).to_command())
+    }
+
+    /// `transfer retry` for the selected item.
+    pub fn transfer_retry(&mut self) -> Option<String> {
+        let update = &self.transfer_panel.selected()?.update;
+        Some(TransferCommand::Retry(update.id).to_command())
+    }
 }
 
 impl Widget for &App {
@@ -1653,3 +3347,1084 @@ impl Widget for &App {
         // but kept for compatibility if needed.
     }
 }
+
+/// The spans of one log row, styled by what it starts with. Markers a
+/// line opens with only count on an entry's first line, except diff
+/// and event-log markers, which every line of a block carries.
+fn log_line_spans<'a>(log: &'a str, first: bool, theme: &Theme) -> Vec<Span<'a>> {
+    let icons = theme.icons;
+    if pager::page_reference(log).is_some() {
+        vec![Span::styled(log, theme.bold(theme.accent))]
+    } else if let Some(diff) = log.strip_prefix(watch::DIFF_MARKER) {
+        let style = match diff.chars().next() {
+            Some('+') => theme.success,
+            Some('-') => theme.danger,
+            Some('@') => theme.focus,
+            _ => theme.text,
+        };
+        vec![
+            Span::styled(icons.gutter, theme.muted),
+            Span::styled(diff, style),
+        ]
+    } else if let Some(level) = eventlog::line_level(log) {
+        let style = match level {
+            EventLevel::Critical => theme.bold(theme.danger),
+            EventLevel::Error => theme.danger,
+            EventLevel::Warning => theme.warning,
+            EventLevel::Information => theme.text,
+            EventLevel::Verbose => theme.muted,
+        };
+        vec![
+            Span::styled(icons.gutter, theme.muted),
+            Span::styled(&log[eventlog::ENTRY_MARKER.len_utf8()..], style),
+        ]
+    } else if !first {
+        vec![Span::raw(log)]
+    } else if let Some(rest) = log.strip_prefix('>') {
+        vec![
+            Span::styled("> ", theme.success),
+            Span::raw(rest.strip_prefix(' ').unwrap_or(rest)),
+        ]
+    } else if let Some(rest) = log.strip_prefix('-') {
+        vec![
+            Span::styled("- ", theme.info),
+            Span::styled(
+                rest.strip_prefix(' ').unwrap_or(rest),
+                theme.quote(theme.text),
+            ),
+        ]
+    } else if log.starts_with("[SEND]") {
+        vec![
+            Span::styled(icons.send, theme.focus),
+            Span::styled(log, theme.muted),
+        ]
+    } else if let Some(severity) = ErrorSeverity::of_line(log) {
+        let style = match severity {
+            ErrorSeverity::Hint => theme.warning,
+            ErrorSeverity::Error => theme.danger,
+            ErrorSeverity::Corruption => theme.bold(theme.danger),
+            ErrorSeverity::Crash => theme.reversed(theme.danger),
+        };
+        vec![Span::styled(log, style)]
+    } else if log.starts_with(LATE_MARKER) {
+        vec![Span::styled(log, theme.faint(theme.muted))]
+    } else if log.starts_with("[RECV]") || log.starts_with("[DONE]") {
+        vec![
+            Span::styled(icons.recv, theme.success),
+            Span::styled(log, theme.muted),
+        ]
+    } else {
+        vec![Span::raw(log)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(mode: DeleteMode) -> PendingDelete {
+        PendingDelete {
+            paths: vec![PathBuf::from("C:\\a b.txt"), PathBuf::from("C:\\c")],
+            mode,
+            local: false,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn pending_delete_prompt_and_command() {
+        let recycle = pending(DeleteMode::Recycle);
+        assert!(recycle.prompt().contains("Recycle Bin"));
+        assert_eq!(recycle.to_command(), r#"Delete "C:\a b.txt" "C:\c""#);
+
+        let permanent = pending(DeleteMode::Permanent);
+        assert!(permanent.prompt().starts_with("PERMANENTLY"));
+        assert!(permanent.to_command().starts_with("Delete --permanent "));
+    }
+
+    #[test]
+    fn confirm_recycle_enables_undo() {
+        let mut app = App::new();
+        app.tree_explorer.pending_delete = Some(pending(DeleteMode::Recycle));
+        // The popup was the confirmation the master asks for.
+        assert!(app.confirm_delete().unwrap().ends_with(" --confirm"));
+        assert!(app.tree_explorer.pending_delete.is_none());
+
+        let undo = app.tree_undo_delete();
+        assert_eq!(
+            undo,
+            vec![r#"TrashRestore "C:\a b.txt""#, r#"TrashRestore "C:\c""#]
+        );
+        assert!(app.tree_undo_delete().is_empty());
+    }
+
+    #[test]
+    fn permanent_delete_and_cancel_leave_nothing_to_undo() {
+        let mut app = App::new();
+        app.tree_explorer.pending_delete = Some(pending(DeleteMode::Permanent));
+        assert!(app.confirm_delete().is_some());
+        assert!(app.tree_undo_delete().is_empty());
+
+        app.tree_explorer.pending_delete = Some(pending(DeleteMode::Recycle));
+        app.cancel_delete();
+        assert!(app.confirm_delete().is_none());
+    }
+
+    #[test]
+    fn commands_the_slave_lacks_are_refused_here() {
+        let mut app = App::new();
+        app.update(MasterEvent::SlaveFeatures {
+            version: "0.3.1".to_string(),
+            features: Some(FeatureSet::from_names(["registry"])),
+        });
+        app.command_to_execute = "services".to_string();
+        assert_eq!(app.handle_enter(), None);
+        assert_eq!(
+            app.logs.iter().last().unwrap(),
+            "Error: services not supported by connected slave (v0.3.1)"
+        );
+        app.tree_explorer.last_recycled = vec![PathBuf::from(r"C:\a")];
+        assert!(app.tree_undo_delete().is_empty());
+        assert_eq!(
+            app.logs.iter().last().unwrap(),
+            "Error: TrashRestore not supported by connected slave (v0.3.1)"
+        );
+
+        // What it has, and anything from a slave that sent no list, goes.
+        app.command_to_execute = "startup".to_string();
+        assert_eq!(app.handle_enter().as_deref(), Some("startup"));
+        app.update(MasterEvent::SlaveFeatures {
+            version: "0.2.0".to_string(),
+            features: None,
+        });
+        app.command_to_execute = "services".to_string();
+        assert_eq!(app.handle_enter().as_deref(), Some("services"));
+        assert_eq!(app.tree_undo_delete().len(), 1);
+    }
+
+    #[test]
+    fn stopping_a_protected_service_asks_first() {
+        let mut app = App::new();
+        app.command_to_execute = "service Spooler stop".to_string();
+        assert_eq!(app.handle_enter().as_deref(), Some("service Spooler stop"));
+
+        app.command_to_execute = "service RpcSs restart ".to_string();
+        assert_eq!(app.handle_enter(), None);
+        let question = &app.pending_command.as_ref().unwrap().question;
+        assert!(question.contains("'RpcSs'"), "{}", question);
+        assert_eq!(app.confirm_command(), ["service RpcSs restart --confirm"]);
+        assert!(app.pending_command.is_none());
+
+        app.command_to_execute = "service RpcSs stop".to_string();
+        app.handle_enter();
+        app.cancel_command();
+        assert!(app.confirm_command().is_empty());
+        assert_eq!(
+            app.logs.iter().last().unwrap(),
+            "Cancelled: service RpcSs stop"
+        );
+    }
+
+    #[test]
+    fn destructive_commands_wait_for_a_yes() {
+        let mut app = App::new();
+        app.update(MasterEvent::SlaveConnected("10.0.0.7:4000".into()));
+        app.update(MasterEvent::SlaveNamed("PC-7".into()));
+        app.command_to_execute = "SystemAction shutdown".to_string();
+        assert_eq!(app.handle_enter(), None);
+        assert_eq!(
+            app.pending_command.as_ref().unwrap().question,
+            "Send 'SystemAction shutdown' to PC-7 (10.0.0.7:4000)? This cannot be undone."
+        );
+
+        // The popup keeps what it says true: events wait behind it.
+        app.update(MasterEvent::SlaveDisconnected("10.0.0.7:4000".into()));
+        app.update(MasterEvent::log("after"));
+        assert_eq!(app.slave_info.name, "PC-7");
+        assert!(!app.logs.iter().any(|entry| entry == "after"));
+        assert_eq!(app.confirm_command(), ["SystemAction shutdown --confirm"]);
+        assert_eq!(app.slave_info.ip, "Not Connected");
+        assert!(app.logs.iter().any(|entry| entry == "after"));
+
+        app.command_to_execute = "SystemAction lock".to_string();
+        assert_eq!(app.handle_enter().as_deref(), Some("SystemAction lock"));
+        let mut off =
+            App::new().with_confirm_gate(ConfirmGate::new(&crate::config::CommandsConfig {
+                confirm_destructive: false,
+                ..Default::default()
+            }));
+        off.command_to_execute = "Delete C:\\x".to_string();
+        assert_eq!(off.handle_enter().as_deref(), Some("Delete C:\\x"));
+    }
+
+    #[test]
+    fn large_pastes_and_uploads_ask_first() {
+        let dir = std::env::temp_dir().join(format!("tix-app-paste-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, len: usize| {
+            let path = dir.join(name);
+            std::fs::write(&path, vec![0u8; len]).unwrap();
+            path
+        };
+        let (small, large, huge) = (file("s", 10), file("l", 500), file("h", 5000));
+        let guard =
+            tix_core::paste_guard::PasteGuard::new(&tix_core::paste_guard::PasteGuardConfig {
+                confirm_bytes: 100,
+                refuse_bytes: 1000,
+            });
+        let mut app = App::new().with_confirm_gate(ConfirmGate::default().with_paste_guard(guard));
+        app.update(MasterEvent::LinkRate(Some(100)));
+        app.tree_explorer.active_side = true;
+        app.update(MasterEvent::TreeData {
+            is_slave: true,
+            path: "drives".to_string(),
+            data: "/srv".to_string(),
+        });
+
+        // The whole paste is weighed, and confirmed, at once.
+        app.tree_explorer.clipboard = vec![large.clone(), small.clone()];
+        assert!(app.tree_paste().is_empty());
+        let pending = app.pending_command.as_ref().unwrap();
+        assert_eq!(
+            pending.question,
+            "This paste of 2 items is 510 B, about 5.1s at 100 B/s. Send it?"
+        );
+        let sent = app.confirm_command();
+        assert_eq!(sent.len(), 2);
+        assert!(
+            sent.iter()
+                .all(|cmd| cmd.starts_with("Upload ") && cmd.ends_with(" --confirm"))
+        );
+
+        app.tree_explorer.clipboard = vec![huge.clone()];
+        assert!(app.tree_paste().is_empty());
+        assert!(app.pending_command.is_none());
+        assert!(
+            app.logs
+                .iter()
+                .last()
+                .unwrap()
+                .contains("over the 1000 B limit")
+        );
+
+        // A typed Upload goes through the same guard.
+        app.command_to_execute = format!("Upload {}|/srv", large.display());
+        assert_eq!(app.handle_enter(), None);
+        assert!(app.pending_command.take().is_some());
+        app.command_to_execute = format!("Upload {}|/srv", small.display());
+        assert!(app.handle_enter().is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// An app with something in every panel that has an icon or border.
+    fn busy_app(theme: ThemeName) -> App {
+        let mut app = App::new().with_theme(Theme::named(theme));
+        app.logs.push("[SEND] ReqID 1: ListDir");
+        app.logs.push("[RECV] ReqID 1: 2 entries");
+        app.logs.push(format!("{}+added line", watch::DIFF_MARKER));
+        app.tasks
+            .update(TaskId::Request(1), "Solved", std::time::Instant::now());
+        app.completion = CompletionState {
+            options: ["Logs", "boot.ini"]
+                .iter()
+                .enumerate()
+                .map(|(i, name)| CompletionOption {
+                    display: name.to_string(),
+                    value: name.to_string(),
+                    is_dir: i == 0,
+                })
+                .collect(),
+            active: true,
+            ..CompletionState::default()
+        };
+        let node = |name: &str, is_dir| FileNode {
+            name: name.to_string(),
+            path: PathBuf::from(name),
+            is_dir,
+            is_expanded: false,
+            children: None,
+            is_selected: false,
+            attributes: FileAttributes::empty(),
+            size: None,
+        };
+        app.tree_explorer.local_tree.root_nodes = vec![node("C:\\", true), node("a.txt", false)];
+        app.tree_explorer.local_tree.root_nodes[1].size = Some(1536);
+        app.tree_explorer.pending_delete = Some(pending(DeleteMode::Recycle));
+        app
+    }
+
+    /// Render every tab of `app` and return the buffers.
+    fn render_tabs(app: &mut App) -> Vec<Buffer> {
+        let area = Rect::new(0, 0, 100, 30);
+        [Tab::Main, Tab::TreeExplorer, Tab::SystemSettings]
+            .into_iter()
+            .map(|tab| {
+                app.active_tab = tab;
+                let mut buf = Buffer::empty(area);
+                app.render_all(area, &mut buf);
+                buf
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ascii_theme_draws_only_ascii() {
+        let mut app = busy_app(ThemeName::Ascii);
+        let text: String = render_tabs(&mut app)
+            .iter()
+            .flat_map(|buf| buf.content.iter().map(|cell| cell.symbol()))
+            .collect();
+        let odd: String = text.chars().filter(|c| !c.is_ascii()).collect();
+        assert!(odd.is_empty(), "non-ASCII glyphs drawn: {odd:?}");
+        for drawn in [
+            "[D] Logs",
+            "[F] boot.ini",
+            "[F] a.txt",
+            "1.5 KiB",
+            "-> [SEND]",
+            "|+added",
+            "+---",
+        ] {
+            assert!(text.contains(drawn), "{drawn} missing");
+        }
+
+        // The default theme does draw emoji and box-drawing characters.
+        let mut app = busy_app(ThemeName::Default);
+        let text: String = render_tabs(&mut app)[0]
+            .content
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(text.contains('📁') && text.contains('┃'));
+    }
+
+    /// Draw `app` on a `width × height` test terminal, as `draw` does
+    /// for the real one, and return its rows.
+    fn draw_rows(app: &mut App, width: u16, height: u16) -> Vec<String> {
+        use ratatui::{Terminal, backend::TestBackend};
+
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let buf = terminal.backend().buffer();
+        (0..height)
+            .map(|y| (0..width).map(|x| buf[(x, y)].symbol()).collect())
+            .collect()
+    }
+
+    /// Column and row of the first `needle` drawn; the ASCII theme keeps
+    /// byte offsets equal to columns.
+    fn position(rows: &[String], needle: &str) -> Option<(usize, usize)> {
+        rows.iter()
+            .enumerate()
+            .find_map(|(y, row)| row.find(needle).map(|x| (x, y)))
+    }
+
+    #[test]
+    fn layout_follows_the_terminal_size() {
+        let sizes = [(40, 10), (60, 20), (80, 24), (200, 60)];
+        let modes: Vec<LayoutMode> = sizes
+            .iter()
+            .map(|&(w, h)| LayoutMode::for_area(Rect::new(0, 0, w, h)))
+            .collect();
+        assert_eq!(
+            modes,
+            [
+                LayoutMode::TooSmall,
+                LayoutMode::Narrow,
+                LayoutMode::Narrow,
+                LayoutMode::Wide
+            ]
+        );
+
+        let mut app = busy_app(ThemeName::Ascii);
+        for (width, height) in sizes
+            .into_iter()
+            .chain([(0, 0), (1, 1), (59, 40), (99, 20)])
+        {
+            for tab in [Tab::Main, Tab::TreeExplorer, Tab::SystemSettings] {
+                app.active_tab = tab;
+                draw_rows(&mut app, width, height);
+            }
+        }
+        app.active_tab = Tab::Main;
+        app.completion.active = false;
+
+        let rows = draw_rows(&mut app, 40, 10);
+        assert!(position(&rows, "terminal too small (need 60").is_some());
+        assert!(position(&rows, "[F1]").is_none());
+
+        // Narrow: tab keys only, sidebar under the logs.
+        for (width, height) in [(60, 20), (80, 24)] {
+            let rows = draw_rows(&mut app, width, height);
+            assert!(position(&rows, "[F1][F2][F3]").is_some());
+            assert!(position(&rows, "Main Console").is_none());
+            let (_, logs) = position(&rows, "[RECV]").unwrap();
+            let (_, slave) = position(&rows, "Slave PC").unwrap();
+            assert!(slave > logs, "{rows:#?}");
+        }
+
+        // Wide: full titles, sidebar to the right of the logs.
+        let rows = draw_rows(&mut app, 200, 60);
+        assert!(position(&rows, "[F1] Main Console").is_some());
+        let (logs, _) = position(&rows, "[RECV]").unwrap();
+        let (slave, _) = position(&rows, "Slave PC").unwrap();
+        assert!(slave > 100 + logs, "{rows:#?}");
+    }
+
+    #[test]
+    fn high_contrast_theme_is_white_on_black_without_dim() {
+        use ratatui::style::{Color, Modifier};
+
+        let mut app = busy_app(ThemeName::HighContrast);
+        for buf in render_tabs(&mut app) {
+            for cell in &buf.content {
+                assert!(!cell.modifier.contains(Modifier::DIM), "{cell:?}");
+                assert!(
+                    matches!(cell.fg, Color::White | Color::Black | Color::Reset),
+                    "{cell:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn empty_slave_directory_is_loaded_with_no_children() {
+        let mut app = App::new();
+        app.tree_explorer.active_side = true;
+        app.update(MasterEvent::TreeData {
+            is_slave: true,
+            path: "drives".to_string(),
+            data: "/srv,/tmp".to_string(),
+        });
+        assert_eq!(app.tree_toggle_expand().as_deref(), Some("ListDir /srv"));
+
+        // An empty directory lists as the path alone.
+        app.update(MasterEvent::TreeData {
+            is_slave: true,
+            path: "dir_listing".to_string(),
+            data: "PATH|/srv".to_string(),
+        });
+        let srv = &app.tree_explorer.slave_tree.root_nodes[0];
+        assert!(srv.is_expanded);
+        assert!(srv.children.as_ref().is_some_and(Vec::is_empty));
+
+        app.active_tab = Tab::TreeExplorer;
+        let text: String = render_tabs(&mut app)[1]
+            .content
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(text.contains("(empty)"));
+
+        // The placeholder is not a row: the cursor goes straight to /tmp,
+        // and reopening /srv does not ask again.
+        app.tree_cursor_down();
+        assert_eq!(app.tree_explorer.slave_tree.cursor_index, 1);
+        app.tree_cursor_up();
+        assert_eq!(app.tree_toggle_expand(), None);
+        assert_eq!(app.tree_toggle_expand(), None);
+        assert!(app.tree_explorer.slave_tree.root_nodes[0].is_expanded);
+    }
+
+    #[test]
+    fn hidden_slave_entries_are_filtered_and_links_marked() {
+        let mut app = App::new().with_theme(Theme::named(ThemeName::Ascii));
+        app.tree_explorer.active_side = true;
+        app.update(MasterEvent::TreeData {
+            is_slave: true,
+            path: "drives".to_string(),
+            data: "C:\\".to_string(),
+        });
+        app.tree_toggle_expand();
+        let hidden = FileAttributes::HIDDEN | FileAttributes::SYSTEM;
+        let junction = FileAttributes::HIDDEN | FileAttributes::REPARSE_POINT;
+        app.update(MasterEvent::TreeData {
+            is_slave: true,
+            path: "dir_listing".to_string(),
+            data: format!(
+                "PATH|C:\\;Users|1|0;desktop.ini|0|282|{};Application Data|1|0|{};Links|1|0|{};old.txt|0|5",
+                hidden.bits(),
+                junction.bits(),
+                FileAttributes::REPARSE_POINT.bits(),
+            ),
+        });
+        let children = app.tree_explorer.slave_tree.root_nodes[0]
+            .children
+            .clone()
+            .unwrap();
+        let attrs: Vec<(&str, FileAttributes)> = children
+            .iter()
+            .map(|c| (c.name.as_str(), c.attributes))
+            .collect();
+        assert!(attrs.contains(&("desktop.ini", hidden)));
+        assert!(attrs.contains(&("Application Data", junction)));
+        assert!(attrs.contains(&("old.txt", FileAttributes::empty())));
+
+        let tree_text = |app: &mut App| -> String {
+            render_tabs(app)[1]
+                .content
+                .iter()
+                .map(|cell| cell.symbol())
+                .collect()
+        };
+        let text = tree_text(&mut app);
+        assert!(!text.contains("desktop.ini") && !text.contains("Application Data"));
+        assert!(text.contains("[L] Links") && text.contains("[D] Users"));
+
+        // Rows: C:\, Links, Users, old.txt. Keep the cursor on old.txt
+        // while the hidden rows appear above it.
+        for _ in 0..3 {
+            app.tree_cursor_down();
+        }
+        assert_eq!(app.tree_explorer.slave_tree.cursor_index, 3);
+        app.tree_toggle_hidden();
+        assert!(app.tree_explorer.slave_tree.show_hidden);
+        assert!(!app.tree_explorer.local_tree.show_hidden);
+        assert_eq!(app.tree_explorer.slave_tree.cursor_index, 5);
+        let text = tree_text(&mut app);
+        assert!(text.contains("[L] Application Data") && text.contains("[F] desktop.ini"));
+        assert!(text.contains("+ hidden"));
+
+        app.tree_toggle_hidden();
+        assert_eq!(app.tree_explorer.slave_tree.cursor_index, 3);
+    }
+
+    #[test]
+    fn empty_local_directory_is_loaded_with_no_children() {
+        let dir = std::env::temp_dir().join(format!("tix-empty-tree-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("hollow")).unwrap();
+
+        let mut node = FileNode {
+            name: "root".to_string(),
+            path: dir.clone(),
+            is_dir: true,
+            is_expanded: true,
+            children: None,
+            is_selected: false,
+            attributes: FileAttributes::empty(),
+            size: None,
+        };
+        node.children = localops::list_dir(&node.path);
+        let hollow = &mut node.children.as_mut().unwrap()[0];
+        hollow.children = localops::list_dir(&hollow.path);
+        assert!(hollow.children.as_ref().is_some_and(Vec::is_empty));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// An app whose host tree is `dir`, opened, with the cursor on it.
+    fn host_tree_app(dir: &Path) -> App {
+        let mut app = App::new();
+        let mut root = FileNode {
+            name: "root".to_string(),
+            path: dir.to_path_buf(),
+            is_dir: true,
+            is_expanded: true,
+            children: None,
+            is_selected: false,
+            attributes: FileAttributes::empty(),
+            size: None,
+        };
+        root.children = localops::list_dir(dir);
+        app.tree_explorer.local_tree.root_nodes = vec![root];
+        app
+    }
+
+    fn child_names(app: &App) -> Vec<String> {
+        let root = &app.tree_explorer.local_tree.root_nodes[0];
+        let children = root.children.as_deref().unwrap_or_default();
+        children.iter().map(|c| c.name.clone()).collect()
+    }
+
+    #[test]
+    fn host_folders_are_created_and_renamed_in_place() {
+        let dir = std::env::temp_dir().join(format!("tix-host-ops-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("taken"), b"x").unwrap();
+        let mut app = host_tree_app(&dir);
+
+        app.tree_request_new_folder();
+        for c in "taken".chars() {
+            app.name_input_push(c);
+        }
+        app.confirm_name_input();
+        let input = app.tree_explorer.pending_name.as_ref().unwrap();
+        assert!(input.error.as_deref().unwrap().contains("already exists"));
+
+        for _ in 0..5 {
+            app.name_input_pop();
+        }
+        "docs".chars().for_each(|c| app.name_input_push(c));
+        assert!(
+            app.tree_explorer
+                .pending_name
+                .as_ref()
+                .unwrap()
+                .error
+                .is_none()
+        );
+        app.confirm_name_input();
+        assert!(app.tree_explorer.pending_name.is_none());
+        assert!(dir.join("docs").is_dir());
+        assert_eq!(child_names(&app), ["docs", "taken"]);
+        // The cursor follows the new folder.
+        assert_eq!(app.tree_explorer.local_tree.cursor_index, 1);
+
+        app.tree_request_rename();
+        let input = app.tree_explorer.pending_name.as_mut().unwrap();
+        assert_eq!(input.name, "docs");
+        input.name = "notes".to_string();
+        app.confirm_name_input();
+        assert!(dir.join("notes").is_dir() && !dir.join("docs").exists());
+        assert_eq!(child_names(&app), ["notes", "taken"]);
+
+        // Drive roots cannot be renamed, and the slave side is not ours.
+        app.tree_explorer.local_tree.cursor_index = 0;
+        app.tree_request_rename();
+        assert!(app.tree_explorer.pending_name.is_none());
+        app.tree_switch_side();
+        app.tree_request_new_folder();
+        assert!(app.tree_explorer.pending_name.is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn host_delete_refreshes_the_parent_and_keeps_errors_in_the_popup() {
+        let dir = std::env::temp_dir().join(format!("tix-host-delete-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("old/inner")).unwrap();
+        let mut app = host_tree_app(&dir);
+
+        app.tree_explorer.local_tree.cursor_index = 1;
+        app.tree_request_delete(DeleteMode::Permanent);
+        let pending = app.tree_explorer.pending_delete.as_ref().unwrap();
+        assert!(pending.local);
+        assert!(pending.prompt().contains("on this PC"));
+        assert!(app.confirm_delete().is_none());
+        assert!(!dir.join("old").exists());
+        assert!(child_names(&app).is_empty());
+        assert_eq!(app.tree_explorer.local_tree.cursor_index, 0);
+
+        // A path that vanished behind our back is reported in the popup.
+        app.tree_explorer.pending_delete = Some(PendingDelete {
+            paths: vec![dir.join("ghost")],
+            mode: DeleteMode::Permanent,
+            local: true,
+            error: None,
+        });
+        assert!(app.confirm_delete().is_none());
+        let error = app
+            .tree_explorer
+            .pending_delete
+            .as_ref()
+            .unwrap()
+            .error
+            .clone();
+        assert!(error.unwrap().contains("no longer exists"));
+        app.active_tab = Tab::TreeExplorer;
+        let area = Rect::new(0, 0, 100, 30);
+        let mut buf = Buffer::empty(area);
+        app.render_all(area, &mut buf);
+        let text: String = buf.content.iter().map(|cell| cell.symbol()).collect();
+        assert!(text.contains("no longer exists") && text.contains("[Y] Retry"));
+
+        app.cancel_delete();
+        assert!(app.tree_explorer.pending_delete.is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn host_paste_copies_on_the_worker_while_events_keep_flowing() {
+        let dir = std::env::temp_dir().join(format!("tix-host-paste-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let src = dir.join("big");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::create_dir_all(dir.join("dest")).unwrap();
+        for i in 0..64 {
+            std::fs::write(src.join(format!("part{i:02}")), vec![i as u8; 256 * 1024]).unwrap();
+        }
+
+        // The worker holds its last report of the copy until released.
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let (release, gate) = std::sync::mpsc::channel::<()>();
+        let worker = LocalOpsWorker::spawn_reporting(move |event| {
+            if matches!(&event, MasterEvent::LocalCopy(p) if p.done) {
+                let _ = gate.recv();
+            }
+            let _ = tx.send(event);
+        });
+        let mut app = host_tree_app(&dir).with_local_ops(worker.unwrap());
+        app.tree_explorer.clipboard = vec![src.clone()];
+        // Rows: the root, then big, then dest.
+        app.tree_explorer.local_tree.cursor_index = 2;
+        assert!(app.tree_paste().is_empty());
+
+        // The paste came straight back; the UI still takes events.
+        app.update(MasterEvent::log("still responsive".to_string()));
+        assert_eq!(app.logs.iter().last().unwrap(), "still responsive");
+        while let Ok(event) = rx.try_recv() {
+            assert!(!matches!(&event, MasterEvent::LocalCopy(p) if p.done));
+            app.update(event);
+        }
+        let copy = TaskId::Local(LOCAL_COPY_TASK);
+        assert!(app.tasks.get(&copy).unwrap().status.starts_with("Copying"));
+        release.send(()).unwrap();
+
+        // The end of the copy re-reads the destination.
+        let mut copied = false;
+        while let Some(event) = rx.blocking_recv() {
+            let listing = matches!(&event, MasterEvent::LocalListing(_));
+            copied |= matches!(&event, MasterEvent::LocalCopy(p) if p.done);
+            app.update(event);
+            if copied && listing {
+                break;
+            }
+        }
+        let done = format!(
+            "Copied 64 files, 16.0 MiB to {}",
+            dir.join("dest").display()
+        );
+        assert_eq!(app.tasks.get(&copy).unwrap().status, done);
+        let dest = &app.tree_explorer.local_tree.root_nodes[0]
+            .children
+            .as_ref()
+            .unwrap()[1];
+        let names: Vec<_> = dest.children.iter().flatten().map(|c| &c.name).collect();
+        assert_eq!(names, ["big"]);
+        assert_eq!(std::fs::read_dir(dir.join("dest/big")).unwrap().count(), 64);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn palette_runs_inserts_and_reveals() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+        fn pick(app: &mut App, query: &str) -> Option<String> {
+            app.open_palette();
+            for c in query.chars() {
+                app.handle_palette_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+            }
+            app.handle_palette_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))
+        }
+
+        // Argument-less commands are sent, and remembered.
+        let mut app = App::new();
+        assert_eq!(pick(&mut app, "pwd").as_deref(), Some("pwd"));
+        assert!(app.palette.is_none());
+        assert_eq!(app.history, ["pwd"]);
+
+        // Others wait on the prompt, with their arguments as a hint.
+        assert_eq!(pick(&mut app, "cance"), None);
+        assert_eq!(app.command_to_execute, "cancel ");
+        assert_eq!(app.input_hint.as_deref(), Some("<task id>"));
+        app.command_to_execute.push('7');
+        app.on_input_change();
+        assert_eq!(app.input_hint, None);
+
+        // Esc leaves everything as it was.
+        app.open_palette();
+        app.handle_palette_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(app.palette.is_none());
+        assert_eq!(app.command_to_execute, "cancel 7");
+
+        // A recent folder opens in the tree, one listing at a time.
+        app.update(MasterEvent::TreeData {
+            is_slave: true,
+            path: "drives".to_string(),
+            data: "/srv,/tmp".to_string(),
+        });
+        app.update(MasterEvent::RemoteCwd(Some("/srv/app/logs".into())));
+        assert_eq!(pick(&mut app, "logs"), None);
+        assert_eq!(app.active_tab, Tab::TreeExplorer);
+        assert!(app.tree_explorer.active_side);
+        assert_eq!(app.take_remote_request().as_deref(), Some("ListDir /srv"));
+        for (listing, next) in [
+            ("PATH|/srv;app|1|0|0;etc|1|0|0", Some("ListDir /srv/app")),
+            ("PATH|/srv/app;logs|1|0|0", Some("ListDir /srv/app/logs")),
+            ("PATH|/srv/app/logs;today.log|0|10|0", None),
+        ] {
+            app.update(MasterEvent::TreeData {
+                is_slave: true,
+                path: "dir_listing".to_string(),
+                data: listing.to_string(),
+            });
+            assert_eq!(app.take_remote_request().as_deref(), next);
+        }
+        // /srv, app, logs
+        assert_eq!(app.tree_explorer.slave_tree.cursor_index, 2);
+        assert_eq!(app.recent_paths[0], "/srv/app/logs");
+    }
+
+    #[test]
+    fn theme_command_switches_locally() {
+        let mut app = App::new();
+        app.command_to_execute = "theme ascii".to_string();
+        assert!(app.handle_enter().is_none());
+        assert_eq!(app.theme.name, ThemeName::Ascii);
+
+        app.command_to_execute = "theme neon".to_string();
+        assert!(app.handle_enter().is_none());
+        assert_eq!(app.theme.name, ThemeName::Ascii);
+        assert!(
+            app.logs
+                .iter()
+                .last()
+                .unwrap()
+                .starts_with("Error: unknown theme")
+        );
+
+        // Other commands still go to the master.
+        app.command_to_execute = "themes".to_string();
+        assert_eq!(app.handle_enter().as_deref(), Some("themes"));
+    }
+
+    #[test]
+    fn reduced_motion_keeps_suggestions_closed_while_typing() {
+        let mut app = App::new().with_reduced_motion(true);
+        app.command_to_execute = "Li".to_string();
+        app.handle_tab();
+        assert!(app.completion.active);
+        app.command_to_execute.push('s');
+        app.on_input_change();
+        assert!(!app.completion.active);
+        assert!(!app.needs_completion_update);
+
+        let mut app = App::new();
+        app.on_input_change();
+        assert!(app.needs_completion_update);
+    }
+
+    #[test]
+    fn slave_paths_complete_from_fetched_listings() {
+        let mut app = App::new();
+        app.update(MasterEvent::SlaveConnected("10.0.0.2:4000".into()));
+        app.update(MasterEvent::RemoteCwd(Some(r"C:\Users\bob".into())));
+        app.command_to_execute = r#"ListDir "Documents\Pro"#.to_string();
+        app.handle_tab();
+        assert!(!app.completion.active);
+        let request = app.take_remote_request();
+        assert_eq!(request.as_deref(), Some(r"complete C:\Users\bob\Documents"));
+        // Tabbing again while it is on its way asks nothing more.
+        app.handle_tab();
+        assert_eq!(app.take_remote_request(), None);
+
+        app.update(MasterEvent::RemoteListing {
+            dir: r"C:\Users\bob\Documents".into(),
+            data: r"PATH|C:\Users\bob\Documents;Projects|1|0|16;proposal.doc|0|9|32;x|0|1|32"
+                .into(),
+        });
+        assert!(app.needs_completion_update);
+        app.update_completion();
+        let names: Vec<&str> = app
+            .completion
+            .options
+            .iter()
+            .map(|o| o.display.as_str())
+            .collect();
+        assert_eq!(names, ["Projects", "proposal.doc"]);
+        assert_eq!(app.handle_enter(), None);
+        assert_eq!(app.command_to_execute, r#"ListDir "Documents\Projects\"#);
+
+        // Host paths still complete locally once the slave is gone.
+        app.update(MasterEvent::SlaveDisconnected("bob-pc".into()));
+        app.command_to_execute = "ListDir Pro".to_string();
+        app.handle_tab();
+        assert_eq!(app.take_remote_request(), None);
+    }
+
+    #[test]
+    fn esc_cancels_a_running_search_before_quitting() {
+        let mut app = App::new();
+        app.update(MasterEvent::TaskUpdate {
+            id: 7,
+            status: "Searching C:\\ for *.log (0 found)".to_string(),
+        });
+        app.update(MasterEvent::TaskUpdate {
+            id: 7,
+            status: "Searching C:\\ for *.log (64 found)".to_string(),
+        });
+        assert_eq!(app.running_searches, vec![7]);
+        assert_eq!(app.handle_esc(), Some("cancel 7".to_string()));
+        assert!(!app.exit);
+
+        app.update(MasterEvent::TaskUpdate {
+            id: 7,
+            status: "Cancelled search (64 found)".to_string(),
+        });
+        assert_eq!(app.handle_esc(), None);
+        assert!(app.exit);
+    }
+
+    #[test]
+    fn long_responses_are_paged() {
+        let mut app = App::new().with_paging(3, 100);
+        app.update(MasterEvent::Response {
+            id: 4,
+            text: "a\nb".to_string(),
+        });
+        app.update(MasterEvent::Response {
+            id: 5,
+            text: "1\n2\n3\n4".to_string(),
+        });
+        let tail: Vec<&str> = app.logs.iter().skip(2).map(LogEntry::text).collect();
+        assert_eq!(
+            tail,
+            ["- Slave: a\nb", "[ReqID 5] 4 lines — press Enter to view"]
+        );
+
+        // Enter on an empty prompt opens the page on screen.
+        app.log_view_height = 10;
+        assert!(app.handle_enter().is_none());
+        assert_eq!(app.pager.as_ref().map(Pager::request_id), Some(5));
+        app.handle_pager_key(crossterm::event::KeyCode::Char('q').into());
+        assert!(app.pager.is_none());
+
+        // Scrolled out of view: nothing to open.
+        app.log_view_height = 1;
+        app.log_scroll = 1;
+        app.autoscroll = false;
+        app.handle_enter();
+        assert!(app.pager.is_none());
+    }
+
+    #[test]
+    fn multi_line_entries_indent_under_their_time() {
+        let mut app = App::new().with_theme(Theme::named(ThemeName::Ascii));
+        app.update(MasterEvent::log("first\nsecond"));
+        let rows = draw_rows(&mut app, 100, 30);
+        let (x, y) = position(&rows, "first").unwrap();
+        assert_eq!(position(&rows, "second"), Some((x + 2, y + 1)));
+
+        app.toggle_log_times();
+        let rows = draw_rows(&mut app, 100, 30);
+        let (x_timed, y) = position(&rows, "first").unwrap();
+        assert_eq!(x_timed, x + pager::CLOCK_WIDTH);
+        assert_eq!(position(&rows, "second"), Some((x_timed + 2, y + 1)));
+        let clock: Vec<char> = rows[y][x..x_timed].chars().collect();
+        assert_eq!(
+            (clock[2], clock[5], clock[8], clock[12]),
+            (':', ':', '.', ' ')
+        );
+        assert!(
+            clock[..8]
+                .iter()
+                .filter(|c| **c != ':')
+                .all(char::is_ascii_digit)
+        );
+    }
+
+    #[test]
+    fn a_scrolled_log_stays_put_as_entries_arrive() {
+        let mut app = App::new();
+        for i in 0..20 {
+            app.update(MasterEvent::log(format!("line {i}")));
+        }
+        app.log_view_height = 5;
+        let shown = |app: &App| -> Vec<String> {
+            app.visible_log()
+                .iter()
+                .map(|row| row.line.to_string())
+                .collect()
+        };
+        assert_eq!(shown(&app).last().unwrap(), "line 19");
+
+        app.handle_up();
+        app.handle_up();
+        let before = shown(&app);
+        assert_eq!(before.last().unwrap(), "line 17");
+        // A three-line entry below the view pushes the scroll up by its
+        // three rows, so the same rows stay on screen.
+        app.update(MasterEvent::log("a\nb\nc"));
+        assert_eq!(app.log_scroll, 5);
+        assert_eq!(shown(&app), before);
+        // Timestamps take columns, not rows: the view does not move.
+        app.toggle_log_times();
+        assert_eq!(shown(&app), before);
+
+        // Back at the bottom, autoscroll follows new entries again.
+        for _ in 0..5 {
+            app.handle_down();
+        }
+        assert!(app.autoscroll);
+        assert_eq!(shown(&app)[2..], ["a", "b", "c"]);
+        app.update(MasterEvent::log("newest"));
+        assert_eq!(shown(&app).last().unwrap(), "newest");
+    }
+
+    #[test]
+    fn command_errors_are_logged_by_severity() {
+        let hint = error_line(&TixError::NotConnected);
+        assert_eq!(hint, "Hint: no slave connected");
+        assert_eq!(ErrorSeverity::of_line(&hint), Some(ErrorSeverity::Hint));
+
+        let typo = error_line(&TixError::InvalidCommandSyntax(
+            "Unknown command: lss".into(),
+        ));
+        assert_eq!(typo, "Hint: Unknown command: lss");
+
+        let corrupt = error_line(&TixError::ChecksumMismatch);
+        assert!(corrupt.starts_with("Data corruption: "));
+        assert_eq!(
+            ErrorSeverity::of_line(&corrupt),
+            Some(ErrorSeverity::Corruption)
+        );
+
+        let lost = error_line(&TixError::ChannelClosed);
+        assert_eq!(ErrorSeverity::of_line(&lost), Some(ErrorSeverity::Error));
+        assert_eq!(ErrorSeverity::of_line("[RECV] ReqID 3"), None);
+        assert_eq!(
+            ErrorSeverity::of_line("Slave crash: ReqID 4 panicked: boom"),
+            Some(ErrorSeverity::Crash)
+        );
+    }
+
+    #[test]
+    fn commands_are_sent_as_typed_and_written_down_redacted() {
+        use crate::config::DataConfig;
+        use crate::retention::DataStore;
+
+        let dir = std::env::temp_dir().join(format!("tix-app-history-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.log");
+        std::fs::write(&path, "1\tListDrives\n2\tpwd\n3\tListDrives\n").unwrap();
+        let data = DataConfig {
+            history: path.clone(),
+            ..DataConfig::default()
+        };
+        let store = DataStore::new(&data, dir.join("cache"));
+        let mut app = App::new().with_history_file(store.history_file().unwrap());
+        assert_eq!(app.history, ["ListDrives", "pwd"]);
+
+        // Executed first: the master gets the command untouched.
+        let typed = "ShellExecute net use Z: \\\\srv\\s password=hunter2";
+        app.command_to_execute = typed.to_string();
+        assert_eq!(app.handle_enter().as_deref(), Some(typed));
+        assert_eq!(app.history[0], typed);
+
+        // A confirmed command is written down only once it is sent.
+        app.command_to_execute = "SystemAction shutdown -p hunter2".to_string();
+        assert_eq!(app.handle_enter(), None);
+        let before = std::fs::read_to_string(&path).unwrap();
+        assert!(!before.contains("SystemAction"), "{}", before);
+        assert_eq!(
+            app.confirm_command(),
+            ["SystemAction shutdown -p hunter2 --confirm"]
+        );
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("hunter2"), "{}", text);
+        let written: Vec<&str> = text
+            .lines()
+            .filter_map(|line| Some(line.split_once('\t')?.1))
+            .collect();
+        assert_eq!(
+            written[3..],
+            [
+                "ShellExecute net use Z: \\\\srv\\s password=[REDACTED]",
+                "SystemAction shutdown -p [REDACTED]",
+            ]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,230 @@
+//! Splitting console input into arguments.
+//!
+//! Arguments are separated by whitespace. Double quotes group text with
+//! spaces into one argument and may start or stop mid-word
+//! (`--name="My File"` is one argument). Backslashes follow the
+//! Windows command-line convention so paths need no escaping:
+//!
+//! - a backslash is literal unless a run of them ends in `"`;
+//! - `2n` backslashes before `"` give `n` backslashes and the quote
+//!   opens or closes a quoted section;
+//! - `2n + 1` backslashes before `"` give `n` backslashes and a literal
+//!   `"`.
+
+/// Split `input` into arguments. Fails on an unterminated quote.
+pub fn split_args(input: &str) -> Result<Vec<String>, String> {
+    Ok(scan(input, |_| false)?
+        .into_iter()
+        .map(|a| a.text)
+        .collect())
+}
+
+/// Split the options in front of a bare `--` into arguments and return
+/// the text after it as typed, for commands whose tail is handed on
+/// verbatim (a shell command line keeps its own quoting). Without a
+/// separator every argument is an option and the tail is `None`.
+pub fn split_options(input: &str) -> Result<(Vec<String>, Option<&str>), String> {
+    let is_separator = |arg: &Arg| &input[arg.start..arg.end] == "--";
+    let mut args = scan(input, is_separator)?;
+    let tail = match args.last() {
+        Some(last) if is_separator(last) => {
+            let tail = input[last.end..].trim();
+            args.pop();
+            Some(tail)
+        }
+        _ => None,
+    };
+    Ok((args.into_iter().map(|a| a.text).collect(), tail))
+}
+
+/// Split `input` at every `;` outside quotes and each part into
+/// arguments, for commands that take a list of steps. Empty parts are
+/// dropped.
+pub fn split_steps(input: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut backslashes = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '\\' => {
+                backslashes += 1;
+                continue;
+            }
+            '"' if backslashes % 2 == 0 => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                parts.push(&input[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        backslashes = 0;
+    }
+    parts.push(&input[start..]);
+    let mut steps = Vec::new();
+    for part in parts {
+        let args = split_args(part)?;
+        if !args.is_empty() {
+            steps.push(args);
+        }
+    }
+    Ok(steps)
+}
+
+/// One argument and the bytes of `input` it was read from.
+struct Arg {
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+/// Read arguments until the input ends or `stop` accepts one.
+fn scan(input: &str, stop: impl Fn(&Arg) -> bool) -> Result<Vec<Arg>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut start = None;
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() && !in_quotes => {
+                if let Some(begin) = start.take() {
+                    let arg = Arg {
+                        text: std::mem::take(&mut current),
+                        start: begin,
+                        end: i,
+                    };
+                    let done = stop(&arg);
+                    args.push(arg);
+                    if done {
+                        return Ok(args);
+                    }
+                }
+                continue;
+            }
+            _ => {
+                start.get_or_insert(i);
+            }
+        }
+        match c {
+            '\\' => {
+                let mut run = 1;
+                while chars.peek().map(|&(_, c)| c) == Some('\\') {
+                    chars.next();
+                    run += 1;
+                }
+                if chars.peek().map(|&(_, c)| c) == Some('"') {
+                    current.extend(std::iter::repeat_n('\\', run / 2));
+                    if run % 2 == 1 {
+                        chars.next();
+                        current.push('"');
+                    }
+                } else {
+                    current.extend(std::iter::repeat_n('\\', run));
+                }
+            }
+            '"' => in_quotes = !in_quotes,
+            c => current.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("unterminated quote".to_string());
+    }
+    if let Some(begin) = start {
+        args.push(Arg {
+            text: current,
+            start: begin,
+            end: input.len(),
+        });
+    }
+    Ok(args)
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(input: &str) -> Vec<String> {
+        split_args(input).unwrap()
+    }
+
+    #[test]
+    fn plain_words_and_extra_whitespace() {
+        assert_eq!(split("  a   b\tc  "), ["a", "b", "c"]);
+        assert!(split("   ").is_empty());
+        assert!(split("").is_empty());
+    }
+
+    #[test]
+    fn quoted_paths_keep_spaces_and_backslashes() {
+        assert_eq!(
+            split(r#""C:\Program Files\app" C:\dest"#),
+            [r"C:\Program Files\app", r"C:\dest"]
+        );
+        // Quotes may sit mid-word.
+        assert_eq!(split(r#"--name="My File" x"#), ["--name=My File", "x"]);
+        // Trailing spaces inside quotes are kept, outside they are not.
+        assert_eq!(
+            split(r#""name with trailing  "   "#),
+            ["name with trailing  "]
+        );
+        // An empty pair of quotes is an empty argument.
+        assert_eq!(split(r#"a "" b"#), ["a", "", "b"]);
+    }
+
+    #[test]
+    fn embedded_quotes_and_backslash_runs() {
+        assert_eq!(split(r#"say \"hi\""#), ["say", r#""hi""#]);
+        assert_eq!(split(r#""a \"quoted\" word""#), [r#"a "quoted" word"#]);
+        // A directory with a trailing backslash inside quotes.
+        assert_eq!(split(r#""C:\My Dir\\" next"#), [r"C:\My Dir\", "next"]);
+        assert_eq!(split(r"\\server\share"), [r"\\server\share"]);
+    }
+
+    #[test]
+    fn unterminated_quote_is_an_error() {
+        assert!(split_args(r#"Copy "C:\Program Files"#).is_err());
+    }
+
+    #[test]
+    fn steps_split_at_unquoted_semicolons() {
+        let steps =
+            split_steps(r#"mkdir C:\new; move "C:\a;b.txt" C:\new ;; delete C:\old;"#).unwrap();
+        assert_eq!(
+            steps,
+            [
+                vec!["mkdir", r"C:\new"],
+                vec!["move", r"C:\a;b.txt", r"C:\new"],
+                vec!["delete", r"C:\old"],
+            ]
+        );
+        // An escaped quote does not open a quoted section.
+        assert_eq!(
+            split_steps(r#"a \"; b"#).unwrap(),
+            [vec!["a", "\""], vec!["b"]]
+        );
+        assert!(split_steps(r#"a "b; c"#).is_err());
+        assert!(split_steps(" ; ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn options_stop_at_the_separator() {
+        let (opts, tail) = split_options(r#"-d "C:\My Proj" -e A=1 -- echo "a -- b"  "#).unwrap();
+        assert_eq!(opts, ["-d", r"C:\My Proj", "-e", "A=1"]);
+        assert_eq!(tail, Some(r#"echo "a -- b""#));
+
+        // The tail is not tokenized, so its quoting is its own business.
+        let (opts, tail) = split_options(r#"-- echo "unbalanced"#).unwrap();
+        assert!(opts.is_empty());
+        assert_eq!(tail, Some(r#"echo "unbalanced"#));
+
+        // A quoted "--" is an argument, not the separator.
+        let (opts, tail) = split_options(r#"-e "--" x"#).unwrap();
+        assert_eq!(opts, ["-e", "--", "x"]);
+        assert_eq!(tail, None);
+        assert_eq!(split_options("-d x --").unwrap().1, Some(""));
+    }
+}
@@ -0,0 +1,115 @@
+//! RDP control bridge — lets `tix-rdp-gui --via-master` reach the slave
+//! through the master's existing connection.
+//!
+//! The bridge accepts one viewer at a time on its own port and speaks the
+//! regular tix-core packet protocol with it. Packets from the viewer are
+//! handed to [`TixMaster`](crate::master::TixMaster), which re-issues them
+//! to the slave under its own request IDs; responses travel back the same
+//! way. Only control traffic (`ScreenStart`, `ScreenStop`, `ScreenMode`,
+//! `SendSas`, `ScreenDictionary`, `ScreenPreflight`, `ScreenSharing`,
+//! input) and files
+//! dropped onto the viewer window (`FileWrite`) flow through here — the
+//! screen stream itself stays on UDP. Input is not tracked, so a
+//! view-only refusal from the slave reaches the viewer under request
+//! ID 0.
+//!
+//! A viewer's `Hello` goes no further than the master, which answers it
+//! with the slave's, so the viewer learns which features the slave has.
+
+use tix_core::{Command, Connection, ConnectionInfo, Packet};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+use crate::app::MasterEvent;
+
+/// Default port the bridge listens on (control port + 1).
+pub const DEFAULT_BRIDGE_PORT: u16 = 4322;
+
+/// Commands a viewer is allowed to send through the bridge.
+pub fn is_bridged_command(cmd: Command) -> bool {
+    matches!(
+        cmd,
+        Command::ScreenStart
+            | Command::ScreenStop
+            | Command::InputMouse
+            | Command::InputKeyboard
+            | Command::ScreenMode
+            | Command::SendSas
+            | Command::ScreenDictionary
+            | Command::ScreenPreflight
+            | Command::ScreenSharing
+            | Command::FileWrite
+    )
+}
+
+/// Master-side handles for a running bridge.
+pub struct BridgeHandle {
+    /// Packets received from the viewer.
+    pub from_viewer: mpsc::UnboundedReceiver<Packet>,
+    /// Packets to deliver to the viewer.
+    pub to_viewer: mpsc::UnboundedSender<Packet>,
+}
+
+/// Bind the bridge listener and spawn its accept loop.
+pub async fn spawn(
+    conn_info: ConnectionInfo,
+    ui_tx: mpsc::UnboundedSender<MasterEvent>,
+) -> Result<BridgeHandle, std::io::Error> {
+    let listener = TcpListener::bind(conn_info.to_socket_string()).await?;
+    let (from_viewer_tx, from_viewer) = mpsc::unbounded_channel();
+    let (to_viewer, mut to_viewer_rx) = mpsc::unbounded_channel::<Packet>();
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(pair) => pair,
+                Err(e) => {
+                    let _ = ui_tx.send(MasterEvent::log(format!("[RDP ] Accept failed: {}", e)));
+                    continue;
+                }
+            };
+            let _ = ui_tx.send(MasterEvent::log(format!(
+                "[RDP ] Viewer connected from {}",
+                peer
+            )));
+
+            // Responses addressed to a previous viewer are meaningless now
+            while to_viewer_rx.try_recv().is_ok() {}
+
+            let mut conn = Connection::new(stream);
+            loop {
+                tokio::select! {
+                    pkt = conn.recv() => match pkt {
+                        Some(pkt) => {
+                            if from_viewer_tx.send(pkt).is_err() {
+                                return;
+                            }
+                        }
+                        None => break,
+                    },
+                    Some(pkt) = to_viewer_rx.recv() => {
+                        if conn.send(pkt).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            let _ = ui_tx.send(MasterEvent::log(format!(
+                "[RDP ] Viewer {} disconnected",
+                peer
+            )));
+
+            // Don't leave the slave streaming to a viewer that is gone.
+            // Request ID 0 tells the master no reply is expected.
+            if let Ok(stop) = Packet::new_command(0, Command::ScreenStop, Vec::new()) {
+                let _ = from_viewer_tx.send(stop);
+            }
+        }
+    });
+
+    Ok(BridgeHandle {
+        from_viewer,
+        to_viewer,
+    })
+}
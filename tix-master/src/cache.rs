@@ -0,0 +1,375 @@
+//! Answers to queries the slave need not be asked twice.
+//!
+//! The tree explorer asks for the drive list whenever it opens on an
+//! empty tree, and every surface that shows the host asks for
+//! `sysinfo`. The [`ResponseCache`] keeps the answers of commands the
+//! registry marks `cached` (see [`crate::commands`]), keyed by slave,
+//! command and a hash of the request payload, and the master answers
+//! the next identical request from it until the entry's time is up.
+//! With `revalidate`, a hit past half that time is also sent on to the
+//! slave so its fresh answer replaces the entry in the background.
+//!
+//! Entries go when a command that invalidates them is sent and again
+//! when it finishes, on `invalidate [command]`, and all at once when a
+//! slave with another ID connects: the cache never answers for a
+//! different machine. A slave that has not said who it is gets none.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use tix_core::Command;
+
+use crate::commands::{self, COMMANDS, CommandSpec};
+use crate::config::CacheConfig;
+
+/// Answers kept by default.
+pub const DEFAULT_MAX_ENTRIES: usize = 64;
+
+/// Where an answer is kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    slave: String,
+    command: &'static str,
+    payload: [u8; 32],
+}
+
+impl CacheKey {
+    /// The registry name of the command, e.g. `ListDrives`.
+    pub fn command(&self) -> &'static str {
+        self.command
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    command: Command,
+    response: Vec<u8>,
+    stored: Instant,
+    ttl: Duration,
+}
+
+/// A cached answer still good to serve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit<'a> {
+    /// What the slave answered with, as for [`Command`] responses.
+    pub command: Command,
+    pub response: &'a [u8],
+    /// Time since the slave gave it.
+    pub age: Duration,
+    /// Past half its time: ask the slave again as well.
+    pub revalidate: bool,
+}
+
+/// The master's cache of query answers.
+#[derive(Debug)]
+pub struct ResponseCache {
+    /// 0 keeps nothing.
+    max_entries: usize,
+    revalidate: bool,
+    /// Lifetimes that override the registry's, by command name.
+    ttl_secs: BTreeMap<String, u64>,
+    /// The slave the entries came from.
+    slave: Option<String>,
+    entries: HashMap<CacheKey, Entry>,
+    hits: u64,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(&CacheConfig::default())
+    }
+}
+
+impl ResponseCache {
+    pub fn new(config: &CacheConfig) -> Self {
+        Self {
+            max_entries: config.max_entries,
+            revalidate: config.revalidate,
+            ttl_secs: config.ttl_secs.clone(),
+            slave: None,
+            entries: HashMap::new(),
+            hits: 0,
+        }
+    }
+
+    /// Answers held.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Requests answered from the cache so far.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// How long `spec`'s answers stay good; `None` when they are never
+    /// kept.
+    fn ttl(&self, spec: &CommandSpec) -> Option<Duration> {
+        self.ttl_secs
+            .get(spec.name)
+            .map(|&secs| Duration::from_secs(secs))
+            .or(spec.cache)
+            .filter(|ttl| !ttl.is_zero())
+    }
+
+    /// Where the answer to `input`, sent to `slave` with `payload`, is
+    /// kept; `None` when it never is: the command is not cached, the
+    /// slave has no ID, or the cache is off.
+    pub fn key(&self, slave: Option<&str>, input: &str, payload: &[u8]) -> Option<CacheKey> {
+        let slave = slave.filter(|id| !id.is_empty())?;
+        if self.max_entries == 0 {
+            return None;
+        }
+        let spec = commands::lookup(input)?;
+        self.ttl(spec)?;
+        Some(CacheKey {
+            slave: slave.to_string(),
+            command: spec.name,
+            payload: *blake3::hash(payload).as_bytes(),
+        })
+    }
+
+    /// The answer kept under `key`, if it is still good at `now`.
+    pub fn get(&mut self, key: &CacheKey, now: Instant) -> Option<Hit<'_>> {
+        if self.slave.as_deref() != Some(key.slave.as_str()) {
+            return None;
+        }
+        let age = now.saturating_duration_since(self.entries.get(key)?.stored);
+        if age >= self.entries[key].ttl {
+            self.entries.remove(key);
+            return None;
+        }
+        self.hits += 1;
+        let entry = &self.entries[key];
+        Some(Hit {
+            command: entry.command,
+            response: &entry.response,
+            age,
+            revalidate: self.revalidate && age * 2 >= entry.ttl,
+        })
+    }
+
+    /// Keep `response` under `key`, dropping the oldest answers past
+    /// the limit. An answer from another slave than the bound one is
+    /// not kept.
+    pub fn store(&mut self, key: CacheKey, command: Command, response: Vec<u8>, now: Instant) {
+        let Some(ttl) = commands::cached(key.command).and_then(|spec| self.ttl(spec)) else {
+            return;
+        };
+        if self.slave.as_deref() != Some(key.slave.as_str()) || self.max_entries == 0 {
+            return;
+        }
+        self.entries
+            .retain(|_, e| now.saturating_duration_since(e.stored) < e.ttl);
+        self.entries.insert(
+            key,
+            Entry {
+                command,
+                response,
+                stored: now,
+                ttl,
+            },
+        );
+        while self.entries.len() > self.max_entries {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.stored)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+
+    /// `slave` identified itself: forget everything when the answers
+    /// came from another one.
+    pub fn bind(&mut self, slave: &str) {
+        if self.slave.as_deref() != Some(slave) {
+            self.entries.clear();
+            self.slave = Some(slave.to_string());
+        }
+    }
+
+    /// `spec` was sent or finished: drop the answers it makes stale.
+    /// Returns how many went.
+    pub fn changed_by(&mut self, spec: &CommandSpec) -> usize {
+        spec.invalidates
+            .iter()
+            .map(|name| self.drop_command(name))
+            .sum()
+    }
+
+    /// `invalidate [command]`: drop every answer, or those of one
+    /// cached command. Returns how many went.
+    pub fn invalidate(&mut self, command: Option<&str>) -> Result<usize, String> {
+        let Some(name) = command else {
+            let dropped = self.entries.len();
+            self.entries.clear();
+            return Ok(dropped);
+        };
+        let spec = commands::cached(name).ok_or_else(|| {
+            let cached: Vec<&str> = COMMANDS
+                .iter()
+                .filter(|c| c.cache.is_some())
+                .map(|c| c.name)
+                .collect();
+            format!(
+                "{} is not cached; cached commands: {}",
+                name,
+                cached.join(", ")
+            )
+        })?;
+        Ok(self.drop_command(spec.name))
+    }
+
+    fn drop_command(&mut self, name: &str) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|key, _| key.command != name);
+        before - self.entries.len()
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLAVE: &str = "9f2c";
+
+    fn bound(config: CacheConfig) -> ResponseCache {
+        let mut cache = ResponseCache::new(&config);
+        cache.bind(SLAVE);
+        cache
+    }
+
+    fn drives(cache: &mut ResponseCache, now: Instant) -> CacheKey {
+        let key = cache.key(Some(SLAVE), "ListDrives", &[]).unwrap();
+        cache.store(key.clone(), Command::ListDrives, b"C:\\|D:\\".to_vec(), now);
+        key
+    }
+
+    #[test]
+    fn answers_expire_and_are_revalidated_past_half_time() {
+        let mut cache = bound(CacheConfig::default());
+        let t0 = Instant::now();
+        let key = drives(&mut cache, t0);
+
+        let hit = cache.get(&key, t0 + Duration::from_secs(10)).unwrap();
+        assert_eq!(hit.response, b"C:\\|D:\\");
+        assert_eq!(hit.command, Command::ListDrives);
+        assert!(!hit.revalidate);
+        assert!(
+            cache
+                .get(&key, t0 + Duration::from_secs(30))
+                .unwrap()
+                .revalidate
+        );
+        assert!(cache.get(&key, t0 + Duration::from_secs(60)).is_none());
+        assert_eq!((cache.len(), cache.hits()), (0, 2));
+
+        // The config overrides the registry; 0 never keeps it.
+        let mut config = CacheConfig::default();
+        config.ttl_secs.insert("ListDrives".to_string(), 5);
+        config.ttl_secs.insert("sysinfo".to_string(), 0);
+        let mut cache = bound(config);
+        let key = drives(&mut cache, t0);
+        assert!(cache.get(&key, t0 + Duration::from_secs(5)).is_none());
+        assert!(cache.key(Some(SLAVE), "sysinfo", &[]).is_none());
+    }
+
+    #[test]
+    fn commands_that_change_the_slave_drop_what_they_make_stale() {
+        let mut cache = bound(CacheConfig::default());
+        let now = Instant::now();
+        let key = drives(&mut cache, now);
+        let info = cache.key(Some(SLAVE), "sysinfo", &[]).unwrap();
+        cache.store(info.clone(), Command::SystemInfo, vec![1], now);
+
+        assert_eq!(cache.changed_by(commands::lookup("Ping").unwrap()), 0);
+        assert_eq!(
+            cache.changed_by(commands::lookup(r"Delete C:\old").unwrap()),
+            2
+        );
+        assert!(cache.get(&key, now).is_none() && cache.get(&info, now).is_none());
+
+        drives(&mut cache, now);
+        cache.store(info, Command::SystemInfo, vec![1], now);
+        assert_eq!(cache.invalidate(Some("ListDrives")), Ok(1));
+        assert_eq!(cache.invalidate(None), Ok(1));
+        assert_eq!(
+            cache.invalidate(Some("ListDir")).unwrap_err(),
+            "ListDir is not cached; cached commands: ListDrives, sysinfo"
+        );
+    }
+
+    #[test]
+    fn another_slave_never_sees_the_answers() {
+        let mut cache = bound(CacheConfig::default());
+        let now = Instant::now();
+        let key = drives(&mut cache, now);
+
+        // The same slave back again keeps them.
+        cache.bind(SLAVE);
+        assert!(cache.get(&key, now).is_some());
+
+        cache.bind("77aa");
+        assert_eq!(cache.len(), 0);
+        assert!(cache.get(&key, now).is_none());
+        // A late answer from the old slave is not kept either.
+        cache.store(key.clone(), Command::ListDrives, vec![1], now);
+        assert_eq!(cache.len(), 0);
+        assert_ne!(cache.key(Some("77aa"), "ListDrives", &[]), Some(key));
+    }
+
+    #[test]
+    fn uncached_commands_bypass_it() {
+        let mut cache = bound(CacheConfig::default());
+        assert!(cache.key(Some(SLAVE), r"ListDir C:\", b"C:\\").is_none());
+        assert!(cache.key(Some(SLAVE), "Ping", &[]).is_none());
+        assert!(cache.key(Some(SLAVE), r"Delete C:\a", &[]).is_none());
+        // No ID, no cache.
+        assert!(cache.key(None, "ListDrives", &[]).is_none());
+        assert!(cache.key(Some(""), "ListDrives", &[]).is_none());
+
+        let off = ResponseCache::new(&CacheConfig {
+            max_entries: 0,
+            ..CacheConfig::default()
+        });
+        assert!(off.key(Some(SLAVE), "ListDrives", &[]).is_none());
+
+        // Payloads are part of the key.
+        let a = cache.key(Some(SLAVE), "sysinfo", b"a").unwrap();
+        let b = cache.key(Some(SLAVE), "sysinfo", b"b").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a.command(), "sysinfo");
+        cache.store(a.clone(), Command::SystemInfo, vec![1], Instant::now());
+        assert!(cache.get(&b, Instant::now()).is_none());
+    }
+
+    #[test]
+    fn the_oldest_answers_go_first() {
+        let mut cache = bound(CacheConfig {
+            max_entries: 2,
+            ..CacheConfig::default()
+        });
+        let t0 = Instant::now();
+        let keys: Vec<CacheKey> = (0..3u8)
+            .map(|i| {
+                let key = cache.key(Some(SLAVE), "sysinfo", &[i]).unwrap();
+                let at = t0 + Duration::from_millis(i as u64);
+                cache.store(key.clone(), Command::SystemInfo, vec![i], at);
+                key
+            })
+            .collect();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&keys[0], t0).is_none());
+        assert!(cache.get(&keys[2], t0).is_some());
+    }
+}
@@ -0,0 +1,609 @@
+//! The console's commands, and which of them need confirming.
+//!
+//! Every command the console offers is a [`CommandSpec`] in
+//! [`COMMANDS`], which also feeds the suggestion popup and the command
+//! palette, with its arguments and a line of help. Commands that
+//! cannot be taken back, such as deleting files or shutting the slave
+//! down, carry a [`Risk`] other than `Safe`. A new entry is safe unless
+//! it says otherwise.
+//!
+//! The [`ConfirmGate`] holds those back. The master refuses a
+//! destructive command unless it ends in `--confirm`. The console asks
+//! first and adds the flag. `exec` fails at once unless it was given
+//! `--yes`, and so does anything else that feeds the master commands
+//! without a person watching. `confirm_destructive = false` under
+//! `[commands]` turns all of it off.
+//!
+//! The gate also weighs what an `Upload` would send against the
+//! `[paste_guard]` limits (see [`tix_core::paste_guard`]): a large one
+//! needs confirming the same way, whatever `confirm_destructive` says,
+//! and one over the hard limit is refused outright.
+//!
+//! A query whose answer changes slowly, such as the drive list, is
+//! marked `cached` with how long its answer stays good; the master's
+//! [`ResponseCache`](crate::cache::ResponseCache) answers it again from
+//! memory until then. Commands that change what such a query reports
+//! name it in `invalidates`.
+//!
+//! A command that only some slaves can run names the [`Feature`] it
+//! `needs`; the console dims it, and refuses it, when the connected
+//! slave's `Hello` left that feature out.
+
+use std::path::Path;
+use std::time::Duration;
+
+use tix_core::Feature;
+use tix_core::format::format_bytes;
+use tix_core::paste_guard::{self, PasteGuard, Verdict};
+
+use crate::args::split_args;
+use crate::config::CommandsConfig;
+use crate::services::CONFIRM_FLAG;
+
+/// What sending a command can do to the slave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Risk {
+    Safe,
+    /// Always destructive.
+    Destructive,
+    /// Destructive when one of these arguments is given.
+    DestructiveWith(&'static [&'static str]),
+}
+
+/// One console command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandSpec {
+    /// What the command starts with; may be two words (`reg query`).
+    pub name: &'static str,
+    /// What follows the name, `<required>` and `[optional]`; empty when
+    /// the command takes nothing.
+    pub args: &'static str,
+    /// One line on what the command does.
+    pub help: &'static str,
+    pub risk: Risk,
+    /// How long the master may reuse the answer; `None` when it always
+    /// asks the slave.
+    pub cache: Option<Duration>,
+    /// Cached commands whose answers are stale once this one ran.
+    pub invalidates: &'static [&'static str],
+    /// The optional slave feature the command needs, if any.
+    pub feature: Option<Feature>,
+}
+
+impl CommandSpec {
+    const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            args: "",
+            help: "",
+            risk: Risk::Safe,
+            cache: None,
+            invalidates: &[],
+            feature: None,
+        }
+    }
+
+    const fn about(mut self, args: &'static str, help: &'static str) -> Self {
+        self.args = args;
+        self.help = help;
+        self
+    }
+
+    const fn destructive(mut self) -> Self {
+        self.risk = Risk::Destructive;
+        self
+    }
+
+    const fn destructive_with(mut self, args: &'static [&'static str]) -> Self {
+        self.risk = Risk::DestructiveWith(args);
+        self
+    }
+
+    const fn cached(mut self, secs: u64) -> Self {
+        self.cache = Some(Duration::from_secs(secs));
+        self
+    }
+
+    const fn invalidates(mut self, commands: &'static [&'static str]) -> Self {
+        self.invalidates = commands;
+        self
+    }
+
+    const fn needs(mut self, feature: Feature) -> Self {
+        self.feature = Some(feature);
+        self
+    }
+
+    /// The arguments of `input`, when it is this command.
+    fn args<'a>(&self, input: &'a str) -> Option<&'a str> {
+        let rest = input.trim().strip_prefix(self.name)?;
+        (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(rest)
+    }
+
+    /// Whether this command with arguments `rest` is destructive.
+    fn is_destructive(&self, rest: &str) -> bool {
+        match self.risk {
+            Risk::Safe => false,
+            Risk::Destructive => true,
+            Risk::DestructiveWith(flags) => {
+                let args = split_args(rest)
+                    .unwrap_or_else(|_| rest.split_whitespace().map(String::from).collect());
+                args.iter().any(|arg| flags.contains(&arg.as_str()))
+            }
+        }
+    }
+}
+
+/// What a command that writes to the slave's disks or changes its
+/// state makes stale: free space, session traffic and path locks.
+const CHANGES_SLAVE: &[&str] = &["ListDrives", "sysinfo"];
+
+/// Every console command, in the order the suggestion popup lists them.
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("Ping").about("", "Check the slave answers"),
+    CommandSpec::new("ShellExecute")
+        .about(
+            "[-s <shell>] [-d <dir>] [-e KEY=VALUE]... [-t <secs>] -- <command>",
+            "Run a command in the slave's shell",
+        )
+        .invalidates(CHANGES_SLAVE),
+    CommandSpec::new("Copy")
+        .about(
+            "[-r] [-f] <source> <destination>",
+            "Copy files on the slave",
+        )
+        .destructive_with(&["--overwrite", "-f"])
+        .invalidates(CHANGES_SLAVE),
+    CommandSpec::new("ListDrives")
+        .about("", "List the slave's drives")
+        .cached(60),
+    // The tree explorer keeps the listings it has; nothing else here.
+    CommandSpec::new("ListDir").about("<path>", "List a slave directory"),
+    CommandSpec::new("Upload")
+        .about("<local>|<remote>", "Copy a file or folder to the slave")
+        .invalidates(CHANGES_SLAVE),
+    CommandSpec::new("Download").about("<remote>|<local>", "Copy a file from the slave"),
+    CommandSpec::new("Delete")
+        .about("[--permanent] <path>...", "Recycle or delete slave paths")
+        .destructive()
+        .invalidates(CHANGES_SLAVE),
+    CommandSpec::new("TrashRestore")
+        .about("<path>...", "Restore paths from the Recycle Bin")
+        .invalidates(CHANGES_SLAVE)
+        .needs(Feature::Trash),
+    CommandSpec::new("Transaction")
+        .about(
+            "<step> ; <step>...",
+            "File steps on the slave that all happen or none do",
+        )
+        .destructive()
+        .invalidates(CHANGES_SLAVE),
+    CommandSpec::new("SystemAction")
+        .about(
+            "<shutdown|reboot|sleep>",
+            "Shut down, reboot or suspend the slave",
+        )
+        .destructive_with(&["shutdown", "reboot"])
+        .invalidates(CHANGES_SLAVE),
+    CommandSpec::new("reg query")
+        .about("<path> [value]", "Read a registry key or value")
+        .needs(Feature::Registry),
+    CommandSpec::new("startup")
+        .about("", "List programs started at logon")
+        .needs(Feature::Registry),
+    CommandSpec::new("services")
+        .about("[filter]", "List Windows services")
+        .needs(Feature::Services),
+    // Protected services have their own guard (see `crate::services`).
+    CommandSpec::new("service")
+        .about(
+            "<name> start|stop|restart [-t <secs>]",
+            "Start, stop or restart a service",
+        )
+        .invalidates(CHANGES_SLAVE)
+        .needs(Feature::Services),
+    CommandSpec::new("net").about(
+        "if | routes | resolve <name> | check <host:port>",
+        "Network diagnostics from the slave",
+    ),
+    // Traffic moves with every command; nothing polls it more often.
+    CommandSpec::new("sysinfo")
+        .about("", "Host name and session traffic")
+        .cached(10),
+    CommandSpec::new("selftest").about("", "Check the slave can capture, inject and write"),
+    CommandSpec::new("tasks").about("", "Tasks running on the slave"),
+    CommandSpec::new("ping").about("[-c <count>] [-i <ms>]", "Measure round trips"),
+    CommandSpec::new("update")
+        .about(
+            "<binary> <version> [--force] [--no-restart]",
+            "Push a new slave build",
+        )
+        .invalidates(CHANGES_SLAVE),
+    CommandSpec::new("name").about("[current|<id>] [<new name>]", "List or name known slaves"),
+    CommandSpec::new("watch").about("[<path> [<interval>]]", "Follow a remote file"),
+    CommandSpec::new("unwatch").about("<task id>", "Stop following a file"),
+    CommandSpec::new("find").about(
+        "[--max <n>] [--depth <n>] <dir> <pattern>",
+        "Find files by name",
+    ),
+    CommandSpec::new("eventlog")
+        .about(
+            "[channel] [--errors|--warnings] [--since <age>] [-n <count>]",
+            "Recent event log entries",
+        )
+        .needs(Feature::EventLog),
+    CommandSpec::new("schedule")
+        .about(
+            "add|list|results|del ...",
+            "Commands the slave runs on an interval",
+        )
+        .destructive_with(&["del", "delete", "rm"])
+        .invalidates(CHANGES_SLAVE),
+    CommandSpec::new("cd").about("[path]", "Set the slave working directory"),
+    CommandSpec::new("pwd").about("", "Print the slave working directory"),
+    CommandSpec::new("cancel").about("<task id>", "Cancel a running task"),
+    CommandSpec::new("transfers").about("", "Queued, running and recent transfers"),
+    CommandSpec::new("transfer").about(
+        "pause|resume|cancel|retry <id>",
+        "Control one transfer item",
+    ),
+    CommandSpec::new("stats").about("", "Pending requests and late responses"),
+    CommandSpec::new("invalidate").about("[command]", "Forget cached answers"),
+    CommandSpec::new("debug wire").about(
+        "[on|off] [-b <bytes>] [-c <file>] [--slave]",
+        "Log every packet sent and received",
+    ),
+    CommandSpec::new("save-output").about(
+        "<req_id> <file> [--stderr]",
+        "Write a command's raw output to a file",
+    ),
+    CommandSpec::new("theme").about("[name]", "Switch the console theme"),
+    CommandSpec::new("role").about("[<name> [<password>]]", "Show or switch the operator role"),
+    CommandSpec::new("Exit").about("", "Quit the console"),
+];
+
+/// The names of [`COMMANDS`], for completion.
+pub fn names() -> Vec<String> {
+    COMMANDS.iter().map(|c| c.name.to_string()).collect()
+}
+
+/// The command `input` invokes, if it is one of [`COMMANDS`].
+pub fn lookup(input: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|c| c.args(input).is_some())
+}
+
+/// The cached command called `name`.
+pub fn cached(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|c| c.name == name && c.cache.is_some())
+}
+
+/// Whether `input` is a destructive command, confirmed or not.
+pub fn is_destructive(input: &str) -> bool {
+    let input = strip_confirmation(input).unwrap_or(input);
+    lookup(input).is_some_and(|spec| {
+        spec.args(input)
+            .is_some_and(|rest| spec.is_destructive(rest))
+    })
+}
+
+/// `input` without its trailing `--confirm`, if it has one.
+fn strip_confirmation(input: &str) -> Option<&str> {
+    input
+        .trim_end()
+        .strip_suffix(CONFIRM_FLAG)
+        .filter(|rest| rest.ends_with(char::is_whitespace))
+        .map(str::trim_end)
+}
+
+/// `input` marked as confirmed.
+pub fn confirmed(input: &str) -> String {
+    format!("{} {}", input.trim_end(), CONFIRM_FLAG)
+}
+
+/// The local source of an `Upload` and the bytes it sends, when `input`
+/// is one.
+fn upload_source(input: &str) -> Option<(&str, u64)> {
+    let input = strip_confirmation(input).unwrap_or(input);
+    let spec = lookup(input).filter(|spec| spec.name == "Upload")?;
+    let (local, _) = spec.args(input)?.split_once('|')?;
+    let local = local.trim();
+    Some((local, paste_guard::path_size(Path::new(local))))
+}
+
+// ── Gate ─────────────────────────────────────────────────────────
+
+/// Holds destructive commands and large uploads back until someone
+/// agrees to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfirmGate {
+    enabled: bool,
+    paste: PasteGuard,
+}
+
+impl Default for ConfirmGate {
+    fn default() -> Self {
+        Self::new(&CommandsConfig::default())
+    }
+}
+
+impl ConfirmGate {
+    pub fn new(config: &CommandsConfig) -> Self {
+        Self {
+            enabled: config.confirm_destructive,
+            paste: PasteGuard::default(),
+        }
+    }
+
+    /// Weigh uploads against `guard` rather than the default limits.
+    pub fn with_paste_guard(mut self, guard: PasteGuard) -> Self {
+        self.paste = guard;
+        self
+    }
+
+    /// The limits uploads are weighed against.
+    pub fn paste_guard(&self) -> PasteGuard {
+        self.paste
+    }
+
+    /// Whether `input` is destructive and nobody has agreed to it yet.
+    pub fn needs_confirmation(&self, input: &str) -> bool {
+        self.enabled && strip_confirmation(input).is_none() && is_destructive(input)
+    }
+
+    /// For the console: the question to ask before sending `input` when
+    /// it is a large `Upload`, with the time it should take at `rate`
+    /// bytes a second, or why it is refused.
+    pub fn upload_confirmation(
+        &self,
+        input: &str,
+        rate: Option<u64>,
+    ) -> Result<Option<String>, String> {
+        let Some((source, bytes)) = upload_source(input) else {
+            return Ok(None);
+        };
+        match self.paste.check(source, bytes, rate) {
+            Verdict::Refuse(reason) => Err(reason),
+            Verdict::Confirm(question) if strip_confirmation(input).is_none() => Ok(Some(question)),
+            _ => Ok(None),
+        }
+    }
+
+    /// An `Upload` that is refused, or waits for a `--confirm` that
+    /// `input` lacks; `unconfirmed` says how to give it.
+    fn upload_problem(&self, input: &str, unconfirmed: &str) -> Option<String> {
+        let (source, bytes) = upload_source(input)?;
+        match self.paste.check(source, bytes, None) {
+            Verdict::Refuse(reason) => Some(reason),
+            Verdict::Confirm(_) if strip_confirmation(input).is_none() => Some(format!(
+                "'{}' sends {}; {}",
+                input.trim(),
+                format_bytes(bytes),
+                unconfirmed
+            )),
+            _ => None,
+        }
+    }
+
+    /// For the console: the question to ask before sending `input` to
+    /// `slave`, when it needs one.
+    pub fn confirmation(&self, input: &str, slave: &str) -> Option<String> {
+        self.needs_confirmation(input).then(|| {
+            format!(
+                "Send '{}' to {}? This cannot be undone.",
+                input.trim(),
+                slave
+            )
+        })
+    }
+
+    /// For the master: `input` ready to parse, without the `--confirm`
+    /// of a destructive command, or why it is refused.
+    pub fn check<'a>(&self, input: &'a str) -> Result<&'a str, String> {
+        if upload_source(input).is_some() {
+            let unconfirmed = format!("add {} to send it", CONFIRM_FLAG);
+            if let Some(problem) = self.upload_problem(input, &unconfirmed) {
+                return Err(problem);
+            }
+            return Ok(strip_confirmation(input).unwrap_or(input));
+        }
+        if self.needs_confirmation(input) {
+            return Err(format!(
+                "'{}' is destructive; add {} to send it",
+                input.trim(),
+                CONFIRM_FLAG
+            ));
+        }
+        match strip_confirmation(input) {
+            Some(stripped) if is_destructive(stripped) => Ok(stripped),
+            _ => Ok(input),
+        }
+    }
+
+    /// For `exec` and scripts, which have nobody to ask: `input`
+    /// confirmed when `yes` agreed to it in advance, or why it is
+    /// refused.
+    pub fn unattended(&self, input: &str, yes: bool) -> Result<String, String> {
+        if let Some((_, bytes)) = upload_source(input) {
+            let unconfirmed = "nobody is here to confirm it; pass --yes to send it";
+            return match self.upload_problem(input, unconfirmed) {
+                Some(_) if yes && !self.paste.refuses(bytes) => Ok(confirmed(input)),
+                Some(problem) => Err(problem),
+                None => Ok(input.to_string()),
+            };
+        }
+        if !is_destructive(input) || strip_confirmation(input).is_some() {
+            return Ok(input.to_string());
+        }
+        if yes || !self.enabled {
+            return Ok(confirmed(input));
+        }
+        Err(format!(
+            "'{}' is destructive and nobody is here to confirm it; pass --yes to run it",
+            input.trim()
+        ))
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classification_is_per_command_and_argument() {
+        assert_eq!(lookup("reg query HKLM\\x").unwrap().name, "reg query");
+        assert_eq!(lookup("services").unwrap().name, "services");
+        assert_eq!(lookup("service Spooler stop").unwrap().name, "service");
+        assert!(lookup("Deleted").is_none());
+
+        assert!(is_destructive(r"Delete C:\a.txt"));
+        assert!(is_destructive("SystemAction shutdown"));
+        assert!(is_destructive("SystemAction reboot --confirm"));
+        assert!(!is_destructive("SystemAction lock"));
+        assert!(is_destructive(r"Copy -f C:\a C:\b"));
+        assert!(!is_destructive(r"Copy C:\a C:\b"));
+        // Any step may be a delete, however it is spelled.
+        assert!(is_destructive(r"Transaction mkdir C:\n; move C:\a C:\n\a"));
+        assert!(is_destructive("schedule rm disk"));
+        assert!(!is_destructive("schedule list"));
+        assert!(!is_destructive("ShellExecute rm -rf /tmp/x"));
+        assert_eq!(names().len(), COMMANDS.len());
+        assert!(COMMANDS.iter().all(|c| !c.help.is_empty()));
+    }
+
+    #[test]
+    fn invalidations_name_cached_commands() {
+        assert_eq!(cached("ListDrives").unwrap().cache, Some(Duration::from_secs(60)));
+        assert!(cached("ListDir").is_none());
+        assert!(cached("Delete").is_none());
+        for spec in COMMANDS {
+            for name in spec.invalidates {
+                assert!(cached(name).is_some(), "{} invalidates {}", spec.name, name);
+            }
+            // What changes the slave is never served from memory.
+            assert!(spec.cache.is_none() || spec.invalidates.is_empty());
+            assert!(spec.cache.is_none() || spec.risk == Risk::Safe);
+        }
+    }
+
+    #[test]
+    fn interactive_console_asks_and_confirms() {
+        let gate = ConfirmGate::default();
+        assert_eq!(
+            gate.confirmation("SystemAction shutdown ", "PC-7 (10.0.0.7)")
+                .as_deref(),
+            Some("Send 'SystemAction shutdown' to PC-7 (10.0.0.7)? This cannot be undone.")
+        );
+        assert_eq!(gate.confirmation("SystemAction lock", "PC-7"), None);
+        assert_eq!(gate.confirmation("Delete a --confirm", "PC-7"), None);
+        assert_eq!(confirmed("Delete a "), "Delete a --confirm");
+    }
+
+    #[test]
+    fn scripted_commands_are_refused_until_confirmed() {
+        let gate = ConfirmGate::default();
+        let err = gate.check("Delete --permanent C:\\old").unwrap_err();
+        assert!(err.contains("add --confirm"), "{}", err);
+        assert_eq!(
+            gate.check("Delete --permanent C:\\old --confirm"),
+            Ok("Delete --permanent C:\\old")
+        );
+        // Only destructive commands lose the flag; the service guard
+        // reads its own.
+        assert_eq!(
+            gate.check("service RpcSs stop --confirm"),
+            Ok("service RpcSs stop --confirm")
+        );
+        assert_eq!(gate.check("ListDir C:\\"), Ok("ListDir C:\\"));
+
+        let off = ConfirmGate::new(&CommandsConfig {
+            confirm_destructive: false,
+            ..CommandsConfig::default()
+        });
+        assert!(!off.needs_confirmation("SystemAction reboot"));
+        assert_eq!(off.check("SystemAction reboot"), Ok("SystemAction reboot"));
+    }
+
+    #[test]
+    fn one_shot_needs_yes() {
+        let gate = ConfirmGate::default();
+        let err = gate.unattended("SystemAction shutdown", false).unwrap_err();
+        assert!(err.contains("--yes"), "{}", err);
+        assert_eq!(
+            gate.unattended("SystemAction shutdown", true).as_deref(),
+            Ok("SystemAction shutdown --confirm")
+        );
+        assert_eq!(gate.unattended("Ping", false).as_deref(), Ok("Ping"));
+        assert_eq!(
+            gate.unattended("Delete a --confirm", false).as_deref(),
+            Ok("Delete a --confirm")
+        );
+        let off = ConfirmGate::new(&CommandsConfig {
+            confirm_destructive: false,
+            ..CommandsConfig::default()
+        });
+        assert_eq!(
+            off.unattended("Delete a", false).as_deref(),
+            Ok("Delete a --confirm")
+        );
+    }
+
+    #[test]
+    fn large_uploads_wait_and_huge_ones_are_refused() {
+        let dir = std::env::temp_dir().join(format!("tix-upload-gate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, len: usize| {
+            let path = dir.join(name);
+            std::fs::write(&path, vec![0u8; len]).unwrap();
+            format!("Upload {}|C:\\in", path.display())
+        };
+        let (small, large, huge) = (file("s", 10), file("l", 500), file("h", 5000));
+        let gate = ConfirmGate::default().with_paste_guard(PasteGuard::new(
+            &tix_core::paste_guard::PasteGuardConfig {
+                confirm_bytes: 100,
+                refuse_bytes: 1000,
+            },
+        ));
+
+        // The console asks about the large one and refuses the huge one.
+        assert_eq!(gate.upload_confirmation(&small, None), Ok(None));
+        let question = gate
+            .upload_confirmation(&large, Some(100))
+            .unwrap()
+            .unwrap();
+        assert!(
+            question.ends_with("is 500 B, about 5.0s at 100 B/s. Send it?"),
+            "{}",
+            question
+        );
+        assert_eq!(gate.upload_confirmation(&confirmed(&large), None), Ok(None));
+        assert!(
+            gate.upload_confirmation(&huge, None)
+                .unwrap_err()
+                .contains("limit")
+        );
+        assert_eq!(gate.upload_confirmation("Ping", None), Ok(None));
+
+        // The master wants the flag, and takes it off before parsing.
+        assert!(
+            gate.check(&large)
+                .unwrap_err()
+                .ends_with("sends 500 B; add --confirm to send it")
+        );
+        assert_eq!(gate.check(&confirmed(&large)), Ok(large.as_str()));
+        assert!(gate.check(&confirmed(&huge)).is_err());
+        assert_eq!(gate.check(&small), Ok(small.as_str()));
+
+        // Scripts agree with --yes, but never to the huge one.
+        assert!(
+            gate.unattended(&large, false)
+                .unwrap_err()
+                .contains("--yes")
+        );
+        assert_eq!(gate.unattended(&large, true), Ok(confirmed(&large)));
+        assert!(gate.unattended(&huge, true).is_err());
+        assert_eq!(gate.unattended(&small, false), Ok(small.clone()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,626 @@
+//! Optional master configuration, read from `tix-master.toml`.
+//!
+//! ```toml
+//! [watch]
+//! cache_dir = "tix-cache"
+//! keep_versions = 5
+//! repair_rounds = 2
+//!
+//! [inventory]
+//! path = "tix-inventory.json"
+//!
+//! [ui]
+//! theme = "default"
+//! reduced_motion = false
+//! task_history = 20
+//!
+//! [responses]
+//! show_late_payload = false
+//!
+//! [notify]
+//! task_failed = true
+//! slave_disconnected = true
+//! bell = true
+//! banner_secs = 5
+//! notify_cmd = "notify-send tix {message}"
+//!
+//! [services]
+//! protected = ["RpcSs", "WinDefend", "tix*"]
+//! timeout_secs = 30
+//!
+//! [commands]
+//! confirm_destructive = true
+//! timeout_secs = 30
+//! shell = "cmd"
+//!
+//! [wire]
+//! enabled = false
+//! dump_bytes = 64
+//! capture = ""
+//!
+//! [paste_guard]
+//! confirm_bytes = 10485760
+//! refuse_bytes = 2147483648
+//!
+//! [transfer]
+//! compression_level = 3
+//! resumable = false
+//!
+//! [cache]
+//! max_entries = 64
+//! revalidate = true
+//! ttl_secs = { ListDrives = 60, sysinfo = 10 }
+//!
+//! [data]
+//! history = "tix-history.log"
+//! transcripts = "tix-transcripts"
+//! journals = ""
+//! redact = ['(?i)pin\s+(?P<secret>\d+)']
+//! default_redactions = true
+//!
+//! [data.retention]
+//! history = "forever"
+//! transcripts = "30d"
+//! caches = "7d"
+//! journals = "14d"
+//!
+//! [role.viewer]
+//! allow = ["Ping", "ListDir", "ListDrives", "Download", "FileRead", "Screen*"]
+//!
+//! [role.admin]
+//! allow = ["*"]
+//! password = "$argon2id$v=19$m=19456,t=2,p=1$…"
+//! ```
+//!
+//! Every section and field may be omitted; a missing file means the
+//! defaults. Unknown keys and unusable values are errors (see
+//! [`tix_core::config`]).
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tix_core::config::Validate;
+use tix_core::network::tls::TlsConfig;
+use tix_core::network::wiretap::WireConfig;
+use tix_core::paste_guard::PasteGuardConfig;
+use tix_core::policy::CommandSet;
+use tix_core::protocol::file::DEFAULT_REPAIR_ROUNDS;
+use tix_core::protocol::service::DEFAULT_CONTROL_TIMEOUT;
+use tix_core::protocol::{ShellKind, TransferConfig};
+
+use crate::inventory::DEFAULT_INVENTORY_PATH;
+use crate::cache::DEFAULT_MAX_ENTRIES;
+use crate::commands;
+use crate::master::DEFAULT_REQUEST_TIMEOUT_SECS;
+use crate::notify::{DEFAULT_BANNER_SECS, DEFAULT_MIN_INTERVAL_SECS};
+use crate::retention::{DEFAULT_HISTORY_PATH, Redactor, Retention, Rules};
+use crate::roles;
+use crate::services::DEFAULT_PROTECTED_SERVICES;
+use crate::tasks::DEFAULT_TASK_HISTORY;
+use crate::theme::ThemeName;
+use crate::watch::{DEFAULT_CACHE_DIR, DEFAULT_KEEP_VERSIONS};
+
+/// Default config file, looked up in the working directory.
+pub const DEFAULT_CONFIG_PATH: &str = "tix-master.toml";
+
+/// Top-level configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MasterConfig {
+    /// The `watch` command's local cache.
+    pub watch: WatchConfig,
+    /// Where known slaves are remembered.
+    pub inventory: InventoryConfig,
+    /// How the console looks.
+    pub ui: UiConfig,
+    /// Responses that arrive when nothing waits for them.
+    pub responses: ResponsesConfig,
+    /// Alerts for finished tasks and slaves coming and going.
+    pub notify: NotifyConfig,
+    /// The `service` command.
+    pub services: ServicesConfig,
+    /// Console commands in general.
+    pub commands: CommandsConfig,
+    /// Packet logging on slave connections (see `debug wire`).
+    pub wire: WireConfig,
+    /// TLS on slave connections (see [`tix_core::network::tls`]); the
+    /// master presents the certificate.
+    pub tls: TlsConfig,
+    /// How large an upload may be before it is confirmed or refused.
+    pub paste_guard: PasteGuardConfig,
+    /// How file chunks sent to the slave are packed.
+    pub transfer: TransferConfig,
+    /// Answers the master reuses instead of asking the slave again.
+    pub cache: CacheConfig,
+    /// What the master keeps on disk, for how long, and what is masked
+    /// in it.
+    pub data: DataConfig,
+    /// Operator roles by name (see [`crate::roles`]).
+    pub role: BTreeMap<String, RoleConfig>,
+}
+
+/// Where `watch` keeps downloaded versions, how many, and how hard it
+/// tries to fix a corrupt download.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchConfig {
+    /// Root of the cache; each watched file gets a folder inside it.
+    pub cache_dir: PathBuf,
+    /// Versions kept per file, newest first. At least two are kept so
+    /// there is always something to diff against.
+    pub keep_versions: usize,
+    /// Rounds spent re-fetching the chunks of a download that fails its
+    /// hash check; 0 gives up at once.
+    pub repair_rounds: u32,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            cache_dir: PathBuf::from(DEFAULT_CACHE_DIR),
+            keep_versions: DEFAULT_KEEP_VERSIONS,
+            repair_rounds: DEFAULT_REPAIR_ROUNDS,
+        }
+    }
+}
+
+/// The slave inventory file (see [`crate::inventory`]).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct InventoryConfig {
+    pub path: PathBuf,
+}
+
+impl Default for InventoryConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(DEFAULT_INVENTORY_PATH),
+        }
+    }
+}
+
+/// Console appearance (see [`crate::theme`]).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    /// `default`, `high-contrast` or `ascii`; `theme <name>` switches at
+    /// runtime.
+    pub theme: ThemeName,
+    /// Only open the suggestion popup on Tab, never while typing, and
+    /// keep the Tasks spinner still.
+    pub reduced_motion: bool,
+    /// Finished tasks the Tasks sidebar keeps (see [`crate::tasks`]).
+    pub task_history: usize,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            theme: ThemeName::default(),
+            reduced_motion: false,
+            task_history: DEFAULT_TASK_HISTORY,
+        }
+    }
+}
+
+/// Late and duplicate responses (see [`crate::late`]).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponsesConfig {
+    /// Log the payload of a late response under its `[LATE]` line
+    /// instead of only its size.
+    pub show_late_payload: bool,
+}
+
+/// What the console alerts on and how (see [`crate::notify`]).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// A task the Tasks panel shows as solved.
+    pub task_finished: bool,
+    /// A task that failed or timed out.
+    pub task_failed: bool,
+    pub slave_connected: bool,
+    pub slave_disconnected: bool,
+    /// An upload, download or host copy that completed.
+    pub transfer_complete: bool,
+    /// Ring the terminal bell.
+    pub bell: bool,
+    /// Seconds the banner stays at the top of the console; 0 for none.
+    pub banner_secs: u64,
+    /// Command run for every alert, `{message}` replaced by its text.
+    pub notify_cmd: Option<String>,
+    /// Least time between two alerts of the same kind; those in between
+    /// are counted into the next one.
+    pub min_interval_secs: u64,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            task_finished: false,
+            task_failed: true,
+            slave_connected: false,
+            slave_disconnected: true,
+            transfer_complete: true,
+            bell: true,
+            banner_secs: DEFAULT_BANNER_SECS,
+            notify_cmd: None,
+            min_interval_secs: DEFAULT_MIN_INTERVAL_SECS,
+        }
+    }
+}
+
+/// Services on the slave that are not stopped by accident (see
+/// [`crate::services`]).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServicesConfig {
+    /// Names, or globs with `*` and `?`, of services that `service stop`
+    /// and `service restart` only act on once confirmed.
+    pub protected: Vec<String>,
+    /// How long the slave waits for a service to start or stop.
+    pub timeout_secs: u64,
+}
+
+impl Default for ServicesConfig {
+    fn default() -> Self {
+        Self {
+            protected: DEFAULT_PROTECTED_SERVICES.map(String::from).to_vec(),
+            timeout_secs: DEFAULT_CONTROL_TIMEOUT.as_secs(),
+        }
+    }
+}
+
+/// Commands that cannot be taken back (see [`crate::commands`]), and
+/// how long any command waits for its reply.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandsConfig {
+    /// Ask before sending them from the console, and refuse them in
+    /// `exec` without `--yes`.
+    pub confirm_destructive: bool,
+    /// Shortest wait for a reply. The master waits longer when the
+    /// heartbeats show a slow or jittery link, more so for commands
+    /// that take a while on the slave.
+    pub timeout_secs: u64,
+    /// Shell `ShellExecute` runs in unless `-s` names one: `cmd` (the
+    /// slave's system shell), `powershell`, `pwsh`, or a custom shell the
+    /// slave allows.
+    pub shell: String,
+}
+
+impl Default for CommandsConfig {
+    fn default() -> Self {
+        Self {
+            confirm_destructive: true,
+            timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+            shell: ShellKind::Cmd.to_string(),
+        }
+    }
+}
+
+impl CommandsConfig {
+    /// `shell`, the system shell when it names none.
+    pub fn default_shell(&self) -> ShellKind {
+        self.shell.trim().parse().unwrap_or_default()
+    }
+}
+
+/// Answers to queries kept in memory (see [`crate::cache`]).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Answers kept at most, the oldest dropped first; 0 turns the
+    /// cache off.
+    pub max_entries: usize,
+    /// Past half its time, serve an answer and ask the slave as well so
+    /// the next one is fresh.
+    pub revalidate: bool,
+    /// Seconds the answers of a cached command stay good, by its name,
+    /// over the registry's own; 0 never keeps them.
+    pub ttl_secs: BTreeMap<String, u64>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: DEFAULT_MAX_ENTRIES,
+            revalidate: true,
+            ttl_secs: BTreeMap::new(),
+        }
+    }
+}
+
+/// Files the master writes and how long they stay (see
+/// [`crate::retention`]).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DataConfig {
+    /// Command history file; empty keeps history for the session only.
+    pub history: PathBuf,
+    /// Folder for a transcript of each session's console log; empty
+    /// writes none.
+    pub transcripts: PathBuf,
+    /// Folder the viewer's input journals are written to, so they are
+    /// cleaned up with the rest; empty leaves them alone.
+    pub journals: PathBuf,
+    /// Regular expressions whose matches are masked before command text
+    /// is written down; with a group named `secret` only that is.
+    pub redact: Vec<String>,
+    /// Apply the built-in patterns for `password=`, `-p <arg>` and
+    /// `Authorization` headers before `redact`.
+    pub default_redactions: bool,
+    pub retention: RetentionConfig,
+}
+
+impl Default for DataConfig {
+    fn default() -> Self {
+        Self {
+            history: PathBuf::from(DEFAULT_HISTORY_PATH),
+            transcripts: PathBuf::new(),
+            journals: PathBuf::new(),
+            redact: Vec::new(),
+            default_redactions: true,
+            retention: RetentionConfig::default(),
+        }
+    }
+}
+
+impl DataConfig {
+    /// The redactor the patterns describe; the built-in one if they do
+    /// not compile, which validation reports.
+    pub fn redactor(&self) -> Redactor {
+        Redactor::new(&self.redact, self.default_redactions).unwrap_or_default()
+    }
+}
+
+/// How long each artifact is kept: `forever` or a duration such as
+/// `30d` or `12h`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub history: String,
+    pub transcripts: String,
+    pub caches: String,
+    pub journals: String,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        let rules = Rules::default();
+        Self {
+            history: rules.history.to_string(),
+            transcripts: rules.transcripts.to_string(),
+            caches: rules.caches.to_string(),
+            journals: rules.journals.to_string(),
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// The rules, each unreadable value at its default.
+    pub fn rules(&self) -> Rules {
+        let defaults = Rules::default();
+        let read = |text: &str, default| Retention::parse(text).unwrap_or(default);
+        Rules {
+            history: read(&self.history, defaults.history),
+            transcripts: read(&self.transcripts, defaults.transcripts),
+            caches: read(&self.caches, defaults.caches),
+            journals: read(&self.journals, defaults.journals),
+        }
+    }
+
+    fn problems(&self) -> Vec<String> {
+        [
+            ("history", &self.history),
+            ("transcripts", &self.transcripts),
+            ("caches", &self.caches),
+            ("journals", &self.journals),
+        ]
+        .into_iter()
+        .filter_map(|(name, text)| {
+            let problem = Retention::parse(text).err()?;
+            Some(format!("data.retention.{}: {}", name, problem))
+        })
+        .collect()
+    }
+}
+
+/// One operator role.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoleConfig {
+    /// Commands the role may send: names or patterns such as `File*`
+    /// and `!ShellExecute`.
+    pub allow: Vec<String>,
+    /// Argon2 hash from `tix-master hash-password`; switching to the
+    /// role with `role` then asks for the password.
+    pub password: Option<String>,
+}
+
+impl Validate for MasterConfig {
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.services.timeout_secs == 0 {
+            problems.push("services.timeout_secs must be at least 1".to_string());
+        }
+        if self.commands.timeout_secs == 0 {
+            problems.push("commands.timeout_secs must be at least 1".to_string());
+        }
+        if self.commands.shell.trim().is_empty() {
+            problems.push("commands.shell names no shell".to_string());
+        }
+        if self.inventory.path.as_os_str().is_empty() {
+            problems.push("inventory.path must not be empty".to_string());
+        }
+        for name in self.cache.ttl_secs.keys() {
+            if commands::cached(name).is_none() {
+                problems.push(format!("cache.ttl_secs: `{}` is not a cached command", name));
+            }
+        }
+        if let Err(e) = Redactor::new(&self.data.redact, false) {
+            problems.push(format!("data.redact: {}", e));
+        }
+        problems.extend(self.data.retention.problems());
+        problems.extend(self.paste_guard.problems());
+        problems.extend(self.transfer.problems());
+        for (name, role) in &self.role {
+            for problem in CommandSet::problems(&role.allow) {
+                problems.push(format!("role.{}.allow: {}", name, problem));
+            }
+            if role
+                .password
+                .as_ref()
+                .is_some_and(|hash| !roles::is_password_hash(hash))
+            {
+                problems.push(format!(
+                    "role.{}.password must be an argon2 hash from `tix-master hash-password`",
+                    name
+                ));
+            }
+        }
+        problems
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn empty_config_uses_defaults() {
+        let cfg: MasterConfig = toml::from_str("").unwrap();
+        assert_eq!(cfg, MasterConfig::default());
+        assert_eq!(cfg.watch.cache_dir, PathBuf::from(DEFAULT_CACHE_DIR));
+
+        let cfg: MasterConfig = toml::from_str("[watch]\nkeep_versions = 2\n").unwrap();
+        assert_eq!(cfg.watch.keep_versions, 2);
+        assert_eq!(cfg.watch.cache_dir, PathBuf::from(DEFAULT_CACHE_DIR));
+        assert_eq!(cfg.watch.repair_rounds, DEFAULT_REPAIR_ROUNDS);
+        assert_eq!(cfg.inventory.path, PathBuf::from(DEFAULT_INVENTORY_PATH));
+        assert_eq!(cfg.ui.theme, ThemeName::Default);
+
+        let cfg: MasterConfig =
+            toml::from_str("[ui]\ntheme = \"high-contrast\"\nreduced_motion = true\n").unwrap();
+        assert_eq!(cfg.ui.theme, ThemeName::HighContrast);
+        assert!(cfg.ui.reduced_motion);
+        assert_eq!(cfg.ui.task_history, DEFAULT_TASK_HISTORY);
+        assert!(!cfg.responses.show_late_payload);
+        assert_eq!(cfg.notify, NotifyConfig::default());
+
+        let cfg: MasterConfig =
+            toml::from_str("[notify]\ntask_finished = true\nnotify_cmd = \"say {message}\"\n")
+                .unwrap();
+        assert!(cfg.notify.task_finished && cfg.notify.task_failed);
+        assert_eq!(cfg.notify.notify_cmd.as_deref(), Some("say {message}"));
+        assert!(toml::from_str::<MasterConfig>("[ui]\ntheme = \"neon\"\n").is_err());
+
+        let cfg: MasterConfig = toml::from_str("[services]\nprotected = []\n").unwrap();
+        assert!(cfg.services.protected.is_empty());
+        assert_eq!(cfg.services.timeout_secs, 30);
+        assert!(MasterConfig::default().services.protected.len() > 1);
+
+        assert!(cfg.commands.confirm_destructive);
+        let cfg: MasterConfig =
+            toml::from_str("[commands]\nconfirm_destructive = false\n").unwrap();
+        assert!(!cfg.commands.confirm_destructive);
+        assert_eq!(cfg.commands.timeout_secs, 30);
+        assert_eq!(cfg.commands.default_shell(), ShellKind::Cmd);
+
+        let cfg: MasterConfig = toml::from_str("[commands]\nshell = \"pwsh\"\n").unwrap();
+        assert_eq!(cfg.commands.default_shell(), ShellKind::PwshCore);
+        let cfg: MasterConfig = toml::from_str("[commands]\nshell = \"bash\"\n").unwrap();
+        assert_eq!(
+            cfg.commands.default_shell(),
+            ShellKind::Custom("bash".into())
+        );
+    }
+
+    #[test]
+    fn mistakes_are_reported() {
+        let parse = |text| tix_core::config::parse::<MasterConfig>(Path::new("m.toml"), text);
+        let err = parse("[notify]\nbel = false\n").unwrap_err().to_string();
+        assert!(err.contains("line 2, column 1"), "{}", err);
+        assert!(err.ends_with("did you mean `bell`?"), "{}", err);
+
+        let err = parse("[services]\ntimeout_secs = 0\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.ends_with("services.timeout_secs must be at least 1"),
+            "{}",
+            err
+        );
+        assert!(MasterConfig::default().problems().is_empty());
+
+        let err = parse("[role.viewer]\nallow = [\"Ping\", \"Disk*\", \"Dirlist\"]\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("role.viewer.allow: `Disk*` matches no command"),
+            "{}",
+            err
+        );
+        let err = parse("[role.admin]\nallow = [\"*\"]\npassword = \"hunter2\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("role.admin.password must be an argon2 hash"),
+            "{}",
+            err
+        );
+        let err = parse("[data]\nredact = [\"(open\"]\n[data.retention]\ncaches = \"weekly\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("data.redact: `(open`:"), "{}", err);
+        assert!(
+            err.contains("data.retention.caches: `weekly` is neither `forever` nor a duration"),
+            "{}",
+            err
+        );
+        let cfg: MasterConfig = toml::from_str("[data.retention]\nhistory = \"90d\"\n").unwrap();
+        assert_eq!(cfg.data.retention.rules().history.to_string(), "90d");
+        assert_eq!(cfg.data.retention.rules().caches.to_string(), "7d");
+        let err = parse("[paste_guard]\nconfirm_bytes = 5000\nrefuse_bytes = 10\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("paste_guard.refuse_bytes (10) must not be below"),
+            "{}",
+            err
+        );
+        let err = parse("[transfer]\ncompression_level = 40\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("transfer.compression_level (40) must be between 0 and 19"),
+            "{}",
+            err
+        );
+        let err = parse("[cache]\nttl_secs = { ListDrives = 5, ListDir = 5 }\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.ends_with("cache.ttl_secs: `ListDir` is not a cached command"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn roles_are_parsed() {
+        let cfg: MasterConfig = toml::from_str(
+            "[role.viewer]\nallow = [\"Ping\", \"Screen*\"]\n\n[role.admin]\nallow = [\"*\"]\n",
+        )
+        .unwrap();
+        assert_eq!(cfg.role.len(), 2);
+        assert_eq!(cfg.role["viewer"].allow, ["Ping", "Screen*"]);
+        assert_eq!(cfg.role["admin"].password, None);
+        assert!(cfg.problems().is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+//! The `eventlog` console command: recent entries of a slave event log.
+//!
+//! `eventlog [system|application|<log>] [--errors|--warnings]
+//! [--since <age>] [-n N]` sends an [`EventLogQueryRequest`]. Entries are
+//! logged newest first as the slave's batches arrive, one line each,
+//! coloured by level; the query's row in the Tasks panel counts them.
+
+use std::time::Duration;
+
+use tix_core::protocol::eventlog::MAX_ENTRIES_LIMIT;
+use tix_core::protocol::{EventLevel, EventLogEntry, EventLogQueryRequest, EventLogSummary};
+
+use crate::watch::parse_duration;
+
+/// First character of every entry line in the log, so the console can
+/// colour entries by level.
+pub const ENTRY_MARKER: char = '┆';
+
+/// Log read when none is named.
+const DEFAULT_LOG: &str = "System";
+
+const USAGE: &str = "eventlog [system|application] [--errors|--warnings] [--since 1h] [-n N]";
+
+/// Parse the arguments following `eventlog`; `--since` counts back from
+/// `now_ms`.
+pub fn parse_eventlog(args: &[String], now_ms: u64) -> Result<EventLogQueryRequest, String> {
+    let mut log = None;
+    let mut req = EventLogQueryRequest::new(DEFAULT_LOG);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--errors" => req.level = Some(EventLevel::Error),
+            "--warnings" => req.level = Some(EventLevel::Warning),
+            "--since" => {
+                let age = args
+                    .next()
+                    .ok_or("--since requires an age (e.g. 30m, 1h, 2d)")?;
+                let age = parse_duration(age)?;
+                req.since_ms = Some(now_ms.saturating_sub(age.as_millis() as u64));
+            }
+            "-n" => {
+                req.max_entries = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|n| (1..=MAX_ENTRIES_LIMIT).contains(n))
+                    .ok_or_else(|| {
+                        format!("-n requires a number from 1 to {}", MAX_ENTRIES_LIMIT)
+                    })?;
+            }
+            flag if flag.starts_with('-') => return Err(format!("Usage: {}", USAGE)),
+            name if log.is_none() => log = Some(log_name(name)),
+            _ => return Err(format!("Usage: {}", USAGE)),
+        }
+    }
+    if let Some(log) = log {
+        req.log = log;
+    }
+    Ok(req)
+}
+
+/// `system` and `application` in any case name the two classic logs;
+/// anything else is passed on as a channel name.
+fn log_name(name: &str) -> String {
+    match name.to_lowercase().as_str() {
+        "system" => "System".to_string(),
+        "application" | "app" => "Application".to_string(),
+        _ => name.to_string(),
+    }
+}
+
+/// An `eventlog` waiting on the slave. Its request ID is its Tasks row.
+#[derive(Debug)]
+pub struct EventLogQuery {
+    pub log: String,
+    /// Entries received so far.
+    pub received: u64,
+}
+
+impl EventLogQuery {
+    pub fn new(req: &EventLogQueryRequest) -> Self {
+        Self {
+            log: req.log.clone(),
+            received: 0,
+        }
+    }
+
+    /// Tasks panel status while the query runs.
+    pub fn status(&self) -> String {
+        format!("Reading {} log ({} entries)", self.log, self.received)
+    }
+}
+
+/// One log line per entry: level, UTC time, source, event ID, message.
+pub fn format_entry(entry: &EventLogEntry) -> String {
+    format!(
+        "{}{:<8} {} {} [{}] {}",
+        ENTRY_MARKER,
+        entry.level.to_string(),
+        format_utc(entry.time_ms),
+        entry.provider,
+        entry.event_id,
+        entry.message
+    )
+}
+
+/// The level of an entry line written by [`format_entry`].
+pub fn line_level(line: &str) -> Option<EventLevel> {
+    let level = line.strip_prefix(ENTRY_MARKER)?.split(' ').next()?;
+    [
+        EventLevel::Critical,
+        EventLevel::Error,
+        EventLevel::Warning,
+        EventLevel::Information,
+        EventLevel::Verbose,
+    ]
+    .into_iter()
+    .find(|l| l.to_string() == level)
+}
+
+/// Closing log line of a query.
+pub fn format_summary(log: &str, summary: &EventLogSummary) -> String {
+    if let Some(error) = &summary.error {
+        return format!("Error: event log {}", error);
+    }
+    format!(
+        "[EVNT] {} entr{} from {}, {} ms{}",
+        summary.entries,
+        if summary.entries == 1 { "y" } else { "ies" },
+        log,
+        summary.elapsed_ms,
+        if summary.truncated {
+            "; older entries left out (-n)"
+        } else {
+            ""
+        }
+    )
+}
+
+/// `time_ms` (Unix milliseconds) as `YYYY-MM-DD HH:MM:SS` UTC.
+pub(crate) fn format_utc(time_ms: u64) -> String {
+    let secs = Duration::from_millis(time_ms).as_secs();
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's algorithm).
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rest / 3_600,
+        rest % 3_600 / 60,
+        rest % 60
+    )
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::protocol::{EventLogError, EventLogErrorKind};
+
+    const NOW: u64 = 1_800_000_000_000;
+
+    fn args(text: &str) -> Vec<String> {
+        crate::args::split_args(text).unwrap()
+    }
+
+    #[test]
+    fn parses_log_level_and_since() {
+        let req = parse_eventlog(&[], NOW).unwrap();
+        assert_eq!(req, EventLogQueryRequest::new("System"));
+
+        let req = parse_eventlog(&args("application --errors --since 1h -n 20"), NOW).unwrap();
+        assert_eq!(req.log, "Application");
+        assert_eq!(req.level, Some(EventLevel::Error));
+        assert_eq!(req.since_ms, Some(NOW - 3_600_000));
+        assert_eq!(req.max_entries, 20);
+
+        let req = parse_eventlog(&args("--warnings --since 2d SYSTEM"), NOW).unwrap();
+        assert_eq!(req.log, "System");
+        assert_eq!(req.level, Some(EventLevel::Warning));
+        assert_eq!(req.since_ms, Some(NOW - 2 * 86_400_000));
+
+        let req = parse_eventlog(&args("Microsoft-Windows-PowerShell/Operational"), NOW).unwrap();
+        assert_eq!(req.log, "Microsoft-Windows-PowerShell/Operational");
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse_eventlog(&args("--since"), NOW).is_err());
+        assert!(
+            parse_eventlog(&args("--since yesterday"), NOW)
+                .unwrap_err()
+                .contains("yesterday")
+        );
+        assert!(parse_eventlog(&args("-n 0"), NOW).is_err());
+        assert!(parse_eventlog(&args("-n 5000"), NOW).is_err());
+        assert!(parse_eventlog(&args("system application"), NOW).is_err());
+        assert!(parse_eventlog(&args("--critical"), NOW).is_err());
+    }
+
+    #[test]
+    fn entries_carry_their_level() {
+        let entry = EventLogEntry {
+            time_ms: 1_700_000_000_123,
+            level: EventLevel::Warning,
+            provider: "Service Control Manager".into(),
+            event_id: 7031,
+            message: "The Print Spooler service terminated unexpectedly.".into(),
+        };
+        let line = format_entry(&entry);
+        assert_eq!(
+            line,
+            "┆warning  2023-11-14 22:13:20 Service Control Manager [7031] \
+             The Print Spooler service terminated unexpectedly."
+        );
+        assert_eq!(line_level(&line), Some(EventLevel::Warning));
+        assert_eq!(line_level("warning  not an entry"), None);
+        assert_eq!(format_utc(951_782_400_000), "2000-02-29 00:00:00");
+    }
+
+    #[test]
+    fn summary_reports_errors_and_the_cap() {
+        let summary = EventLogSummary {
+            entries: 50,
+            truncated: true,
+            elapsed_ms: 8,
+            error: None,
+        };
+        assert_eq!(
+            format_summary("System", &summary),
+            "[EVNT] 50 entries from System, 8 ms; older entries left out (-n)"
+        );
+        let failed = EventLogSummary {
+            error: Some(EventLogError {
+                kind: EventLogErrorKind::AccessDenied,
+                message: "Security: Access is denied.".into(),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_summary("Security", &failed),
+            "Error: event log access denied: Security: Access is denied."
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Slaves the master has seen, kept across restarts.
+//!
+//! Records are keyed by the stable ID each slave presents in its
+//! `Hello` ([`HelloInfo::slave_id`]), so a slave that reboots or comes
+//! back on a new IP is recognised as the same machine. Each record holds
+//! the friendly name given with the `name` command and what was last
+//! seen of the slave. The file is JSON and is rewritten on every
+//! connect, disconnect and rename.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tix_core::config::write_atomic;
+use tix_core::protocol::HelloInfo;
+
+/// Default inventory file, in the working directory.
+pub const DEFAULT_INVENTORY_PATH: &str = "tix-inventory.json";
+
+/// What the master knows about one slave.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlaveRecord {
+    /// Name given with `name`; empty until then.
+    pub name: String,
+    pub hostname: String,
+    pub os: String,
+    pub version: String,
+    /// Address of the last connection.
+    pub last_ip: String,
+    /// Unix time in seconds of the last connect or disconnect.
+    pub last_seen: u64,
+}
+
+impl SlaveRecord {
+    /// The friendly name, else the host name.
+    pub fn display_name(&self) -> &str {
+        if !self.name.is_empty() {
+            &self.name
+        } else if !self.hostname.is_empty() {
+            &self.hostname
+        } else {
+            "unnamed slave"
+        }
+    }
+}
+
+/// Known slaves by ID, optionally backed by a file.
+#[derive(Debug, Default)]
+pub struct Inventory {
+    /// `None` keeps the inventory in memory only.
+    path: Option<PathBuf>,
+    slaves: BTreeMap<String, SlaveRecord>,
+}
+
+impl Inventory {
+    /// An empty inventory that is never saved.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the inventory at `path`, where later saves go too. A missing
+    /// file is an empty inventory; an unreadable one is an error, so it
+    /// is not overwritten.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let slaves = match std::fs::read_to_string(path) {
+            Ok(text) => {
+                serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            slaves,
+        })
+    }
+
+    /// Write the inventory back to its file, if it has one. The file is
+    /// replaced in one step, so a crash cannot leave half of it.
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text = serde_json::to_string_pretty(&self.slaves).map_err(|e| e.to_string())?;
+        write_atomic(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// The record for slave `id`.
+    pub fn get(&self, id: &str) -> Option<&SlaveRecord> {
+        self.slaves.get(id)
+    }
+
+    /// Every known slave, by ID.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &SlaveRecord)> {
+        self.slaves.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.slaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slaves.is_empty()
+    }
+
+    /// Note that the slave described by `hello` connected from `ip`.
+    /// Returns the record as it was before, if the slave was known.
+    pub fn connected(&mut self, hello: &HelloInfo, ip: &str, now: u64) -> Option<SlaveRecord> {
+        let record = self.slaves.entry(hello.slave_id.clone()).or_default();
+        let previous = (record.last_seen != 0).then(|| record.clone());
+        record.hostname = hello.hostname.clone();
+        record.os = hello.os.clone();
+        record.version = hello.version.clone();
+        record.last_ip = ip.to_string();
+        record.last_seen = now;
+        previous
+    }
+
+    /// Note that slave `id` went away.
+    pub fn disconnected(&mut self, id: &str, now: u64) {
+        if let Some(record) = self.slaves.get_mut(id) {
+            record.last_seen = now;
+        }
+    }
+
+    /// Give slave `id` the friendly name `name`; an empty name clears it.
+    pub fn rename(&mut self, id: &str, name: &str) -> Result<(), String> {
+        let record = self
+            .slaves
+            .get_mut(id)
+            .ok_or_else(|| format!("unknown slave {}", id))?;
+        record.name = name.trim().to_string();
+        Ok(())
+    }
+
+    /// The ID `key` names: a full ID, a unique ID prefix or a friendly
+    /// name.
+    pub fn resolve(&self, key: &str) -> Result<String, String> {
+        if self.slaves.contains_key(key) {
+            return Ok(key.to_string());
+        }
+        let matches: Vec<&String> = self
+            .slaves
+            .iter()
+            .filter(|(id, record)| id.starts_with(key) || record.name == key)
+            .map(|(id, _)| id)
+            .collect();
+        match matches.as_slice() {
+            [id] => Ok((*id).clone()),
+            [] => Err(format!("no known slave matches '{}'", key)),
+            _ => Err(format!(
+                "'{}' matches {} slaves; give more of the ID",
+                key,
+                matches.len()
+            )),
+        }
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// How long ago `then` was, coarsely: `42s`, `5m`, `3h`, `2d`.
+pub fn format_age(then: u64, now: u64) -> String {
+    match now.saturating_sub(then) {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86_400 => format!("{}h", s / 3600),
+        s => format!("{}d", s / 86_400),
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "6f1c2b9e-3d4a-4c1b-9a8e-2f7d5e0c1b3a";
+
+    fn hello(id: &str, hostname: &str) -> HelloInfo {
+        let mut hello = HelloInfo::local("tix-slave", "0.2.0").with_slave_id(id);
+        hello.hostname = hostname.to_string();
+        hello
+    }
+
+    #[test]
+    fn inventory_survives_save_and_load() {
+        let path = std::env::temp_dir().join(format!("tix-inventory-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut inventory = Inventory::load(&path).unwrap();
+        assert!(inventory.is_empty());
+        assert!(
+            inventory
+                .connected(&hello(ID, "DESK-01"), "10.0.0.7", 1_000)
+                .is_none()
+        );
+        inventory.rename(ID, "front desk").unwrap();
+        inventory.disconnected(ID, 1_500);
+        inventory.save().unwrap();
+
+        let loaded = Inventory::load(&path).unwrap();
+        let record = loaded.get(ID).unwrap();
+        assert_eq!(record.display_name(), "front desk");
+        assert_eq!(record.hostname, "DESK-01");
+        assert_eq!(record.last_ip, "10.0.0.7");
+        assert_eq!(record.last_seen, 1_500);
+        assert_eq!(loaded.len(), 1);
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(Inventory::load(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn reconnect_returns_the_previous_record() {
+        let mut inventory = Inventory::new();
+        inventory.connected(&hello(ID, "DESK-01"), "10.0.0.7", 1_000);
+        inventory.disconnected(ID, 2_000);
+        let previous = inventory
+            .connected(&hello(ID, "DESK-01"), "10.0.0.9", 3_000)
+            .unwrap();
+        assert_eq!(previous.last_ip, "10.0.0.7");
+        assert_eq!(previous.last_seen, 2_000);
+        assert_eq!(inventory.get(ID).unwrap().last_ip, "10.0.0.9");
+        assert!(inventory.save().is_ok());
+    }
+
+    #[test]
+    fn resolve_by_prefix_or_name() {
+        let mut inventory = Inventory::new();
+        inventory.connected(&hello(ID, "A"), "10.0.0.7", 1);
+        inventory.connected(
+            &hello("6f1c0000-0000-4000-8000-000000000000", "B"),
+            "10.0.0.8",
+            1,
+        );
+        inventory.rename(ID, "lab").unwrap();
+
+        assert_eq!(inventory.resolve(ID).unwrap(), ID);
+        assert_eq!(inventory.resolve("6f1c2").unwrap(), ID);
+        assert_eq!(inventory.resolve("lab").unwrap(), ID);
+        assert!(inventory.resolve("6f1c").is_err());
+        assert!(inventory.resolve("ffff").is_err());
+        assert!(inventory.rename("ffff", "x").is_err());
+    }
+
+    #[test]
+    fn ages_are_coarse() {
+        assert_eq!(format_age(100, 142), "42s");
+        assert_eq!(format_age(0, 600), "10m");
+        assert_eq!(format_age(0, 7_200), "2h");
+        assert_eq!(format_age(0, 3 * 86_400), "3d");
+        assert_eq!(format_age(50, 10), "0s");
+    }
+}
@@ -0,0 +1,333 @@
+//! Responses for requests the master is no longer waiting on.
+//!
+//! A slow slave may answer after the request timed out, a retry bug may
+//! answer twice, and a cancelled search keeps streaming until the
+//! cancel lands. [`LateResponses`] names each such packet from what
+//! [`MasterState`](tix_core::MasterState) remembers of the request, logs
+//! one faint `[LATE]` line for it and counts it for the `stats` command.
+//! A stream gets one line when its first orphaned packet arrives and one
+//! when it ends, however many packets come in between.
+//!
+//! A response carrying the nonce of an earlier session, a straggler
+//! from before a reconnect, is handled the same way but never looked up:
+//! its request ID means nothing in this session.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use tix_core::format::{format_bytes, format_duration};
+use tix_core::{Command, FinishedRequest, Packet, ProtocolFlags, RequestEnd};
+
+/// Prefix of every line logged here; the console draws them faint.
+pub const LATE_MARKER: &str = "[LATE]";
+
+/// Payload lines shown with `show_late_payload`.
+const PAYLOAD_PREVIEW_LINES: usize = 10;
+
+/// Counts since the slave connected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LateStats {
+    /// Responses to requests that had timed out or been cancelled.
+    pub late: u64,
+    /// Responses to requests already answered.
+    pub duplicate: u64,
+    /// Responses to request IDs the master has no record of.
+    pub unknown: u64,
+    /// Responses stamped with an earlier session's nonce.
+    pub other_session: u64,
+    /// Packets of orphaned streams, first and last included.
+    pub stream_packets: u64,
+    /// Payload bytes thrown away across all of the above.
+    pub discarded_bytes: u64,
+}
+
+/// An orphaned stream still sending.
+#[derive(Debug)]
+struct OrphanStream {
+    packets: u64,
+    bytes: u64,
+}
+
+/// Classifies and counts unexpected responses.
+#[derive(Debug, Default)]
+pub struct LateResponses {
+    /// Log the payload of late single responses too.
+    show_payload: bool,
+    stats: LateStats,
+    streams: HashMap<u64, OrphanStream>,
+}
+
+impl LateResponses {
+    pub fn new(show_payload: bool) -> Self {
+        Self {
+            show_payload,
+            ..Self::default()
+        }
+    }
+
+    pub fn stats(&self) -> LateStats {
+        self.stats
+    }
+
+    /// Forget counts and open streams, e.g. on disconnect.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.show_payload);
+    }
+
+    /// Account for `packet`, which answers no pending request.
+    /// `finished` is what is remembered of its request. Returns the
+    /// lines to log.
+    pub fn handle(
+        &mut self,
+        packet: &Packet,
+        finished: Option<&FinishedRequest>,
+        now: Instant,
+    ) -> Vec<String> {
+        self.account(packet, |late| late.classify(finished, now))
+    }
+
+    /// Account for `packet`, which carries another session's nonce.
+    /// Returns the lines to log.
+    pub fn handle_other_session(&mut self, packet: &Packet) -> Vec<String> {
+        self.account(packet, |late| {
+            late.stats.other_session += 1;
+            (
+                "stale",
+                format!(", from an earlier session ({:08x})", packet.session()),
+            )
+        })
+    }
+
+    /// Count `packet` and describe it, with `classify` naming its kind
+    /// once per response or stream.
+    fn account(
+        &mut self,
+        packet: &Packet,
+        classify: impl FnOnce(&mut Self) -> (&'static str, String),
+    ) -> Vec<String> {
+        let req_id = packet.request_id();
+        let bytes = packet.payload().len() as u64;
+        let flags = packet.flags();
+        let streaming = flags.contains(ProtocolFlags::STREAMING);
+        let last = flags.contains(ProtocolFlags::FINAL_FRAGMENT);
+        self.stats.discarded_bytes += bytes;
+
+        if let Some(stream) = self.streams.get_mut(&req_id) {
+            stream.packets += 1;
+            stream.bytes += bytes;
+            self.stats.stream_packets += 1;
+            if !last {
+                return Vec::new();
+            }
+            let stream = self.streams.remove(&req_id).unwrap_or(OrphanStream {
+                packets: 0,
+                bytes: 0,
+            });
+            return vec![format!(
+                "{} stream for req {} ended: {} packet(s), {} discarded",
+                LATE_MARKER,
+                req_id,
+                stream.packets,
+                format_bytes(stream.bytes)
+            )];
+        }
+
+        let (kind, context) = classify(self);
+        let command = match packet.command() {
+            Ok(cmd) => format!("{:?}", cmd),
+            Err(_) => "?".to_string(),
+        };
+        if streaming && !last {
+            self.stats.stream_packets += 1;
+            self.streams
+                .insert(req_id, OrphanStream { packets: 1, bytes });
+            return vec![format!(
+                "{} {} stream for req {} ({}){} — discarding until it ends",
+                LATE_MARKER, kind, req_id, command, context
+            )];
+        }
+
+        // Ping loss is already reported by the ping statistics.
+        if packet.command().ok() == Some(Command::Ping) {
+            return Vec::new();
+        }
+        let mut lines = vec![format!(
+            "{} {} response for req {} ({}){} — payload {} discarded",
+            LATE_MARKER,
+            kind,
+            req_id,
+            command,
+            context,
+            format_bytes(bytes)
+        )];
+        if self.show_payload {
+            let text = String::from_utf8_lossy(packet.payload());
+            lines.extend(
+                text.lines()
+                    .take(PAYLOAD_PREVIEW_LINES)
+                    .map(|line| format!("{}   {}", LATE_MARKER, line)),
+            );
+        }
+        lines
+    }
+
+    /// Count a new late response and describe it: its kind and why the
+    /// master was no longer waiting.
+    fn classify(
+        &mut self,
+        finished: Option<&FinishedRequest>,
+        now: Instant,
+    ) -> (&'static str, String) {
+        let Some(finished) = finished else {
+            self.stats.unknown += 1;
+            return ("unexpected", ", unknown request".to_string());
+        };
+        let ago = format_duration(now.saturating_duration_since(finished.at));
+        match finished.end {
+            RequestEnd::Answered => {
+                self.stats.duplicate += 1;
+                ("duplicate", format!(", already answered {} ago", ago))
+            }
+            RequestEnd::TimedOut => {
+                self.stats.late += 1;
+                ("late", format!(", already timed out {} ago", ago))
+            }
+            RequestEnd::Abandoned => {
+                self.stats.late += 1;
+                ("late", format!(", cancelled {} ago", ago))
+            }
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn finished(end: RequestEnd, at: Instant) -> FinishedRequest {
+        FinishedRequest {
+            command: Some(Command::Copy),
+            end,
+            at,
+        }
+    }
+
+    fn response(req_id: u64, payload: &[u8], flags: ProtocolFlags) -> Packet {
+        Packet::new_response_with_flags(req_id, Command::Copy, payload.to_vec(), flags).unwrap()
+    }
+
+    #[test]
+    fn late_response_after_timeout() {
+        let mut late = LateResponses::new(false);
+        let now = Instant::now();
+        let timed_out = finished(RequestEnd::TimedOut, now - Duration::from_secs(4));
+        let lines = late.handle(
+            &response(17, &[0; 2150], ProtocolFlags::empty()),
+            Some(&timed_out),
+            now,
+        );
+        assert_eq!(
+            lines,
+            vec![
+                "[LATE] late response for req 17 (Copy), already timed out 4.0s ago — \
+                 payload 2.1 KiB discarded"
+                    .to_string()
+            ]
+        );
+        let stats = late.stats();
+        assert_eq!(stats.late, 1);
+        assert_eq!(stats.discarded_bytes, 2150);
+    }
+
+    #[test]
+    fn duplicate_and_unknown_responses() {
+        let mut late = LateResponses::new(true);
+        let now = Instant::now();
+        let answered = finished(RequestEnd::Answered, now);
+        let lines = late.handle(
+            &response(3, b"Copied a -> b\nsecond line", ProtocolFlags::empty()),
+            Some(&answered),
+            now,
+        );
+        assert!(
+            lines[0].contains("duplicate response for req 3"),
+            "{:?}",
+            lines
+        );
+        assert_eq!(lines[1], "[LATE]   Copied a -> b");
+        assert_eq!(lines.len(), 3);
+
+        let lines = late.handle(&response(99, b"", ProtocolFlags::empty()), None, now);
+        assert!(lines[0].contains("unknown request"), "{:?}", lines);
+
+        let stats = late.stats();
+        assert_eq!((stats.duplicate, stats.unknown, stats.late), (1, 1, 0));
+    }
+
+    #[test]
+    fn other_session_responses_are_counted_apart() {
+        let mut late = LateResponses::new(false);
+        let mut packet = response(5, &[0; 10], ProtocolFlags::empty());
+        packet.set_session(0xAB);
+        let lines = late.handle_other_session(&packet);
+        assert_eq!(
+            lines,
+            vec![
+                "[LATE] stale response for req 5 (Copy), from an earlier session (000000ab) — \
+                 payload 10 B discarded"
+                    .to_string()
+            ]
+        );
+        let stats = late.stats();
+        assert_eq!((stats.other_session, stats.late, stats.unknown), (1, 0, 0));
+    }
+
+    #[test]
+    fn orphaned_stream_is_logged_once_and_summarised() {
+        let mut late = LateResponses::new(false);
+        let now = Instant::now();
+        let cancelled = finished(RequestEnd::Abandoned, now);
+        let first = late.handle(
+            &response(8, &[0; 100], ProtocolFlags::STREAMING),
+            Some(&cancelled),
+            now,
+        );
+        assert_eq!(first.len(), 1);
+        assert!(first[0].contains("late stream for req 8"), "{:?}", first);
+        for _ in 0..5 {
+            let lines = late.handle(
+                &response(8, &[0; 100], ProtocolFlags::STREAMING),
+                Some(&cancelled),
+                now,
+            );
+            assert!(lines.is_empty());
+        }
+        let end = late.handle(
+            &response(8, &[0; 10], ProtocolFlags::FINAL_FRAGMENT),
+            Some(&cancelled),
+            now,
+        );
+        assert_eq!(
+            end,
+            vec!["[LATE] stream for req 8 ended: 7 packet(s), 610 B discarded".to_string()]
+        );
+        let stats = late.stats();
+        assert_eq!(stats.late, 1, "a stream counts as one late response");
+        assert_eq!(stats.stream_packets, 7);
+
+        late.reset();
+        assert_eq!(late.stats(), LateStats::default());
+    }
+
+    #[test]
+    fn late_pongs_are_counted_quietly() {
+        let mut late = LateResponses::new(false);
+        let pong = Packet::new_response(5, Command::Ping, b"Pong".to_vec()).unwrap();
+        let lost = finished(RequestEnd::TimedOut, Instant::now());
+        assert!(late.handle(&pong, Some(&lost), Instant::now()).is_empty());
+        assert_eq!(late.stats().late, 1);
+    }
+}
@@ -1,5 +1,37 @@
 mod app;
+mod args;
+pub mod bridge;
+pub mod cache;
+pub mod commands;
+pub mod config;
+pub mod eventlog;
+pub mod inventory;
+pub mod late;
+pub mod localfs;
+pub mod localops;
 mod master;
+pub mod netdiag;
+pub mod notify;
+pub mod oneshot;
+pub mod pager;
+pub mod palette;
+pub mod ping;
+pub mod remote_path;
+pub mod retention;
+pub mod roles;
+pub mod schedule;
+pub mod search;
+pub mod selftest;
+pub mod services;
+pub mod shell_output;
+mod table;
+pub mod tasks;
+pub mod theme;
+pub mod transfers;
+mod update;
+pub mod watch;
+pub mod watchdog;
+pub mod wire;
 
-pub use app::{App, MasterEvent, Tab, UiEvent};
+pub use app::{App, ErrorSeverity, MasterEvent, Tab, UiEvent, error_line};
 pub use master::Master;
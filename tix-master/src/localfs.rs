@@ -0,0 +1,214 @@
+//! File management on the master's own disks, for the host side of the
+//! Tree Explorer.
+//!
+//! Every operation returns a message fit for the confirmation popup on
+//! failure, so the UI never has to interpret an `io::Error` itself.
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use tix_core::protocol::DeleteMode;
+
+/// Characters Windows refuses in a file name.
+const FORBIDDEN: &[char] = &['\\', '/', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Device names Windows reserves in every directory.
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Create, rename and delete host files and directories.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFsOps;
+
+impl LocalFsOps {
+    /// Check that `name` can be used as a single path component and
+    /// return it without surrounding whitespace.
+    pub fn validate_name(name: &str) -> Result<&str, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Name cannot be empty".to_string());
+        }
+        if name == "." || name == ".." {
+            return Err(format!("'{}' is not a valid name", name));
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|c| FORBIDDEN.contains(c) || c.is_control())
+        {
+            return Err(format!("Names cannot contain '{}'", c.escape_default()));
+        }
+        if name.ends_with('.') {
+            return Err("Names cannot end with a dot".to_string());
+        }
+        let stem = name.split('.').next().unwrap_or(name);
+        if RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+            return Err(format!("'{}' is reserved by Windows", stem));
+        }
+        Ok(name)
+    }
+
+    /// Create the directory `name` inside `parent`.
+    pub fn create_dir(&self, parent: &Path, name: &str) -> Result<PathBuf, String> {
+        let name = Self::validate_name(name)?;
+        let target = parent.join(name);
+        if target.symlink_metadata().is_ok() {
+            return Err(format!("'{}' already exists in {}", name, parent.display()));
+        }
+        std::fs::create_dir(&target).map_err(|e| describe(&e, &target))?;
+        Ok(target)
+    }
+
+    /// Give `path` the new file name `name`, keeping it in the same
+    /// directory. Changing only the case of the name is allowed.
+    pub fn rename(&self, path: &Path, name: &str) -> Result<PathBuf, String> {
+        let name = Self::validate_name(name)?;
+        let (Some(parent), Some(old)) = (path.parent(), path.file_name()) else {
+            return Err(format!("{} cannot be renamed", path.display()));
+        };
+        if old == name {
+            return Ok(path.to_path_buf());
+        }
+        let target = parent.join(name);
+        let case_only = old.to_string_lossy().eq_ignore_ascii_case(name);
+        if !case_only && target.symlink_metadata().is_ok() {
+            return Err(format!("'{}' already exists in {}", name, parent.display()));
+        }
+        std::fs::rename(path, &target).map_err(|e| describe(&e, path))?;
+        Ok(target)
+    }
+
+    /// Delete `path`, directories recursively, either to the Recycle Bin
+    /// or for good.
+    pub fn delete(&self, path: &Path, mode: DeleteMode) -> Result<(), String> {
+        let meta = path.symlink_metadata().map_err(|e| describe(&e, path))?;
+        if path.parent().is_none() {
+            return Err(format!("{} cannot be deleted", path.display()));
+        }
+        match mode {
+            DeleteMode::Recycle => trash::delete(path).map_err(|e| {
+                format!(
+                    "Could not move {} to the Recycle Bin: {}",
+                    path.display(),
+                    e
+                )
+            }),
+            DeleteMode::Permanent if meta.is_dir() => {
+                std::fs::remove_dir_all(path).map_err(|e| describe(&e, path))
+            }
+            DeleteMode::Permanent => std::fs::remove_file(path).map_err(|e| describe(&e, path)),
+        }
+    }
+}
+
+/// Popup text for an I/O failure on `path`.
+fn describe(err: &std::io::Error, path: &Path) -> String {
+    match err.kind() {
+        ErrorKind::PermissionDenied => format!("Permission denied: {}", path.display()),
+        ErrorKind::NotFound => format!("{} no longer exists", path.display()),
+        ErrorKind::AlreadyExists => format!("{} already exists", path.display()),
+        _ => format!("{}: {}", path.display(), err),
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::testing::temp_dir;
+
+    #[test]
+    fn names_are_validated() {
+        assert_eq!(LocalFsOps::validate_name("  notes  "), Ok("notes"));
+        assert_eq!(LocalFsOps::validate_name("console.log"), Ok("console.log"));
+        for bad in [
+            "",
+            "   ",
+            ".",
+            "..",
+            "a/b",
+            "a\\b",
+            "what?",
+            "tab\there",
+            "dot.",
+        ] {
+            assert!(LocalFsOps::validate_name(bad).is_err(), "{bad:?}");
+        }
+        let err = LocalFsOps::validate_name("nul.txt").unwrap_err();
+        assert!(err.contains("reserved"), "{err}");
+    }
+
+    #[test]
+    fn create_dir_refuses_existing_names() {
+        let dir = temp_dir("localfs-create");
+        let ops = LocalFsOps;
+
+        let made = ops.create_dir(&dir, "fresh").unwrap();
+        assert!(made.is_dir());
+        assert_eq!(made, dir.join("fresh"));
+
+        let err = ops.create_dir(&dir, "fresh").unwrap_err();
+        assert!(err.contains("already exists"), "{err}");
+        std::fs::write(dir.join("file"), b"x").unwrap();
+        assert!(ops.create_dir(&dir, "file").is_err());
+
+        let err = ops.create_dir(&dir.join("missing"), "sub").unwrap_err();
+        assert!(err.contains("no longer exists"), "{err}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rename_keeps_the_parent_and_refuses_collisions() {
+        let dir = temp_dir("localfs-rename");
+        let ops = LocalFsOps;
+        std::fs::write(dir.join("a.txt"), b"a").unwrap();
+        std::fs::write(dir.join("b.txt"), b"b").unwrap();
+
+        let err = ops.rename(&dir.join("a.txt"), "b.txt").unwrap_err();
+        assert!(err.contains("already exists"), "{err}");
+        assert_eq!(std::fs::read(dir.join("b.txt")).unwrap(), b"b");
+
+        let renamed = ops.rename(&dir.join("a.txt"), "c.txt").unwrap();
+        assert_eq!(renamed, dir.join("c.txt"));
+        assert_eq!(std::fs::read(&renamed).unwrap(), b"a");
+        assert!(!dir.join("a.txt").exists());
+
+        // Same name is a no-op, a case change goes through.
+        assert_eq!(ops.rename(&renamed, "c.txt").unwrap(), renamed);
+        assert_eq!(ops.rename(&renamed, "C.txt").unwrap(), dir.join("C.txt"));
+
+        assert!(ops.rename(&dir.join("gone"), "x").is_err());
+        assert!(ops.rename(&dir.join("C.txt"), "../escape").is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn permanent_delete_removes_files_and_trees() {
+        let dir = temp_dir("localfs-delete");
+        let ops = LocalFsOps;
+        std::fs::create_dir_all(dir.join("tree/deep")).unwrap();
+        std::fs::write(dir.join("tree/deep/f"), b"f").unwrap();
+        std::fs::write(dir.join("loose"), b"l").unwrap();
+
+        ops.delete(&dir.join("tree"), DeleteMode::Permanent)
+            .unwrap();
+        ops.delete(&dir.join("loose"), DeleteMode::Permanent)
+            .unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let err = ops
+            .delete(&dir.join("loose"), DeleteMode::Permanent)
+            .unwrap_err();
+        assert!(err.contains("no longer exists"), "{err}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn drive_roots_are_left_alone() {
+        let root = Path::new("/");
+        assert!(LocalFsOps.rename(root, "x").is_err());
+        assert!(LocalFsOps.delete(root, DeleteMode::Permanent).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! Host file system work for the Tree Explorer, off the UI task.
+//!
+//! Listing a directory on a slow share or copying a large folder can take
+//! seconds. The console hands that work to a [`LocalOpsWorker`], a thread
+//! of its own that runs one [`LocalOp`] at a time and reports back with
+//! [`MasterEvent`]s, so the screen keeps redrawing and the master's events
+//! keep flowing while it runs.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc as std_mpsc;
+use std::time::{Duration, Instant};
+
+use tix_core::protocol::{FileAttributes, is_reparse_point};
+use tokio::sync::mpsc;
+
+use crate::app::{FileNode, MasterEvent};
+
+/// Least time between two progress reports of a copy.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Work the worker does on the host.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocalOp {
+    /// Read the entries of `dir`, answered with a [`LocalListing`].
+    List {
+        dir: PathBuf,
+        /// Open the directory once it is listed.
+        open: bool,
+        /// Put the cursor on this entry once it is listed.
+        select: Option<PathBuf>,
+    },
+    /// Copy files and directories into `dest_dir`, reported with
+    /// [`CopyProgress`].
+    Copy {
+        sources: Vec<PathBuf>,
+        dest_dir: PathBuf,
+    },
+}
+
+/// The entries of a host directory.
+#[derive(Debug, Clone)]
+pub struct LocalListing {
+    pub dir: PathBuf,
+    /// Sorted entries; `None` when the directory could not be read.
+    pub children: Option<Vec<FileNode>>,
+    pub open: bool,
+    pub select: Option<PathBuf>,
+}
+
+/// How far a copy has got.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CopyProgress {
+    pub dest_dir: PathBuf,
+    pub files: u64,
+    pub bytes: u64,
+    /// Set on the last report of the copy.
+    pub done: bool,
+    /// One line per item that could not be copied.
+    pub errors: Vec<String>,
+}
+
+/// Handle to the thread that runs [`LocalOp`]s.
+#[derive(Debug)]
+pub struct LocalOpsWorker {
+    ops: std_mpsc::Sender<LocalOp>,
+}
+
+impl LocalOpsWorker {
+    /// Start the worker. Its reports arrive on `events`; it stops once
+    /// the handle is dropped and the queued operations are done.
+    pub fn spawn(events: mpsc::UnboundedSender<MasterEvent>) -> io::Result<Self> {
+        Self::spawn_reporting(move |event| {
+            let _ = events.send(event);
+        })
+    }
+
+    /// [`spawn`](Self::spawn) with every report handed to `report`.
+    pub(crate) fn spawn_reporting(
+        mut report: impl FnMut(MasterEvent) + Send + 'static,
+    ) -> io::Result<Self> {
+        let (ops, queue) = std_mpsc::channel::<LocalOp>();
+        std::thread::Builder::new()
+            .name("tix-local-ops".to_string())
+            .spawn(move || {
+                for op in queue {
+                    run(op, &mut report);
+                }
+            })?;
+        Ok(Self { ops })
+    }
+
+    /// Queue `op` behind whatever the worker is doing. The op comes
+    /// back if the worker is gone.
+    pub fn submit(&self, op: LocalOp) -> Result<(), LocalOp> {
+        self.ops.send(op).map_err(|e| e.0)
+    }
+}
+
+/// Run `op` on the calling thread, handing every report to `report`.
+pub fn run(op: LocalOp, report: &mut dyn FnMut(MasterEvent)) {
+    match op {
+        LocalOp::List { dir, open, select } => {
+            let children = list_dir(&dir);
+            report(MasterEvent::LocalListing(LocalListing {
+                dir,
+                children,
+                open,
+                select,
+            }));
+        }
+        LocalOp::Copy { sources, dest_dir } => copy_into(&sources, &dest_dir, report),
+    }
+}
+
+/// Entries of `dir`, directories first, then by name.
+pub fn list_dir(dir: &Path) -> Option<Vec<FileNode>> {
+    let entries = std::fs::read_dir(dir).ok()?;
+    let mut children = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_dir = path.is_dir();
+        let meta = entry.metadata().ok();
+        let attributes = meta
+            .as_ref()
+            .map_or(FileAttributes::empty(), FileAttributes::of);
+        let size = meta.filter(|m| m.is_file()).map(|m| m.len());
+        children.push(FileNode {
+            name,
+            path,
+            is_dir,
+            is_expanded: false,
+            children: None,
+            is_selected: false,
+            attributes,
+            size,
+        });
+    }
+    children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+    Some(children)
+}
+
+fn copy_into(sources: &[PathBuf], dest_dir: &Path, report: &mut dyn FnMut(MasterEvent)) {
+    let mut copy = Copy {
+        progress: CopyProgress {
+            dest_dir: dest_dir.to_path_buf(),
+            ..CopyProgress::default()
+        },
+        last_report: Instant::now(),
+        report,
+    };
+    for src in sources {
+        let Some(name) = src.file_name() else {
+            copy.fail(format!("{} cannot be copied", src.display()));
+            continue;
+        };
+        if src.is_dir() && dest_dir.starts_with(src) {
+            copy.fail(format!("{} cannot be copied into itself", src.display()));
+            continue;
+        }
+        let dest = dest_dir.join(name);
+        let result = if src.is_dir() {
+            copy.tree(src, &dest)
+        } else {
+            copy.file(src, &dest)
+        };
+        if let Err(e) = result {
+            copy.fail(format!("{}: {}", src.display(), e));
+        }
+    }
+    copy.progress.done = true;
+    (copy.report)(MasterEvent::LocalCopy(copy.progress));
+}
+
+/// A copy in progress.
+struct Copy<'a> {
+    progress: CopyProgress,
+    last_report: Instant,
+    report: &'a mut dyn FnMut(MasterEvent),
+}
+
+impl Copy<'_> {
+    fn tree(&mut self, src: &Path, dst: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dst)?;
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            let ty = entry.file_type()?;
+            if is_reparse_point(&entry.metadata()?) && entry.path().is_dir() {
+                // Not followed: a junction back up the tree would copy forever.
+                continue;
+            }
+            if ty.is_dir() {
+                self.tree(&entry.path(), &dst.join(entry.file_name()))?;
+            } else {
+                self.file(&entry.path(), &dst.join(entry.file_name()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn file(&mut self, src: &Path, dst: &Path) -> io::Result<()> {
+        let bytes = std::fs::copy(src, dst)?;
+        self.progress.files += 1;
+        self.progress.bytes += bytes;
+        if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.last_report = Instant::now();
+            (self.report)(MasterEvent::LocalCopy(self.progress.clone()));
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, error: String) {
+        self.progress.errors.push(error);
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::testing::temp_dir;
+
+    /// Every path under `root` with the contents of the files, sorted.
+    fn snapshot(root: &Path) -> Vec<(PathBuf, Option<Vec<u8>>)> {
+        fn walk(root: &Path, dir: &Path, out: &mut Vec<(PathBuf, Option<Vec<u8>>)>) {
+            for entry in std::fs::read_dir(dir).unwrap().flatten() {
+                let path = entry.path();
+                let relative = path.strip_prefix(root).unwrap().to_path_buf();
+                if path.is_dir() {
+                    out.push((relative, None));
+                    walk(root, &path, out);
+                } else {
+                    out.push((relative, Some(std::fs::read(&path).unwrap())));
+                }
+            }
+        }
+        let mut out = Vec::new();
+        walk(root, root, &mut out);
+        out.sort();
+        out
+    }
+
+    /// Run `op` to the end on a worker and return its reports.
+    fn run_on_worker(op: LocalOp) -> Vec<MasterEvent> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let worker = LocalOpsWorker::spawn(tx).unwrap();
+        worker.submit(op).unwrap();
+        drop(worker);
+        let mut events = Vec::new();
+        while let Some(event) = rx.blocking_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn worker_copies_the_same_tree_as_the_source() {
+        let dir = temp_dir("localops-copy");
+        let src = dir.join("project");
+        std::fs::create_dir_all(src.join("src/deep")).unwrap();
+        std::fs::create_dir_all(src.join("empty")).unwrap();
+        std::fs::write(src.join("README"), b"readme").unwrap();
+        std::fs::write(src.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(src.join("src/deep/data.bin"), vec![7u8; 70_000]).unwrap();
+        std::fs::write(dir.join("loose.txt"), b"loose").unwrap();
+        let dest = dir.join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+
+        let events = run_on_worker(LocalOp::Copy {
+            sources: vec![src.clone(), dir.join("loose.txt")],
+            dest_dir: dest.clone(),
+        });
+
+        assert_eq!(snapshot(&dest.join("project")), snapshot(&src));
+        assert_eq!(std::fs::read(dest.join("loose.txt")).unwrap(), b"loose");
+        let Some(MasterEvent::LocalCopy(last)) = events.last() else {
+            panic!("no final report: {events:?}");
+        };
+        assert!(last.done && last.errors.is_empty(), "{last:?}");
+        assert_eq!(last.files, 4);
+        assert_eq!(last.bytes, 6 + 12 + 70_000 + 5);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn copy_into_itself_is_refused() {
+        let dir = temp_dir("localops-self");
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+
+        let events = run_on_worker(LocalOp::Copy {
+            sources: vec![dir.join("a"), dir.join("missing")],
+            dest_dir: dir.join("a/b"),
+        });
+        let Some(MasterEvent::LocalCopy(last)) = events.last() else {
+            panic!("no final report: {events:?}");
+        };
+        assert_eq!(last.errors.len(), 2, "{:?}", last.errors);
+        assert!(last.errors[0].contains("into itself"));
+        assert!(!dir.join("a/b/a").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn listings_sort_directories_first() {
+        let dir = temp_dir("localops-list");
+        std::fs::create_dir_all(dir.join("zeta")).unwrap();
+        std::fs::write(dir.join("alpha.txt"), b"12345").unwrap();
+
+        let events = run_on_worker(LocalOp::List {
+            dir: dir.clone(),
+            open: true,
+            select: None,
+        });
+        let [MasterEvent::LocalListing(listing)] = events.as_slice() else {
+            panic!("unexpected reports: {events:?}");
+        };
+        let children = listing.children.as_ref().unwrap();
+        let names: Vec<_> = children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["zeta", "alpha.txt"]);
+        assert_eq!(children[0].size, None);
+        assert_eq!(children[1].size, Some(5));
+        assert!(list_dir(&dir.join("missing")).is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -8,22 +8,292 @@
 ##    ## ##     ##  ######     ##    ######## ##    ##
 */
 
+use clap::{Args, Parser, Subcommand};
 use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use ratatui::{Terminal, backend::CrosstermBackend};
-use std::time::Duration;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 use tix_core::ConnectionInfo;
-use tix_master::{App, Master, MasterEvent, UiEvent};
+use tix_core::config;
+use tix_core::paste_guard::PasteGuard;
+use tix_core::protocol::DeleteMode;
+use tix_master::bridge::{self, DEFAULT_BRIDGE_PORT};
+use tix_master::commands::ConfirmGate;
+use tix_master::config::{DEFAULT_CONFIG_PATH, MasterConfig};
+use tix_master::inventory::Inventory;
+use tix_master::localops::LocalOpsWorker;
+use tix_master::notify::Notifier;
+use tix_master::oneshot::{self, EXIT_FAILED, EXIT_USAGE, Report, Target};
+use tix_master::pager::{DEFAULT_LOG_CAPACITY, DEFAULT_PAGE_THRESHOLD};
+use tix_master::retention::{Artifact, CLEAN_INTERVAL, DataStore};
+use tix_master::roles::{self, Roles};
+use tix_master::services::ServiceGuard;
+use tix_master::theme::Theme;
+use tix_master::watchdog::UiWatchdog;
+use tix_master::wire;
+use tix_master::{App, Master, MasterEvent, UiEvent, error_line};
 use tokio::sync::mpsc;
 
+#[derive(Parser)]
+#[command(
+    name = "tix-master",
+    about = "TIX master console and one-shot commands"
+)]
+struct Cli {
+    /// Without a subcommand the interactive console starts.
+    #[command(subcommand)]
+    command: Option<Mode>,
+    /// Run on the defaults when tix-master.toml is invalid instead of
+    /// exiting
+    #[arg(long, global = true)]
+    ignore_config_errors: bool,
+    /// Start in this operator role from tix-master.toml; the commands it
+    /// does not allow are never sent
+    #[arg(long, global = true)]
+    role: Option<String>,
+    /// Delete what the master keeps on disk: history, transcripts,
+    /// caches, journals, or all of them when no artifact is given
+    #[arg(
+        long,
+        value_name = "ARTIFACT",
+        num_args = 0..=1,
+        default_missing_value = "all"
+    )]
+    purge_data: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Mode {
+    /// Start the interactive console (the default)
+    Tui,
+    /// Run one console command on a slave and print its reply
+    Exec {
+        #[command(flatten)]
+        opts: OneShotOpts,
+        /// Run a destructive command (Delete, SystemAction shutdown, ...)
+        /// without being asked; without it such commands fail
+        #[arg(long, short = 'y')]
+        yes: bool,
+        /// Console command, e.g. `ShellExecute whoami`; several words are
+        /// joined with spaces
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Copy a local file to a slave
+    Upload {
+        #[command(flatten)]
+        opts: OneShotOpts,
+        /// Send a file over `paste_guard.confirm_bytes` without being
+        /// asked; without it such files fail
+        #[arg(long, short = 'y')]
+        yes: bool,
+        local: PathBuf,
+        remote: String,
+    },
+    /// Copy a file from a slave
+    Download {
+        #[command(flatten)]
+        opts: OneShotOpts,
+        remote: String,
+        local: PathBuf,
+    },
+    /// Read a password from stdin and print the hash a role's `password`
+    /// setting takes
+    HashPassword,
+}
+
+#[derive(Args)]
+struct OneShotOpts {
+    /// Address to listen on for the slave, or the slave's with --connect
+    #[arg(long, default_value = "127.0.0.1:4321")]
+    target: String,
+    /// Dial a listening slave instead of waiting for one
+    #[arg(long)]
+    connect: bool,
+    /// Give up after this long: 500ms, 30s, 5m
+    #[arg(long, default_value = "30s", value_parser = oneshot::parse_timeout)]
+    timeout: Duration,
+    /// Print the result as one JSON object
+    #[arg(long)]
+    json: bool,
+}
+
 #[tokio::main]
 pub async fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+    if let Some(what) = &cli.purge_data {
+        return purge_data(what, cli.ignore_config_errors);
+    }
+    let (opts, report) = match cli.command.unwrap_or(Mode::Tui) {
+        Mode::Tui => return run_tui(cli.ignore_config_errors, cli.role).await,
+        Mode::HashPassword => return hash_password(),
+        Mode::Exec { opts, yes, command } => {
+            let (config, _) = config::load_or_exit::<MasterConfig>(
+                std::path::Path::new(DEFAULT_CONFIG_PATH),
+                cli.ignore_config_errors,
+            );
+            let gate = ConfirmGate::new(&config.commands)
+                .with_paste_guard(PasteGuard::new(&config.paste_guard));
+            let roles = roles_or_exit(&config, cli.role.as_deref());
+            let report = match Target::parse(&opts.target, opts.connect) {
+                Ok(target) => {
+                    let command = command.join(" ");
+                    oneshot::exec(&target, &command, opts.timeout, gate, roles, yes).await
+                }
+                Err(e) => usage_error(e),
+            };
+            (opts, report)
+        }
+        Mode::Upload {
+            opts,
+            yes,
+            local,
+            remote,
+        } => {
+            let (config, _) = config::load_or_exit::<MasterConfig>(
+                std::path::Path::new(DEFAULT_CONFIG_PATH),
+                cli.ignore_config_errors,
+            );
+            let guard = PasteGuard::new(&config.paste_guard);
+            let report = match Target::parse(&opts.target, opts.connect) {
+                Ok(target) => {
+                    oneshot::upload(&target, &local, &remote, opts.timeout, guard, yes).await
+                }
+                Err(e) => usage_error(e),
+            };
+            (opts, report)
+        }
+        Mode::Download {
+            opts,
+            remote,
+            local,
+        } => {
+            let report = match Target::parse(&opts.target, opts.connect) {
+                Ok(target) => oneshot::download(&target, &remote, &local, opts.timeout).await,
+                Err(e) => usage_error(e),
+            };
+            (opts, report)
+        }
+    };
+
+    if opts.json {
+        println!("{}", report.to_json());
+    } else if report.ok() {
+        println!("{}", report.output);
+    } else {
+        eprintln!("{}", report.output);
+    }
+    std::process::exit(report.exit_code);
+}
+
+/// The roles in `config`, in `role` if given; an unknown one stops the
+/// master before it connects to anything.
+fn roles_or_exit(config: &MasterConfig, role: Option<&str>) -> Roles {
+    let mut roles = Roles::new(&config.role);
+    if let Some(role) = role
+        && let Err(e) = roles.start_as(role)
+    {
+        eprintln!("--role: {}", e);
+        std::process::exit(EXIT_USAGE);
+    }
+    roles
+}
+
+/// `hash-password`: one line from stdin in, its argon2 hash out.
+fn hash_password() -> std::io::Result<()> {
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        eprintln!("hash-password: no password on stdin");
+        std::process::exit(EXIT_USAGE);
+    }
+    match roles::hash_password(password) {
+        Ok(hash) => println!("{}", hash),
+        Err(e) => {
+            eprintln!("hash-password: {}", e);
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+    Ok(())
+}
+
+/// `--purge-data`: delete the files of one artifact, or of every one,
+/// and say what went.
+fn purge_data(what: &str, ignore_config_errors: bool) -> std::io::Result<()> {
+    let only = match what {
+        "all" => None,
+        name => match name.parse::<Artifact>() {
+            Ok(artifact) => Some(artifact),
+            Err(e) => {
+                eprintln!("--purge-data: {}", e);
+                std::process::exit(EXIT_USAGE);
+            }
+        },
+    };
+    let (config, _) = config::load_or_exit::<MasterConfig>(
+        std::path::Path::new(DEFAULT_CONFIG_PATH),
+        ignore_config_errors,
+    );
+    let store = DataStore::new(&config.data, &config.watch.cache_dir);
+    let report = store.purge(only);
+    if report.is_empty() {
+        println!("Nothing to purge: {} is not kept on disk", what);
+    }
+    for removed in &report {
+        println!("{}", removed);
+    }
+    if report.iter().any(|removed| !removed.errors.is_empty()) {
+        std::process::exit(EXIT_FAILED);
+    }
+    Ok(())
+}
+
+fn usage_error(message: String) -> Report {
+    Report {
+        command: String::new(),
+        request_id: None,
+        duration_ms: 0,
+        exit_code: EXIT_USAGE,
+        output: message,
+        transfer: None,
+    }
+}
+
+/// The interactive console.
+async fn run_tui(ignore_config_errors: bool, role: Option<String>) -> std::io::Result<()> {
+    // A bad config file stops the console before it takes the terminal.
+    let (config, config_note) = config::load_or_exit::<MasterConfig>(
+        std::path::Path::new(DEFAULT_CONFIG_PATH),
+        ignore_config_errors,
+    );
+    let roles = roles_or_exit(&config, role.as_deref());
+    let data = DataStore::new(&config.data, &config.watch.cache_dir);
+
     // 1. Setup communication channels
     let (master_tx, mut master_rx) = mpsc::unbounded_channel::<MasterEvent>();
+
+    // Old history, transcripts and caches go at startup and once a day.
+    let cleaner = data.clone();
+    let cleanup_tx = master_tx.clone();
+    std::thread::spawn(move || {
+        loop {
+            for removed in cleaner.clean(SystemTime::now()) {
+                let line = format!("[DATA] Expired {}", removed);
+                if cleanup_tx.send(MasterEvent::log(line)).is_err() {
+                    return;
+                }
+            }
+            std::thread::sleep(CLEAN_INTERVAL);
+        }
+    });
     let (ui_tx, mut ui_rx) = mpsc::unbounded_channel::<UiEvent>();
     let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel::<String>();
 
     // 2. Spawn Input Task (Dedicated thread for blocking crossterm poll)
     let input_ui_tx = ui_tx.clone();
+    // A failed send ends the loop, so each arm keeps its own `if`.
+    #[allow(clippy::collapsible_match)]
     tokio::task::spawn_blocking(move || {
         loop {
             if event::poll(Duration::from_millis(10)).unwrap_or(false)
@@ -47,13 +317,38 @@ pub async fn main() -> std::io::Result<()> {
     });
 
     // 3. Spawn Master Task
+    let ui_config = config.ui.clone();
+    let service_guard = ServiceGuard::new(&config.services);
+    let confirm_gate =
+        ConfirmGate::new(&config.commands).with_paste_guard(PasteGuard::new(&config.paste_guard));
+    let notifier = Notifier::new(config.notify.clone()).with_events(master_tx.clone());
+    let local_ops = LocalOpsWorker::spawn(master_tx.clone())?;
     let master_event_tx = master_tx.clone();
+    wire::forward_logs(master_tx.clone());
     tokio::spawn(async move {
         let conn_info = ConnectionInfo::new("127.0.0.1".to_string(), 4321);
         let mut master = match Master::listen(conn_info, master_event_tx.clone()).await {
-            Ok(m) => m,
+            Ok(m) => {
+                let inventory = Inventory::load(&config.inventory.path).unwrap_or_else(|e| {
+                    let _ = master_event_tx.send(MasterEvent::log(format!(
+                        "Cannot read slave inventory {}; known slaves are not remembered",
+                        e
+                    )));
+                    Inventory::new()
+                });
+                m.with_watch_config(&config.watch)
+                    .with_responses_config(&config.responses)
+                    .with_inventory(inventory)
+                    .with_services_config(&config.services)
+                    .with_commands_config(&config.commands)
+                    .with_paste_guard_config(&config.paste_guard)
+                    .with_transfer_config(&config.transfer)
+                    .with_cache_config(&config.cache)
+                    .with_wire_config(&config.wire)
+                    .with_roles(roles)
+            }
             Err(e) => {
-                let _ = master_event_tx.send(MasterEvent::Log(format!(
+                let _ = master_event_tx.send(MasterEvent::log(format!(
                     "Critical Error: Failed to start listener: {}",
                     e
                 )));
@@ -61,16 +356,55 @@ pub async fn main() -> std::io::Result<()> {
             }
         };
 
+        // With `tls.enabled` a slave that cannot do TLS is not let in,
+        // so a certificate that cannot be loaded stops the listener.
+        if config.tls.enabled {
+            match config.tls.acceptor() {
+                Ok(acceptor) => {
+                    let _ = master_event_tx.send(MasterEvent::log(format!(
+                        "[TLS ] Slaves connect over TLS; certificate SHA-256 {}",
+                        acceptor.fingerprint()
+                    )));
+                    master = master.with_tls(acceptor);
+                }
+                Err(e) => {
+                    let _ = master_event_tx.send(MasterEvent::log(format!(
+                        "Critical Error: Cannot set up TLS: {}",
+                        e
+                    )));
+                    return;
+                }
+            }
+        }
+
+        // RDP bridge for `tix-rdp-gui --via-master`
+        let bridge_info = ConnectionInfo::new("127.0.0.1".to_string(), DEFAULT_BRIDGE_PORT);
+        let mut bridge_rx = match bridge::spawn(bridge_info, master_event_tx.clone()).await {
+            Ok(handle) => {
+                master.attach_bridge(handle.to_viewer);
+                Some(handle.from_viewer)
+            }
+            Err(e) => {
+                let _ = master_event_tx.send(MasterEvent::log(format!(
+                    "RDP bridge disabled: failed to bind port {}: {}",
+                    DEFAULT_BRIDGE_PORT, e
+                )));
+                None
+            }
+        };
+
         // Interval for checking request timeouts
         let mut timeout_check = tokio::time::interval(Duration::from_secs(2));
         timeout_check.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         loop {
+            let ping_due = master.next_ping_due();
+            let watch_due = master.next_watch_due();
             tokio::select! {
                 // Handle commands from UI
                 Some(cmd) = cmd_rx.recv() => {
                     if let Err(e) = master.execute_command(cmd).await {
-                        let _ = master_event_tx.send(MasterEvent::Log(format!("Command Error: {}", e)));
+                        let _ = master_event_tx.send(MasterEvent::log(error_line(&e)));
                     }
                 }
 
@@ -83,6 +417,36 @@ pub async fn main() -> std::io::Result<()> {
                     }
                 } => {}
 
+                // Relay RDP viewer traffic to the slave
+                Some(pkt) = async {
+                    match bridge_rx.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    master.forward_from_bridge(pkt).await;
+                }
+
+                // Next probe of a running `ping -c N`
+                _ = async {
+                    match ping_due {
+                        Some(due) => tokio::time::sleep_until(due.into()).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    master.send_due_pings().await;
+                }
+
+                // Next hash check of a running `watch`
+                _ = async {
+                    match watch_due {
+                        Some(due) => tokio::time::sleep_until(due.into()).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    master.poll_watches().await;
+                }
+
                 // Check for timed-out requests
                 _ = timeout_check.tick() => {
                     master.check_timeouts();
@@ -97,25 +461,105 @@ pub async fn main() -> std::io::Result<()> {
     let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
     terminal.clear()?;
 
-    let mut app = App::new();
+    // Paging threshold and log size can be tuned from the environment
+    let env_lines = |name: &str, default: usize| {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    };
+    let mut app = App::new()
+        .with_paging(
+            env_lines("TIX_PAGE_LINES", DEFAULT_PAGE_THRESHOLD),
+            env_lines("TIX_LOG_LINES", DEFAULT_LOG_CAPACITY),
+        )
+        .with_theme(Theme::named(ui_config.theme))
+        .with_reduced_motion(ui_config.reduced_motion)
+        .with_task_history(ui_config.task_history)
+        .with_local_ops(local_ops)
+        .with_notifier(notifier)
+        .with_service_guard(service_guard)
+        .with_confirm_gate(confirm_gate);
+    if let Some(history) = data.history_file() {
+        app = app.with_history_file(history);
+    }
+    match data.transcript() {
+        Some(Ok(transcript)) => app.logs.set_transcript(transcript),
+        Some(Err(e)) => app.logs.push(format!("[DATA] Cannot start a transcript: {}", e)),
+        None => {}
+    }
+    if let Some(note) = config_note {
+        app.logs.push(note);
+    }
 
     // 5. Main UI Event Loop (Purely Reactive)
+    // Each pass is timed, so work that blocks the loop shows up in the log.
+    let mut watchdog = UiWatchdog::default();
     loop {
         terminal.draw(|f| app.draw(f))?;
+        if let Some(warning) = watchdog.finish() {
+            app.logs.push(warning);
+        }
 
         tokio::select! {
             // Handle Master events (Logs, Slave status, Task updates)
             Some(event) = master_rx.recv() => {
+                watchdog.start(format!("master event {}", event.kind()));
                 app.update(event);
             }
 
             // Handle UI events (Keyboard, Resize)
             Some(event) = ui_rx.recv() => {
+                if let UiEvent::Key(key) = &event {
+                    watchdog.start(format!("key {:?}", key.code));
+                }
                 match event {
                     UiEvent::Key(key) => {
                         if key.kind == KeyEventKind::Press {
                             match key.code {
                                 KeyCode::Char('c') if key.modifiers.contains(event::KeyModifiers::CONTROL) => break,
+
+                                // An open pager takes every other key
+                                _ if app.pager.is_some() => app.handle_pager_key(key),
+
+                                // So does the command confirmation popup
+                                KeyCode::Char('y') | KeyCode::Char('Y') if app.pending_command.is_some() => {
+                                    for cmd in app.confirm_command() {
+                                        let _ = cmd_tx.send(cmd);
+                                    }
+                                }
+                                _ if app.pending_command.is_some() => app.cancel_command(),
+
+                                // Delete confirmation popup swallows every key
+                                KeyCode::Char('y') | KeyCode::Char('Y') if app.tree_explorer.pending_delete.is_some() => {
+                                    if let Some(cmd) = app.confirm_delete() {
+                                        let _ = cmd_tx.send(cmd);
+                                    }
+                                }
+                                _ if app.tree_explorer.pending_delete.is_some() => app.cancel_delete(),
+
+                                // Name popup takes typing until Enter or Esc
+                                KeyCode::Enter if app.tree_explorer.pending_name.is_some() => app.confirm_name_input(),
+                                KeyCode::Esc if app.tree_explorer.pending_name.is_some() => app.cancel_name_input(),
+                                KeyCode::Backspace if app.tree_explorer.pending_name.is_some() => app.name_input_pop(),
+                                KeyCode::Char(c) if app.tree_explorer.pending_name.is_some() => app.name_input_push(c),
+                                _ if app.tree_explorer.pending_name.is_some() => {}
+
+                                // The command palette keeps the focus until Enter or Esc
+                                _ if app.palette.is_some() => {
+                                    if let Some(cmd) = app.handle_palette_key(key) {
+                                        app.logs.push(format!("> {}", roles::redact(&cmd)));
+                                        let _ = cmd_tx.send(cmd);
+                                    }
+                                }
+                                KeyCode::Char('p') if key.modifiers.contains(event::KeyModifiers::CONTROL) => app.open_palette(),
+                                KeyCode::Char('t') if key.modifiers.contains(event::KeyModifiers::CONTROL) => app.toggle_log_times(),
+
+                                // F2 renames on the host tree, switches tabs elsewhere
+                                KeyCode::F(2) if app.active_tab == tix_master::Tab::TreeExplorer && !app.tree_explorer.active_side => {
+                                    app.tree_request_rename();
+                                }
+
                                 KeyCode::F(1) => app.set_tab(tix_master::Tab::Main),
                                 KeyCode::F(2) => {
                                     app.set_tab(tix_master::Tab::TreeExplorer);
@@ -127,7 +571,11 @@ pub async fn main() -> std::io::Result<()> {
                                 },
                                 KeyCode::F(3) => app.set_tab(tix_master::Tab::SystemSettings),
                                 KeyCode::Char('q') => app.exit = true,
-                                KeyCode::Esc => app.handle_esc(),
+                                KeyCode::Esc => {
+                                    if let Some(cmd) = app.handle_esc() {
+                                        let _ = cmd_tx.send(cmd);
+                                    }
+                                }
 
                                 // Tab-specific navigation
                                 KeyCode::Up if app.active_tab == tix_master::Tab::TreeExplorer => app.tree_cursor_up(),
@@ -152,6 +600,41 @@ pub async fn main() -> std::io::Result<()> {
                                         let _ = cmd_tx.send(cmd);
                                     }
                                 }
+                                KeyCode::Delete if app.active_tab == tix_master::Tab::TreeExplorer => {
+                                    let mode = if key.modifiers.contains(event::KeyModifiers::SHIFT) {
+                                        DeleteMode::Permanent
+                                    } else {
+                                        DeleteMode::Recycle
+                                    };
+                                    app.tree_request_delete(mode);
+                                }
+                                KeyCode::Char('n') if app.active_tab == tix_master::Tab::TreeExplorer => app.tree_request_new_folder(),
+                                KeyCode::Char('h') if app.active_tab == tix_master::Tab::TreeExplorer => app.tree_toggle_hidden(),
+                                KeyCode::Char('u') if app.active_tab == tix_master::Tab::TreeExplorer => {
+                                    for cmd in app.tree_undo_delete() {
+                                        let _ = cmd_tx.send(cmd);
+                                    }
+                                }
+                                KeyCode::Char('T') if app.active_tab == tix_master::Tab::TreeExplorer => app.transfer_panel.toggle(),
+
+                                // Transfers panel, while it is shown
+                                KeyCode::Char('[') if app.active_tab == tix_master::Tab::TreeExplorer && app.transfer_panel.is_visible() => app.transfer_panel.select_prev(),
+                                KeyCode::Char(']') if app.active_tab == tix_master::Tab::TreeExplorer && app.transfer_panel.is_visible() => app.transfer_panel.select_next(),
+                                KeyCode::Char('p') if app.active_tab == tix_master::Tab::TreeExplorer && app.transfer_panel.is_visible() => {
+                                    if let Some(cmd) = app.transfer_toggle_pause() {
+                                        let _ = cmd_tx.send(cmd);
+                                    }
+                                }
+                                KeyCode::Char('X') if app.active_tab == tix_master::Tab::TreeExplorer && app.transfer_panel.is_visible() => {
+                                    if let Some(cmd) = app.transfer_cancel() {
+                                        let _ = cmd_tx.send(cmd);
+                                    }
+                                }
+                                KeyCode::Char('r') if app.active_tab == tix_master::Tab::TreeExplorer && app.transfer_panel.is_visible() => {
+                                    if let Some(cmd) = app.transfer_retry() {
+                                        let _ = cmd_tx.send(cmd);
+                                    }
+                                }
 
                                 // System tab actions
                                 KeyCode::Char('1') if app.active_tab == tix_master::Tab::SystemSettings => {
@@ -177,7 +660,7 @@ pub async fn main() -> std::io::Result<()> {
                                 KeyCode::Up if app.active_tab == tix_master::Tab::Main => app.handle_up(),
                                 KeyCode::Down if app.active_tab == tix_master::Tab::Main => app.handle_down(),
                                 KeyCode::PageUp if app.active_tab == tix_master::Tab::Main => {
-                                    app.log_scroll = (app.log_scroll + 10).min(app.logs.len().saturating_sub(1));
+                                    app.log_scroll = (app.log_scroll + 10).min(app.logs.rows().saturating_sub(1));
                                     app.autoscroll = false;
                                 }
                                 KeyCode::PageDown if app.active_tab == tix_master::Tab::Main => {
@@ -188,7 +671,7 @@ pub async fn main() -> std::io::Result<()> {
                                 }
                                 KeyCode::Enter if app.active_tab == tix_master::Tab::Main => {
                                     if let Some(cmd) = app.handle_enter() {
-                                        app.logs.push(format!("> {}", cmd));
+                                        app.logs.push(format!("> {}", roles::redact(&cmd)));
                                         // Send command to Master task
                                         let _ = cmd_tx.send(cmd);
                                     }
@@ -208,11 +691,17 @@ pub async fn main() -> std::io::Result<()> {
             _ = tokio::time::sleep(Duration::from_millis(50)) => {
                 // Debounced completion update
                 if app.needs_completion_update && app.last_input_time.elapsed() >= Duration::from_millis(150) {
+                    watchdog.start("completion update");
                     app.update_completion();
                 }
             }
         }
 
+        // Listings that path completion asked for.
+        if let Some(cmd) = app.take_remote_request() {
+            let _ = cmd_tx.send(cmd);
+        }
+
         if app.exit {
             break;
         }
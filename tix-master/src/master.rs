@@ -6,29 +6,159 @@
 
 pub type Master = TixMaster;
 
-use std::time::Duration;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
 
-use tix_core::{Command, Connection, ConnectionInfo, MasterState, Packet};
-use tokio::net::TcpListener;
+use tix_core::format::{format_bytes, format_duration};
+use tix_core::network::tls::TlsAcceptor;
+use tix_core::network::upload::{AckWindow, Flow};
+use tix_core::network::wiretap::WireConfig;
+use tix_core::paste_guard::{PasteGuard, PasteGuardConfig};
+use tix_core::protocol::file::{DEFAULT_REPAIR_ROUNDS, DEFAULT_UPLOAD_WINDOW};
+use tix_core::protocol::shell::{ShellResponseKind, classify_shell_response};
+use tix_core::protocol::{
+    ChunkCompressor, ChunkRepair, DeltaSyncRequest, EventLogBatch, EventLogErrorKind,
+    EventLogSummary, FileChunk, FileDigest, FileHashRequest, FileHashResponse,
+    FileHashVerification, FileSearchBatch, FileSearchSummary, FileTransferAck, FileWriteProgress,
+    HelloInfo, TransferConfig, UpdateApplyResponse, apply_delta,
+};
+use tix_core::protocol::{
+    CopyRequest, DeleteMode, DeleteOutcome, FileDeleteRequest, FileDeleteResponse,
+    FileTransactionRequest, FileTransactionResponse, LimitExceeded, NetDiagResponse,
+    RegistryQueryRequest, RegistryQueryResponse, ScheduleNameRequest, ScheduleResponse,
+    ScheduleRunReport, ScreenStartResponse, SelfTestReport, ServiceControlResponse,
+    ServiceListResponse, ShellExecuteRequest, ShellExitStatus, ShellKind, ShellOutputChunk,
+    StartupListResponse, SystemInfoResponse, TaskFailure, TaskListResponse, TransactionOp,
+    TrashRestoreRequest, TrashRestoreResponse, WireTapResponse, WireTapSettings,
+};
+use tix_core::{
+    Command, Connection, ConnectionInfo, Feature, FragmentReassembler, Liveness, MasterState,
+    Packet, PeerCapabilities, ProtocolFlags, TixError,
+};
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 
 use crate::app::MasterEvent;
+use crate::args::{split_args, split_options, split_steps};
+use crate::cache::{CacheKey, ResponseCache};
+use crate::commands::{self, CommandSpec, ConfirmGate};
+use crate::config::{CacheConfig, CommandsConfig, ResponsesConfig, ServicesConfig, WatchConfig};
+use crate::eventlog::{self, EventLogQuery};
+use crate::inventory::{self, Inventory};
+use crate::late::LateResponses;
+use crate::netdiag;
+use crate::ping::{self, PING_TIMEOUT, PingArgs, PingBurst, PingStats};
+use crate::remote_path;
+use crate::roles::{self, Roles};
+use crate::schedule::{self, ScheduleCommand};
+use crate::search::{self, Search};
+use crate::selftest;
+use crate::services::{self, ServiceGuard};
+use crate::shell_output::{self, ShellOutputs};
+use crate::table::format_table;
+use crate::tasks;
+use crate::transfers::{
+    self, Action, Direction, PartFile, PlannedFile, Run, TransferCommand, Transfers,
+};
+use crate::update::{self, PendingUpdate, UpdateArgs};
+use crate::watch::{self, InFlight, Watch, WatchArgs, WatchCache};
+use crate::wire::{self, WireCommand};
 
-/// Default timeout applied to all outbound requests (seconds).
-const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+/// Shortest wait for a reply by default (seconds); a slow link
+/// stretches it (see [`tix_core::network::rtt`]).
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// How much longer than the slave's own wait a `service` reply may take.
+const SERVICE_REPLY_SLACK: Duration = Duration::from_secs(10);
 
 /// A tix listener that accepts a single slave connection and manages
 /// the request / response lifecycle through [`MasterState`].
 #[derive(Debug)]
 pub struct TixMaster {
-    listener: TcpListener,
+    /// `None` when the master dialled its slave instead.
+    listener: Option<TcpListener>,
+    /// Set when accepted slaves must speak TLS (`tls.enabled`).
+    tls: Option<TlsAcceptor>,
     conn: Option<Connection>,
     master_conn_info: Option<ConnectionInfo>,
     slave_conn_info: Option<ConnectionInfo>,
     state: MasterState,
     ui_tx: mpsc::UnboundedSender<MasterEvent>,
-    /// Monotonically increasing request ID counter.
-    next_req_id: u64,
+    /// Sender towards the RDP viewer attached through the bridge.
+    bridge_tx: Option<mpsc::UnboundedSender<Packet>>,
+    /// Requests forwarded for the viewer: our request ID → viewer's.
+    bridge_requests: HashMap<u64, u64>,
+    /// Uploads from the viewer still streaming: viewer ID -> our ID.
+    bridge_uploads: HashMap<u64, u64>,
+    /// Round-trip statistics for the connected slave.
+    ping: PingStats,
+    /// Running `ping -c N` command, if any.
+    ping_burst: Option<PingBurst>,
+    /// What the slave reported in its `Hello`, once it answered.
+    slave_hello: Option<HelloInfo>,
+    /// Slaves seen so far, by the ID in their `Hello`.
+    inventory: Inventory,
+    /// `update` command whose binary is still uploading.
+    pending_update: Option<PendingUpdate>,
+    /// Running `watch` commands.
+    watches: Vec<Watch>,
+    /// Local versions of watched files.
+    watch_cache: WatchCache,
+    /// Repair rounds a watch download gets when its hash mismatches.
+    repair_rounds: u32,
+    /// Running `find` commands by request ID.
+    searches: HashMap<u64, Search>,
+    /// Running `eventlog` commands by request ID.
+    event_queries: HashMap<u64, EventLogQuery>,
+    /// Responses that arrived after their request stopped pending.
+    late: LateResponses,
+    /// Output of shell commands, running and recently finished.
+    shell_outputs: ShellOutputs,
+    /// Responses arriving in fragments, by request ID.
+    fragments: FragmentReassembler,
+    /// Current directory set with `cd`, per slave ID (or address, for a
+    /// slave that has not said who it is).
+    cwds: HashMap<String, String>,
+    /// `cd` targets whose listing is still on its way, by request ID.
+    cd_requests: HashMap<u64, String>,
+    /// Directories listed for path completion, by request ID.
+    completion_requests: HashMap<u64, String>,
+    /// Protected services and the wait for `service` commands.
+    services: ServiceGuard,
+    /// Refuses destructive commands nobody confirmed.
+    confirm: ConfirmGate,
+    /// How each slave connection's wire tap is set, kept across
+    /// reconnects.
+    wire: WireTapSettings,
+    /// Which commands the operator may send.
+    roles: Roles,
+    /// Shortest wait for a reply (`commands.timeout_secs`).
+    request_floor: Duration,
+    /// Shell `ShellExecute` runs in without `-s` (`commands.shell`).
+    default_shell: ShellKind,
+    /// What the Slave panel last showed of the link's round trip.
+    rtt_line: String,
+    /// The send rate the console was last told about.
+    send_rate: Option<u64>,
+    /// How file chunks are packed when the slave takes compressed ones.
+    compressor: ChunkCompressor,
+    /// Uploads and downloads queued for the transfers panel.
+    transfers: Transfers,
+    /// What is working on each live transfer item, by item ID.
+    transfer_runs: HashMap<u64, Run>,
+    /// Requests of paused or cancelled transfers, whose replies are
+    /// dropped quietly.
+    retired: HashSet<u64>,
+    /// Answers of cached commands, kept per slave.
+    cache: ResponseCache,
+    /// Requests whose answers go into the cache, by request ID; `true`
+    /// for a refresh of a served answer, which nobody waits on.
+    cached_requests: HashMap<u64, (CacheKey, bool)>,
+    /// Requests whose completion makes cached answers stale.
+    changes: HashMap<u64, &'static CommandSpec>,
 }
 
 impl TixMaster {
@@ -36,34 +166,206 @@ impl TixMaster {
     pub async fn listen(
         conn_info: ConnectionInfo,
         ui_tx: mpsc::UnboundedSender<MasterEvent>,
-    ) -> Result<Self, std::io::Error> {
+    ) -> Result<Self, TixError> {
         let listener = TcpListener::bind(conn_info.to_socket_string()).await?;
+        Ok(Self::new(Some(listener), Some(conn_info), ui_tx))
+    }
+
+    /// Dial a slave listening at `slave` instead of waiting for one.
+    /// There is nothing to accept afterwards; once the slave hangs up,
+    /// the master stays disconnected.
+    pub async fn connect(
+        slave: ConnectionInfo,
+        ui_tx: mpsc::UnboundedSender<MasterEvent>,
+    ) -> Result<Self, TixError> {
+        let stream = TcpStream::connect(slave.to_socket_string()).await?;
+        let local = stream.local_addr()?;
+        let local = ConnectionInfo::new(local.ip().to_string(), local.port());
+        let mut master = Self::new(None, Some(local), ui_tx);
+        master.attach(Connection::new(stream)).await?;
+        Ok(master)
+    }
 
+    fn new(
+        listener: Option<TcpListener>,
+        master_conn_info: Option<ConnectionInfo>,
+        ui_tx: mpsc::UnboundedSender<MasterEvent>,
+    ) -> Self {
         let mut state = MasterState::new();
         state.set_default_timeout(Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS));
 
-        Ok(Self {
+        let mut master = Self {
             listener,
+            tls: None,
             conn: None,
-            master_conn_info: Some(conn_info),
+            master_conn_info,
             slave_conn_info: None,
             state,
             ui_tx,
-            next_req_id: 1,
-        })
+            bridge_tx: None,
+            bridge_requests: HashMap::new(),
+            bridge_uploads: HashMap::new(),
+            ping: PingStats::default(),
+            ping_burst: None,
+            slave_hello: None,
+            inventory: Inventory::new(),
+            pending_update: None,
+            watches: Vec::new(),
+            watch_cache: WatchCache::new(watch::DEFAULT_CACHE_DIR, watch::DEFAULT_KEEP_VERSIONS),
+            repair_rounds: DEFAULT_REPAIR_ROUNDS,
+            searches: HashMap::new(),
+            event_queries: HashMap::new(),
+            late: LateResponses::default(),
+            shell_outputs: ShellOutputs::new(),
+            fragments: FragmentReassembler::new(),
+            cwds: HashMap::new(),
+            cd_requests: HashMap::new(),
+            completion_requests: HashMap::new(),
+            services: ServiceGuard::default(),
+            confirm: ConfirmGate::default(),
+            wire: WireConfig::default().settings(),
+            roles: Roles::default(),
+            request_floor: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS),
+            default_shell: ShellKind::Cmd,
+            rtt_line: String::new(),
+            send_rate: None,
+            compressor: TransferConfig::default().compressor(),
+            transfers: Transfers::new(TransferConfig::default().resumable),
+            transfer_runs: HashMap::new(),
+            retired: HashSet::new(),
+            cache: ResponseCache::default(),
+            cached_requests: HashMap::new(),
+            changes: HashMap::new(),
+        };
+        master.advertise_capabilities();
+        master
+    }
+
+    /// Tell the state what this end supports, for the next `Hello`.
+    fn advertise_capabilities(&mut self) {
+        self.state.set_local_capabilities(PeerCapabilities {
+            compression: self.compressor.is_enabled(),
+            ..PeerCapabilities::default()
+        });
+    }
+
+    /// Keep and repair `watch` versions as `config` says.
+    pub fn with_watch_config(mut self, config: &WatchConfig) -> Self {
+        self.watch_cache = WatchCache::new(&config.cache_dir, config.keep_versions);
+        self.repair_rounds = config.repair_rounds;
+        self
+    }
+
+    /// Handle late responses as `config` says.
+    pub fn with_responses_config(mut self, config: &ResponsesConfig) -> Self {
+        self.late = LateResponses::new(config.show_late_payload);
+        self
+    }
+
+    /// Guard and time `service` commands as `config` says.
+    pub fn with_services_config(mut self, config: &ServicesConfig) -> Self {
+        self.services = ServiceGuard::new(config);
+        self
+    }
+
+    /// Confirm destructive commands and time replies as `config` says.
+    pub fn with_commands_config(mut self, config: &CommandsConfig) -> Self {
+        self.confirm = ConfirmGate::new(config).with_paste_guard(self.confirm.paste_guard());
+        self.request_floor = Duration::from_secs(config.timeout_secs.max(1));
+        self.state.set_default_timeout(self.request_floor);
+        self.default_shell = config.default_shell();
+        self
+    }
+
+    /// Confirm or refuse large uploads as `config` says.
+    pub fn with_paste_guard_config(mut self, config: &PasteGuardConfig) -> Self {
+        self.confirm = self.confirm.with_paste_guard(PasteGuard::new(config));
+        self
+    }
+
+    /// Pack file chunks and keep partial downloads as `config` says.
+    pub fn with_transfer_config(mut self, config: &TransferConfig) -> Self {
+        self.compressor = config.compressor();
+        self.transfers = Transfers::new(config.resumable);
+        self.advertise_capabilities();
+        self
+    }
+
+    /// Tap slave connections as `config` says.
+    pub fn with_wire_config(mut self, config: &WireConfig) -> Self {
+        self.wire = config.settings();
+        self
+    }
+
+    /// Accept slaves over TLS only, presenting `acceptor`'s certificate.
+    pub fn with_tls(mut self, acceptor: TlsAcceptor) -> Self {
+        self.tls = Some(acceptor);
+        self
+    }
+
+    /// Hold back the commands the active role of `roles` does not
+    /// allow.
+    pub fn with_roles(mut self, roles: Roles) -> Self {
+        self.roles = roles;
+        self.announce_role();
+        self
+    }
+
+    /// Keep answers of cached commands as `config` says.
+    pub fn with_cache_config(mut self, config: &CacheConfig) -> Self {
+        self.cache = ResponseCache::new(config);
+        self
+    }
+
+    /// Remember slaves in `inventory` (and its file).
+    pub fn with_inventory(mut self, inventory: Inventory) -> Self {
+        self.inventory = inventory;
+        self
     }
 
     // ── Connection management ────────────────────────────────────
 
     /// Accept exactly one incoming connection.
-    pub async fn accept_one(&mut self) -> Result<(), std::io::Error> {
-        let (stream, _) = self.listener.accept().await?;
-        let slave_info = ConnectionInfo::new(
-            stream.peer_addr()?.ip().to_string(),
-            stream.peer_addr()?.port(),
-        );
+    pub async fn accept_one(&mut self) -> Result<(), TixError> {
+        let Some(listener) = &self.listener else {
+            return Err(TixError::Other(
+                "master dialled its slave; there is nothing to accept".into(),
+            ));
+        };
+        let (stream, peer) = listener.accept().await?;
+        let conn = match &self.tls {
+            Some(acceptor) => match Connection::accept_tls(stream, acceptor).await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    let _ = self.ui_tx.send(MasterEvent::log(format!(
+                        "[WARN] TLS handshake with {} failed: {}",
+                        peer, e
+                    )));
+                    return Err(e.into());
+                }
+            },
+            None => Connection::new(stream),
+        };
+        self.attach(conn).await
+    }
+
+    /// Take `conn` as the slave connection and say hello.
+    async fn attach(&mut self, mut conn: Connection) -> Result<(), TixError> {
+        let peer = conn
+            .peer_addr()
+            .ok_or_else(|| TixError::Other("slave connection has no peer address".into()))?;
+        let slave_info = ConnectionInfo::new(peer.ip().to_string(), peer.port());
         self.slave_conn_info = Some(slave_info.clone());
-        self.conn = Some(Connection::new(stream));
+        if let Err(e) = conn.wire_tap().apply(&self.wire) {
+            let _ = self
+                .ui_tx
+                .send(MasterEvent::log(format!("[WARN] wire capture: {}", e)));
+            self.wire.capture = None;
+            let _ = conn.wire_tap().apply(&self.wire);
+        }
+        self.watch_liveness(&conn);
+        conn.set_session(self.state.session());
+        self.conn = Some(conn);
 
         // Advance connection phase
         let _ = self.state.phase_mut().begin_connect();
@@ -73,11 +375,64 @@ impl TixMaster {
         let _ = self
             .ui_tx
             .send(MasterEvent::SlaveConnected(format!("{}", slave_info)));
+        let _ = self
+            .ui_tx
+            .send(MasterEvent::RemoteCwd(self.cwd().map(String::from)));
+        self.send_hello().await;
         Ok(())
     }
 
+    /// Log when `conn`'s slave goes quiet, gives up or comes back. A
+    /// dead link that was not suspect first is a plain disconnect, which
+    /// `process_connection` reports.
+    fn watch_liveness(&self, conn: &Connection) {
+        let mut liveness = conn.on_liveness_change();
+        let interval = conn.options().interval;
+        let ui_tx = self.ui_tx.clone();
+        tokio::spawn(async move {
+            let mut previous = Liveness::Alive;
+            while liveness.changed().await.is_ok() {
+                let now = *liveness.borrow_and_update();
+                let line = match (previous, now) {
+                    (_, Liveness::Suspect) => Some(format!(
+                        "[CONN] No heartbeat from the slave for {}",
+                        format_duration(interval)
+                    )),
+                    (Liveness::Suspect, Liveness::Dead) => {
+                        Some("[CONN] Slave has gone silent; the link looks dead".to_string())
+                    }
+                    (Liveness::Dead | Liveness::Suspect, Liveness::Alive) => {
+                        Some("[CONN] Slave is responding again".to_string())
+                    }
+                    _ => None,
+                };
+                previous = now;
+                if let Some(line) = line {
+                    let _ = ui_tx.send(MasterEvent::log(line));
+                }
+            }
+        });
+    }
+
+    /// Introduce ourselves; the slave answers with its own version.
+    async fn send_hello(&mut self) {
+        let req_id = self.state.next_request_id();
+        let info = HelloInfo::local("tix-master", env!("CARGO_PKG_VERSION"))
+            .with_compression(self.compressor.is_enabled())
+            .with_session(self.state.session());
+        let Ok(packet) = info.into_command_packet(req_id) else {
+            return;
+        };
+        self.track(req_id, &packet);
+        if let Some(conn) = &self.conn
+            && conn.send(packet).await.is_err()
+        {
+            self.state.resolve(req_id);
+        }
+    }
+
     /// Read and handle one inbound packet, if available.
-    pub async fn process_connection(&mut self) -> Result<(), std::io::Error> {
+    pub async fn process_connection(&mut self) -> Result<(), TixError> {
         let conn = match self.conn.as_mut() {
             Some(c) => c,
             None => return Ok(()),
@@ -86,44 +441,153 @@ impl TixMaster {
         match conn.recv().await {
             Some(packet) => {
                 let req_id = packet.request_id();
-                if req_id > 0 && self.state.is_request_pending(req_id) {
-                    match self.process_packet(&packet) {
-                        Ok(response) => {
-                            self.state.resolve(req_id);
-                            let _ = self
-                                .ui_tx
-                                .send(MasterEvent::Log(format!("- Slave: {}", response)));
-                            let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
-                                id: req_id,
-                                status: "Solved".to_string(),
-                            });
-                        }
-                        Err(e) => {
-                            self.state.resolve(req_id);
-                            let _ = self
-                                .ui_tx
-                                .send(MasterEvent::Log(format!("- Slave Error: {}", e)));
-                            let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
-                                id: req_id,
-                                status: "Failed".to_string(),
-                            });
-                        }
+                if self.state.is_from_other_session(&packet) {
+                    // Its request ID belongs to an earlier session.
+                    for line in self.late.handle_other_session(&packet) {
+                        let _ = self.ui_tx.send(MasterEvent::log(line));
+                    }
+                } else if packet.flags().contains(ProtocolFlags::STREAMING)
+                    && let Some(&viewer_id) = self.bridge_requests.get(&req_id)
+                {
+                    // Upload progress: the request stays open for the ack.
+                    self.relay_to_viewer(viewer_id, &packet);
+                } else if let Some(viewer_id) = self.bridge_requests.remove(&req_id) {
+                    self.state.resolve(req_id);
+                    self.relay_to_viewer(viewer_id, &packet);
+                } else if matches!(packet.command(), Ok(Command::Ping))
+                    && let Some(req) = self.state.get_request(req_id)
+                {
+                    let rtt = req.elapsed();
+                    self.state.resolve(req_id);
+                    self.record_pong(req_id, rtt);
+                } else if matches!(
+                    packet.command(),
+                    Ok(Command::InputMouse | Command::InputKeyboard)
+                ) {
+                    // A view-only session refusing input; only the
+                    // viewer cares.
+                    if let Some(tx) = &self.bridge_tx
+                        && let Ok(cmd) = packet.command()
+                        && let Ok(pkt) = Packet::new_response(0, cmd, packet.payload().to_vec())
+                    {
+                        let _ = tx.send(pkt);
+                    }
+                } else if matches!(packet.command(), Ok(Command::Hello))
+                    && self.state.resolve(req_id).is_some()
+                {
+                    self.record_hello(&packet);
+                } else if self.retired.contains(&req_id) {
+                    // A transfer that was paused or cancelled.
+                    if !packet.flags().contains(ProtocolFlags::STREAMING) {
+                        self.retired.remove(&req_id);
+                    }
+                    self.state.resolve(req_id);
+                } else if let Some(id) = self.transfer_waiting_on(req_id) {
+                    self.continue_transfer(id, &packet).await;
+                } else if self
+                    .pending_update
+                    .as_ref()
+                    .is_some_and(|p| p.upload_id == req_id)
+                {
+                    self.state.resolve(req_id);
+                    self.continue_update(&packet).await;
+                } else if let Some(index) = self.watch_waiting_on(req_id) {
+                    self.continue_watch(index, &packet).await;
+                } else if self.searches.contains_key(&req_id) {
+                    self.continue_search(req_id, &packet);
+                } else if self.event_queries.contains_key(&req_id) {
+                    self.continue_event_log(req_id, &packet);
+                } else if req_id == 0 && matches!(packet.command(), Ok(Command::TaskList)) {
+                    self.report_slow_tasks(&packet);
+                } else if req_id == 0 && matches!(packet.command(), Ok(Command::ScheduleResults)) {
+                    self.report_schedule_run(&packet);
+                } else if req_id > 0 && packet.flags().contains(ProtocolFlags::ERROR) {
+                    self.shell_outputs.discard(req_id);
+                    self.record_task_failure(&packet);
+                } else if req_id > 0
+                    && matches!(packet.command(), Ok(Command::ShellExecute))
+                    && self.state.is_request_pending(req_id)
+                {
+                    self.continue_shell(req_id, &packet);
+                } else if req_id > 0
+                    && packet.is_fragment()
+                    && self.state.is_request_pending(req_id)
+                {
+                    match self.fragments.push(&packet) {
+                        Ok(Some(whole)) => self.answer(req_id, whole.command, &whole.payload),
+                        Ok(None) => {}
+                        Err(e) => self.fail(req_id, format!("reassembly failed: {}", e)),
+                    }
+                } else if req_id > 0 && self.state.is_request_pending(req_id) {
+                    match packet.command() {
+                        Ok(cmd) => self.answer(req_id, cmd, packet.payload()),
+                        Err(e) => self.fail(req_id, e.to_string()),
+                    }
+                } else if req_id > 0 {
+                    let finished = self.state.recently_finished(req_id);
+                    for line in self.late.handle(&packet, finished, Instant::now()) {
+                        let _ = self.ui_tx.send(MasterEvent::log(line));
                     }
                 }
             }
             None => {
                 // Connection dropped — reset state
                 self.conn = None;
+                self.bridge_requests.clear();
+                self.bridge_uploads.clear();
+                if self.ping_burst.take().is_some() {
+                    let _ = self.ui_tx.send(MasterEvent::log(
+                        "[PING] Burst aborted: slave disconnected".to_string(),
+                    ));
+                }
+                self.ping = PingStats::default();
+                self.late.reset();
+                self.shell_outputs.abort_running();
+                self.fragments = FragmentReassembler::new();
+                self.cd_requests.clear();
+                self.completion_requests.clear();
+                self.cached_requests.clear();
+                self.changes.clear();
+                let _ = self.ui_tx.send(MasterEvent::RemoteCwd(None));
+                let name = self.forget_slave();
+                if let Some(pending) = self.pending_update.take() {
+                    pending.upload.abort();
+                    let _ = self.ui_tx.send(MasterEvent::log(
+                        "[UPDT] Update aborted: slave disconnected".to_string(),
+                    ));
+                }
+                for watch in self.watches.drain(..) {
+                    let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                        id: watch.id,
+                        status: format!("Stopped watching {}: slave disconnected", watch.path),
+                    });
+                }
+                for (id, search) in self.searches.drain() {
+                    let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                        id,
+                        status: format!(
+                            "Search stopped: slave disconnected ({} found)",
+                            search.found
+                        ),
+                    });
+                }
+                for (id, query) in self.event_queries.drain() {
+                    let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                        id,
+                        status: format!(
+                            "Event log read stopped: slave disconnected ({} entries)",
+                            query.received
+                        ),
+                    });
+                }
+                self.fail_transfers("slave disconnected");
                 self.slave_conn_info = None;
+                self.rtt_line.clear();
+                self.send_rate = None;
                 self.state = MasterState::new();
-                self.state
-                    .set_default_timeout(Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS));
-                let _ = self
-                    .ui_tx
-                    .send(MasterEvent::Log("Slave disconnected".to_string()));
-                let _ = self
-                    .ui_tx
-                    .send(MasterEvent::SlaveConnected("Not Connected".to_string()));
+                self.state.set_default_timeout(self.request_floor);
+                self.advertise_capabilities();
+                let _ = self.ui_tx.send(MasterEvent::SlaveDisconnected(name));
             }
         }
         Ok(())
@@ -131,10 +595,56 @@ impl TixMaster {
 
     /// Drain any requests whose deadline has expired and notify the UI.
     pub fn check_timeouts(&mut self) {
+        self.report_link();
+        for id in self.fragments.expire() {
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
+                "[TOUT] ReqID {}: stopped waiting for the rest of a fragmented response",
+                id
+            )));
+        }
         let expired = self.state.drain_expired();
         for (id, req) in expired {
+            self.bridge_requests.remove(&id);
+            self.fragments.discard(id);
+            self.settle_change(id);
             let cmd = req.packet.command().ok();
-            let _ = self.ui_tx.send(MasterEvent::Log(format!(
+            if let Some((key, true)) = self.cached_requests.remove(&id) {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[CACHE] ReqID {}: no fresh {} from slave; the cached one stays",
+                    id,
+                    key.command()
+                )));
+                continue;
+            }
+            if cmd == Some(Command::Ping) {
+                self.record_ping_loss(id, req.deadline.unwrap_or(PING_TIMEOUT));
+                continue;
+            }
+            if let Some(dir) = self.completion_requests.remove(&id) {
+                let _ = self.ui_tx.send(MasterEvent::RemoteListing {
+                    dir,
+                    data: String::new(),
+                });
+                continue;
+            }
+            if let Some(dir) = self.cd_requests.remove(&id) {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[TOUT] ReqID {}: cd {}: no answer from slave",
+                    id, dir
+                )));
+                continue;
+            }
+            if let Some(index) = self.watch_waiting_on(id) {
+                let watch = &mut self.watches[index];
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[WTCH] {}: no answer from slave, retrying in {}",
+                    watch.path,
+                    watch::format_interval(watch.interval)
+                )));
+                watch.reschedule(Instant::now());
+                continue;
+            }
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
                 "[TOUT] ReqID {}: {:?} timed out after {:.1}s",
                 id,
                 cmd,
@@ -147,29 +657,2097 @@ impl TixMaster {
         }
     }
 
-    // ── Packet interpretation ────────────────────────────────────
-
-    fn process_packet(&self, packet: &Packet) -> Result<String, std::io::Error> {
-        let cmd = packet
-            .command()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    // ── Ping ─────────────────────────────────────────────────────
+
+    /// Round-trip statistics for the connected slave.
+    pub fn ping_stats(&self) -> &PingStats {
+        &self.ping
+    }
+
+    /// When the running `ping -c N` burst wants its next probe sent.
+    pub fn next_ping_due(&self) -> Option<Instant> {
+        self.ping_burst.as_ref()?.next_due()
+    }
+
+    /// Send the burst's next probe if it is due.
+    pub async fn send_due_pings(&mut self) {
+        let now = Instant::now();
+        if self.next_ping_due().is_none_or(|due| due > now) {
+            return;
+        }
+        match self.send_ping().await {
+            Ok(req_id) => {
+                if let Some(burst) = self.ping_burst.as_mut() {
+                    burst.on_sent(req_id, now);
+                }
+            }
+            Err(e) => {
+                self.ping_burst = None;
+                let _ = self
+                    .ui_tx
+                    .send(MasterEvent::log(format!("[PING] Burst aborted: {}", e)));
+            }
+        }
+    }
+
+    /// Start `ping -c N [-i ms]`.
+    fn start_ping_burst(&mut self, args: PingArgs) -> Result<(), String> {
+        if self.ping_burst.is_some() {
+            return Err("a ping burst is already running".to_string());
+        }
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[PING] {}: {} probes every {} ms",
+            self.get_client_host_str(),
+            args.count,
+            args.interval.as_millis()
+        )));
+        self.ping_burst = Some(PingBurst::new(args, Instant::now()));
+        Ok(())
+    }
+
+    /// Send one tracked `Ping` and return its request ID.
+    async fn send_ping(&mut self) -> Result<u64, TixError> {
+        let Some(conn) = self.conn.as_ref() else {
+            return Err(TixError::NotConnected);
+        };
+        let req_id = self.state.next_request_id();
+        let packet = Packet::new_command(req_id, Command::Ping, Vec::new())?;
+        let timeout = self.request_timeout(Command::Ping);
+        self.state
+            .track_with_deadline(req_id, packet.clone(), Some(timeout));
+        if let Err(e) = conn.send(packet).await {
+            self.state.resolve(req_id);
+            return Err(e);
+        }
+        Ok(req_id)
+    }
+
+    /// Show the heartbeat round trip in the Slave PC panel, and tell the
+    /// console the rate uploads go at, when they change.
+    fn report_link(&mut self) {
+        let Some(conn) = &self.conn else {
+            return;
+        };
+        let line = ping::rtt_line(&conn.stats().rtt());
+        if line != self.rtt_line {
+            self.rtt_line = line.clone();
+            let _ = self.ui_tx.send(MasterEvent::LinkRtt(line));
+        }
+        let rate = conn.stats().send_rate();
+        if rate != self.send_rate {
+            self.send_rate = rate;
+            let _ = self.ui_tx.send(MasterEvent::LinkRate(rate));
+        }
+    }
+
+    /// How long to wait for the answer to `cmd`: the configured floor,
+    /// or the ping one for pings, stretched when the heartbeats say the
+    /// link is slow.
+    fn request_timeout(&self, cmd: Command) -> Duration {
+        let floor = match cmd {
+            Command::Ping => PING_TIMEOUT,
+            _ => self.request_floor,
+        };
+        match &self.conn {
+            Some(conn) => conn.stats().rtt().request_timeout(floor, cmd),
+            None => floor,
+        }
+    }
+
+    fn timeout_for(&self, packet: &Packet) -> Duration {
+        packet
+            .command()
+            .map_or(self.request_floor, |cmd| self.request_timeout(cmd))
+    }
+
+    /// Track `packet` until its answer or its command's timeout.
+    fn track(&mut self, req_id: u64, packet: &Packet) {
+        let timeout = self.timeout_for(packet);
+        self.state
+            .track_with_deadline(req_id, packet.clone(), Some(timeout));
+    }
+
+    fn record_pong(&mut self, req_id: u64, rtt: Duration) {
+        self.ping.record_reply(rtt);
+        let time_ms = rtt.as_secs_f64() * 1000.0;
+
+        match self
+            .ping_burst
+            .as_mut()
+            .and_then(|b| b.on_reply(req_id, rtt))
+        {
+            Some(seq) => {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[PING] Reply from {}: seq={} time={:.1} ms",
+                    self.get_client_host_str(),
+                    seq,
+                    time_ms
+                )));
+                self.finish_ping_burst_if_done();
+            }
+            None => {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "- Slave: Pong (time={:.1} ms)",
+                    time_ms
+                )));
+                let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                    id: req_id,
+                    status: "Solved".to_string(),
+                });
+            }
+        }
+        let _ = self
+            .ui_tx
+            .send(MasterEvent::PingStats(self.ping.panel_line()));
+    }
+
+    fn record_ping_loss(&mut self, req_id: u64, waited: Duration) {
+        self.ping.record_loss();
+
+        match self.ping_burst.as_mut().and_then(|b| b.on_loss(req_id)) {
+            Some(seq) => {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[PING] seq={} timed out after {:.1}s",
+                    seq,
+                    waited.as_secs_f64()
+                )));
+                self.finish_ping_burst_if_done();
+            }
+            None => {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[TOUT] ReqID {}: Ping unanswered after {:.1}s, slave may be unreachable",
+                    req_id,
+                    waited.as_secs_f64()
+                )));
+                let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                    id: req_id,
+                    status: "Timed out".to_string(),
+                });
+            }
+        }
+        let _ = self
+            .ui_tx
+            .send(MasterEvent::PingStats(self.ping.panel_line()));
+    }
+
+    fn finish_ping_burst_if_done(&mut self) {
+        if !self.ping_burst.as_ref().is_some_and(|b| b.is_done()) {
+            return;
+        }
+        if let Some(burst) = self.ping_burst.take() {
+            let summary = burst.stats().summary(&self.get_client_host_str());
+            let _ = self.ui_tx.send(MasterEvent::log(summary));
+        }
+    }
+
+    // ── Slave tasks ──────────────────────────────────────────────
+
+    /// Table of the slave's running tasks. Tasks answering one of our
+    /// pending requests also update that request's Tasks panel entry.
+    fn describe_tasks(&self, response: &TaskListResponse) -> String {
+        let rows: Vec<Vec<String>> = response
+            .tasks
+            .iter()
+            .map(|t| {
+                let slow = response.is_slow(t);
+                if self.state.is_request_pending(t.request_id) {
+                    // The Tasks panel times it.
+                    let status = if slow { "Running (slow)" } else { "Running" };
+                    let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                        id: t.request_id,
+                        status: status.to_string(),
+                    });
+                }
+                vec![
+                    t.request_id.to_string(),
+                    t.name.clone().unwrap_or_else(|| "-".to_string()),
+                    format!(
+                        "{}{}",
+                        format_duration(t.elapsed()),
+                        if slow { " !" } else { "" }
+                    ),
+                    t.timeout_ms
+                        .map(|ms| format_duration(Duration::from_millis(ms)))
+                        .unwrap_or_else(|| "none".to_string()),
+                ]
+            })
+            .collect();
+        let mut out = format!("{} tasks running on the slave", rows.len());
+        if !rows.is_empty() {
+            out.push('\n');
+            out.push_str(&format_table(
+                &["ReqID", "Task", "Elapsed", "Timeout"],
+                &rows,
+            ));
+        }
+        out
+    }
+
+    /// The slave flagged tasks that crossed its slow-task threshold.
+    fn report_slow_tasks(&self, packet: &Packet) {
+        let line = match TaskListResponse::from_bytes(packet.payload()) {
+            Ok(warning) => format!(
+                "[SLOW] Running longer than {}: {}",
+                format_duration(Duration::from_millis(warning.slow_after_ms)),
+                self.describe_tasks(&warning)
+            ),
+            Err(e) => format!("[WARN] Bad slow-task warning from slave: {}", e),
+        };
+        let _ = self.ui_tx.send(MasterEvent::log(line));
+    }
+
+    /// A scheduled command finished on the slave.
+    fn report_schedule_run(&self, packet: &Packet) {
+        let line = match ScheduleRunReport::from_bytes(packet.payload()) {
+            Ok(report) => schedule::format_report(&report),
+            Err(e) => format!("[WARN] Bad schedule report from slave: {}", e),
+        };
+        let _ = self.ui_tx.send(MasterEvent::log(line));
+    }
+
+    // ── RDP bridge ───────────────────────────────────────────────
+
+    // ── Slave update ─────────────────────────────────────────────
+
+    fn record_hello(&mut self, packet: &Packet) {
+        match HelloInfo::from_bytes(packet.payload()) {
+            Ok(info) => {
+                let wire = info.negotiated_version();
+                if let Some(conn) = &self.conn {
+                    conn.set_wire_version(wire);
+                }
+                self.state.set_wire_version(wire);
+                let caps = self
+                    .state
+                    .negotiate_capabilities(&info.capabilities())
+                    .clone();
+                let chunks = if caps.compression {
+                    "compressed"
+                } else {
+                    "raw"
+                };
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[CONN] Slave runs {} {} on {} ({}, {}, chunks {})",
+                    info.product, info.version, info.hostname, info.os, wire, chunks
+                )));
+                if let Some(features) = &caps.features {
+                    let missing: Vec<&str> = Feature::ALL
+                        .into_iter()
+                        .filter(|f| !features.contains(*f))
+                        .map(Feature::as_str)
+                        .collect();
+                    if !missing.is_empty() {
+                        let _ = self.ui_tx.send(MasterEvent::log(format!(
+                            "[CONN] Not on this slave: {}",
+                            missing.join(", ")
+                        )));
+                    }
+                }
+                let _ = self.ui_tx.send(MasterEvent::SlaveFeatures {
+                    version: info.version.clone(),
+                    features: caps.features.clone(),
+                });
+                if let Some(conn) = &self.conn {
+                    conn.set_peer_capabilities(caps);
+                }
+                if !info.slave_id.is_empty() {
+                    self.identify_slave(&info);
+                }
+                self.slave_hello = Some(info);
+            }
+            Err(e) => {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[WARN] Bad Hello from slave: {}",
+                    e
+                )));
+            }
+        }
+    }
+
+    /// Look the slave up in the inventory, or add it, and put its name
+    /// up wherever its address was shown.
+    fn identify_slave(&mut self, info: &HelloInfo) {
+        let ip = self
+            .slave_conn_info
+            .as_ref()
+            .map(|c| c.ip().to_string())
+            .unwrap_or_default();
+        let now = inventory::unix_now();
+        self.cache.bind(&info.slave_id);
+        let previous = self.inventory.connected(info, &ip, now);
+        let name = self
+            .inventory
+            .get(&info.slave_id)
+            .map(|r| r.display_name().to_string())
+            .unwrap_or_default();
+        let line = match previous {
+            Some(prev) => format!(
+                "[CONN] {} is back (last seen {} ago from {})",
+                name,
+                inventory::format_age(prev.last_seen, now),
+                prev.last_ip
+            ),
+            None => format!(
+                "[CONN] New slave {} ({}); give it a name with: name current <name>",
+                name, info.slave_id
+            ),
+        };
+        let _ = self.ui_tx.send(MasterEvent::log(line));
+        self.save_inventory();
+        let _ = self.ui_tx.send(MasterEvent::SlaveNamed(name));
+        let _ = self
+            .ui_tx
+            .send(MasterEvent::RemoteCwd(self.cwd().map(String::from)));
+    }
+
+    /// Drop the connected slave's identity, noting in the inventory when
+    /// it left. Returns how to refer to it.
+    fn forget_slave(&mut self) -> String {
+        let Some(hello) = self.slave_hello.take() else {
+            return "Slave".to_string();
+        };
+        match self.inventory.get(&hello.slave_id) {
+            Some(record) => {
+                let name = record.display_name().to_string();
+                self.inventory
+                    .disconnected(&hello.slave_id, inventory::unix_now());
+                self.save_inventory();
+                name
+            }
+            None => "Slave".to_string(),
+        }
+    }
+
+    fn save_inventory(&self) {
+        if let Err(e) = self.inventory.save() {
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
+                "[WARN] Cannot save inventory: {}",
+                e
+            )));
+        }
+    }
+
+    /// The connected slave's ID, once its `Hello` arrived.
+    pub fn slave_id(&self) -> Option<&str> {
+        self.slave_hello
+            .as_ref()
+            .map(|h| h.slave_id.as_str())
+            .filter(|id| !id.is_empty())
+    }
+
+    /// Slaves seen so far.
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    /// `name`: list known slaves. `name <id|current> <friendly>`: name
+    /// one; an empty name (`""`) clears it.
+    fn name_slave(&mut self, args: &[String]) -> Result<(), String> {
+        let Some((key, words)) = args.split_first() else {
+            let _ = self.ui_tx.send(MasterEvent::log(self.list_inventory()));
+            return Ok(());
+        };
+        if words.is_empty() {
+            return Err("usage: name <id|current> <friendly name>".to_string());
+        }
+        let name = words.join(" ");
+        let id = match key.as_str() {
+            "current" => self
+                .slave_id()
+                .map(str::to_string)
+                .ok_or("the connected slave has not identified itself")?,
+            key => self.inventory.resolve(key)?,
+        };
+        self.inventory.rename(&id, &name)?;
+        self.save_inventory();
+        let display = self
+            .inventory
+            .get(&id)
+            .map(|r| r.display_name().to_string())
+            .unwrap_or_default();
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[CONN] {} is now {}",
+            id, display
+        )));
+        if self.slave_id() == Some(id.as_str()) {
+            let _ = self.ui_tx.send(MasterEvent::SlaveNamed(display));
+        }
+        Ok(())
+    }
+
+    fn list_inventory(&self) -> String {
+        if self.inventory.is_empty() {
+            return "No slaves seen yet".to_string();
+        }
+        let now = inventory::unix_now();
+        let rows: Vec<Vec<String>> = self
+            .inventory
+            .iter()
+            .map(|(id, record)| {
+                let seen = if self.slave_id() == Some(id.as_str()) {
+                    "connected".to_string()
+                } else {
+                    format!("{} ago", inventory::format_age(record.last_seen, now))
+                };
+                vec![
+                    record.display_name().to_string(),
+                    record.last_ip.clone(),
+                    seen,
+                    record.os.clone(),
+                    id.clone(),
+                ]
+            })
+            .collect();
+        format_table(&["NAME", "LAST IP", "SEEN", "OS", "ID"], &rows)
+    }
+
+    /// Version the slave reported in its `Hello`.
+    pub fn slave_version(&self) -> Option<&str> {
+        self.slave_hello.as_ref().map(|h| h.version.as_str())
+    }
+
+    /// Start an `update`: stream the binary in the background and wait
+    /// for the slave's upload ack in [`Self::continue_update`].
+    fn start_update(&mut self, args: UpdateArgs) -> Result<(), String> {
+        if self
+            .pending_update
+            .as_ref()
+            .is_some_and(|p| !p.upload.is_finished())
+        {
+            return Err("an update is already uploading".to_string());
+        }
+        if let Some(reason) = args.skip_reason(self.slave_version()) {
+            let _ = self
+                .ui_tx
+                .send(MasterEvent::log(format!("[UPDT] Skipped: {}", reason)));
+            return Ok(());
+        }
+        if !args.local.is_file() {
+            return Err(format!("{} is not a file", args.local.display()));
+        }
+        let tx = self.conn.as_ref().ok_or("No slave connected")?.sender();
+
+        let req_id = self.state.next_request_id();
+        let remote = args.staged_name();
+        let first = Packet::new_command(req_id, Command::FileWrite, Vec::new())
+            .map_err(|e| e.to_string())?;
+        // Uploads take as long as the file does.
+        self.state.track_with_deadline(req_id, first, None);
+
+        let local = args.local.clone();
+        let ui_tx = self.ui_tx.clone();
+        let compressor = self
+            .compressor
+            .for_peer(self.state.negotiated_capabilities());
+        let upload = tokio::spawn(async move {
+            let result = tix_core::network::upload::send_file(
+                &tx,
+                req_id,
+                &local,
+                remote,
+                &AtomicU64::new(0),
+                compressor,
+            )
+            .await;
+            if let Err(e) = &result {
+                let _ = ui_tx.send(MasterEvent::log(format!(
+                    "[UPDT] ReqID {}: upload failed: {}",
+                    req_id, e
+                )));
+            }
+            result
+        });
+
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[UPDT] ReqID {}: uploading {} ({}) as version {}",
+            req_id,
+            args.local.display(),
+            format_bytes(args.local.metadata().map_or(0, |m| m.len())),
+            args.version
+        )));
+        let _ = self.ui_tx.send(MasterEvent::TaskStarted {
+            id: req_id,
+            command: "Update".to_string(),
+            summary: format!("to version {}", args.version),
+        });
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: req_id,
+            status: "Uploading...".to_string(),
+        });
+        self.pending_update = Some(PendingUpdate {
+            upload_id: req_id,
+            args,
+            upload,
+        });
+        Ok(())
+    }
+
+    /// The slave answered the update upload: on success, ask it to
+    /// apply the staged file.
+    async fn continue_update(&mut self, packet: &Packet) {
+        let Some(PendingUpdate {
+            upload_id,
+            args,
+            upload,
+        }) = self.pending_update.take()
+        else {
+            return;
+        };
+        let staged = match packet.command() {
+            Ok(Command::FileWrite) => match FileTransferAck::from_bytes(packet.payload()) {
+                Ok(FileTransferAck {
+                    path, error: None, ..
+                }) => Ok(path),
+                Ok(FileTransferAck { error: Some(e), .. }) => Err(e),
+                Err(e) => Err(format!("bad ack: {}", e)),
+            },
+            Ok(Command::LimitExceeded) => match LimitExceeded::from_bytes(packet.payload()) {
+                Ok(refusal) => Err(refusal.to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+            other => Err(format!("unexpected {:?}", other)),
+        };
+        // The ack only comes after the last packet, so the task is done.
+        let uploaded = match staged {
+            Ok(path) => match upload.await {
+                Ok(Ok(hash)) => Ok((path, hash)),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+            Err(e) => {
+                upload.abort();
+                Err(e)
+            }
+        };
+        let (staged, hash) = match uploaded {
+            Ok(pair) => pair,
+            Err(e) => {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[UPDT] ReqID {}: upload failed: {}",
+                    upload_id, e
+                )));
+                let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                    id: upload_id,
+                    status: "Failed".to_string(),
+                });
+                return;
+            }
+        };
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: upload_id,
+            status: "Solved".to_string(),
+        });
+
+        let req_id = self.state.next_request_id();
+        let packet = match args.apply_request(staged.clone(), hash).into_packet(req_id) {
+            Ok(pkt) => pkt,
+            Err(e) => {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[UPDT] Cannot build request: {}",
+                    e
+                )));
+                return;
+            }
+        };
+        self.track(req_id, &packet);
+        let sent = match &self.conn {
+            Some(conn) => conn.send(packet).await.is_ok(),
+            None => false,
+        };
+        if !sent {
+            self.state.resolve(req_id);
+            return;
+        }
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[UPDT] ReqID {}: staged at {}, asking slave to apply",
+            req_id, staged
+        )));
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: req_id,
+            status: "Waiting...".to_string(),
+        });
+    }
+
+    /// Route slave responses for bridged requests to the given viewer
+    /// channel (see [`crate::bridge`]).
+    pub fn attach_bridge(&mut self, tx: mpsc::UnboundedSender<Packet>) {
+        self.bridge_tx = Some(tx);
+    }
+
+    /// Re-issue a packet received from the RDP viewer to the slave.
+    ///
+    /// Requests get a fresh master request ID so they cannot collide
+    /// with console commands; the viewer's ID is restored on the way
+    /// back. Input events and preflight reports are fire-and-forget and
+    /// are not tracked. An
+    /// upload is many `FileWrite` packets under one viewer ID; they all
+    /// keep the ID given to the first one, which is the one tracked.
+    pub async fn forward_from_bridge(&mut self, packet: Packet) {
+        // A viewer's Hello is answered with the slave's, so it knows
+        // what the slave can do; it goes no further.
+        if matches!(packet.command(), Ok(Command::Hello)) {
+            if let Some(hello) = &self.slave_hello
+                && let Ok(pkt) = hello.clone().into_response_packet(packet.request_id())
+                && let Some(tx) = &self.bridge_tx
+            {
+                let _ = tx.send(pkt);
+            }
+            return;
+        }
+        let cmd = match packet.command() {
+            Ok(cmd) if crate::bridge::is_bridged_command(cmd) => cmd,
+            other => {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[RDP ] Dropping non-RDP packet from viewer: {:?}",
+                    other
+                )));
+                return;
+            }
+        };
+        let viewer_id = packet.request_id();
+
+        if let Some(refusal) = self.refusal(cmd) {
+            let refusal = refusal.to_string();
+            let _ = self
+                .ui_tx
+                .send(MasterEvent::log(format!("[RDP ] Viewer: {}", refusal)));
+            if cmd == Command::ScreenStart
+                && let Ok(pkt) = ScreenStartResponse::Failed(refusal).into_packet(viewer_id)
+                && let Some(tx) = &self.bridge_tx
+            {
+                let _ = tx.send(pkt);
+            }
+            return;
+        }
+
+        let Some(conn) = self.conn.as_ref() else {
+            if cmd == Command::ScreenStart
+                && let Ok(pkt) = ScreenStartResponse::Failed("No slave connected".to_string())
+                    .into_packet(viewer_id)
+                && let Some(tx) = &self.bridge_tx
+            {
+                let _ = tx.send(pkt);
+            }
+            return;
+        };
+
+        let continued_upload = if cmd == Command::FileWrite {
+            let id = self.bridge_uploads.get(&viewer_id).copied();
+            if packet.flags().contains(ProtocolFlags::FINAL_FRAGMENT) {
+                self.bridge_uploads.remove(&viewer_id);
+            }
+            id
+        } else {
+            None
+        };
+        let req_id = continued_upload.unwrap_or_else(|| self.state.next_request_id());
+        if cmd == Command::FileWrite
+            && continued_upload.is_none()
+            && !packet.flags().contains(ProtocolFlags::FINAL_FRAGMENT)
+        {
+            self.bridge_uploads.insert(viewer_id, req_id);
+        }
+
+        let forwarded = match Packet::new_command_with_flags(
+            req_id,
+            cmd,
+            packet.payload().to_vec(),
+            packet.flags(),
+        ) {
+            Ok(pkt) => pkt,
+            Err(e) => {
+                let _ = self.ui_tx.send(MasterEvent::log(format!(
+                    "[RDP ] Cannot forward {:?}: {}",
+                    cmd, e
+                )));
+                return;
+            }
+        };
+
+        let tracked = viewer_id != 0
+            && continued_upload.is_none()
+            && !matches!(
+                cmd,
+                Command::InputMouse
+                    | Command::InputKeyboard
+                    | Command::ScreenPreflight
+                    | Command::ScreenSharing
+            );
+        if tracked {
+            if cmd == Command::FileWrite {
+                // Uploads take as long as the file does.
+                self.state
+                    .track_with_deadline(req_id, forwarded.clone(), None);
+            } else {
+                let timeout = self.timeout_for(&forwarded);
+                self.state
+                    .track_with_deadline(req_id, forwarded.clone(), Some(timeout));
+            }
+            self.bridge_requests.insert(req_id, viewer_id);
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
+                "[RDP ] ReqID {}: forwarding {:?} from viewer",
+                req_id, cmd
+            )));
+        }
+
+        if conn.send(forwarded).await.is_err() && tracked {
+            self.state.resolve(req_id);
+            self.bridge_requests.remove(&req_id);
+        }
+    }
+
+    /// Send a slave response back to the viewer under its original ID.
+    fn relay_to_viewer(&self, viewer_id: u64, packet: &Packet) {
+        let Some(tx) = &self.bridge_tx else {
+            return;
+        };
+        let Ok(cmd) = packet.command() else {
+            return;
+        };
+        if let Ok(pkt) = Packet::new_response_with_flags(
+            viewer_id,
+            cmd,
+            packet.payload().to_vec(),
+            packet.flags(),
+        ) {
+            let _ = tx.send(pkt);
+        }
+        if !packet.flags().contains(ProtocolFlags::STREAMING) {
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
+                "[RDP ] {:?} response relayed to viewer",
+                cmd
+            )));
+        }
+    }
+
+    // ── Watch ────────────────────────────────────────────────────
+
+    /// When the next `watch` check is due.
+    pub fn next_watch_due(&self) -> Option<Instant> {
+        self.watches.iter().filter_map(Watch::next_due).min()
+    }
+
+    /// Send a `FileHash` for every watch whose check is due.
+    pub async fn poll_watches(&mut self) {
+        let now = Instant::now();
+        for index in 0..self.watches.len() {
+            if self.watches[index].next_due().is_none_or(|due| due > now) {
+                continue;
+            }
+            let req_id = self.state.next_request_id();
+            let request = FileHashRequest::new(self.watches[index].path.clone());
+            match self.send_tracked(request.into_packet(req_id)).await {
+                Ok(()) => self.watches[index].in_flight = Some(InFlight::Hash(req_id)),
+                Err(e) => {
+                    let watch = &mut self.watches[index];
+                    let _ = self
+                        .ui_tx
+                        .send(MasterEvent::log(format!("[WTCH] {}: {}", watch.path, e)));
+                    watch.reschedule(now);
+                }
+            }
+        }
+    }
+
+    /// Track and send a request built for a watch.
+    async fn send_tracked(
+        &mut self,
+        packet: Result<Packet, tix_core::TixError>,
+    ) -> Result<(), String> {
+        let packet = packet.map_err(|e| e.to_string())?;
+        let req_id = packet.request_id();
+        let conn = self.conn.as_ref().ok_or("No slave connected")?;
+        let timeout = self.timeout_for(&packet);
+        self.state
+            .track_with_deadline(req_id, packet.clone(), Some(timeout));
+        if let Err(e) = conn.send(packet).await {
+            self.state.resolve(req_id);
+            return Err(e.to_string());
+        }
+        Ok(())
+    }
+
+    /// Index of the watch waiting on `req_id`.
+    fn watch_waiting_on(&self, req_id: u64) -> Option<usize> {
+        self.watches
+            .iter()
+            .position(|w| w.request_id() == Some(req_id))
+    }
+
+    /// Start `watch <path> [interval]`; the first check goes out on the
+    /// next poll.
+    fn start_watch(&mut self, args: WatchArgs) {
+        let id = self.state.next_request_id();
+        let watch = Watch::new(id, args, Instant::now());
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[WTCH] Watch {}: following {} (cache {})",
+            id,
+            watch.path,
+            self.watch_cache.dir_for(&watch.path).display()
+        )));
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id,
+            status: watch.status(),
+        });
+        self.watches.push(watch);
+    }
+
+    /// `unwatch <id>`.
+    fn stop_watch(&mut self, id: u64) -> Result<(), String> {
+        let index = self
+            .watches
+            .iter()
+            .position(|w| w.id == id)
+            .ok_or_else(|| format!("no watch with ID {}", id))?;
+        let watch = self.watches.remove(index);
+        if let Some(req_id) = watch.request_id() {
+            self.state.abandon(req_id);
+        }
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[WTCH] Watch {}: stopped following {}",
+            id, watch.path
+        )));
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id,
+            status: format!("Cancelled watching {}", watch.path),
+        });
+        Ok(())
+    }
+
+    // ── Transfers ────────────────────────────────────────────────
+
+    /// Item whose running request is `req_id`.
+    fn transfer_waiting_on(&self, req_id: u64) -> Option<u64> {
+        self.transfer_runs
+            .iter()
+            .find(|(_, run)| run.request_id() == req_id)
+            .map(|(&id, _)| id)
+    }
+
+    /// Tell the transfers panel where item `id` stands.
+    fn emit_transfer(&self, id: u64) {
+        if let Some(update) = self.transfers.get(id) {
+            let _ = self.ui_tx.send(MasterEvent::Transfer(update.clone()));
+        }
+    }
+
+    /// Queue `files` as one job, which gets a Tasks row of its own.
+    async fn queue_job(&mut self, direction: Direction, files: Vec<PlannedFile>) {
+        let job = self.state.next_request_id();
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[SEND] ReqID {}: Queued {} of {} file(s)",
+            job,
+            direction,
+            files.len()
+        )));
+        let _ = self.ui_tx.send(MasterEvent::TaskStarted {
+            id: job,
+            command: direction.to_string(),
+            summary: format!("{} file(s)", files.len()),
+        });
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: job,
+            status: "Waiting...".to_string(),
+        });
+        for file in files {
+            let id = self
+                .transfers
+                .enqueue(job, direction, file.source, file.dest, file.size);
+            self.emit_transfer(id);
+        }
+        let next = self.transfers.schedule();
+        self.apply_transfer_actions(next.into_iter().collect())
+            .await;
+    }
+
+    /// `Upload <local>|<remote>` with `local` on this machine.
+    async fn queue_upload(&mut self, local: &Path, remote: &str) -> Result<(), String> {
+        let remote = remote_path::resolve(self.cwd(), &remote_path::unquote(remote));
+        let files = transfers::plan_upload(local, &remote)
+            .map_err(|e| format!("{}: {}", local.display(), e))?;
+        if files.is_empty() {
+            return Err(format!("{} has no files to upload", local.display()));
+        }
+        self.queue_job(Direction::Upload, files).await;
+        Ok(())
+    }
+
+    /// `Download <remote>|<local>`: fetch a slave file to this machine.
+    async fn queue_download(&mut self, remote: &str, local: &str) -> Result<(), String> {
+        let remote = remote_path::resolve(self.cwd(), &remote_path::unquote(remote));
+        let dest = transfers::download_dest(&remote, &remote_path::unquote(local));
+        let file = PlannedFile {
+            source: remote,
+            dest: dest.display().to_string(),
+            size: None,
+        };
+        self.queue_job(Direction::Download, vec![file]).await;
+        Ok(())
+    }
+
+    /// `transfer pause|resume|cancel|retry <id>`.
+    async fn command_transfer(&mut self, command: TransferCommand) -> Result<(), String> {
+        let actions = match command {
+            TransferCommand::Pause(id) => self.transfers.pause(id),
+            TransferCommand::Resume(id) => self.transfers.resume(id),
+            TransferCommand::Cancel(id) => self.transfers.cancel(id),
+            TransferCommand::Retry(id) => self.transfers.retry(id),
+        }?;
+        let id = match command {
+            TransferCommand::Pause(id)
+            | TransferCommand::Resume(id)
+            | TransferCommand::Cancel(id)
+            | TransferCommand::Retry(id) => id,
+        };
+        if let Some(update) = self.transfers.get(id) {
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
+                "[XFER] Transfer {}: {} {}",
+                id, update.state, update.source
+            )));
+        }
+        self.emit_transfer(id);
+        self.apply_transfer_actions(actions).await;
+        if let Some(job) = self.transfers.get(id).map(|update| update.job) {
+            self.settle_job(job);
+        }
+        Ok(())
+    }
+
+    /// Carry out what the queue asked for, and whatever that leads to:
+    /// an item that cannot start fails, and the next one is tried.
+    async fn apply_transfer_actions(&mut self, actions: Vec<Action>) {
+        let mut actions: VecDeque<Action> = actions.into();
+        while let Some(action) = actions.pop_front() {
+            match action {
+                Action::Start(id) => {
+                    if let Err(e) = self.start_transfer(id).await {
+                        let job = self.transfers.get(id).map(|update| update.job);
+                        actions.extend(self.transfers.finish(id, Err(e)));
+                        if let Some(job) = job {
+                            self.settle_job(job);
+                        }
+                    }
+                }
+                Action::Unpause(id) => {
+                    if let Some(Run::Upload { flow, .. }) = self.transfer_runs.get(&id) {
+                        let _ = flow.send(Flow::Run);
+                    }
+                }
+                Action::Hold(id) => {
+                    if let Some(Run::Upload { flow, .. }) = self.transfer_runs.get(&id) {
+                        let _ = flow.send(Flow::Pause);
+                    }
+                }
+                Action::Halt(id) => self.stop_transfer_run(id).await,
+                Action::Abort { id, remove_part } => {
+                    self.stop_transfer_run(id).await;
+                    if remove_part {
+                        self.discard_part(id);
+                    }
+                }
+                Action::Discard(id) => self.discard_part(id),
+            }
+            if let Action::Start(id) | Action::Abort { id, .. } | Action::Discard(id) = action {
+                self.emit_transfer(id);
+            }
+        }
+    }
+
+    /// Send the first request of item `id` and record its run.
+    async fn start_transfer(&mut self, id: u64) -> Result<(), String> {
+        let update = self.transfers.get(id).cloned().ok_or("no such transfer")?;
+        let tx = self.conn.as_ref().ok_or("No slave connected")?.sender();
+        let req_id = self.state.next_request_id();
+        let run = match update.direction {
+            Direction::Upload => {
+                let first = Packet::new_command(req_id, Command::FileWrite, Vec::new())
+                    .map_err(|e| e.to_string())?;
+                // Uploads take as long as the file does.
+                self.state.track_with_deadline(req_id, first, None);
+                let (flow, flow_rx) = tokio::sync::watch::channel(Flow::Run);
+                let (acks, window) = AckWindow::channel(DEFAULT_UPLOAD_WINDOW);
+                let mut window = window.with_flow(flow_rx);
+                let compressor = self
+                    .compressor
+                    .for_peer(self.state.negotiated_capabilities());
+                let local = update.source.clone();
+                let remote = update.dest.clone();
+                let task = tokio::spawn(async move {
+                    tix_core::network::upload::send_file_windowed(
+                        &tx,
+                        req_id,
+                        Path::new(&local),
+                        remote,
+                        &AtomicU64::new(0),
+                        compressor,
+                        &mut window,
+                    )
+                    .await
+                });
+                Run::Upload {
+                    req_id,
+                    flow,
+                    acks,
+                    task,
+                }
+            }
+            Direction::Download => {
+                let packet = FileHashRequest::new(update.source.clone())
+                    .into_packet(req_id)
+                    .map_err(|e| e.to_string())?;
+                self.send_untimed(packet).await?;
+                Run::Hashing { req_id }
+            }
+        };
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[XFER] Transfer {}: {} {} -> {} (ReqID {})",
+            id, update.direction, update.source, update.dest, req_id
+        )));
+        self.transfer_runs.insert(id, run);
+        self.transfers.started(id);
+        Ok(())
+    }
+
+    /// Track and send a transfer request; transfers wait as long as the
+    /// file takes.
+    async fn send_untimed(&mut self, packet: Packet) -> Result<(), String> {
+        let req_id = packet.request_id();
+        let conn = self.conn.as_ref().ok_or("No slave connected")?;
+        self.state.track_with_deadline(req_id, packet.clone(), None);
+        if let Err(e) = conn.send(packet).await {
+            self.state.resolve(req_id);
+            return Err(e.to_string());
+        }
+        Ok(())
+    }
+
+    /// Stop whatever is working on item `id`. An upload is told to give
+    /// up, so the slave deletes what it wrote; a download's request is
+    /// cancelled on the slave. Either way, what the slave still sends
+    /// for it is dropped.
+    async fn stop_transfer_run(&mut self, id: u64) {
+        let Some(run) = self.transfer_runs.remove(&id) else {
+            return;
+        };
+        let req_id = run.request_id();
+        self.state.resolve(req_id);
+        self.retired.insert(req_id);
+        match run {
+            Run::Upload { flow, .. } => {
+                // The task sends the empty closing packet and ends.
+                let _ = flow.send(Flow::Cancel);
+            }
+            Run::Hashing { .. } | Run::Fetching { .. } => {
+                let cancel_id = self.state.next_request_id();
+                self.retired.insert(cancel_id);
+                if let (Some(conn), Ok(packet)) = (
+                    &self.conn,
+                    Packet::new_command(
+                        cancel_id,
+                        Command::ShellCancel,
+                        req_id.to_le_bytes().to_vec(),
+                    ),
+                ) {
+                    let _ = conn.send(packet).await;
+                }
+            }
+        }
+    }
+
+    /// Delete the `.part` of download `id`.
+    fn discard_part(&self, id: u64) {
+        let Some(update) = self.transfers.get(id) else {
+            return;
+        };
+        if update.direction != Direction::Download {
+            return;
+        }
+        if let Err(e) = transfers::remove_part(Path::new(&update.dest)) {
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
+                "[WARN] Transfer {}: could not delete {}: {}",
+                id,
+                transfers::part_path(Path::new(&update.dest)).display(),
+                e
+            )));
+        }
+    }
+
+    /// A reply for running item `id`.
+    async fn continue_transfer(&mut self, id: u64, packet: &Packet) {
+        let req_id = packet.request_id();
+        let flags = packet.flags();
+        let Some(run) = self.transfer_runs.get_mut(&id) else {
+            return;
+        };
+        let result = match run {
+            _ if flags.contains(ProtocolFlags::ERROR) => {
+                self.state.resolve(req_id);
+                Err(TaskFailure::from_payload(packet.payload()).to_string())
+            }
+            Run::Upload { acks, .. } if flags.contains(ProtocolFlags::STREAMING) => {
+                if let Ok(progress) = FileWriteProgress::from_bytes(packet.payload()) {
+                    let bytes = progress.bytes_written;
+                    let _ = acks.send(progress);
+                    self.transfers.progress(id, bytes, None);
+                    self.emit_transfer(id);
+                }
+                return;
+            }
+            Run::Upload { .. } => {
+                self.state.resolve(req_id);
+                let Some(Run::Upload { task, .. }) = self.transfer_runs.remove(&id) else {
+                    return;
+                };
+                Self::finish_upload(packet, task).await
+            }
+            Run::Hashing { .. } => {
+                self.state.resolve(req_id);
+                match FileHashResponse::from_bytes(packet.payload()) {
+                    Ok(FileHashResponse::Hashed(digest)) => {
+                        match self.fetch_transfer(id, digest).await {
+                            Ok(()) => return,
+                            Err(e) => Err(e),
+                        }
+                    }
+                    Ok(FileHashResponse::Failed(e)) => Err(e),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            Run::Fetching {
+                part,
+                chunk_size,
+                bytes,
+                reported,
+                ..
+            } if flags.contains(ProtocolFlags::STREAMING) => {
+                let written = FileChunk::from_bytes(packet.payload())
+                    .and_then(|c| c.unpack(*chunk_size))
+                    .map_err(|e| e.to_string())
+                    .and_then(|chunk| {
+                        part.write(&chunk).map_err(|e| e.to_string())?;
+                        Ok(chunk.data.len() as u64)
+                    });
+                match written {
+                    Ok(len) => {
+                        *bytes += len;
+                        if reported.elapsed() >= transfers::PROGRESS_EVERY {
+                            *reported = Instant::now();
+                            let bytes = *bytes;
+                            self.transfers.progress(id, bytes, None);
+                            self.emit_transfer(id);
+                        }
+                        return;
+                    }
+                    Err(e) => {
+                        // Stop the rest of the sync from coming.
+                        self.stop_transfer_run(id).await;
+                        Err(e)
+                    }
+                }
+            }
+            Run::Fetching { .. } if flags.contains(ProtocolFlags::FINAL_FRAGMENT) => {
+                self.state.resolve(req_id);
+                let Some(Run::Fetching { part, .. }) = self.transfer_runs.remove(&id) else {
+                    return;
+                };
+                FileHashVerification::from_bytes(packet.payload())
+                    .map_err(|e| e.to_string())
+                    .and_then(|verification| part.finish(&verification))
+            }
+            Run::Fetching { .. } => {
+                self.state.resolve(req_id);
+                Err(String::from_utf8_lossy(packet.payload()).into_owned())
+            }
+        };
+        self.end_transfer(id, result).await;
+    }
+
+    /// The slave's answer to an upload. The ack only comes after the
+    /// last packet, so the task is done by then.
+    async fn finish_upload(
+        packet: &Packet,
+        task: tokio::task::JoinHandle<Result<[u8; 32], TixError>>,
+    ) -> Result<(), String> {
+        let acked = match packet.command() {
+            Ok(Command::FileWrite) => match FileTransferAck::from_bytes(packet.payload()) {
+                Ok(FileTransferAck { error: None, .. }) => Ok(()),
+                Ok(FileTransferAck { error: Some(e), .. }) => Err(e),
+                Err(e) => Err(format!("bad ack: {}", e)),
+            },
+            Ok(Command::LimitExceeded) => match LimitExceeded::from_bytes(packet.payload()) {
+                Ok(refusal) => Err(refusal.to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+            other => Err(format!("unexpected {:?}", other)),
+        };
+        match acked {
+            Ok(()) => match task.await {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+            // What went wrong on this end says more than the slave's
+            // view of it.
+            Err(e) if task.is_finished() => match task.await {
+                Ok(Err(local)) => Err(local.to_string()),
+                _ => Err(e),
+            },
+            Err(e) => {
+                task.abort();
+                Err(e)
+            }
+        }
+    }
+
+    /// The slave hashed download `id`: open its `.part` and ask for the
+    /// chunks it does not have yet.
+    async fn fetch_transfer(&mut self, id: u64, digest: FileDigest) -> Result<(), String> {
+        let update = self.transfers.get(id).cloned().ok_or("no such transfer")?;
+        let dest = Path::new(&update.dest);
+        let (part, have) = PartFile::open(dest, digest.chunk_size)
+            .map_err(|e| format!("{}: {}", transfers::part_path(dest).display(), e))?;
+        let resumed = transfers::resumed_bytes(&have, &digest);
+        if resumed > 0 {
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
+                "[XFER] Transfer {}: resuming with {} of {} already here",
+                id,
+                format_bytes(resumed),
+                format_bytes(digest.size)
+            )));
+        }
+        self.transfers.progress(id, resumed, Some(digest.size));
+        self.emit_transfer(id);
+
+        let req_id = self.state.next_request_id();
+        let packet = DeltaSyncRequest::new(update.source, &have)
+            .into_packet(req_id)
+            .map_err(|e| e.to_string())?;
+        self.send_untimed(packet).await?;
+        self.transfer_runs.insert(
+            id,
+            Run::Fetching {
+                req_id,
+                part,
+                chunk_size: digest.chunk_size,
+                bytes: resumed,
+                reported: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Item `id` arrived or failed: record it, start what comes next and
+    /// report the job if that was its last item.
+    async fn end_transfer(&mut self, id: u64, result: Result<(), String>) {
+        self.transfer_runs.remove(&id);
+        if let Err(e) = &result {
+            let _ = self
+                .ui_tx
+                .send(MasterEvent::log(format!("[XFER] Transfer {}: {}", id, e)));
+        }
+        let actions = self.transfers.finish(id, result);
+        self.emit_transfer(id);
+        let job = self.transfers.get(id).map(|update| update.job);
+        self.apply_transfer_actions(actions).await;
+        if let Some(job) = job {
+            self.settle_job(job);
+        }
+    }
+
+    /// Report job `job` once none of its items is left to run.
+    fn settle_job(&mut self, job: u64) {
+        let Some(outcome) = self.transfers.settle(job) else {
+            return;
+        };
+        let _ = self.ui_tx.send(MasterEvent::Response {
+            id: job,
+            text: outcome.describe(),
+        });
+        if outcome.done > 0 {
+            let _ = self.ui_tx.send(MasterEvent::RefreshTree {
+                is_slave: outcome.direction == Direction::Upload,
+            });
+        }
+        if outcome.direction == Direction::Upload
+            && let Some(spec) = commands::lookup("Upload")
+        {
+            self.cache.changed_by(spec);
+        }
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: job,
+            status: if outcome.is_success() {
+                "Solved"
+            } else {
+                "Failed"
+            }
+            .to_string(),
+        });
+    }
+
+    /// The slave went away: every item not finished fails.
+    fn fail_transfers(&mut self, reason: &str) {
+        for (_, run) in self.transfer_runs.drain() {
+            if let Run::Upload { task, .. } = run {
+                task.abort();
+            }
+        }
+        self.retired.clear();
+        let changed = self.transfers.fail_all(reason);
+        let mut jobs = Vec::new();
+        for &id in &changed {
+            self.emit_transfer(id);
+            if !self.transfers.is_resumable() {
+                self.discard_part(id);
+            }
+            if let Some(update) = self.transfers.get(id)
+                && !jobs.contains(&update.job)
+            {
+                jobs.push(update.job);
+            }
+        }
+        for job in jobs {
+            self.settle_job(job);
+        }
+    }
+
+    // ── Search ───────────────────────────────────────────────────
+
+    /// `find`: send the search and give it a Tasks row. It has no
+    /// deadline; a long walk is stopped with `cancel` instead.
+    async fn start_search(&mut self, args: &[String]) -> Result<(), String> {
+        let mut req = search::parse_find(args)?;
+        req.root = remote_path::resolve(self.cwd(), &req.root);
+        let conn = self.conn.as_ref().ok_or("No slave connected")?;
+        let req_id = self.state.next_request_id();
+        let search = Search::new(&req);
+        let packet = req.into_packet(req_id).map_err(|e| e.to_string())?;
+        self.state.track_with_deadline(req_id, packet.clone(), None);
+        if let Err(e) = conn.send(packet).await {
+            self.state.resolve(req_id);
+            return Err(e.to_string());
+        }
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[FIND] ReqID {}: searching {} for {}",
+            req_id, search.root, search.pattern
+        )));
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: req_id,
+            status: search.status(),
+        });
+        self.searches.insert(req_id, search);
+        Ok(())
+    }
+
+    /// Log a batch of matches, or the summary that ends the search.
+    fn continue_search(&mut self, req_id: u64, packet: &Packet) {
+        if packet.flags().contains(ProtocolFlags::STREAMING) {
+            let Some(search) = self.searches.get_mut(&req_id) else {
+                return;
+            };
+            match FileSearchBatch::from_bytes(packet.payload()) {
+                Ok(batch) => {
+                    search.found += batch.matches.len() as u64;
+                    for entry in &batch.matches {
+                        let _ = self
+                            .ui_tx
+                            .send(MasterEvent::log(search::format_match(entry)));
+                    }
+                    let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                        id: req_id,
+                        status: search.status(),
+                    });
+                }
+                Err(e) => {
+                    let _ = self.ui_tx.send(MasterEvent::log(format!(
+                        "[FIND] ReqID {}: bad batch: {}",
+                        req_id, e
+                    )));
+                }
+            }
+            return;
+        }
+
+        self.searches.remove(&req_id);
+        self.state.resolve(req_id);
+        let summary = if packet.flags().contains(ProtocolFlags::ERROR) {
+            FileSearchSummary::failed(TaskFailure::from_payload(packet.payload()).to_string())
+        } else {
+            FileSearchSummary::from_bytes(packet.payload()).unwrap_or_else(|_| {
+                FileSearchSummary::failed(String::from_utf8_lossy(packet.payload()))
+            })
+        };
+        let _ = self
+            .ui_tx
+            .send(MasterEvent::log(search::format_summary(&summary)));
+        let status = match summary.error {
+            Some(_) => "Failed".to_string(),
+            None => format!("Solved: {} found", summary.matches),
+        };
+        let _ = self
+            .ui_tx
+            .send(MasterEvent::TaskUpdate { id: req_id, status });
+    }
+
+    // ── Event log ────────────────────────────────────────────────
+
+    /// `eventlog`: send the query and give it a Tasks row. Like `find`,
+    /// it streams and has no deadline.
+    async fn start_event_log(&mut self, args: &[String]) -> Result<(), String> {
+        let req = eventlog::parse_eventlog(args, inventory::unix_now() * 1000)?;
+        let conn = self.conn.as_ref().ok_or("No slave connected")?;
+        let req_id = self.state.next_request_id();
+        let query = EventLogQuery::new(&req);
+        let packet = req.into_packet(req_id).map_err(|e| e.to_string())?;
+        self.state.track_with_deadline(req_id, packet.clone(), None);
+        if let Err(e) = conn.send(packet).await {
+            self.state.resolve(req_id);
+            return Err(e.to_string());
+        }
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[EVNT] ReqID {}: reading the {} log",
+            req_id, query.log
+        )));
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: req_id,
+            status: query.status(),
+        });
+        self.event_queries.insert(req_id, query);
+        Ok(())
+    }
+
+    /// Log a batch of entries, or the summary that ends the query.
+    fn continue_event_log(&mut self, req_id: u64, packet: &Packet) {
+        if packet.flags().contains(ProtocolFlags::STREAMING) {
+            let Some(query) = self.event_queries.get_mut(&req_id) else {
+                return;
+            };
+            match EventLogBatch::from_bytes(packet.payload()) {
+                Ok(batch) => {
+                    query.received += batch.entries.len() as u64;
+                    for entry in &batch.entries {
+                        let _ = self
+                            .ui_tx
+                            .send(MasterEvent::log(eventlog::format_entry(entry)));
+                    }
+                    let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                        id: req_id,
+                        status: query.status(),
+                    });
+                }
+                Err(e) => {
+                    let _ = self.ui_tx.send(MasterEvent::log(format!(
+                        "[EVNT] ReqID {}: bad batch: {}",
+                        req_id, e
+                    )));
+                }
+            }
+            return;
+        }
+
+        let Some(query) = self.event_queries.remove(&req_id) else {
+            return;
+        };
+        self.state.resolve(req_id);
+        let summary = if packet.flags().contains(ProtocolFlags::ERROR) {
+            EventLogSummary::failed(
+                EventLogErrorKind::Other,
+                TaskFailure::from_payload(packet.payload()).to_string(),
+            )
+        } else {
+            EventLogSummary::from_bytes(packet.payload()).unwrap_or_else(|e| {
+                EventLogSummary::failed(EventLogErrorKind::Other, format!("bad summary: {}", e))
+            })
+        };
+        let _ = self.ui_tx.send(MasterEvent::log(eventlog::format_summary(
+            &query.log, &summary,
+        )));
+        let status = match summary.error {
+            Some(_) => "Failed".to_string(),
+            None => format!("Solved: {} entries", summary.entries),
+        };
+        let _ = self
+            .ui_tx
+            .send(MasterEvent::TaskUpdate { id: req_id, status });
+    }
+
+    /// Forget event log query `id` ahead of a `cancel`.
+    fn stop_event_log(&mut self, id: u64) {
+        if let Some(query) = self.event_queries.remove(&id) {
+            self.state.abandon(id);
+            let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                id,
+                status: format!("Cancelled event log read ({} entries)", query.received),
+            });
+        }
+    }
+
+    /// The slave's task for a request failed instead of answering it.
+    /// A chunk of a shell command's output, or its exit.
+    fn continue_shell(&mut self, req_id: u64, packet: &Packet) {
+        let (text, solved) = match classify_shell_response(packet) {
+            ShellResponseKind::OutputChunk => {
+                match ShellOutputChunk::from_bytes(packet.payload()) {
+                    Ok(chunk) => self.shell_outputs.push(req_id, chunk),
+                    Err(e) => {
+                        let _ = self.ui_tx.send(MasterEvent::log(format!(
+                            "[EXEC] ReqID {}: bad output chunk: {}",
+                            req_id, e
+                        )));
+                    }
+                }
+                return;
+            }
+            ShellResponseKind::Exit => match ShellExitStatus::from_bytes(packet.payload()) {
+                Ok(exit) => (
+                    self.shell_outputs.finish(req_id, &exit),
+                    exit.error.is_none(),
+                ),
+                Err(e) => {
+                    self.shell_outputs.discard(req_id);
+                    (format!("Bad exit status: {}", e), false)
+                }
+            },
+            ShellResponseKind::LegacySingle => (
+                self.shell_outputs.finish_legacy(req_id, packet.payload()),
+                true,
+            ),
+        };
+        self.state.resolve(req_id);
+        self.settle_change(req_id);
+        let _ = self.ui_tx.send(MasterEvent::Response { id: req_id, text });
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: req_id,
+            status: if solved { "Solved" } else { "Failed" }.to_string(),
+        });
+    }
+
+    /// `save-output <req_id> <file> [--stderr]`: write the raw output of
+    /// a finished shell command to a local file.
+    fn save_output(&self, args: &[String]) -> Result<(), String> {
+        let (req_id, file, stderr) = shell_output::parse_save_output(args)?;
+        let written = self
+            .shell_outputs
+            .save(req_id, std::path::Path::new(&file), stderr)?;
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[EXEC] ReqID {}: saved {} to {}",
+            req_id,
+            format_bytes(written),
+            file
+        )));
+        Ok(())
+    }
+
+    fn record_task_failure(&mut self, packet: &Packet) {
+        let req_id = packet.request_id();
+        let failure = TaskFailure::from_payload(packet.payload());
+        if let Some(dir) = self.completion_requests.remove(&req_id) {
+            self.state.resolve(req_id);
+            let _ = self.ui_tx.send(MasterEvent::RemoteListing {
+                dir,
+                data: String::new(),
+            });
+            return;
+        }
+        if let Some(dir) = self.cd_requests.remove(&req_id) {
+            self.state.resolve(req_id);
+            let _ = self
+                .ui_tx
+                .send(MasterEvent::log(format!("Error: cd {}: {}", dir, failure)));
+            return;
+        }
+        self.cached_requests.remove(&req_id);
+        self.settle_change(req_id);
+        if self.state.resolve(req_id).is_some() {
+            // A panic is a slave bug, not a failed command: said apart.
+            let (line, status) = match &failure {
+                TaskFailure::Panicked(msg) => (
+                    format!("Slave crash: ReqID {} panicked: {}", req_id, msg),
+                    "Panicked",
+                ),
+                _ => (format!("- Slave Error: {}", failure), "Failed"),
+            };
+            let _ = self.ui_tx.send(MasterEvent::log(line));
+            let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                id: req_id,
+                status: status.to_string(),
+            });
+        } else if failure != TaskFailure::Cancelled {
+            // A cancel we sent ourselves needs no report.
+            let finished = self.state.recently_finished(req_id);
+            for line in self.late.handle(packet, finished, Instant::now()) {
+                let _ = self.ui_tx.send(MasterEvent::log(line));
+            }
+        }
+    }
+
+    /// Forget search `id` ahead of a `cancel`: batches still in flight
+    /// are dropped.
+    fn stop_search(&mut self, id: u64) {
+        if let Some(search) = self.searches.remove(&id) {
+            self.state.abandon(id);
+            let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                id,
+                status: format!("Cancelled search ({} found)", search.found),
+            });
+        }
+    }
+
+    /// `stats`: request bookkeeping for this connection.
+    fn log_stats(&self) {
+        let stats = self.late.stats();
+        let mut lines = vec![
+            format!("[STAT] Pending requests: {}", self.state.pending_count()),
+            format!(
+                "[STAT] Late responses: {} (streams: {} packet(s))",
+                stats.late, stats.stream_packets
+            ),
+            format!("[STAT] Duplicate responses: {}", stats.duplicate),
+            format!("[STAT] Unknown request IDs: {}", stats.unknown),
+            format!(
+                "[STAT] Responses from earlier sessions: {}",
+                stats.other_session
+            ),
+            format!(
+                "[STAT] Discarded payload: {}",
+                format_bytes(stats.discarded_bytes)
+            ),
+            format!(
+                "[STAT] Cached answers: {} ({} served)",
+                self.cache.len(),
+                self.cache.hits()
+            ),
+        ];
+        if let Some(conn) = &self.conn {
+            let rtt = conn.stats().rtt();
+            lines.push(format!(
+                "[STAT] Heartbeat RTT: {} ({} samples)",
+                ping::rtt_line(&rtt),
+                rtt.samples()
+            ));
+            lines.push(format!(
+                "[STAT] Timeouts: ListDir {}, ShellExecute {}, Ping {}; slave dead after {} silent",
+                format_duration(self.request_timeout(Command::ListDir)),
+                format_duration(self.request_timeout(Command::ShellExecute)),
+                format_duration(self.request_timeout(Command::Ping)),
+                format_duration(conn.miss_deadline())
+            ));
+            lines.push(match conn.stats().send_rate() {
+                Some(rate) => format!("[STAT] Send rate: {}/s", format_bytes(rate)),
+                None => "[STAT] Send rate: not measured yet".to_string(),
+            });
+        }
+        for line in lines {
+            let _ = self.ui_tx.send(MasterEvent::log(line));
+        }
+    }
+
+    /// Apply `settings` to the slave connection's wire tap, now if there
+    /// is one and to every connection after it.
+    fn set_wire(&mut self, settings: WireTapSettings) -> Result<(), TixError> {
+        if let Some(conn) = &self.conn {
+            conn.wire_tap().apply(&settings)?;
+        }
+        self.wire = settings;
+        self.log_wire();
+        Ok(())
+    }
+
+    fn log_wire(&self) {
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[WIRE] Master {}",
+            wire::describe(&self.wire)
+        )));
+    }
+
+    /// `role` shows the active role; `role <name> [password]` switches.
+    fn switch_role(&mut self, args: Option<(String, Option<String>)>) -> Result<(), String> {
+        let Some((name, password)) = args else {
+            let _ = self.ui_tx.send(MasterEvent::log(self.roles.describe()));
+            return Ok(());
+        };
+        self.roles.switch(&name, password.as_deref())?;
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[ROLE] Now acting as {} ({} commands allowed)",
+            name,
+            self.roles.allowed().len()
+        )));
+        self.announce_role();
+        Ok(())
+    }
+
+    /// Tell the console which commands it may offer.
+    fn announce_role(&self) {
+        let _ = self.ui_tx.send(MasterEvent::Role {
+            name: self.roles.active().map(String::from),
+            allowed: self.roles.allowed(),
+        });
+    }
+
+    /// Refuse `cmd` when the active role does not allow it, or the
+    /// slave said it cannot run it. The refusal gets a request ID of its
+    /// own, so it shows up as a failed task.
+    fn permit(&mut self, cmd: Command) -> Result<(), TixError> {
+        let refusal = self.refusal(cmd);
+        self.refuse(refusal)
+    }
+
+    /// Refuse a `ShellExecute` in `shell` when the slave said it has no
+    /// such shell.
+    fn permit_shell(&mut self, shell: &ShellKind) -> Result<(), TixError> {
+        let refusal = self.unsupported(Feature::for_shell(shell), || {
+            format!("ShellExecute in {}", shell)
+        });
+        self.refuse(refusal)
+    }
+
+    /// Why `cmd` may not go to the slave, if it may not.
+    fn refusal(&self, cmd: Command) -> Option<TixError> {
+        if !self.roles.permits(cmd) {
+            return Some(TixError::NotPermitted {
+                command: cmd.to_string(),
+                role: self.roles.active().unwrap_or_default().to_string(),
+            });
+        }
+        self.unsupported(Feature::for_command(cmd), || cmd.to_string())
+    }
+
+    /// The refusal of what `command` names when it needs `feature` and
+    /// the slave's `Hello` left it out. A slave that listed nothing is
+    /// given the benefit of the doubt.
+    fn unsupported(
+        &self,
+        feature: Option<Feature>,
+        command: impl FnOnce() -> String,
+    ) -> Option<TixError> {
+        let features = self.conn.as_ref()?.peer_capabilities()?.features?;
+        feature.filter(|f| !features.contains(*f))?;
+        Some(TixError::NotSupported {
+            command: command(),
+            version: self.slave_version().unwrap_or("?").to_string(),
+        })
+    }
+
+    /// Show `refusal`, if any, as a failed task of its own, and hand it
+    /// back.
+    fn refuse(&mut self, refusal: Option<TixError>) -> Result<(), TixError> {
+        let Some(err) = refusal else {
+            return Ok(());
+        };
+        let status = match err {
+            TixError::NotSupported { .. } => "Failed: not supported",
+            _ => "Failed: not permitted",
+        };
+        let req_id = self.state.next_request_id();
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: req_id,
+            status: status.to_string(),
+        });
+        Err(err)
+    }
+
+    /// Table of running watches for `watch` without arguments.
+    fn list_watches(&self) -> String {
+        if self.watches.is_empty() {
+            return "No watches running".to_string();
+        }
+        let rows: Vec<Vec<String>> = self
+            .watches
+            .iter()
+            .map(|w| {
+                vec![
+                    w.id.to_string(),
+                    w.path.clone(),
+                    watch::format_interval(w.interval),
+                    w.changes.to_string(),
+                ]
+            })
+            .collect();
+        format_table(&["ID", "Path", "Every", "Changes"], &rows)
+    }
+
+    /// A reply for the watch at `index`: a digest to compare, a delta
+    /// chunk, or the end of the delta.
+    async fn continue_watch(&mut self, index: usize, packet: &Packet) {
+        let req_id = packet.request_id();
+        let flags = packet.flags();
+        let path = self.watches[index].path.clone();
+        let outcome = match self.watches[index].in_flight.as_mut() {
+            Some(_) if flags.contains(ProtocolFlags::ERROR) => {
+                self.state.resolve(req_id);
+                Err(TaskFailure::from_payload(packet.payload()).to_string())
+            }
+            Some(InFlight::Hash(_)) => {
+                self.state.resolve(req_id);
+                match FileHashResponse::from_bytes(packet.payload()) {
+                    Ok(FileHashResponse::Hashed(digest)) => {
+                        self.start_watch_sync(index, digest).await
+                    }
+                    Ok(FileHashResponse::Failed(e)) => Err(e),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            Some(InFlight::Sync { chunks, repair, .. })
+                if flags.contains(ProtocolFlags::STREAMING) =>
+            {
+                let chunk_size = repair.chunk_size();
+                match FileChunk::from_bytes(packet.payload()).and_then(|c| c.unpack(chunk_size)) {
+                    Ok(chunk) => {
+                        chunks.push(chunk);
+                        return;
+                    }
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            Some(InFlight::Sync { .. }) if flags.contains(ProtocolFlags::FINAL_FRAGMENT) => {
+                self.state.resolve(req_id);
+                match FileHashVerification::from_bytes(packet.payload()) {
+                    Ok(verification) => self.finish_watch_sync(index, &verification).await,
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            Some(InFlight::Sync { .. }) => {
+                self.state.resolve(req_id);
+                Err(String::from_utf8_lossy(packet.payload()).into_owned())
+            }
+            None => return,
+        };
+        if let Err(e) = outcome {
+            let _ = self
+                .ui_tx
+                .send(MasterEvent::log(format!("[WTCH] {}: {}", path, e)));
+        }
+        let watch = &mut self.watches[index];
+        if watch.request_id() == Some(req_id) {
+            watch.reschedule(Instant::now());
+        }
+    }
+
+    /// The slave reported `digest`: if it is not the cached version, ask
+    /// for the chunks that differ from it.
+    async fn start_watch_sync(&mut self, index: usize, digest: FileDigest) -> Result<(), String> {
+        if self.watches[index].is_current(&digest.hash) {
+            return Ok(());
+        }
+        let path = self.watches[index].path.clone();
+        let base = self
+            .watch_cache
+            .latest(&path)
+            .map_err(|e| format!("cache: {}", e))?;
+        let base_digest = FileDigest::of(base.as_deref().unwrap_or_default(), digest.chunk_size);
+        if base_digest.hash == digest.hash && base.is_some() {
+            // Cached by an earlier run already.
+            self.watches[index].set_current(digest.hash);
+            return Ok(());
+        }
+        let req_id = self.state.next_request_id();
+        let request = DeltaSyncRequest::new(path.clone(), &base_digest);
+        self.send_tracked(request.into_packet(req_id)).await?;
+        self.watches[index].in_flight = Some(InFlight::Sync {
+            req_id,
+            base,
+            chunks: Vec::new(),
+            rebuilt: None,
+            repair: ChunkRepair::new(path, digest.chunk_size, self.repair_rounds),
+        });
+        Ok(())
+    }
+
+    /// All changed chunks are in: rebuild the file and check it against
+    /// the slave's hash. A mismatch starts a repair round; a match is
+    /// cached and logged with what changed.
+    async fn finish_watch_sync(
+        &mut self,
+        index: usize,
+        verification: &FileHashVerification,
+    ) -> Result<(), String> {
+        let watch = &mut self.watches[index];
+        let Some(InFlight::Sync {
+            base,
+            chunks,
+            rebuilt,
+            mut repair,
+            ..
+        }) = watch.in_flight.take()
+        else {
+            return Ok(());
+        };
+        let patched = rebuilt.as_deref().or(base.as_deref()).unwrap_or_default();
+        let data = apply_delta(patched, &chunks, verification.total_bytes);
+        repair.received(&chunks);
+        if let Some(request) = repair
+            .check(&data, verification)
+            .map_err(|e| e.to_string())?
+        {
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
+                "[WTCH] {}: hash mismatch, re-fetching the chunks that differ (repair {} of {})",
+                watch.path,
+                repair.rounds(),
+                self.repair_rounds
+            )));
+            let req_id = self.state.next_request_id();
+            self.send_tracked(request.into_packet(req_id)).await?;
+            self.watches[index].in_flight = Some(InFlight::Sync {
+                req_id,
+                base,
+                chunks: Vec::new(),
+                rebuilt: Some(data),
+                repair,
+            });
+            return Ok(());
+        }
+        if repair.rounds() > 0 {
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
+                "[WTCH] {}: repaired chunk(s) {:?} in {} round(s)",
+                watch.path,
+                repair.last_chunks(),
+                repair.rounds()
+            )));
+        }
+        let version = self
+            .watch_cache
+            .store(&watch.path, &data)
+            .map_err(|e| format!("cache: {}", e))?;
+        watch.set_current(verification.blake3_hash);
+        watch.changes += 1;
+        for line in watch::describe_change(&watch.path, version, base.as_deref(), &data) {
+            let _ = self.ui_tx.send(MasterEvent::log(line));
+        }
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: watch.id,
+            status: watch.status(),
+        });
+        Ok(())
+    }
+
+    // ── Response cache ───────────────────────────────────────────
+
+    /// Answer `input` from the cache when it holds `key`. Returns
+    /// whether to ask the slave as well, or `None` when the cache has
+    /// nothing.
+    fn serve_cached(&mut self, key: &CacheKey, input: &str) -> Option<bool> {
+        let hit = self.cache.get(key, Instant::now())?;
+        let (cmd, response, age, refresh) =
+            (hit.command, hit.response.to_vec(), hit.age, hit.revalidate);
+        let req_id = self.state.next_request_id();
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[CACHE] ReqID {}: {} answered from the cache ({} old){}",
+            req_id,
+            key.command(),
+            format_duration(age),
+            if refresh { "; refreshing" } else { "" }
+        )));
+        let _ = self.ui_tx.send(MasterEvent::TaskStarted {
+            id: req_id,
+            command: format!("{:?}", cmd),
+            summary: tasks::summarize(&roles::redact(input)),
+        });
+        let status = match self.process_response(cmd, &response) {
+            Ok(text) => {
+                let _ = self.ui_tx.send(MasterEvent::Response { id: req_id, text });
+                "Solved (cached)"
+            }
+            Err(e) => {
+                let _ = self
+                    .ui_tx
+                    .send(MasterEvent::log(format!("- Slave Error: {}", e)));
+                "Failed"
+            }
+        };
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: req_id,
+            status: status.to_string(),
+        });
+        Some(refresh)
+    }
+
+    /// Request `req_id` is over: drop the cached answers it made stale.
+    fn settle_change(&mut self, req_id: u64) {
+        if let Some(spec) = self.changes.remove(&req_id) {
+            self.cache.changed_by(spec);
+        }
+    }
+
+    /// `invalidate [command]`
+    fn invalidate(&mut self, rest: &str) -> Result<(), String> {
+        let command = Some(rest.trim()).filter(|name| !name.is_empty());
+        let dropped = self.cache.invalidate(command)?;
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
+            "[CACHE] Dropped {} cached answer{}",
+            dropped,
+            if dropped == 1 { "" } else { "s" }
+        )));
+        Ok(())
+    }
+
+    // ── Packet interpretation ────────────────────────────────────
+
+    /// Resolve a pending request with its response.
+    fn answer(&mut self, req_id: u64, cmd: Command, payload: &[u8]) {
+        self.settle_change(req_id);
+        if let Some(dir) = self.completion_requests.remove(&req_id) {
+            self.state.resolve(req_id);
+            let data = String::from_utf8_lossy(payload).into_owned();
+            let _ = self.ui_tx.send(MasterEvent::RemoteListing { dir, data });
+            return;
+        }
+        if let Some(dir) = self.cd_requests.remove(&req_id) {
+            self.state.resolve(req_id);
+            self.set_cwd(dir);
+            return;
+        }
+        let cached = self.cached_requests.remove(&req_id);
+        match self.process_response(cmd, payload) {
+            Ok(response) => {
+                self.state.resolve(req_id);
+                if let Some((key, refresh)) = cached {
+                    self.cache.store(key, cmd, payload.to_vec(), Instant::now());
+                    if refresh {
+                        return;
+                    }
+                }
+                let _ = self.ui_tx.send(MasterEvent::Response {
+                    id: req_id,
+                    text: response,
+                });
+                let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+                    id: req_id,
+                    status: "Solved".to_string(),
+                });
+            }
+            Err(e) => self.fail(req_id, e.to_string()),
+        }
+    }
+
+    /// Resolve a pending request that went wrong on the way back.
+    fn fail(&mut self, req_id: u64, error: String) {
+        self.state.resolve(req_id);
+        self.fragments.discard(req_id);
+        self.cd_requests.remove(&req_id);
+        self.completion_requests.remove(&req_id);
+        self.cached_requests.remove(&req_id);
+        self.settle_change(req_id);
+        let _ = self
+            .ui_tx
+            .send(MasterEvent::log(format!("- Slave Error: {}", error)));
+        let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
+            id: req_id,
+            status: "Failed".to_string(),
+        });
+    }
 
+    /// Render the response to a request, once all of its payload is in.
+    fn process_response(&self, cmd: Command, payload: &[u8]) -> Result<String, TixError> {
         match cmd {
             Command::Ping => Ok("Pong".to_string()),
 
-            Command::ShellExecute => {
-                let output = String::from_utf8_lossy(packet.payload());
-                Ok(format!("{}", output))
-            }
-
             Command::Copy => {
-                let result_str = String::from_utf8_lossy(packet.payload());
+                let result_str = String::from_utf8_lossy(payload);
                 let _ = self.ui_tx.send(MasterEvent::RefreshTree { is_slave: true });
                 Ok(format!("{}", result_str))
             }
 
             Command::ListDrives => {
-                let drives_str = String::from_utf8_lossy(packet.payload()).to_string();
+                let drives_str = String::from_utf8_lossy(payload).to_string();
                 let _ = self.ui_tx.send(MasterEvent::TreeData {
                     is_slave: true,
                     path: "drives".to_string(),
@@ -179,7 +2757,7 @@ impl TixMaster {
             }
 
             Command::ListDir => {
-                let data_str = String::from_utf8_lossy(packet.payload()).to_string();
+                let data_str = String::from_utf8_lossy(payload).to_string();
                 let _ = self.ui_tx.send(MasterEvent::TreeData {
                     is_slave: true,
                     path: "dir_listing".to_string(),
@@ -188,6 +2766,11 @@ impl TixMaster {
                 Ok("Directory listing received".to_string())
             }
 
+            Command::UpdateApply => {
+                let response = UpdateApplyResponse::from_bytes(payload)?;
+                update::describe_response(&response).map_err(TixError::Other)
+            }
+
             Command::Upload => {
                 let _ = self.ui_tx.send(MasterEvent::RefreshTree { is_slave: true });
                 Ok("Upload complete".to_string())
@@ -200,15 +2783,238 @@ impl TixMaster {
                 Ok("Download complete".to_string())
             }
 
+            Command::FileDelete => {
+                let response = FileDeleteResponse::from_bytes(payload)?;
+                let _ = self.ui_tx.send(MasterEvent::RefreshTree { is_slave: true });
+
+                let mode = if response.fell_back_to_permanent() {
+                    DeleteMode::Permanent
+                } else {
+                    response.requested
+                };
+                let total = response.items.len();
+                let mut out = format!(
+                    "Deleted {} of {} item(s) {}",
+                    total - response.failed_count(),
+                    total,
+                    mode.describe()
+                );
+                let rows: Vec<Vec<String>> = response
+                    .items
+                    .iter()
+                    .map(|item| {
+                        let result = match &item.outcome {
+                            DeleteOutcome::Recycled => "recycled".to_string(),
+                            DeleteOutcome::Deleted => "deleted".to_string(),
+                            DeleteOutcome::Failed(e) => format!("failed: {}", e),
+                        };
+                        vec![item.path.clone(), result]
+                    })
+                    .collect();
+                if !rows.is_empty() {
+                    out.push('\n');
+                    out.push_str(&format_table(&["Path", "Result"], &rows));
+                }
+                if let Some(note) = &response.note {
+                    out.push_str(&format!("\n  ! {}", note));
+                }
+                Ok(out)
+            }
+
+            Command::TrashRestore => match TrashRestoreResponse::from_bytes(payload)? {
+                TrashRestoreResponse::Restored { path } => {
+                    let _ = self.ui_tx.send(MasterEvent::RefreshTree { is_slave: true });
+                    Ok(format!("Restored {}", path))
+                }
+                TrashRestoreResponse::NotFound(msg)
+                | TrashRestoreResponse::Unsupported(msg)
+                | TrashRestoreResponse::Failed(msg) => {
+                    Err(TixError::Other(format!("Restore failed: {}", msg)))
+                }
+            },
+
+            Command::FileTransaction => {
+                let response = FileTransactionResponse::from_bytes(payload)?;
+                if let Some(error) = &response.error {
+                    return Err(TixError::Other(format!("Transaction refused: {}", error)));
+                }
+                let _ = self.ui_tx.send(MasterEvent::RefreshTree { is_slave: true });
+
+                let mut out = match response.failed_step() {
+                    None => format!("Transaction committed: {} step(s)", response.steps.len()),
+                    Some((i, _)) => match response.left_in_effect() {
+                        0 => format!("Transaction rolled back: step {} failed", i + 1),
+                        n => format!(
+                            "Transaction failed at step {}; {} step(s) could not be undone",
+                            i + 1,
+                            n
+                        ),
+                    },
+                };
+                let rows: Vec<Vec<String>> = response
+                    .steps
+                    .iter()
+                    .enumerate()
+                    .map(|(i, step)| {
+                        vec![
+                            (i + 1).to_string(),
+                            step.op.to_string(),
+                            step.outcome.to_string(),
+                        ]
+                    })
+                    .collect();
+                out.push('\n');
+                out.push_str(&format_table(&["#", "Step", "Result"], &rows));
+                Ok(out)
+            }
+
             Command::SystemAction => {
-                let msg = String::from_utf8_lossy(packet.payload()).to_string();
+                let msg = String::from_utf8_lossy(payload).to_string();
                 Ok(format!("System action: {}", msg))
             }
 
-            _ => Err(std::io::Error::other(format!(
-                "Unhandled command: {:?}",
-                cmd
-            ))),
+            Command::RegistryQuery => {
+                let response = RegistryQueryResponse::from_bytes(payload)?;
+                match response {
+                    RegistryQueryResponse::Value(entry) => Ok(format_table(
+                        &["Name", "Type", "Data"],
+                        &[vec![
+                            entry.name,
+                            entry.value.type_name().to_string(),
+                            entry.value.to_string(),
+                        ]],
+                    )),
+                    RegistryQueryResponse::Key { subkeys, values } => {
+                        let mut out = format!("{} subkeys, {} values", subkeys.len(), values.len());
+                        for key in &subkeys {
+                            out.push_str(&format!("\n  [{}]", key));
+                        }
+                        if !values.is_empty() {
+                            let rows: Vec<Vec<String>> = values
+                                .iter()
+                                .map(|v| {
+                                    vec![
+                                        v.name.clone(),
+                                        v.value.type_name().to_string(),
+                                        v.value.to_string(),
+                                    ]
+                                })
+                                .collect();
+                            out.push('\n');
+                            out.push_str(&format_table(&["Name", "Type", "Data"], &rows));
+                        }
+                        Ok(out)
+                    }
+                    RegistryQueryResponse::Error { kind, message } => Err(TixError::Other(
+                        format!("Registry query failed ({:?}): {}", kind, message),
+                    )),
+                }
+            }
+
+            Command::StartupList => {
+                let response = StartupListResponse::from_bytes(payload)?;
+                let rows: Vec<Vec<String>> = response
+                    .entries
+                    .iter()
+                    .map(|e| vec![e.name.clone(), e.source.to_string(), e.command.clone()])
+                    .collect();
+                let mut out = format!("{} startup entries", rows.len());
+                if !rows.is_empty() {
+                    out.push('\n');
+                    out.push_str(&format_table(&["Name", "Location", "Command"], &rows));
+                }
+                for err in &response.errors {
+                    out.push_str(&format!("\n  ! {}", err));
+                }
+                Ok(out)
+            }
+
+            Command::ServiceList => {
+                services::describe_list(ServiceListResponse::from_bytes(payload)?)
+                    .map_err(TixError::Other)
+            }
+
+            Command::ServiceControl => {
+                services::describe_control(ServiceControlResponse::from_bytes(payload)?)
+                    .map_err(TixError::Other)
+            }
+
+            Command::NetDiag => {
+                netdiag::describe(NetDiagResponse::from_bytes(payload)?).map_err(TixError::Other)
+            }
+
+            Command::WireTap => wire::describe_response(WireTapResponse::from_bytes(payload)?)
+                .map_err(TixError::Other),
+
+            Command::SelfTest => Ok(selftest::describe(&SelfTestReport::from_bytes(payload)?)),
+
+            Command::ScheduleCreate
+            | Command::ScheduleList
+            | Command::ScheduleDelete
+            | Command::ScheduleResults => {
+                schedule::describe(ScheduleResponse::from_bytes(payload)?).map_err(TixError::Other)
+            }
+
+            Command::SystemInfo => {
+                let info = SystemInfoResponse::from_bytes(payload)?;
+                let session = &info.session;
+                let mut out = format!(
+                    "{} ({})\n  traffic: {}",
+                    info.hostname,
+                    info.os,
+                    session.summary()
+                );
+                let rows: Vec<Vec<String>> = session
+                    .commands
+                    .iter()
+                    .map(|c| {
+                        vec![
+                            c.command.clone(),
+                            c.packets.to_string(),
+                            format_bytes(c.bytes_sent),
+                            format_bytes(c.bytes_received),
+                        ]
+                    })
+                    .collect();
+                if !rows.is_empty() {
+                    out.push('\n');
+                    out.push_str(&format_table(
+                        &["Command", "Packets", "Sent", "Received"],
+                        &rows,
+                    ));
+                }
+                let locks: Vec<Vec<String>> = info
+                    .path_locks
+                    .iter()
+                    .map(|l| {
+                        vec![
+                            l.path.clone(),
+                            l.access.to_string(),
+                            l.request_id.to_string(),
+                            format_duration(Duration::from_millis(l.held_ms)),
+                        ]
+                    })
+                    .collect();
+                if !locks.is_empty() {
+                    out.push_str("\n  path locks:\n");
+                    out.push_str(&format_table(&["Path", "Access", "ReqID", "Held"], &locks));
+                }
+                Ok(out)
+            }
+
+            Command::ShellCancel => Ok(String::from_utf8_lossy(payload).into_owned()),
+
+            Command::TaskList => {
+                let response = TaskListResponse::from_bytes(payload)?;
+                Ok(self.describe_tasks(&response))
+            }
+
+            Command::LimitExceeded => {
+                let refusal = LimitExceeded::from_bytes(payload)?;
+                Err(TixError::Other(refusal.to_string()))
+            }
+
+            _ => Err(TixError::Other(format!("Unhandled command: {:?}", cmd))),
         }
     }
 
@@ -216,57 +3022,339 @@ impl TixMaster {
 
     /// Parse a text command from the TUI and send the corresponding
     /// packet to the connected slave.
-    pub async fn execute_command(&mut self, cmd: String) -> Result<(), std::io::Error> {
+    pub async fn execute_command(&mut self, cmd: String) -> Result<(), TixError> {
+        // Naming works on the inventory, with or without a slave.
+        if let Some(rest) = cmd.trim().strip_prefix("name")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            return split_args(rest)
+                .and_then(|args| self.name_slave(&args))
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        // Saved output outlives the slave that wrote it.
+        if let Some(rest) = cmd.trim().strip_prefix("save-output")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            return split_args(rest)
+                .and_then(|args| self.save_output(&args))
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        // Roles are the operator's, not the slave's.
+        if let Some(rest) = cmd.trim().strip_prefix("role")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            return roles::parse(rest)
+                .and_then(|args| self.switch_role(args))
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        // The master's own wire tap is set with or without a slave.
+        let mut slave_wire = None;
+        if let Some(rest) = cmd.trim().strip_prefix("debug wire")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            match wire::parse(rest, &self.wire).map_err(TixError::InvalidCommandSyntax)? {
+                WireCommand::Status => self.log_wire(),
+                WireCommand::Local(settings) => self.set_wire(settings)?,
+                WireCommand::Slave(settings) => slave_wire = Some(settings),
+            }
+            if slave_wire.is_none() {
+                return Ok(());
+            }
+        }
+
+        // The transfer queue outlives the slave too: failed items can
+        // be listed, cancelled and retried once it is back.
+        if cmd.trim() == "transfers" {
+            let _ = self.ui_tx.send(MasterEvent::log(self.transfers.describe()));
+            return Ok(());
+        }
+        if let Some(rest) = cmd.trim().strip_prefix("transfer")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            let command = split_args(rest)
+                .and_then(|args| TransferCommand::parse(&args))
+                .map_err(TixError::InvalidCommandSyntax)?;
+            return self
+                .command_transfer(command)
+                .await
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        // So is the cache of the slave's answers.
+        if let Some(rest) = cmd.trim().strip_prefix("invalidate")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            return self.invalidate(rest).map_err(TixError::InvalidCommandSyntax);
+        }
+
         if self.conn.is_none() {
-            let _ = self
-                .ui_tx
-                .send(MasterEvent::Log("Error: No slave connected".to_string()));
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::NotConnected,
-                "No slave connected",
-            ));
+            return Err(TixError::NotConnected);
         }
 
         let cmd_trimmed = cmd.trim();
         if cmd_trimmed.is_empty() {
             return Ok(());
         }
+        let cmd_trimmed = self
+            .confirm
+            .check(cmd_trimmed)
+            .map_err(TixError::InvalidCommandSyntax)?;
 
-        let (tix_cmd, payload) = match Self::parse_command(cmd_trimmed) {
-            Ok(pair) => pair,
-            Err(msg) => {
-                let _ = self.ui_tx.send(MasterEvent::Log(format!("Error: {}", msg)));
-                return Err(std::io::Error::other(msg));
+        // What this command changes is stale from now on, and again
+        // once it finished.
+        let spec = commands::lookup(cmd_trimmed);
+        if let Some(spec) = spec {
+            self.cache.changed_by(spec);
+        }
+
+        if cmd_trimmed == "stats" {
+            self.log_stats();
+            return Ok(());
+        }
+
+        if cmd_trimmed == "pwd" {
+            self.print_cwd();
+            return Ok(());
+        }
+
+        if let Some(rest) = cmd_trimmed.strip_prefix("cd")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            if !rest.trim().is_empty() {
+                self.permit(Command::ListDir)?;
+            }
+            return self
+                .change_dir(rest)
+                .await
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        // Path completion asks for listings the user never sees.
+        if let Some(dir) = cmd_trimmed.strip_prefix("complete ") {
+            let dir = dir.trim().to_string();
+            let listing = if self.roles.permits(Command::ListDir) {
+                self.send_listing(&dir).await
+            } else {
+                Err(String::new())
+            };
+            match listing {
+                Ok(req_id) => {
+                    self.completion_requests.insert(req_id, dir);
+                }
+                Err(_) => {
+                    let _ = self.ui_tx.send(MasterEvent::RemoteListing {
+                        dir,
+                        data: String::new(),
+                    });
+                }
+            }
+            return Ok(());
+        }
+
+        if let Some(rest) = cmd_trimmed.strip_prefix("ping")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            self.permit(Command::Ping)?;
+            return split_args(rest)
+                .and_then(|args| PingArgs::parse(&args))
+                .and_then(|args| self.start_ping_burst(args))
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        if let Some(rest) = cmd_trimmed.strip_prefix("unwatch")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            return rest
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| "unwatch requires a watch ID".to_string())
+                .and_then(|id| self.stop_watch(id))
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        if let Some(rest) = cmd_trimmed.strip_prefix("watch")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            let result = split_args(rest).and_then(|args| {
+                if args.is_empty() {
+                    let _ = self.ui_tx.send(MasterEvent::log(self.list_watches()));
+                    return Ok(None);
+                }
+                WatchArgs::parse(&args).map(Some)
+            });
+            let args = match result {
+                Ok(Some(args)) => args,
+                Ok(None) => return Ok(()),
+                Err(msg) => return Err(TixError::InvalidCommandSyntax(msg)),
+            };
+            // Watches hash the file, then fetch what changed.
+            self.permit(Command::FileHash)?;
+            self.permit(Command::FileRead)?;
+            self.start_watch(args);
+            return Ok(());
+        }
+
+        if let Some(rest) = cmd_trimmed.strip_prefix("find")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            self.permit(Command::FileSearch)?;
+            let args = split_args(rest).map_err(TixError::InvalidCommandSyntax)?;
+            return self
+                .start_search(&args)
+                .await
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        if let Some(rest) = cmd_trimmed.strip_prefix("eventlog")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            self.permit(Command::EventLogQuery)?;
+            let args = split_args(rest).map_err(TixError::InvalidCommandSyntax)?;
+            return self
+                .start_event_log(&args)
+                .await
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        // `cancel` is sent like any other command; a search it names is
+        // closed here so late batches are ignored.
+        if let Some(rest) = cmd_trimmed.strip_prefix("cancel ")
+            && let Ok(id) = rest.trim().parse::<u64>()
+        {
+            self.stop_search(id);
+            self.stop_event_log(id);
+        }
+
+        if let Some(rest) = cmd_trimmed.strip_prefix("update")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            self.permit(Command::FileWrite)?;
+            self.permit(Command::UpdateApply)?;
+            return split_args(rest)
+                .and_then(|args| UpdateArgs::parse(&args))
+                .and_then(|args| self.start_update(args))
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        // Files on this machine go through the transfer queue; a source
+        // that is not here is the slave's own copy.
+        if let Some(rest) = cmd_trimmed.strip_prefix("Upload ")
+            && let Some((local, remote)) = rest.split_once('|')
+            && Path::new(&remote_path::unquote(local)).exists()
+        {
+            self.permit(Command::Upload)?;
+            self.permit(Command::FileWrite)?;
+            let local = remote_path::unquote(local);
+            return self
+                .queue_upload(Path::new(&local), remote)
+                .await
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+        if let Some(rest) = cmd_trimmed.strip_prefix("Download ")
+            && !rest.trim().is_empty()
+        {
+            // Downloads hash the file, then fetch it.
+            self.permit(Command::Download)?;
+            self.permit(Command::FileRead)?;
+            let (remote, local) = rest.split_once('|').unwrap_or((rest, ""));
+            return self
+                .queue_download(remote, local)
+                .await
+                .map_err(TixError::InvalidCommandSyntax);
+        }
+
+        // `service` answers once the service has settled, which may take
+        // longer than the usual request timeout.
+        let mut deadline = None;
+        let mut shell = None;
+        let (tix_cmd, payload) = if let Some(rest) = cmd_trimmed.strip_prefix("service")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            let req = self
+                .services
+                .parse(rest)
+                .map_err(TixError::InvalidCommandSyntax)?;
+            deadline = Some(req.timeout() + SERVICE_REPLY_SLACK);
+            (Command::ServiceControl, req.to_bytes()?)
+        } else if let Some(settings) = slave_wire {
+            (Command::WireTap, settings.to_bytes()?)
+        } else if let Some(rest) = cmd_trimmed.strip_prefix("ShellExecute") {
+            shell = Self::parse_shell_execute(rest, &self.default_shell)
+                .ok()
+                .map(|req| req.shell);
+            Self::shell_execute_payload(rest, &self.default_shell)
+                .map_err(TixError::InvalidCommandSyntax)?
+        } else {
+            match Self::parse_command(cmd_trimmed, self.cwd()) {
+                Ok(pair) => pair,
+                Err(msg) => return Err(TixError::InvalidCommandSyntax(msg)),
             }
         };
 
-        let req_id = self.next_req_id;
-        self.next_req_id += 1;
+        self.permit(tix_cmd)?;
+        if let Some(shell) = &shell {
+            self.permit_shell(shell)?;
+        }
+
+        let cache_key = self.cache.key(self.slave_id(), cmd_trimmed, &payload);
+        let refresh = match &cache_key {
+            Some(key) => match self.serve_cached(key, cmd_trimmed) {
+                Some(false) => return Ok(()),
+                Some(true) => true,
+                None => false,
+            },
+            None => false,
+        };
+
+        let req_id = self.state.next_request_id();
 
-        let _ = self.ui_tx.send(MasterEvent::Log(format!(
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
             "[SEND] ReqID {}: Sending {:?} to slave...",
             req_id, tix_cmd
         )));
 
-        let packet = Packet::new_command(req_id, tix_cmd, payload)
-            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        let packet = Packet::new_command(req_id, tix_cmd, payload)?;
 
-        // Track in MasterState before sending
-        self.state.track(req_id, packet.clone());
+        // Track in MasterState before sending. Pings get a short
+        // deadline so an unreachable slave shows up as loss quickly
+        // (see `request_timeout`).
+        if deadline.is_some() {
+            self.state
+                .track_with_deadline(req_id, packet.clone(), deadline);
+        } else {
+            self.track(req_id, &packet);
+        }
 
         if let Err(e) = self.conn.as_ref().unwrap().send(packet).await {
             self.state.resolve(req_id);
-            let _ = self.ui_tx.send(MasterEvent::Log(format!(
+            let _ = self.ui_tx.send(MasterEvent::log(format!(
                 "[ERR ] ReqID {}: Failed to send packet: {}",
                 req_id, e
             )));
-            return Err(std::io::Error::other(e.to_string()));
+            return Err(e);
         }
 
-        let _ = self.ui_tx.send(MasterEvent::Log(format!(
+        let _ = self.ui_tx.send(MasterEvent::log(format!(
             "[SEND] ReqID {}: Packet sent successfully",
             req_id
         )));
+        if let Some(key) = cache_key {
+            self.cached_requests.insert(req_id, (key, refresh));
+        }
+        if let Some(spec) = spec.filter(|spec| !spec.invalidates.is_empty()) {
+            self.changes.insert(req_id, spec);
+        }
+        if refresh {
+            return Ok(());
+        }
+        let _ = self.ui_tx.send(MasterEvent::TaskStarted {
+            id: req_id,
+            command: format!("{:?}", tix_cmd),
+            summary: tasks::summarize(&roles::redact(cmd_trimmed)),
+        });
         let _ = self.ui_tx.send(MasterEvent::TaskUpdate {
             id: req_id,
             status: "Waiting...".to_string(),
@@ -274,26 +3362,75 @@ impl TixMaster {
         Ok(())
     }
 
+    /// One step of a `Transaction`: `mkdir <path>`, `copy <src> <dest>`,
+    /// `move <src> <dest>` or `delete [--permanent] <path>`.
+    fn parse_transaction_step(
+        step: &[String],
+        resolve: impl Fn(&str) -> String,
+    ) -> Result<TransactionOp, String> {
+        let (verb, args) = step.split_first().ok_or("empty step")?;
+        match (verb.to_lowercase().as_str(), args) {
+            ("mkdir", [path]) => Ok(TransactionOp::Mkdir {
+                path: resolve(path),
+            }),
+            ("copy", [src, dest]) => Ok(TransactionOp::Copy {
+                src: resolve(src),
+                dest: resolve(dest),
+            }),
+            ("move", [src, dest]) => Ok(TransactionOp::Move {
+                src: resolve(src),
+                dest: resolve(dest),
+            }),
+            ("delete", [path]) => Ok(TransactionOp::Delete {
+                path: resolve(path),
+                mode: DeleteMode::Recycle,
+            }),
+            ("delete", [flag, path]) if flag == "--permanent" => Ok(TransactionOp::Delete {
+                path: resolve(path),
+                mode: DeleteMode::Permanent,
+            }),
+            ("mkdir", _) => Err("mkdir requires <path>".to_string()),
+            ("copy" | "move", _) => Err(format!("{} requires <src> <dest>", verb)),
+            ("delete", _) => Err("delete requires [--permanent] <path>".to_string()),
+            _ => Err(format!(
+                "unknown step `{}`; use mkdir, copy, move or delete",
+                verb
+            )),
+        }
+    }
+
     /// Parse a user-entered command string into a `(Command, payload)`.
-    fn parse_command(input: &str) -> Result<(Command, Vec<u8>), String> {
+    /// Slave paths are resolved against `cwd`, the directory set with
+    /// `cd`.
+    fn parse_command(input: &str, cwd: Option<&str>) -> Result<(Command, Vec<u8>), String> {
+        let resolve = |path: &str| remote_path::resolve(cwd, path);
+
         if input == "Ping" {
             return Ok((Command::Ping, Vec::new()));
         }
 
         if let Some(rest) = input.strip_prefix("ShellExecute") {
-            let arg = rest.trim_start();
-            if arg.is_empty() {
-                return Err("ShellExecute requires a command".to_string());
-            }
-            return Ok((Command::ShellExecute, arg.as_bytes().to_vec()));
+            return Self::shell_execute_payload(rest, &ShellKind::Cmd);
         }
 
         if let Some(rest) = input.strip_prefix("Copy") {
-            let arg = rest.trim_start();
-            if arg.is_empty() {
-                return Err("Copy requires <src> <dest>".to_string());
+            let mut overwrite = false;
+            let mut recursive = false;
+            let mut paths = Vec::new();
+            for arg in split_args(rest)? {
+                match arg.as_str() {
+                    "--overwrite" | "-f" => overwrite = true,
+                    "--recursive" | "-r" => recursive = true,
+                    _ => paths.push(arg),
+                }
             }
-            return Ok((Command::Copy, arg.as_bytes().to_vec()));
+            let [src, dest] = <[String; 2]>::try_from(paths).map_err(|_| {
+                "Copy requires [--overwrite] [--recursive] <src> <dest>".to_string()
+            })?;
+            let req = CopyRequest::new(resolve(&src), resolve(&dest))
+                .with_overwrite(overwrite)
+                .with_recursive(recursive);
+            return Ok((Command::Copy, req.to_bytes().map_err(|e| e.to_string())?));
         }
 
         if input.starts_with("ListDrives") {
@@ -301,9 +3438,13 @@ impl TixMaster {
         }
 
         if let Some(rest) = input.strip_prefix("ListDir") {
-            let path = rest.trim_start();
-            let path = if path.is_empty() { "." } else { path };
-            return Ok((Command::ListDir, path.as_bytes().to_vec()));
+            let path = resolve(&remote_path::unquote(rest));
+            let path = if path.is_empty() {
+                ".".to_string()
+            } else {
+                path
+            };
+            return Ok((Command::ListDir, path.into_bytes()));
         }
 
         if let Some(rest) = input.strip_prefix("Upload") {
@@ -311,7 +3452,13 @@ impl TixMaster {
             if arg.is_empty() {
                 return Err("Upload requires <local>|<remote>".to_string());
             }
-            return Ok((Command::Upload, arg.as_bytes().to_vec()));
+            let arg = match arg.split_once('|') {
+                Some((local, remote)) => {
+                    format!("{}|{}", local, resolve(&remote_path::unquote(remote)))
+                }
+                None => arg.to_string(),
+            };
+            return Ok((Command::Upload, arg.into_bytes()));
         }
 
         if let Some(rest) = input.strip_prefix("Download") {
@@ -319,7 +3466,67 @@ impl TixMaster {
             if arg.is_empty() {
                 return Err("Download requires <remote>|<local>".to_string());
             }
-            return Ok((Command::Download, arg.as_bytes().to_vec()));
+            let arg = match arg.split_once('|') {
+                Some((remote, local)) => {
+                    format!("{}|{}", resolve(&remote_path::unquote(remote)), local)
+                }
+                None => resolve(&remote_path::unquote(arg)),
+            };
+            return Ok((Command::Download, arg.into_bytes()));
+        }
+
+        if let Some(rest) = input.strip_prefix("Delete") {
+            let mut args = split_args(rest)?;
+            let mode = match args.iter().position(|a| a == "--permanent") {
+                Some(i) => {
+                    args.remove(i);
+                    DeleteMode::Permanent
+                }
+                None => DeleteMode::Recycle,
+            };
+            if args.is_empty() {
+                return Err("Delete requires [--permanent] <path>...".to_string());
+            }
+            let paths = args.iter().map(|path| resolve(path)).collect::<Vec<_>>();
+            let req = FileDeleteRequest::new(paths).with_mode(mode);
+            return Ok((
+                Command::FileDelete,
+                req.to_bytes().map_err(|e| e.to_string())?,
+            ));
+        }
+
+        if let Some(rest) = input.strip_prefix("TrashRestore") {
+            let path = match split_args(rest)?.as_slice() {
+                [path] => resolve(path),
+                _ => return Err("TrashRestore requires <original path>".to_string()),
+            };
+            return Ok((
+                Command::TrashRestore,
+                TrashRestoreRequest::new(path)
+                    .to_bytes()
+                    .map_err(|e| e.to_string())?,
+            ));
+        }
+
+        if let Some(rest) = input.strip_prefix("Transaction") {
+            let steps = split_steps(rest)?;
+            if steps.is_empty() {
+                return Err("Transaction requires <step> ; <step> ...".to_string());
+            }
+            let ops = steps
+                .iter()
+                .enumerate()
+                .map(|(i, step)| {
+                    Self::parse_transaction_step(step, resolve)
+                        .map_err(|e| format!("Transaction step {}: {}", i + 1, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let req = FileTransactionRequest::new(ops);
+            req.validate()?;
+            return Ok((
+                Command::FileTransaction,
+                req.to_bytes().map_err(|e| e.to_string())?,
+            ));
         }
 
         if let Some(rest) = input.strip_prefix("SystemAction") {
@@ -330,9 +3537,241 @@ impl TixMaster {
             return Ok((Command::SystemAction, action.as_bytes().to_vec()));
         }
 
+        if let Some(rest) = input.strip_prefix("reg query") {
+            let args = split_args(rest)?;
+            let (path, value) = match args.as_slice() {
+                [path] => (path, None),
+                [path, value] => (path, Some(value)),
+                _ => return Err("reg query requires <path> [value]".to_string()),
+            };
+            let mut req = RegistryQueryRequest::from_path(path).map_err(|e| e.to_string())?;
+            if let Some(value) = value {
+                req = req.with_value(value.as_str());
+            }
+            return Ok((
+                Command::RegistryQuery,
+                req.to_bytes().map_err(|e| e.to_string())?,
+            ));
+        }
+
+        if input == "sysinfo" {
+            return Ok((Command::SystemInfo, Vec::new()));
+        }
+
+        if input == "selftest" {
+            return Ok((Command::SelfTest, Vec::new()));
+        }
+
+        if input == "startup" {
+            return Ok((Command::StartupList, Vec::new()));
+        }
+
+        if let Some(rest) = input.strip_prefix("services")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            let req = services::parse_list(rest)?;
+            return Ok((
+                Command::ServiceList,
+                req.to_bytes().map_err(|e| e.to_string())?,
+            ));
+        }
+
+        if let Some(rest) = input.strip_prefix("net")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            let req = netdiag::parse(rest)?;
+            return Ok((Command::NetDiag, req.to_bytes().map_err(|e| e.to_string())?));
+        }
+
+        if let Some(rest) = input.strip_prefix("schedule")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            let (cmd, payload) = match ScheduleCommand::parse(rest)? {
+                ScheduleCommand::Create(req) => (Command::ScheduleCreate, req.to_bytes()),
+                ScheduleCommand::List => (Command::ScheduleList, Ok(Vec::new())),
+                ScheduleCommand::Delete(name) => (
+                    Command::ScheduleDelete,
+                    ScheduleNameRequest::new(name).to_bytes(),
+                ),
+                ScheduleCommand::Results(name) => (
+                    Command::ScheduleResults,
+                    ScheduleNameRequest::new(name).to_bytes(),
+                ),
+            };
+            return Ok((cmd, payload.map_err(|e| e.to_string())?));
+        }
+
+        if input == "tasks" {
+            return Ok((Command::TaskList, Vec::new()));
+        }
+
+        if let Some(rest) = input.strip_prefix("cancel")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            let id: u64 = rest
+                .trim()
+                .parse()
+                .map_err(|_| "cancel requires a task ID".to_string())?;
+            return Ok((Command::ShellCancel, id.to_le_bytes().to_vec()));
+        }
+
         Err(format!("Unknown command: '{}'", input))
     }
 
+    /// `ShellExecute` arguments as a payload, run in `shell` unless `-s`
+    /// names another. A bare command for the system shell goes out as
+    /// text, which every slave accepts.
+    fn shell_execute_payload(rest: &str, shell: &ShellKind) -> Result<(Command, Vec<u8>), String> {
+        let req = Self::parse_shell_execute(rest, shell)?;
+        if req.working_dir.is_none()
+            && req.env.is_empty()
+            && req.timeout_ms == 0
+            && req.shell == ShellKind::Cmd
+        {
+            return Ok((Command::ShellExecute, req.command.into_bytes()));
+        }
+        Ok((
+            Command::ShellExecute,
+            req.to_bytes().map_err(|e| e.to_string())?,
+        ))
+    }
+
+    /// `ShellExecute [-s <shell>] [-d <dir>] [-e KEY=VALUE]... [-t <secs>] -- <command>`,
+    /// or just `ShellExecute <command>`. The command after `--` goes to
+    /// the slave's shell exactly as typed: `shell`, unless `-s` names
+    /// `cmd`, `powershell`, `pwsh` or a custom shell the slave allows. No
+    /// timeout is sent unless `-t` asks for one; the slave's own task
+    /// timeout still applies.
+    fn parse_shell_execute(rest: &str, shell: &ShellKind) -> Result<ShellExecuteRequest, String> {
+        const USAGE: &str =
+            "ShellExecute [-s <shell>] [-d <dir>] [-e KEY=VALUE]... [-t <secs>] -- <command>";
+        let rest = rest.trim();
+        let first = rest.split_whitespace().next().unwrap_or_default();
+        if !matches!(
+            first,
+            "-s" | "--shell" | "-d" | "--dir" | "-e" | "--env" | "-t" | "--timeout" | "--"
+        ) {
+            if rest.is_empty() {
+                return Err("ShellExecute requires a command".to_string());
+            }
+            return Ok(ShellExecuteRequest::new(rest)
+                .with_timeout(0)
+                .with_shell(shell.clone()));
+        }
+
+        let (options, command) = split_options(rest)?;
+        let command = command
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| format!("Usage: {}", USAGE))?;
+        let mut req = ShellExecuteRequest::new(command)
+            .with_timeout(0)
+            .with_shell(shell.clone());
+        let mut options = options.into_iter();
+        while let Some(option) = options.next() {
+            let mut value = || {
+                options
+                    .next()
+                    .ok_or_else(|| format!("{} requires a value", option))
+            };
+            match option.as_str() {
+                "-s" | "--shell" => {
+                    req = req.with_shell(value()?.parse().map_err(|e| format!("-s: {e}"))?)
+                }
+                "-d" | "--dir" => req = req.with_working_dir(value()?),
+                "-e" | "--env" => {
+                    let pair = value()?;
+                    let (key, val) = pair
+                        .split_once('=')
+                        .filter(|(key, _)| !key.is_empty())
+                        .ok_or_else(|| format!("-e expects KEY=VALUE, got '{}'", pair))?;
+                    req = req.with_env(key, val);
+                }
+                "-t" | "--timeout" => {
+                    let secs: u64 = value()?
+                        .parse()
+                        .map_err(|_| "-t expects a number of seconds".to_string())?;
+                    req = req.with_timeout(secs.saturating_mul(1000));
+                }
+                other => return Err(format!("Unknown option '{}'. Usage: {}", other, USAGE)),
+            }
+        }
+        Ok(req)
+    }
+
+    // ── Remote working directory ─────────────────────────────────
+
+    /// Where the connected slave's `cd` is kept: its ID, or its address
+    /// until it says who it is.
+    fn cwd_key(&self) -> Option<String> {
+        match self.slave_id() {
+            Some(id) => Some(id.to_string()),
+            None => self.slave_conn_info.as_ref().map(|c| c.ip().to_string()),
+        }
+    }
+
+    /// The connected slave's current directory, once `cd` set one.
+    pub fn cwd(&self) -> Option<&str> {
+        self.cwds.get(&self.cwd_key()?).map(String::as_str)
+    }
+
+    /// `cd <path>`: list `path` on the slave and make it the current
+    /// directory once that succeeds. Without a path, show the current
+    /// one.
+    async fn change_dir(&mut self, rest: &str) -> Result<(), String> {
+        let arg = remote_path::unquote(rest);
+        if arg.is_empty() {
+            self.print_cwd();
+            return Ok(());
+        }
+        let cwd = self.cwd();
+        if cwd.is_none() && !remote_path::is_absolute(&arg) {
+            return Err(format!(
+                "cd {}: no current directory yet; start from an absolute path",
+                arg
+            ));
+        }
+        let target = remote_path::normalize(&remote_path::resolve(cwd, &arg));
+        let req_id = self.send_listing(&target).await?;
+        self.cd_requests.insert(req_id, target);
+        Ok(())
+    }
+
+    fn set_cwd(&mut self, dir: String) {
+        let Some(key) = self.cwd_key() else {
+            return;
+        };
+        let _ = self
+            .ui_tx
+            .send(MasterEvent::log(format!("Remote directory: {}", dir)));
+        let _ = self.ui_tx.send(MasterEvent::RemoteCwd(Some(dir.clone())));
+        self.cwds.insert(key, dir);
+    }
+
+    /// `pwd`
+    fn print_cwd(&self) {
+        let line = match self.cwd() {
+            Some(dir) => format!("Remote directory: {}", dir),
+            None => "No remote directory; relative paths go to the slave as typed".to_string(),
+        };
+        let _ = self.ui_tx.send(MasterEvent::log(line));
+    }
+
+    /// Send a `ListDir` of `dir` whose answer only the master sees.
+    async fn send_listing(&mut self, dir: &str) -> Result<u64, String> {
+        let conn = self.conn.as_ref().ok_or("No slave connected")?;
+        let req_id = self.state.next_request_id();
+        let packet = Packet::new_command(req_id, Command::ListDir, dir.as_bytes().to_vec())
+            .map_err(|e| e.to_string())?;
+        let timeout = self.timeout_for(&packet);
+        self.state
+            .track_with_deadline(req_id, packet.clone(), Some(timeout));
+        if let Err(e) = conn.send(packet).await {
+            self.state.resolve(req_id);
+            return Err(e.to_string());
+        }
+        Ok(req_id)
+    }
+
     // ── Accessors ────────────────────────────────────────────────
 
     /// Display string for the connected slave.
@@ -351,6 +3790,16 @@ impl TixMaster {
             .unwrap_or_else(|| "Unknown".to_string())
     }
 
+    /// Address the master listens on, or its end of the connection it
+    /// dialled.
+    pub fn local_addr(&self) -> Result<SocketAddr, TixError> {
+        match (&self.listener, &self.conn) {
+            (Some(listener), _) => Ok(listener.local_addr()?),
+            (None, Some(conn)) => conn.local_addr().ok_or(TixError::NotConnected),
+            (None, None) => Err(TixError::NotConnected),
+        }
+    }
+
     /// Whether a slave is currently connected.
     pub fn is_connected(&self) -> bool {
         self.conn.is_some()
@@ -361,3 +3810,189 @@ impl TixMaster {
         self.state.pending_count()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tasks() {
+        let (cmd, payload) = TixMaster::parse_command("tasks", None).unwrap();
+        assert_eq!(cmd, Command::TaskList);
+        assert!(payload.is_empty());
+    }
+
+    #[test]
+    fn parse_delete_defaults_to_recycle() {
+        let (cmd, payload) =
+            TixMaster::parse_command(r#"Delete "C:\My Docs\a.txt" C:\b"#, None).unwrap();
+        assert_eq!(cmd, Command::FileDelete);
+        let req = FileDeleteRequest::from_bytes(&payload).unwrap();
+        assert_eq!(req.mode, DeleteMode::Recycle);
+        assert_eq!(req.paths, vec!["C:\\My Docs\\a.txt", "C:\\b"]);
+    }
+
+    #[test]
+    fn parse_copy_with_quoted_paths_and_flags() {
+        let (cmd, payload) =
+            TixMaster::parse_command(r#"Copy -r "C:\Program Files\app" "D:\new home\\" "#, None)
+                .unwrap();
+        assert_eq!(cmd, Command::Copy);
+        let req = CopyRequest::from_bytes(&payload).unwrap();
+        assert_eq!(req.src, "C:\\Program Files\\app");
+        assert_eq!(req.dest, "D:\\new home\\");
+        assert!(req.recursive);
+        assert!(!req.overwrite);
+
+        assert!(TixMaster::parse_command(r"Copy C:\Program Files\app C:\dest", None).is_err());
+        assert!(TixMaster::parse_command(r#"Copy "C:\a.txt"#, None).is_err());
+    }
+
+    #[test]
+    fn parse_delete_permanent_flag() {
+        let (_, payload) = TixMaster::parse_command("Delete C:\\tmp --permanent", None).unwrap();
+        let req = FileDeleteRequest::from_bytes(&payload).unwrap();
+        assert_eq!(req.mode, DeleteMode::Permanent);
+        assert_eq!(req.paths, vec!["C:\\tmp"]);
+
+        assert!(TixMaster::parse_command("Delete --permanent", None).is_err());
+    }
+
+    #[test]
+    fn parse_transaction_steps() {
+        let (cmd, payload) = TixMaster::parse_command(
+            r#"Transaction mkdir archive; move "a b.txt" archive\a.txt; COPY x y ; delete --permanent old"#,
+            Some(r"C:\work"),
+        )
+        .unwrap();
+        assert_eq!(cmd, Command::FileTransaction);
+        let req = FileTransactionRequest::from_bytes(&payload).unwrap();
+        assert_eq!(
+            req.ops,
+            [
+                TransactionOp::Mkdir {
+                    path: r"C:\work\archive".to_string()
+                },
+                TransactionOp::Move {
+                    src: r"C:\work\a b.txt".to_string(),
+                    dest: r"C:\work\archive\a.txt".to_string()
+                },
+                TransactionOp::Copy {
+                    src: r"C:\work\x".to_string(),
+                    dest: r"C:\work\y".to_string()
+                },
+                TransactionOp::Delete {
+                    path: r"C:\work\old".to_string(),
+                    mode: DeleteMode::Permanent
+                },
+            ]
+        );
+
+        let err = TixMaster::parse_command("Transaction mkdir a; move b", None).unwrap_err();
+        assert_eq!(err, "Transaction step 2: move requires <src> <dest>");
+        assert!(TixMaster::parse_command("Transaction rename a b", None).is_err());
+        assert!(TixMaster::parse_command("Transaction ;", None).is_err());
+        let many = vec!["mkdir x"; 65].join(";");
+        assert!(TixMaster::parse_command(&format!("Transaction {}", many), None).is_err());
+    }
+
+    #[test]
+    fn parse_shell_execute_options() {
+        let (cmd, payload) = TixMaster::parse_command(
+            r#"ShellExecute -d "C:\My Proj" -e RUST_LOG=debug -e EMPTY= -t 90 -- cargo build --release"#, None)
+        .unwrap();
+        assert_eq!(cmd, Command::ShellExecute);
+        let req = ShellExecuteRequest::from_bytes(&payload).unwrap();
+        assert_eq!(req.command, "cargo build --release");
+        assert_eq!(req.working_dir.as_deref(), Some("C:\\My Proj"));
+        assert_eq!(req.env["RUST_LOG"], "debug");
+        assert_eq!(req.env["EMPTY"], "");
+        assert_eq!(req.timeout_ms, 90_000);
+
+        // Without options the whole line is the command, quotes and all.
+        // It is sent as plain text, like before options existed.
+        let (_, payload) = TixMaster::parse_command(r#"ShellExecute echo "a -- b""#, None).unwrap();
+        assert_eq!(payload, br#"echo "a -- b""#);
+    }
+
+    #[test]
+    fn parse_shell_execute_picks_a_shell() {
+        let (_, payload) = TixMaster::parse_command(
+            r#"ShellExecute -s powershell -- Get-Date "$env:TEMP""#,
+            None,
+        )
+        .unwrap();
+        let req = ShellExecuteRequest::from_bytes(&payload).unwrap();
+        assert_eq!(req.shell, ShellKind::PowerShell);
+        assert_eq!(req.command, r#"Get-Date "$env:TEMP""#);
+
+        // The configured default applies to bare commands too, which then
+        // go out as a full request; `-s` still overrides it.
+        let pwsh = ShellKind::PwshCore;
+        let (_, payload) = TixMaster::shell_execute_payload(" Get-Date", &pwsh).unwrap();
+        let req = ShellExecuteRequest::from_bytes(&payload).unwrap();
+        assert_eq!(
+            (req.shell, req.command.as_str()),
+            (pwsh.clone(), "Get-Date")
+        );
+        let (_, payload) = TixMaster::shell_execute_payload(" -s bash -- ls | wc", &pwsh).unwrap();
+        let req = ShellExecuteRequest::from_bytes(&payload).unwrap();
+        assert_eq!(req.shell, ShellKind::Custom("bash".into()));
+        let (_, payload) = TixMaster::shell_execute_payload(" -s cmd -- dir", &pwsh).unwrap();
+        assert_eq!(payload, b"dir");
+    }
+
+    #[test]
+    fn parse_shell_execute_rejects_bad_options() {
+        for bad in [
+            "ShellExecute",
+            "ShellExecute -d C:\\x",
+            "ShellExecute -d C:\\x --",
+            "ShellExecute -e NOEQUALS -- dir",
+            "ShellExecute -e =x -- dir",
+            "ShellExecute -t soon -- dir",
+            "ShellExecute -d -- dir",
+            "ShellExecute -e A=1 -x -- dir",
+            "ShellExecute -s -- dir",
+        ] {
+            assert!(TixMaster::parse_command(bad, None).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn relative_paths_follow_the_remote_directory() {
+        let cwd = Some(r"C:\Users\bob");
+        let (_, payload) = TixMaster::parse_command(r#"ListDir "My Docs""#, cwd).unwrap();
+        assert_eq!(payload, br"C:\Users\bob\My Docs");
+        let (_, payload) = TixMaster::parse_command("ListDir", cwd).unwrap();
+        assert_eq!(payload, br"C:\Users\bob");
+        let (_, payload) = TixMaster::parse_command("ListDir", None).unwrap();
+        assert_eq!(payload, b".");
+
+        let (_, payload) = TixMaster::parse_command(r"Copy ..\a.txt D:\b", cwd).unwrap();
+        let req = CopyRequest::from_bytes(&payload).unwrap();
+        assert_eq!(
+            (req.src.as_str(), req.dest.as_str()),
+            (r"C:\Users\a.txt", r"D:\b")
+        );
+
+        let (_, payload) =
+            TixMaster::parse_command(r"Download logs\x.log|C:\here\x.log", cwd).unwrap();
+        assert_eq!(payload, br"C:\Users\bob\logs\x.log|C:\here\x.log");
+        let (_, payload) = TixMaster::parse_command(r"Upload local.txt|up\", cwd).unwrap();
+        assert_eq!(payload, br"local.txt|C:\Users\bob\up\");
+
+        let (_, payload) = TixMaster::parse_command(r#"Delete "old files" C:\x"#, cwd).unwrap();
+        let req = FileDeleteRequest::from_bytes(&payload).unwrap();
+        assert_eq!(req.paths, [r"C:\Users\bob\old files", r"C:\x"]);
+    }
+
+    #[test]
+    fn parse_trash_restore() {
+        let (cmd, payload) =
+            TixMaster::parse_command(r#"TrashRestore "C:\a b.txt""#, None).unwrap();
+        assert_eq!(cmd, Command::TrashRestore);
+        let req = TrashRestoreRequest::from_bytes(&payload).unwrap();
+        assert_eq!(req.original_path, "C:\\a b.txt");
+    }
+}
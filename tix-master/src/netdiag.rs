@@ -0,0 +1,340 @@
+//! The `net` console command: network diagnostics run on the slave.
+//!
+//! `net if` lists its adapters, `net routes` its routing table,
+//! `net resolve <name>` asks its resolver, and `net check <host:port>`
+//! has it open a TCP connection, which answers "can the slave reach X"
+//! without guessing from localized `ping` or `Test-NetConnection` text.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use tix_core::protocol::netdiag::DEFAULT_PORT_CHECK_TIMEOUT;
+use tix_core::protocol::{NetDiagRequest, NetDiagResponse, NetInterface, RouteEntry};
+
+use crate::args::split_args;
+use crate::table::format_table;
+
+const USAGE: &str = "net if | net routes | net resolve <name> | net check <host:port> [-t <secs>]";
+
+/// Longest port check the console asks for, so the answer still comes
+/// within the request timeout.
+pub const MAX_PORT_CHECK_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Parse what follows `net`.
+pub fn parse(rest: &str) -> Result<NetDiagRequest, String> {
+    let args = split_args(rest)?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["if" | "interfaces"] => Ok(NetDiagRequest::InterfaceList),
+        ["routes" | "route"] => Ok(NetDiagRequest::RouteTable),
+        ["resolve", name] => Ok(NetDiagRequest::DnsResolve {
+            name: name.to_string(),
+        }),
+        ["check", target, options @ ..] => {
+            let (host, port) = parse_target(target)?;
+            let timeout = match options {
+                [] => DEFAULT_PORT_CHECK_TIMEOUT,
+                ["-t" | "--timeout", secs] => secs
+                    .parse()
+                    .ok()
+                    .filter(|secs| (1..=MAX_PORT_CHECK_TIMEOUT.as_secs()).contains(secs))
+                    .map(Duration::from_secs)
+                    .ok_or_else(|| {
+                        format!("-t needs 1 to {} seconds", MAX_PORT_CHECK_TIMEOUT.as_secs())
+                    })?,
+                _ => return Err(format!("Usage: {}", USAGE)),
+            };
+            Ok(NetDiagRequest::PortCheck {
+                host,
+                port,
+                timeout_ms: timeout.as_millis() as u64,
+            })
+        }
+        _ => Err(format!("Usage: {}", USAGE)),
+    }
+}
+
+/// Split `host:port`; IPv6 addresses go in brackets, `[::1]:3389`.
+fn parse_target(target: &str) -> Result<(String, u16), String> {
+    let (host, port) = target
+        .rsplit_once(':')
+        .ok_or_else(|| format!("'{}' needs a port: <host:port>", target))?;
+    let host = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed
+            .strip_suffix(']')
+            .ok_or_else(|| format!("unclosed '[' in '{}'", target))?,
+        None if host.contains(':') => {
+            return Err(format!(
+                "put IPv6 addresses in brackets: [{}]:{}",
+                host, port
+            ));
+        }
+        None => host,
+    };
+    if host.is_empty() {
+        return Err(format!("'{}' has no host", target));
+    }
+    let port = port
+        .parse::<u16>()
+        .ok()
+        .filter(|port| *port != 0)
+        .ok_or_else(|| format!("'{}' is not a port", port))?;
+    Ok((host.to_string(), port))
+}
+
+/// Log text for a `NetDiag` reply.
+pub fn describe(response: NetDiagResponse) -> Result<String, String> {
+    match response {
+        NetDiagResponse::Interfaces(interfaces) => Ok(describe_interfaces(&interfaces)),
+        NetDiagResponse::Routes(routes) => Ok(describe_routes(&routes)),
+        NetDiagResponse::Resolved(result) => {
+            let mut out = format!(
+                "{} resolved to {} addresses in {}",
+                result.name,
+                result.addresses.len(),
+                millis(Duration::from_micros(result.elapsed_us))
+            );
+            if !result.addresses.is_empty() {
+                let rows: Vec<Vec<String>> = result
+                    .addresses
+                    .iter()
+                    .map(|address| vec![family(address).to_string(), address.to_string()])
+                    .collect();
+                out.push('\n');
+                out.push_str(&format_table(&["Family", "Address"], &rows));
+            }
+            Ok(out)
+        }
+        NetDiagResponse::PortCheck(result) => {
+            let row = vec![
+                format!("{}:{}", result.host, result.port),
+                result.address.map_or("-".to_string(), |a| a.to_string()),
+                millis(result.elapsed()),
+                result.outcome.to_string(),
+            ];
+            Ok(format_table(
+                &["Target", "Address", "Time", "Result"],
+                &[row],
+            ))
+        }
+        NetDiagResponse::Error { kind, message } => {
+            Err(format!("Network diagnostics {}: {}", kind, message))
+        }
+    }
+}
+
+fn describe_interfaces(interfaces: &[NetInterface]) -> String {
+    let list = |items: Vec<String>| {
+        if items.is_empty() {
+            "-".to_string()
+        } else {
+            items.join(", ")
+        }
+    };
+    let rows: Vec<Vec<String>> = interfaces
+        .iter()
+        .map(|i| {
+            vec![
+                i.index.to_string(),
+                i.name.clone(),
+                i.state.to_string(),
+                i.speed_bps.map_or("-".to_string(), speed),
+                i.mac.clone().unwrap_or_else(|| "-".to_string()),
+                list(i.gateways.iter().map(IpAddr::to_string).collect()),
+                list(i.dns_servers.iter().map(IpAddr::to_string).collect()),
+                list(i.addresses.iter().map(|a| a.to_string()).collect()),
+            ]
+        })
+        .collect();
+    let mut out = format!("{} interfaces", rows.len());
+    if !rows.is_empty() {
+        out.push('\n');
+        out.push_str(&format_table(
+            &[
+                "Index",
+                "Name",
+                "State",
+                "Speed",
+                "MAC",
+                "Gateway",
+                "DNS",
+                "Addresses",
+            ],
+            &rows,
+        ));
+    }
+    out
+}
+
+fn describe_routes(routes: &[RouteEntry]) -> String {
+    let rows: Vec<Vec<String>> = routes
+        .iter()
+        .map(|r| {
+            vec![
+                format!("{}/{}", r.destination, r.prefix_len),
+                r.next_hop
+                    .map_or("on-link".to_string(), |hop| hop.to_string()),
+                r.metric.to_string(),
+                r.interface
+                    .clone()
+                    .unwrap_or_else(|| format!("#{}", r.interface_index)),
+            ]
+        })
+        .collect();
+    let mut out = format!("{} routes", rows.len());
+    if !rows.is_empty() {
+        out.push('\n');
+        out.push_str(&format_table(
+            &["Destination", "Next hop", "Metric", "Interface"],
+            &rows,
+        ));
+    }
+    out
+}
+
+fn family(address: &IpAddr) -> &'static str {
+    match address {
+        IpAddr::V4(_) => "IPv4",
+        IpAddr::V6(_) => "IPv6",
+    }
+}
+
+fn millis(elapsed: Duration) -> String {
+    format!("{:.1} ms", elapsed.as_secs_f64() * 1000.0)
+}
+
+/// Link speed in the largest unit that keeps it whole, e.g. `1 Gb/s`.
+fn speed(bps: u64) -> String {
+    const UNITS: [(u64, &str); 3] = [
+        (1_000_000_000, "Gb/s"),
+        (1_000_000, "Mb/s"),
+        (1_000, "kb/s"),
+    ];
+    UNITS
+        .iter()
+        .find(|(scale, _)| bps >= *scale && bps.is_multiple_of(*scale))
+        .map_or(format!("{} b/s", bps), |(scale, unit)| {
+            format!("{} {}", bps / scale, unit)
+        })
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use tix_core::protocol::{
+        InterfaceAddress, LinkState, NetDiagErrorKind, PortCheckOutcome, PortCheckResult,
+    };
+
+    #[test]
+    fn parses_net_commands() {
+        assert_eq!(parse(" if").unwrap(), NetDiagRequest::InterfaceList);
+        assert_eq!(parse(" routes").unwrap(), NetDiagRequest::RouteTable);
+        assert_eq!(
+            parse(" resolve intranet.local").unwrap(),
+            NetDiagRequest::DnsResolve {
+                name: "intranet.local".into()
+            }
+        );
+        assert_eq!(
+            parse(" check 10.0.0.5:3389").unwrap(),
+            NetDiagRequest::port_check("10.0.0.5", 3389)
+        );
+        assert_eq!(
+            parse(" check [fe80::1]:22 -t 10").unwrap(),
+            NetDiagRequest::PortCheck {
+                host: "fe80::1".into(),
+                port: 22,
+                timeout_ms: 10_000,
+            }
+        );
+
+        for bad in [
+            "",
+            " if now",
+            " resolve",
+            " check host",
+            " check host:0",
+            " check :80",
+            " check fe80::1:22",
+            " check host:80 -t 60",
+        ] {
+            assert!(parse(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn replies_render_as_tables() {
+        let ethernet = NetInterface {
+            index: 12,
+            name: "Ethernet".into(),
+            description: "Intel(R) Ethernet".into(),
+            state: LinkState::Up,
+            mac: Some("00:1b:21:3a:4f:5c".into()),
+            mtu: 1500,
+            speed_bps: Some(1_000_000_000),
+            addresses: vec![InterfaceAddress {
+                address: Ipv4Addr::new(192, 168, 1, 20).into(),
+                prefix_len: 24,
+            }],
+            gateways: vec![Ipv4Addr::new(192, 168, 1, 1).into()],
+            dns_servers: Vec::new(),
+        };
+        let table = describe(NetDiagResponse::Interfaces(vec![ethernet])).unwrap();
+        assert!(table.starts_with("1 interfaces\nIndex"), "{}", table);
+        assert!(
+            table.contains("12     Ethernet  up     1 Gb/s  00:1b:21:3a:4f:5c  192.168.1.1  -    192.168.1.20/24"),
+            "{}",
+            table
+        );
+
+        let routes = describe(NetDiagResponse::Routes(vec![RouteEntry {
+            destination: Ipv4Addr::UNSPECIFIED.into(),
+            prefix_len: 0,
+            next_hop: None,
+            interface_index: 7,
+            interface: None,
+            metric: 25,
+        }]))
+        .unwrap();
+        assert!(
+            routes.ends_with("0.0.0.0/0    on-link   25      #7"),
+            "{}",
+            routes
+        );
+
+        let check = describe(NetDiagResponse::PortCheck(PortCheckResult {
+            host: "master".into(),
+            port: 7331,
+            address: Some("192.168.1.2:7331".parse().unwrap()),
+            outcome: PortCheckOutcome::Refused,
+            elapsed_us: 1_250,
+        }))
+        .unwrap();
+        assert!(
+            check.ends_with("master:7331  192.168.1.2:7331  1.2 ms  refused"),
+            "{}",
+            check
+        );
+
+        let err = describe(NetDiagResponse::error(
+            NetDiagErrorKind::Unsupported,
+            "the route table is only available on Windows",
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "Network diagnostics unsupported: the route table is only available on Windows"
+        );
+    }
+
+    #[test]
+    fn speeds_use_whole_units() {
+        assert_eq!(speed(1_000_000_000), "1 Gb/s");
+        assert_eq!(speed(100_000_000), "100 Mb/s");
+        assert_eq!(speed(2_500_000_000), "2500 Mb/s");
+        assert_eq!(speed(999), "999 b/s");
+    }
+}
@@ -0,0 +1,517 @@
+//! Alerts for things the operator may be looking away from.
+//!
+//! A long transfer or a slave dropping off happens while the console
+//! sits in another terminal tab. The [`Notifier`] watches the
+//! [`MasterEvent`]s on their way to the UI, picks out those worth an
+//! alert (see [`classify`]) and, for the kinds enabled in `[notify]`,
+//! rings the terminal bell, shows a banner across the top of the console
+//! and runs `notify_cmd` for a desktop toast. A slave that keeps
+//! reconnecting gets one alert per `min_interval_secs`; the rest are
+//! counted into the next.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Modifier;
+use ratatui::text::Line;
+use ratatui::widgets::{Paragraph, Widget};
+use tix_core::format::format_bytes;
+use tokio::sync::mpsc;
+
+use crate::app::MasterEvent;
+use crate::config::NotifyConfig;
+use crate::theme::Theme;
+
+/// Seconds a banner stays up unless configured otherwise.
+pub const DEFAULT_BANNER_SECS: u64 = 5;
+
+/// Seconds between two alerts of the same kind unless configured
+/// otherwise.
+pub const DEFAULT_MIN_INTERVAL_SECS: u64 = 10;
+
+/// The kinds of event that can raise an alert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    TaskFinished,
+    TaskFailed,
+    SlaveConnected,
+    SlaveDisconnected,
+    TransferComplete,
+}
+
+impl Trigger {
+    /// Bad news, drawn in the danger colour.
+    pub fn is_failure(self) -> bool {
+        matches!(self, Trigger::TaskFailed | Trigger::SlaveDisconnected)
+    }
+
+    fn enabled(self, config: &NotifyConfig) -> bool {
+        match self {
+            Trigger::TaskFinished => config.task_finished,
+            Trigger::TaskFailed => config.task_failed,
+            Trigger::SlaveConnected => config.slave_connected,
+            Trigger::SlaveDisconnected => config.slave_disconnected,
+            Trigger::TransferComplete => config.transfer_complete,
+        }
+    }
+}
+
+/// An event worth telling the operator about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub trigger: Trigger,
+    pub message: String,
+}
+
+impl Alert {
+    fn new(trigger: Trigger, message: String) -> Self {
+        Self { trigger, message }
+    }
+}
+
+/// The alert `event` calls for, if any.
+pub fn classify(event: &MasterEvent) -> Option<Alert> {
+    match event {
+        MasterEvent::TaskUpdate { id, status } => {
+            if status.starts_with("Solved") {
+                Some(Alert::new(
+                    Trigger::TaskFinished,
+                    format!("Task {} solved", id),
+                ))
+            } else if status.starts_with("Failed") {
+                Some(Alert::new(
+                    Trigger::TaskFailed,
+                    format!("Task {} failed", id),
+                ))
+            } else if status.starts_with("Panicked") {
+                Some(Alert::new(
+                    Trigger::TaskFailed,
+                    format!("Task {} panicked on the slave", id),
+                ))
+            } else if status.starts_with("Timed out") {
+                Some(Alert::new(
+                    Trigger::TaskFailed,
+                    format!("Task {} timed out", id),
+                ))
+            } else {
+                None
+            }
+        }
+        MasterEvent::Response { id, text }
+            if text.starts_with("Upload complete") || text.starts_with("Download complete") =>
+        {
+            let what = text.lines().next().unwrap_or_default();
+            Some(Alert::new(
+                Trigger::TransferComplete,
+                format!("{} (ReqID {})", what, id),
+            ))
+        }
+        MasterEvent::LocalCopy(progress) if progress.done => {
+            if progress.errors.is_empty() {
+                Some(Alert::new(
+                    Trigger::TransferComplete,
+                    format!(
+                        "Copied {} files ({}) to {}",
+                        progress.files,
+                        format_bytes(progress.bytes),
+                        progress.dest_dir.display()
+                    ),
+                ))
+            } else {
+                Some(Alert::new(
+                    Trigger::TaskFailed,
+                    format!(
+                        "Copy to {} failed for {} items",
+                        progress.dest_dir.display(),
+                        progress.errors.len()
+                    ),
+                ))
+            }
+        }
+        MasterEvent::SlaveConnected(addr) => Some(Alert::new(
+            Trigger::SlaveConnected,
+            format!("Slave connected: {}", addr),
+        )),
+        MasterEvent::SlaveDisconnected(name) => Some(Alert::new(
+            Trigger::SlaveDisconnected,
+            format!("{} disconnected", name),
+        )),
+        _ => None,
+    }
+}
+
+/// What to do about one alert.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub message: String,
+    pub bell: bool,
+    /// `notify_cmd` with the message filled in.
+    pub command: Option<String>,
+}
+
+/// The line across the top of the console.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Banner {
+    pub text: String,
+    pub trigger: Trigger,
+    until: Instant,
+}
+
+/// Turns [`MasterEvent`]s into alerts.
+#[derive(Debug)]
+pub struct Notifier {
+    config: NotifyConfig,
+    /// When each kind last raised an alert.
+    last: HashMap<Trigger, Instant>,
+    /// Alerts held back by the rate limit since.
+    suppressed: HashMap<Trigger, u32>,
+    banner: Option<Banner>,
+    /// Where a failing `notify_cmd` is reported.
+    events: Option<mpsc::UnboundedSender<MasterEvent>>,
+    /// Set once a `notify_cmd` failure was reported.
+    command_failed: Arc<AtomicBool>,
+}
+
+impl Notifier {
+    pub fn new(config: NotifyConfig) -> Self {
+        Self {
+            config,
+            last: HashMap::new(),
+            suppressed: HashMap::new(),
+            banner: None,
+            events: None,
+            command_failed: Arc::default(),
+        }
+    }
+
+    /// Log the first `notify_cmd` failure through `events`.
+    pub fn with_events(mut self, events: mpsc::UnboundedSender<MasterEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Look at `event` on its way to the UI, and deliver the alert it
+    /// calls for.
+    pub fn observe(&mut self, event: &MasterEvent) {
+        if let Some(notification) = self.notify(event, Instant::now()) {
+            self.deliver(&notification);
+        }
+    }
+
+    /// The banner to draw, while it lasts.
+    pub fn banner(&self) -> Option<&Banner> {
+        self.banner_at(Instant::now())
+    }
+
+    fn banner_at(&self, now: Instant) -> Option<&Banner> {
+        self.banner.as_ref().filter(|b| now < b.until)
+    }
+
+    /// Decide what `event` calls for at `now`, putting up its banner.
+    fn notify(&mut self, event: &MasterEvent, now: Instant) -> Option<Notification> {
+        let alert = classify(event)?;
+        if !alert.trigger.enabled(&self.config) {
+            return None;
+        }
+        let interval = Duration::from_secs(self.config.min_interval_secs);
+        if let Some(last) = self.last.get(&alert.trigger)
+            && now.saturating_duration_since(*last) < interval
+        {
+            *self.suppressed.entry(alert.trigger).or_default() += 1;
+            return None;
+        }
+        self.last.insert(alert.trigger, now);
+        let mut message = alert.message;
+        if let Some(more) = self.suppressed.remove(&alert.trigger) {
+            message.push_str(&format!(" (+{} more)", more));
+        }
+        if self.config.banner_secs > 0 {
+            self.banner = Some(Banner {
+                text: message.clone(),
+                trigger: alert.trigger,
+                until: now + Duration::from_secs(self.config.banner_secs),
+            });
+        }
+        let command = self
+            .config
+            .notify_cmd
+            .as_deref()
+            .map(|template| template.replace("{message}", &shell_safe(&message)));
+        Some(Notification {
+            message,
+            bell: self.config.bell,
+            command,
+        })
+    }
+
+    /// Ring the bell and start the command. The command runs on a
+    /// blocking task and is not waited for.
+    fn deliver(&self, notification: &Notification) {
+        if notification.bell {
+            let mut out = std::io::stdout();
+            let _ = out.write_all(b"\x07");
+            let _ = out.flush();
+        }
+        let Some(command) = notification.command.clone() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let message = notification.message.clone();
+        let events = self.events.clone();
+        let failed = Arc::clone(&self.command_failed);
+        runtime.spawn_blocking(move || {
+            let Err(e) = run_command(&command, &message) else {
+                return;
+            };
+            if !failed.swap(true, Ordering::Relaxed)
+                && let Some(events) = events
+            {
+                let _ = events.send(MasterEvent::log(format!(
+                    "[WARN] notify_cmd failed: {}; later failures are not logged",
+                    e
+                )));
+            }
+        });
+    }
+}
+
+/// `message` reduced to characters no shell gives a meaning to, for
+/// pasting into `notify_cmd`. The untouched text is in `TIX_MESSAGE`.
+fn shell_safe(message: &str) -> String {
+    message
+        .chars()
+        .filter(|c| c.is_alphanumeric() || " .,:-_/+@#".contains(*c))
+        .collect()
+}
+
+/// Run `command` through the platform shell and wait for it.
+fn run_command(command: &str, message: &str) -> Result<(), String> {
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = std::process::Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    };
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    let status = cmd
+        .env("TIX_MESSAGE", message)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map_err(|e| format!("{}: {}", command, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{}: {}", command, status))
+    }
+}
+
+/// Draw `banner` across `area`, a single row.
+pub fn render_banner(banner: &Banner, theme: &Theme, area: Rect, buf: &mut Buffer) {
+    let style = if banner.trigger.is_failure() {
+        theme.danger
+    } else {
+        theme.success
+    };
+    // Reversed, so the row stands out on any palette.
+    let style = style.add_modifier(Modifier::REVERSED | Modifier::BOLD);
+    let text = format!(" {} ", banner.text);
+    Paragraph::new(Line::from(text))
+        .style(style)
+        .render(area, buf);
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::localops::CopyProgress;
+
+    fn task(id: u64, status: &str) -> MasterEvent {
+        MasterEvent::TaskUpdate {
+            id,
+            status: status.to_string(),
+        }
+    }
+
+    fn everything() -> NotifyConfig {
+        NotifyConfig {
+            task_finished: true,
+            slave_connected: true,
+            bell: false,
+            ..NotifyConfig::default()
+        }
+    }
+
+    #[test]
+    fn events_are_classified() {
+        let alert = |event| classify(&event).map(|a| (a.trigger, a.message));
+        assert_eq!(
+            alert(task(4, "Solved: 3 found")),
+            Some((Trigger::TaskFinished, "Task 4 solved".to_string()))
+        );
+        assert_eq!(
+            alert(task(5, "Failed")),
+            Some((Trigger::TaskFailed, "Task 5 failed".to_string()))
+        );
+        assert_eq!(
+            alert(task(6, "Timed out")),
+            Some((Trigger::TaskFailed, "Task 6 timed out".to_string()))
+        );
+        assert_eq!(
+            alert(task(6, "Panicked")),
+            Some((Trigger::TaskFailed, "Task 6 panicked on the slave".to_string()))
+        );
+        assert_eq!(alert(task(7, "Waiting...")), None);
+        assert_eq!(alert(task(7, "Running 4.2s")), None);
+        assert_eq!(
+            alert(MasterEvent::Response {
+                id: 8,
+                text: "Download complete".to_string()
+            }),
+            Some((
+                Trigger::TransferComplete,
+                "Download complete (ReqID 8)".to_string()
+            ))
+        );
+        assert_eq!(
+            alert(MasterEvent::Response {
+                id: 9,
+                text: "Pong".to_string()
+            }),
+            None
+        );
+        assert_eq!(
+            alert(MasterEvent::SlaveDisconnected("build-box".to_string())),
+            Some((
+                Trigger::SlaveDisconnected,
+                "build-box disconnected".to_string()
+            ))
+        );
+        assert_eq!(
+            alert(MasterEvent::SlaveConnected("10.0.0.7:50122".to_string())),
+            Some((
+                Trigger::SlaveConnected,
+                "Slave connected: 10.0.0.7:50122".to_string()
+            ))
+        );
+        assert_eq!(alert(MasterEvent::log("hello".to_string())), None);
+
+        let copy = |done, errors: Vec<String>| {
+            MasterEvent::LocalCopy(CopyProgress {
+                dest_dir: "backup".into(),
+                files: 3,
+                bytes: 2048,
+                done,
+                errors,
+            })
+        };
+        assert_eq!(alert(copy(false, Vec::new())), None);
+        assert_eq!(
+            alert(copy(true, Vec::new())),
+            Some((
+                Trigger::TransferComplete,
+                "Copied 3 files (2.0 KiB) to backup".to_string()
+            ))
+        );
+        assert_eq!(
+            alert(copy(true, vec!["a: denied".to_string()])).map(|a| a.0),
+            Some(Trigger::TaskFailed)
+        );
+    }
+
+    #[test]
+    fn disabled_triggers_stay_quiet() {
+        let mut notifier = Notifier::new(NotifyConfig::default());
+        let now = Instant::now();
+        assert_eq!(notifier.notify(&task(1, "Solved"), now), None);
+        assert!(notifier.banner_at(now).is_none());
+        let failed = notifier.notify(&task(2, "Failed"), now).unwrap();
+        assert_eq!(failed.message, "Task 2 failed");
+        assert!(failed.bell);
+        assert_eq!(failed.command, None);
+    }
+
+    #[test]
+    fn a_flapping_slave_is_rate_limited() {
+        let mut notifier = Notifier::new(everything());
+        let t0 = Instant::now();
+        let drop = MasterEvent::SlaveDisconnected("laptop".to_string());
+        assert!(notifier.notify(&drop, t0).is_some());
+        for i in 1..=3 {
+            assert_eq!(notifier.notify(&drop, t0 + Duration::from_secs(i)), None);
+        }
+        // Other kinds have their own budget.
+        assert!(notifier.notify(&task(3, "Failed"), t0).is_some());
+
+        let later = t0 + Duration::from_secs(DEFAULT_MIN_INTERVAL_SECS);
+        let next = notifier.notify(&drop, later).unwrap();
+        assert_eq!(next.message, "laptop disconnected (+3 more)");
+        assert_eq!(notifier.notify(&drop, later), None);
+    }
+
+    #[test]
+    fn the_command_gets_a_shell_safe_message() {
+        let mut notifier = Notifier::new(NotifyConfig {
+            notify_cmd: Some("toast --text '{message}'".to_string()),
+            ..everything()
+        });
+        let event = MasterEvent::SlaveDisconnected("x'; rm -rf ~ #`$(id)`".to_string());
+        let notification = notifier.notify(&event, Instant::now()).unwrap();
+        assert_eq!(
+            notification.command.as_deref(),
+            Some("toast --text 'x rm -rf  #id disconnected'")
+        );
+        assert_eq!(notification.message, "x'; rm -rf ~ #`$(id)` disconnected");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn the_command_sees_the_raw_message() {
+        assert_eq!(
+            run_command("test \"$TIX_MESSAGE\" = 'a \"b\"'", "a \"b\""),
+            Ok(())
+        );
+        assert!(run_command("exit 3", "").unwrap_err().contains("3"));
+    }
+
+    #[test]
+    fn the_banner_is_drawn_until_it_expires() {
+        let mut notifier = Notifier::new(everything());
+        let t0 = Instant::now();
+        notifier.notify(&task(12, "Failed"), t0).unwrap();
+        let banner = notifier.banner_at(t0).unwrap().clone();
+
+        let theme = Theme::default();
+        let area = Rect::new(0, 0, 30, 1);
+        let mut buf = Buffer::empty(area);
+        render_banner(&banner, &theme, area, &mut buf);
+        let row: String = buf.content.iter().map(|cell| cell.symbol()).collect();
+        assert_eq!(row, " Task 12 failed               ");
+        let cell = &buf[(29, 0)];
+        assert_eq!(cell.fg, theme.danger.fg.unwrap());
+        assert!(cell.modifier.contains(Modifier::REVERSED));
+
+        notifier.notify(&task(13, "Solved"), t0).unwrap();
+        let banner = notifier.banner_at(t0).unwrap().clone();
+        render_banner(&banner, &theme, area, &mut buf);
+        assert_eq!(buf[(1, 0)].fg, theme.success.fg.unwrap());
+
+        let gone = t0 + Duration::from_secs(DEFAULT_BANNER_SECS);
+        assert!(notifier.banner_at(gone).is_none());
+    }
+}
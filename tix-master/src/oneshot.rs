@@ -0,0 +1,375 @@
+//! Non-interactive `exec`, `upload` and `download`.
+//!
+//! Each runs a single operation against one slave and reports it as a
+//! [`Report`] plus a process exit code, for CI jobs and scripts that
+//! have no use for the console. `exec` takes the same command strings
+//! as the console and goes through the same [`Master`]; the
+//! transfers use the chunked, hash-verified path of
+//! [`TixClient`](tix_core::client::TixClient).
+//!
+//! Destructive commands (see [`crate::commands`]) are refused before
+//! anything is sent unless `exec` was told `yes`: there is nobody to
+//! ask. So are uploads over the `[paste_guard]` confirmation size, with
+//! `exec` and `upload` alike, and those over its hard limit even then.
+//!
+//! By default the master listens on the target address and waits for
+//! the slave to dial in, as the console does. With `connect` it dials a
+//! slave listening at the target instead.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tix_core::client::{TixClient, TransferSummary};
+use tix_core::format::{format_bytes, format_rate};
+use tix_core::paste_guard::{self, PasteGuard, Verdict};
+use tix_core::{Connection, ConnectionInfo, TixError};
+use tokio::sync::mpsc;
+
+use crate::app::MasterEvent;
+use crate::commands::ConfirmGate;
+use crate::master::Master;
+use crate::roles::Roles;
+use crate::watch::parse_duration;
+
+/// The operation succeeded.
+pub const EXIT_OK: i32 = 0;
+/// The slave reported an error, or a shell command exited non-zero.
+pub const EXIT_FAILED: i32 = 1;
+/// The command could not be parsed or is console-only.
+pub const EXIT_USAGE: i32 = 2;
+/// No slave, or no answer, within the timeout.
+pub const EXIT_TIMEOUT: i32 = 3;
+/// The connection could not be set up or was lost.
+pub const EXIT_DISCONNECTED: i32 = 4;
+
+/// Console commands that keep running or report over several events;
+/// they have no single reply to wait for.
+const CONSOLE_ONLY: &[&str] = &[
+    "ping",
+    "watch",
+    "unwatch",
+    "update",
+    "find",
+    "stats",
+    "save-output",
+    "transfers",
+    "transfer",
+    "invalidate",
+];
+
+// ── Target ───────────────────────────────────────────────────────
+
+/// Where to find the slave.
+#[derive(Debug, Clone)]
+pub struct Target {
+    /// Address to listen on, or the slave's address with `connect`.
+    pub addr: ConnectionInfo,
+    /// Dial the slave instead of waiting for it.
+    pub connect: bool,
+}
+
+impl Target {
+    /// Parse `host:port`.
+    pub fn parse(text: &str, connect: bool) -> Result<Self, String> {
+        let (host, port) = text
+            .rsplit_once(':')
+            .ok_or_else(|| format!("'{}' is not host:port", text))?;
+        let port = port
+            .parse()
+            .map_err(|_| format!("'{}' is not a port number", port))?;
+        Ok(Self {
+            addr: ConnectionInfo::new(host.trim_matches(['[', ']']).to_string(), port),
+            connect,
+        })
+    }
+}
+
+/// `--timeout` values: `500ms`, `30s`, `5m` or plain seconds.
+pub fn parse_timeout(text: &str) -> Result<Duration, String> {
+    parse_duration(text)
+}
+
+// ── Report ───────────────────────────────────────────────────────
+
+/// Outcome of a one-shot operation; `--json` prints it as is.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// The command string, or `upload`/`download`.
+    pub command: String,
+    /// ID of the request carrying the command, once it was sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<u64>,
+    /// Time from start to the final answer.
+    pub duration_ms: u64,
+    /// Process exit code (`EXIT_*`).
+    pub exit_code: i32,
+    /// The slave's reply, or what went wrong.
+    pub output: String,
+    /// File details for a completed transfer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer: Option<TransferInfo>,
+}
+
+/// A transfer's result in a [`Report`].
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferInfo {
+    pub path: String,
+    pub bytes: u64,
+    pub blake3: String,
+}
+
+impl Report {
+    fn new(command: impl Into<String>, start: Instant, exit_code: i32, output: String) -> Self {
+        Self {
+            command: command.into(),
+            request_id: None,
+            duration_ms: start.elapsed().as_millis() as u64,
+            exit_code,
+            output,
+            transfer: None,
+        }
+    }
+
+    /// Whether the operation succeeded.
+    pub fn ok(&self) -> bool {
+        self.exit_code == EXIT_OK
+    }
+
+    /// The report as one line of JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+// ── exec ─────────────────────────────────────────────────────────
+
+/// Run the console command `command` on the slave at `target` and wait
+/// for its reply, all within `timeout`. A destructive command only runs
+/// with `yes`, unless `gate` lets it through anyway; one `roles` does
+/// not allow never runs.
+pub async fn exec(
+    target: &Target,
+    command: &str,
+    timeout: Duration,
+    gate: ConfirmGate,
+    roles: Roles,
+    yes: bool,
+) -> Report {
+    let start = Instant::now();
+    let command = command.trim();
+    let fail = |code, output: String| Report::new(command, start, code, output);
+
+    let first = command.split_whitespace().next().unwrap_or_default();
+    if CONSOLE_ONLY.contains(&first) {
+        return fail(
+            EXIT_USAGE,
+            format!("'{}' is only available in the console", first),
+        );
+    }
+    let to_send = match gate.unattended(command, yes) {
+        Ok(to_send) => to_send,
+        Err(e) => return fail(EXIT_USAGE, e),
+    };
+
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let deadline = tokio::time::Instant::from_std(start + timeout);
+    let mut master = match open_master(target, tx, deadline).await {
+        Ok(master) => master.with_roles(roles),
+        Err((code, output)) => return fail(code, output),
+    };
+
+    if let Err(e) = master.execute_command(to_send).await {
+        let code = match e {
+            TixError::InvalidCommandSyntax(_) => EXIT_USAGE,
+            TixError::NotConnected => EXIT_DISCONNECTED,
+            TixError::NotPermitted { .. } | TixError::NotSupported { .. } => EXIT_FAILED,
+            _ if master.is_connected() => EXIT_USAGE,
+            _ => EXIT_DISCONNECTED,
+        };
+        return fail(code, e.to_string());
+    }
+    let Some(id) = std::iter::from_fn(|| events.try_recv().ok()).find_map(|event| match event {
+        MasterEvent::TaskUpdate { id, status } if status == "Waiting..." => Some(id),
+        _ => None,
+    }) else {
+        return fail(EXIT_USAGE, "nothing to send".to_string());
+    };
+
+    let mut reply = None;
+    let mut notes = Vec::new();
+    loop {
+        tokio::select! {
+            _ = master.process_connection() => {}
+            _ = tokio::time::sleep_until(deadline) => {
+                let mut report = fail(EXIT_TIMEOUT, format!("no reply within {:?}", timeout));
+                report.request_id = Some(id);
+                return report;
+            }
+        }
+        while let Ok(event) = events.try_recv() {
+            match event {
+                MasterEvent::Response { id: rid, text } if rid == id => reply = Some(text),
+                // Pongs and slave errors are only logged.
+                MasterEvent::Log(line)
+                    if line.starts_with("- Slave") || line.starts_with("Slave crash:") =>
+                {
+                    notes.push(line.to_string())
+                }
+                MasterEvent::TaskUpdate { id: rid, status } if rid == id => {
+                    let output = reply.take().unwrap_or_else(|| notes.join("\n"));
+                    let code = match status.as_str() {
+                        "Solved" if shell_failed(command, &output) => EXIT_FAILED,
+                        "Solved" => EXIT_OK,
+                        "Timed out" => EXIT_TIMEOUT,
+                        _ => EXIT_FAILED,
+                    };
+                    let mut report = fail(code, output);
+                    report.request_id = Some(id);
+                    return report;
+                }
+                _ => {}
+            }
+        }
+        if !master.is_connected() {
+            let mut report = fail(EXIT_DISCONNECTED, "slave disconnected".to_string());
+            report.request_id = Some(id);
+            return report;
+        }
+    }
+}
+
+/// Listen for or dial the slave, by `deadline`.
+async fn open_master(
+    target: &Target,
+    tx: mpsc::UnboundedSender<MasterEvent>,
+    deadline: tokio::time::Instant,
+) -> Result<Master, (i32, String)> {
+    let addr = &target.addr;
+    if target.connect {
+        return match tokio::time::timeout_at(deadline, Master::connect(addr.clone(), tx)).await {
+            Ok(Ok(master)) => Ok(master),
+            Ok(Err(e)) => Err((EXIT_DISCONNECTED, format!("{}: {}", addr, e))),
+            Err(_) => Err((EXIT_TIMEOUT, format!("{}: connect timed out", addr))),
+        };
+    }
+
+    let mut master = Master::listen(addr.clone(), tx)
+        .await
+        .map_err(|e| (EXIT_DISCONNECTED, format!("listen on {}: {}", addr, e)))?;
+    match tokio::time::timeout_at(deadline, master.accept_one()).await {
+        Ok(Ok(())) => Ok(master),
+        Ok(Err(e)) => Err((EXIT_DISCONNECTED, format!("accept: {}", e))),
+        Err(_) => Err((EXIT_TIMEOUT, format!("no slave connected to {}", addr))),
+    }
+}
+
+/// A `ShellExecute` reply whose `Exit Code:` line is not 0.
+fn shell_failed(command: &str, reply: &str) -> bool {
+    command.starts_with("ShellExecute")
+        && reply
+            .rsplit_once("Exit Code: ")
+            .is_some_and(|(_, code)| code.trim() != "0")
+}
+
+// ── upload / download ────────────────────────────────────────────
+
+/// Copy `local` to the slave as `remote`.
+pub async fn upload(
+    target: &Target,
+    local: &Path,
+    remote: &str,
+    timeout: Duration,
+    guard: PasteGuard,
+    yes: bool,
+) -> Report {
+    let start = Instant::now();
+    let name = local.display().to_string();
+    let refusal = match guard.check(&name, paste_guard::path_size(local), None) {
+        Verdict::Refuse(reason) => Some(reason),
+        Verdict::Confirm(_) if !yes => Some(format!(
+            "{} is larger than paste_guard.confirm_bytes and nobody is here to confirm it; \
+             pass --yes to send it",
+            name
+        )),
+        _ => None,
+    };
+    if let Some(reason) = refusal {
+        return Report::new("upload", start, EXIT_USAGE, reason);
+    }
+    let result = async {
+        let client = open_client(target, timeout).await?;
+        client.upload(local, remote).await.map_err(classify)
+    }
+    .await;
+    transfer_report("upload", start, result)
+}
+
+/// Copy the slave's `remote` file to `local`.
+pub async fn download(target: &Target, remote: &str, local: &Path, timeout: Duration) -> Report {
+    let start = Instant::now();
+    let result = async {
+        let client = open_client(target, timeout).await?;
+        client.download(remote, local).await.map_err(classify)
+    }
+    .await;
+    transfer_report("download", start, result)
+}
+
+/// A client for the slave at `target`. `timeout` bounds the wait for
+/// the slave and, once connected, every pause in the transfer.
+async fn open_client(target: &Target, timeout: Duration) -> Result<TixClient, (i32, String)> {
+    let connecting = async {
+        if target.connect {
+            Connection::connect(&target.addr)
+                .await
+                .map(TixClient::new)
+                .map_err(TixError::from)
+        } else {
+            TixClient::listen(&target.addr).await
+        }
+    };
+    match tokio::time::timeout(timeout, connecting).await {
+        Ok(Ok(client)) => Ok(client.with_timeout(timeout)),
+        Ok(Err(e)) => Err((EXIT_DISCONNECTED, format!("{}: {}", target.addr, e))),
+        Err(_) => Err((
+            EXIT_TIMEOUT,
+            format!("no slave at {} within {:?}", target.addr, timeout),
+        )),
+    }
+}
+
+fn transfer_report(
+    command: &str,
+    start: Instant,
+    result: Result<TransferSummary, (i32, String)>,
+) -> Report {
+    match result {
+        Ok(summary) => {
+            let output = format!(
+                "{} ({}) -> {}",
+                format_bytes(summary.bytes),
+                format_rate(summary.bytes, start.elapsed()),
+                summary.path
+            );
+            let mut report = Report::new(command, start, EXIT_OK, output);
+            report.transfer = Some(TransferInfo {
+                path: summary.path,
+                bytes: summary.bytes,
+                blake3: summary.hash.iter().map(|b| format!("{:02x}", b)).collect(),
+            });
+            report
+        }
+        Err((code, output)) => Report::new(command, start, code, output),
+    }
+}
+
+/// Exit code and message for a failed transfer.
+fn classify(e: TixError) -> (i32, String) {
+    let code = match e {
+        TixError::Timeout(_) => EXIT_TIMEOUT,
+        TixError::ChannelClosed | TixError::Connection(_) => EXIT_DISCONNECTED,
+        _ => EXIT_FAILED,
+    };
+    (code, e.to_string())
+}
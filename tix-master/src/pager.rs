@@ -0,0 +1,871 @@
+//! Bounded console log and a pager for large slave responses.
+//!
+//! A response longer than the paging threshold is kept out of the log:
+//! the log gets one collapsed line pointing at it, and the text opens
+//! in a full-screen [`Pager`] with its own scroll position. The log
+//! itself is a ring buffer of timestamped [`LogEntry`]s, so only the
+//! newest survive a long session. Both only ever touch the lines on
+//! screen when drawn.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Deref;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+
+use crate::retention::Transcript;
+use crate::theme::Theme;
+
+/// Responses with more lines than this are paged.
+pub const DEFAULT_PAGE_THRESHOLD: usize = 500;
+
+/// Console log entries kept before the oldest are dropped.
+pub const DEFAULT_LOG_CAPACITY: usize = 10_000;
+
+/// How late an entry may arrive and still be put in timestamp order.
+pub const REORDER_WINDOW: Duration = Duration::from_millis(200);
+
+/// Columns the `HH:MM:SS.mmm ` prefix takes.
+pub const CLOCK_WIDTH: usize = 13;
+
+/// Indent of an entry's later lines, under the text of its first.
+pub const CONTINUATION_INDENT: &str = "  ";
+
+/// Paged responses kept; older ones are dropped with their text.
+pub const MAX_PAGES: usize = 32;
+
+const PAGE_HINT: &str = "press Enter to view";
+
+// ── LogEntry ─────────────────────────────────────────────────────
+
+/// One console log message, stamped where it was produced.
+///
+/// A message of several lines stays one entry: it is drawn as a block,
+/// its later lines indented under the first, and nothing else is ever
+/// put between them.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// Orders entries; immune to the wall clock being set.
+    at: Instant,
+    /// What the timestamp prefix shows.
+    wall: SystemTime,
+    text: String,
+}
+
+impl LogEntry {
+    /// `text`, stamped now.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self::stamped(Instant::now(), SystemTime::now(), text)
+    }
+
+    /// `text`, stamped at a given moment.
+    pub fn stamped(at: Instant, wall: SystemTime, text: impl Into<String>) -> Self {
+        Self {
+            at,
+            wall,
+            text: text.into(),
+        }
+    }
+
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn wall(&self) -> SystemTime {
+        self.wall
+    }
+
+    /// Screen rows the entry takes, one per line of text.
+    pub fn rows(&self) -> usize {
+        self.text.lines().count().max(1)
+    }
+
+    /// `HH:MM:SS.mmm` of the moment it was stamped, in UTC.
+    pub fn clock(&self) -> String {
+        let since = self.wall.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since.as_secs();
+        format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            secs / 3600 % 24,
+            secs / 60 % 60,
+            secs % 60,
+            since.subsec_millis()
+        )
+    }
+}
+
+impl Deref for LogEntry {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl PartialEq<str> for LogEntry {
+    fn eq(&self, other: &str) -> bool {
+        self.text == other
+    }
+}
+
+impl PartialEq<&str> for LogEntry {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
+impl From<String> for LogEntry {
+    fn from(text: String) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<&str> for LogEntry {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+/// One screen row of a [`LogEntry`].
+#[derive(Debug, Clone, Copy)]
+pub struct LogRow<'a> {
+    pub entry: &'a LogEntry,
+    pub line: &'a str,
+    /// Whether this is the entry's first line.
+    pub first: bool,
+}
+
+impl LogRow<'_> {
+    /// What goes in front of the text: the timestamp on a first line
+    /// when `show_time` is set, blanks of the same width under it, and
+    /// [`CONTINUATION_INDENT`] on every later line.
+    pub fn prefix(&self, show_time: bool) -> String {
+        let clock = match (show_time, self.first) {
+            (false, _) => String::new(),
+            (true, true) => format!("{} ", self.entry.clock()),
+            (true, false) => " ".repeat(CLOCK_WIDTH),
+        };
+        if self.first {
+            clock
+        } else {
+            clock + CONTINUATION_INDENT
+        }
+    }
+
+    /// Columns left for the text in a pane `width` columns wide.
+    pub fn text_width(&self, width: usize, show_time: bool) -> usize {
+        width.saturating_sub(self.prefix(show_time).chars().count())
+    }
+}
+
+// ── LogBuffer ────────────────────────────────────────────────────
+
+/// Console log entries in timestamp order, holding at most `capacity`.
+///
+/// Entries come from the UI thread and the network task over separate
+/// paths, so one can arrive after a later one. [`push`](Self::push)
+/// puts it back in order when it is no more than [`REORDER_WINDOW`]
+/// late; anything later than that is appended where it arrived rather
+/// than slipped in above lines already read.
+#[derive(Debug)]
+pub struct LogBuffer {
+    entries: VecDeque<LogEntry>,
+    /// Total rows of `entries`.
+    rows: usize,
+    /// Rows ever pushed, dropped ones included.
+    added: usize,
+    capacity: usize,
+    /// Where every entry is also written, if anywhere.
+    transcript: Option<Transcript>,
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOG_CAPACITY)
+    }
+}
+
+impl LogBuffer {
+    /// An empty log keeping the newest `capacity` entries (at least one).
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            rows: 0,
+            added: 0,
+            capacity: capacity.max(1),
+            transcript: None,
+        }
+    }
+
+    /// Write every entry pushed from now on to `transcript` as well.
+    pub fn set_transcript(&mut self, transcript: Transcript) {
+        self.transcript = Some(transcript);
+    }
+
+    /// Add an entry in timestamp order, dropping the oldest one when
+    /// full.
+    pub fn push(&mut self, entry: impl Into<LogEntry>) {
+        let entry = entry.into();
+        if let Some(transcript) = &mut self.transcript
+            && let Err(e) = transcript.record(&entry)
+        {
+            let path = transcript.path().display().to_string();
+            self.transcript = None;
+            self.push(format!("[DATA] Transcript {} stopped: {}", path, e));
+        }
+        if self.entries.len() == self.capacity
+            && let Some(oldest) = self.entries.pop_front()
+        {
+            self.rows -= oldest.rows();
+        }
+        let mut index = self.entries.len();
+        while index > 0 {
+            let before = &self.entries[index - 1];
+            if before.at <= entry.at || before.at - entry.at > REORDER_WINDOW {
+                break;
+            }
+            index -= 1;
+        }
+        let rows = entry.rows();
+        self.rows += rows;
+        self.added = self.added.wrapping_add(rows);
+        self.entries.insert(index, entry);
+    }
+
+    /// Change the capacity, dropping the oldest entries beyond it.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        let excess = self.entries.len().saturating_sub(self.capacity);
+        for entry in self.entries.drain(..excess) {
+            self.rows -= entry.rows();
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Entries kept.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Screen rows the entries take.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Rows pushed since the log was made, counting dropped ones; the
+    /// difference between two readings is what arrived in between.
+    pub fn rows_added(&self) -> usize {
+        self.added
+    }
+
+    /// Text of entry `index`, counting from the oldest kept.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(LogEntry::text)
+    }
+
+    /// The `height` rows on screen when scrolled `scroll` rows up from
+    /// the bottom, oldest first. Scrolling stops at the top row.
+    pub fn visible(&self, scroll: usize, height: usize) -> Vec<LogRow<'_>> {
+        let scroll = scroll.min(self.rows.saturating_sub(height));
+        let mut rows: Vec<LogRow> = self
+            .entries
+            .iter()
+            .rev()
+            .flat_map(|entry| {
+                let lines: Vec<&str> = match entry.text.lines().count() {
+                    0 => vec![""],
+                    _ => entry.text.lines().collect(),
+                };
+                lines
+                    .into_iter()
+                    .enumerate()
+                    .rev()
+                    .map(move |(i, line)| LogRow {
+                        entry,
+                        line,
+                        first: i == 0,
+                    })
+            })
+            .skip(scroll)
+            .take(height)
+            .collect();
+        rows.reverse();
+        rows
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &LogEntry> {
+        self.entries.iter()
+    }
+}
+
+// ── Page ─────────────────────────────────────────────────────────
+
+/// A large response kept out of the console log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub request_id: u64,
+    pub lines: Vec<String>,
+}
+
+impl Page {
+    pub fn new(request_id: u64, text: &str) -> Self {
+        Self {
+            request_id,
+            lines: text.lines().map(str::to_string).collect(),
+        }
+    }
+
+    /// The log line standing in for this response.
+    pub fn collapsed_line(&self) -> String {
+        format!(
+            "[ReqID {}] {} lines — {}",
+            self.request_id,
+            group_thousands(self.lines.len()),
+            PAGE_HINT
+        )
+    }
+}
+
+/// The request ID a collapsed log line points at.
+pub fn page_reference(line: &str) -> Option<u64> {
+    if !line.ends_with(PAGE_HINT) {
+        return None;
+    }
+    let rest = line.strip_prefix("[ReqID ")?;
+    let (id, _) = rest.split_once(']')?;
+    id.parse().ok()
+}
+
+/// `14203` → `"14,203"`.
+pub fn group_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// ── Pager ────────────────────────────────────────────────────────
+
+/// What the pager's bottom line is collecting.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PagerPrompt {
+    None,
+    /// `/` was pressed; typing a search term.
+    Search(String),
+    /// `:` was pressed; typing a line number.
+    Jump(String),
+}
+
+/// Full-screen viewer for one [`Page`].
+#[derive(Debug)]
+pub struct Pager {
+    page: Page,
+    /// Index of the first line on screen.
+    top: usize,
+    /// Lines that fit on screen, updated on every draw.
+    height: usize,
+    query: Option<String>,
+    prompt: PagerPrompt,
+    status: Option<String>,
+}
+
+impl Pager {
+    pub fn new(page: Page) -> Self {
+        Self {
+            page,
+            top: 0,
+            height: 1,
+            query: None,
+            prompt: PagerPrompt::None,
+            status: None,
+        }
+    }
+
+    pub fn request_id(&self) -> u64 {
+        self.page.request_id
+    }
+
+    /// First line on screen (0-based).
+    pub fn top(&self) -> usize {
+        self.top
+    }
+
+    pub fn prompt(&self) -> &PagerPrompt {
+        &self.prompt
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    /// Set how many lines fit on screen and keep the view in range.
+    pub fn set_height(&mut self, height: usize) {
+        self.height = height.max(1);
+        self.top = self.top.min(self.max_top());
+    }
+
+    fn max_top(&self) -> usize {
+        self.page.lines.len().saturating_sub(self.height)
+    }
+
+    pub fn scroll_down(&mut self, n: usize) {
+        self.top = self.top.saturating_add(n).min(self.max_top());
+    }
+
+    pub fn scroll_up(&mut self, n: usize) {
+        self.top = self.top.saturating_sub(n);
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_down(self.height.saturating_sub(1).max(1));
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_up(self.height.saturating_sub(1).max(1));
+    }
+
+    pub fn home(&mut self) {
+        self.top = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.top = self.max_top();
+    }
+
+    /// Show 1-based line `line` at the top (clamped to the last page).
+    pub fn jump_to(&mut self, line: usize) {
+        self.top = line.saturating_sub(1).min(self.max_top());
+    }
+
+    /// How far through the text the bottom of the screen is, 0-100.
+    pub fn percent(&self) -> u16 {
+        let total = self.page.lines.len();
+        if total == 0 {
+            return 100;
+        }
+        let bottom = (self.top + self.height).min(total);
+        (bottom * 100 / total) as u16
+    }
+
+    /// Search for `query` (case-insensitive) and remember it for
+    /// [`search_next`](Self::search_next).
+    pub fn search(&mut self, query: &str) {
+        self.query = Some(query.to_lowercase());
+        self.find(self.top, true);
+    }
+
+    /// Next match below the top line, wrapping around.
+    pub fn search_next(&mut self) {
+        self.find(self.top + 1, true);
+    }
+
+    /// Previous match above the top line, wrapping around.
+    pub fn search_prev(&mut self) {
+        self.find(self.top + self.page.lines.len().saturating_sub(1), false);
+    }
+
+    /// Put the first line matching the query at or after (`forward`) or
+    /// at or before `from` at the top, wrapping at either end.
+    fn find(&mut self, from: usize, forward: bool) {
+        let Some(query) = self.query.clone().filter(|q| !q.is_empty()) else {
+            return;
+        };
+        let total = self.page.lines.len();
+        let hit = (0..total)
+            .map(|step| {
+                if forward {
+                    (from + step) % total
+                } else {
+                    (from + total - step % total) % total
+                }
+            })
+            .find(|&i| self.page.lines[i].to_lowercase().contains(&query));
+        match hit {
+            Some(line) => {
+                // Matches near the end scroll no further than the last
+                // page; the status line says which line matched.
+                self.top = line.min(self.max_top());
+                self.status = Some(format!("match at line {}", line + 1));
+            }
+            None => self.status = Some(format!("pattern not found: {query}")),
+        }
+    }
+
+    /// Handle a key. Returns `false` when the pager should close.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match &mut self.prompt {
+            PagerPrompt::Search(input) | PagerPrompt::Jump(input) => {
+                match key.code {
+                    KeyCode::Char(c) => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Esc => self.prompt = PagerPrompt::None,
+                    KeyCode::Enter => {
+                        let prompt = std::mem::replace(&mut self.prompt, PagerPrompt::None);
+                        match prompt {
+                            PagerPrompt::Search(q) if !q.is_empty() => self.search(&q),
+                            PagerPrompt::Jump(n) => match n.trim().parse::<usize>() {
+                                Ok(line) => self.jump_to(line),
+                                Err(_) => self.status = Some(format!("not a line number: {n}")),
+                            },
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+                return true;
+            }
+            PagerPrompt::None => {}
+        }
+
+        self.status = None;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_up(1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_down(1),
+            KeyCode::PageUp | KeyCode::Char('b') => self.page_up(),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.page_down(),
+            KeyCode::Home | KeyCode::Char('g') => self.home(),
+            KeyCode::End | KeyCode::Char('G') => self.end(),
+            KeyCode::Char('/') => self.prompt = PagerPrompt::Search(String::new()),
+            KeyCode::Char(':') => self.prompt = PagerPrompt::Jump(String::new()),
+            KeyCode::Char('n') => self.search_next(),
+            KeyCode::Char('N') => self.search_prev(),
+            _ => {}
+        }
+        true
+    }
+
+    /// Draw over `area` in `theme`. Only the lines on screen are styled,
+    /// so the cost does not depend on the response size.
+    pub fn render(&mut self, area: Rect, buf: &mut Buffer, theme: &Theme) {
+        Clear.render(area, buf);
+        let block = Block::bordered()
+            .title(Line::from(vec![
+                Span::styled(format!(" ReqID {} ", self.page.request_id), theme.heading),
+                Span::styled(
+                    format!("{} lines ", group_thousands(self.page.lines.len())),
+                    theme.muted,
+                ),
+            ]))
+            .border_set(theme.border_set)
+            .border_style(theme.border);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(inner);
+        self.set_height(layout[0].height as usize);
+
+        let query = self.query.as_deref().unwrap_or("");
+        let end = (self.top + self.height).min(self.page.lines.len());
+        let lines: Vec<Line> = self.page.lines[self.top..end]
+            .iter()
+            .map(|line| {
+                if !query.is_empty() && line.to_lowercase().contains(query) {
+                    Line::styled(line.as_str(), theme.matched)
+                } else {
+                    Line::raw(line.as_str())
+                }
+            })
+            .collect();
+        Paragraph::new(lines).render(layout[0], buf);
+
+        let status = match &self.prompt {
+            PagerPrompt::Search(input) => format!("/{input}"),
+            PagerPrompt::Jump(input) => format!(":{input}"),
+            PagerPrompt::None => format!(
+                "line {}  {}%  {}",
+                self.top + 1,
+                self.percent(),
+                self.status
+                    .as_deref()
+                    .unwrap_or("/ search  n/N next/prev  : line  q close")
+            ),
+        };
+        Paragraph::new(Line::styled(status, theme.focus)).render(layout[1], buf);
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pager(lines: usize, height: usize) -> Pager {
+        let text: Vec<String> = (1..=lines).map(|i| format!("line {i}")).collect();
+        let mut pager = Pager::new(Page::new(7, &text.join("\n")));
+        pager.set_height(height);
+        pager
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::from(code)
+    }
+
+    /// Entry `text` stamped `ms` milliseconds after `base`.
+    fn entry(base: Instant, ms: u64, text: &str) -> LogEntry {
+        let late = Duration::from_millis(ms);
+        LogEntry::stamped(base + late, UNIX_EPOCH + late, text)
+    }
+
+    fn texts(log: &LogBuffer) -> Vec<&str> {
+        log.iter().map(LogEntry::text).collect()
+    }
+
+    #[test]
+    fn log_buffer_keeps_newest_entries() {
+        let mut log = LogBuffer::new(3);
+        for i in 0..5 {
+            log.push(format!("{i}"));
+        }
+        assert_eq!(log.len(), 3);
+        assert_eq!(texts(&log), ["2", "3", "4"]);
+
+        log.set_capacity(2);
+        assert_eq!(texts(&log), ["3", "4"]);
+        assert_eq!(log.rows(), 2);
+        log.set_capacity(0);
+        assert_eq!(log.capacity(), 1);
+        assert_eq!(log.get(0), Some("4"));
+    }
+
+    #[test]
+    fn multi_line_entries_stay_together() {
+        let base = Instant::now();
+        let mut log = LogBuffer::new(10);
+        log.push(entry(base, 0, "- Slave: a\nb\nc"));
+        assert_eq!(log.rows_added(), 3);
+        log.push(entry(base, 5, "[CONN] ping"));
+        // Stamped between the response and the ping, it goes after the
+        // whole response, never between its lines.
+        log.push(entry(base, 2, "late"));
+        assert_eq!(texts(&log), ["- Slave: a\nb\nc", "late", "[CONN] ping"]);
+        assert_eq!((log.len(), log.rows()), (3, 5));
+
+        let rows = log.visible(0, 10);
+        let lines: Vec<(&str, bool)> = rows.iter().map(|row| (row.line, row.first)).collect();
+        assert_eq!(
+            lines,
+            [
+                ("- Slave: a", true),
+                ("b", false),
+                ("c", false),
+                ("late", true),
+                ("[CONN] ping", true),
+            ]
+        );
+
+        // Dropping the entry drops all of its rows.
+        log.set_capacity(2);
+        assert_eq!(log.rows(), 2);
+    }
+
+    #[test]
+    fn late_entries_are_put_in_order_within_the_window() {
+        let base = Instant::now();
+        let mut log = LogBuffer::new(10);
+        log.push(entry(base, 0, "a"));
+        log.push(entry(base, 100, "c"));
+        log.push(entry(base, 250, "d"));
+        log.push(entry(base, 90, "b"));
+        // 160 ms behind "d" and 10 ms behind "c": slips in before both.
+        assert_eq!(texts(&log), ["a", "b", "c", "d"]);
+
+        // 300 ms behind the newest is too late to move up.
+        log.push(entry(base, 400, "e"));
+        log.push(entry(base, 100, "stale"));
+        assert_eq!(texts(&log), ["a", "b", "c", "d", "e", "stale"]);
+
+        // Equal stamps keep their arrival order.
+        log.push(entry(base, 400, "f"));
+        log.push(entry(base, 400, "g"));
+        assert_eq!(&texts(&log)[6..], ["f", "g"]);
+    }
+
+    #[test]
+    fn visible_rows_count_from_the_bottom() {
+        let base = Instant::now();
+        let mut log = LogBuffer::new(10);
+        log.push(entry(base, 0, "1"));
+        log.push(entry(base, 1, "2\n3\n4"));
+        log.push(entry(base, 2, "5"));
+        let lines = |scroll, height| -> Vec<String> {
+            log.visible(scroll, height)
+                .iter()
+                .map(|row| row.line.to_string())
+                .collect()
+        };
+        assert_eq!(lines(0, 2), ["4", "5"]);
+        // An entry cut by the top edge shows only its lower lines.
+        assert_eq!(lines(1, 2), ["3", "4"]);
+        // Scrolling stops with the first row at the top.
+        assert_eq!(lines(9, 2), ["1", "2"]);
+        assert_eq!(lines(0, 9), ["1", "2", "3", "4", "5"]);
+    }
+
+    #[test]
+    fn timestamps_and_indents_take_columns() {
+        let base = Instant::now();
+        let late = Duration::from_millis(45_296_789); // 12:34:56.789
+        let entry = LogEntry::stamped(base, UNIX_EPOCH + late, "first\nsecond");
+        assert_eq!(entry.clock(), "12:34:56.789");
+        let first = LogRow {
+            entry: &entry,
+            line: "first",
+            first: true,
+        };
+        let next = LogRow {
+            first: false,
+            line: "second",
+            ..first
+        };
+
+        assert_eq!(first.prefix(false), "");
+        assert_eq!(next.prefix(false), CONTINUATION_INDENT);
+        assert_eq!(first.prefix(true), "12:34:56.789 ");
+        assert_eq!(first.prefix(true).len(), CLOCK_WIDTH);
+        // Later lines line up under the first line's text, then indent.
+        assert_eq!(
+            next.prefix(true),
+            format!("{}{}", " ".repeat(CLOCK_WIDTH), CONTINUATION_INDENT)
+        );
+
+        assert_eq!(first.text_width(80, false), 80);
+        assert_eq!(first.text_width(80, true), 80 - CLOCK_WIDTH);
+        assert_eq!(next.text_width(80, true), 80 - CLOCK_WIDTH - 2);
+        assert_eq!(next.text_width(10, true), 0);
+    }
+
+    #[test]
+    fn collapsed_line_points_back_at_page() {
+        let page = Page::new(12, &"x\n".repeat(14_203));
+        let line = page.collapsed_line();
+        assert_eq!(line, "[ReqID 12] 14,203 lines — press Enter to view");
+        assert_eq!(page_reference(&line), Some(12));
+        assert_eq!(page_reference("[ReqID 12] done"), None);
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(1_000_000), "1,000,000");
+    }
+
+    #[test]
+    fn navigation_stays_in_range() {
+        let mut p = pager(100, 10);
+        p.scroll_up(5);
+        assert_eq!(p.top(), 0);
+        p.page_down();
+        assert_eq!(p.top(), 9);
+        p.end();
+        assert_eq!(p.top(), 90);
+        assert_eq!(p.percent(), 100);
+        p.scroll_down(50);
+        assert_eq!(p.top(), 90);
+        p.home();
+        assert_eq!(p.percent(), 10);
+
+        p.jump_to(42);
+        assert_eq!(p.top(), 41);
+        p.jump_to(1000);
+        assert_eq!(p.top(), 90);
+        p.jump_to(0);
+        assert_eq!(p.top(), 0);
+
+        // Shorter than the screen: nowhere to scroll.
+        let mut short = pager(3, 10);
+        short.page_down();
+        assert_eq!(short.top(), 0);
+        assert_eq!(short.percent(), 100);
+    }
+
+    #[test]
+    fn search_wraps_and_reports_misses() {
+        let mut p = pager(100, 10);
+        p.search("LINE 5");
+        assert_eq!(p.top(), 4); // "line 5"
+        p.search_next();
+        assert_eq!(p.top(), 49); // "line 50"
+        p.search_next();
+        assert_eq!(p.top(), 50); // "line 51"
+        p.search_prev();
+        assert_eq!(p.top(), 49);
+
+        p.jump_to(60);
+        p.search_next();
+        assert_eq!(p.top(), 4, "wraps to the start");
+
+        p.search("nope");
+        assert_eq!(p.top(), 4);
+        assert!(p.status().unwrap().contains("not found"));
+    }
+
+    #[test]
+    fn keys_drive_prompts() {
+        let mut p = pager(100, 10);
+        p.handle_key(key(KeyCode::Char(':')));
+        for c in "25".chars() {
+            p.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(p.prompt(), &PagerPrompt::Jump("25".into()));
+        p.handle_key(key(KeyCode::Enter));
+        assert_eq!(p.top(), 24);
+
+        p.handle_key(key(KeyCode::Char('/')));
+        p.handle_key(key(KeyCode::Char('9')));
+        p.handle_key(key(KeyCode::Char('9')));
+        p.handle_key(key(KeyCode::Enter));
+        assert_eq!(p.top(), 90, "line 99 is on the last page");
+        assert_eq!(p.status(), Some("match at line 99"));
+
+        // `q` inside a prompt is text, outside it closes.
+        p.handle_key(key(KeyCode::Char('/')));
+        assert!(p.handle_key(key(KeyCode::Char('q'))));
+        assert!(p.handle_key(key(KeyCode::Esc)));
+        assert!(!p.handle_key(key(KeyCode::Char('q'))));
+    }
+
+    #[test]
+    fn render_starts_at_top_line() {
+        let mut p = pager(200_000, 1);
+        p.jump_to(150_000);
+        let area = Rect::new(0, 0, 40, 12);
+        let mut buf = Buffer::empty(area);
+        p.render(area, &mut buf, &Theme::default());
+        // Two border rows and the status line leave nine for text.
+        assert_eq!(p.height, 9);
+        let row: String = (1..39).map(|x| buf[(x, 1)].symbol().to_string()).collect();
+        assert!(row.starts_with("line 150000"), "{row}");
+    }
+}
@@ -0,0 +1,403 @@
+//! The command palette (`Ctrl+P`): one fuzzy-filtered list of console
+//! commands, the connected slave, recently visited slave folders and
+//! recently sent commands.
+//!
+//! Typing narrows the list with [`score`], a subsequence match that
+//! favours runs of consecutive letters and letters that start a word.
+//! `Enter` uses the selected entry: a command that takes no arguments
+//! is sent at once, one that does is put on the command line with its
+//! arguments shown as a placeholder, a folder opens in the tree
+//! explorer and the slave opens its details.
+
+use std::collections::VecDeque;
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use crate::commands::CommandSpec;
+
+/// Sent commands the palette offers again.
+pub const MAX_HISTORY: usize = 50;
+
+/// Visited slave folders the palette offers again.
+pub const MAX_RECENT_PATHS: usize = 20;
+
+/// Added for each matched letter that directly follows the previous
+/// match, times the length of the run so far.
+const RUN_BONUS: u32 = 4;
+
+/// Added for a matched letter at the start of a word.
+const WORD_START_BONUS: u32 = 3;
+
+// ── Entries ──────────────────────────────────────────────────────
+
+/// Where an entry comes from. Sources are listed in this order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Command,
+    Slave,
+    Path,
+    History,
+}
+
+impl Source {
+    /// Tag drawn in front of the entry.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Self::Command => "cmd",
+            Self::Slave => "slave",
+            Self::Path => "dir",
+            Self::History => "recent",
+        }
+    }
+}
+
+/// What choosing an entry does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteAction {
+    /// Send this command.
+    Run(String),
+    /// Put `text` on the command line, with `hint` drawn after it until
+    /// the user types.
+    Insert { text: String, hint: String },
+    /// Show this slave folder in the tree explorer.
+    Reveal(String),
+    /// Show the connected slave's details.
+    ShowSlave,
+}
+
+/// One line of the palette.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteEntry {
+    pub source: Source,
+    /// Text the query is matched against first.
+    pub label: String,
+    /// Drawn dimmed after the label; matched when the label is not.
+    pub detail: String,
+    pub action: PaletteAction,
+    /// Drawn dimmed throughout: the connected slave cannot run it.
+    pub unavailable: bool,
+}
+
+impl PaletteEntry {
+    pub fn command(spec: &CommandSpec) -> Self {
+        let action = if spec.args.is_empty() {
+            PaletteAction::Run(spec.name.to_string())
+        } else {
+            PaletteAction::Insert {
+                text: format!("{} ", spec.name),
+                hint: spec.args.to_string(),
+            }
+        };
+        let detail = if spec.args.is_empty() {
+            spec.help.to_string()
+        } else {
+            format!("{} - {}", spec.args, spec.help)
+        };
+        Self {
+            source: Source::Command,
+            label: spec.name.to_string(),
+            detail,
+            action,
+            unavailable: false,
+        }
+    }
+
+    pub fn slave(label: &str) -> Self {
+        Self {
+            source: Source::Slave,
+            label: label.to_string(),
+            detail: "connected - show details".to_string(),
+            action: PaletteAction::ShowSlave,
+            unavailable: false,
+        }
+    }
+
+    pub fn path(path: &str) -> Self {
+        Self {
+            source: Source::Path,
+            label: path.to_string(),
+            detail: "open in the tree explorer".to_string(),
+            action: PaletteAction::Reveal(path.to_string()),
+            unavailable: false,
+        }
+    }
+
+    pub fn history(command: &str) -> Self {
+        Self {
+            source: Source::History,
+            label: command.to_string(),
+            detail: String::new(),
+            action: PaletteAction::Insert {
+                text: command.to_string(),
+                hint: String::new(),
+            },
+            unavailable: false,
+        }
+    }
+
+    /// Dim the entry, with `note` saying why in place of its detail.
+    pub fn unavailable(mut self, note: String) -> Self {
+        self.detail = note;
+        self.unavailable = true;
+        self
+    }
+}
+
+/// Everything the palette lists, in source order: `commands`, the
+/// connected slave, then `paths` and `history`, newest first.
+pub fn gather<'a>(
+    commands: &[CommandSpec],
+    slave: Option<&str>,
+    paths: impl IntoIterator<Item = &'a String>,
+    history: impl IntoIterator<Item = &'a String>,
+) -> Vec<PaletteEntry> {
+    let mut entries: Vec<PaletteEntry> = commands.iter().map(PaletteEntry::command).collect();
+    entries.extend(slave.map(PaletteEntry::slave));
+    entries.extend(paths.into_iter().map(|p| PaletteEntry::path(p)));
+    entries.extend(history.into_iter().map(|c| PaletteEntry::history(c)));
+    entries
+}
+
+/// Move `item` to the front of `list`, dropping an older copy and
+/// anything past `cap`.
+pub fn remember(list: &mut VecDeque<String>, item: &str, cap: usize) {
+    list.retain(|old| old != item);
+    list.push_front(item.to_string());
+    list.truncate(cap);
+}
+
+// ── Matching ─────────────────────────────────────────────────────
+
+/// How well `query` matches `candidate`, ignoring case, or `None` when
+/// its letters do not all appear in `candidate` in order. Each letter
+/// is matched as early as it can be.
+pub fn score(query: &str, candidate: &str) -> Option<u32> {
+    let candidate: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut run = 0;
+    for wanted in query.chars().flat_map(char::to_lowercase) {
+        let found = next + candidate[next..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if found > 0 && found == next {
+            run += 1;
+            score += RUN_BONUS * run;
+        } else {
+            run = 0;
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += WORD_START_BONUS;
+        }
+        next = found + 1;
+    }
+    Some(score)
+}
+
+/// Indices of the `entries` matching `query`, best first. A label
+/// match counts double a detail match; ties go to the shorter label,
+/// then to the earlier entry. An empty query keeps every entry in
+/// order.
+pub fn rank(query: &str, entries: &[PaletteEntry]) -> Vec<usize> {
+    if query.is_empty() {
+        return (0..entries.len()).collect();
+    }
+    let mut ranked: Vec<(u32, usize, usize)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entry)| {
+            let score = score(query, &entry.label)
+                .map(|s| s * 2)
+                .or_else(|| score(query, &entry.detail))?;
+            Some((score, entry.label.chars().count(), i))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+    ranked.into_iter().map(|(_, _, i)| i).collect()
+}
+
+// ── Palette ──────────────────────────────────────────────────────
+
+/// An open palette: the query typed so far and what it matches.
+#[derive(Debug, Clone, Default)]
+pub struct Palette {
+    query: String,
+    entries: Vec<PaletteEntry>,
+    /// Indices into `entries`, best first.
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl Palette {
+    pub fn new(entries: Vec<PaletteEntry>) -> Self {
+        let mut palette = Self {
+            entries,
+            ..Self::default()
+        };
+        palette.refilter();
+        palette
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Matching entries, best first.
+    pub fn matches(&self) -> impl Iterator<Item = &PaletteEntry> {
+        self.matches.iter().map(|&i| &self.entries[i])
+    }
+
+    /// Position of the selection in [`Palette::matches`].
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected(&self) -> Option<&PaletteEntry> {
+        self.matches.get(self.selected).map(|&i| &self.entries[i])
+    }
+
+    /// Apply a key. Returns the chosen entry's action on `Enter`.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<PaletteAction> {
+        match key.code {
+            KeyCode::Enter => return self.selected().map(|entry| entry.action.clone()),
+            KeyCode::Up => self.select(self.selected.checked_sub(1)),
+            KeyCode::Down => self.select(Some(self.selected + 1)),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refilter();
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.refilter();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Select match `index`, wrapping past either end.
+    fn select(&mut self, index: Option<usize>) {
+        let len = self.matches.len();
+        if len > 0 {
+            self.selected = index.map_or(len - 1, |i| i % len);
+        }
+    }
+
+    fn refilter(&mut self) {
+        self.matches = rank(&self.query, &self.entries);
+        self.selected = 0;
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::COMMANDS;
+    use crossterm::event::KeyModifiers;
+
+    fn labels(palette: &Palette) -> Vec<&str> {
+        palette.matches().map(|e| e.label.as_str()).collect()
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn scores_reward_runs_and_word_starts() {
+        assert_eq!(score("", "anything"), Some(0));
+        assert_eq!(score("xyz", "services"), None);
+        assert_eq!(score("sv", "vs"), None);
+        // s(1+3) e(1+4) r(1+8)
+        assert_eq!(score("ser", "services"), Some(18));
+        // s(1+3), then a run begins mid-word: e(1) r(1+4)
+        assert_eq!(score("ser", "sysuser"), Some(10));
+        assert!(score("sr", "save-output").is_none());
+        // Case is ignored; a letter after a separator starts a word.
+        assert_eq!(score("Q", "reg query"), Some(4));
+        assert!(score("rq", "reg query") > score("rq", "regquery"));
+    }
+
+    #[test]
+    fn ranking_is_deterministic() {
+        let history = vec!["services spool*".to_string(), "ListDir C:\\".to_string()];
+        let paths = vec![r"C:\Users\bob\Documents".to_string()];
+        let entries = gather(COMMANDS, Some("PC-7"), &paths, &history);
+        assert_eq!(entries.len(), COMMANDS.len() + 4);
+
+        let mut palette = Palette::new(entries);
+        // Nothing typed: everything, in source order.
+        assert_eq!(palette.matches().count(), COMMANDS.len() + 4);
+        assert_eq!(palette.selected().unwrap().label, "Ping");
+
+        for c in "serv".chars() {
+            palette.handle_key(key(KeyCode::Char(c)));
+        }
+        // Label matches first, the tighter one ahead; then commands whose
+        // help happens to contain the letters.
+        let ranked = labels(&palette);
+        assert_eq!(ranked[..3], ["service", "services", "services spool*"]);
+        assert!(ranked[3..].contains(&"ShellExecute"), "{:?}", ranked);
+        assert!(!ranked.contains(&"ListDir C:\\"), "{:?}", ranked);
+
+        // Deleting letters widens the match again.
+        for _ in 0..4 {
+            palette.handle_key(key(KeyCode::Backspace));
+        }
+        assert_eq!(palette.matches().count(), COMMANDS.len() + 4);
+
+        let mut palette = Palette::new(gather(COMMANDS, Some("PC-7"), &paths, &history));
+        for c in "docs".chars() {
+            palette.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(labels(&palette)[0], r"C:\Users\bob\Documents");
+        assert_eq!(
+            palette.handle_key(key(KeyCode::Enter)),
+            Some(PaletteAction::Reveal(r"C:\Users\bob\Documents".to_string()))
+        );
+    }
+
+    #[test]
+    fn commands_run_or_wait_for_arguments() {
+        let mut palette = Palette::new(gather(COMMANDS, None, &[], &[]));
+        for c in "sysinfo".chars() {
+            palette.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(
+            palette.handle_key(key(KeyCode::Enter)),
+            Some(PaletteAction::Run("sysinfo".to_string()))
+        );
+
+        let mut palette = Palette::new(gather(COMMANDS, None, &[], &[]));
+        palette.handle_key(key(KeyCode::Char('n')));
+        palette.handle_key(key(KeyCode::Char('e')));
+        palette.handle_key(key(KeyCode::Char('t')));
+        assert_eq!(
+            palette.handle_key(key(KeyCode::Enter)),
+            Some(PaletteAction::Insert {
+                text: "net ".to_string(),
+                hint: "if | routes | resolve <name> | check <host:port>".to_string(),
+            })
+        );
+
+        // The selection wraps both ways and survives an empty result.
+        palette.handle_key(key(KeyCode::Up));
+        assert_eq!(palette.selected_index(), palette.matches().count() - 1);
+        palette.handle_key(key(KeyCode::Down));
+        assert_eq!(palette.selected_index(), 0);
+        palette.handle_key(key(KeyCode::Char('#')));
+        assert_eq!(palette.handle_key(key(KeyCode::Enter)), None);
+    }
+
+    #[test]
+    fn remembered_items_move_to_the_front() {
+        let mut list = VecDeque::new();
+        for item in ["a", "b", "c", "a"] {
+            remember(&mut list, item, 3);
+        }
+        assert_eq!(list, ["a", "c", "b"]);
+        remember(&mut list, "d", 3);
+        assert_eq!(list, ["d", "a", "c"]);
+    }
+}
@@ -0,0 +1,391 @@
+//! Round-trip statistics for `Ping`.
+//!
+//! [`PingStats`] keeps a rolling window of RTT samples plus lifetime
+//! sent/received/lost counters for the connected slave; the Slave PC
+//! panel shows its [`panel_line`](PingStats::panel_line). [`PingBurst`]
+//! drives the `ping -c N [-i ms]` console command and prints a summary
+//! in the style of the system `ping` tool once every probe has either
+//! been answered or timed out. [`rtt_line`] shows the round trip the
+//! connection's heartbeats measure, which needs no pings at all.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use tix_core::RttEstimator;
+
+/// RTT samples kept for the Slave PC panel.
+pub const PING_WINDOW: usize = 20;
+
+/// How long a ping may go unanswered before it counts as lost.
+pub const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default number of probes for `ping` without `-c`.
+const DEFAULT_COUNT: u32 = 4;
+
+/// Default and minimum spacing between probes.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Largest burst accepted by `-c`.
+const MAX_COUNT: u32 = 1000;
+
+// ── PingStats ────────────────────────────────────────────────────
+
+/// Rolling RTT window with loss accounting.
+#[derive(Debug, Clone)]
+pub struct PingStats {
+    samples: VecDeque<Duration>,
+    capacity: usize,
+    last: Option<Duration>,
+    received: u64,
+    lost: u64,
+}
+
+impl PingStats {
+    /// Statistics over the last `capacity` replies.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            last: None,
+            received: 0,
+            lost: 0,
+        }
+    }
+
+    /// Account for a reply that took `rtt`.
+    pub fn record_reply(&mut self, rtt: Duration) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt);
+        self.last = Some(rtt);
+        self.received += 1;
+    }
+
+    /// Account for a probe that timed out.
+    pub fn record_loss(&mut self) {
+        self.lost += 1;
+    }
+
+    /// Most recent RTT.
+    pub fn last(&self) -> Option<Duration> {
+        self.last
+    }
+
+    /// Fastest RTT in the window.
+    pub fn min(&self) -> Option<Duration> {
+        self.samples.iter().min().copied()
+    }
+
+    /// Slowest RTT in the window.
+    pub fn max(&self) -> Option<Duration> {
+        self.samples.iter().max().copied()
+    }
+
+    /// Mean RTT over the window.
+    pub fn avg(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.samples.iter().sum::<Duration>() / self.samples.len() as u32)
+    }
+
+    /// Replies received.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Probes that timed out.
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    /// Probes settled either way.
+    pub fn transmitted(&self) -> u64 {
+        self.received + self.lost
+    }
+
+    /// Share of settled probes that were lost, in percent.
+    pub fn loss_percent(&self) -> f64 {
+        match self.transmitted() {
+            0 => 0.0,
+            n => self.lost as f64 * 100.0 / n as f64,
+        }
+    }
+
+    /// One line for the Slave PC panel.
+    pub fn panel_line(&self) -> String {
+        let Some(last) = self.last else {
+            return match self.lost {
+                0 => "N/A".to_string(),
+                n => format!("unreachable ({} lost)", n),
+            };
+        };
+        let mut line = format!(
+            "{} ms ({}/{}/{})",
+            ms(last),
+            self.min().map(ms).unwrap_or_default(),
+            self.avg().map(ms).unwrap_or_default(),
+            self.max().map(ms).unwrap_or_default(),
+        );
+        if self.lost > 0 {
+            line.push_str(&format!(" {} lost", self.lost));
+        }
+        line
+    }
+
+    /// Multi-line summary in the style of the system `ping` tool.
+    pub fn summary(&self, host: &str) -> String {
+        let mut out = format!(
+            "--- {} ping statistics ---\n{} packets transmitted, {} received, {:.0}% packet loss",
+            host,
+            self.transmitted(),
+            self.received,
+            self.loss_percent()
+        );
+        if let (Some(min), Some(avg), Some(max)) = (self.min(), self.avg(), self.max()) {
+            out.push_str(&format!(
+                "\nrtt min/avg/max = {}/{}/{} ms",
+                ms(min),
+                ms(avg),
+                ms(max)
+            ));
+        }
+        out
+    }
+}
+
+impl Default for PingStats {
+    fn default() -> Self {
+        Self::new(PING_WINDOW)
+    }
+}
+
+/// The heartbeat round trip for the Slave PC panel: smoothed RTT and
+/// its variance, or `N/A` until the slave has echoed a heartbeat.
+pub fn rtt_line(rtt: &RttEstimator) -> String {
+    match rtt.srtt() {
+        Some(srtt) => format!("{} ms ±{}", ms(srtt), ms(rtt.rttvar())),
+        None => "N/A".to_string(),
+    }
+}
+
+/// Milliseconds with one decimal.
+fn ms(d: Duration) -> String {
+    format!("{:.1}", d.as_secs_f64() * 1000.0)
+}
+
+// ── Bursts ───────────────────────────────────────────────────────
+
+/// Parsed `ping [-c N] [-i ms]` arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingArgs {
+    pub count: u32,
+    pub interval: Duration,
+}
+
+impl PingArgs {
+    /// Parse the arguments following `ping`.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut out = Self {
+            count: DEFAULT_COUNT,
+            interval: DEFAULT_INTERVAL,
+        };
+        let mut it = args.iter();
+        while let Some(flag) = it.next() {
+            let value = |v: Option<&String>| -> Result<u64, String> {
+                v.ok_or_else(|| format!("{} needs a value", flag))?
+                    .parse()
+                    .map_err(|_| format!("{} needs a number", flag))
+            };
+            match flag.as_str() {
+                "-c" => {
+                    let count = value(it.next())?;
+                    if count == 0 || count > MAX_COUNT as u64 {
+                        return Err(format!("-c must be between 1 and {}", MAX_COUNT));
+                    }
+                    out.count = count as u32;
+                }
+                "-i" => {
+                    let interval = Duration::from_millis(value(it.next())?);
+                    if interval < MIN_INTERVAL {
+                        return Err(format!(
+                            "-i must be at least {} ms",
+                            MIN_INTERVAL.as_millis()
+                        ));
+                    }
+                    out.interval = interval;
+                }
+                other => return Err(format!("unknown ping option '{}'", other)),
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// A `ping -c N` run in progress.
+#[derive(Debug)]
+pub struct PingBurst {
+    args: PingArgs,
+    sent: u32,
+    next_at: Instant,
+    /// Request ID → sequence number of probes awaiting an answer.
+    outstanding: HashMap<u64, u32>,
+    stats: PingStats,
+}
+
+impl PingBurst {
+    /// A burst whose first probe is due at `now`.
+    pub fn new(args: PingArgs, now: Instant) -> Self {
+        Self {
+            args,
+            sent: 0,
+            next_at: now,
+            outstanding: HashMap::new(),
+            stats: PingStats::new(args.count as usize),
+        }
+    }
+
+    /// When the next probe should go out; `None` once all are sent.
+    pub fn next_due(&self) -> Option<Instant> {
+        (self.sent < self.args.count).then_some(self.next_at)
+    }
+
+    /// Record that a probe went out as `request_id`. Returns its
+    /// sequence number.
+    pub fn on_sent(&mut self, request_id: u64, now: Instant) -> u32 {
+        let seq = self.sent + 1;
+        self.sent = seq;
+        self.next_at = now + self.args.interval;
+        self.outstanding.insert(request_id, seq);
+        seq
+    }
+
+    /// Whether `request_id` is one of this burst's probes.
+    pub fn owns(&self, request_id: u64) -> bool {
+        self.outstanding.contains_key(&request_id)
+    }
+
+    /// Settle a probe with a reply; returns its sequence number.
+    pub fn on_reply(&mut self, request_id: u64, rtt: Duration) -> Option<u32> {
+        let seq = self.outstanding.remove(&request_id)?;
+        self.stats.record_reply(rtt);
+        Some(seq)
+    }
+
+    /// Settle a probe as lost; returns its sequence number.
+    pub fn on_loss(&mut self, request_id: u64) -> Option<u32> {
+        let seq = self.outstanding.remove(&request_id)?;
+        self.stats.record_loss();
+        Some(seq)
+    }
+
+    /// All probes sent and settled.
+    pub fn is_done(&self) -> bool {
+        self.sent == self.args.count && self.outstanding.is_empty()
+    }
+
+    /// Statistics for this burst only.
+    pub fn stats(&self) -> &PingStats {
+        &self.stats
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn window_keeps_latest_samples() {
+        let mut stats = PingStats::new(3);
+        for n in [50, 10, 20, 30] {
+            stats.record_reply(ms(n));
+        }
+        // The 50 ms sample has rolled out.
+        assert_eq!(stats.min(), Some(ms(10)));
+        assert_eq!(stats.max(), Some(ms(30)));
+        assert_eq!(stats.avg(), Some(ms(20)));
+        assert_eq!(stats.last(), Some(ms(30)));
+        assert_eq!(stats.received(), 4);
+    }
+
+    #[test]
+    fn loss_and_summary() {
+        let mut stats = PingStats::new(10);
+        assert_eq!(stats.panel_line(), "N/A");
+        stats.record_loss();
+        assert_eq!(stats.panel_line(), "unreachable (1 lost)");
+
+        stats.record_reply(ms(2));
+        stats.record_reply(ms(4));
+        stats.record_reply(ms(6));
+        assert_eq!(stats.loss_percent(), 25.0);
+        assert_eq!(stats.panel_line(), "6.0 ms (2.0/4.0/6.0) 1 lost");
+
+        let summary = stats.summary("10.0.0.5:4321");
+        assert!(summary.contains("4 packets transmitted, 3 received, 25% packet loss"));
+        assert!(summary.ends_with("rtt min/avg/max = 2.0/4.0/6.0 ms"));
+    }
+
+    #[test]
+    fn heartbeat_rtt_line() {
+        let mut rtt = RttEstimator::default();
+        assert_eq!(rtt_line(&rtt), "N/A");
+        rtt.observe(ms(30));
+        assert_eq!(rtt_line(&rtt), "30.0 ms ±15.0");
+    }
+
+    #[test]
+    fn parse_args() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        assert_eq!(
+            PingArgs::parse(&[]).unwrap(),
+            PingArgs {
+                count: 4,
+                interval: ms(1000)
+            }
+        );
+        assert_eq!(
+            PingArgs::parse(&args("-c 10 -i 250")).unwrap(),
+            PingArgs {
+                count: 10,
+                interval: ms(250)
+            }
+        );
+        assert!(PingArgs::parse(&args("-c 0")).is_err());
+        assert!(PingArgs::parse(&args("-i 5")).is_err());
+        assert!(PingArgs::parse(&args("-c")).is_err());
+        assert!(PingArgs::parse(&args("-x 1")).is_err());
+    }
+
+    #[test]
+    fn burst_schedules_and_settles() {
+        let start = Instant::now();
+        let args = PingArgs {
+            count: 2,
+            interval: ms(200),
+        };
+        let mut burst = PingBurst::new(args, start);
+
+        assert_eq!(burst.next_due(), Some(start));
+        assert_eq!(burst.on_sent(7, start), 1);
+        assert_eq!(burst.next_due(), Some(start + ms(200)));
+        assert_eq!(burst.on_sent(8, start + ms(200)), 2);
+        assert_eq!(burst.next_due(), None);
+        assert!(!burst.is_done());
+
+        assert_eq!(burst.on_reply(7, ms(3)), Some(1));
+        assert_eq!(burst.on_reply(99, ms(3)), None);
+        assert_eq!(burst.on_loss(8), Some(2));
+        assert!(burst.is_done());
+        assert_eq!(burst.stats().lost(), 1);
+        assert_eq!(burst.stats().received(), 1);
+    }
+}
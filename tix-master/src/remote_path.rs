@@ -0,0 +1,399 @@
+//! The console's remote working directory.
+//!
+//! `cd <path>` gives the connected slave a current directory once a
+//! `ListDir` of it has succeeded. File commands then take paths relative
+//! to it, and the master resolves them before anything is sent, so the
+//! slave only ever sees the path the user meant. Windows paths
+//! (`C:\Users`, `D:`, `\\server\share`) and POSIX ones (`/home`) are
+//! both understood; a relative path takes the separator of the directory
+//! it is resolved against.
+//!
+//! Path completion on the slave lists directories with quiet `ListDir`s
+//! and keeps the answers in [`RemoteCompletions`] for a few seconds.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::args::split_args;
+
+/// How long a listing fetched for completion is used before it is
+/// fetched again.
+pub const LISTING_TTL: Duration = Duration::from_secs(10);
+
+/// How long an unanswered listing request holds back another one for
+/// the same directory.
+pub const REQUEST_RETRY: Duration = Duration::from_secs(3);
+
+/// Commands whose arguments are all paths on the slave.
+const REMOTE_PATH_COMMANDS: [&str; 6] = ["cd", "ListDir", "Copy", "Delete", "TrashRestore", "find"];
+
+/// Whether `path` names a place without help from a current directory.
+/// `D:` and `D:dir` count: they are taken from the root of the drive.
+pub fn is_absolute(path: &str) -> bool {
+    path.starts_with(['/', '\\']) || has_drive(path)
+}
+
+fn has_drive(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// The separator paths under `dir` are written with: `\` for drive and
+/// UNC paths, `/` for POSIX ones, `\` when nothing tells.
+pub fn separator(dir: &str) -> char {
+    if has_drive(dir) || dir.contains('\\') || !dir.contains('/') {
+        '\\'
+    } else {
+        '/'
+    }
+}
+
+/// Resolve `path` against `cwd`. Absolute paths, and relative ones with
+/// no current directory, go to the slave as typed. A trailing separator
+/// is kept: some commands read it as "into this directory".
+pub fn resolve(cwd: Option<&str>, path: &str) -> String {
+    let path = path.trim();
+    let cwd = match cwd {
+        Some(cwd) if !is_absolute(path) => cwd,
+        _ => return path.to_string(),
+    };
+    if path.is_empty() {
+        return cwd.to_string();
+    }
+    let sep = separator(cwd);
+    let mut joined = if cwd.ends_with(['/', '\\']) {
+        normalize(&format!("{}{}", cwd, path))
+    } else {
+        normalize(&format!("{}{}{}", cwd, sep, path))
+    };
+    if path.ends_with(['/', '\\']) && !joined.ends_with(sep) {
+        joined.push(sep);
+    }
+    joined
+}
+
+/// Collapse repeated separators, `.` and `..` in an absolute path, and
+/// take `D:` and `D:dir` from the root of the drive. `..` never climbs
+/// above the root.
+pub fn normalize(path: &str) -> String {
+    let sep = separator(path);
+    let (mut out, rest) = split_root(path, sep);
+    let mut parts: Vec<&str> = Vec::new();
+    for part in rest.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    out.push_str(&parts.join(&sep.to_string()));
+    out
+}
+
+/// Whether `path` is `dir` or lies under it. Either separator counts
+/// and case is ignored, as on a Windows slave.
+pub fn contains(dir: &str, path: &str) -> bool {
+    let parts = |p: &str| -> Vec<String> {
+        p.split(['/', '\\'])
+            .filter(|part| !part.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    parts(path).starts_with(&parts(dir))
+}
+
+/// Split off the root of an absolute path: `C:\`, `\\server\share\` or
+/// `/`.
+fn split_root(path: &str, sep: char) -> (String, &str) {
+    if has_drive(path) {
+        let drive = path[..2].to_ascii_uppercase();
+        return (format!("{}{}", drive, sep), &path[2..]);
+    }
+    if let Some(unc) = path
+        .strip_prefix("\\\\")
+        .or_else(|| path.strip_prefix("//"))
+    {
+        let mut parts = unc.splitn(3, ['/', '\\']);
+        let server = parts.next().unwrap_or_default();
+        let share = parts.next().unwrap_or_default();
+        let rest = parts.next().unwrap_or_default();
+        let root = if share.is_empty() {
+            format!("{0}{0}{1}", sep, server)
+        } else {
+            format!("{0}{0}{1}{0}{2}{0}", sep, server, share)
+        };
+        return (root, rest);
+    }
+    match path.strip_prefix(['/', '\\']) {
+        Some(rest) => (sep.to_string(), rest),
+        None => (String::new(), path),
+    }
+}
+
+/// Take one path argument: quoted, it is unquoted by the console's
+/// tokenizer; otherwise it is the text as typed, spaces included.
+pub fn unquote(text: &str) -> String {
+    match split_args(text) {
+        Ok(args) if args.len() == 1 => args.into_iter().next().unwrap_or_default(),
+        _ => text.trim().to_string(),
+    }
+}
+
+/// The slave path being typed at the end of `input`, and the byte
+/// offset it starts at, when the command takes slave paths there.
+/// `Download <remote>|<local>` and `Upload <local>|<remote>` only count
+/// on their slave side.
+pub fn remote_word(input: &str) -> Option<(usize, &str)> {
+    let (command, _) = input.split_once(char::is_whitespace)?;
+    let mut start = input.len();
+    let mut in_quotes = false;
+    let mut after_space = false;
+    for (i, c) in input.char_indices() {
+        if c.is_whitespace() && !in_quotes {
+            after_space = true;
+            continue;
+        }
+        if after_space {
+            start = i;
+            after_space = false;
+        }
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+    }
+    if after_space {
+        start = input.len();
+    }
+
+    let word = &input[start..];
+    let start = match (command, word.rfind('|')) {
+        _ if REMOTE_PATH_COMMANDS.contains(&command) => start,
+        ("Download", None) => start,
+        ("Upload", Some(bar)) => start + bar + 1,
+        _ => return None,
+    };
+    let start = if input[start..].starts_with('"') {
+        start + 1
+    } else {
+        start
+    };
+    Some((start, &input[start..]))
+}
+
+// ── Completion cache ─────────────────────────────────────────────
+
+/// One entry of a slave directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// Read a `ListDir` reply: `PATH|<dir>;<name>|<is_dir>|...;...`.
+pub fn parse_listing(data: &str) -> Vec<RemoteEntry> {
+    data.split(';')
+        .filter(|entry| !entry.is_empty() && !entry.starts_with("PATH|"))
+        .filter_map(|entry| {
+            let mut fields = entry.split('|');
+            let name = fields.next()?;
+            let is_dir = fields.next()? == "1";
+            Some(RemoteEntry {
+                name: name.to_string(),
+                is_dir,
+            })
+        })
+        .collect()
+}
+
+/// What [`RemoteCompletions::lookup`] has for a directory.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lookup<'a> {
+    /// A recent listing.
+    Ready(&'a [RemoteEntry]),
+    /// Nothing usable: ask the slave, the request is now counted.
+    Fetch,
+    /// Already asked; the answer is on its way.
+    Waiting,
+}
+
+/// Slave directory listings fetched for path completion.
+#[derive(Debug, Default)]
+pub struct RemoteCompletions {
+    listings: HashMap<String, (Instant, Vec<RemoteEntry>)>,
+    requested: HashMap<String, Instant>,
+}
+
+impl RemoteCompletions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The entries of `dir`, or whether to ask for them. A directory is
+    /// asked for at most once per [`REQUEST_RETRY`] while unanswered.
+    pub fn lookup(&mut self, dir: &str, now: Instant) -> Lookup<'_> {
+        let fresh = self
+            .listings
+            .get(dir)
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) < LISTING_TTL);
+        if fresh {
+            return Lookup::Ready(&self.listings[dir].1);
+        }
+        match self.requested.get(dir) {
+            Some(at) if now.saturating_duration_since(*at) < REQUEST_RETRY => Lookup::Waiting,
+            _ => {
+                self.requested.insert(dir.to_string(), now);
+                Lookup::Fetch
+            }
+        }
+    }
+
+    /// The slave answered for `dir`; a failed listing is stored empty so
+    /// it is not asked for again right away.
+    pub fn store(&mut self, dir: &str, entries: Vec<RemoteEntry>, now: Instant) {
+        self.requested.remove(dir);
+        self.listings.insert(dir.to_string(), (now, entries));
+    }
+
+    /// Forget everything, as when the slave changes.
+    pub fn clear(&mut self) {
+        self.listings.clear();
+        self.requested.clear();
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths_join_the_current_directory() {
+        let cwd = Some(r"C:\Users\bob");
+        assert_eq!(resolve(cwd, "Documents"), r"C:\Users\bob\Documents");
+        assert_eq!(
+            resolve(cwd, r"..\alice\.\notes.txt"),
+            r"C:\Users\alice\notes.txt"
+        );
+        assert_eq!(resolve(cwd, r"..\..\..\.."), r"C:\");
+        assert_eq!(resolve(cwd, ""), r"C:\Users\bob");
+        assert_eq!(resolve(Some("/home/bob"), "../alice/x"), "/home/alice/x");
+        assert_eq!(resolve(Some("/"), "tmp"), "/tmp");
+    }
+
+    #[test]
+    fn absolute_paths_pass_through_and_trailing_separators_stay() {
+        let cwd = Some(r"C:\Users\bob");
+        assert_eq!(resolve(cwd, r"D:\new home\"), r"D:\new home\");
+        assert_eq!(resolve(cwd, "D:"), "D:");
+        assert_eq!(resolve(cwd, "/etc/hosts"), "/etc/hosts");
+        assert_eq!(resolve(cwd, r"sub\"), r"C:\Users\bob\sub\");
+        assert_eq!(resolve(Some(r"\\nas\share\x"), ".."), r"\\nas\share\");
+    }
+
+    #[test]
+    fn normalized_paths_are_tidy_and_rooted() {
+        assert_eq!(normalize("D:"), r"D:\");
+        assert_eq!(normalize("d:games"), r"D:\games");
+        assert_eq!(normalize(r"E:\a\\b\..\c\"), r"E:\a\c");
+        assert_eq!(normalize("/etc/./hosts"), "/etc/hosts");
+        assert_eq!(normalize(r"\\nas\share\x\.."), r"\\nas\share\");
+    }
+
+    #[test]
+    fn containment_ignores_case_and_separators() {
+        assert!(contains(r"C:\", r"c:\Users\Bob"));
+        assert!(contains(r"C:\Users", "C:/users/"));
+        assert!(contains("/", "/home/bob"));
+        assert!(!contains(r"C:\Users\Bob", r"C:\Users"));
+        assert!(!contains(r"C:\Use", r"C:\Users"));
+    }
+
+    #[test]
+    fn without_a_current_directory_relative_paths_pass_through() {
+        assert_eq!(resolve(None, r"sub\file.txt"), r"sub\file.txt");
+        assert_eq!(resolve(None, r"C:\Temp\..\Windows"), r"C:\Temp\..\Windows");
+    }
+
+    #[test]
+    fn quoted_arguments_lose_their_quotes() {
+        assert_eq!(
+            unquote(r#" "Program Files\My App" "#),
+            r"Program Files\My App"
+        );
+        assert_eq!(unquote(r"My Documents\a.txt"), r"My Documents\a.txt");
+    }
+
+    #[test]
+    fn remote_words_are_found_per_command() {
+        assert_eq!(remote_word(r"cd Win"), Some((3, "Win")));
+        assert_eq!(remote_word(r"Copy a.txt ..\b"), Some((11, r"..\b")));
+        assert_eq!(
+            remote_word(r#"ListDir "Program Files\Mi"#),
+            Some((9, r"Program Files\Mi"))
+        );
+        assert_eq!(remote_word("ListDir "), Some((8, "")));
+        assert_eq!(remote_word(r"Download logs\a"), Some((9, r"logs\a")));
+        assert_eq!(remote_word(r"Download a.log|C:\lo"), None);
+        assert_eq!(remote_word(r"Upload C:\local.txt"), None);
+        assert_eq!(
+            remote_word(r"Upload C:\local.txt|sub\d"),
+            Some((20, r"sub\d"))
+        );
+        assert_eq!(remote_word(r"save-output 3 out"), None);
+        assert_eq!(remote_word("cd"), None);
+    }
+
+    #[test]
+    fn listings_are_parsed_without_their_header() {
+        let entries = parse_listing(r"PATH|C:\x;docs|1|0|16;a.txt|0|12|32;;old|0");
+        assert_eq!(
+            entries,
+            [
+                RemoteEntry {
+                    name: "docs".into(),
+                    is_dir: true
+                },
+                RemoteEntry {
+                    name: "a.txt".into(),
+                    is_dir: false
+                },
+                RemoteEntry {
+                    name: "old".into(),
+                    is_dir: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn completions_are_fetched_once_and_cached() {
+        let t0 = Instant::now();
+        let mut cache = RemoteCompletions::new();
+        assert_eq!(cache.lookup(r"C:\x", t0), Lookup::Fetch);
+        // Typing on while the request is out asks nothing more.
+        assert_eq!(
+            cache.lookup(r"C:\x", t0 + Duration::from_secs(1)),
+            Lookup::Waiting
+        );
+        // An answer that never came is asked for again.
+        assert_eq!(cache.lookup(r"C:\x", t0 + REQUEST_RETRY), Lookup::Fetch);
+
+        let entry = RemoteEntry {
+            name: "a".into(),
+            is_dir: false,
+        };
+        cache.store(r"C:\x", vec![entry.clone()], t0 + REQUEST_RETRY);
+        let later = t0 + REQUEST_RETRY + LISTING_TTL - Duration::from_secs(1);
+        assert_eq!(cache.lookup(r"C:\x", later), Lookup::Ready(&[entry]));
+        assert_eq!(
+            cache.lookup(r"C:\x", t0 + REQUEST_RETRY + LISTING_TTL),
+            Lookup::Fetch
+        );
+
+        cache.clear();
+        assert_eq!(cache.lookup(r"C:\y", t0), Lookup::Fetch);
+    }
+}
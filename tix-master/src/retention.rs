@@ -0,0 +1,1103 @@
+//! What the master keeps on disk, for how long, and what of it is
+//! masked first.
+//!
+//! Four kinds of [`Artifact`] pile up over time: the console's command
+//! history, transcripts of the console log, the `watch` cache of
+//! downloaded file versions, and the viewer's input journals. Each has
+//! a [`Retention`] in `[data.retention]` (by default history is kept
+//! forever, transcripts 30 days, caches 7 and journals 14), and
+//! [`DataStore::clean`] deletes whatever is older. The console runs it
+//! at startup and once a day; `tix-master --purge-data [artifact]`
+//! deletes everything of one kind, or of all of them, at once.
+//!
+//! Command text goes through a [`Redactor`] on its way into the history
+//! file or a transcript: role passwords are masked as on screen (see
+//! [`roles::redact`]), then every match of the redaction patterns
+//! becomes [`REDACTED`]. Only the copy on disk is masked; the command
+//! has already been handed to the master as typed.
+//!
+//! Cleanup only deletes files it recognises as its own: `session-*.log`
+//! among transcripts, `*.jsonl` among journals and `v000001`-style
+//! versions in the cache's per-file folders. A folder pointed at the
+//! wrong place loses nothing else.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use regex::Regex;
+use tix_core::config::write_atomic;
+use tix_core::format::format_bytes;
+
+use crate::config::DataConfig;
+use crate::eventlog::format_utc;
+use crate::pager::LogEntry;
+use crate::roles;
+use crate::watch::parse_duration;
+
+/// What a masked secret is replaced with.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Default command history file, in the working directory.
+pub const DEFAULT_HISTORY_PATH: &str = "tix-history.log";
+
+/// Time between cleanup passes after the one at startup.
+pub const CLEAN_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+
+/// Patterns used unless `data.default_redactions` is off: the value of
+/// `password=` and its kin, the argument of `-p` or `--password`, and
+/// the credentials of an `Authorization` header. They err on the side
+/// of masking, so `mkdir -p logs` is remembered as `mkdir -p [REDACTED]`.
+pub const DEFAULT_REDACTIONS: &[&str] = &[
+    r#"(?i)\b(?:password|passwd|pwd|secret|token|api[_-]?key)\s*=\s*(?P<secret>"[^"]*"|'[^']*'|[^\s&;,]+)"#,
+    r#"(?:^|\s)(?:-p|--password)(?:\s+|=)(?P<secret>"[^"]*"|'[^']*'|\S+)"#,
+    r#"(?i)\bauthorization\s*:\s*(?:(?:basic|bearer|digest|negotiate)\s+)?(?P<secret>"[^"]*"|'[^']*'|[^\s"']+)"#,
+];
+
+/// Prefix of a transcript's file name.
+const TRANSCRIPT_PREFIX: &str = "session-";
+
+// ── Artifact ─────────────────────────────────────────────────────
+
+/// A kind of file the master leaves behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Artifact {
+    /// Commands typed at the console, one file.
+    History,
+    /// The console log of each session.
+    Transcripts,
+    /// Versions downloaded by `watch`.
+    Caches,
+    /// Input journals recorded by the viewer.
+    Journals,
+}
+
+impl Artifact {
+    pub const ALL: [Artifact; 4] = [
+        Artifact::History,
+        Artifact::Transcripts,
+        Artifact::Caches,
+        Artifact::Journals,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Artifact::History => "history",
+            Artifact::Transcripts => "transcripts",
+            Artifact::Caches => "caches",
+            Artifact::Journals => "journals",
+        }
+    }
+
+    /// Whether `name`, a file where the artifact is kept, is one of
+    /// its files.
+    fn owns(self, name: &str) -> bool {
+        match self {
+            Artifact::History => true,
+            Artifact::Transcripts => name.starts_with(TRANSCRIPT_PREFIX) && name.ends_with(".log"),
+            Artifact::Caches => name
+                .strip_prefix('v')
+                .is_some_and(|n| n.len() >= 6 && n.bytes().all(|b| b.is_ascii_digit())),
+            Artifact::Journals => name.ends_with(".jsonl"),
+        }
+    }
+}
+
+impl fmt::Display for Artifact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Artifact {
+    type Err = String;
+
+    /// A name as [`Artifact::as_str`] gives it, or in the singular.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "history" => Ok(Artifact::History),
+            "transcript" | "transcripts" => Ok(Artifact::Transcripts),
+            "cache" | "caches" => Ok(Artifact::Caches),
+            "journal" | "journals" => Ok(Artifact::Journals),
+            _ => Err(format!(
+                "unknown artifact `{}`; one of history, transcripts, caches, journals",
+                s
+            )),
+        }
+    }
+}
+
+// ── Retention ────────────────────────────────────────────────────
+
+/// How long an artifact's files are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retention {
+    Forever,
+    For(Duration),
+}
+
+impl Retention {
+    /// `forever`, or a duration such as `30d`, `12h` or `90m`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim() {
+            "forever" => Ok(Retention::Forever),
+            other => parse_duration(other).map(Retention::For).map_err(|_| {
+                format!(
+                    "`{}` is neither `forever` nor a duration such as 30d or 12h",
+                    text
+                )
+            }),
+        }
+    }
+
+    /// Whether something `age` old has outlived it.
+    pub fn expired(self, age: Duration) -> bool {
+        match self {
+            Retention::Forever => false,
+            Retention::For(keep) => age > keep,
+        }
+    }
+}
+
+impl fmt::Display for Retention {
+    /// In the largest unit that divides it: `30d`, `36h`, `90s`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Retention::For(keep) = self else {
+            return f.write_str("forever");
+        };
+        let secs = keep.as_secs();
+        if keep.subsec_nanos() != 0 {
+            return write!(f, "{}ms", keep.as_millis());
+        }
+        match [(86_400, "d"), (3_600, "h"), (60, "m")]
+            .into_iter()
+            .find(|(unit, _)| secs > 0 && secs % unit == 0)
+        {
+            Some((unit, suffix)) => write!(f, "{}{}", secs / unit, suffix),
+            None => write!(f, "{}s", secs),
+        }
+    }
+}
+
+/// The retention of every artifact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rules {
+    pub history: Retention,
+    pub transcripts: Retention,
+    pub caches: Retention,
+    pub journals: Retention,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        let days = |n: u64| Retention::For(Duration::from_secs(n * 86_400));
+        Self {
+            history: Retention::Forever,
+            transcripts: days(30),
+            caches: days(7),
+            journals: days(14),
+        }
+    }
+}
+
+impl Rules {
+    pub fn get(&self, artifact: Artifact) -> Retention {
+        match artifact {
+            Artifact::History => self.history,
+            Artifact::Transcripts => self.transcripts,
+            Artifact::Caches => self.caches,
+            Artifact::Journals => self.journals,
+        }
+    }
+}
+
+// ── Redactor ─────────────────────────────────────────────────────
+
+/// Masks secrets in command text before it is written down.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Default for Redactor {
+    /// The [`DEFAULT_REDACTIONS`] alone.
+    fn default() -> Self {
+        Self::new(&[], true).expect("default redactions compile")
+    }
+}
+
+impl Redactor {
+    /// [`DEFAULT_REDACTIONS`] if `defaults`, then `extra`, applied in
+    /// that order. A pattern with a group named `secret` masks only
+    /// that group; any other masks its whole match.
+    pub fn new(extra: &[String], defaults: bool) -> Result<Self, String> {
+        let defaults = DEFAULT_REDACTIONS.iter().filter(|_| defaults).copied();
+        let patterns = defaults
+            .chain(extra.iter().map(String::as_str))
+            .map(|pattern| Regex::new(pattern).map_err(|e| format!("`{}`: {}", pattern, e)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// `text` with a role password and every pattern's matches masked.
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = roles::redact(text);
+        for pattern in &self.patterns {
+            text = mask(pattern, &text);
+        }
+        text
+    }
+}
+
+/// `text` with each match of `pattern`, or its `secret` group, replaced
+/// by [`REDACTED`].
+fn mask(pattern: &Regex, text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in pattern.captures_iter(text) {
+        let Some(secret) = caps.name("secret").or_else(|| caps.get(0)) else {
+            continue;
+        };
+        if secret.is_empty() {
+            continue;
+        }
+        out.push_str(&text[last..secret.start()]);
+        out.push_str(REDACTED);
+        last = secret.end();
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+// ── History ──────────────────────────────────────────────────────
+
+/// The command history file: one `<unix seconds>\t<command>` line per
+/// command, oldest first, each redacted.
+#[derive(Debug, Clone)]
+pub struct HistoryFile {
+    path: PathBuf,
+    redactor: Redactor,
+    /// Held while a line is appended or the file rewritten by cleanup.
+    lock: Arc<Mutex<()>>,
+}
+
+impl HistoryFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The commands in the file, oldest first; none if it is missing.
+    pub fn load(&self) -> io::Result<Vec<String>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(text
+            .lines()
+            .map(|line| parse_history_line(line).map_or(line, |(_, command)| command))
+            .filter(|command| !command.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Add `command`, redacted, stamped `now` (Unix seconds). Returns
+    /// what was written.
+    pub fn append(&self, command: &str, now: u64) -> io::Result<String> {
+        let redacted = self.redactor.redact(command).replace(['\r', '\n'], " ");
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}\t{}", now, redacted)?;
+        Ok(redacted)
+    }
+}
+
+fn parse_history_line(line: &str) -> Option<(u64, &str)> {
+    let (stamp, command) = line.split_once('\t')?;
+    Some((stamp.parse().ok()?, command))
+}
+
+/// `text`, a history file, without the lines older than `keep` at `now`
+/// (Unix seconds), and how many those were. Lines without a stamp are
+/// kept: nothing says they are old.
+fn expire_history(text: &str, keep: Retention, now: u64) -> (String, usize) {
+    let mut kept = String::with_capacity(text.len());
+    let mut dropped = 0;
+    for line in text.lines() {
+        let expired = parse_history_line(line)
+            .is_some_and(|(stamp, _)| keep.expired(Duration::from_secs(now.saturating_sub(stamp))));
+        if expired {
+            dropped += 1;
+        } else {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    (kept, dropped)
+}
+
+// ── Transcript ───────────────────────────────────────────────────
+
+/// One session's console log on disk, every entry redacted.
+#[derive(Debug)]
+pub struct Transcript {
+    path: PathBuf,
+    file: LineWriter<File>,
+    redactor: Redactor,
+}
+
+impl Transcript {
+    /// A transcript in `dir`, named after when the session `started`.
+    pub fn create(dir: &Path, started: SystemTime, redactor: Redactor) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(transcript_name(started));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: LineWriter::new(file),
+            redactor,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write `entry` with its date and time.
+    pub fn record(&mut self, entry: &LogEntry) -> io::Result<()> {
+        writeln!(
+            self.file,
+            "{} {}",
+            format_utc(unix_millis(entry.wall())),
+            self.redactor.redact(entry.text())
+        )
+    }
+}
+
+/// `session-2026-10-17_140322.log` for a session started then (UTC).
+fn transcript_name(started: SystemTime) -> String {
+    let stamp = format_utc(unix_millis(started))
+        .replace(' ', "_")
+        .replace(':', "");
+    format!("{}{}.log", TRANSCRIPT_PREFIX, stamp)
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+// ── DataStore ────────────────────────────────────────────────────
+
+/// What cleanup or a purge removed of one artifact.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Removed {
+    pub artifact: Option<Artifact>,
+    pub place: PathBuf,
+    /// Whole files deleted.
+    pub files: usize,
+    pub bytes: u64,
+    /// History lines dropped from a file that stays.
+    pub entries: usize,
+    /// Files that could not be read or deleted, with why.
+    pub errors: Vec<String>,
+}
+
+impl Removed {
+    fn new(artifact: Artifact, place: &Path) -> Self {
+        Self {
+            artifact: Some(artifact),
+            place: place.to_path_buf(),
+            ..Self::default()
+        }
+    }
+
+    /// Whether nothing was removed and nothing went wrong.
+    pub fn is_empty(&self) -> bool {
+        self.files == 0 && self.entries == 0 && self.errors.is_empty()
+    }
+
+    fn delete(&mut self, path: &Path, bytes: u64) {
+        match fs::remove_file(path) {
+            Ok(()) => {
+                self.files += 1;
+                self.bytes += bytes;
+            }
+            Err(e) => self.errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+}
+
+impl fmt::Display for Removed {
+    /// `transcripts: 3 files (12.0 KiB) from tix-transcripts`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(artifact) = self.artifact {
+            write!(f, "{}: ", artifact)?;
+        }
+        let mut parts = Vec::new();
+        if self.files > 0 {
+            parts.push(format!(
+                "{} file{} ({})",
+                self.files,
+                if self.files == 1 { "" } else { "s" },
+                format_bytes(self.bytes)
+            ));
+        }
+        if self.entries > 0 {
+            parts.push(format!(
+                "{} entr{}",
+                self.entries,
+                if self.entries == 1 { "y" } else { "ies" }
+            ));
+        }
+        if parts.is_empty() {
+            parts.push("nothing".to_string());
+        }
+        write!(f, "{} from {}", parts.join(", "), self.place.display())?;
+        for error in &self.errors {
+            write!(f, "; cannot remove {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where every artifact is kept and for how long.
+#[derive(Debug, Clone)]
+pub struct DataStore {
+    history: Option<PathBuf>,
+    transcripts: Option<PathBuf>,
+    caches: PathBuf,
+    journals: Option<PathBuf>,
+    rules: Rules,
+    redactor: Redactor,
+    history_lock: Arc<Mutex<()>>,
+}
+
+impl DataStore {
+    /// The artifacts `data` names, with the `watch` cache under
+    /// `cache_dir`.
+    pub fn new(data: &DataConfig, cache_dir: impl Into<PathBuf>) -> Self {
+        let place = |path: &PathBuf| (!path.as_os_str().is_empty()).then(|| path.clone());
+        Self {
+            history: place(&data.history),
+            transcripts: place(&data.transcripts),
+            caches: cache_dir.into(),
+            journals: place(&data.journals),
+            rules: data.retention.rules(),
+            redactor: data.redactor(),
+            history_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+
+    /// Where `artifact` is kept, if it is.
+    pub fn place(&self, artifact: Artifact) -> Option<&Path> {
+        match artifact {
+            Artifact::History => self.history.as_deref(),
+            Artifact::Transcripts => self.transcripts.as_deref(),
+            Artifact::Caches => Some(&self.caches),
+            Artifact::Journals => self.journals.as_deref(),
+        }
+    }
+
+    /// The history file, unless history is kept in memory only.
+    pub fn history_file(&self) -> Option<HistoryFile> {
+        Some(HistoryFile {
+            path: self.history.clone()?,
+            redactor: self.redactor.clone(),
+            lock: self.history_lock.clone(),
+        })
+    }
+
+    /// A transcript for a session started now, unless none are kept.
+    pub fn transcript(&self) -> Option<io::Result<Transcript>> {
+        let dir = self.transcripts.as_deref()?;
+        Some(Transcript::create(
+            dir,
+            SystemTime::now(),
+            self.redactor.clone(),
+        ))
+    }
+
+    /// Delete what has outlived its retention at `now`; only artifacts
+    /// something happened to are reported.
+    pub fn clean(&self, now: SystemTime) -> Vec<Removed> {
+        Artifact::ALL
+            .into_iter()
+            .filter_map(|artifact| self.clean_one(artifact, now))
+            .filter(|removed| !removed.is_empty())
+            .collect()
+    }
+
+    fn clean_one(&self, artifact: Artifact, now: SystemTime) -> Option<Removed> {
+        let place = self.place(artifact)?;
+        let Retention::For(keep) = self.rules.get(artifact) else {
+            return None;
+        };
+        let mut removed = Removed::new(artifact, place);
+        if artifact == Artifact::History {
+            self.expire_history(place, keep, now, &mut removed);
+            return Some(removed);
+        }
+        for (path, meta) in self.files(artifact, &mut removed) {
+            let age = meta
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            if age > keep {
+                removed.delete(&path, meta.len());
+            }
+        }
+        self.prune_cache_folders(artifact);
+        Some(removed)
+    }
+
+    fn expire_history(&self, path: &Path, keep: Duration, now: SystemTime, removed: &mut Removed) {
+        let _guard = self.history_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => return removed.errors.push(format!("{}: {}", path.display(), e)),
+        };
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let (kept, dropped) = expire_history(&text, Retention::For(keep), now);
+        if dropped == 0 {
+            return;
+        }
+        match write_atomic(path, kept) {
+            Ok(()) => removed.entries = dropped,
+            Err(e) => removed.errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// Delete every file of `only`, or of every artifact; each one kept
+    /// somewhere is reported, removed or not.
+    pub fn purge(&self, only: Option<Artifact>) -> Vec<Removed> {
+        let mut report = Vec::new();
+        for artifact in Artifact::ALL {
+            if only.is_some_and(|only| only != artifact) {
+                continue;
+            }
+            let Some(place) = self.place(artifact) else {
+                continue;
+            };
+            let mut removed = Removed::new(artifact, place);
+            if artifact == Artifact::History {
+                let _guard = self.history_lock.lock().unwrap_or_else(|e| e.into_inner());
+                if let Ok(meta) = fs::symlink_metadata(place)
+                    && meta.is_file()
+                {
+                    removed.delete(place, meta.len());
+                }
+            } else {
+                for (path, meta) in self.files(artifact, &mut removed) {
+                    removed.delete(&path, meta.len());
+                }
+                self.prune_cache_folders(artifact);
+            }
+            report.push(removed);
+        }
+        report
+    }
+
+    /// The regular files of a folder-kept `artifact` that it owns.
+    /// Folders that cannot be read are noted in `removed`.
+    fn files(&self, artifact: Artifact, removed: &mut Removed) -> Vec<(PathBuf, fs::Metadata)> {
+        let Some(place) = self.place(artifact) else {
+            return Vec::new();
+        };
+        let folders = if artifact == Artifact::Caches {
+            read_dir(place, removed)
+                .into_iter()
+                .filter(|(_, meta)| meta.is_dir())
+                .map(|(path, _)| path)
+                .collect()
+        } else {
+            vec![place.to_path_buf()]
+        };
+        let mut files = Vec::new();
+        for folder in folders {
+            files.extend(
+                read_dir(&folder, removed)
+                    .into_iter()
+                    .filter(|(path, meta)| {
+                        meta.is_file()
+                            && path
+                                .file_name()
+                                .and_then(|name| name.to_str())
+                                .is_some_and(|name| artifact.owns(name))
+                    }),
+            );
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files
+    }
+
+    /// Remove the cache's per-file folders left empty.
+    fn prune_cache_folders(&self, artifact: Artifact) {
+        if artifact != Artifact::Caches {
+            return;
+        }
+        let mut ignored = Removed::default();
+        for (folder, meta) in read_dir(&self.caches, &mut ignored) {
+            if meta.is_dir() {
+                // Fails, as it should, unless the folder is empty.
+                let _ = fs::remove_dir(folder);
+            }
+        }
+    }
+}
+
+/// Entries of `dir` with their own (not followed) metadata; none if it
+/// does not exist.
+fn read_dir(dir: &Path, removed: &mut Removed) -> Vec<(PathBuf, fs::Metadata)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            removed.errors.push(format!("{}: {}", dir.display(), e));
+            return Vec::new();
+        }
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let meta = fs::symlink_metadata(&path).ok()?;
+            Some((path, meta))
+        })
+        .collect()
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use tix_core::testing::temp_dir;
+
+    const DAY: u64 = 86_400;
+
+    /// A file at `path` last written `days_ago` before `now`.
+    fn aged(path: &Path, days_ago: u64, now: SystemTime) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"data").unwrap();
+        let file = File::options().write(true).open(path).unwrap();
+        file.set_modified(now - Duration::from_secs(days_ago * DAY))
+            .unwrap();
+    }
+
+    fn store(dir: &Path) -> DataStore {
+        let data = DataConfig {
+            history: dir.join("history.log"),
+            transcripts: dir.join("transcripts"),
+            journals: dir.join("journals"),
+            ..DataConfig::default()
+        };
+        DataStore::new(&data, dir.join("cache"))
+    }
+
+    #[test]
+    fn retention_reads_forever_and_durations() {
+        assert_eq!(Retention::parse("forever"), Ok(Retention::Forever));
+        assert_eq!(
+            Retention::parse("30d"),
+            Ok(Retention::For(Duration::from_secs(30 * DAY)))
+        );
+        assert_eq!(
+            Retention::parse(" 12h "),
+            Ok(Retention::For(Duration::from_secs(12 * 3_600)))
+        );
+        assert!(Retention::parse("never").unwrap_err().contains("`never`"));
+        assert!(Retention::parse("").is_err());
+
+        for text in ["forever", "30d", "36h", "90m", "45s", "500ms"] {
+            assert_eq!(Retention::parse(text).unwrap().to_string(), text);
+        }
+        let week = Retention::For(Duration::from_secs(7 * DAY));
+        assert!(!week.expired(Duration::from_secs(7 * DAY)));
+        assert!(week.expired(Duration::from_secs(7 * DAY + 1)));
+        assert!(!Retention::Forever.expired(Duration::MAX));
+
+        let rules = Rules::default();
+        assert_eq!(rules.get(Artifact::History), Retention::Forever);
+        assert_eq!(rules.get(Artifact::Transcripts).to_string(), "30d");
+        assert_eq!(rules.get(Artifact::Caches).to_string(), "7d");
+        assert_eq!(rules.get(Artifact::Journals).to_string(), "14d");
+    }
+
+    #[test]
+    fn artifacts_are_named_either_way() {
+        for artifact in Artifact::ALL {
+            assert_eq!(artifact.as_str().parse(), Ok(artifact));
+        }
+        assert_eq!("Journal".parse(), Ok(Artifact::Journals));
+        assert_eq!("cache".parse(), Ok(Artifact::Caches));
+        assert!("logs".parse::<Artifact>().unwrap_err().contains("`logs`"));
+    }
+
+    #[test]
+    fn default_patterns_mask_secrets_only() {
+        let redactor = Redactor::default();
+        let cases = [
+            (
+                "ShellExecute net use Z: \\\\srv\\share password=hunter2 /user:bob",
+                "ShellExecute net use Z: \\\\srv\\share password=[REDACTED] /user:bob",
+            ),
+            (
+                "ShellExecute curl \"https://h/?user=bob&PWD=s3cret&x=1\"",
+                "ShellExecute curl \"https://h/?user=bob&PWD=[REDACTED]&x=1\"",
+            ),
+            (
+                "ShellExecute setx API_KEY = 'a b c'",
+                "ShellExecute setx API_KEY = [REDACTED]",
+            ),
+            (
+                "ShellExecute mysql -u root -p hunter2 db",
+                "ShellExecute mysql -u root -p [REDACTED] db",
+            ),
+            (
+                "ShellExecute tool --password=\"two words\"",
+                "ShellExecute tool --password=[REDACTED]",
+            ),
+            (
+                "ShellExecute curl -H \"Authorization: Bearer eyJhbGci.x.y\" https://h",
+                "ShellExecute curl -H \"Authorization: Bearer [REDACTED]\" https://h",
+            ),
+            (
+                "ShellExecute curl -H 'authorization:dXNlcjpwYXNz' https://h",
+                "ShellExecute curl -H 'authorization:[REDACTED]' https://h",
+            ),
+        ];
+        for (typed, kept) in cases {
+            assert_eq!(redactor.redact(typed), kept);
+        }
+        for plain in [
+            "ListDir C:\\Users",
+            "ShellExecute whoami /priv",
+            "ShellExecute ping -n 3 host",
+            "ShellExecute dir C:\\passwords",
+        ] {
+            assert_eq!(redactor.redact(plain), plain);
+        }
+    }
+
+    #[test]
+    fn config_patterns_mask_their_secret_group_or_whole_match() {
+        let extra = vec![
+            r"\b\d{4}-\d{4}-\d{4}-\d{4}\b".to_string(),
+            r"(?i)pin\s+(?P<secret>\d+)".to_string(),
+        ];
+        let redactor = Redactor::new(&extra, false).unwrap();
+        assert_eq!(
+            redactor.redact("pay 1234-5678-9012-3456 then PIN 4321 password=x"),
+            "pay [REDACTED] then PIN [REDACTED] password=x"
+        );
+
+        // Defaults first, then the config's, each on what the last left.
+        let extra = vec![r"token=\[REDACTED\]".to_string()];
+        let redactor = Redactor::new(&extra, true).unwrap();
+        assert_eq!(redactor.redact("token=abc"), "[REDACTED]");
+
+        let err = Redactor::new(&["(unclosed".to_string()], true).unwrap_err();
+        assert!(err.starts_with("`(unclosed`:"), "{}", err);
+    }
+
+    #[test]
+    fn role_passwords_are_masked_before_the_patterns() {
+        let redactor = Redactor::new(&[], false).unwrap();
+        assert_eq!(redactor.redact("role admin hunter2"), "role admin *******");
+        // A pattern matching the password never sees it.
+        let redactor = Redactor::new(&["hunter2".to_string()], false).unwrap();
+        assert_eq!(redactor.redact("role admin hunter2"), "role admin *******");
+        assert_eq!(redactor.redact("echo hunter2"), "echo [REDACTED]");
+    }
+
+    #[test]
+    fn history_is_written_redacted_and_read_back_in_order() {
+        let dir = temp_dir("retention-history");
+        let history = store(&dir).history_file().unwrap();
+        assert_eq!(history.load().unwrap(), Vec::<String>::new());
+
+        let typed = "ShellExecute net user bob password=hunter2";
+        let written = history.append(typed, 1_000).unwrap();
+        assert_eq!(written, "ShellExecute net user bob password=[REDACTED]");
+        history.append("ListDrives", 1_001).unwrap();
+        history.append("echo a\nb", 1_002).unwrap();
+
+        let text = fs::read_to_string(history.path()).unwrap();
+        assert!(!text.contains("hunter2"), "{}", text);
+        assert!(text.starts_with("1000\tShellExecute net user bob"));
+        assert_eq!(
+            history.load().unwrap(),
+            [written.as_str(), "ListDrives", "echo a b"]
+        );
+
+        let without_history = DataConfig {
+            history: PathBuf::new(),
+            ..DataConfig::default()
+        };
+        assert!(
+            DataStore::new(&without_history, &dir)
+                .history_file()
+                .is_none()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn old_history_lines_are_dropped() {
+        let now = 100 * DAY;
+        let text = format!(
+            "{}\told\n{}\tborderline\nno stamp\n{}\tnew\n",
+            now - 31 * DAY,
+            now - 30 * DAY,
+            now - DAY
+        );
+        let month = Retention::For(Duration::from_secs(30 * DAY));
+        let (kept, dropped) = expire_history(&text, month, now);
+        assert_eq!(dropped, 1);
+        assert_eq!(
+            kept,
+            format!(
+                "{}\tborderline\nno stamp\n{}\tnew\n",
+                now - 30 * DAY,
+                now - DAY
+            )
+        );
+        assert_eq!(
+            expire_history(&text, Retention::Forever, now),
+            (text.clone(), 0)
+        );
+
+        // Through the store, which rewrites the file in place.
+        let dir = temp_dir("retention-history-clean");
+        let mut data = DataConfig {
+            history: dir.join("history.log"),
+            ..DataConfig::default()
+        };
+        fs::write(&data.history, &text).unwrap();
+        let now = UNIX_EPOCH + Duration::from_secs(now);
+        assert!(
+            DataStore::new(&data, dir.join("cache"))
+                .clean(now)
+                .is_empty()
+        );
+
+        data.retention.history = "30d".to_string();
+        let removed = DataStore::new(&data, dir.join("cache")).clean(now);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].entries, 1);
+        assert_eq!(fs::read_to_string(&data.history).unwrap(), kept);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cleanup_deletes_only_old_files_it_owns() {
+        let dir = temp_dir("retention-clean");
+        let now = SystemTime::now();
+        let store = store(&dir);
+        let transcripts = dir.join("transcripts");
+        let journals = dir.join("journals");
+        let cache = dir.join("cache");
+
+        aged(&transcripts.join("session-2026-01-01_000000.log"), 31, now);
+        aged(&transcripts.join("session-2026-02-01_000000.log"), 29, now);
+        aged(&transcripts.join("notes.log"), 90, now);
+        aged(&transcripts.join("session-old.txt"), 90, now);
+        aged(&journals.join("old.jsonl"), 15, now);
+        aged(&journals.join("recent.jsonl"), 13, now);
+        aged(&journals.join("old.json"), 90, now);
+        aged(&cache.join("C__a.txt-0123abcd").join("v000001"), 8, now);
+        aged(&cache.join("C__a.txt-0123abcd").join("v000002"), 1, now);
+        aged(&cache.join("C__b.txt-4567ef01").join("v000007"), 10, now);
+        aged(&cache.join("C__c.txt-89abcdef").join("keep.me"), 90, now);
+        aged(&cache.join("v000001"), 90, now);
+        aged(&cache.join("C__d.txt-00000000").join("v12"), 90, now);
+        // Nested folders are not the cache's layout.
+        aged(&transcripts.join("sub").join("session-x.log"), 90, now);
+
+        let removed = store.clean(now);
+        let summary: Vec<(Option<Artifact>, usize)> =
+            removed.iter().map(|r| (r.artifact, r.files)).collect();
+        assert_eq!(
+            summary,
+            [
+                (Some(Artifact::Transcripts), 1),
+                (Some(Artifact::Caches), 2),
+                (Some(Artifact::Journals), 1),
+            ]
+        );
+        assert!(
+            removed
+                .iter()
+                .all(|r| r.errors.is_empty() && r.bytes == 4 * r.files as u64)
+        );
+
+        let left = |folder: &Path| {
+            let mut names: Vec<String> = fs::read_dir(folder)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            left(&transcripts),
+            [
+                "notes.log",
+                "session-2026-02-01_000000.log",
+                "session-old.txt",
+                "sub"
+            ]
+        );
+        assert_eq!(left(&journals), ["old.json", "recent.jsonl"]);
+        // The emptied folder went with its last version.
+        assert_eq!(
+            left(&cache),
+            [
+                "C__a.txt-0123abcd",
+                "C__c.txt-89abcdef",
+                "C__d.txt-00000000",
+                "v000001"
+            ]
+        );
+        assert_eq!(left(&cache.join("C__a.txt-0123abcd")), ["v000002"]);
+
+        // A second pass finds nothing more.
+        assert!(store.clean(now).is_empty());
+        // Nor does one in a tree that was never created.
+        assert!(self::store(&dir.join("missing")).clean(now).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn retention_comes_from_the_config() {
+        let dir = temp_dir("retention-rules");
+        let now = SystemTime::now();
+        let log = dir.join("transcripts").join("session-a.log");
+        aged(&log, 2, now);
+
+        let mut data = DataConfig {
+            transcripts: dir.join("transcripts"),
+            ..DataConfig::default()
+        };
+        data.retention.transcripts = "forever".to_string();
+        assert!(
+            DataStore::new(&data, dir.join("cache"))
+                .clean(now)
+                .is_empty()
+        );
+        data.retention.transcripts = "36h".to_string();
+        let removed = DataStore::new(&data, dir.join("cache")).clean(now);
+        assert_eq!(removed[0].files, 1);
+        assert!(!log.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn purge_deletes_every_owned_file_and_reports_it() {
+        let dir = temp_dir("retention-purge");
+        let now = SystemTime::now();
+        let store = store(&dir);
+        aged(&dir.join("history.log"), 0, now);
+        aged(&dir.join("transcripts").join("session-a.log"), 0, now);
+        aged(&dir.join("transcripts").join("session-b.log"), 0, now);
+        aged(&dir.join("transcripts").join("readme.txt"), 0, now);
+        aged(
+            &dir.join("cache").join("C__a-01234567").join("v000001"),
+            0,
+            now,
+        );
+
+        let removed = store.purge(Some(Artifact::Transcripts));
+        assert_eq!(removed.len(), 1);
+        assert_eq!(
+            removed[0].to_string(),
+            format!(
+                "transcripts: 2 files (8 B) from {}",
+                dir.join("transcripts").display()
+            )
+        );
+        assert!(dir.join("transcripts").join("readme.txt").exists());
+        assert!(dir.join("history.log").exists());
+
+        let removed = store.purge(None);
+        let summary: Vec<String> = removed
+            .iter()
+            .map(|r| r.to_string().split(" from ").next().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                "history: 1 file (4 B)",
+                "transcripts: nothing",
+                "caches: 1 file (4 B)",
+                "journals: nothing",
+            ]
+        );
+        assert!(!dir.join("history.log").exists());
+        assert!(!dir.join("cache").join("C__a-01234567").exists());
+
+        // Artifacts kept nowhere are not reported.
+        let data = DataConfig {
+            history: PathBuf::new(),
+            ..DataConfig::default()
+        };
+        let removed = DataStore::new(&data, dir.join("cache")).purge(Some(Artifact::History));
+        assert!(removed.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn transcripts_are_redacted_and_named_after_their_start() {
+        let dir = temp_dir("retention-transcript");
+        let started = UNIX_EPOCH + Duration::from_secs(1_792_245_802);
+        assert_eq!(transcript_name(started), "session-2026-10-17_140322.log");
+
+        let mut transcript = Transcript::create(&dir, started, Redactor::default()).unwrap();
+        transcript
+            .record(&LogEntry::stamped(
+                Instant::now(),
+                started,
+                "> ShellExecute runas /user:x -p s3cret cmd",
+            ))
+            .unwrap();
+        transcript
+            .record(&LogEntry::stamped(Instant::now(), started, "Done."))
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(transcript.path()).unwrap(),
+            "2026-10-17 14:03:22 > ShellExecute runas /user:x -p [REDACTED] cmd\n\
+             2026-10-17 14:03:22 Done.\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn removals_read_as_a_sentence() {
+        let mut removed = Removed::new(Artifact::History, Path::new("h.log"));
+        assert!(removed.is_empty());
+        assert_eq!(removed.to_string(), "history: nothing from h.log");
+        removed.entries = 1;
+        assert_eq!(removed.to_string(), "history: 1 entry from h.log");
+        removed.entries = 12;
+        removed.errors.push("h.log: denied".to_string());
+        assert_eq!(
+            removed.to_string(),
+            "history: 12 entries from h.log; cannot remove h.log: denied"
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Operator roles: which commands the console may send to a slave.
+//!
+//! ```toml
+//! [role.viewer]
+//! allow = ["Ping", "ListDir", "ListDrives", "Download", "FileRead", "Screen*"]
+//!
+//! [role.admin]
+//! allow = ["*"]
+//! password = "$argon2id$v=19$m=19456,t=2,p=1$…"
+//! ```
+//!
+//! `allow` takes the same patterns as the slave's `audit.commands` (see
+//! [`tix_core::policy`]). `tix-master --role viewer` starts in a role;
+//! without `--role` nothing is held back. In the console `role` shows
+//! the active role and `role <name> [password]` switches, which a role
+//! with a `password` only allows with it. `tix-master hash-password`
+//! turns a password into the argon2 hash the config keeps.
+//!
+//! Commands are checked on the master before anything is sent, so a
+//! refused one never reaches the slave, whatever the slave's own config
+//! says.
+
+use std::collections::BTreeMap;
+
+use argon2::Argon2;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use tix_core::Command;
+use tix_core::policy::CommandSet;
+
+use crate::args::split_args;
+use crate::config::RoleConfig;
+
+/// The roles the config defines and the one in force.
+#[derive(Debug, Clone, Default)]
+pub struct Roles {
+    roles: BTreeMap<String, Role>,
+    /// `None` until a role is chosen: every command is allowed.
+    active: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Role {
+    allowed: CommandSet,
+    /// Argon2 hash in PHC form, when switching needs a password.
+    password: Option<String>,
+}
+
+impl Roles {
+    pub fn new(config: &BTreeMap<String, RoleConfig>) -> Self {
+        let roles = config
+            .iter()
+            .map(|(name, role)| {
+                let role = Role {
+                    allowed: CommandSet::new(&role.allow),
+                    password: role.password.clone(),
+                };
+                (name.clone(), role)
+            })
+            .collect();
+        Self {
+            roles,
+            active: None,
+        }
+    }
+
+    /// Start in role `name`, password or not: whoever starts the master
+    /// already holds its config.
+    pub fn start_as(&mut self, name: &str) -> Result<(), String> {
+        self.role(name)?;
+        self.active = Some(name.to_string());
+        Ok(())
+    }
+
+    /// `role <name> [password]`: switch to another role.
+    pub fn switch(&mut self, name: &str, password: Option<&str>) -> Result<(), String> {
+        let role = self.role(name)?;
+        if let Some(hash) = &role.password {
+            let password = password.ok_or_else(|| format!("role {} needs a password", name))?;
+            if !verify_password(hash, password) {
+                return Err(format!("wrong password for role {}", name));
+            }
+        }
+        self.active = Some(name.to_string());
+        Ok(())
+    }
+
+    fn role(&self, name: &str) -> Result<&Role, String> {
+        self.roles.get(name).ok_or_else(|| {
+            if self.roles.is_empty() {
+                format!("unknown role {}; tix-master.toml defines none", name)
+            } else {
+                let known: Vec<&str> = self.roles.keys().map(String::as_str).collect();
+                format!("unknown role {}; known: {}", name, known.join(", "))
+            }
+        })
+    }
+
+    /// Name of the role in force, if any.
+    pub fn active(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    pub fn permits(&self, cmd: Command) -> bool {
+        self.active
+            .as_ref()
+            .and_then(|name| self.roles.get(name))
+            .is_none_or(|role| role.allowed.contains(cmd))
+    }
+
+    /// The commands the role in force allows.
+    pub fn allowed(&self) -> CommandSet {
+        match self.active.as_ref().and_then(|name| self.roles.get(name)) {
+            Some(role) => role.allowed.clone(),
+            None => CommandSet::all(),
+        }
+    }
+
+    /// What `role` without arguments shows.
+    pub fn describe(&self) -> String {
+        let mut out = match &self.active {
+            Some(name) => {
+                let allowed = self.allowed();
+                let names: Vec<String> = allowed.iter().map(|cmd| cmd.to_string()).collect();
+                format!("Role {}: {}", name, names.join(", "))
+            }
+            None => "No role: every command is allowed".to_string(),
+        };
+        if !self.roles.is_empty() {
+            let known: Vec<&str> = self.roles.keys().map(String::as_str).collect();
+            out.push_str(&format!("\nRoles: {}", known.join(", ")));
+        }
+        out
+    }
+}
+
+/// Split what follows `role` into a role name and a password.
+pub fn parse(rest: &str) -> Result<Option<(String, Option<String>)>, String> {
+    let mut args = split_args(rest)?.into_iter();
+    let Some(name) = args.next() else {
+        return Ok(None);
+    };
+    let password = args.next();
+    if args.next().is_some() {
+        return Err("Usage: role [<name> [<password>]]".to_string());
+    }
+    Ok(Some((name, password)))
+}
+
+/// `line` as it may be shown and remembered: a `role` password masked.
+pub fn redact(line: &str) -> String {
+    let Some(rest) = line.strip_prefix("role ") else {
+        return line.to_string();
+    };
+    let name_len = rest.trim_start().find(char::is_whitespace);
+    match name_len {
+        Some(len) => {
+            let start = line.len() - rest.trim_start().len() + len;
+            let (kept, secret) = line.split_at(start);
+            let masked: String = secret
+                .chars()
+                .map(|c| if c.is_whitespace() { c } else { '*' })
+                .collect();
+            format!("{}{}", kept, masked)
+        }
+        None => line.to_string(),
+    }
+}
+
+/// Hash `password` for a role's `password` setting.
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Whether `hash` is something [`verify_password`] can check against.
+pub fn is_password_hash(hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| hash.algorithm.as_str().starts_with("argon2"))
+}
+
+fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roles() -> Roles {
+        let mut config = BTreeMap::new();
+        config.insert(
+            "viewer".to_string(),
+            RoleConfig {
+                allow: vec![
+                    "Ping".to_string(),
+                    "ListDir".to_string(),
+                    "Screen*".to_string(),
+                ],
+                password: None,
+            },
+        );
+        config.insert(
+            "admin".to_string(),
+            RoleConfig {
+                allow: vec!["*".to_string()],
+                password: Some(hash_password("hunter2").unwrap()),
+            },
+        );
+        Roles::new(&config)
+    }
+
+    #[test]
+    fn a_role_holds_back_what_it_does_not_allow() {
+        let mut roles = roles();
+        assert!(roles.permits(Command::ShellExecute));
+        roles.start_as("viewer").unwrap();
+        assert_eq!(roles.active(), Some("viewer"));
+        assert!(roles.permits(Command::ListDir));
+        assert!(roles.permits(Command::ScreenStart));
+        assert!(!roles.permits(Command::ShellExecute));
+        assert!(!roles.permits(Command::SystemAction));
+        assert_eq!(roles.allowed().len(), 9);
+        assert!(
+            roles
+                .start_as("root")
+                .unwrap_err()
+                .contains("known: admin, viewer")
+        );
+    }
+
+    #[test]
+    fn switching_checks_the_password() {
+        let mut roles = roles();
+        roles.start_as("viewer").unwrap();
+        assert_eq!(
+            roles.switch("admin", None).unwrap_err(),
+            "role admin needs a password"
+        );
+        assert_eq!(
+            roles.switch("admin", Some("hunter3")).unwrap_err(),
+            "wrong password for role admin"
+        );
+        assert_eq!(roles.active(), Some("viewer"));
+        roles.switch("admin", Some("hunter2")).unwrap();
+        assert!(roles.permits(Command::ShellExecute));
+        // Going back to a role without a password needs none.
+        roles.switch("viewer", None).unwrap();
+        assert!(!roles.permits(Command::ShellExecute));
+    }
+
+    #[test]
+    fn hashes_are_argon2() {
+        let hash = hash_password("pw").unwrap();
+        assert!(hash.starts_with("$argon2id$"), "{}", hash);
+        assert!(is_password_hash(&hash));
+        assert!(!is_password_hash("pw"));
+        assert!(verify_password(&hash, "pw"));
+        assert!(!verify_password("pw", "pw"));
+    }
+
+    #[test]
+    fn passwords_are_masked() {
+        assert_eq!(parse("").unwrap(), None);
+        assert_eq!(
+            parse(" admin \"two words\"").unwrap(),
+            Some(("admin".to_string(), Some("two words".to_string())))
+        );
+        assert!(parse(" admin pw extra").is_err());
+        assert_eq!(redact("role admin hunter2"), "role admin *******");
+        assert_eq!(redact("role  admin a b"), "role  admin * *");
+        assert_eq!(redact("role viewer"), "role viewer");
+        assert_eq!(redact("ShellExecute role x y"), "ShellExecute role x y");
+    }
+}
@@ -0,0 +1,302 @@
+//! The `schedule` console command: commands the slave runs on its own.
+//!
+//! `schedule add <name> <interval> [-n <runs>] -- <command>` has the
+//! slave run a shell command every interval (10s at the least), until it
+//! has run `-n` times or the schedule is deleted. `schedule list` shows
+//! them all, `schedule del <name>` removes one and `schedule results
+//! <name>` prints the recent runs the slave kept. Runs that finish while
+//! the master is connected are also logged as they arrive.
+
+use std::time::Duration;
+
+use tix_core::format::format_duration;
+use tix_core::protocol::{
+    ScheduleCreateRequest, ScheduleInfo, ScheduleResponse, ScheduleRunReport, ScheduleRunResult,
+};
+
+use crate::args::{split_args, split_options};
+use crate::eventlog::format_utc;
+use crate::table::format_table;
+use crate::watch::parse_duration;
+
+const USAGE: &str = "schedule add <name> <interval> [-n <runs>] -- <command> \
+                     | list | del <name> | results <name>";
+
+/// A parsed `schedule` command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleCommand {
+    Create(ScheduleCreateRequest),
+    List,
+    Delete(String),
+    Results(String),
+}
+
+impl ScheduleCommand {
+    /// Parse what follows `schedule`.
+    pub fn parse(rest: &str) -> Result<Self, String> {
+        let rest = rest.trim();
+        let (sub, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        match sub {
+            "add" => parse_add(args).map(Self::Create),
+            "" | "list" | "ls" if args.trim().is_empty() => Ok(Self::List),
+            "del" | "delete" | "rm" => one_name(args).map(Self::Delete),
+            "results" => one_name(args).map(Self::Results),
+            _ => Err(format!("Usage: {}", USAGE)),
+        }
+    }
+}
+
+/// `<name> <interval> [-n <runs>] -- <command>`.
+fn parse_add(args: &str) -> Result<ScheduleCreateRequest, String> {
+    let (options, command) = split_options(args)?;
+    let command = command
+        .filter(|c| !c.is_empty())
+        .ok_or_else(|| format!("Usage: {}", USAGE))?;
+    let mut positional = Vec::new();
+    let mut max_runs = None;
+    let mut options = options.into_iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "-n" | "--runs" => {
+                max_runs = Some(
+                    options
+                        .next()
+                        .and_then(|n| n.parse::<u32>().ok())
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("{} needs a number of runs", option))?,
+                );
+            }
+            flag if flag.starts_with('-') => return Err(format!("Usage: {}", USAGE)),
+            _ => positional.push(option),
+        }
+    }
+    let [name, interval] = positional.as_slice() else {
+        return Err(format!("Usage: {}", USAGE));
+    };
+    let mut req = ScheduleCreateRequest::new(name, command, parse_duration(interval)?);
+    if let Some(runs) = max_runs {
+        req = req.with_max_runs(runs);
+    }
+    req.validate()?;
+    Ok(req)
+}
+
+fn one_name(args: &str) -> Result<String, String> {
+    match split_args(args)?.as_slice() {
+        [name] => Ok(name.clone()),
+        _ => Err(format!("Usage: {}", USAGE)),
+    }
+}
+
+/// The slave's answer to any `schedule` command; refusals are `Err`.
+pub fn describe(response: ScheduleResponse) -> Result<String, String> {
+    match response {
+        ScheduleResponse::Created(info) => Ok(format!(
+            "Schedule {} created: every {}{}, first run in {}",
+            info.name,
+            format_duration(Duration::from_secs(info.interval_secs)),
+            info.max_runs
+                .map(|n| format!(", {} run{}", n, if n == 1 { "" } else { "s" }))
+                .unwrap_or_default(),
+            format_duration(Duration::from_millis(info.next_run_ms.unwrap_or(0)))
+        )),
+        ScheduleResponse::Schedules(list) => Ok(describe_list(&list)),
+        ScheduleResponse::Deleted(name) => Ok(format!("Schedule {} deleted", name)),
+        ScheduleResponse::Results { name, results } => Ok(describe_results(&name, &results)),
+        ScheduleResponse::Error(e) => Err(format!("Schedule: {}", e)),
+    }
+}
+
+fn describe_list(list: &[ScheduleInfo]) -> String {
+    let rows: Vec<Vec<String>> = list
+        .iter()
+        .map(|s| {
+            let mut runs = match s.max_runs {
+                Some(max) => format!("{}/{}", s.runs, max),
+                None => s.runs.to_string(),
+            };
+            if s.skipped > 0 {
+                runs.push_str(&format!(" ({} skipped)", s.skipped));
+            }
+            vec![
+                s.name.clone(),
+                format_duration(Duration::from_secs(s.interval_secs)),
+                runs,
+                s.next_run_ms
+                    .map(|ms| format_duration(Duration::from_millis(ms)))
+                    .unwrap_or_else(|| "done".to_string()),
+                s.last
+                    .as_ref()
+                    .map(outcome)
+                    .unwrap_or_else(|| "-".to_string()),
+                s.command.clone(),
+            ]
+        })
+        .collect();
+    let mut out = format!("{} schedules", rows.len());
+    if !rows.is_empty() {
+        out.push('\n');
+        out.push_str(&format_table(
+            &["Name", "Every", "Runs", "Next", "Last", "Command"],
+            &rows,
+        ));
+    }
+    out
+}
+
+fn describe_results(name: &str, results: &[ScheduleRunResult]) -> String {
+    let mut out = format!("{} kept results of {}", results.len(), name);
+    for result in results {
+        out.push_str(&format!(
+            "\n#{} {} UTC: {}",
+            result.run,
+            format_utc(result.started_ms),
+            outcome(result)
+        ));
+        for line in result.output.lines() {
+            out.push_str("\n  ");
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+/// `exit 0 in 1.2s`, or why the run did not finish.
+fn outcome(result: &ScheduleRunResult) -> String {
+    let elapsed = format_duration(Duration::from_millis(result.elapsed_ms));
+    match (&result.error, result.exit_code) {
+        (Some(error), _) => format!("{} after {}", error, elapsed),
+        (None, Some(code)) => format!("exit {} in {}", code, elapsed),
+        (None, None) => format!("ended in {}", elapsed),
+    }
+}
+
+/// Log line for a run the slave pushed as it finished.
+pub fn format_report(report: &ScheduleRunReport) -> String {
+    let mut line = format!(
+        "[SCHD] {} run {}: {}",
+        report.name,
+        report.result.run,
+        outcome(&report.result)
+    );
+    if let Some(first) = report.result.output.lines().find(|l| !l.trim().is_empty()) {
+        line.push_str(&format!(" — {}", first.trim()));
+    }
+    if report.finished {
+        line.push_str(" (last run)");
+    }
+    line
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_subcommands() {
+        let add = ScheduleCommand::parse(r#"add disk-free 5m -n 3 -- dir "C:\Program Files""#);
+        assert_eq!(
+            add,
+            Ok(ScheduleCommand::Create(
+                ScheduleCreateRequest::new(
+                    "disk-free",
+                    r#"dir "C:\Program Files""#,
+                    Duration::from_secs(300)
+                )
+                .with_max_runs(3)
+            ))
+        );
+        assert_eq!(ScheduleCommand::parse(""), Ok(ScheduleCommand::List));
+        assert_eq!(ScheduleCommand::parse(" list"), Ok(ScheduleCommand::List));
+        assert_eq!(
+            ScheduleCommand::parse("del disk-free"),
+            Ok(ScheduleCommand::Delete("disk-free".into()))
+        );
+        assert_eq!(
+            ScheduleCommand::parse("results disk-free"),
+            Ok(ScheduleCommand::Results("disk-free".into()))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_schedules() {
+        for bad in [
+            "add disk 5m",
+            "add disk 5m --",
+            "add disk -- dir",
+            "add disk 5s -- dir",
+            "add disk 5m -n 0 -- dir",
+            "add \"disk free\" 5m -- dir",
+            "add disk 5m -x -- dir",
+            "del",
+            "results a b",
+            "list extra",
+            "pause disk",
+        ] {
+            assert!(ScheduleCommand::parse(bad).is_err(), "{}", bad);
+        }
+        assert!(
+            ScheduleCommand::parse("add disk 5s -- dir")
+                .unwrap_err()
+                .contains("at least 10 seconds")
+        );
+    }
+
+    #[test]
+    fn describes_schedules_and_runs() {
+        let last = ScheduleRunResult {
+            run: 4,
+            started_ms: 1_700_000_000_000,
+            elapsed_ms: 1_200,
+            exit_code: Some(0),
+            output: "12 GB free\n".into(),
+            error: None,
+        };
+        let info = ScheduleInfo {
+            name: "disk".into(),
+            command: "dir".into(),
+            interval_secs: 300,
+            max_runs: Some(10),
+            runs: 4,
+            skipped: 1,
+            next_run_ms: Some(90_000),
+            last: Some(last.clone()),
+        };
+        let list = describe(ScheduleResponse::Schedules(vec![info.clone()])).unwrap();
+        assert!(list.starts_with("1 schedules\n"));
+        assert!(list.contains("4/10 (1 skipped)"));
+        assert!(list.contains("exit 0 in 1.2s"));
+        assert_eq!(
+            describe(ScheduleResponse::Created(info)).unwrap(),
+            "Schedule disk created: every 5m 00s, 10 runs, first run in 1m 30s"
+        );
+
+        let results = describe(ScheduleResponse::Results {
+            name: "disk".into(),
+            results: vec![last.clone()],
+        })
+        .unwrap();
+        assert_eq!(
+            results,
+            "1 kept results of disk\n#4 2023-11-14 22:13:20 UTC: exit 0 in 1.2s\n  12 GB free"
+        );
+        assert!(describe(ScheduleResponse::Error("no schedule named 'x'".into())).is_err());
+
+        let report = ScheduleRunReport {
+            name: "disk".into(),
+            result: ScheduleRunResult {
+                exit_code: None,
+                error: Some("task timed out after 30s".into()),
+                elapsed_ms: 30_000,
+                ..last
+            },
+            finished: true,
+        };
+        assert_eq!(
+            format_report(&report),
+            "[SCHD] disk run 4: task timed out after 30s after 30.0s — 12 GB free (last run)"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! The `find` console command: search a slave directory by name.
+//!
+//! `find [--case] [--dirs] [--max N] [--depth N] <path> <pattern>` sends a
+//! [`FileSearchRequest`]; matches are logged batch by batch as the slave
+//! walks the tree and the search's row in the Tasks panel counts them.
+//! `cancel <id>`, or Esc while a search is running, stops it.
+
+use tix_core::format::format_bytes;
+use tix_core::protocol::{FileMetadata, FileSearchRequest, FileSearchSummary};
+
+/// Start of the Tasks panel status of a running search; the console
+/// cancels the newest such task on Esc.
+pub const SEARCH_STATUS_PREFIX: &str = "Searching";
+
+/// Parse the arguments following `find`.
+pub fn parse_find(args: &[String]) -> Result<FileSearchRequest, String> {
+    const USAGE: &str = "find requires [--case] [--dirs] [--max N] [--depth N] <path> <pattern>";
+    let mut case_sensitive = false;
+    let mut include_dirs = false;
+    let mut max_results = None;
+    let mut max_depth = 0;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut number = |flag: &str| -> Result<u32, String> {
+            args.next()
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(|| format!("{} requires a number", flag))
+        };
+        match arg.as_str() {
+            "--case" => case_sensitive = true,
+            "--dirs" => include_dirs = true,
+            "--max" => max_results = Some(number("--max")?),
+            "--depth" => max_depth = number("--depth")?,
+            _ => positional.push(arg.clone()),
+        }
+    }
+    let [root, pattern] = <[String; 2]>::try_from(positional).map_err(|_| USAGE.to_string())?;
+    let mut req = FileSearchRequest::new(root, pattern)
+        .with_case_sensitive(case_sensitive)
+        .with_include_dirs(include_dirs)
+        .with_max_depth(max_depth);
+    if let Some(max) = max_results {
+        req = req.with_max_results(max);
+    }
+    Ok(req)
+}
+
+/// A `find` waiting on the slave. Its request ID is its Tasks row.
+#[derive(Debug)]
+pub struct Search {
+    pub root: String,
+    pub pattern: String,
+    /// Matches received so far.
+    pub found: u64,
+}
+
+impl Search {
+    pub fn new(req: &FileSearchRequest) -> Self {
+        Self {
+            root: req.root.clone(),
+            pattern: req.pattern.clone(),
+            found: 0,
+        }
+    }
+
+    /// Tasks panel status while the search runs.
+    pub fn status(&self) -> String {
+        format!(
+            "{} {} for {} ({} found)",
+            SEARCH_STATUS_PREFIX, self.root, self.pattern, self.found
+        )
+    }
+}
+
+/// One log line per match.
+pub fn format_match(entry: &FileMetadata) -> String {
+    if entry.is_directory {
+        format!("  {}{}", entry.path, std::path::MAIN_SEPARATOR)
+    } else {
+        format!("  {} ({})", entry.path, format_bytes(entry.size))
+    }
+}
+
+/// Closing log line of a search.
+pub fn format_summary(summary: &FileSearchSummary) -> String {
+    if let Some(error) = &summary.error {
+        return format!("[FIND] Search failed: {}", error);
+    }
+    format!(
+        "[FIND] {} match(es) in {} director(ies), {} ms{}",
+        summary.matches,
+        summary.directories_scanned,
+        summary.elapsed_ms,
+        if summary.truncated {
+            "; stopped at --max"
+        } else {
+            ""
+        }
+    )
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        crate::args::split_args(text).unwrap()
+    }
+
+    #[test]
+    fn parses_flags_and_positionals() {
+        let req = parse_find(&args(
+            r#"--max 50 --dirs "C:\Program Files" *.exe --depth 3"#,
+        ))
+        .unwrap();
+        assert_eq!(req.root, r"C:\Program Files");
+        assert_eq!(req.pattern, "*.exe");
+        assert_eq!(req.max_results, 50);
+        assert_eq!(req.max_depth, 3);
+        assert!(req.include_dirs);
+        assert!(!req.case_sensitive);
+
+        let req = parse_find(&args("/var/log syslog --case")).unwrap();
+        assert!(req.case_sensitive);
+        assert_eq!(
+            req,
+            FileSearchRequest::new("/var/log", "syslog").with_case_sensitive(true)
+        );
+
+        assert!(parse_find(&args("/var/log")).is_err());
+        assert!(
+            parse_find(&args("/ x --max lots"))
+                .unwrap_err()
+                .contains("--max")
+        );
+    }
+
+    #[test]
+    fn summary_mentions_the_cap() {
+        let summary = FileSearchSummary {
+            matches: 10,
+            directories_scanned: 3,
+            elapsed_ms: 12,
+            truncated: true,
+            error: None,
+        };
+        assert!(format_summary(&summary).contains("stopped at --max"));
+        assert!(format_summary(&FileSearchSummary::failed("gone")).contains("failed: gone"));
+    }
+}
@@ -0,0 +1,61 @@
+//! The `selftest` console command: the slave checks it can capture,
+//! inject input, bind a screen socket and write its files, and the
+//! console shows what passed and what to do about the rest.
+
+use tix_core::protocol::SelfTestReport;
+
+use crate::table::format_table;
+
+/// Render the slave's report: a summary line, one row per check, then
+/// the hints of those that did not pass.
+pub fn describe(report: &SelfTestReport) -> String {
+    let rows: Vec<Vec<String>> = report
+        .checks
+        .iter()
+        .map(|check| {
+            vec![
+                check.name.clone(),
+                check.status.to_string(),
+                check.detail.clone(),
+            ]
+        })
+        .collect();
+    let mut out = format!("Self-test on {}: {}\n", report.hostname, report.summary());
+    out.push_str(&format_table(&["Check", "Status", "Detail"], &rows));
+    for check in &report.checks {
+        if let Some(hint) = &check.hint {
+            out.push_str(&format!("\n  {}: {}", check.name, hint));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::protocol::CheckResult;
+
+    #[test]
+    fn report_renders_as_a_table_with_hints() {
+        let report = SelfTestReport {
+            hostname: "DESK-07".into(),
+            checks: vec![
+                CheckResult::pass("session", "session 1, desktop Default"),
+                CheckResult::warn(
+                    "capture monitor 0",
+                    "1920x1080, no frame within 1000 ms at 0,0",
+                    "check nobody has locked it",
+                ),
+            ],
+        };
+        assert_eq!(
+            describe(&report),
+            "Self-test on DESK-07: 2 checks: 1 pass, 1 warn, 0 fail\n\
+             Check              Status  Detail\n\
+             -----------------  ------  -----------------------------------------\n\
+             session            pass    session 1, desktop Default\n\
+             capture monitor 0  warn    1920x1080, no frame within 1000 ms at 0,0\n\
+             \x20 capture monitor 0: check nobody has locked it"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! The `services` and `service` console commands.
+//!
+//! `services [filter]` lists the slave's Windows services as a table.
+//! `service <name> start|stop|restart [-t <secs>]` acts on one, and the
+//! answer comes once the service has settled. Stopping or restarting a
+//! service on the protected list (`[services] protected`) is refused
+//! unless the command ends in `--confirm`; the console asks first and
+//! adds it, while scripts have to spell it out.
+
+use std::time::Duration;
+
+use tix_core::protocol::service::service_name_matches;
+use tix_core::protocol::{
+    ServiceAction, ServiceControlRequest, ServiceControlResponse, ServiceListRequest,
+    ServiceListResponse,
+};
+
+use crate::args::split_args;
+use crate::config::ServicesConfig;
+use crate::table::format_table;
+
+/// Appended to a `service` command to act on a protected service.
+pub const CONFIRM_FLAG: &str = "--confirm";
+
+/// Protected unless the config says otherwise: services whose loss cuts
+/// the slave off or leaves it unprotected.
+pub const DEFAULT_PROTECTED_SERVICES: [&str; 7] = [
+    "RpcSs",
+    "EventLog",
+    "WinDefend",
+    "Dnscache",
+    "LanmanServer",
+    "TermService",
+    "tix*",
+];
+
+const USAGE: &str = "service <name> start|stop|restart [-t <secs>]";
+
+/// `services [filter]`.
+pub fn parse_list(rest: &str) -> Result<ServiceListRequest, String> {
+    match split_args(rest)?.as_slice() {
+        [] => Ok(ServiceListRequest::default()),
+        [filter] => Ok(ServiceListRequest::new(Some(filter.clone()))),
+        _ => Err("services takes at most one filter; quote it if it has spaces".to_string()),
+    }
+}
+
+/// A parsed `service` command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceCommand {
+    pub request: ServiceControlRequest,
+    /// `--confirm` was given.
+    pub confirmed: bool,
+}
+
+impl ServiceCommand {
+    /// Parse what follows `service`, waiting `timeout` unless `-t` says
+    /// otherwise.
+    pub fn parse(rest: &str, timeout: Duration) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut confirmed = false;
+        let mut timeout = timeout;
+        let mut args = split_args(rest)?.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                CONFIRM_FLAG => confirmed = true,
+                "-t" | "--timeout" => {
+                    timeout = args
+                        .next()
+                        .and_then(|secs| secs.parse().ok())
+                        .filter(|secs| *secs > 0)
+                        .map(Duration::from_secs)
+                        .ok_or_else(|| format!("{} needs a number of seconds", arg))?;
+                }
+                _ => positional.push(arg),
+            }
+        }
+        let [name, action] = positional.as_slice() else {
+            return Err(format!("Usage: {}", USAGE));
+        };
+        let action: ServiceAction = action.parse().map_err(|e| format!("{}", e))?;
+        Ok(Self {
+            request: ServiceControlRequest::new(name.as_str(), action).with_timeout(timeout),
+            confirmed,
+        })
+    }
+}
+
+/// The services `service stop` and `service restart` only act on once
+/// confirmed, and how long the slave waits on a service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceGuard {
+    protected: Vec<String>,
+    timeout: Duration,
+}
+
+impl Default for ServiceGuard {
+    fn default() -> Self {
+        Self::new(&ServicesConfig::default())
+    }
+}
+
+impl ServiceGuard {
+    pub fn new(config: &ServicesConfig) -> Self {
+        Self {
+            protected: config.protected.clone(),
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+        }
+    }
+
+    pub fn is_protected(&self, name: &str) -> bool {
+        self.protected
+            .iter()
+            .any(|pattern| service_name_matches(pattern, name))
+    }
+
+    /// Whether `command` takes down a protected service.
+    pub fn needs_confirmation(&self, command: &ServiceCommand) -> bool {
+        command.request.action.interrupts() && self.is_protected(&command.request.name)
+    }
+
+    /// Parse what follows `service` and refuse an unconfirmed action on
+    /// a protected service.
+    pub fn parse(&self, rest: &str) -> Result<ServiceControlRequest, String> {
+        let command = ServiceCommand::parse(rest, self.timeout)?;
+        if self.needs_confirmation(&command) && !command.confirmed {
+            return Err(format!(
+                "{} is a protected service; add {} to {} it",
+                command.request.name, CONFIRM_FLAG, command.request.action
+            ));
+        }
+        Ok(command.request)
+    }
+
+    /// For the console: the question to ask before sending `input`,
+    /// when it is an unconfirmed `service` command that needs it.
+    pub fn confirmation(&self, input: &str) -> Option<String> {
+        let rest = input.trim().strip_prefix("service")?;
+        if !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let command = ServiceCommand::parse(rest, self.timeout).ok()?;
+        if command.confirmed || !self.needs_confirmation(&command) {
+            return None;
+        }
+        let action = match command.request.action {
+            ServiceAction::Restart => "Restart",
+            _ => "Stop",
+        };
+        Some(format!(
+            "{} protected service '{}' on the slave?",
+            action, command.request.name
+        ))
+    }
+}
+
+/// Log text for a `ServiceList` reply.
+pub fn describe_list(response: ServiceListResponse) -> Result<String, String> {
+    let services = match response {
+        ServiceListResponse::Services(services) => services,
+        ServiceListResponse::Error { kind, message } => {
+            return Err(format!("Service list {}: {}", kind, message));
+        }
+    };
+    let rows: Vec<Vec<String>> = services
+        .iter()
+        .map(|s| {
+            vec![
+                s.name.clone(),
+                s.state.to_string(),
+                s.start_type.map_or("?".to_string(), |t| t.to_string()),
+                s.display_name.clone(),
+            ]
+        })
+        .collect();
+    let mut out = format!("{} services", rows.len());
+    if !rows.is_empty() {
+        out.push('\n');
+        out.push_str(&format_table(
+            &["Name", "State", "Start", "Display name"],
+            &rows,
+        ));
+    }
+    Ok(out)
+}
+
+/// Log text for a `ServiceControl` reply.
+pub fn describe_control(response: ServiceControlResponse) -> Result<String, String> {
+    match response {
+        ServiceControlResponse::Done { action, service } => Ok(format!(
+            "Service {} ({}): {} done, now {}",
+            service.name, service.display_name, action, service.state
+        )),
+        ServiceControlResponse::Error {
+            kind,
+            message,
+            state,
+        } => {
+            let mut out = format!("Service control {}: {}", kind, message);
+            if let Some(state) = state {
+                out.push_str(&format!(" (now {})", state));
+            }
+            Err(out)
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::protocol::{ServiceErrorKind, ServiceInfo, ServiceStartType, ServiceState};
+
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn parses_service_commands() {
+        let cmd = ServiceCommand::parse(" Spooler restart -t 5", TIMEOUT).unwrap();
+        assert_eq!(cmd.request.name, "Spooler");
+        assert_eq!(cmd.request.action, ServiceAction::Restart);
+        assert_eq!(cmd.request.timeout(), Duration::from_secs(5));
+        assert!(!cmd.confirmed);
+
+        let cmd = ServiceCommand::parse(" \"My Service\" STOP --confirm", TIMEOUT).unwrap();
+        assert_eq!(cmd.request.name, "My Service");
+        assert_eq!(cmd.request.timeout(), TIMEOUT);
+        assert!(cmd.confirmed);
+
+        assert!(ServiceCommand::parse(" Spooler", TIMEOUT).is_err());
+        assert!(ServiceCommand::parse(" Spooler pause", TIMEOUT).is_err());
+        assert!(ServiceCommand::parse(" Spooler stop -t 0", TIMEOUT).is_err());
+
+        assert_eq!(parse_list("").unwrap().filter, None);
+        assert_eq!(
+            parse_list(" \"print spooler\"").unwrap().filter.as_deref(),
+            Some("print spooler")
+        );
+        assert!(parse_list(" a b").is_err());
+    }
+
+    #[test]
+    fn protected_services_need_confirming_to_go_down() {
+        let guard = ServiceGuard::default();
+        assert!(guard.is_protected("rpcss"));
+        assert!(guard.is_protected("tix-rdp-slave"));
+        assert!(!guard.is_protected("Spooler"));
+
+        let err = guard.parse(" WinDefend stop").unwrap_err();
+        assert!(err.contains("--confirm"), "{}", err);
+        assert!(guard.parse(" WinDefend restart --confirm").is_ok());
+        // Starting one is always fine, and so is anything unprotected.
+        assert!(guard.parse(" WinDefend start").is_ok());
+        assert!(guard.parse(" Spooler stop").is_ok());
+
+        let question = guard.confirmation("service RpcSs restart").unwrap();
+        assert!(question.starts_with("Restart protected service 'RpcSs'"));
+        assert_eq!(guard.confirmation("service RpcSs stop --confirm"), None);
+        assert_eq!(guard.confirmation("service Spooler stop"), None);
+        assert_eq!(guard.confirmation("services RpcSs"), None);
+
+        let open = ServiceGuard::new(&ServicesConfig {
+            protected: Vec::new(),
+            timeout_secs: 10,
+        });
+        assert!(open.parse(" RpcSs stop").is_ok());
+    }
+
+    #[test]
+    fn replies_are_described() {
+        let spooler = ServiceInfo {
+            name: "Spooler".into(),
+            display_name: "Print Spooler".into(),
+            state: ServiceState::Running,
+            start_type: Some(ServiceStartType::Automatic),
+        };
+        let table = describe_list(ServiceListResponse::Services(vec![spooler.clone()])).unwrap();
+        assert!(table.starts_with("1 services\nName"));
+        assert!(table.contains("Spooler  running  auto   Print Spooler"));
+
+        let done = describe_control(ServiceControlResponse::Done {
+            action: ServiceAction::Restart,
+            service: spooler,
+        })
+        .unwrap();
+        assert_eq!(
+            done,
+            "Service Spooler (Print Spooler): restart done, now running"
+        );
+
+        let err = describe_control(ServiceControlResponse::error(
+            ServiceErrorKind::AccessDenied,
+            "Spooler: Access is denied.",
+            Some(ServiceState::Running),
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "Service control access denied: Spooler: Access is denied. (now running)"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! Shell output as the slave wrote it.
+//!
+//! The slave streams a command's stdout and stderr as raw
+//! [`ShellOutputChunk`]s and names its OEM code page in the closing
+//! [`ShellExitStatus`]. [`ShellOutputs`] gathers the bytes per request and
+//! renders them once the command exits: UTF-8 when they are, the slave's
+//! code page otherwise (so `dir` on a French Windows keeps its accents and
+//! `tree` its box drawing), and a one-line summary for anything that looks
+//! binary. The bytes of the last few commands are kept for `save-output`.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+use encoding_rs::Encoding;
+use tix_core::format::format_bytes;
+use tix_core::protocol::{ShellExitStatus, ShellOutputChunk};
+
+/// Finished commands whose output `save-output` can still write.
+pub const KEPT_OUTPUTS: usize = 16;
+
+/// Bytes inspected by [`looks_binary`].
+const SAMPLE_LEN: usize = 8 * 1024;
+
+/// Text never has NULs, UTF-16 has one every other byte; one per hundred
+/// is well clear of both.
+const NUL_PER_MILLE: usize = 10;
+
+/// Whether `data` is better saved than printed: NUL bytes make up at
+/// least 1% of its first 8 KiB, and no byte order mark says otherwise.
+pub fn looks_binary(data: &[u8]) -> bool {
+    let sample = &data[..data.len().min(SAMPLE_LEN)];
+    if sample.is_empty() || Encoding::for_bom(sample).is_some() {
+        return false;
+    }
+    let nuls = sample.iter().filter(|&&b| b == 0).count();
+    nuls * 1000 >= sample.len() * NUL_PER_MILLE
+}
+
+/// Decode shell output written in Windows code page `codepage` (0 when
+/// unknown). A byte order mark wins, then valid UTF-8, then the code
+/// page; bytes nothing can make sense of become U+FFFD.
+pub fn decode(data: &[u8], codepage: u32) -> String {
+    if let Some((encoding, bom)) = Encoding::for_bom(data) {
+        let (text, _) = encoding.decode_without_bom_handling(&data[bom..]);
+        return text.into_owned();
+    }
+    if let Ok(text) = std::str::from_utf8(data) {
+        return text.to_string();
+    }
+    let Ok(codepage) = u16::try_from(codepage) else {
+        return String::from_utf8_lossy(data).into_owned();
+    };
+    // The DOS pages cmd.exe writes in (437, 850, 866, ...) are not in
+    // the WHATWG set encoding_rs implements.
+    if let Some(table) = oem_cp::code_table::DECODING_TABLE_CP_MAP.get(&codepage) {
+        return table.decode_string_lossy(data);
+    }
+    match codepage::to_encoding_no_replacement(codepage) {
+        Some(encoding) => encoding.decode_without_bom_handling(data).0.into_owned(),
+        None => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
+/// Output of one shell command.
+#[derive(Debug, Default)]
+struct Capture {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl Capture {
+    fn stream(&self, stderr: bool) -> &[u8] {
+        if stderr { &self.stderr } else { &self.stdout }
+    }
+
+    fn render(&self, req_id: u64, stderr: bool, codepage: u32) -> String {
+        let data = self.stream(stderr);
+        if looks_binary(data) {
+            let flag = if stderr { " --stderr" } else { "" };
+            return format!(
+                "<{} binary output — use `save-output {} <file>{}` to save>",
+                format_bytes(data.len() as u64),
+                req_id,
+                flag
+            );
+        }
+        decode(data, codepage)
+    }
+}
+
+/// Shell output by request ID.
+#[derive(Debug, Default)]
+pub struct ShellOutputs {
+    running: HashMap<u64, Capture>,
+    /// Oldest first, at most [`KEPT_OUTPUTS`].
+    finished: VecDeque<(u64, Capture)>,
+}
+
+impl ShellOutputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a chunk of a running command's output.
+    pub fn push(&mut self, req_id: u64, chunk: ShellOutputChunk) {
+        let capture = self.running.entry(req_id).or_default();
+        if chunk.is_stdout {
+            capture.stdout.extend(chunk.data);
+        } else {
+            capture.stderr.extend(chunk.data);
+        }
+    }
+
+    /// The command exited: render what it wrote, in the
+    /// `stdout: ..\nstderr: ..\nExit Code: N` form of a console reply,
+    /// after a `shell: ..` line when the slave names the interpreter.
+    pub fn finish(&mut self, req_id: u64, exit: &ShellExitStatus) -> String {
+        let capture = self.running.remove(&req_id).unwrap_or_default();
+        let mut stderr = capture.render(req_id, true, exit.codepage);
+        if let Some(error) = &exit.error {
+            if !stderr.is_empty() && !stderr.ends_with('\n') {
+                stderr.push('\n');
+            }
+            stderr.push_str(error);
+        }
+        let shell = if exit.shell.is_empty() {
+            String::new()
+        } else {
+            format!("shell: {}\n", exit.shell)
+        };
+        let text = format!(
+            "{}stdout: {}\nstderr: {}\nExit Code: {}",
+            shell,
+            capture.render(req_id, false, exit.codepage),
+            stderr,
+            exit.exit_code
+        );
+        self.keep(req_id, capture);
+        text
+    }
+
+    /// A slave that answers in one packet, already rendered as text by
+    /// its side.
+    pub fn finish_legacy(&mut self, req_id: u64, payload: &[u8]) -> String {
+        self.running.remove(&req_id);
+        let capture = Capture {
+            stdout: payload.to_vec(),
+            stderr: Vec::new(),
+        };
+        let text = capture.render(req_id, false, 0);
+        self.keep(req_id, capture);
+        text
+    }
+
+    /// Write the raw stdout (or stderr) of a finished command to `path`.
+    /// Returns the number of bytes written.
+    pub fn save(&self, req_id: u64, path: &Path, stderr: bool) -> Result<u64, String> {
+        let (_, capture) = self
+            .finished
+            .iter()
+            .find(|(id, _)| *id == req_id)
+            .ok_or_else(|| format!("No saved output for ReqID {}", req_id))?;
+        let data = capture.stream(stderr);
+        std::fs::write(path, data).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(data.len() as u64)
+    }
+
+    /// Drop the output of a command that failed instead of exiting.
+    pub fn discard(&mut self, req_id: u64) {
+        self.running.remove(&req_id);
+    }
+
+    /// The slave went away; its running commands will not finish.
+    pub fn abort_running(&mut self) {
+        self.running.clear();
+    }
+
+    fn keep(&mut self, req_id: u64, capture: Capture) {
+        if self.finished.len() == KEPT_OUTPUTS {
+            self.finished.pop_front();
+        }
+        self.finished.push_back((req_id, capture));
+    }
+}
+
+/// Parse the arguments following `save-output`: `<req_id> <file>
+/// [--stderr]`.
+pub fn parse_save_output(args: &[String]) -> Result<(u64, String, bool), String> {
+    const USAGE: &str = "save-output requires <req_id> <file> [--stderr]";
+    let stderr = args.iter().any(|a| a == "--stderr");
+    let positional: Vec<&String> = args.iter().filter(|a| *a != "--stderr").collect();
+    let [id, file] = positional.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+    let id = id.parse().map_err(|_| USAGE.to_string())?;
+    Ok((id, file.to_string(), stderr))
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dir` on a French Windows: "Répertoire de C:\" in CP850.
+    const CP850_DIR: &[u8] = b"R\x82pertoire de C:\\";
+
+    /// The top of `tree` in CP850: ╔══ and └──.
+    const CP850_TREE: &[u8] = b"\xc9\xcd\xcd \xc0\xc4\xc4";
+
+    #[test]
+    fn utf8_passes_through() {
+        assert_eq!(
+            decode("Répertoire — 東京".as_bytes(), 850),
+            "Répertoire — 東京"
+        );
+        assert_eq!(decode(b"", 850), "");
+    }
+
+    #[test]
+    fn oem_output_is_decoded_with_the_slave_code_page() {
+        assert_eq!(decode(CP850_DIR, 850), "Répertoire de C:\\");
+        assert_eq!(decode(CP850_TREE, 850), "╔══ └──");
+        // The same bytes in CP437 agree on the box drawing.
+        assert_eq!(decode(CP850_TREE, 437), "╔══ └──");
+    }
+
+    #[test]
+    fn ansi_output_is_decoded_with_the_slave_code_page() {
+        assert_eq!(decode(b"caf\xe9 \x80 5", 1252), "café € 5");
+        assert_eq!(decode(b"\xc0\xe1", 1251), "Аб");
+    }
+
+    #[test]
+    fn unknown_code_pages_fall_back_to_replacement() {
+        assert_eq!(decode(CP850_DIR, 0), "R\u{fffd}pertoire de C:\\");
+        assert_eq!(decode(CP850_DIR, 70_000), "R\u{fffd}pertoire de C:\\");
+    }
+
+    #[test]
+    fn byte_order_marks_win_over_the_code_page() {
+        assert_eq!(decode(b"\xff\xfeh\x00i\x00", 850), "hi");
+        assert_eq!(decode(b"\xef\xbb\xbfhi", 850), "hi");
+    }
+
+    #[test]
+    fn binary_is_told_apart_from_text() {
+        assert!(!looks_binary(b""));
+        assert!(!looks_binary(CP850_TREE));
+        assert!(!looks_binary(&b"line of text\r\n".repeat(1000)));
+        // An executable's header.
+        let mut exe = b"MZ\x90\x00\x03\x00\x00\x00\x04\x00\x00\x00\xff\xff\x00\x00".to_vec();
+        exe.extend(std::iter::repeat_n(0x41, 200));
+        assert!(looks_binary(&exe));
+        // A single NUL in a long text is not enough.
+        let mut text = b"a".repeat(5000);
+        text[2500] = 0;
+        assert!(!looks_binary(&text));
+        // Only the start is sampled.
+        let mut late = b"a".repeat(SAMPLE_LEN);
+        late.extend(vec![0; 4096]);
+        assert!(!looks_binary(&late));
+        // `cmd /u` output is text despite its NULs, once it says so.
+        assert!(!looks_binary(b"\xff\xfeh\x00i\x00"));
+    }
+
+    #[test]
+    fn binary_output_is_summarized_and_can_be_saved() {
+        let mut outputs = ShellOutputs::new();
+        let blob: Vec<u8> = b"MZ\x90\x00\x03\x00\x00\x00"
+            .iter()
+            .copied()
+            .cycle()
+            .take(1229)
+            .collect();
+        outputs.push(7, ShellOutputChunk::stdout(0, blob[..1000].to_vec()));
+        outputs.push(7, ShellOutputChunk::stderr(1, CP850_DIR.to_vec()));
+        outputs.push(7, ShellOutputChunk::stdout(2, blob[1000..].to_vec()));
+        let text = outputs.finish(7, &ShellExitStatus::success(0, 3).with_codepage(850));
+        assert_eq!(
+            text,
+            "stdout: <1.2 KiB binary output — use `save-output 7 <file>` to save>\n\
+             stderr: Répertoire de C:\\\nExit Code: 0"
+        );
+
+        let dir = std::env::temp_dir().join(format!("tix-shell-output-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(outputs.save(7, &dir.join("out.bin"), false), Ok(1229));
+        assert_eq!(std::fs::read(dir.join("out.bin")).unwrap(), blob);
+        assert_eq!(outputs.save(7, &dir.join("err.txt"), true), Ok(17));
+        assert_eq!(std::fs::read(dir.join("err.txt")).unwrap(), CP850_DIR);
+        assert!(outputs.save(8, &dir.join("x"), false).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_starts_report_their_error() {
+        let mut outputs = ShellOutputs::new();
+        let text = outputs.finish(3, &ShellExitStatus::failed("program not found"));
+        assert_eq!(text, "stdout: \nstderr: program not found\nExit Code: -1");
+    }
+
+    #[test]
+    fn names_the_shell_the_slave_ran() {
+        let mut outputs = ShellOutputs::new();
+        outputs.push(4, ShellOutputChunk::stdout(0, b"ab3".to_vec()));
+        let exit =
+            ShellExitStatus::success(0, 1).with_shell("pwsh -NoProfile -NonInteractive -Command");
+        assert_eq!(
+            outputs.finish(4, &exit),
+            "shell: pwsh -NoProfile -NonInteractive -Command\nstdout: ab3\nstderr: \nExit Code: 0"
+        );
+    }
+
+    #[test]
+    fn only_the_latest_outputs_are_kept() {
+        let mut outputs = ShellOutputs::new();
+        for id in 1..=KEPT_OUTPUTS as u64 + 1 {
+            outputs.push(id, ShellOutputChunk::stdout(0, b"x".to_vec()));
+            outputs.finish(id, &ShellExitStatus::success(0, 1));
+        }
+        let path = std::env::temp_dir().join(format!("tix-shell-kept-{}", std::process::id()));
+        assert!(outputs.save(1, &path, false).is_err());
+        assert_eq!(outputs.save(2, &path, false), Ok(1));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn save_output_arguments() {
+        let args = |s: &[&str]| s.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            parse_save_output(&args(&["7", "out.bin"])),
+            Ok((7, "out.bin".to_string(), false))
+        );
+        assert_eq!(
+            parse_save_output(&args(&["--stderr", "7", "err.txt"])),
+            Ok((7, "err.txt".to_string(), true))
+        );
+        assert!(parse_save_output(&args(&["seven", "out.bin"])).is_err());
+        assert!(parse_save_output(&args(&["7"])).is_err());
+    }
+}
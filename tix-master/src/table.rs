@@ -0,0 +1,37 @@
+//! Plain-text table rendering for the log pane.
+
+/// Render `rows` under `headers` as left-aligned, space-padded columns.
+///
+/// The last column is never padded so long values (command lines,
+/// binary dumps) do not drag trailing whitespace into the log.
+pub fn format_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let render_row = |cells: Vec<&str>| -> String {
+        let last = cells.len().saturating_sub(1);
+        let mut line = String::new();
+        for (i, (cell, width)) in cells.iter().zip(&widths).enumerate() {
+            if i == last {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{:<width$}  ", cell, width = width));
+            }
+        }
+        line.trim_end().to_string()
+    };
+
+    let separators: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+
+    let mut lines = Vec::with_capacity(rows.len() + 2);
+    lines.push(render_row(headers.to_vec()));
+    lines.push(render_row(separators.iter().map(String::as_str).collect()));
+    for row in rows {
+        lines.push(render_row(row.iter().map(String::as_str).collect()));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,478 @@
+//! The Tasks sidebar: one row per request, search, watch or host copy.
+//!
+//! The master reports a task with `MasterEvent::TaskStarted` (what was
+//! sent) and then `MasterEvent::TaskUpdate` (its status text). A
+//! [`TaskList`] keeps one [`TaskRow`] per task with the times it was sent
+//! and resolved, and [`TaskPanel`] draws them: a spinner and the time so
+//! far while a task is in flight, how long it took once it is done
+//! (`< 12 > ShellExecute dir — done in 3.4s`). Times are worked out at
+//! draw time, so the 50 ms UI tick keeps them current.
+//!
+//! Finished rows are kept for [`DEFAULT_TASK_HISTORY`] tasks, or
+//! `task_history` under `[ui]`; rows still in flight always stay.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{List, ListItem, Widget};
+use tix_core::format::format_duration;
+
+use crate::theme::Theme;
+
+/// Finished rows kept when the config does not say.
+pub const DEFAULT_TASK_HISTORY: usize = 20;
+
+/// Longest argument summary a row shows.
+const SUMMARY_CHARS: usize = 32;
+
+/// How long each spinner frame shows.
+const SPINNER_FRAME: Duration = Duration::from_millis(100);
+
+/// Statuses of tasks still waiting on the slave or the host.
+const IN_FLIGHT: [&str; 6] = [
+    "Waiting",
+    "Uploading",
+    "Running",
+    "Searching",
+    "Reading",
+    "Copying",
+];
+
+/// Statuses of tasks that run until cancelled.
+const ONGOING: [&str; 1] = ["Watching"];
+
+/// What a row is about: a request ID, or a task on the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskId {
+    Request(u64),
+    Local(&'static str),
+}
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Request(id) => write!(f, "{}", id),
+            Self::Local(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Where a task is, going by its status text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Sent and not answered yet.
+    InFlight,
+    /// Runs until cancelled, like a `watch`.
+    Ongoing,
+    /// Solved, failed, timed out or cancelled.
+    Done,
+}
+
+impl Phase {
+    pub fn of(status: &str) -> Self {
+        if IN_FLIGHT.iter().any(|p| status.starts_with(p)) {
+            Self::InFlight
+        } else if ONGOING.iter().any(|p| status.starts_with(p)) {
+            Self::Ongoing
+        } else {
+            Self::Done
+        }
+    }
+}
+
+/// One task in the sidebar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskRow {
+    pub id: TaskId,
+    /// The command sent, e.g. `ShellExecute`; `None` when the master
+    /// only reported a status.
+    pub command: Option<String>,
+    /// Its arguments, shortened, e.g. `dir`.
+    pub summary: String,
+    /// The last status the master reported.
+    pub status: String,
+    pub sent: Instant,
+    /// When the status turned final.
+    pub finished: Option<Instant>,
+}
+
+impl TaskRow {
+    pub fn phase(&self) -> Phase {
+        Phase::of(&self.status)
+    }
+
+    /// The command and its arguments, e.g. `ShellExecute dir`.
+    pub fn label(&self) -> Option<String> {
+        let command = self.command.as_deref()?;
+        if self.summary.is_empty() {
+            Some(command.to_string())
+        } else {
+            Some(format!("{} {}", command, self.summary))
+        }
+    }
+
+    /// What follows the label at `now`: the status and the time so far
+    /// while in flight, the outcome and how long it took once done.
+    pub fn progress(&self, now: Instant) -> String {
+        match (self.phase(), self.finished) {
+            (Phase::Done, Some(finished)) => {
+                let took = format_duration(finished.saturating_duration_since(self.sent));
+                match self.status.strip_prefix("Solved") {
+                    Some(rest) => format!("done in {}{}", took, rest),
+                    None => format!("{} after {}", self.status, took),
+                }
+            }
+            (Phase::InFlight, _) => format!(
+                "{} {}",
+                self.status,
+                format_elapsed(now.saturating_duration_since(self.sent))
+            ),
+            _ => self.status.clone(),
+        }
+    }
+}
+
+/// The arguments of command line `line` for a row's summary: the words
+/// after the command, cut to [`SUMMARY_CHARS`] with `...`.
+pub fn summarize(line: &str) -> String {
+    let args = line
+        .split_whitespace()
+        .skip(1)
+        .collect::<Vec<_>>()
+        .join(" ");
+    if args.chars().count() <= SUMMARY_CHARS {
+        return args;
+    }
+    let cut: String = args.chars().take(SUMMARY_CHARS - 3).collect();
+    format!("{}...", cut)
+}
+
+/// Time a task has been in flight, in whole seconds under a minute
+/// (`0s`, `12s`) so it ticks once a second, then as
+/// [`format_duration`] (`1m 05s`).
+pub fn format_elapsed(elapsed: Duration) -> String {
+    if elapsed < Duration::from_secs(60) {
+        format!("{}s", elapsed.as_secs())
+    } else {
+        format_duration(elapsed)
+    }
+}
+
+/// The rows of the Tasks sidebar, oldest first.
+#[derive(Debug, Clone)]
+pub struct TaskList {
+    rows: Vec<TaskRow>,
+    /// Finished rows to keep.
+    history: usize,
+}
+
+impl Default for TaskList {
+    fn default() -> Self {
+        Self::with_history(DEFAULT_TASK_HISTORY)
+    }
+}
+
+impl TaskList {
+    pub fn with_history(history: usize) -> Self {
+        Self {
+            rows: Vec::new(),
+            history,
+        }
+    }
+
+    pub fn rows(&self) -> &[TaskRow] {
+        &self.rows
+    }
+
+    pub fn get(&self, id: &TaskId) -> Option<&TaskRow> {
+        self.rows.iter().find(|row| row.id == *id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Task `id` was sent at `now` as `command` with `summary`.
+    pub fn start(&mut self, id: TaskId, command: &str, summary: &str, now: Instant) {
+        let row = self.row(id, now);
+        row.command = Some(command.to_string());
+        row.summary = summary.to_string();
+    }
+
+    /// Task `id` reported `status` at `now`; a task not seen before gets
+    /// a row of its own.
+    pub fn update(&mut self, id: TaskId, status: &str, now: Instant) {
+        let row = self.row(id, now);
+        row.status = status.to_string();
+        match row.phase() {
+            Phase::Done => row.finished = row.finished.or(Some(now)),
+            // Finished and started over, like a second host copy.
+            _ if row.finished.take().is_some() => row.sent = now,
+            _ => {}
+        }
+        self.age_out();
+    }
+
+    /// The row of `id`, added at `now` if missing.
+    fn row(&mut self, id: TaskId, now: Instant) -> &mut TaskRow {
+        let index = match self.rows.iter().position(|row| row.id == id) {
+            Some(index) => index,
+            None => {
+                self.rows.push(TaskRow {
+                    id,
+                    command: None,
+                    summary: String::new(),
+                    status: String::new(),
+                    sent: now,
+                    finished: None,
+                });
+                self.rows.len() - 1
+            }
+        };
+        &mut self.rows[index]
+    }
+
+    /// Drop the earliest finished rows past the history.
+    fn age_out(&mut self) {
+        let mut excess = self
+            .rows
+            .iter()
+            .filter(|row| row.finished.is_some())
+            .count()
+            .saturating_sub(self.history);
+        if excess == 0 {
+            return;
+        }
+        let mut finished: Vec<Instant> = self.rows.iter().filter_map(|row| row.finished).collect();
+        finished.sort();
+        let cutoff = finished[excess - 1];
+        self.rows.retain(|row| match row.finished {
+            Some(at) if at <= cutoff && excess > 0 => {
+                excess -= 1;
+                false
+            }
+            _ => true,
+        });
+    }
+}
+
+/// The Tasks sidebar's list, drawn as of `now`.
+pub struct TaskPanel<'a> {
+    pub tasks: &'a TaskList,
+    pub theme: &'a Theme,
+    pub now: Instant,
+    /// Turn the spinner; a still one for reduced motion.
+    pub animate: bool,
+}
+
+impl TaskPanel<'_> {
+    fn style(&self, row: &TaskRow) -> ratatui::style::Style {
+        let theme = self.theme;
+        let status = row.status.as_str();
+        if status.starts_with("Running") || status.starts_with("Solved") {
+            theme.success
+        } else if status.starts_with("Waiting") {
+            theme.warning
+        } else if status.starts_with("Watching") {
+            theme.focus
+        } else if status.starts_with("Failed") {
+            theme.danger
+        } else if status.starts_with("Panicked") {
+            theme.reversed(theme.danger)
+        } else {
+            theme.text
+        }
+    }
+
+    fn spinner(&self, row: &TaskRow) -> &'static str {
+        let frames = self.theme.icons.spinner;
+        if !self.animate {
+            return frames[0];
+        }
+        let elapsed = self.now.saturating_duration_since(row.sent);
+        let frame = elapsed.as_millis() / SPINNER_FRAME.as_millis();
+        frames[(frame % frames.len() as u128) as usize]
+    }
+
+    fn line(&self, row: &TaskRow) -> Line<'static> {
+        let style = self.style(row);
+        let mut spans = vec![Span::styled(format!("< {} > ", row.id), style)];
+        if row.phase() == Phase::InFlight {
+            spans.push(Span::styled(format!("{} ", self.spinner(row)), style));
+        }
+        if let Some(label) = row.label() {
+            spans.push(Span::styled(label, self.theme.value));
+            spans.push(Span::styled(self.theme.icons.dash, self.theme.muted));
+        }
+        spans.push(Span::styled(row.progress(self.now), style));
+        Line::from(spans)
+    }
+}
+
+impl Widget for TaskPanel<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let items: Vec<ListItem> = self
+            .tasks
+            .rows()
+            .iter()
+            .map(|row| ListItem::new(self.line(row)))
+            .collect();
+        List::new(items).render(area, buf);
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::ThemeName;
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_millis((s * 1000.0).round() as u64)
+    }
+
+    #[test]
+    fn rows_follow_their_task_from_send_to_done() {
+        let t0 = Instant::now();
+        let mut tasks = TaskList::default();
+        tasks.start(TaskId::Request(12), "ShellExecute", "dir", t0);
+        tasks.update(TaskId::Request(12), "Waiting...", t0);
+        tasks.update(TaskId::Request(12), "Running", t0 + secs(1.0));
+        assert_eq!(tasks.len(), 1);
+        let row = tasks.get(&TaskId::Request(12)).unwrap();
+        assert_eq!(row.phase(), Phase::InFlight);
+        assert_eq!(row.label().as_deref(), Some("ShellExecute dir"));
+        assert_eq!(row.progress(t0 + secs(2.9)), "Running 2s");
+
+        tasks.update(TaskId::Request(12), "Solved", t0 + secs(3.4));
+        let row = tasks.get(&TaskId::Request(12)).unwrap();
+        assert_eq!(row.finished, Some(t0 + secs(3.4)));
+        // Done: the time stops.
+        assert_eq!(row.progress(t0 + secs(60.0)), "done in 3.4s");
+
+        // A status without a start gets a row too, timed from then.
+        tasks.update(TaskId::Local("host copy"), "Copying to C:\\x", t0);
+        let row = tasks.get(&TaskId::Local("host copy")).unwrap();
+        assert_eq!(row.label(), None);
+        assert_eq!(row.progress(t0 + secs(75.0)), "Copying to C:\\x 1m 15s");
+    }
+
+    #[test]
+    fn outcomes_read_as_sentences() {
+        let t0 = Instant::now();
+        let mut tasks = TaskList::default();
+        let cases = [
+            ("Solved: 3 found", "done in 1.5s: 3 found"),
+            ("Failed", "Failed after 1.5s"),
+            ("Timed out", "Timed out after 1.5s"),
+            (
+                "Watching C:\\a every 5s (0 changes)",
+                "Watching C:\\a every 5s (0 changes)",
+            ),
+        ];
+        for (id, (status, shown)) in cases.into_iter().enumerate() {
+            let id = TaskId::Request(id as u64);
+            tasks.update(id.clone(), "Waiting...", t0);
+            tasks.update(id.clone(), status, t0 + secs(1.5));
+            assert_eq!(tasks.get(&id).unwrap().progress(t0 + secs(9.0)), shown);
+        }
+    }
+
+    #[test]
+    fn finished_rows_age_out_oldest_first() {
+        let t0 = Instant::now();
+        let mut tasks = TaskList::with_history(2);
+        tasks.update(TaskId::Request(1), "Waiting...", t0);
+        for id in 2..=5 {
+            tasks.update(TaskId::Request(id), "Waiting...", t0);
+        }
+        // Finished out of order: 4 first, then 2, 5 and 3.
+        for (n, id) in [4, 2, 5, 3].into_iter().enumerate() {
+            tasks.update(TaskId::Request(id), "Solved", t0 + secs(n as f64));
+        }
+        let ids: Vec<String> = tasks.rows().iter().map(|row| row.id.to_string()).collect();
+        // 1 is still in flight; 5 and 3 finished last.
+        assert_eq!(ids, ["1", "3", "5"]);
+
+        // A task coming back to life is in flight again.
+        tasks.update(TaskId::Request(5), "Running", t0 + secs(9.0));
+        let row = tasks.get(&TaskId::Request(5)).unwrap();
+        assert_eq!((row.finished, row.sent), (None, t0 + secs(9.0)));
+
+        let mut none = TaskList::with_history(0);
+        none.update(TaskId::Request(1), "Failed", t0);
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn summaries_keep_the_arguments_short() {
+        assert_eq!(summarize("ShellExecute  dir   /b"), "dir /b");
+        assert_eq!(summarize("Ping"), "");
+        let long = summarize("ListDir C:\\Users\\me\\Documents\\Projects\\tix");
+        assert_eq!(long, "C:\\Users\\me\\Documents\\Project...");
+        assert_eq!(long.chars().count(), SUMMARY_CHARS);
+    }
+
+    #[test]
+    fn elapsed_ticks_in_whole_seconds() {
+        assert_eq!(format_elapsed(secs(0.4)), "0s");
+        assert_eq!(format_elapsed(secs(12.9)), "12s");
+        assert_eq!(format_elapsed(secs(65.0)), "1m 05s");
+        assert_eq!(format_elapsed(secs(3725.0)), "1h 02m");
+    }
+
+    #[test]
+    fn panel_snapshot() {
+        use ratatui::{Terminal, backend::TestBackend};
+
+        let t0 = Instant::now();
+        let mut tasks = TaskList::default();
+        tasks.start(TaskId::Request(12), "ShellExecute", "dir", t0);
+        tasks.update(TaskId::Request(12), "Solved", t0 + secs(3.4));
+        tasks.start(TaskId::Request(13), "ListDir", "C:\\", t0 + secs(4.0));
+        tasks.update(TaskId::Request(13), "Waiting...", t0 + secs(4.0));
+        tasks.update(TaskId::Local("host copy"), "Copying to D:\\", t0);
+        tasks.update(
+            TaskId::Local("host copy"),
+            "Copied 2 files to D:\\",
+            t0 + secs(2.5),
+        );
+        let theme = Theme::named(ThemeName::Ascii);
+        let draw = |now, animate| {
+            let panel = TaskPanel {
+                tasks: &tasks,
+                theme: &theme,
+                now,
+                animate,
+            };
+            let mut terminal = Terminal::new(TestBackend::new(48, 3)).unwrap();
+            terminal
+                .draw(|frame| frame.render_widget(panel, frame.area()))
+                .unwrap();
+            let buf = terminal.backend().buffer().clone();
+            (0..3)
+                .map(|y| (0..48).map(|x| buf[(x, y)].symbol()).collect::<String>())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            draw(t0 + secs(16.2), true),
+            [
+                "< 12 > ShellExecute dir - done in 3.4s          ",
+                "< 13 > - ListDir C:\\ - Waiting... 12s           ",
+                "< host copy > Copied 2 files to D:\\ after 2.5s  ",
+            ]
+        );
+        // The spinner turns every frame, unless motion is reduced.
+        assert!(draw(t0 + secs(16.3), true)[1].starts_with("< 13 > \\ "));
+        assert!(draw(t0 + secs(16.3), false)[1].starts_with("< 13 > | "));
+    }
+}
@@ -0,0 +1,336 @@
+//! Console colours, borders and icons.
+//!
+//! Every style the console draws with comes from a [`Theme`], picked in
+//! `tix-master.toml` or with the `theme` command:
+//!
+//! - `default` — the cyan/dark-gray palette with emoji file icons.
+//! - `high-contrast` — bold white on black, no dimmed text, for
+//!   monochrome terminals and colour-vision deficiencies.
+//! - `ascii` — the default colours with `[D]`/`[F]` icons and `+-|`
+//!   borders, for fonts without emoji or box-drawing glyphs.
+
+use std::fmt;
+use std::str::FromStr;
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::border;
+use serde::{Deserialize, Serialize};
+
+/// The built-in themes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Default,
+    HighContrast,
+    Ascii,
+}
+
+impl ThemeName {
+    pub const ALL: [ThemeName; 3] = [
+        ThemeName::Default,
+        ThemeName::HighContrast,
+        ThemeName::Ascii,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ThemeName::Default => "default",
+            ThemeName::HighContrast => "high-contrast",
+            ThemeName::Ascii => "ascii",
+        }
+    }
+}
+
+impl fmt::Display for ThemeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ThemeName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|name| name.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|n| n.as_str()).collect();
+                format!("unknown theme '{}' (one of: {})", s, names.join(", "))
+            })
+    }
+}
+
+/// Markers drawn in front of entries and log lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Icons {
+    pub dir: &'static str,
+    pub dir_open: &'static str,
+    pub file: &'static str,
+    /// Junction, symbolic link or other reparse point.
+    pub link: &'static str,
+    /// Request sent to the slave.
+    pub send: &'static str,
+    /// Reply or completion from the slave.
+    pub recv: &'static str,
+    /// Gutter in front of `watch` diff lines.
+    pub gutter: &'static str,
+    /// Filled and empty cells of a progress bar.
+    pub bar_full: &'static str,
+    pub bar_empty: &'static str,
+    /// Frames of the spinner on tasks in flight.
+    pub spinner: &'static [&'static str],
+    /// Between a task's command and its progress.
+    pub dash: &'static str,
+}
+
+const EMOJI_ICONS: Icons = Icons {
+    dir: "📁 ",
+    dir_open: "📂 ",
+    file: "📄 ",
+    link: "🔗 ",
+    send: "→ ",
+    recv: "← ",
+    gutter: "│",
+    bar_full: "█",
+    bar_empty: "░",
+    spinner: &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+    dash: " — ",
+};
+
+const ASCII_ICONS: Icons = Icons {
+    dir: "[D] ",
+    dir_open: "[D] ",
+    file: "[F] ",
+    link: "[L] ",
+    send: "-> ",
+    recv: "<- ",
+    gutter: "|",
+    bar_full: "#",
+    bar_empty: "-",
+    spinner: &["|", "/", "-", "\\"],
+    dash: " - ",
+};
+
+/// Borders drawn with `+`, `-` and `|` only.
+pub const ASCII_BORDER: border::Set<'static> = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// Styles by role, so render code never names a colour itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub name: ThemeName,
+    /// Ordinary labels and inactive tabs.
+    pub text: Style,
+    /// Secondary text: sent/received log lines, extra slave details.
+    pub muted: Style,
+    /// Values next to labels.
+    pub value: Style,
+    /// Panel borders.
+    pub border: Style,
+    /// Border and title of the focused panel or popup.
+    pub focus: Style,
+    /// Panel titles and section labels.
+    pub title: Style,
+    /// Titles of log, task and action panels.
+    pub heading: Style,
+    /// Selected tab or suggestion.
+    pub selected: Style,
+    /// Cursor row in the file trees.
+    pub cursor: Style,
+    /// Search matches in the pager.
+    pub matched: Style,
+    pub success: Style,
+    pub warning: Style,
+    pub danger: Style,
+    pub info: Style,
+    /// Addresses.
+    pub address: Style,
+    /// Paged-response references and memory figures.
+    pub accent: Style,
+    /// Whether [`faint`](Self::faint) may dim text.
+    pub dim: bool,
+    pub border_set: border::Set<'static>,
+    /// Border of the outermost console frame.
+    pub frame_set: border::Set<'static>,
+    pub icons: Icons,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::named(ThemeName::Default)
+    }
+}
+
+impl Theme {
+    pub fn named(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Default => Self::colour(),
+            ThemeName::HighContrast => Self::high_contrast(),
+            ThemeName::Ascii => Self {
+                name,
+                border_set: ASCII_BORDER,
+                frame_set: ASCII_BORDER,
+                icons: ASCII_ICONS,
+                ..Self::colour()
+            },
+        }
+    }
+
+    fn colour() -> Self {
+        let fg = |color| Style::default().fg(color);
+        Self {
+            name: ThemeName::Default,
+            text: fg(Color::Gray),
+            muted: fg(Color::DarkGray),
+            value: fg(Color::White),
+            border: fg(Color::DarkGray),
+            focus: fg(Color::Cyan),
+            title: fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            heading: fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            selected: Style::default()
+                .bg(Color::Cyan)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            cursor: Style::default().bg(Color::Cyan).fg(Color::Black),
+            matched: Style::default().fg(Color::Black).bg(Color::Yellow),
+            success: fg(Color::Green),
+            warning: fg(Color::Yellow),
+            danger: fg(Color::Red),
+            info: fg(Color::Blue),
+            address: fg(Color::Yellow),
+            accent: fg(Color::Magenta),
+            dim: true,
+            border_set: border::PLAIN,
+            frame_set: border::THICK,
+            icons: EMOJI_ICONS,
+        }
+    }
+
+    fn high_contrast() -> Self {
+        let plain = Style::default().fg(Color::White).bg(Color::Black);
+        let bold = plain.add_modifier(Modifier::BOLD);
+        let inverse = Style::default()
+            .fg(Color::Black)
+            .bg(Color::White)
+            .add_modifier(Modifier::BOLD);
+        Self {
+            name: ThemeName::HighContrast,
+            text: plain,
+            muted: plain,
+            value: bold,
+            border: plain,
+            focus: bold,
+            title: bold,
+            heading: bold,
+            selected: inverse,
+            cursor: inverse,
+            matched: inverse,
+            success: bold,
+            warning: bold,
+            danger: bold,
+            info: plain,
+            address: bold,
+            accent: bold,
+            dim: false,
+            border_set: border::PLAIN,
+            frame_set: border::THICK,
+            icons: EMOJI_ICONS,
+        }
+    }
+
+    /// `style` toned down for hints, unless the theme forbids dimming.
+    pub fn faint(&self, style: Style) -> Style {
+        if self.dim {
+            style.add_modifier(Modifier::DIM)
+        } else {
+            style
+        }
+    }
+
+    /// `style` in bold.
+    pub fn bold(&self, style: Style) -> Style {
+        style.add_modifier(Modifier::BOLD)
+    }
+
+    /// `style` with its colours swapped, for what must not be missed.
+    pub fn reversed(&self, style: Style) -> Style {
+        style.add_modifier(Modifier::REVERSED)
+    }
+
+    /// Italic for quoted slave output; high contrast keeps it upright.
+    pub fn quote(&self, style: Style) -> Style {
+        if self.dim {
+            style.add_modifier(Modifier::ITALIC)
+        } else {
+            style
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for name in ThemeName::ALL {
+            assert_eq!(name.as_str().parse::<ThemeName>(), Ok(name));
+            assert_eq!(Theme::named(name).name, name);
+        }
+        assert_eq!("High-Contrast".parse(), Ok(ThemeName::HighContrast));
+        assert!("neon".parse::<ThemeName>().unwrap_err().contains("ascii"));
+    }
+
+    #[test]
+    fn high_contrast_never_dims() {
+        let theme = Theme::named(ThemeName::HighContrast);
+        let style = theme.faint(theme.success);
+        assert!(!style.add_modifier.contains(Modifier::DIM));
+        for style in [theme.text, theme.muted, theme.border, theme.title] {
+            assert_eq!(style.fg, Some(Color::White));
+            assert!(!style.add_modifier.contains(Modifier::DIM));
+        }
+        assert!(
+            Theme::default()
+                .faint(Style::default())
+                .add_modifier
+                .contains(Modifier::DIM)
+        );
+    }
+
+    #[test]
+    fn ascii_glyphs_are_ascii() {
+        let theme = Theme::named(ThemeName::Ascii);
+        let icons = theme.icons;
+        for glyph in [
+            icons.dir,
+            icons.dir_open,
+            icons.file,
+            icons.link,
+            icons.send,
+            icons.recv,
+            icons.gutter,
+            icons.bar_full,
+            icons.bar_empty,
+            icons.dash,
+            theme.border_set.top_left,
+            theme.border_set.horizontal_top,
+            theme.frame_set.vertical_left,
+        ] {
+            assert!(glyph.is_ascii(), "{glyph:?}");
+        }
+        assert!(icons.spinner.iter().all(|frame| frame.is_ascii()));
+    }
+}
@@ -0,0 +1,1468 @@
+//! The transfer queue behind the file explorer's transfers panel.
+//!
+//! `Upload <local>|<remote>` with a source on this machine, and every
+//! `Download <remote>|<local>`, become a job of one item per file; a
+//! folder upload is an item for each file under it. [`Transfers`] runs
+//! the items one at a time, in the order they were queued, and is the
+//! state machine behind `transfer pause|resume|cancel|retry`: every
+//! change returns the [`Action`]s the master has to carry out on the
+//! wire. The master reports each change as a [`TransferUpdate`], which
+//! the console's [`TransferPanel`] keeps per item, with a rate and ETA.
+//!
+//! Uploads stream with `FileWrite` (see [`tix_core::network::upload`]):
+//! pausing holds the chunks back and cancelling has the slave delete
+//! what it wrote. Downloads hash the remote file for its size, then
+//! delta-sync it into `<dest>.part`, which is renamed once the hash
+//! matches. Pausing stops the sync and keeps the `.part`, so resuming
+//! only fetches the chunks still missing. A cancelled or failed
+//! download's `.part` is deleted unless `transfer.resumable` is on, in
+//! which case a retry continues from it.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tix_core::TixError;
+use tix_core::format::format_bytes;
+use tix_core::network::upload::Flow;
+use tix_core::protocol::{FileChunk, FileDigest, FileHashVerification, FileWriteProgress};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+use crate::remote_path;
+use crate::table::format_table;
+
+/// Finished items kept for the panel and `transfers`.
+pub const KEEP_FINISHED: usize = 50;
+
+/// Shortest gap between two progress updates of a download.
+pub const PROGRESS_EVERY: Duration = Duration::from_millis(250);
+
+/// Weight of the newest sample in the panel's smoothed rate.
+const RATE_SMOOTHING: f64 = 0.3;
+
+// ── Items ────────────────────────────────────────────────────────
+
+/// Which way an item moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From this machine to the slave.
+    Upload,
+    /// From the slave to this machine.
+    Download,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Upload => write!(f, "upload"),
+            Self::Download => write!(f, "download"),
+        }
+    }
+}
+
+/// Where an item stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferState {
+    Queued,
+    Active,
+    Paused,
+    Failed,
+    Done,
+    Cancelled,
+}
+
+impl TransferState {
+    /// Whether the item only moves again if retried.
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Failed | Self::Done | Self::Cancelled)
+    }
+}
+
+impl fmt::Display for TransferState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Queued => write!(f, "queued"),
+            Self::Active => write!(f, "active"),
+            Self::Paused => write!(f, "paused"),
+            Self::Failed => write!(f, "failed"),
+            Self::Done => write!(f, "done"),
+            Self::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// One item, as the master reports it to the console.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferUpdate {
+    pub id: u64,
+    /// Request ID of the command that queued it, as in the Tasks sidebar.
+    pub job: u64,
+    pub direction: Direction,
+    pub source: String,
+    pub dest: String,
+    /// Bytes the destination holds so far.
+    pub bytes: u64,
+    /// Size of the file, once known.
+    pub total: Option<u64>,
+    pub state: TransferState,
+    /// Why the item failed.
+    pub error: Option<String>,
+}
+
+impl TransferUpdate {
+    /// How far along the item is, from 0 to 1; `None` while the size is
+    /// unknown.
+    pub fn fraction(&self) -> Option<f64> {
+        let total = self.total?;
+        if total == 0 {
+            return Some(if self.state == TransferState::Done {
+                1.0
+            } else {
+                0.0
+            });
+        }
+        Some((self.bytes as f64 / total as f64).min(1.0))
+    }
+}
+
+// ── Queue ────────────────────────────────────────────────────────
+
+/// What the master has to do after a change to the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Start the item: from the top, or from its `.part` for a download.
+    Start(u64),
+    /// Let a held upload send again.
+    Unpause(u64),
+    /// Hold a running upload's chunks back.
+    Hold(u64),
+    /// Stop a running download, keeping its `.part`.
+    Halt(u64),
+    /// Stop a running item for good. `remove_part` says whether a
+    /// download's `.part` goes too; an upload's partial file is the
+    /// slave's to delete.
+    Abort { id: u64, remove_part: bool },
+    /// Delete the `.part` of a download that is not running.
+    Discard(u64),
+}
+
+/// How a job ended, once none of its items is left to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobOutcome {
+    pub direction: Direction,
+    /// Destination of the first item.
+    pub dest: String,
+    pub files: usize,
+    pub done: usize,
+    pub cancelled: usize,
+    /// Bytes of the items that are done.
+    pub bytes: u64,
+    /// Error of the first item that failed.
+    pub error: Option<String>,
+}
+
+impl JobOutcome {
+    /// Whether every item arrived.
+    pub fn is_success(&self) -> bool {
+        self.done == self.files
+    }
+
+    /// The job's line in the console, e.g. `Upload complete: C:\a.txt
+    /// (1.5 KiB)`.
+    pub fn describe(&self) -> String {
+        let what = match self.direction {
+            Direction::Upload => "Upload",
+            Direction::Download => "Download",
+        };
+        let files = if self.files == 1 {
+            self.dest.clone()
+        } else {
+            format!("{} files", self.files)
+        };
+        if self.is_success() {
+            return format!(
+                "{} complete: {} ({})",
+                what,
+                files,
+                format_bytes(self.bytes)
+            );
+        }
+        let failed = self.files - self.done - self.cancelled;
+        match &self.error {
+            Some(error) if failed > 0 => format!(
+                "{} failed: {} of {} file(s) did not arrive: {}",
+                what,
+                self.files - self.done,
+                self.files,
+                error
+            ),
+            _ => format!(
+                "{} cancelled: {} of {} file(s) arrived",
+                what, self.done, self.files
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Item {
+    info: TransferUpdate,
+    /// A task or request is working on the item: an upload that is held
+    /// stays live, a halted download does not.
+    live: bool,
+}
+
+/// Queued, running and recently finished transfer items.
+#[derive(Debug, Default)]
+pub struct Transfers {
+    items: Vec<Item>,
+    next_id: u64,
+    resumable: bool,
+    /// Jobs whose outcome was already reported.
+    settled: HashSet<u64>,
+}
+
+impl Transfers {
+    /// An empty queue; `resumable` keeps the `.part` of downloads that
+    /// are cancelled or fail.
+    pub fn new(resumable: bool) -> Self {
+        Self {
+            resumable,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_resumable(&self) -> bool {
+        self.resumable
+    }
+
+    /// Item `id`, if it is still kept.
+    pub fn get(&self, id: u64) -> Option<&TransferUpdate> {
+        self.item(id).map(|item| &item.info)
+    }
+
+    /// Every item kept, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &TransferUpdate> {
+        self.items.iter().map(|item| &item.info)
+    }
+
+    /// The item running now.
+    pub fn active(&self) -> Option<u64> {
+        self.items
+            .iter()
+            .find(|item| item.info.state == TransferState::Active)
+            .map(|item| item.info.id)
+    }
+
+    /// Queue a file of job `job`; it starts once [`schedule`](Self::schedule)
+    /// gets to it.
+    pub fn enqueue(
+        &mut self,
+        job: u64,
+        direction: Direction,
+        source: String,
+        dest: String,
+        total: Option<u64>,
+    ) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.settled.remove(&job);
+        self.items.push(Item {
+            info: TransferUpdate {
+                id,
+                job,
+                direction,
+                source,
+                dest,
+                bytes: 0,
+                total,
+                state: TransferState::Queued,
+                error: None,
+            },
+            live: false,
+        });
+        self.prune();
+        id
+    }
+
+    /// Start the oldest queued item if nothing is running.
+    pub fn schedule(&mut self) -> Option<Action> {
+        if self.active().is_some() {
+            return None;
+        }
+        let item = self
+            .items
+            .iter_mut()
+            .find(|item| item.info.state == TransferState::Queued)?;
+        item.info.state = TransferState::Active;
+        Some(if item.live {
+            Action::Unpause(item.info.id)
+        } else {
+            Action::Start(item.info.id)
+        })
+    }
+
+    /// Pause item `id`: a running one stops sending or fetching, and the
+    /// next queued item starts; a queued one waits until resumed.
+    pub fn pause(&mut self, id: u64) -> Result<Vec<Action>, String> {
+        let item = self.item_mut(id)?;
+        let action = match item.info.state {
+            TransferState::Queued => None,
+            TransferState::Active if item.info.direction == Direction::Upload => {
+                Some(Action::Hold(id))
+            }
+            TransferState::Active => {
+                item.live = false;
+                Some(Action::Halt(id))
+            }
+            state => return Err(format!("transfer {} is {}", id, state)),
+        };
+        item.info.state = TransferState::Paused;
+        Ok(action.into_iter().chain(self.schedule()).collect())
+    }
+
+    /// Queue paused item `id` again.
+    pub fn resume(&mut self, id: u64) -> Result<Vec<Action>, String> {
+        let item = self.item_mut(id)?;
+        if item.info.state != TransferState::Paused {
+            return Err(format!("transfer {} is {}", id, item.info.state));
+        }
+        item.info.state = TransferState::Queued;
+        Ok(self.schedule().into_iter().collect())
+    }
+
+    /// Give item `id` up. A partial destination is deleted, except the
+    /// `.part` of a download when resumable.
+    pub fn cancel(&mut self, id: u64) -> Result<Vec<Action>, String> {
+        let resumable = self.resumable;
+        let item = self.item_mut(id)?;
+        let was_active = item.info.state == TransferState::Active;
+        let action = match item.info.state {
+            TransferState::Done | TransferState::Cancelled => {
+                return Err(format!("transfer {} is {}", id, item.info.state));
+            }
+            _ if item.live => Some(Action::Abort {
+                id,
+                remove_part: !resumable,
+            }),
+            _ if item.info.direction == Direction::Download && !resumable => {
+                Some(Action::Discard(id))
+            }
+            _ => None,
+        };
+        item.live = false;
+        item.info.state = TransferState::Cancelled;
+        let next = if was_active { self.schedule() } else { None };
+        Ok(action.into_iter().chain(next).collect())
+    }
+
+    /// Queue failed or cancelled item `id` again.
+    pub fn retry(&mut self, id: u64) -> Result<Vec<Action>, String> {
+        let item = self.item_mut(id)?;
+        if !matches!(
+            item.info.state,
+            TransferState::Failed | TransferState::Cancelled
+        ) {
+            return Err(format!("transfer {} is {}", id, item.info.state));
+        }
+        item.info.state = TransferState::Queued;
+        item.info.error = None;
+        let job = item.info.job;
+        self.settled.remove(&job);
+        Ok(self.schedule().into_iter().collect())
+    }
+
+    /// Mark item `id` as being worked on, once its run is in place.
+    pub fn started(&mut self, id: u64) {
+        if let Ok(item) = self.item_mut(id) {
+            item.live = true;
+            item.info.bytes = 0;
+        }
+    }
+
+    /// Record how far item `id` has got.
+    pub fn progress(&mut self, id: u64, bytes: u64, total: Option<u64>) {
+        if let Ok(item) = self.item_mut(id) {
+            item.info.bytes = bytes;
+            if total.is_some() {
+                item.info.total = total;
+            }
+        }
+    }
+
+    /// Item `id` arrived or failed; start the next one. A failed
+    /// download's `.part` goes unless resumable.
+    pub fn finish(&mut self, id: u64, result: Result<(), String>) -> Vec<Action> {
+        let resumable = self.resumable;
+        let mut actions = Vec::new();
+        if let Ok(item) = self.item_mut(id)
+            && item.info.state == TransferState::Active
+        {
+            item.live = false;
+            match result {
+                Ok(()) => {
+                    item.info.state = TransferState::Done;
+                    if let Some(total) = item.info.total {
+                        item.info.bytes = total;
+                    }
+                }
+                Err(error) => {
+                    item.info.state = TransferState::Failed;
+                    item.info.error = Some(error);
+                    if item.info.direction == Direction::Download && !resumable {
+                        actions.push(Action::Discard(id));
+                    }
+                }
+            }
+        }
+        actions.extend(self.schedule());
+        self.prune();
+        actions
+    }
+
+    /// Fail every item that has not finished, e.g. when the slave goes.
+    /// Returns the items that changed.
+    pub fn fail_all(&mut self, reason: &str) -> Vec<u64> {
+        let mut changed = Vec::new();
+        for item in &mut self.items {
+            if !item.info.state.is_finished() {
+                item.live = false;
+                item.info.state = TransferState::Failed;
+                item.info.error = Some(reason.to_string());
+                changed.push(item.info.id);
+            }
+        }
+        changed
+    }
+
+    /// How job `job` ended, the first time it is asked once none of its
+    /// items is left to run.
+    pub fn settle(&mut self, job: u64) -> Option<JobOutcome> {
+        if self.settled.contains(&job) {
+            return None;
+        }
+        let mut items = self.iter().filter(|info| info.job == job).peekable();
+        let first = items.peek()?;
+        let mut outcome = JobOutcome {
+            direction: first.direction,
+            dest: first.dest.clone(),
+            files: 0,
+            done: 0,
+            cancelled: 0,
+            bytes: 0,
+            error: None,
+        };
+        for info in items {
+            outcome.files += 1;
+            match info.state {
+                TransferState::Done => {
+                    outcome.done += 1;
+                    outcome.bytes += info.bytes;
+                }
+                TransferState::Cancelled => outcome.cancelled += 1,
+                TransferState::Failed => {
+                    if outcome.error.is_none() {
+                        outcome.error = info.error.clone();
+                    }
+                }
+                _ => return None,
+            }
+        }
+        self.settled.insert(job);
+        Some(outcome)
+    }
+
+    /// `transfers`: every item kept, as a table.
+    pub fn describe(&self) -> String {
+        if self.items.is_empty() {
+            return "No transfers".to_string();
+        }
+        let rows: Vec<Vec<String>> = self
+            .iter()
+            .map(|info| {
+                let progress = match info.total {
+                    Some(total) => {
+                        format!("{} / {}", format_bytes(info.bytes), format_bytes(total))
+                    }
+                    None => format_bytes(info.bytes),
+                };
+                let state = match &info.error {
+                    Some(error) => format!("{}: {}", info.state, error),
+                    None => info.state.to_string(),
+                };
+                vec![
+                    info.id.to_string(),
+                    info.job.to_string(),
+                    info.direction.to_string(),
+                    info.source.clone(),
+                    info.dest.clone(),
+                    progress,
+                    state,
+                ]
+            })
+            .collect();
+        format_table(
+            &[
+                "ID",
+                "Job",
+                "Direction",
+                "Source",
+                "Destination",
+                "Progress",
+                "State",
+            ],
+            &rows,
+        )
+    }
+
+    fn item(&self, id: u64) -> Option<&Item> {
+        self.items.iter().find(|item| item.info.id == id)
+    }
+
+    fn item_mut(&mut self, id: u64) -> Result<&mut Item, String> {
+        self.items
+            .iter_mut()
+            .find(|item| item.info.id == id)
+            .ok_or_else(|| format!("no transfer {}", id))
+    }
+
+    /// Forget the oldest finished items beyond [`KEEP_FINISHED`].
+    fn prune(&mut self) {
+        let finished = self
+            .items
+            .iter()
+            .filter(|item| item.info.state.is_finished())
+            .count();
+        let mut excess = finished.saturating_sub(KEEP_FINISHED);
+        self.items.retain(|item| {
+            if excess > 0 && item.info.state.is_finished() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+}
+
+// ── Console commands ─────────────────────────────────────────────
+
+/// Parsed `transfer <verb> <id>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferCommand {
+    Pause(u64),
+    Resume(u64),
+    Cancel(u64),
+    Retry(u64),
+}
+
+impl TransferCommand {
+    /// Parse the arguments following `transfer`.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        const USAGE: &str = "transfer requires pause|resume|cancel|retry <id>";
+        let [verb, id] = args else {
+            return Err(USAGE.to_string());
+        };
+        let id = id
+            .parse::<u64>()
+            .map_err(|_| format!("transfer: {:?} is not a transfer ID", id))?;
+        match verb.as_str() {
+            "pause" => Ok(Self::Pause(id)),
+            "resume" => Ok(Self::Resume(id)),
+            "cancel" => Ok(Self::Cancel(id)),
+            "retry" => Ok(Self::Retry(id)),
+            _ => Err(USAGE.to_string()),
+        }
+    }
+
+    /// The command that does the same, for key bindings.
+    pub fn to_command(self) -> String {
+        match self {
+            Self::Pause(id) => format!("transfer pause {}", id),
+            Self::Resume(id) => format!("transfer resume {}", id),
+            Self::Cancel(id) => format!("transfer cancel {}", id),
+            Self::Retry(id) => format!("transfer retry {}", id),
+        }
+    }
+}
+
+// ── Planning ─────────────────────────────────────────────────────
+
+/// One file to move: source, destination and size, if known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedFile {
+    pub source: String,
+    pub dest: String,
+    pub size: Option<u64>,
+}
+
+/// The files `Upload <local>|<remote>` sends. `remote` is a folder to
+/// upload into when it ends with a separator or `local` is a folder,
+/// which then goes over as a folder of the same name, a file at a time.
+pub fn plan_upload(local: &Path, remote: &str) -> io::Result<Vec<PlannedFile>> {
+    let sep = remote_path::separator(remote);
+    let into_folder = remote.ends_with(['\\', '/']) || local.is_dir();
+    let remote = remote.trim_end_matches(['\\', '/']);
+    if !into_folder {
+        return Ok(vec![PlannedFile {
+            source: local.display().to_string(),
+            dest: remote.to_string(),
+            size: Some(fs::metadata(local)?.len()),
+        }]);
+    }
+    let name = local
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let base = format!("{}{}{}", remote, sep, name);
+    if !local.is_dir() {
+        return Ok(vec![PlannedFile {
+            source: local.display().to_string(),
+            dest: base,
+            size: Some(fs::metadata(local)?.len()),
+        }]);
+    }
+    let mut files = Vec::new();
+    let mut dirs = vec![(local.to_path_buf(), base)];
+    while let Some((dir, remote_dir)) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let dest = format!(
+                "{}{}{}",
+                remote_dir,
+                sep,
+                entry.file_name().to_string_lossy()
+            );
+            let meta = entry.metadata()?;
+            if meta.is_dir() {
+                dirs.push((entry.path(), dest));
+            } else {
+                files.push(PlannedFile {
+                    source: entry.path().display().to_string(),
+                    dest,
+                    size: Some(meta.len()),
+                });
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Where `Download <remote>|<local>` saves: into `local` when it is a
+/// folder here or ends with a separator, into the working directory
+/// when empty, and as `local` otherwise.
+pub fn download_dest(remote: &str, local: &str) -> PathBuf {
+    let name = remote
+        .trim_end_matches(['\\', '/'])
+        .rsplit(['\\', '/'])
+        .next()
+        .unwrap_or_default();
+    let local_path = Path::new(local);
+    if local.is_empty() {
+        PathBuf::from(name)
+    } else if local.ends_with(['\\', '/']) || local_path.is_dir() {
+        local_path.join(name)
+    } else {
+        local_path.to_path_buf()
+    }
+}
+
+// ── Runs ─────────────────────────────────────────────────────────
+
+/// What is working on a live item.
+#[derive(Debug)]
+pub enum Run {
+    /// Streaming to the slave.
+    Upload {
+        req_id: u64,
+        flow: watch::Sender<Flow>,
+        /// Where the slave's progress reports go.
+        acks: mpsc::UnboundedSender<FileWriteProgress>,
+        task: JoinHandle<Result<[u8; 32], TixError>>,
+    },
+    /// Waiting for the slave's hash of the file.
+    Hashing { req_id: u64 },
+    /// Delta-syncing into the `.part`.
+    Fetching {
+        req_id: u64,
+        part: PartFile,
+        chunk_size: u32,
+        bytes: u64,
+        /// When progress was last reported.
+        reported: Instant,
+    },
+}
+
+impl Run {
+    /// The request the slave answers.
+    pub fn request_id(&self) -> u64 {
+        match self {
+            Self::Upload { req_id, .. }
+            | Self::Hashing { req_id }
+            | Self::Fetching { req_id, .. } => *req_id,
+        }
+    }
+}
+
+// ── Partial downloads ────────────────────────────────────────────
+
+/// Where a download into `dest` is written until its hash checks out.
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+/// Delete the `.part` of a download into `dest`, if there is one.
+pub fn remove_part(dest: &Path) -> io::Result<()> {
+    match fs::remove_file(part_path(dest)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Bytes of `part` a delta sync against `remote` will not fetch again.
+pub fn resumed_bytes(part: &FileDigest, remote: &FileDigest) -> u64 {
+    part.chunks
+        .iter()
+        .zip(&remote.chunks)
+        .filter(|(have, want)| have.hash == want.hash && have.length == want.length)
+        .map(|(have, _)| u64::from(have.length))
+        .sum()
+}
+
+/// The `.part` file of a running download.
+#[derive(Debug)]
+pub struct PartFile {
+    dest: PathBuf,
+    file: File,
+}
+
+impl PartFile {
+    /// Open the `.part` of `dest`, keeping what an earlier attempt left,
+    /// and digest it in `chunk_size` chunks for the delta request.
+    pub fn open(dest: &Path, chunk_size: u32) -> io::Result<(Self, FileDigest)> {
+        if let Some(parent) = dest.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(part_path(dest))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        let digest = FileDigest::of(&data, chunk_size);
+        let part = Self {
+            dest: dest.to_path_buf(),
+            file,
+        };
+        Ok((part, digest))
+    }
+
+    /// Write `chunk` at its offset.
+    pub fn write(&mut self, chunk: &FileChunk) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(chunk.offset))?;
+        self.file.write_all(&chunk.data)
+    }
+
+    /// Cut the file to the size the slave reported, check it against the
+    /// slave's hash and move it into place. A file that fails the check
+    /// stays behind as the `.part`.
+    pub fn finish(mut self, verification: &FileHashVerification) -> Result<(), String> {
+        let part = part_path(&self.dest);
+        let io_err = |e: io::Error| format!("{}: {}", part.display(), e);
+        self.file
+            .set_len(verification.total_bytes)
+            .map_err(io_err)?;
+        self.file.flush().map_err(io_err)?;
+        self.file.seek(SeekFrom::Start(0)).map_err(io_err)?;
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_reader(&mut self.file).map_err(io_err)?;
+        drop(self.file);
+        if *hasher.finalize().as_bytes() != verification.blake3_hash {
+            return Err("hash mismatch".to_string());
+        }
+        fs::rename(&part, &self.dest).map_err(|e| format!("{}: {}", self.dest.display(), e))
+    }
+}
+
+// ── Panel ────────────────────────────────────────────────────────
+
+/// One item in the transfers panel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanelRow {
+    pub update: TransferUpdate,
+    /// Bytes per second while active, smoothed.
+    pub rate: Option<f64>,
+    /// When the byte count last moved, and to what.
+    sample: Option<(Instant, u64)>,
+}
+
+impl PanelRow {
+    /// Time left at the current rate.
+    pub fn eta(&self) -> Option<Duration> {
+        let rate = self.rate.filter(|rate| *rate > 0.0)?;
+        let left = self.update.total?.saturating_sub(self.update.bytes);
+        Some(Duration::from_secs_f64(left as f64 / rate))
+    }
+}
+
+/// What the console shows of the transfer queue, fed by
+/// [`TransferUpdate`]s.
+#[derive(Debug, Default)]
+pub struct TransferPanel {
+    rows: Vec<PanelRow>,
+    selected: usize,
+    visible: bool,
+}
+
+impl TransferPanel {
+    /// Take in `update`, received at `now`.
+    pub fn apply_at(&mut self, update: TransferUpdate, now: Instant) {
+        let Some(row) = self.rows.iter_mut().find(|row| row.update.id == update.id) else {
+            let active = update.state == TransferState::Active;
+            self.rows.push(PanelRow {
+                sample: active.then_some((now, update.bytes)),
+                update,
+                rate: None,
+            });
+            self.prune();
+            return;
+        };
+        if update.state != TransferState::Active {
+            row.rate = None;
+            row.sample = None;
+        } else {
+            match row.sample {
+                Some((at, bytes)) if update.bytes > bytes => {
+                    let secs = now.saturating_duration_since(at).as_secs_f64();
+                    if secs > 0.0 {
+                        let rate = (update.bytes - bytes) as f64 / secs;
+                        row.rate = Some(match row.rate {
+                            Some(old) => old + RATE_SMOOTHING * (rate - old),
+                            None => rate,
+                        });
+                        row.sample = Some((now, update.bytes));
+                    }
+                }
+                // Started over, or the first update since it started.
+                Some((_, bytes)) if update.bytes == bytes => {}
+                _ => {
+                    row.rate = None;
+                    row.sample = Some((now, update.bytes));
+                }
+            }
+        }
+        row.update = update;
+        self.prune();
+    }
+
+    pub fn rows(&self) -> &[PanelRow] {
+        &self.rows
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Index of the selected row.
+    pub fn selected_index(&self) -> usize {
+        self.selected.min(self.rows.len().saturating_sub(1))
+    }
+
+    /// The selected row, if there are any.
+    pub fn selected(&self) -> Option<&PanelRow> {
+        self.rows.get(self.selected_index())
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected_index() + 1 < self.rows.len() {
+            self.selected = self.selected_index() + 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected_index().saturating_sub(1);
+    }
+
+    /// Forget the oldest finished rows beyond [`KEEP_FINISHED`], keeping
+    /// the same row selected where it survives.
+    fn prune(&mut self) {
+        let finished = self
+            .rows
+            .iter()
+            .filter(|row| row.update.state.is_finished())
+            .count();
+        let mut excess = finished.saturating_sub(KEEP_FINISHED);
+        let selected = self.selected().map(|row| row.update.id);
+        self.rows.retain(|row| {
+            if excess > 0 && row.update.state.is_finished() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+        if let Some(id) = selected {
+            self.selected = self
+                .rows
+                .iter()
+                .position(|row| row.update.id == id)
+                .unwrap_or(0);
+        }
+    }
+}
+
+/// A bar of `width` cells, `fraction` of them (rounded down) `full` and
+/// the rest `empty`.
+pub fn progress_bar(fraction: f64, width: usize, full: &str, empty: &str) -> String {
+    let fraction = if fraction.is_nan() {
+        0.0
+    } else {
+        fraction.clamp(0.0, 1.0)
+    };
+    let filled = ((fraction * width as f64).floor() as usize).min(width);
+    format!("{}{}", full.repeat(filled), empty.repeat(width - filled))
+}
+
+/// Column widths of the transfers panel for a given width: the bar
+/// grows with the panel, and source and destination share what the
+/// fixed columns leave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanelColumns {
+    pub path: usize,
+    /// Cells of the progress bar, not counting the percentage after it.
+    pub bar: usize,
+}
+
+const DIR_WIDTH: usize = 8;
+const RATE_WIDTH: usize = 11;
+const ETA_WIDTH: usize = 8;
+const STATE_WIDTH: usize = 9;
+/// ` 100%` after the bar.
+const PERCENT_WIDTH: usize = 5;
+
+impl PanelColumns {
+    pub fn for_width(width: usize) -> Self {
+        let bar = (width / 6).clamp(5, 20);
+        // Six spaces between seven columns.
+        let fixed = DIR_WIDTH + bar + PERCENT_WIDTH + RATE_WIDTH + ETA_WIDTH + STATE_WIDTH + 6;
+        let path = (width.saturating_sub(fixed) / 2).max(4);
+        Self { path, bar }
+    }
+
+    pub fn header(&self) -> String {
+        self.line(["DIR", "SOURCE", "DEST", "PROGRESS", "RATE", "ETA", "STATE"])
+    }
+
+    /// The row of `update`, with its bar, rate and ETA already drawn.
+    pub fn row(&self, update: &TransferUpdate, bar: &str, rate: &str, eta: &str) -> String {
+        self.line([
+            &update.direction.to_string(),
+            &clip(&update.source, self.path),
+            &clip(&update.dest, self.path),
+            bar,
+            rate,
+            eta,
+            &update.state.to_string(),
+        ])
+    }
+
+    fn line(&self, cells: [&str; 7]) -> String {
+        let widths = [
+            DIR_WIDTH,
+            self.path,
+            self.path,
+            self.bar + PERCENT_WIDTH,
+            RATE_WIDTH,
+            ETA_WIDTH,
+            STATE_WIDTH,
+        ];
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell))
+            .collect::<Vec<_>>()
+            .join(" ")
+            .trim_end()
+            .to_string()
+    }
+}
+
+/// `text` cut to `width` characters, keeping its end: the file name
+/// says more than the drive it is on.
+pub fn clip(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len <= width {
+        return text.to_string();
+    }
+    if width <= 2 {
+        return text.chars().skip(len - width).collect();
+    }
+    let tail: String = text.chars().skip(len - (width - 2)).collect();
+    format!("..{}", tail)
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::testing::temp_dir;
+
+    fn queue(resumable: bool, directions: &[Direction]) -> Transfers {
+        let mut transfers = Transfers::new(resumable);
+        for (i, direction) in directions.iter().enumerate() {
+            transfers.enqueue(
+                7,
+                *direction,
+                format!("src{i}"),
+                format!("dst{i}"),
+                Some(100),
+            );
+        }
+        transfers
+    }
+
+    fn state(transfers: &Transfers, id: u64) -> TransferState {
+        transfers.get(id).unwrap().state
+    }
+
+    #[test]
+    fn progress_bars_fill_by_width() {
+        assert_eq!(progress_bar(0.5, 10, "#", "-"), "#####-----");
+        assert_eq!(progress_bar(0.0, 4, "#", "-"), "----");
+        assert_eq!(progress_bar(1.0, 4, "#", "-"), "####");
+        // Only a finished transfer shows a full bar.
+        assert_eq!(progress_bar(0.999, 8, "#", "-"), "#######-");
+        assert_eq!(progress_bar(0.33, 3, "█", "░"), "░░░");
+        assert_eq!(progress_bar(0.34, 3, "█", "░"), "█░░");
+        assert_eq!(progress_bar(0.5, 1, "#", "-"), "-");
+        assert_eq!(progress_bar(0.5, 0, "#", "-"), "");
+        assert_eq!(progress_bar(7.0, 5, "#", "-"), "#####");
+        assert_eq!(progress_bar(-1.0, 5, "#", "-"), "-----");
+        assert_eq!(progress_bar(f64::NAN, 5, "#", "-"), "-----");
+        for width in 0..40 {
+            assert_eq!(progress_bar(0.25, width, "#", "-").len(), width);
+        }
+    }
+
+    #[test]
+    fn panel_columns_fit_the_width() {
+        for width in [80, 120, 200] {
+            let columns = PanelColumns::for_width(width);
+            assert!(columns.header().chars().count() <= width);
+        }
+        assert_eq!(PanelColumns::for_width(30).bar, 5);
+        assert_eq!(PanelColumns::for_width(300).bar, 20);
+
+        let columns = PanelColumns::for_width(120);
+        let update = TransferUpdate {
+            id: 1,
+            job: 1,
+            direction: Direction::Download,
+            source: "C:\\Users\\me\\Documents\\quarterly-report-final.xlsx".into(),
+            dest: "report.xlsx".into(),
+            bytes: 0,
+            total: None,
+            state: TransferState::Paused,
+            error: None,
+        };
+        let row = columns.row(&update, "##--  40%", "1.0 KiB/s", "3s");
+        assert_eq!(row.find("paused"), columns.header().find("STATE"));
+        assert!(row.starts_with("download ..rterly-report-final.xlsx report.xlsx "));
+    }
+
+    #[test]
+    fn clipping_keeps_the_file_name() {
+        assert_eq!(clip("C:\\data\\a.txt", 20), "C:\\data\\a.txt");
+        assert_eq!(clip("C:\\data\\a.txt", 7), "..a.txt");
+        assert_eq!(clip("abcdef", 2), "ef");
+        assert_eq!(clip("abcdef", 0), "");
+    }
+
+    #[test]
+    fn one_item_runs_at_a_time() {
+        let mut transfers = queue(false, &[Direction::Upload, Direction::Download]);
+        assert_eq!(transfers.schedule(), Some(Action::Start(1)));
+        assert_eq!(transfers.schedule(), None);
+        transfers.started(1);
+        assert_eq!(state(&transfers, 2), TransferState::Queued);
+
+        assert_eq!(transfers.finish(1, Ok(())), [Action::Start(2)]);
+        assert_eq!(state(&transfers, 1), TransferState::Done);
+        assert_eq!(transfers.get(1).unwrap().bytes, 100);
+        assert_eq!(transfers.active(), Some(2));
+    }
+
+    #[test]
+    fn pausing_an_upload_holds_it_and_starts_the_next() {
+        let mut transfers = queue(false, &[Direction::Upload, Direction::Upload]);
+        transfers.schedule();
+        transfers.started(1);
+
+        assert_eq!(
+            transfers.pause(1).unwrap(),
+            [Action::Hold(1), Action::Start(2)]
+        );
+        assert_eq!(state(&transfers, 1), TransferState::Paused);
+        transfers.started(2);
+
+        // Queued behind the running item, then picked up where it
+        // stopped: the held task is still there.
+        assert!(transfers.resume(1).unwrap().is_empty());
+        assert_eq!(state(&transfers, 1), TransferState::Queued);
+        assert_eq!(transfers.finish(2, Ok(())), [Action::Unpause(1)]);
+        assert_eq!(state(&transfers, 1), TransferState::Active);
+
+        assert!(transfers.resume(1).unwrap_err().contains("active"));
+        assert!(transfers.pause(2).unwrap_err().contains("done"));
+        assert!(transfers.pause(9).unwrap_err().contains("no transfer 9"));
+    }
+
+    #[test]
+    fn pausing_a_download_halts_it_and_resuming_starts_over() {
+        let mut transfers = queue(false, &[Direction::Download]);
+        transfers.schedule();
+        transfers.started(1);
+        assert_eq!(transfers.pause(1).unwrap(), [Action::Halt(1)]);
+        assert_eq!(transfers.active(), None);
+        // The next start delta-syncs against the kept `.part`.
+        assert_eq!(transfers.resume(1).unwrap(), [Action::Start(1)]);
+    }
+
+    #[test]
+    fn queued_items_pause_without_touching_the_wire() {
+        let mut transfers = queue(false, &[Direction::Upload, Direction::Upload]);
+        transfers.schedule();
+        assert!(transfers.pause(2).unwrap().is_empty());
+        assert_eq!(transfers.finish(1, Ok(())), []);
+        assert_eq!(state(&transfers, 2), TransferState::Paused);
+        assert_eq!(transfers.resume(2).unwrap(), [Action::Start(2)]);
+    }
+
+    #[test]
+    fn cancelling_removes_partial_files_unless_resumable() {
+        let mut transfers = queue(false, &[Direction::Download, Direction::Upload]);
+        transfers.schedule();
+        transfers.started(1);
+        assert_eq!(
+            transfers.cancel(1).unwrap(),
+            [
+                Action::Abort {
+                    id: 1,
+                    remove_part: true
+                },
+                Action::Start(2)
+            ]
+        );
+        assert_eq!(state(&transfers, 1), TransferState::Cancelled);
+        assert!(transfers.cancel(1).unwrap_err().contains("cancelled"));
+
+        // A held upload is aborted too; the slave deletes its part.
+        transfers.started(2);
+        transfers.pause(2).unwrap();
+        assert_eq!(
+            transfers.cancel(2).unwrap(),
+            [Action::Abort {
+                id: 2,
+                remove_part: true
+            }]
+        );
+
+        let mut resumable = queue(true, &[Direction::Download, Direction::Download]);
+        resumable.schedule();
+        resumable.started(1);
+        assert_eq!(
+            resumable.cancel(1).unwrap(),
+            [
+                Action::Abort {
+                    id: 1,
+                    remove_part: false
+                },
+                Action::Start(2)
+            ]
+        );
+    }
+
+    #[test]
+    fn stopped_downloads_discard_their_part_unless_resumable() {
+        let mut transfers = queue(false, &[Direction::Download, Direction::Download]);
+        transfers.schedule();
+        transfers.started(1);
+        transfers.pause(1).unwrap();
+        // Halted, so nothing runs: only the `.part` is left to delete.
+        let actions = transfers.cancel(1).unwrap();
+        assert_eq!(actions, [Action::Discard(1)]);
+
+        transfers.started(2);
+        assert_eq!(
+            transfers.finish(2, Err("hash mismatch".into())),
+            [Action::Discard(2)]
+        );
+        assert_eq!(
+            transfers.get(2).unwrap().error.as_deref(),
+            Some("hash mismatch")
+        );
+
+        let mut resumable = queue(true, &[Direction::Download]);
+        resumable.schedule();
+        resumable.started(1);
+        assert!(resumable.finish(1, Err("gone".into())).is_empty());
+        resumable.pause(1).unwrap_err();
+        assert!(resumable.cancel(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_items_retry_and_their_job_settles_again() {
+        let mut transfers = queue(false, &[Direction::Upload, Direction::Upload]);
+        transfers.schedule();
+        transfers.started(1);
+        assert_eq!(transfers.settle(7), None);
+        transfers.finish(1, Ok(()));
+        transfers.started(2);
+        transfers.finish(2, Err("disk full".into()));
+
+        let outcome = transfers.settle(7).unwrap();
+        assert_eq!((outcome.files, outcome.done), (2, 1));
+        assert_eq!(
+            outcome.describe(),
+            "Upload failed: 1 of 2 file(s) did not arrive: disk full"
+        );
+        assert_eq!(transfers.settle(7), None);
+
+        assert!(transfers.retry(1).unwrap_err().contains("done"));
+        assert_eq!(transfers.retry(2).unwrap(), [Action::Start(2)]);
+        assert_eq!(transfers.get(2).unwrap().error, None);
+        transfers.started(2);
+        transfers.finish(2, Ok(()));
+        let outcome = transfers.settle(7).unwrap();
+        assert!(outcome.is_success());
+        assert_eq!(outcome.describe(), "Upload complete: 2 files (200 B)");
+    }
+
+    #[test]
+    fn a_lost_slave_fails_what_is_left() {
+        let mut transfers = queue(false, &[Direction::Upload, Direction::Download]);
+        transfers.schedule();
+        transfers.started(1);
+        assert_eq!(transfers.fail_all("slave disconnected"), [1, 2]);
+        assert_eq!(transfers.active(), None);
+        let outcome = transfers.settle(7).unwrap();
+        assert!(outcome.describe().ends_with(": slave disconnected"));
+    }
+
+    #[test]
+    fn finished_items_are_pruned_oldest_first() {
+        let mut transfers = Transfers::new(false);
+        for i in 0..KEEP_FINISHED as u64 + 3 {
+            let id = transfers.enqueue(i, Direction::Upload, "a".into(), "b".into(), None);
+            transfers.schedule();
+            transfers.finish(id, Ok(()));
+        }
+        assert_eq!(transfers.iter().count(), KEEP_FINISHED);
+        assert_eq!(transfers.iter().next().unwrap().id, 4);
+    }
+
+    #[test]
+    fn folders_upload_a_file_at_a_time() {
+        let dir = temp_dir("transfers-plan");
+        let photos = dir.join("photos");
+        fs::create_dir_all(photos.join("2024")).unwrap();
+        fs::write(photos.join("a.jpg"), b"aa").unwrap();
+        fs::write(photos.join("2024").join("b.jpg"), b"bbb").unwrap();
+
+        let plan = plan_upload(&photos, "D:\\backup").unwrap();
+        let dests: Vec<(&str, Option<u64>)> =
+            plan.iter().map(|f| (f.dest.as_str(), f.size)).collect();
+        assert_eq!(
+            dests,
+            [
+                ("D:\\backup\\photos\\a.jpg", Some(2)),
+                ("D:\\backup\\photos\\2024\\b.jpg", Some(3)),
+            ]
+        );
+
+        let file = photos.join("a.jpg");
+        assert_eq!(
+            plan_upload(&file, "/srv/in/").unwrap()[0].dest,
+            "/srv/in/a.jpg"
+        );
+        assert_eq!(
+            plan_upload(&file, "D:\\x.jpg").unwrap()[0].dest,
+            "D:\\x.jpg"
+        );
+        assert!(plan_upload(&dir.join("missing"), "D:\\x").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn downloads_land_by_name_in_folders() {
+        let dir = temp_dir("transfers-dest");
+        let local = dir.display().to_string();
+        assert_eq!(
+            download_dest("C:\\logs\\app.log", &local),
+            dir.join("app.log")
+        );
+        assert_eq!(
+            download_dest("C:\\logs\\app.log", "saved/"),
+            Path::new("saved/").join("app.log")
+        );
+        assert_eq!(
+            download_dest("/var/log/syslog", ""),
+            PathBuf::from("syslog")
+        );
+        assert_eq!(
+            download_dest("C:\\logs\\app.log", "copy.log"),
+            PathBuf::from("copy.log")
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn transfer_commands_parse() {
+        let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+        assert_eq!(
+            TransferCommand::parse(&args("pause 3")),
+            Ok(TransferCommand::Pause(3))
+        );
+        assert_eq!(
+            TransferCommand::parse(&args("retry 12"))
+                .unwrap()
+                .to_command(),
+            "transfer retry 12"
+        );
+        assert!(TransferCommand::parse(&args("stop 3")).is_err());
+        assert!(
+            TransferCommand::parse(&args("cancel x"))
+                .unwrap_err()
+                .contains("\"x\"")
+        );
+        assert!(TransferCommand::parse(&[]).is_err());
+    }
+
+    #[test]
+    fn a_part_file_resumes_and_moves_into_place() {
+        let dir = temp_dir("transfers-part");
+        let dest = dir.join("nested").join("report.bin");
+        let contents: Vec<u8> = (0..10u8).collect();
+        let remote = FileDigest::of(&contents, 4);
+
+        let (mut part, digest) = PartFile::open(&dest, 4).unwrap();
+        assert_eq!(resumed_bytes(&digest, &remote), 0);
+        part.write(&FileChunk::new(0, 0, contents[..4].to_vec()))
+            .unwrap();
+        drop(part);
+        assert_eq!(part_path(&dest), dir.join("nested").join("report.bin.part"));
+
+        // Paused, then resumed: the first chunk is already there.
+        let (mut part, digest) = PartFile::open(&dest, 4).unwrap();
+        assert_eq!(resumed_bytes(&digest, &remote), 4);
+        for chunk in [4, 8] {
+            let end = (chunk + 4).min(contents.len());
+            part.write(&FileChunk::new(
+                chunk as u64,
+                chunk as u64 / 4,
+                contents[chunk..end].to_vec(),
+            ))
+            .unwrap();
+        }
+        let verification = FileHashVerification::new(*blake3::hash(&contents).as_bytes(), 10, 3);
+        part.finish(&verification).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), contents);
+        assert!(!part_path(&dest).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_bad_part_stays_until_removed() {
+        let dir = temp_dir("transfers-bad");
+        let dest = dir.join("x.bin");
+        let (mut part, _) = PartFile::open(&dest, 4).unwrap();
+        part.write(&FileChunk::new(0, 0, b"abcd".to_vec())).unwrap();
+        let err = part
+            .finish(&FileHashVerification::new([0; 32], 4, 1))
+            .unwrap_err();
+        assert_eq!(err, "hash mismatch");
+        assert!(!dest.exists());
+        assert!(part_path(&dest).exists());
+
+        remove_part(&dest).unwrap();
+        assert!(!part_path(&dest).exists());
+        remove_part(&dest).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn update(id: u64, bytes: u64, state: TransferState) -> TransferUpdate {
+        TransferUpdate {
+            id,
+            job: 1,
+            direction: Direction::Download,
+            source: "C:\\big.iso".into(),
+            dest: "big.iso".into(),
+            bytes,
+            total: Some(1000),
+            state,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn the_panel_tracks_rate_and_eta_per_item() {
+        let mut panel = TransferPanel::default();
+        let t0 = Instant::now();
+        panel.apply_at(update(1, 0, TransferState::Active), t0);
+        panel.apply_at(
+            update(1, 100, TransferState::Active),
+            t0 + Duration::from_secs(1),
+        );
+        let row = &panel.rows()[0];
+        assert_eq!(row.rate, Some(100.0));
+        assert_eq!(row.eta(), Some(Duration::from_secs(9)));
+        assert_eq!(row.update.fraction(), Some(0.1));
+
+        // Smoothed: a burst of 400 B/s only moves it part way.
+        panel.apply_at(
+            update(1, 500, TransferState::Active),
+            t0 + Duration::from_secs(2),
+        );
+        assert_eq!(panel.rows()[0].rate, Some(190.0));
+
+        panel.apply_at(
+            update(1, 500, TransferState::Paused),
+            t0 + Duration::from_secs(3),
+        );
+        assert_eq!(panel.rows()[0].rate, None);
+        assert_eq!(panel.rows()[0].eta(), None);
+    }
+
+    #[test]
+    fn the_panel_selection_follows_its_rows() {
+        let mut panel = TransferPanel::default();
+        assert!(panel.selected().is_none());
+        let now = Instant::now();
+        for id in 1..=3 {
+            panel.apply_at(update(id, 0, TransferState::Queued), now);
+        }
+        panel.select_prev();
+        assert_eq!(panel.selected().unwrap().update.id, 1);
+        panel.select_next();
+        panel.select_next();
+        panel.select_next();
+        assert_eq!(panel.selected().unwrap().update.id, 3);
+
+        panel.toggle();
+        assert!(panel.is_visible());
+    }
+}
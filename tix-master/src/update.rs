@@ -0,0 +1,181 @@
+//! The `update` console command: push a new slave build and swap it in.
+//!
+//! `update <exe> <version> [--force] [--no-restart]` uploads the file to
+//! the slave's staging folder as a normal `FileWrite`, then sends
+//! `UpdateApply` with the hash of what was sent. The slave checks the
+//! hash and version itself; the master only skips slaves whose `Hello`
+//! already reported the same or a newer version.
+
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+use tix_core::TixError;
+use tix_core::protocol::update::{self, UpdateApplyRequest, UpdateApplyResponse};
+use tokio::task::JoinHandle;
+
+/// Parsed `update` arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateArgs {
+    /// The new slave executable on this machine.
+    pub local: PathBuf,
+    /// Version of that build.
+    pub version: String,
+    /// Push even if the slave reports the same or a newer version.
+    pub force: bool,
+    /// Restart the slave once the binary is replaced.
+    pub restart: bool,
+}
+
+impl UpdateArgs {
+    /// Parse the arguments following `update`.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut force = false;
+        let mut restart = true;
+        for arg in args {
+            match arg.as_str() {
+                "--force" => force = true,
+                "--no-restart" => restart = false,
+                flag if flag.starts_with("--") => {
+                    return Err(format!("unknown update option '{}'", flag));
+                }
+                _ => positional.push(arg),
+            }
+        }
+        let [local, version] = positional.as_slice() else {
+            return Err("update requires <local exe> <version> [--force] [--no-restart]".into());
+        };
+        if update::parse_version(version).is_none() {
+            return Err(format!("'{}' is not a version number", version));
+        }
+        Ok(Self {
+            local: PathBuf::from(local),
+            version: version.to_string(),
+            force,
+            restart,
+        })
+    }
+
+    /// Why this update should not be sent to a slave running
+    /// `slave_version`, if it should not.
+    pub fn skip_reason(&self, slave_version: Option<&str>) -> Option<String> {
+        if self.force {
+            return None;
+        }
+        let current = slave_version?;
+        match update::compare_versions(current, &self.version)? {
+            Ordering::Less => None,
+            _ => Some(format!(
+                "slave already runs {} (offered {}); use --force to push anyway",
+                current, self.version
+            )),
+        }
+    }
+
+    /// File name the build is staged under on the slave. A bare name
+    /// lands in the slave's Downloads folder.
+    pub fn staged_name(&self) -> String {
+        format!("tix-slave-{}.update", self.version)
+    }
+
+    /// The `UpdateApply` request once the slave acknowledged the upload
+    /// at `staged_path`.
+    pub fn apply_request(&self, staged_path: String, hash: [u8; 32]) -> UpdateApplyRequest {
+        UpdateApplyRequest {
+            staged_path,
+            blake3_hash: hash,
+            version: self.version.clone(),
+            allow_downgrade: self.force,
+            restart: self.restart,
+        }
+    }
+}
+
+/// An update whose binary is still being uploaded.
+#[derive(Debug)]
+pub struct PendingUpdate {
+    /// Request ID of the `FileWrite` upload.
+    pub upload_id: u64,
+    pub args: UpdateArgs,
+    /// The upload task; yields the hash of what was sent.
+    pub upload: JoinHandle<Result<[u8; 32], TixError>>,
+}
+
+/// Console line for the slave's answer to `UpdateApply`.
+pub fn describe_response(response: &UpdateApplyResponse) -> Result<String, String> {
+    match response {
+        UpdateApplyResponse::Accepted {
+            previous_version,
+            new_version,
+            restarting,
+        } => Ok(format!(
+            "Updated slave {} -> {}{}",
+            previous_version,
+            new_version,
+            if *restarting {
+                ", restarting"
+            } else {
+                "; takes effect on next start"
+            }
+        )),
+        UpdateApplyResponse::Rejected(e) => Err(format!("Update refused: {}", e)),
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::protocol::UpdateError;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_flags_in_any_position() {
+        let parsed =
+            UpdateArgs::parse(&args(&["--force", "new.exe", "0.2.0", "--no-restart"])).unwrap();
+        assert_eq!(parsed.local, PathBuf::from("new.exe"));
+        assert_eq!(parsed.version, "0.2.0");
+        assert!(parsed.force);
+        assert!(!parsed.restart);
+
+        assert!(UpdateArgs::parse(&args(&["new.exe"])).is_err());
+        assert!(UpdateArgs::parse(&args(&["new.exe", "latest"])).is_err());
+        assert!(UpdateArgs::parse(&args(&["new.exe", "0.2.0", "--now"])).is_err());
+    }
+
+    #[test]
+    fn skips_slaves_that_are_up_to_date() {
+        let update = UpdateArgs::parse(&args(&["new.exe", "0.2.0"])).unwrap();
+        assert!(update.skip_reason(Some("0.1.0")).is_none());
+        assert!(update.skip_reason(Some("0.2.0")).is_some());
+        assert!(update.skip_reason(Some("0.3.0")).is_some());
+        // Without a Hello the slave decides.
+        assert!(update.skip_reason(None).is_none());
+
+        let forced = UpdateArgs::parse(&args(&["new.exe", "0.2.0", "--force"])).unwrap();
+        assert!(forced.skip_reason(Some("0.3.0")).is_none());
+    }
+
+    #[test]
+    fn describes_outcomes() {
+        let accepted = UpdateApplyResponse::Accepted {
+            previous_version: "0.1.0".into(),
+            new_version: "0.2.0".into(),
+            restarting: true,
+        };
+        assert_eq!(
+            describe_response(&accepted).unwrap(),
+            "Updated slave 0.1.0 -> 0.2.0, restarting"
+        );
+        let rejected = UpdateApplyResponse::Rejected(UpdateError::AlreadyInstalled("0.2.0".into()));
+        assert!(
+            describe_response(&rejected)
+                .unwrap_err()
+                .contains("already installed")
+        );
+    }
+}
@@ -0,0 +1,466 @@
+//! The `watch` console command: follow a remote file and show what
+//! changed.
+//!
+//! `watch <remote-path> [interval]` asks the slave for the file's
+//! [`FileDigest`](tix_core::protocol::FileDigest) every interval. When
+//! the whole-file hash moves, only the chunks that differ from the
+//! newest cached copy are pulled with a delta-sync `FileRead`, the
+//! rebuilt file is stored as a new version and the log shows a unified
+//! diff against the previous one. Binary files only get a size and hash
+//! line. A rebuilt file that fails the slave's hash is repaired chunk by
+//! chunk for up to `repair_rounds` rounds before the check is given up.
+//!
+//! [`WatchCache`] keeps one folder per remote path under `cache_dir`
+//! (see [`crate::config`]) holding `v000001`, `v000002`, … and drops the
+//! oldest once there are more than `keep_versions`.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use similar::{ChangeTag, TextDiff};
+use tix_core::format::format_bytes;
+use tix_core::protocol::{ChunkRepair, FileChunk};
+
+/// Default `cache_dir`, relative to the working directory.
+pub const DEFAULT_CACHE_DIR: &str = "tix-cache";
+
+/// Default `keep_versions`.
+pub const DEFAULT_KEEP_VERSIONS: usize = 5;
+
+/// Polling interval when `watch` is given none.
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(10);
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// First character of every diff line in the log, so the console can
+/// colour them apart from ordinary output.
+pub const DIFF_MARKER: char = '│';
+
+/// Context lines around each hunk.
+const DIFF_CONTEXT: usize = 3;
+
+/// Diff lines printed per change; the cached versions hold the rest.
+const MAX_DIFF_LINES: usize = 200;
+
+// ── Arguments ────────────────────────────────────────────────────
+
+/// Parsed `watch` arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchArgs {
+    /// File to follow on the slave.
+    pub path: String,
+    /// Time between hash checks.
+    pub interval: Duration,
+}
+
+impl WatchArgs {
+    /// Parse the arguments following `watch`.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let (path, interval) = match args {
+            [path] => (path, DEFAULT_WATCH_INTERVAL),
+            [path, interval] => (path, parse_interval(interval)?),
+            _ => return Err("watch requires <remote path> [interval]".to_string()),
+        };
+        Ok(Self {
+            path: path.clone(),
+            interval,
+        })
+    }
+}
+
+/// `500ms`, `30s`, `5m`, `1h`, `2d` or a bare number of seconds.
+pub(crate) fn parse_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("'{}' is not an interval (e.g. 30s, 5m, 500ms)", text);
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => text.split_at(i),
+        None => (text, "s"),
+    };
+    let n: u64 = number.parse().map_err(|_| invalid())?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(n)),
+        "s" => Ok(Duration::from_secs(n)),
+        "m" => Ok(Duration::from_secs(n * 60)),
+        "h" => Ok(Duration::from_secs(n * 3_600)),
+        "d" => Ok(Duration::from_secs(n * 86_400)),
+        _ => Err(invalid()),
+    }
+}
+
+/// A watch interval: [`parse_duration`], but not too short.
+fn parse_interval(text: &str) -> Result<Duration, String> {
+    let interval = parse_duration(text)?;
+    if interval < MIN_INTERVAL {
+        return Err(format!(
+            "interval must be at least {} ms",
+            MIN_INTERVAL.as_millis()
+        ));
+    }
+    Ok(interval)
+}
+
+/// `interval` the way `watch` accepts it.
+pub fn format_interval(interval: Duration) -> String {
+    match interval.as_millis() {
+        ms if ms % 60_000 == 0 => format!("{}m", ms / 60_000),
+        ms if ms % 1000 == 0 => format!("{}s", ms / 1000),
+        ms => format!("{}ms", ms),
+    }
+}
+
+// ── Watch ────────────────────────────────────────────────────────
+
+/// One running `watch`. Its ID is the row it owns in the Tasks panel.
+#[derive(Debug)]
+pub struct Watch {
+    pub id: u64,
+    pub path: String,
+    pub interval: Duration,
+    next_due: Instant,
+    /// Hash of the newest version already cached.
+    last_hash: Option<[u8; 32]>,
+    /// Versions pulled since the watch started.
+    pub changes: u64,
+    pub(crate) in_flight: Option<InFlight>,
+}
+
+/// The request a watch is waiting on.
+#[derive(Debug)]
+pub(crate) enum InFlight {
+    /// `FileHash` for the current digest.
+    Hash(u64),
+    /// Delta-sync `FileRead` rebuilding the new version on top of
+    /// `base`, the newest cached one (empty if there is none).
+    Sync {
+        req_id: u64,
+        base: Option<Vec<u8>>,
+        chunks: Vec<FileChunk>,
+        /// The rebuilt copy while it is being repaired, which the
+        /// chunks patch instead of `base`.
+        rebuilt: Option<Vec<u8>>,
+        repair: ChunkRepair,
+    },
+}
+
+impl Watch {
+    /// A watch whose first check is due right away.
+    pub fn new(id: u64, args: WatchArgs, now: Instant) -> Self {
+        Self {
+            id,
+            path: args.path,
+            interval: args.interval,
+            next_due: now,
+            last_hash: None,
+            changes: 0,
+            in_flight: None,
+        }
+    }
+
+    /// When the next check should go out; `None` while one is running.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.in_flight.is_none().then_some(self.next_due)
+    }
+
+    /// The request this watch is waiting on.
+    pub fn request_id(&self) -> Option<u64> {
+        match self.in_flight.as_ref()? {
+            InFlight::Hash(id) | InFlight::Sync { req_id: id, .. } => Some(*id),
+        }
+    }
+
+    /// Whether `hash` is the version already cached.
+    pub fn is_current(&self, hash: &[u8; 32]) -> bool {
+        self.last_hash.as_ref() == Some(hash)
+    }
+
+    /// Remember `hash` as the newest cached version.
+    pub fn set_current(&mut self, hash: [u8; 32]) {
+        self.last_hash = Some(hash);
+    }
+
+    /// The running check is over; the next one is an interval away.
+    pub fn reschedule(&mut self, now: Instant) {
+        self.in_flight = None;
+        self.next_due = now + self.interval;
+    }
+
+    /// Tasks panel status.
+    pub fn status(&self) -> String {
+        format!(
+            "Watching {} every {} ({} change{})",
+            self.path,
+            format_interval(self.interval),
+            self.changes,
+            if self.changes == 1 { "" } else { "s" }
+        )
+    }
+}
+
+// ── Cache ────────────────────────────────────────────────────────
+
+/// Versioned local copies of watched files.
+#[derive(Debug, Clone)]
+pub struct WatchCache {
+    root: PathBuf,
+    keep: usize,
+}
+
+impl WatchCache {
+    /// A cache under `root` keeping `keep` versions per file (at least
+    /// two, so a change always has a previous version to diff against).
+    pub fn new(root: impl Into<PathBuf>, keep: usize) -> Self {
+        Self {
+            root: root.into(),
+            keep: keep.max(2),
+        }
+    }
+
+    /// Folder holding the versions of `remote`. The name keeps the tail
+    /// of the path readable and adds a hash so paths differing only in
+    /// characters a file name cannot hold do not collide.
+    pub fn dir_for(&self, remote: &str) -> PathBuf {
+        let readable: String = remote
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let tail = &readable[readable.len().saturating_sub(48)..];
+        let hash = blake3::hash(remote.as_bytes()).to_hex();
+        self.root.join(format!("{}-{}", tail, &hash[..8]))
+    }
+
+    /// Cached version numbers of `remote` with their files, oldest first.
+    pub fn versions(&self, remote: &str) -> io::Result<Vec<(u64, PathBuf)>> {
+        let dir = self.dir_for(remote);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut versions: Vec<(u64, PathBuf)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                let n = name.to_str()?.strip_prefix('v')?.parse().ok()?;
+                Some((n, entry.path()))
+            })
+            .collect();
+        versions.sort_unstable_by_key(|(n, _)| *n);
+        Ok(versions)
+    }
+
+    /// Contents of the newest cached version of `remote`, if any.
+    pub fn latest(&self, remote: &str) -> io::Result<Option<Vec<u8>>> {
+        match self.versions(remote)?.pop() {
+            Some((_, path)) => std::fs::read(path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Store `data` as the next version of `remote`, evict versions past
+    /// the limit and return the new version number.
+    pub fn store(&self, remote: &str, data: &[u8]) -> io::Result<u64> {
+        let versions = self.versions(remote)?;
+        let next = versions.last().map_or(1, |(n, _)| n + 1);
+        let dir = self.dir_for(remote);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(version_path(&dir, next), data)?;
+
+        let excess = (versions.len() + 1).saturating_sub(self.keep);
+        for (_, path) in versions.into_iter().take(excess) {
+            std::fs::remove_file(path)?;
+        }
+        Ok(next)
+    }
+}
+
+fn version_path(dir: &Path, version: u64) -> PathBuf {
+    dir.join(format!("v{:06}", version))
+}
+
+// ── Diff ─────────────────────────────────────────────────────────
+
+/// `data` as text, or `None` if it looks binary.
+fn as_text(data: &[u8]) -> Option<&str> {
+    if data.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(data).ok()
+}
+
+/// Unified diff of `old` against `new`, one log line per diff line,
+/// each starting with [`DIFF_MARKER`].
+pub fn render_diff(old: &str, new: &str) -> Vec<String> {
+    let diff = TextDiff::from_lines(old, new);
+    let mut lines = Vec::new();
+    for hunk in diff
+        .unified_diff()
+        .context_radius(DIFF_CONTEXT)
+        .iter_hunks()
+    {
+        lines.push(format!("{}{}", DIFF_MARKER, hunk.header()));
+        for change in hunk.iter_changes() {
+            let sign = match change.tag() {
+                ChangeTag::Delete => '-',
+                ChangeTag::Insert => '+',
+                ChangeTag::Equal => ' ',
+            };
+            let text = change.value().trim_end_matches(['\n', '\r']);
+            lines.push(format!("{}{}{}", DIFF_MARKER, sign, text));
+        }
+    }
+    lines
+}
+
+/// Log lines announcing version `version` of `path`. `old` is the
+/// previous cached version, if there was one.
+pub fn describe_change(path: &str, version: u64, old: Option<&[u8]>, new: &[u8]) -> Vec<String> {
+    let Some(old) = old else {
+        return vec![format!(
+            "[WTCH] {}: cached as v{} ({})",
+            path,
+            version,
+            format_bytes(new.len() as u64)
+        )];
+    };
+    let (Some(old_text), Some(new_text)) = (as_text(old), as_text(new)) else {
+        return vec![format!(
+            "[WTCH] {} changed (v{}, binary): {} → {}, hash {} → {}",
+            path,
+            version,
+            format_bytes(old.len() as u64),
+            format_bytes(new.len() as u64),
+            short_hash(old),
+            short_hash(new)
+        )];
+    };
+
+    let mut diff = render_diff(old_text, new_text);
+    let added = diff
+        .iter()
+        .filter(|l| l[DIFF_MARKER.len_utf8()..].starts_with('+'))
+        .count();
+    let removed = diff
+        .iter()
+        .filter(|l| l[DIFF_MARKER.len_utf8()..].starts_with('-'))
+        .count();
+    let mut lines = vec![format!(
+        "[WTCH] {} changed (v{}): +{} -{} lines",
+        path, version, added, removed
+    )];
+    if diff.len() > MAX_DIFF_LINES {
+        let rest = diff.len() - MAX_DIFF_LINES;
+        diff.truncate(MAX_DIFF_LINES);
+        diff.push(format!("{} … {} more diff lines", DIFF_MARKER, rest));
+    }
+    lines.extend(diff);
+    lines
+}
+
+fn short_hash(data: &[u8]) -> String {
+    blake3::hash(data).to_hex()[..8].to_string()
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Result<WatchArgs, String> {
+        let owned: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        WatchArgs::parse(&owned)
+    }
+
+    fn temp_cache(name: &str, keep: usize) -> WatchCache {
+        let root = std::env::temp_dir().join(format!("tix-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        WatchCache::new(root, keep)
+    }
+
+    #[test]
+    fn parse_watch_args() {
+        let a = args(&["C:\\logs\\app.log"]).unwrap();
+        assert_eq!(a.interval, DEFAULT_WATCH_INTERVAL);
+        assert_eq!(
+            args(&["x", "500ms"]).unwrap().interval,
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            args(&["x", "5m"]).unwrap().interval,
+            Duration::from_secs(300)
+        );
+        assert_eq!(
+            args(&["x", "30"]).unwrap().interval,
+            Duration::from_secs(30)
+        );
+        assert!(args(&["x", "10ms"]).is_err());
+        assert!(args(&["x", "soon"]).is_err());
+        assert!(args(&[]).is_err());
+    }
+
+    #[test]
+    fn diff_marks_hunks_and_changes() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nb\nc\nD\ne\nf\ng\nh\ni\n";
+        let lines = render_diff(old, new);
+        assert_eq!(
+            lines,
+            [
+                "│@@ -1,8 +1,9 @@",
+                "│ a",
+                "│ b",
+                "│ c",
+                "│-d",
+                "│+D",
+                "│ e",
+                "│ f",
+                "│ g",
+                "│ h",
+                "│+i",
+            ]
+        );
+        assert!(render_diff(old, old).is_empty());
+    }
+
+    #[test]
+    fn describe_counts_lines_and_handles_binary() {
+        let lines = describe_change("app.log", 2, Some(b"one\ntwo\n"), b"one\n2\n3\n");
+        assert_eq!(lines[0], "[WTCH] app.log changed (v2): +2 -1 lines");
+        assert!(lines[1..].iter().all(|l| l.starts_with(DIFF_MARKER)));
+
+        let first = describe_change("app.log", 1, None, b"one\n");
+        assert_eq!(first, ["[WTCH] app.log: cached as v1 (4 B)"]);
+
+        let binary = describe_change("img.bin", 3, Some(b"\0\x01"), b"\0\x01\x02");
+        assert_eq!(binary.len(), 1);
+        assert!(binary[0].contains("binary"));
+        assert!(binary[0].contains("2 B → 3 B"));
+    }
+
+    #[test]
+    fn cache_rotates_old_versions() {
+        let cache = temp_cache("watch-rotate", 3);
+        assert_eq!(cache.latest("/var/log/app.log").unwrap(), None);
+        for i in 1..=5u8 {
+            assert_eq!(cache.store("/var/log/app.log", &[i]).unwrap(), i as u64);
+        }
+        let kept: Vec<u64> = cache
+            .versions("/var/log/app.log")
+            .unwrap()
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect();
+        assert_eq!(kept, [3, 4, 5]);
+        assert_eq!(cache.latest("/var/log/app.log").unwrap(), Some(vec![5]));
+
+        // Other files have their own folder and history.
+        assert_ne!(cache.dir_for("C:\\app.log"), cache.dir_for("C:/app.log"));
+        assert_eq!(cache.latest("C:\\app.log").unwrap(), None);
+        let _ = std::fs::remove_dir_all(&cache.root);
+    }
+}
@@ -0,0 +1,98 @@
+//! Stall detection for the console's UI loop.
+//!
+//! Every pass of the loop handles one event and redraws. Anything slow on
+//! that path (blocking file system calls, a huge render) freezes the
+//! screen and holds up the master's events, so [`UiWatchdog`] times each
+//! pass and names the event behind any that ran long.
+
+use std::time::{Duration, Instant};
+
+use tix_core::format::format_duration;
+
+/// A pass longer than this is reported.
+pub const STALL_THRESHOLD: Duration = Duration::from_millis(250);
+
+/// Times the UI loop's passes.
+#[derive(Debug)]
+pub struct UiWatchdog {
+    threshold: Duration,
+    /// The event being handled and when handling started.
+    current: Option<(String, Instant)>,
+    stalls: u64,
+}
+
+impl Default for UiWatchdog {
+    fn default() -> Self {
+        Self::new(STALL_THRESHOLD)
+    }
+}
+
+impl UiWatchdog {
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            current: None,
+            stalls: 0,
+        }
+    }
+
+    /// The loop woke up to handle `event`.
+    pub fn start(&mut self, event: impl Into<String>) {
+        self.start_at(event, Instant::now());
+    }
+
+    /// The pass is over, redraw included. Returns the warning to log if
+    /// it stalled.
+    pub fn finish(&mut self) -> Option<String> {
+        self.finish_at(Instant::now())
+    }
+
+    /// Passes that ran past the threshold so far.
+    pub fn stalls(&self) -> u64 {
+        self.stalls
+    }
+
+    fn start_at(&mut self, event: impl Into<String>, now: Instant) {
+        self.current = Some((event.into(), now));
+    }
+
+    fn finish_at(&mut self, now: Instant) -> Option<String> {
+        let (event, started) = self.current.take()?;
+        let took = now.saturating_duration_since(started);
+        if took <= self.threshold {
+            return None;
+        }
+        self.stalls += 1;
+        Some(format!(
+            "[WARN] UI stalled for {} handling {}",
+            format_duration(took),
+            event
+        ))
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_long_passes_are_reported() {
+        let mut watchdog = UiWatchdog::default();
+        let t0 = Instant::now();
+
+        watchdog.start_at("key Char('v')", t0);
+        assert_eq!(watchdog.finish_at(t0 + Duration::from_millis(250)), None);
+
+        watchdog.start_at("key Char('v')", t0);
+        let warning = watchdog
+            .finish_at(t0 + Duration::from_millis(1300))
+            .unwrap();
+        assert_eq!(warning, "[WARN] UI stalled for 1.3s handling key Char('v')");
+        assert_eq!(watchdog.stalls(), 1);
+
+        // A pass that never started (a redraw with no event) is not timed.
+        assert_eq!(watchdog.finish_at(t0 + Duration::from_secs(5)), None);
+    }
+}
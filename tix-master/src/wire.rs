@@ -0,0 +1,245 @@
+//! The `debug wire` console command: log every packet on the master's
+//! connection, or have the slave log its end.
+//!
+//! `debug wire on` turns the master's wire tap on, `debug wire off`
+//! turns it off and closes its capture file, and `debug wire` alone
+//! shows how it is set. `-b <bytes>` sets how much of each payload is
+//! dumped and `-c <file>` mirrors the packets to a capture file for
+//! `wire-dump`. With `--slave` the settings go to the slave as a
+//! `WireTap` request instead; there `on` is implied and a capture file
+//! is only kept while `-c` names it.
+//!
+//! The packet lines are `tracing` events; [`forward_logs`] routes them
+//! into the console log.
+
+use std::io::Write;
+
+use tix_core::network::wiretap::{self, WireConfig};
+use tix_core::protocol::{WireTapResponse, WireTapSettings};
+use tokio::sync::mpsc;
+use tracing_subscriber::EnvFilter;
+
+use crate::app::MasterEvent;
+use crate::args::split_args;
+
+const USAGE: &str = "debug wire [on|off] [-b <bytes>] [-c <file>] [--slave]";
+
+/// What a `debug wire` line asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireCommand {
+    /// Show the master's tap.
+    Status,
+    /// Set the master's tap.
+    Local(WireTapSettings),
+    /// Ask the slave to set its tap.
+    Slave(WireTapSettings),
+}
+
+/// Parse what follows `debug wire`. Settings the line leaves out keep
+/// their value in `current`, the master's tap.
+pub fn parse(rest: &str, current: &WireTapSettings) -> Result<WireCommand, String> {
+    let args = split_args(rest)?;
+    if args.is_empty() {
+        return Ok(WireCommand::Status);
+    }
+    let mut enabled = None;
+    let mut dump_bytes = None;
+    let mut capture = None;
+    let mut slave = false;
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            "on" => enabled = Some(true),
+            "off" => enabled = Some(false),
+            "-b" | "--bytes" => {
+                let bytes = args.next().and_then(|n| n.parse().ok());
+                dump_bytes = Some(bytes.ok_or("-b needs a byte count")?);
+            }
+            "-c" | "--capture" => {
+                capture = Some(args.next().ok_or("-c needs a file")?.to_string());
+            }
+            "--slave" => slave = true,
+            _ => return Err(format!("Usage: {}", USAGE)),
+        }
+    }
+
+    let base = if slave {
+        WireTapSettings {
+            enabled: true,
+            ..WireConfig::default().settings()
+        }
+    } else {
+        current.clone()
+    };
+    let enabled = enabled.unwrap_or(base.enabled);
+    if !enabled && capture.is_some() {
+        return Err("-c only goes with on".to_string());
+    }
+    let settings = WireTapSettings {
+        enabled,
+        dump_bytes: dump_bytes.unwrap_or(base.dump_bytes),
+        capture: capture.or(base.capture).filter(|_| enabled),
+    };
+    Ok(if slave {
+        WireCommand::Slave(settings)
+    } else {
+        WireCommand::Local(settings)
+    })
+}
+
+/// One line on how a tap is set.
+pub fn describe(settings: &WireTapSettings) -> String {
+    if !settings.enabled {
+        return "packet log off".to_string();
+    }
+    let mut out = format!("packet log on, {} payload bytes shown", settings.dump_bytes);
+    if let Some(path) = &settings.capture {
+        out.push_str(&format!(", capturing to {}", path));
+    }
+    out
+}
+
+/// Render the slave's answer to a `WireTap` request.
+pub fn describe_response(response: WireTapResponse) -> Result<String, String> {
+    match response {
+        WireTapResponse::Applied(settings) => Ok(format!("Slave {}", describe(&settings))),
+        WireTapResponse::Failed(e) => Err(format!("Slave wire tap unchanged: {}", e)),
+    }
+}
+
+/// Show the wire tap's lines in the console log as `[WIRE] …`; the
+/// terminal belongs to the UI, so they must not go to stdout.
+pub fn forward_logs(tx: mpsc::UnboundedSender<MasterEvent>) {
+    let writer = move || LogLine {
+        tx: tx.clone(),
+        text: Vec::new(),
+    };
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(format!("{}=debug", wiretap::TARGET)))
+        .without_time()
+        .with_target(false)
+        .with_level(false)
+        .with_ansi(false)
+        .with_writer(writer)
+        .try_init();
+}
+
+/// One formatted event, sent to the log when the formatter is done.
+struct LogLine {
+    tx: mpsc::UnboundedSender<MasterEvent>,
+    text: Vec<u8>,
+}
+
+impl Write for LogLine {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.text.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogLine {
+    fn drop(&mut self) {
+        let text = String::from_utf8_lossy(&self.text);
+        let text = text.trim_end();
+        if !text.is_empty() {
+            let _ = self.tx.send(MasterEvent::log(format!("[WIRE] {}", text)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn off() -> WireTapSettings {
+        WireConfig::default().settings()
+    }
+
+    #[test]
+    fn parses_local_settings() {
+        assert_eq!(parse("", &off()).unwrap(), WireCommand::Status);
+        let WireCommand::Local(on) = parse(" on -b 16 -c wire.cap", &off()).unwrap() else {
+            panic!("not a local command");
+        };
+        assert_eq!(
+            on,
+            WireTapSettings {
+                enabled: true,
+                dump_bytes: 16,
+                capture: Some("wire.cap".to_string()),
+            }
+        );
+        // Left-out settings stay; `off` closes the capture.
+        let WireCommand::Local(more) = parse(" -b 256", &on).unwrap() else {
+            panic!("not a local command");
+        };
+        assert_eq!(more.dump_bytes, 256);
+        assert_eq!(more.capture, on.capture);
+        let WireCommand::Local(stopped) = parse(" off", &on).unwrap() else {
+            panic!("not a local command");
+        };
+        assert!(!stopped.enabled);
+        assert_eq!(stopped.capture, None);
+    }
+
+    #[test]
+    fn slave_settings_start_from_the_defaults() {
+        let on = WireTapSettings {
+            enabled: true,
+            dump_bytes: 16,
+            capture: Some("master.cap".to_string()),
+        };
+        let WireCommand::Slave(slave) = parse(" --slave", &on).unwrap() else {
+            panic!("not a slave command");
+        };
+        assert!(slave.enabled);
+        assert_eq!(slave.dump_bytes, wiretap::DEFAULT_DUMP_BYTES as u32);
+        assert_eq!(slave.capture, None);
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        assert!(parse(" loud", &off()).is_err());
+        assert!(parse(" on -b many", &off()).is_err());
+        assert!(parse(" on -c", &off()).is_err());
+        assert!(parse(" off -c wire.cap", &off()).is_err());
+    }
+
+    #[test]
+    fn describes_settings() {
+        assert_eq!(describe(&off()), "packet log off");
+        let on = WireTapSettings {
+            enabled: true,
+            dump_bytes: 64,
+            capture: Some("wire.cap".to_string()),
+        };
+        assert_eq!(
+            describe(&on),
+            "packet log on, 64 payload bytes shown, capturing to wire.cap"
+        );
+        let failed = WireTapResponse::Failed("no such directory".to_string());
+        assert!(describe_response(failed).is_err());
+    }
+
+    #[test]
+    fn log_lines_reach_the_console() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        drop(LogLine {
+            tx: tx.clone(),
+            text: b"direction=sent command=Ping\n".to_vec(),
+        });
+        drop(LogLine {
+            tx,
+            text: Vec::new(),
+        });
+        match rx.try_recv() {
+            Ok(MasterEvent::Log(line)) => assert_eq!(line, "[WIRE] direction=sent command=Ping"),
+            other => panic!("unexpected {:?}", other.is_ok()),
+        }
+        assert!(rx.try_recv().is_err());
+    }
+}
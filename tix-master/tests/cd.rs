@@ -0,0 +1,87 @@
+//! `cd` and relative paths against a real slave over loopback.
+
+mod common;
+
+use std::path::Path;
+
+use tix_core::{ConnectionInfo, TixError};
+use tix_master::{Master, MasterEvent};
+use tix_slave::audit::Audit;
+use tix_slave::config::SlaveConfig;
+use tix_slave::run_with_reconnect;
+use tokio::sync::mpsc;
+
+use common::run_until;
+
+fn is_cwd(event: &MasterEvent, dir: &Path) -> bool {
+    matches!(event, MasterEvent::RemoteCwd(Some(d)) if Path::new(d) == dir)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn relative_download_after_cd() {
+    let root = std::env::temp_dir().join(format!("tix-cd-{}", std::process::id()));
+    let dir = root.join("remote dir");
+    std::fs::create_dir_all(dir.join("logs")).unwrap();
+    std::fs::write(dir.join("logs").join("today.log"), b"remote bytes").unwrap();
+    let dest = root.join("fetched.log");
+
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap();
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), master.local_addr().unwrap().port());
+    let slave = tokio::spawn(async move {
+        run_with_reconnect(&info, &SlaveConfig::default(), "cd", &Audit::default(), None).await
+    });
+    master.accept_one().await.unwrap();
+    run_until(
+        &mut master,
+        &mut ui_rx,
+        |e| matches!(e, MasterEvent::Log(line) if line.starts_with("[CONN] Slave runs")),
+    )
+    .await;
+
+    // Relative paths need somewhere to start from.
+    let err = master.execute_command("cd logs".to_string()).await;
+    assert!(matches!(err, Err(TixError::InvalidCommandSyntax(_))));
+
+    // A directory that is not there is refused.
+    let missing = root.join("missing");
+    master
+        .execute_command(format!("cd {}", missing.display()))
+        .await
+        .unwrap();
+    run_until(
+        &mut master,
+        &mut ui_rx,
+        |e| matches!(e, MasterEvent::Log(line) if line.starts_with("Error: cd")),
+    )
+    .await;
+    assert_eq!(master.cwd(), None);
+
+    master
+        .execute_command(format!("cd \"{}\"", dir.display()))
+        .await
+        .unwrap();
+    run_until(&mut master, &mut ui_rx, |e| is_cwd(e, &dir)).await;
+    master.execute_command("cd logs".to_string()).await.unwrap();
+    run_until(&mut master, &mut ui_rx, |e| is_cwd(e, &dir.join("logs"))).await;
+    master.execute_command("cd ..".to_string()).await.unwrap();
+    run_until(&mut master, &mut ui_rx, |e| is_cwd(e, &dir)).await;
+    assert_eq!(master.cwd().map(Path::new), Some(dir.as_path()));
+
+    master
+        .execute_command(format!("Download logs/today.log|{}", dest.display()))
+        .await
+        .unwrap();
+    run_until(
+        &mut master,
+        &mut ui_rx,
+        |e| matches!(e, MasterEvent::TaskUpdate { status, .. } if status == "Solved"),
+    )
+    .await;
+    assert_eq!(std::fs::read(&dest).unwrap(), b"remote bytes");
+
+    slave.abort();
+    let _ = std::fs::remove_dir_all(&root);
+}
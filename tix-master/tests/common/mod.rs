@@ -0,0 +1,91 @@
+//! Helpers shared by the master's integration tests.
+//!
+//! Each test binary compiles its own copy and uses a different subset.
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use tix_master::{Master, MasterEvent};
+use tokio::sync::mpsc;
+
+/// How long a test waits for the event it expects before failing.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Drive `master` (its connection and any watch that falls due) until
+/// `pick` accepts a UI event, and return what it picked. Events already
+/// queued are looked at before the master is driven again.
+pub async fn run_until_some<T>(
+    master: &mut Master,
+    ui_rx: &mut mpsc::UnboundedReceiver<MasterEvent>,
+    mut pick: impl FnMut(MasterEvent) -> Option<T>,
+) -> T {
+    let run = async {
+        loop {
+            while let Ok(event) = ui_rx.try_recv() {
+                if let Some(found) = pick(event) {
+                    return found;
+                }
+            }
+            let due = master.next_watch_due();
+            tokio::select! {
+                result = master.process_connection() => result.unwrap(),
+                _ = async {
+                    match due {
+                        Some(due) => tokio::time::sleep_until(due.into()).await,
+                        None => std::future::pending().await,
+                    }
+                } => master.poll_watches().await,
+            }
+        }
+    };
+    tokio::time::timeout(TIMEOUT, run)
+        .await
+        .expect("the master never got there")
+}
+
+/// Drive `master` until `done` holds for a UI event, returning every
+/// event seen on the way, that one included.
+pub async fn run_until(
+    master: &mut Master,
+    ui_rx: &mut mpsc::UnboundedReceiver<MasterEvent>,
+    done: impl Fn(&MasterEvent) -> bool,
+) -> Vec<MasterEvent> {
+    let mut seen = Vec::new();
+    run_until_some(master, ui_rx, |event| {
+        let last = done(&event);
+        seen.push(event);
+        last.then_some(())
+    })
+    .await;
+    seen
+}
+
+/// Drive `master` until a command response arrives; return its text.
+pub async fn run_until_response(
+    master: &mut Master,
+    ui_rx: &mut mpsc::UnboundedReceiver<MasterEvent>,
+) -> String {
+    run_until_some(master, ui_rx, |event| match event {
+        MasterEvent::Response { text, .. } => Some(text),
+        _ => None,
+    })
+    .await
+}
+
+/// Drive `master` until a log line or response contains `needle`;
+/// return that line.
+pub async fn run_until_line(
+    master: &mut Master,
+    ui_rx: &mut mpsc::UnboundedReceiver<MasterEvent>,
+    needle: &str,
+) -> String {
+    run_until_some(master, ui_rx, |event| {
+        let line = match event {
+            MasterEvent::Log(line) => line.to_string(),
+            MasterEvent::Response { text, .. } => text,
+            _ => return None,
+        };
+        line.contains(needle).then_some(line)
+    })
+    .await
+}
@@ -0,0 +1,259 @@
+//! One-shot `exec` against loopback slaves, library and binary.
+
+use std::time::Duration;
+
+use tix_core::protocol::TaskFailure;
+use tix_core::{Command, Connection, ConnectionInfo, Packet, TaskOptions, TaskPool};
+use tix_master::commands::ConfirmGate;
+use tix_master::oneshot::{
+    self, EXIT_DISCONNECTED, EXIT_FAILED, EXIT_OK, EXIT_TIMEOUT, EXIT_USAGE, Target,
+};
+use tix_master::roles::Roles;
+use tokio::net::TcpListener;
+
+/// How the fake slave treats a ShellExecute.
+#[derive(Clone, Copy)]
+enum Shell {
+    /// `echo`: reply with the command as stdout and exit 0.
+    Echo,
+    /// Reply with exit code 2.
+    Fail,
+    /// Never reply.
+    Silent,
+    /// Run it in a task pool with a 1 ms timeout, as the slave does with
+    /// `tasks.timeout_ms = 1`.
+    Pooled,
+}
+
+/// Minimal slave: answers Ping, ShellExecute per `shell`, and Delete
+/// with a reply the master cannot decode.
+async fn serve(mut conn: Connection, shell: Shell) {
+    if let Shell::Pooled = shell {
+        return serve_pooled(conn).await;
+    }
+    while let Some(pkt) = conn.recv().await {
+        let id = pkt.request_id();
+        let reply = match pkt.command() {
+            Ok(Command::Ping) => b"Pong".to_vec(),
+            Ok(Command::ShellExecute) => {
+                let cmd = String::from_utf8_lossy(pkt.payload()).into_owned();
+                match shell {
+                    Shell::Echo => format!("stdout: {}\nstderr: \nExit Code: 0", cmd).into_bytes(),
+                    Shell::Fail => b"stdout: \nstderr: no such file\nExit Code: 2".to_vec(),
+                    Shell::Silent | Shell::Pooled => continue,
+                }
+            }
+            Ok(Command::FileDelete) => b"access denied".to_vec(),
+            _ => continue,
+        };
+        let cmd = pkt.command().unwrap();
+        let packet = Packet::new_response(id, cmd, reply).unwrap();
+        if conn.send(packet).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Slave whose shell commands outlive their 1 ms task timeout; the
+/// pool's error is the only answer.
+async fn serve_pooled(mut conn: Connection) {
+    let mut pool = TaskPool::new();
+    let options = TaskOptions::new().with_timeout(Duration::from_millis(1));
+    loop {
+        tokio::select! {
+            pkt = conn.recv() => {
+                let Some(pkt) = pkt else { return };
+                if pkt.command().ok() == Some(Command::ShellExecute) {
+                    let work = |tx: tix_core::ConnectionSender, id, _| async move {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        let reply = b"stdout: \nstderr: \nExit Code: 0".to_vec();
+                        let pkt = Packet::new_response(id, Command::ShellExecute, reply).unwrap();
+                        let _ = tx.send(pkt).await;
+                    };
+                    let id = pkt.request_id();
+                    pool.spawn_with_options(conn.sender(), id, Vec::new(), work, options.clone())
+                        .unwrap();
+                }
+            }
+            Some(event) = pool.recv() => {
+                let id = event.request_id();
+                if let Some(err) = pool.process_event(event).await {
+                    let pkt = TaskFailure::from(&err).into_packet(id, Command::ShellExecute).unwrap();
+                    let _ = conn.send(pkt).await;
+                }
+            }
+        }
+    }
+}
+
+/// A slave listening on a loopback port, for `--connect`.
+async fn listening_slave(shell: Shell) -> Target {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve(Connection::new(stream), shell));
+        }
+    });
+    Target::parse(&format!("127.0.0.1:{}", port), true).unwrap()
+}
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `exec` as run without `--yes`, on the default config.
+async fn exec(target: &Target, command: &str, timeout: Duration) -> oneshot::Report {
+    oneshot::exec(
+        target,
+        command,
+        timeout,
+        ConfirmGate::default(),
+        Roles::default(),
+        false,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn exec_prints_the_reply() {
+    let target = listening_slave(Shell::Echo).await;
+    let report = exec(&target, "ShellExecute whoami", TIMEOUT).await;
+    assert_eq!(report.exit_code, EXIT_OK, "{}", report.output);
+    assert!(
+        report.output.contains("stdout: whoami"),
+        "{}",
+        report.output
+    );
+    assert!(report.request_id.is_some());
+
+    let report = exec(&target, "Ping", TIMEOUT).await;
+    assert_eq!(report.exit_code, EXIT_OK, "{}", report.output);
+    assert!(report.output.contains("Pong"), "{}", report.output);
+}
+
+#[tokio::test]
+async fn exec_waits_for_a_slave_to_dial_in() {
+    let port = {
+        let probe = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        probe.local_addr().unwrap().port()
+    };
+    tokio::spawn(async move {
+        let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+        loop {
+            if let Ok(conn) = Connection::connect(&info).await {
+                return serve(conn, Shell::Echo).await;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    });
+    let target = Target::parse(&format!("127.0.0.1:{}", port), false).unwrap();
+    let report = exec(&target, "ShellExecute hostname", TIMEOUT).await;
+    assert_eq!(report.exit_code, EXIT_OK, "{}", report.output);
+}
+
+#[tokio::test]
+async fn exec_exit_codes() {
+    let failing = listening_slave(Shell::Fail).await;
+    let report = exec(&failing, "ShellExecute cat missing", TIMEOUT).await;
+    assert_eq!(report.exit_code, EXIT_FAILED);
+    assert!(report.output.contains("no such file"));
+
+    let report = exec(&failing, r"Delete C:\a.txt", TIMEOUT).await;
+    assert_eq!(report.exit_code, EXIT_USAGE, "{}", report.output);
+    assert!(report.output.contains("--yes"), "{}", report.output);
+    let gate = ConfirmGate::default();
+    let roles = Roles::default();
+    let report = oneshot::exec(&failing, r"Delete C:\a.txt", TIMEOUT, gate, roles, true).await;
+    assert_eq!(report.exit_code, EXIT_FAILED);
+    assert!(
+        report.output.starts_with("- Slave Error:"),
+        "{}",
+        report.output
+    );
+
+    let report = exec(&failing, "ShellExecute", TIMEOUT).await;
+    assert_eq!(report.exit_code, EXIT_USAGE, "{}", report.output);
+    let report = exec(&failing, r"watch C:\log.txt", TIMEOUT).await;
+    assert_eq!(report.exit_code, EXIT_USAGE);
+
+    let silent = listening_slave(Shell::Silent).await;
+    let report = exec(&silent, "ShellExecute sleep 60", Duration::from_millis(300)).await;
+    assert_eq!(report.exit_code, EXIT_TIMEOUT);
+
+    let nobody = Target::parse("127.0.0.1:1", true).unwrap();
+    let report = exec(&nobody, "Ping", TIMEOUT).await;
+    assert_eq!(report.exit_code, EXIT_DISCONNECTED);
+}
+
+#[tokio::test]
+async fn task_timeout_fails_the_request_promptly() {
+    let target = listening_slave(Shell::Pooled).await;
+    let report = exec(&target, "ShellExecute sleep 60", Duration::from_secs(1)).await;
+    assert_eq!(report.exit_code, EXIT_FAILED, "{}", report.output);
+    assert!(
+        report.output.contains("task timed out after 1 ms"),
+        "{}",
+        report.output
+    );
+}
+
+#[tokio::test]
+async fn exec_binary_prints_json_without_touching_the_terminal() {
+    let target = listening_slave(Shell::Echo).await;
+    let exe = env!("CARGO_BIN_EXE_tix-master");
+    let args = [
+        "exec",
+        "--connect",
+        "--json",
+        "--target",
+        &target.addr.to_socket_string(),
+        "--timeout",
+        "10s",
+        "--",
+        "ShellExecute",
+        "whoami",
+    ];
+    let output = tokio::process::Command::new(exe)
+        .args(args)
+        .output()
+        .await
+        .unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_OK));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // No alternate screen, raw mode or cursor games.
+    assert!(!stdout.contains('\x1b'), "{:?}", stdout);
+    let json: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(json["exit_code"], 0);
+    assert_eq!(json["command"], "ShellExecute whoami");
+    assert!(json["output"].as_str().unwrap().contains("stdout: whoami"));
+}
+
+#[tokio::test]
+async fn destructive_exec_fails_fast_without_yes() {
+    // Refused before dialing: the target does not even exist.
+    let nobody = Target::parse("127.0.0.1:1", true).unwrap();
+    let report = exec(&nobody, "SystemAction shutdown", TIMEOUT).await;
+    assert_eq!(report.exit_code, EXIT_USAGE);
+    assert!(report.request_id.is_none());
+
+    let exe = env!("CARGO_BIN_EXE_tix-master");
+    let args = [
+        "exec",
+        "--connect",
+        "--target",
+        "127.0.0.1:1",
+        "--",
+        "SystemAction",
+        "shutdown",
+    ];
+    let run = tokio::process::Command::new(exe)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .output();
+    let output = tokio::time::timeout(Duration::from_secs(5), run)
+        .await
+        .expect("exec waited for an answer nobody can give")
+        .unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_USAGE));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("pass --yes"), "{}", stderr);
+}
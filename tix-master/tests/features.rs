@@ -0,0 +1,87 @@
+//! Slave features against a real slave over loopback: a command the
+//! slave said it cannot run is refused on the master and never sent.
+
+mod common;
+
+use tix_core::{ConnectionInfo, Feature, FeatureSet, TixError};
+use tix_master::{Master, MasterEvent};
+use tix_slave::audit::{Audit, AuditEntry};
+use tix_slave::config::{AuditConfig, SlaveConfig};
+use tix_slave::run_with_reconnect;
+use tokio::sync::mpsc;
+
+use common::{run_until_response, run_until_some};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unsupported_commands_never_reach_the_slave() {
+    let dir = std::env::temp_dir().join(format!("tix-features-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let audit = AuditConfig {
+        path: dir.join("audit.jsonl").display().to_string(),
+        commands: vec!["*".to_string(), "!Hello".to_string()],
+        ..AuditConfig::default()
+    };
+    let _ = std::fs::remove_file(&audit.path);
+
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap();
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), master.local_addr().unwrap().port());
+    let slave_audit = Audit::open(&audit).unwrap();
+    tokio::spawn(async move {
+        run_with_reconnect(
+            &info,
+            &SlaveConfig::default(),
+            "features",
+            &slave_audit,
+            None,
+        )
+        .await
+    });
+    master.accept_one().await.unwrap();
+    let (version, features): (String, FeatureSet) =
+        run_until_some(&mut master, &mut ui_rx, |e| match e {
+            MasterEvent::SlaveFeatures { version, features } => Some((version, features?)),
+            _ => None,
+        })
+        .await;
+    // Ctrl+Alt+Del is off in the default config, on every platform.
+    assert!(!features.contains(Feature::Sas));
+
+    let result = master.execute_command("services".to_string()).await;
+    if features.contains(Feature::Services) {
+        result.unwrap();
+        run_until_response(&mut master, &mut ui_rx).await;
+    } else {
+        let err = result.unwrap_err();
+        assert!(matches!(err, TixError::NotSupported { .. }));
+        assert_eq!(
+            err.to_string(),
+            format!("ServiceList not supported by connected slave (v{version})")
+        );
+        let failed = std::iter::from_fn(|| ui_rx.try_recv().ok()).any(
+            |e| matches!(e, MasterEvent::TaskUpdate { status, .. } if status == "Failed: not supported"),
+        );
+        assert!(failed);
+    }
+
+    // Commands outside any feature always go through.
+    master
+        .execute_command(format!("ListDir {}", dir.display()))
+        .await
+        .unwrap();
+    run_until_response(&mut master, &mut ui_rx).await;
+    let audited: Vec<String> = std::fs::read_to_string(&audit.path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str::<AuditEntry>(line).unwrap().command)
+        .collect();
+    if features.contains(Feature::Services) {
+        assert_eq!(audited, ["ServiceList", "ListDir"]);
+    } else {
+        assert_eq!(audited, ["ListDir"]);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,99 @@
+//! A slave that reconnects is recognised by the ID in its `Hello`.
+
+use std::time::Duration;
+
+use tix_core::protocol::HelloInfo;
+use tix_core::{Command, Connection, ConnectionInfo};
+use tix_master::inventory::Inventory;
+use tix_master::{Master, MasterEvent};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+const SLAVE_ID: &str = "0b9f4c52-8a7e-4d21-b3c6-5e1f2a7d9c40";
+
+/// A slave listening on loopback that answers every `Hello` with
+/// `SLAVE_ID` and then waits for the master to hang up.
+async fn slave() -> ConnectionInfo {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let mut conn = Connection::new(stream);
+            tokio::spawn(async move {
+                while let Some(pkt) = conn.recv().await {
+                    if pkt.command().ok() == Some(Command::Hello) {
+                        let mut hello =
+                            HelloInfo::local("tix-slave", "0.1.0").with_slave_id(SLAVE_ID);
+                        hello.hostname = "LAB-PC".to_string();
+                        let reply = hello.into_response_packet(pkt.request_id()).unwrap();
+                        conn.send(reply).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+    ConnectionInfo::new("127.0.0.1".to_string(), port)
+}
+
+/// Connect with the inventory at `path` and wait for the slave to
+/// identify itself. Returns the master and the log lines so far.
+async fn connect(slave: &ConnectionInfo, path: &std::path::Path) -> (Master, Vec<String>) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let inventory = Inventory::load(path).unwrap();
+    let mut master = Master::connect(slave.clone(), tx)
+        .await
+        .unwrap()
+        .with_inventory(inventory);
+    let mut logs = Vec::new();
+    let identified = async {
+        loop {
+            master.process_connection().await.unwrap();
+            while let Ok(event) = rx.try_recv() {
+                match event {
+                    MasterEvent::Log(line) => logs.push(line.to_string()),
+                    MasterEvent::SlaveNamed(name) => return name,
+                    _ => {}
+                }
+            }
+        }
+    };
+    let name = tokio::time::timeout(Duration::from_secs(10), identified)
+        .await
+        .expect("slave never identified itself");
+    logs.push(format!("named {}", name));
+    (master, logs)
+}
+
+#[tokio::test]
+async fn reconnecting_slave_keeps_its_identity() {
+    let path = std::env::temp_dir().join(format!("tix-identity-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let slave = slave().await;
+
+    let (mut master, logs) = connect(&slave, &path).await;
+    assert_eq!(master.slave_id(), Some(SLAVE_ID));
+    assert!(
+        logs.iter().any(|l| l.contains("New slave LAB-PC")),
+        "{:?}",
+        logs
+    );
+    master
+        .execute_command("name current front desk".to_string())
+        .await
+        .unwrap();
+    drop(master);
+
+    // A fresh master process reading the same inventory file.
+    let (master, logs) = connect(&slave, &path).await;
+    assert_eq!(master.slave_id(), Some(SLAVE_ID));
+    assert!(
+        logs.iter().any(|l| l.contains("front desk is back")),
+        "{:?}",
+        logs
+    );
+    assert!(logs.iter().any(|l| l == "named front desk"), "{:?}", logs);
+    let record = master.inventory().get(SLAVE_ID).unwrap();
+    assert_eq!(record.last_ip, "127.0.0.1");
+    assert_eq!(master.inventory().len(), 1);
+    let _ = std::fs::remove_file(&path);
+}
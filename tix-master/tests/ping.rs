@@ -0,0 +1,78 @@
+//! `ping -c N` against a loopback slave that answers every Ping.
+
+use std::time::Duration;
+
+use tix_core::{Command, Connection, ConnectionInfo, Packet, TixError};
+use tix_master::{Master, MasterEvent};
+use tokio::sync::mpsc;
+
+/// Minimal slave: reply "Pong" to every Ping until the master goes away.
+async fn echo_slave(port: u16) {
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+    let mut conn = Connection::connect(&info).await.unwrap();
+    while let Some(pkt) = conn.recv().await {
+        if matches!(pkt.command(), Ok(Command::Ping)) {
+            let pong =
+                Packet::new_response(pkt.request_id(), Command::Ping, b"Pong".to_vec()).unwrap();
+            if conn.send(pong).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn ping_burst_reports_loopback_rtt() {
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap();
+    let port = master.local_addr().unwrap().port();
+    let err = master.execute_command("ping".to_string()).await;
+    assert!(matches!(err, Err(TixError::NotConnected)));
+
+    let slave = tokio::spawn(echo_slave(port));
+    master.accept_one().await.unwrap();
+    master
+        .execute_command("ping -c 3 -i 100".to_string())
+        .await
+        .unwrap();
+
+    let run = async {
+        loop {
+            let ping_due = master.next_ping_due();
+            tokio::select! {
+                _ = master.process_connection() => {}
+                _ = async {
+                    match ping_due {
+                        Some(due) => tokio::time::sleep_until(due.into()).await,
+                        None => std::future::pending().await,
+                    }
+                } => master.send_due_pings().await,
+            }
+            while let Ok(event) = ui_rx.try_recv() {
+                if let MasterEvent::Log(line) = event
+                    && line.contains("ping statistics")
+                {
+                    return line;
+                }
+            }
+        }
+    };
+    let summary = tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .expect("ping burst did not finish");
+
+    assert!(
+        summary.contains("3 packets transmitted, 3 received, 0% packet loss"),
+        "{summary}"
+    );
+    assert!(summary.contains("rtt min/avg/max"), "{summary}");
+
+    let stats = master.ping_stats();
+    assert_eq!(stats.received(), 3);
+    assert_eq!(stats.lost(), 0);
+    assert!(stats.max().unwrap() < Duration::from_secs(1));
+
+    slave.abort();
+}
@@ -0,0 +1,128 @@
+//! Operator roles against a real slave over loopback: what a role holds
+//! back never reaches the slave, which audits everything it is sent.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use tix_core::{ConnectionInfo, TixError};
+use tix_master::config::RoleConfig;
+use tix_master::roles::{self, Roles};
+use tix_master::{Master, MasterEvent};
+use tix_slave::audit::{Audit, AuditEntry};
+use tix_slave::config::{AuditConfig, SlaveConfig};
+use tix_slave::run_with_reconnect;
+use tokio::sync::mpsc;
+
+use common::run_until;
+
+/// Commands the slave's audit log has seen.
+fn audited(config: &AuditConfig) -> Vec<String> {
+    std::fs::read_to_string(&config.path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str::<AuditEntry>(line).unwrap().command)
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn refused_commands_never_reach_the_slave() {
+    let dir = std::env::temp_dir().join(format!("tix-roles-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let audit = AuditConfig {
+        path: dir.join("audit.jsonl").display().to_string(),
+        commands: vec!["*".to_string(), "!Hello".to_string()],
+        ..AuditConfig::default()
+    };
+    let _ = std::fs::remove_file(&audit.path);
+
+    let mut config = BTreeMap::new();
+    config.insert(
+        "viewer".to_string(),
+        RoleConfig {
+            allow: vec!["ListDir".to_string(), "Ping".to_string()],
+            password: None,
+        },
+    );
+    config.insert(
+        "admin".to_string(),
+        RoleConfig {
+            allow: vec!["*".to_string()],
+            password: Some(roles::hash_password("hunter2").unwrap()),
+        },
+    );
+    let mut roles = Roles::new(&config);
+    roles.start_as("viewer").unwrap();
+
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap()
+        .with_roles(roles);
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), master.local_addr().unwrap().port());
+    let slave_audit = Audit::open(&audit).unwrap();
+    tokio::spawn(async move {
+        run_with_reconnect(&info, &SlaveConfig::default(), "roles", &slave_audit, None).await
+    });
+    master.accept_one().await.unwrap();
+    run_until(
+        &mut master,
+        &mut ui_rx,
+        |e| matches!(e, MasterEvent::Log(line) if line.starts_with("[CONN] Slave runs")),
+    )
+    .await;
+
+    // Refused on the master, and shown as a failed task.
+    let err = master
+        .execute_command("ShellExecute whoami".to_string())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, TixError::NotPermitted { .. }));
+    assert_eq!(
+        err.to_string(),
+        "ShellExecute not permitted for role viewer"
+    );
+    let failed = std::iter::from_fn(|| ui_rx.try_recv().ok()).any(
+        |e| matches!(e, MasterEvent::TaskUpdate { status, .. } if status.starts_with("Failed")),
+    );
+    assert!(failed);
+
+    // What the role allows goes through.
+    master
+        .execute_command(format!("ListDir {}", dir.display()))
+        .await
+        .unwrap();
+    run_until(&mut master, &mut ui_rx, |e| {
+        matches!(e, MasterEvent::Response { .. })
+    })
+    .await;
+    assert_eq!(audited(&audit), ["ListDir"]);
+
+    // A wrong password changes nothing; the right one lifts the limits.
+    let err = master
+        .execute_command("role admin hunter3".to_string())
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "wrong password for role admin");
+    assert!(
+        master
+            .execute_command("ShellExecute whoami".to_string())
+            .await
+            .is_err()
+    );
+    master
+        .execute_command("role admin hunter2".to_string())
+        .await
+        .unwrap();
+    master
+        .execute_command("ShellExecute whoami".to_string())
+        .await
+        .unwrap();
+    run_until(&mut master, &mut ui_rx, |e| {
+        matches!(e, MasterEvent::Response { .. })
+    })
+    .await;
+    assert_eq!(audited(&audit), ["ListDir", "ShellExecute"]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
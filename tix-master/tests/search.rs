@@ -0,0 +1,159 @@
+//! `find` against a loopback slave that streams canned matches.
+
+mod common;
+
+use tix_core::protocol::{
+    FileMetadata, FileSearchBatch, FileSearchRequest, FileSearchSummary, HelloInfo,
+};
+use tix_core::{Command, Connection, ConnectionInfo, Packet};
+use tix_master::{Master, MasterEvent};
+use tokio::sync::mpsc;
+
+use common::run_until;
+
+fn entry(path: &str) -> FileMetadata {
+    FileMetadata {
+        name: path.rsplit('/').next().unwrap().to_string(),
+        path: path.to_string(),
+        size: 10,
+        modified: 0,
+        is_directory: false,
+        hash: None,
+        attributes: Default::default(),
+    }
+}
+
+/// Answers a `FileSearch` for `/logs` with two batches and a summary,
+/// and one for `/slow` with a batch and then nothing.
+async fn fake_slave(port: u16) {
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+    let mut conn = Connection::connect(&info).await.unwrap();
+    while let Some(pkt) = conn.recv().await {
+        let req_id = pkt.request_id();
+        let mut replies: Vec<Packet> = Vec::new();
+        match pkt.command() {
+            Ok(Command::FileSearch) => {
+                let req = FileSearchRequest::from_bytes(pkt.payload()).unwrap();
+                let batch = |n, paths: &[&str]| FileSearchBatch {
+                    batch_number: n,
+                    matches: paths.iter().map(|p| entry(p)).collect(),
+                };
+                replies.push(batch(0, &["/logs/a.log"]).into_packet(req_id).unwrap());
+                if req.root == "/logs" {
+                    replies.push(batch(1, &["/logs/x/b.log"]).into_packet(req_id).unwrap());
+                    let summary = FileSearchSummary {
+                        matches: 2,
+                        directories_scanned: 2,
+                        ..Default::default()
+                    };
+                    replies.push(summary.into_packet(req_id).unwrap());
+                }
+            }
+            Ok(Command::Hello) => {
+                let hello = HelloInfo::local("tix-slave", "0.1.0");
+                replies.push(hello.into_response_packet(req_id).unwrap());
+            }
+            Ok(Command::ShellCancel) => {
+                let target = u64::from_le_bytes(pkt.payload().try_into().unwrap());
+                let text = format!("Cancelled ReqID {}", target);
+                replies.push(
+                    Packet::new_response(req_id, Command::ShellCancel, text.into_bytes()).unwrap(),
+                );
+            }
+            _ => {}
+        }
+        for reply in replies {
+            if conn.send(reply).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn logs(events: &[MasterEvent]) -> Vec<&str> {
+    events
+        .iter()
+        .filter_map(|e| match e {
+            MasterEvent::Log(line) => Some(line.text()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn find_streams_matches_then_cancel_stops_tracking() {
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap();
+    let port = master.local_addr().unwrap().port();
+    tokio::spawn(fake_slave(port));
+    master.accept_one().await.unwrap();
+
+    master
+        .execute_command("find /logs *.log".to_string())
+        .await
+        .unwrap();
+    let events = run_until(
+        &mut master,
+        &mut ui_rx,
+        |e| matches!(e, MasterEvent::TaskUpdate { status, .. } if status.starts_with("Solved")),
+    )
+    .await;
+    let lines = logs(&events);
+    assert!(
+        lines.iter().any(|l| l.contains("/logs/a.log (10 B)")),
+        "{:?}",
+        lines
+    );
+    assert!(
+        lines.iter().any(|l| l.contains("/logs/x/b.log")),
+        "{:?}",
+        lines
+    );
+    assert!(
+        lines.iter().any(|l| l.starts_with("[FIND] 2 match(es)")),
+        "{:?}",
+        lines
+    );
+    assert!(events.iter().any(|e| matches!(
+        e,
+        MasterEvent::TaskUpdate { status, .. } if status.contains("(1 found)")
+    )));
+    assert_eq!(master.pending_request_count(), 0);
+
+    // A search that never finishes on its own.
+    master
+        .execute_command("find /slow x".to_string())
+        .await
+        .unwrap();
+    let events = run_until(
+        &mut master,
+        &mut ui_rx,
+        |e| matches!(e, MasterEvent::TaskUpdate { status, .. } if status.contains("(1 found)")),
+    )
+    .await;
+    let id = events
+        .iter()
+        .find_map(|e| match e {
+            MasterEvent::TaskUpdate { id, .. } => Some(*id),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(master.pending_request_count(), 1);
+    master
+        .execute_command(format!("cancel {}", id))
+        .await
+        .unwrap();
+    let events = run_until(
+        &mut master,
+        &mut ui_rx,
+        |e| matches!(e, MasterEvent::Response { text, .. } if text.contains("Cancelled ReqID")),
+    )
+    .await;
+    assert!(events.iter().any(|e| matches!(
+        e,
+        MasterEvent::TaskUpdate { id: row, status } if *row == id && status.starts_with("Cancelled search")
+    )));
+    assert_eq!(master.pending_request_count(), 0);
+}
@@ -0,0 +1,110 @@
+//! Request IDs and session nonces across a reconnect, against a fake
+//! slave that answers late.
+
+mod common;
+
+use std::time::Duration;
+
+use tix_core::protocol::HelloInfo;
+use tix_core::{Command, Connection, ConnectionInfo, Packet, WireVersion};
+use tix_master::{Master, MasterEvent};
+use tokio::sync::mpsc;
+
+use common::run_until;
+
+/// Connect a fake slave, answer the master's `Hello` and switch to TIX2
+/// like a real slave would.
+async fn connect_slave(
+    master: &mut Master,
+    ui_rx: &mut mpsc::UnboundedReceiver<MasterEvent>,
+) -> Connection {
+    let port = master.local_addr().unwrap().port();
+    let mut slave = Connection::connect(&ConnectionInfo::new("127.0.0.1".to_string(), port))
+        .await
+        .unwrap();
+    master.accept_one().await.unwrap();
+    let hello = slave.recv().await.unwrap();
+    assert_eq!(hello.command().unwrap(), Command::Hello);
+    let reply = HelloInfo::local("tix-slave", "0.2.0")
+        .into_response_packet(hello.request_id())
+        .unwrap();
+    slave.send(reply).await.unwrap();
+    slave.set_wire_version(WireVersion::V2);
+    run_until(
+        master,
+        ui_rx,
+        |e| matches!(e, MasterEvent::Log(line) if line.starts_with("[CONN] Slave runs")),
+    )
+    .await;
+    slave
+}
+
+/// Have the master send a `Copy` and read it on the slave's side.
+async fn copy_request(master: &mut Master, slave: &mut Connection) -> Packet {
+    master
+        .execute_command("Copy a b".to_string())
+        .await
+        .unwrap();
+    let request = tokio::time::timeout(Duration::from_secs(5), slave.recv())
+        .await
+        .expect("no request")
+        .unwrap();
+    assert_eq!(request.command().unwrap(), Command::Copy);
+    request
+}
+
+#[tokio::test]
+async fn straggler_from_an_earlier_session_is_dropped() {
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap();
+
+    // First session: a request that is never answered, then the slave
+    // goes away.
+    let mut first = connect_slave(&mut master, &mut ui_rx).await;
+    let old = copy_request(&mut master, &mut first).await;
+    assert_ne!(old.session(), 0);
+    drop(first);
+    run_until(&mut master, &mut ui_rx, |e| {
+        matches!(e, MasterEvent::SlaveDisconnected(_))
+    })
+    .await;
+
+    // Second session: new IDs, new nonce.
+    let mut second = connect_slave(&mut master, &mut ui_rx).await;
+    let new = copy_request(&mut master, &mut second).await;
+    assert_ne!(new.session(), 0);
+    assert_ne!(new.session(), old.session());
+    assert_ne!(new.request_id(), old.request_id());
+    second.set_session(new.session());
+
+    // The old session's answer turns up under the new request's ID; it
+    // must not be taken for the answer.
+    let mut straggler =
+        Packet::new_response(new.request_id(), Command::Copy, b"stale copy".to_vec()).unwrap();
+    straggler.set_session(old.session());
+    second.send(straggler).await.unwrap();
+    let answer =
+        Packet::new_response(new.request_id(), Command::Copy, b"fresh copy".to_vec()).unwrap();
+    second.send(answer).await.unwrap();
+
+    let events = run_until(&mut master, &mut ui_rx, |e| {
+        matches!(e, MasterEvent::Response { .. })
+    })
+    .await;
+    let Some(MasterEvent::Response { id, text }) = events.last() else {
+        unreachable!()
+    };
+    assert_eq!((*id, text.as_str()), (new.request_id(), "fresh copy"));
+    let stale = format!(
+        "[LATE] stale response for req {} (Copy), from an earlier session",
+        new.request_id()
+    );
+    assert!(
+        events
+            .iter()
+            .any(|e| matches!(e, MasterEvent::Log(line) if line.starts_with(&stale))),
+        "{events:?}"
+    );
+}
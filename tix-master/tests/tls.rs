@@ -0,0 +1,86 @@
+//! A master that only accepts slaves over TLS, against a real slave
+//! pinned to its certificate and a plaintext one.
+
+mod common;
+
+use tix_core::network::tls::{TlsAcceptor, TlsConfig};
+use tix_core::testing::temp_dir;
+use tix_core::{Connection, ConnectionInfo};
+use tix_master::{Master, MasterEvent};
+use tix_slave::audit::Audit;
+use tix_slave::config::SlaveConfig;
+use tix_slave::run_with_reconnect;
+use tokio::sync::mpsc;
+
+use common::{run_until, run_until_response};
+
+fn acceptor(dir: &std::path::Path) -> TlsAcceptor {
+    TlsConfig {
+        enabled: true,
+        cert: dir.join("master.crt"),
+        key: dir.join("master.key"),
+        ..TlsConfig::default()
+    }
+    .acceptor()
+    .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pinned_slave_works_over_tls() {
+    let dir = temp_dir("master-tls-pinned");
+    let acceptor = acceptor(&dir);
+    let mut config = SlaveConfig::default();
+    config.tls.enabled = true;
+    config.tls.pin_sha256 = acceptor.fingerprint().to_string();
+
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap()
+        .with_tls(acceptor);
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), master.local_addr().unwrap().port());
+    let slave = tokio::spawn(async move {
+        run_with_reconnect(&info, &config, "tls", &Audit::default(), None).await
+    });
+    master.accept_one().await.unwrap();
+    run_until(
+        &mut master,
+        &mut ui_rx,
+        |e| matches!(e, MasterEvent::Log(line) if line.starts_with("[CONN] Slave runs")),
+    )
+    .await;
+
+    // A full request round trip over the session.
+    master
+        .execute_command(format!("ListDir {}", dir.display()))
+        .await
+        .unwrap();
+    let response = run_until_response(&mut master, &mut ui_rx).await;
+    assert_eq!(response, "Directory listing received");
+
+    slave.abort();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn plaintext_slave_is_turned_away() {
+    let dir = temp_dir("master-tls-plaintext");
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap()
+        .with_tls(acceptor(&dir));
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), master.local_addr().unwrap().port());
+
+    let (accepted, slave) = tokio::join!(master.accept_one(), Connection::connect(&info));
+    let mut slave = slave.unwrap();
+    assert!(accepted.is_err());
+    assert!(!master.is_connected());
+    let warned = std::iter::from_fn(|| ui_rx.try_recv().ok())
+        .any(|e| matches!(e, MasterEvent::Log(line) if line.starts_with("[WARN] TLS handshake")));
+    assert!(warned);
+    // The slave's side just closes.
+    assert!(slave.recv().await.is_none());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,94 @@
+//! File transactions against a real slave over loopback: every step
+//! happens, or the slave puts back the ones that did.
+
+mod common;
+
+use tix_core::ConnectionInfo;
+use tix_master::{Master, MasterEvent};
+use tix_slave::audit::Audit;
+use tix_slave::config::SlaveConfig;
+use tix_slave::run_with_reconnect;
+use tokio::sync::mpsc;
+
+use common::{run_until, run_until_response};
+
+/// Send `command` and wait for the text of its response.
+async fn transact(
+    master: &mut Master,
+    ui_rx: &mut mpsc::UnboundedReceiver<MasterEvent>,
+    command: String,
+) -> String {
+    master.execute_command(command).await.unwrap();
+    run_until_response(master, ui_rx).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_failed_step_puts_the_earlier_ones_back() {
+    let dir = std::env::temp_dir().join(format!("tix-transaction-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.txt"), b"alpha").unwrap();
+    std::fs::write(dir.join("b.txt"), b"beta").unwrap();
+
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap();
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), master.local_addr().unwrap().port());
+    tokio::spawn(async move {
+        let audit = Audit::default();
+        run_with_reconnect(&info, &SlaveConfig::default(), "transaction", &audit, None).await
+    });
+    master.accept_one().await.unwrap();
+    run_until(
+        &mut master,
+        &mut ui_rx,
+        |e| matches!(e, MasterEvent::Log(line) if line.starts_with("[CONN] Slave runs")),
+    )
+    .await;
+    let at = |name: &str| format!("\"{}\"", dir.join(name).display());
+
+    let text = transact(
+        &mut master,
+        &mut ui_rx,
+        format!(
+            "Transaction mkdir {}; move {} {} --confirm",
+            at("kept"),
+            at("a.txt"),
+            at("kept/a.txt")
+        ),
+    )
+    .await;
+    assert!(
+        text.starts_with("Transaction committed: 2 step(s)"),
+        "{text}"
+    );
+    assert_eq!(
+        std::fs::read(dir.join("kept").join("a.txt")).unwrap(),
+        b"alpha"
+    );
+
+    // The third step has nothing to move, so the first two are undone.
+    let text = transact(
+        &mut master,
+        &mut ui_rx,
+        format!(
+            "Transaction mkdir {}; move {} {}; move {} {} --confirm",
+            at("new"),
+            at("b.txt"),
+            at("new/b.txt"),
+            at("a.txt"),
+            at("new/a.txt")
+        ),
+    )
+    .await;
+    assert!(
+        text.starts_with("Transaction rolled back: step 3 failed"),
+        "{text}"
+    );
+    assert!(text.contains("does not exist"), "{text}");
+    assert!(dir.join("b.txt").exists());
+    assert!(!dir.join("new").exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
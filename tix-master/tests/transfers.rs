@@ -0,0 +1,176 @@
+//! Downloads through the transfer queue, against a loopback slave
+//! serving real temp files.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use tix_core::protocol::file::DEFAULT_CHUNK_SIZE;
+use tix_core::protocol::{
+    DeltaSyncRequest, FileDigest, FileHashRequest, FileHashResponse, TransferConfig,
+};
+use tix_core::testing::temp_dir;
+use tix_core::{Command, Connection, ConnectionInfo, Packet, ProtocolFlags};
+use tix_master::{Master, MasterEvent};
+use tokio::sync::mpsc;
+
+use common::run_until_response;
+
+/// Minimal slave: hashes files and answers delta syncs from disk,
+/// reporting how many chunks each sync sent. With `answer` off it never
+/// replies, so a transfer stays running.
+async fn fake_slave(port: u16, answer: bool, synced: mpsc::UnboundedSender<usize>) {
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+    let mut conn = Connection::connect(&info).await.unwrap();
+    while let Some(pkt) = conn.recv().await {
+        let req_id = pkt.request_id();
+        let replies: Vec<Packet> = match pkt.command() {
+            _ if !answer => Vec::new(),
+            Ok(Command::FileHash) => {
+                let req = FileHashRequest::from_bytes(pkt.payload()).unwrap();
+                let response = match std::fs::read(&req.path) {
+                    Ok(data) => FileHashResponse::Hashed(FileDigest::of(&data, req.chunk_size)),
+                    Err(e) => FileHashResponse::Failed(e.to_string()),
+                };
+                vec![response.into_packet(req_id).unwrap()]
+            }
+            Ok(Command::FileRead) if pkt.flags().contains(ProtocolFlags::ACK_REQUESTED) => {
+                let req = DeltaSyncRequest::from_bytes(pkt.payload()).unwrap();
+                let data = std::fs::read(&req.path).unwrap();
+                let chunks = req.changed_chunks(&data);
+                let _ = synced.send(chunks.len());
+                let mut replies: Vec<Packet> = chunks
+                    .into_iter()
+                    .map(|c| c.into_packet(req_id, Command::FileRead).unwrap())
+                    .collect();
+                replies.push(
+                    req.verification(&data)
+                        .into_packet(req_id, Command::FileRead)
+                        .unwrap(),
+                );
+                replies
+            }
+            _ => Vec::new(),
+        };
+        for reply in replies {
+            if conn.send(reply).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// A master connected to a fake slave.
+async fn connect(
+    config: &TransferConfig,
+    answer: bool,
+) -> (
+    Master,
+    mpsc::UnboundedReceiver<MasterEvent>,
+    mpsc::UnboundedReceiver<usize>,
+) {
+    let (ui_tx, ui_rx) = mpsc::unbounded_channel();
+    let (synced_tx, synced_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap()
+        .with_transfer_config(config);
+    let port = master.local_addr().unwrap().port();
+    tokio::spawn(fake_slave(port, answer, synced_tx));
+    master.accept_one().await.unwrap();
+    (master, ui_rx, synced_rx)
+}
+
+fn contents(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+fn part_of(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+#[tokio::test]
+async fn download_lands_once_the_hash_matches() {
+    let dir = temp_dir("transfers-download");
+    let remote = dir.join("remote.bin");
+    std::fs::write(&remote, contents(3 * DEFAULT_CHUNK_SIZE + 17)).unwrap();
+    let local = dir.join("here");
+
+    let (mut master, mut ui_rx, _) = connect(&TransferConfig::default(), true).await;
+    master
+        .execute_command(format!(
+            "Download {}|{}/",
+            remote.display(),
+            local.display()
+        ))
+        .await
+        .unwrap();
+    let response = run_until_response(&mut master, &mut ui_rx).await;
+    assert!(response.starts_with("Download complete: "), "{response}");
+
+    let landed = local.join("remote.bin");
+    assert_eq!(
+        std::fs::read(&landed).unwrap(),
+        std::fs::read(&remote).unwrap()
+    );
+    assert!(!part_of(&landed).exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn resumable_download_fetches_only_what_the_part_lacks() {
+    let dir = temp_dir("transfers-resume");
+    let remote = dir.join("remote.bin");
+    let data = contents(4 * DEFAULT_CHUNK_SIZE);
+    std::fs::write(&remote, &data).unwrap();
+    let dest = dir.join("copy.bin");
+    // An earlier attempt got the first two chunks.
+    std::fs::write(part_of(&dest), &data[..2 * DEFAULT_CHUNK_SIZE]).unwrap();
+
+    let config = TransferConfig {
+        resumable: true,
+        ..TransferConfig::default()
+    };
+    let (mut master, mut ui_rx, mut synced) = connect(&config, true).await;
+    master
+        .execute_command(format!("Download {}|{}", remote.display(), dest.display()))
+        .await
+        .unwrap();
+    let response = run_until_response(&mut master, &mut ui_rx).await;
+    assert!(response.starts_with("Download complete: "), "{response}");
+    assert_eq!(synced.recv().await, Some(2));
+    assert_eq!(std::fs::read(&dest).unwrap(), data);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn cancelled_download_deletes_its_part() {
+    let dir = temp_dir("transfers-cancel");
+    let dest = dir.join("copy.bin");
+    std::fs::write(part_of(&dest), b"left over").unwrap();
+
+    // The slave never answers, so the item is still running.
+    let (mut master, mut ui_rx, _) = connect(&TransferConfig::default(), false).await;
+    master
+        .execute_command(format!("Download C:\\data\\copy.bin|{}", dest.display()))
+        .await
+        .unwrap();
+    master
+        .execute_command("transfer cancel 1".to_string())
+        .await
+        .unwrap();
+    let response = run_until_response(&mut master, &mut ui_rx).await;
+    assert_eq!(response, "Download cancelled: 0 of 1 file(s) arrived");
+    assert!(!part_of(&dest).exists());
+
+    // A cancelled item cannot be cancelled again.
+    assert!(
+        master
+            .execute_command("transfer cancel 1".to_string())
+            .await
+            .is_err()
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
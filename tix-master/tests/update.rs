@@ -0,0 +1,128 @@
+//! `update` against a loopback slave that stages the upload in memory.
+
+mod common;
+
+use tix_core::protocol::{
+    FileHashVerification, FileTransferAck, FileTransferHeader, HelloInfo, UpdateApplyRequest,
+    UpdateApplyResponse,
+};
+use tix_core::{Command, Connection, ConnectionInfo, ProtocolFlags};
+use tix_master::{Master, MasterEvent};
+use tokio::sync::mpsc;
+
+use common::run_until_line;
+
+/// Minimal slave: answers Hello as `version`, acks uploads, and accepts
+/// any UpdateApply whose hash matches what it received. Reports the
+/// request it was asked to apply.
+async fn fake_slave(port: u16, version: &str, applied: mpsc::UnboundedSender<UpdateApplyRequest>) {
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+    let mut conn = Connection::connect(&info).await.unwrap();
+    let mut staged = String::new();
+    let mut received = [0u8; 32];
+    while let Some(pkt) = conn.recv().await {
+        let reply = match pkt.command() {
+            Ok(Command::Hello) => HelloInfo::local("tix-slave", version)
+                .into_response_packet(pkt.request_id())
+                .ok(),
+            Ok(Command::FileWrite) if pkt.flags().contains(ProtocolFlags::FINAL_FRAGMENT) => {
+                received = FileHashVerification::from_bytes(pkt.payload())
+                    .unwrap()
+                    .blake3_hash;
+                FileTransferAck {
+                    path: format!("C:\\Users\\me\\Downloads\\{}", staged),
+                    bytes_written: 0,
+                    error: None,
+                }
+                .into_packet(pkt.request_id())
+                .ok()
+            }
+            Ok(Command::FileWrite) => {
+                if let Ok(header) = FileTransferHeader::from_bytes(pkt.payload()) {
+                    staged = header.path;
+                }
+                None
+            }
+            Ok(Command::UpdateApply) => {
+                let req = UpdateApplyRequest::from_bytes(pkt.payload()).unwrap();
+                assert_eq!(req.blake3_hash, received);
+                let response = UpdateApplyResponse::Accepted {
+                    previous_version: version.to_string(),
+                    new_version: req.version.clone(),
+                    restarting: req.restart,
+                };
+                let _ = applied.send(req);
+                response.into_packet(pkt.request_id()).ok()
+            }
+            _ => None,
+        };
+        if let Some(reply) = reply
+            && conn.send(reply).await.is_err()
+        {
+            return;
+        }
+    }
+}
+
+#[tokio::test]
+async fn update_uploads_then_applies() {
+    let exe = std::env::temp_dir().join(format!("tix-update-test-{}.exe", std::process::id()));
+    std::fs::write(&exe, vec![0x4D; 100_000]).unwrap();
+
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap();
+    let port = master.local_addr().unwrap().port();
+    let (applied_tx, mut applied_rx) = mpsc::unbounded_channel();
+    let slave = tokio::spawn(fake_slave(port, "0.1.0", applied_tx));
+
+    master.accept_one().await.unwrap();
+    run_until_line(&mut master, &mut ui_rx, "Slave runs tix-slave 0.1.0").await;
+    assert_eq!(master.slave_version(), Some("0.1.0"));
+
+    master
+        .execute_command(format!("update \"{}\" 0.2.0", exe.display()))
+        .await
+        .unwrap();
+    let line = run_until_line(&mut master, &mut ui_rx, "Updated slave").await;
+    assert!(line.contains("0.1.0 -> 0.2.0, restarting"), "{line}");
+
+    let req = applied_rx.recv().await.unwrap();
+    assert!(req.staged_path.ends_with("tix-slave-0.2.0.update"));
+    assert_eq!(
+        req.blake3_hash,
+        *blake3::hash(&std::fs::read(&exe).unwrap()).as_bytes()
+    );
+
+    let _ = std::fs::remove_file(&exe);
+    slave.abort();
+}
+
+#[tokio::test]
+async fn up_to_date_slave_is_skipped() {
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap();
+    let port = master.local_addr().unwrap().port();
+    let (applied_tx, _applied_rx) = mpsc::unbounded_channel();
+    let slave = tokio::spawn(fake_slave(port, "0.3.0", applied_tx));
+
+    master.accept_one().await.unwrap();
+    run_until_line(&mut master, &mut ui_rx, "Slave runs").await;
+
+    master
+        .execute_command("update new.exe 0.2.0".to_string())
+        .await
+        .unwrap();
+    let mut skipped = false;
+    while let Ok(event) = ui_rx.try_recv() {
+        if let MasterEvent::Log(line) = event {
+            skipped |= line.contains("Skipped: slave already runs 0.3.0");
+        }
+    }
+    assert!(skipped);
+    assert_eq!(master.pending_request_count(), 0);
+    slave.abort();
+}
@@ -0,0 +1,263 @@
+//! `watch` against a loopback slave serving a real temp file.
+
+mod common;
+
+use std::path::PathBuf;
+
+use tix_core::format::format_bytes;
+use tix_core::protocol::file::DEFAULT_CHUNK_SIZE;
+use tix_core::protocol::{DeltaSyncRequest, FileDigest, FileHashRequest, FileHashResponse};
+use tix_core::{Command, Connection, ConnectionInfo, Packet, ProtocolFlags, TixError};
+use tix_master::config::WatchConfig;
+use tix_master::{Master, MasterEvent};
+use tokio::sync::mpsc;
+
+use common::run_until_line;
+
+/// What the fake slave gets wrong in its delta-sync replies.
+#[derive(Clone, Copy, PartialEq)]
+enum Fault {
+    None,
+    /// The first reply's chunk 1 arrives with a bit flipped.
+    CorruptFirstReply,
+    /// The file is rewritten between reading the chunks and hashing it
+    /// for the verification, on every reply.
+    ChangesWhileRead,
+}
+
+/// Minimal slave: hashes files and answers delta syncs from disk.
+async fn fake_slave(port: u16) {
+    faulty_slave(port, Fault::None).await
+}
+
+async fn faulty_slave(port: u16, fault: Fault) {
+    let mut syncs = 0u32;
+    let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+    let mut conn = Connection::connect(&info).await.unwrap();
+    while let Some(pkt) = conn.recv().await {
+        let req_id = pkt.request_id();
+        let replies: Vec<Packet> = match pkt.command() {
+            Ok(Command::FileHash) => {
+                let req = FileHashRequest::from_bytes(pkt.payload()).unwrap();
+                let response = match std::fs::read(&req.path) {
+                    Ok(data) => FileHashResponse::Hashed(FileDigest::of(&data, req.chunk_size)),
+                    Err(e) => FileHashResponse::Failed(e.to_string()),
+                };
+                vec![response.into_packet(req_id).unwrap()]
+            }
+            Ok(Command::FileRead) if pkt.flags().contains(ProtocolFlags::ACK_REQUESTED) => {
+                let req = DeltaSyncRequest::from_bytes(pkt.payload()).unwrap();
+                let mut data = std::fs::read(&req.path).unwrap();
+                let mut chunks = req.changed_chunks(&data);
+                syncs += 1;
+                match fault {
+                    Fault::CorruptFirstReply if syncs == 1 => {
+                        let chunk = chunks.iter_mut().find(|c| c.chunk_index == 1).unwrap();
+                        chunk.data[7] ^= 0x01;
+                    }
+                    Fault::ChangesWhileRead => {
+                        data[DEFAULT_CHUNK_SIZE + 3] = syncs as u8;
+                        std::fs::write(&req.path, &data).unwrap();
+                    }
+                    _ => {}
+                }
+                let mut replies: Vec<Packet> = chunks
+                    .into_iter()
+                    .map(|c| c.into_packet(req_id, Command::FileRead).unwrap())
+                    .collect();
+                replies.push(
+                    req.verification(&data)
+                        .into_packet(req_id, Command::FileRead)
+                        .unwrap(),
+                );
+                replies
+            }
+            _ => Vec::new(),
+        };
+        for reply in replies {
+            if conn.send(reply).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// The first log line containing `needle` and the lines logged right
+/// after it, such as a change's diff.
+async fn logged_from(
+    master: &mut Master,
+    ui_rx: &mut mpsc::UnboundedReceiver<MasterEvent>,
+    needle: &str,
+) -> Vec<String> {
+    let mut lines = vec![run_until_line(master, ui_rx, needle).await];
+    while let Ok(event) = ui_rx.try_recv() {
+        if let MasterEvent::Log(line) = event {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("tix-watch-{}-{}", name, std::process::id()))
+}
+
+#[tokio::test]
+async fn watch_logs_diff_of_changed_file() {
+    let file = temp_path("app.log");
+    let cache_dir = temp_path("cache");
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    // Big enough that the edit leaves most chunks untouched.
+    let mut contents: String = (0..8_000).map(|i| format!("line {i}\n")).collect();
+    std::fs::write(&file, &contents).unwrap();
+
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+    let config = WatchConfig {
+        cache_dir: cache_dir.clone(),
+        keep_versions: 2,
+        ..WatchConfig::default()
+    };
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap()
+        .with_watch_config(&config);
+    let port = master.local_addr().unwrap().port();
+    let slave = tokio::spawn(fake_slave(port));
+    master.accept_one().await.unwrap();
+
+    master
+        .execute_command(format!("watch \"{}\" 100ms", file.display()))
+        .await
+        .unwrap();
+    // The watch owns a task row from the start.
+    let mut id = None;
+    while let Ok(event) = ui_rx.try_recv() {
+        if let MasterEvent::TaskUpdate { id: task, status } = event
+            && status.starts_with("Watching")
+        {
+            id = Some(task);
+        }
+    }
+    let id = id.expect("watch has a task row");
+    let first = logged_from(&mut master, &mut ui_rx, "cached as v1").await;
+    let size = format_bytes(contents.len() as u64);
+    assert!(first[0].contains(&format!("({size})")), "{}", first[0]);
+
+    contents = contents.replace("line 5000\n", "line five thousand\n");
+    contents.push_str("appended\n");
+    std::fs::write(&file, &contents).unwrap();
+
+    let change = logged_from(&mut master, &mut ui_rx, "changed (v2)").await;
+    assert!(change[0].ends_with("+2 -1 lines"), "{:?}", change[0]);
+    let diff = &change[1..];
+    assert!(diff.iter().any(|l| l == "│-line 5000"), "{diff:?}");
+    assert!(diff.iter().any(|l| l == "│+line five thousand"));
+    assert!(diff.iter().any(|l| l == "│+appended"));
+    assert!(diff.iter().filter(|l| l.starts_with("│@@")).count() == 2);
+
+    // A third version evicts the first.
+    std::fs::write(&file, "short\n").unwrap();
+    logged_from(&mut master, &mut ui_rx, "changed (v3)").await;
+    let dir = std::fs::read_dir(&cache_dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let mut kept: Vec<String> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    kept.sort();
+    assert_eq!(kept, ["v000002", "v000003"]);
+
+    master.execute_command("watch".to_string()).await.unwrap();
+    master
+        .execute_command(format!("unwatch {id}"))
+        .await
+        .unwrap();
+    assert_eq!(master.next_watch_due(), None);
+    let err = master.execute_command("unwatch 999".to_string()).await;
+    assert!(matches!(err, Err(TixError::InvalidCommandSyntax(_))));
+
+    let _ = std::fs::remove_file(&file);
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    slave.abort();
+}
+
+/// A master watching `file` every 100 ms through a slave with `fault`.
+async fn watch_through(
+    file: &std::path::Path,
+    cache_dir: &std::path::Path,
+    fault: Fault,
+) -> (Master, mpsc::UnboundedReceiver<MasterEvent>) {
+    let (ui_tx, ui_rx) = mpsc::unbounded_channel();
+    let config = WatchConfig {
+        cache_dir: cache_dir.to_path_buf(),
+        ..WatchConfig::default()
+    };
+    let mut master = Master::listen(ConnectionInfo::new("127.0.0.1".to_string(), 0), ui_tx)
+        .await
+        .unwrap()
+        .with_watch_config(&config);
+    let port = master.local_addr().unwrap().port();
+    tokio::spawn(faulty_slave(port, fault));
+    master.accept_one().await.unwrap();
+    master
+        .execute_command(format!("watch \"{}\" 100ms", file.display()))
+        .await
+        .unwrap();
+    (master, ui_rx)
+}
+
+#[tokio::test]
+async fn corrupt_chunk_is_repaired_in_one_round() {
+    let file = temp_path("repair.bin");
+    let cache_dir = temp_path("repair-cache");
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let contents: Vec<u8> = (0..3 * DEFAULT_CHUNK_SIZE as u32)
+        .map(|i| i as u8)
+        .collect();
+    std::fs::write(&file, &contents).unwrap();
+
+    let (mut master, mut ui_rx) = watch_through(&file, &cache_dir, Fault::CorruptFirstReply).await;
+    let mismatch = logged_from(&mut master, &mut ui_rx, "hash mismatch").await;
+    assert!(mismatch[0].contains("(repair 1 of 2)"), "{:?}", mismatch);
+    let lines = logged_from(&mut master, &mut ui_rx, "repaired").await;
+    assert!(
+        lines[0].ends_with("repaired chunk(s) [1] in 1 round(s)"),
+        "{:?}",
+        lines
+    );
+    if !lines.iter().any(|l| l.contains("cached as v1")) {
+        logged_from(&mut master, &mut ui_rx, "cached as v1").await;
+    }
+    let dir = std::fs::read_dir(&cache_dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(std::fs::read(dir.path().join("v000001")).unwrap(), contents);
+
+    let _ = std::fs::remove_file(&file);
+    let _ = std::fs::remove_dir_all(&cache_dir);
+}
+
+#[tokio::test]
+async fn file_changing_under_the_slave_gives_up_after_the_rounds() {
+    let file = temp_path("moving.bin");
+    let cache_dir = temp_path("moving-cache");
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    std::fs::write(&file, vec![0u8; 2 * DEFAULT_CHUNK_SIZE]).unwrap();
+
+    let (mut master, mut ui_rx) = watch_through(&file, &cache_dir, Fault::ChangesWhileRead).await;
+    let failed = logged_from(&mut master, &mut ui_rx, "still corrupt").await;
+    assert!(
+        failed[0].ends_with("chunk(s) [1] still corrupt after 2 repair round(s)"),
+        "{:?}",
+        failed
+    );
+    assert!(!cache_dir.exists() || std::fs::read_dir(&cache_dir).unwrap().next().is_none());
+
+    let _ = std::fs::remove_file(&file);
+    let _ = std::fs::remove_dir_all(&cache_dir);
+}
@@ -0,0 +1,62 @@
+//! Time of each post-processing stage on a 1080p frame shown in a
+//! 1440p window, against the 60 fps frame budget.
+//!
+//! Run with `cargo bench -p tix-rdp-gui --bench postprocess`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use tix_rdp_gui::postprocess::{
+    BilinearScale, ColorTemperature, FramePostProcessor, Grayscale, NIGHT_LIGHT_KELVIN,
+    NearestScale,
+};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+const OUT_WIDTH: u32 = 2560;
+const OUT_HEIGHT: u32 = 1440;
+const ITERATIONS: u32 = 30;
+/// One frame at 60 fps.
+const BUDGET: Duration = Duration::from_micros(16_667);
+
+fn bench(name: &str, stage: &dyn FramePostProcessor, src: &[u8]) {
+    let (w, h) = stage.output_size(WIDTH, HEIGHT);
+    let mut dst = vec![0u8; w as usize * h as usize * 4];
+    stage.process(src, WIDTH, HEIGHT, &mut dst); // warm up
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        stage.process(black_box(src), WIDTH, HEIGHT, &mut dst);
+    }
+    let per_iter = start.elapsed() / ITERATIONS;
+    let budget = per_iter.as_secs_f64() / BUDGET.as_secs_f64() * 100.0;
+    println!(
+        "{name:<20} {:>8.3} ms/frame  {budget:>5.1}% of the 60 fps budget{}",
+        per_iter.as_secs_f64() * 1000.0,
+        if per_iter > BUDGET { "  OVER" } else { "" }
+    );
+    black_box(&dst);
+}
+
+fn main() {
+    let src: Vec<u8> = (0..WIDTH as usize * HEIGHT as usize * 4)
+        .map(|i| (i * 31 % 251) as u8)
+        .collect();
+    let (width, height) = (OUT_WIDTH, OUT_HEIGHT);
+
+    bench(
+        "nearest 1080p->1440p",
+        &NearestScale { width, height },
+        &src,
+    );
+    bench(
+        "bilinear 1080p->1440p",
+        &BilinearScale { width, height },
+        &src,
+    );
+    bench("grayscale 1080p", &Grayscale, &src);
+    bench(
+        "night-light 1080p",
+        &ColorTemperature::new(NIGHT_LIGHT_KELVIN),
+        &src,
+    );
+}
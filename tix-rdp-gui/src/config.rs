@@ -1,12 +1,22 @@
 //! GUI client configuration.
 
 use std::path::Path;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
+use tix_core::config::{self, Validate};
+use tix_core::paste_guard::PasteGuardConfig;
+use tix_core::protocol::screen::ImageFormat;
+use tix_core::rdp::client::DEFAULT_MAX_STREAM_ERRORS;
+use tix_core::rdp::transport::{DEFAULT_MTU, MIN_MTU, TransportConfig};
+use tix_core::rdp::types::PixelFormat;
+
+use crate::postprocess::{ColorFilter, FilterSet, PostProcessing, ScaleFilter};
+use crate::stall::DEFAULT_STALL_INTERVALS;
 
 /// Top-level configuration for the GUI client.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct GuiConfig {
     /// Network settings.
     pub network: NetworkConfig,
@@ -16,23 +26,56 @@ pub struct GuiConfig {
     pub performance: PerformanceConfig,
     /// Input forwarding settings.
     pub input: InputConfig,
+    /// Drag-and-drop uploads.
+    pub upload: UploadConfig,
+    /// How large a dropped file may be before it is confirmed or
+    /// refused.
+    pub paste_guard: PasteGuardConfig,
+    /// Local screenshots (Ctrl+S).
+    pub snapshot: SnapshotConfig,
+    /// Locking an idle session.
+    pub lock: LockConfig,
     /// Logging.
     pub logging: LoggingConfig,
+    /// Metrics export.
+    pub metrics: MetricsConfig,
 }
 
 /// Network settings.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct NetworkConfig {
     /// Slave control address (IP:port for TCP handshake).
     pub slave_address: String,
+    /// Wait on this TCP port for a slave that dials in (its
+    /// `connect_to`) instead of connecting to `slave_address`; 0
+    /// connects out. Not used via the master.
+    pub listen_port: u16,
     /// Connection timeout in milliseconds.
     pub timeout_ms: u64,
+    /// Reach a classic tix-slave through the master's RDP bridge
+    /// instead of connecting to a tix-rdp-slave directly.
+    pub via_master: bool,
+    /// Master RDP bridge address (used when `via_master` is set).
+    pub master_address: String,
+    /// Screen datagram size to ask the slave for (via master; a direct
+    /// tix-rdp-slave uses its own setting and reports it back).
+    pub mtu: usize,
+    /// Ask the slave to measure the path MTU (via master).
+    pub probe_mtu: bool,
+    /// Have the slave check that its UDP reaches us before streaming
+    /// (via master); a blocked path fails the connect with a hint
+    /// instead of leaving the window black.
+    pub preflight: bool,
+    /// UDP receive buffer (`SO_RCVBUF`) in bytes; 0 keeps the OS default.
+    pub recv_buffer: usize,
+    /// UDP send buffer (`SO_SNDBUF`) in bytes; 0 keeps the OS default.
+    pub send_buffer: usize,
 }
 
 /// Display settings.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct DisplayConfig {
     /// Initial window width.
     pub width: u32,
@@ -42,31 +85,115 @@ pub struct DisplayConfig {
     pub fullscreen: bool,
     /// Enable vsync (cap rendering to monitor refresh rate).
     pub vsync: bool,
+    /// Slave monitors to show, one window each (0 = primary). Empty
+    /// shows whichever monitor the slave captures by default.
+    pub monitors: Vec<u8>,
+    /// How frames are scaled to the window: "nearest" or "bilinear".
+    pub scale_filter: String,
+    /// Colour filters on at startup: "grayscale", "night-light".
+    /// Ctrl+Alt+G and Ctrl+Alt+N switch them during the session.
+    pub filters: Vec<String>,
 }
 
 /// Performance settings.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct PerformanceConfig {
     /// Max buffered frames before dropping.
     pub buffer_size: u32,
     /// Quality hint: "low", "medium", "high".
     pub quality: String,
+    /// Pixel format to request when connecting through the master:
+    /// "bgra" (full colour), "rgb565" or "gray". A standalone
+    /// tix-rdp-slave uses its own `screen.pixel_format` instead.
+    pub pixel_format: String,
+    /// Show frames evenly spaced by capture time rather than as they
+    /// arrive, at the cost of some latency (see `tix_rdp_gui::pacing`).
+    pub smooth_pacing: bool,
+    /// Most latency the pacing may add, in milliseconds.
+    pub max_pacing_ms: u64,
+    /// Let the slave compress frames against a shared zstd dictionary
+    /// (via master).
+    pub zstd_dictionary: bool,
+    /// Keep dictionaries the slave sent here, so the next session only
+    /// names them. Empty keeps none; the built-in one is always known.
+    pub dictionary_dir: String,
+    /// Frames in a row that may fail to decode before the session is
+    /// given up on; the ones in between are skipped. 0 never gives up.
+    pub max_stream_errors: u32,
+    /// Start with a thumbnail every few seconds instead of frames, for
+    /// links too slow for anything else (see `tix_core::rdp::thumbnail`).
+    pub low_bandwidth: bool,
+    /// Let the slave switch to thumbnails by itself when frames no
+    /// longer fit the link, and back when they do.
+    pub thumbnails: bool,
+    /// Frame intervals screen data may arrive for without a frame before
+    /// the window says the stream has stalled (see
+    /// `tix_rdp_gui::stall`). 0 never says so.
+    pub stall_intervals: u32,
+    /// Dim the frame slightly while the stream is stalled.
+    pub dim_on_stall: bool,
 }
 
 /// Input forwarding.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct InputConfig {
     /// Forward mouse events.
     pub capture_mouse: bool,
     /// Forward keyboard events.
     pub capture_keyboard: bool,
+    /// Start the session view-only: the slave refuses all input until
+    /// control is toggled on.
+    pub view_only: bool,
+    /// Append every input event sent to this JSONL file (see
+    /// `tix_rdp_gui::journal`). Empty keeps no journal.
+    pub journal: String,
+    /// Send pointer moves over the screen socket when the slave takes
+    /// them; `false` keeps all input on the control connection.
+    pub udp_pointer: bool,
+    /// Stamp input events with when they were sent, when the slave takes
+    /// them, so it can drop pointer moves a backlog has overtaken.
+    pub timed_input: bool,
+    /// Send nothing but key and button releases while the stream is
+    /// stalled, so clicks at a frozen picture do not all land once it
+    /// recovers.
+    pub pause_on_stall: bool,
+}
+
+/// Drag-and-drop uploads.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UploadConfig {
+    /// Directory on the slave that dropped files are written to. Empty
+    /// means the slave's Downloads folder.
+    pub remote_dir: String,
+}
+
+/// Local screenshots.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotConfig {
+    /// Directory screenshots are written to, created on first use.
+    /// Empty means the working directory.
+    pub dir: String,
+}
+
+/// Locking an idle session (see `tix_rdp_gui::lock`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockConfig {
+    /// Minutes without local input before the session locks; 0 never
+    /// locks.
+    pub idle_minutes: u64,
+    /// Token to type before control resumes. Empty unlocks on
+    /// Ctrl+Alt+U alone.
+    pub require_token: String,
 }
 
 /// Logging.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct LoggingConfig {
     /// Log level.
     pub level: String,
@@ -74,25 +201,29 @@ pub struct LoggingConfig {
     pub file: String,
 }
 
-// ── Defaults ─────────────────────────────────────────────────────
-
-impl Default for GuiConfig {
-    fn default() -> Self {
-        Self {
-            network: NetworkConfig::default(),
-            display: DisplayConfig::default(),
-            performance: PerformanceConfig::default(),
-            input: InputConfig::default(),
-            logging: LoggingConfig::default(),
-        }
-    }
+/// Prometheus metrics endpoint (needs the `metrics` build feature).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Serve `/metrics` on `127.0.0.1:<port>`; 0 turns it off.
+    pub port: u16,
 }
 
+// ── Defaults ─────────────────────────────────────────────────────
+
 impl Default for NetworkConfig {
     fn default() -> Self {
         Self {
             slave_address: "127.0.0.1:7332".into(),
+            listen_port: 0,
             timeout_ms: 5000,
+            via_master: false,
+            master_address: "127.0.0.1:4322".into(),
+            mtu: DEFAULT_MTU,
+            probe_mtu: false,
+            preflight: true,
+            recv_buffer: 0,
+            send_buffer: 0,
         }
     }
 }
@@ -104,6 +235,9 @@ impl Default for DisplayConfig {
             height: 1080,
             fullscreen: false,
             vsync: true,
+            monitors: Vec::new(),
+            scale_filter: "bilinear".into(),
+            filters: Vec::new(),
         }
     }
 }
@@ -113,6 +247,16 @@ impl Default for PerformanceConfig {
         Self {
             buffer_size: 3,
             quality: "high".into(),
+            pixel_format: "bgra".into(),
+            smooth_pacing: true,
+            max_pacing_ms: 100,
+            zstd_dictionary: true,
+            dictionary_dir: String::new(),
+            max_stream_errors: DEFAULT_MAX_STREAM_ERRORS,
+            low_bandwidth: false,
+            thumbnails: true,
+            stall_intervals: DEFAULT_STALL_INTERVALS,
+            dim_on_stall: true,
         }
     }
 }
@@ -122,6 +266,20 @@ impl Default for InputConfig {
         Self {
             capture_mouse: true,
             capture_keyboard: true,
+            view_only: false,
+            journal: String::new(),
+            udp_pointer: true,
+            timed_input: true,
+            pause_on_stall: true,
+        }
+    }
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            idle_minutes: 10,
+            require_token: String::new(),
         }
     }
 }
@@ -137,27 +295,185 @@ impl Default for LoggingConfig {
 
 // ── Loading ──────────────────────────────────────────────────────
 
-impl GuiConfig {
-    /// Load from a TOML file, falling back to defaults.
-    pub fn load(path: &Path) -> Self {
-        match std::fs::read_to_string(path) {
-            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
-                tracing::warn!("invalid config {}: {e}; using defaults", path.display());
-                Self::default()
-            }),
-            Err(_) => {
-                tracing::info!("no config at {}; using defaults", path.display());
-                Self::default()
+impl DisplayConfig {
+    /// `scale_filter`; unknown names mean bilinear.
+    pub fn scale_filter(&self) -> ScaleFilter {
+        self.scale_filter.parse().unwrap_or_default()
+    }
+
+    /// The known names in `filters`.
+    pub fn filter_set(&self) -> FilterSet {
+        FilterSet::new(self.filters.iter().filter_map(|name| name.parse().ok()))
+    }
+
+    /// Whether `filter` is among the known names in `filters`.
+    pub fn has_filter(&self, filter: ColorFilter) -> bool {
+        self.filter_set().contains(filter)
+    }
+
+    /// Switch `filter` on or off in `filters`, keeping the names in
+    /// pipeline order.
+    pub fn set_filter(&mut self, filter: ColorFilter, on: bool) {
+        let mut set = self.filter_set();
+        if set.contains(filter) != on {
+            set.toggle(filter);
+        }
+        self.filters = ColorFilter::NAMES
+            .iter()
+            .filter(|name| name.parse().is_ok_and(|f| set.contains(f)))
+            .map(|name| name.to_string())
+            .collect();
+    }
+
+    /// The frame post-processing these settings ask for.
+    pub fn post_processing(&self) -> PostProcessing {
+        PostProcessing::new(self.scale_filter(), &self.filter_set())
+    }
+}
+
+impl PerformanceConfig {
+    /// `pixel_format` as a `ScreenStart` format; unknown names mean full
+    /// colour.
+    pub fn image_format(&self) -> ImageFormat {
+        self.pixel_format
+            .parse::<PixelFormat>()
+            .map(ImageFormat::from)
+            .unwrap_or(ImageFormat::RawBgra)
+    }
+}
+
+impl Validate for GuiConfig {
+    fn problems(&self) -> Vec<String> {
+        let network = &self.network;
+        let mut problems = Vec::new();
+        if network.via_master {
+            problems.extend(config::address_problem(
+                "network.master_address",
+                &network.master_address,
+            ));
+        } else if network.listen_port == 0 {
+            problems.extend(config::address_problem(
+                "network.slave_address",
+                &network.slave_address,
+            ));
+        }
+        if network.mtu < MIN_MTU {
+            problems.push(format!(
+                "network.mtu = {} is too small; it must be at least {MIN_MTU}",
+                network.mtu
+            ));
+        }
+        if self.display.width == 0 || self.display.height == 0 {
+            problems.push(format!(
+                "display.width and display.height must not be 0 (got {}x{})",
+                self.display.width, self.display.height
+            ));
+        }
+        if self.display.scale_filter.parse::<ScaleFilter>().is_err() {
+            problems.push(unknown_name(
+                "display.scale_filter",
+                &self.display.scale_filter,
+                &ScaleFilter::NAMES,
+            ));
+        }
+        for name in &self.display.filters {
+            if name.parse::<ColorFilter>().is_err() {
+                problems.push(unknown_name("display.filters", name, &ColorFilter::NAMES));
             }
         }
+        problems.extend(self.paste_guard.problems());
+        problems
     }
+}
 
+/// "`key`: unknown name `name`", with the closest of `known` if there
+/// is one close enough.
+fn unknown_name(key: &str, name: &str, known: &[&str]) -> String {
+    match config::did_you_mean(name, known) {
+        Some(close) => format!("{key}: unknown name `{name}`; did you mean `{close}`?"),
+        None => format!("{key}: unknown name `{name}` (expected one of {})", known.join(", ")),
+    }
+}
+
+impl GuiConfig {
     /// Write default config to a file.
     pub fn write_default(path: &Path) -> std::io::Result<()> {
         let cfg = Self::default();
         let text = toml::to_string_pretty(&cfg)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        std::fs::write(path, text)
+            .map_err(std::io::Error::other)?;
+        config::write_atomic(path, text)
+    }
+
+    /// Store the address `network` connected to in the file at `path`,
+    /// leaving every other setting as the file has it. A missing file is
+    /// created from the defaults; an unreadable one is left alone.
+    pub fn persist_address(path: &Path, network: &NetworkConfig) -> std::io::Result<()> {
+        Self::update_file(path, |cfg| {
+            if network.via_master {
+                cfg.network.master_address = network.master_address.clone();
+            } else {
+                cfg.network.slave_address = network.slave_address.clone();
+            }
+        })
+    }
+
+    /// Read the file at `path`, let `update` change it and write it back
+    /// atomically. A missing file starts from the defaults; an
+    /// unreadable one is left alone.
+    pub fn update_file(path: &Path, update: impl FnOnce(&mut Self)) -> std::io::Result<()> {
+        let mut cfg: Self = match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e),
+        };
+        update(&mut cfg);
+        let text = toml::to_string_pretty(&cfg).map_err(std::io::Error::other)?;
+        config::write_atomic(path, text)
+    }
+}
+
+impl LockConfig {
+    /// How long the session may go without input, or `None` if it
+    /// never locks by itself.
+    pub fn idle(&self) -> Option<Duration> {
+        (self.idle_minutes > 0).then(|| Duration::from_secs(self.idle_minutes * 60))
+    }
+
+    /// The token asked for on unlock, if any.
+    pub fn token(&self) -> Option<String> {
+        (!self.require_token.is_empty()).then(|| self.require_token.clone())
+    }
+}
+
+impl NetworkConfig {
+    /// Where the viewer connects: the master's bridge or the slave.
+    pub fn target_address(&self) -> &str {
+        if self.via_master {
+            &self.master_address
+        } else {
+            &self.slave_address
+        }
+    }
+
+    /// Replace the address [`target_address`](Self::target_address)
+    /// returns.
+    pub fn set_target_address(&mut self, address: String) {
+        if self.via_master {
+            self.master_address = address;
+        } else {
+            self.slave_address = address;
+        }
+    }
+
+    /// Socket tuning for the screen-receive socket.
+    pub fn transport_config(&self) -> TransportConfig {
+        TransportConfig {
+            mtu: self.mtu,
+            probe_mtu: self.probe_mtu,
+            recv_buffer: (self.recv_buffer > 0).then_some(self.recv_buffer),
+            send_buffer: (self.send_buffer > 0).then_some(self.send_buffer),
+        }
     }
 }
 
@@ -181,6 +497,110 @@ mod tests {
         let text = toml::to_string_pretty(&cfg).unwrap();
         let parsed: GuiConfig = toml::from_str(&text).unwrap();
         assert_eq!(parsed.display.width, 1920);
-        assert_eq!(parsed.network.slave_address, "192.168.1.100:7332");
+        assert_eq!(parsed.network.slave_address, "127.0.0.1:7332");
+        assert!(!parsed.network.via_master);
+        assert_eq!(parsed.network.listen_port, 0);
+        assert!(parsed.network.preflight);
+        assert!(parsed.upload.remote_dir.is_empty());
+        assert!(parsed.snapshot.dir.is_empty());
+        assert!(parsed.display.monitors.is_empty());
+        assert!(!parsed.input.view_only);
+        assert_eq!(parsed.metrics.port, 0);
+        assert_eq!(parsed.performance.image_format(), ImageFormat::RawBgra);
+        assert!(parsed.performance.smooth_pacing);
+        assert_eq!(parsed.performance.max_pacing_ms, 100);
+        assert!(parsed.performance.zstd_dictionary);
+        assert!(parsed.performance.dictionary_dir.is_empty());
+        assert_eq!(parsed.performance.max_stream_errors, 30);
+        assert_eq!(parsed.performance.stall_intervals, 3);
+        assert!(parsed.performance.dim_on_stall);
+        assert!(parsed.input.pause_on_stall);
+        assert_eq!(parsed.lock.idle(), Some(Duration::from_secs(600)));
+        assert_eq!(parsed.lock.token(), None);
+    }
+
+    #[test]
+    fn lock_settings() {
+        let cfg: GuiConfig =
+            toml::from_str("[lock]\nidle_minutes = 0\nrequire_token = \"hunter2\"\n").unwrap();
+        assert_eq!(cfg.lock.idle(), None);
+        assert_eq!(cfg.lock.token().as_deref(), Some("hunter2"));
+    }
+
+    #[test]
+    fn reduced_pixel_format() {
+        let cfg: GuiConfig =
+            toml::from_str("[performance]\npixel_format = \"rgb565\"\n").unwrap();
+        assert_eq!(cfg.performance.image_format(), ImageFormat::Rgb565);
+    }
+
+    #[test]
+    fn via_master_from_partial_toml() {
+        let cfg: GuiConfig = toml::from_str(
+            "[network]\nvia_master = true\nmaster_address = \"10.0.0.1:4322\"\n",
+        )
+        .unwrap();
+        assert!(cfg.network.via_master);
+        assert_eq!(cfg.network.master_address, "10.0.0.1:4322");
+        assert_eq!(cfg.network.timeout_ms, 5000);
+    }
+
+    #[test]
+    fn bad_settings_are_refused() {
+        let parse = |text| config::parse::<GuiConfig>(Path::new("tix-rdp-gui.toml"), text);
+        let err = parse("[display]\nwidht = 800\n").unwrap_err().to_string();
+        assert!(err.contains("line 2, column 1"), "{err}");
+        assert!(err.ends_with("did you mean `width`?"), "{err}");
+
+        let err = parse("[network]\nslave_address = \"10.0.0.5:0\"\nmtu = 99\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("network.slave_address = \"10.0.0.5:0\": port must not be 0"));
+        assert!(err.contains("network.mtu = 99 is too small"), "{err}");
+
+        // Only the address in use has to be right.
+        assert!(parse("[network]\nslave_address = \"x\"\nlisten_port = 7332\n").is_ok());
+        let err = parse("[network]\nvia_master = true\nmaster_address = \"m\"\n").unwrap_err();
+        assert!(err.to_string().contains("network.master_address"), "{err}");
+        assert!(parse("[display]\nheight = 0\n").is_err());
+        let err = parse("[paste_guard]\nrefuse_bytes = 1\n").unwrap_err().to_string();
+        assert!(err.contains("paste_guard.refuse_bytes (1) must not be below"), "{err}");
+        assert!(GuiConfig::default().problems().is_empty());
+    }
+
+    #[test]
+    fn display_filters() {
+        let cfg: GuiConfig = toml::from_str(
+            "[display]\nscale_filter = \"nearest\"\nfilters = [\"night-light\", \"grayscale\"]\n",
+        )
+        .unwrap();
+        assert_eq!(cfg.display.scale_filter(), ScaleFilter::Nearest);
+        let filters = cfg.display.filter_set();
+        assert!(filters.contains(ColorFilter::Grayscale));
+        assert!(filters.contains(ColorFilter::NightLight));
+        assert_eq!(GuiConfig::default().display.scale_filter(), ScaleFilter::Bilinear);
+
+        let parse = |text| config::parse::<GuiConfig>(Path::new("tix-rdp-gui.toml"), text);
+        let err = parse("[display]\nscale_filter = \"bilnear\"\n").unwrap_err().to_string();
+        assert!(err.contains("unknown name `bilnear`; did you mean `bilinear`?"), "{err}");
+        let err = parse("[display]\nfilters = [\"sepia\"]\n").unwrap_err().to_string();
+        assert!(err.contains("expected one of grayscale, night-light"), "{err}");
+    }
+
+    #[test]
+    fn persisted_address_keeps_other_settings() {
+        let path = std::env::temp_dir()
+            .join(format!("tix-gui-persist-{}.toml", std::process::id()));
+        std::fs::write(&path, "[display]\nwidth = 800\n").unwrap();
+
+        let mut network = NetworkConfig::default();
+        network.set_target_address("10.1.2.3:7332".into());
+        assert_eq!(network.target_address(), "10.1.2.3:7332");
+        GuiConfig::persist_address(&path, &network).unwrap();
+
+        let saved: GuiConfig = config::load(&path).unwrap().unwrap();
+        assert_eq!(saved.network.slave_address, "10.1.2.3:7332");
+        assert_eq!(saved.display.width, 800);
+        let _ = std::fs::remove_file(&path);
     }
 }
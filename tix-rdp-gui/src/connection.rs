@@ -2,93 +2,664 @@
 //!
 //! Handles the initial handshake (UDP port exchange), and provides
 //! a method to send serialised input events over the control stream.
+//! While it waits for the slave's answer the screen socket acks MTU
+//! probes, so a slave that measures the path gets a result.
+//!
+//! Two transports are supported:
+//!
+//! - **Direct** — the bespoke tix-rdp-slave protocol (raw port exchange,
+//!   tagged input frames). We normally dial the slave; with
+//!   `network.listen_port` set we wait for a slave behind NAT to dial
+//!   us, and the exchange that follows is the same.
+//! - **Via master** — tix-core [`Packet`]s sent to the master's RDP
+//!   bridge, which relays them to a classic `tix-slave` over the
+//!   existing control connection. The UDP endpoint is negotiated through
+//!   the `ScreenStart` ack. Files dropped onto the window are uploaded
+//!   over this connection too, so drag-and-drop needs this mode.
+//!
+//! The handshake starts screen session 0. Each further monitor gets a
+//! session of its own from [`open_session`](SlaveConnection::open_session)
+//! — another `ScreenStart` via the master, a tag-3 frame answered on the
+//! stream when direct — with its own UDP socket on our side.
+//!
+//! Via the master, every `ScreenStart` can ask the slave to check its
+//! UDP reaches us first (see [`tix_core::rdp::preflight`]). We echo its
+//! probes while waiting for the ack, tell the slave how many arrived,
+//! and fail the session with a firewall hint when none did.
+//!
+//! A direct slave that stops sends a tag-5 frame with the reason before
+//! closing the stream; [`session_ended`](SlaveConnection::session_ended)
+//! picks it up.
+//!
+//! Via the master, a session whose ack offers a frame dictionary only
+//! uses it once [`confirm_dictionary`](SlaveConnection::confirm_dictionary)
+//! says the decoder holds it.
+//!
+//! [`set_low_bandwidth`](SlaveConnection::set_low_bandwidth) switches a
+//! session between frames and thumbnails: a `ScreenSharing` request via
+//! the master, a tag-8 frame when direct. Direct session 0 has no
+//! `ScreenStart`, so the handshake sends one straight away to say we
+//! show thumbnails.
+//!
+//! Via the master we also send a `Hello` once the first session is up;
+//! the bridge answers with the slave's, whose feature list says which
+//! of our hotkeys the slave can carry out (see
+//! [`unsupported`](SlaveConnection::unsupported)). The direct protocol
+//! has no such exchange.
+
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tracing::{info, warn};
 
-use tokio::io::AsyncWriteExt;
-use tokio::net::TcpStream;
-use tracing::info;
+use tix_core::protocol::HelloInfo;
+use tix_core::protocol::screen::{
+    DictionaryOffer, KeyEvent, MouseEvent, ScreenConfig, ScreenDictionaryRequest,
+    ScreenModeRequest, ScreenPreflightRequest, ScreenSharingRequest, ScreenStartRequest,
+    ScreenStartResponse, ScreenStopRequest, TimedKeyEvent, TimedMouseEvent,
+};
+use tix_core::rdp::preflight::{self, PreflightVerdict, ProbeTally};
+use tix_core::rdp::transport::DEFAULT_MTU;
+use tix_core::{Command, Connection, ConnectionInfo, ConnectionSender, Feature, Liveness, Packet};
 
 use crate::config::GuiConfig;
+use crate::dictionary;
+
+/// Request ID used for the `ScreenStart` handshake in via-master mode.
+const SCREEN_START_REQ_ID: u64 = 1;
+
+/// The underlying control channel.
+enum Control {
+    Direct(TcpStream),
+    ViaMaster { conn: Connection, next_req_id: u64 },
+}
 
 /// Manages the TCP control connection to the slave.
 pub struct SlaveConnection {
-    stream: TcpStream,
-    /// The slave's UDP port for screen data.
-    slave_screen_port: u16,
-    /// The local UDP port we will listen on.
-    local_udp_port: u16,
+    control: Control,
+    /// Bytes written/read on a direct control stream. Via the master,
+    /// the tix-core [`Connection`] keeps its own counters.
+    direct_traffic: (u64, u64),
+    /// The slave's UDP endpoint for screen data.
+    slave_screen_addr: SocketAddr,
+    /// MTU the slave streams with.
+    mtu: usize,
+    /// Where session 0's monitor sits on the slave's virtual desktop.
+    origin: (i32, i32),
+    /// Session 0 takes pointer moves over UDP.
+    udp_input: bool,
+    /// The frame dictionary session 0's ack offered.
+    dictionary: Option<DictionaryOffer>,
+    /// Frames a second session 0 streams at.
+    fps: u8,
+    /// The slave takes input stamped, in milliseconds since `clock`.
+    timed_input: bool,
+    clock: Instant,
+    /// What every `ScreenStart` asks for; sessions differ in port,
+    /// monitor and ID.
+    request: ScreenStartRequest,
+    /// How long to wait for the slave to answer.
+    timeout: Duration,
+    /// The slave's `Hello`, as the master's bridge relayed it.
+    slave: Option<HelloInfo>,
 }
 
 impl SlaveConnection {
     /// Connect to the slave, exchange UDP ports.
     ///
-    /// `local_udp_port` is the port the GUI client will bind for
-    /// receiving screen frames.
+    /// `udp` is the socket the GUI client receives screen frames on.
+    /// Uses the master bridge when `network.via_master` is set.
     pub async fn connect(
         config: &GuiConfig,
-        local_udp_port: u16,
+        udp: &UdpSocket,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if config.network.via_master {
+            Self::connect_via_master(config, udp).await
+        } else {
+            Self::connect_direct(config, udp).await
+        }
+    }
+
+    /// Bespoke handshake with a standalone tix-rdp-slave, over a
+    /// stream we dialled or, with `listen_port`, one the slave dialled.
+    async fn connect_direct(
+        config: &GuiConfig,
+        udp: &UdpSocket,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let addr: SocketAddr = config.network.slave_address.parse()?;
-        let timeout = std::time::Duration::from_millis(config.network.timeout_ms);
+        let local_udp_port = udp.local_addr()?.port();
+        let timeout = Duration::from_millis(config.network.timeout_ms);
 
-        info!("connecting to slave at {addr}");
-        let stream = tokio::time::timeout(timeout, TcpStream::connect(addr)).await??;
+        let stream = match config.network.listen_port {
+            0 => {
+                let addr: SocketAddr = config.network.slave_address.parse()?;
+                info!("connecting to slave at {addr}");
+                tokio::time::timeout(timeout, TcpStream::connect(addr)).await??
+            }
+            port => {
+                let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port))).await?;
+                info!("waiting for the slave to dial in on port {port}");
+                let accepted = tokio::time::timeout(timeout, listener.accept()).await;
+                let (stream, peer) = accepted.map_err(|_| {
+                    format!("no slave dialled in on port {port} within {timeout:?}")
+                })??;
+                info!("slave dialled in from {peer}");
+                stream
+            }
+        };
         stream.set_nodelay(true)?;
 
-        // Send our UDP port.
+        // Send our UDP port, and the monitor when the user picked one.
+        let mut hello = local_udp_port.to_le_bytes().to_vec();
+        hello.extend(config.display.monitors.first());
         stream.writable().await?;
-        stream.try_write(&local_udp_port.to_le_bytes())?;
+        stream.try_write(&hello)?;
 
-        // Read slave's UDP port.
-        let mut buf = [0u8; 2];
-        stream.readable().await?;
+        // Read slave's UDP port and stream MTU, plus the monitor's origin
+        // if we named one. Older slaves send only the port.
+        let mut buf = [0u8; 12];
+        answering_probes(udp, &mut ProbeTally::default(), stream.readable()).await??;
         let n = stream.try_read(&mut buf)?;
         if n < 2 {
             return Err("slave did not respond with UDP port".into());
         }
-        let slave_screen_port = u16::from_le_bytes(buf);
+        let slave_screen_port = u16::from_le_bytes([buf[0], buf[1]]);
+        let mtu = match n {
+            4.. => u16::from_le_bytes([buf[2], buf[3]]) as usize,
+            _ => DEFAULT_MTU,
+        };
+        let origin = match n {
+            12 => (
+                i32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]),
+                i32::from_le_bytes([buf[8], buf[9], buf[10], buf[11]]),
+            ),
+            _ => (0, 0),
+        };
 
         info!(
-            "negotiated UDP ports: local={local_udp_port}, slave={slave_screen_port}"
+            "negotiated UDP ports: local={local_udp_port}, slave={slave_screen_port}, MTU {mtu}"
         );
 
-        Ok(Self {
-            stream,
-            slave_screen_port,
+        let slave_screen_addr = SocketAddr::new(stream.peer_addr()?.ip(), slave_screen_port);
+        let request = screen_request(config);
+        let mut conn = Self {
+            control: Control::Direct(stream),
+            direct_traffic: (hello.len() as u64, n as u64),
+            slave_screen_addr,
+            mtu,
+            origin,
+            udp_input: false,
+            dictionary: None,
+            fps: request.fps,
+            timed_input: false,
+            clock: Instant::now(),
+            request,
+            timeout,
+            slave: None,
+        };
+        // The direct protocol has no ScreenStart; a view-only session
+        // is requested straight after the port exchange.
+        if config.input.view_only {
+            conn.set_control(false).await?;
+        }
+        let performance = &config.performance;
+        if performance.low_bandwidth || performance.thumbnails {
+            conn.set_low_bandwidth(0, performance.low_bandwidth, performance.thumbnails)
+                .await?;
+        }
+        Ok(conn)
+    }
+
+    /// `ScreenStart` handshake through the master's RDP bridge.
+    async fn connect_via_master(
+        config: &GuiConfig,
+        udp: &UdpSocket,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let local_udp_port = udp.local_addr()?.port();
+        let addr: SocketAddr = config.network.master_address.parse()?;
+        let timeout = Duration::from_millis(config.network.timeout_ms);
+
+        info!("connecting to master bridge at {addr}");
+        let info = ConnectionInfo::new(addr.ip().to_string(), addr.port());
+        let mut conn = tokio::time::timeout(timeout, Connection::connect(&info)).await??;
+
+        let request = screen_request(config);
+        let first = request.clone().with_udp_port(local_udp_port);
+        conn.send(first.into_packet(SCREEN_START_REQ_ID)?).await?;
+        let mut tally = ProbeTally::default();
+        let mut slave = None;
+        let screen = await_ack(
+            &mut conn,
+            SCREEN_START_REQ_ID,
+            udp,
+            timeout,
+            &mut tally,
+            &mut slave,
+        )
+        .await?;
+        let (slave_screen_addr, mtu) = stream_endpoint(&screen, addr.ip())?;
+        let mut next_req_id = SCREEN_START_REQ_ID + 1;
+        let verdict = report_preflight(
+            &mut conn,
+            Self::next_id(&mut next_req_id),
+            &screen,
+            &tally,
+            slave_screen_addr,
             local_udp_port,
+        )
+        .await?;
+
+        info!(
+            "slave streaming {}x{} {} from {slave_screen_addr} (local UDP port {local_udp_port}, \
+             MTU {mtu}, {}px blocks)",
+            screen.width, screen.height, screen.format, screen.block_size
+        );
+        // A master that predates the answer drops it.
+        let hello = HelloInfo::local("tix-rdp-gui", env!("CARGO_PKG_VERSION"));
+        conn.send(hello.into_command_packet(Self::next_id(&mut next_req_id))?)
+            .await?;
+
+        Ok(Self {
+            control: Control::ViaMaster { conn, next_req_id },
+            direct_traffic: (0, 0),
+            slave_screen_addr,
+            mtu,
+            origin: screen.origin,
+            udp_input: screen.udp_input && verdict != Some(PreflightVerdict::OneWay),
+            dictionary: screen.dictionary,
+            fps: screen.fps,
+            timed_input: screen.timed_input,
+            clock: Instant::now(),
+            request,
+            timeout,
+            slave,
         })
     }
 
+    /// Start screen session `session` on `monitor`, streaming to `udp`.
+    /// Returns the session's settings with its endpoint resolved and a
+    /// usable MTU filled in.
+    pub async fn open_session(
+        &mut self,
+        session: u8,
+        monitor: u8,
+        udp: &UdpSocket,
+    ) -> Result<ScreenConfig, Box<dyn std::error::Error>> {
+        let local_port = udp.local_addr()?.port();
+        let request = self
+            .request
+            .clone()
+            .with_udp_port(local_port)
+            .with_monitor(monitor)
+            .with_session(session);
+        let (mut screen, host) = match &mut self.control {
+            Control::ViaMaster { conn, next_req_id } => {
+                let id = Self::next_id(next_req_id);
+                conn.send(request.into_packet(id)?).await?;
+                let mut tally = ProbeTally::default();
+                let mut screen =
+                    await_ack(conn, id, udp, self.timeout, &mut tally, &mut self.slave).await?;
+                let host = conn
+                    .peer_addr()
+                    .map(|a| a.ip())
+                    .unwrap_or(self.slave_screen_addr.ip());
+                let (addr, _) = stream_endpoint(&screen, host)?;
+                let id = Self::next_id(next_req_id);
+                let verdict = report_preflight(conn, id, &screen, &tally, addr, local_port).await?;
+                if verdict == Some(PreflightVerdict::OneWay) {
+                    screen.udp_input = false;
+                }
+                (screen, host)
+            }
+            Control::Direct(_) => {
+                self.send_tagged(3, &request.to_bytes()?).await?;
+                let (tag, data) = self.read_tagged().await?;
+                if tag == 5 {
+                    let reason = String::from_utf8_lossy(&data);
+                    return Err(format!("remote ended the session: {reason}").into());
+                }
+                if tag != 3 {
+                    return Err(format!("unexpected reply tag {tag} to a session request").into());
+                }
+                let screen = match ScreenStartResponse::from_bytes(&data)? {
+                    ScreenStartResponse::Started(screen) => screen,
+                    ScreenStartResponse::Failed(reason) => {
+                        return Err(format!("slave refused session {session}: {reason}").into());
+                    }
+                };
+                (screen, self.slave_screen_addr.ip())
+            }
+        };
+        let (addr, mtu) = stream_endpoint(&screen, host)?;
+        screen.udp_endpoint = Some(addr);
+        // Input is not per session: once any ack accepts stamps, the
+        // slave reads them on every one.
+        self.timed_input |= screen.timed_input;
+        screen.mtu = mtu.min(u16::MAX as usize) as u16;
+        info!(
+            "session {session}: monitor {monitor} {}x{} at {:?} from {addr}, {}px blocks",
+            screen.width, screen.height, screen.origin, screen.block_size
+        );
+        Ok(screen)
+    }
+
+    /// Stop screen session `session`, leaving the others streaming.
+    pub async fn close_session(&mut self, session: u8) -> Result<(), Box<dyn std::error::Error>> {
+        match &mut self.control {
+            Control::Direct(_) => self.send_tagged(4, &[session]).await,
+            Control::ViaMaster { conn, next_req_id } => {
+                let stop = ScreenStopRequest::session(session);
+                Ok(conn.send(stop.into_packet(Self::next_id(next_req_id))?).await?)
+            }
+        }
+    }
+
+    /// Where session 0's monitor sits on the slave's virtual desktop;
+    /// pointer positions are offset by it.
+    pub fn origin(&self) -> (i32, i32) {
+        self.origin
+    }
+
+    /// Whether session 0 takes pointer moves on its UDP socket. Never
+    /// for a direct connection, whose port exchange cannot offer it.
+    pub fn udp_input(&self) -> bool {
+        self.udp_input
+    }
+
+    /// Whether input events go out stamped with the time they were
+    /// sent. A direct connection only stamps once a session opened
+    /// after the port exchange has accepted it.
+    pub fn timed_input(&self) -> bool {
+        self.timed_input
+    }
+
+    /// The frame dictionary the slave offered for session 0; other
+    /// sessions find theirs in the [`ScreenConfig`] from
+    /// [`open_session`](Self::open_session).
+    pub fn dictionary_offer(&self) -> Option<&DictionaryOffer> {
+        self.dictionary.as_ref()
+    }
+
+    /// Frames a second session 0 streams at: what the ack says, or for
+    /// a direct connection, whose port exchange does not say, what a
+    /// `ScreenStart` asks for. Other sessions find theirs in the
+    /// [`ScreenConfig`] from [`open_session`](Self::open_session).
+    pub fn fps(&self) -> u8 {
+        self.fps
+    }
+
+    /// MTU the slave streams screen data with.
+    pub fn mtu(&self) -> usize {
+        self.mtu
+    }
+
     /// The slave's UDP screen-data port.
     pub fn slave_screen_port(&self) -> u16 {
-        self.slave_screen_port
+        self.slave_screen_addr.port()
     }
 
     /// The slave's IP + screen port as a full address.
     pub fn slave_screen_addr(&self) -> Result<SocketAddr, Box<dyn std::error::Error>> {
-        let peer = self.stream.peer_addr()?;
-        Ok(SocketAddr::new(peer.ip(), self.slave_screen_port))
+        Ok(self.slave_screen_addr)
     }
 
-    /// Send a mouse event over the control channel.
+    /// Bytes `(sent, received)` on the control channel so far.
+    pub fn control_traffic(&self) -> (u64, u64) {
+        match &self.control {
+            Control::Direct(_) => self.direct_traffic,
+            Control::ViaMaster { conn, .. } => {
+                let stats = conn.stats();
+                (stats.bytes_sent(), stats.bytes_received())
+            }
+        }
+    }
+
+    /// Smoothed round trip of the control channel, measured from
+    /// heartbeat echoes. Only the master connection has heartbeats, and
+    /// only once the master has echoed one.
+    pub fn control_rtt(&self) -> Option<Duration> {
+        match &self.control {
+            Control::Direct(_) => None,
+            Control::ViaMaster { conn, .. } => conn.stats().rtt().srtt(),
+        }
+    }
+
+    /// Whether the connection to the master has gone dead: closed, or
+    /// silent past the heartbeat deadline. A direct slave's end comes
+    /// through [`session_ended`](Self::session_ended) instead.
+    pub fn control_lost(&self) -> bool {
+        match &self.control {
+            Control::Direct(_) => false,
+            Control::ViaMaster { conn, .. } => {
+                *conn.on_liveness_change().borrow() == Liveness::Dead
+            }
+        }
+    }
+
+    /// Bytes a second the control channel has been sending at, for
+    /// upload estimates. Only uploads through the master measure it.
+    pub fn control_send_rate(&self) -> Option<u64> {
+        match &self.control {
+            Control::Direct(_) => None,
+            Control::ViaMaster { conn, .. } => conn.stats().send_rate(),
+        }
+    }
+
+    /// Send a mouse event over the control channel, stamped when the
+    /// slave takes [timed input](Self::timed_input).
     ///
-    /// Wire format: tag(1) + len(2) + bincode payload.
+    /// Wire format (direct mode): tag(1) + len(2) + bincode payload.
     pub async fn send_mouse(
         &mut self,
-        event: &tix_core::protocol::screen::MouseEvent,
+        event: &MouseEvent,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let payload = bincode::serialize(event)?;
-        self.send_tagged(0, &payload).await
+        if self.timed_input {
+            let timed = TimedMouseEvent {
+                at_ms: self.clock.elapsed().as_millis() as u64,
+                event: *event,
+            };
+            return match &mut self.control {
+                Control::Direct(_) => self.send_tagged(6, &timed.to_bytes()?).await,
+                Control::ViaMaster { conn, next_req_id } => {
+                    let pkt = timed.into_packet(Self::next_id(next_req_id))?;
+                    Ok(conn.send(pkt).await?)
+                }
+            };
+        }
+        match &mut self.control {
+            Control::Direct(_) => {
+                let payload = bincode::serialize(event)?;
+                self.send_tagged(0, &payload).await
+            }
+            Control::ViaMaster { conn, next_req_id } => {
+                let pkt = event.into_packet(Self::next_id(next_req_id))?;
+                Ok(conn.send(pkt).await?)
+            }
+        }
     }
 
-    /// Send a keyboard event over the control channel.
+    /// Send a keyboard event over the control channel, stamped like
+    /// [`send_mouse`](Self::send_mouse).
     pub async fn send_keyboard(
         &mut self,
-        event: &tix_core::protocol::screen::KeyEvent,
+        event: &KeyEvent,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.timed_input {
+            let timed = TimedKeyEvent {
+                at_ms: self.clock.elapsed().as_millis() as u64,
+                event: *event,
+            };
+            return match &mut self.control {
+                Control::Direct(_) => self.send_tagged(7, &timed.to_bytes()?).await,
+                Control::ViaMaster { conn, next_req_id } => {
+                    let pkt = timed.into_packet(Self::next_id(next_req_id))?;
+                    Ok(conn.send(pkt).await?)
+                }
+            };
+        }
+        match &mut self.control {
+            Control::Direct(_) => {
+                let payload = bincode::serialize(event)?;
+                self.send_tagged(1, &payload).await
+            }
+            Control::ViaMaster { conn, next_req_id } => {
+                let pkt = event.into_packet(Self::next_id(next_req_id))?;
+                Ok(conn.send(pkt).await?)
+            }
+        }
+    }
+
+    /// Switch the session between control and view-only. Via the master
+    /// the slave answers with a `ScreenModeResponse` (see
+    /// [`poll_responses`](Self::poll_responses)); the direct protocol
+    /// has no replies.
+    pub async fn set_control(&mut self, control: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let request = ScreenModeRequest { control };
+        match &mut self.control {
+            Control::Direct(_) => {
+                let payload = request.to_bytes()?;
+                self.send_tagged(2, &payload).await
+            }
+            Control::ViaMaster { conn, next_req_id } => {
+                let pkt = request.into_packet(Self::next_id(next_req_id))?;
+                Ok(conn.send(pkt).await?)
+            }
+        }
+    }
+
+    /// Switch `session` to thumbnails (`low_bandwidth`) or back to
+    /// frames; `fallback` lets the slave switch to thumbnails by itself
+    /// when frames no longer fit the link. Neither transport answers.
+    pub async fn set_low_bandwidth(
+        &mut self,
+        session: u8,
+        low_bandwidth: bool,
+        fallback: bool,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let payload = bincode::serialize(event)?;
-        self.send_tagged(1, &payload).await
+        let request = ScreenSharingRequest { session, low_bandwidth, fallback };
+        match &mut self.control {
+            Control::Direct(_) => {
+                let payload = request.to_bytes()?;
+                self.send_tagged(8, &payload).await
+            }
+            Control::ViaMaster { conn, next_req_id } => {
+                let pkt = request.into_packet(Self::next_id(next_req_id))?;
+                Ok(conn.send(pkt).await?)
+            }
+        }
+    }
+
+    /// Tell the slave our decoder for `session` holds the dictionary
+    /// hashing to `hash`. It answers with a `ScreenDictionaryResponse`;
+    /// the direct protocol never offers a dictionary.
+    pub async fn confirm_dictionary(
+        &mut self,
+        session: u8,
+        hash: u64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match &mut self.control {
+            Control::Direct(_) => Err("frame dictionaries need --via-master".into()),
+            Control::ViaMaster { conn, next_req_id } => {
+                let request = ScreenDictionaryRequest { session, hash };
+                Ok(conn.send(request.into_packet(Self::next_id(next_req_id))?).await?)
+            }
+        }
+    }
+
+    /// Ask the slave to raise Ctrl+Alt+Del. It answers with a
+    /// `SasResponse` (see [`crate::special::sas_feedback`]); the direct
+    /// protocol cannot carry the request.
+    pub async fn send_sas(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match &mut self.control {
+            Control::Direct(_) => Err("Ctrl+Alt+Del needs --via-master".into()),
+            Control::ViaMaster { conn, next_req_id } => {
+                let id = Self::next_id(next_req_id);
+                let pkt = Packet::new_command(id, Command::SendSas, Vec::new())?;
+                Ok(conn.send(pkt).await?)
+            }
+        }
+    }
+
+    /// Ask the slave to stop streaming. Only meaningful via the master;
+    /// a direct tix-rdp-slave stops when the TCP stream closes.
+    pub async fn stop_screen(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Control::ViaMaster { conn, next_req_id } = &mut self.control {
+            let pkt = ScreenStopRequest::default().into_packet(Self::next_id(next_req_id))?;
+            conn.send(pkt).await?;
+        }
+        Ok(())
+    }
+
+    /// A sender and fresh request ID for a `FileWrite` upload. Only the
+    /// tix-core connection to the master can carry one.
+    pub fn begin_upload(&mut self) -> Result<(ConnectionSender, u64), Box<dyn std::error::Error>> {
+        match &mut self.control {
+            Control::Direct(_) => Err("drag-and-drop upload needs --via-master".into()),
+            Control::ViaMaster { conn, next_req_id } => {
+                Ok((conn.sender(), Self::next_id(next_req_id)))
+            }
+        }
+    }
+
+    /// Packets the slave has sent back since the last call (upload acks,
+    /// refusals, mode changes). Never waits.
+    pub fn poll_responses(&mut self) -> Vec<Packet> {
+        let mut out = Vec::new();
+        if let Control::ViaMaster { conn, .. } = &mut self.control {
+            while let Some(pkt) = conn.try_recv() {
+                if !note_hello(conn, &pkt, &mut self.slave) {
+                    out.push(pkt);
+                }
+            }
+        }
+        out
+    }
+
+    /// Why the slave cannot do what needs `feature`, when its `Hello`
+    /// left it out. `None` when it can, or has not said.
+    pub fn unsupported(&self, feature: Feature) -> Option<String> {
+        let Control::ViaMaster { conn, .. } = &self.control else {
+            return None;
+        };
+        let features = conn.peer_capabilities()?.features?;
+        if features.contains(feature) {
+            return None;
+        }
+        let version = self
+            .slave
+            .as_ref()
+            .map_or("?", |hello| hello.version.as_str());
+        Some(format!("not supported by connected slave (v{version})"))
+    }
+
+    /// Why a direct slave ended the session, once its tag-5 frame or
+    /// the end of the stream has arrived. Never waits, and leaves any
+    /// other frame for [`read_tagged`](Self::read_tagged). Via the master
+    /// the control connection reports its own disconnects.
+    pub async fn session_ended(&mut self) -> Option<String> {
+        let Control::Direct(stream) = &mut self.control else {
+            return None;
+        };
+        let mut header = [0u8; 3];
+        let peeked = tokio::time::timeout(Duration::ZERO, stream.peek(&mut header)).await;
+        match peeked {
+            Err(_) => return None,
+            Ok(Err(e)) => return Some(e.to_string()),
+            Ok(Ok(0)) => return Some("the slave closed the connection".into()),
+            Ok(Ok(n)) if n < header.len() || header[0] != 5 => return None,
+            Ok(Ok(_)) => {}
+        }
+        let mut frame = vec![0u8; 3 + u16::from_le_bytes([header[1], header[2]]) as usize];
+        match tokio::time::timeout(Duration::ZERO, stream.peek(&mut frame)).await {
+            Ok(Ok(n)) if n == frame.len() => {}
+            _ => return None,
+        }
+        stream.read_exact(&mut frame).await.ok()?;
+        self.direct_traffic.1 += frame.len() as u64;
+        Some(String::from_utf8_lossy(&frame[3..]).into_owned())
     }
 
     /// Low-level tagged write.
@@ -97,19 +668,189 @@ impl SlaveConnection {
         tag: u8,
         data: &[u8],
     ) -> Result<(), Box<dyn std::error::Error>> {
+        let Control::Direct(stream) = &mut self.control else {
+            return Err("tagged frames are only used by the direct protocol".into());
+        };
+
         let len = data.len() as u16;
         let mut header = [0u8; 3];
         header[0] = tag;
         header[1..3].copy_from_slice(&len.to_le_bytes());
 
-        self.stream.write_all(&header).await?;
-        self.stream.write_all(data).await?;
+        stream.write_all(&header).await?;
+        stream.write_all(data).await?;
+        self.direct_traffic.0 += (header.len() + data.len()) as u64;
         Ok(())
     }
 
+    /// Low-level tagged read, the slave's answer to a tagged request.
+    async fn read_tagged(&mut self) -> Result<(u8, Vec<u8>), Box<dyn std::error::Error>> {
+        let Control::Direct(stream) = &mut self.control else {
+            return Err("tagged frames are only used by the direct protocol".into());
+        };
+
+        let read = async {
+            let mut header = [0u8; 3];
+            stream.read_exact(&mut header).await?;
+            let mut data = vec![0u8; u16::from_le_bytes([header[1], header[2]]) as usize];
+            stream.read_exact(&mut data).await?;
+            Ok::<_, std::io::Error>((header[0], data))
+        };
+        let (tag, data) = tokio::time::timeout(self.timeout, read).await??;
+        self.direct_traffic.1 += (3 + data.len()) as u64;
+        Ok((tag, data))
+    }
+
+    fn next_id(counter: &mut u64) -> u64 {
+        let id = *counter;
+        *counter += 1;
+        id
+    }
+
     /// Consume self and return the underlying TCP stream (for
-    /// advanced usage or shutdown).
-    pub fn into_stream(self) -> TcpStream {
-        self.stream
+    /// advanced usage or shutdown). `None` in via-master mode.
+    pub fn into_stream(self) -> Option<TcpStream> {
+        match self.control {
+            Control::Direct(stream) => Some(stream),
+            Control::ViaMaster { .. } => None,
+        }
+    }
+}
+
+/// The `ScreenStart` request every session starts from.
+fn screen_request(config: &GuiConfig) -> ScreenStartRequest {
+    let request = ScreenStartRequest::new()
+        .with_format(config.performance.image_format())
+        .with_control(!config.input.view_only)
+        .with_mtu(config.network.mtu.min(u16::MAX as usize) as u16)
+        .with_mtu_probe(config.network.probe_mtu)
+        .with_udp_input(config.input.udp_pointer)
+        .with_timed_input(config.input.timed_input)
+        .with_preflight(config.network.preflight)
+        .with_thumbnails(config.performance.thumbnails)
+        .with_low_bandwidth(config.performance.low_bandwidth)
+        .with_monitor(config.display.monitors.first().copied().unwrap_or(0));
+    if config.performance.zstd_dictionary {
+        let dir = Path::new(&config.performance.dictionary_dir);
+        request.with_dictionary(dictionary::known(dir))
+    } else {
+        request
+    }
+}
+
+/// Wait for the `ScreenStart` ack to request `id`, skipping anything
+/// else.
+async fn await_ack(
+    conn: &mut Connection,
+    id: u64,
+    udp: &UdpSocket,
+    timeout: Duration,
+    tally: &mut ProbeTally,
+    slave: &mut Option<HelloInfo>,
+) -> Result<ScreenConfig, Box<dyn std::error::Error>> {
+    let wait_ack = async {
+        while let Some(pkt) = conn.recv().await {
+            if note_hello(conn, &pkt, slave) {
+                continue;
+            }
+            if pkt.request_id() == id && matches!(pkt.command(), Ok(Command::ScreenStart)) {
+                return Some(pkt);
+            }
+        }
+        None
+    };
+    let ack = tokio::time::timeout(timeout, answering_probes(udp, tally, wait_ack))
+        .await??
+        .ok_or("master closed the connection during ScreenStart")?;
+
+    match ScreenStartResponse::from_bytes(ack.payload())? {
+        ScreenStartResponse::Started(screen) => Ok(screen),
+        ScreenStartResponse::Failed(reason) => {
+            Err(format!("slave refused ScreenStart: {reason}").into())
+        }
+    }
+}
+
+/// Keep the slave's `Hello` if `pkt` is it, and what it advertises on
+/// `conn`. Returns whether it was.
+fn note_hello(conn: &Connection, pkt: &Packet, slave: &mut Option<HelloInfo>) -> bool {
+    if !matches!(pkt.command(), Ok(Command::Hello)) {
+        return false;
+    }
+    match HelloInfo::from_bytes(pkt.payload()) {
+        Ok(hello) => {
+            info!("slave runs {} {}", hello.product, hello.version);
+            conn.set_peer_capabilities(hello.capabilities());
+            *slave = Some(hello);
+        }
+        Err(e) => warn!("bad Hello from the master: {e}"),
+    }
+    true
+}
+
+/// Judge the preflight the ack reports, if it ran, and send the slave
+/// our side of it. Fails when none of the slave's probes arrived; the
+/// slave stops the session on hearing so.
+async fn report_preflight(
+    conn: &mut Connection,
+    id: u64,
+    screen: &ScreenConfig,
+    tally: &ProbeTally,
+    advertised: SocketAddr,
+    local_port: u16,
+) -> Result<Option<PreflightVerdict>, Box<dyn std::error::Error>> {
+    let Some(report) = &screen.preflight else {
+        return Ok(None);
+    };
+    let ours = ScreenPreflightRequest {
+        session: screen.session,
+        received: tally.received(),
+        source: tally.source,
+    };
+    conn.send(ours.into_packet(id)?).await?;
+
+    let assessment = preflight::assess(report, tally, advertised, local_port);
+    match assessment.verdict {
+        PreflightVerdict::Blocked => return Err(assessment.to_string().into()),
+        PreflightVerdict::Clear => info!("session {}: {assessment}", screen.session),
+        PreflightVerdict::Lossy | PreflightVerdict::OneWay => {
+            warn!("session {}: {assessment}", screen.session)
+        }
+    }
+    Ok(Some(assessment.verdict))
+}
+
+/// The address and MTU a session streams from. `host` stands in for a
+/// wildcard IP, which means "the host you reached the master on".
+fn stream_endpoint(
+    screen: &ScreenConfig,
+    host: IpAddr,
+) -> Result<(SocketAddr, usize), Box<dyn std::error::Error>> {
+    let mut addr = screen
+        .udp_endpoint
+        .ok_or("ScreenStart ack did not include a UDP endpoint")?;
+    if addr.ip().is_unspecified() {
+        addr.set_ip(host);
+    }
+    // Zero from a slave that predates MTU negotiation.
+    let mtu = match screen.mtu {
+        0 => DEFAULT_MTU,
+        mtu => mtu as usize,
+    };
+    Ok((addr, mtu))
+}
+
+/// Drive `fut` while acking MTU probes on `udp` and echoing preflight
+/// probes, which are counted into `tally`.
+async fn answering_probes<T>(
+    udp: &UdpSocket,
+    tally: &mut ProbeTally,
+    fut: impl Future<Output = T>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    tokio::select! {
+        out = fut => Ok(out),
+        Err(e) = preflight::answer_probes(udp, tally) => {
+            Err(format!("screen UDP socket: {e}").into())
+        }
     }
 }
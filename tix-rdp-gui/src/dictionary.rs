@@ -0,0 +1,147 @@
+//! Frame dictionaries the viewer holds.
+//!
+//! The `ScreenStart` request lists the dictionaries we already have by
+//! hash, so the slave sends the bytes of one only the first time. The
+//! built-in dictionary is always listed; others are kept in
+//! `performance.dictionary_dir` as `<hash>.dict`, the hash in 16 hex
+//! digits. Without a directory every other dictionary is sent again on
+//! each connect.
+
+use std::path::{Path, PathBuf};
+
+use tix_core::protocol::screen::{DictionaryOffer, ScreenDictionaryResponse};
+use tix_core::rdp::dictionary::FrameDictionary;
+use tix_core::{Command, Packet};
+
+/// Hashes of the dictionaries that need not be sent: the built-in one
+/// and those saved in `dir`.
+pub fn known(dir: &Path) -> Vec<u64> {
+    let mut hashes = vec![FrameDictionary::builtin().hash()];
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return hashes;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "dict")
+            && let Some(hash) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| u64::from_str_radix(stem, 16).ok())
+            && !hashes.contains(&hash)
+        {
+            hashes.push(hash);
+        }
+    }
+    hashes
+}
+
+/// The dictionary `offer` stands for: the one it carries, saved to
+/// `dir` for next time, or one we hold when it only names the hash.
+pub fn resolve(offer: &DictionaryOffer, dir: &Path) -> Result<FrameDictionary, String> {
+    let builtin = FrameDictionary::builtin();
+    if offer.bytes.is_empty() && offer.hash == builtin.hash() {
+        return Ok(builtin);
+    }
+    let path = cache_path(dir, offer.hash);
+    let dict = if offer.bytes.is_empty() {
+        let bytes = std::fs::read(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        FrameDictionary::new(bytes).map_err(|e| format!("{}: {e}", path.display()))?
+    } else {
+        FrameDictionary::new(offer.bytes.clone()).map_err(|e| e.to_string())?
+    };
+    if dict.hash() != offer.hash {
+        return Err(format!(
+            "dictionary hashes to {:016x}, not the {:016x} offered",
+            dict.hash(),
+            offer.hash
+        ));
+    }
+    if !offer.bytes.is_empty() && !dir.as_os_str().is_empty() {
+        std::fs::create_dir_all(dir)
+            .and_then(|()| std::fs::write(&path, dict.bytes()))
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+    Ok(dict)
+}
+
+/// Interpret the slave's answer to a `ScreenDictionary` confirmation.
+/// `None` for any other packet.
+pub fn feedback(packet: &Packet) -> Option<Result<(), String>> {
+    if packet.command().ok()? != Command::ScreenDictionary {
+        return None;
+    }
+    Some(match ScreenDictionaryResponse::from_bytes(packet.payload()) {
+        Ok(ScreenDictionaryResponse::Enabled) => Ok(()),
+        Ok(ScreenDictionaryResponse::Refused(reason)) => {
+            Err(format!("frame dictionary refused: {reason}"))
+        }
+        Err(e) => Err(format!("bad ScreenDictionary response: {e}")),
+    })
+}
+
+fn cache_path(dir: &Path, hash: u64) -> PathBuf {
+    dir.join(format!("{hash:016x}.dict"))
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sent_dictionaries_are_cached_by_hash() {
+        let dir = std::env::temp_dir().join(format!("tix-dictionary-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let builtin = FrameDictionary::builtin();
+        assert_eq!(known(&dir), vec![builtin.hash()]);
+
+        // Named only: the built-in one needs no cache.
+        let named = DictionaryOffer {
+            hash: builtin.hash(),
+            bytes: Vec::new(),
+        };
+        assert_eq!(resolve(&named, Path::new("")).unwrap(), builtin);
+
+        let samples: Vec<Vec<u8>> = (0..200u32)
+            .map(|i| format!("window {i} title bar {} pixels", i * 7).repeat(8).into_bytes())
+            .collect();
+        let trained = FrameDictionary::train(&samples, 4096).unwrap();
+        let sent = DictionaryOffer {
+            hash: trained.hash(),
+            bytes: trained.bytes().to_vec(),
+        };
+        assert_eq!(resolve(&sent, &dir).unwrap(), trained);
+        assert_eq!(known(&dir), vec![builtin.hash(), trained.hash()]);
+
+        let named = DictionaryOffer {
+            hash: trained.hash(),
+            bytes: Vec::new(),
+        };
+        assert_eq!(resolve(&named, &dir).unwrap(), trained);
+        assert!(resolve(&named, Path::new("")).is_err());
+
+        let forged = DictionaryOffer {
+            hash: trained.hash() ^ 1,
+            bytes: trained.bytes().to_vec(),
+        };
+        assert!(resolve(&forged, &dir).unwrap_err().contains("not the"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn confirmation_answers_are_recognised() {
+        let enabled = ScreenDictionaryResponse::Enabled.into_packet(4).unwrap();
+        assert_eq!(feedback(&enabled), Some(Ok(())));
+        let refused = ScreenDictionaryResponse::Refused("no screen session 2".into())
+            .into_packet(5)
+            .unwrap();
+        assert_eq!(
+            feedback(&refused),
+            Some(Err("frame dictionary refused: no screen session 2".into()))
+        );
+        let other = Packet::new_response(6, Command::SendSas, Vec::new()).unwrap();
+        assert_eq!(feedback(&other), None);
+    }
+}
@@ -1,41 +1,752 @@
 //! Display renderer — blits decoded frame buffers to the window.
 //!
-//! Uses GDI `StretchDIBits` for maximum compatibility. A future
-//! iteration could use Direct3D 11 for GPU-accelerated rendering.
+//! Uses GDI for maximum compatibility. A future iteration could use
+//! Direct3D 11 for GPU-accelerated rendering.
+//!
+//! Frames are composed in a backbuffer the size of the client area (a
+//! DIB section) and put on screen with a single `BitBlt`, so the window
+//! never shows a half-drawn frame. When a frame only changes a few
+//! blocks of the one before it, only the rectangle around them is
+//! copied in and presented (see [`Present`]).
+//!
+//! The remote image is letterboxed: scaled to fit the window while
+//! keeping its aspect ratio, with black bars filling the rest. Scaling
+//! and any colour filters happen in the renderer's
+//! [`PostProcessing`](crate::postprocess::PostProcessing), so GDI only
+//! ever copies pixels 1:1. An optional [`ProgressOverlay`] is drawn as a
+//! bar along the bottom edge; a [`Notice`] uses the same strip for a few
+//! seconds of plain text.
+//! While the slave reports a secure desktop, a [`StatusPanel`] replaces
+//! the frozen frame; a [`Banner`] across the top says when input is not
+//! reaching it although frames are, or that the slave sends thumbnails
+//! for a slow link, with what it has in the foreground on a second
+//! line. Before a session exists the renderer draws the
+//! [`ConnectDialog`](crate::wizard::ConnectDialog) instead. A locked
+//! session (see [`crate::lock`]) is drawn dimmed under a [`LockPanel`].
+//! A stalled stream (see [`crate::stall`]) keeps its last frame up,
+//! optionally a little darker, with a [`StallHint`] in the top-right
+//! corner.
+
+use std::time::{Duration, Instant};
+
+use tix_core::rdp::DirtyRect;
+use tix_core::rdp::desktop::ScreenStatus;
+use tix_core::rdp::thumbnail::ForegroundInfo;
+
+// ── Viewport ─────────────────────────────────────────────────────
+
+/// Where the remote frame lands inside the client area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// Largest aspect-preserving rectangle for a `frame_w × frame_h`
+    /// image, centred in a `window_w × window_h` client area.
+    pub fn letterbox(window_w: u32, window_h: u32, frame_w: u32, frame_h: u32) -> Self {
+        if frame_w == 0 || frame_h == 0 || window_w == 0 || window_h == 0 {
+            return Self {
+                x: 0,
+                y: 0,
+                width: window_w,
+                height: window_h,
+            };
+        }
+
+        // Compare window_w / window_h against frame_w / frame_h without floats.
+        let (ww, wh, fw, fh) = (window_w as u64, window_h as u64, frame_w as u64, frame_h as u64);
+        let (width, height) = if ww * fh > wh * fw {
+            // Window is wider than the frame: bars left and right.
+            ((wh * fw / fh) as u32, window_h)
+        } else {
+            (window_w, (ww * fh / fw) as u32)
+        };
+
+        Self {
+            x: ((window_w - width) / 2) as i32,
+            y: ((window_h - height) / 2) as i32,
+            width,
+            height,
+        }
+    }
+
+    /// Map a client-area point to remote screen coordinates, clamping
+    /// points that fall on the bars to the nearest edge.
+    pub fn to_remote(&self, x: i32, y: i32, remote_w: u32, remote_h: u32) -> (i32, i32) {
+        if self.width == 0 || self.height == 0 {
+            return (0, 0);
+        }
+        let rel_x = (x - self.x).clamp(0, self.width as i32 - 1);
+        let rel_y = (y - self.y).clamp(0, self.height as i32 - 1);
+        (
+            (rel_x as f64 / self.width as f64 * remote_w as f64) as i32,
+            (rel_y as f64 / self.height as f64 * remote_h as f64) as i32,
+        )
+    }
+}
+
+// ── Presenting ───────────────────────────────────────────────────
+
+/// What a render puts on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Present {
+    /// The whole client area: bars, frame and overlays.
+    Full,
+    /// Only this part of the viewport, in client coordinates.
+    Partial(Viewport),
+    /// Nothing: the frame changed no pixels.
+    Skip,
+}
+
+impl Present {
+    /// How to show a `frame_w × frame_h` frame letterboxed into `vp`.
+    ///
+    /// `dirty` is what changed since the frame presented last, `None`
+    /// meaning everything. `stale` says the backbuffer no longer
+    /// matches the window: a resize, new filters or overlays, or a
+    /// panel drawn in between.
+    pub fn plan(
+        dirty: Option<&[DirtyRect]>,
+        stale: bool,
+        frame_w: u32,
+        frame_h: u32,
+        vp: &Viewport,
+    ) -> Self {
+        let Some(dirty) = dirty.filter(|_| !stale) else {
+            return Self::Full;
+        };
+        let Some(bounds) = DirtyRect::bounding(dirty) else {
+            return Self::Skip;
+        };
+        match scale_to_viewport(&bounds, frame_w, frame_h, vp) {
+            None => Self::Skip,
+            Some(rect) if rect == *vp => Self::Full,
+            Some(rect) => Self::Partial(rect),
+        }
+    }
+
+    /// Pixels copied to a `window_w × window_h` client area.
+    pub fn pixels(&self, window_w: u32, window_h: u32) -> u64 {
+        match self {
+            Self::Full => window_w as u64 * window_h as u64,
+            Self::Partial(rect) => rect.width as u64 * rect.height as u64,
+            Self::Skip => 0,
+        }
+    }
+}
+
+/// Where `rect` of a `frame_w × frame_h` frame lands in `vp`, clipped
+/// to it. The rectangle first grows by a frame pixel on every side: a
+/// scaled output pixel blends the source pixels next to the one under
+/// it, so a change bleeds that far.
+fn scale_to_viewport(
+    rect: &DirtyRect,
+    frame_w: u32,
+    frame_h: u32,
+    vp: &Viewport,
+) -> Option<Viewport> {
+    if frame_w == 0 || frame_h == 0 {
+        return None;
+    }
+    let span = |start: u32, len: u32, frame: u32, out: u32| {
+        let (frame, out) = (frame as u64, out as u64);
+        let first = (start.saturating_sub(1) as u64).min(frame);
+        let end = (start as u64 + len as u64 + 1).min(frame);
+        let from = first * out / frame;
+        let to = (end * out).div_ceil(frame).min(out);
+        (from as u32, to.saturating_sub(from) as u32)
+    };
+    let (x, width) = span(rect.x, rect.width, frame_w, vp.width);
+    let (y, height) = span(rect.y, rect.height, frame_h, vp.height);
+    (width > 0 && height > 0).then_some(Viewport {
+        x: vp.x + x as i32,
+        y: vp.y + y as i32,
+        width,
+        height,
+    })
+}
+
+/// Copy `region` of `frame` into `target`, a `target_w` pixels wide
+/// BGRA8 image of the client area. `frame` is the image drawn at `vp`
+/// (`vp.width × vp.height`), and `region` lies inside `vp`.
+pub fn copy_region(
+    frame: &[u8],
+    vp: &Viewport,
+    region: &Viewport,
+    target: &mut [u8],
+    target_w: u32,
+) {
+    let (frame_stride, target_stride) = (vp.width as usize * 4, target_w as usize * 4);
+    let (fx, fy) = ((region.x - vp.x) as usize, (region.y - vp.y) as usize);
+    let row_bytes = region.width as usize * 4;
+    for row in 0..region.height as usize {
+        let src = &frame[(fy + row) * frame_stride + fx * 4..][..row_bytes];
+        let at = (region.y as usize + row) * target_stride + region.x as usize * 4;
+        target[at..at + row_bytes].copy_from_slice(src);
+    }
+}
+
+/// Window pixels copied by recent presents, for the stats line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UploadStats {
+    /// Frames put on screen, and how many of them partially.
+    pub frames: u64,
+    pub partial: u64,
+    pub pixels: u64,
+}
+
+impl UploadStats {
+    pub fn record(&mut self, present: &Present, window_w: u32, window_h: u32) {
+        if *present == Present::Skip {
+            return;
+        }
+        self.frames += 1;
+        self.partial += matches!(present, Present::Partial(_)) as u64;
+        self.pixels += present.pixels(window_w, window_h);
+    }
+
+    /// Average pixels uploaded per frame.
+    pub fn per_frame(&self) -> u64 {
+        self.pixels.checked_div(self.frames).unwrap_or(0)
+    }
+
+    /// `0.42 MP/frame, 93% partial`, for the title bar.
+    pub fn summary(&self) -> String {
+        format!(
+            "{:.2} MP/frame, {}% partial",
+            self.per_frame() as f64 / 1e6,
+            (self.partial * 100).checked_div(self.frames).unwrap_or(0)
+        )
+    }
+}
+
+// ── Progress overlay ─────────────────────────────────────────────
+
+/// Height of the progress bar strip in pixels.
+const OVERLAY_HEIGHT: u32 = 24;
+
+/// A labelled progress bar along the bottom of the window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressOverlay {
+    /// Text drawn over the bar.
+    pub label: String,
+    /// Completed fraction, `0.0..=1.0`.
+    pub fraction: f32,
+}
+
+impl ProgressOverlay {
+    /// The full-width track and the filled part of it for a
+    /// `window_w × window_h` client area.
+    pub fn bar(&self, window_w: u32, window_h: u32) -> (Viewport, Viewport) {
+        let height = OVERLAY_HEIGHT.min(window_h);
+        let track = Viewport {
+            x: 0,
+            y: (window_h - height) as i32,
+            width: window_w,
+            height,
+        };
+        let fill = Viewport {
+            width: (window_w as f32 * self.fraction.clamp(0.0, 1.0)) as u32,
+            ..track
+        };
+        (track, fill)
+    }
+}
+
+/// A short message in the overlay strip that goes away by itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notice {
+    label: String,
+    until: Instant,
+}
+
+impl Notice {
+    /// Show `label` from `now` for `duration`.
+    pub fn new(label: impl Into<String>, now: Instant, duration: Duration) -> Self {
+        Self { label: label.into(), until: now + duration }
+    }
+
+    /// The strip to draw at `now`, or `None` once the notice has expired.
+    pub fn overlay(&self, now: Instant) -> Option<ProgressOverlay> {
+        (now < self.until).then(|| ProgressOverlay { label: self.label.clone(), fraction: 0.0 })
+    }
+}
+
+// ── Status panel ─────────────────────────────────────────────────
+
+/// Size of the status panel, shrunk to fit small windows.
+const STATUS_PANEL_WIDTH: u32 = 560;
+const STATUS_PANEL_HEIGHT: u32 = 72;
+
+/// A notice drawn centred on a black window in place of the remote
+/// frame, e.g. while a UAC prompt holds the slave's screen or capture
+/// there keeps failing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusPanel {
+    pub title: String,
+    pub detail: String,
+}
+
+impl StatusPanel {
+    /// The panel for `status`, or `None` while the stream is live.
+    pub fn for_status(status: &ScreenStatus) -> Option<Self> {
+        (!status.is_live()).then(|| Self {
+            title: "Remote screen unavailable".into(),
+            detail: status.message(),
+        })
+    }
+
+    /// The panel rectangle, centred in a `window_w × window_h` area.
+    pub fn panel(&self, window_w: u32, window_h: u32) -> Viewport {
+        let width = STATUS_PANEL_WIDTH.min(window_w);
+        let height = STATUS_PANEL_HEIGHT.min(window_h);
+        Viewport {
+            x: ((window_w - width) / 2) as i32,
+            y: ((window_h - height) / 2) as i32,
+            width,
+            height,
+        }
+    }
+}
+
+// ── Banner ───────────────────────────────────────────────────────
+
+/// Height of the banner strip in pixels, and what a detail line adds.
+const BANNER_HEIGHT: u32 = 28;
+const BANNER_DETAIL_HEIGHT: u32 = 20;
+
+/// Banner label while the slave sends thumbnails.
+pub const LOW_BANDWIDTH_LABEL: &str = "LOW BANDWIDTH MODE (F7 for full frames)";
+
+/// A warning across the top of a live frame, for trouble that does not
+/// stop the stream: the slave's input being blocked by an elevated
+/// window, for one, or a link only fit for thumbnails.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Banner {
+    pub label: String,
+    /// A second, smaller line under the label.
+    pub detail: Option<String>,
+}
+
+impl Banner {
+    /// The banner for `status`, or `None` when there is nothing to warn
+    /// about over the frame.
+    pub fn for_status(status: &ScreenStatus) -> Option<Self> {
+        status.input_blocked().map(|reason| Self { label: reason.into(), detail: None })
+    }
+
+    /// The banner over thumbnails, naming what the slave has in the
+    /// foreground.
+    pub fn low_bandwidth(foreground: &ForegroundInfo) -> Self {
+        Self { label: LOW_BANDWIDTH_LABEL.into(), detail: Some(foreground.summary()) }
+    }
+
+    /// One banner saying both, low bandwidth first; either alone as is.
+    pub fn combine(low_bandwidth: Option<Self>, status: Option<Self>) -> Option<Self> {
+        match (low_bandwidth, status) {
+            (Some(low), Some(status)) => Some(Self {
+                label: format!("{} · {}", low.label, status.label),
+                detail: low.detail,
+            }),
+            (low, status) => low.or(status),
+        }
+    }
+
+    /// The strip along the top of a `window_w × window_h` area.
+    pub fn strip(&self, window_w: u32, window_h: u32) -> Viewport {
+        let height = match self.detail {
+            Some(_) => BANNER_HEIGHT + BANNER_DETAIL_HEIGHT,
+            None => BANNER_HEIGHT,
+        };
+        Viewport { x: 0, y: 0, width: window_w, height: height.min(window_h) }
+    }
+}
+
+// ── Menu ─────────────────────────────────────────────────────────
+
+/// Width of the menu panel and height of each of its rows.
+const MENU_WIDTH: u32 = 360;
+const MENU_ROW_HEIGHT: u32 = 24;
+/// Space above the rows, taken by the title.
+const MENU_HEADER: u32 = 36;
+
+/// A list with one highlighted row, drawn over the top-left of the
+/// frame (see [`crate::special`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuPanel {
+    pub title: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+impl MenuPanel {
+    /// The panel rectangle in a `window_w × window_h` area.
+    pub fn panel(&self, window_w: u32, window_h: u32) -> Viewport {
+        let height = MENU_HEADER + MENU_ROW_HEIGHT * self.items.len() as u32 + 8;
+        Viewport { x: 16, y: 16, width: MENU_WIDTH.min(window_w), height: height.min(window_h) }
+    }
+
+    /// Row `index` inside `panel`.
+    pub fn row(&self, panel: &Viewport, index: usize) -> Viewport {
+        Viewport {
+            x: panel.x + 4,
+            y: panel.y + (MENU_HEADER + MENU_ROW_HEIGHT * index as u32) as i32,
+            width: panel.width.saturating_sub(8),
+            height: MENU_ROW_HEIGHT,
+        }
+    }
+}
+
+// ── Lock panel ───────────────────────────────────────────────────
+
+/// Height of the lock panel, with and without the token prompt.
+const LOCK_PANEL_HEIGHT: u32 = 56;
+const LOCK_PROMPT_HEIGHT: u32 = 108;
+
+/// What a locked session shows over its dimmed frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockPanel {
+    pub title: String,
+    /// The token typed so far, masked; `None` until it is asked for.
+    pub prompt: Option<String>,
+    /// Why the last token was refused.
+    pub error: Option<String>,
+}
+
+impl LockPanel {
+    /// The panel rectangle, centred in a `window_w × window_h` area.
+    pub fn panel(&self, window_w: u32, window_h: u32) -> Viewport {
+        let height = if self.prompt.is_some() { LOCK_PROMPT_HEIGHT } else { LOCK_PANEL_HEIGHT };
+        let width = STATUS_PANEL_WIDTH.min(window_w);
+        let height = height.min(window_h);
+        Viewport {
+            x: ((window_w - width) / 2) as i32,
+            y: ((window_h - height) / 2) as i32,
+            width,
+            height,
+        }
+    }
+
+    /// The token field inside `panel`.
+    pub fn field(&self, panel: &Viewport) -> Viewport {
+        Viewport {
+            x: panel.x + 20,
+            y: panel.y + 44,
+            width: panel.width.saturating_sub(40),
+            height: 26,
+        }
+    }
+}
+
+/// A copy of a BGRA8 `frame` at a third of its brightness, for a locked
+/// session.
+pub fn dim_frame(frame: &[u8]) -> Vec<u8> {
+    frame
+        .chunks_exact(4)
+        .flat_map(|px| [px[0] / 3, px[1] / 3, px[2] / 3, px[3]])
+        .collect()
+}
+
+// ── Stall hint ───────────────────────────────────────────────────
+
+/// Size of the stall hint and its distance from the window edges.
+const STALL_HINT_WIDTH: u32 = 300;
+const STALL_HINT_HEIGHT: u32 = 24;
+const STALL_HINT_MARGIN: u32 = 12;
+
+/// A note in the top-right corner while the frame on screen is the last
+/// one that arrived before the stream stalled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StallHint {
+    pub label: String,
+    /// Draw the frame slightly darker underneath.
+    pub dim: bool,
+}
+
+impl StallHint {
+    /// The hint rectangle in a `window_w × window_h` area, kept clear of
+    /// a banner `below` pixels tall.
+    pub fn badge(&self, window_w: u32, window_h: u32, below: u32) -> Viewport {
+        let width = STALL_HINT_WIDTH.min(window_w);
+        let y = (below + STALL_HINT_MARGIN).min(window_h.saturating_sub(STALL_HINT_HEIGHT));
+        Viewport {
+            x: window_w.saturating_sub(width + STALL_HINT_MARGIN) as i32,
+            y: y as i32,
+            width,
+            height: STALL_HINT_HEIGHT.min(window_h),
+        }
+    }
+}
+
+/// A copy of a BGRA8 `frame` at three quarters of its brightness, for a
+/// stalled stream.
+pub fn shade_frame(frame: &[u8]) -> Vec<u8> {
+    frame
+        .chunks_exact(4)
+        .flat_map(|px| {
+            let shade = |c: u8| (c as u16 * 3 / 4) as u8;
+            [shade(px[0]), shade(px[1]), shade(px[2]), px[3]]
+        })
+        .collect()
+}
 
 #[cfg(target_os = "windows")]
 mod platform {
     use windows::Win32::Foundation::*;
     use windows::Win32::Graphics::Gdi::*;
 
+    use tix_core::rdp::DirtyRect;
+
+    use super::{
+        BANNER_HEIGHT, Banner, LockPanel, MenuPanel, Present, ProgressOverlay, StallHint,
+        StatusPanel, UploadStats, Viewport, copy_region, dim_frame, shade_frame,
+    };
+    use crate::postprocess::{FilterSet, PostProcessing, ScaleFilter};
+    use crate::wizard::{ConnectDialog, DialogLayout, Focus};
+
+    const PANEL_COLOR: COLORREF = COLORREF(0x0030_3030);
+    const ACCENT_COLOR: COLORREF = COLORREF(0x00C0_7A1E);
+    const BUTTON_COLOR: COLORREF = COLORREF(0x0050_5050);
+    const TEXT_COLOR: COLORREF = COLORREF(0x00FF_FFFF);
+    const ERROR_COLOR: COLORREF = COLORREF(0x0080_80FF);
+    const WARNING_COLOR: COLORREF = COLORREF(0x0000_5AB4);
+
+    fn rect(vp: &Viewport) -> RECT {
+        RECT {
+            left: vp.x,
+            top: vp.y,
+            right: vp.x + vp.width as i32,
+            bottom: vp.y + vp.height as i32,
+        }
+    }
+
+    unsafe fn fill(hdc: HDC, vp: &Viewport, color: COLORREF) {
+        unsafe {
+            let brush = CreateSolidBrush(color);
+            FillRect(hdc, &rect(vp), brush);
+            let _ = DeleteObject(brush);
+        }
+    }
+
+    unsafe fn text_out(hdc: HDC, x: i32, y: i32, text: &str, color: COLORREF) {
+        let wide: Vec<u16> = text.encode_utf16().collect();
+        unsafe {
+            SetTextColor(hdc, color);
+            let _ = TextOutW(hdc, x, y, &wide);
+        }
+    }
+
+    unsafe fn text_width(hdc: HDC, text: &str) -> i32 {
+        let wide: Vec<u16> = text.encode_utf16().collect();
+        let mut size = SIZE::default();
+        unsafe {
+            let _ = GetTextExtentPoint32W(hdc, &wide, &mut size);
+        }
+        size.cx
+    }
+
+    /// A top-down 32-bit DIB header for a `width × height` image.
+    fn bitmap_info(width: u32, height: u32) -> BITMAPINFO {
+        BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width as i32,
+                // Negative height = top-down DIB (origin at top-left).
+                biHeight: -(height as i32),
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0,
+                biSizeImage: 0,
+                biXPelsPerMeter: 0,
+                biYPelsPerMeter: 0,
+                biClrUsed: 0,
+                biClrImportant: 0,
+            },
+            bmiColors: [RGBQUAD::default(); 1],
+        }
+    }
+
+    /// The client area off screen: a DIB section selected into a memory
+    /// DC, written directly through `bits`.
+    struct BackBuffer {
+        dc: HDC,
+        bitmap: HBITMAP,
+        previous: HGDIOBJ,
+        bits: *mut u8,
+        width: u32,
+        height: u32,
+    }
+
+    impl BackBuffer {
+        unsafe fn new(window_dc: HDC, width: u32, height: u32) -> Option<Self> {
+            let bmi = bitmap_info(width, height);
+            let mut bits = std::ptr::null_mut();
+            unsafe {
+                let bitmap = CreateDIBSection(
+                    window_dc,
+                    &bmi,
+                    DIB_RGB_COLORS,
+                    &mut bits,
+                    HANDLE::default(),
+                    0,
+                )
+                .ok()?;
+                let dc = CreateCompatibleDC(window_dc);
+                if bits.is_null() || dc.is_invalid() {
+                    let _ = DeleteObject(bitmap);
+                    return None;
+                }
+                let previous = SelectObject(dc, bitmap);
+                Some(Self { dc, bitmap, previous, bits: bits.cast(), width, height })
+            }
+        }
+
+        /// The pixels, once GDI has finished drawing into them.
+        unsafe fn pixels(&mut self) -> &mut [u8] {
+            let len = self.width as usize * self.height as usize * 4;
+            unsafe {
+                let _ = GdiFlush();
+                std::slice::from_raw_parts_mut(self.bits, len)
+            }
+        }
+    }
+
+    impl Drop for BackBuffer {
+        fn drop(&mut self) {
+            unsafe {
+                SelectObject(self.dc, self.previous);
+                let _ = DeleteObject(self.bitmap);
+                let _ = DeleteDC(self.dc);
+            }
+        }
+    }
+
     /// Renders BGRA8 frame buffers into an HWND using GDI.
     pub struct DisplayRenderer {
         hwnd: HWND,
         width: u32,
         height: u32,
+        overlay: Option<ProgressOverlay>,
+        banner: Option<Banner>,
+        menu: Option<MenuPanel>,
+        lock: Option<LockPanel>,
+        stall: Option<StallHint>,
+        post: PostProcessing,
+        back: Option<BackBuffer>,
+        /// Size of the frame the backbuffer holds.
+        frame_size: (u32, u32),
+        /// The next render has to present everything.
+        stale: bool,
+        uploads: UploadStats,
     }
 
     impl DisplayRenderer {
         /// Create a renderer targeting the given window.
         pub fn new(hwnd: HWND, width: u32, height: u32) -> Self {
-            Self { hwnd, width, height }
+            Self {
+                hwnd,
+                width,
+                height,
+                overlay: None,
+                banner: None,
+                menu: None,
+                lock: None,
+                stall: None,
+                post: PostProcessing::default(),
+                back: None,
+                frame_size: (0, 0),
+                stale: true,
+                uploads: UploadStats::default(),
+            }
+        }
+
+        /// Scale and filter frames with `post` instead of the default
+        /// bilinear scaling.
+        pub fn with_post_processing(mut self, post: PostProcessing) -> Self {
+            self.post = post;
+            self
+        }
+
+        /// Scale frames with `scale` from the next
+        /// [`render`](Self::render) on.
+        pub fn set_scale_filter(&mut self, scale: ScaleFilter) {
+            self.post.set_scale(scale);
+            self.stale = true;
+        }
+
+        /// Apply `filters` to frames from the next
+        /// [`render`](Self::render) on.
+        pub fn set_filters(&mut self, filters: &FilterSet) {
+            self.post.set_filters(filters);
+            self.stale = true;
+        }
+
+        /// Show (or with `None`, hide) a banner over the top of the
+        /// frame. Takes effect on the next [`render`](Self::render).
+        pub fn set_banner(&mut self, banner: Option<Banner>) {
+            self.banner = banner;
+            self.stale = true;
+        }
+
+        /// Dim the frame under `lock` (or with `None`, draw it as it
+        /// is). Takes effect on the next [`render`](Self::render).
+        pub fn set_lock(&mut self, lock: Option<LockPanel>) {
+            self.lock = lock;
+            self.stale = true;
+        }
+
+        /// Show (or with `None`, hide) the stall hint, dimming the frame
+        /// under it if it asks. Takes effect on the next
+        /// [`render`](Self::render).
+        pub fn set_stall(&mut self, stall: Option<StallHint>) {
+            self.stall = stall;
+            self.stale = true;
+        }
+
+        /// Show (or with `None`, hide) a menu over the frame. Takes
+        /// effect on the next [`render`](Self::render).
+        pub fn set_menu(&mut self, menu: Option<MenuPanel>) {
+            self.menu = menu;
+            self.stale = true;
+        }
+
+        /// Show (or with `None`, hide) the progress bar. Takes effect on
+        /// the next [`render`](Self::render).
+        pub fn set_overlay(&mut self, overlay: Option<ProgressOverlay>) {
+            self.overlay = overlay;
+            self.stale = true;
         }
 
         /// Update the target size (call after WM_SIZE).
         pub fn resize(&mut self, width: u32, height: u32) {
             self.width = width;
             self.height = height;
+            self.stale = true;
+        }
+
+        /// What presents have copied since the last call.
+        pub fn take_upload_stats(&mut self) -> UploadStats {
+            std::mem::take(&mut self.uploads)
         }
 
         /// Render a BGRA8 frame buffer to the window.
         ///
         /// `frame_width` / `frame_height` describe the pixel dimensions
-        /// of `data`. The image is stretched to fill the window.
+        /// of `data`. The image is filtered, scaled to its letterboxed
+        /// viewport and composed with the overlays in the backbuffer.
+        /// `dirty` lists what changed since the frame rendered last
+        /// (`None` for everything); only that part is copied and
+        /// presented, unless something else asks for a full present.
         pub fn render(
-            &self,
+            &mut self,
             data: &[u8],
             frame_width: u32,
             frame_height: u32,
+            dirty: Option<&[DirtyRect]>,
         ) -> Result<(), String> {
             if data.is_empty() {
                 return Ok(());
@@ -50,49 +761,262 @@ mod platform {
                 ));
             }
 
+            let vp = Viewport::letterbox(self.width, self.height, frame_width, frame_height);
+            if vp.width == 0 || vp.height == 0 {
+                return Ok(());
+            }
+
+            let fits = self
+                .back
+                .as_ref()
+                .is_some_and(|b| (b.width, b.height) == (self.width, self.height));
+            let stale = self.stale || !fits || self.frame_size != (frame_width, frame_height);
+            let present = Present::plan(dirty, stale, frame_width, frame_height, &vp);
+            let region = match present {
+                Present::Skip => return Ok(()),
+                Present::Full => vp,
+                Present::Partial(rect) => rect,
+            };
+
             unsafe {
                 let hdc = GetDC(self.hwnd);
                 if hdc.is_invalid() {
                     return Err("GetDC failed".into());
                 }
+                if !fits {
+                    self.back = None;
+                    self.back = BackBuffer::new(hdc, self.width, self.height);
+                }
+                let Some(back) = self.back.as_mut() else {
+                    ReleaseDC(self.hwnd, hdc);
+                    return Err("CreateDIBSection failed".into());
+                };
 
-                let bmi = BITMAPINFO {
-                    bmiHeader: BITMAPINFOHEADER {
-                        biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
-                        biWidth: frame_width as i32,
-                        // Negative height = top-down DIB (origin at top-left).
-                        biHeight: -(frame_height as i32),
-                        biPlanes: 1,
-                        biBitCount: 32,
-                        biCompression: BI_RGB.0,
-                        biSizeImage: 0,
-                        biXPelsPerMeter: 0,
-                        biYPelsPerMeter: 0,
-                        biClrUsed: 0,
-                        biClrImportant: 0,
-                    },
-                    bmiColors: [RGBQUAD::default(); 1],
+                let data =
+                    self.post.process(data, frame_width, frame_height, vp.width, vp.height);
+                // The lock darkens the frame further than a stall.
+                let dimmed = match (&self.lock, &self.stall) {
+                    (Some(_), _) => Some(dim_frame(data)),
+                    (None, Some(stall)) if stall.dim => Some(shade_frame(data)),
+                    _ => None,
                 };
+                let data = dimmed.as_deref().unwrap_or(data);
+                let target_w = back.width;
+                copy_region(data, &vp, &region, back.pixels(), target_w);
 
-                StretchDIBits(
+                let back_dc = back.dc;
+                if present == Present::Full {
+                    self.paint_bars(back_dc, &vp);
+                }
+                // A copied region may have covered part of an overlay.
+                if let Some(overlay) = &self.overlay {
+                    self.paint_overlay(back_dc, overlay);
+                }
+                if let Some(banner) = &self.banner {
+                    self.paint_banner(back_dc, banner);
+                }
+                if let Some(stall) = &self.stall {
+                    self.paint_stall(back_dc, stall);
+                }
+                if let Some(menu) = &self.menu {
+                    self.paint_menu(back_dc, menu);
+                }
+                if let Some(lock) = &self.lock {
+                    self.paint_lock(back_dc, lock);
+                }
+
+                let shown = match present {
+                    Present::Partial(rect) => rect,
+                    _ => Viewport { x: 0, y: 0, width: self.width, height: self.height },
+                };
+                let blitted = BitBlt(
                     hdc,
-                    0,
-                    0,
-                    self.width as i32,
-                    self.height as i32,
-                    0,
-                    0,
-                    frame_width as i32,
-                    frame_height as i32,
-                    Some(data.as_ptr() as *const _),
-                    &bmi,
-                    DIB_RGB_COLORS,
+                    shown.x,
+                    shown.y,
+                    shown.width as i32,
+                    shown.height as i32,
+                    back_dc,
+                    shown.x,
+                    shown.y,
                     SRCCOPY,
                 );
+                ReleaseDC(self.hwnd, hdc);
+                blitted.map_err(|e| format!("BitBlt failed: {e}"))?;
+            }
+
+            self.stale = false;
+            self.frame_size = (frame_width, frame_height);
+            self.uploads.record(&present, self.width, self.height);
+            Ok(())
+        }
+
+        /// Blacken the client area outside the viewport.
+        unsafe fn paint_bars(&self, hdc: HDC, vp: &Viewport) {
+            let (w, h) = (self.width as i32, self.height as i32);
+            let (right, bottom) = (vp.x + vp.width as i32, vp.y + vp.height as i32);
+            unsafe {
+                if vp.x > 0 {
+                    let _ = PatBlt(hdc, 0, 0, vp.x, h, BLACKNESS);
+                    let _ = PatBlt(hdc, right, 0, w - right, h, BLACKNESS);
+                }
+                if vp.y > 0 {
+                    let _ = PatBlt(hdc, 0, 0, w, vp.y, BLACKNESS);
+                    let _ = PatBlt(hdc, 0, bottom, w, h - bottom, BLACKNESS);
+                }
+            }
+        }
 
+        /// Draw the progress bar over the bottom of the frame.
+        unsafe fn paint_overlay(&self, hdc: HDC, overlay: &ProgressOverlay) {
+            let (track, bar) = overlay.bar(self.width, self.height);
+            unsafe {
+                fill(hdc, &track, PANEL_COLOR);
+                fill(hdc, &bar, ACCENT_COLOR);
+                SetBkMode(hdc, TRANSPARENT);
+                text_out(hdc, track.x + 8, track.y + 4, &overlay.label, TEXT_COLOR);
+            }
+        }
+
+        /// Draw the banner across the top of the frame.
+        unsafe fn paint_banner(&self, hdc: HDC, banner: &Banner) {
+            let strip = banner.strip(self.width, self.height);
+            unsafe {
+                fill(hdc, &strip, WARNING_COLOR);
+                SetBkMode(hdc, TRANSPARENT);
+                text_out(hdc, strip.x + 10, strip.y + 6, &banner.label, TEXT_COLOR);
+                if let Some(detail) = &banner.detail {
+                    let y = strip.y + BANNER_HEIGHT as i32;
+                    text_out(hdc, strip.x + 10, y, detail, TEXT_COLOR);
+                }
+            }
+        }
+
+        /// Draw the stall hint in the top-right corner, under the banner
+        /// if there is one.
+        unsafe fn paint_stall(&self, hdc: HDC, stall: &StallHint) {
+            let below = self.banner.as_ref().map_or(0, |b| b.strip(self.width, self.height).height);
+            let badge = stall.badge(self.width, self.height, below);
+            unsafe {
+                fill(hdc, &badge, PANEL_COLOR);
+                let accent = Viewport { width: 4, ..badge };
+                fill(hdc, &accent, WARNING_COLOR);
+                SetBkMode(hdc, TRANSPARENT);
+                text_out(hdc, badge.x + 12, badge.y + 4, &stall.label, TEXT_COLOR);
+            }
+        }
+
+        /// Draw the menu over the top-left of the frame.
+        unsafe fn paint_menu(&self, hdc: HDC, menu: &MenuPanel) {
+            let panel = menu.panel(self.width, self.height);
+            unsafe {
+                fill(hdc, &panel, PANEL_COLOR);
+                SetBkMode(hdc, TRANSPARENT);
+                text_out(hdc, panel.x + 12, panel.y + 10, &menu.title, TEXT_COLOR);
+                for (i, item) in menu.items.iter().enumerate() {
+                    let row = menu.row(&panel, i);
+                    if i == menu.selected {
+                        fill(hdc, &row, ACCENT_COLOR);
+                    }
+                    text_out(hdc, row.x + 8, row.y + 4, item, TEXT_COLOR);
+                }
+            }
+        }
+
+        /// Draw the lock panel, and the token prompt once it is open,
+        /// over the middle of the frame.
+        unsafe fn paint_lock(&self, hdc: HDC, lock: &LockPanel) {
+            let panel = lock.panel(self.width, self.height);
+            unsafe {
+                fill(hdc, &panel, PANEL_COLOR);
+                let accent = Viewport { width: 4, ..panel };
+                fill(hdc, &accent, ACCENT_COLOR);
+                SetBkMode(hdc, TRANSPARENT);
+                text_out(hdc, panel.x + 20, panel.y + 18, &lock.title, TEXT_COLOR);
+                if let Some(prompt) = &lock.prompt {
+                    let field = lock.field(&panel);
+                    fill(hdc, &field, TEXT_COLOR);
+                    text_out(hdc, field.x + 6, field.y + 5, prompt, COLORREF(0));
+                    let x = field.x + 6 + text_width(hdc, prompt);
+                    let _ = PatBlt(hdc, x, field.y + 4, 1, field.height as i32 - 8, BLACKNESS);
+                    if let Some(error) = &lock.error {
+                        text_out(hdc, field.x, field.y + 32, error, ERROR_COLOR);
+                    }
+                }
+            }
+        }
+
+        /// Draw `status` on a black client area instead of a frame.
+        pub fn render_status(&mut self, status: &StatusPanel) -> Result<(), String> {
+            let panel = status.panel(self.width, self.height);
+            self.stale = true;
+            unsafe {
+                let hdc = GetDC(self.hwnd);
+                if hdc.is_invalid() {
+                    return Err("GetDC failed".into());
+                }
+                let _ = PatBlt(hdc, 0, 0, self.width as i32, self.height as i32, BLACKNESS);
+                fill(hdc, &panel, PANEL_COLOR);
+                let accent = Viewport { width: 4, ..panel };
+                fill(hdc, &accent, ACCENT_COLOR);
+                SetBkMode(hdc, TRANSPARENT);
+                text_out(hdc, panel.x + 20, panel.y + 16, &status.title, TEXT_COLOR);
+                text_out(hdc, panel.x + 20, panel.y + 40, &status.detail, TEXT_COLOR);
                 ReleaseDC(self.hwnd, hdc);
             }
+            Ok(())
+        }
+
+        /// Draw the connect dialog on a black client area.
+        pub fn render_dialog(&self, dialog: &ConnectDialog) -> Result<(), String> {
+            let layout = DialogLayout::new(self.width, self.height);
+            let (panel, field) = (layout.panel, layout.field);
+            let editing = dialog.focus() == Focus::Address && !dialog.is_connecting();
+
+            unsafe {
+                let hdc = GetDC(self.hwnd);
+                if hdc.is_invalid() {
+                    return Err("GetDC failed".into());
+                }
+                let _ = PatBlt(hdc, 0, 0, self.width as i32, self.height as i32, BLACKNESS);
+                fill(hdc, &panel, PANEL_COLOR);
+                SetBkMode(hdc, TRANSPARENT);
+
+                let left = panel.x + 20;
+                text_out(hdc, left, panel.y + 16, "Cannot reach the slave", TEXT_COLOR);
+                let status_color = if dialog.is_connecting() { TEXT_COLOR } else { ERROR_COLOR };
+                text_out(hdc, left, panel.y + 38, &dialog.status(), status_color);
+                text_out(hdc, left, field.y - 20, "Address (host:port)", TEXT_COLOR);
+
+                // Address field, outlined in the accent colour when focused.
+                if editing {
+                    let outline = Viewport {
+                        x: field.x - 2,
+                        y: field.y - 2,
+                        width: field.width + 4,
+                        height: field.height + 4,
+                    };
+                    fill(hdc, &outline, ACCENT_COLOR);
+                }
+                fill(hdc, &field, TEXT_COLOR);
+                let address = dialog.address();
+                text_out(hdc, field.x + 6, field.y + 5, address.text(), COLORREF(0));
+                if editing {
+                    let x = field.x + 6 + text_width(hdc, &address.text()[..address.caret()]);
+                    let _ = PatBlt(hdc, x, field.y + 4, 1, field.height as i32 - 8, BLACKNESS);
+                }
 
+                for (button, label, focus) in [
+                    (layout.retry, "Retry", Focus::Retry),
+                    (layout.quit, "Quit", Focus::Quit),
+                ] {
+                    let color = if dialog.focus() == focus { ACCENT_COLOR } else { BUTTON_COLOR };
+                    fill(hdc, &button, color);
+                    let x = button.x + (button.width as i32 - text_width(hdc, label)) / 2;
+                    text_out(hdc, x, button.y + 7, label, TEXT_COLOR);
+                }
+
+                ReleaseDC(self.hwnd, hdc);
+            }
             Ok(())
         }
     }
@@ -105,25 +1029,279 @@ pub use platform::*;
 
 #[cfg(not(target_os = "windows"))]
 pub mod stub {
+    use tix_core::rdp::DirtyRect;
+
+    use super::{
+        Banner, LockPanel, MenuPanel, ProgressOverlay, StallHint, StatusPanel, UploadStats,
+    };
+    use crate::postprocess::{FilterSet, PostProcessing, ScaleFilter};
+    use crate::wizard::ConnectDialog;
+
     pub struct DisplayRenderer;
 
     impl DisplayRenderer {
-        pub fn new(_hwnd: (), _w: u32, _h: u32) -> Self {
+        pub fn new(_hwnd: isize, _w: u32, _h: u32) -> Self {
             Self
         }
 
+        pub fn with_post_processing(self, _post: PostProcessing) -> Self {
+            self
+        }
+
+        pub fn set_scale_filter(&mut self, _scale: ScaleFilter) {}
+
+        pub fn set_filters(&mut self, _filters: &FilterSet) {}
+
+        pub fn set_overlay(&mut self, _overlay: Option<ProgressOverlay>) {}
+
+        pub fn set_banner(&mut self, _banner: Option<Banner>) {}
+
+        pub fn set_menu(&mut self, _menu: Option<MenuPanel>) {}
+
+        pub fn set_lock(&mut self, _lock: Option<LockPanel>) {}
+
+        pub fn set_stall(&mut self, _stall: Option<StallHint>) {}
+
         pub fn resize(&mut self, _w: u32, _h: u32) {}
 
+        pub fn take_upload_stats(&mut self) -> UploadStats {
+            UploadStats::default()
+        }
+
         pub fn render(
-            &self,
+            &mut self,
             _data: &[u8],
             _fw: u32,
             _fh: u32,
+            _dirty: Option<&[DirtyRect]>,
         ) -> Result<(), String> {
             Err("Display rendering is only supported on Windows".into())
         }
+
+        pub fn render_status(&mut self, _status: &StatusPanel) -> Result<(), String> {
+            Err("Display rendering is only supported on Windows".into())
+        }
+
+        pub fn render_dialog(&self, _dialog: &ConnectDialog) -> Result<(), String> {
+            Err("Display rendering is only supported on Windows".into())
+        }
     }
 }
 
 #[cfg(not(target_os = "windows"))]
 pub use stub::*;
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterbox_pillarboxes_narrow_frame() {
+        // 4:3 frame in a 16:9 window → bars left and right.
+        let vp = Viewport::letterbox(1920, 1080, 1024, 768);
+        assert_eq!(vp, Viewport { x: 240, y: 0, width: 1440, height: 1080 });
+    }
+
+    #[test]
+    fn letterbox_bars_top_and_bottom() {
+        let vp = Viewport::letterbox(1000, 1000, 1920, 1080);
+        assert_eq!(vp.width, 1000);
+        assert_eq!(vp.height, 562);
+        assert_eq!(vp.x, 0);
+        assert_eq!(vp.y, 219);
+    }
+
+    #[test]
+    fn overlay_bar_spans_bottom_edge() {
+        let overlay = ProgressOverlay {
+            label: "Uploading a.txt".into(),
+            fraction: 0.25,
+        };
+        let (track, fill) = overlay.bar(800, 600);
+        assert_eq!(track, Viewport { x: 0, y: 576, width: 800, height: 24 });
+        assert_eq!(fill, Viewport { width: 200, ..track });
+
+        let done = ProgressOverlay { fraction: 7.0, ..overlay };
+        assert_eq!(done.bar(800, 10).1, Viewport { x: 0, y: 0, width: 800, height: 10 });
+    }
+
+    #[test]
+    fn notice_expires() {
+        let now = Instant::now();
+        let notice = Notice::new("Saved screenshot.png", now, Duration::from_secs(3));
+        let overlay = notice.overlay(now + Duration::from_secs(2)).unwrap();
+        assert_eq!(overlay.label, "Saved screenshot.png");
+        assert_eq!(overlay.fraction, 0.0);
+        assert_eq!(notice.overlay(now + Duration::from_secs(3)), None);
+    }
+
+    #[test]
+    fn status_panel_unless_live() {
+        assert_eq!(StatusPanel::for_status(&ScreenStatus::Live), None);
+        let secure = ScreenStatus::SecureDesktop { desktop: Some("Winlogon".into()) };
+        let panel = StatusPanel::for_status(&secure).unwrap();
+        assert!(panel.detail.contains("interaction required at the machine"));
+        let failed = ScreenStatus::CaptureFailed { error: "DuplicateOutput failed".into() };
+        assert!(StatusPanel::for_status(&failed).unwrap().detail.contains("retrying"));
+        let blocked = ScreenStatus::InputBlocked { reason: "elevated window".into() };
+        assert_eq!(StatusPanel::for_status(&blocked), None);
+
+        assert_eq!(panel.panel(1280, 720), Viewport { x: 360, y: 324, width: 560, height: 72 });
+        assert_eq!(panel.panel(300, 50), Viewport { x: 0, y: 0, width: 300, height: 50 });
+    }
+
+    #[test]
+    fn banner_only_for_blocked_input() {
+        let blocked = ScreenStatus::InputBlocked { reason: "elevated window".into() };
+        let banner = Banner::for_status(&blocked).unwrap();
+        assert_eq!(banner.label, "elevated window");
+        assert_eq!(banner.strip(800, 600), Viewport { x: 0, y: 0, width: 800, height: 28 });
+        assert_eq!(banner.strip(800, 10).height, 10);
+        assert_eq!(Banner::for_status(&ScreenStatus::Live), None);
+        assert_eq!(Banner::for_status(&ScreenStatus::SecureDesktop { desktop: None }), None);
+    }
+
+    #[test]
+    fn low_bandwidth_banner_names_the_foreground() {
+        let foreground = ForegroundInfo {
+            title: "Quarterly report.xlsx - Excel".into(),
+            process: "EXCEL.EXE".into(),
+            cursor: Some((640, 360)),
+        };
+        let low = Banner::low_bandwidth(&foreground);
+        assert_eq!(low.label, LOW_BANDWIDTH_LABEL);
+        assert_eq!(low.detail.as_deref(), Some(foreground.summary().as_str()));
+        // The detail line makes the strip taller.
+        assert_eq!(low.strip(800, 600), Viewport { x: 0, y: 0, width: 800, height: 48 });
+
+        let blocked = Banner::for_status(&ScreenStatus::InputBlocked {
+            reason: "elevated window".into(),
+        });
+        let both = Banner::combine(Some(low.clone()), blocked.clone()).unwrap();
+        assert_eq!(both.label, format!("{LOW_BANDWIDTH_LABEL} · elevated window"));
+        assert_eq!(both.detail, low.detail);
+        assert_eq!(Banner::combine(None, blocked.clone()), blocked);
+        assert_eq!(Banner::combine(Some(low.clone()), None), Some(low));
+        assert_eq!(Banner::combine(None, None), None);
+    }
+
+    #[test]
+    fn menu_rows_stack_below_the_title() {
+        let items = vec!["a".into(), "b".into()];
+        let menu = MenuPanel { title: "Send keys".into(), items, selected: 1 };
+        let panel = menu.panel(1280, 720);
+        assert_eq!(panel, Viewport { x: 16, y: 16, width: 360, height: 92 });
+        assert_eq!(menu.row(&panel, 0), Viewport { x: 20, y: 52, width: 352, height: 24 });
+        assert_eq!(menu.row(&panel, 1).y, 76);
+        assert_eq!(menu.panel(200, 50).width, 200);
+    }
+
+    #[test]
+    fn lock_panel_grows_for_the_prompt() {
+        let mut lock = LockPanel { title: "Session locked".into(), prompt: None, error: None };
+        assert_eq!(lock.panel(1280, 720), Viewport { x: 360, y: 332, width: 560, height: 56 });
+        lock.prompt = Some("***".into());
+        let panel = lock.panel(1280, 720);
+        assert_eq!(panel.height, 108);
+        assert_eq!(lock.field(&panel), Viewport { x: 380, y: 350, width: 520, height: 26 });
+    }
+
+    #[test]
+    fn dimming_keeps_alpha() {
+        let frame = [255, 90, 3, 255, 0, 0, 0, 0];
+        assert_eq!(dim_frame(&frame), [85, 30, 1, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn stall_hint_sits_in_the_corner_under_any_banner() {
+        let hint = StallHint { label: "Stream stalled (1.2 s)".into(), dim: true };
+        assert_eq!(hint.badge(1280, 720, 0), Viewport { x: 968, y: 12, width: 300, height: 24 });
+        assert_eq!(hint.badge(1280, 720, 48).y, 60);
+        assert_eq!(hint.badge(200, 20, 0), Viewport { x: 0, y: 0, width: 200, height: 20 });
+        let frame = [255, 90, 3, 255];
+        assert_eq!(shade_frame(&frame), [191, 67, 2, 255]);
+    }
+
+    fn block(x: u32, y: u32, width: u32, height: u32) -> DirtyRect {
+        DirtyRect { x, y, width, height }
+    }
+
+    #[test]
+    fn present_falls_back_to_full() {
+        let vp = Viewport::letterbox(1920, 1080, 1920, 1080);
+        let dirty = [block(64, 32, 16, 16)];
+        assert_eq!(Present::plan(None, false, 1920, 1080, &vp), Present::Full);
+        assert_eq!(Present::plan(Some(&dirty), true, 1920, 1080, &vp), Present::Full);
+        let everything = [block(0, 0, 960, 1080), block(960, 0, 960, 1080)];
+        assert_eq!(Present::plan(Some(&everything), false, 1920, 1080, &vp), Present::Full);
+        assert_eq!(Present::plan(Some(&[]), false, 1920, 1080, &vp), Present::Skip);
+        assert_eq!(
+            Present::plan(Some(&[block(5, 5, 0, 9)]), false, 1920, 1080, &vp),
+            Present::Skip
+        );
+    }
+
+    #[test]
+    fn partial_present_covers_the_blocks_and_their_neighbours() {
+        let vp = Viewport::letterbox(1920, 1080, 1920, 1080);
+        let dirty = [block(64, 32, 16, 16), block(96, 48, 16, 16)];
+        assert_eq!(
+            Present::plan(Some(&dirty), false, 1920, 1080, &vp),
+            Present::Partial(Viewport { x: 63, y: 31, width: 50, height: 34 })
+        );
+
+        // 1024×768 pillarboxed into 1920×1080: ×1.40625, offset 240.
+        let vp = Viewport::letterbox(1920, 1080, 1024, 768);
+        let present = Present::plan(Some(&[block(100, 100, 10, 10)]), false, 1024, 768, &vp);
+        assert_eq!(present, Present::Partial(Viewport { x: 379, y: 139, width: 18, height: 18 }));
+        assert_eq!(present.pixels(1920, 1080), 324);
+
+        // Blocks on the edge stay inside the viewport.
+        assert_eq!(
+            Present::plan(Some(&[block(1008, 752, 16, 16)]), false, 1024, 768, &vp),
+            Present::Partial(Viewport { x: 240 + 1416, y: 1056, width: 24, height: 24 })
+        );
+    }
+
+    #[test]
+    fn copy_region_copies_rows_into_place() {
+        // A 2×2 frame at (1, 1) of a 4×3 target; copy its right column.
+        let frame: Vec<u8> = (0..16).collect();
+        let vp = Viewport { x: 1, y: 1, width: 2, height: 2 };
+        let region = Viewport { x: 2, y: 1, width: 1, height: 2 };
+        let mut target = vec![0u8; 4 * 3 * 4];
+        copy_region(&frame, &vp, &region, &mut target, 4);
+        assert_eq!(&target[24..28], &[4, 5, 6, 7]);
+        assert_eq!(&target[40..44], &[12, 13, 14, 15]);
+        assert_eq!(target.iter().filter(|b| **b != 0).count(), 8);
+    }
+
+    #[test]
+    fn upload_stats_average_what_was_copied() {
+        let mut stats = UploadStats::default();
+        assert_eq!(stats.summary(), "0.00 MP/frame, 0% partial");
+        stats.record(&Present::Full, 1920, 1080);
+        stats.record(&Present::Skip, 1920, 1080);
+        for _ in 0..3 {
+            stats.record(
+                &Present::Partial(Viewport { x: 0, y: 0, width: 100, height: 100 }),
+                1920,
+                1080,
+            );
+        }
+        assert_eq!(stats.frames, 4);
+        assert_eq!(stats.per_frame(), (1920 * 1080 + 30_000) / 4);
+        assert_eq!(stats.summary(), "0.53 MP/frame, 75% partial");
+    }
+
+    #[test]
+    fn to_remote_clamps_bars() {
+        let vp = Viewport::letterbox(1920, 1080, 1024, 768);
+        assert_eq!(vp.to_remote(0, 0, 1024, 768), (0, 0));
+        assert_eq!(vp.to_remote(240 + 720, 540, 1024, 768), (512, 384));
+        let (x, _) = vp.to_remote(1919, 540, 1024, 768);
+        assert_eq!(x, 1023);
+    }
+}
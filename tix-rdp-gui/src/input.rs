@@ -2,27 +2,34 @@
 //!
 //! Translates [`WindowEvent`]s from the Win32 message loop into
 //! TIX protocol [`MouseEvent`] / [`KeyEvent`] types that can be
-//! serialised and sent to the slave.
+//! serialised and sent to the slave. [`ViewMode`] tracks whether the
+//! session is view-only; the slave enforces it, the viewer just stops
+//! sending input so nothing gets refused. [`InputRoute`] decides which
+//! events may take the UDP pointer path.
 
 use tix_core::protocol::screen::{
-    KeyAction, KeyEvent, MouseButton, MouseEvent, MouseEventKind,
+    InputRejection, KeyAction, KeyEvent, MouseButton, MouseEvent, MouseEventKind,
+    ScreenModeResponse,
 };
+use tix_core::{Command, Packet};
 
+use crate::display::Viewport;
 use crate::window::{MouseBtn, WindowEvent};
 
 /// Convert a window event to a protocol input event (if applicable).
+///
+/// `viewport` is where the remote image is drawn; pointer positions are
+/// mapped through it so the letterbox bars do not skew coordinates.
 pub fn translate_event(
     event: &WindowEvent,
-    window_width: u32,
-    window_height: u32,
+    viewport: &Viewport,
     remote_width: u32,
     remote_height: u32,
 ) -> Option<InputAction> {
     match event {
         WindowEvent::MouseMove(x, y) => {
             // Scale from window coordinates to remote coordinates.
-            let rx = (*x as f64 / window_width as f64 * remote_width as f64) as i32;
-            let ry = (*y as f64 / window_height as f64 * remote_height as f64) as i32;
+            let (rx, ry) = viewport.to_remote(*x, *y, remote_width, remote_height);
             Some(InputAction::Mouse(MouseEvent {
                 x: rx,
                 y: ry,
@@ -72,12 +79,283 @@ pub fn translate_event(
                 modifiers: 0,
             }))
         }
-        WindowEvent::Close | WindowEvent::Resize(..) => None,
+        WindowEvent::Close
+        | WindowEvent::Resize(..)
+        | WindowEvent::Char(_)
+        | WindowEvent::FilesDropped(..) => None,
     }
 }
 
 /// Tagged union of input actions to send to the slave.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InputAction {
     Mouse(MouseEvent),
     Key(KeyEvent),
 }
+
+impl InputAction {
+    /// Move a pointer position from the monitor a window shows onto the
+    /// slave's virtual desktop, where that monitor's top-left corner is
+    /// `origin`. Buttons, wheel and keys carry no position.
+    pub fn on_monitor(self, origin: (i32, i32)) -> Self {
+        match self {
+            InputAction::Mouse(mut me) if matches!(me.kind, MouseEventKind::Move) => {
+                me.x += origin.0;
+                me.y += origin.1;
+                InputAction::Mouse(me)
+            }
+            other => other,
+        }
+    }
+}
+
+// ── Routing ──────────────────────────────────────────────────────
+
+/// Where one input event goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Routed {
+    /// A pointer datagram on the screen socket.
+    Pointer(i32, i32),
+    /// The control connection, in order with everything else on it.
+    Control(InputAction),
+}
+
+/// Splits one session's input between the control connection and the
+/// UDP pointer path.
+///
+/// Only moves go over UDP. Before any other mouse event the last
+/// position is repeated on the control connection, so a click lands
+/// where the pointer was even if its datagram was lost or is still in
+/// flight; the control connection keeps everything else in order.
+#[derive(Debug, Clone, Copy)]
+pub struct InputRoute {
+    udp: bool,
+    /// Position sent over UDP but not yet over the control connection.
+    unsynced: Option<(i32, i32)>,
+}
+
+impl InputRoute {
+    /// A route for a session that takes pointer moves over UDP
+    /// (`udp`), or one that keeps everything on the control connection.
+    pub fn new(udp: bool) -> Self {
+        Self {
+            udp,
+            unsynced: None,
+        }
+    }
+
+    /// The sends `action` turns into, in order.
+    pub fn route(&mut self, action: InputAction) -> Vec<Routed> {
+        match action {
+            InputAction::Mouse(me) if self.udp && me.kind == MouseEventKind::Move => {
+                self.unsynced = Some((me.x, me.y));
+                vec![Routed::Pointer(me.x, me.y)]
+            }
+            InputAction::Mouse(_) => match self.unsynced.take() {
+                Some((x, y)) => vec![
+                    Routed::Control(InputAction::Mouse(MouseEvent::move_to(x, y))),
+                    Routed::Control(action),
+                ],
+                None => vec![Routed::Control(action)],
+            },
+            InputAction::Key(_) => vec![Routed::Control(action)],
+        }
+    }
+}
+
+// ── View-only mode ───────────────────────────────────────────────
+
+/// Virtual-key code of the key that toggles view-only mode (F8). It is
+/// never forwarded to the slave.
+pub const MODE_TOGGLE_VK: u16 = 0x77;
+
+/// Whether `event` is the mode toggle key, pressed or released.
+pub fn is_mode_key(event: &WindowEvent) -> bool {
+    matches!(event, WindowEvent::Key(MODE_TOGGLE_VK, _, _))
+}
+
+/// Virtual-key code of the key that switches a window between frames
+/// and low-bandwidth thumbnails (F7). It is never forwarded either.
+pub const BANDWIDTH_TOGGLE_VK: u16 = 0x76;
+
+/// Whether `event` is the low-bandwidth toggle key, pressed or released.
+pub fn is_bandwidth_key(event: &WindowEvent) -> bool {
+    matches!(event, WindowEvent::Key(BANDWIDTH_TOGGLE_VK, _, _))
+}
+
+/// Control vs view-only, as the viewer last asked for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewMode {
+    view_only: bool,
+}
+
+impl ViewMode {
+    pub fn new(view_only: bool) -> Self {
+        Self { view_only }
+    }
+
+    pub fn is_view_only(&self) -> bool {
+        self.view_only
+    }
+
+    /// Flip the mode. Returns `true` if the session should now accept
+    /// input (the value to send in a `ScreenModeRequest`).
+    pub fn toggle(&mut self) -> bool {
+        self.view_only = !self.view_only;
+        !self.view_only
+    }
+
+    /// Adopt the mode the slave reports.
+    pub fn confirm(&mut self, control: bool) {
+        self.view_only = !control;
+    }
+
+    /// Appended to the window title.
+    pub fn title_suffix(&self) -> &'static str {
+        if self.view_only { " [VIEW ONLY]" } else { "" }
+    }
+}
+
+/// Interpret a slave packet about the session mode: a `ScreenMode`
+/// answer or a refused input event. `None` for anything else.
+pub fn mode_feedback(packet: &Packet) -> Option<Result<bool, String>> {
+    match packet.command().ok()? {
+        Command::ScreenMode => Some(match ScreenModeResponse::from_bytes(packet.payload()) {
+            Ok(ScreenModeResponse::Applied { control }) => Ok(control),
+            Ok(ScreenModeResponse::NoSession) => Err("no screen session to switch".into()),
+            Err(e) => Err(format!("bad ScreenMode response: {e}")),
+        }),
+        Command::InputMouse | Command::InputKeyboard => {
+            Some(match InputRejection::from_bytes(packet.payload()) {
+                Ok(InputRejection::ViewOnly) => Ok(false),
+                Err(e) => Err(format!("bad input rejection: {e}")),
+            })
+        }
+        _ => None,
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggle_round_trip() {
+        let mut mode = ViewMode::new(true);
+        assert_eq!(mode.title_suffix(), " [VIEW ONLY]");
+
+        // Ask for control, the slave confirms it.
+        let control = mode.toggle();
+        assert!(control);
+        let reply = ScreenModeResponse::Applied { control }.into_packet(4).unwrap();
+        mode.confirm(mode_feedback(&reply).unwrap().unwrap());
+        assert!(!mode.is_view_only());
+        assert_eq!(mode.title_suffix(), "");
+
+        // A refused input means the slave still thinks we are view-only.
+        let refused = InputRejection::ViewOnly
+            .into_packet(0, Command::InputKeyboard)
+            .unwrap();
+        mode.confirm(mode_feedback(&refused).unwrap().unwrap());
+        assert!(mode.is_view_only());
+
+        let none = ScreenModeResponse::NoSession.into_packet(5).unwrap();
+        assert!(mode_feedback(&none).unwrap().is_err());
+    }
+
+    #[test]
+    fn pointer_moves_land_on_the_right_monitor() {
+        let viewport = Viewport::letterbox(1920, 1080, 1920, 1080);
+        let moved = translate_event(&WindowEvent::MouseMove(100, 50), &viewport, 1920, 1080)
+            .unwrap()
+            .on_monitor((-1920, 0));
+        let InputAction::Mouse(me) = moved else { panic!("not a mouse event") };
+        assert_eq!((me.x, me.y), (-1820, 50));
+
+        let click = translate_event(
+            &WindowEvent::MouseButton(MouseBtn::Left, true),
+            &viewport,
+            1920,
+            1080,
+        )
+        .unwrap()
+        .on_monitor((1920, 0));
+        let InputAction::Mouse(me) = click else { panic!("not a mouse event") };
+        assert_eq!((me.x, me.y), (0, 0));
+    }
+
+    #[test]
+    fn only_moves_take_the_pointer_path() {
+        let mut route = InputRoute::new(true);
+        let press = InputAction::Mouse(MouseEvent::press(0, 0, MouseButton::Left));
+        let key = InputAction::Key(KeyEvent::press(0x41, 0x1E, 0));
+
+        assert_eq!(
+            route.route(InputAction::Mouse(MouseEvent::move_to(5, 6))),
+            [Routed::Pointer(5, 6)]
+        );
+        assert_eq!(route.route(key), [Routed::Control(key)]);
+
+        let mut tcp = InputRoute::new(false);
+        let moved = InputAction::Mouse(MouseEvent::move_to(5, 6));
+        assert_eq!(tcp.route(moved), [Routed::Control(moved)]);
+        assert_eq!(tcp.route(press), [Routed::Control(press)]);
+    }
+
+    #[test]
+    fn buttons_keep_their_order_and_land_at_the_last_position() {
+        use MouseButton::{Left, Right};
+        let events = [
+            InputAction::Mouse(MouseEvent::move_to(1, 1)),
+            InputAction::Mouse(MouseEvent::move_to(2, 2)),
+            InputAction::Mouse(MouseEvent::press(0, 0, Left)),
+            InputAction::Key(KeyEvent::press(0x10, 0x2A, 0)),
+            InputAction::Mouse(MouseEvent::move_to(3, 3)),
+            InputAction::Mouse(MouseEvent::release(0, 0, Left)),
+            InputAction::Mouse(MouseEvent::press(0, 0, Right)),
+            InputAction::Mouse(MouseEvent::scroll(0, 0, -120)),
+            InputAction::Key(KeyEvent::release(0x10, 0x2A, 0)),
+            InputAction::Mouse(MouseEvent::release(0, 0, Right)),
+        ];
+        let mut route = InputRoute::new(true);
+        let control: Vec<InputAction> = events
+            .iter()
+            .flat_map(|&e| route.route(e))
+            .filter_map(|r| match r {
+                Routed::Control(action) => Some(action),
+                Routed::Pointer(..) => None,
+            })
+            .collect();
+
+        // Everything but the moves arrives in the order it happened.
+        let not_moves: Vec<InputAction> = control
+            .iter()
+            .copied()
+            .filter(|a| !matches!(a, InputAction::Mouse(me) if me.kind == MouseEventKind::Move))
+            .collect();
+        let expected: Vec<InputAction> = events
+            .iter()
+            .copied()
+            .filter(|a| !matches!(a, InputAction::Mouse(me) if me.kind == MouseEventKind::Move))
+            .collect();
+        assert_eq!(not_moves, expected);
+
+        // Each button that followed a UDP move is preceded by that move.
+        assert_eq!(control[0], InputAction::Mouse(MouseEvent::move_to(2, 2)));
+        assert_eq!(control[1], events[2]);
+        assert_eq!(control[3], InputAction::Mouse(MouseEvent::move_to(3, 3)));
+        assert_eq!(control[4], events[5]);
+        assert_eq!(control.len(), expected.len() + 2);
+    }
+
+    #[test]
+    fn toggle_key_is_recognised_both_ways() {
+        assert!(is_mode_key(&WindowEvent::Key(MODE_TOGGLE_VK, 0x42, true)));
+        assert!(is_mode_key(&WindowEvent::Key(MODE_TOGGLE_VK, 0x42, false)));
+        assert!(!is_mode_key(&WindowEvent::Key(0x41, 0x1E, true)));
+        assert!(is_bandwidth_key(&WindowEvent::Key(BANDWIDTH_TOGGLE_VK, 0x41, true)));
+        assert!(!is_bandwidth_key(&WindowEvent::Key(MODE_TOGGLE_VK, 0x42, true)));
+    }
+}
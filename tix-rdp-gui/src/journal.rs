@@ -0,0 +1,443 @@
+//! Input journal: a record of every input event sent to the slave.
+//!
+//! With `journal` set under `[input]`, each mouse and keyboard event the
+//! viewer actually sends is appended to that file as one JSON line:
+//!
+//! ```text
+//! {"t_ms":1520,"event":"mouse","kind":"Move","button":"None","x":2945,"y":410,"scroll_delta":0}
+//! {"t_ms":1604,"event":"key","action":"Press","virtual_key":65,"scan_code":30,"modifiers":0}
+//! {"t_ms":2210,"event":"private","on":true}
+//! {"t_ms":2395,"event":"redacted_key","action":"Press"}
+//! ```
+//!
+//! `t_ms` counts from the start of the session on a monotonic clock, and
+//! pointer positions are on the slave's virtual desktop. F9 toggles
+//! private mode for typing passwords: keys are still sent, but the
+//! journal only records that a key went down or up. Lines are written by
+//! a task of their own, so a slow disk never holds up input, and the
+//! file is flushed when the session ends.
+//!
+//! `tix-rdp-gui --dump-journal <file>` prints a journal as a timeline
+//! followed by totals (see [`dump`]).
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tix_core::protocol::screen::{KeyAction, MouseButton, MouseEventKind};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::input::InputAction;
+use crate::window::WindowEvent;
+
+/// Virtual-key code of the key that toggles private mode (F9). While a
+/// journal is open it is not forwarded to the slave.
+pub const PRIVATE_TOGGLE_VK: u16 = 0x78;
+
+/// Whether `event` is the private mode key, pressed or released.
+pub fn is_private_key(event: &WindowEvent) -> bool {
+    matches!(event, WindowEvent::Key(PRIVATE_TOGGLE_VK, _, _))
+}
+
+// ── Entries ──────────────────────────────────────────────────────
+
+/// One line of the journal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Milliseconds since the session started.
+    pub t_ms: u64,
+    #[serde(flatten)]
+    pub event: JournalEvent,
+}
+
+/// What happened.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JournalEvent {
+    /// A mouse event as sent. Only moves carry a position.
+    Mouse {
+        kind: MouseEventKind,
+        button: MouseButton,
+        x: i32,
+        y: i32,
+        scroll_delta: i16,
+    },
+    /// A key event as sent.
+    Key {
+        action: KeyAction,
+        virtual_key: u16,
+        scan_code: u16,
+        modifiers: u8,
+    },
+    /// A key event sent in private mode; which key is not recorded.
+    RedactedKey { action: KeyAction },
+    /// Private mode switched on or off.
+    Private { on: bool },
+}
+
+impl JournalEvent {
+    /// The entry for `action`, with key details left out when `private`.
+    pub fn from_action(action: &InputAction, private: bool) -> Self {
+        match action {
+            InputAction::Mouse(me) => Self::Mouse {
+                kind: me.kind,
+                button: me.button,
+                x: me.x,
+                y: me.y,
+                scroll_delta: me.scroll_delta,
+            },
+            InputAction::Key(ke) if private => Self::RedactedKey { action: ke.action },
+            InputAction::Key(ke) => Self::Key {
+                action: ke.action,
+                virtual_key: ke.virtual_key,
+                scan_code: ke.scan_code,
+                modifiers: ke.modifiers,
+            },
+        }
+    }
+}
+
+// ── Writer ───────────────────────────────────────────────────────
+
+/// An open journal. Recording never blocks: entries go down a channel
+/// to a writer task.
+pub struct InputJournal {
+    path: PathBuf,
+    started: Instant,
+    private: bool,
+    tx: mpsc::UnboundedSender<JournalEntry>,
+    writer: JoinHandle<Result<(), String>>,
+}
+
+impl InputJournal {
+    /// Append to the journal at `path`, creating it if needed.
+    pub async fn open(path: &Path) -> Result<Self, String> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        Ok(Self {
+            path: path.to_path_buf(),
+            started: Instant::now(),
+            private: false,
+            tx,
+            writer: tokio::spawn(write_entries(BufWriter::new(file), rx)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private
+    }
+
+    /// Record an input event that was sent.
+    pub fn record(&self, action: &InputAction) {
+        self.push(JournalEvent::from_action(action, self.private));
+    }
+
+    /// Flip private mode. Returns whether it is now on.
+    pub fn toggle_private(&mut self) -> bool {
+        self.private = !self.private;
+        self.push(JournalEvent::Private { on: self.private });
+        self.private
+    }
+
+    /// Appended to the window title.
+    pub fn title_suffix(&self) -> &'static str {
+        if self.private { " [PRIVATE]" } else { "" }
+    }
+
+    fn push(&self, event: JournalEvent) {
+        let t_ms = self.started.elapsed().as_millis() as u64;
+        // The writer only stops early on a write error, which `close`
+        // reports.
+        let _ = self.tx.send(JournalEntry { t_ms, event });
+    }
+
+    /// Write out what is still queued and flush the file.
+    pub async fn close(self) -> Result<(), String> {
+        drop(self.tx);
+        match self.writer.await {
+            Ok(result) => result.map_err(|e| format!("{}: {e}", self.path.display())),
+            Err(e) => Err(format!("journal writer failed: {e}")),
+        }
+    }
+}
+
+/// Write entries as they come, flushing whenever the queue runs dry.
+async fn write_entries<W: tokio::io::AsyncWrite + Unpin>(
+    mut out: BufWriter<W>,
+    mut rx: mpsc::UnboundedReceiver<JournalEntry>,
+) -> Result<(), String> {
+    while let Some(entry) = rx.recv().await {
+        let mut line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+        out.write_all(line.as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+        if rx.is_empty() {
+            out.flush().await.map_err(|e| e.to_string())?;
+        }
+    }
+    out.flush().await.map_err(|e| e.to_string())
+}
+
+// ── Reading ──────────────────────────────────────────────────────
+
+/// Parse a journal. Blank lines are skipped; anything else that is not
+/// an entry is an error naming its line.
+pub fn parse(text: &str) -> Result<Vec<JournalEntry>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {e}", i + 1)))
+        .collect()
+}
+
+/// Totals over a journal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JournalStats {
+    /// Key presses, private ones included.
+    pub keys: u64,
+    /// Key presses made in private mode.
+    pub redacted_keys: u64,
+    /// Mouse button presses.
+    pub clicks: u64,
+    pub scrolls: u64,
+    pub moves: u64,
+    /// Time from the first entry to the last.
+    pub span: Duration,
+}
+
+impl JournalStats {
+    pub fn of(entries: &[JournalEntry]) -> Self {
+        let mut stats = Self::default();
+        for entry in entries {
+            match &entry.event {
+                JournalEvent::Key {
+                    action: KeyAction::Press,
+                    ..
+                } => stats.keys += 1,
+                JournalEvent::RedactedKey {
+                    action: KeyAction::Press,
+                } => {
+                    stats.keys += 1;
+                    stats.redacted_keys += 1;
+                }
+                JournalEvent::Mouse { kind, .. } => match kind {
+                    MouseEventKind::Press | MouseEventKind::DoubleClick => stats.clicks += 1,
+                    MouseEventKind::Scroll => stats.scrolls += 1,
+                    MouseEventKind::Move => stats.moves += 1,
+                    MouseEventKind::Release => {}
+                },
+                _ => {}
+            }
+        }
+        if let (Some(first), Some(last)) = (entries.first(), entries.last()) {
+            stats.span = Duration::from_millis(last.t_ms.saturating_sub(first.t_ms));
+        }
+        stats
+    }
+}
+
+/// One timeline line for `entry`.
+pub fn format_entry(entry: &JournalEntry) -> String {
+    let what = match &entry.event {
+        JournalEvent::Mouse {
+            kind: MouseEventKind::Move,
+            x,
+            y,
+            ..
+        } => format!("move    to ({x}, {y})"),
+        JournalEvent::Mouse {
+            kind: MouseEventKind::Scroll,
+            scroll_delta,
+            ..
+        } => format!("scroll  {scroll_delta:+}"),
+        JournalEvent::Mouse { kind, button, .. } => {
+            format!(
+                "{:<7} {button:?} button",
+                format!("{kind:?}").to_lowercase()
+            )
+        }
+        JournalEvent::Key {
+            action,
+            virtual_key,
+            scan_code,
+            modifiers,
+        } => {
+            let mut text = format!(
+                "{:<7} key vk 0x{virtual_key:02X} scan 0x{scan_code:02X}",
+                format!("{action:?}").to_lowercase()
+            );
+            if *modifiers != 0 {
+                text.push_str(&format!(" mods 0x{modifiers:02X}"));
+            }
+            text
+        }
+        JournalEvent::RedactedKey { action } => {
+            format!("{:<7} key [redacted]", format!("{action:?}").to_lowercase())
+        }
+        JournalEvent::Private { on } => {
+            format!("private mode {}", if *on { "on" } else { "off" })
+        }
+    };
+    format!("{:>10.3}s  {what}", entry.t_ms as f64 / 1000.0)
+}
+
+/// The `--dump-journal` report: a timeline, then totals.
+pub fn dump(entries: &[JournalEntry]) -> String {
+    let mut out: String = entries
+        .iter()
+        .map(|entry| format_entry(entry) + "\n")
+        .collect();
+    let stats = JournalStats::of(entries);
+    out.push_str(&format!(
+        "\n{} event(s) over {:.1}s\n",
+        entries.len(),
+        stats.span.as_secs_f64()
+    ));
+    out.push_str(&format!(
+        "keys typed: {} ({} redacted)\n",
+        stats.keys, stats.redacted_keys
+    ));
+    out.push_str(&format!(
+        "clicks: {}, scrolls: {}, pointer moves: {}\n",
+        stats.clicks, stats.scrolls, stats.moves
+    ));
+    out
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::protocol::screen::{KeyEvent, MouseEvent};
+
+    fn key(vk: u16, action: KeyAction) -> InputAction {
+        InputAction::Key(KeyEvent {
+            virtual_key: vk,
+            scan_code: vk + 1,
+            action,
+            modifiers: 0,
+        })
+    }
+
+    #[test]
+    fn entries_serialize_as_flat_json() {
+        let entry = JournalEntry {
+            t_ms: 1604,
+            event: JournalEvent::from_action(&key(0x41, KeyAction::Press), false),
+        };
+        let line = serde_json::to_string(&entry).unwrap();
+        assert_eq!(
+            line,
+            r#"{"t_ms":1604,"event":"key","action":"Press","virtual_key":65,"scan_code":66,"modifiers":0}"#
+        );
+        assert_eq!(parse(&line).unwrap(), vec![entry]);
+
+        let moved =
+            JournalEvent::from_action(&InputAction::Mouse(MouseEvent::move_to(-5, 9)), true);
+        let line = serde_json::to_string(&JournalEntry {
+            t_ms: 3,
+            event: moved,
+        })
+        .unwrap();
+        assert!(line.contains(r#""event":"mouse","kind":"Move""#), "{line}");
+        assert!(line.contains(r#""x":-5,"y":9"#), "{line}");
+
+        let err =
+            parse("\n{\"t_ms\":1,\"event\":\"private\",\"on\":true}\nnot json\n").unwrap_err();
+        assert!(err.starts_with("line 3:"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn private_mode_redacts_keys_mid_stream() {
+        let path = std::env::temp_dir().join(format!("tix-journal-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut journal = InputJournal::open(&path).await.unwrap();
+        journal.record(&key(0x55, KeyAction::Press));
+        assert!(journal.toggle_private());
+        assert_eq!(journal.title_suffix(), " [PRIVATE]");
+        journal.record(&key(0x50, KeyAction::Press));
+        journal.record(&key(0x50, KeyAction::Release));
+        assert!(!journal.toggle_private());
+        journal.record(&key(0x0D, KeyAction::Press));
+        journal.close().await.unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("\"virtual_key\":80"), "{text}");
+        let events: Vec<JournalEvent> =
+            parse(&text).unwrap().into_iter().map(|e| e.event).collect();
+        assert_eq!(
+            events,
+            vec![
+                JournalEvent::from_action(&key(0x55, KeyAction::Press), false),
+                JournalEvent::Private { on: true },
+                JournalEvent::RedactedKey {
+                    action: KeyAction::Press
+                },
+                JournalEvent::RedactedKey {
+                    action: KeyAction::Release
+                },
+                JournalEvent::Private { on: false },
+                JournalEvent::from_action(&key(0x0D, KeyAction::Press), false),
+            ]
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn dump_lists_the_timeline_and_totals() {
+        let entries = vec![
+            JournalEntry {
+                t_ms: 1520,
+                event: JournalEvent::from_action(
+                    &InputAction::Mouse(MouseEvent::move_to(2945, 410)),
+                    false,
+                ),
+            },
+            JournalEntry {
+                t_ms: 1550,
+                event: JournalEvent::from_action(
+                    &InputAction::Mouse(MouseEvent::press(0, 0, MouseButton::Left)),
+                    false,
+                ),
+            },
+            JournalEntry {
+                t_ms: 1604,
+                event: JournalEvent::from_action(&key(0x41, KeyAction::Press), false),
+            },
+            JournalEntry {
+                t_ms: 2395,
+                event: JournalEvent::from_action(&key(0x41, KeyAction::Press), true),
+            },
+            JournalEntry {
+                t_ms: 4020,
+                event: JournalEvent::from_action(
+                    &InputAction::Mouse(MouseEvent::scroll(0, 0, -120)),
+                    false,
+                ),
+            },
+        ];
+        let text = dump(&entries);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "     1.520s  move    to (2945, 410)");
+        assert_eq!(lines[1], "     1.550s  press   Left button");
+        assert_eq!(lines[2], "     1.604s  press   key vk 0x41 scan 0x42");
+        assert_eq!(lines[3], "     2.395s  press   key [redacted]");
+        assert_eq!(lines[4], "     4.020s  scroll  -120");
+        assert_eq!(lines[6], "5 event(s) over 2.5s");
+        assert_eq!(lines[7], "keys typed: 2 (1 redacted)");
+        assert_eq!(lines[8], "clicks: 1, scrolls: 1, pointer moves: 1");
+    }
+}
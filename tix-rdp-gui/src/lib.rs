@@ -3,10 +3,34 @@
 //! Runs on the **master** machine. Connects to `tix-rdp-slave`,
 //! receives screen frames over UDP, renders them into a native
 //! Win32 window, and forwards local mouse/keyboard input back
-//! to the slave via TCP.
+//! to the slave via TCP. Files dropped onto the window are uploaded
+//! to the slave, the input sent can be journalled to a file, and Ctrl+S
+//! saves a screenshot of the remote screen locally. F11 opens a menu of
+//! key combinations the local machine would otherwise keep, such as
+//! Ctrl+Alt+Del, and F10 a settings overlay that changes and saves the
+//! runtime settings without editing the TOML. A session left idle
+//! locks itself until Ctrl+Alt+U. If the slave cannot be reached at
+//! startup, a connect dialog in the window lets the user fix the address
+//! and retry. Frames are scaled to the window, and optionally grayed or
+//! warmed, on the CPU before they are drawn. Frame dictionaries the
+//! slave sends are kept so later sessions only need to name them. A
+//! stream that stalls says so in a corner and holds input back until
+//! frames come again.
 
 pub mod config;
 pub mod connection;
+pub mod dictionary;
 pub mod display;
 pub mod input;
+pub mod journal;
+pub mod lock;
+pub mod pacing;
+pub mod postprocess;
+pub mod settings;
+pub mod snapshot;
+pub mod special;
+pub mod stall;
+pub mod taskbar;
+pub mod upload;
 pub mod window;
+pub mod wizard;
@@ -0,0 +1,336 @@
+//! Locking an unattended session.
+//!
+//! A viewer left open in control of a slave lets anyone at the master's
+//! desk drive the remote machine. After `idle_minutes` without local
+//! input the session locks: the viewer goes view-only, the frame is
+//! dimmed and a panel asks for Ctrl+Alt+U. With `require_token` set,
+//! Ctrl+Alt+U opens a prompt for it instead, and control comes back only
+//! once it is typed correctly.
+//!
+//! Only mouse and keyboard input in the window counts as activity;
+//! bringing the window back to the front does not unlock it nor keep it
+//! unlocked.
+
+use std::time::{Duration, Instant};
+
+use crate::display::LockPanel;
+use crate::window::WindowEvent;
+use crate::wizard::TextField;
+
+/// `U`, which with Ctrl and Alt held unlocks the session.
+pub const UNLOCK_VK: u16 = 0x55;
+
+const ENTER_VK: u16 = 0x0D;
+const ESCAPE_VK: u16 = 0x1B;
+/// `VK_CONTROL` and its left / right variants.
+const CONTROL_VKS: [u16; 3] = [0x11, 0xA2, 0xA3];
+/// `VK_MENU` (Alt) and its left / right variants.
+const ALT_VKS: [u16; 3] = [0x12, 0xA4, 0xA5];
+
+/// What to do with a window event once the lock has seen it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockUse {
+    /// Not locked, or not input: handle it as usual.
+    Forward,
+    /// Taken by the lock: drop it.
+    Swallow,
+    /// The session was just unlocked; drop the event and hand control
+    /// back.
+    Unlock,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Unlocked,
+    Locked,
+    /// Waiting for the token; `error` says why the last try failed.
+    Prompt {
+        field: TextField,
+        error: Option<String>,
+    },
+}
+
+/// The lock of one viewer, shared by all of its windows.
+#[derive(Debug)]
+pub struct SessionLock {
+    /// `None` never locks on its own.
+    idle: Option<Duration>,
+    /// Typed to unlock; `None` unlocks on the hotkey alone.
+    token: Option<String>,
+    last_input: Instant,
+    state: State,
+    ctrl: bool,
+    alt: bool,
+    /// A key the lock used whose release must not reach the slave.
+    swallow_release: Option<u16>,
+}
+
+impl SessionLock {
+    /// A lock that engages after `idle` without input (never with
+    /// `None`) and, given a `token`, asks for it before unlocking.
+    pub fn new(idle: Option<Duration>, token: Option<String>, now: Instant) -> Self {
+        Self {
+            idle,
+            token: token.filter(|t| !t.is_empty()),
+            last_input: now,
+            state: State::Unlocked,
+            ctrl: false,
+            alt: false,
+            swallow_release: None,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.state != State::Unlocked
+    }
+
+    /// Lock now, whatever the idle timer says.
+    pub fn lock(&mut self) {
+        self.state = State::Locked;
+    }
+
+    /// Lock if the session has been idle long enough. Returns `true`
+    /// when this call locked it.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        let Some(idle) = self.idle else {
+            return false;
+        };
+        if self.is_locked() || now.saturating_duration_since(self.last_input) < idle {
+            return false;
+        }
+        self.lock();
+        true
+    }
+
+    /// Watch one window event. Input restarts the idle timer while
+    /// unlocked; while locked it goes to the hotkey and the token prompt
+    /// and nowhere else.
+    pub fn observe(&mut self, event: &WindowEvent, now: Instant) -> LockUse {
+        if let WindowEvent::Key(vk, _, pressed) = *event {
+            if CONTROL_VKS.contains(&vk) {
+                self.ctrl = pressed;
+            }
+            if ALT_VKS.contains(&vk) {
+                self.alt = pressed;
+            }
+            if !pressed && self.swallow_release == Some(vk) {
+                self.swallow_release = None;
+                return LockUse::Swallow;
+            }
+        }
+        let input = !matches!(event, WindowEvent::Close | WindowEvent::Resize(..));
+        if !self.is_locked() {
+            if input {
+                self.last_input = now;
+            }
+            return LockUse::Forward;
+        }
+        if !input {
+            return LockUse::Forward;
+        }
+
+        match (&mut self.state, event) {
+            (State::Locked, WindowEvent::Key(UNLOCK_VK, _, true)) if self.ctrl && self.alt => {
+                self.swallow_release = Some(UNLOCK_VK);
+                if self.token.is_none() {
+                    return self.unlock(now);
+                }
+                self.state = State::Prompt {
+                    field: TextField::new(""),
+                    error: None,
+                };
+            }
+            (State::Prompt { .. }, WindowEvent::Key(ESCAPE_VK, _, true)) => {
+                self.state = State::Locked;
+            }
+            (State::Prompt { field, error }, WindowEvent::Key(ENTER_VK, _, true)) => {
+                if self.token.as_deref() == Some(field.text()) {
+                    return self.unlock(now);
+                }
+                *field = TextField::new("");
+                *error = Some("Wrong token".into());
+            }
+            (State::Prompt { field, .. }, WindowEvent::Key(vk, _, true)) => {
+                field.handle_key(*vk);
+            }
+            // Enter and Backspace also arrive as control characters,
+            // which the field ignores.
+            (State::Prompt { field, .. }, WindowEvent::Char(c)) => field.insert(*c),
+            _ => {}
+        }
+        LockUse::Swallow
+    }
+
+    /// What to draw over the dimmed frame, or `None` while unlocked.
+    pub fn panel(&self) -> Option<LockPanel> {
+        let (prompt, error) = match &self.state {
+            State::Unlocked => return None,
+            State::Locked => (None, None),
+            State::Prompt { field, error } => (Some("*".repeat(field.text().len())), error.clone()),
+        };
+        let hint = if prompt.is_some() {
+            "Type the token and press Enter (Esc to cancel)"
+        } else {
+            "Session locked — press Ctrl+Alt+U to resume control"
+        };
+        Some(LockPanel {
+            title: hint.into(),
+            prompt,
+            error,
+        })
+    }
+
+    /// Appended to the window title.
+    pub fn title_suffix(&self) -> &'static str {
+        if self.is_locked() { " [LOCKED]" } else { "" }
+    }
+
+    fn unlock(&mut self, now: Instant) -> LockUse {
+        self.state = State::Unlocked;
+        self.last_input = now;
+        LockUse::Unlock
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDLE: Duration = Duration::from_secs(600);
+    const BACK_VK: u16 = 0x08;
+
+    fn key(vk: u16, pressed: bool) -> WindowEvent {
+        WindowEvent::Key(vk, 0, pressed)
+    }
+
+    /// Press Ctrl+Alt+U and let go of it all.
+    fn hotkey(lock: &mut SessionLock, now: Instant) -> Vec<LockUse> {
+        [
+            key(0xA2, true),
+            key(0x12, true),
+            key(UNLOCK_VK, true),
+            key(UNLOCK_VK, false),
+            key(0x12, false),
+            key(0xA2, false),
+        ]
+        .iter()
+        .map(|ev| lock.observe(ev, now))
+        .collect()
+    }
+
+    fn type_text(lock: &mut SessionLock, text: &str, now: Instant) {
+        for c in text.chars() {
+            assert_eq!(lock.observe(&WindowEvent::Char(c), now), LockUse::Swallow);
+        }
+    }
+
+    #[test]
+    fn input_restarts_the_idle_timer() {
+        let t0 = Instant::now();
+        let mut lock = SessionLock::new(Some(IDLE), None, t0);
+        assert!(!lock.tick(t0 + IDLE - Duration::from_secs(1)));
+
+        let moved = t0 + Duration::from_secs(300);
+        let event = WindowEvent::MouseMove(10, 10);
+        assert_eq!(lock.observe(&event, moved), LockUse::Forward);
+        assert!(!lock.tick(t0 + IDLE));
+        // Resizing the window is not activity.
+        lock.observe(&WindowEvent::Resize(800, 600), moved + IDLE / 2);
+        assert!(lock.tick(moved + IDLE));
+        assert!(lock.is_locked());
+        assert_eq!(lock.title_suffix(), " [LOCKED]");
+        // Only once.
+        assert!(!lock.tick(moved + IDLE * 2));
+    }
+
+    #[test]
+    fn never_locks_without_an_idle_period() {
+        let t0 = Instant::now();
+        let mut lock = SessionLock::new(None, None, t0);
+        assert!(!lock.tick(t0 + Duration::from_secs(86_400)));
+        assert!(lock.panel().is_none());
+    }
+
+    #[test]
+    fn locked_input_goes_nowhere_but_the_hotkey_unlocks() {
+        let t0 = Instant::now();
+        let mut lock = SessionLock::new(Some(IDLE), None, t0);
+        lock.lock();
+        let panel = lock.panel().unwrap();
+        assert!(panel.title.contains("Ctrl+Alt+U"));
+        assert_eq!(panel.prompt, None);
+
+        for ev in [
+            key(0x41, true),
+            WindowEvent::MouseButton(crate::window::MouseBtn::Left, true),
+            WindowEvent::FilesDropped(Vec::new(), 0, 0),
+            key(UNLOCK_VK, true),
+        ] {
+            assert_eq!(lock.observe(&ev, t0), LockUse::Swallow);
+        }
+        assert_eq!(lock.observe(&WindowEvent::Close, t0), LockUse::Forward);
+        assert!(lock.is_locked());
+
+        let later = t0 + IDLE * 3;
+        let uses = hotkey(&mut lock, later);
+        assert_eq!(uses[2], LockUse::Unlock);
+        // The U release stays local; the modifiers go through once the
+        // session is back.
+        assert_eq!(uses[3], LockUse::Swallow);
+        assert_eq!(uses[4..], [LockUse::Forward, LockUse::Forward]);
+        assert!(!lock.is_locked());
+        // Unlocking counts as activity.
+        assert!(!lock.tick(later + IDLE / 2));
+    }
+
+    #[test]
+    fn the_token_must_be_typed_to_unlock() {
+        let t0 = Instant::now();
+        let mut lock = SessionLock::new(Some(IDLE), Some("s3cret".into()), t0);
+        lock.lock();
+        assert!(!hotkey(&mut lock, t0).contains(&LockUse::Unlock));
+        assert_eq!(lock.panel().unwrap().prompt.as_deref(), Some(""));
+
+        type_text(&mut lock, "s3crat", t0);
+        assert_eq!(lock.panel().unwrap().prompt.as_deref(), Some("******"));
+        assert_eq!(lock.observe(&key(ENTER_VK, true), t0), LockUse::Swallow);
+        let panel = lock.panel().unwrap();
+        assert_eq!(panel.error.as_deref(), Some("Wrong token"));
+        assert_eq!(panel.prompt.as_deref(), Some(""));
+        assert!(lock.is_locked());
+
+        type_text(&mut lock, "s3cx", t0);
+        lock.observe(&key(BACK_VK, true), t0);
+        type_text(&mut lock, "ret\r", t0);
+        assert_eq!(lock.observe(&key(ENTER_VK, true), t0), LockUse::Unlock);
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn escape_leaves_the_prompt_locked() {
+        let t0 = Instant::now();
+        let mut lock = SessionLock::new(Some(IDLE), Some("s3cret".into()), t0);
+        lock.lock();
+        hotkey(&mut lock, t0);
+        type_text(&mut lock, "s3", t0);
+        lock.observe(&key(ESCAPE_VK, true), t0);
+        assert!(lock.is_locked());
+        assert_eq!(lock.panel().unwrap().prompt, None);
+
+        // The prompt starts empty next time.
+        hotkey(&mut lock, t0);
+        type_text(&mut lock, "cret", t0);
+        assert_eq!(lock.observe(&key(ENTER_VK, true), t0), LockUse::Swallow);
+        assert!(lock.is_locked());
+    }
+
+    #[test]
+    fn an_empty_token_needs_only_the_hotkey() {
+        let t0 = Instant::now();
+        let mut lock = SessionLock::new(Some(IDLE), Some(String::new()), t0);
+        lock.lock();
+        assert!(hotkey(&mut lock, t0).contains(&LockUse::Unlock));
+    }
+}
@@ -4,26 +4,130 @@
 //! tix-rdp-gui                    Connect with defaults
 //! tix-rdp-gui --config <path>   Use custom config TOML
 //! tix-rdp-gui --gen-config      Dump default config and exit
+//! tix-rdp-gui --via-master      Stream from a tix-slave via the master
+//! tix-rdp-gui --view-only       Watch without sending any input
+//! tix-rdp-gui --retry-forever   Keep retrying an unreachable slave
+//! tix-rdp-gui --monitors 0,1    One window per slave monitor
+//! tix-rdp-gui --low-latency     Show frames as they arrive, unpaced
+//! tix-rdp-gui --journal <path>  Record the input sent to a JSONL file
+//! tix-rdp-gui --dump-journal <path>  Print a recorded journal and exit
+//! tix-rdp-gui --snapshot-dir <dir>   Where Ctrl+S saves screenshots
+//! tix-rdp-gui --ignore-config-errors  Run on the defaults if the config is bad
 //! ```
+//!
+//! When the slave cannot be reached, a connect dialog lets the user fix
+//! the address and retry; an address that then works is written back to
+//! the config file. `--retry-forever` replaces the dialog with retries
+//! on a backoff for unattended screens.
+//!
+//! F8 toggles between control and view-only at any time; the title bar
+//! shows `[VIEW ONLY]` while input is off. After `lock.idle_minutes`
+//! without local input the session locks (see [`tix_rdp_gui::lock`]):
+//! view-only, dimmed, and back in control only after Ctrl+Alt+U. With a journal open, F9
+//! toggles private mode (see [`tix_rdp_gui::journal`]).
+//!
+//! Ctrl+S saves the window's remote screen as a PNG (see
+//! [`tix_rdp_gui::snapshot`]) and says where in the overlay strip.
+//!
+//! F11 opens a menu of key combinations the local machine would keep
+//! for itself (Ctrl+Alt+Del, Win+L, …); Ctrl+Alt+End sends Ctrl+Alt+Del
+//! straight away (see [`tix_rdp_gui::special`]).
+//!
+//! Ctrl+Alt+G and Ctrl+Alt+N switch the grayscale and night-light
+//! filters on and off in every window (see [`tix_rdp_gui::postprocess`]).
+//!
+//! F10 opens the settings overlay: those filters, scaling, view-only,
+//! input capture and pacing, changed with the arrow keys and saved back
+//! to the config file on request (see [`tix_rdp_gui::settings`]).
+//!
+//! In `--via-master` mode, files dropped onto the window are uploaded to
+//! the slave (see [`tix_rdp_gui::upload`]); large ones are confirmed
+//! first and very large ones refused.
+//!
+//! Frames are normally held briefly so they appear as evenly spaced as
+//! they were captured (see [`tix_rdp_gui::pacing`]); the title bar
+//! shows the latency this adds. `--low-latency` turns that off.
+//!
+//! When screen data keeps arriving but no frame comes of it, the window
+//! keeps the last frame up, slightly dimmed, with a "stream stalled"
+//! note in the corner, and sends only key and button releases until
+//! frames come again (see [`tix_rdp_gui::stall`]). The title bar counts
+//! the stalls, their total length and the frames that never arrived.
+//!
+//! With `--monitors`, each listed monitor streams as a screen session of
+//! its own into its own window; pointer input from a window lands on
+//! that monitor. Closing one window ends its session, closing the last
+//! ends the viewer.
+//!
+//! Sessions whose slave takes them get pointer moves over the screen
+//! socket; everything else goes over the control connection.
+//!
+//! On a link too slow for frames the slave sends a thumbnail every few
+//! seconds instead (see [`tix_core::rdp::thumbnail`]), which the window
+//! scales up under a LOW BANDWIDTH MODE banner naming the slave's
+//! foreground window; input still goes through. F7 switches a window
+//! between thumbnails and frames.
+//!
+//! Upload progress also shows on the taskbar button, and the button
+//! flashes when the connection is lost, the slave ends the session or an
+//! upload settles while no viewer window has the focus (see
+//! [`tix_rdp_gui::taskbar`]).
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use clap::Parser;
 use tokio::net::UdpSocket;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
-use tix_core::rdp::client::ScreenClient;
-use tix_core::rdp::transport::ScreenTransport;
+use tix_core::Feature;
+use tix_core::config;
+use tix_core::format::format_duration;
+use tix_core::paste_guard::PasteGuard;
+use tix_core::protocol::SessionStats;
+use tix_core::protocol::screen::{DictionaryOffer, MouseEvent};
+use tix_core::rdp::client::{FrameStats, ScreenClient, SnapshotHandle, TimedFrame};
+use tix_core::rdp::desktop::ScreenStatus;
+use tix_core::rdp::pointer::PointerSender;
+use tix_core::rdp::telemetry::{self, Role};
+use tix_core::rdp::thumbnail::ForegroundInfo;
+use tix_core::rdp::transport::{MAX_SESSIONS, ScreenTransport, TrafficCounter};
 use tix_core::rdp::types::PixelFormat;
 
 use tix_rdp_gui::config::GuiConfig;
 use tix_rdp_gui::connection::SlaveConnection;
-use tix_rdp_gui::display::DisplayRenderer;
-use tix_rdp_gui::input::{translate_event, InputAction};
+use tix_rdp_gui::dictionary;
+use tix_rdp_gui::display::{Banner, DisplayRenderer, Notice, StallHint, StatusPanel, Viewport};
+use tix_rdp_gui::input::{
+    is_bandwidth_key, is_mode_key, mode_feedback, translate_event, InputAction, InputRoute,
+    Routed, ViewMode,
+};
+use tix_rdp_gui::journal::{self, InputJournal, is_private_key};
+use tix_rdp_gui::lock::{LockUse, SessionLock};
+use tix_rdp_gui::pacing::Pacer;
+use tix_rdp_gui::postprocess::{ColorFilter, FilterHotkeys, FilterKeyUse};
+use tix_rdp_gui::settings::{Apply, SettingsOverlay, SettingsUse};
+use tix_rdp_gui::snapshot::{self, HotkeyUse, SnapshotHotkey, NOTICE_DURATION};
+use tix_rdp_gui::special::{sas_feedback, MenuUse, SpecialKeyMenu, SpecialKeys};
+use tix_rdp_gui::stall::StallDetector;
+use tix_rdp_gui::taskbar::{Attention, Notable};
+use tix_rdp_gui::upload::{remote_path, send_file, PromptUse, UploadQueue};
 use tix_rdp_gui::window::{NativeWindow, WindowEvent};
+use tix_rdp_gui::wizard::{retry_delay, ConnectDialog, DialogAction};
+
+/// How often the title-bar stats line is refreshed.
+const STATS_LINE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How often the connect dialog pumps events and redraws.
+const DIALOG_TICK: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// How long "remote ended the session" stays up; the window is left
+/// open on the last picture until the user closes it.
+const ENDED_NOTICE: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
 
 // ── CLI ──────────────────────────────────────────────────────────
 
@@ -38,9 +142,60 @@ struct Cli {
     #[arg(short, long)]
     slave: Option<String>,
 
+    /// Connect through the master's RDP bridge (classic tix-slave)
+    /// instead of a standalone tix-rdp-slave.
+    #[arg(long)]
+    via_master: bool,
+
+    /// Master bridge address (overrides config). Implies --via-master.
+    #[arg(long)]
+    master: Option<String>,
+
+    /// Wait on this TCP port for a slave behind NAT to dial in (its
+    /// `connect_to`) instead of connecting to it.
+    #[arg(long, value_name = "PORT")]
+    listen: Option<u16>,
+
+    /// Start view-only: the slave refuses all input until F8 is pressed.
+    #[arg(long)]
+    view_only: bool,
+
+    /// Keep retrying an unreachable slave with backoff instead of
+    /// showing the connect dialog (for unattended screens).
+    #[arg(long)]
+    retry_forever: bool,
+
+    /// Slave monitors to show, one window each (overrides config).
+    /// Example: 0,1
+    #[arg(long, value_delimiter = ',')]
+    monitors: Vec<u8>,
+
+    /// Show every frame the moment it arrives instead of pacing frames
+    /// evenly (less latency, less smooth motion).
+    #[arg(long)]
+    low_latency: bool,
+
+    /// Append every input event sent to this JSONL file (overrides
+    /// config).
+    #[arg(long, value_name = "PATH")]
+    journal: Option<PathBuf>,
+
+    /// Directory Ctrl+S saves screenshots to (overrides config).
+    #[arg(long, value_name = "DIR")]
+    snapshot_dir: Option<PathBuf>,
+
+    /// Print an input journal as a timeline with totals and exit.
+    #[arg(long, value_name = "PATH")]
+    dump_journal: Option<PathBuf>,
+
     /// Print the default configuration to stdout and exit.
     #[arg(long)]
     gen_config: bool,
+
+    /// Run on the defaults when the config file is invalid instead of
+    /// exiting.
+    #[arg(long)]
+    ignore_config_errors: bool,
 }
 
 // ── Main ─────────────────────────────────────────────────────────
@@ -54,11 +209,44 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("{text}");
         return Ok(());
     }
+    if let Some(path) = &cli.dump_journal {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        let entries = journal::parse(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        print!("{}", journal::dump(&entries));
+        return Ok(());
+    }
 
-    let mut config = GuiConfig::load(&cli.config);
+    let (mut config, config_note) =
+        config::load_or_exit::<GuiConfig>(&cli.config, cli.ignore_config_errors);
     if let Some(addr) = cli.slave {
         config.network.slave_address = addr;
     }
+    if let Some(addr) = cli.master {
+        config.network.master_address = addr;
+        config.network.via_master = true;
+    }
+    if cli.via_master {
+        config.network.via_master = true;
+    }
+    if let Some(port) = cli.listen {
+        config.network.listen_port = port;
+    }
+    if cli.view_only {
+        config.input.view_only = true;
+    }
+    if !cli.monitors.is_empty() {
+        config.display.monitors = cli.monitors;
+    }
+    if cli.low_latency {
+        config.performance.smooth_pacing = false;
+    }
+    if let Some(path) = &cli.journal {
+        config.input.journal = path.display().to_string();
+    }
+    if let Some(dir) = &cli.snapshot_dir {
+        config.snapshot.dir = dir.display().to_string();
+    }
 
     // Init tracing.
     let filter = EnvFilter::try_from_default_env()
@@ -66,107 +254,595 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt().with_env_filter(filter).init();
 
     info!("tix-rdp-gui v{}", env!("CARGO_PKG_VERSION"));
+    if let Some(note) = config_note {
+        info!("{note}");
+    }
+
+    if config.metrics.port != 0 {
+        match telemetry::serve(config.metrics.port) {
+            Ok(()) => info!("metrics on http://127.0.0.1:{}/metrics", config.metrics.port),
+            Err(e) => warn!("metrics endpoint not started: {e}"),
+        }
+    }
 
     // ── 1. Create the window ────────────────────────────────────
 
-    let window = NativeWindow::create(
-        "TIX Remote Desktop",
-        config.display.width,
-        config.display.height,
-    )?;
-    let mut renderer = DisplayRenderer::new(
-        window.hwnd(),
-        config.display.width,
-        config.display.height,
-    );
+    let mut monitors = config.display.monitors.clone();
+    if monitors.len() > MAX_SESSIONS as usize {
+        warn!("showing the first {MAX_SESSIONS} of {} monitors", monitors.len());
+        monitors.truncate(MAX_SESSIONS as usize);
+    }
+    let title = |i: usize| match monitors.len() {
+        0 | 1 => "TIX Remote Desktop".to_string(),
+        _ => format!("TIX Remote Desktop [monitor {}]", monitors[i]),
+    };
+
+    let window = NativeWindow::create(&title(0), config.display.width, config.display.height)?;
+    let mut renderer =
+        DisplayRenderer::new(window.hwnd(), config.display.width, config.display.height)
+            .with_post_processing(config.display.post_processing());
 
     // ── 2. Connect to the slave ─────────────────────────────────
 
     // Bind a UDP socket for receiving screen frames.
-    let udp = UdpSocket::bind("0.0.0.0:0").await?;
-    let local_udp_port = udp.local_addr()?.port();
+    let udp = config.network.transport_config().bind("0.0.0.0:0".parse()?)?;
 
-    let mut conn = SlaveConnection::connect(&config, local_udp_port).await?;
+    let connected = establish(
+        &window,
+        &mut renderer,
+        &mut config,
+        &cli.config,
+        &udp,
+        cli.retry_forever,
+    )
+    .await?;
+    let Some(mut conn) = connected else {
+        info!("connection abandoned");
+        return Ok(());
+    };
     let slave_screen_addr = conn.slave_screen_addr()?;
     info!("slave screen addr: {slave_screen_addr}");
 
-    let transport = ScreenTransport::new(udp, slave_screen_addr);
-
-    // ── 3. Start the RDP client ─────────────────────────────────
+    // ── 3. Start the RDP clients ────────────────────────────────
 
-    let mut client = ScreenClient::new(transport, PixelFormat::Bgra8);
-    let mut frame_rx = client.frame_receiver();
-    let stats_rx = client.stats_receiver();
-    let running = Arc::new(AtomicBool::new(true));
-
-    let client_running = running.clone();
-    let client_handle = tokio::spawn(async move {
-        if let Err(e) = client.run().await {
-            error!("RDP client error: {e}");
+    // Session 0 came with the handshake; every further monitor gets a
+    // session, a socket and a window of its own.
+    let transport = ScreenTransport::new(udp, slave_screen_addr).with_mtu(conn.mtu());
+    let pointer = conn.udp_input().then(|| transport.pointer_sender());
+    let (client, dictionary) = screen_client(transport, conn.dictionary_offer(), &config);
+    let mut views = vec![MonitorView::start(
+        0,
+        title(0),
+        window,
+        renderer,
+        &config,
+        conn.origin(),
+        client,
+    )
+    .with_pointer(pointer)
+    .with_fps(conn.fps())];
+    if let Some(hash) = dictionary
+        && let Err(e) = conn.confirm_dictionary(0, hash).await
+    {
+        warn!("cannot enable the frame dictionary: {e}");
+    }
+    for (i, &monitor) in monitors.iter().enumerate().skip(1) {
+        let session = i as u8;
+        let udp = config.network.transport_config().bind("0.0.0.0:0".parse()?)?;
+        let screen = match conn.open_session(session, monitor, &udp).await {
+            Ok(screen) => screen,
+            Err(e) => {
+                warn!("cannot show monitor {monitor}: {e}");
+                continue;
+            }
+        };
+        let Some(addr) = screen.udp_endpoint else {
+            continue;
+        };
+        let window =
+            NativeWindow::create(&title(i), config.display.width, config.display.height)?;
+        let renderer =
+            DisplayRenderer::new(window.hwnd(), config.display.width, config.display.height)
+                .with_post_processing(config.display.post_processing());
+        let transport = ScreenTransport::new(udp, addr)
+            .with_mtu(screen.mtu as usize)
+            .with_session(session);
+        let pointer = screen.udp_input.then(|| transport.pointer_sender());
+        let (client, dictionary) = screen_client(transport, screen.dictionary.as_ref(), &config);
+        views.push(
+            MonitorView::start(
+                session,
+                title(i),
+                window,
+                renderer,
+                &config,
+                screen.origin,
+                client,
+            )
+            .with_pointer(pointer)
+            .with_fps(screen.fps),
+        );
+        if let Some(hash) = dictionary
+            && let Err(e) = conn.confirm_dictionary(session, hash).await
+        {
+            warn!("cannot enable the frame dictionary on {}: {e}", title(i));
         }
-        client_running.store(false, Ordering::SeqCst);
-    });
+    }
 
     // ── 4. Event loop ───────────────────────────────────────────
 
-    let mut remote_width = config.display.width;
-    let mut remote_height = config.display.height;
-    let mut win_width = config.display.width;
-    let mut win_height = config.display.height;
-
-    loop {
-        if !running.load(Ordering::SeqCst) {
-            break;
+    let mut last_stats_line = std::time::Instant::now();
+    let mut uploads = UploadQueue::default().with_guard(PasteGuard::new(&config.paste_guard));
+    let mut upload_prompt = None;
+    let mut upload_task: Option<JoinHandle<Result<(), String>>> = None;
+    let mut attention = Attention::default();
+    let mut overlay = None;
+    let mut notice: Option<Notice> = None;
+    let mut remote_ended = false;
+    let mut snapshot_tasks: Vec<JoinHandle<Result<PathBuf, String>>> = Vec::new();
+    let mut mode = ViewMode::new(config.input.view_only);
+    let mut lock =
+        SessionLock::new(config.lock.idle(), config.lock.token(), std::time::Instant::now());
+    let mut lock_panel = None;
+    let mut filters = config.display.filter_set();
+    let mut filters_changed = false;
+    let mut scale_changed = false;
+    let mut pacing_changed = false;
+    // Whether unlocking hands control back, i.e. the lock took it.
+    let mut unlock_to_control = false;
+    let mut title_stale = true;
+    let mut input_journal = None;
+    if !config.input.journal.is_empty() {
+        match InputJournal::open(Path::new(&config.input.journal)).await {
+            Ok(j) => {
+                info!("journalling input to {}", j.path().display());
+                input_journal = Some(j);
+            }
+            Err(e) => warn!("input journal not kept: {e}"),
         }
+    }
 
+    'session: loop {
         // Pump window messages.
-        let events = window.poll_events();
-        for ev in &events {
-            match ev {
-                WindowEvent::Close => {
-                    running.store(false, Ordering::SeqCst);
-                    break;
-                }
-                WindowEvent::Resize(w, h) => {
-                    win_width = *w;
-                    win_height = *h;
-                    renderer.resize(*w, *h);
+        let mut closed = Vec::new();
+        for (index, view) in views.iter_mut().enumerate() {
+            if !view.running.load(Ordering::SeqCst) {
+                break 'session;
+            }
+            let events = view.window.poll_events();
+            for ev in &events {
+                // The lock sees everything first; a large drop waiting for
+                // an answer takes the keyboard next.
+                let mut chain: [&mut dyn EventFilter; 6] = [
+                    &mut lock,
+                    &mut uploads,
+                    &mut view.settings,
+                    &mut view.special,
+                    &mut view.hotkey,
+                    &mut view.filter_keys,
+                ];
+                match filter_event(&mut chain, ev, &mut config) {
+                    Filtered::Forward => {}
+                    Filtered::Swallow => continue,
+                    Filtered::Redraw => {
+                        view.show_menu(&config);
+                        continue;
+                    }
+                    Filtered::Unlocked => {
+                        info!("session unlocked");
+                        if std::mem::take(&mut unlock_to_control) {
+                            mode.toggle();
+                            if let Err(e) = conn.set_control(true).await {
+                                warn!("failed to resume control: {e}");
+                            }
+                        }
+                        title_stale = true;
+                        continue;
+                    }
+                    Filtered::Answered(msg) => {
+                        info!("{msg}");
+                        continue;
+                    }
+                    Filtered::Changed(apply) => {
+                        view.show_menu(&config);
+                        match apply {
+                            Apply::Nothing => {}
+                            Apply::Scale => scale_changed = true,
+                            Apply::Filters => {
+                                filters = config.display.filter_set();
+                                filters_changed = true;
+                            }
+                            Apply::Control => {
+                                if config.input.view_only != mode.is_view_only() {
+                                    switch_mode(&mut conn, &mut mode, &mut config).await;
+                                    title_stale = true;
+                                }
+                            }
+                            Apply::Pacing => pacing_changed = true,
+                        }
+                        continue;
+                    }
+                    Filtered::Save => {
+                        let label = match view.settings.model().persist(&cli.config, &config) {
+                            Ok(()) => {
+                                info!("saved settings to {}", cli.config.display());
+                                "Settings saved".to_string()
+                            }
+                            Err(e) => {
+                                warn!("cannot save settings: {e}");
+                                format!("Settings not saved: {e}")
+                            }
+                        };
+                        let now = std::time::Instant::now();
+                        notice = Some(Notice::new(label, now, NOTICE_DURATION));
+                        continue;
+                    }
+                    Filtered::Send(keys) => {
+                        view.show_menu(&config);
+                        let label = if mode.is_view_only() {
+                            format!("{} not sent: view-only", keys.label())
+                        } else {
+                            let journal = input_journal.as_ref();
+                            match send_special(&mut conn, view, keys, journal).await {
+                                // The slave's answer says how it went.
+                                Ok(()) if keys.needs_sas() => continue,
+                                Ok(()) => format!("Sent {}", keys.label()),
+                                Err(e) => {
+                                    warn!("failed to send {}: {e}", keys.label());
+                                    format!("{} not sent: {e}", keys.label())
+                                }
+                            }
+                        };
+                        let now = std::time::Instant::now();
+                        notice = Some(Notice::new(label, now, NOTICE_DURATION));
+                        continue;
+                    }
+                    Filtered::Snapshot => {
+                        match view.snapshots.snapshot() {
+                            Some(frame) => {
+                                let dir = PathBuf::from(&config.snapshot.dir);
+                                snapshot_tasks.push(tokio::task::spawn_blocking(move || {
+                                    snapshot::save(&frame, &dir)
+                                }));
+                            }
+                            None => {
+                                notice = Some(Notice::new(
+                                    "No frame yet",
+                                    std::time::Instant::now(),
+                                    NOTICE_DURATION,
+                                ));
+                            }
+                        }
+                        continue;
+                    }
+                    Filtered::Toggle(filter) => {
+                        let on = filters.toggle(filter);
+                        config.display.set_filter(filter, on);
+                        let state = if on { "on" } else { "off" };
+                        let label = format!("{filter} {state}");
+                        info!("{label}");
+                        filters_changed = true;
+                        let now = std::time::Instant::now();
+                        notice = Some(Notice::new(label, now, NOTICE_DURATION));
+                        continue;
+                    }
                 }
-                _ => {}
+                if is_mode_key(ev) {
+                    if let WindowEvent::Key(_, _, true) = ev {
+                        switch_mode(&mut conn, &mut mode, &mut config).await;
+                        title_stale = true;
+                    }
+                    continue;
+                }
+                if is_bandwidth_key(ev) {
+                    if let WindowEvent::Key(_, _, true) = ev {
+                        let label = switch_sharing(&mut conn, view, &config).await;
+                        let now = std::time::Instant::now();
+                        notice = Some(Notice::new(label, now, NOTICE_DURATION));
+                    }
+                    continue;
+                }
+                if let Some(j) = input_journal.as_mut()
+                    && is_private_key(ev)
+                {
+                    if let WindowEvent::Key(_, _, true) = ev {
+                        let private = j.toggle_private();
+                        info!("private mode {}", if private { "on" } else { "off" });
+                        title_stale = true;
+                    }
+                    continue;
+                }
+
+                match ev {
+                    WindowEvent::Close => {
+                        closed.push(index);
+                        break;
+                    }
+                    WindowEvent::Resize(w, h) => view.resize(*w, *h),
+                    WindowEvent::FilesDropped(paths, _, _) => {
+                        let rate = conn.control_send_rate();
+                        for msg in uploads.enqueue(paths.clone(), rate) {
+                            warn!("{msg}");
+                        }
+                    }
+                    _ => {}
+                }
+
+                // Forward input to slave. Nothing is sent while view-only.
+                if !mode.is_view_only()
+                    && (config.input.capture_mouse || config.input.capture_keyboard)
+                    && let Some(action) =
+                        translate_event(ev, &view.viewport, view.remote.0, view.remote.1)
+                {
+                    let action = action.on_monitor(view.origin);
+                    // Clicks at a stalled picture would all land at once
+                    // when it recovers.
+                    if view.stall.holds_back(&action) {
+                        continue;
+                    }
+                    match send_input(&mut conn, view, action).await {
+                        Ok(()) => {
+                            if let Some(j) = &input_journal {
+                                j.record(&action);
+                            }
+                        }
+                        Err(e) => warn!("failed to send input: {e}"),
+                    }
+                }
+            }
+        }
+        // An idle session locks: view-only until Ctrl+Alt+U.
+        if lock.tick(std::time::Instant::now()) {
+            info!("session locked after {} minutes without input", config.lock.idle_minutes);
+            if !mode.is_view_only() {
+                mode.toggle();
+                unlock_to_control = true;
+                if let Err(e) = conn.set_control(false).await {
+                    warn!("failed to switch to view-only: {e}");
+                }
+            }
+            title_stale = true;
+        }
+        let next_lock = lock.panel();
+        if next_lock != lock_panel {
+            lock_panel = next_lock;
+            for view in &mut views {
+                view.renderer.set_lock(lock_panel.clone());
+                view.repaint = true;
+            }
+        }
+        if std::mem::take(&mut filters_changed) {
+            for view in &mut views {
+                view.renderer.set_filters(&filters);
+                view.repaint = true;
+            }
+        }
+        if std::mem::take(&mut scale_changed) {
+            for view in &mut views {
+                view.renderer.set_scale_filter(config.display.scale_filter());
+                view.repaint = true;
+            }
+        }
+        if std::mem::take(&mut pacing_changed) {
+            for view in &mut views {
+                view.pacer = pacer(&config);
             }
+        }
 
-            // Forward input to slave.
-            if config.input.capture_mouse || config.input.capture_keyboard {
-                if let Some(action) = translate_event(
-                    ev,
-                    win_width,
-                    win_height,
-                    remote_width,
-                    remote_height,
-                ) {
-                    let result = match action {
-                        InputAction::Mouse(me) => conn.send_mouse(&me).await,
-                        InputAction::Key(ke) => conn.send_keyboard(&ke).await,
-                    };
-                    if let Err(e) = result {
-                        warn!("failed to send input: {e}");
+        // The last window takes the whole connection down with it.
+        for index in closed.into_iter().rev() {
+            if views.len() == 1 {
+                break 'session;
+            }
+            let view = views.remove(index);
+            info!("closing {}", view.name);
+            if !remote_ended
+                && let Err(e) = conn.close_session(view.session).await
+            {
+                warn!("failed to stop session {}: {e}", view.session);
+            }
+            view.shutdown().await;
+        }
+
+        // Drag-and-drop uploads: settle the active one, start the next.
+        for pkt in conn.poll_responses() {
+            if let Some(feedback) = dictionary::feedback(&pkt) {
+                match feedback {
+                    Ok(()) => info!("slave compresses frames with the dictionary"),
+                    Err(msg) => warn!("{msg}"),
+                }
+                continue;
+            }
+            if let Some(feedback) = sas_feedback(&pkt) {
+                let label = match feedback {
+                    Ok(()) => {
+                        info!("slave raised Ctrl+Alt+Del");
+                        "Sent Ctrl+Alt+Del".to_string()
                     }
+                    Err(msg) => {
+                        warn!("{msg}");
+                        msg
+                    }
+                };
+                notice = Some(Notice::new(label, std::time::Instant::now(), NOTICE_DURATION));
+                continue;
+            }
+            if let Some(feedback) = mode_feedback(&pkt) {
+                match feedback {
+                    Ok(control) if control == mode.is_view_only() => {
+                        warn!("slave reports the session is {}", if control {
+                            "in control"
+                        } else {
+                            "view-only"
+                        });
+                        mode.confirm(control);
+                        title_stale = true;
+                    }
+                    Ok(_) => {}
+                    Err(msg) => warn!("{msg}"),
                 }
+                continue;
             }
+            match uploads.handle_response(&pkt) {
+                Some(Ok(msg)) => {
+                    info!("{msg}");
+                    let queued = uploads.queued();
+                    flash(&views, &Notable::TransferFinished { queued });
+                }
+                Some(Err(msg)) => {
+                    warn!("{msg}");
+                    flash(&views, &Notable::TransferFailed);
+                }
+                None => continue,
+            }
+            if let Some(task) = upload_task.take() {
+                task.abort();
+            }
+        }
+        // The slave's Hello says whether it can raise Ctrl+Alt+Del.
+        let sas_unavailable = conn.unsupported(Feature::Sas);
+        for view in &mut views {
+            view.special.set_sas_unavailable(sas_unavailable.clone());
+        }
+        // A direct slave that stops says so before it closes the stream.
+        if !remote_ended && let Some(reason) = conn.session_ended().await {
+            warn!("remote ended the session: {reason}");
+            remote_ended = true;
+            mode.confirm(false);
+            title_stale = true;
+            let label = format!("Remote ended the session ({reason})");
+            notice = Some(Notice::new(label, std::time::Instant::now(), ENDED_NOTICE));
+            flash(&views, &Notable::SessionEnded);
+        }
+        if let Some(event) = attention.connection(conn.control_lost()) {
+            warn!("lost the connection to the master");
+            flash(&views, &event);
+        }
+        if let Some(task) = upload_task.as_mut()
+            && task.is_finished()
+        {
+            // Sent everything: keep waiting for the ack. A failure to send
+            // means no ack is coming.
+            if let Ok(Err(e)) = task.await
+                && let Some(msg) = uploads.fail_active(&e)
+            {
+                warn!("{msg}");
+                flash(&views, &Notable::TransferFailed);
+            }
+            upload_task = None;
+        }
+        if let Some(path) = uploads.next_ready() {
+            let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            match conn.begin_upload() {
+                Ok((tx, req_id)) => {
+                    let (sent, window) = uploads.start(req_id, &path, size);
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    let remote = remote_path(&config.upload.remote_dir, &name);
+                    info!("uploading {} to {remote}", path.display());
+                    let upload = send_file(tx, req_id, path, remote, sent, window);
+                    upload_task = Some(tokio::spawn(upload));
+                }
+                Err(e) => warn!("cannot upload {}: {e}", path.display()),
+            }
+        }
+        // Screenshots being written: report the ones that are done.
+        let mut pending = Vec::with_capacity(snapshot_tasks.len());
+        for task in snapshot_tasks.drain(..) {
+            if !task.is_finished() {
+                pending.push(task);
+                continue;
+            }
+            let label = match task.await {
+                Ok(Ok(path)) => {
+                    info!("saved screenshot {}", path.display());
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    format!("Saved {name}")
+                }
+                Ok(Err(e)) => {
+                    warn!("screenshot not saved: {e}");
+                    format!("Screenshot failed: {e}")
+                }
+                Err(e) => format!("Screenshot failed: {e}"),
+            };
+            notice = Some(Notice::new(label, std::time::Instant::now(), NOTICE_DURATION));
+        }
+        snapshot_tasks = pending;
+
+        if let Some(progress) = attention.progress(uploads.progress()) {
+            for view in &views {
+                view.window.set_taskbar_progress(progress);
+            }
+        }
+
+        // Upload progress wins over a notice.
+        let next_overlay = uploads
+            .overlay()
+            .or_else(|| notice.as_ref()?.overlay(std::time::Instant::now()));
+        let overlay_changed = next_overlay != overlay;
+        if overlay_changed {
+            overlay = next_overlay;
         }
 
-        // Check for new frames.
-        if frame_rx.has_changed().unwrap_or(false) {
-            let frame_buf = frame_rx.borrow_and_update().clone();
-            let stats = stats_rx.borrow().clone();
+        // The prompt for a large drop stands in for the menus.
+        let next_prompt = uploads.prompt();
+        let prompt_changed = next_prompt != upload_prompt;
+        if prompt_changed {
+            upload_prompt = next_prompt;
+        }
 
-            if stats.width > 0 && stats.height > 0 {
-                remote_width = stats.width;
-                remote_height = stats.height;
+        for view in &mut views {
+            if overlay_changed {
+                view.renderer.set_overlay(overlay.clone());
             }
+            if prompt_changed {
+                match &upload_prompt {
+                    Some(prompt) => view.renderer.set_menu(Some(prompt.clone())),
+                    None => view.show_menu(&config),
+                }
+            }
+            view.draw(overlay_changed);
+        }
 
-            if let Err(e) = renderer.render(&frame_buf, remote_width, remote_height) {
-                warn!("render error: {e}");
+        // Stats line in the title bar, refreshed once a second.
+        if title_stale || last_stats_line.elapsed() >= STATS_LINE_INTERVAL {
+            last_stats_line = std::time::Instant::now();
+            title_stale = false;
+            let (control_sent, control_received) = conn.control_traffic();
+            let rtt = conn
+                .control_rtt()
+                .map_or(String::new(), |rtt| format!(", rtt {}", format_duration(rtt)));
+            for view in &mut views {
+                let session = SessionStats {
+                    control_sent,
+                    control_received,
+                    screen_sent: view.screen_traffic.sent(),
+                    screen_received: view.screen_traffic.received(),
+                    ..Default::default()
+                };
+                let (width, height) = view.remote;
+                let (fps, errors) = {
+                    let stats = view.stats_rx.borrow();
+                    let errors = match stats.stream_errors {
+                        0 => String::new(),
+                        n => format!(", stream errors: {n}"),
+                    };
+                    (stats.fps, errors)
+                };
+                let pacing = match &view.pacer {
+                    Some(pacer) => format!(" +{} pacing", format_duration(pacer.added_latency())),
+                    None => String::new(),
+                };
+                let stalls = view.stall.summary(std::time::Instant::now());
+                let rate = format!("{fps:.0} fps{pacing}{errors}{stalls}");
+                let private = input_journal.as_ref().map_or("", |j| j.title_suffix());
+                let uploads = view.renderer.take_upload_stats().summary();
+                view.window.set_title(&format!(
+                    "{} - {width}x{height} @ {rate}, {uploads}{rtt} - {}{}{}{private}",
+                    view.name,
+                    session.summary(),
+                    mode.title_suffix(),
+                    lock.title_suffix()
+                ));
             }
         }
 
@@ -177,9 +853,639 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // ── 5. Shutdown ─────────────────────────────────────────────
 
     info!("shutting down");
-    client_handle.abort();
-    let _ = client_handle.await;
+    for view in views {
+        view.shutdown().await;
+    }
+    if let Some(task) = upload_task {
+        task.abort();
+    }
+    if let Err(e) = conn.stop_screen().await {
+        warn!("failed to stop remote capture: {e}");
+    }
     drop(conn);
+    if let Some(j) = input_journal
+        && let Err(e) = j.close().await
+    {
+        warn!("input journal incomplete: {e}");
+    }
+
+    Ok(())
+}
+
+/// Flash the viewer windows' taskbar buttons for `event`, unless the
+/// user is looking at one of them.
+fn flash(views: &[MonitorView], event: &Notable) {
+    let focused = views.iter().any(|view| view.window.is_focused());
+    if let Some(count) = event.flashes(focused) {
+        for view in views {
+            view.window.flash(count);
+        }
+    }
+}
+
+/// Switch between control and view-only (`F8`, or the setting), and
+/// tell the slave.
+async fn switch_mode(conn: &mut SlaveConnection, mode: &mut ViewMode, config: &mut GuiConfig) {
+    let control = mode.toggle();
+    config.input.view_only = !control;
+    info!("switching to {}", if control { "control" } else { "view-only" });
+    if let Err(e) = conn.set_control(control).await {
+        warn!("failed to switch mode: {e}");
+    }
+}
+
+/// Switch `view` between thumbnails and frames (`F7`): whichever it is
+/// not showing now. Returns what to say in the overlay strip.
+async fn switch_sharing(
+    conn: &mut SlaveConnection,
+    view: &MonitorView,
+    config: &GuiConfig,
+) -> String {
+    let low_bandwidth = view.low_bandwidth_rx.borrow().is_none();
+    let wanted = if low_bandwidth { "thumbnails" } else { "full frames" };
+    info!("{}: asking for {wanted}", view.name);
+    let fallback = config.performance.thumbnails;
+    match conn.set_low_bandwidth(view.session, low_bandwidth, fallback).await {
+        Ok(()) => format!("Asked for {wanted}"),
+        Err(e) => {
+            warn!("failed to ask for {wanted}: {e}");
+            format!("Cannot switch to {wanted}: {e}")
+        }
+    }
+}
+
+/// A client for the frames on `transport`, holding the dictionary the
+/// session's ack offered when we can load it. Returns the dictionary's
+/// hash too: the slave waits for it to be confirmed.
+fn screen_client(
+    transport: ScreenTransport,
+    offer: Option<&DictionaryOffer>,
+    config: &GuiConfig,
+) -> (ScreenClient, Option<u64>) {
+    let client = ScreenClient::new(transport, PixelFormat::Bgra8)
+        .with_max_stream_errors(config.performance.max_stream_errors);
+    let Some(offer) = offer.filter(|_| config.performance.zstd_dictionary) else {
+        return (client, None);
+    };
+    let dir = std::path::Path::new(&config.performance.dictionary_dir);
+    match dictionary::resolve(offer, dir) {
+        Ok(dict) => {
+            info!("decoding with frame dictionary {:016x}", dict.hash());
+            (client.with_dictionary(&dict), Some(dict.hash()))
+        }
+        Err(e) => {
+            warn!("not using the offered frame dictionary: {e}");
+            (client, None)
+        }
+    }
+}
+
+/// The frame pacer `config` asks for, if any.
+fn pacer(config: &GuiConfig) -> Option<Pacer> {
+    let max_latency = std::time::Duration::from_millis(config.performance.max_pacing_ms);
+    config.performance.smooth_pacing.then(|| Pacer::new(max_latency))
+}
 
+/// Send `action` from `view`'s window the way its session takes input.
+async fn send_input(
+    conn: &mut SlaveConnection,
+    view: &mut MonitorView,
+    action: InputAction,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for routed in view.route.route(action) {
+        match routed {
+            Routed::Pointer(x, y) => match &view.pointer {
+                Some(pointer) => pointer.send(x, y).await?,
+                None => conn.send_mouse(&MouseEvent::move_to(x, y)).await?,
+            },
+            Routed::Control(InputAction::Mouse(me)) => conn.send_mouse(&me).await?,
+            Routed::Control(InputAction::Key(ke)) => conn.send_keyboard(&ke).await?,
+        }
+    }
     Ok(())
 }
+
+/// Send a combination from the special-keys menu: Ctrl+Alt+Del as a
+/// request to the slave, the rest as key events, journalled like typed
+/// ones.
+async fn send_special(
+    conn: &mut SlaveConnection,
+    view: &mut MonitorView,
+    keys: SpecialKeys,
+    journal: Option<&InputJournal>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if keys.needs_sas() {
+        return conn.send_sas().await;
+    }
+    for event in keys.key_events() {
+        let action = InputAction::Key(event);
+        send_input(conn, view, action).await?;
+        if let Some(j) = journal {
+            j.record(&action);
+        }
+    }
+    Ok(())
+}
+
+// ── Event filters ────────────────────────────────────────────────
+
+/// Something in a window that can take an event before the slave sees
+/// it: a hotkey, an overlay, a prompt.
+trait EventFilter {
+    /// Feed every window event through here before forwarding it.
+    fn observe(&mut self, event: &WindowEvent, config: &mut GuiConfig) -> Filtered;
+}
+
+/// What became of an event an [`EventFilter`] saw.
+enum Filtered {
+    /// Not for this filter: the next one sees it.
+    Forward,
+    /// Taken: drop it.
+    Swallow,
+    /// Taken by an open menu, which needs drawing again.
+    Redraw,
+    /// The session was just unlocked; hand control back.
+    Unlocked,
+    /// An upload prompt was answered; log this.
+    Answered(String),
+    /// A setting changed in the config; make it take effect.
+    Changed(Apply),
+    /// "Save" was chosen in the settings: persist the config.
+    Save,
+    /// Send this combination.
+    Send(SpecialKeys),
+    /// Take a snapshot.
+    Snapshot,
+    /// Switch this filter on or off.
+    Toggle(ColorFilter),
+}
+
+/// Pass `event` down `chain` until a filter takes it.
+fn filter_event(
+    chain: &mut [&mut dyn EventFilter],
+    event: &WindowEvent,
+    config: &mut GuiConfig,
+) -> Filtered {
+    for filter in chain {
+        match filter.observe(event, config) {
+            Filtered::Forward => {}
+            taken => return taken,
+        }
+    }
+    Filtered::Forward
+}
+
+impl EventFilter for SessionLock {
+    fn observe(&mut self, event: &WindowEvent, _: &mut GuiConfig) -> Filtered {
+        match SessionLock::observe(self, event, std::time::Instant::now()) {
+            LockUse::Forward => Filtered::Forward,
+            LockUse::Swallow => Filtered::Swallow,
+            LockUse::Unlock => Filtered::Unlocked,
+        }
+    }
+}
+
+impl EventFilter for UploadQueue {
+    fn observe(&mut self, event: &WindowEvent, _: &mut GuiConfig) -> Filtered {
+        match UploadQueue::observe(self, event) {
+            PromptUse::Forward => Filtered::Forward,
+            PromptUse::Swallow => Filtered::Swallow,
+            PromptUse::Answered(msg) => Filtered::Answered(msg),
+        }
+    }
+}
+
+impl EventFilter for SettingsOverlay {
+    fn observe(&mut self, event: &WindowEvent, config: &mut GuiConfig) -> Filtered {
+        match SettingsOverlay::observe(self, event, config) {
+            SettingsUse::Forward => Filtered::Forward,
+            SettingsUse::Swallow => Filtered::Redraw,
+            SettingsUse::Changed(apply) => Filtered::Changed(apply),
+            SettingsUse::Save => Filtered::Save,
+        }
+    }
+}
+
+impl EventFilter for SpecialKeyMenu {
+    fn observe(&mut self, event: &WindowEvent, _: &mut GuiConfig) -> Filtered {
+        match SpecialKeyMenu::observe(self, event) {
+            MenuUse::Forward => Filtered::Forward,
+            MenuUse::Swallow => Filtered::Redraw,
+            MenuUse::Send(keys) => Filtered::Send(keys),
+        }
+    }
+}
+
+impl EventFilter for SnapshotHotkey {
+    fn observe(&mut self, event: &WindowEvent, _: &mut GuiConfig) -> Filtered {
+        match SnapshotHotkey::observe(self, event) {
+            HotkeyUse::Forward => Filtered::Forward,
+            HotkeyUse::Swallow => Filtered::Swallow,
+            HotkeyUse::Take => Filtered::Snapshot,
+        }
+    }
+}
+
+impl EventFilter for FilterHotkeys {
+    fn observe(&mut self, event: &WindowEvent, _: &mut GuiConfig) -> Filtered {
+        match FilterHotkeys::observe(self, event) {
+            FilterKeyUse::Forward => Filtered::Forward,
+            FilterKeyUse::Swallow => Filtered::Swallow,
+            FilterKeyUse::Toggle(filter) => Filtered::Toggle(filter),
+        }
+    }
+}
+
+// ── Monitor views ────────────────────────────────────────────────
+
+/// One slave monitor: a screen session drawn into a window of its own.
+struct MonitorView {
+    session: u8,
+    /// Title-bar prefix naming the monitor.
+    name: String,
+    window: NativeWindow,
+    renderer: DisplayRenderer,
+    /// The monitor's top-left corner on the slave's virtual desktop.
+    origin: (i32, i32),
+    client_handle: JoinHandle<()>,
+    /// Cleared when the client stops on its own.
+    running: Arc<AtomicBool>,
+    frame_rx: watch::Receiver<TimedFrame>,
+    stats_rx: watch::Receiver<FrameStats>,
+    /// Holds frames until they are due; `None` shows them on arrival.
+    pacer: Option<Pacer>,
+    /// Notices the stream stalling, and the hint it gave last.
+    stall: StallDetector,
+    stall_hint: Option<StallHint>,
+    status_rx: watch::Receiver<ScreenStatus>,
+    /// The slave's foreground window while it sends thumbnails.
+    low_bandwidth_rx: watch::Receiver<Option<ForegroundInfo>>,
+    screen_traffic: TrafficCounter,
+    /// Remote resolution and window size.
+    remote: (u32, u32),
+    size: (u32, u32),
+    viewport: Viewport,
+    last_frame: TimedFrame,
+    /// Sequence number of the frame on screen, which the dirty
+    /// regions of the next one are relative to.
+    presented: Option<u64>,
+    status_panel: Option<StatusPanel>,
+    /// Shown over the frame while the slave's input is blocked.
+    banner: Option<Banner>,
+    /// Shown over thumbnails, together with `banner` if both apply.
+    low_bandwidth: Option<Banner>,
+    repaint: bool,
+    /// Sends pointer moves over the screen socket, when the session
+    /// takes them there.
+    pointer: Option<PointerSender>,
+    route: InputRoute,
+    /// Copies the newest decoded frame for Ctrl+S.
+    snapshots: SnapshotHandle,
+    hotkey: SnapshotHotkey,
+    /// Ctrl+Alt+G and Ctrl+Alt+N.
+    filter_keys: FilterHotkeys,
+    /// F11 menu and Ctrl+Alt+End.
+    special: SpecialKeyMenu,
+    /// F10 settings.
+    settings: SettingsOverlay,
+}
+
+impl MonitorView {
+    /// Start receiving session `session` with `client` into `window`.
+    fn start(
+        session: u8,
+        name: String,
+        window: NativeWindow,
+        renderer: DisplayRenderer,
+        config: &GuiConfig,
+        origin: (i32, i32),
+        mut client: ScreenClient,
+    ) -> Self {
+        let screen_traffic = client.traffic();
+        let frame_rx = client.frame_receiver();
+        let stats_rx = client.stats_receiver();
+        let status_rx = client.status_receiver();
+        let low_bandwidth_rx = client.low_bandwidth_receiver();
+        let snapshots = client.snapshot_handle();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let client_running = running.clone();
+        let client_handle = tokio::spawn(async move {
+            if let Err(e) = client.run().await {
+                error!("RDP client error: {e}");
+            }
+            client_running.store(false, Ordering::SeqCst);
+        });
+
+        let size = (config.display.width, config.display.height);
+        Self {
+            session,
+            name,
+            window,
+            renderer,
+            origin,
+            client_handle,
+            running,
+            frame_rx,
+            stats_rx,
+            pacer: pacer(config),
+            stall: StallDetector::new(config.performance.stall_intervals)
+                .with_dim(config.performance.dim_on_stall)
+                .with_input_paused(config.input.pause_on_stall),
+            stall_hint: None,
+            status_rx,
+            low_bandwidth_rx,
+            screen_traffic,
+            remote: size,
+            size,
+            viewport: Viewport::letterbox(size.0, size.1, size.0, size.1),
+            last_frame: TimedFrame::default(),
+            presented: None,
+            status_panel: None,
+            banner: None,
+            low_bandwidth: None,
+            repaint: false,
+            pointer: None,
+            route: InputRoute::new(false),
+            snapshots,
+            hotkey: SnapshotHotkey::default(),
+            filter_keys: FilterHotkeys::default(),
+            special: SpecialKeyMenu::default(),
+            settings: SettingsOverlay::default(),
+        }
+    }
+
+    /// Send pointer moves with `pointer` rather than over the control
+    /// connection.
+    fn with_pointer(mut self, pointer: Option<PointerSender>) -> Self {
+        self.route = InputRoute::new(pointer.is_some());
+        self.pointer = pointer;
+        self
+    }
+
+    /// Look for stalls at a stream of `fps` frames a second.
+    fn with_fps(mut self, fps: u8) -> Self {
+        self.stall.set_fps(fps);
+        self
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width, height);
+        self.renderer.resize(width, height);
+        self.viewport = Viewport::letterbox(width, height, self.remote.0, self.remote.1);
+        self.repaint = true;
+    }
+
+    /// Hand the settings or the special-keys menu, whichever is open, to
+    /// the renderer.
+    fn show_menu(&mut self, config: &GuiConfig) {
+        self.renderer.set_menu(self.settings.panel(config).or_else(|| self.special.panel()));
+        self.repaint = true;
+    }
+
+    /// Draw a new frame or status change, or redraw the last frame when
+    /// the window or the overlay asks for it.
+    fn draw(&mut self, overlay_changed: bool) {
+        // A secure desktop on the slave (UAC prompt, lock screen) stops
+        // the stream: say so instead of leaving the last frame up.
+        let mut banners_changed = false;
+        if self.status_rx.has_changed().unwrap_or(false) {
+            let status = self.status_rx.borrow_and_update().clone();
+            self.status_panel = StatusPanel::for_status(&status);
+            let banner = Banner::for_status(&status);
+            if self.status_panel.is_some() || banner.is_some() {
+                warn!("slave: {}", status.message());
+            } else if self.banner.is_some() {
+                info!("input reaches the slave again");
+            } else {
+                info!("slave screen is live again");
+            }
+            self.banner = banner;
+            banners_changed = true;
+        }
+        // Thumbnails come with the foreground window, refreshed with each.
+        if self.low_bandwidth_rx.has_changed().unwrap_or(false) {
+            let low_bandwidth =
+                self.low_bandwidth_rx.borrow_and_update().as_ref().map(Banner::low_bandwidth);
+            match (&self.low_bandwidth, &low_bandwidth) {
+                (None, Some(_)) => warn!("{}: low bandwidth, showing thumbnails", self.name),
+                (Some(_), None) => info!("{}: full frames again", self.name),
+                _ => {}
+            }
+            self.low_bandwidth = low_bandwidth;
+            banners_changed = true;
+        }
+        if banners_changed {
+            let banner = Banner::combine(self.low_bandwidth.clone(), self.banner.clone());
+            self.renderer.set_banner(banner);
+            self.repaint = true;
+        }
+
+        let (width, height) = self.remote;
+        if let Some(panel) = &self.status_panel {
+            if (self.repaint || overlay_changed)
+                && let Err(e) = self.renderer.render_status(panel)
+            {
+                warn!("render error: {e}");
+            }
+        } else if let Some(frame) = self.next_frame() {
+            self.last_frame = frame;
+            let (frame_width, frame_height) = (self.last_frame.width, self.last_frame.height);
+            if frame_width > 0 && frame_height > 0 && (frame_width, frame_height) != self.remote {
+                info!(
+                    "remote resolution changed: {width}x{height} -> {frame_width}x{frame_height}"
+                );
+                self.remote = (frame_width, frame_height);
+                self.viewport =
+                    Viewport::letterbox(self.size.0, self.size.1, frame_width, frame_height);
+            }
+
+            let (width, height) = self.remote;
+            let render_start = std::time::Instant::now();
+            let frame_number = self.last_frame.frame_number;
+            let dirty = self.last_frame.dirty_since(self.presented);
+            let rendered = tracing::debug_span!("render", frame_number)
+                .in_scope(|| self.renderer.render(&self.last_frame.buffer, width, height, dirty));
+            telemetry::stage_duration(Role::Viewer, "render", render_start.elapsed());
+            self.presented = rendered.is_ok().then_some(self.last_frame.seq);
+            if let Err(e) = rendered {
+                warn!("render error: {e}");
+            }
+        } else if (self.repaint || overlay_changed)
+            && let Err(e) = self.renderer.render(&self.last_frame.buffer, width, height, None)
+        {
+            warn!("render error: {e}");
+        }
+        self.repaint = false;
+        self.watch_stall(banners_changed);
+    }
+
+    /// Look for a stall and show or clear the hint, which is drawn on
+    /// the next pass. `interrupted` says the slave's status just changed.
+    fn watch_stall(&mut self, interrupted: bool) {
+        let now = std::time::Instant::now();
+        let received = self.screen_traffic.received();
+        // Secure desktops and thumbnails stop frames on purpose, and a
+        // status datagram is no part of a frame.
+        if interrupted || self.status_panel.is_some() || self.low_bandwidth.is_some() {
+            self.stall.interrupt(received, now);
+        } else if self.stall.tick(received, now) {
+            warn!("{}: stream stalled, holding the last frame", self.name);
+        }
+        let hint = self.stall.hint(now);
+        if hint != self.stall_hint {
+            self.renderer.set_stall(hint.clone());
+            self.stall_hint = hint;
+            self.repaint = true;
+        }
+    }
+
+    /// The frame to draw now, if any: the newest received one, or with
+    /// pacing on, the newest that has come due.
+    fn next_frame(&mut self) -> Option<TimedFrame> {
+        if self.frame_rx.has_changed().unwrap_or(false) {
+            let frame = self.frame_rx.borrow_and_update().clone();
+            // Thumbnails are not numbered like frames.
+            if self.low_bandwidth.is_none()
+                && let Some(lasted) = self.stall.frame(
+                    frame.frame_number,
+                    self.screen_traffic.received(),
+                    frame.arrival_ts,
+                )
+            {
+                info!("{}: stream back after {}", self.name, format_duration(lasted));
+            }
+            match &mut self.pacer {
+                Some(pacer) => {
+                    pacer.offer(frame);
+                }
+                None => return Some(frame),
+            }
+        }
+        self.pacer.as_mut()?.due(std::time::Instant::now())
+    }
+
+    /// Stop the client and close the window.
+    async fn shutdown(self) {
+        self.client_handle.abort();
+        let _ = self.client_handle.await;
+    }
+}
+
+// ── Connecting ───────────────────────────────────────────────────
+
+/// Connect to the configured target. If that fails, keep retrying
+/// (`retry_forever`) or run the connect dialog until an attempt works.
+/// `Ok(None)` means the user closed the window or quit the dialog.
+async fn establish(
+    window: &NativeWindow,
+    renderer: &mut DisplayRenderer,
+    config: &mut GuiConfig,
+    config_path: &Path,
+    udp: &UdpSocket,
+    retry_forever: bool,
+) -> Result<Option<SlaveConnection>, Box<dyn std::error::Error>> {
+    let error = match SlaveConnection::connect(config, udp).await {
+        Ok(conn) => return Ok(Some(conn)),
+        Err(e) => e.to_string(),
+    };
+    let first_address = config.network.target_address().to_string();
+    warn!("cannot connect to {first_address}: {error}");
+
+    if retry_forever {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let delay = retry_delay(attempt);
+            window.set_title(&format!(
+                "TIX Remote Desktop - waiting for {first_address} (retry in {}s)",
+                delay.as_secs()
+            ));
+            let deadline = tokio::time::Instant::now() + delay;
+            while tokio::time::Instant::now() < deadline {
+                if window.poll_events().iter().any(|ev| matches!(ev, WindowEvent::Close)) {
+                    return Ok(None);
+                }
+                tokio::time::sleep(DIALOG_TICK).await;
+            }
+            match SlaveConnection::connect(config, udp).await {
+                Ok(conn) => return Ok(Some(conn)),
+                Err(e) => warn!("retry {attempt} failed: {e}"),
+            }
+        }
+    }
+
+    window.set_title("TIX Remote Desktop - not connected");
+    let mut dialog = ConnectDialog::new(&first_address, error);
+    let mut tick = tokio::time::interval(DIALOG_TICK);
+    loop {
+        tick.tick().await;
+        let mut action = DialogAction::None;
+        for ev in window.poll_events() {
+            if let WindowEvent::Resize(w, h) = ev {
+                renderer.resize(w, h);
+            }
+            if action == DialogAction::None {
+                action = dialog.handle_event(&ev);
+            }
+        }
+
+        match action {
+            DialogAction::None => {}
+            DialogAction::Quit => return Ok(None),
+            DialogAction::Retry(address) => {
+                info!("retrying {address}");
+                config.network.set_target_address(address);
+                dialog.start_connecting();
+                let attempt = SlaveConnection::connect(config, udp);
+                tokio::pin!(attempt);
+                let result = loop {
+                    tokio::select! {
+                        result = &mut attempt => break Some(result),
+                        _ = tick.tick() => {
+                            let mut quit = false;
+                            for ev in window.poll_events() {
+                                if let WindowEvent::Resize(w, h) = ev {
+                                    renderer.resize(w, h);
+                                }
+                                quit |= dialog.handle_event(&ev) == DialogAction::Quit;
+                            }
+                            if quit {
+                                break None;
+                            }
+                            dialog.tick();
+                            draw_dialog(renderer, &dialog);
+                        }
+                    }
+                };
+                match result {
+                    None => return Ok(None),
+                    Some(Ok(conn)) => {
+                        if config.network.target_address() != first_address {
+                            match GuiConfig::persist_address(config_path, &config.network) {
+                                Ok(()) => info!(
+                                    "saved {} to {}",
+                                    config.network.target_address(),
+                                    config_path.display()
+                                ),
+                                Err(e) => warn!("cannot save address: {e}"),
+                            }
+                        }
+                        return Ok(Some(conn));
+                    }
+                    Some(Err(e)) => {
+                        warn!("retry failed: {e}");
+                        dialog.failed(e.to_string());
+                    }
+                }
+            }
+        }
+        draw_dialog(renderer, &dialog);
+    }
+}
+
+fn draw_dialog(renderer: &DisplayRenderer, dialog: &ConnectDialog) {
+    if let Err(e) = renderer.render_dialog(dialog) {
+        tracing::debug!("dialog render error: {e}");
+    }
+}
@@ -0,0 +1,341 @@
+//! Presentation pacing for received frames.
+//!
+//! The slave captures at an even rate, but frames arrive with network
+//! and decode jitter; drawing each one the moment it lands makes steady
+//! motion stutter. A [`Pacer`] holds every frame until
+//!
+//! ```text
+//! capture_ts + offset + latency
+//! ```
+//!
+//! on the local clock. `offset` is the smallest arrival-minus-capture
+//! gap seen recently, so the two machines' clocks are never compared
+//! directly: the fastest frames define "no delay". `latency` is the 95th
+//! percentile of how much later than that the recent frames landed,
+//! capped by `max_latency`, so nearly every frame is shown exactly one
+//! capture interval after the previous one. A frame captured before
+//! one already accepted would go backwards and is dropped.
+//!
+//! Frames without a capture time (slaves that predate it) are shown as
+//! they arrive.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use tix_core::rdp::client::TimedFrame;
+
+/// Gaps remembered for the offset and percentile.
+pub const WINDOW: usize = 120;
+
+/// Share of recent frames that should arrive in time to be shown
+/// evenly.
+pub const PERCENTILE: f64 = 0.95;
+
+/// A capture time this far behind the last one means the slave started
+/// a new stream clock, not that a frame was late.
+const CLOCK_RESET: Duration = Duration::from_secs(2);
+
+/// Frames held at most; older ones are dropped past this.
+const MAX_PENDING: usize = 8;
+
+/// Holds received frames until their presentation time.
+#[derive(Debug)]
+pub struct Pacer {
+    max_latency: Duration,
+    /// Local instant arrivals are measured from.
+    anchor: Option<Instant>,
+    /// Recent arrival-minus-capture gaps in µs, oldest first.
+    gaps: VecDeque<i64>,
+    /// Current offset (smallest gap) in µs.
+    offset: i64,
+    latency: Duration,
+    /// Accepted frames by presentation time.
+    pending: VecDeque<(Instant, TimedFrame)>,
+    /// Capture time of the newest accepted frame.
+    last_capture: Option<Duration>,
+    dropped: u64,
+}
+
+impl Pacer {
+    /// A pacer that never adds more than `max_latency`.
+    pub fn new(max_latency: Duration) -> Self {
+        Self {
+            max_latency,
+            anchor: None,
+            gaps: VecDeque::with_capacity(WINDOW),
+            offset: 0,
+            latency: Duration::ZERO,
+            pending: VecDeque::new(),
+            last_capture: None,
+            dropped: 0,
+        }
+    }
+
+    /// Accept `frame` and return when it should be shown, or `None` if
+    /// it was dropped for arriving out of order.
+    pub fn offer(&mut self, frame: TimedFrame) -> Option<Instant> {
+        let arrival = frame.arrival_ts;
+        let Some(capture) = frame.capture_ts else {
+            self.dropped += self.pending.len() as u64;
+            self.pending.clear();
+            self.pending.push_back((arrival, frame));
+            return Some(arrival);
+        };
+
+        if let Some(last) = self.last_capture
+            && capture <= last
+        {
+            if last - capture < CLOCK_RESET {
+                self.dropped += 1;
+                return None;
+            }
+            self.reset();
+        }
+
+        let anchor = *self.anchor.get_or_insert(arrival);
+        let since_anchor = arrival.saturating_duration_since(anchor).as_micros() as i64;
+        let gap = since_anchor - capture.as_micros() as i64;
+        if self.gaps.len() == WINDOW {
+            self.gaps.pop_front();
+        }
+        self.gaps.push_back(gap);
+        self.retune();
+
+        let target = capture.as_micros() as i64 + self.offset;
+        let mut present_at = anchor + Duration::from_micros(target.max(0) as u64) + self.latency;
+        // Shown no earlier than it arrived, and never before a frame
+        // accepted earlier.
+        present_at = present_at.max(arrival);
+        if let Some((previous, _)) = self.pending.back() {
+            present_at = present_at.max(*previous);
+        }
+
+        if self.pending.len() == MAX_PENDING {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+        self.pending.push_back((present_at, frame));
+        self.last_capture = Some(capture);
+        Some(present_at)
+    }
+
+    /// The newest frame due by `now`. Older frames that are also due
+    /// are skipped: they were not shown in time.
+    pub fn due(&mut self, now: Instant) -> Option<TimedFrame> {
+        let mut newest = None;
+        while let Some((at, _)) = self.pending.front() {
+            if *at > now {
+                break;
+            }
+            if newest.is_some() {
+                self.dropped += 1;
+            }
+            newest = self.pending.pop_front().map(|(_, frame)| frame);
+        }
+        newest
+    }
+
+    /// When the next held frame is due.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.pending.front().map(|(at, _)| *at)
+    }
+
+    /// How much later than the fastest frames every frame is shown.
+    pub fn added_latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Frames received but never shown.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Recompute the offset and latency from the remembered gaps.
+    fn retune(&mut self) {
+        self.offset = self.gaps.iter().copied().min().unwrap_or(0);
+        let mut late: Vec<i64> = self.gaps.iter().map(|g| g - self.offset).collect();
+        late.sort_unstable();
+        let rank = ((late.len() as f64 * PERCENTILE).ceil() as usize).clamp(1, late.len());
+        let latency = Duration::from_micros(late[rank - 1] as u64);
+        self.latency = latency.min(self.max_latency);
+    }
+
+    /// Forget everything learnt about the stream clock.
+    fn reset(&mut self) {
+        self.anchor = None;
+        self.gaps.clear();
+        self.offset = 0;
+        self.latency = Duration::ZERO;
+        self.dropped += self.pending.len() as u64;
+        self.pending.clear();
+        self.last_capture = None;
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_micros(16_667);
+    const MAX: Duration = Duration::from_millis(100);
+
+    fn frame(number: u64, capture: Option<Duration>, arrival: Instant) -> TimedFrame {
+        TimedFrame {
+            frame_number: number,
+            capture_ts: capture,
+            arrival_ts: arrival,
+            ..TimedFrame::default()
+        }
+    }
+
+    /// Deterministic jitter in `0..max_ms` milliseconds.
+    fn jitter(seed: &mut u64, max_ms: u64) -> Duration {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        Duration::from_micros((*seed >> 33) % (max_ms * 1000).max(1))
+    }
+
+    /// Feed `count` frames captured `INTERVAL` apart, with the slave's
+    /// clock `skew` ahead of ours and 5 ms plus jitter of transit, and
+    /// return the scheduled presentation times.
+    fn run(pacer: &mut Pacer, count: u64, max_jitter_ms: u64) -> Vec<Instant> {
+        let start = Instant::now();
+        let skew = Duration::from_secs(3600);
+        let mut seed = 7;
+        (0..count)
+            .map(|n| {
+                let capture = skew + INTERVAL * n as u32;
+                let transit = Duration::from_millis(5) + jitter(&mut seed, max_jitter_ms);
+                let arrival = start + INTERVAL * n as u32 + transit;
+                pacer.offer(frame(n, Some(capture), arrival)).unwrap()
+            })
+            .collect()
+    }
+
+    fn intervals(times: &[Instant]) -> Vec<Duration> {
+        times.windows(2).map(|w| w[1] - w[0]).collect()
+    }
+
+    #[test]
+    fn steady_arrivals_add_no_latency() {
+        let mut pacer = Pacer::new(MAX);
+        let times = run(&mut pacer, 60, 0);
+        assert_eq!(pacer.added_latency(), Duration::ZERO);
+        assert!(intervals(&times).iter().all(|d| *d == INTERVAL));
+    }
+
+    #[test]
+    fn jittery_arrivals_are_shown_evenly() {
+        let mut pacer = Pacer::new(MAX);
+        let times = run(&mut pacer, 600, 20);
+
+        // Once the window has seen the jitter, the latency sits near its
+        // 95th percentile and nearly every frame is one interval apart.
+        let latency = pacer.added_latency();
+        assert!(latency > Duration::from_millis(15), "{latency:?}");
+        assert!(latency < Duration::from_millis(20), "{latency:?}");
+        let steady = intervals(&times[WINDOW..]);
+        let even = steady
+            .iter()
+            .filter(|d| d.abs_diff(INTERVAL) < Duration::from_millis(1))
+            .count();
+        assert!(even * 100 >= steady.len() * 90, "{even} of {}", steady.len());
+        // Never out of order.
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn unpaced_arrivals_are_uneven() {
+        // The same sequence shown on arrival, for contrast.
+        let start = Instant::now();
+        let mut seed = 7;
+        let arrivals: Vec<Instant> = (0..600u32)
+            .map(|n| start + INTERVAL * n + jitter(&mut seed, 20))
+            .collect();
+        let even = intervals(&arrivals[WINDOW..])
+            .iter()
+            .filter(|d| d.abs_diff(INTERVAL) < Duration::from_millis(1))
+            .count();
+        assert!(even * 100 < 20 * (600 - WINDOW), "{even}");
+    }
+
+    #[test]
+    fn latency_is_capped() {
+        let mut pacer = Pacer::new(Duration::from_millis(10));
+        run(&mut pacer, 300, 50);
+        assert_eq!(pacer.added_latency(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn late_frames_are_shown_on_arrival() {
+        let mut pacer = Pacer::new(MAX);
+        let start = Instant::now();
+        for n in 0..10u32 {
+            pacer.offer(frame(n as u64, Some(INTERVAL * n), start + INTERVAL * n));
+        }
+        // Held up 300 ms, well past the cap: shown as soon as it lands.
+        let arrival = start + INTERVAL * 10 + Duration::from_millis(300);
+        let at = pacer.offer(frame(10, Some(INTERVAL * 10), arrival)).unwrap();
+        assert_eq!(at, arrival);
+    }
+
+    #[test]
+    fn out_of_order_frames_are_dropped() {
+        let mut pacer = Pacer::new(MAX);
+        let start = Instant::now();
+        assert!(pacer.offer(frame(1, Some(INTERVAL), start)).is_some());
+        assert!(pacer.offer(frame(3, Some(INTERVAL * 3), start + INTERVAL)).is_some());
+        // Frame 2 overtaken by frame 3, and a duplicate of 3.
+        assert!(pacer.offer(frame(2, Some(INTERVAL * 2), start + INTERVAL)).is_none());
+        assert!(pacer.offer(frame(3, Some(INTERVAL * 3), start + INTERVAL)).is_none());
+        assert_eq!(pacer.dropped(), 2);
+    }
+
+    #[test]
+    fn new_stream_clock_starts_over() {
+        let mut pacer = Pacer::new(MAX);
+        let start = Instant::now();
+        let late = Duration::from_secs(60);
+        pacer.offer(frame(1, Some(late), start));
+        let at = pacer.offer(frame(2, Some(Duration::from_millis(1)), start + INTERVAL));
+        assert_eq!(at, Some(start + INTERVAL));
+        assert_eq!(pacer.added_latency(), Duration::ZERO);
+    }
+
+    #[test]
+    fn due_returns_the_newest_frame_in_time() {
+        let mut pacer = Pacer::new(MAX);
+        let start = Instant::now();
+        for n in 0..3u32 {
+            pacer.offer(frame(n as u64, Some(INTERVAL * n), start + INTERVAL * n));
+        }
+        assert_eq!(pacer.next_due(), Some(start));
+        assert_eq!(pacer.due(start).unwrap().frame_number, 0);
+        assert!(pacer.due(start).is_none());
+        // Both remaining frames are due: only the newer one is shown.
+        let shown = pacer.due(start + INTERVAL * 5).unwrap();
+        assert_eq!(shown.frame_number, 2);
+        assert_eq!(pacer.dropped(), 1);
+        assert_eq!(pacer.next_due(), None);
+    }
+
+    #[test]
+    fn frames_without_capture_time_are_shown_at_once() {
+        let mut pacer = Pacer::new(MAX);
+        let start = Instant::now();
+        assert_eq!(pacer.offer(frame(1, None, start)), Some(start));
+        assert_eq!(pacer.due(start).unwrap().frame_number, 1);
+    }
+
+    #[test]
+    fn a_stalled_renderer_keeps_only_recent_frames() {
+        let mut pacer = Pacer::new(MAX);
+        let start = Instant::now();
+        for n in 0..20u32 {
+            pacer.offer(frame(n as u64, Some(INTERVAL * n), start + INTERVAL * n));
+        }
+        assert_eq!(pacer.dropped(), 20 - MAX_PENDING as u64);
+        assert_eq!(pacer.due(start + INTERVAL * 20).unwrap().frame_number, 19);
+    }
+}
@@ -0,0 +1,673 @@
+//! Frame post-processing between the decoder and the window.
+//!
+//! Every decoded BGRA8 frame goes through a [`PostProcessing`] pipeline
+//! before [`DisplayRenderer`](crate::display::DisplayRenderer) draws
+//! it: the colour filters that are switched on, then a scale to the
+//! letterboxed viewport, so the renderer blits the result 1:1.
+//!
+//! * Scaling is `display.scale_filter`: `nearest` keeps text crisp at
+//!   integer zoom, `bilinear` (the default) smooths everything else.
+//! * Colour filters are `display.filters`: `grayscale` and
+//!   `night-light` (a warm tint for long sessions). Ctrl+Alt+G and
+//!   Ctrl+Alt+N switch them at runtime (see [`FilterHotkeys`]).
+//!
+//! All stages run on the CPU in integer arithmetic over whole rows, a
+//! shape the compiler vectorises; `benches/postprocess.rs` times them
+//! against a 60 fps frame budget.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::window::WindowEvent;
+
+/// Colour temperature of the night-light filter, in kelvin.
+pub const NIGHT_LIGHT_KELVIN: u32 = 3400;
+
+/// Virtual-key codes of the filter hotkeys, pressed with Ctrl+Alt.
+pub const GRAYSCALE_VK: u16 = 0x47; // G
+pub const NIGHT_LIGHT_VK: u16 = 0x4E; // N
+
+/// `VK_CONTROL` and its left / right variants.
+const CONTROL_VKS: [u16; 3] = [0x11, 0xA2, 0xA3];
+/// `VK_MENU` (Alt) and its left / right variants.
+const ALT_VKS: [u16; 3] = [0x12, 0xA4, 0xA5];
+
+// ── Stages ───────────────────────────────────────────────────────
+
+/// One stage of the pipeline, over BGRA8 images.
+pub trait FramePostProcessor: Send {
+    /// Size of the output for a `width × height` input.
+    fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        (width, height)
+    }
+
+    /// Process `src`, a `width × height` image, into `dst`, which holds
+    /// exactly [`output_size`](Self::output_size) pixels.
+    fn process(&self, src: &[u8], width: u32, height: u32, dst: &mut [u8]);
+}
+
+/// Scale to `width × height` by taking the source pixel under each
+/// output pixel's centre.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NearestScale {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FramePostProcessor for NearestScale {
+    fn output_size(&self, _width: u32, _height: u32) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn process(&self, src: &[u8], width: u32, height: u32, dst: &mut [u8]) {
+        let (sw, sh) = (width as u64, height as u64);
+        let (dw, dh) = (self.width as u64, self.height as u64);
+        let columns: Vec<usize> = (0..dw)
+            .map(|x| ((2 * x + 1) * sw / (2 * dw)) as usize * 4)
+            .collect();
+        let src_stride = width as usize * 4;
+        for (y, row) in dst.chunks_exact_mut(self.width as usize * 4).enumerate() {
+            let sy = ((2 * y as u64 + 1) * sh / (2 * dh)) as usize;
+            let src_row = &src[sy * src_stride..][..src_stride];
+            for (out, &sx) in row.chunks_exact_mut(4).zip(&columns) {
+                out.copy_from_slice(&src_row[sx..sx + 4]);
+            }
+        }
+    }
+}
+
+/// Scale to `width × height` by blending the four nearest source
+/// pixels, weighted to 1/256.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BilinearScale {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The two source indices an output coordinate falls between and the
+/// weight of the second, for each of `dst` output coordinates.
+fn bilinear_taps(src: u32, dst: u32) -> Vec<(usize, usize, u32)> {
+    let last = src.saturating_sub(1) as i64;
+    (0..dst as i64)
+        .map(|d| {
+            // Centre-aligned: (d + 0.5) * src / dst - 0.5, in 1/256.
+            let pos = ((2 * d + 1) * src as i64 * 256 / (2 * dst as i64) - 128).max(0);
+            let (i, frac) = (pos >> 8, (pos & 0xFF) as u32);
+            if i >= last {
+                (last as usize, last as usize, 0)
+            } else {
+                (i as usize, i as usize + 1, frac)
+            }
+        })
+        .collect()
+}
+
+impl FramePostProcessor for BilinearScale {
+    fn output_size(&self, _width: u32, _height: u32) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn process(&self, src: &[u8], width: u32, height: u32, dst: &mut [u8]) {
+        let columns = bilinear_taps(width, self.width);
+        let rows = bilinear_taps(height, self.height);
+        let (src_stride, out_stride) = (width as usize * 4, self.width as usize * 4);
+        let source_row = |y: usize| &src[y * src_stride..][..src_stride];
+        // Two source rows already scaled across, and which rows they are:
+        // each source row is scaled once however many output rows it
+        // feeds, leaving a plain blend of two rows per output row.
+        let mut upper = (usize::MAX, vec![0u8; out_stride]);
+        let mut lower = (usize::MAX, vec![0u8; out_stride]);
+        for (row, &(y0, y1, fy)) in dst.chunks_exact_mut(out_stride).zip(&rows) {
+            if upper.0 != y0 {
+                if lower.0 == y0 {
+                    std::mem::swap(&mut upper, &mut lower);
+                } else {
+                    scale_row(source_row(y0), &columns, &mut upper.1);
+                    upper.0 = y0;
+                }
+            }
+            if lower.0 != y1 {
+                scale_row(source_row(y1), &columns, &mut lower.1);
+                lower.0 = y1;
+            }
+            let fy = fy as u16;
+            for ((out, &a), &b) in row.iter_mut().zip(&upper.1).zip(&lower.1) {
+                *out = blend(a, b, fy);
+            }
+        }
+    }
+}
+
+/// One source row scaled across to `columns.len()` pixels.
+fn scale_row(src: &[u8], columns: &[(usize, usize, u32)], out: &mut [u8]) {
+    for (px, &(x0, x1, fx)) in out.chunks_exact_mut(4).zip(columns) {
+        let (a, b) = (&src[x0 * 4..][..4], &src[x1 * 4..][..4]);
+        for i in 0..4 {
+            px[i] = blend(a[i], b[i], fx as u16);
+        }
+    }
+}
+
+/// `a` moved `weight`/256 of the way to `b`, rounded. Stays within
+/// 16 bits so whole rows go through in wide vector registers.
+fn blend(a: u8, b: u8, weight: u16) -> u8 {
+    ((a as u16 * (256 - weight) + b as u16 * weight + 128) >> 8) as u8
+}
+
+/// Luma only (BT.601 weights); alpha is kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Grayscale;
+
+impl FramePostProcessor for Grayscale {
+    fn process(&self, src: &[u8], _width: u32, _height: u32, dst: &mut [u8]) {
+        for (out, px) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+            let (b, g, r) = (px[0] as u32, px[1] as u32, px[2] as u32);
+            let luma = ((77 * r + 150 * g + 29 * b + 128) >> 8) as u8;
+            out.copy_from_slice(&[luma, luma, luma, px[3]]);
+        }
+    }
+}
+
+/// Tint towards the colour of a light source at a temperature: each
+/// channel is scaled by that source's share of it, so 6600 K and above
+/// changes nothing and lower temperatures take out blue first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorTemperature {
+    /// Blue, green and red gains in 1/255.
+    gains: [u8; 3],
+}
+
+impl ColorTemperature {
+    /// The tint of a `kelvin` light source (Tanner Helland's fit of the
+    /// black-body colours).
+    pub fn new(kelvin: u32) -> Self {
+        let t = kelvin.clamp(1000, 40_000) as f64 / 100.0;
+        let red = if t <= 66.0 {
+            255.0
+        } else {
+            329.698_727_446 * (t - 60.0).powf(-0.133_204_759_2)
+        };
+        let green = if t <= 66.0 {
+            99.470_802_586_1 * t.ln() - 161.119_568_166_1
+        } else {
+            288.122_169_528_3 * (t - 60.0).powf(-0.075_514_849_2)
+        };
+        let blue = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.517_731_223_1 * (t - 10.0).ln() - 305.044_792_730_7
+        };
+        let gain = |c: f64| c.clamp(0.0, 255.0).round() as u8;
+        Self::with_gains(gain(blue), gain(green), gain(red))
+    }
+
+    /// Scale blue, green and red by `gain / 255`.
+    pub fn with_gains(blue: u8, green: u8, red: u8) -> Self {
+        Self {
+            gains: [blue, green, red],
+        }
+    }
+}
+
+impl FramePostProcessor for ColorTemperature {
+    fn process(&self, src: &[u8], _width: u32, _height: u32, dst: &mut [u8]) {
+        let [gb, gg, gr] = self.gains.map(u32::from);
+        for (out, px) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+            out.copy_from_slice(&[
+                ((px[0] as u32 * gb + 127) / 255) as u8,
+                ((px[1] as u32 * gg + 127) / 255) as u8,
+                ((px[2] as u32 * gr + 127) / 255) as u8,
+                px[3],
+            ]);
+        }
+    }
+}
+
+// ── Settings ─────────────────────────────────────────────────────
+
+/// How frames are scaled to the window (`display.scale_filter`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScaleFilter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+impl ScaleFilter {
+    pub const NAMES: [&str; 2] = ["nearest", "bilinear"];
+
+    /// The stage scaling to `width × height`.
+    pub fn to(self, width: u32, height: u32) -> Box<dyn FramePostProcessor> {
+        match self {
+            Self::Nearest => Box::new(NearestScale { width, height }),
+            Self::Bilinear => Box::new(BilinearScale { width, height }),
+        }
+    }
+}
+
+impl FromStr for ScaleFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Self::Nearest),
+            "bilinear" => Ok(Self::Bilinear),
+            _ => Err(format!("unknown scale filter '{s}'")),
+        }
+    }
+}
+
+/// A colour filter that can be switched on and off
+/// (`display.filters`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorFilter {
+    Grayscale,
+    NightLight,
+}
+
+impl ColorFilter {
+    pub const NAMES: [&str; 2] = ["grayscale", "night-light"];
+
+    /// The filter's stage.
+    pub fn stage(self) -> Box<dyn FramePostProcessor> {
+        match self {
+            Self::Grayscale => Box::new(Grayscale),
+            Self::NightLight => Box::new(ColorTemperature::new(NIGHT_LIGHT_KELVIN)),
+        }
+    }
+}
+
+impl fmt::Display for ColorFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Grayscale => write!(f, "Grayscale"),
+            Self::NightLight => write!(f, "Night light"),
+        }
+    }
+}
+
+impl FromStr for ColorFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grayscale" => Ok(Self::Grayscale),
+            "night-light" => Ok(Self::NightLight),
+            _ => Err(format!("unknown filter '{s}'")),
+        }
+    }
+}
+
+/// The colour filters switched on, applied in a fixed order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterSet(Vec<ColorFilter>);
+
+impl FilterSet {
+    pub fn new(filters: impl IntoIterator<Item = ColorFilter>) -> Self {
+        let mut set = Self::default();
+        for filter in filters {
+            if !set.contains(filter) {
+                set.toggle(filter);
+            }
+        }
+        set
+    }
+
+    pub fn contains(&self, filter: ColorFilter) -> bool {
+        self.0.contains(&filter)
+    }
+
+    /// Switch `filter` on or off; returns whether it is now on.
+    pub fn toggle(&mut self, filter: ColorFilter) -> bool {
+        if let Some(i) = self.0.iter().position(|&f| f == filter) {
+            self.0.remove(i);
+            return false;
+        }
+        self.0.push(filter);
+        self.0.sort();
+        true
+    }
+}
+
+// ── Pipeline ─────────────────────────────────────────────────────
+
+/// The colour filters and scaler frames pass through, with the two
+/// buffers the stages alternate between.
+pub struct PostProcessing {
+    scale: ScaleFilter,
+    filters: Vec<Box<dyn FramePostProcessor>>,
+    front: Vec<u8>,
+    back: Vec<u8>,
+}
+
+impl Default for PostProcessing {
+    fn default() -> Self {
+        Self::new(ScaleFilter::default(), &FilterSet::default())
+    }
+}
+
+impl PostProcessing {
+    pub fn new(scale: ScaleFilter, filters: &FilterSet) -> Self {
+        let mut post = Self {
+            scale,
+            filters: Vec::new(),
+            front: Vec::new(),
+            back: Vec::new(),
+        };
+        post.set_filters(filters);
+        post
+    }
+
+    /// Scale with `scale` from the next frame on.
+    pub fn set_scale(&mut self, scale: ScaleFilter) {
+        self.scale = scale;
+    }
+
+    /// Use `filters` from the next frame on.
+    pub fn set_filters(&mut self, filters: &FilterSet) {
+        self.filters = filters.0.iter().map(|f| f.stage()).collect();
+    }
+
+    /// `src`, a `width × height` frame, through the colour filters and
+    /// scaled to `out_width × out_height`. A frame that needs neither
+    /// comes back as it is.
+    pub fn process<'a>(
+        &'a mut self,
+        src: &'a [u8],
+        width: u32,
+        height: u32,
+        out_width: u32,
+        out_height: u32,
+    ) -> &'a [u8] {
+        let src = &src[..width as usize * height as usize * 4];
+        let scaler =
+            ((width, height) != (out_width, out_height) && out_width > 0 && out_height > 0)
+                .then(|| self.scale.to(out_width, out_height));
+        let Self {
+            filters,
+            front,
+            back,
+            ..
+        } = self;
+        let mut size = (width, height);
+        let mut in_front = false;
+        for stage in filters.iter().chain(&scaler) {
+            if in_front {
+                size = apply(stage.as_ref(), front, size, back);
+                std::mem::swap(front, back);
+            } else {
+                size = apply(stage.as_ref(), src, size, front);
+                in_front = true;
+            }
+        }
+        if in_front { front } else { src }
+    }
+}
+
+/// Run `stage` over `src` (`size`) into `dst`; returns the new size.
+fn apply(
+    stage: &dyn FramePostProcessor,
+    src: &[u8],
+    size: (u32, u32),
+    dst: &mut Vec<u8>,
+) -> (u32, u32) {
+    let (width, height) = stage.output_size(size.0, size.1);
+    dst.resize(width as usize * height as usize * 4, 0);
+    stage.process(src, size.0, size.1, dst);
+    (width, height)
+}
+
+// ── Hotkeys ──────────────────────────────────────────────────────
+
+/// What to do with a window event after [`FilterHotkeys`] has seen it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKeyUse {
+    /// Not a filter hotkey: pass it on.
+    Forward,
+    /// Part of a hotkey already handled; drop it.
+    Swallow,
+    /// Switch this filter on or off.
+    Toggle(ColorFilter),
+}
+
+/// Ctrl+Alt+G and Ctrl+Alt+N, the grayscale and night-light switches.
+/// The letter never reaches the slave; the modifiers do, as with the
+/// other local shortcuts.
+#[derive(Debug, Default)]
+pub struct FilterHotkeys {
+    ctrl: bool,
+    alt: bool,
+    /// The letter went down as part of a hotkey and has not come up.
+    held: Option<u16>,
+}
+
+impl FilterHotkeys {
+    /// Spot the filter hotkeys in one window event.
+    pub fn observe(&mut self, event: &WindowEvent) -> FilterKeyUse {
+        let WindowEvent::Key(vk, _, pressed) = *event else {
+            return FilterKeyUse::Forward;
+        };
+        if CONTROL_VKS.contains(&vk) {
+            self.ctrl = pressed;
+        }
+        if ALT_VKS.contains(&vk) {
+            self.alt = pressed;
+        }
+        if self.held == Some(vk) {
+            if !pressed {
+                self.held = None;
+            }
+            return FilterKeyUse::Swallow;
+        }
+        let filter = match vk {
+            GRAYSCALE_VK => ColorFilter::Grayscale,
+            NIGHT_LIGHT_VK => ColorFilter::NightLight,
+            _ => return FilterKeyUse::Forward,
+        };
+        if pressed && self.ctrl && self.alt {
+            self.held = Some(vk);
+            return FilterKeyUse::Toggle(filter);
+        }
+        FilterKeyUse::Forward
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width × height` image whose pixels are `px(x, y)`.
+    fn image(width: u32, height: u32, px: impl Fn(u32, u32) -> [u8; 4]) -> Vec<u8> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| px(x, y))
+            .collect()
+    }
+
+    fn run(stage: &dyn FramePostProcessor, src: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let mut dst = Vec::new();
+        apply(stage, src, (width, height), &mut dst);
+        dst
+    }
+
+    #[test]
+    fn nearest_duplicates_and_drops_pixels() {
+        let src = image(2, 2, |x, y| [(10 * (x + 2 * y)) as u8, 0, 0, 255]);
+        let up = run(
+            &NearestScale {
+                width: 4,
+                height: 4,
+            },
+            &src,
+            2,
+            2,
+        );
+        let column = |row: u32, x: u32| up[((row * 4 + x) * 4) as usize];
+        assert_eq!(
+            [column(0, 0), column(0, 1), column(0, 2), column(0, 3)],
+            [0, 0, 10, 10]
+        );
+        assert_eq!([column(3, 0), column(3, 3)], [20, 30]);
+
+        let src = image(4, 1, |x, _| [x as u8, 0, 0, 255]);
+        let down = run(
+            &NearestScale {
+                width: 2,
+                height: 1,
+            },
+            &src,
+            4,
+            1,
+        );
+        assert_eq!(down, [1, 0, 0, 255, 3, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bilinear_blends_neighbours() {
+        let src = image(2, 1, |x, _| {
+            if x == 0 {
+                [0, 0, 0, 255]
+            } else {
+                [255, 255, 255, 255]
+            }
+        });
+        let up = run(
+            &BilinearScale {
+                width: 4,
+                height: 1,
+            },
+            &src,
+            2,
+            1,
+        );
+        let blues: Vec<u8> = up.chunks(4).map(|px| px[0]).collect();
+        assert_eq!(blues, [0, 64, 191, 255]);
+        assert!(up.chunks(4).all(|px| px[3] == 255));
+
+        // Vertical taps work the same way, and a flat image stays flat.
+        let src = image(1, 2, |_, y| [0, (y * 200) as u8, 0, 0]);
+        let up = run(
+            &BilinearScale {
+                width: 1,
+                height: 4,
+            },
+            &src,
+            1,
+            2,
+        );
+        let greens: Vec<u8> = up.chunks(4).map(|px| px[1]).collect();
+        assert_eq!(greens, [0, 50, 150, 200]);
+        let flat = image(3, 3, |_, _| [7, 8, 9, 10]);
+        assert_eq!(
+            run(
+                &BilinearScale {
+                    width: 5,
+                    height: 2
+                },
+                &flat,
+                3,
+                3
+            ),
+            image(5, 2, |_, _| [7, 8, 9, 10])
+        );
+    }
+
+    #[test]
+    fn grayscale_uses_luma_and_keeps_alpha() {
+        let src = [0, 0, 255, 200, 255, 255, 255, 255, 255, 0, 0, 0];
+        let out = run(&Grayscale, &src, 3, 1);
+        assert_eq!(out, [77, 77, 77, 200, 255, 255, 255, 255, 29, 29, 29, 0]);
+    }
+
+    #[test]
+    fn color_temperature_scales_channels() {
+        let tint = ColorTemperature::with_gains(128, 200, 255);
+        let out = run(&tint, &[255, 255, 255, 9, 100, 0, 10, 9], 2, 1);
+        assert_eq!(out, [128, 200, 255, 9, 50, 0, 10, 9]);
+
+        assert_eq!(
+            ColorTemperature::new(6600),
+            ColorTemperature::with_gains(255, 255, 255)
+        );
+        let warm = ColorTemperature::new(NIGHT_LIGHT_KELVIN);
+        assert!(warm.gains[0] < warm.gains[1] && warm.gains[1] < warm.gains[2]);
+        assert_eq!(warm.gains[2], 255);
+    }
+
+    #[test]
+    fn pipeline_filters_then_scales() {
+        let src = image(2, 1, |_, _| [0, 0, 255, 255]);
+        let mut post = PostProcessing::new(ScaleFilter::Nearest, &FilterSet::default());
+        // Nothing to do: the frame itself comes back.
+        assert_eq!(post.process(&src, 2, 1, 2, 1).as_ptr(), src.as_ptr());
+        assert_eq!(post.process(&src, 2, 1, 4, 2).len(), 4 * 2 * 4);
+
+        post.set_filters(&FilterSet::new([ColorFilter::Grayscale]));
+        assert_eq!(
+            post.process(&src, 2, 1, 2, 1),
+            image(2, 1, |_, _| [77, 77, 77, 255])
+        );
+        let night = ColorTemperature::new(NIGHT_LIGHT_KELVIN);
+        post.set_filters(&FilterSet::new([
+            ColorFilter::NightLight,
+            ColorFilter::Grayscale,
+        ]));
+        let expected = run(&night, &image(4, 1, |_, _| [77, 77, 77, 255]), 4, 1);
+        assert_eq!(post.process(&src, 2, 1, 4, 1), expected);
+    }
+
+    #[test]
+    fn settings_parse_and_toggle() {
+        assert_eq!("nearest".parse(), Ok(ScaleFilter::Nearest));
+        assert!("bicubic".parse::<ScaleFilter>().is_err());
+        assert_eq!("night-light".parse(), Ok(ColorFilter::NightLight));
+
+        let mut set = FilterSet::new([ColorFilter::NightLight, ColorFilter::NightLight]);
+        assert!(set.toggle(ColorFilter::Grayscale));
+        assert_eq!(
+            set,
+            FilterSet::new([ColorFilter::Grayscale, ColorFilter::NightLight])
+        );
+        assert!(!set.toggle(ColorFilter::NightLight));
+        assert!(!set.contains(ColorFilter::NightLight));
+    }
+
+    #[test]
+    fn hotkeys_need_ctrl_and_alt() {
+        let mut keys = FilterHotkeys::default();
+        let key = |vk, pressed| WindowEvent::Key(vk, 0, pressed);
+        assert_eq!(
+            keys.observe(&key(GRAYSCALE_VK, true)),
+            FilterKeyUse::Forward
+        );
+        assert_eq!(
+            keys.observe(&key(GRAYSCALE_VK, false)),
+            FilterKeyUse::Forward
+        );
+
+        assert_eq!(keys.observe(&key(0x11, true)), FilterKeyUse::Forward);
+        assert_eq!(keys.observe(&key(0x12, true)), FilterKeyUse::Forward);
+        assert_eq!(
+            keys.observe(&key(NIGHT_LIGHT_VK, true)),
+            FilterKeyUse::Toggle(ColorFilter::NightLight)
+        );
+        // Auto-repeat and the release stay local.
+        assert_eq!(
+            keys.observe(&key(NIGHT_LIGHT_VK, true)),
+            FilterKeyUse::Swallow
+        );
+        assert_eq!(keys.observe(&key(0x12, false)), FilterKeyUse::Forward);
+        assert_eq!(
+            keys.observe(&key(NIGHT_LIGHT_VK, false)),
+            FilterKeyUse::Swallow
+        );
+        assert_eq!(
+            keys.observe(&WindowEvent::MouseWheel(120)),
+            FilterKeyUse::Forward
+        );
+    }
+}
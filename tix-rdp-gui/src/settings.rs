@@ -0,0 +1,489 @@
+//! The settings overlay: runtime changes without editing the TOML.
+//!
+//! F10 opens a list of the settings that can change during a session
+//! over the remote screen. Up / Down pick one; Left and Right step its
+//! value (flip a switch, cycle through names, move a number by its
+//! step), Enter steps it forward. A change applies at once, the way the
+//! matching hotkey would (see [`Apply`]). The last row, "Save", writes
+//! the values shown back to the config file; Esc or F10 closes the list.
+//!
+//! [`SettingsModel`] is the registry behind the list: one [`Setting`]
+//! per line of [`SettingsModel::default`], each reading and writing a
+//! [`GuiConfig`] through plain accessors. [`SettingsOverlay`] is the
+//! key handling on top of it.
+
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::config::GuiConfig;
+use crate::display::MenuPanel;
+use crate::postprocess::{ColorFilter, ScaleFilter};
+use crate::window::WindowEvent;
+
+/// Virtual-key code of the key that opens and closes the overlay (F10).
+pub const SETTINGS_VK: u16 = 0x79;
+
+const LEFT_VK: u16 = 0x25;
+const UP_VK: u16 = 0x26;
+const RIGHT_VK: u16 = 0x27;
+const DOWN_VK: u16 = 0x28;
+const ENTER_VK: u16 = 0x0D;
+const ESCAPE_VK: u16 = 0x1B;
+
+/// The TOML key of a field of [`GuiConfig`] with its `get` and `set`.
+macro_rules! field {
+    ($first:ident $(. $rest:ident)*) => {
+        (
+            concat!(stringify!($first) $(, ".", stringify!($rest))*),
+            |c: &GuiConfig| Clone::clone(&c.$first $(.$rest)*),
+            |c: &mut GuiConfig, value| c.$first $(.$rest)* = value,
+        )
+    };
+}
+
+// ── Registry ─────────────────────────────────────────────────────
+
+/// What has to happen outside the config for a changed setting to take
+/// effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Apply {
+    /// Read from the config where it is used: nothing to do.
+    Nothing,
+    /// Hand `display.scale_filter` to every renderer.
+    Scale,
+    /// Rebuild the colour filters from `display.filters`.
+    Filters,
+    /// Switch to control or view-only, as `F8` does.
+    Control,
+    /// Rebuild every window's frame pacer.
+    Pacing,
+}
+
+/// A setting's value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Switch(bool),
+    Name(String),
+    Number(u64),
+}
+
+/// How a setting is read, written and stepped.
+#[derive(Clone, Copy)]
+enum Kind {
+    Switch {
+        get: fn(&GuiConfig) -> bool,
+        set: fn(&mut GuiConfig, bool),
+    },
+    Choice {
+        names: &'static [&'static str],
+        get: fn(&GuiConfig) -> String,
+        set: fn(&mut GuiConfig, String),
+    },
+    Number {
+        range: (u64, u64),
+        step: u64,
+        unit: &'static str,
+        get: fn(&GuiConfig) -> u64,
+        set: fn(&mut GuiConfig, u64),
+    },
+}
+
+/// One entry of the registry.
+#[derive(Clone, Copy)]
+pub struct Setting {
+    /// Shown in the overlay.
+    pub label: &'static str,
+    /// Where it lives in the TOML.
+    pub key: &'static str,
+    pub apply: Apply,
+    kind: Kind,
+}
+
+/// A setting's TOML key and how to read and write it.
+pub type Field<T> = (&'static str, fn(&GuiConfig) -> T, fn(&mut GuiConfig, T));
+
+/// The range of a number setting, its step and its unit.
+pub type Steps = (RangeInclusive<u64>, u64, &'static str);
+
+impl Setting {
+    /// An on / off setting.
+    pub fn switch(label: &'static str, apply: Apply, (key, get, set): Field<bool>) -> Self {
+        Self { label, key, apply, kind: Kind::Switch { get, set } }
+    }
+
+    /// A setting holding one of `names`.
+    pub fn choice(
+        label: &'static str,
+        apply: Apply,
+        names: &'static [&'static str],
+        (key, get, set): Field<String>,
+    ) -> Self {
+        Self { label, key, apply, kind: Kind::Choice { names, get, set } }
+    }
+
+    /// A number in a range, moved a step at a time.
+    pub fn number(
+        label: &'static str,
+        apply: Apply,
+        (range, step, unit): Steps,
+        (key, get, set): Field<u64>,
+    ) -> Self {
+        let range = (*range.start(), *range.end());
+        Self { label, key, apply, kind: Kind::Number { range, step, unit, get, set } }
+    }
+
+    /// The value in `config`.
+    pub fn value(&self, config: &GuiConfig) -> Value {
+        match self.kind {
+            Kind::Switch { get, .. } => Value::Switch(get(config)),
+            Kind::Choice { get, .. } => Value::Name(get(config)),
+            Kind::Number { get, .. } => Value::Number(get(config)),
+        }
+    }
+
+    /// The value as the overlay shows it.
+    pub fn display(&self, config: &GuiConfig) -> String {
+        match (self.value(config), self.kind) {
+            (Value::Switch(on), _) => if on { "on" } else { "off" }.into(),
+            (Value::Name(name), _) => name,
+            (Value::Number(n), Kind::Number { unit, .. }) => format!("{n} {unit}"),
+            (Value::Number(n), _) => n.to_string(),
+        }
+    }
+
+    /// The values it can take, for the overlay.
+    pub fn allowed(&self) -> String {
+        match self.kind {
+            Kind::Switch { .. } => "on, off".into(),
+            Kind::Choice { names, .. } => names.join(", "),
+            Kind::Number { range: (min, max), step, .. } => format!("{min}-{max} by {step}"),
+        }
+    }
+
+    /// Move the value one step forward (or back) in `config`. Names wrap
+    /// around, numbers stop at the ends of their range. Returns whether
+    /// anything changed.
+    pub fn step(&self, config: &mut GuiConfig, forward: bool) -> bool {
+        let before = self.value(config);
+        match self.kind {
+            Kind::Switch { get, set } => set(config, !get(config)),
+            Kind::Choice { names, get, set } => {
+                let count = names.len();
+                // A name the list does not know steps onto the first.
+                let next = match names.iter().position(|&n| n == get(config)) {
+                    Some(i) if forward => (i + 1) % count,
+                    Some(i) => (i + count - 1) % count,
+                    None => 0,
+                };
+                set(config, names[next].to_string());
+            }
+            Kind::Number { range: (min, max), step, get, set, .. } => {
+                let n = get(config);
+                let next = if forward { n.saturating_add(step) } else { n.saturating_sub(step) };
+                set(config, next.clamp(min, max));
+            }
+        }
+        self.value(config) != before
+    }
+
+    /// Copy the value from `from` into `to`.
+    fn copy(&self, from: &GuiConfig, to: &mut GuiConfig) {
+        match self.kind {
+            Kind::Switch { get, set } => set(to, get(from)),
+            Kind::Choice { get, set, .. } => set(to, get(from)),
+            Kind::Number { get, set, .. } => set(to, get(from)),
+        }
+    }
+}
+
+/// The settings the overlay offers.
+pub struct SettingsModel {
+    settings: Vec<Setting>,
+}
+
+impl Default for SettingsModel {
+    fn default() -> Self {
+        use ColorFilter::{Grayscale, NightLight};
+        let gray: Field<bool> = (
+            "display.filters",
+            |c| c.display.has_filter(Grayscale),
+            |c, on| c.display.set_filter(Grayscale, on),
+        );
+        let warm: Field<bool> = (
+            "display.filters",
+            |c| c.display.has_filter(NightLight),
+            |c, on| c.display.set_filter(NightLight, on),
+        );
+        let scales = &ScaleFilter::NAMES;
+        let pacing = (0..=500, 25, "ms");
+        Self::new(vec![
+            Setting::choice("Scaling", Apply::Scale, scales, field!(display.scale_filter)),
+            Setting::switch("Grayscale", Apply::Filters, gray),
+            Setting::switch("Night light", Apply::Filters, warm),
+            Setting::switch("View-only", Apply::Control, field!(input.view_only)),
+            Setting::switch("Send mouse", Apply::Nothing, field!(input.capture_mouse)),
+            Setting::switch("Send keyboard", Apply::Nothing, field!(input.capture_keyboard)),
+            Setting::switch("Smooth pacing", Apply::Pacing, field!(performance.smooth_pacing)),
+            Setting::number("Max pacing", Apply::Pacing, pacing, field!(performance.max_pacing_ms)),
+        ])
+    }
+}
+
+impl SettingsModel {
+    pub fn new(settings: Vec<Setting>) -> Self {
+        Self { settings }
+    }
+
+    pub fn settings(&self) -> &[Setting] {
+        &self.settings
+    }
+
+    /// Write every setting's value in `config` to the file at `path`,
+    /// leaving the rest of the file as it is.
+    pub fn persist(&self, path: &Path, config: &GuiConfig) -> std::io::Result<()> {
+        GuiConfig::update_file(path, |saved| {
+            for setting in &self.settings {
+                setting.copy(config, saved);
+            }
+        })
+    }
+}
+
+// ── Overlay ──────────────────────────────────────────────────────
+
+/// What to do with a window event once the overlay has seen it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsUse {
+    /// Not for the overlay: handle it as usual.
+    Forward,
+    /// Taken by the overlay: drop it.
+    Swallow,
+    /// A setting changed in the config; make it take effect.
+    Changed(Apply),
+    /// "Save" was chosen: persist the config.
+    Save,
+}
+
+/// One window's settings overlay.
+///
+/// Like the special-keys menu, it keeps key presses local while open
+/// and lets releases through, except those of the keys it used.
+#[derive(Default)]
+pub struct SettingsOverlay {
+    model: SettingsModel,
+    open: bool,
+    /// A setting's index, or one past the last for "Save".
+    cursor: usize,
+    swallow_release: Option<u16>,
+}
+
+impl SettingsOverlay {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn model(&self) -> &SettingsModel {
+        &self.model
+    }
+
+    /// Drive the overlay from one window event. Changes are made to
+    /// `config`.
+    pub fn observe(&mut self, event: &WindowEvent, config: &mut GuiConfig) -> SettingsUse {
+        let WindowEvent::Key(vk, _, pressed) = *event else {
+            return SettingsUse::Forward;
+        };
+        if !pressed {
+            if self.swallow_release == Some(vk) || vk == SETTINGS_VK {
+                self.swallow_release = None;
+                return SettingsUse::Swallow;
+            }
+            return SettingsUse::Forward;
+        }
+        if !self.open {
+            if vk == SETTINGS_VK {
+                self.open = true;
+                self.cursor = 0;
+                return SettingsUse::Swallow;
+            }
+            return SettingsUse::Forward;
+        }
+
+        let rows = self.model.settings.len() + 1;
+        let forward = match vk {
+            UP_VK => {
+                self.cursor = (self.cursor + rows - 1) % rows;
+                return SettingsUse::Swallow;
+            }
+            DOWN_VK => {
+                self.cursor = (self.cursor + 1) % rows;
+                return SettingsUse::Swallow;
+            }
+            SETTINGS_VK | ESCAPE_VK => {
+                self.open = false;
+                self.swallow_release = Some(vk);
+                return SettingsUse::Swallow;
+            }
+            ENTER_VK | RIGHT_VK => true,
+            LEFT_VK => false,
+            _ => return SettingsUse::Swallow,
+        };
+        let Some(setting) = self.model.settings.get(self.cursor) else {
+            return if vk == ENTER_VK { SettingsUse::Save } else { SettingsUse::Swallow };
+        };
+        if setting.step(config, forward) {
+            SettingsUse::Changed(setting.apply)
+        } else {
+            SettingsUse::Swallow
+        }
+    }
+
+    /// What to draw for `config`, or `None` while the overlay is closed.
+    pub fn panel(&self, config: &GuiConfig) -> Option<MenuPanel> {
+        self.open.then(|| {
+            let mut items: Vec<String> = self
+                .model
+                .settings
+                .iter()
+                .map(|s| format!("{}: {}  ({})", s.label, s.display(config), s.allowed()))
+                .collect();
+            items.push("Save".into());
+            MenuPanel {
+                title: "Settings (Left/Right change, Esc to close)".into(),
+                items,
+                selected: self.cursor,
+            }
+        })
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(vk: u16, pressed: bool) -> WindowEvent {
+        WindowEvent::Key(vk, 0, pressed)
+    }
+
+    fn press(overlay: &mut SettingsOverlay, config: &mut GuiConfig, vk: u16) -> SettingsUse {
+        overlay.observe(&key(vk, true), config)
+    }
+
+    fn setting(label: &str) -> Setting {
+        *SettingsModel::default()
+            .settings()
+            .iter()
+            .find(|s| s.label == label)
+            .unwrap()
+    }
+
+    #[test]
+    fn entries_read_and_step_the_config() {
+        let mut config = GuiConfig::default();
+        let scaling = setting("Scaling");
+        assert_eq!(scaling.value(&config), Value::Name("bilinear".into()));
+        assert_eq!(scaling.allowed(), "nearest, bilinear");
+        // Names wrap around both ways.
+        assert!(scaling.step(&mut config, true));
+        assert_eq!(config.display.scale_filter, "nearest");
+        assert!(scaling.step(&mut config, false));
+        assert_eq!(config.display.scale_filter, "bilinear");
+
+        let night = setting("Night light");
+        assert!(night.step(&mut config, true));
+        assert_eq!(config.display.filters, ["night-light"]);
+        assert!(setting("Grayscale").step(&mut config, true));
+        assert_eq!(config.display.filters, ["grayscale", "night-light"]);
+        assert_eq!(night.display(&config), "on");
+        night.step(&mut config, false);
+        assert_eq!(config.display.filters, ["grayscale"]);
+    }
+
+    #[test]
+    fn fields_name_their_toml_keys() {
+        let model = SettingsModel::default();
+        let keys: Vec<_> = model.settings().iter().map(|s| s.key).collect();
+        assert_eq!(keys[0], "display.scale_filter");
+        assert_eq!(keys[1], "display.filters");
+        assert_eq!(keys.last(), Some(&"performance.max_pacing_ms"));
+    }
+
+    #[test]
+    fn numbers_stop_at_the_ends_of_their_range() {
+        let mut config = GuiConfig::default();
+        let pacing = setting("Max pacing");
+        assert_eq!(pacing.display(&config), "100 ms");
+        assert_eq!(pacing.allowed(), "0-500 by 25");
+        assert!(pacing.step(&mut config, true));
+        assert_eq!(config.performance.max_pacing_ms, 125);
+
+        config.performance.max_pacing_ms = 10;
+        assert!(pacing.step(&mut config, false));
+        assert_eq!(config.performance.max_pacing_ms, 0);
+        assert!(!pacing.step(&mut config, false));
+        config.performance.max_pacing_ms = 500;
+        assert!(!pacing.step(&mut config, true));
+    }
+
+    #[test]
+    fn overlay_navigates_and_reports_what_changed() {
+        let mut config = GuiConfig::default();
+        let mut overlay = SettingsOverlay::default();
+        assert_eq!(press(&mut overlay, &mut config, RIGHT_VK), SettingsUse::Forward);
+        assert_eq!(press(&mut overlay, &mut config, SETTINGS_VK), SettingsUse::Swallow);
+        assert_eq!(overlay.observe(&key(SETTINGS_VK, false), &mut config), SettingsUse::Swallow);
+        assert!(overlay.is_open());
+
+        assert_eq!(
+            press(&mut overlay, &mut config, ENTER_VK),
+            SettingsUse::Changed(Apply::Scale)
+        );
+        assert_eq!(config.display.scale_filter, "nearest");
+        press(&mut overlay, &mut config, DOWN_VK);
+        assert_eq!(
+            press(&mut overlay, &mut config, LEFT_VK),
+            SettingsUse::Changed(Apply::Filters)
+        );
+        assert!(config.display.has_filter(ColorFilter::Grayscale));
+        // Other presses stay local; releases pass.
+        assert_eq!(press(&mut overlay, &mut config, 0x41), SettingsUse::Swallow);
+        assert_eq!(overlay.observe(&key(0xA2, false), &mut config), SettingsUse::Forward);
+
+        // Up from the top wraps to "Save".
+        press(&mut overlay, &mut config, UP_VK);
+        press(&mut overlay, &mut config, UP_VK);
+        let panel = overlay.panel(&config).unwrap();
+        assert_eq!(panel.items[panel.selected], "Save");
+        assert_eq!(panel.items[0], "Scaling: nearest  (nearest, bilinear)");
+        assert_eq!(press(&mut overlay, &mut config, RIGHT_VK), SettingsUse::Swallow);
+        assert_eq!(press(&mut overlay, &mut config, ENTER_VK), SettingsUse::Save);
+
+        assert_eq!(press(&mut overlay, &mut config, ESCAPE_VK), SettingsUse::Swallow);
+        assert!(!overlay.is_open());
+        assert!(overlay.panel(&config).is_none());
+        assert_eq!(overlay.observe(&key(ESCAPE_VK, false), &mut config), SettingsUse::Swallow);
+        assert_eq!(press(&mut overlay, &mut config, ESCAPE_VK), SettingsUse::Forward);
+    }
+
+    #[test]
+    fn saving_writes_the_settings_and_keeps_the_rest() {
+        let path = std::env::temp_dir()
+            .join(format!("tix-gui-settings-{}.toml", std::process::id()));
+        std::fs::write(&path, "[display]\nwidth = 800\n").unwrap();
+
+        let mut config = GuiConfig::default();
+        config.display.filters = vec!["grayscale".into()];
+        config.performance.smooth_pacing = false;
+        // Not a setting the overlay offers: stays as the file has it.
+        config.network.slave_address = "10.9.9.9:7332".into();
+        SettingsModel::default().persist(&path, &config).unwrap();
+
+        let saved: GuiConfig = tix_core::config::load(&path).unwrap().unwrap();
+        assert_eq!(saved.display.filters, ["grayscale"]);
+        assert!(!saved.performance.smooth_pacing);
+        assert_eq!(saved.display.width, 800);
+        assert_eq!(saved.network.slave_address, "127.0.0.1:7332");
+        let _ = std::fs::remove_file(&path);
+    }
+}
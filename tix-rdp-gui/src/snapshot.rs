@@ -0,0 +1,247 @@
+//! Local screenshots of the remote screen.
+//!
+//! Ctrl+S in a viewer window saves the newest frame of that window's
+//! session as a PNG in `snapshot.dir`, at the remote resolution whatever
+//! the window's scaling. The `S` keystroke stays local; Ctrl on its own
+//! still reaches the slave, as it was pressed before the shortcut was
+//! recognisable.
+//!
+//! Files are named after the UTC time they were taken,
+//! `screenshot_20241016_153012.png`, with `-2`, `-3`, … appended when
+//! several land in the same second.
+
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use image::ImageEncoder;
+use image::codecs::png::PngEncoder;
+use tix_core::rdp::client::SnapshotFrame;
+
+use crate::window::WindowEvent;
+
+/// Virtual-key code of `S`.
+pub const SNAPSHOT_VK: u16 = 0x53;
+
+/// `VK_CONTROL` and its left / right variants.
+const CONTROL_VKS: [u16; 3] = [0x11, 0xA2, 0xA3];
+
+/// How long the outcome stays on screen.
+pub const NOTICE_DURATION: Duration = Duration::from_secs(3);
+
+// ── Hotkey ───────────────────────────────────────────────────────
+
+/// What to do with a key event once the shortcut has seen it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyUse {
+    /// Not part of the shortcut: handle it as usual.
+    Forward,
+    /// The shortcut's own key repeating or being released: drop it.
+    Swallow,
+    /// Ctrl+S was just pressed: take a snapshot.
+    Take,
+}
+
+/// Spots Ctrl+S among one window's key events.
+#[derive(Debug, Default)]
+pub struct SnapshotHotkey {
+    ctrl: bool,
+    /// `S` went down as part of the shortcut and has not come up yet.
+    held: bool,
+}
+
+impl SnapshotHotkey {
+    /// Spot Ctrl+S in one window event.
+    pub fn observe(&mut self, event: &WindowEvent) -> HotkeyUse {
+        let WindowEvent::Key(vk, _, pressed) = *event else {
+            return HotkeyUse::Forward;
+        };
+        if CONTROL_VKS.contains(&vk) {
+            self.ctrl = pressed;
+            return HotkeyUse::Forward;
+        }
+        if vk != SNAPSHOT_VK {
+            return HotkeyUse::Forward;
+        }
+        match (pressed, self.held) {
+            (true, false) if self.ctrl => {
+                self.held = true;
+                HotkeyUse::Take
+            }
+            (true, true) => HotkeyUse::Swallow,
+            (false, true) => {
+                self.held = false;
+                HotkeyUse::Swallow
+            }
+            _ => HotkeyUse::Forward,
+        }
+    }
+}
+
+// ── Saving ───────────────────────────────────────────────────────
+
+/// `frame` as a PNG file image.
+pub fn encode_png(frame: &SnapshotFrame) -> Result<Vec<u8>, String> {
+    let rgba = frame.to_rgba8().map_err(|e| e.to_string())?;
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(
+            &rgba,
+            frame.width,
+            frame.height,
+            image::ExtendedColorType::Rgba8,
+        )
+        .map_err(|e| format!("PNG encoding failed: {e}"))?;
+    Ok(png)
+}
+
+/// Write `frame` to a new file in `dir` (created if missing; empty
+/// means the working directory) and return its path. Blocks: run it
+/// off the UI loop.
+pub fn save(frame: &SnapshotFrame, dir: &Path) -> Result<PathBuf, String> {
+    let png = encode_png(frame)?;
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+
+    let stem = file_stem(unix_now());
+    for n in 1u32.. {
+        let name = match n {
+            1 => format!("{stem}.png"),
+            n => format!("{stem}-{n}.png"),
+        };
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(&png)
+                    .map_err(|e| format!("{}: {e}", path.display()))?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        }
+    }
+    unreachable!("ran out of screenshot names")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `screenshot_YYYYMMDD_HHMMSS` for `unix_secs`, in UTC.
+fn file_stem(unix_secs: u64) -> String {
+    let (days, secs) = (unix_secs / 86_400, unix_secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "screenshot_{year:04}{month:02}{day:02}_{:02}{:02}{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Gregorian date of the day `days` after 1970-01-01 (Howard Hinnant's
+/// algorithm).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::rdp::types::PixelFormat;
+
+    /// A BGRA gradient: red across, green down, blue constant.
+    fn gradient(width: u32, height: u32) -> SnapshotFrame {
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let (r, g) = (
+                    (x * 255 / (width - 1)) as u8,
+                    (y * 255 / (height - 1)) as u8,
+                );
+                data.extend_from_slice(&[0x40, g, r, 0]);
+            }
+        }
+        SnapshotFrame {
+            width,
+            height,
+            format: PixelFormat::Bgra8,
+            frame_number: 1,
+            data,
+        }
+    }
+
+    #[test]
+    fn png_decodes_to_the_same_pixels() {
+        let frame = gradient(64, 48);
+        let png = encode_png(&frame).unwrap();
+        let decoded = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(decoded.dimensions(), (64, 48));
+        for (x, y, px) in decoded.enumerate_pixels() {
+            let expected = [(x * 255 / 63) as u8, (y * 255 / 47) as u8, 0x40, 0xFF];
+            assert_eq!(px.0, expected, "pixel {x},{y}");
+        }
+    }
+
+    #[test]
+    fn saves_never_overwrite() {
+        let dir = std::env::temp_dir().join(format!("tix-snapshot-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let frame = gradient(4, 4);
+        let first = save(&frame, &dir).unwrap();
+        let second = save(&frame, &dir).unwrap();
+        assert_ne!(first, second);
+        let name = first.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(
+            name.starts_with("screenshot_") && name.ends_with(".png"),
+            "{name}"
+        );
+        assert_eq!(std::fs::read(&second).unwrap(), encode_png(&frame).unwrap());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn names_use_the_utc_time() {
+        assert_eq!(file_stem(0), "screenshot_19700101_000000");
+        assert_eq!(file_stem(1_729_092_612), "screenshot_20241016_153012");
+        assert_eq!(file_stem(951_782_400), "screenshot_20000229_000000");
+    }
+
+    #[test]
+    fn ctrl_s_is_taken_once_and_kept_local() {
+        let mut hotkey = SnapshotHotkey::default();
+        let key = |vk, pressed| WindowEvent::Key(vk, 0, pressed);
+        assert_eq!(hotkey.observe(&key(SNAPSHOT_VK, true)), HotkeyUse::Forward);
+        assert_eq!(hotkey.observe(&key(SNAPSHOT_VK, false)), HotkeyUse::Forward);
+
+        assert_eq!(hotkey.observe(&key(0x11, true)), HotkeyUse::Forward);
+        assert_eq!(hotkey.observe(&key(SNAPSHOT_VK, true)), HotkeyUse::Take);
+        // Auto-repeat does not take another.
+        assert_eq!(hotkey.observe(&key(SNAPSHOT_VK, true)), HotkeyUse::Swallow);
+        assert_eq!(hotkey.observe(&key(0x11, false)), HotkeyUse::Forward);
+        assert_eq!(hotkey.observe(&key(SNAPSHOT_VK, false)), HotkeyUse::Swallow);
+        assert_eq!(
+            hotkey.observe(&WindowEvent::MouseWheel(120)),
+            HotkeyUse::Forward
+        );
+    }
+}
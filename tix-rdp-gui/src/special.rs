@@ -0,0 +1,468 @@
+//! Key combinations the local machine keeps for itself.
+//!
+//! Ctrl+Alt+Del, Win+L and a few others are handled by the viewer's own
+//! machine and never reach its window, so they cannot be forwarded like
+//! other keys. F11 opens a menu of them instead: Up / Down pick one,
+//! Enter (or its number) sends it, Esc or F11 closes the menu.
+//! Ctrl+Alt+End sends Ctrl+Alt+Del directly, as in other remote desktop
+//! clients.
+//!
+//! Most entries go out as scripted key presses over the normal input
+//! path. Ctrl+Alt+Del cannot be injected at all, so it becomes a
+//! `SendSas` request the slave carries out itself (see
+//! [`sas_feedback`]). A slave whose `Hello` says it cannot has the entry
+//! greyed out with the reason, and Ctrl+Alt+End passes through as
+//! ordinary keys.
+
+use tix_core::protocol::screen::{KeyAction, KeyEvent, SasResponse};
+use tix_core::{Command, Packet};
+
+use crate::display::MenuPanel;
+use crate::window::WindowEvent;
+
+/// Virtual-key code of the key that opens and closes the menu (F11).
+pub const MENU_VK: u16 = 0x7A;
+
+/// `End`, which with Ctrl and Alt held stands in for `Del`.
+const END_VK: u16 = 0x23;
+const UP_VK: u16 = 0x26;
+const DOWN_VK: u16 = 0x28;
+const ENTER_VK: u16 = 0x0D;
+const ESCAPE_VK: u16 = 0x1B;
+/// `1`; the entries are numbered from here.
+const DIGIT_ONE_VK: u16 = 0x31;
+
+/// `VK_CONTROL` and its left / right variants.
+const CONTROL_VKS: [u16; 3] = [0x11, 0xA2, 0xA3];
+/// `VK_MENU` (Alt) and its left / right variants.
+const ALT_VKS: [u16; 3] = [0x12, 0xA4, 0xA5];
+
+// ── Sequences ────────────────────────────────────────────────────
+
+/// A combination the menu can send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialKeys {
+    /// The secure attention sequence, raised by the slave.
+    CtrlAltDel,
+    /// Win+L: lock the workstation.
+    Lock,
+    /// Alt+F4: close the active window.
+    CloseWindow,
+    /// PrintScreen.
+    PrintScreen,
+    /// Ctrl+Shift+Esc: Task Manager.
+    TaskManager,
+}
+
+impl SpecialKeys {
+    /// Menu order.
+    pub const ALL: [Self; 5] = [
+        Self::CtrlAltDel,
+        Self::Lock,
+        Self::CloseWindow,
+        Self::PrintScreen,
+        Self::TaskManager,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::CtrlAltDel => "Ctrl+Alt+Del",
+            Self::Lock => "Win+L (lock)",
+            Self::CloseWindow => "Alt+F4",
+            Self::PrintScreen => "PrintScreen",
+            Self::TaskManager => "Ctrl+Shift+Esc (Task Manager)",
+        }
+    }
+
+    /// Whether the slave has to raise it with `SendSas` rather than
+    /// have it injected.
+    pub fn needs_sas(self) -> bool {
+        self == Self::CtrlAltDel
+    }
+
+    /// Virtual-key codes in the order they go down.
+    pub fn keys(self) -> &'static [u16] {
+        match self {
+            Self::CtrlAltDel => &[0x11, 0x12, 0x2E],
+            Self::Lock => &[0x5B, 0x4C],
+            Self::CloseWindow => &[0x12, 0x73],
+            Self::PrintScreen => &[0x2C],
+            Self::TaskManager => &[0x11, 0x10, 0x1B],
+        }
+    }
+
+    /// The events to inject: every key pressed in order, then released
+    /// in reverse. They carry no scan code, so the slave injects them by
+    /// virtual key and extended keys such as the Windows key need no
+    /// special casing.
+    pub fn key_events(self) -> Vec<KeyEvent> {
+        let keys = self.keys();
+        let press = keys.iter().map(|&vk| (vk, KeyAction::Press));
+        let release = keys.iter().rev().map(|&vk| (vk, KeyAction::Release));
+        press
+            .chain(release)
+            .map(|(virtual_key, action)| KeyEvent {
+                virtual_key,
+                scan_code: 0,
+                action,
+                modifiers: 0,
+            })
+            .collect()
+    }
+}
+
+/// Interpret the slave's answer to a `SendSas`. `None` for any other
+/// packet.
+pub fn sas_feedback(packet: &Packet) -> Option<Result<(), String>> {
+    if packet.command().ok()? != Command::SendSas {
+        return None;
+    }
+    Some(match SasResponse::from_bytes(packet.payload()) {
+        Ok(SasResponse::Sent) => Ok(()),
+        Ok(SasResponse::Refused(refusal)) => Err(format!("Ctrl+Alt+Del refused: {refusal}")),
+        Err(e) => Err(format!("bad SendSas response: {e}")),
+    })
+}
+
+// ── Menu ─────────────────────────────────────────────────────────
+
+/// What to do with a window event once the menu has seen it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuUse {
+    /// Not for the menu: handle it as usual.
+    Forward,
+    /// Taken by the menu or a shortcut: drop it.
+    Swallow,
+    /// Send this combination.
+    Send(SpecialKeys),
+}
+
+/// One window's special-keys menu and the Ctrl+Alt+End shortcut.
+///
+/// While the menu is open, key presses stay local. Releases still go
+/// through, so a modifier held when the menu opened does not stay down
+/// on the slave.
+#[derive(Debug, Default)]
+pub struct SpecialKeyMenu {
+    open: bool,
+    cursor: usize,
+    ctrl: bool,
+    alt: bool,
+    /// Keys the menu used whose releases must not reach the slave; a
+    /// few at most, one per key held down.
+    swallow_release: Vec<u16>,
+    /// Why the slave cannot raise Ctrl+Alt+Del, when it said so.
+    sas_unavailable: Option<String>,
+}
+
+impl SpecialKeyMenu {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Turn Ctrl+Alt+Del off, saying `why`, or back on with `None`.
+    pub fn set_sas_unavailable(&mut self, why: Option<String>) {
+        self.sas_unavailable = why;
+    }
+
+    /// Whether `keys` can be sent to this slave.
+    fn available(&self, keys: SpecialKeys) -> bool {
+        !keys.needs_sas() || self.sas_unavailable.is_none()
+    }
+
+    /// Drive the menu and the Ctrl+Alt+End shortcut from one window
+    /// event.
+    pub fn observe(&mut self, event: &WindowEvent) -> MenuUse {
+        let WindowEvent::Key(vk, _, pressed) = *event else {
+            return MenuUse::Forward;
+        };
+        if CONTROL_VKS.contains(&vk) {
+            self.ctrl = pressed;
+        }
+        if ALT_VKS.contains(&vk) {
+            self.alt = pressed;
+        }
+        if !pressed {
+            if let Some(held) = self.swallow_release.iter().position(|&k| k == vk) {
+                self.swallow_release.swap_remove(held);
+                return MenuUse::Swallow;
+            }
+            if vk == MENU_VK {
+                return MenuUse::Swallow;
+            }
+            return MenuUse::Forward;
+        }
+        if !self.open {
+            return match vk {
+                MENU_VK => {
+                    self.open = true;
+                    self.cursor = 0;
+                    MenuUse::Swallow
+                }
+                END_VK if self.ctrl && self.alt && self.available(SpecialKeys::CtrlAltDel) => {
+                    self.swallow(vk);
+                    MenuUse::Send(SpecialKeys::CtrlAltDel)
+                }
+                _ => MenuUse::Forward,
+            };
+        }
+
+        let count = SpecialKeys::ALL.len();
+        match vk {
+            UP_VK => self.cursor = (self.cursor + count - 1) % count,
+            DOWN_VK => self.cursor = (self.cursor + 1) % count,
+            MENU_VK | ESCAPE_VK => self.close(vk),
+            ENTER_VK if self.available(SpecialKeys::ALL[self.cursor]) => {
+                self.close(vk);
+                return MenuUse::Send(SpecialKeys::ALL[self.cursor]);
+            }
+            _ if (DIGIT_ONE_VK..DIGIT_ONE_VK + count as u16).contains(&vk) => {
+                let keys = SpecialKeys::ALL[(vk - DIGIT_ONE_VK) as usize];
+                if self.available(keys) {
+                    self.close(vk);
+                    return MenuUse::Send(keys);
+                }
+            }
+            _ => {}
+        }
+        // The remote never saw the press, so it must not see the release.
+        self.swallow(vk);
+        MenuUse::Swallow
+    }
+
+    /// What to draw, or `None` while the menu is closed.
+    pub fn panel(&self) -> Option<MenuPanel> {
+        self.open.then(|| MenuPanel {
+            title: "Send keys (Enter to send, Esc to close)".into(),
+            items: SpecialKeys::ALL
+                .iter()
+                .enumerate()
+                .map(|(i, keys)| match &self.sas_unavailable {
+                    Some(why) if keys.needs_sas() => {
+                        format!("{}  {} ({})", i + 1, keys.label(), why)
+                    }
+                    _ => format!("{}  {}", i + 1, keys.label()),
+                })
+                .collect(),
+            selected: self.cursor,
+        })
+    }
+
+    fn close(&mut self, vk: u16) {
+        self.open = false;
+        self.swallow(vk);
+    }
+
+    /// Keep `vk`'s release from the slave.
+    fn swallow(&mut self, vk: u16) {
+        if !self.swallow_release.contains(&vk) {
+            self.swallow_release.push(vk);
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(vk: u16, pressed: bool) -> WindowEvent {
+        WindowEvent::Key(vk, 0, pressed)
+    }
+
+    fn tap(menu: &mut SpecialKeyMenu, vk: u16) -> (MenuUse, MenuUse) {
+        (menu.observe(&key(vk, true)), menu.observe(&key(vk, false)))
+    }
+
+    #[test]
+    fn sequences_press_in_order_and_release_in_reverse() {
+        let events = SpecialKeys::TaskManager.key_events();
+        let got: Vec<_> = events.iter().map(|e| (e.virtual_key, e.action)).collect();
+        assert_eq!(
+            got,
+            [
+                (0x11, KeyAction::Press),
+                (0x10, KeyAction::Press),
+                (0x1B, KeyAction::Press),
+                (0x1B, KeyAction::Release),
+                (0x10, KeyAction::Release),
+                (0x11, KeyAction::Release),
+            ]
+        );
+        assert!(events.iter().all(|e| e.scan_code == 0));
+
+        assert_eq!(SpecialKeys::Lock.keys(), [0x5B, 0x4C]);
+        assert_eq!(SpecialKeys::CloseWindow.keys(), [0x12, 0x73]);
+        assert_eq!(SpecialKeys::PrintScreen.key_events().len(), 2);
+    }
+
+    #[test]
+    fn every_sequence_lets_go_of_what_it_pressed() {
+        for keys in SpecialKeys::ALL {
+            let events = keys.key_events();
+            let (down, up) = events.split_at(keys.keys().len());
+            assert!(
+                down.iter().all(|e| e.action == KeyAction::Press),
+                "{keys:?}"
+            );
+            assert!(
+                up.iter().all(|e| e.action == KeyAction::Release),
+                "{keys:?}"
+            );
+            let mut released: Vec<_> = up.iter().map(|e| e.virtual_key).collect();
+            released.reverse();
+            assert_eq!(released, keys.keys(), "{keys:?}");
+        }
+        let sas: Vec<_> = SpecialKeys::ALL
+            .into_iter()
+            .filter(|k| k.needs_sas())
+            .collect();
+        assert_eq!(sas, [SpecialKeys::CtrlAltDel]);
+    }
+
+    #[test]
+    fn menu_navigates_and_sends_the_highlighted_entry() {
+        let mut menu = SpecialKeyMenu::default();
+        assert_eq!(
+            tap(&mut menu, MENU_VK),
+            (MenuUse::Swallow, MenuUse::Swallow)
+        );
+        assert!(menu.is_open());
+
+        // Up from the top wraps to the bottom; Down comes back.
+        menu.observe(&key(UP_VK, true));
+        assert_eq!(menu.panel().unwrap().selected, SpecialKeys::ALL.len() - 1);
+        menu.observe(&key(DOWN_VK, true));
+        menu.observe(&key(DOWN_VK, true));
+        assert_eq!(menu.panel().unwrap().selected, 1);
+        // Other presses stay local while the menu is open.
+        assert_eq!(menu.observe(&key(0x41, true)), MenuUse::Swallow);
+
+        assert_eq!(
+            tap(&mut menu, ENTER_VK),
+            (MenuUse::Send(SpecialKeys::Lock), MenuUse::Swallow)
+        );
+        assert!(!menu.is_open());
+        assert!(menu.panel().is_none());
+        assert_eq!(menu.observe(&key(0x41, true)), MenuUse::Forward);
+    }
+
+    #[test]
+    fn digits_send_and_escape_closes() {
+        let mut menu = SpecialKeyMenu::default();
+        tap(&mut menu, MENU_VK);
+        assert_eq!(
+            menu.observe(&key(DIGIT_ONE_VK + 4, true)),
+            MenuUse::Send(SpecialKeys::TaskManager)
+        );
+        assert!(!menu.is_open());
+
+        tap(&mut menu, MENU_VK);
+        assert_eq!(
+            tap(&mut menu, ESCAPE_VK),
+            (MenuUse::Swallow, MenuUse::Swallow)
+        );
+        assert!(!menu.is_open());
+        // Esc is an ordinary key again.
+        assert_eq!(
+            tap(&mut menu, ESCAPE_VK),
+            (MenuUse::Forward, MenuUse::Forward)
+        );
+    }
+
+    #[test]
+    fn releases_pass_through_an_open_menu() {
+        let mut menu = SpecialKeyMenu::default();
+        assert_eq!(menu.observe(&key(0xA2, true)), MenuUse::Forward);
+        tap(&mut menu, MENU_VK);
+        assert_eq!(menu.observe(&key(0xA2, false)), MenuUse::Forward);
+    }
+
+    #[test]
+    fn every_key_held_over_the_menu_has_its_release_swallowed() {
+        let mut menu = SpecialKeyMenu::default();
+        tap(&mut menu, MENU_VK);
+        assert_eq!(menu.observe(&key(DOWN_VK, true)), MenuUse::Swallow);
+        assert_eq!(menu.observe(&key(0x41, true)), MenuUse::Swallow);
+        assert_eq!(menu.observe(&key(ESCAPE_VK, true)), MenuUse::Swallow);
+        assert!(!menu.is_open());
+
+        // Let go in any order: the slave saw none of the presses.
+        assert_eq!(menu.observe(&key(DOWN_VK, false)), MenuUse::Swallow);
+        assert_eq!(menu.observe(&key(ESCAPE_VK, false)), MenuUse::Swallow);
+        assert_eq!(menu.observe(&key(0x41, false)), MenuUse::Swallow);
+        assert_eq!(tap(&mut menu, 0x41), (MenuUse::Forward, MenuUse::Forward));
+    }
+
+    #[test]
+    fn ctrl_alt_end_sends_ctrl_alt_del() {
+        let mut menu = SpecialKeyMenu::default();
+        assert_eq!(tap(&mut menu, END_VK), (MenuUse::Forward, MenuUse::Forward));
+
+        menu.observe(&key(0xA2, true));
+        menu.observe(&key(0x12, true));
+        assert_eq!(
+            tap(&mut menu, END_VK),
+            (MenuUse::Send(SpecialKeys::CtrlAltDel), MenuUse::Swallow)
+        );
+        assert!(!menu.is_open());
+    }
+
+    #[test]
+    fn a_slave_without_sas_greys_it_out() {
+        let mut menu = SpecialKeyMenu::default();
+        menu.set_sas_unavailable(Some("not supported by connected slave (v0.3.1)".into()));
+
+        // The shortcut is just keys again.
+        menu.observe(&key(0xA2, true));
+        menu.observe(&key(0x12, true));
+        assert_eq!(tap(&mut menu, END_VK), (MenuUse::Forward, MenuUse::Forward));
+        menu.observe(&key(0xA2, false));
+        menu.observe(&key(0x12, false));
+
+        // The entry says why and stays put when picked.
+        tap(&mut menu, MENU_VK);
+        assert_eq!(
+            menu.panel().unwrap().items[0],
+            "1  Ctrl+Alt+Del (not supported by connected slave (v0.3.1))"
+        );
+        assert_eq!(
+            tap(&mut menu, ENTER_VK),
+            (MenuUse::Swallow, MenuUse::Swallow)
+        );
+        assert_eq!(menu.observe(&key(DIGIT_ONE_VK, true)), MenuUse::Swallow);
+        assert!(menu.is_open());
+        assert_eq!(
+            menu.observe(&key(DIGIT_ONE_VK + 1, true)),
+            MenuUse::Send(SpecialKeys::Lock)
+        );
+
+        menu.set_sas_unavailable(None);
+        menu.observe(&key(0xA2, true));
+        menu.observe(&key(0x12, true));
+        assert_eq!(
+            menu.observe(&key(END_VK, true)),
+            MenuUse::Send(SpecialKeys::CtrlAltDel)
+        );
+    }
+
+    #[test]
+    fn sas_answers_become_feedback() {
+        use tix_core::protocol::screen::SasRefusal;
+
+        let sent = SasResponse::Sent.into_packet(3).unwrap();
+        assert_eq!(sas_feedback(&sent), Some(Ok(())));
+        let refused = SasResponse::Refused(SasRefusal::NotAService)
+            .into_packet(4)
+            .unwrap();
+        assert_eq!(
+            sas_feedback(&refused),
+            Some(Err(
+                "Ctrl+Alt+Del refused: the slave is not running as a service".into()
+            ))
+        );
+        let other = Packet::new_response(5, Command::ScreenMode, vec![]).unwrap();
+        assert_eq!(sas_feedback(&other), None);
+    }
+}
@@ -0,0 +1,441 @@
+//! Telling a stalled stream from a still screen.
+//!
+//! When frames stop coming the window keeps showing the last one, and a
+//! user who cannot tell that from a frozen remote machine starts
+//! clicking; the clicks queue up and all land at once when the stream
+//! recovers. A [`StallDetector`] notices the stall so the viewer can say
+//! so in a corner, dim the frame slightly and hold input back.
+//!
+//! The slave sends nothing while its screen does not change, so silence
+//! alone is not a stall. The stream has *stalled* once screen data has
+//! been arriving for more than `after` frame intervals (at the
+//! negotiated frame rate, once it is known) without a frame coming of it: datagrams are
+//! being lost, or a frame never got all of its chunks. The next frame
+//! ends the stall. Frames the slave numbered that never arrived are
+//! counted as *missed*; the viewer hid each by keeping the one before on
+//! screen.
+//!
+//! Everything here works on the timestamps and byte counts it is given,
+//! so the state machine runs the same in tests as behind a window.
+
+use std::time::{Duration, Instant};
+
+use tix_core::protocol::screen::{KeyAction, MouseEventKind};
+
+use crate::display::StallHint;
+use crate::input::InputAction;
+
+/// Frame intervals data may arrive for without a frame before the
+/// stream counts as stalled.
+pub const DEFAULT_STALL_INTERVALS: u32 = 3;
+
+/// Watches one session's frames and screen traffic for stalls.
+#[derive(Debug)]
+pub struct StallDetector {
+    /// Frame intervals data may arrive for without a frame.
+    after: u32,
+    /// How long that is at the stream's frame rate; `None` never stalls.
+    patience: Option<Duration>,
+    /// Draw the frame slightly darker while stalled.
+    dim: bool,
+    /// Hold input back while stalled.
+    pause_input: bool,
+    /// Screen bytes received as of the last look.
+    received: u64,
+    /// When data first arrived after the last frame.
+    waiting_since: Option<Instant>,
+    stalled: bool,
+    /// Number of the last frame, as stamped by the slave.
+    last_number: Option<u64>,
+    episodes: u64,
+    /// Time spent in stalls that have ended.
+    stalled_total: Duration,
+    missed: u64,
+}
+
+impl StallDetector {
+    /// A detector that stalls after `after` frame intervals; 0 never
+    /// stalls, and neither does any until [`set_fps`](Self::set_fps).
+    pub fn new(after: u32) -> Self {
+        Self {
+            after,
+            patience: None,
+            dim: false,
+            pause_input: false,
+            received: 0,
+            waiting_since: None,
+            stalled: false,
+            last_number: None,
+            episodes: 0,
+            stalled_total: Duration::ZERO,
+            missed: 0,
+        }
+    }
+
+    /// The stream runs at `fps` frames a second.
+    pub fn set_fps(&mut self, fps: u8) {
+        let interval = Duration::from_secs(1) / u32::from(fps.max(1));
+        self.patience = (self.after > 0).then(|| interval * self.after);
+    }
+
+    /// Dim the frame under the hint while stalled.
+    pub fn with_dim(mut self, dim: bool) -> Self {
+        self.dim = dim;
+        self
+    }
+
+    /// Hold input back while stalled (see [`holds_back`](Self::holds_back)).
+    pub fn with_input_paused(mut self, pause_input: bool) -> Self {
+        self.pause_input = pause_input;
+        self
+    }
+
+    /// Frame `number` arrived at `now`, when `received` screen bytes had
+    /// come in. Returns how long the stall it ended lasted.
+    pub fn frame(&mut self, number: u64, received: u64, now: Instant) -> Option<Duration> {
+        // A number that goes backwards is a new stream, not a gap.
+        if let Some(last) = self.last_number
+            && number > last
+        {
+            self.missed += number - last - 1;
+        }
+        self.last_number = Some(number);
+        self.received = received;
+        let since = self.waiting_since.take();
+        if !std::mem::take(&mut self.stalled) {
+            return None;
+        }
+        let lasted = now.saturating_duration_since(since?);
+        self.stalled_total += lasted;
+        Some(lasted)
+    }
+
+    /// Look at the screen traffic: `received` bytes so far at `now`.
+    /// Returns `true` when this call found the stream stalled.
+    pub fn tick(&mut self, received: u64, now: Instant) -> bool {
+        if received > self.received {
+            self.received = received;
+            self.waiting_since.get_or_insert(now);
+        }
+        // Nothing is held on screen before the first frame.
+        if self.stalled || self.last_number.is_none() {
+            return false;
+        }
+        let (Some(patience), Some(since)) = (self.patience, self.waiting_since) else {
+            return false;
+        };
+        if now.saturating_duration_since(since) <= patience {
+            return false;
+        }
+        self.stalled = true;
+        self.episodes += 1;
+        true
+    }
+
+    /// The stream stopped on purpose (a secure desktop, thumbnails
+    /// instead of frames): end any stall and forget the data and frame
+    /// numbers so far, so the frames that resume it are no gap.
+    pub fn interrupt(&mut self, received: u64, now: Instant) {
+        if let Some(since) = self.waiting_since.take()
+            && std::mem::take(&mut self.stalled)
+        {
+            self.stalled_total += now.saturating_duration_since(since);
+        }
+        self.received = received;
+        self.last_number = None;
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// How long the current stall has lasted, if there is one.
+    pub fn stalled_for(&self, now: Instant) -> Option<Duration> {
+        let since = self.waiting_since.filter(|_| self.stalled)?;
+        Some(now.saturating_duration_since(since))
+    }
+
+    /// Whether to keep `action` from the slave: everything but releases
+    /// while stalled with input paused. Releases still go, so nothing
+    /// pressed before the stall stays down.
+    pub fn holds_back(&self, action: &InputAction) -> bool {
+        if !self.stalled || !self.pause_input {
+            return false;
+        }
+        let release = match action {
+            InputAction::Mouse(me) => me.kind == MouseEventKind::Release,
+            InputAction::Key(ke) => ke.action == KeyAction::Release,
+        };
+        !release
+    }
+
+    /// What to draw in the corner at `now`, or `None` while frames come.
+    /// The label counts tenths of a second, so it changes ten times a
+    /// second at most.
+    pub fn hint(&self, now: Instant) -> Option<StallHint> {
+        let stalled = self.stalled_for(now)?;
+        let tenths = stalled.as_millis() / 100;
+        let mut label = format!("Stream stalled ({}.{} s)", tenths / 10, tenths % 10);
+        if self.pause_input {
+            label.push_str(" · input paused");
+        }
+        Some(StallHint {
+            label,
+            dim: self.dim,
+        })
+    }
+
+    /// Stalls so far, the current one included.
+    pub fn episodes(&self) -> u64 {
+        self.episodes
+    }
+
+    /// Time spent stalled so far, the current stall included.
+    pub fn stalled_total(&self, now: Instant) -> Duration {
+        self.stalled_total + self.stalled_for(now).unwrap_or_default()
+    }
+
+    /// Frames the slave sent that never arrived.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// For the title bar's stats line: `, stalls: 2 (3.4s), missed
+    /// frames: 17`, or nothing while there is nothing to report.
+    pub fn summary(&self, now: Instant) -> String {
+        let mut summary = String::new();
+        if self.episodes > 0 {
+            let total = tix_core::format::format_duration(self.stalled_total(now));
+            summary.push_str(&format!(", stalls: {} ({total})", self.episodes));
+        }
+        if self.missed > 0 {
+            summary.push_str(&format!(", missed frames: {}", self.missed));
+        }
+        summary
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use tix_core::protocol::screen::{KeyEvent, MouseButton, MouseEvent};
+
+    use super::*;
+
+    /// 30 fps, as a `ScreenStart` asks for by default.
+    const INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 30);
+    /// Bytes in each scripted frame.
+    const FRAME: u64 = 10_000;
+
+    fn detector(fps: u8, after: u32) -> StallDetector {
+        let mut detector = StallDetector::new(after);
+        detector.set_fps(fps);
+        detector
+    }
+
+    /// One step of a scripted stream.
+    #[derive(Clone, Copy)]
+    enum Step {
+        /// A frame with this number arrives whole.
+        Frame(u64),
+        /// Part of a frame arrives, which never completes.
+        Partial,
+        /// Nothing arrives.
+        Quiet,
+    }
+
+    /// Play `script` one frame interval per step from `start`, and return
+    /// the steps (by index) at which the detector was stalled.
+    fn play(detector: &mut StallDetector, start: Instant, script: &[Step]) -> Vec<usize> {
+        let mut received = 0;
+        let mut stalled = Vec::new();
+        for (i, step) in script.iter().enumerate() {
+            let now = start + INTERVAL * i as u32;
+            match *step {
+                Step::Frame(number) => {
+                    received += FRAME;
+                    detector.tick(received - FRAME / 2, now);
+                    detector.frame(number, received, now);
+                }
+                Step::Partial => received += FRAME / 4,
+                Step::Quiet => {}
+            }
+            detector.tick(received, now);
+            if detector.is_stalled() {
+                stalled.push(i);
+            }
+        }
+        stalled
+    }
+
+    fn frames(numbers: std::ops::Range<u64>) -> Vec<Step> {
+        numbers.map(Step::Frame).collect()
+    }
+
+    #[test]
+    fn a_still_screen_never_stalls() {
+        let mut detector = detector(30, 3);
+        // A few frames, then nothing for ten seconds but a keyframe.
+        let mut script = frames(0..5);
+        script.extend([Step::Quiet; 300]);
+        script.push(Step::Frame(5));
+        script.extend([Step::Quiet; 10]);
+        assert!(play(&mut detector, Instant::now(), &script).is_empty());
+        assert_eq!(detector.episodes(), 0);
+        assert_eq!(detector.summary(Instant::now()), "");
+    }
+
+    #[test]
+    fn data_without_frames_stalls_until_the_next_frame() {
+        let mut detector = detector(30, 3).with_dim(true);
+        let start = Instant::now();
+        let mut script = frames(0..5);
+        script.extend([Step::Partial; 10]);
+        script.extend(frames(20..25));
+        let stalled = play(&mut detector, start, &script);
+
+        // Data from step 5 on, more than three intervals of it by step 9;
+        // frame 20 at step 15 ends it.
+        assert_eq!(stalled, (9..15).collect::<Vec<_>>());
+        assert!(!detector.is_stalled());
+        assert_eq!(detector.episodes(), 1);
+        assert_eq!(detector.missed(), 15);
+        let end = start + INTERVAL * 30;
+        assert_eq!(detector.stalled_total(end), INTERVAL * 10);
+        assert_eq!(
+            detector.summary(end),
+            ", stalls: 1 (333ms), missed frames: 15"
+        );
+    }
+
+    #[test]
+    fn the_hint_counts_up_and_goes_with_the_next_frame() {
+        let mut detector = detector(30, 3).with_dim(true).with_input_paused(true);
+        let start = Instant::now();
+        detector.frame(0, 0, start);
+        assert!(!detector.tick(500, start + INTERVAL));
+        assert!(detector.hint(start + INTERVAL).is_none());
+        // Three intervals are not enough, a moment more is.
+        assert!(!detector.tick(900, start + INTERVAL * 4));
+        assert!(detector.tick(900, start + INTERVAL * 5));
+        assert!(!detector.tick(900, start + INTERVAL * 6));
+
+        let hint = detector
+            .hint(start + INTERVAL + Duration::from_millis(1_250))
+            .unwrap();
+        assert_eq!(hint.label, "Stream stalled (1.2 s) · input paused");
+        assert!(hint.dim);
+        let lasted = detector.frame(1, 1_000, start + Duration::from_secs(2));
+        assert_eq!(lasted, Some(Duration::from_secs(2) - INTERVAL));
+        assert!(detector.hint(start + Duration::from_secs(2)).is_none());
+    }
+
+    #[test]
+    fn repeated_short_stalls_are_counted_apart() {
+        let mut detector = detector(30, 3);
+        let start = Instant::now();
+        let mut script = Vec::new();
+        let mut number = 0;
+        // Two intervals of loss at a time only costs frames; five is a
+        // stall, every time.
+        for lost in [2, 5, 2, 5, 5] {
+            script.extend(frames(number..number + 3));
+            script.extend(vec![Step::Partial; lost]);
+            number += 3 + lost as u64;
+        }
+        script.extend(frames(number..number + 3));
+        let stalled = play(&mut detector, start, &script);
+
+        assert_eq!(detector.episodes(), 3);
+        assert_eq!(stalled.len(), 3);
+        assert_eq!(detector.missed(), 2 + 5 + 2 + 5 + 5);
+        let end = start + INTERVAL * script.len() as u32;
+        assert_eq!(detector.stalled_total(end), INTERVAL * 15);
+    }
+
+    #[test]
+    fn nothing_stalls_before_the_first_frame_or_when_turned_off() {
+        let start = Instant::now();
+        let mut waiting = detector(30, 3);
+        assert!(play(&mut waiting, start, &[Step::Partial; 30]).is_empty());
+
+        let mut script = frames(0..2);
+        script.extend([Step::Partial; 30]);
+        let mut unknown_rate = StallDetector::new(3);
+        assert!(play(&mut unknown_rate, start, &script).is_empty());
+
+        let mut off = detector(30, 0);
+        assert!(play(&mut off, start, &script).is_empty());
+        assert_eq!(off.missed(), 0);
+    }
+
+    #[test]
+    fn a_slower_stream_is_given_longer() {
+        let start = Instant::now();
+        let mut detector = detector(10, 3);
+        detector.frame(0, 0, start);
+        detector.tick(100, start);
+        assert!(!detector.tick(100, start + Duration::from_millis(300)));
+        assert!(detector.tick(100, start + Duration::from_millis(301)));
+    }
+
+    #[test]
+    fn an_interrupted_stream_is_not_a_stall() {
+        let start = Instant::now();
+        let mut detector = detector(30, 3);
+        detector.frame(0, 0, start);
+        detector.tick(100, start);
+        assert!(detector.tick(200, start + INTERVAL * 4));
+
+        // A secure desktop comes up: the stall ends, and its status
+        // datagram is not taken for the start of another.
+        detector.interrupt(300, start + INTERVAL * 6);
+        assert!(!detector.is_stalled());
+        assert!(!detector.tick(300, start + INTERVAL * 20));
+        assert_eq!(detector.episodes(), 1);
+        assert_eq!(detector.stalled_total(start), INTERVAL * 6);
+        // Frames resume further on without having missed anything.
+        detector.frame(40, 300, start + INTERVAL * 21);
+        assert_eq!(detector.missed(), 0);
+    }
+
+    #[test]
+    fn only_releases_get_through_a_stall() {
+        let mut detector = detector(30, 3).with_input_paused(true);
+        let key = |action| {
+            InputAction::Key(KeyEvent {
+                virtual_key: 0x41,
+                scan_code: 0,
+                action,
+                modifiers: 0,
+            })
+        };
+        let mouse = |kind| {
+            InputAction::Mouse(MouseEvent {
+                kind,
+                button: MouseButton::Left,
+                ..MouseEvent::move_to(10, 10)
+            })
+        };
+        let all = [
+            key(KeyAction::Press),
+            key(KeyAction::Release),
+            mouse(MouseEventKind::Move),
+            mouse(MouseEventKind::Press),
+            mouse(MouseEventKind::Release),
+        ];
+        assert!(all.iter().all(|a| !detector.holds_back(a)));
+
+        let start = Instant::now();
+        detector.frame(0, 0, start);
+        detector.tick(100, start);
+        detector.tick(100, start + INTERVAL * 4);
+        let held: Vec<bool> = all.iter().map(|a| detector.holds_back(a)).collect();
+        assert_eq!(held, [true, false, true, true, false]);
+
+        // Without pausing, a stall only shows.
+        detector.pause_input = false;
+        assert!(all.iter().all(|a| !detector.holds_back(a)));
+    }
+}
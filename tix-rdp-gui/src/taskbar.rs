@@ -0,0 +1,178 @@
+//! What the taskbar button shows.
+//!
+//! Each viewer window carries the TIX icon, mirrors the progress of a
+//! dropped-file upload on its taskbar button, and flashes the button
+//! when something worth looking at happens while the window is in the
+//! background: the connection to the slave is lost, the slave ends the
+//! session, or an upload finishes or fails. [`Attention`] decides what
+//! to show and when; the Win32 calls behind it are thin wrappers on
+//! [`NativeWindow`](crate::window::NativeWindow), no-ops elsewhere.
+
+use image::ImageFormat;
+
+/// The window icon, a 64×64 PNG built into the binary.
+const ICON_PNG: &[u8] = include_bytes!("../assets/tix-icon.png");
+
+/// Flashes for a lost connection or an ended session.
+pub const URGENT_FLASHES: u32 = 5;
+
+/// Flashes for a finished or failed upload.
+pub const TRANSFER_FLASHES: u32 = 2;
+
+/// Steps the taskbar progress is shown in; finer changes are not sent.
+pub const PROGRESS_STEPS: u16 = 100;
+
+/// An icon as 32-bit BGRA rows, top row first, alpha not premultiplied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Icon {
+    pub width: u32,
+    pub height: u32,
+    pub bgra: Vec<u8>,
+}
+
+/// The TIX icon, ready for `CreateIconIndirect`.
+pub fn window_icon() -> Result<Icon, String> {
+    let rgba = image::load_from_memory_with_format(ICON_PNG, ImageFormat::Png)
+        .map_err(|e| format!("window icon: {e}"))?
+        .to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let mut bgra = rgba.into_raw();
+    for pixel in bgra.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+    Ok(Icon {
+        width,
+        height,
+        bgra,
+    })
+}
+
+/// Something the user may want to come back to the window for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notable {
+    /// The control connection went dead.
+    ConnectionLost,
+    /// The slave ended the session.
+    SessionEnded,
+    /// An upload was acknowledged; `queued` more are waiting.
+    TransferFinished { queued: usize },
+    /// An upload failed or was refused.
+    TransferFailed,
+}
+
+impl Notable {
+    /// How many times to flash the taskbar button for this, `None` for
+    /// not at all. Nothing flashes while the window has the focus, and an
+    /// upload with more behind it waits for the last one.
+    pub fn flashes(&self, focused: bool) -> Option<u32> {
+        if focused {
+            return None;
+        }
+        match self {
+            Self::ConnectionLost | Self::SessionEnded => Some(URGENT_FLASHES),
+            Self::TransferFinished { queued: 0 } | Self::TransferFailed => Some(TRANSFER_FLASHES),
+            Self::TransferFinished { .. } => None,
+        }
+    }
+}
+
+/// Taskbar state across the main loop's turns.
+#[derive(Debug, Default)]
+pub struct Attention {
+    /// The step last shown, `None` when the button shows no progress.
+    shown: Option<u16>,
+    /// The control connection was dead at the last look.
+    lost: bool,
+}
+
+impl Attention {
+    /// The active upload is `fraction` done (`None`: no upload). Returns
+    /// the progress to put on the button when it differs from what is
+    /// there, `Some(None)` to clear it.
+    pub fn progress(&mut self, fraction: Option<f32>) -> Option<Option<f32>> {
+        let step = fraction.map(|f| (f.clamp(0.0, 1.0) * PROGRESS_STEPS as f32).round() as u16);
+        if step == self.shown {
+            return None;
+        }
+        self.shown = step;
+        Some(step.map(|s| s as f32 / PROGRESS_STEPS as f32))
+    }
+
+    /// The control connection is `lost` or not. Returns
+    /// [`Notable::ConnectionLost`] the first time it is seen lost.
+    pub fn connection(&mut self, lost: bool) -> Option<Notable> {
+        let was_lost = std::mem::replace(&mut self.lost, lost);
+        (lost && !was_lost).then_some(Notable::ConnectionLost)
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_follows_the_upload_and_clears_after_it() {
+        let mut attention = Attention::default();
+        assert_eq!(attention.progress(None), None);
+        assert_eq!(attention.progress(Some(0.0)), Some(Some(0.0)));
+        // Less than a step: nothing to send.
+        assert_eq!(attention.progress(Some(0.004)), None);
+        assert_eq!(attention.progress(Some(0.25)), Some(Some(0.25)));
+        assert_eq!(attention.progress(Some(0.251)), None);
+        assert_eq!(attention.progress(Some(1.5)), Some(Some(1.0)));
+        // Done or failed: the upload is gone and the button cleared, once.
+        assert_eq!(attention.progress(None), Some(None));
+        assert_eq!(attention.progress(None), None);
+        // The next upload starts from scratch.
+        assert_eq!(attention.progress(Some(0.0)), Some(Some(0.0)));
+    }
+
+    #[test]
+    fn flashes_only_in_the_background() {
+        let finished = |queued| Notable::TransferFinished { queued };
+        assert_eq!(Notable::ConnectionLost.flashes(true), None);
+        assert_eq!(finished(0).flashes(true), None);
+
+        assert_eq!(Notable::ConnectionLost.flashes(false), Some(URGENT_FLASHES));
+        assert_eq!(Notable::SessionEnded.flashes(false), Some(URGENT_FLASHES));
+        assert_eq!(
+            Notable::TransferFailed.flashes(false),
+            Some(TRANSFER_FLASHES)
+        );
+        assert_eq!(finished(0).flashes(false), Some(TRANSFER_FLASHES));
+        // More uploads behind this one: wait for the last.
+        assert_eq!(finished(2).flashes(false), None);
+    }
+
+    #[test]
+    fn a_lost_connection_is_reported_once() {
+        let mut attention = Attention::default();
+        assert_eq!(attention.connection(false), None);
+        assert_eq!(attention.connection(true), Some(Notable::ConnectionLost));
+        assert_eq!(attention.connection(true), None);
+        // Heartbeats came back, then stopped again.
+        assert_eq!(attention.connection(false), None);
+        assert_eq!(attention.connection(true), Some(Notable::ConnectionLost));
+    }
+
+    #[test]
+    fn icon_is_a_square_bgra_image_with_transparency() {
+        let icon = window_icon().unwrap();
+        assert_eq!((icon.width, icon.height), (64, 64));
+        assert_eq!(icon.bgra.len(), 64 * 64 * 4);
+        let alphas: Vec<u8> = icon.bgra.chunks_exact(4).map(|p| p[3]).collect();
+        assert!(alphas.contains(&0));
+        assert!(alphas.contains(&255));
+
+        // Red and blue swapped from the PNG's RGBA.
+        let rgba = image::load_from_memory(ICON_PNG).unwrap().to_rgba8();
+        let first_opaque = rgba.pixels().position(|p| p.0[3] == 255).unwrap();
+        let [r, g, b, a] = rgba.pixels().nth(first_opaque).unwrap().0;
+        assert_eq!(
+            &icon.bgra[first_opaque * 4..first_opaque * 4 + 4],
+            &[b, g, r, a]
+        );
+    }
+}
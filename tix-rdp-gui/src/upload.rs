@@ -0,0 +1,484 @@
+//! Drag-and-drop file upload.
+//!
+//! Files dropped onto the window are queued and sent to the slave one at
+//! a time as chunked `FileWrite` uploads over the control connection.
+//! [`UploadQueue`] keeps the bookkeeping (what is waiting, what is in
+//! flight, how far along it is) and turns slave responses into log lines;
+//! [`send_file`] does the streaming in a background task. The slave
+//! reports how much it has written as the upload goes, which paces the
+//! sender and gives the overlay its transfer rate.
+//!
+//! Dropped files are weighed against the `[paste_guard]` limits first
+//! (see [`tix_core::paste_guard`]). One too large to send is refused; a
+//! large one waits behind a prompt, one file at a time, until Enter (or
+//! `Y`) sends it or Esc (or `N`) skips it.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use tix_core::format::{format_bytes, format_rate};
+use tix_core::network::upload::{send_file_windowed, AckWindow};
+use tix_core::paste_guard::{PasteGuard, Verdict};
+use tix_core::protocol::file::DEFAULT_UPLOAD_WINDOW;
+use tix_core::protocol::{ChunkCompressor, FileTransferAck, FileWriteProgress, LimitExceeded};
+use tix_core::{Command, ConnectionSender, Packet, ProtocolFlags};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::display::{MenuPanel, ProgressOverlay};
+use crate::window::WindowEvent;
+
+const ENTER_VK: u16 = 0x0D;
+const ESCAPE_VK: u16 = 0x1B;
+const Y_VK: u16 = 0x59;
+const N_VK: u16 = 0x4E;
+
+/// Characters per line of the prompt, to fit the menu panel.
+const PROMPT_WIDTH: usize = 44;
+
+/// The upload currently being streamed.
+#[derive(Debug)]
+pub struct ActiveUpload {
+    pub request_id: u64,
+    pub name: String,
+    pub size: u64,
+    /// Bytes handed to the connection so far, updated by [`send_file`].
+    pub sent: Arc<AtomicU64>,
+    /// Bytes the slave has reported written.
+    pub written: u64,
+    pub started: Instant,
+    /// Where the slave's progress reports go, for [`send_file`]'s window.
+    acks: UnboundedSender<FileWriteProgress>,
+}
+
+/// What to do with a window event once the upload prompt has seen it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptUse {
+    /// Not for the prompt: handle it as usual.
+    Forward,
+    /// Taken by the prompt: drop it.
+    Swallow,
+    /// The prompt was answered; log this.
+    Answered(String),
+}
+
+/// Files waiting to be uploaded, in drop order.
+#[derive(Debug, Default)]
+pub struct UploadQueue {
+    pending: VecDeque<PathBuf>,
+    /// Large files waiting for a yes, with the question to ask.
+    unconfirmed: VecDeque<(PathBuf, String)>,
+    active: Option<ActiveUpload>,
+    guard: PasteGuard,
+    /// The key that answered the prompt, whose release stays local.
+    swallow_release: Option<u16>,
+}
+
+impl UploadQueue {
+    /// Weigh dropped files against `guard` rather than the default
+    /// limits.
+    pub fn with_guard(mut self, guard: PasteGuard) -> Self {
+        self.guard = guard;
+        self
+    }
+
+    /// Queue dropped paths, estimating large ones at `rate` bytes a
+    /// second. Returns a message for every path that was refused.
+    pub fn enqueue(&mut self, paths: Vec<PathBuf>, rate: Option<u64>) -> Vec<String> {
+        let mut rejected = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                rejected.push(format!(
+                    "{}: folders cannot be uploaded yet, drop the files inside it instead",
+                    path.display()
+                ));
+            } else if !path.is_file() {
+                rejected.push(format!("{}: not a readable file", path.display()));
+            } else {
+                let size = std::fs::metadata(&path).map_or(0, |m| m.len());
+                match self.guard.check(&file_name(&path), size, rate) {
+                    Verdict::Proceed => self.pending.push_back(path),
+                    Verdict::Confirm(question) => self.unconfirmed.push_back((path, question)),
+                    Verdict::Refuse(reason) => rejected.push(reason),
+                }
+            }
+        }
+        rejected
+    }
+
+    /// Whether a large file is waiting for an answer.
+    pub fn is_asking(&self) -> bool {
+        !self.unconfirmed.is_empty()
+    }
+
+    /// Answer the waiting prompt from one window event. While a file
+    /// waits for an answer, keys stay local.
+    pub fn observe(&mut self, event: &WindowEvent) -> PromptUse {
+        let WindowEvent::Key(vk, _, pressed) = *event else {
+            return PromptUse::Forward;
+        };
+        if !pressed {
+            if self.swallow_release == Some(vk) {
+                self.swallow_release = None;
+                return PromptUse::Swallow;
+            }
+            return PromptUse::Forward;
+        }
+        let send = match vk {
+            ENTER_VK | Y_VK => true,
+            ESCAPE_VK | N_VK => false,
+            _ if self.is_asking() => return PromptUse::Swallow,
+            _ => return PromptUse::Forward,
+        };
+        let Some((path, _)) = self.unconfirmed.pop_front() else {
+            return PromptUse::Forward;
+        };
+        self.swallow_release = Some(vk);
+        let name = file_name(&path);
+        if send {
+            self.pending.push_back(path);
+            PromptUse::Answered(format!("sending {name} as asked"))
+        } else {
+            PromptUse::Answered(format!("skipped {name}"))
+        }
+    }
+
+    /// The prompt for the next large file, `None` when none waits.
+    pub fn prompt(&self) -> Option<MenuPanel> {
+        let (_, question) = self.unconfirmed.front()?;
+        let items = wrap(question, PROMPT_WIDTH);
+        Some(MenuPanel {
+            title: "Large upload: Enter sends, Esc skips".into(),
+            // Nothing to pick from: no row is highlighted.
+            selected: items.len(),
+            items,
+        })
+    }
+
+    /// Files waiting behind the active upload.
+    pub fn queued(&self) -> usize {
+        self.pending.len() + self.unconfirmed.len()
+    }
+
+    /// Next file to start, if nothing is in flight.
+    pub fn next_ready(&mut self) -> Option<PathBuf> {
+        if self.active.is_some() {
+            return None;
+        }
+        self.pending.pop_front()
+    }
+
+    /// Record that `path` is now streaming under `request_id`. Returns
+    /// the progress counter and ack window for [`send_file`].
+    pub fn start(
+        &mut self,
+        request_id: u64,
+        path: &Path,
+        size: u64,
+    ) -> (Arc<AtomicU64>, AckWindow) {
+        let sent = Arc::new(AtomicU64::new(0));
+        let (acks, window) = AckWindow::channel(DEFAULT_UPLOAD_WINDOW);
+        self.active = Some(ActiveUpload {
+            request_id,
+            name: file_name(path),
+            size,
+            sent: sent.clone(),
+            written: 0,
+            started: Instant::now(),
+            acks,
+        });
+        (sent, window)
+    }
+
+    /// The upload in flight.
+    pub fn active(&self) -> Option<&ActiveUpload> {
+        self.active.as_ref()
+    }
+
+    /// Give up on the active upload (e.g. the connection failed).
+    pub fn fail_active(&mut self, reason: &str) -> Option<String> {
+        let active = self.active.take()?;
+        Some(format!("upload of {} failed: {}", active.name, reason))
+    }
+
+    /// Handle a packet from the slave. Returns a log line when it settles
+    /// the active upload, `None` when it is progress or about something
+    /// else.
+    pub fn handle_response(&mut self, packet: &Packet) -> Option<Result<String, String>> {
+        let active = self.active.as_mut()?;
+        if packet.request_id() != active.request_id {
+            return None;
+        }
+        if packet.command().ok() == Some(Command::FileWrite)
+            && packet.flags().contains(ProtocolFlags::STREAMING)
+        {
+            if let Ok(progress) = FileWriteProgress::from_bytes(packet.payload()) {
+                active.written = active.written.max(progress.bytes_written);
+                let _ = active.acks.send(progress);
+            }
+            return None;
+        }
+        let outcome = match packet.command() {
+            Ok(Command::FileWrite) => match FileTransferAck::from_bytes(packet.payload()) {
+                Ok(FileTransferAck {
+                    path,
+                    bytes_written,
+                    error: None,
+                }) => Ok(format!(
+                    "uploaded {} ({} bytes) to {}",
+                    active.name, bytes_written, path
+                )),
+                Ok(FileTransferAck {
+                    error: Some(e), ..
+                }) => Err(format!("upload of {} failed: {}", active.name, e)),
+                Err(e) => Err(format!("upload of {}: bad ack: {}", active.name, e)),
+            },
+            Ok(Command::LimitExceeded) => match LimitExceeded::from_bytes(packet.payload()) {
+                Ok(refusal) => Err(format!("upload of {} refused: {}", active.name, refusal)),
+                Err(e) => Err(format!("upload of {} refused: {}", active.name, e)),
+            },
+            _ => return None,
+        };
+        self.active = None;
+        Some(outcome)
+    }
+
+    /// How far the active upload is, 0.0 to 1.0; `None` when idle.
+    pub fn progress(&self) -> Option<f32> {
+        let active = self.active.as_ref()?;
+        if active.size == 0 {
+            return Some(1.0);
+        }
+        Some(active.sent.load(Ordering::Relaxed) as f32 / active.size as f32)
+    }
+
+    /// Progress bar for the active upload, `None` when idle.
+    pub fn overlay(&self) -> Option<ProgressOverlay> {
+        let active = self.active.as_ref()?;
+        let sent = active.sent.load(Ordering::Relaxed);
+        let fraction = self.progress()?;
+        let mut label = format!(
+            "Uploading {} - {} of {} ({:.0}%)",
+            active.name,
+            format_bytes(sent),
+            format_bytes(active.size),
+            fraction * 100.0
+        );
+        if active.written > 0 {
+            label.push_str(&format!(
+                ", {}",
+                format_rate(active.written, active.started.elapsed())
+            ));
+        }
+        if sent >= active.size {
+            label = format!("Verifying {}", active.name);
+        }
+        if !self.pending.is_empty() {
+            label.push_str(&format!(" ({} more queued)", self.pending.len()));
+        }
+        Some(ProgressOverlay { label, fraction })
+    }
+}
+
+/// Where `file_name` goes on the slave. An empty `remote_dir` sends a
+/// bare name, which the slave puts in its Downloads folder.
+pub fn remote_path(remote_dir: &str, file_name: &str) -> String {
+    if remote_dir.is_empty() {
+        return file_name.to_string();
+    }
+    if remote_dir.ends_with('\\') || remote_dir.ends_with('/') {
+        format!("{remote_dir}{file_name}")
+    } else {
+        format!("{remote_dir}\\{file_name}")
+    }
+}
+
+/// `text` broken into lines of at most `width` characters, at spaces
+/// where it can be.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Stream `local` to the slave as `remote`: header, chunks, then the
+/// hash. `sent` is bumped after every chunk so the overlay can follow,
+/// and no more than `window` allows is sent ahead of the slave.
+pub async fn send_file(
+    tx: ConnectionSender,
+    request_id: u64,
+    local: PathBuf,
+    remote: String,
+    sent: Arc<AtomicU64>,
+    mut window: AckWindow,
+) -> Result<(), String> {
+    // The viewer never exchanges a Hello, so its chunks go raw.
+    let raw = ChunkCompressor::raw();
+    send_file_windowed(&tx, request_id, &local, remote, &sent, raw, &mut window)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("tix-gui-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn directories_are_rejected_and_files_queue_in_order() {
+        let a = temp_file("a.txt", b"a");
+        let b = temp_file("b.txt", b"b");
+        let mut queue = UploadQueue::default();
+
+        let rejected = queue.enqueue(vec![a.clone(), std::env::temp_dir(), b.clone()], None);
+        assert_eq!(rejected.len(), 1);
+        assert!(rejected[0].contains("folders"));
+
+        assert_eq!(queue.next_ready(), Some(a.clone()));
+        let _ = queue.start(5, &a, 1);
+        // One at a time.
+        assert_eq!(queue.next_ready(), None);
+        assert_eq!(queue.queued(), 1);
+        assert!(queue.overlay().unwrap().label.contains("1 more queued"));
+
+        let ack = FileTransferAck {
+            path: "C:\\Users\\me\\Downloads\\a.txt".into(),
+            bytes_written: 1,
+            error: None,
+        };
+        let msg = queue
+            .handle_response(&ack.into_packet(5).unwrap())
+            .unwrap()
+            .unwrap();
+        assert!(msg.contains("uploaded"));
+        assert_eq!(queue.next_ready(), Some(b.clone()));
+
+        let _ = std::fs::remove_file(a);
+        let _ = std::fs::remove_file(b);
+    }
+
+    #[test]
+    fn refusal_and_unrelated_packets() {
+        let mut queue = UploadQueue::default();
+        let _ = queue.start(9, Path::new("big.iso"), 100);
+
+        let other = Packet::new_response(3, Command::FileWrite, Vec::new()).unwrap();
+        assert!(queue.handle_response(&other).is_none());
+
+        let refusal = LimitExceeded {
+            command: "FileWrite".into(),
+            used: 10,
+            limit: 5,
+            retry_after_secs: 60,
+        };
+        let err = queue
+            .handle_response(&refusal.into_packet(9).unwrap())
+            .unwrap()
+            .unwrap_err();
+        assert!(err.contains("refused"));
+        assert!(queue.active().is_none());
+    }
+
+    #[test]
+    fn progress_feeds_the_window_and_the_rate() {
+        let mut queue = UploadQueue::default();
+        let (sent, _window) = queue.start(4, Path::new("big.iso"), 1 << 20);
+        assert_eq!(queue.progress(), Some(0.0));
+        assert!(!queue.overlay().unwrap().label.contains("/s"));
+        sent.store(1 << 18, Ordering::Relaxed);
+        assert_eq!(queue.progress(), Some(0.25));
+
+        let progress = FileWriteProgress::new(16, 1 << 19);
+        assert!(queue
+            .handle_response(&progress.into_packet(4).unwrap())
+            .is_none());
+        assert_eq!(queue.active().unwrap().written, 1 << 19);
+        assert!(queue.overlay().unwrap().label.contains("/s"));
+
+        // The final ack still settles it.
+        let ack = FileTransferAck {
+            path: "big.iso".into(),
+            bytes_written: 0,
+            error: Some("disk full".into()),
+        };
+        let err = queue
+            .handle_response(&ack.into_packet(4).unwrap())
+            .unwrap()
+            .unwrap_err();
+        assert!(err.contains("disk full"));
+        assert_eq!(queue.progress(), None);
+    }
+
+    #[test]
+    fn large_files_wait_for_an_answer() {
+        let small = temp_file("small.bin", &[0; 10]);
+        let large = temp_file("large.bin", &[0; 500]);
+        let huge = temp_file("huge.bin", &[0; 5000]);
+        let guard = PasteGuard::new(&tix_core::paste_guard::PasteGuardConfig {
+            confirm_bytes: 100,
+            refuse_bytes: 1000,
+        });
+        let mut queue = UploadQueue::default().with_guard(guard);
+
+        let rejected = queue.enqueue(vec![large.clone(), huge.clone(), small.clone()], Some(50));
+        assert_eq!(rejected.len(), 1);
+        assert!(rejected[0].contains("over the 1000 B limit"), "{}", rejected[0]);
+        // The small one goes ahead while the large one waits.
+        assert_eq!(queue.next_ready(), Some(small.clone()));
+        let prompt = queue.prompt().unwrap();
+        assert!(prompt.items.join(" ").ends_with("is 500 B, about 10.0s at 50 B/s. Send it?"));
+        assert!(prompt.items.iter().all(|line| line.chars().count() <= PROMPT_WIDTH));
+
+        // Other keys stay local; Enter sends, and its release stays local too.
+        let key = |vk, pressed| WindowEvent::Key(vk, 0, pressed);
+        assert_eq!(queue.observe(&key(0x41, true)), PromptUse::Swallow);
+        assert_eq!(queue.observe(&WindowEvent::MouseMove(1, 1)), PromptUse::Forward);
+        assert!(matches!(queue.observe(&key(ENTER_VK, true)), PromptUse::Answered(_)));
+        assert_eq!(queue.observe(&key(ENTER_VK, false)), PromptUse::Swallow);
+        assert!(!queue.is_asking());
+        assert_eq!(queue.observe(&key(ENTER_VK, true)), PromptUse::Forward);
+        assert_eq!(queue.next_ready(), Some(large.clone()));
+
+        queue.enqueue(vec![large.clone()], None);
+        assert_eq!(
+            queue.observe(&key(ESCAPE_VK, true)),
+            PromptUse::Answered(format!("skipped {}", file_name(&large)))
+        );
+        assert_eq!(queue.next_ready(), None);
+
+        for path in [small, large, huge] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn remote_path_joins_dir() {
+        assert_eq!(remote_path("", "a.txt"), "a.txt");
+        assert_eq!(remote_path("C:\\Temp", "a.txt"), "C:\\Temp\\a.txt");
+        assert_eq!(remote_path("C:\\Temp\\", "a.txt"), "C:\\Temp\\a.txt");
+    }
+}
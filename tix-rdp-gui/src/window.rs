@@ -2,17 +2,37 @@
 //!
 //! Creates a native HWND used by the display renderer. The window
 //! produces [`WindowEvent`]s that the main loop processes for input
-//! forwarding and lifecycle management.
+//! forwarding and lifecycle management. Files dragged from Explorer are
+//! accepted through `WM_DROPFILES` and reported as
+//! [`WindowEvent::FilesDropped`]. The window carries the TIX icon and
+//! can show progress on, and flash, its taskbar button (see
+//! [`crate::taskbar`]); elsewhere those calls do nothing.
 
 #[cfg(target_os = "windows")]
 mod platform {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use std::path::PathBuf;
     use std::sync::mpsc;
 
     use windows::Win32::Foundation::*;
+    use windows::Win32::Graphics::Gdi::{CreateBitmap, DeleteObject};
+    use windows::Win32::System::Com::{
+        CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED, CoCreateInstance, CoInitializeEx,
+    };
     use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::Win32::UI::Shell::{
+        DragAcceptFiles, DragFinish, DragQueryFileW, DragQueryPoint, HDROP, ITaskbarList3,
+        TBPF_NOPROGRESS, TBPF_NORMAL, TaskbarList,
+    };
     use windows::Win32::UI::WindowsAndMessaging::*;
     use windows::core::PCWSTR;
 
+    use crate::taskbar::{self, Icon};
+
+    /// Units of `SetProgressValue`'s total.
+    const PROGRESS_TOTAL: u64 = 1000;
+
     /// Events produced by the window message loop.
     #[derive(Debug, Clone)]
     pub enum WindowEvent {
@@ -28,6 +48,12 @@ mod platform {
         MouseWheel(i16),
         /// Key down/up: virtual-key code, scan code, pressed.
         Key(u16, u16, bool),
+        /// Character typed, after keyboard layout translation. Only the
+        /// connect dialog uses it; input forwarding works on `Key`.
+        Char(char),
+        /// Files dropped from Explorer, with the client-relative drop
+        /// point.
+        FilesDropped(Vec<PathBuf>, i32, i32),
     }
 
     /// Mouse button identifiers.
@@ -44,6 +70,10 @@ mod platform {
         pub width: u32,
         pub height: u32,
         event_rx: mpsc::Receiver<WindowEvent>,
+        /// The window and taskbar icon, destroyed with the window.
+        icon: Option<HICON>,
+        /// The shell's taskbar, `None` when COM would not give it.
+        taskbar: Option<ITaskbarList3>,
     }
 
     // We store a raw pointer to the mpsc sender in GWLP_USERDATA.
@@ -120,6 +150,17 @@ mod platform {
                 let _ = tx.send(WindowEvent::Key(vk, scan, false));
                 LRESULT(0)
             }
+            WM_CHAR => {
+                if let Some(c) = char::from_u32(wparam.0 as u32) {
+                    let _ = tx.send(WindowEvent::Char(c));
+                }
+                LRESULT(0)
+            }
+            WM_DROPFILES => {
+                let hdrop = HDROP(wparam.0 as *mut _);
+                let _ = tx.send(unsafe { dropped_files(hdrop) });
+                LRESULT(0)
+            }
             WM_DESTROY => {
                 unsafe { PostQuitMessage(0) };
                 LRESULT(0)
@@ -128,6 +169,63 @@ mod platform {
         }
     }
 
+    /// Collect the paths and drop point from a `WM_DROPFILES` handle,
+    /// then release it.
+    unsafe fn dropped_files(hdrop: HDROP) -> WindowEvent {
+        unsafe {
+            let count = DragQueryFileW(hdrop, u32::MAX, None);
+            let mut paths = Vec::with_capacity(count as usize);
+            for i in 0..count {
+                let len = DragQueryFileW(hdrop, i, None) as usize;
+                let mut buf = vec![0u16; len + 1];
+                let copied = DragQueryFileW(hdrop, i, Some(&mut buf)) as usize;
+                paths.push(PathBuf::from(OsString::from_wide(&buf[..copied])));
+            }
+            let mut pt = POINT::default();
+            let _ = DragQueryPoint(hdrop, &mut pt);
+            DragFinish(hdrop);
+            WindowEvent::FilesDropped(paths, pt.x, pt.y)
+        }
+    }
+
+    /// An `HICON` from `icon`'s pixels.
+    fn create_icon(icon: &Icon) -> Result<HICON, String> {
+        unsafe {
+            let color = CreateBitmap(
+                icon.width as i32,
+                icon.height as i32,
+                1,
+                32,
+                Some(icon.bgra.as_ptr().cast()),
+            );
+            // An all-zero mask: the color bitmap's alpha decides.
+            let mask = CreateBitmap(icon.width as i32, icon.height as i32, 1, 1, None);
+            let info = ICONINFO {
+                fIcon: TRUE,
+                xHotspot: 0,
+                yHotspot: 0,
+                hbmMask: mask,
+                hbmColor: color,
+            };
+            let hicon = CreateIconIndirect(&info);
+            let _ = DeleteObject(color);
+            let _ = DeleteObject(mask);
+            hicon.map_err(|e| format!("CreateIconIndirect: {e}"))
+        }
+    }
+
+    /// The shell's taskbar list, initialised.
+    fn taskbar_list() -> Option<ITaskbarList3> {
+        unsafe {
+            // Already initialised (even in another mode) is fine.
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            let list: ITaskbarList3 =
+                CoCreateInstance(&TaskbarList, None, CLSCTX_INPROC_SERVER).ok()?;
+            list.HrInit().ok()?;
+            Some(list)
+        }
+    }
+
     impl NativeWindow {
         /// Create a new top-level window.
         pub fn create(title: &str, width: u32, height: u32) -> Result<Self, String> {
@@ -148,8 +246,10 @@ mod platform {
                 ..Default::default()
             };
 
+            // One class serves every window; with several monitors the
+            // second registration finds it already there.
             let atom = unsafe { RegisterClassW(&wc) };
-            if atom == 0 {
+            if atom == 0 && unsafe { GetLastError() } != ERROR_CLASS_ALREADY_EXISTS {
                 return Err("RegisterClassW failed".into());
             }
 
@@ -181,13 +281,37 @@ mod platform {
             let tx_ptr = Box::into_raw(tx_box);
             unsafe {
                 SetWindowLongPtrW(hwnd, GWLP_USERDATA, tx_ptr as isize);
+                DragAcceptFiles(hwnd, TRUE);
             }
 
+            let icon = taskbar::window_icon().and_then(|icon| create_icon(&icon));
+            let icon = match icon {
+                Ok(hicon) => {
+                    for size in [ICON_BIG, ICON_SMALL] {
+                        let _ = unsafe {
+                            SendMessageW(
+                                hwnd,
+                                WM_SETICON,
+                                WPARAM(size as usize),
+                                LPARAM(hicon.0 as isize),
+                            )
+                        };
+                    }
+                    Some(hicon)
+                }
+                Err(e) => {
+                    tracing::warn!("{e}");
+                    None
+                }
+            };
+
             Ok(Self {
                 hwnd,
                 width,
                 height,
                 event_rx,
+                icon,
+                taskbar: taskbar_list(),
             })
         }
 
@@ -211,11 +335,59 @@ mod platform {
         pub fn hwnd(&self) -> HWND {
             self.hwnd
         }
+
+        /// Replace the title bar text (used as the stats line).
+        pub fn set_title(&self, title: &str) {
+            let wide: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
+            unsafe {
+                let _ = SetWindowTextW(self.hwnd, PCWSTR(wide.as_ptr()));
+            }
+        }
+
+        /// Whether the window is the one the user is working in.
+        pub fn is_focused(&self) -> bool {
+            unsafe { GetForegroundWindow() == self.hwnd }
+        }
+
+        /// Show `progress` (0.0 to 1.0) on the taskbar button, or no
+        /// progress at all.
+        pub fn set_taskbar_progress(&self, progress: Option<f32>) {
+            let Some(list) = &self.taskbar else {
+                return;
+            };
+            unsafe {
+                match progress {
+                    Some(fraction) => {
+                        let done = (fraction.clamp(0.0, 1.0) * PROGRESS_TOTAL as f32) as u64;
+                        let _ = list.SetProgressState(self.hwnd, TBPF_NORMAL);
+                        let _ = list.SetProgressValue(self.hwnd, done, PROGRESS_TOTAL);
+                    }
+                    None => {
+                        let _ = list.SetProgressState(self.hwnd, TBPF_NOPROGRESS);
+                    }
+                }
+            }
+        }
+
+        /// Flash the taskbar button and caption `count` times.
+        pub fn flash(&self, count: u32) {
+            let info = FLASHWINFO {
+                cbSize: std::mem::size_of::<FLASHWINFO>() as u32,
+                hwnd: self.hwnd,
+                dwFlags: FLASHW_ALL,
+                uCount: count,
+                dwTimeout: 0,
+            };
+            unsafe {
+                let _ = FlashWindowEx(&info);
+            }
+        }
     }
 
     impl Drop for NativeWindow {
         fn drop(&mut self) {
             unsafe {
+                DragAcceptFiles(self.hwnd, FALSE);
                 // Recover and drop the boxed sender.
                 let ptr = GetWindowLongPtrW(self.hwnd, GWLP_USERDATA)
                     as *mut mpsc::Sender<WindowEvent>;
@@ -224,6 +396,9 @@ mod platform {
                     SetWindowLongPtrW(self.hwnd, GWLP_USERDATA, 0);
                 }
                 let _ = DestroyWindow(self.hwnd);
+                if let Some(icon) = self.icon.take() {
+                    let _ = DestroyIcon(icon);
+                }
             }
         }
     }
@@ -236,6 +411,8 @@ pub use platform::*;
 
 #[cfg(not(target_os = "windows"))]
 pub mod stub {
+    use std::path::PathBuf;
+
     #[derive(Debug, Clone)]
     pub enum WindowEvent {
         Close,
@@ -244,6 +421,8 @@ pub mod stub {
         MouseButton(MouseBtn, bool),
         MouseWheel(i16),
         Key(u16, u16, bool),
+        Char(char),
+        FilesDropped(Vec<PathBuf>, i32, i32),
     }
 
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -263,6 +442,21 @@ pub mod stub {
         pub fn poll_events(&self) -> Vec<WindowEvent> {
             Vec::new()
         }
+
+        /// Placeholder handle so callers compile on every platform.
+        pub fn hwnd(&self) -> isize {
+            0
+        }
+
+        pub fn set_title(&self, _title: &str) {}
+
+        pub fn is_focused(&self) -> bool {
+            true
+        }
+
+        pub fn set_taskbar_progress(&self, _progress: Option<f32>) {}
+
+        pub fn flash(&self, _count: u32) {}
     }
 }
 
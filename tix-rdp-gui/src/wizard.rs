@@ -0,0 +1,484 @@
+//! Connect dialog shown when the slave cannot be reached at startup.
+//!
+//! There is no widget toolkit, so the dialog is a small state machine
+//! fed with [`WindowEvent`]s and drawn by the display renderer. It holds
+//! the target address in an editable [`TextField`], the last error, and
+//! which of the three controls (address, Retry, Quit) has the focus.
+//! Tab / Shift-Tab move the focus, Enter activates it, Escape quits.
+
+use std::time::Duration;
+
+use crate::display::Viewport;
+use crate::window::WindowEvent;
+
+const VK_BACK: u16 = 0x08;
+const VK_TAB: u16 = 0x09;
+const VK_RETURN: u16 = 0x0D;
+const VK_SHIFT: u16 = 0x10;
+const VK_ESCAPE: u16 = 0x1B;
+const VK_END: u16 = 0x23;
+const VK_HOME: u16 = 0x24;
+const VK_LEFT: u16 = 0x25;
+const VK_RIGHT: u16 = 0x27;
+const VK_DELETE: u16 = 0x2E;
+
+/// Longest address the field accepts.
+const MAX_ADDRESS_LEN: usize = 255;
+
+/// Spinner frames shown while a retry is in flight.
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// First delay between `--retry-forever` attempts.
+pub const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay between `--retry-forever` attempts.
+pub const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Delay before retry number `attempt` (starting at 1) when retrying
+/// without the dialog: doubles from [`RETRY_BASE_DELAY`] up to
+/// [`RETRY_MAX_DELAY`].
+pub fn retry_delay(attempt: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1).min(5));
+    (RETRY_BASE_DELAY * factor).min(RETRY_MAX_DELAY)
+}
+
+// ── TextField ────────────────────────────────────────────────────
+
+/// Single-line ASCII text with a caret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextField {
+    text: String,
+    /// Byte offset of the caret; always on a char boundary since only
+    /// ASCII is ever inserted.
+    caret: usize,
+}
+
+impl TextField {
+    /// A field holding `text` (non-ASCII characters dropped) with the
+    /// caret at the end.
+    pub fn new(text: &str) -> Self {
+        let text: String = text.chars().filter(char::is_ascii).collect();
+        let caret = text.len();
+        Self { text, caret }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    /// Insert a printable ASCII character at the caret. Anything else
+    /// is ignored.
+    pub fn insert(&mut self, c: char) {
+        if (' '..='~').contains(&c) && self.text.len() < MAX_ADDRESS_LEN {
+            self.text.insert(self.caret, c);
+            self.caret += 1;
+        }
+    }
+
+    /// Delete the character before the caret.
+    pub fn backspace(&mut self) {
+        if self.caret > 0 {
+            self.caret -= 1;
+            self.text.remove(self.caret);
+        }
+    }
+
+    /// Delete the character under the caret.
+    pub fn delete(&mut self) {
+        if self.caret < self.text.len() {
+            self.text.remove(self.caret);
+        }
+    }
+
+    pub fn left(&mut self) {
+        self.caret = self.caret.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.caret = (self.caret + 1).min(self.text.len());
+    }
+
+    pub fn home(&mut self) {
+        self.caret = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.caret = self.text.len();
+    }
+
+    /// Apply an editing key. Returns `false` for keys that do not edit.
+    pub fn handle_key(&mut self, vk: u16) -> bool {
+        match vk {
+            VK_BACK => self.backspace(),
+            VK_DELETE => self.delete(),
+            VK_LEFT => self.left(),
+            VK_RIGHT => self.right(),
+            VK_HOME => self.home(),
+            VK_END => self.end(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// ── ConnectDialog ────────────────────────────────────────────────
+
+/// Control holding the keyboard focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    Address,
+    Retry,
+    Quit,
+}
+
+impl Focus {
+    fn next(self) -> Self {
+        match self {
+            Self::Address => Self::Retry,
+            Self::Retry => Self::Quit,
+            Self::Quit => Self::Address,
+        }
+    }
+
+    fn prev(self) -> Self {
+        match self {
+            Self::Address => Self::Quit,
+            Self::Retry => Self::Address,
+            Self::Quit => Self::Retry,
+        }
+    }
+}
+
+/// What the caller should do after feeding the dialog an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DialogAction {
+    /// Keep showing the dialog.
+    None,
+    /// Try to connect to this address.
+    Retry(String),
+    /// Give up and exit.
+    Quit,
+}
+
+/// The connect dialog's state.
+#[derive(Debug, Clone)]
+pub struct ConnectDialog {
+    address: TextField,
+    error: String,
+    focus: Focus,
+    shift: bool,
+    /// Spinner frame while a retry is running; `None` while editing.
+    connecting: Option<usize>,
+}
+
+impl ConnectDialog {
+    /// Show `error` for a failed attempt on `address`. The focus starts
+    /// on Retry so Enter simply tries again.
+    pub fn new(address: &str, error: impl Into<String>) -> Self {
+        Self {
+            address: TextField::new(address),
+            error: error.into(),
+            focus: Focus::Retry,
+            shift: false,
+            connecting: None,
+        }
+    }
+
+    pub fn address(&self) -> &TextField {
+        &self.address
+    }
+
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+
+    pub fn focus(&self) -> Focus {
+        self.focus
+    }
+
+    pub fn is_connecting(&self) -> bool {
+        self.connecting.is_some()
+    }
+
+    /// The attempt started by the last [`DialogAction::Retry`] is
+    /// running; the dialog shows a spinner and only accepts Quit.
+    pub fn start_connecting(&mut self) {
+        self.connecting = Some(0);
+    }
+
+    /// The attempt failed; back to editing with the new error.
+    pub fn failed(&mut self, error: impl Into<String>) {
+        self.error = error.into();
+        self.connecting = None;
+    }
+
+    /// Advance the spinner.
+    pub fn tick(&mut self) {
+        if let Some(frame) = &mut self.connecting {
+            *frame = (*frame + 1) % SPINNER.len();
+        }
+    }
+
+    /// Status line under the address: the spinner while connecting,
+    /// otherwise the error.
+    pub fn status(&self) -> String {
+        match self.connecting {
+            Some(frame) => format!("{} Connecting to {}...", SPINNER[frame], self.address.text()),
+            None => self.error.clone(),
+        }
+    }
+
+    /// Feed one window event.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> DialogAction {
+        match event {
+            WindowEvent::Close => DialogAction::Quit,
+            WindowEvent::Key(VK_SHIFT, _, pressed) => {
+                self.shift = *pressed;
+                DialogAction::None
+            }
+            WindowEvent::Key(VK_ESCAPE, _, true) => DialogAction::Quit,
+            WindowEvent::Key(_, _, true) | WindowEvent::Char(_) if self.is_connecting() => {
+                DialogAction::None
+            }
+            WindowEvent::Key(VK_TAB, _, true) => {
+                self.focus = if self.shift { self.focus.prev() } else { self.focus.next() };
+                DialogAction::None
+            }
+            WindowEvent::Key(VK_RETURN, _, true) => self.activate(),
+            WindowEvent::Key(vk, _, true) => {
+                if self.focus == Focus::Address {
+                    self.address.handle_key(*vk);
+                } else if *vk == VK_LEFT {
+                    self.focus = self.focus.prev();
+                } else if *vk == VK_RIGHT {
+                    self.focus = self.focus.next();
+                }
+                DialogAction::None
+            }
+            WindowEvent::Char(c) if self.focus == Focus::Address => {
+                self.address.insert(*c);
+                DialogAction::None
+            }
+            _ => DialogAction::None,
+        }
+    }
+
+    /// Enter on the focused control.
+    fn activate(&mut self) -> DialogAction {
+        match self.focus {
+            Focus::Quit => DialogAction::Quit,
+            Focus::Address | Focus::Retry => match validate_address(self.address.text()) {
+                Ok(()) => DialogAction::Retry(self.address.text().to_string()),
+                Err(e) => {
+                    self.error = e;
+                    self.focus = Focus::Address;
+                    DialogAction::None
+                }
+            },
+        }
+    }
+}
+
+/// Check that `address` looks like `host:port`.
+pub fn validate_address(address: &str) -> Result<(), String> {
+    let (host, port) = address
+        .rsplit_once(':')
+        .ok_or_else(|| format!("'{address}' is not host:port"))?;
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return Err(format!("'{address}' has no host name"));
+    }
+    match port.parse::<u16>() {
+        Ok(p) if p != 0 => Ok(()),
+        _ => Err(format!("'{port}' is not a port number")),
+    }
+}
+
+// ── Layout ───────────────────────────────────────────────────────
+
+/// Where the dialog's parts are drawn in a client area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DialogLayout {
+    pub panel: Viewport,
+    pub field: Viewport,
+    pub retry: Viewport,
+    pub quit: Viewport,
+}
+
+impl DialogLayout {
+    const PANEL_W: u32 = 480;
+    const PANEL_H: u32 = 200;
+    const MARGIN: u32 = 20;
+    const BUTTON_W: u32 = 96;
+    const BUTTON_H: u32 = 30;
+
+    /// Centre the dialog in a `window_w × window_h` client area,
+    /// shrinking it if the window is smaller.
+    pub fn new(window_w: u32, window_h: u32) -> Self {
+        let width = Self::PANEL_W.min(window_w);
+        let height = Self::PANEL_H.min(window_h);
+        let panel = Viewport {
+            x: ((window_w - width) / 2) as i32,
+            y: ((window_h - height) / 2) as i32,
+            width,
+            height,
+        };
+        let inner_w = width.saturating_sub(2 * Self::MARGIN);
+        let field = Viewport {
+            x: panel.x + Self::MARGIN as i32,
+            y: panel.y + 84,
+            width: inner_w,
+            height: 26,
+        };
+        let buttons_y = panel.y + height as i32 - (Self::MARGIN + Self::BUTTON_H) as i32;
+        let quit = Viewport {
+            x: panel.x + width as i32 - (Self::MARGIN + Self::BUTTON_W) as i32,
+            y: buttons_y,
+            width: Self::BUTTON_W,
+            height: Self::BUTTON_H,
+        };
+        let retry = Viewport {
+            x: quit.x - (Self::BUTTON_W + 12) as i32,
+            ..quit
+        };
+        Self {
+            panel,
+            field,
+            retry,
+            quit,
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(vk: u16) -> WindowEvent {
+        WindowEvent::Key(vk, 0, true)
+    }
+
+    fn type_text(dialog: &mut ConnectDialog, text: &str) {
+        for c in text.chars() {
+            dialog.handle_event(&WindowEvent::Char(c));
+        }
+    }
+
+    #[test]
+    fn text_field_edits_at_the_caret() {
+        let mut field = TextField::new("10.0.0.1:7332");
+        field.home();
+        field.insert('x');
+        assert_eq!(field.text(), "x10.0.0.1:7332");
+        field.backspace();
+        field.end();
+        field.left();
+        field.delete();
+        assert_eq!(field.text(), "10.0.0.1:733");
+        assert_eq!(field.caret(), 12);
+        field.right();
+        assert_eq!(field.caret(), 12);
+
+        // Control characters and non-ASCII never get in.
+        field.insert('\u{8}');
+        field.insert('é');
+        assert_eq!(field.text(), "10.0.0.1:733");
+        assert_eq!(TextField::new("hôst:1").text(), "hst:1");
+    }
+
+    #[test]
+    fn tab_cycles_focus_and_shift_reverses() {
+        let mut dialog = ConnectDialog::new("127.0.0.1:7332", "refused");
+        assert_eq!(dialog.focus(), Focus::Retry);
+        dialog.handle_event(&key(VK_TAB));
+        assert_eq!(dialog.focus(), Focus::Quit);
+        dialog.handle_event(&key(VK_TAB));
+        assert_eq!(dialog.focus(), Focus::Address);
+
+        dialog.handle_event(&key(VK_SHIFT));
+        dialog.handle_event(&key(VK_TAB));
+        assert_eq!(dialog.focus(), Focus::Quit);
+        dialog.handle_event(&WindowEvent::Key(VK_SHIFT, 0, false));
+        dialog.handle_event(&key(VK_TAB));
+        assert_eq!(dialog.focus(), Focus::Address);
+    }
+
+    #[test]
+    fn edit_then_retry_until_connected() {
+        let mut dialog = ConnectDialog::new("127.0.0.1:7332", "connection refused");
+        dialog.handle_event(&key(VK_TAB));
+        dialog.handle_event(&key(VK_TAB));
+        assert_eq!(dialog.focus(), Focus::Address);
+
+        // Replace the port.
+        for _ in 0..4 {
+            dialog.handle_event(&key(VK_BACK));
+        }
+        type_text(&mut dialog, "7400");
+        assert_eq!(dialog.address().text(), "127.0.0.1:7400");
+
+        let action = dialog.handle_event(&key(VK_RETURN));
+        assert_eq!(action, DialogAction::Retry("127.0.0.1:7400".into()));
+
+        // While connecting only Escape does anything.
+        dialog.start_connecting();
+        assert!(dialog.status().contains("Connecting to 127.0.0.1:7400"));
+        dialog.tick();
+        type_text(&mut dialog, "zz");
+        assert_eq!(dialog.handle_event(&key(VK_RETURN)), DialogAction::None);
+        assert_eq!(dialog.address().text(), "127.0.0.1:7400");
+
+        dialog.failed("timed out");
+        assert!(!dialog.is_connecting());
+        assert_eq!(dialog.status(), "timed out");
+        assert_eq!(dialog.handle_event(&key(VK_ESCAPE)), DialogAction::Quit);
+    }
+
+    #[test]
+    fn bad_address_is_refused_before_retrying() {
+        let mut dialog = ConnectDialog::new("slave-pc", "no port");
+        assert_eq!(dialog.handle_event(&key(VK_RETURN)), DialogAction::None);
+        assert_eq!(dialog.focus(), Focus::Address);
+        assert!(dialog.error().contains("host:port"));
+
+        assert!(validate_address("slave-pc:7332").is_ok());
+        assert!(validate_address(":7332").is_err());
+        assert!(validate_address("slave-pc:0").is_err());
+        assert!(validate_address("slave-pc:http").is_err());
+    }
+
+    #[test]
+    fn quit_from_buttons_and_close() {
+        let mut dialog = ConnectDialog::new("127.0.0.1:7332", "refused");
+        dialog.handle_event(&key(VK_RIGHT));
+        assert_eq!(dialog.focus(), Focus::Quit);
+        assert_eq!(dialog.handle_event(&key(VK_RETURN)), DialogAction::Quit);
+        assert_eq!(
+            ConnectDialog::new("a:1", "").handle_event(&WindowEvent::Close),
+            DialogAction::Quit
+        );
+    }
+
+    #[test]
+    fn layout_fits_small_windows() {
+        let layout = DialogLayout::new(1920, 1080);
+        assert_eq!(layout.panel.width, 480);
+        assert!(layout.retry.x + layout.retry.width as i32 <= layout.quit.x);
+
+        let tiny = DialogLayout::new(300, 150);
+        assert_eq!((tiny.panel.x, tiny.panel.y), (0, 0));
+        assert_eq!(tiny.panel.width, 300);
+    }
+
+    #[test]
+    fn retry_delay_backs_off_to_a_cap() {
+        assert_eq!(retry_delay(1), Duration::from_secs(1));
+        assert_eq!(retry_delay(3), Duration::from_secs(4));
+        assert_eq!(retry_delay(50), RETRY_MAX_DELAY);
+    }
+}
@@ -1,12 +1,27 @@
 //! Configuration for the RDP slave service.
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
+use tix_core::config::{self, Validate};
+use tix_core::rdp::burst::DEFAULT_STALE_AFTER;
+use tix_core::rdp::delta::supported_block_size;
+use tix_core::rdp::encoder::{
+    DEFAULT_KEYFRAME_FRAMES, DEFAULT_KEYFRAME_INTERVAL, DEFAULT_SCENE_CHANGE_PERCENT,
+};
+use tix_core::rdp::gpu::GpuPrepMode;
+use tix_core::rdp::merge::DEFAULT_MAX_WASTE_PERCENT;
+use tix_core::rdp::transport::{DEFAULT_MTU, MIN_MTU, TransportConfig};
+use tix_core::rdp::throttle::Priority;
+use tix_core::rdp::thumbnail::{
+    DEFAULT_INTERVAL, DEFAULT_MAX_DIMENSION, DEFAULT_QUALITY, ThumbnailConfig,
+};
+use tix_core::rdp::types::PixelFormat;
 
 /// Top-level configuration loaded from a TOML file.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct SlaveConfig {
     /// Network settings.
     pub network: NetworkConfig,
@@ -16,23 +31,40 @@ pub struct SlaveConfig {
     pub performance: PerformanceConfig,
     /// Logging settings.
     pub logging: LoggingConfig,
+    /// Metrics export.
+    pub metrics: MetricsConfig,
+    /// Service lifecycle.
+    pub service: ServiceConfig,
 }
 
 /// Network configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct NetworkConfig {
     /// UDP port to bind for screen data.
     pub listen_port: u16,
     /// TCP port to listen for control connections.
     pub control_port: u16,
+    /// Viewer to dial (`host:port`) instead of listening on
+    /// `control_port`, for slaves behind NAT. The viewer runs with
+    /// `--listen`. Empty listens.
+    pub connect_to: String,
     /// Maximum concurrent master connections (1 for direct RJ-45).
     pub max_connections: u32,
+    /// Screen datagram size in bytes (UDP payload); the upper bound
+    /// when `probe_mtu` is on.
+    pub mtu: usize,
+    /// Measure the path MTU to the master at session start.
+    pub probe_mtu: bool,
+    /// UDP receive buffer (`SO_RCVBUF`) in bytes; 0 keeps the OS default.
+    pub recv_buffer: usize,
+    /// UDP send buffer (`SO_SNDBUF`) in bytes; 0 keeps the OS default.
+    pub send_buffer: usize,
 }
 
 /// Screen capture configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct ScreenConfig {
     /// Capture quality preset: "low", "medium", "high".
     pub capture_quality: String,
@@ -40,17 +72,74 @@ pub struct ScreenConfig {
     pub fps: u8,
     /// Enable delta detection (send only changed blocks).
     pub delta_detection: bool,
-    /// Block size for delta detection (pixels).
+    /// Block size for delta detection (pixels): 8, 16, 32 or 64. Other
+    /// values are rounded down to one of them.
     pub block_size: usize,
+    /// Coalesce neighbouring changed blocks into larger rectangles.
+    pub merge_blocks: bool,
+    /// Share of a merged rectangle, in percent, that may be unchanged
+    /// pixels.
+    pub merge_waste_percent: u8,
     /// Monitor index to capture (0 = primary).
     pub monitor_index: u32,
     /// DXGI acquire timeout in milliseconds.
     pub capture_timeout_ms: u32,
+    /// Pixel format on the wire: "bgra" (full colour), "rgb565" or
+    /// "gray". Reduced formats trade colour depth for bandwidth.
+    pub pixel_format: String,
+    /// Diff frames and pack the changed tiles on the GPU, reading back
+    /// only those: "auto" (when the GPU can), "on" (fail if it cannot)
+    /// or "off".
+    pub gpu_prep: String,
+    /// Capture UAC prompts and the lock screen by switching to the
+    /// secure desktop. Only works when the service runs as SYSTEM;
+    /// otherwise the viewer is just told the machine needs attention.
+    pub secure_desktop_capture: bool,
+    /// Seconds without a captured frame before the capturer is torn
+    /// down and rebuilt (0 disables the watchdog).
+    pub stall_timeout_secs: u64,
+    /// Fruitless rebuilds in a row before the viewer is told capture
+    /// has failed. Rebuilding continues, with growing pauses.
+    pub max_rebuild_failures: u32,
+    /// Screen sessions (one per monitor) a connected master may run at
+    /// once. Session `n` sends from UDP port `listen_port + n`.
+    pub max_sessions: usize,
+    /// CPU budget for the stream in percent of the machine (0 = no
+    /// limit). Over it, compression is lowered first, then the frame
+    /// rate.
+    pub max_cpu_percent: u8,
+    /// Process priority: "normal" or "background" (Windows background
+    /// mode, which also lowers I/O and memory priority).
+    pub priority: String,
+    /// Send a full frame at least every this many frames (0 = only by
+    /// time).
+    pub keyframe_frames: u64,
+    /// Send a full frame at least this often in seconds, even when the
+    /// screen is still (0 = only by count).
+    pub keyframe_secs: u64,
+    /// Send a frame whose changes cover at least this share of the
+    /// screen, in percent, as a full frame (0 = never).
+    pub scene_change_percent: u8,
+    /// Drop a stamped pointer move that a later one overtook and that
+    /// is this many milliseconds behind the newest input (0 = apply
+    /// every move).
+    pub stale_move_ms: u64,
+    /// Send periodic thumbnails instead of frames when even the most
+    /// compressed frames overrun `target_bandwidth_mbps`, for viewers
+    /// that can show them. Frames are tried again now and then.
+    pub low_bandwidth_fallback: bool,
+    /// Longer side of a low-bandwidth thumbnail in pixels.
+    pub thumbnail_max_dimension: u32,
+    /// Milliseconds between low-bandwidth thumbnails; longer when the
+    /// bandwidth budget cannot carry them that often.
+    pub thumbnail_interval_ms: u64,
+    /// JPEG quality of low-bandwidth thumbnails (1-100).
+    pub thumbnail_quality: u8,
 }
 
 /// Performance tuning.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct PerformanceConfig {
     /// Target bandwidth in megabytes per second.
     pub target_bandwidth_mbps: u64,
@@ -60,33 +149,49 @@ pub struct PerformanceConfig {
 
 /// Logging settings.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct LoggingConfig {
     /// Log level: "trace", "debug", "info", "warn", "error".
     pub level: String,
     /// Optional log file path. If empty, logs to stderr.
     pub file: String,
+    /// File each panic is appended to as a JSON line (see
+    /// [`tix_core::crash`]). Empty records nothing; panics are still
+    /// logged.
+    pub crash_file: String,
 }
 
-// ── Defaults ─────────────────────────────────────────────────────
+/// Prometheus metrics endpoint (needs the `metrics` build feature).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Serve `/metrics` on `127.0.0.1:<port>`; 0 turns it off.
+    pub port: u16,
+}
 
-impl Default for SlaveConfig {
-    fn default() -> Self {
-        Self {
-            network: NetworkConfig::default(),
-            screen: ScreenConfig::default(),
-            performance: PerformanceConfig::default(),
-            logging: LoggingConfig::default(),
-        }
-    }
+/// Service lifecycle settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceConfig {
+    /// How long a stop may take, in milliseconds: capture loops finish
+    /// their frame and the viewer is told the session ended within it,
+    /// and whatever is still running afterwards is aborted.
+    pub shutdown_grace_ms: u64,
 }
 
+// ── Defaults ─────────────────────────────────────────────────────
+
 impl Default for NetworkConfig {
     fn default() -> Self {
         Self {
             listen_port: 7331,
             control_port: 7332,
+            connect_to: String::new(),
             max_connections: 1,
+            mtu: DEFAULT_MTU,
+            probe_mtu: false,
+            recv_buffer: 0,
+            send_buffer: 0,
         }
     }
 }
@@ -98,8 +203,26 @@ impl Default for ScreenConfig {
             fps: 60,
             delta_detection: true,
             block_size: 64,
+            merge_blocks: true,
+            merge_waste_percent: DEFAULT_MAX_WASTE_PERCENT,
             monitor_index: 0,
             capture_timeout_ms: 100,
+            pixel_format: "bgra".into(),
+            gpu_prep: "auto".into(),
+            secure_desktop_capture: false,
+            stall_timeout_secs: 5,
+            max_rebuild_failures: 3,
+            max_sessions: 2,
+            max_cpu_percent: 0,
+            priority: "normal".into(),
+            keyframe_frames: DEFAULT_KEYFRAME_FRAMES,
+            keyframe_secs: DEFAULT_KEYFRAME_INTERVAL.as_secs(),
+            scene_change_percent: DEFAULT_SCENE_CHANGE_PERCENT,
+            stale_move_ms: DEFAULT_STALE_AFTER.as_millis() as u64,
+            low_bandwidth_fallback: true,
+            thumbnail_max_dimension: DEFAULT_MAX_DIMENSION,
+            thumbnail_interval_ms: DEFAULT_INTERVAL.as_millis() as u64,
+            thumbnail_quality: DEFAULT_QUALITY,
         }
     }
 }
@@ -113,51 +236,164 @@ impl Default for PerformanceConfig {
     }
 }
 
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            shutdown_grace_ms: 3000,
+        }
+    }
+}
+
 impl Default for LoggingConfig {
     fn default() -> Self {
         Self {
             level: "info".into(),
             file: String::new(),
+            crash_file: "tix-rdp-slave-crash.jsonl".into(),
         }
     }
 }
 
+impl LoggingConfig {
+    /// The crash log, `None` when off.
+    pub fn crash_log(&self) -> Option<PathBuf> {
+        (!self.crash_file.is_empty()).then(|| PathBuf::from(&self.crash_file))
+    }
+}
+
 // ── Loading ──────────────────────────────────────────────────────
 
-impl SlaveConfig {
-    /// Load configuration from a TOML file, falling back to defaults.
-    pub fn load(path: &Path) -> Self {
-        match std::fs::read_to_string(path) {
-            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
-                tracing::warn!("invalid config {}: {e}; using defaults", path.display());
-                Self::default()
-            }),
-            Err(_) => {
-                tracing::info!(
-                    "no config at {}; using defaults",
-                    path.display()
-                );
-                Self::default()
+impl Validate for SlaveConfig {
+    fn problems(&self) -> Vec<String> {
+        let network = &self.network;
+        let mut problems = Vec::new();
+        if network.listen_port == 0 {
+            problems.push("network.listen_port must not be 0".to_string());
+        }
+        if network.connect_to.is_empty() {
+            if network.control_port == 0 {
+                problems.push("network.control_port must not be 0".to_string());
             }
+        } else {
+            problems.extend(config::address_problem("network.connect_to", &network.connect_to));
+        }
+        if network.mtu < MIN_MTU {
+            problems.push(format!(
+                "network.mtu = {} is too small; it must be at least {MIN_MTU}",
+                network.mtu
+            ));
+        }
+        if self.screen.fps == 0 {
+            problems.push("screen.fps must be at least 1".to_string());
+        }
+        if self.screen.thumbnail_max_dimension < 16 {
+            problems.push("screen.thumbnail_max_dimension must be at least 16".to_string());
+        }
+        if !(1..=100).contains(&self.screen.thumbnail_quality) {
+            problems.push(format!(
+                "screen.thumbnail_quality = {} is out of range; it must be 1-100",
+                self.screen.thumbnail_quality
+            ));
         }
+        problems
     }
+}
 
+impl SlaveConfig {
     /// Write the default configuration to a file (for bootstrapping).
     pub fn write_default(path: &Path) -> std::io::Result<()> {
         let cfg = Self::default();
         let text = toml::to_string_pretty(&cfg)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        std::fs::write(path, text)
+            .map_err(std::io::Error::other)?;
+        config::write_atomic(path, text)
     }
 
     /// Convert capture settings into a `ScreenServiceConfig`.
+    ///
+    /// An unknown `pixel_format` falls back to full colour. With
+    /// `adaptive_quality` on, the encoder may also drop to RGB565 on its
+    /// own when the link is saturated. An unknown `gpu_prep` means
+    /// "auto", and an unknown `priority` leaves the priority alone. A `block_size` outside 8/16/32/64 is rounded down
+    /// to the nearest supported size.
     pub fn to_service_config(&self) -> tix_core::rdp::service::ScreenServiceConfig {
+        let pixel_format = self.screen.pixel_format.parse().unwrap_or_else(|e| {
+            tracing::warn!("{e}; using bgra");
+            PixelFormat::Bgra8
+        });
+        let gpu_prep = self.screen.gpu_prep.parse().unwrap_or_else(|e| {
+            tracing::warn!("{e}; using auto");
+            GpuPrepMode::Auto
+        });
+        let priority = self.screen.priority.parse().unwrap_or_else(|e| {
+            tracing::warn!("{e}; using normal");
+            Priority::Normal
+        });
+        let block_size = supported_block_size(self.screen.block_size);
+        if block_size != self.screen.block_size {
+            tracing::warn!(
+                "block_size {} is not supported; using {block_size}",
+                self.screen.block_size
+            );
+        }
         tix_core::rdp::service::ScreenServiceConfig {
             target_fps: self.screen.fps.clamp(1, 60),
-            block_size: self.screen.block_size.max(8),
+            block_size,
+            merge_waste_percent: self
+                .screen
+                .merge_blocks
+                .then_some(self.screen.merge_waste_percent.min(100)),
             target_bandwidth: self.performance.target_bandwidth_mbps * 1024 * 1024,
             monitor_index: self.screen.monitor_index,
             capture_timeout_ms: self.screen.capture_timeout_ms,
+            pixel_format,
+            gpu_prep,
+            allow_format_downgrade: self.performance.adaptive_quality,
+            secure_desktop_capture: self.screen.secure_desktop_capture,
+            stall_timeout: (self.screen.stall_timeout_secs > 0)
+                .then(|| Duration::from_secs(self.screen.stall_timeout_secs)),
+            max_rebuild_failures: self.screen.max_rebuild_failures,
+            max_cpu_percent: (self.screen.max_cpu_percent > 0)
+                .then(|| self.screen.max_cpu_percent.min(100)),
+            priority,
+            keyframe_frames: (self.screen.keyframe_frames > 0)
+                .then_some(self.screen.keyframe_frames),
+            keyframe_interval: (self.screen.keyframe_secs > 0)
+                .then(|| Duration::from_secs(self.screen.keyframe_secs)),
+            scene_change_percent: (self.screen.scene_change_percent > 0)
+                .then(|| self.screen.scene_change_percent.min(100)),
+            thumbnails: ThumbnailConfig {
+                max_dimension: self.screen.thumbnail_max_dimension,
+                interval: Duration::from_millis(self.screen.thumbnail_interval_ms),
+                quality: self.screen.thumbnail_quality,
+            },
+            low_bandwidth: false,
+            low_bandwidth_fallback: self.screen.low_bandwidth_fallback,
+        }
+    }
+}
+
+impl ScreenConfig {
+    /// [`stale_move_ms`](Self::stale_move_ms) as a duration.
+    pub fn stale_move(&self) -> Duration {
+        Duration::from_millis(self.stale_move_ms)
+    }
+}
+
+impl ServiceConfig {
+    /// [`shutdown_grace_ms`](Self::shutdown_grace_ms) as a duration.
+    pub fn shutdown_grace(&self) -> Duration {
+        Duration::from_millis(self.shutdown_grace_ms)
+    }
+}
+
+impl NetworkConfig {
+    /// Socket tuning for the screen UDP socket.
+    pub fn transport_config(&self) -> TransportConfig {
+        TransportConfig {
+            mtu: self.mtu,
+            probe_mtu: self.probe_mtu,
+            recv_buffer: (self.recv_buffer > 0).then_some(self.recv_buffer),
+            send_buffer: (self.send_buffer > 0).then_some(self.send_buffer),
         }
     }
 }
@@ -183,6 +419,16 @@ mod tests {
         let parsed: SlaveConfig = toml::from_str(&text).unwrap();
         assert_eq!(parsed.network.listen_port, 7331);
         assert_eq!(parsed.screen.fps, 60);
+        assert_eq!(parsed.screen.max_sessions, 2);
+        assert!(parsed.network.connect_to.is_empty());
+    }
+
+    #[test]
+    fn shutdown_grace_defaults_to_three_seconds() {
+        let cfg = SlaveConfig::default();
+        assert_eq!(cfg.service.shutdown_grace(), Duration::from_secs(3));
+        let cfg = parse("[service]\nshutdown_grace_ms = 500").unwrap();
+        assert_eq!(cfg.service.shutdown_grace(), Duration::from_millis(500));
     }
 
     #[test]
@@ -192,4 +438,173 @@ mod tests {
         let svc = cfg.to_service_config();
         assert_eq!(svc.target_fps, 60);
     }
+
+    #[test]
+    fn block_size_and_merging_reach_service_config() {
+        let mut cfg = SlaveConfig::default();
+        let svc = cfg.to_service_config();
+        assert_eq!(svc.block_size, 64);
+        assert_eq!(svc.merge_waste_percent, Some(DEFAULT_MAX_WASTE_PERCENT));
+
+        cfg.screen.block_size = 20;
+        cfg.screen.merge_waste_percent = 150;
+        let svc = cfg.to_service_config();
+        assert_eq!(svc.block_size, 16);
+        assert_eq!(svc.merge_waste_percent, Some(100));
+
+        cfg.screen.merge_blocks = false;
+        assert_eq!(cfg.to_service_config().merge_waste_percent, None);
+    }
+
+    #[test]
+    fn pixel_format_reaches_service_config() {
+        let mut cfg = SlaveConfig::default();
+        assert_eq!(cfg.to_service_config().pixel_format, PixelFormat::Bgra8);
+        cfg.screen.pixel_format = "rgb565".into();
+        cfg.performance.adaptive_quality = false;
+        let svc = cfg.to_service_config();
+        assert_eq!(svc.pixel_format, PixelFormat::Rgb565);
+        assert!(!svc.allow_format_downgrade);
+        cfg.screen.pixel_format = "sepia".into();
+        assert_eq!(cfg.to_service_config().pixel_format, PixelFormat::Bgra8);
+    }
+
+    #[test]
+    fn gpu_prep_reaches_service_config() {
+        let mut cfg = SlaveConfig::default();
+        assert_eq!(cfg.to_service_config().gpu_prep, GpuPrepMode::Auto);
+        cfg.screen.gpu_prep = "off".into();
+        assert_eq!(cfg.to_service_config().gpu_prep, GpuPrepMode::Off);
+        cfg.screen.gpu_prep = "maybe".into();
+        assert_eq!(cfg.to_service_config().gpu_prep, GpuPrepMode::Auto);
+    }
+
+    #[test]
+    fn secure_desktop_capture_is_opt_in() {
+        let cfg = SlaveConfig::default();
+        assert!(!cfg.to_service_config().secure_desktop_capture);
+        let cfg: SlaveConfig = toml::from_str("[screen]\nsecure_desktop_capture = true").unwrap();
+        assert!(cfg.to_service_config().secure_desktop_capture);
+    }
+
+    #[test]
+    fn zero_stall_timeout_disables_watchdog() {
+        let cfg = SlaveConfig::default();
+        assert_eq!(cfg.to_service_config().stall_timeout, Some(Duration::from_secs(5)));
+        let cfg: SlaveConfig = toml::from_str("[screen]\nstall_timeout_secs = 0").unwrap();
+        assert_eq!(cfg.to_service_config().stall_timeout, None);
+    }
+
+    #[test]
+    fn cpu_budget_and_priority_reach_service_config() {
+        let svc = SlaveConfig::default().to_service_config();
+        assert_eq!((svc.max_cpu_percent, svc.priority), (None, Priority::Normal));
+
+        let cfg: SlaveConfig =
+            toml::from_str("[screen]\nmax_cpu_percent = 25\npriority = \"background\"").unwrap();
+        let svc = cfg.to_service_config();
+        assert_eq!(svc.max_cpu_percent, Some(25));
+        assert_eq!(svc.priority, Priority::Background);
+
+        let cfg: SlaveConfig =
+            toml::from_str("[screen]\nmax_cpu_percent = 250\npriority = \"idle\"").unwrap();
+        let svc = cfg.to_service_config();
+        assert_eq!(svc.max_cpu_percent, Some(100));
+        assert_eq!(svc.priority, Priority::Normal);
+    }
+
+    #[test]
+    fn keyframe_cadence_reaches_service_config() {
+        let svc = SlaveConfig::default().to_service_config();
+        assert_eq!(svc.keyframe_frames, Some(300));
+        assert_eq!(svc.keyframe_interval, Some(Duration::from_secs(10)));
+        assert_eq!(svc.scene_change_percent, Some(60));
+
+        let cfg: SlaveConfig = toml::from_str(
+            "[screen]\nkeyframe_frames = 0\nkeyframe_secs = 2\nscene_change_percent = 0",
+        )
+        .unwrap();
+        let svc = cfg.to_service_config();
+        assert_eq!(svc.keyframe_frames, None);
+        assert_eq!(svc.keyframe_interval, Some(Duration::from_secs(2)));
+        assert_eq!(svc.scene_change_percent, None);
+    }
+
+    #[test]
+    fn thumbnails_reach_service_config() {
+        let svc = SlaveConfig::default().to_service_config();
+        assert_eq!(svc.thumbnails, ThumbnailConfig::default());
+        assert!(svc.low_bandwidth_fallback && !svc.low_bandwidth);
+
+        let cfg: SlaveConfig = toml::from_str(
+            "[screen]\nlow_bandwidth_fallback = false\nthumbnail_max_dimension = 320\n\
+             thumbnail_interval_ms = 5000\nthumbnail_quality = 40",
+        )
+        .unwrap();
+        let svc = cfg.to_service_config();
+        assert!(!svc.low_bandwidth_fallback);
+        assert_eq!(
+            svc.thumbnails,
+            ThumbnailConfig {
+                max_dimension: 320,
+                interval: Duration::from_secs(5),
+                quality: 40,
+            }
+        );
+    }
+
+    fn parse(text: &str) -> Result<SlaveConfig, config::ConfigError> {
+        config::parse(Path::new("tix-rdp-slave.toml"), text)
+    }
+
+    #[test]
+    fn unknown_keys_are_refused() {
+        let err = parse("[screen]\nfsp = 30\n").unwrap_err().to_string();
+        assert!(err.contains("line 2, column 1"), "{err}");
+        assert!(err.ends_with("did you mean `fps`?"), "{err}");
+        let err = parse("[netwrok]\nmtu = 1200\n").unwrap_err().to_string();
+        assert!(err.ends_with("did you mean `network`?"), "{err}");
+        // Out of range for the type.
+        assert!(parse("[network]\nlisten_port = 70000\n").is_err());
+    }
+
+    #[test]
+    fn settings_that_cannot_work_are_refused() {
+        let err = parse(
+            "[network]\nlisten_port = 0\nmtu = 64\n[screen]\nfps = 0\n\
+             thumbnail_max_dimension = 8\nthumbnail_quality = 0\n",
+        )
+        .unwrap_err();
+        let config::ConfigError::Invalid { problems, .. } = err else {
+            panic!("unexpected {err:?}");
+        };
+        assert_eq!(
+            problems,
+            [
+                "network.listen_port must not be 0",
+                "network.mtu = 64 is too small; it must be at least 100",
+                "screen.fps must be at least 1",
+                "screen.thumbnail_max_dimension must be at least 16",
+                "screen.thumbnail_quality = 0 is out of range; it must be 1-100",
+            ]
+        );
+
+        // The control port is not used when dialling out, but the
+        // address dialled must have one.
+        assert!(parse("[network]\ncontrol_port = 0\nconnect_to = \"viewer:7332\"").is_ok());
+        let err = parse("[network]\nconnect_to = \"viewer\"").unwrap_err();
+        assert!(err.to_string().contains("network.connect_to"), "{err}");
+        assert!(SlaveConfig::default().problems().is_empty());
+    }
+
+    #[test]
+    fn zero_buffer_sizes_keep_os_defaults() {
+        let mut cfg: SlaveConfig = toml::from_str("[network]\nrecv_buffer = 4194304").unwrap();
+        assert_eq!(cfg.network.mtu, DEFAULT_MTU);
+        let tuning = cfg.network.transport_config();
+        assert_eq!(tuning.recv_buffer, Some(4 * 1024 * 1024));
+        assert_eq!(tuning.send_buffer, None);
+        cfg.network.probe_mtu = true;
+        assert!(cfg.network.transport_config().probe_mtu);
+    }
 }
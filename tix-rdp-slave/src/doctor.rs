@@ -0,0 +1,122 @@
+//! `tix-rdp-slave --doctor`: the self-test of
+//! [`tix_core::rdp::doctor`], run against this slave's configuration
+//! and printed as a table.
+
+use std::path::{Path, PathBuf};
+
+use tix_core::protocol::selftest::SelfTestReport;
+use tix_core::rdp::doctor::{DEFAULT_CAPTURE_TIMEOUT_MS, DoctorOptions};
+
+use crate::config::SlaveConfig;
+
+/// What to check for a slave running `config`, loaded from
+/// `config_path`: the screen UDP port, and the directories of the
+/// config file, the log file and the crash log.
+pub fn options(config: &SlaveConfig, config_path: &Path) -> DoctorOptions {
+    let mut directories = vec![("config".to_string(), parent(config_path))];
+    if !config.logging.file.is_empty() {
+        directories.push(("log".to_string(), parent(Path::new(&config.logging.file))));
+    }
+    if let Some(crash_log) = config.logging.crash_log() {
+        directories.push(("crash log".to_string(), parent(&crash_log)));
+    }
+    DoctorOptions {
+        capture_timeout_ms: config
+            .screen
+            .capture_timeout_ms
+            .max(DEFAULT_CAPTURE_TIMEOUT_MS),
+        udp_port: config.network.listen_port,
+        directories,
+        ..DoctorOptions::default()
+    }
+}
+
+fn parent(path: &Path) -> PathBuf {
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// The report as a table, one row per check, with the hints for those
+/// that did not pass underneath.
+pub fn render(report: &SelfTestReport) -> String {
+    let name_width = report
+        .checks
+        .iter()
+        .map(|check| check.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("CHECK".len());
+
+    let mut lines = vec![
+        format!("{:<name_width$}  STATUS  DETAIL", "CHECK"),
+        format!("{}  ------  ------", "-".repeat(name_width)),
+    ];
+    for check in &report.checks {
+        let status = check.status.to_string().to_uppercase();
+        lines.push(format!(
+            "{:<name_width$}  {:<6}  {}",
+            check.name, status, check.detail
+        ));
+    }
+
+    let hints: Vec<String> = report
+        .checks
+        .iter()
+        .filter_map(|check| Some(format!("  {}: {}", check.name, check.hint.as_ref()?)))
+        .collect();
+    if !hints.is_empty() {
+        lines.push(String::new());
+        lines.push("What to do:".to_string());
+        lines.extend(hints);
+    }
+    lines.push(String::new());
+    lines.push(format!("{}: {}", report.hostname, report.summary()));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use tix_core::protocol::selftest::CheckResult;
+
+    use super::*;
+
+    #[test]
+    fn options_follow_the_config() {
+        let mut config = SlaveConfig::default();
+        config.logging.file = "logs/slave.log".into();
+        config.logging.crash_file = "crashes/slave.jsonl".into();
+        let options = options(&config, Path::new("tix-rdp-slave.toml"));
+        assert_eq!(options.udp_port, config.network.listen_port);
+        assert_eq!(
+            options.directories,
+            [
+                ("config".to_string(), PathBuf::new()),
+                ("log".to_string(), PathBuf::from("logs")),
+                ("crash log".to_string(), PathBuf::from("crashes")),
+            ]
+        );
+        assert_eq!(options.capture_timeout_ms, DEFAULT_CAPTURE_TIMEOUT_MS);
+    }
+
+    #[test]
+    fn table_lists_checks_then_hints() {
+        let report = SelfTestReport {
+            hostname: "DESK-07".into(),
+            checks: vec![
+                CheckResult::pass("session", "session 1, desktop Default"),
+                CheckResult::fail("UDP port 7331", "address in use", "stop the other listener"),
+            ],
+        };
+        assert_eq!(
+            render(&report),
+            "CHECK          STATUS  DETAIL\n\
+             -------------  ------  ------\n\
+             session        PASS    session 1, desktop Default\n\
+             UDP port 7331  FAIL    address in use\n\
+             \n\
+             What to do:\n\
+             \x20 UDP port 7331: stop the other listener\n\
+             \n\
+             DESK-07: 2 checks: 1 pass, 0 warn, 1 fail"
+        );
+    }
+}
@@ -12,8 +12,11 @@
 //! - **Console**: Run in the foreground for debugging (`--console`).
 //! - **Service**: Run as a Windows service (default when launched by SCM).
 //! - **Install / Uninstall**: Register or remove the Windows service.
+//! - **Doctor**: Check capture, input, the UDP port and the config and
+//!   log directories, then exit (`--doctor`).
 
 pub mod config;
+pub mod doctor;
 pub mod service;
 
 #[cfg(target_os = "windows")]
@@ -6,14 +6,20 @@
 //! tix-rdp-slave --uninstall      Remove Windows service
 //! tix-rdp-slave --config <path>  Load a custom config TOML
 //! tix-rdp-slave --gen-config     Write default config to stdout
+//! tix-rdp-slave --ignore-config-errors  Run on the defaults if the config is bad
+//! tix-rdp-slave --doctor         Check this machine can run the slave
 //! ```
 
 use std::path::PathBuf;
 
 use clap::Parser;
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 
+use tix_core::{config, crash};
+use tix_core::protocol::selftest::CheckStatus;
+use tix_core::rdp::{doctor, telemetry};
+
 use tix_rdp_slave::config::SlaveConfig;
 use tix_rdp_slave::service::RdpSlaveService;
 
@@ -37,6 +43,16 @@ struct Cli {
     /// Print the default configuration to stdout and exit.
     #[arg(long)]
     gen_config: bool,
+
+    /// Run on the defaults when the config file is invalid instead of
+    /// exiting.
+    #[arg(long)]
+    ignore_config_errors: bool,
+
+    /// Check capture, input injection, the UDP port and the config and
+    /// log directories, print the results and exit (1 if any failed).
+    #[arg(long)]
+    doctor: bool,
 }
 
 // ── Main ─────────────────────────────────────────────────────────
@@ -75,7 +91,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Load config.
-    let config = SlaveConfig::load(&cli.config);
+    let (config, config_note) =
+        config::load_or_exit::<SlaveConfig>(&cli.config, cli.ignore_config_errors);
+
+    // --doctor: run the self-test and exit.
+    if cli.doctor {
+        if let Some(note) = config_note {
+            println!("{note}");
+        }
+        let options = tix_rdp_slave::doctor::options(&config, &cli.config);
+        let report = tokio::task::spawn_blocking(move || doctor::run(&options)).await?;
+        println!("{}", tix_rdp_slave::doctor::render(&report));
+        if report.status() == CheckStatus::Fail {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
     // Init tracing.
     let filter = EnvFilter::try_from_default_env()
@@ -83,13 +114,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt()
         .with_env_filter(filter)
         .init();
+    crash::install(
+        "tix-rdp-slave",
+        env!("CARGO_PKG_VERSION"),
+        config.logging.crash_log(),
+    );
 
     info!("tix-rdp-slave v{}", env!("CARGO_PKG_VERSION"));
-    info!("control port: {}", config.network.control_port);
+    if let Some(note) = config_note {
+        info!("{note}");
+    }
+    match config.network.connect_to.as_str() {
+        "" => info!("control port: {}", config.network.control_port),
+        master => info!("control: dialling {master}"),
+    }
     info!("screen UDP port: {}", config.network.listen_port);
     info!("target FPS: {}", config.screen.fps);
     info!("monitor: {}", config.screen.monitor_index);
 
+    if config.metrics.port != 0 {
+        match telemetry::serve(config.metrics.port) {
+            Ok(()) => info!("metrics on http://127.0.0.1:{}/metrics", config.metrics.port),
+            Err(e) => warn!("metrics endpoint not started: {e}"),
+        }
+    }
+
     // Run in console mode.
     let service = RdpSlaveService::new(config);
     let stop = service.stop_handle();
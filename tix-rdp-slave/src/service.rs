@@ -3,31 +3,82 @@
 //! Manages the lifecycle of the screen-capture pipeline and
 //! input-injection loop. Can run in either console or Windows
 //! service mode.
-
-use std::net::SocketAddr;
+//!
+//! The control connection is normally accepted from the viewer. With
+//! `network.connect_to` set the slave dials the viewer instead, for
+//! machines behind NAT; either way the same negotiation follows and the
+//! screen stream goes to the address the control connection reached.
+//!
+//! Sessions opened with a `ScreenStart` that offers `udp_input` also
+//! take pointer moves on their UDP socket. The port exchange has no room
+//! for the offer, so session 0 keeps all input on the control stream.
+//! The same goes for `timed_input`: once a `ScreenStart` ack has
+//! accepted it, the viewer may send its input stamped (tags 6 and 7),
+//! and pointer moves that later input already waiting on the stream has
+//! overtaken are dropped (see [`tix_core::rdp::burst`]).
+//!
+//! Tag 8 switches a session between frames and low-bandwidth thumbnails
+//! (see [`tix_core::rdp::thumbnail`]) and says whether the viewer can
+//! show thumbnails at all: until it does, `screen.low_bandwidth_fallback`
+//! stays dormant, since an older viewer would be left on its last frame.
+//! Sessions opened with tag 3 take both from their `ScreenStart`.
+//!
+//! Stopping the service winds the connected session down in order: the
+//! capture loops finish the frame they are on, the viewer gets a
+//! session-end frame on the control stream and sees it close, and the
+//! loops then drop their UDP sockets and capturers. All of it has
+//! `service.shutdown_grace_ms`; loops still running after that are
+//! aborted.
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, error, info, warn};
+
+use tix_core::protocol::screen::{
+    KeyEvent, MouseEvent, ScreenConfig, ScreenModeRequest, ScreenSharingRequest,
+    ScreenStartRequest, ScreenStartResponse, TimedKeyEvent, TimedMouseEvent,
+};
+use tix_core::TixError;
+use tix_core::rdp::burst::{InputBurst, TimedInput};
+use tix_core::rdp::capture::{DxgiCapturer, FrameSource};
+use tix_core::rdp::desktop::{DesktopProbe, InputDesktop};
+use tix_core::rdp::input::{InputGate, InputInjector};
+use tix_core::rdp::service::{ScreenService, ScreenServiceConfig};
+use tix_core::rdp::thumbnail::SharingSwitch;
+use tix_core::rdp::transport::{MAX_SESSIONS, ScreenTransport};
 
-use tokio::net::{TcpListener, UdpSocket};
-use tracing::{error, info, warn};
+use crate::config::SlaveConfig;
 
-use tix_core::protocol::screen::{KeyEvent, MouseEvent};
-use tix_core::rdp::input::InputInjector;
-use tix_core::rdp::service::ScreenService;
-use tix_core::rdp::transport::ScreenTransport;
+/// First pause between attempts to reach the viewer in `connect_to`
+/// mode; it doubles up to [`MAX_REDIAL_DELAY`].
+const REDIAL_DELAY: Duration = Duration::from_secs(1);
+const MAX_REDIAL_DELAY: Duration = Duration::from_secs(5);
 
-use crate::config::SlaveConfig;
+/// Builds the capture pipeline for a session.
+type ScreenBuilder = Box<
+    dyn Fn(ScreenTransport, ScreenServiceConfig) -> Result<ScreenService, TixError> + Send + Sync,
+>;
 
 // ── RdpSlaveService ──────────────────────────────────────────────
 
 /// The top-level RDP slave service.
 ///
-/// Owns the screen-capture service and a TCP control listener for
-/// accepting master connections, negotiating parameters, and
-/// forwarding input events.
+/// Owns the screen-capture service and the TCP control connection
+/// (accepted, or dialled with `connect_to`) used for negotiating
+/// parameters and forwarding input events.
 pub struct RdpSlaveService {
     config: SlaveConfig,
     running: Arc<AtomicBool>,
+    build_screen: ScreenBuilder,
 }
 
 impl RdpSlaveService {
@@ -36,9 +87,23 @@ impl RdpSlaveService {
         Self {
             config,
             running: Arc::new(AtomicBool::new(false)),
+            build_screen: Box::new(ScreenService::with_config),
         }
     }
 
+    /// Capture from the source `make` returns for a monitor index
+    /// instead of DXGI.
+    pub fn with_frame_source<S: FrameSource + 'static>(
+        mut self,
+        make: impl Fn(u32) -> S + Send + Sync + 'static,
+    ) -> Self {
+        self.build_screen = Box::new(move |transport, config| {
+            let source = make(config.monitor_index);
+            Ok(ScreenService::with_source(transport, config, source))
+        });
+        self
+    }
+
     /// Obtain a handle that can be used to stop the service from
     /// another task or the Windows SCM handler.
     pub fn stop_handle(&self) -> Arc<AtomicBool> {
@@ -47,57 +112,57 @@ impl RdpSlaveService {
 
     /// Run the service until stopped.
     ///
-    /// 1. Binds a TCP listener for control (handshake, input relay).
-    /// 2. Waits for a master to connect.
+    /// 1. Binds a TCP listener for control (handshake, input relay),
+    ///    unless `connect_to` is set.
+    /// 2. Waits for a master to connect, or dials `connect_to`.
     /// 3. Sets up a UDP socket pair and starts `ScreenService`.
     /// 4. Forwards incoming input events to `InputInjector`.
-    /// 5. Shuts down cleanly when `running` becomes `false`.
+    /// 5. Shuts down in order when `running` becomes `false` (see the
+    ///    module docs) and returns once the session is wound down.
     pub async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
         self.running.store(true, Ordering::SeqCst);
 
-        let control_addr: SocketAddr =
-            format!("0.0.0.0:{}", self.config.network.control_port).parse()?;
-        let listener = TcpListener::bind(control_addr).await?;
-        info!("RDP slave listening on {control_addr}");
-
-        // Accept masters until stopped.
-        while self.running.load(Ordering::SeqCst) {
-            let accept = tokio::select! {
-                result = listener.accept() => result,
-                _ = Self::wait_for_stop(&self.running) => break,
-            };
-
-            let (stream, peer) = match accept {
-                Ok(pair) => pair,
-                Err(e) => {
-                    warn!("accept error: {e}");
-                    continue;
-                }
-            };
+        let listener = match self.config.network.connect_to.as_str() {
+            "" => {
+                let control_addr: SocketAddr =
+                    format!("0.0.0.0:{}", self.config.network.control_port).parse()?;
+                let listener = TcpListener::bind(control_addr).await?;
+                info!("RDP slave listening on {control_addr}");
+                Some(listener)
+            }
+            master => {
+                info!("RDP slave dialling out to {master}");
+                None
+            }
+        };
 
+        // Serve masters one after another until stopped.
+        while let Some((stream, peer)) = self.next_master(listener.as_ref()).await {
             info!("master connected from {peer}");
 
             // Negotiate control channel (simplified: read the master's
-            // UDP port, respond with our UDP listen port).
-            let master_screen_addr = self.negotiate_control(&stream, peer).await;
-            let master_screen_addr = match master_screen_addr {
-                Ok(addr) => addr,
+            // UDP port, bind and tune our UDP socket, respond with our
+            // port and the stream MTU).
+            let negotiated = self.negotiate_control(&stream, peer).await;
+            let (udp, master_screen_addr, mtu, monitor) = match negotiated {
+                Ok(negotiated) => negotiated,
                 Err(e) => {
                     warn!("negotiation failed with {peer}: {e}");
                     continue;
                 }
             };
-
-            // Bind UDP for screen data.
-            let udp_addr: SocketAddr =
-                format!("0.0.0.0:{}", self.config.network.listen_port).parse()?;
-            let udp = UdpSocket::bind(udp_addr).await?;
-            info!("UDP screen transport on {udp_addr} → {master_screen_addr}");
-
-            let transport = ScreenTransport::new(udp, master_screen_addr);
-            let svc_config = self.config.to_service_config();
-
-            let mut screen_svc = match ScreenService::with_config(transport, svc_config) {
+            info!(
+                "UDP screen transport on port {} → {master_screen_addr} (MTU {mtu})",
+                self.config.network.listen_port
+            );
+
+            let transport = ScreenTransport::new(udp, master_screen_addr).with_mtu(mtu);
+            let mut svc_config = self.config.to_service_config();
+            svc_config.monitor_index = monitor;
+            // Until tag 8 says the viewer shows thumbnails.
+            svc_config.low_bandwidth_fallback = false;
+
+            let screen_svc = match (self.build_screen)(transport, svc_config) {
                 Ok(s) => s,
                 Err(e) => {
                     error!("failed to initialise screen service: {e}");
@@ -105,23 +170,27 @@ impl RdpSlaveService {
                 }
             };
 
-            let svc_running = screen_svc.stop_handle();
+            // Every session reports the breaker of the one injector.
+            let injector = InputInjector::new();
+            let mut sessions = Sessions::new(self.config.screen.max_sessions, peer.ip(), mtu);
+            sessions.insert(0, screen_svc.with_injector(injector.clone()));
             let global_running = Arc::clone(&self.running);
 
-            // Spawn screen capture loop.
-            let capture_handle = tokio::spawn(async move {
-                if let Err(e) = screen_svc.run().await {
-                    error!("screen service error: {e}");
-                }
-            });
-
             // Run input forwarding on the TCP control stream until
             // the master disconnects or the service is stopped.
-            let injector = InputInjector::new();
-            self.forward_input(stream, &injector, &global_running).await;
+            let gate = Arc::new(InputGate::new(true));
+            let writer = self
+                .forward_input(stream, &mut sessions, &injector, &gate, &global_running)
+                .await;
+            if gate.rejected() > 0 {
+                info!("refused {} input events from {peer} while view-only", gate.rejected());
+            }
 
-            svc_running.store(false, Ordering::SeqCst);
-            let _ = capture_handle.await;
+            if self.is_running() {
+                sessions.close_all().await;
+            } else {
+                self.shut_down(sessions, writer).await;
+            }
             info!("session with {peer} ended");
         }
 
@@ -142,59 +211,275 @@ impl RdpSlaveService {
 
     // ── Internal ─────────────────────────────────────────────────
 
+    /// The next control connection: accepted from `listener`, or dialled
+    /// to `connect_to` when there is none. `None` once stopped.
+    async fn next_master(
+        &self,
+        listener: Option<&TcpListener>,
+    ) -> Option<(TcpStream, SocketAddr)> {
+        let mut delay = REDIAL_DELAY;
+        loop {
+            let attempt = async {
+                match listener {
+                    Some(listener) => listener.accept().await,
+                    None => {
+                        let stream = TcpStream::connect(&self.config.network.connect_to).await?;
+                        let peer = stream.peer_addr()?;
+                        Ok((stream, peer))
+                    }
+                }
+            };
+            let result = tokio::select! {
+                result = attempt => result,
+                _ = Self::wait_for_stop(&self.running) => return None,
+            };
+            match result {
+                Ok(pair) => return Some(pair),
+                Err(e) if listener.is_some() => warn!("accept error: {e}"),
+                Err(e) => {
+                    debug!("cannot reach {}: {e}", self.config.network.connect_to);
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = Self::wait_for_stop(&self.running) => return None,
+                    }
+                    delay = (delay * 2).min(MAX_REDIAL_DELAY);
+                }
+            }
+        }
+    }
+
     /// Simple control-channel negotiation.
     ///
     /// Protocol (all little-endian):
     /// 1. Master sends 2-byte UDP port it is listening on.
-    /// 2. Slave responds with 2-byte UDP port it will send to.
+    /// 2. Slave binds its screen socket and, if `probe_mtu` is set,
+    ///    probes the path to that port (the master answers probes
+    ///    while it waits for step 3).
+    /// 3. Slave responds with 2-byte UDP port it will send from and
+    ///    2-byte stream MTU.
     ///
-    /// Returns the full `SocketAddr` of the master's screen-receive port.
+    /// A viewer showing several monitors adds a third byte to step 1,
+    /// the monitor for session 0, and gets the monitor's `i32` x and y
+    /// on the virtual desktop appended to the reply (12 bytes).
+    ///
+    /// Returns the bound screen socket, the full `SocketAddr` of the
+    /// master's screen-receive port, the MTU and the monitor to capture.
     async fn negotiate_control(
         &self,
-        stream: &tokio::net::TcpStream,
+        stream: &TcpStream,
         peer: SocketAddr,
-    ) -> Result<SocketAddr, Box<dyn std::error::Error>> {
-        let mut buf = [0u8; 2];
+    ) -> Result<(UdpSocket, SocketAddr, usize, u32), Box<dyn std::error::Error>> {
+        let mut buf = [0u8; 3];
         stream.readable().await?;
         let n = stream.try_read(&mut buf)?;
         if n < 2 {
             return Err("master did not send UDP port".into());
         }
+        let requested_monitor = (n == 3).then_some(buf[2] as u32);
 
-        let master_udp_port = u16::from_le_bytes(buf);
+        let master_udp_port = u16::from_le_bytes([buf[0], buf[1]]);
         let master_screen_addr = SocketAddr::new(peer.ip(), master_udp_port);
 
-        // Respond with our screen UDP port.
-        let our_port = self.config.network.listen_port;
+        // Bind UDP for screen data and settle the MTU.
+        let tuning = self.config.network.transport_config();
+        let udp = tuning.bind(SocketAddr::from(([0, 0, 0, 0], self.config.network.listen_port)))?;
+        let our_port = udp.local_addr()?.port();
+        let mtu = tuning.negotiate_mtu(&udp, master_screen_addr).await.min(u16::MAX as usize);
+
+        // Respond with our screen UDP port and the MTU, plus the
+        // monitor's origin if the master picked one.
+        let mut reply = Vec::with_capacity(12);
+        reply.extend_from_slice(&our_port.to_le_bytes());
+        reply.extend_from_slice(&(mtu as u16).to_le_bytes());
+        if let Some(monitor) = requested_monitor {
+            let (x, y) = DxgiCapturer::new(monitor).map(|c| c.origin()).unwrap_or_default();
+            reply.extend_from_slice(&x.to_le_bytes());
+            reply.extend_from_slice(&y.to_le_bytes());
+        }
         stream.writable().await?;
-        stream.try_write(&our_port.to_le_bytes())?;
+        stream.try_write(&reply)?;
+
+        let monitor = requested_monitor.unwrap_or(self.config.screen.monitor_index);
+        Ok((udp, master_screen_addr, mtu, monitor))
+    }
 
-        Ok(master_screen_addr)
+    /// Start the screen session `req` asks for, replacing one with the
+    /// same ID. It streams to the master's `req.udp_port` from
+    /// `listen_port + session`.
+    async fn open_session(
+        &self,
+        req: &ScreenStartRequest,
+        sessions: &mut Sessions,
+        gate: &Arc<InputGate>,
+        injector: &InputInjector,
+    ) -> ScreenStartResponse {
+        let id = req.session;
+        if id >= MAX_SESSIONS {
+            return ScreenStartResponse::Failed(format!(
+                "screen session {id} out of range (0-{})",
+                MAX_SESSIONS - 1
+            ));
+        }
+        sessions.close(id).await;
+        if !sessions.has_room() {
+            return ScreenStartResponse::Failed(format!(
+                "{} screen sessions already running (limit {})",
+                sessions.len(),
+                sessions.limit
+            ));
+        }
+        let Some(udp_port) = req.udp_port else {
+            return ScreenStartResponse::Failed("missing the master UDP port".into());
+        };
+
+        let started = async {
+            let (width, height, origin) = {
+                let probe = DxgiCapturer::new(req.monitor as u32).map_err(|e| e.to_string())?;
+                (probe.width(), probe.height(), probe.origin())
+            };
+            let our_port = match self.config.network.listen_port {
+                0 => 0,
+                port => port.wrapping_add(id as u16),
+            };
+            let udp = self
+                .config
+                .network
+                .transport_config()
+                .bind(SocketAddr::from(([0, 0, 0, 0], our_port)))
+                .map_err(|e| format!("UDP bind failed: {e}"))?;
+            let our_port = udp.local_addr().map_err(|e| e.to_string())?.port();
+            let master = SocketAddr::new(sessions.master_ip, udp_port);
+            let transport = ScreenTransport::new(udp, master)
+                .with_mtu(sessions.mtu)
+                .with_session(id);
+
+            let mut svc_config = self.config.to_service_config();
+            svc_config.monitor_index = req.monitor as u32;
+            svc_config.low_bandwidth = req.low_bandwidth;
+            svc_config.low_bandwidth_fallback &= req.thumbnails;
+            let thumbnails = svc_config.low_bandwidth_fallback;
+            let (fps, format) = (svc_config.target_fps, svc_config.pixel_format.into());
+            let block_size = svc_config.block_size as u16;
+            let mut service = (self.build_screen)(transport, svc_config)
+                .map_err(|e| e.to_string())?
+                .with_injector(injector.clone());
+            if req.udp_input {
+                service = service.with_pointer_input(Arc::clone(gate));
+            }
+            sessions.insert(id, service);
+            info!("screen session {id}: monitor {} → {master}", req.monitor);
+
+            Ok::<_, String>(ScreenConfig {
+                width,
+                height,
+                quality: req.quality,
+                fps,
+                format,
+                monitor_name: format!("Monitor {}", req.monitor),
+                udp_endpoint: Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), our_port)),
+                mtu: sessions.mtu as u16,
+                session: id,
+                origin,
+                block_size,
+                udp_input: req.udp_input,
+                // The standalone service has no `ScreenDictionary`
+                // handler, so it never offers one.
+                dictionary: None,
+                preflight: None,
+                timed_input: req.timed_input,
+                thumbnails,
+                low_bandwidth: req.low_bandwidth,
+            })
+        };
+        match started.await {
+            Ok(config) => ScreenStartResponse::Started(config),
+            Err(e) => {
+                warn!("screen session {id} failed to start: {e}");
+                ScreenStartResponse::Failed(e)
+            }
+        }
+    }
+
+    /// Wind down the session when the service is stopping: let every
+    /// capture loop finish its frame, send the viewer a tag-5 frame and
+    /// close the stream, then wait for the loops to release their
+    /// sockets and capturers, aborting those that outlast the grace
+    /// period.
+    async fn shut_down(&self, mut sessions: Sessions, mut writer: OwnedWriteHalf) {
+        let grace = self.config.service.shutdown_grace();
+        let deadline = Instant::now() + grace;
+        sessions.signal_all();
+
+        let notice = async {
+            Self::write_frame(&mut writer, 5, b"service stopping").await?;
+            writer.shutdown().await?;
+            Ok::<_, Box<dyn std::error::Error>>(())
+        };
+        match tokio::time::timeout_at(deadline, notice).await {
+            Ok(Ok(())) => debug!("told the viewer the session ended"),
+            Ok(Err(e)) => warn!("cannot tell the viewer the session ended: {e}"),
+            Err(_) => warn!("timed out telling the viewer the session ended"),
+        }
+
+        let aborted = sessions.join_until(deadline).await;
+        if aborted > 0 {
+            warn!("aborted {aborted} screen session(s) still running after {grace:?}");
+        }
     }
 
     /// Read input events from the TCP control stream and inject them.
+    /// Returns the write half once the stream closes or the service
+    /// stops.
     ///
     /// Wire format per event (little-endian):
     /// ```text
-    /// tag:  u8   (0 = mouse, 1 = keyboard)
-    /// data: [u8] (bincode-serialised MouseEvent or KeyEvent)
+    /// tag:  u8   (0 = mouse, 1 = keyboard, 2 = mode change,
+    ///            3 = open screen session, 4 = close screen session,
+    ///            6 = timed mouse, 7 = timed keyboard,
+    ///            8 = frames or thumbnails)
     /// len:  u16  (length of `data`)
+    /// data: [u8] (bincode-serialised MouseEvent, KeyEvent,
+    ///            ScreenModeRequest, ScreenStartRequest, TimedMouseEvent,
+    ///            TimedKeyEvent or ScreenSharingRequest; the session byte
+    ///            for tag 4)
     /// ```
+    ///
+    /// Timed events are collected while more of the stream is already
+    /// buffered and applied together once it runs dry, before any other
+    /// frame, so moves the rest of the burst has overtaken can be
+    /// dropped.
+    ///
+    /// Sessions start in control mode; a mode change to view-only makes
+    /// `gate` refuse every later mouse and keyboard event, including the
+    /// pointer moves of sessions that take them over UDP, until control
+    /// is restored. Refusals get no reply, so they are only logged (once
+    /// per switch) and counted. Tag 3 is the one request answered: a
+    /// frame of the same layout carrying a bincode ScreenStartResponse.
+    /// The only frame sent unasked is tag 5, the reason in UTF-8, when
+    /// the service stops.
     async fn forward_input(
         &self,
-        stream: tokio::net::TcpStream,
+        stream: TcpStream,
+        sessions: &mut Sessions,
         injector: &InputInjector,
+        gate: &Arc<InputGate>,
         running: &Arc<AtomicBool>,
-    ) {
+    ) -> OwnedWriteHalf {
         use tokio::io::AsyncReadExt;
 
-        let mut stream = tokio::io::BufReader::new(stream);
+        let (reader, mut writer) = stream.into_split();
+        let mut stream = tokio::io::BufReader::new(reader);
         let mut header = [0u8; 3]; // tag(1) + len(2)
+        let mut desktop = InputDesktop;
+        let mut burst = InputBurst::new(self.config.screen.stale_move());
 
         loop {
             if !running.load(Ordering::SeqCst) {
                 break;
             }
+            if !burst.is_empty() && (stream.buffer().is_empty() || burst.is_full()) {
+                self.apply_input(&mut burst, injector, &mut desktop);
+            }
 
             let read = tokio::select! {
                 r = stream.read_exact(&mut header) => r,
@@ -221,13 +506,26 @@ impl RdpSlaveService {
                 break;
             }
 
+            if matches!(tag, 0 | 1 | 6 | 7) && gate.admit().is_err() {
+                if gate.rejected() == 1 {
+                    warn!("view-only session: dropping input from the master");
+                }
+                continue;
+            }
+            if !matches!(tag, 6 | 7) {
+                self.apply_input(&mut burst, injector, &mut desktop);
+            }
+
             match tag {
                 0 => {
                     // Mouse event.
                     match bincode::deserialize::<MouseEvent>(&payload) {
                         Ok(ev) => {
-                            if let Err(e) = injector.inject_mouse(&ev) {
-                                warn!("inject_mouse error: {e}");
+                            self.follow_input_desktop(&mut desktop);
+                            match injector.inject_mouse(&ev) {
+                                Ok(()) => {}
+                                Err(TixError::InputBlocked(e)) => debug!("mouse dropped: {e}"),
+                                Err(e) => warn!("inject_mouse error: {e}"),
                             }
                         }
                         Err(e) => warn!("malformed mouse event: {e}"),
@@ -237,18 +535,132 @@ impl RdpSlaveService {
                     // Keyboard event.
                     match bincode::deserialize::<KeyEvent>(&payload) {
                         Ok(ev) => {
-                            if let Err(e) = injector.inject_keyboard(&ev) {
-                                warn!("inject_keyboard error: {e}");
+                            self.follow_input_desktop(&mut desktop);
+                            match injector.inject_keyboard(&ev) {
+                                Ok(()) => {}
+                                Err(TixError::InputBlocked(e)) => debug!("key dropped: {e}"),
+                                Err(e) => warn!("inject_keyboard error: {e}"),
                             }
                         }
                         Err(e) => warn!("malformed key event: {e}"),
                     }
                 }
+                2 => match bincode::deserialize::<ScreenModeRequest>(&payload) {
+                    Ok(mode) => {
+                        gate.set_control(mode.control);
+                        info!(
+                            "session is now {}",
+                            if mode.control { "in control" } else { "view-only" }
+                        );
+                    }
+                    Err(e) => warn!("malformed mode change: {e}"),
+                },
+                3 => {
+                    let response = match ScreenStartRequest::from_bytes(&payload) {
+                        Ok(req) => self.open_session(&req, sessions, gate, injector).await,
+                        Err(e) => ScreenStartResponse::Failed(format!("bad session request: {e}")),
+                    };
+                    if let Err(e) = Self::reply(&mut writer, 3, &response).await {
+                        warn!("cannot answer the session request: {e}");
+                        break;
+                    }
+                }
+                4 => match payload.first() {
+                    Some(&id) if sessions.close(id).await => info!("screen session {id} closed"),
+                    Some(id) => debug!("no screen session {id} to close"),
+                    None => warn!("session close without a session"),
+                },
+                6 => match TimedMouseEvent::from_bytes(&payload) {
+                    Ok(ev) => burst.push(TimedInput::Mouse(ev)),
+                    Err(e) => warn!("malformed timed mouse event: {e}"),
+                },
+                7 => match TimedKeyEvent::from_bytes(&payload) {
+                    Ok(ev) => burst.push(TimedInput::Key(ev)),
+                    Err(e) => warn!("malformed timed key event: {e}"),
+                },
+                8 => match ScreenSharingRequest::from_bytes(&payload) {
+                    Ok(req) => {
+                        let fallback = req.fallback && self.config.screen.low_bandwidth_fallback;
+                        if sessions.switch_sharing(req.session, req.low_bandwidth, fallback) {
+                            info!(
+                                "screen session {}: {}",
+                                req.session,
+                                if req.low_bandwidth { "thumbnails" } else { "frames" }
+                            );
+                        } else {
+                            debug!("no screen session {} to switch", req.session);
+                        }
+                    }
+                    Err(e) => warn!("malformed sharing switch: {e}"),
+                },
                 _ => {
                     warn!("unknown input tag: {tag}");
                 }
             }
         }
+        writer
+    }
+
+    /// Send `response` back on the control stream as a `tag` frame.
+    async fn reply(
+        writer: &mut OwnedWriteHalf,
+        tag: u8,
+        response: &ScreenStartResponse,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Self::write_frame(writer, tag, &response.to_bytes()?).await
+    }
+
+    async fn write_frame(
+        writer: &mut OwnedWriteHalf,
+        tag: u8,
+        data: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut frame = vec![tag];
+        frame.extend_from_slice(&u16::try_from(data.len())?.to_le_bytes());
+        frame.extend_from_slice(data);
+        writer.write_all(&frame).await?;
+        Ok(())
+    }
+
+    /// With `secure_desktop_capture` on, move the injecting thread onto
+    /// the input desktop first, so a UAC prompt receives the event. The
+    /// task may run on a different worker each time, hence per event.
+    /// Inject the timed input waiting in `burst`, minus the pointer
+    /// moves later input has overtaken.
+    fn apply_input(
+        &self,
+        burst: &mut InputBurst,
+        injector: &InputInjector,
+        desktop: &mut InputDesktop,
+    ) {
+        if burst.is_empty() {
+            return;
+        }
+        let dropped = burst.dropped();
+        let inputs = burst.take();
+        if dropped == 0 && burst.dropped() > 0 {
+            info!("input is backing up; dropping pointer moves it has overtaken");
+        }
+        self.follow_input_desktop(desktop);
+        for input in inputs {
+            let result = match input {
+                TimedInput::Mouse(ev) => injector.inject_mouse(&ev.event),
+                TimedInput::Key(ev) => injector.inject_keyboard(&ev.event),
+            };
+            match result {
+                Ok(()) => {}
+                Err(TixError::InputBlocked(e)) => debug!("input dropped: {e}"),
+                Err(e) => warn!("input injection error: {e}"),
+            }
+        }
+    }
+
+    fn follow_input_desktop(&self, desktop: &mut InputDesktop) {
+        if self.config.screen.secure_desktop_capture
+            && let Err(e) = desktop.attach()
+        {
+            debug!("cannot attach input to the secure desktop: {e}");
+        }
     }
 
     /// Async helper: resolves when `running` becomes false.
@@ -257,8 +669,111 @@ impl RdpSlaveService {
             if !running.load(Ordering::SeqCst) {
                 return;
             }
-            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+// ── Sessions ─────────────────────────────────────────────────────
+
+/// The screen sessions of one master connection, by session ID.
+struct Sessions {
+    limit: usize,
+    master_ip: IpAddr,
+    mtu: usize,
+    running: BTreeMap<u8, (Arc<AtomicBool>, JoinHandle<()>)>,
+    /// Each running session's switch between frames and thumbnails.
+    sharing: BTreeMap<u8, Arc<SharingSwitch>>,
+}
+
+impl Sessions {
+    fn new(limit: usize, master_ip: IpAddr, mtu: usize) -> Self {
+        Self {
+            limit,
+            master_ip,
+            mtu,
+            running: BTreeMap::new(),
+            sharing: BTreeMap::new(),
+        }
+    }
+
+    /// Spawn `service`'s capture loop as session `id`.
+    fn insert(&mut self, id: u8, mut service: ScreenService) {
+        let stop = service.stop_handle();
+        self.sharing.insert(id, service.sharing_switch());
+        let handle = tokio::spawn(async move {
+            if let Err(e) = service.run().await {
+                error!("screen service error: {e}");
+            }
+            // Release the duplication and the UDP socket now rather
+            // than whenever the task is reaped.
+            drop(service);
+        });
+        self.running.insert(id, (stop, handle));
+    }
+
+    /// Whether another session may start. Sessions whose capture loop
+    /// has died no longer count.
+    fn has_room(&mut self) -> bool {
+        self.running.retain(|_, (_, handle)| !handle.is_finished());
+        self.running.len() < self.limit
+    }
+
+    fn len(&self) -> usize {
+        self.running.len()
+    }
+
+    /// Send session `id` thumbnails (`low_bandwidth`) or frames, and
+    /// allow it to fall back to thumbnails by itself or not; false if
+    /// there is no such session.
+    fn switch_sharing(&self, id: u8, low_bandwidth: bool, fallback: bool) -> bool {
+        let Some(switch) = self.sharing.get(&id) else {
+            return false;
+        };
+        switch.set_fallback(fallback);
+        switch.request(low_bandwidth);
+        true
+    }
+
+    /// Stop session `id` and wait for it; false if there was none.
+    async fn close(&mut self, id: u8) -> bool {
+        self.sharing.remove(&id);
+        let Some((stop, handle)) = self.running.remove(&id) else {
+            return false;
+        };
+        stop.store(false, Ordering::SeqCst);
+        let _ = handle.await;
+        true
+    }
+
+    async fn close_all(&mut self) {
+        self.signal_all();
+        self.sharing.clear();
+        for (_, (_, handle)) in std::mem::take(&mut self.running) {
+            let _ = handle.await;
+        }
+    }
+
+    /// Ask every capture loop to stop after its current frame.
+    fn signal_all(&self) {
+        for (stop, _) in self.running.values() {
+            stop.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Wait for every session until `deadline`, then abort the rest.
+    /// Returns how many had to be aborted.
+    async fn join_until(&mut self, deadline: Instant) -> usize {
+        let mut aborted = 0;
+        self.sharing.clear();
+        for (id, (_, mut handle)) in std::mem::take(&mut self.running) {
+            if tokio::time::timeout_at(deadline, &mut handle).await.is_err() {
+                warn!("screen session {id} did not stop in time");
+                handle.abort();
+                aborted += 1;
+            }
         }
+        aborted
     }
 }
 
@@ -275,6 +790,130 @@ mod tests {
         assert!(!svc.is_running());
     }
 
+    fn frame(tag: u8, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        out.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[tokio::test]
+    async fn view_only_mode_blocks_input_until_restored() {
+        let mouse = bincode::serialize(&MouseEvent::move_to(10, 10)).unwrap();
+        let timed = TimedMouseEvent {
+            at_ms: 5,
+            event: MouseEvent::move_to(10, 10),
+        }
+        .to_bytes()
+        .unwrap();
+        let view_only = bincode::serialize(&ScreenModeRequest { control: false }).unwrap();
+        let control = bincode::serialize(&ScreenModeRequest { control: true }).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let master = tokio::spawn(async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            for bytes in [
+                frame(2, &view_only),
+                frame(0, &mouse),
+                frame(6, &timed),
+                frame(0, &mouse),
+                frame(2, &control),
+                frame(0, &mouse),
+                frame(6, &timed),
+            ] {
+                stream.write_all(&bytes).await.unwrap();
+            }
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+
+        let svc = RdpSlaveService::new(SlaveConfig::default());
+        let running = Arc::new(AtomicBool::new(true));
+        let gate = Arc::new(InputGate::new(true));
+        let mut sessions = Sessions::new(1, addr.ip(), 1400);
+        svc.forward_input(stream, &mut sessions, &InputInjector::new(), &gate, &running)
+            .await;
+        master.await.unwrap();
+
+        // The events sent while view-only were refused, timed or not;
+        // the ones after control came back went through to the injector.
+        assert_eq!(gate.rejected(), 3);
+        assert!(gate.allows_control());
+    }
+
+    #[tokio::test]
+    async fn session_requests_are_answered_and_limited() {
+        use tokio::io::AsyncReadExt;
+
+        let request = ScreenStartRequest::default()
+            .with_udp_port(40000)
+            .with_monitor(1)
+            .with_session(1)
+            .to_bytes()
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let master = tokio::spawn(async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            stream.write_all(&frame(3, &request)).await.unwrap();
+            let mut header = [0u8; 3];
+            stream.read_exact(&mut header).await.unwrap();
+            assert_eq!(header[0], 3);
+            let mut data = vec![0u8; u16::from_le_bytes([header[1], header[2]]) as usize];
+            stream.read_exact(&mut data).await.unwrap();
+            stream.write_all(&frame(4, &[0])).await.unwrap();
+            ScreenStartResponse::from_bytes(&data).unwrap()
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+
+        // Session 0 stands in for the one the handshake started.
+        let mut sessions = Sessions::new(1, addr.ip(), 1400);
+        let stop = Arc::new(AtomicBool::new(true));
+        let flag = Arc::clone(&stop);
+        let handle = tokio::spawn(async move {
+            while flag.load(Ordering::SeqCst) {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        });
+        sessions.running.insert(0, (Arc::clone(&stop), handle));
+
+        let svc = RdpSlaveService::new(SlaveConfig::default());
+        let running = Arc::new(AtomicBool::new(true));
+        let gate = Arc::new(InputGate::new(true));
+        svc.forward_input(stream, &mut sessions, &InputInjector::new(), &gate, &running)
+            .await;
+
+        match master.await.unwrap() {
+            ScreenStartResponse::Failed(reason) => assert!(reason.contains("limit"), "{reason}"),
+            other => panic!("expected a refusal, got {other:?}"),
+        }
+        assert_eq!(sessions.len(), 0);
+        assert!(!stop.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn sessions_outlasting_the_grace_period_are_aborted() {
+        let mut sessions = Sessions::new(2, IpAddr::V4(Ipv4Addr::LOCALHOST), 1400);
+        for (id, obeys) in [(0, true), (1, false)] {
+            let stop = Arc::new(AtomicBool::new(true));
+            let flag = Arc::clone(&stop);
+            let handle = tokio::spawn(async move {
+                while !obeys || flag.load(Ordering::SeqCst) {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            });
+            sessions.running.insert(id, (stop, handle));
+        }
+
+        sessions.signal_all();
+        let started = Instant::now();
+        let aborted = sessions.join_until(started + Duration::from_millis(200)).await;
+        assert_eq!(aborted, 1);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(sessions.len(), 0);
+    }
+
     #[test]
     fn stop_handle_works() {
         let svc = RdpSlaveService::new(SlaveConfig::default());
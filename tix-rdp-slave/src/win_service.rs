@@ -2,13 +2,15 @@
 //!
 //! Registers the process with the Windows Service Control Manager
 //! (SCM) and translates service control messages (stop, shutdown)
-//! into the `running` flag used by [`RdpSlaveService`].
+//! into the `running` flag used by [`RdpSlaveService`]. While the
+//! service winds its session down the SCM sees `SERVICE_STOP_PENDING`,
+//! with the shutdown grace period as the wait hint.
 //!
 //! Also provides `install` / `uninstall` helpers.
 
 #![cfg(target_os = "windows")]
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::sync::{Arc, OnceLock};
 
 use tracing::{error, info};
@@ -23,6 +25,17 @@ use crate::service::RdpSlaveService;
 /// Global stop flag shared with the SCM handler callback.
 static STOP_FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
 
+/// The status handle, as an address, for the handler to report
+/// `SERVICE_STOP_PENDING` with.
+static STATUS_HANDLE: AtomicUsize = AtomicUsize::new(0);
+
+/// Wait hint for `SERVICE_STOP_PENDING`, in milliseconds.
+static STOP_WAIT_HINT: AtomicU32 = AtomicU32::new(0);
+
+/// Added to the shutdown grace period for the wait hint, covering the
+/// runtime teardown after the session is wound down.
+const STOP_HINT_MARGIN_MS: u64 = 1000;
+
 const SERVICE_NAME: PCWSTR = w!("TixRdpSlave");
 const SERVICE_DISPLAY: PCWSTR = w!("TIX RDP Slave Service");
 const SERVICE_DESCRIPTION_TEXT: PCWSTR =
@@ -160,6 +173,14 @@ unsafe extern "system" fn service_main_trampoline(
 
     // Build the service.
     let config = SlaveConfig::default(); // In production, load from file.
+    tix_core::crash::install(
+        "tix-rdp-slave",
+        env!("CARGO_PKG_VERSION"),
+        config.logging.crash_log(),
+    );
+    let wait_hint = config.service.shutdown_grace_ms + STOP_HINT_MARGIN_MS;
+    STOP_WAIT_HINT.store(wait_hint.min(u32::MAX as u64) as u32, Ordering::SeqCst);
+    STATUS_HANDLE.store(status_handle.0 as usize, Ordering::SeqCst);
     let svc = RdpSlaveService::new(config);
     let _ = STOP_FLAG.set(svc.stop_handle());
 
@@ -182,6 +203,16 @@ unsafe extern "system" fn service_main_trampoline(
 unsafe extern "system" fn ctrl_handler(control: u32) {
     match control {
         SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            let handle = STATUS_HANDLE.load(Ordering::SeqCst);
+            if handle != 0 {
+                let handle = SERVICE_STATUS_HANDLE(handle as *mut std::ffi::c_void);
+                report_status(
+                    handle,
+                    SERVICE_STOP_PENDING,
+                    0,
+                    STOP_WAIT_HINT.load(Ordering::SeqCst),
+                );
+            }
             if let Some(flag) = STOP_FLAG.get() {
                 flag.store(false, Ordering::SeqCst);
             }
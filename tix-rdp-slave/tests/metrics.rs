@@ -0,0 +1,73 @@
+//! Pipeline metrics from both ends of a loopback screen stream.
+
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use tix_core::rdp::client::ScreenClient;
+use tix_core::rdp::service::{ScreenService, ScreenServiceConfig};
+use tix_core::rdp::telemetry;
+use tix_core::rdp::transport::ScreenTransport;
+use tix_core::rdp::types::PixelFormat;
+use tix_core::testing::SyntheticSource;
+use tokio::net::UdpSocket;
+
+#[tokio::test]
+async fn histograms_fill_on_both_ends() {
+    let handle = telemetry::install_recorder().unwrap();
+
+    let send_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let recv_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let send_addr = send_sock.local_addr().unwrap();
+    let recv_addr = recv_sock.local_addr().unwrap();
+
+    let config = ScreenServiceConfig {
+        block_size: 16,
+        ..ScreenServiceConfig::default()
+    };
+    let mut service = ScreenService::with_source(
+        ScreenTransport::new(send_sock, recv_addr),
+        config,
+        SyntheticSource::new(64, 48),
+    );
+    let stop_service = service.stop_handle();
+    let service_task = tokio::spawn(async move { service.run().await });
+
+    let mut client = ScreenClient::new(
+        ScreenTransport::new(recv_sock, send_addr),
+        PixelFormat::Bgra8,
+    );
+    let mut stats = client.stats_receiver();
+    let stop_client = client.stop_handle();
+    let client_task = tokio::spawn(async move { client.run().await });
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while stats.borrow().total_frames < 5 {
+            stats.changed().await.unwrap();
+        }
+    })
+    .await
+    .expect("stream stalled");
+
+    stop_service.store(false, Ordering::SeqCst);
+    stop_client.store(false, Ordering::SeqCst);
+    service_task.await.unwrap().unwrap();
+    client_task.abort();
+
+    let text = handle.render();
+    for series in [
+        r#"tix_rdp_stage_seconds_count{role="slave",stage="encode"}"#,
+        r#"tix_rdp_stage_seconds_count{role="slave",stage="send"}"#,
+        r#"tix_rdp_stage_seconds_count{role="viewer",stage="decode"}"#,
+        r#"tix_rdp_frame_latency_seconds_count{role="viewer"}"#,
+        r#"tix_rdp_frames_total{role="slave",stage="sent"}"#,
+        r#"tix_rdp_frames_total{role="viewer",stage="received"}"#,
+        r#"tix_rdp_bytes_total{role="slave"}"#,
+    ] {
+        let count = text
+            .lines()
+            .find_map(|line| line.strip_prefix(series))
+            .and_then(|rest| rest.trim().parse::<f64>().ok())
+            .unwrap_or_else(|| panic!("{series} missing from:\n{text}"));
+        assert!(count >= 5.0, "{series} = {count}");
+    }
+}
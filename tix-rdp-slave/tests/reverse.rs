@@ -0,0 +1,68 @@
+//! A slave behind NAT dials the viewer, negotiates and streams to it.
+
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use tix_core::rdp::client::ScreenClient;
+use tix_core::rdp::transport::ScreenTransport;
+use tix_core::rdp::types::PixelFormat;
+use tix_core::testing::SyntheticSource;
+use tix_rdp_gui::config::GuiConfig;
+use tix_rdp_gui::connection::SlaveConnection;
+use tix_rdp_slave::config::SlaveConfig;
+use tix_rdp_slave::service::RdpSlaveService;
+
+#[tokio::test]
+async fn slave_dials_the_listening_viewer_and_streams() {
+    let port = {
+        let probe = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        probe.local_addr().unwrap().port()
+    };
+
+    // The slave dials before the viewer listens and has to try again.
+    let mut slave_config = SlaveConfig::default();
+    slave_config.network.connect_to = format!("127.0.0.1:{port}");
+    slave_config.network.listen_port = 0;
+    slave_config.screen.block_size = 16;
+    let service =
+        RdpSlaveService::new(slave_config).with_frame_source(|_| SyntheticSource::new(64, 48));
+    let stop = service.stop_handle();
+
+    let viewer = async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let mut config = GuiConfig::default();
+        config.network.listen_port = port;
+        config.network.slave_address = "203.0.113.1:7332".into(); // never dialled
+        config.network.timeout_ms = 10_000;
+        let udp = config
+            .network
+            .transport_config()
+            .bind("127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        let conn = SlaveConnection::connect(&config, &udp).await.unwrap();
+        let slave_screen_addr = conn.slave_screen_addr().unwrap();
+        assert!(slave_screen_addr.ip().is_loopback());
+        assert_ne!(slave_screen_addr.port(), 0);
+
+        let mut client = ScreenClient::new(
+            ScreenTransport::new(udp, slave_screen_addr).with_mtu(conn.mtu()),
+            PixelFormat::Bgra8,
+        );
+        let mut stats = client.stats_receiver();
+        let client_task = tokio::spawn(async move { client.run().await });
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while stats.borrow().total_frames < 3 {
+                stats.changed().await.unwrap();
+            }
+        })
+        .await
+        .expect("no frames reached the viewer");
+
+        drop(conn);
+        client_task.abort();
+        stop.store(false, Ordering::SeqCst);
+    };
+
+    let (result, ()) = tokio::join!(service.run(), viewer);
+    result.unwrap();
+}
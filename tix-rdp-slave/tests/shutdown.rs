@@ -0,0 +1,106 @@
+//! Stopping a console-mode slave mid-stream: the viewer is told the
+//! session ended before the control stream closes, and the stream stops.
+
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use tix_core::testing::SyntheticSource;
+use tix_rdp_gui::config::GuiConfig;
+use tix_rdp_gui::connection::SlaveConnection;
+use tix_rdp_slave::config::SlaveConfig;
+use tix_rdp_slave::service::RdpSlaveService;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// A slave listening on a free control port, streaming synthetic frames.
+fn slave() -> (RdpSlaveService, u16) {
+    let port = {
+        let probe = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        probe.local_addr().unwrap().port()
+    };
+    let mut config = SlaveConfig::default();
+    config.network.control_port = port;
+    config.network.listen_port = 0;
+    config.screen.block_size = 16;
+    config.service.shutdown_grace_ms = 2000;
+    let service = RdpSlaveService::new(config).with_frame_source(|_| SyntheticSource::new(64, 48));
+    (service, port)
+}
+
+#[tokio::test]
+async fn stopping_tells_the_viewer_before_closing() {
+    let (service, port) = slave();
+    let stop = service.stop_handle();
+
+    let viewer = async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut control = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        control
+            .write_all(&udp.local_addr().unwrap().port().to_le_bytes())
+            .await
+            .unwrap();
+        let mut reply = [0u8; 4];
+        control.read_exact(&mut reply).await.unwrap();
+
+        // Streaming: the first datagram arrives.
+        let mut datagram = vec![0u8; 65536];
+        tokio::time::timeout(Duration::from_secs(5), udp.recv(&mut datagram))
+            .await
+            .expect("no screen data before the stop")
+            .unwrap();
+
+        let stopped_at = Instant::now();
+        stop.store(false, Ordering::SeqCst);
+
+        // A tag-5 frame with the reason, then the end of the stream.
+        let mut header = [0u8; 3];
+        tokio::time::timeout(Duration::from_secs(5), control.read_exact(&mut header))
+            .await
+            .expect("no session-end frame")
+            .unwrap();
+        assert_eq!(header[0], 5);
+        let mut reason = vec![0u8; u16::from_le_bytes([header[1], header[2]]) as usize];
+        control.read_exact(&mut reason).await.unwrap();
+        assert_eq!(reason, b"service stopping");
+        let mut rest = Vec::new();
+        control.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty(), "{} bytes after the session-end frame", rest.len());
+        stopped_at
+    };
+
+    let (result, stopped_at) = tokio::join!(service.run(), viewer);
+    result.unwrap();
+    assert!(stopped_at.elapsed() < Duration::from_secs(2), "{:?}", stopped_at.elapsed());
+}
+
+#[tokio::test]
+async fn the_viewer_connection_reports_the_session_end() {
+    let (service, port) = slave();
+    let stop = service.stop_handle();
+
+    let viewer = async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let mut config = GuiConfig::default();
+        config.network.slave_address = format!("127.0.0.1:{port}");
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut conn = SlaveConnection::connect(&config, &udp).await.unwrap();
+        assert_eq!(conn.session_ended().await, None);
+
+        stop.store(false, Ordering::SeqCst);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(reason) = conn.session_ended().await {
+                    return reason;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the session end was not noticed")
+    };
+
+    let (result, reason) = tokio::join!(service.run(), viewer);
+    result.unwrap();
+    assert_eq!(reason, "service stopping");
+}
@@ -0,0 +1,477 @@
+//! Append-only audit log of privileged commands.
+//!
+//! Every command named in `audit.commands` (names or patterns such as
+//! `File*`, see [`tix_core::policy`]) is written to the log as one JSON
+//! line when it is dispatched, whether it goes on to run or is refused:
+//!
+//! ```json
+//! {"ts_ms":1760600000000,"peer":"10.0.0.2:4321","command":"ShellExecute","request_id":7,"args":"whoami","outcome":"accepted","prev":"9f2c…"}
+//! ```
+//!
+//! With `audit.hash_chain` on, `prev` is the Blake3 hash of the line
+//! before it, so an edited, inserted or deleted line breaks the chain
+//! from that point on. `tix-slave --verify-audit` walks the rotated files
+//! oldest first and then the live one. The chain carries across
+//! rotations; the first line kept is taken on trust, as whatever it
+//! pointed to may have been rotated away.
+//!
+//! Entries are synced to disk before the command runs, except input
+//! events, which are too frequent to fsync one by one.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tix_core::Command;
+use tix_core::policy::CommandSet;
+use tix_core::protocol::{
+    CopyRequest, FileDeleteRequest, FileTransactionRequest, FileTransferHeader,
+    ScheduleCreateRequest, ScheduleNameRequest, ScreenDictionaryRequest, ScreenModeRequest,
+    ScreenPreflightRequest, ScreenSharingRequest, ScreenStartRequest, ServiceControlRequest,
+    ShellExecuteRequest, TrashRestoreRequest, UpdateApplyRequest, WireTapSettings,
+};
+
+use crate::config::AuditConfig;
+
+/// Longest free-text argument kept in an entry, in characters.
+const MAX_ARGS_CHARS: usize = 256;
+
+/// One line of the log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix time in milliseconds.
+    pub ts_ms: u64,
+    /// Address of the master that sent the command.
+    pub peer: String,
+    pub command: String,
+    pub request_id: u64,
+    /// Short, human-readable summary of the payload.
+    pub args: String,
+    /// `accepted`, or why the command was refused.
+    pub outcome: String,
+    /// Hex Blake3 of the previous line, when hash-chaining.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev: Option<String>,
+}
+
+/// An open log file and its rotation state.
+struct AuditLog {
+    path: PathBuf,
+    file: File,
+    /// Size of the live file.
+    written: u64,
+    max_bytes: u64,
+    keep: usize,
+    hash_chain: bool,
+    /// Hash of the last line written, the next entry's `prev`.
+    last_hash: Option<String>,
+    commands: CommandSet,
+}
+
+/// Handle to the audit log, shared by every connection. The default
+/// handle records nothing.
+#[derive(Clone, Default)]
+pub struct Audit {
+    log: Option<Arc<Mutex<AuditLog>>>,
+}
+
+impl Audit {
+    /// Open (or create) the log described by `config`, continuing the
+    /// hash chain of whatever is already there.
+    pub fn open(config: &AuditConfig) -> Result<Self, String> {
+        let path = PathBuf::from(&config.path);
+        let err = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(err)?;
+        let written = file.metadata().map_err(err)?.len();
+        let last_hash = if written > 0 {
+            last_line_hash(&path)
+        } else {
+            last_line_hash(&rotated_path(&path, 1))
+        };
+        for problem in CommandSet::problems(&config.commands) {
+            println!("[WARN] audit.commands: {}", problem);
+        }
+        Ok(Self {
+            log: Some(Arc::new(Mutex::new(AuditLog {
+                path,
+                file,
+                written,
+                max_bytes: config.max_bytes,
+                keep: config.keep,
+                hash_chain: config.hash_chain,
+                last_hash,
+                commands: CommandSet::new(&config.commands),
+            }))),
+        })
+    }
+
+    /// Record `cmd` from `peer` if it is one of the audited commands.
+    /// Failures to write are reported but never stop the command.
+    pub fn record(&self, peer: &str, cmd: Command, request_id: u64, payload: &[u8], outcome: &str) {
+        let Some(log) = &self.log else {
+            return;
+        };
+        let mut log = log.lock().unwrap_or_else(|e| e.into_inner());
+        if !log.commands.contains(cmd) {
+            return;
+        }
+        let name = cmd.to_string();
+        let entry = AuditEntry {
+            ts_ms: unix_millis(),
+            peer: peer.to_string(),
+            command: name,
+            request_id,
+            args: summarize(cmd, payload),
+            outcome: outcome.to_string(),
+            prev: None,
+        };
+        let sync = !matches!(cmd, Command::InputMouse | Command::InputKeyboard);
+        if let Err(e) = log.append(entry, sync) {
+            println!("[AUDT] Could not write {}: {}", log.path.display(), e);
+        }
+    }
+}
+
+impl AuditLog {
+    fn append(&mut self, mut entry: AuditEntry, sync: bool) -> std::io::Result<()> {
+        if self.hash_chain {
+            entry.prev = self.last_hash.clone();
+        }
+        let line = serde_json::to_string(&entry).map_err(std::io::Error::from)?;
+        if self.max_bytes > 0
+            && self.written > 0
+            && self.written + line.len() as u64 >= self.max_bytes
+        {
+            self.rotate()?;
+        }
+        self.file.write_all(format!("{}\n", line).as_bytes())?;
+        if sync {
+            self.file.sync_data()?;
+        }
+        self.written += line.len() as u64 + 1;
+        self.last_hash = Some(blake3::hash(line.as_bytes()).to_hex().to_string());
+        Ok(())
+    }
+
+    /// Shift `path.1` … `path.keep` up by one, dropping the oldest, move
+    /// the live file to `path.1` and start a new one.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if self.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let _ = std::fs::remove_file(rotated_path(&self.path, self.keep));
+            for n in (1..self.keep).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() {
+                    std::fs::rename(&from, rotated_path(&self.path, n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+/// `path` with `.n` appended: where the `n`th most recent rotation lives.
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Check the hash chain through the rotated files, oldest first, and then
+/// the live log at `path`. Returns the number of entries checked.
+pub fn verify(path: &Path) -> Result<usize, String> {
+    let mut files: Vec<PathBuf> = (1..)
+        .map(|n| rotated_path(path, n))
+        .take_while(|p| p.exists())
+        .collect();
+    files.reverse();
+    if path.exists() {
+        files.push(path.to_path_buf());
+    }
+    if files.is_empty() {
+        return Err(format!("{}: no audit log", path.display()));
+    }
+
+    let mut last_hash: Option<String> = None;
+    let mut checked = 0;
+    for file in &files {
+        let text =
+            std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+        for (index, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let at = || format!("{} line {}", file.display(), index + 1);
+            let entry: AuditEntry =
+                serde_json::from_str(line).map_err(|e| format!("{}: {}", at(), e))?;
+            if let Some(expected) = &last_hash {
+                match &entry.prev {
+                    Some(prev) if prev == expected => {}
+                    Some(_) => return Err(format!("{}: hash chain broken", at())),
+                    None => return Err(format!("{}: entry is not hash-chained", at())),
+                }
+            }
+            last_hash = Some(blake3::hash(line.as_bytes()).to_hex().to_string());
+            checked += 1;
+        }
+    }
+    Ok(checked)
+}
+
+/// Hash of the last line of `path`, if it has one.
+fn last_line_hash(path: &Path) -> Option<String> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let line = text.lines().rev().find(|l| !l.is_empty())?;
+    Some(blake3::hash(line.as_bytes()).to_hex().to_string())
+}
+
+/// What the command was asked to do, without bulk data or keystrokes.
+fn summarize(cmd: Command, payload: &[u8]) -> String {
+    let summary = match cmd {
+        Command::Upload | Command::Download | Command::SystemAction => {
+            String::from_utf8_lossy(payload).into_owned()
+        }
+        // Environment values are left out: they are where secrets go.
+        Command::ShellExecute => match ShellExecuteRequest::from_bytes(payload) {
+            Ok(req) => match req.working_dir {
+                Some(dir) => format!("{} (in {})", req.command, dir),
+                None => req.command,
+            },
+            Err(_) => String::from_utf8_lossy(payload).into_owned(),
+        },
+        Command::Copy => match CopyRequest::from_bytes(payload) {
+            Ok(req) => format!("{} -> {}", req.src, req.dest),
+            Err(_) => String::from_utf8_lossy(payload).into_owned(),
+        },
+        Command::FileWrite => match FileTransferHeader::from_bytes(payload) {
+            Ok(header) => format!("{} ({} bytes)", header.path, header.size),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::FileDelete => match FileDeleteRequest::from_bytes(payload) {
+            Ok(req) => format!("{:?} {}", req.mode, req.paths.join(", ")),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::FileTransaction => match FileTransactionRequest::from_bytes(payload) {
+            Ok(req) => req
+                .ops
+                .iter()
+                .map(|op| op.to_string())
+                .collect::<Vec<_>>()
+                .join("; "),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::TrashRestore => match TrashRestoreRequest::from_bytes(payload) {
+            Ok(req) => req.original_path,
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::ServiceControl => match ServiceControlRequest::from_bytes(payload) {
+            Ok(req) => format!("{} {}", req.action, req.name),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::ScheduleCreate => match ScheduleCreateRequest::from_bytes(payload) {
+            Ok(req) => format!("{} every {}s: {}", req.name, req.interval_secs, req.command),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::ScheduleDelete => match ScheduleNameRequest::from_bytes(payload) {
+            Ok(req) => req.name,
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::ScreenStart => match ScreenStartRequest::from_bytes(payload) {
+            Ok(req) => format!("session {} at {} fps", req.session, req.fps),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::ScreenMode => match ScreenModeRequest::from_bytes(payload) {
+            Ok(req) => format!("{:?}", req),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::ScreenDictionary => match ScreenDictionaryRequest::from_bytes(payload) {
+            Ok(req) => format!("session {} dictionary {:016x}", req.session, req.hash),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::ScreenPreflight => match ScreenPreflightRequest::from_bytes(payload) {
+            Ok(req) => format!("session {} received {}", req.session, req.received),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::ScreenSharing => match ScreenSharingRequest::from_bytes(payload) {
+            Ok(req) => format!("{:?}", req),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::UpdateApply => match UpdateApplyRequest::from_bytes(payload) {
+            Ok(req) => format!("version {} from {}", req.version, req.staged_path),
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::WireTap => match WireTapSettings::from_bytes(payload) {
+            Ok(req) => match (req.enabled, req.capture) {
+                (false, _) => "off".to_string(),
+                (true, None) => format!("on, {} bytes", req.dump_bytes),
+                (true, Some(path)) => format!("on, {} bytes, capture {}", req.dump_bytes, path),
+            },
+            Err(_) => format!("{} bytes", payload.len()),
+        },
+        Command::InputMouse | Command::InputKeyboard | Command::SendSas => String::new(),
+        _ => format!("{} bytes", payload.len()),
+    };
+    if summary.chars().count() > MAX_ARGS_CHARS {
+        let cut: String = summary.chars().take(MAX_ARGS_CHARS).collect();
+        format!("{}…", cut)
+    } else {
+        summary
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str, max_bytes: u64) -> AuditConfig {
+        let path =
+            std::env::temp_dir().join(format!("tix-audit-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        for n in 1..=4 {
+            let _ = std::fs::remove_file(rotated_path(&path, n));
+        }
+        AuditConfig {
+            path: path.display().to_string(),
+            max_bytes,
+            keep: 2,
+            ..AuditConfig::default()
+        }
+    }
+
+    fn cleanup(config: &AuditConfig) {
+        let path = Path::new(&config.path);
+        let _ = std::fs::remove_file(path);
+        for n in 1..=4 {
+            let _ = std::fs::remove_file(rotated_path(path, n));
+        }
+    }
+
+    fn entries(path: &Path) -> Vec<AuditEntry> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn chain_verifies_and_detects_tampering() {
+        let config = config("chain", 0);
+        let path = PathBuf::from(&config.path);
+        let audit = Audit::open(&config).unwrap();
+        for id in 1..=3 {
+            audit.record(
+                "10.0.0.2:4321",
+                Command::ShellExecute,
+                id,
+                b"whoami",
+                "accepted",
+            );
+        }
+        // Not audited by default.
+        audit.record("10.0.0.2:4321", Command::ListDir, 4, b"C:\\", "accepted");
+        assert_eq!(verify(&path), Ok(3));
+
+        // Reopening continues the same chain.
+        drop(audit);
+        let audit = Audit::open(&config).unwrap();
+        audit.record(
+            "10.0.0.2:4321",
+            Command::SystemAction,
+            5,
+            b"lock",
+            "accepted",
+        );
+        assert_eq!(verify(&path), Ok(4));
+        let logged = entries(&path);
+        assert_eq!(logged[0].prev, None);
+        assert!(logged[1..].iter().all(|e| e.prev.is_some()));
+        assert_eq!(logged[3].args, "lock");
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, text.replacen("whoami", "hostname", 1)).unwrap();
+        let err = verify(&path).unwrap_err();
+        assert!(err.contains("line 2") && err.contains("broken"), "{}", err);
+        cleanup(&config);
+    }
+
+    #[test]
+    fn rotation_keeps_the_chain_across_files() {
+        let config = config("rotate", 400);
+        let path = PathBuf::from(&config.path);
+        let audit = Audit::open(&config).unwrap();
+        for id in 1..=12 {
+            audit.record("10.0.0.2:4321", Command::FileDelete, id, b"", "accepted");
+        }
+        assert!(rotated_path(&path, 1).exists());
+        assert!(rotated_path(&path, 2).exists());
+        assert!(
+            !rotated_path(&path, 3).exists(),
+            "only `keep` files are kept"
+        );
+        for file in [path.clone(), rotated_path(&path, 1)] {
+            assert!(std::fs::metadata(&file).unwrap().len() <= 400);
+        }
+        let live = entries(&path);
+        assert_eq!(live.last().unwrap().request_id, 12);
+        assert!(live[0].prev.is_some(), "chain continues into the new file");
+        let total = verify(&path).unwrap();
+        assert!(total < 12 && total > live.len(), "{}", total);
+        cleanup(&config);
+    }
+
+    #[test]
+    fn refused_commands_are_recorded() {
+        let config = config("refused", 0);
+        let path = PathBuf::from(&config.path);
+        let audit = Audit::open(&config).unwrap();
+        audit.record(
+            "10.0.0.2:4321",
+            Command::Upload,
+            9,
+            b"C:\\a.bin|/tmp/a.bin",
+            "refused: bandwidth limit reached",
+        );
+        let logged = entries(&path);
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].command, "Upload");
+        assert_eq!(logged[0].request_id, 9);
+        assert_eq!(logged[0].peer, "10.0.0.2:4321");
+        assert_eq!(logged[0].args, "C:\\a.bin|/tmp/a.bin");
+        assert!(logged[0].outcome.starts_with("refused"));
+        cleanup(&config);
+    }
+
+    #[test]
+    fn long_arguments_are_truncated_and_keys_are_not_logged() {
+        let long = "x".repeat(1000);
+        let summary = summarize(Command::ShellExecute, long.as_bytes());
+        assert_eq!(summary.chars().count(), MAX_ARGS_CHARS + 1);
+        assert_eq!(summarize(Command::InputKeyboard, b"secret"), "");
+        assert!(CommandSet::problems(&["UpdateApply"]).is_empty());
+        assert!(!CommandSet::problems(&["Shell"]).is_empty());
+    }
+}
@@ -0,0 +1,523 @@
+//! Optional slave configuration, read from `tix-slave.toml`.
+//!
+//! ```toml
+//! [limits]
+//! max_bytes_per_hour = 1073741824
+//!
+//! [locks]
+//! timeout_secs = 30
+//!
+//! [tasks]
+//! slow_after_secs = 300
+//! timeout_ms = 0
+//!
+//! [screen]
+//! max_sessions = 2
+//! allow_sas = false
+//! zstd_dictionary = true
+//! dictionary_path = ""
+//! stale_move_ms = 100
+//!
+//! [audit]
+//! path = "tix-audit.jsonl"
+//! commands = ["ShellExecute", "FileWrite", "FileDelete", "SystemAction"]
+//! max_bytes = 10485760
+//! keep = 5
+//! hash_chain = true
+//!
+//! [wire]
+//! enabled = false
+//! dump_bytes = 64
+//! capture = ""
+//!
+//! [transfer]
+//! compression_level = 3
+//!
+//! [crash]
+//! path = "tix-slave-crash.jsonl"
+//!
+//! [shell.custom]
+//! bash = ["bash", "-c"]
+//! ```
+//!
+//! Every section and field may be omitted; a missing file means "no
+//! limits", the default lock timeout, the default slow-task threshold,
+//! the default number of concurrent screen sessions and an audit log of
+//! the default privileged commands (see [`crate::audit`]), no packet
+//! logging, file chunks compressed at zstd level 3 and panics recorded
+//! in `tix-slave-crash.jsonl` (see [`tix_core::crash`]), and no shells
+//! beyond the built-in ones (see [`crate::shell`]). A file with
+//! unknown keys or command names is refused (see [`tix_core::config`]).
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tix_core::config::Validate;
+use tix_core::network::tls::TlsConfig;
+use tix_core::network::wiretap::WireConfig;
+use tix_core::policy::CommandSet;
+use tix_core::protocol::TransferConfig;
+use tix_core::rdp::burst::DEFAULT_STALE_AFTER;
+use tix_core::rdp::dictionary::FrameDictionary;
+use tix_core::rdp::doctor::DoctorOptions;
+
+/// Default config file, looked up in the working directory.
+pub const DEFAULT_CONFIG_PATH: &str = "tix-slave.toml";
+
+/// Top-level configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlaveConfig {
+    /// Resource limits applied per connection.
+    pub limits: LimitsConfig,
+    /// Per-path locking of file operations.
+    pub locks: LocksConfig,
+    /// Long-running task warnings.
+    pub tasks: TasksConfig,
+    /// Screen sharing.
+    pub screen: ScreenConfig,
+    /// Audit log of privileged commands.
+    pub audit: AuditConfig,
+    /// Packet logging on the master connection (see
+    /// [`tix_core::network::wiretap`]).
+    pub wire: WireConfig,
+    /// TLS on the master connection (see [`tix_core::network::tls`]);
+    /// the slave checks the master's certificate.
+    pub tls: TlsConfig,
+    /// Compression of the file chunks sent to the master.
+    pub transfer: TransferConfig,
+    /// Where panics are recorded.
+    pub crash: CrashConfig,
+    /// Shells a `ShellExecute` may name besides the built-in ones.
+    pub shell: ShellConfig,
+}
+
+/// Per-session resource limits.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Cap on control + screen traffic over any rolling hour. Once hit,
+    /// the screen stream pauses and new file transfers are refused until
+    /// enough traffic ages out.
+    pub max_bytes_per_hour: Option<u64>,
+}
+
+/// Per-path file operation locks.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocksConfig {
+    /// How long a file operation waits for a path another task holds
+    /// before failing with "resource busy".
+    pub timeout_secs: u64,
+}
+
+impl Default for LocksConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: crate::locks::DEFAULT_LOCK_TIMEOUT.as_secs(),
+        }
+    }
+}
+
+impl LocksConfig {
+    /// The configured timeout.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+}
+
+/// Default for `tasks.slow_after_secs`.
+pub const DEFAULT_SLOW_TASK_SECS: u64 = 5 * 60;
+
+/// Long-running task warnings.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TasksConfig {
+    /// A task running longer than this is reported to the master once.
+    /// `0` turns the warning off.
+    pub slow_after_secs: u64,
+    /// A pooled task still running after this many milliseconds is
+    /// stopped and its request answered with a timeout error. `0`, the
+    /// default, lets tasks run for as long as they need.
+    pub timeout_ms: u64,
+}
+
+impl Default for TasksConfig {
+    fn default() -> Self {
+        Self {
+            slow_after_secs: DEFAULT_SLOW_TASK_SECS,
+            timeout_ms: 0,
+        }
+    }
+}
+
+impl TasksConfig {
+    /// The configured threshold, `None` when warnings are off.
+    pub fn slow_after(&self) -> Option<Duration> {
+        (self.slow_after_secs > 0).then(|| Duration::from_secs(self.slow_after_secs))
+    }
+
+    /// The configured task timeout, `None` when tasks are unbounded.
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout_ms > 0).then(|| Duration::from_millis(self.timeout_ms))
+    }
+}
+
+/// Default for `screen.max_sessions`: a dual-monitor desk.
+pub const DEFAULT_MAX_SCREEN_SESSIONS: usize = 2;
+
+/// Screen sharing.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScreenConfig {
+    /// Screen sessions (one per monitor) that may stream at once; a
+    /// `ScreenStart` beyond this is refused.
+    pub max_sessions: usize,
+    /// Let a viewer in control raise Ctrl+Alt+Del (see [`crate::sas`]).
+    pub allow_sas: bool,
+    /// Offer viewers a zstd dictionary for the frames (see
+    /// [`tix_core::rdp::dictionary`]).
+    pub zstd_dictionary: bool,
+    /// Dictionary trained with the `train_dictionary` example; empty
+    /// for the one built into tix.
+    pub dictionary_path: String,
+    /// A pointer move from a viewer that stamps its input is dropped
+    /// when a later move arrives with it and it is this many
+    /// milliseconds behind the newest event (see
+    /// [`tix_core::rdp::burst`]). `0` applies every move.
+    pub stale_move_ms: u64,
+}
+
+impl Default for ScreenConfig {
+    fn default() -> Self {
+        Self {
+            max_sessions: DEFAULT_MAX_SCREEN_SESSIONS,
+            allow_sas: false,
+            zstd_dictionary: true,
+            dictionary_path: String::new(),
+            stale_move_ms: DEFAULT_STALE_AFTER.as_millis() as u64,
+        }
+    }
+}
+
+impl ScreenConfig {
+    /// How far behind the newest input a pointer move may fall.
+    pub fn stale_move(&self) -> Duration {
+        Duration::from_millis(self.stale_move_ms)
+    }
+
+    /// The dictionary to offer, `None` when `zstd_dictionary` is off.
+    pub fn dictionary(&self) -> Result<Option<FrameDictionary>, String> {
+        if !self.zstd_dictionary {
+            return Ok(None);
+        }
+        if self.dictionary_path.is_empty() {
+            return Ok(Some(FrameDictionary::builtin()));
+        }
+        let bytes = std::fs::read(&self.dictionary_path)
+            .map_err(|e| format!("screen.dictionary_path {}: {}", self.dictionary_path, e))?;
+        FrameDictionary::new(bytes)
+            .map(Some)
+            .map_err(|e| format!("screen.dictionary_path {}: {}", self.dictionary_path, e))
+    }
+}
+
+/// Commands audited unless `audit.commands` says otherwise.
+pub const DEFAULT_AUDITED_COMMANDS: [&str; 17] = [
+    "ShellExecute",
+    "Copy",
+    "Upload",
+    "Download",
+    "FileWrite",
+    "FileDelete",
+    "FileTransaction",
+    "TrashRestore",
+    "SystemAction",
+    "ServiceControl",
+    "ScheduleCreate",
+    "ScheduleDelete",
+    "ScreenStart",
+    "ScreenMode",
+    "SendSas",
+    "UpdateApply",
+    "WireTap",
+];
+
+/// Audit log of privileged commands.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// Log file; rotated copies get `.1`, `.2`, … appended.
+    pub path: String,
+    /// Commands to record, by name or pattern (`Schedule*`, `!Input*`).
+    /// Add `InputMouse` and `InputKeyboard` to record remote input too;
+    /// an empty list turns the log off.
+    pub commands: Vec<String>,
+    /// Rotate once the live file would grow past this. `0` never rotates.
+    pub max_bytes: u64,
+    /// Rotated files kept; older ones are deleted.
+    pub keep: usize,
+    /// Chain each entry to the Blake3 of the line before it.
+    pub hash_chain: bool,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            path: "tix-audit.jsonl".to_string(),
+            commands: DEFAULT_AUDITED_COMMANDS.map(String::from).to_vec(),
+            max_bytes: 10 * 1024 * 1024,
+            keep: 5,
+            hash_chain: true,
+        }
+    }
+}
+
+/// Crash log.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrashConfig {
+    /// File each panic is appended to as a JSON line. Empty records
+    /// nothing; panics are still logged.
+    pub path: String,
+}
+
+impl Default for CrashConfig {
+    fn default() -> Self {
+        Self {
+            path: "tix-slave-crash.jsonl".to_string(),
+        }
+    }
+}
+
+impl CrashConfig {
+    /// The crash log, `None` when off.
+    pub fn log_path(&self) -> Option<PathBuf> {
+        (!self.path.is_empty()).then(|| PathBuf::from(&self.path))
+    }
+}
+
+/// Allow-list of custom shells.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShellConfig {
+    /// Shell name → program and the arguments that come before the
+    /// command, e.g. `bash = ["bash", "-c"]`. Names are matched as the
+    /// master sends them.
+    pub custom: BTreeMap<String, Vec<String>>,
+}
+
+impl ShellConfig {
+    /// Entries that can never be used.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (name, argv) in &self.custom {
+            if matches!(
+                name.to_ascii_lowercase().as_str(),
+                "cmd" | "powershell" | "pwsh"
+            ) {
+                problems.push(format!(
+                    "shell.custom: `{}` is a built-in shell and cannot be replaced",
+                    name
+                ));
+            }
+            if argv.first().is_none_or(|program| program.is_empty()) {
+                problems.push(format!("shell.custom: `{}` names no program", name));
+            }
+        }
+        problems
+    }
+}
+
+impl SlaveConfig {
+    /// What a `SelfTest` request checks: capture, input, that a screen
+    /// socket can be bound, and the directories of the config file and
+    /// (when they are on) the audit and crash logs.
+    pub fn self_test(&self) -> DoctorOptions {
+        let parent = |path: &str| {
+            Path::new(path)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default()
+        };
+        let mut directories: Vec<(String, PathBuf)> =
+            vec![("config".to_string(), parent(DEFAULT_CONFIG_PATH))];
+        if !self.audit.commands.is_empty() {
+            directories.push(("audit log".to_string(), parent(&self.audit.path)));
+        }
+        if !self.crash.path.is_empty() {
+            directories.push(("crash log".to_string(), parent(&self.crash.path)));
+        }
+        DoctorOptions {
+            directories,
+            ..DoctorOptions::default()
+        }
+    }
+}
+
+impl Validate for SlaveConfig {
+    fn problems(&self) -> Vec<String> {
+        CommandSet::problems(&self.audit.commands)
+            .into_iter()
+            .map(|problem| format!("audit.commands: {}", problem))
+            .chain(self.transfer.problems())
+            .chain(self.shell.problems())
+            .chain(self.tls.connect_problems())
+            .collect()
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn parses_wire_tap() {
+        let cfg: SlaveConfig =
+            toml::from_str("[wire]\nenabled = true\ncapture = \"wire.cap\"\n").unwrap();
+        let settings = cfg.wire.settings();
+        assert!(settings.enabled);
+        assert_eq!(settings.dump_bytes, 64);
+        assert_eq!(settings.capture.as_deref(), Some("wire.cap"));
+        assert_eq!(SlaveConfig::default().wire.settings().capture, None);
+    }
+
+    #[test]
+    fn transfer_compression_can_be_turned_off() {
+        assert!(SlaveConfig::default().transfer.compressor().is_enabled());
+        let cfg: SlaveConfig = toml::from_str("[transfer]\ncompression_level = 0\n").unwrap();
+        assert!(!cfg.transfer.compressor().is_enabled());
+        let cfg: SlaveConfig = toml::from_str("[transfer]\ncompression_level = 99\n").unwrap();
+        assert_eq!(cfg.problems().len(), 1);
+    }
+
+    #[test]
+    fn parses_limits() {
+        let cfg: SlaveConfig = toml::from_str("[limits]\nmax_bytes_per_hour = 5000\n").unwrap();
+        assert_eq!(cfg.limits.max_bytes_per_hour, Some(5000));
+    }
+
+    #[test]
+    fn empty_config_means_no_limit() {
+        let cfg: SlaveConfig = toml::from_str("").unwrap();
+        assert_eq!(cfg, SlaveConfig::default());
+        assert_eq!(cfg.limits.max_bytes_per_hour, None);
+        assert_eq!(cfg.locks.timeout(), Duration::from_secs(30));
+        assert_eq!(cfg.tasks.slow_after(), Some(Duration::from_secs(300)));
+        assert_eq!(cfg.screen.max_sessions, DEFAULT_MAX_SCREEN_SESSIONS);
+        assert!(!cfg.screen.allow_sas);
+        assert_eq!(
+            cfg.screen.dictionary().unwrap(),
+            Some(FrameDictionary::builtin())
+        );
+        assert!(cfg.audit.commands.iter().any(|c| c == "ShellExecute"));
+        assert!(cfg.audit.hash_chain);
+        assert_eq!(
+            cfg.crash.log_path(),
+            Some(PathBuf::from("tix-slave-crash.jsonl"))
+        );
+        let off: SlaveConfig = toml::from_str("[crash]\npath = \"\"\n").unwrap();
+        assert_eq!(off.crash.log_path(), None);
+    }
+
+    #[test]
+    fn parses_audit_section() {
+        let cfg: SlaveConfig = toml::from_str(
+            "[audit]\npath = \"audit.log\"\ncommands = [\"InputKeyboard\"]\nmax_bytes = 0\n",
+        )
+        .unwrap();
+        assert_eq!(cfg.audit.path, "audit.log");
+        assert_eq!(cfg.audit.commands, vec!["InputKeyboard".to_string()]);
+        assert_eq!(cfg.audit.max_bytes, 0);
+        assert_eq!(cfg.audit.keep, AuditConfig::default().keep);
+    }
+
+    #[test]
+    fn slow_task_warning_can_be_disabled() {
+        let cfg: SlaveConfig = toml::from_str("[tasks]\nslow_after_secs = 0\n").unwrap();
+        assert_eq!(cfg.tasks.slow_after(), None);
+        assert_eq!(cfg.tasks.timeout(), None);
+
+        let cfg: SlaveConfig = toml::from_str("[tasks]\ntimeout_ms = 1500\n").unwrap();
+        assert_eq!(cfg.tasks.timeout(), Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn unknown_keys_and_commands_are_refused() {
+        let parse = |text| tix_core::config::parse::<SlaveConfig>(Path::new("s.toml"), text);
+        let err = parse("[locks]\ntimeout = 5\n").unwrap_err().to_string();
+        assert!(err.contains("line 2, column 1"), "{}", err);
+        assert!(err.ends_with("did you mean `timeout_secs`?"), "{}", err);
+
+        let err = parse("[audit]\ncommands = [\"ShellExec\", \"Nonsense\"]\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("unknown command `ShellExec`; did you mean `ShellExecute`?"),
+            "{}",
+            err
+        );
+        assert!(err.ends_with("unknown command `Nonsense`"), "{}", err);
+        let err = parse("[audit]\ncommands = [\"Disk*\"]\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.ends_with("`Disk*` matches no command"), "{}", err);
+        assert!(parse("[audit]\ncommands = [\"*\", \"!Input*\"]\n").is_ok());
+        assert!(SlaveConfig::default().problems().is_empty());
+    }
+
+    #[test]
+    fn screen_dictionary_can_be_replaced_or_turned_off() {
+        let cfg: SlaveConfig = toml::from_str("[screen]\nzstd_dictionary = false\n").unwrap();
+        assert_eq!(cfg.screen.dictionary().unwrap(), None);
+
+        let dir = std::env::temp_dir().join(format!("tix-dict-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let trained = dir.join("screen.dict");
+        std::fs::write(&trained, FrameDictionary::builtin().bytes()).unwrap();
+        let junk = dir.join("junk.dict");
+        std::fs::write(&junk, b"not a dictionary").unwrap();
+
+        let mut screen = ScreenConfig {
+            dictionary_path: trained.display().to_string(),
+            ..ScreenConfig::default()
+        };
+        assert!(screen.dictionary().unwrap().is_some());
+        screen.dictionary_path = junk.display().to_string();
+        let err = screen.dictionary().unwrap_err();
+        assert!(err.contains("not a zstd dictionary"), "{}", err);
+        screen.dictionary_path = dir.join("missing.dict").display().to_string();
+        assert!(screen.dictionary().is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_custom_shells() {
+        let cfg: SlaveConfig =
+            toml::from_str("[shell.custom]\nbash = [\"/bin/bash\", \"-c\"]\n").unwrap();
+        assert_eq!(
+            cfg.shell.custom.get("bash"),
+            Some(&vec!["/bin/bash".to_string(), "-c".to_string()])
+        );
+        assert!(cfg.problems().is_empty());
+
+        let cfg: SlaveConfig =
+            toml::from_str("[shell.custom]\nPwsh = [\"pwsh\"]\nnothing = []\n").unwrap();
+        let problems = cfg.problems();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].contains("`Pwsh` is a built-in shell"));
+        assert!(problems[1].ends_with("`nothing` names no program"));
+    }
+
+    #[test]
+    fn parses_lock_timeout() {
+        let cfg: SlaveConfig = toml::from_str("[locks]\ntimeout_secs = 5\n").unwrap();
+        assert_eq!(cfg.locks.timeout_secs, 5);
+        assert_eq!(cfg.limits, LimitsConfig::default());
+    }
+}
@@ -0,0 +1,388 @@
+//! `EventLogQuery`: read the newest entries of a Windows event log and
+//! stream them to the master.
+//!
+//! The Event Log API blocks, so the query runs on a blocking thread and
+//! hands each batch to the task over a channel. Cancelling the task
+//! drops the receiving end, which stops the query at its next batch.
+
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use tix_core::ConnectionSender;
+use tix_core::protocol::{
+    EventLogBatch, EventLogEntry, EventLogErrorKind, EventLogQueryRequest, EventLogSummary,
+};
+use tokio::sync::mpsc;
+
+/// Run the query described by `payload`, streaming entries to `tx` and
+/// ending with an [`EventLogSummary`].
+pub async fn run(tx: ConnectionSender, req_id: u64, payload: Vec<u8>) {
+    let started = Instant::now();
+    let mut summary = match EventLogQueryRequest::from_bytes(&payload) {
+        Ok(req) => stream(&tx, req_id, req).await,
+        Err(e) => EventLogSummary::failed(
+            EventLogErrorKind::Other,
+            format!("Invalid EventLogQuery payload: {}", e),
+        ),
+    };
+    summary.elapsed_ms = started.elapsed().as_millis() as u64;
+    match &summary.error {
+        Some(e) => println!("[ERR ] ReqID {}: event log {}", req_id, e),
+        None => println!("[DONE] ReqID {}: {} event(s)", req_id, summary.entries),
+    }
+    if let Ok(pkt) = summary.into_packet(req_id) {
+        let _ = tx.send(pkt).await;
+    }
+}
+
+async fn stream(tx: &ConnectionSender, req_id: u64, req: EventLogQueryRequest) -> EventLogSummary {
+    let (batch_tx, mut batch_rx) = mpsc::channel(2);
+    let query = tokio::task::spawn_blocking(move || {
+        query(&req, |entries| batch_tx.blocking_send(entries).is_ok())
+    });
+    let mut batch_number = 0;
+    while let Some(entries) = batch_rx.recv().await {
+        let batch = EventLogBatch {
+            batch_number,
+            entries,
+        };
+        batch_number += 1;
+        if let Ok(pkt) = batch.into_packet(req_id) {
+            let _ = tx.send(pkt).await;
+        }
+    }
+    query
+        .await
+        .unwrap_or_else(|e| EventLogSummary::failed(EventLogErrorKind::Other, e.to_string()))
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Read the entries `req` asks for, newest first, handing them to
+/// `emit` a batch at a time until it returns `false`. Blocks.
+#[cfg(windows)]
+fn query(
+    req: &EventLogQueryRequest,
+    mut emit: impl FnMut(Vec<EventLogEntry>) -> bool,
+) -> EventLogSummary {
+    use tix_core::protocol::eventlog::EVENTLOG_BATCH_SIZE;
+
+    let failed = |e: &windows::core::Error| {
+        EventLogSummary::failed(win::kind(e), format!("{}: {}", req.log, win::describe(e)))
+    };
+    let results = match win::Query::open(&req.log, &req.xpath(now_ms())) {
+        Ok(results) => results,
+        Err(e) => return failed(&e),
+    };
+    let mut renderer = match win::Renderer::new() {
+        Ok(renderer) => renderer,
+        Err(e) => return failed(&e),
+    };
+    let limit = req.entry_limit();
+    let mut summary = EventLogSummary::default();
+    let mut seen = 0;
+    loop {
+        let wanted = (limit - seen).min(EVENTLOG_BATCH_SIZE);
+        if wanted == 0 {
+            // One more event means the cap cut the query short.
+            summary.truncated = results.next(1).is_ok_and(|more| !more.is_empty());
+            break;
+        }
+        let events = match results.next(wanted) {
+            Ok(events) if events.is_empty() => break,
+            Ok(events) => events,
+            Err(e) => {
+                summary.error = failed(&e).error;
+                break;
+            }
+        };
+        seen += events.len();
+        // An event that cannot be rendered is left out, not fatal.
+        let entries: Vec<EventLogEntry> = events
+            .iter()
+            .filter_map(|event| renderer.entry(event).ok())
+            .collect();
+        summary.entries += entries.len() as u64;
+        if !entries.is_empty() && !emit(entries) {
+            break;
+        }
+    }
+    summary
+}
+
+#[cfg(not(windows))]
+fn query(
+    _req: &EventLogQueryRequest,
+    _emit: impl FnMut(Vec<EventLogEntry>) -> bool,
+) -> EventLogSummary {
+    EventLogSummary::failed(
+        EventLogErrorKind::Unsupported,
+        "event logs are only available on Windows",
+    )
+}
+
+// ── Windows Event Log access ─────────────────────────────────────
+
+#[cfg(windows)]
+mod win {
+    use std::collections::HashMap;
+
+    use tix_core::protocol::eventlog::truncate_message;
+    use tix_core::protocol::{EventLevel, EventLogEntry, EventLogErrorKind};
+    use windows::Win32::Foundation::{
+        ERROR_ACCESS_DENIED, ERROR_EVT_CHANNEL_NOT_FOUND, ERROR_EVT_INVALID_QUERY,
+        ERROR_INSUFFICIENT_BUFFER, ERROR_NO_MORE_ITEMS, WIN32_ERROR,
+    };
+    use windows::Win32::System::EventLog::{
+        EVT_HANDLE, EVT_VARIANT, EvtClose, EvtCreateRenderContext, EvtFormatMessage,
+        EvtFormatMessageEvent, EvtNext, EvtOpenPublisherMetadata, EvtQuery, EvtQueryChannelPath,
+        EvtQueryReverseDirection, EvtRender, EvtRenderContextSystem, EvtRenderEventValues,
+        EvtSystemEventID, EvtSystemLevel, EvtSystemPropertyIdEND, EvtSystemProviderName,
+        EvtSystemTimeCreated, EvtVarTypeByte, EvtVarTypeFileTime, EvtVarTypeString,
+        EvtVarTypeUInt16,
+    };
+    use windows::core::{Error, PCWSTR};
+
+    /// 100 ns FILETIME ticks between 1601-01-01 and the Unix epoch.
+    const FILETIME_UNIX_OFFSET: u64 = 116_444_736_000_000_000;
+
+    fn code(e: &Error) -> Option<WIN32_ERROR> {
+        WIN32_ERROR::from_error(e)
+    }
+
+    pub fn kind(e: &Error) -> EventLogErrorKind {
+        match code(e) {
+            Some(ERROR_ACCESS_DENIED) => EventLogErrorKind::AccessDenied,
+            Some(ERROR_EVT_CHANNEL_NOT_FOUND) => EventLogErrorKind::NotFound,
+            Some(ERROR_EVT_INVALID_QUERY) => EventLogErrorKind::InvalidQuery,
+            _ => EventLogErrorKind::Other,
+        }
+    }
+
+    pub fn describe(e: &Error) -> String {
+        e.message().trim_end().to_string()
+    }
+
+    /// NUL-terminated UTF-16 copy of `s`.
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// An Event Log API handle, closed on drop.
+    pub struct Handle(EVT_HANDLE);
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            unsafe {
+                let _ = EvtClose(self.0);
+            }
+        }
+    }
+
+    /// The events of one log matching a filter, newest first.
+    pub struct Query(Handle);
+
+    impl Query {
+        pub fn open(log: &str, xpath: &str) -> Result<Self, Error> {
+            let (log, xpath) = (wide(log), wide(xpath));
+            let flags = EvtQueryChannelPath.0 | EvtQueryReverseDirection.0;
+            let handle = unsafe {
+                EvtQuery(
+                    EVT_HANDLE(0),
+                    PCWSTR(log.as_ptr()),
+                    PCWSTR(xpath.as_ptr()),
+                    flags,
+                )?
+            };
+            Ok(Self(Handle(handle)))
+        }
+
+        /// Up to `count` more events; none once the query is used up.
+        pub fn next(&self, count: usize) -> Result<Vec<Handle>, Error> {
+            let mut raw = vec![0isize; count];
+            let mut returned = 0u32;
+            match unsafe { EvtNext(self.0.0, &mut raw, u32::MAX, 0, &mut returned) } {
+                Ok(()) => {}
+                Err(e) if code(&e) == Some(ERROR_NO_MORE_ITEMS) => return Ok(Vec::new()),
+                Err(e) => return Err(e),
+            }
+            raw.truncate(returned as usize);
+            Ok(raw.into_iter().map(|h| Handle(EVT_HANDLE(h))).collect())
+        }
+    }
+
+    /// Turns event handles into entries, keeping the message tables of
+    /// the providers it has met open.
+    pub struct Renderer {
+        context: Handle,
+        publishers: HashMap<String, Option<Handle>>,
+    }
+
+    impl Renderer {
+        pub fn new() -> Result<Self, Error> {
+            let context = unsafe { EvtCreateRenderContext(None, EvtRenderContextSystem.0)? };
+            Ok(Self {
+                context: Handle(context),
+                publishers: HashMap::new(),
+            })
+        }
+
+        pub fn entry(&mut self, event: &Handle) -> Result<EventLogEntry, Error> {
+            let values = self.system_values(event)?;
+            let value = |id: i32| &values[id as usize];
+            let provider = match value(EvtSystemProviderName.0) {
+                v if v.Type == EvtVarTypeString.0 as u32 => {
+                    unsafe { v.Anonymous.StringVal.to_string() }.unwrap_or_default()
+                }
+                _ => String::new(),
+            };
+            let event_id = match value(EvtSystemEventID.0) {
+                v if v.Type == EvtVarTypeUInt16.0 as u32 => unsafe { v.Anonymous.UInt16Val },
+                _ => 0,
+            };
+            let level = match value(EvtSystemLevel.0) {
+                v if v.Type == EvtVarTypeByte.0 as u32 => unsafe { v.Anonymous.ByteVal },
+                _ => 0,
+            };
+            let time_ms = match value(EvtSystemTimeCreated.0) {
+                v if v.Type == EvtVarTypeFileTime.0 as u32 => {
+                    let ticks = unsafe { v.Anonymous.FileTimeVal };
+                    ticks.saturating_sub(FILETIME_UNIX_OFFSET) / 10_000
+                }
+                _ => 0,
+            };
+            let message = self.message(&provider, event).unwrap_or_default();
+            Ok(EventLogEntry {
+                time_ms,
+                level: EventLevel::from_raw(level),
+                provider,
+                event_id: u32::from(event_id),
+                message: truncate_message(&message),
+            })
+        }
+
+        /// The event's system properties, indexed by
+        /// `EVT_SYSTEM_PROPERTY_ID`.
+        fn system_values(&self, event: &Handle) -> Result<Vec<EVT_VARIANT>, Error> {
+            let (mut used, mut count) = (0u32, 0u32);
+            let render = |buf: &mut Vec<EVT_VARIANT>, used: &mut u32, count: &mut u32| unsafe {
+                EvtRender(
+                    self.context.0,
+                    event.0,
+                    EvtRenderEventValues.0,
+                    (buf.len() * size_of::<EVT_VARIANT>()) as u32,
+                    Some(buf.as_mut_ptr().cast()),
+                    used,
+                    count,
+                )
+            };
+            // The property strings are stored after the array, so the
+            // size asked for can exceed the fixed part.
+            let slots = EvtSystemPropertyIdEND.0 as usize;
+            let mut buf = vec![unsafe { std::mem::zeroed::<EVT_VARIANT>() }; slots];
+            if let Err(e) = render(&mut buf, &mut used, &mut count) {
+                if code(&e) != Some(ERROR_INSUFFICIENT_BUFFER) {
+                    return Err(e);
+                }
+                let len = (used as usize).div_ceil(size_of::<EVT_VARIANT>());
+                buf = vec![unsafe { std::mem::zeroed::<EVT_VARIANT>() }; len.max(slots)];
+                render(&mut buf, &mut used, &mut count)?;
+            }
+            Ok(buf)
+        }
+
+        /// The event's message as its provider words it, when the
+        /// provider's message table can be found.
+        fn message(&mut self, provider: &str, event: &Handle) -> Option<String> {
+            let publisher = self
+                .publishers
+                .entry(provider.to_string())
+                .or_insert_with(|| {
+                    let name = wide(provider);
+                    unsafe {
+                        EvtOpenPublisherMetadata(
+                            EVT_HANDLE(0),
+                            PCWSTR(name.as_ptr()),
+                            PCWSTR::null(),
+                            0,
+                            0,
+                        )
+                    }
+                    .ok()
+                    .map(Handle)
+                })
+                .as_ref()?;
+            let flags = EvtFormatMessageEvent.0;
+            let mut used = 0u32;
+            match unsafe { EvtFormatMessage(publisher.0, event.0, 0, None, flags, None, &mut used) }
+            {
+                Err(e) if code(&e) == Some(ERROR_INSUFFICIENT_BUFFER) => {}
+                _ => return None,
+            }
+            let mut buf = vec![0u16; used as usize];
+            unsafe {
+                EvtFormatMessage(
+                    publisher.0,
+                    event.0,
+                    0,
+                    None,
+                    flags,
+                    Some(&mut buf),
+                    &mut used,
+                )
+            }
+            .ok()?;
+            let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+            Some(String::from_utf16_lossy(&buf[..len]))
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(windows)]
+    #[test]
+    fn reads_the_newest_system_events() {
+        let req = EventLogQueryRequest::new("System").with_max_entries(5);
+        let mut entries = Vec::new();
+        let summary = query(&req, |batch| {
+            entries.extend(batch);
+            true
+        });
+        assert_eq!(summary.error, None);
+        assert!(!entries.is_empty(), "the System log is never empty");
+        assert!(entries.len() <= 5);
+        assert!(entries.windows(2).all(|w| w[0].time_ms >= w[1].time_ms));
+        assert!(entries.iter().all(|e| !e.provider.is_empty()));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn a_missing_log_is_not_found() {
+        let req = EventLogQueryRequest::new("tix-does-not-exist");
+        let summary = query(&req, |_| true);
+        assert_eq!(
+            summary.error.map(|e| e.kind),
+            Some(EventLogErrorKind::NotFound)
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn other_platforms_have_no_event_log() {
+        let summary = query(&EventLogQueryRequest::new("System"), |_| true);
+        assert_eq!(
+            summary.error.map(|e| e.kind),
+            Some(EventLogErrorKind::Unsupported)
+        );
+        assert_eq!(summary.entries, 0);
+    }
+}
@@ -0,0 +1,65 @@
+//! Stable identity of this slave installation.
+//!
+//! A UUID generated on first run and kept in `tix-slave.id` next to the
+//! config file. The slave presents it in its `Hello`, so the master
+//! recognises the machine across restarts, reconnects and new IPs.
+
+use std::path::{Path, PathBuf};
+
+use tix_core::config::write_atomic;
+use uuid::Uuid;
+
+/// File the ID is kept in, beside the config file.
+pub const ID_FILE_NAME: &str = "tix-slave.id";
+
+/// Where the ID lives for the config file at `config_path`.
+pub fn id_path(config_path: &Path) -> PathBuf {
+    config_path.with_file_name(ID_FILE_NAME)
+}
+
+/// The ID stored at `path`, or a new one written there. A file that
+/// does not hold a UUID is replaced. When the new ID cannot be saved it
+/// is still used, but only lasts until the process exits.
+pub fn load_or_create(path: &Path) -> String {
+    if let Ok(text) = std::fs::read_to_string(path)
+        && let Ok(id) = Uuid::parse_str(text.trim())
+    {
+        return id.to_string();
+    }
+    let id = Uuid::new_v4().to_string();
+    match write_atomic(path, format!("{}\n", id)) {
+        Ok(()) => println!("[INIT] New slave ID {} saved to {}", id, path.display()),
+        Err(e) => println!(
+            "[WARN] Cannot save slave ID to {}: {}; it will change on restart",
+            path.display(),
+            e
+        ),
+    }
+    id
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_survives_a_restart() {
+        let dir = std::env::temp_dir().join(format!("tix-slave-id-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = id_path(&dir.join("tix-slave.toml"));
+        assert_eq!(path, dir.join(ID_FILE_NAME));
+        let _ = std::fs::remove_file(&path);
+
+        let first = load_or_create(&path);
+        assert!(Uuid::parse_str(&first).is_ok());
+        assert_eq!(load_or_create(&path), first);
+
+        std::fs::write(&path, "not a uuid").unwrap();
+        let replaced = load_or_create(&path);
+        assert_ne!(replaced, first);
+        assert_eq!(load_or_create(&path), replaced);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
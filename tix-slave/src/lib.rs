@@ -0,0 +1,32 @@
+//! TIX Slave — connects to a master and executes commands.
+//!
+//! Handles shell execution, file operations, directory listing,
+//! system actions, Windows services, and more. Automatically reconnects on disconnect
+//! with exponential backoff.
+//!
+//! Settings are read from `tix-slave.toml` in the working directory when
+//! present (see [`config`]). The slave's stable ID is kept beside it (see
+//! [`identity`]), and so are its scheduled commands (see [`schedule`]).
+
+pub mod audit;
+pub mod config;
+mod eventlog;
+pub mod identity;
+mod limits;
+mod locks;
+mod netdiag;
+mod probe;
+mod registry;
+pub mod sas;
+pub mod schedule;
+mod screen;
+mod search;
+pub mod selfupdate;
+mod service;
+pub mod shell;
+mod slave;
+mod transaction;
+mod trash;
+mod upload;
+
+pub use slave::{TixSlave, run_with_reconnect};
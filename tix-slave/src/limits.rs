@@ -0,0 +1,179 @@
+//! Enforcement of `limits.max_bytes_per_hour`.
+//!
+//! [`SessionBudget`] watches the combined control + screen byte count.
+//! When the cap is hit the slave pauses its screen stream and refuses
+//! new file transfers with a [`LimitExceeded`] response; the control
+//! channel itself (heartbeats, listings, shell) keeps working so the
+//! master can still see what is going on.
+
+use std::time::Duration;
+
+use tix_core::Command;
+use tix_core::network::{BandwidthWindow, Clock, SystemClock};
+use tix_core::protocol::{LimitExceeded, SessionStats};
+
+/// State change reported by [`SessionBudget::update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetChange {
+    /// The cap was just reached.
+    Throttled,
+    /// Enough traffic aged out; normal service resumes.
+    Released,
+}
+
+/// Sliding one-hour byte budget for a session.
+pub struct SessionBudget<C: Clock = SystemClock> {
+    window: BandwidthWindow<C>,
+    throttled: bool,
+}
+
+impl SessionBudget {
+    /// Budget for `max_bytes_per_hour` (`None` = accounting only).
+    pub fn new(max_bytes_per_hour: Option<u64>) -> Self {
+        Self::with_window(BandwidthWindow::per_hour(max_bytes_per_hour))
+    }
+}
+
+impl<C: Clock> SessionBudget<C> {
+    /// Budget over an explicit window (tests drive its clock).
+    pub fn with_window(window: BandwidthWindow<C>) -> Self {
+        Self {
+            window,
+            throttled: false,
+        }
+    }
+
+    /// Commands that move file contents and are refused while throttled.
+    pub fn is_transfer(cmd: Command) -> bool {
+        matches!(
+            cmd,
+            Command::FileRead
+                | Command::FileWrite
+                | Command::Copy
+                | Command::FileTransaction
+                | Command::Upload
+                | Command::Download
+        )
+    }
+
+    /// Feed the session's cumulative byte count and report whether the
+    /// throttle state changed.
+    pub fn update(&mut self, total_bytes: u64) -> Option<BudgetChange> {
+        self.window.observe_total(total_bytes);
+        let exceeded = self.window.is_exceeded();
+        match (self.throttled, exceeded) {
+            (false, true) => {
+                self.throttled = true;
+                Some(BudgetChange::Throttled)
+            }
+            (true, false) => {
+                self.throttled = false;
+                Some(BudgetChange::Released)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the session is currently over its cap.
+    pub fn is_throttled(&self) -> bool {
+        self.throttled
+    }
+
+    /// Refuse `cmd` if it is a transfer and the session is throttled.
+    pub fn check(&mut self, cmd: Command) -> Result<(), LimitExceeded> {
+        if !self.throttled || !Self::is_transfer(cmd) {
+            return Ok(());
+        }
+        let retry_after = self.window.retry_after().unwrap_or(Duration::ZERO);
+        Err(LimitExceeded {
+            command: format!("{:?}", cmd),
+            used: self.window.used(),
+            limit: self.window.limit().unwrap_or(0),
+            retry_after_secs: retry_after.as_secs(),
+        })
+    }
+
+    /// Copy the window state into `stats`.
+    pub fn fill_stats(&mut self, stats: &mut SessionStats) {
+        stats.window_bytes = self.window.used();
+        stats.limit_per_hour = self.window.limit();
+        stats.throttled = self.throttled;
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    #[derive(Clone)]
+    struct ManualClock(Arc<Mutex<Instant>>);
+
+    impl ManualClock {
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn budget(limit: u64) -> (SessionBudget<ManualClock>, ManualClock) {
+        let clock = ManualClock(Arc::new(Mutex::new(Instant::now())));
+        let window =
+            BandwidthWindow::with_clock(Duration::from_secs(3600), Some(limit), clock.clone());
+        (SessionBudget::with_window(window), clock)
+    }
+
+    #[test]
+    fn throttles_transfers_until_window_rolls_over() {
+        let (mut budget, clock) = budget(1000);
+
+        assert_eq!(budget.update(400), None);
+        assert!(budget.check(Command::Download).is_ok());
+
+        clock.advance(Duration::from_secs(20 * 60));
+        assert_eq!(budget.update(1200), Some(BudgetChange::Throttled));
+        assert_eq!(budget.update(1200), None);
+
+        let refusal = budget.check(Command::Upload).unwrap_err();
+        assert_eq!(refusal.command, "Upload");
+        assert_eq!(refusal.used, 1200);
+        assert_eq!(refusal.limit, 1000);
+        // The first 400 bytes leave the window 40 minutes from now.
+        assert_eq!(refusal.retry_after_secs, 40 * 60);
+
+        // Control traffic is never refused.
+        assert!(budget.check(Command::ListDir).is_ok());
+        assert!(budget.check(Command::ShellExecute).is_ok());
+
+        clock.advance(Duration::from_secs(40 * 60));
+        assert_eq!(budget.update(1200), Some(BudgetChange::Released));
+        assert!(budget.check(Command::Upload).is_ok());
+    }
+
+    #[test]
+    fn stats_reflect_window() {
+        let (mut budget, _clock) = budget(100);
+        budget.update(150);
+
+        let mut stats = SessionStats::default();
+        budget.fill_stats(&mut stats);
+        assert_eq!(stats.window_bytes, 150);
+        assert_eq!(stats.limit_per_hour, Some(100));
+        assert!(stats.throttled);
+    }
+
+    #[test]
+    fn no_limit_never_throttles() {
+        let mut budget = SessionBudget::new(None);
+        assert_eq!(budget.update(u64::MAX / 2), None);
+        assert!(budget.check(Command::Download).is_ok());
+    }
+}
@@ -0,0 +1,451 @@
+//! Per-path locks that keep concurrent file operations from racing.
+//!
+//! Every file-touching task takes a [`PathGuard`] from the shared
+//! [`PathLocks`] before it starts: shared access for paths it only reads,
+//! exclusive access for paths it writes, deletes or moves. A lock covers
+//! everything under its path, so it conflicts with locks on the path's
+//! ancestors and descendants as well as on the path itself: a recursive
+//! delete of a directory waits for a write to a file inside it, and the
+//! other way round. Operations on several paths lock them all in one
+//! step or none at all, so two tasks can never hold part of what the
+//! other waits for. Waiting is bounded by `locks.timeout_secs`; a task
+//! that cannot get its paths in time fails with [`PathBusy`] instead of
+//! queueing forever.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use tix_core::TaskError;
+use tix_core::protocol::{LockAccess, PathLockInfo};
+use tokio::sync::Notify;
+
+/// Default for `locks.timeout_secs`.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Absolute, lexically cleaned form of `path` used as the lock key.
+/// Windows paths compare case-insensitively, so they are lowercased.
+pub fn normalize(path: &Path) -> PathBuf {
+    let abs = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut out = PathBuf::new();
+    for component in abs.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    if cfg!(windows) {
+        PathBuf::from(out.to_string_lossy().to_lowercase())
+    } else {
+        out
+    }
+}
+
+/// Whether locks on normalized paths `a` and `b` keep each other out:
+/// one path contains the other and either lock is exclusive.
+fn conflicts(a: &Path, a_access: LockAccess, b: &Path, b_access: LockAccess) -> bool {
+    (a_access == LockAccess::Exclusive || b_access == LockAccess::Exclusive)
+        && (a.starts_with(b) || b.starts_with(a))
+}
+
+// ── Errors ───────────────────────────────────────────────────────
+
+/// A path could not be locked before the timeout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathBusy {
+    /// The path that stayed locked.
+    pub path: String,
+    /// How long the task waited.
+    pub waited: Duration,
+    /// Who held it, or a path above or below it, when the wait gave up.
+    pub holders: Vec<PathLockInfo>,
+}
+
+impl fmt::Display for PathBusy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "resource busy: {}", self.path)?;
+        let holders: Vec<String> = self
+            .holders
+            .iter()
+            .map(|h| format!("ReqID {} ({})", h.request_id, h.access))
+            .collect();
+        if !holders.is_empty() {
+            write!(f, " is held by {}", holders.join(", "))?;
+        }
+        write!(f, "; gave up after {:.1}s", self.waited.as_secs_f64())
+    }
+}
+
+impl From<PathBusy> for TaskError {
+    fn from(busy: PathBusy) -> Self {
+        TaskError::ResourceBusy {
+            path: busy.path,
+            waited: busy.waited,
+        }
+    }
+}
+
+// ── Lock table ───────────────────────────────────────────────────
+
+struct Holder {
+    path: PathBuf,
+    access: LockAccess,
+    request_id: u64,
+    since: Instant,
+}
+
+struct Inner {
+    timeout: Duration,
+    /// Every lock held, by token.
+    holders: Mutex<HashMap<u64, Holder>>,
+    /// Woken whenever a guard gives its locks back.
+    released: Notify,
+    next_token: AtomicU64,
+}
+
+impl Inner {
+    fn holders(&self) -> MutexGuard<'_, HashMap<u64, Holder>> {
+        self.holders.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Shared table of path locks. Cloning is cheap; all clones see the
+/// same locks.
+#[derive(Clone)]
+pub struct PathLocks {
+    inner: Arc<Inner>,
+}
+
+impl Default for PathLocks {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOCK_TIMEOUT)
+    }
+}
+
+/// Locks held by one task; released on drop.
+pub struct PathGuard {
+    tokens: Vec<u64>,
+    inner: Arc<Inner>,
+}
+
+impl Drop for PathGuard {
+    fn drop(&mut self) {
+        let mut holders = self.inner.holders();
+        for token in &self.tokens {
+            holders.remove(token);
+        }
+        drop(holders);
+        self.inner.released.notify_waiters();
+    }
+}
+
+impl PathLocks {
+    /// A lock table whose waits give up after `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                timeout,
+                holders: Mutex::new(HashMap::new()),
+                released: Notify::new(),
+                next_token: AtomicU64::new(1),
+            }),
+        }
+    }
+
+    /// Shared access to one path.
+    pub async fn read(&self, request_id: u64, path: &Path) -> Result<PathGuard, PathBusy> {
+        self.acquire(request_id, &[(path, LockAccess::Shared)])
+            .await
+    }
+
+    /// Exclusive access to one path.
+    pub async fn write(&self, request_id: u64, path: &Path) -> Result<PathGuard, PathBusy> {
+        self.acquire(request_id, &[(path, LockAccess::Exclusive)])
+            .await
+    }
+
+    /// Lock several paths at once, waiting up to the configured timeout.
+    /// A path listed twice is locked once, exclusively if either entry
+    /// asks for it.
+    pub async fn acquire(
+        &self,
+        request_id: u64,
+        paths: &[(&Path, LockAccess)],
+    ) -> Result<PathGuard, PathBusy> {
+        let wanted = Self::ordered(paths);
+        let started = Instant::now();
+        let deadline = tokio::time::Instant::from_std(started + self.inner.timeout);
+        loop {
+            // Listen before looking, so a release in between still wakes us.
+            let released = self.inner.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            let blocked = match self.take(request_id, &wanted) {
+                Ok(guard) => return Ok(guard),
+                Err(blocked) => blocked,
+            };
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                return Err(self.busy(&blocked, started.elapsed()));
+            }
+        }
+    }
+
+    /// Every lock currently held, oldest first.
+    pub fn holders(&self) -> Vec<PathLockInfo> {
+        let holders = self.inner.holders();
+        let mut out: Vec<(Instant, u64, PathLockInfo)> = holders
+            .iter()
+            .map(|(token, h)| (h.since, *token, Self::info(h)))
+            .collect();
+        out.sort_by_key(|(since, token, _)| (*since, *token));
+        out.into_iter().map(|(_, _, info)| info).collect()
+    }
+
+    /// Normalize, merge duplicates and sort.
+    fn ordered(paths: &[(&Path, LockAccess)]) -> BTreeMap<PathBuf, LockAccess> {
+        let mut ordered = BTreeMap::new();
+        for (path, access) in paths {
+            let entry = ordered.entry(normalize(path)).or_insert(*access);
+            if *access == LockAccess::Exclusive {
+                *entry = LockAccess::Exclusive;
+            }
+        }
+        ordered
+    }
+
+    /// Lock every path in `wanted` if none conflicts with a held lock,
+    /// else name the first one that does.
+    fn take(
+        &self,
+        request_id: u64,
+        wanted: &BTreeMap<PathBuf, LockAccess>,
+    ) -> Result<PathGuard, PathBuf> {
+        let mut holders = self.inner.holders();
+        for (path, access) in wanted {
+            if holders
+                .values()
+                .any(|h| conflicts(&h.path, h.access, path, *access))
+            {
+                return Err(path.clone());
+            }
+        }
+        let since = Instant::now();
+        let mut tokens = Vec::with_capacity(wanted.len());
+        for (path, access) in wanted {
+            let token = self.inner.next_token.fetch_add(1, Ordering::Relaxed);
+            holders.insert(
+                token,
+                Holder {
+                    path: path.clone(),
+                    access: *access,
+                    request_id,
+                    since,
+                },
+            );
+            tokens.push(token);
+        }
+        Ok(PathGuard {
+            tokens,
+            inner: self.inner.clone(),
+        })
+    }
+
+    fn busy(&self, path: &Path, waited: Duration) -> PathBusy {
+        let holders = self.inner.holders();
+        PathBusy {
+            path: path.display().to_string(),
+            waited,
+            holders: holders
+                .values()
+                .filter(|h| h.path.starts_with(path) || path.starts_with(&h.path))
+                .map(Self::info)
+                .collect(),
+        }
+    }
+
+    fn info(holder: &Holder) -> PathLockInfo {
+        PathLockInfo {
+            path: holder.path.display().to_string(),
+            access: holder.access,
+            request_id: holder.request_id,
+            held_ms: holder.since.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHORT: Duration = Duration::from_millis(50);
+
+    fn p(s: &str) -> PathBuf {
+        std::env::temp_dir().join(s)
+    }
+
+    #[tokio::test]
+    async fn readers_share_writers_exclude() {
+        let locks = PathLocks::new(SHORT);
+        let file = p("tix-locks/a.bin");
+
+        let r1 = locks.read(1, &file).await.unwrap();
+        let _r2 = locks.read(2, &file).await.unwrap();
+        assert_eq!(locks.holders().len(), 2);
+
+        let busy = locks.write(3, &file).await.err().unwrap();
+        assert_eq!(busy.holders.len(), 2);
+        assert!(busy.waited >= SHORT);
+        assert!(busy.to_string().starts_with("resource busy"));
+
+        drop(r1);
+        drop(_r2);
+        let w = locks.write(3, &file).await.unwrap();
+        assert!(locks.read(4, &file).await.is_err());
+        assert!(locks.write(5, &file).await.is_err());
+        drop(w);
+        assert!(locks.holders().is_empty());
+    }
+
+    #[tokio::test]
+    async fn equivalent_spellings_share_a_lock() {
+        let locks = PathLocks::new(SHORT);
+        let _w = locks.write(1, &p("tix-locks/dir/../b.txt")).await.unwrap();
+        assert!(locks.read(2, &p("tix-locks/./b.txt")).await.is_err());
+        // Other paths are unaffected.
+        assert!(locks.read(2, &p("tix-locks/c.txt")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn opposite_move_orders_do_not_deadlock() {
+        let locks = PathLocks::new(Duration::from_secs(5));
+        let (a, b) = (p("tix-locks/move/a"), p("tix-locks/move/b"));
+
+        // Two moves in opposite directions, repeatedly and concurrently.
+        let mut tasks = Vec::new();
+        for i in 0..20u64 {
+            let locks = locks.clone();
+            let (src, dst) = if i % 2 == 0 {
+                (a.clone(), b.clone())
+            } else {
+                (b.clone(), a.clone())
+            };
+            tasks.push(tokio::spawn(async move {
+                let paths = [
+                    (src.as_path(), LockAccess::Exclusive),
+                    (dst.as_path(), LockAccess::Exclusive),
+                ];
+                let guard = locks.acquire(i, &paths).await.unwrap();
+                tokio::task::yield_now().await;
+                drop(guard);
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert!(locks.holders().is_empty());
+    }
+
+    #[tokio::test]
+    async fn parent_is_locked_before_child() {
+        let locks = PathLocks::new(SHORT);
+        let parent = p("tix-locks/tree");
+        let child = parent.join("leaf.txt");
+
+        let guard = locks
+            .acquire(
+                1,
+                &[
+                    (child.as_path(), LockAccess::Exclusive),
+                    (parent.as_path(), LockAccess::Shared),
+                ],
+            )
+            .await
+            .unwrap();
+        let order: Vec<String> = locks.holders().into_iter().map(|h| h.path).collect();
+        assert_eq!(
+            order,
+            vec![
+                normalize(&parent).display().to_string(),
+                normalize(&child).display().to_string()
+            ]
+        );
+        drop(guard);
+    }
+
+    #[tokio::test]
+    async fn timeout_releases_partial_acquisition() {
+        let locks = PathLocks::new(SHORT);
+        let (a, b) = (p("tix-locks/t/a"), p("tix-locks/t/b"));
+        let _held = locks.write(1, &b).await.unwrap();
+
+        let busy = locks
+            .acquire(
+                2,
+                &[
+                    (a.as_path(), LockAccess::Shared),
+                    (b.as_path(), LockAccess::Exclusive),
+                ],
+            )
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(busy.path, normalize(&b).display().to_string());
+        assert_eq!(busy.holders[0].request_id, 1);
+        assert!(matches!(
+            TaskError::from(busy),
+            TaskError::ResourceBusy { .. }
+        ));
+        // Nothing on `a` was left held by the failed attempt.
+        assert!(locks.write(3, &a).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn directory_lock_covers_its_contents() {
+        let locks = PathLocks::new(SHORT);
+        let dir = p("tix-locks/rmdir");
+        let file = dir.join("sub/file.txt");
+
+        // A recursive delete of the directory keeps writers out of files below it.
+        let delete = locks.write(1, &dir).await.unwrap();
+        let busy = locks.write(2, &file).await.err().unwrap();
+        assert_eq!(busy.holders[0].request_id, 1);
+        assert!(locks.read(3, &file).await.is_err());
+        // A sibling that merely shares a name prefix is not inside it.
+        assert!(locks.write(4, &p("tix-locks/rmdir2")).await.is_ok());
+        drop(delete);
+
+        // And a write inside the directory holds off the delete.
+        let write = locks.write(2, &file).await.unwrap();
+        assert!(locks.write(1, &dir).await.is_err());
+        // Readers of the directory don't conflict with each other, only with writers.
+        drop(write);
+        let _list = locks.read(5, &dir).await.unwrap();
+        assert!(locks.read(6, &file).await.is_ok());
+        assert!(locks.write(7, &file).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn waiter_wakes_when_the_holder_lets_go() {
+        let locks = PathLocks::new(Duration::from_secs(5));
+        let dir = p("tix-locks/wake");
+        let held = locks.write(1, &dir).await.unwrap();
+
+        let waiter = {
+            let locks = locks.clone();
+            let file = dir.join("f");
+            tokio::spawn(async move { locks.write(2, &file).await.map(drop) })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(held);
+        waiter.await.unwrap().unwrap();
+        assert!(locks.holders().is_empty());
+    }
+}
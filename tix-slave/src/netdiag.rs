@@ -0,0 +1,399 @@
+//! Answering `NetDiag` requests: the slave's interfaces and routes, name
+//! resolution, and TCP port checks.
+//!
+//! Interfaces and routes come from the IP Helper API on Windows and are
+//! unsupported elsewhere. Resolution and port checks go through tokio
+//! and work on every platform.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use tix_core::protocol::{
+    DnsResult, NetDiagErrorKind, NetDiagRequest, NetDiagResponse, PortCheckOutcome, PortCheckResult,
+};
+use tokio::net::{TcpStream, lookup_host};
+use tokio::time::{Instant, timeout_at};
+
+/// Answer `req`. The IP Helper calls block, so they run on a blocking
+/// thread.
+pub async fn answer(req: NetDiagRequest) -> NetDiagResponse {
+    match req {
+        NetDiagRequest::InterfaceList => blocking(interfaces).await,
+        NetDiagRequest::RouteTable => blocking(routes).await,
+        NetDiagRequest::DnsResolve { name } => resolve(&name).await,
+        NetDiagRequest::PortCheck {
+            host,
+            port,
+            timeout_ms,
+        } => NetDiagResponse::PortCheck(
+            port_check(&host, port, Duration::from_millis(timeout_ms)).await,
+        ),
+    }
+}
+
+async fn blocking(f: fn() -> NetDiagResponse) -> NetDiagResponse {
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| NetDiagResponse::error(NetDiagErrorKind::Other, e.to_string()))
+}
+
+/// One-line summary of `response` for the slave's log.
+pub fn summary(response: &NetDiagResponse) -> String {
+    match response {
+        NetDiagResponse::Interfaces(interfaces) => format!("{} interfaces", interfaces.len()),
+        NetDiagResponse::Routes(routes) => format!("{} routes", routes.len()),
+        NetDiagResponse::Resolved(result) => {
+            format!("{} → {} addresses", result.name, result.addresses.len())
+        }
+        NetDiagResponse::PortCheck(result) => {
+            format!("{}:{} {}", result.host, result.port, result.outcome)
+        }
+        NetDiagResponse::Error { kind, message } => format!("{}: {}", kind, message),
+    }
+}
+
+// ── Resolution and Port Checks ───────────────────────────────────
+
+/// Resolve `name` with the system resolver.
+pub async fn resolve(name: &str) -> NetDiagResponse {
+    let start = Instant::now();
+    match lookup_host((name, 0)).await {
+        Ok(found) => {
+            let mut addresses: Vec<IpAddr> = Vec::new();
+            for address in found {
+                if !addresses.contains(&address.ip()) {
+                    addresses.push(address.ip());
+                }
+            }
+            NetDiagResponse::Resolved(DnsResult {
+                name: name.to_string(),
+                addresses,
+                elapsed_us: start.elapsed().as_micros() as u64,
+            })
+        }
+        Err(e) => NetDiagResponse::error(NetDiagErrorKind::Resolve, format!("{}: {}", name, e)),
+    }
+}
+
+/// Connect to `host:port`, trying each address the name resolves to
+/// until one accepts, all within `timeout`. The connection is closed
+/// straight away.
+pub async fn port_check(host: &str, port: u16, timeout: Duration) -> PortCheckResult {
+    let start = Instant::now();
+    let deadline = start + timeout;
+    let result = |address, outcome| PortCheckResult {
+        host: host.to_string(),
+        port,
+        address,
+        outcome,
+        elapsed_us: start.elapsed().as_micros() as u64,
+    };
+
+    let addresses: Vec<SocketAddr> = match timeout_at(deadline, lookup_host((host, port))).await {
+        Ok(Ok(found)) => found.collect(),
+        Ok(Err(e)) => return result(None, PortCheckOutcome::Unresolved(e.to_string())),
+        Err(_) => return result(None, PortCheckOutcome::TimedOut),
+    };
+    if addresses.is_empty() {
+        let outcome = PortCheckOutcome::Unresolved("no addresses".to_string());
+        return result(None, outcome);
+    }
+
+    let mut last = (None, PortCheckOutcome::TimedOut);
+    for address in addresses {
+        match timeout_at(deadline, TcpStream::connect(address)).await {
+            Ok(Ok(_stream)) => return result(Some(address), PortCheckOutcome::Open),
+            Ok(Err(e)) => last = (Some(address), connect_outcome(&e)),
+            Err(_) => {
+                last = (Some(address), PortCheckOutcome::TimedOut);
+                break;
+            }
+        }
+    }
+    result(last.0, last.1)
+}
+
+fn connect_outcome(e: &io::Error) -> PortCheckOutcome {
+    match e.kind() {
+        io::ErrorKind::ConnectionRefused => PortCheckOutcome::Refused,
+        io::ErrorKind::TimedOut => PortCheckOutcome::TimedOut,
+        _ => PortCheckOutcome::Failed(e.to_string()),
+    }
+}
+
+// ── Interfaces and Routes ────────────────────────────────────────
+
+/// Every adapter, ordered by interface index.
+#[cfg(windows)]
+pub fn interfaces() -> NetDiagResponse {
+    match win::adapters() {
+        Ok(mut interfaces) => {
+            interfaces.sort_by_key(|interface| interface.index);
+            NetDiagResponse::Interfaces(interfaces)
+        }
+        Err(e) => NetDiagResponse::error(NetDiagErrorKind::Other, win::describe(&e)),
+    }
+}
+
+/// Every adapter, ordered by interface index.
+#[cfg(not(windows))]
+pub fn interfaces() -> NetDiagResponse {
+    NetDiagResponse::error(
+        NetDiagErrorKind::Unsupported,
+        "interface listing is only available on Windows",
+    )
+}
+
+/// Both routing tables, IPv4 first, each family by destination.
+#[cfg(windows)]
+pub fn routes() -> NetDiagResponse {
+    let mut routes = match win::routes() {
+        Ok(routes) => routes,
+        Err(e) => return NetDiagResponse::error(NetDiagErrorKind::Other, win::describe(&e)),
+    };
+    // Name each route's interface; a failed listing just leaves them out.
+    if let Ok(adapters) = win::adapters() {
+        for route in &mut routes {
+            route.interface = adapters
+                .iter()
+                .find(|adapter| adapter.index == route.interface_index)
+                .map(|adapter| adapter.name.clone());
+        }
+    }
+    routes.sort_by_key(|route| {
+        (
+            route.destination.is_ipv6(),
+            route.destination,
+            route.prefix_len,
+        )
+    });
+    NetDiagResponse::Routes(routes)
+}
+
+/// Both routing tables, IPv4 first, each family by destination.
+#[cfg(not(windows))]
+pub fn routes() -> NetDiagResponse {
+    NetDiagResponse::error(
+        NetDiagErrorKind::Unsupported,
+        "the route table is only available on Windows",
+    )
+}
+
+// ── Windows IP Helper access ─────────────────────────────────────
+
+#[cfg(windows)]
+mod win {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use tix_core::protocol::{InterfaceAddress, LinkState, NetInterface, RouteEntry};
+    use windows::Win32::Foundation::{ERROR_BUFFER_OVERFLOW, ERROR_SUCCESS, WIN32_ERROR};
+    use windows::Win32::NetworkManagement::IpHelper::{
+        FreeMibTable, GAA_FLAG_INCLUDE_GATEWAYS, GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_MULTICAST,
+        GetAdaptersAddresses, GetIpForwardTable2, IP_ADAPTER_ADDRESSES_LH, MIB_IPFORWARD_TABLE2,
+    };
+    use windows::Win32::NetworkManagement::Ndis::{
+        IfOperStatusDormant, IfOperStatusDown, IfOperStatusLowerLayerDown, IfOperStatusUp,
+    };
+    use windows::Win32::Networking::WinSock::{
+        AF_INET, AF_INET6, AF_UNSPEC, SOCKADDR, SOCKADDR_IN, SOCKADDR_IN6,
+    };
+    use windows::core::Error;
+
+    pub fn describe(e: &Error) -> String {
+        e.message().trim_end().to_string()
+    }
+
+    /// The address `sa` points at, if it is IPv4 or IPv6.
+    ///
+    /// # Safety
+    /// `sa` is null or points at a socket address of its family's size.
+    unsafe fn ip(sa: *const SOCKADDR) -> Option<IpAddr> {
+        if sa.is_null() {
+            return None;
+        }
+        unsafe {
+            match (*sa).sa_family {
+                AF_INET => {
+                    let v4 = &*sa.cast::<SOCKADDR_IN>();
+                    Some(Ipv4Addr::from(u32::from_be(v4.sin_addr.S_un.S_addr)).into())
+                }
+                AF_INET6 => {
+                    let v6 = &*sa.cast::<SOCKADDR_IN6>();
+                    Some(Ipv6Addr::from(v6.sin6_addr.u.Byte).into())
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// Follow a `Next`-linked IP Helper list from `first`, collecting the
+    /// address of each node.
+    macro_rules! collect_addresses {
+        ($first:expr, |$node:ident| $item:expr) => {{
+            let mut items = Vec::new();
+            let mut next = $first;
+            while let Some($node) = unsafe { next.as_ref() } {
+                if let Some(item) = $item {
+                    items.push(item);
+                }
+                next = $node.Next;
+            }
+            items
+        }};
+    }
+
+    /// Every adapter `GetAdaptersAddresses` reports, in its order.
+    pub fn adapters() -> Result<Vec<NetInterface>, Error> {
+        let flags = GAA_FLAG_INCLUDE_GATEWAYS | GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST;
+        // 15 KB is Microsoft's suggested start; grow when told to.
+        let mut size: u32 = 15 * 1024;
+        let mut buf: Vec<u64>;
+        loop {
+            buf = vec![0; (size as usize).div_ceil(8)];
+            let first = buf.as_mut_ptr().cast::<IP_ADAPTER_ADDRESSES_LH>();
+            let code = unsafe {
+                GetAdaptersAddresses(AF_UNSPEC.0 as u32, flags, None, Some(first), &mut size)
+            };
+            match WIN32_ERROR(code) {
+                ERROR_SUCCESS => break,
+                ERROR_BUFFER_OVERFLOW => continue,
+                other => return Err(other.into()),
+            }
+        }
+
+        let mut interfaces = Vec::new();
+        let mut next = buf.as_ptr().cast::<IP_ADAPTER_ADDRESSES_LH>();
+        while let Some(adapter) = unsafe { next.as_ref() } {
+            interfaces.push(interface(adapter));
+            next = adapter.Next;
+        }
+        Ok(interfaces)
+    }
+
+    fn interface(adapter: &IP_ADAPTER_ADDRESSES_LH) -> NetInterface {
+        let index = match unsafe { adapter.Anonymous1.Anonymous.IfIndex } {
+            0 => adapter.Ipv6IfIndex,
+            index => index,
+        };
+        let state = match adapter.OperStatus {
+            IfOperStatusUp => LinkState::Up,
+            IfOperStatusDown | IfOperStatusLowerLayerDown => LinkState::Down,
+            IfOperStatusDormant => LinkState::Dormant,
+            _ => LinkState::Unknown,
+        };
+        let mac_len = (adapter.PhysicalAddressLength as usize).min(adapter.PhysicalAddress.len());
+        let mac = (mac_len > 0).then(|| {
+            adapter.PhysicalAddress[..mac_len]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(":")
+        });
+        NetInterface {
+            index,
+            name: unsafe { adapter.FriendlyName.to_string() }.unwrap_or_default(),
+            description: unsafe { adapter.Description.to_string() }.unwrap_or_default(),
+            state,
+            mac,
+            mtu: adapter.Mtu,
+            speed_bps: Some(adapter.TransmitLinkSpeed).filter(|s| *s != 0 && *s != u64::MAX),
+            addresses: collect_addresses!(adapter.FirstUnicastAddress, |node| {
+                unsafe { ip(node.Address.lpSockaddr) }.map(|address| InterfaceAddress {
+                    address,
+                    prefix_len: node.OnLinkPrefixLength,
+                })
+            }),
+            gateways: collect_addresses!(adapter.FirstGatewayAddress, |node| unsafe {
+                ip(node.Address.lpSockaddr)
+            }),
+            dns_servers: collect_addresses!(adapter.FirstDnsServerAddress, |node| unsafe {
+                ip(node.Address.lpSockaddr)
+            }),
+        }
+    }
+
+    /// Every IPv4 and IPv6 route, unnamed and unsorted.
+    pub fn routes() -> Result<Vec<RouteEntry>, Error> {
+        let mut table: *mut MIB_IPFORWARD_TABLE2 = std::ptr::null_mut();
+        unsafe { GetIpForwardTable2(AF_UNSPEC, &mut table) }.ok()?;
+        let rows = unsafe {
+            std::slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize)
+        };
+        let routes = rows
+            .iter()
+            .filter_map(|row| {
+                let prefix = &row.DestinationPrefix;
+                let destination = unsafe { ip((&raw const prefix.Prefix).cast()) }?;
+                let next_hop = unsafe { ip((&raw const row.NextHop).cast()) }
+                    .filter(|hop| !hop.is_unspecified());
+                Some(RouteEntry {
+                    destination,
+                    prefix_len: prefix.PrefixLength,
+                    next_hop,
+                    interface_index: row.InterfaceIndex,
+                    interface: None,
+                    metric: row.Metric,
+                })
+            })
+            .collect();
+        unsafe { FreeMibTable(table.cast()) };
+        Ok(routes)
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use tokio::net::TcpListener;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[tokio::test]
+    async fn port_checks_tell_open_from_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let open = port_check("127.0.0.1", port, TIMEOUT).await;
+        assert_eq!(open.outcome, PortCheckOutcome::Open);
+        assert_eq!(
+            open.address,
+            Some(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+        );
+        assert!(open.elapsed() < TIMEOUT);
+
+        drop(listener);
+        let closed = port_check("127.0.0.1", port, TIMEOUT).await;
+        assert_eq!(closed.outcome, PortCheckOutcome::Refused, "{:?}", closed);
+    }
+
+    #[tokio::test]
+    async fn localhost_resolves_to_loopback() {
+        let NetDiagResponse::Resolved(result) = resolve("localhost").await else {
+            panic!("localhost did not resolve");
+        };
+        assert!(!result.addresses.is_empty());
+        assert!(
+            result.addresses.iter().all(IpAddr::is_loopback),
+            "{:?}",
+            result
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn interfaces_and_routes_need_windows() {
+        for response in [interfaces(), routes()] {
+            assert!(matches!(
+                response,
+                NetDiagResponse::Error {
+                    kind: NetDiagErrorKind::Unsupported,
+                    ..
+                }
+            ));
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! What optional features this slave has, found once at startup and
+//! listed in its `Hello` so the master can refuse what it cannot do
+//! before asking (see [`tix_core::features`]).
+//!
+//! The Windows APIs are there or not by build; PowerShell is there when
+//! its program is on `PATH`; the screen and Ctrl+Alt+Del also need the
+//! config to allow them.
+
+use std::path::Path;
+
+use tix_core::features::{Feature, FeatureSet};
+use tix_core::rdp::capture::CAPTURE_AVAILABLE;
+
+use crate::config::SlaveConfig;
+use crate::sas::SasHost;
+
+/// The optional features this slave has under `config`.
+pub fn features(config: &SlaveConfig) -> FeatureSet {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let dirs: Vec<_> = std::env::split_paths(&path).collect();
+    features_with(config, |program| {
+        dirs.iter().any(|dir| is_program(&dir.join(program)))
+    })
+}
+
+/// [`features`], with `installed` saying whether a program can be found.
+fn features_with(config: &SlaveConfig, installed: impl Fn(&str) -> bool) -> FeatureSet {
+    let windows = cfg!(windows);
+    let checks = [
+        (Feature::PowerShell, installed("powershell")),
+        (Feature::Pwsh, installed("pwsh")),
+        (Feature::Services, windows),
+        (Feature::EventLog, windows),
+        (Feature::Registry, windows),
+        (Feature::Trash, windows),
+        (
+            Feature::Screen,
+            CAPTURE_AVAILABLE && config.screen.max_sessions > 0,
+        ),
+        (
+            Feature::Sas,
+            CAPTURE_AVAILABLE && config.screen.allow_sas && SasHost::detect().supported,
+        ),
+    ];
+    checks
+        .into_iter()
+        .filter_map(|(feature, present)| present.then_some(feature))
+        .collect()
+}
+
+/// Whether `path`, or `path.exe` on Windows, is a file.
+fn is_program(path: &Path) -> bool {
+    path.is_file() || (cfg!(windows) && path.with_extension("exe").is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shells_come_from_path_and_the_rest_from_the_build() {
+        let config = SlaveConfig::default();
+        let features = features_with(&config, |program| program == "pwsh");
+        assert!(features.contains(Feature::Pwsh));
+        assert!(!features.contains(Feature::PowerShell));
+        for feature in [Feature::Services, Feature::EventLog, Feature::Trash] {
+            assert_eq!(features.contains(feature), cfg!(windows), "{feature}");
+        }
+        assert_eq!(features.contains(Feature::Screen), CAPTURE_AVAILABLE);
+        // Ctrl+Alt+Del is off until the config allows it.
+        assert!(!features.contains(Feature::Sas));
+
+        let mut config = SlaveConfig::default();
+        config.screen.max_sessions = 0;
+        assert!(!features_with(&config, |_| true).contains(Feature::Screen));
+    }
+}
@@ -0,0 +1,365 @@
+//! Read-only registry queries and startup-program enumeration.
+//!
+//! All functions here block on OS calls and are meant to run on a
+//! blocking thread (`tokio::task::spawn_blocking`).
+
+use std::path::{Path, PathBuf};
+use tix_core::protocol::{
+    RegistryQueryRequest, RegistryQueryResponse, StartupEntry, StartupListResponse, StartupSource,
+};
+
+#[cfg(windows)]
+use tix_core::protocol::RegistryHive;
+
+/// `Run` / `RunOnce` keys scanned by [`startup_list`].
+#[cfg(windows)]
+const RUN_KEYS: &[(RegistryHive, &str)] = &[
+    (
+        RegistryHive::LocalMachine,
+        "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run",
+    ),
+    (
+        RegistryHive::LocalMachine,
+        "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+    ),
+    (
+        RegistryHive::LocalMachine,
+        "SOFTWARE\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\Run",
+    ),
+    (
+        RegistryHive::CurrentUser,
+        "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run",
+    ),
+    (
+        RegistryHive::CurrentUser,
+        "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+    ),
+];
+
+// ── Registry query ───────────────────────────────────────────────
+
+/// Execute a registry query, mapping OS failures into an error payload.
+#[cfg(windows)]
+pub fn query(req: &RegistryQueryRequest) -> RegistryQueryResponse {
+    let key = match win::Key::open(req.hive, &req.key_path) {
+        Ok(key) => key,
+        Err(e) => return win::error_response(&req.full_path(), e),
+    };
+
+    match &req.value_name {
+        Some(name) => match key.value(name) {
+            Ok(value) => RegistryQueryResponse::Value(tix_core::protocol::RegistryEntry {
+                name: name.clone(),
+                value,
+            }),
+            Err(e) => win::error_response(&format!("{}\\{}", req.full_path(), name), e),
+        },
+        None => match key.subkeys().and_then(|s| key.values().map(|v| (s, v))) {
+            Ok((subkeys, values)) => RegistryQueryResponse::Key { subkeys, values },
+            Err(e) => win::error_response(&req.full_path(), e),
+        },
+    }
+}
+
+/// Execute a registry query, mapping OS failures into an error payload.
+#[cfg(not(windows))]
+pub fn query(req: &RegistryQueryRequest) -> RegistryQueryResponse {
+    RegistryQueryResponse::error(
+        tix_core::protocol::RegistryErrorKind::Unsupported,
+        format!("{}: registry is only available on Windows", req.full_path()),
+    )
+}
+
+/// A `REG_DWORD` value, or `None` if it is missing, unreadable or of
+/// another type.
+#[cfg(windows)]
+pub(crate) fn read_dword(hive: RegistryHive, path: &str, name: &str) -> Option<u32> {
+    match win::Key::open(hive, path).ok()?.value(name).ok()? {
+        tix_core::protocol::RegistryValue::Dword(value) => Some(value),
+        _ => None,
+    }
+}
+
+// ── Startup list ─────────────────────────────────────────────────
+
+/// Collect startup entries from the `Run` keys and the Startup folders.
+///
+/// Unreadable locations are reported in `errors` rather than failing the
+/// whole listing.
+pub fn startup_list() -> StartupListResponse {
+    let mut resp = StartupListResponse::default();
+
+    #[cfg(windows)]
+    for &(hive, path) in RUN_KEYS {
+        let full_path = format!("{}\\{}", hive, path);
+        let values = win::Key::open(hive, path).and_then(|key| key.values());
+        match values {
+            Ok(values) => resp
+                .entries
+                .extend(values.into_iter().map(|v| StartupEntry {
+                    name: v.name,
+                    command: v.value.to_string(),
+                    source: StartupSource::RegistryKey(full_path.clone()),
+                })),
+            // A missing Run key simply means nothing is registered there
+            Err(e) if win::is_not_found(e) => {}
+            Err(e) => resp
+                .errors
+                .push(format!("{}: {}", full_path, win::describe(e))),
+        }
+    }
+
+    for folder in startup_folders() {
+        let read_dir = match std::fs::read_dir(&folder) {
+            Ok(rd) => rd,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                resp.errors.push(format!("{}: {}", folder.display(), e));
+                continue;
+            }
+        };
+        for entry in read_dir.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Explorer's hidden folder settings file is not a program
+            if name.eq_ignore_ascii_case("desktop.ini") {
+                continue;
+            }
+            resp.entries.push(StartupEntry {
+                name,
+                command: entry.path().display().to_string(),
+                source: StartupSource::StartupFolder(folder.display().to_string()),
+            });
+        }
+    }
+
+    resp
+}
+
+/// Per-user and all-users Startup folders, when the environment defines
+/// them.
+fn startup_folders() -> Vec<PathBuf> {
+    let suffix = Path::new("Microsoft\\Windows\\Start Menu\\Programs\\Startup");
+    ["APPDATA", "ProgramData"]
+        .into_iter()
+        .filter_map(std::env::var_os)
+        .map(|base| PathBuf::from(base).join(suffix))
+        .collect()
+}
+
+// ── Windows registry access ──────────────────────────────────────
+
+#[cfg(windows)]
+mod win {
+    use tix_core::protocol::{
+        RegistryEntry, RegistryErrorKind, RegistryHive, RegistryQueryResponse, RegistryValue,
+    };
+    use windows::Win32::Foundation::{
+        ERROR_ACCESS_DENIED, ERROR_FILE_NOT_FOUND, ERROR_NO_MORE_ITEMS, ERROR_PATH_NOT_FOUND,
+        ERROR_SUCCESS, WIN32_ERROR,
+    };
+    use windows::Win32::System::Registry::{
+        HKEY, HKEY_CLASSES_ROOT, HKEY_CURRENT_CONFIG, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE,
+        HKEY_USERS, KEY_READ, REG_VALUE_TYPE, RegCloseKey, RegEnumKeyExW, RegEnumValueW,
+        RegOpenKeyExW, RegQueryValueExW,
+    };
+    use windows::core::{PCWSTR, PWSTR};
+
+    /// Longest key name the registry allows, plus the NUL terminator.
+    const MAX_KEY_NAME: usize = 256;
+    /// Longest value name the registry allows, plus the NUL terminator.
+    const MAX_VALUE_NAME: usize = 16_384;
+
+    /// NUL-terminated UTF-16 copy of `s`.
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn check(status: WIN32_ERROR) -> Result<(), WIN32_ERROR> {
+        if status == ERROR_SUCCESS {
+            Ok(())
+        } else {
+            Err(status)
+        }
+    }
+
+    pub fn is_not_found(e: WIN32_ERROR) -> bool {
+        e == ERROR_FILE_NOT_FOUND || e == ERROR_PATH_NOT_FOUND
+    }
+
+    pub fn describe(e: WIN32_ERROR) -> String {
+        std::io::Error::from_raw_os_error(e.0 as i32).to_string()
+    }
+
+    pub fn error_response(path: &str, e: WIN32_ERROR) -> RegistryQueryResponse {
+        let kind = if e == ERROR_ACCESS_DENIED {
+            RegistryErrorKind::AccessDenied
+        } else if is_not_found(e) {
+            RegistryErrorKind::NotFound
+        } else {
+            RegistryErrorKind::Other
+        };
+        RegistryQueryResponse::error(kind, format!("{}: {}", path, describe(e)))
+    }
+
+    /// An open registry key, closed on drop.
+    pub struct Key(HKEY);
+
+    impl Drop for Key {
+        fn drop(&mut self) {
+            unsafe {
+                let _ = RegCloseKey(self.0);
+            }
+        }
+    }
+
+    impl Key {
+        /// Open `path` below `hive` with `KEY_READ`.
+        pub fn open(hive: RegistryHive, path: &str) -> Result<Self, WIN32_ERROR> {
+            let root = match hive {
+                RegistryHive::ClassesRoot => HKEY_CLASSES_ROOT,
+                RegistryHive::CurrentUser => HKEY_CURRENT_USER,
+                RegistryHive::LocalMachine => HKEY_LOCAL_MACHINE,
+                RegistryHive::Users => HKEY_USERS,
+                RegistryHive::CurrentConfig => HKEY_CURRENT_CONFIG,
+            };
+            let path = wide(path);
+            let mut hkey = HKEY::default();
+            check(unsafe { RegOpenKeyExW(root, PCWSTR(path.as_ptr()), 0, KEY_READ, &mut hkey) })?;
+            Ok(Self(hkey))
+        }
+
+        /// Read a single value.
+        pub fn value(&self, name: &str) -> Result<RegistryValue, WIN32_ERROR> {
+            let name = wide(name);
+            let mut kind = REG_VALUE_TYPE::default();
+            let mut len = 0u32;
+            check(unsafe {
+                RegQueryValueExW(
+                    self.0,
+                    PCWSTR(name.as_ptr()),
+                    None,
+                    Some(&mut kind),
+                    None,
+                    Some(&mut len),
+                )
+            })?;
+
+            let mut data = vec![0u8; len as usize];
+            check(unsafe {
+                RegQueryValueExW(
+                    self.0,
+                    PCWSTR(name.as_ptr()),
+                    None,
+                    Some(&mut kind),
+                    Some(data.as_mut_ptr()),
+                    Some(&mut len),
+                )
+            })?;
+            data.truncate(len as usize);
+            Ok(RegistryValue::from_raw(kind.0, data))
+        }
+
+        /// Names of all direct subkeys.
+        pub fn subkeys(&self) -> Result<Vec<String>, WIN32_ERROR> {
+            let mut names = Vec::new();
+            let mut buf = [0u16; MAX_KEY_NAME];
+            for index in 0.. {
+                let mut len = buf.len() as u32;
+                let status = unsafe {
+                    RegEnumKeyExW(
+                        self.0,
+                        index,
+                        PWSTR(buf.as_mut_ptr()),
+                        &mut len,
+                        None,
+                        PWSTR::null(),
+                        None,
+                        None,
+                    )
+                };
+                if status == ERROR_NO_MORE_ITEMS {
+                    break;
+                }
+                check(status)?;
+                names.push(String::from_utf16_lossy(&buf[..len as usize]));
+            }
+            Ok(names)
+        }
+
+        /// All values stored directly in this key.
+        pub fn values(&self) -> Result<Vec<RegistryEntry>, WIN32_ERROR> {
+            let mut entries = Vec::new();
+            let mut buf = vec![0u16; MAX_VALUE_NAME];
+            for index in 0.. {
+                let mut len = buf.len() as u32;
+                let status = unsafe {
+                    RegEnumValueW(
+                        self.0,
+                        index,
+                        PWSTR(buf.as_mut_ptr()),
+                        &mut len,
+                        None,
+                        None,
+                        None,
+                        None,
+                    )
+                };
+                if status == ERROR_NO_MORE_ITEMS {
+                    break;
+                }
+                check(status)?;
+                let name = String::from_utf16_lossy(&buf[..len as usize]);
+                let value = self.value(&name)?;
+                entries.push(RegistryEntry { name, value });
+            }
+            Ok(entries)
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(all(test, windows))]
+mod tests {
+    use super::*;
+    use tix_core::protocol::RegistryValue;
+
+    const CURRENT_VERSION: &str = "HKLM\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion";
+
+    #[test]
+    fn query_well_known_value() {
+        let req = RegistryQueryRequest::from_path(CURRENT_VERSION)
+            .unwrap()
+            .with_value("ProductName");
+        match query(&req) {
+            RegistryQueryResponse::Value(entry) => {
+                assert_eq!(entry.name, "ProductName");
+                assert!(matches!(entry.value, RegistryValue::String(ref s) if !s.is_empty()));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn enumerate_well_known_key() {
+        let req = RegistryQueryRequest::from_path(CURRENT_VERSION).unwrap();
+        match query(&req) {
+            RegistryQueryResponse::Key { values, .. } => {
+                assert!(values.iter().any(|v| v.name == "CurrentBuild"));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_key_is_not_found() {
+        let req = RegistryQueryRequest::from_path("HKLM\\SOFTWARE\\tix-does-not-exist").unwrap();
+        assert!(matches!(
+            query(&req),
+            RegistryQueryResponse::Error {
+                kind: tix_core::protocol::RegistryErrorKind::NotFound,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,217 @@
+//! Ctrl+Alt+Del for the viewer.
+//!
+//! Windows drops a Ctrl+Alt+Del that arrives through `SendInput`, so the
+//! viewer asks for it with `Command::SendSas` and the slave raises it
+//! with `SendSAS` from `sas.dll`. That call silently does nothing unless
+//! the caller runs as a service and the `SoftwareSASGeneration` policy
+//! lets services use it, so [`gate`] checks all of that first and the
+//! viewer is told which condition failed.
+
+use tix_core::protocol::{SasRefusal, SasResponse};
+
+/// What the slave's host allows, as far as the secure attention
+/// sequence goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SasHost {
+    /// The platform has a `SendSAS` to call.
+    pub supported: bool,
+    /// The slave runs in session 0, as a service.
+    pub is_service: bool,
+    /// `SoftwareSASGeneration` lets services raise the sequence.
+    pub policy_allows: bool,
+}
+
+impl SasHost {
+    /// Inspect the running process and the machine policy.
+    #[cfg(windows)]
+    pub fn detect() -> Self {
+        Self {
+            supported: true,
+            is_service: win::in_session_zero(),
+            policy_allows: win::policy_allows_services(),
+        }
+    }
+
+    /// Inspect the running process and the machine policy.
+    #[cfg(not(windows))]
+    pub fn detect() -> Self {
+        Self {
+            supported: false,
+            is_service: false,
+            policy_allows: false,
+        }
+    }
+}
+
+/// Decide whether a `SendSas` may go ahead. `control` is the mode of the
+/// running screen session, `None` when there is none. The checks run
+/// from the viewer's side outwards, so the refusal names the first
+/// thing the user can do something about.
+pub fn gate(allowed: bool, control: Option<bool>, host: &SasHost) -> Result<(), SasRefusal> {
+    if !allowed {
+        return Err(SasRefusal::Disabled);
+    }
+    match control {
+        None => return Err(SasRefusal::NoSession),
+        Some(false) => return Err(SasRefusal::ViewOnly),
+        Some(true) => {}
+    }
+    if !host.supported {
+        return Err(SasRefusal::Unsupported);
+    }
+    if !host.is_service {
+        return Err(SasRefusal::NotAService);
+    }
+    if !host.policy_allows {
+        return Err(SasRefusal::PolicyDenied);
+    }
+    Ok(())
+}
+
+/// Raise the sequence if [`gate`] lets it through. Blocks on registry
+/// and loader calls: run it on a blocking thread.
+pub fn send(allowed: bool, control: Option<bool>) -> SasResponse {
+    let outcome = gate(allowed, control, &SasHost::detect()).and_then(|()| raise());
+    match outcome {
+        Ok(()) => SasResponse::Sent,
+        Err(refusal) => SasResponse::Refused(refusal),
+    }
+}
+
+#[cfg(windows)]
+fn raise() -> Result<(), SasRefusal> {
+    win::send_sas().map_err(SasRefusal::Failed)
+}
+
+#[cfg(not(windows))]
+fn raise() -> Result<(), SasRefusal> {
+    Err(SasRefusal::Unsupported)
+}
+
+#[cfg(windows)]
+mod win {
+    use tix_core::protocol::RegistryHive;
+    use windows::Win32::Foundation::FreeLibrary;
+    use windows::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
+    use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+    use windows::core::{s, w};
+
+    const POLICY_KEY: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Policies\\System";
+
+    /// `SoftwareSASGeneration` values that include services: 1 (services)
+    /// and 3 (services and Ease of Access applications).
+    const SERVICES_ALLOWED: [u32; 2] = [1, 3];
+
+    pub fn in_session_zero() -> bool {
+        let mut session = u32::MAX;
+        unsafe { ProcessIdToSessionId(std::process::id(), &mut session) }.is_ok() && session == 0
+    }
+
+    pub fn policy_allows_services() -> bool {
+        crate::registry::read_dword(
+            RegistryHive::LocalMachine,
+            POLICY_KEY,
+            "SoftwareSASGeneration",
+        )
+        .is_some_and(|value| SERVICES_ALLOWED.contains(&value))
+    }
+
+    /// Call `SendSAS(FALSE)`, loading `sas.dll` for the occasion.
+    pub fn send_sas() -> Result<(), String> {
+        type SendSas = unsafe extern "system" fn(as_user: i32);
+
+        let module = unsafe { LoadLibraryW(w!("sas.dll")) }.map_err(|e| e.to_string())?;
+        let result = match unsafe { GetProcAddress(module, s!("SendSAS")) } {
+            Some(proc) => {
+                // SAFETY: `SendSAS` has this signature in every sas.dll.
+                let send: SendSas = unsafe { std::mem::transmute(proc) };
+                unsafe { send(0) };
+                Ok(())
+            }
+            None => Err("sas.dll has no SendSAS".to_string()),
+        };
+        let _ = unsafe { FreeLibrary(module) };
+        result
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVICE: SasHost = SasHost {
+        supported: true,
+        is_service: true,
+        policy_allows: true,
+    };
+
+    #[test]
+    fn a_service_with_the_policy_may_send_it() {
+        assert_eq!(gate(true, Some(true), &SERVICE), Ok(()));
+    }
+
+    #[test]
+    fn refusals_name_the_first_missing_condition() {
+        let cases = [
+            (false, Some(true), SERVICE, SasRefusal::Disabled),
+            (true, None, SERVICE, SasRefusal::NoSession),
+            (true, Some(false), SERVICE, SasRefusal::ViewOnly),
+            (
+                true,
+                Some(true),
+                SasHost {
+                    supported: false,
+                    ..SERVICE
+                },
+                SasRefusal::Unsupported,
+            ),
+            (
+                true,
+                Some(true),
+                SasHost {
+                    is_service: false,
+                    ..SERVICE
+                },
+                SasRefusal::NotAService,
+            ),
+            (
+                true,
+                Some(true),
+                SasHost {
+                    policy_allows: false,
+                    ..SERVICE
+                },
+                SasRefusal::PolicyDenied,
+            ),
+            // The config switch wins over everything else.
+            (
+                false,
+                None,
+                SasHost {
+                    supported: false,
+                    is_service: false,
+                    policy_allows: false,
+                },
+                SasRefusal::Disabled,
+            ),
+        ];
+        for (allowed, control, host, expected) in cases {
+            assert_eq!(gate(allowed, control, &host), Err(expected), "{host:?}");
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn other_platforms_refuse_it() {
+        assert_eq!(
+            send(true, Some(true)),
+            SasResponse::Refused(SasRefusal::Unsupported)
+        );
+        assert_eq!(
+            send(false, Some(true)),
+            SasResponse::Refused(SasRefusal::Disabled)
+        );
+    }
+}
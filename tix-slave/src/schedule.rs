@@ -0,0 +1,676 @@
+//! Scheduled commands: shell commands the slave runs every so often on
+//! its own, whether or not a master is connected.
+//!
+//! Schedules are kept in `tix-schedules.json` next to the config file, so
+//! they survive a restart. Each run goes through a [`TaskPool`] like any
+//! other task, with the `tasks.timeout_ms` limit, and a run that is still
+//! going when the next one is due makes that one be skipped. The last
+//! [`RESULT_HISTORY`] results of every schedule are kept for
+//! `ScheduleResults`, and each one is also pushed to the master as soon
+//! as the run ends when one is connected.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+
+use tix_core::config::write_atomic;
+use tix_core::protocol::schedule::truncate_output;
+use tix_core::protocol::{
+    ScheduleCreateRequest, ScheduleInfo, ScheduleNameRequest, ScheduleResponse, ScheduleRunReport,
+    ScheduleRunResult, ShellKind,
+};
+use tix_core::{Command, ConnectionSender, TaskOptions, TaskPool};
+
+use crate::config::ShellConfig;
+use crate::shell::ShellInvocation;
+
+/// File the schedules are kept in, beside the config file.
+pub const SCHEDULES_FILE_NAME: &str = "tix-schedules.json";
+
+/// Results kept per schedule.
+pub const RESULT_HISTORY: usize = 20;
+
+/// Most schedules one slave holds.
+pub const MAX_SCHEDULES: usize = 64;
+
+/// Where the schedules live for the config file at `config_path`.
+pub fn schedules_path(config_path: &Path) -> PathBuf {
+    config_path.with_file_name(SCHEDULES_FILE_NAME)
+}
+
+/// How a run ended: the exit code and output, or why the command could
+/// not be run.
+type Outcome = Result<(i32, String), String>;
+
+/// Runs one command line to the end.
+type Runner = Arc<dyn Fn(String) -> BoxFuture<'static, Outcome> + Send + Sync>;
+
+/// The default [`Runner`]: the command line through the system shell.
+fn run_shell(command: String) -> BoxFuture<'static, Outcome> {
+    Box::pin(async move {
+        let shell = ShellInvocation::resolve(&ShellKind::Cmd, &command, &ShellConfig::default())?;
+        let output = shell
+            .to_command()
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| format!("failed to start {}: {}", shell.program, e))?;
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok((output.status.code().unwrap_or(-1), text))
+    })
+}
+
+/// One schedule as saved in the schedules file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Saved {
+    #[serde(flatten)]
+    spec: ScheduleCreateRequest,
+    #[serde(default)]
+    runs: u32,
+}
+
+/// A schedule and what it has done.
+struct Entry {
+    spec: ScheduleCreateRequest,
+    /// Task ID its runs use in the pool; one ID per schedule is what
+    /// keeps two runs of it from overlapping.
+    task_id: u64,
+    runs: u32,
+    skipped: u32,
+    results: VecDeque<ScheduleRunResult>,
+    /// When the interval started counting.
+    since: Instant,
+    /// Sends `Due` every interval; gone once the runs are used up.
+    driver: Option<JoinHandle<()>>,
+}
+
+impl Entry {
+    fn used_up(&self) -> bool {
+        self.spec.max_runs.is_some_and(|max| self.runs >= max)
+    }
+
+    fn info(&self) -> ScheduleInfo {
+        let interval = self.spec.interval().as_millis().max(1) as u64;
+        let elapsed = self.since.elapsed().as_millis() as u64;
+        ScheduleInfo {
+            name: self.spec.name.clone(),
+            command: self.spec.command.clone(),
+            interval_secs: self.spec.interval_secs,
+            max_runs: self.spec.max_runs,
+            runs: self.runs,
+            skipped: self.skipped,
+            next_run_ms: self.driver.is_some().then(|| interval - elapsed % interval),
+            last: self.results.back().cloned(),
+        }
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        if let Some(driver) = self.driver.take() {
+            driver.abort();
+        }
+    }
+}
+
+/// Everything the [`Scheduler`] handles and its engine share.
+struct Table {
+    entries: BTreeMap<String, Entry>,
+    next_task_id: u64,
+    /// Schedules file; `None` keeps them in memory only.
+    path: Option<PathBuf>,
+    /// The connected master, for pushed results.
+    master: Option<ConnectionSender>,
+}
+
+impl Table {
+    /// Write every schedule to the schedules file. A failure is logged
+    /// and the schedules keep running from memory.
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let saved: Vec<Saved> = self
+            .entries
+            .values()
+            .map(|e| Saved {
+                spec: e.spec.clone(),
+                runs: e.runs,
+            })
+            .collect();
+        let result = serde_json::to_string_pretty(&saved)
+            .map_err(|e| e.to_string())
+            .and_then(|text| write_atomic(path, text).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            println!("[WARN] Cannot save schedules to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// The schedules saved at `path`; none when the file is missing or
+/// cannot be read.
+fn load(path: &Path) -> Vec<Saved> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            println!("[WARN] Cannot read {}: {}", path.display(), e);
+            return Vec::new();
+        }
+    };
+    match serde_json::from_str::<Vec<Saved>>(&text) {
+        Ok(saved) => saved
+            .into_iter()
+            .filter(|s| match s.spec.validate() {
+                Ok(()) => true,
+                Err(e) => {
+                    println!("[WARN] Dropping schedule '{}': {}", s.spec.name, e);
+                    false
+                }
+            })
+            .collect(),
+        Err(e) => {
+            println!("[WARN] Ignoring {}: {}", path.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+/// What the drivers and the [`Scheduler`] tell the engine.
+enum Signal {
+    /// The named schedule should run now.
+    Due(String),
+    /// Stop the run using this task ID.
+    Cancel(u64),
+}
+
+/// Handle to the slave's schedules. Clones share them; the engine that
+/// runs them stops when the last clone is dropped.
+#[derive(Clone)]
+pub struct Scheduler {
+    table: Arc<Mutex<Table>>,
+    signals: mpsc::UnboundedSender<Signal>,
+}
+
+impl Scheduler {
+    /// Load the schedules saved at `path` (in memory only when `None`)
+    /// and start running them; each run is stopped after `timeout`.
+    pub fn start(path: Option<PathBuf>, timeout: Option<Duration>) -> Self {
+        Self::start_with_runner(path, timeout, Arc::new(run_shell))
+    }
+
+    fn start_with_runner(path: Option<PathBuf>, timeout: Option<Duration>, runner: Runner) -> Self {
+        let saved = path.as_deref().map(load).unwrap_or_default();
+        let (signals, signal_rx) = mpsc::unbounded_channel();
+        let table = Arc::new(Mutex::new(Table {
+            entries: BTreeMap::new(),
+            next_task_id: 1,
+            path,
+            master: None,
+        }));
+        let scheduler = Self { table, signals };
+        {
+            let mut table = scheduler.table.lock().unwrap();
+            for s in saved {
+                scheduler.insert(&mut table, s.spec, s.runs);
+            }
+            if !table.entries.is_empty() {
+                println!("[SCHD] {} schedule(s) loaded", table.entries.len());
+            }
+        }
+        let (done_tx, done_rx) = mpsc::unbounded_channel();
+        let engine = Engine {
+            table: scheduler.table.clone(),
+            pool: TaskPool::new(),
+            runner,
+            timeout,
+            running: HashMap::new(),
+            done_tx,
+            done_rx,
+            nowhere: ConnectionSender::from(mpsc::channel(1).0),
+        };
+        tokio::spawn(engine.run(signal_rx));
+        scheduler
+    }
+
+    fn insert(&self, table: &mut Table, spec: ScheduleCreateRequest, runs: u32) {
+        let task_id = table.next_task_id;
+        table.next_task_id += 1;
+        let mut entry = Entry {
+            spec,
+            task_id,
+            runs,
+            skipped: 0,
+            results: VecDeque::new(),
+            since: Instant::now(),
+            driver: None,
+        };
+        if !entry.used_up() {
+            entry.driver = Some(drive(
+                entry.spec.name.clone(),
+                entry.spec.interval(),
+                &self.signals,
+            ));
+        }
+        table.entries.insert(entry.spec.name.clone(), entry);
+    }
+
+    /// Push every finished run to `master` from now on.
+    pub fn attach(&self, master: ConnectionSender) {
+        self.table.lock().unwrap().master = Some(master);
+    }
+
+    /// Answer a schedule `cmd` carrying `payload`.
+    pub fn handle(&self, cmd: Command, payload: &[u8]) -> ScheduleResponse {
+        let name = || ScheduleNameRequest::from_bytes(payload).map(|r| r.name);
+        let result = match cmd {
+            Command::ScheduleCreate => {
+                ScheduleCreateRequest::from_bytes(payload).map(|r| self.create(r))
+            }
+            Command::ScheduleList => Ok(self.list()),
+            Command::ScheduleDelete => name().map(|n| self.delete(&n)),
+            Command::ScheduleResults => name().map(|n| self.results(&n)),
+            _ => return ScheduleResponse::Error(format!("{} is not a schedule command", cmd)),
+        };
+        result
+            .unwrap_or_else(|e| ScheduleResponse::Error(format!("Invalid {} payload: {}", cmd, e)))
+    }
+
+    /// Add a schedule; its first run comes one interval from now.
+    pub fn create(&self, spec: ScheduleCreateRequest) -> ScheduleResponse {
+        if let Err(e) = spec.validate() {
+            return ScheduleResponse::Error(e);
+        }
+        let mut table = self.table.lock().unwrap();
+        if table.entries.contains_key(&spec.name) {
+            return ScheduleResponse::Error(format!("schedule '{}' already exists", spec.name));
+        }
+        if table.entries.len() >= MAX_SCHEDULES {
+            return ScheduleResponse::Error(format!(
+                "this slave already has {} schedules",
+                MAX_SCHEDULES
+            ));
+        }
+        let name = spec.name.clone();
+        println!(
+            "[SCHD] {}: every {}s: {}",
+            name, spec.interval_secs, spec.command
+        );
+        self.insert(&mut table, spec, 0);
+        table.save();
+        ScheduleResponse::Created(table.entries[&name].info())
+    }
+
+    /// Every schedule, by name.
+    pub fn list(&self) -> ScheduleResponse {
+        let table = self.table.lock().unwrap();
+        ScheduleResponse::Schedules(table.entries.values().map(Entry::info).collect())
+    }
+
+    /// Remove a schedule, cancelling its run if one is going.
+    pub fn delete(&self, name: &str) -> ScheduleResponse {
+        let mut table = self.table.lock().unwrap();
+        let Some(entry) = table.entries.remove(name) else {
+            return no_such_schedule(name);
+        };
+        let _ = self.signals.send(Signal::Cancel(entry.task_id));
+        drop(entry);
+        table.save();
+        println!("[SCHD] {}: deleted", name);
+        ScheduleResponse::Deleted(name.to_string())
+    }
+
+    /// The kept results of a schedule, oldest first.
+    pub fn results(&self, name: &str) -> ScheduleResponse {
+        let table = self.table.lock().unwrap();
+        match table.entries.get(name) {
+            Some(entry) => ScheduleResponse::Results {
+                name: name.to_string(),
+                results: entry.results.iter().cloned().collect(),
+            },
+            None => no_such_schedule(name),
+        }
+    }
+}
+
+fn no_such_schedule(name: &str) -> ScheduleResponse {
+    ScheduleResponse::Error(format!("no schedule named '{}'", name))
+}
+
+/// Ask for a run of `name` every `interval`, starting one interval from
+/// now. Ticks missed while the engine was busy are dropped, not caught up.
+///
+/// The driver only holds a weak sender, so it does not keep the engine
+/// alive once every [`Scheduler`] is gone.
+fn drive(
+    name: String,
+    interval: Duration,
+    signals: &mpsc::UnboundedSender<Signal>,
+) -> JoinHandle<()> {
+    let signals = signals.downgrade();
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval_at(Instant::now() + interval, interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            ticks.tick().await;
+            let Some(signals) = signals.upgrade() else {
+                return;
+            };
+            if signals.send(Signal::Due(name.clone())).is_err() {
+                return;
+            }
+        }
+    })
+}
+
+/// A run in progress.
+struct Run {
+    name: String,
+    number: u32,
+    started: Instant,
+    started_ms: u64,
+}
+
+/// Starts due runs in its pool and records how they end.
+struct Engine {
+    table: Arc<Mutex<Table>>,
+    pool: TaskPool,
+    runner: Runner,
+    timeout: Option<Duration>,
+    running: HashMap<u64, Run>,
+    done_tx: mpsc::UnboundedSender<(u64, Outcome)>,
+    done_rx: mpsc::UnboundedReceiver<(u64, Outcome)>,
+    /// Runs answer no request, but the pool hands every task a sender;
+    /// this one leads nowhere.
+    nowhere: ConnectionSender,
+}
+
+impl Engine {
+    async fn run(mut self, mut signals: mpsc::UnboundedReceiver<Signal>) {
+        loop {
+            tokio::select! {
+                signal = signals.recv() => match signal {
+                    Some(Signal::Due(name)) => self.start_run(&name),
+                    Some(Signal::Cancel(task_id)) => {
+                        self.pool.cancel_task(task_id);
+                    }
+                    None => {
+                        self.pool.cancel_all();
+                        return;
+                    }
+                },
+                Some((task_id, outcome)) = self.done_rx.recv() => {
+                    self.finish(task_id, outcome).await;
+                }
+                Some(event) = self.pool.recv() => {
+                    let task_id = event.request_id();
+                    if let Some(err) = self.pool.process_event(event).await {
+                        self.finish(task_id, Err(err.to_string())).await;
+                    }
+                }
+            }
+        }
+    }
+
+    fn start_run(&mut self, name: &str) {
+        let (task_id, command, number) = {
+            let mut table = self.table.lock().unwrap();
+            let Some(entry) = table.entries.get_mut(name) else {
+                return;
+            };
+            if self.pool.is_active(entry.task_id) {
+                entry.skipped += 1;
+                println!(
+                    "[SCHD] {}: run {} is still going, skipping this one",
+                    name, entry.runs
+                );
+                return;
+            }
+            entry.runs += 1;
+            if entry.used_up()
+                && let Some(driver) = entry.driver.take()
+            {
+                driver.abort();
+            }
+            let run = (entry.task_id, entry.spec.command.clone(), entry.runs);
+            table.save();
+            run
+        };
+        println!("[SCHD] {}: run {}: {}", name, number, command);
+
+        let options = TaskOptions::new().with_name(format!("Schedule {}", name));
+        let options = match self.timeout {
+            Some(timeout) => options.with_timeout(timeout),
+            None => options,
+        };
+        let runner = self.runner.clone();
+        let done = self.done_tx.clone();
+        let spawned = self.pool.spawn_with_options(
+            self.nowhere.clone(),
+            task_id,
+            Vec::new(),
+            move |_tx, task_id, _payload| async move {
+                let outcome = runner(command).await;
+                let _ = done.send((task_id, outcome));
+            },
+            options,
+        );
+        match spawned {
+            Ok(()) => {
+                let started_ms = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0);
+                self.running.insert(
+                    task_id,
+                    Run {
+                        name: name.to_string(),
+                        number,
+                        started: Instant::now(),
+                        started_ms,
+                    },
+                );
+            }
+            Err(e) => println!("[ERR ] Schedule {}: {}", name, e),
+        }
+    }
+
+    /// Record how the run using `task_id` ended and push it to the master.
+    async fn finish(&mut self, task_id: u64, outcome: Outcome) {
+        let Some(run) = self.running.remove(&task_id) else {
+            return;
+        };
+        let mut result = ScheduleRunResult {
+            run: run.number,
+            started_ms: run.started_ms,
+            elapsed_ms: run.started.elapsed().as_millis() as u64,
+            ..Default::default()
+        };
+        match outcome {
+            Ok((code, output)) => {
+                println!(
+                    "[SCHD] {}: run {} exited with {}",
+                    run.name, run.number, code
+                );
+                result.exit_code = Some(code);
+                result.output = truncate_output(&output);
+            }
+            Err(e) => {
+                println!("[SCHD] {}: run {} failed: {}", run.name, run.number, e);
+                result.error = Some(e);
+            }
+        }
+
+        let (master, finished) = {
+            let mut guard = self.table.lock().unwrap();
+            let table = &mut *guard;
+            // A schedule deleted while it ran keeps nothing.
+            let Some(entry) = table.entries.get_mut(&run.name) else {
+                return;
+            };
+            entry.results.push_back(result.clone());
+            if entry.results.len() > RESULT_HISTORY {
+                entry.results.pop_front();
+            }
+            (table.master.clone(), entry.used_up())
+        };
+        if let Some(master) = master {
+            let report = ScheduleRunReport {
+                name: run.name,
+                result,
+                finished,
+            };
+            if let Ok(pkt) = report.into_packet() {
+                let _ = master.send(pkt).await;
+            }
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A runner that takes `duration` for every command and echoes it.
+    fn fake_runner(duration: Duration) -> Runner {
+        Arc::new(move |command: String| -> BoxFuture<'static, Outcome> {
+            Box::pin(async move {
+                tokio::time::sleep(duration).await;
+                Ok((0, command))
+            })
+        })
+    }
+
+    fn spec(name: &str, interval_secs: u64) -> ScheduleCreateRequest {
+        ScheduleCreateRequest::new(name, "echo hi", Duration::from_secs(interval_secs))
+    }
+
+    fn schedules(scheduler: &Scheduler) -> Vec<ScheduleInfo> {
+        match scheduler.list() {
+            ScheduleResponse::Schedules(list) => list,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    fn results(scheduler: &Scheduler, name: &str) -> Vec<ScheduleRunResult> {
+        match scheduler.results(name) {
+            ScheduleResponse::Results { results, .. } => results,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn schedules_survive_a_restart() {
+        let dir = std::env::temp_dir().join(format!("tix-schedules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = schedules_path(&dir.join("tix-slave.toml"));
+        assert_eq!(path, dir.join(SCHEDULES_FILE_NAME));
+        let _ = std::fs::remove_file(&path);
+
+        let scheduler = Scheduler::start(Some(path.clone()), None);
+        assert!(matches!(
+            scheduler.create(spec("disk", 300).with_max_runs(5)),
+            ScheduleResponse::Created(_)
+        ));
+        assert!(matches!(
+            scheduler.create(spec("uptime", 60)),
+            ScheduleResponse::Created(_)
+        ));
+        assert!(matches!(
+            scheduler.create(spec("disk", 60)),
+            ScheduleResponse::Error(_)
+        ));
+        scheduler.delete("uptime");
+        drop(scheduler);
+
+        let restarted = Scheduler::start(Some(path.clone()), None);
+        let list = schedules(&restarted);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].name, "disk");
+        assert_eq!(list[0].command, "echo hi");
+        assert_eq!(list[0].interval_secs, 300);
+        assert_eq!(list[0].max_runs, Some(5));
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(schedules(&Scheduler::start(Some(path.clone()), None)).is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_once_per_interval() {
+        let scheduler =
+            Scheduler::start_with_runner(None, None, fake_runner(Duration::from_secs(1)));
+        scheduler.create(spec("often", 60));
+        scheduler.create(spec("twice", 30).with_max_runs(2));
+
+        tokio::time::sleep(Duration::from_secs(59)).await;
+        assert!(results(&scheduler, "often").is_empty());
+
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        let done = results(&scheduler, "often");
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].run, 1);
+        assert!(done[0].succeeded());
+        assert_eq!(done[0].output, "echo hi");
+        assert_eq!(done[0].elapsed_ms, 1_000);
+
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        assert_eq!(results(&scheduler, "often").len(), 3);
+        let twice = &schedules(&scheduler)[1];
+        assert_eq!(twice.runs, 2);
+        assert_eq!(twice.next_run_ms, None);
+        assert_eq!(results(&scheduler, "twice").len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn overlapping_runs_are_skipped() {
+        let scheduler =
+            Scheduler::start_with_runner(None, None, fake_runner(Duration::from_secs(150)));
+        scheduler.create(spec("slow", 60));
+
+        // Runs start at 60 s and 240 s; the ones due at 120 s and 180 s
+        // find the first still going.
+        tokio::time::sleep(Duration::from_secs(250)).await;
+        let info = &schedules(&scheduler)[0];
+        assert_eq!(info.runs, 2);
+        assert_eq!(info.skipped, 2);
+        assert_eq!(results(&scheduler, "slow").len(), 1);
+
+        // Deleting a schedule cancels its run.
+        assert_eq!(
+            scheduler.delete("slow"),
+            ScheduleResponse::Deleted("slow".into())
+        );
+        tokio::time::sleep(Duration::from_secs(200)).await;
+        assert!(schedules(&scheduler).is_empty());
+        assert!(matches!(
+            scheduler.results("slow"),
+            ScheduleResponse::Error(_)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_time_out_like_tasks() {
+        let scheduler = Scheduler::start_with_runner(
+            None,
+            Some(Duration::from_secs(5)),
+            fake_runner(Duration::from_secs(30)),
+        );
+        scheduler.create(spec("hung", 60).with_max_runs(1));
+        tokio::time::sleep(Duration::from_secs(70)).await;
+        let done = results(&scheduler, "hung");
+        assert_eq!(done.len(), 1);
+        assert!(!done[0].succeeded());
+        assert!(done[0].error.as_deref().unwrap().contains("timed out"));
+    }
+}
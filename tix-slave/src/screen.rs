@@ -0,0 +1,278 @@
+//! Screen sharing over the classic control connection.
+//!
+//! `ScreenStart` spawns a [`ScreenService`] that streams frames over UDP
+//! to the port announced by the master; the UDP endpoint is negotiated
+//! entirely inside the tix-core packet exchange, so no second control
+//! port is needed.
+//!
+//! Input is only injected while the session is in control mode; a
+//! view-only session (see [`InputGate`]) refuses it.
+//!
+//! Screen traffic is counted into a [`TrafficCounter`] owned by the
+//! caller so the totals survive individual sessions.
+//!
+//! A viewer showing several monitors runs one session per monitor, each
+//! with its own UDP socket and the session ID stamped on its frames.
+//!
+//! The stream MTU comes from the request; when it asks for a probe the
+//! path is measured towards the master's UDP port before the ack goes
+//! out, and the ack reports the result. A request asking for a
+//! preflight has UDP probes sent the same way and the ack reports how
+//! many were echoed; the viewer's answer comes in `ScreenPreflight`.
+//!
+//! When the request offers `udp_input`, the session also takes pointer
+//! moves from the viewer on its UDP socket; they pass the same gate.
+//! When it offers `timed_input`, the ack accepts it and the viewer's
+//! input events arrive stamped with its clock.
+//!
+//! Input from the control connection goes through a clone of the
+//! service's injector, so when it keeps failing (an elevated window in
+//! front, say) the service tells the viewer.
+//!
+//! When the request accepts a dictionary and the slave has one, the ack
+//! offers it (without the bytes if the viewer already knows its hash).
+//! Frames switch to it only when the viewer confirms with
+//! `ScreenDictionary`; see [`ScreenSession::enable_dictionary`].
+//!
+//! A request with `low_bandwidth` starts the session on thumbnails; one
+//! offering `thumbnails` lets the service fall back to them on a slow
+//! link. `ScreenSharing` switches a running session; see
+//! [`ScreenSession::switch_sharing`].
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tix_core::protocol::{DictionaryOffer, ScreenConfig, ScreenStartRequest};
+use tix_core::rdp::capture::DxgiCapturer;
+use tix_core::rdp::delta::supported_block_size;
+use tix_core::rdp::dictionary::FrameDictionary;
+use tix_core::rdp::input::{InputGate, InputInjector};
+use tix_core::rdp::preflight::{self, PREFLIGHT_PROBES};
+use tix_core::rdp::service::{ScreenService, ScreenServiceConfig};
+use tix_core::rdp::thumbnail::SharingSwitch;
+use tix_core::rdp::transport::{MAX_SESSIONS, ScreenTransport, TrafficCounter, TransportConfig};
+use tokio::task::JoinHandle;
+
+/// A running capture session.
+pub struct ScreenSession {
+    stop: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+    injector: InputInjector,
+    gate: Arc<InputGate>,
+    /// Hash of the dictionary offered in the ack, and the service's
+    /// switch to it.
+    dictionary: Option<(u64, Arc<AtomicBool>)>,
+    /// Input events come as `TimedMouseEvent` / `TimedKeyEvent`.
+    timed_input: bool,
+    /// The service's switch between frames and thumbnails.
+    sharing: Arc<SharingSwitch>,
+}
+
+impl ScreenSession {
+    /// Start capturing and streaming to `master_ip:req.udp_port`.
+    ///
+    /// `local_ip` is the address the master reached us on; it is echoed
+    /// back in the ack together with the bound UDP port. `dictionary` is
+    /// offered if the request accepts one.
+    pub async fn start(
+        req: &ScreenStartRequest,
+        master_ip: IpAddr,
+        local_ip: Option<IpAddr>,
+        traffic: TrafficCounter,
+        dictionary: Option<&FrameDictionary>,
+    ) -> Result<(Self, ScreenConfig), String> {
+        let udp_port = req
+            .udp_port
+            .ok_or_else(|| "ScreenStart is missing the master UDP port".to_string())?;
+        if req.session >= MAX_SESSIONS {
+            return Err(format!(
+                "screen session {} out of range (0-{})",
+                req.session,
+                MAX_SESSIONS - 1
+            ));
+        }
+
+        let mut tuning = TransportConfig {
+            probe_mtu: req.probe_mtu,
+            ..TransportConfig::default()
+        };
+        if req.mtu != 0 {
+            tuning.mtu = req.mtu as usize;
+        }
+        let udp = tuning
+            .bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))
+            .map_err(|e| format!("UDP bind failed: {}", e))?;
+        let udp_endpoint = SocketAddr::new(
+            local_ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            udp.local_addr().map_err(|e| e.to_string())?.port(),
+        );
+        let master_udp = SocketAddr::new(master_ip, udp_port);
+        let mtu = tuning.negotiate_mtu(&udp, master_udp).await;
+        if req.probe_mtu {
+            println!("[SCRN] Path MTU to {}: {} bytes", master_udp, mtu);
+        }
+        let preflight = if req.preflight {
+            let report = preflight::run(&udp, master_udp, PREFLIGHT_PROBES).await;
+            println!(
+                "[SCRN] Preflight to {}: {}/{} probes echoed{}",
+                master_udp,
+                report.echoed,
+                report.sent,
+                match report.echoed_from {
+                    Some(from) if from != master_udp => format!(" from {}", from),
+                    _ => String::new(),
+                }
+            );
+            Some(report)
+        } else {
+            None
+        };
+        let transport = ScreenTransport::new(udp, master_udp)
+            .with_traffic(traffic)
+            .with_mtu(mtu)
+            .with_session(req.session);
+
+        let svc_config = ScreenServiceConfig {
+            target_fps: req.fps.clamp(1, 60),
+            monitor_index: req.monitor as u32,
+            pixel_format: req.format.pixel_format(),
+            low_bandwidth: req.low_bandwidth,
+            low_bandwidth_fallback: req.thumbnails,
+            ..ScreenServiceConfig::default()
+        };
+        let monitor_index = svc_config.monitor_index;
+        let pixel_format = svc_config.pixel_format;
+        let block_size = supported_block_size(svc_config.block_size) as u16;
+
+        // Probe the capturer for the real resolution before committing.
+        let (width, height, origin) = {
+            let probe = DxgiCapturer::new(monitor_index).map_err(|e| e.to_string())?;
+            (probe.width(), probe.height(), probe.origin())
+        };
+
+        let gate = Arc::new(InputGate::new(req.control));
+        let injector = InputInjector::new();
+        let mut service = ScreenService::with_config(transport, svc_config)
+            .map_err(|e| e.to_string())?
+            .with_injector(injector.clone());
+        if req.udp_input {
+            service = service.with_pointer_input(Arc::clone(&gate));
+        }
+        let offer = match dictionary {
+            Some(dictionary) if req.dictionary => {
+                service = service.with_dictionary(dictionary.clone());
+                let known = req.known_dictionaries.contains(&dictionary.hash());
+                Some(DictionaryOffer {
+                    hash: dictionary.hash(),
+                    bytes: if known {
+                        Vec::new()
+                    } else {
+                        dictionary.bytes().to_vec()
+                    },
+                })
+            }
+            _ => None,
+        };
+        let use_dictionary = offer
+            .as_ref()
+            .map(|offer| (offer.hash, service.dictionary_handle()));
+        let stop = service.stop_handle();
+        let paused = service.pause_handle();
+        let sharing = service.sharing_switch();
+        let handle = tokio::spawn(async move {
+            if let Err(e) = service.run().await {
+                println!("[ERR ] Screen service stopped: {}", e);
+            }
+        });
+
+        let config = ScreenConfig {
+            width,
+            height,
+            quality: req.quality,
+            fps: req.fps.clamp(1, 60),
+            format: pixel_format.into(),
+            monitor_name: format!("Monitor {}", monitor_index),
+            udp_endpoint: Some(udp_endpoint),
+            mtu: mtu as u16,
+            session: req.session,
+            origin,
+            block_size,
+            udp_input: req.udp_input,
+            dictionary: offer,
+            preflight,
+            timed_input: req.timed_input,
+            thumbnails: req.thumbnails,
+            low_bandwidth: req.low_bandwidth,
+        };
+
+        Ok((
+            Self {
+                stop,
+                paused,
+                handle,
+                injector,
+                gate,
+                dictionary: use_dictionary,
+                timed_input: req.timed_input,
+                sharing,
+            },
+            config,
+        ))
+    }
+
+    /// Injector for input events received while the session is live.
+    pub fn injector(&self) -> &InputInjector {
+        &self.injector
+    }
+
+    /// Control / view-only switch every input event must pass.
+    pub fn gate(&self) -> &InputGate {
+        &self.gate
+    }
+
+    /// Whether the viewer stamps its input events.
+    pub fn timed_input(&self) -> bool {
+        self.timed_input
+    }
+
+    /// Compress frames against the dictionary offered in the ack, now
+    /// that the viewer holds the one with `hash`.
+    pub fn enable_dictionary(&self, hash: u64) -> Result<(), String> {
+        match &self.dictionary {
+            Some((offered, enable)) if *offered == hash => {
+                enable.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+            Some((offered, _)) => Err(format!(
+                "dictionary {:016x} was not offered (offered {:016x})",
+                hash, offered
+            )),
+            None => Err("no dictionary was offered for this session".to_string()),
+        }
+    }
+
+    /// Send thumbnails (`low_bandwidth`) or frames from now on, and
+    /// allow or forbid falling back to thumbnails by itself.
+    pub fn switch_sharing(&self, low_bandwidth: bool, fallback: bool) {
+        self.sharing.set_fallback(fallback);
+        self.sharing.request(low_bandwidth);
+    }
+
+    /// Whether the capture loop is still alive.
+    pub fn is_running(&self) -> bool {
+        !self.handle.is_finished()
+    }
+
+    /// Pause or resume streaming without tearing the session down.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    /// Signal the capture loop to stop and wait for it to exit.
+    pub async fn stop(self) {
+        self.stop.store(false, Ordering::SeqCst);
+        let _ = self.handle.await;
+    }
+}
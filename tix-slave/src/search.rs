@@ -0,0 +1,268 @@
+//! `FileSearch`: walk a directory tree and stream the entries whose names
+//! match.
+//!
+//! The walk is depth-first and never follows symbolic links or
+//! junctions, so a link back up the tree cannot loop it. Directories
+//! that cannot be read are skipped. Every step awaits, so cancelling the
+//! task (a `ShellCancel` naming it) stops the walk at once.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use tix_core::ConnectionSender;
+use tix_core::protocol::search::SEARCH_BATCH_SIZE;
+use tix_core::protocol::{
+    FileAttributes, FileMetadata, FileSearchBatch, FileSearchRequest, FileSearchSummary,
+    is_reparse_point,
+};
+
+/// Longest a match waits in a part-filled batch.
+const BATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Matches not yet sent.
+struct Batcher {
+    tx: ConnectionSender,
+    req_id: u64,
+    pending: Vec<FileMetadata>,
+    sent_batches: u64,
+    last_flush: Instant,
+}
+
+impl Batcher {
+    async fn push(&mut self, entry: FileMetadata) {
+        self.pending.push(entry);
+        if self.pending.len() >= SEARCH_BATCH_SIZE || self.last_flush.elapsed() >= BATCH_INTERVAL {
+            self.flush().await;
+        }
+    }
+
+    async fn flush(&mut self) {
+        self.last_flush = Instant::now();
+        if self.pending.is_empty() {
+            return;
+        }
+        let batch = FileSearchBatch {
+            batch_number: self.sent_batches,
+            matches: std::mem::take(&mut self.pending),
+        };
+        self.sent_batches += 1;
+        if let Ok(pkt) = batch.into_packet(self.req_id) {
+            let _ = self.tx.send(pkt).await;
+        }
+    }
+}
+
+/// Run the search described by `payload`, streaming matches to `tx` and
+/// ending with a [`FileSearchSummary`].
+pub async fn run(tx: ConnectionSender, req_id: u64, payload: Vec<u8>) {
+    let summary = match FileSearchRequest::from_bytes(&payload) {
+        Ok(req) => walk(&tx, req_id, &req).await,
+        Err(e) => FileSearchSummary::failed(format!("Invalid FileSearch payload: {}", e)),
+    };
+    match &summary.error {
+        Some(e) => println!("[ERR ] ReqID {}: {}", req_id, e),
+        None => println!(
+            "[FIND] ReqID {}: {} match(es) in {} director(ies)",
+            req_id, summary.matches, summary.directories_scanned
+        ),
+    }
+    if let Ok(pkt) = summary.into_packet(req_id) {
+        let _ = tx.send(pkt).await;
+    }
+}
+
+async fn walk(tx: &ConnectionSender, req_id: u64, req: &FileSearchRequest) -> FileSearchSummary {
+    let started = Instant::now();
+    let root = PathBuf::from(&req.root);
+    if !tokio::fs::metadata(&root).await.is_ok_and(|m| m.is_dir()) {
+        return FileSearchSummary::failed(format!("{} is not a directory", req.root));
+    }
+
+    let mut batcher = Batcher {
+        tx: tx.clone(),
+        req_id,
+        pending: Vec::new(),
+        sent_batches: 0,
+        last_flush: Instant::now(),
+    };
+    let mut summary = FileSearchSummary::default();
+    let mut stack = vec![(root, 1u32)];
+    'walk: while let Some((dir, depth)) = stack.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        summary.directories_scanned += 1;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_dir = file_type.is_dir();
+            // Links and junctions are reported but never walked into.
+            let metadata = entry.metadata().await.ok();
+            let walkable = is_dir && !metadata.as_ref().is_some_and(is_reparse_point);
+            if walkable && (req.max_depth == 0 || depth < req.max_depth) {
+                stack.push((entry.path(), depth + 1));
+            }
+            if (is_dir && !req.include_dirs) || !req.matches(&name) {
+                continue;
+            }
+            let attributes = metadata
+                .as_ref()
+                .map_or(FileAttributes::empty(), FileAttributes::of);
+            batcher
+                .push(FileMetadata {
+                    name,
+                    path: entry.path().to_string_lossy().into_owned(),
+                    size: if is_dir {
+                        0
+                    } else {
+                        metadata.as_ref().map_or(0, |m| m.len())
+                    },
+                    modified: metadata
+                        .and_then(|m| m.modified().ok())
+                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                        .map_or(0, |d| d.as_secs()),
+                    is_directory: is_dir,
+                    hash: None,
+                    attributes,
+                })
+                .await;
+            summary.matches += 1;
+            if req.max_results > 0 && summary.matches >= u64::from(req.max_results) {
+                summary.truncated = true;
+                break 'walk;
+            }
+        }
+    }
+    batcher.flush().await;
+    summary.elapsed_ms = started.elapsed().as_millis() as u64;
+    summary
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::testing::temp_dir;
+    use tix_core::{Packet, ProtocolFlags, TaskError, TaskEvent, TaskPool};
+    use tokio::sync::mpsc;
+
+    /// `root/a/b/c…` with `per_dir` files named `match-N.log` and
+    /// `other-N.txt` in each of `depth` nested directories.
+    fn tree(name: &str, depth: usize, per_dir: usize) -> PathBuf {
+        let root = temp_dir(&format!("search-{name}"));
+        let mut dir = root.clone();
+        for level in 0..depth {
+            std::fs::create_dir_all(&dir).unwrap();
+            for i in 0..per_dir {
+                std::fs::write(dir.join(format!("match-{}-{}.log", level, i)), b"x").unwrap();
+                std::fs::write(dir.join(format!("other-{}-{}.txt", level, i)), b"").unwrap();
+            }
+            dir = dir.join(format!("logs-{}", level));
+        }
+        root
+    }
+
+    async fn search(req: FileSearchRequest) -> (Vec<FileSearchBatch>, FileSearchSummary) {
+        let (tx, mut rx) = mpsc::channel(1024);
+        run(tx.into(), 3, req.to_bytes().unwrap()).await;
+        let mut batches = Vec::new();
+        while let Ok(pkt) = rx.try_recv() {
+            if pkt.flags().contains(ProtocolFlags::FINAL_FRAGMENT) {
+                return (
+                    batches,
+                    FileSearchSummary::from_bytes(pkt.payload()).unwrap(),
+                );
+            }
+            batches.push(FileSearchBatch::from_bytes(pkt.payload()).unwrap());
+        }
+        panic!("no summary");
+    }
+
+    #[tokio::test]
+    async fn nested_matches_arrive_in_batches() {
+        let root = tree("nested", 4, 50);
+        let req = FileSearchRequest::new(root.display().to_string(), "*.LOG").with_max_results(0);
+        let (batches, summary) = search(req).await;
+
+        assert_eq!(summary.error, None);
+        assert_eq!(summary.matches, 200);
+        assert_eq!(summary.directories_scanned, 4);
+        assert!(!summary.truncated);
+        assert!(
+            batches.len() >= 200 / SEARCH_BATCH_SIZE,
+            "{}",
+            batches.len()
+        );
+        assert!(batches.iter().all(|b| b.matches.len() <= SEARCH_BATCH_SIZE));
+        let numbers: Vec<u64> = batches.iter().map(|b| b.batch_number).collect();
+        assert_eq!(numbers, (0..batches.len() as u64).collect::<Vec<_>>());
+        let found: Vec<&FileMetadata> = batches.iter().flat_map(|b| &b.matches).collect();
+        assert_eq!(found.len(), 200);
+        assert!(
+            found
+                .iter()
+                .all(|m| m.name.ends_with(".log") && m.size == 1)
+        );
+        assert!(found.iter().any(|m| m.path.contains("logs-2")));
+
+        // Depth 2 covers the root and one level down; directories only
+        // show up when asked for.
+        let req = FileSearchRequest::new(root.display().to_string(), "logs")
+            .with_max_depth(2)
+            .with_include_dirs(true);
+        let (batches, summary) = search(req).await;
+        assert_eq!(summary.directories_scanned, 2);
+        let dirs: Vec<&FileMetadata> = batches.iter().flat_map(|b| &b.matches).collect();
+        assert_eq!(dirs.len(), 2);
+        assert!(dirs.iter().all(|m| m.is_directory));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn result_cap_stops_the_walk() {
+        let root = tree("cap", 3, 20);
+        let req = FileSearchRequest::new(root.display().to_string(), "match").with_max_results(25);
+        let (batches, summary) = search(req).await;
+        assert_eq!(summary.matches, 25);
+        assert!(summary.truncated);
+        assert_eq!(batches.iter().map(|b| b.matches.len()).sum::<usize>(), 25);
+
+        let missing = FileSearchRequest::new(root.join("nope").display().to_string(), "x");
+        let (batches, summary) = search(missing).await;
+        assert!(batches.is_empty());
+        assert!(summary.error.unwrap().contains("not a directory"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn cancelling_stops_the_walk_without_a_summary() {
+        let root = tree("cancel", 6, 100);
+        // Room for one batch: the walk blocks on the second until the
+        // test reads, which it only does after cancelling.
+        let (tx, mut rx) = mpsc::channel::<Packet>(1);
+        let mut pool = TaskPool::new();
+        let req = FileSearchRequest::new(root.display().to_string(), "*").with_max_results(0);
+        pool.spawn(tx.into(), 5, req.to_bytes().unwrap(), run)
+            .unwrap();
+
+        let first = rx.recv().await.unwrap();
+        assert!(first.flags().contains(ProtocolFlags::STREAMING));
+        assert!(pool.cancel_task(5));
+        let event = pool.recv().await.unwrap();
+        assert!(matches!(event, TaskEvent::Error(5, TaskError::Cancelled)));
+
+        let mut rest = Vec::new();
+        while let Some(pkt) = rx.recv().await {
+            rest.push(pkt);
+        }
+        assert!(rest.len() <= 1, "walk kept going: {} packets", rest.len());
+        assert!(
+            rest.iter()
+                .all(|p| !p.flags().contains(ProtocolFlags::FINAL_FRAGMENT))
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
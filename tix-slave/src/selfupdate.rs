@@ -0,0 +1,208 @@
+//! Replace the running slave executable with one the master uploaded.
+//!
+//! The master stages the new build with an ordinary `FileWrite` upload
+//! and then sends `UpdateApply`. Windows will not let a running `.exe`
+//! be overwritten or deleted, but it will let it be renamed, so the swap
+//! is: move the current executable to `<name>.old`, move the staged file
+//! into its place, and restart. The `.old` file is removed by the next
+//! process at startup ([`cleanup_previous`]).
+//!
+//! Everything here blocks on the filesystem and is meant for
+//! `tokio::task::spawn_blocking`.
+
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use tix_core::protocol::update::{self, UpdateApplyRequest, UpdateError};
+
+/// Version of this build, compared against the one offered.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Where the running executable is moved during a swap.
+pub fn backup_path(exe: &Path) -> PathBuf {
+    let mut name = exe.as_os_str().to_owned();
+    name.push(".old");
+    PathBuf::from(name)
+}
+
+/// Blake3 hash of a file's contents.
+pub fn hash_file(path: &Path) -> std::io::Result<[u8; 32]> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(*hasher.finalize().as_bytes())
+}
+
+/// Check that `staged` exists and hashes to `expected`.
+pub fn verify_staged(staged: &Path, expected: &[u8; 32]) -> Result<(), UpdateError> {
+    if !staged.is_file() {
+        return Err(UpdateError::StagedMissing(staged.display().to_string()));
+    }
+    let actual = hash_file(staged).map_err(|e| UpdateError::Io(e.to_string()))?;
+    if &actual != expected {
+        return Err(UpdateError::HashMismatch {
+            expected: update::hex(expected),
+            actual: update::hex(&actual),
+        });
+    }
+    Ok(())
+}
+
+/// Put `staged` in place of `target`, keeping the previous file at
+/// [`backup_path`]. On failure `target` is left as it was.
+pub fn swap(staged: &Path, target: &Path) -> Result<(), UpdateError> {
+    let backup = backup_path(target);
+    // A backup left by an earlier update that never restarted.
+    let _ = std::fs::remove_file(&backup);
+
+    std::fs::rename(target, &backup).map_err(|e| UpdateError::Locked(e.to_string()))?;
+
+    // The staging folder may be on another volume, where rename fails;
+    // fall back to a copy.
+    let placed = std::fs::rename(staged, target).or_else(|_| {
+        std::fs::copy(staged, target)?;
+        let _ = std::fs::remove_file(staged);
+        Ok::<(), std::io::Error>(())
+    });
+    if let Err(e) = placed {
+        let _ = std::fs::remove_file(target);
+        return match std::fs::rename(&backup, target) {
+            Ok(()) => Err(UpdateError::Io(e.to_string())),
+            Err(restore) => Err(UpdateError::Io(format!(
+                "{e}; restoring the previous binary also failed: {restore}"
+            ))),
+        };
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(target, std::fs::Permissions::from_mode(0o755));
+    }
+    Ok(())
+}
+
+/// Run every check for `req` and, if they all pass, swap the staged
+/// file over `target`. Checks run cheapest first so a refused update
+/// costs nothing.
+pub fn apply(req: &UpdateApplyRequest, current: &str, target: &Path) -> Result<(), UpdateError> {
+    update::check_version(current, &req.version, req.allow_downgrade)?;
+    let staged = Path::new(&req.staged_path);
+    verify_staged(staged, &req.blake3_hash)?;
+    swap(staged, target)
+}
+
+/// Start `exe` with this process's arguments. The caller exits once
+/// the response to the master has been flushed.
+pub fn spawn_replacement(exe: &Path) -> std::io::Result<()> {
+    let args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    std::process::Command::new(exe).args(args).spawn()?;
+    Ok(())
+}
+
+/// Remove the backup an earlier update left next to `exe`.
+pub fn cleanup_previous(exe: &Path) {
+    let backup = backup_path(exe);
+    if backup.exists() {
+        match std::fs::remove_file(&backup) {
+            Ok(()) => println!("[UPDT] Removed previous binary {}", backup.display()),
+            Err(e) => println!("[WARN] Could not remove {}: {}", backup.display(), e),
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tix_core::testing::temp_dir;
+
+    fn request(staged: &Path, contents: &[u8], version: &str) -> UpdateApplyRequest {
+        UpdateApplyRequest {
+            staged_path: staged.display().to_string(),
+            blake3_hash: *blake3::hash(contents).as_bytes(),
+            version: version.to_string(),
+            allow_downgrade: false,
+            restart: false,
+        }
+    }
+
+    #[test]
+    fn staged_file_replaces_target() {
+        let dir = temp_dir("update-swap");
+        let exe = dir.join("tix-slave.exe");
+        let staged = dir.join("staged.exe");
+        std::fs::write(&exe, b"old build").unwrap();
+        std::fs::write(&staged, b"new build").unwrap();
+
+        apply(&request(&staged, b"new build", "0.2.0"), "0.1.0", &exe).unwrap();
+
+        assert_eq!(std::fs::read(&exe).unwrap(), b"new build");
+        assert_eq!(std::fs::read(backup_path(&exe)).unwrap(), b"old build");
+        assert!(!staged.exists());
+
+        cleanup_previous(&exe);
+        assert!(!backup_path(&exe).exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn hash_mismatch_leaves_target_intact() {
+        let dir = temp_dir("update-hash");
+        let exe = dir.join("tix-slave.exe");
+        let staged = dir.join("staged.exe");
+        std::fs::write(&exe, b"old build").unwrap();
+        std::fs::write(&staged, b"tampered").unwrap();
+
+        let err = apply(&request(&staged, b"new build", "0.2.0"), "0.1.0", &exe).unwrap_err();
+        assert!(matches!(err, UpdateError::HashMismatch { .. }), "{err}");
+        assert_eq!(std::fs::read(&exe).unwrap(), b"old build");
+        assert!(!backup_path(&exe).exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn version_gate_runs_before_anything_else() {
+        let dir = temp_dir("update-version");
+        let exe = dir.join("tix-slave.exe");
+        std::fs::write(&exe, b"old build").unwrap();
+        // The staged file does not even exist: the version check must
+        // refuse first.
+        let staged = dir.join("missing.exe");
+
+        let err = apply(&request(&staged, b"x", "0.1.0"), "0.1.0", &exe).unwrap_err();
+        assert_eq!(err, UpdateError::AlreadyInstalled("0.1.0".into()));
+        let err = apply(&request(&staged, b"x", "0.0.9"), "0.1.0", &exe).unwrap_err();
+        assert!(matches!(err, UpdateError::Downgrade { .. }));
+
+        let mut forced = request(&staged, b"x", "0.0.9");
+        forced.allow_downgrade = true;
+        assert!(matches!(
+            apply(&forced, "0.1.0", &exe),
+            Err(UpdateError::StagedMissing(_))
+        ));
+        assert_eq!(std::fs::read(&exe).unwrap(), b"old build");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn missing_target_is_reported_as_locked() {
+        let dir = temp_dir("update-locked");
+        let staged = dir.join("staged.exe");
+        std::fs::write(&staged, b"new build").unwrap();
+
+        let err = swap(&staged, &dir.join("gone.exe")).unwrap_err();
+        assert!(matches!(err, UpdateError::Locked(_)));
+        assert!(staged.exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
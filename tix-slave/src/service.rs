@@ -0,0 +1,459 @@
+//! Listing Windows services and starting, stopping or restarting them
+//! through the Service Control Manager.
+//!
+//! All functions here block on OS calls, and [`control`] sleeps while it
+//! waits for a service to settle; run them on a blocking thread
+//! (`tokio::task::spawn_blocking`).
+
+use std::time::{Duration, Instant};
+
+use tix_core::protocol::{
+    ServiceControlRequest, ServiceControlResponse, ServiceListRequest, ServiceListResponse,
+    ServiceState,
+};
+
+#[cfg(not(windows))]
+use tix_core::protocol::ServiceErrorKind;
+
+/// How often a control request checks whether the service has settled.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// ── Listing ──────────────────────────────────────────────────────
+
+/// Every Win32 service passing the request's filter, sorted by name.
+#[cfg(windows)]
+pub fn list(req: &ServiceListRequest) -> ServiceListResponse {
+    let manager = match win::Manager::open() {
+        Ok(manager) => manager,
+        Err(e) => return ServiceListResponse::error(win::kind(&e), win::describe(&e)),
+    };
+    let mut services = match manager.services() {
+        Ok(services) => services,
+        Err(e) => return ServiceListResponse::error(win::kind(&e), win::describe(&e)),
+    };
+    services.retain(|service| req.matches(service));
+    // The start type needs each service opened, so only for those kept.
+    for service in &mut services {
+        service.start_type = manager
+            .service(&service.name, win::QUERY)
+            .and_then(|handle| handle.config())
+            .ok()
+            .map(|(_, start_type)| start_type);
+    }
+    services.sort_by_key(|service| service.name.to_lowercase());
+    ServiceListResponse::Services(services)
+}
+
+/// Every Win32 service passing the request's filter, sorted by name.
+#[cfg(not(windows))]
+pub fn list(_req: &ServiceListRequest) -> ServiceListResponse {
+    ServiceListResponse::error(
+        ServiceErrorKind::Unsupported,
+        "services are only available on Windows",
+    )
+}
+
+// ── Control ──────────────────────────────────────────────────────
+
+/// Apply the request's action and wait, up to its timeout, for the
+/// service to settle. A restart stops the service first, unless it is
+/// already stopped, and shares the one timeout between both waits.
+#[cfg(windows)]
+pub fn control(req: &ServiceControlRequest) -> ServiceControlResponse {
+    use tix_core::protocol::{ServiceAction, ServiceErrorKind};
+
+    let name = req.name.as_str();
+    let service = match win::Manager::open().and_then(|m| m.service(name, win::CONTROL)) {
+        Ok(service) => service,
+        Err(e) => return win::control_error(name, &e, None),
+    };
+    let deadline = Instant::now() + req.timeout();
+    let settle = |target| match wait_for(target, deadline, || service.state()) {
+        Ok(Settled::Reached) => None,
+        Ok(Settled::TimedOut(now)) => Some(ServiceControlResponse::error(
+            ServiceErrorKind::Timeout,
+            format!("{} still {} after {} s", name, now, req.timeout().as_secs()),
+            Some(now),
+        )),
+        Ok(Settled::Elsewhere(now)) => Some(ServiceControlResponse::error(
+            ServiceErrorKind::Other,
+            format!("{} is {} instead of {}", name, now, target),
+            Some(now),
+        )),
+        Err(e) => Some(win::control_error(name, &e, None)),
+    };
+
+    if req.action.interrupts() {
+        if let Err(e) = service.stop()
+            && !win::is_not_active(&e)
+        {
+            return win::control_error(name, &e, service.state().ok());
+        }
+        if let Some(failure) = settle(ServiceState::Stopped) {
+            return failure;
+        }
+    }
+    if req.action != ServiceAction::Stop {
+        if let Err(e) = service.start()
+            && !win::is_already_running(&e)
+        {
+            return win::control_error(name, &e, service.state().ok());
+        }
+        if let Some(failure) = settle(ServiceState::Running) {
+            return failure;
+        }
+    }
+
+    match service.info(name) {
+        Ok(info) => ServiceControlResponse::Done {
+            action: req.action,
+            service: info,
+        },
+        Err(e) => win::control_error(name, &e, None),
+    }
+}
+
+/// Apply the request's action and wait, up to its timeout, for the
+/// service to settle.
+#[cfg(not(windows))]
+pub fn control(req: &ServiceControlRequest) -> ServiceControlResponse {
+    ServiceControlResponse::error(
+        ServiceErrorKind::Unsupported,
+        format!("{}: services are only available on Windows", req.name),
+        None,
+    )
+}
+
+/// How a wait for a service state ended.
+#[cfg_attr(not(windows), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Settled {
+    Reached,
+    /// Still on its way when the deadline passed.
+    TimedOut(ServiceState),
+    /// Came to rest in another state, e.g. stopped again right after
+    /// starting.
+    Elsewhere(ServiceState),
+}
+
+/// Poll `state` until it reports `target`, `deadline` passes, or the
+/// service rests in some other state after the first check.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn wait_for<E>(
+    target: ServiceState,
+    deadline: Instant,
+    mut state: impl FnMut() -> Result<ServiceState, E>,
+) -> Result<Settled, E> {
+    let mut first = true;
+    loop {
+        let now = state()?;
+        if now == target {
+            return Ok(Settled::Reached);
+        }
+        // Right after a start or stop the SCM may not have moved yet.
+        if !first && !now.is_pending() {
+            return Ok(Settled::Elsewhere(now));
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(Settled::TimedOut(now));
+        }
+        first = false;
+        std::thread::sleep(POLL_INTERVAL.min(left));
+    }
+}
+
+// ── Windows SCM access ───────────────────────────────────────────
+
+#[cfg(windows)]
+mod win {
+    use tix_core::protocol::{
+        ServiceControlResponse, ServiceErrorKind, ServiceInfo, ServiceStartType, ServiceState,
+    };
+    use windows::Win32::Foundation::{
+        ERROR_ACCESS_DENIED, ERROR_INSUFFICIENT_BUFFER, ERROR_MORE_DATA,
+        ERROR_SERVICE_ALREADY_RUNNING, ERROR_SERVICE_DOES_NOT_EXIST, ERROR_SERVICE_NOT_ACTIVE,
+        WIN32_ERROR,
+    };
+    use windows::Win32::System::Services::{
+        CloseServiceHandle, ControlService, ENUM_SERVICE_STATUS_PROCESSW, EnumServicesStatusExW,
+        OpenSCManagerW, OpenServiceW, QUERY_SERVICE_CONFIGW, QueryServiceConfigW,
+        QueryServiceStatus, SC_ENUM_PROCESS_INFO, SC_HANDLE, SC_MANAGER_CONNECT,
+        SC_MANAGER_ENUMERATE_SERVICE, SERVICE_CONTROL_STOP, SERVICE_QUERY_CONFIG,
+        SERVICE_QUERY_STATUS, SERVICE_START, SERVICE_STATE_ALL, SERVICE_STATUS, SERVICE_STOP,
+        SERVICE_WIN32, StartServiceW,
+    };
+    use windows::core::{Error, PCWSTR};
+
+    /// Rights to read a service's state and configuration.
+    pub const QUERY: u32 = SERVICE_QUERY_STATUS | SERVICE_QUERY_CONFIG;
+    /// Rights to also start and stop it.
+    pub const CONTROL: u32 = QUERY | SERVICE_START | SERVICE_STOP;
+
+    fn code(e: &Error) -> Option<WIN32_ERROR> {
+        WIN32_ERROR::from_error(e)
+    }
+
+    pub fn kind(e: &Error) -> ServiceErrorKind {
+        match code(e) {
+            Some(ERROR_ACCESS_DENIED) => ServiceErrorKind::AccessDenied,
+            Some(ERROR_SERVICE_DOES_NOT_EXIST) => ServiceErrorKind::NotFound,
+            _ => ServiceErrorKind::Other,
+        }
+    }
+
+    pub fn describe(e: &Error) -> String {
+        e.message().trim_end().to_string()
+    }
+
+    pub fn is_not_active(e: &Error) -> bool {
+        code(e) == Some(ERROR_SERVICE_NOT_ACTIVE)
+    }
+
+    pub fn is_already_running(e: &Error) -> bool {
+        code(e) == Some(ERROR_SERVICE_ALREADY_RUNNING)
+    }
+
+    pub fn control_error(
+        name: &str,
+        e: &Error,
+        state: Option<ServiceState>,
+    ) -> ServiceControlResponse {
+        ServiceControlResponse::error(kind(e), format!("{}: {}", name, describe(e)), state)
+    }
+
+    /// NUL-terminated UTF-16 copy of `s`.
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// A zeroed buffer of at least `bytes`, aligned for the structures
+    /// the SCM writes into it.
+    fn buffer(bytes: usize) -> Vec<u64> {
+        vec![0; bytes.div_ceil(8)]
+    }
+
+    fn as_bytes(buf: &mut [u64]) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr().cast(), buf.len() * 8) }
+    }
+
+    /// An SCM or service handle, closed on drop.
+    pub struct Handle(SC_HANDLE);
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            unsafe {
+                let _ = CloseServiceHandle(self.0);
+            }
+        }
+    }
+
+    /// The local Service Control Manager.
+    pub struct Manager(Handle);
+
+    impl Manager {
+        pub fn open() -> Result<Self, Error> {
+            let access = SC_MANAGER_CONNECT | SC_MANAGER_ENUMERATE_SERVICE;
+            let handle = unsafe { OpenSCManagerW(PCWSTR::null(), PCWSTR::null(), access)? };
+            Ok(Self(Handle(handle)))
+        }
+
+        /// Open service `name` with `access` rights.
+        pub fn service(&self, name: &str, access: u32) -> Result<Handle, Error> {
+            let name = wide(name);
+            let handle = unsafe { OpenServiceW(self.0.0, PCWSTR(name.as_ptr()), access)? };
+            Ok(Handle(handle))
+        }
+
+        /// Every Win32 service, without its start type.
+        pub fn services(&self) -> Result<Vec<ServiceInfo>, Error> {
+            let mut services = Vec::new();
+            let mut buf = buffer(64 * 1024);
+            let mut resume = 0u32;
+            loop {
+                let (mut needed, mut count) = (0u32, 0u32);
+                let result = unsafe {
+                    EnumServicesStatusExW(
+                        self.0.0,
+                        SC_ENUM_PROCESS_INFO,
+                        SERVICE_WIN32,
+                        SERVICE_STATE_ALL,
+                        Some(as_bytes(&mut buf)),
+                        &mut needed,
+                        &mut count,
+                        Some(&mut resume),
+                        PCWSTR::null(),
+                    )
+                };
+                let entries = unsafe {
+                    std::slice::from_raw_parts(
+                        buf.as_ptr().cast::<ENUM_SERVICE_STATUS_PROCESSW>(),
+                        count as usize,
+                    )
+                };
+                for entry in entries {
+                    services.push(ServiceInfo {
+                        name: unsafe { entry.lpServiceName.to_string() }.unwrap_or_default(),
+                        display_name: unsafe { entry.lpDisplayName.to_string() }
+                            .unwrap_or_default(),
+                        state: ServiceState::from_raw(entry.ServiceStatusProcess.dwCurrentState.0),
+                        start_type: None,
+                    });
+                }
+                match result {
+                    Ok(()) => return Ok(services),
+                    // The resume handle picks up after what was returned.
+                    Err(e) if code(&e) == Some(ERROR_MORE_DATA) => {
+                        if needed as usize > buf.len() * 8 {
+                            buf = buffer(needed as usize);
+                        }
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+
+    impl Handle {
+        pub fn state(&self) -> Result<ServiceState, Error> {
+            let mut status = SERVICE_STATUS::default();
+            unsafe { QueryServiceStatus(self.0, &mut status)? };
+            Ok(ServiceState::from_raw(status.dwCurrentState.0))
+        }
+
+        /// Display name and start type.
+        pub fn config(&self) -> Result<(String, ServiceStartType), Error> {
+            let mut needed = 0u32;
+            match unsafe { QueryServiceConfigW(self.0, None, 0, &mut needed) } {
+                Err(e) if code(&e) == Some(ERROR_INSUFFICIENT_BUFFER) => {}
+                Err(e) => return Err(e),
+                Ok(()) => {}
+            }
+            let mut buf = buffer(needed as usize);
+            let config = buf.as_mut_ptr().cast::<QUERY_SERVICE_CONFIGW>();
+            unsafe { QueryServiceConfigW(self.0, Some(config), needed, &mut needed)? };
+            let config = unsafe { &*config };
+            let display_name = unsafe { config.lpDisplayName.to_string() }.unwrap_or_default();
+            Ok((
+                display_name,
+                ServiceStartType::from_raw(config.dwStartType.0),
+            ))
+        }
+
+        /// The service as it is now, called `name`.
+        pub fn info(&self, name: &str) -> Result<ServiceInfo, Error> {
+            let (display_name, start_type) = self.config()?;
+            Ok(ServiceInfo {
+                name: name.to_string(),
+                display_name,
+                state: self.state()?,
+                start_type: Some(start_type),
+            })
+        }
+
+        pub fn start(&self) -> Result<(), Error> {
+            unsafe { StartServiceW(self.0, None) }
+        }
+
+        pub fn stop(&self) -> Result<(), Error> {
+            let mut status = SERVICE_STATUS::default();
+            unsafe { ControlService(self.0, SERVICE_CONTROL_STOP, &mut status) }
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A service reporting `states` in turn, then the last one forever.
+    fn scripted(states: &[ServiceState]) -> impl FnMut() -> Result<ServiceState, ()> + '_ {
+        let mut next = 0;
+        move || {
+            let state = states[next.min(states.len() - 1)];
+            next += 1;
+            Ok(state)
+        }
+    }
+
+    #[test]
+    fn waits_through_pending_states() {
+        use ServiceState::*;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let states = [Stopped, StartPending, StartPending, Running];
+        assert_eq!(
+            wait_for(Running, deadline, scripted(&states)),
+            Ok(Settled::Reached)
+        );
+    }
+
+    #[test]
+    fn a_service_that_falls_back_is_reported_at_once() {
+        use ServiceState::*;
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let start = Instant::now();
+        let states = [StartPending, Stopped];
+        assert_eq!(
+            wait_for(Running, deadline, scripted(&states)),
+            Ok(Settled::Elsewhere(Stopped))
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn the_deadline_ends_the_wait() {
+        let deadline = Instant::now() + Duration::from_millis(300);
+        let states = [ServiceState::StopPending];
+        assert_eq!(
+            wait_for(ServiceState::Stopped, deadline, scripted(&states)),
+            Ok(Settled::TimedOut(ServiceState::StopPending))
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn lists_well_known_services() {
+        let ServiceListResponse::Services(services) = list(&ServiceListRequest::default()) else {
+            panic!("listing failed");
+        };
+        let rpc = services
+            .iter()
+            .find(|s| s.name.eq_ignore_ascii_case("RpcSs"))
+            .expect("RpcSs is always installed");
+        assert_eq!(rpc.state, ServiceState::Running);
+        assert!(rpc.start_type.is_some());
+
+        let filtered = list(&ServiceListRequest::new(Some("rpcss".into())));
+        assert!(matches!(filtered, ServiceListResponse::Services(s) if s.len() == 1));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn controlling_a_missing_service_is_not_found() {
+        let req = ServiceControlRequest::new(
+            "tix-does-not-exist",
+            tix_core::protocol::ServiceAction::Start,
+        );
+        assert!(matches!(
+            control(&req),
+            ServiceControlResponse::Error {
+                kind: tix_core::protocol::ServiceErrorKind::NotFound,
+                ..
+            }
+        ));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn other_platforms_have_no_services() {
+        assert!(matches!(
+            list(&ServiceListRequest::default()),
+            ServiceListResponse::Error {
+                kind: ServiceErrorKind::Unsupported,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,296 @@
+//! Which program runs a `ShellExecute`, and with which arguments.
+//!
+//! A [`ShellKind`] resolves to a [`ShellInvocation`]: the system shell
+//! (`cmd /s /c` on Windows, `sh -c` elsewhere), Windows PowerShell or
+//! PowerShell 7 (`-NoProfile -NonInteractive -Command`), or a custom
+//! shell from the `[shell]` allow-list in `tix-slave.toml` (see
+//! [`ShellConfig`]). A custom name missing from the list is refused.
+//!
+//! On Windows the process gets a single command line rather than an
+//! argument vector, so the invocation renders its own
+//! ([`ShellInvocation::command_line`]): arguments are quoted the way the
+//! C runtime splits them, which is what PowerShell and most programs
+//! read, except `cmd`, which takes everything after `/c` as typed.
+//! Leaving that to the standard library escaped embedded quotes as `\"`,
+//! which `cmd` does not understand.
+
+use tix_core::protocol::ShellKind;
+
+use crate::config::ShellConfig;
+
+/// Arguments PowerShell gets before the command.
+const POWERSHELL_ARGS: [&str; 3] = ["-NoProfile", "-NonInteractive", "-Command"];
+
+/// A program and its arguments, the command being the last of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellInvocation {
+    /// The interpreter, looked up on `PATH` unless it is a path.
+    pub program: String,
+    /// Its arguments, ending with the command.
+    pub args: Vec<String>,
+    /// The command is `cmd`'s: on Windows it goes on the command line
+    /// inside one pair of quotes, untouched.
+    verbatim: bool,
+}
+
+impl ShellInvocation {
+    /// How `command` runs in `kind` on this platform.
+    pub fn resolve(kind: &ShellKind, command: &str, shells: &ShellConfig) -> Result<Self, String> {
+        Self::resolve_for(kind, command, shells, cfg!(windows))
+    }
+
+    /// How `command` runs in `kind` on Windows (`windows`) or elsewhere.
+    pub fn resolve_for(
+        kind: &ShellKind,
+        command: &str,
+        shells: &ShellConfig,
+        windows: bool,
+    ) -> Result<Self, String> {
+        let (program, mut args, verbatim) = match kind {
+            ShellKind::Cmd if windows => ("cmd".to_string(), vec!["/s", "/c"], true),
+            ShellKind::Cmd => ("sh".to_string(), vec!["-c"], false),
+            ShellKind::PowerShell => ("powershell".to_string(), POWERSHELL_ARGS.to_vec(), false),
+            ShellKind::PwshCore => ("pwsh".to_string(), POWERSHELL_ARGS.to_vec(), false),
+            ShellKind::Custom(name) => {
+                let Some((program, args)) =
+                    shells.custom.get(name).and_then(|argv| argv.split_first())
+                else {
+                    return Err(format!(
+                        "shell `{}` is not allowed by this slave's [shell] config",
+                        name
+                    ));
+                };
+                let invocation = Self {
+                    program: program.clone(),
+                    args: args.iter().cloned().chain([command.to_string()]).collect(),
+                    verbatim: false,
+                };
+                return Ok(invocation);
+            }
+        };
+        args.push(command);
+        Ok(Self {
+            program,
+            args: args.into_iter().map(str::to_string).collect(),
+            verbatim,
+        })
+    }
+
+    /// The interpreter without the command, for logs and the exit
+    /// status, e.g. `powershell -NoProfile -NonInteractive -Command`.
+    pub fn describe(&self) -> String {
+        let fixed = &self.args[..self.args.len() - 1];
+        std::iter::once(self.program.as_str())
+            .chain(fixed.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The Windows command line: the program, then [`Self::arguments`].
+    pub fn command_line(&self) -> String {
+        let mut line = String::new();
+        quote_arg(&self.program, &mut line);
+        line.push(' ');
+        line.push_str(&self.arguments());
+        line
+    }
+
+    /// The arguments as they follow the program on a Windows command
+    /// line.
+    pub fn arguments(&self) -> String {
+        let mut line = String::new();
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                line.push(' ');
+            }
+            if self.verbatim && i == self.args.len() - 1 {
+                line.push('"');
+                line.push_str(arg);
+                line.push('"');
+            } else {
+                quote_arg(arg, &mut line);
+            }
+        }
+        line
+    }
+
+    /// A process for this invocation, arguments not set apart from the
+    /// command line on Windows.
+    pub fn to_command(&self) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&self.program);
+        #[cfg(windows)]
+        command.raw_arg(self.arguments());
+        #[cfg(not(windows))]
+        command.args(&self.args);
+        command
+    }
+}
+
+/// Append `arg` to `line` so the C runtime's argument splitting gives it
+/// back: quoted when it is empty or holds whitespace or a quote, quotes
+/// escaped, and backslashes doubled where they precede one.
+fn quote_arg(arg: &str, line: &mut String) {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '\u{b}', '"']) {
+        line.push_str(arg);
+        return;
+    }
+    line.push('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                line.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                line.extend(std::iter::repeat_n('\\', backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            line.push(c);
+        }
+    }
+    line.extend(std::iter::repeat_n('\\', backslashes * 2));
+    line.push('"');
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shells() -> ShellConfig {
+        let mut shells = ShellConfig::default();
+        shells
+            .custom
+            .insert("bash".to_string(), vec!["/bin/bash".into(), "-c".into()]);
+        shells
+    }
+
+    #[test]
+    fn argv_and_command_line_per_backend() {
+        let cmd = ShellKind::Cmd;
+        let ps = ShellKind::PowerShell;
+        let pwsh = ShellKind::PwshCore;
+        let bash = ShellKind::Custom("bash".into());
+        // (command, shell, Windows?, argv after the program, Windows command line)
+        let cases: &[(&str, &ShellKind, bool, &[&str], &str)] = &[
+            (
+                r#"echo "a b" | findstr a"#,
+                &cmd,
+                true,
+                &["/s", "/c", r#"echo "a b" | findstr a"#],
+                r#"cmd /s /c "echo "a b" | findstr a""#,
+            ),
+            (
+                r#"echo "$HOME" `date` | wc -c"#,
+                &cmd,
+                false,
+                &["-c", r#"echo "$HOME" `date` | wc -c"#],
+                r#"sh -c "echo \"$HOME\" `date` | wc -c""#,
+            ),
+            (
+                r#"Write-Output "it's $env:USERNAME" | Out-String"#,
+                &ps,
+                true,
+                &[
+                    "-NoProfile",
+                    "-NonInteractive",
+                    "-Command",
+                    r#"Write-Output "it's $env:USERNAME" | Out-String"#,
+                ],
+                r#"powershell -NoProfile -NonInteractive -Command "Write-Output \"it's $env:USERNAME\" | Out-String""#,
+            ),
+            (
+                r#"Write-Output "tab`there" "C:\dir\\""#,
+                &pwsh,
+                false,
+                &[
+                    "-NoProfile",
+                    "-NonInteractive",
+                    "-Command",
+                    r#"Write-Output "tab`there" "C:\dir\\""#,
+                ],
+                r#"pwsh -NoProfile -NonInteractive -Command "Write-Output \"tab`there\" \"C:\dir\\\\\"""#,
+            ),
+            (
+                r#"echo $((1+2)) "`x`" | tr 3 4"#,
+                &bash,
+                false,
+                &["-c", r#"echo $((1+2)) "`x`" | tr 3 4"#],
+                r#"/bin/bash -c "echo $((1+2)) \"`x`\" | tr 3 4""#,
+            ),
+            (
+                "",
+                &pwsh,
+                true,
+                &["-NoProfile", "-NonInteractive", "-Command", ""],
+                r#"pwsh -NoProfile -NonInteractive -Command """#,
+            ),
+        ];
+        for &(command, kind, windows, argv, line) in cases {
+            let invocation = ShellInvocation::resolve_for(kind, command, &shells(), windows)
+                .unwrap_or_else(|e| panic!("{kind}: {e}"));
+            assert_eq!(invocation.args, argv, "{kind} {command}");
+            assert_eq!(invocation.command_line(), line, "{kind} {command}");
+        }
+    }
+
+    #[test]
+    fn describes_the_interpreter_without_the_command() {
+        let resolve = |kind, windows| {
+            ShellInvocation::resolve_for(&kind, "dir", &shells(), windows)
+                .unwrap()
+                .describe()
+        };
+        assert_eq!(resolve(ShellKind::Cmd, true), "cmd /s /c");
+        assert_eq!(resolve(ShellKind::Cmd, false), "sh -c");
+        assert_eq!(
+            resolve(ShellKind::PowerShell, true),
+            "powershell -NoProfile -NonInteractive -Command"
+        );
+        assert_eq!(
+            resolve(ShellKind::Custom("bash".into()), true),
+            "/bin/bash -c"
+        );
+    }
+
+    #[test]
+    fn custom_shells_must_be_allowed() {
+        let err = ShellInvocation::resolve_for(
+            &ShellKind::Custom("zsh".into()),
+            "echo hi",
+            &shells(),
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "shell `zsh` is not allowed by this slave's [shell] config"
+        );
+    }
+
+    /// Runs a trivial expression in whichever PowerShell is installed;
+    /// passes quietly where there is none.
+    #[tokio::test]
+    async fn runs_a_powershell_expression() {
+        for kind in [ShellKind::PwshCore, ShellKind::PowerShell] {
+            let invocation = ShellInvocation::resolve(
+                &kind,
+                r#"Write-Output ("a" + 'b' + "$(1+2)")"#,
+                &shells(),
+            )
+            .unwrap();
+            let Ok(output) = invocation.to_command().output().await else {
+                continue;
+            };
+            assert!(output.status.success(), "{:?}", output);
+            assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "ab3");
+            return;
+        }
+        eprintln!("no PowerShell installed; skipped");
+    }
+}
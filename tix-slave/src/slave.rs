@@ -0,0 +1,2983 @@
+//! The slave's connection to its master: [`TixSlave`] answers every
+//! command on one connection, and [`run_with_reconnect`] keeps a slave
+//! connected across drops.
+
+use crate::audit::Audit;
+use crate::config::{ShellConfig, SlaveConfig};
+use crate::limits::{BudgetChange, SessionBudget};
+use crate::locks::PathLocks;
+use crate::schedule::Scheduler;
+use crate::screen::ScreenSession;
+use crate::shell::ShellInvocation;
+use crate::{
+    eventlog, netdiag, probe, registry, sas, search, selfupdate, service, transaction, trash,
+    upload,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tix_core::crash;
+use tix_core::protocol::update::{HelloInfo, UpdateApplyRequest, UpdateApplyResponse, UpdateError};
+use tix_core::protocol::{
+    CheckResult, ChunkCompressor, CopyRequest, DeleteOutcome, DeletedItem, DeltaSyncRequest,
+    EventLogQueryRequest, FileAttributes, FileDeleteRequest, FileDeleteResponse, FileDigest,
+    FileHashRequest, FileHashResponse, FileSearchRequest, FileTransactionRequest,
+    FileTransactionResponse, KeyEvent, LockAccess, MouseEvent, NetDiagErrorKind, NetDiagRequest,
+    NetDiagResponse, RegistryErrorKind, RegistryQueryRequest, RegistryQueryResponse, SasRefusal,
+    SasResponse, ScheduleResponse, ScreenDictionaryRequest, ScreenDictionaryResponse,
+    ScreenModeRequest, ScreenModeResponse, ScreenPreflightRequest, ScreenSharingRequest,
+    ScreenStartRequest, ScreenStartResponse, ScreenStopRequest, SelfTestReport,
+    ServiceControlRequest, ServiceControlResponse, ServiceErrorKind, ServiceListRequest,
+    ServiceListResponse, SessionStats, ShellExecuteRequest, ShellExitStatus, ShellOutputChunk,
+    StartupListResponse, SystemInfoResponse, TaskFailure, TaskListResponse, TimedKeyEvent,
+    TimedMouseEvent, TrashRestoreRequest, TrashRestoreResponse, WireTapResponse, WireTapSettings,
+    is_reparse_point,
+};
+use tix_core::rdp::TrafficCounter;
+use tix_core::rdp::burst::{InputBurst, TimedInput};
+use tix_core::rdp::dictionary::FrameDictionary;
+use tix_core::rdp::doctor::{self, DoctorOptions};
+use tix_core::{
+    Command, Connection, ConnectionInfo, ConnectionSender, ConnectionStats, Liveness, Packet,
+    PeerCapabilities, ProtocolFlags, SlaveState, TaskError, TaskEvent, TaskOptions, TaskPool,
+    TixError, WireVersion,
+};
+use tokio::sync::mpsc;
+
+// ── Constants ────────────────────────────────────────────────────
+
+/// Base delay between reconnection attempts.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+/// Maximum delay between reconnection attempts.
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+/// Maximum number of consecutive reconnection attempts before giving up.
+const MAX_RECONNECT_ATTEMPTS: u32 = 50;
+/// How often traffic is checked against `limits.max_bytes_per_hour`.
+const BUDGET_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How often running tasks are checked against `tasks.slow_after_secs`.
+const SLOW_TASK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// How long an accepted update waits for its response to reach the
+/// master before the process restarts.
+const UPDATE_RESTART_DELAY: Duration = Duration::from_millis(500);
+
+// ── Helpers ──────────────────────────────────────────────────────
+
+/// Copy a file or directory robustly, with validation.
+///
+/// Existing files at the destination are only replaced when
+/// `req.overwrite` is set, and directories are only copied when
+/// `req.recursive` is. The result line reports the bytes copied.
+async fn perform_robust_copy(req: &CopyRequest) -> Result<String, String> {
+    let src = req.src.as_str();
+    let dest = req.dest.as_str();
+    let src_path = Path::new(src);
+    let mut dest_path = Path::new(dest).to_path_buf();
+
+    if !src_path.exists() {
+        return Err(format!("Source path '{}' does not exist", src));
+    }
+
+    if let Ok(abs_src) = std::fs::canonicalize(src_path)
+        && let Ok(abs_dest) = std::fs::canonicalize(&dest_path)
+        && abs_src == abs_dest
+    {
+        return Err("Source and destination are the same location".to_string());
+    }
+
+    if dest_path.is_dir()
+        && let Some(file_name) = src_path.file_name()
+    {
+        dest_path.push(file_name);
+    }
+
+    if src_path.is_dir() {
+        if !req.recursive {
+            return Err(format!("'{}' is a directory; use --recursive", src));
+        }
+        // Create the target ourselves and copy only the contents into it,
+        // so an empty directory (or one holding only empty directories)
+        // still comes out as a directory.
+        std::fs::create_dir_all(&dest_path)
+            .map_err(|e| format!("Directory copy failed: {}: {}", dest_path.display(), e))?;
+        match copy_tree(src_path, &dest_path, req.overwrite) {
+            Ok((bytes, 0)) => Ok(format!(
+                "Directory '{}' copied to '{}' ({} bytes)",
+                src,
+                dest_path.display(),
+                bytes
+            )),
+            Ok((bytes, skipped)) => Ok(format!(
+                "Directory '{}' copied to '{}' ({} bytes, {} linked folder(s) skipped)",
+                src,
+                dest_path.display(),
+                bytes,
+                skipped
+            )),
+            Err(e) => Err(format!("Directory copy failed: {}", e)),
+        }
+    } else {
+        if dest_path.exists() && !req.overwrite {
+            return Err(format!(
+                "'{}' already exists; use --overwrite",
+                dest_path.display()
+            ));
+        }
+        match std::fs::copy(src_path, &dest_path) {
+            Ok(bytes) => Ok(format!(
+                "File '{}' copied to '{}' ({} bytes)",
+                src,
+                dest_path.display(),
+                bytes
+            )),
+            Err(e) => Err(format!("File copy failed: {}", e)),
+        }
+    }
+}
+
+/// Copy the contents of `src` into the directory `dest`, returning the
+/// bytes copied and the number of directory links left out. Junctions
+/// and directory symlinks are not followed, since one pointing back up
+/// the tree would never finish; file links are copied as files.
+pub(crate) fn copy_tree(src: &Path, dest: &Path, overwrite: bool) -> std::io::Result<(u64, u64)> {
+    let (mut bytes, mut skipped) = (0, 0);
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        let (from, to) = (entry.path(), dest.join(entry.file_name()));
+        if is_reparse_point(&meta) && from.is_dir() {
+            println!("[SKIP] Not following linked folder {}", from.display());
+            skipped += 1;
+        } else if meta.is_dir() {
+            std::fs::create_dir_all(&to)?;
+            let (b, s) = copy_tree(&from, &to, overwrite)?;
+            bytes += b;
+            skipped += s;
+        } else if !overwrite && to.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("'{}' already exists; use --overwrite", to.display()),
+            ));
+        } else {
+            bytes += std::fs::copy(&from, &to)?;
+        }
+    }
+    Ok((bytes, skipped))
+}
+
+/// [`perform_robust_copy`] holding a shared lock on `src` and an
+/// exclusive one on `dest`.
+async fn locked_copy(locks: &PathLocks, req_id: u64, req: &CopyRequest) -> Result<String, String> {
+    let paths = [
+        (Path::new(&req.src), LockAccess::Shared),
+        (Path::new(&req.dest), LockAccess::Exclusive),
+    ];
+    let _guard = locks.acquire(req_id, &paths).await.map_err(|busy| {
+        println!("[BUSY] ReqID {}: {}", req_id, busy);
+        busy.to_string()
+    })?;
+    perform_robust_copy(req).await
+}
+
+/// Read a whole file under a read lock on its path.
+async fn read_locked(locks: &PathLocks, req_id: u64, path: &str) -> Result<Vec<u8>, String> {
+    let _guard = locks
+        .read(req_id, Path::new(path))
+        .await
+        .map_err(|busy| busy.to_string())?;
+    tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Largest piece of shell output sent in one `ShellOutputChunk`.
+const SHELL_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Code page console programs write their output in, for the master to
+/// decode anything that is not UTF-8.
+#[cfg(windows)]
+fn output_codepage() -> u32 {
+    unsafe { windows::Win32::Globalization::GetOEMCP() }
+}
+
+/// Code page console programs write their output in, for the master to
+/// decode anything that is not UTF-8.
+#[cfg(not(windows))]
+fn output_codepage() -> u32 {
+    // CP_UTF8
+    65001
+}
+
+/// Read a `ShellExecute` payload: a [`ShellExecuteRequest`], or the bare
+/// command line older masters send, which sets no deadline of its own.
+fn parse_shell_payload(payload: &[u8]) -> ShellExecuteRequest {
+    ShellExecuteRequest::from_bytes(payload).unwrap_or_else(|_| {
+        ShellExecuteRequest::new(String::from_utf8_lossy(payload)).with_timeout(0)
+    })
+}
+
+/// Read a `Copy` payload: a [`CopyRequest`], or the `<src> <dest>` text
+/// older masters send. Legacy requests keep their old behaviour of
+/// copying directories and replacing existing files.
+fn parse_copy_payload(payload: &[u8]) -> Option<CopyRequest> {
+    if let Ok(req) = CopyRequest::from_bytes(payload) {
+        return Some(req);
+    }
+    let text = String::from_utf8_lossy(payload);
+    let (src, dest) = text.split_once(' ')?;
+    Some(
+        CopyRequest::new(src.trim_matches('"'), dest.trim_matches('"'))
+            .with_overwrite(true)
+            .with_recursive(true),
+    )
+}
+
+// ── TixSlave ─────────────────────────────────────────────────────
+
+pub struct TixSlave {
+    /// The slave connection.
+    conn: Connection,
+    /// State tracking (phase, capabilities, active tasks).
+    state: SlaveState,
+    /// Task pool for spawning concurrent work.
+    task_pool: TaskPool,
+    /// Active screen-sharing sessions by session ID, one per monitor.
+    screens: BTreeMap<u8, ScreenSession>,
+    /// Cap on `screens`.
+    max_screens: usize,
+    /// Control connection byte counters.
+    conn_stats: Arc<ConnectionStats>,
+    /// Screen stream byte counters, shared by every session.
+    screen_traffic: TrafficCounter,
+    /// Hourly traffic cap.
+    budget: SessionBudget,
+    /// `FileWrite` uploads by request ID, each the way into the task
+    /// receiving it. An entry lasts until the upload's closing packet.
+    uploads: HashMap<u64, mpsc::Sender<Packet>>,
+    /// Per-path locks shared by every file-touching task.
+    path_locks: PathLocks,
+    /// Tasks running longer than this are reported to the master.
+    slow_after: Option<Duration>,
+    /// Tasks already reported as slow.
+    slow_reported: HashSet<u64>,
+    /// Deadline given to every pooled task.
+    task_timeout: Option<Duration>,
+    /// Command each pooled task is serving, for its error response.
+    task_commands: HashMap<u64, Command>,
+    /// Stable installation ID presented in the `Hello`.
+    slave_id: String,
+    /// Log of privileged commands.
+    audit: Audit,
+    /// `screen.allow_sas`: a viewer in control may raise Ctrl+Alt+Del.
+    allow_sas: bool,
+    /// Dictionary offered to viewers that accept one.
+    screen_dictionary: Option<FrameDictionary>,
+    /// The slave's scheduled commands; they outlive the connection.
+    scheduler: Option<Scheduler>,
+    /// `transfer.compression_level`; only used once the master's `Hello`
+    /// says it takes compressed chunks.
+    compressor: ChunkCompressor,
+    /// What a `SelfTest` checks.
+    self_test: DoctorOptions,
+    /// Stamped input received but not injected yet.
+    input_burst: InputBurst,
+    /// Custom shells a `ShellExecute` may name.
+    shells: ShellConfig,
+}
+
+impl TixSlave {
+    /// Connect to the master at the given address.
+    pub async fn connect(
+        conn_info: &ConnectionInfo,
+        config: &SlaveConfig,
+    ) -> Result<Self, TixError> {
+        let conn = if config.tls.enabled {
+            Connection::connect_tls(conn_info, &config.tls.connector()?).await?
+        } else {
+            Connection::connect(conn_info).await?
+        };
+        if let Err(e) = conn.wire_tap().apply(&config.wire.settings()) {
+            println!("[WARN] wire.capture: {}", e);
+        }
+        let conn_stats = conn.stats();
+        let mut state = SlaveState::new();
+        let compressor = config.transfer.compressor();
+        let features = probe::features(config);
+        println!("[CONN] Optional features: {}", features);
+        state.set_local_capabilities(PeerCapabilities {
+            compression: compressor.is_enabled(),
+            features: Some(features),
+            ..PeerCapabilities::default()
+        });
+        // Advance through the connection phases
+        let _ = state.phase_mut().begin_connect();
+        let _ = state.phase_mut().begin_handshake();
+        let _ = state.phase_mut().complete_handshake();
+        let screen_dictionary = config.screen.dictionary().unwrap_or_else(|e| {
+            println!("[WARN] {}; offering the built-in screen dictionary", e);
+            Some(FrameDictionary::builtin())
+        });
+        Ok(Self {
+            conn,
+            state,
+            task_pool: TaskPool::new(),
+            screens: BTreeMap::new(),
+            max_screens: config.screen.max_sessions,
+            conn_stats,
+            screen_traffic: TrafficCounter::default(),
+            budget: SessionBudget::new(config.limits.max_bytes_per_hour),
+            uploads: HashMap::new(),
+            path_locks: PathLocks::new(config.locks.timeout()),
+            slow_after: config.tasks.slow_after(),
+            slow_reported: HashSet::new(),
+            task_timeout: config.tasks.timeout(),
+            task_commands: HashMap::new(),
+            slave_id: String::new(),
+            audit: Audit::default(),
+            allow_sas: config.screen.allow_sas,
+            screen_dictionary,
+            scheduler: None,
+            compressor,
+            self_test: config.self_test(),
+            input_burst: InputBurst::new(config.screen.stale_move()),
+            shells: config.shell.clone(),
+        })
+    }
+
+    /// Present `id` as this installation's ID in the `Hello`.
+    pub fn with_slave_id(mut self, id: &str) -> Self {
+        self.slave_id = id.to_string();
+        self
+    }
+
+    /// Record privileged commands in `audit`.
+    pub fn with_audit(mut self, audit: Audit) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Serve schedule commands from `scheduler`, and push its results to
+    /// this connection's master.
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        scheduler.attach(self.conn.sender());
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Run the main loop: handle packets and task events. A master that
+    /// stops sending anything, heartbeats included, counts as lost. The
+    /// tasks still running when it ends are cancelled: their master is
+    /// gone.
+    pub async fn run(&mut self) -> Result<(), TixError> {
+        let result = self.serve().await;
+        self.abandon_tasks("connection closed");
+        result
+    }
+
+    async fn serve(&mut self) -> Result<(), TixError> {
+        let mut budget_tick = tokio::time::interval(BUDGET_CHECK_INTERVAL);
+        let mut slow_tick = tokio::time::interval(SLOW_TASK_CHECK_INTERVAL);
+        let mut liveness = self.conn.on_liveness_change();
+        loop {
+            tokio::select! {
+                packet = self.conn.recv() => {
+                    match packet {
+                        Some(pkt) => {
+                            self.handle_packet(pkt).await?;
+                            // Stamped input already queued behind it
+                            // joins the burst, so moves it has
+                            // overtaken can be dropped.
+                            while !self.input_burst.is_empty()
+                                && let Some(pkt) = self.conn.try_recv()
+                            {
+                                self.handle_packet(pkt).await?;
+                            }
+                            self.apply_input();
+                        }
+                        None => {
+                            println!("[DISC] Connection to master lost");
+                            return Ok(());
+                        }
+                    }
+                }
+
+                Some(task_event) = self.task_pool.recv() => {
+                    self.handle_task_event(task_event).await;
+                }
+
+                _ = budget_tick.tick() => self.enforce_budget(),
+
+                _ = slow_tick.tick() => self.report_slow_tasks().await,
+
+                Ok(()) = liveness.changed() => {
+                    if *liveness.borrow_and_update() == Liveness::Dead {
+                        println!(
+                            "[DISC] Nothing from master for {}s — dropping the connection",
+                            self.conn.last_peer_activity().elapsed().as_secs()
+                        );
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Echo the master's session nonce on everything sent from now on.
+    /// A new session means the master forgot the old one's requests, so
+    /// their tasks are dropped rather than answered into it.
+    fn follow_session(&mut self, session: u32) {
+        let current = self.conn.session();
+        if session == 0 || session == current {
+            return;
+        }
+        if current != 0 {
+            self.abandon_tasks("master started a new session");
+        }
+        self.conn.set_session(session);
+    }
+
+    /// Cancel every pooled task and forget what was kept to answer
+    /// them, so nothing they finish with reaches the master.
+    fn abandon_tasks(&mut self, why: &str) {
+        let count = self.task_pool.active_count();
+        self.task_pool.cancel_all();
+        self.task_commands.clear();
+        self.slow_reported.clear();
+        self.uploads.clear();
+        if count > 0 {
+            println!("[CNCL] Cancelled {} task(s) left over: {}", count, why);
+        }
+    }
+
+    /// Forget a finished task. A failed one has not answered its
+    /// request, so the error goes to the master in its place. Events for
+    /// a task already resolved (the `Finished` after a reported error)
+    /// change nothing.
+    async fn handle_task_event(&mut self, event: TaskEvent) {
+        let req_id = event.request_id();
+        if !self.task_pool.is_active(req_id) {
+            return;
+        }
+        let command = self.task_commands.remove(&req_id);
+        self.state.complete_task(req_id);
+        let Some(err) = self.task_pool.process_event(event).await else {
+            return;
+        };
+        match &err {
+            TaskError::Panicked(_) => println!("[CRSH] ReqID {} {}", req_id, err),
+            _ => println!("[ERR ] ReqID {} failed: {}", req_id, err),
+        }
+        if let Some(command) = command
+            && let Ok(pkt) = TaskFailure::from(&err).into_packet(req_id, command)
+        {
+            let _ = self.conn.send(pkt).await;
+        }
+    }
+
+    /// Options for a pooled task named `name`.
+    fn task_options(&self, name: impl Into<String>) -> TaskOptions {
+        let options = TaskOptions::new().with_name(name);
+        match self.task_timeout {
+            Some(timeout) => options.with_timeout(timeout),
+            None => options,
+        }
+    }
+
+    /// Bytes moved on both channels since this session started.
+    fn total_bytes(&self) -> u64 {
+        self.conn_stats.bytes_sent()
+            + self.conn_stats.bytes_received()
+            + self.screen_traffic.sent()
+            + self.screen_traffic.received()
+    }
+
+    /// Pause or resume the screen stream as the hourly cap is crossed.
+    fn enforce_budget(&mut self) {
+        let change = self.budget.update(self.total_bytes());
+        match change {
+            Some(BudgetChange::Throttled) => {
+                println!("[LIMT] Bandwidth limit reached: screen paused, file transfers refused")
+            }
+            Some(BudgetChange::Released) => {
+                println!("[LIMT] Bandwidth back under limit: resuming normal service")
+            }
+            None => return,
+        }
+        for session in self.screens.values() {
+            session.set_paused(self.budget.is_throttled());
+        }
+    }
+
+    /// Tell the master, once per task, about tasks that crossed the
+    /// slow-task threshold. The warning is a `TaskList` response with
+    /// request ID 0 listing just those tasks.
+    async fn report_slow_tasks(&mut self) {
+        let Some(threshold) = self.slow_after else {
+            return;
+        };
+        let pool = &self.task_pool;
+        self.slow_reported.retain(|id| pool.is_active(*id));
+        let slow: Vec<_> = pool
+            .snapshot()
+            .into_iter()
+            .filter(|t| t.elapsed() >= threshold && !self.slow_reported.contains(&t.request_id))
+            .collect();
+        if slow.is_empty() {
+            return;
+        }
+        for task in &slow {
+            println!(
+                "[SLOW] ReqID {}: {} running for {}s",
+                task.request_id,
+                task.name.as_deref().unwrap_or("task"),
+                task.elapsed().as_secs()
+            );
+            self.slow_reported.insert(task.request_id);
+        }
+        let warning = TaskListResponse {
+            tasks: slow,
+            slow_after_ms: threshold.as_millis() as u64,
+        };
+        if let Ok(pkt) = warning.into_packet(0) {
+            let _ = self.conn.send(pkt).await;
+        }
+    }
+
+    /// Current traffic accounting for this session.
+    fn session_stats(&mut self) -> SessionStats {
+        let mut stats = SessionStats {
+            control_sent: self.conn_stats.bytes_sent(),
+            control_received: self.conn_stats.bytes_received(),
+            screen_sent: self.screen_traffic.sent(),
+            screen_received: self.screen_traffic.received(),
+            commands: self.conn_stats.per_command(),
+            ..Default::default()
+        };
+        self.budget.fill_stats(&mut stats);
+        stats
+    }
+
+    /// Dispatch a received packet to the appropriate handler.
+    async fn handle_packet(&mut self, packet: tix_core::Packet) -> Result<(), TixError> {
+        self.follow_session(packet.session());
+        let cmd = packet.command()?;
+        let req_id = packet.request_id();
+        // Input that arrived first is applied first.
+        if !matches!(cmd, Command::InputMouse | Command::InputKeyboard) {
+            self.apply_input();
+        }
+
+        // Chunks of an upload already under way are neither logged nor
+        // re-checked against the budget; the header was.
+        if cmd == Command::FileWrite && self.uploads.contains_key(&req_id) {
+            return self.handle_file_write(req_id, &packet).await;
+        }
+        println!("[RECV] Command: {:?}, ReqID: {}", cmd, req_id);
+
+        let peer = self
+            .conn
+            .peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
+
+        // A second request under an ID still in flight would run next to
+        // the first and answer under the same ID. Refuse it, leaving the
+        // first one running.
+        if !self.state.register_task(req_id) {
+            println!(
+                "[DUPL] ReqID {} is already in flight; {:?} refused",
+                req_id, cmd
+            );
+            let failure = TaskFailure::DuplicateRequest;
+            self.audit.record(
+                &peer,
+                cmd,
+                req_id,
+                packet.payload(),
+                &format!("refused: {}", failure),
+            );
+            if let Ok(pkt) = failure.into_packet(req_id, cmd) {
+                let _ = self.conn.send(pkt).await;
+            }
+            return Ok(());
+        }
+
+        let check = self.budget.check(cmd);
+        let outcome = match &check {
+            Ok(()) => "accepted".to_string(),
+            Err(refusal) => format!("refused: {}", refusal),
+        };
+        self.audit
+            .record(&peer, cmd, req_id, packet.payload(), &outcome);
+
+        if let Err(refusal) = check {
+            println!("[LIMT] ReqID {}: {}", req_id, refusal);
+            if let Ok(pkt) = refusal.into_packet(req_id) {
+                let _ = self.conn.send(pkt).await;
+            }
+            self.state.complete_task(req_id);
+            return Ok(());
+        }
+
+        let result = match cmd {
+            Command::ShellExecute => {
+                self.handle_shell_execute(req_id, packet.payload());
+                Ok(())
+            }
+            Command::Copy => {
+                self.handle_copy(req_id, packet.payload());
+                Ok(())
+            }
+            Command::ListDrives => {
+                self.handle_list_drives(req_id);
+                Ok(())
+            }
+            Command::ListDir => {
+                self.handle_list_dir(req_id, packet.payload());
+                Ok(())
+            }
+            Command::Upload => {
+                self.handle_upload(req_id, packet.payload());
+                Ok(())
+            }
+            Command::Download => {
+                self.handle_download(req_id, packet.payload());
+                Ok(())
+            }
+            Command::FileWrite => self.handle_file_write(req_id, &packet).await,
+            Command::FileDelete => {
+                self.handle_file_delete(req_id, packet.payload());
+                Ok(())
+            }
+            Command::TrashRestore => {
+                self.handle_trash_restore(req_id, packet.payload());
+                Ok(())
+            }
+            Command::FileTransaction => {
+                self.handle_file_transaction(req_id, packet.payload());
+                Ok(())
+            }
+            Command::FileHash => {
+                self.handle_file_hash(req_id, packet.payload());
+                Ok(())
+            }
+            Command::FileSearch => {
+                self.handle_file_search(req_id, packet.payload());
+                Ok(())
+            }
+            Command::ShellCancel => self.handle_cancel(req_id, packet.payload()).await,
+            Command::FileRead if packet.flags().contains(ProtocolFlags::ACK_REQUESTED) => {
+                self.handle_delta_sync(req_id, packet.payload());
+                Ok(())
+            }
+            Command::SystemAction => {
+                self.handle_system_action(req_id, packet.payload());
+                Ok(())
+            }
+            Command::RegistryQuery => {
+                self.handle_registry_query(req_id, packet.payload());
+                Ok(())
+            }
+            Command::StartupList => {
+                self.handle_startup_list(req_id);
+                Ok(())
+            }
+            Command::ServiceList => {
+                self.handle_service_list(req_id, packet.payload());
+                Ok(())
+            }
+            Command::ServiceControl => {
+                self.handle_service_control(req_id, packet.payload());
+                Ok(())
+            }
+            Command::EventLogQuery => {
+                self.handle_event_log_query(req_id, packet.payload());
+                Ok(())
+            }
+            Command::NetDiag => {
+                self.handle_net_diag(req_id, packet.payload());
+                Ok(())
+            }
+            Command::SelfTest => {
+                self.handle_self_test(req_id);
+                Ok(())
+            }
+            Command::SystemInfo => self.handle_system_info(req_id).await,
+            Command::TaskList => self.handle_task_list(req_id).await,
+            Command::WireTap => self.handle_wire_tap(req_id, packet.payload()).await,
+            Command::ScheduleCreate
+            | Command::ScheduleList
+            | Command::ScheduleDelete
+            | Command::ScheduleResults => self.handle_schedule(cmd, req_id, packet.payload()).await,
+            Command::ScreenStart => self.handle_screen_start(req_id, packet.payload()).await,
+            Command::ScreenStop => self.handle_screen_stop(req_id, packet.payload()).await,
+            Command::ScreenMode => self.handle_screen_mode(req_id, packet.payload()).await,
+            Command::SendSas => self.handle_send_sas(req_id).await,
+            Command::ScreenDictionary => {
+                self.handle_screen_dictionary(req_id, packet.payload())
+                    .await
+            }
+            Command::ScreenPreflight => {
+                self.handle_screen_preflight(req_id, packet.payload()).await
+            }
+            Command::ScreenSharing => {
+                self.handle_screen_sharing(req_id, packet.payload());
+                Ok(())
+            }
+            Command::InputMouse | Command::InputKeyboard => {
+                self.handle_input(cmd, req_id, packet.payload());
+                Ok(())
+            }
+            Command::Ping => self.handle_ping(req_id).await,
+            Command::Hello => self.handle_hello(req_id, packet.payload()).await,
+            Command::UpdateApply => {
+                self.handle_update_apply(req_id, packet.payload());
+                Ok(())
+            }
+            _ => {
+                println!("[WARN] Unknown command: {:?} (ReqID: {})", cmd, req_id);
+                self.state.complete_task(req_id);
+                Ok(())
+            }
+        };
+        // Requests answered on the spot are done; pooled tasks and
+        // uploads finish later.
+        if self.task_pool.is_active(req_id) {
+            self.task_commands.insert(req_id, cmd);
+        } else if !self.uploads.contains_key(&req_id) {
+            self.state.complete_task(req_id);
+        }
+        result
+    }
+
+    // ── Command handlers ─────────────────────────────────────────
+
+    fn handle_shell_execute(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+        let task_pool_tx = self.task_pool.event_sender();
+        let req = parse_shell_payload(&payload);
+        let shells = self.shells.clone();
+
+        // The request's deadline can shorten the configured one, never
+        // extend it.
+        let mut options = self.task_options(format!("ShellExecute {}", req.command));
+        if req.timeout_ms > 0 {
+            let requested = Duration::from_millis(req.timeout_ms);
+            options.timeout = Some(options.timeout.map_or(requested, |t| t.min(requested)));
+        }
+        println!("[TASK] Spawning ShellExecute task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload,
+            |tx, req_id, _payload| async move {
+                let invocation = match ShellInvocation::resolve(&req.shell, &req.command, &shells) {
+                    Ok(invocation) => invocation,
+                    Err(msg) => {
+                        let _ = task_pool_tx
+                            .send(TaskEvent::Error(req_id, TaskError::Failed(msg)))
+                            .await;
+                        return;
+                    }
+                };
+                let shell = invocation.describe();
+                println!(
+                    "[EXEC] ReqID {}: {} \"{}\"{}",
+                    req_id,
+                    shell,
+                    req.command,
+                    req.working_dir
+                        .as_deref()
+                        .map(|dir| format!(" in {}", dir))
+                        .unwrap_or_default()
+                );
+
+                let mut command = invocation.to_command();
+                command.envs(&req.env);
+                if let Some(dir) = &req.working_dir {
+                    if !Path::new(dir).is_dir() {
+                        let err = std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            format!("working directory {} does not exist", dir),
+                        );
+                        let _ = task_pool_tx
+                            .send(TaskEvent::Error(req_id, TaskError::Io(err)))
+                            .await;
+                        return;
+                    }
+                    command.current_dir(dir);
+                }
+
+                // A timeout or cancel drops this future; take the child
+                // process with it.
+                let output = command.kill_on_drop(true).output().await;
+
+                let output = match output {
+                    Ok(output) => output,
+                    Err(e) => {
+                        // The pool answers the master with this error.
+                        let msg = format!("failed to start {}: {}", invocation.program, e);
+                        let _ = task_pool_tx
+                            .send(TaskEvent::Error(req_id, TaskError::Failed(msg)))
+                            .await;
+                        return;
+                    }
+                };
+                let exit_code = output.status.code().unwrap_or(1);
+
+                println!("[DONE] ReqID {} finished with code {}", req_id, exit_code);
+                if !output.stdout.is_empty() {
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    println!("[OUT ] ReqID {}: {}", req_id, stdout.trim());
+                }
+                if !output.stderr.is_empty() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    println!("[ERR ] ReqID {}: {}", req_id, stderr.trim());
+                }
+
+                // The bytes go out as written; the master decodes them.
+                let mut chunks = Vec::new();
+                for (data, is_stdout) in [(&output.stdout, true), (&output.stderr, false)] {
+                    for part in data.chunks(SHELL_CHUNK_SIZE) {
+                        chunks.push(ShellOutputChunk {
+                            chunk_number: chunks.len() as u64,
+                            data: part.to_vec(),
+                            is_stdout,
+                        });
+                    }
+                }
+                let exit = ShellExitStatus::success(exit_code, chunks.len() as u64)
+                    .with_codepage(output_codepage())
+                    .with_shell(shell);
+                for chunk in chunks {
+                    if let Ok(pkt) = chunk.into_packet(req_id)
+                        && let Err(e) = tx.send(pkt).await
+                    {
+                        println!("[ERR ] ReqID {} failed to send output: {}", req_id, e);
+                        return;
+                    }
+                }
+                if let Ok(pkt) = exit.into_packet(req_id)
+                    && let Err(e) = tx.send(pkt).await
+                {
+                    println!("[ERR ] ReqID {} failed to send response: {}", req_id, e);
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    fn handle_copy(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+        let task_pool_tx = self.task_pool.event_sender();
+        let locks = self.path_locks.clone();
+
+        let options = self.task_options(match parse_copy_payload(&payload) {
+            Some(req) => format!("Copy {} -> {}", req.src, req.dest),
+            None => "Copy".to_string(),
+        });
+        println!("[TASK] Spawning Copy task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload,
+            |tx, req_id, payload| async move {
+                let Some(req) = parse_copy_payload(&payload) else {
+                    let err_msg =
+                        "Invalid arguments for Copy. Expected: Copy <src> <dest>".to_string();
+                    let _ = task_pool_tx
+                        .send(TaskEvent::Error(req_id, TaskError::Failed(err_msg)))
+                        .await;
+                    return;
+                };
+
+                let paths = [
+                    (Path::new(&req.src), LockAccess::Shared),
+                    (Path::new(&req.dest), LockAccess::Exclusive),
+                ];
+                let _guard = match locks.acquire(req_id, &paths).await {
+                    Ok(guard) => guard,
+                    Err(busy) => {
+                        println!("[BUSY] ReqID {}: {}", req_id, busy);
+                        let _ = task_pool_tx
+                            .send(TaskEvent::Error(req_id, busy.into()))
+                            .await;
+                        return;
+                    }
+                };
+                println!(
+                    "[EXEC] ReqID {}: Copying '{}' to '{}'",
+                    req_id, req.src, req.dest
+                );
+
+                let result = perform_robust_copy(&req).await;
+                let msg = match &result {
+                    Ok(m) => {
+                        println!("[DONE] ReqID {}: {}", req_id, m);
+                        m.clone()
+                    }
+                    Err(e) => {
+                        println!("[ERR ] ReqID {}: {}", req_id, e);
+                        e.clone()
+                    }
+                };
+
+                if let Ok(pkt) =
+                    tix_core::Packet::new_response(req_id, Command::Copy, msg.into_bytes())
+                {
+                    let _ = tx.send(pkt).await;
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    fn handle_list_drives(&self, req_id: u64) {
+        let tx: ConnectionSender = self.conn.sender();
+        tokio::spawn(async move {
+            let mut drives = Vec::new();
+            #[cfg(windows)]
+            {
+                for drive in b'A'..=b'Z' {
+                    let drive_str = format!("{}:\\", drive as char);
+                    if Path::new(&drive_str).exists() {
+                        drives.push(drive_str);
+                    }
+                }
+            }
+            #[cfg(not(windows))]
+            {
+                drives.push("/".to_string());
+            }
+
+            let response = drives.join(",");
+            if let Ok(pkt) =
+                tix_core::Packet::new_response(req_id, Command::ListDrives, response.into_bytes())
+            {
+                let _ = tx.send(pkt).await;
+            }
+        });
+    }
+
+    fn handle_list_dir(&self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+        let locks = self.path_locks.clone();
+        tokio::spawn(async move {
+            let path_str = String::from_utf8_lossy(&payload);
+            let path = Path::new(path_str.as_ref());
+
+            let mut entries = Vec::new();
+            entries.push(format!("PATH|{}", path_str));
+
+            // A directory being deleted or restored is listed once that
+            // finishes; if it never does, the listing comes back empty.
+            let guard = locks.read(req_id, path).await;
+            if let Err(busy) = &guard {
+                println!("[BUSY] ReqID {}: {}", req_id, busy);
+            }
+            let listing = match &guard {
+                Ok(_) => std::fs::read_dir(path).map(Some),
+                Err(_) => Ok(None),
+            };
+            let read_dir = match listing {
+                Ok(read_dir) => read_dir,
+                // A missing or unreadable directory is an error, so `cd`
+                // can tell it from an empty one.
+                Err(e) => {
+                    println!("[ERR ] ReqID {}: cannot list {}: {}", req_id, path_str, e);
+                    let failure = TaskFailure::Io(format!("{}: {}", path_str, e));
+                    if let Ok(pkt) = failure.into_packet(req_id, Command::ListDir) {
+                        let _ = tx.send(pkt).await;
+                    }
+                    return;
+                }
+            };
+            if let Some(read_dir) = read_dir {
+                for entry in read_dir.flatten() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    let is_dir = entry.path().is_dir();
+                    // The entry's own metadata: a junction reports itself.
+                    let meta = entry.metadata().ok();
+                    let size = meta.as_ref().map_or(0, |m| m.len());
+                    let attributes = meta
+                        .as_ref()
+                        .map_or(FileAttributes::empty(), FileAttributes::of);
+                    entries.push(format!(
+                        "{}|{}|{}|{}",
+                        name,
+                        if is_dir { "1" } else { "0" },
+                        size,
+                        attributes.bits()
+                    ));
+                }
+            }
+
+            // A directory of a few thousand files outgrows one packet.
+            let response = entries.join(";");
+            match tix_core::Packet::new_fragmented(req_id, Command::ListDir, response.into_bytes())
+            {
+                Ok(packets) => {
+                    for pkt in packets {
+                        if tx.send(pkt).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(e) => println!("[ERR ] ReqID {}: listing not sent: {}", req_id, e),
+            }
+        });
+    }
+
+    fn handle_upload(&self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+        let locks = self.path_locks.clone();
+        tokio::spawn(async move {
+            let payload_str = String::from_utf8_lossy(&payload);
+            let parts: Vec<&str> = payload_str.split('|').collect();
+            if parts.len() < 2 {
+                if let Ok(pkt) = tix_core::Packet::new_response(
+                    req_id,
+                    Command::Upload,
+                    b"Invalid upload args".to_vec(),
+                ) {
+                    let _ = tx.send(pkt).await;
+                }
+                return;
+            }
+            let req = CopyRequest::new(parts[0], parts[1])
+                .with_overwrite(true)
+                .with_recursive(true);
+            let result = match locked_copy(&locks, req_id, &req).await {
+                Ok(msg) => format!("Upload successful: {}", msg),
+                Err(e) => format!("Upload failed: {}", e),
+            };
+            if let Ok(pkt) =
+                tix_core::Packet::new_response(req_id, Command::Upload, result.into_bytes())
+            {
+                let _ = tx.send(pkt).await;
+            }
+        });
+    }
+
+    fn handle_download(&self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+        let locks = self.path_locks.clone();
+        tokio::spawn(async move {
+            let payload_str = String::from_utf8_lossy(&payload);
+            let parts: Vec<&str> = payload_str.split('|').collect();
+            if parts.len() < 2 {
+                if let Ok(pkt) = tix_core::Packet::new_response(
+                    req_id,
+                    Command::Download,
+                    b"Invalid download args".to_vec(),
+                ) {
+                    let _ = tx.send(pkt).await;
+                }
+                return;
+            }
+            let req = CopyRequest::new(parts[0], parts[1])
+                .with_overwrite(true)
+                .with_recursive(true);
+            let result = match locked_copy(&locks, req_id, &req).await {
+                Ok(msg) => format!("Download successful: {}", msg),
+                Err(e) => format!("Download failed: {}", e),
+            };
+            if let Ok(pkt) =
+                tix_core::Packet::new_response(req_id, Command::Download, result.into_bytes())
+            {
+                let _ = tx.send(pkt).await;
+            }
+        });
+    }
+
+    /// Pass one packet of a chunked upload to the task receiving it,
+    /// starting that task on the header.
+    async fn handle_file_write(&mut self, req_id: u64, packet: &Packet) -> Result<(), TixError> {
+        let last = packet.flags().contains(ProtocolFlags::FINAL_FRAGMENT);
+        match self.uploads.get(&req_id) {
+            // A task that already answered has let go of its end; the
+            // rest of its packets are dropped.
+            Some(upload) => {
+                let _ = upload.send(packet.clone()).await;
+            }
+            None => {
+                let (packets, rx) = mpsc::channel(upload::UPLOAD_QUEUE);
+                tokio::spawn(upload::receive(
+                    req_id,
+                    packet.clone(),
+                    rx,
+                    upload::default_upload_dir(),
+                    self.path_locks.clone(),
+                    self.conn.sender(),
+                ));
+                self.uploads.insert(req_id, packets);
+            }
+        }
+        if last {
+            self.uploads.remove(&req_id);
+            self.state.complete_task(req_id);
+        }
+        Ok(())
+    }
+
+    fn handle_file_delete(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+        let task_pool_tx = self.task_pool.event_sender();
+        let locks = self.path_locks.clone();
+
+        let options = self.task_options("FileDelete");
+        println!("[TASK] Spawning FileDelete task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload,
+            |tx, req_id, payload| async move {
+                let req = match FileDeleteRequest::from_bytes(&payload) {
+                    Ok(req) => req,
+                    Err(e) => {
+                        let msg = format!("Invalid FileDelete payload: {}", e);
+                        let _ = task_pool_tx
+                            .send(TaskEvent::Error(req_id, TaskError::Failed(msg)))
+                            .await;
+                        return;
+                    }
+                };
+                let paths: Vec<_> = req
+                    .paths
+                    .iter()
+                    .map(|p| (Path::new(p.as_str()), LockAccess::Exclusive))
+                    .collect();
+                let _guard = match locks.acquire(req_id, &paths).await {
+                    Ok(guard) => guard,
+                    Err(busy) => {
+                        println!("[BUSY] ReqID {}: {}", req_id, busy);
+                        let response = FileDeleteResponse {
+                            requested: req.mode,
+                            items: req
+                                .paths
+                                .iter()
+                                .map(|path| DeletedItem {
+                                    path: path.clone(),
+                                    outcome: DeleteOutcome::Failed(busy.to_string()),
+                                })
+                                .collect(),
+                            note: None,
+                        };
+                        if let Ok(pkt) = response.into_packet(req_id) {
+                            let _ = tx.send(pkt).await;
+                        }
+                        return;
+                    }
+                };
+                println!(
+                    "[EXEC] ReqID {}: deleting {} item(s) {}",
+                    req_id,
+                    req.paths.len(),
+                    req.mode.describe()
+                );
+
+                let response = match tokio::task::spawn_blocking(move || trash::delete(&req)).await
+                {
+                    Ok(response) => response,
+                    Err(e) => {
+                        let _ = task_pool_tx
+                            .send(TaskEvent::Error(req_id, TaskError::Failed(e.to_string())))
+                            .await;
+                        return;
+                    }
+                };
+                if let Some(note) = &response.note {
+                    println!("[WARN] ReqID {}: {}", req_id, note);
+                }
+                println!(
+                    "[DONE] ReqID {}: {} of {} item(s) removed",
+                    req_id,
+                    response.items.len() - response.failed_count(),
+                    response.items.len()
+                );
+                if let Ok(pkt) = response.into_packet(req_id) {
+                    let _ = tx.send(pkt).await;
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    /// Run a `FileTransaction` with every path it touches locked for
+    /// the whole of it.
+    fn handle_file_transaction(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+        let task_pool_tx = self.task_pool.event_sender();
+        let locks = self.path_locks.clone();
+
+        let options = self.task_options(match FileTransactionRequest::from_bytes(&payload) {
+            Ok(req) => format!("FileTransaction ({} steps)", req.ops.len()),
+            Err(_) => "FileTransaction".to_string(),
+        });
+        println!("[TASK] Spawning FileTransaction task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload,
+            |tx, req_id, payload| async move {
+                let req = match FileTransactionRequest::from_bytes(&payload) {
+                    Ok(req) => req,
+                    Err(e) => {
+                        let msg = format!("Invalid FileTransaction payload: {}", e);
+                        let _ = task_pool_tx
+                            .send(TaskEvent::Error(req_id, TaskError::Failed(msg)))
+                            .await;
+                        return;
+                    }
+                };
+                if let Err(e) = req.validate() {
+                    println!("[ERR ] ReqID {}: {}", req_id, e);
+                    let response = FileTransactionResponse::refused(&req.ops, e);
+                    if let Ok(pkt) = response.into_packet(req_id) {
+                        let _ = tx.send(pkt).await;
+                    }
+                    return;
+                }
+                let paths: Vec<_> = req
+                    .ops
+                    .iter()
+                    .flat_map(|op| op.paths())
+                    .map(|(path, access)| (Path::new(path), access))
+                    .collect();
+                let guard = match locks.acquire(req_id, &paths).await {
+                    Ok(guard) => guard,
+                    Err(busy) => {
+                        println!("[BUSY] ReqID {}: {}", req_id, busy);
+                        let response = FileTransactionResponse::refused(&req.ops, busy.to_string());
+                        if let Ok(pkt) = response.into_packet(req_id) {
+                            let _ = tx.send(pkt).await;
+                        }
+                        return;
+                    }
+                };
+                println!(
+                    "[EXEC] ReqID {}: transaction of {} step(s)",
+                    req_id,
+                    req.ops.len()
+                );
+
+                let response =
+                    match tokio::task::spawn_blocking(move || transaction::run(&req)).await {
+                        Ok(response) => response,
+                        Err(e) => {
+                            let _ = task_pool_tx
+                                .send(TaskEvent::Error(req_id, TaskError::Failed(e.to_string())))
+                                .await;
+                            return;
+                        }
+                    };
+                drop(guard);
+                match response.failed_step() {
+                    None => println!(
+                        "[DONE] ReqID {}: {} step(s) committed",
+                        req_id,
+                        response.steps.len()
+                    ),
+                    Some((i, e)) => println!(
+                        "[ERR ] ReqID {}: step {} failed ({}); {} step(s) left in effect",
+                        req_id,
+                        i + 1,
+                        e,
+                        response.left_in_effect()
+                    ),
+                }
+                if let Ok(pkt) = response.into_packet(req_id) {
+                    let _ = tx.send(pkt).await;
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    fn handle_trash_restore(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+
+        let locks = self.path_locks.clone();
+
+        let options = self.task_options("TrashRestore");
+        println!("[TASK] Spawning TrashRestore task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload,
+            |tx, req_id, payload| async move {
+                let response = match TrashRestoreRequest::from_bytes(&payload) {
+                    Ok(req) => match locks.write(req_id, Path::new(&req.original_path)).await {
+                        Ok(guard) => {
+                            println!("[EXEC] ReqID {}: restoring {}", req_id, req.original_path);
+                            let response = tokio::task::spawn_blocking(move || {
+                                trash::restore(&req.original_path)
+                            })
+                            .await
+                            .unwrap_or_else(|e| TrashRestoreResponse::Failed(e.to_string()));
+                            drop(guard);
+                            response
+                        }
+                        Err(busy) => TrashRestoreResponse::Failed(busy.to_string()),
+                    },
+                    Err(e) => {
+                        TrashRestoreResponse::Failed(format!("Invalid TrashRestore payload: {}", e))
+                    }
+                };
+                println!("[DONE] ReqID {}: {:?}", req_id, response);
+                if let Ok(pkt) = response.into_packet(req_id) {
+                    let _ = tx.send(pkt).await;
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    fn handle_file_hash(&self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+        let locks = self.path_locks.clone();
+        tokio::spawn(async move {
+            let response = match FileHashRequest::from_bytes(&payload) {
+                Ok(req) => match read_locked(&locks, req_id, &req.path).await {
+                    Ok(data) => FileHashResponse::Hashed(FileDigest::of(&data, req.chunk_size)),
+                    Err(e) => FileHashResponse::Failed(e),
+                },
+                Err(e) => FileHashResponse::Failed(format!("Invalid FileHash payload: {}", e)),
+            };
+            if let FileHashResponse::Failed(e) = &response {
+                println!("[ERR ] ReqID {}: {}", req_id, e);
+            }
+            if let Ok(pkt) = response.into_packet(req_id) {
+                let _ = tx.send(pkt).await;
+            }
+        });
+    }
+
+    /// Walk a directory for names matching a pattern, streaming batches
+    /// of matches. Runs in the task pool so it can be cancelled.
+    fn handle_file_search(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let name = match FileSearchRequest::from_bytes(payload) {
+            Ok(req) => format!("FileSearch {} in {}", req.pattern, req.root),
+            Err(_) => "FileSearch".to_string(),
+        };
+        println!("[TASK] Spawning {} for ReqID: {}", name, req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload.to_vec(),
+            search::run,
+            self.task_options(name),
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    /// Stop the task named by a `ShellCancel` (its request ID, u64 LE) and
+    /// say whether anything was running.
+    async fn handle_cancel(&mut self, req_id: u64, payload: &[u8]) -> Result<(), TixError> {
+        let reply = match <[u8; 8]>::try_from(payload) {
+            Ok(bytes) => {
+                let target = u64::from_le_bytes(bytes);
+                if self.task_pool.cancel_task(target) {
+                    println!("[CNCL] ReqID {}: cancelled ReqID {}", req_id, target);
+                    format!("Cancelled ReqID {}", target)
+                } else {
+                    format!("ReqID {} is not running", target)
+                }
+            }
+            Err(_) => "Invalid ShellCancel payload".to_string(),
+        };
+        if let Ok(pkt) = Packet::new_response(req_id, Command::ShellCancel, reply.into_bytes()) {
+            let _ = self.conn.send(pkt).await;
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    /// Stream the chunks of a file that differ from the master's copy.
+    /// Runs in the task pool, so a `ShellCancel` stops it part way.
+    fn handle_delta_sync(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let compressor = self
+            .compressor
+            .for_peer(self.state.negotiated_capabilities());
+        let locks = self.path_locks.clone();
+        let name = match DeltaSyncRequest::from_bytes(payload) {
+            Ok(req) => format!("DeltaSync {}", req.path),
+            Err(_) => "DeltaSync".to_string(),
+        };
+        let run = move |tx: ConnectionSender, req_id: u64, payload: Vec<u8>| async move {
+            let result = match DeltaSyncRequest::from_bytes(&payload) {
+                Ok(req) => read_locked(&locks, req_id, &req.path)
+                    .await
+                    .map(|data| (req.changed_chunks(&data), req.verification(&data))),
+                Err(e) => Err(format!("Invalid DeltaSyncRequest payload: {}", e)),
+            };
+            let (chunks, verification) = match result {
+                Ok(delta) => delta,
+                Err(e) => {
+                    println!("[ERR ] ReqID {}: {}", req_id, e);
+                    if let Ok(pkt) = Packet::new_response(req_id, Command::FileRead, e.into_bytes())
+                    {
+                        let _ = tx.send(pkt).await;
+                    }
+                    return;
+                }
+            };
+            println!(
+                "[SYNC] ReqID {}: {} of {} chunk(s) changed",
+                req_id,
+                chunks.len(),
+                verification.total_chunks
+            );
+            for chunk in chunks {
+                let chunk = compressor.pack(chunk.offset, chunk.chunk_index, chunk.data);
+                match chunk.into_packet(req_id, Command::FileRead) {
+                    Ok(pkt) => {
+                        if tx.send(pkt).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => println!("[ERR ] ReqID {}: {}", req_id, e),
+                }
+            }
+            if let Ok(pkt) = verification.into_packet(req_id, Command::FileRead) {
+                let _ = tx.send(pkt).await;
+            }
+        };
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload.to_vec(),
+            run,
+            self.task_options(name),
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    fn handle_system_action(&self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+        tokio::spawn(async move {
+            let action = String::from_utf8_lossy(&payload);
+            let result = match action.as_ref() {
+                "shutdown" => {
+                    #[cfg(windows)]
+                    {
+                        let _ = std::process::Command::new("shutdown")
+                            .args(["/s", "/t", "60"])
+                            .spawn();
+                        "Shutdown initiated in 60s".to_string()
+                    }
+                    #[cfg(not(windows))]
+                    {
+                        "Shutdown not supported on this OS".to_string()
+                    }
+                }
+                "reboot" => {
+                    #[cfg(windows)]
+                    {
+                        let _ = std::process::Command::new("shutdown")
+                            .args(["/r", "/t", "60"])
+                            .spawn();
+                        "Reboot initiated in 60s".to_string()
+                    }
+                    #[cfg(not(windows))]
+                    {
+                        "Reboot not supported on this OS".to_string()
+                    }
+                }
+                "sleep" => {
+                    #[cfg(windows)]
+                    {
+                        let _ = std::process::Command::new("rundll32.exe")
+                            .args(["powrprof.dll,SetSuspendState", "0,1,0"])
+                            .spawn();
+                        "Sleep initiated".to_string()
+                    }
+                    #[cfg(not(windows))]
+                    {
+                        "Sleep not supported on this OS".to_string()
+                    }
+                }
+                _ => format!("Unknown system action: {}", action),
+            };
+            if let Ok(pkt) =
+                tix_core::Packet::new_response(req_id, Command::SystemAction, result.into_bytes())
+            {
+                let _ = tx.send(pkt).await;
+            }
+        });
+    }
+
+    fn handle_registry_query(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+
+        let options = self.task_options("RegistryQuery");
+        println!("[TASK] Spawning RegistryQuery task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload,
+            |tx, req_id, payload| async move {
+                let response = match RegistryQueryRequest::from_bytes(&payload) {
+                    Ok(req) => {
+                        println!("[EXEC] ReqID {}: reg query {}", req_id, req.full_path());
+                        tokio::task::spawn_blocking(move || registry::query(&req))
+                            .await
+                            .unwrap_or_else(|e| {
+                                RegistryQueryResponse::error(
+                                    RegistryErrorKind::Other,
+                                    e.to_string(),
+                                )
+                            })
+                    }
+                    Err(e) => RegistryQueryResponse::error(
+                        RegistryErrorKind::Other,
+                        format!("Invalid RegistryQuery payload: {}", e),
+                    ),
+                };
+
+                if let RegistryQueryResponse::Error { message, .. } = &response {
+                    println!("[ERR ] ReqID {}: {}", req_id, message);
+                }
+                if let Ok(pkt) = response.into_packet(req_id) {
+                    let _ = tx.send(pkt).await;
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    fn handle_startup_list(&mut self, req_id: u64) {
+        let tx: ConnectionSender = self.conn.sender();
+
+        let options = self.task_options("StartupList");
+        println!("[TASK] Spawning StartupList task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            Vec::new(),
+            |tx, req_id, _| async move {
+                let response = tokio::task::spawn_blocking(registry::startup_list)
+                    .await
+                    .unwrap_or_else(|e| StartupListResponse {
+                        entries: Vec::new(),
+                        errors: vec![e.to_string()],
+                    });
+                println!(
+                    "[DONE] ReqID {}: {} startup entries, {} unreadable locations",
+                    req_id,
+                    response.entries.len(),
+                    response.errors.len()
+                );
+                if let Ok(pkt) = response.into_packet(req_id) {
+                    let _ = tx.send(pkt).await;
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    fn handle_service_list(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+
+        let options = self.task_options("ServiceList");
+        println!("[TASK] Spawning ServiceList task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload,
+            |tx, req_id, payload| async move {
+                let response = match ServiceListRequest::from_bytes(&payload) {
+                    Ok(req) => tokio::task::spawn_blocking(move || service::list(&req))
+                        .await
+                        .unwrap_or_else(|e| {
+                            ServiceListResponse::error(ServiceErrorKind::Other, e.to_string())
+                        }),
+                    Err(e) => ServiceListResponse::error(
+                        ServiceErrorKind::Other,
+                        format!("Invalid ServiceList payload: {}", e),
+                    ),
+                };
+                match &response {
+                    ServiceListResponse::Services(services) => {
+                        println!("[DONE] ReqID {}: {} services", req_id, services.len())
+                    }
+                    ServiceListResponse::Error { message, .. } => {
+                        println!("[ERR ] ReqID {}: {}", req_id, message)
+                    }
+                }
+                if let Ok(pkt) = response.into_packet(req_id) {
+                    let _ = tx.send(pkt).await;
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    fn handle_net_diag(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+
+        let options = self.task_options("NetDiag");
+        println!("[TASK] Spawning NetDiag task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload,
+            |tx, req_id, payload| async move {
+                let response = match NetDiagRequest::from_bytes(&payload) {
+                    Ok(req) => netdiag::answer(req).await,
+                    Err(e) => NetDiagResponse::error(
+                        NetDiagErrorKind::Other,
+                        format!("Invalid NetDiag payload: {}", e),
+                    ),
+                };
+                let tag = match response {
+                    NetDiagResponse::Error { .. } => "ERR ",
+                    _ => "DONE",
+                };
+                println!(
+                    "[{}] ReqID {}: {}",
+                    tag,
+                    req_id,
+                    netdiag::summary(&response)
+                );
+                if let Ok(pkt) = response.into_packet(req_id) {
+                    let _ = tx.send(pkt).await;
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    fn handle_self_test(&mut self, req_id: u64) {
+        let tx: ConnectionSender = self.conn.sender();
+        let checks = self.self_test.clone();
+
+        let options = self.task_options("SelfTest");
+        println!("[TASK] Spawning SelfTest task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            Vec::new(),
+            |tx, req_id, _| async move {
+                let response = tokio::task::spawn_blocking(move || doctor::run(&checks))
+                    .await
+                    .unwrap_or_else(|e| SelfTestReport {
+                        hostname: String::new(),
+                        checks: vec![CheckResult::fail(
+                            "self-test",
+                            e.to_string(),
+                            "the checks panicked; run tix-rdp-slave --doctor at the machine",
+                        )],
+                    });
+                println!("[DONE] ReqID {}: {}", req_id, response.summary());
+                if let Ok(pkt) = response.into_packet(req_id) {
+                    let _ = tx.send(pkt).await;
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    fn handle_service_control(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+
+        let options = self.task_options("ServiceControl");
+        println!("[TASK] Spawning ServiceControl task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload,
+            |tx, req_id, payload| async move {
+                let response = match ServiceControlRequest::from_bytes(&payload) {
+                    Ok(req) => {
+                        println!(
+                            "[EXEC] ReqID {}: service {} {}",
+                            req_id, req.name, req.action
+                        );
+                        tokio::task::spawn_blocking(move || service::control(&req))
+                            .await
+                            .unwrap_or_else(|e| {
+                                ServiceControlResponse::error(
+                                    ServiceErrorKind::Other,
+                                    e.to_string(),
+                                    None,
+                                )
+                            })
+                    }
+                    Err(e) => ServiceControlResponse::error(
+                        ServiceErrorKind::Other,
+                        format!("Invalid ServiceControl payload: {}", e),
+                        None,
+                    ),
+                };
+                match &response {
+                    ServiceControlResponse::Done { service, .. } => {
+                        println!(
+                            "[DONE] ReqID {}: {} {}",
+                            req_id, service.name, service.state
+                        )
+                    }
+                    ServiceControlResponse::Error { message, .. } => {
+                        println!("[ERR ] ReqID {}: {}", req_id, message)
+                    }
+                }
+                if let Ok(pkt) = response.into_packet(req_id) {
+                    let _ = tx.send(pkt).await;
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    /// Read recent entries of an event log, streaming them in batches.
+    /// Runs in the task pool so it can be cancelled.
+    fn handle_event_log_query(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let name = match EventLogQueryRequest::from_bytes(payload) {
+            Ok(req) => format!("EventLogQuery {}", req.log),
+            Err(_) => "EventLogQuery".to_string(),
+        };
+        println!("[TASK] Spawning {} for ReqID: {}", name, req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload.to_vec(),
+            eventlog::run,
+            self.task_options(name),
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    async fn handle_system_info(&mut self, req_id: u64) -> Result<(), TixError> {
+        let hostname = std::env::var("COMPUTERNAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        let response = SystemInfoResponse {
+            hostname,
+            os: std::env::consts::OS.to_string(),
+            session: self.session_stats(),
+            path_locks: self.path_locks.holders(),
+        };
+        if let Ok(pkt) = response.into_packet(req_id) {
+            let _ = self.conn.send(pkt).await;
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    async fn handle_task_list(&mut self, req_id: u64) -> Result<(), TixError> {
+        let response = TaskListResponse {
+            tasks: self.task_pool.snapshot(),
+            slow_after_ms: self.slow_after.map_or(0, |d| d.as_millis() as u64),
+        };
+        println!(
+            "[DONE] ReqID {}: {} tasks running",
+            req_id,
+            response.tasks.len()
+        );
+        if let Ok(pkt) = response.into_packet(req_id) {
+            let _ = self.conn.send(pkt).await;
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    async fn handle_wire_tap(&mut self, req_id: u64, payload: &[u8]) -> Result<(), TixError> {
+        let tap = self.conn.wire_tap();
+        let response = match WireTapSettings::from_bytes(payload) {
+            Ok(settings) => match tap.apply(&settings) {
+                Ok(()) => WireTapResponse::Applied(tap.settings()),
+                Err(e) => WireTapResponse::Failed(e.to_string()),
+            },
+            Err(e) => WireTapResponse::Failed(format!("Invalid WireTap payload: {}", e)),
+        };
+        match &response {
+            WireTapResponse::Applied(settings) if settings.enabled => println!(
+                "[WIRE] ReqID {}: packet log on ({} bytes)",
+                req_id, settings.dump_bytes
+            ),
+            WireTapResponse::Applied(_) => println!("[WIRE] ReqID {}: packet log off", req_id),
+            WireTapResponse::Failed(e) => println!("[ERR ] ReqID {}: {}", req_id, e),
+        }
+        if let Ok(pkt) = response.into_packet(req_id) {
+            let _ = self.conn.send(pkt).await;
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    async fn handle_schedule(
+        &mut self,
+        cmd: Command,
+        req_id: u64,
+        payload: &[u8],
+    ) -> Result<(), TixError> {
+        let response = match &self.scheduler {
+            Some(scheduler) => scheduler.handle(cmd, payload),
+            None => ScheduleResponse::Error("schedules are not available".to_string()),
+        };
+        if let ScheduleResponse::Error(e) = &response {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+        if let Ok(pkt) = response.into_packet(req_id, cmd) {
+            let _ = self.conn.send(pkt).await;
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    async fn handle_screen_start(&mut self, req_id: u64, payload: &[u8]) -> Result<(), TixError> {
+        let result = match (
+            ScreenStartRequest::from_bytes(payload),
+            self.conn.peer_addr(),
+        ) {
+            (Ok(req), Some(master)) => {
+                // A new ScreenStart replaces the same session left over
+                // from a viewer that went away without sending ScreenStop.
+                if let Some(old) = self.screens.remove(&req.session) {
+                    println!("[SCRN] Replacing screen session {}", req.session);
+                    old.stop().await;
+                }
+                self.prune_screens().await;
+                if self.screens.len() >= self.max_screens {
+                    Err(format!(
+                        "{} screen sessions already running (limit {})",
+                        self.screens.len(),
+                        self.max_screens
+                    ))
+                } else {
+                    println!(
+                        "[SCRN] ReqID {}: starting capture of monitor {} as session {}, frames to {}:{:?}",
+                        req_id,
+                        req.monitor,
+                        req.session,
+                        master.ip(),
+                        req.udp_port
+                    );
+                    ScreenSession::start(
+                        &req,
+                        master.ip(),
+                        self.conn.local_addr().map(|a| a.ip()),
+                        self.screen_traffic.clone(),
+                        self.screen_dictionary.as_ref(),
+                    )
+                    .await
+                }
+            }
+            (Err(e), _) => Err(format!("Invalid ScreenStart payload: {}", e)),
+            (_, None) => Err("Master address unknown".to_string()),
+        };
+
+        let response = match result {
+            Ok((session, config)) => {
+                println!(
+                    "[SCRN] ReqID {}: streaming {}x{} from {:?}",
+                    req_id, config.width, config.height, config.udp_endpoint
+                );
+                if self.budget.is_throttled() {
+                    println!("[LIMT] Bandwidth limit reached: screen starts paused");
+                    session.set_paused(true);
+                }
+                self.screens.insert(config.session, session);
+                ScreenStartResponse::Started(config)
+            }
+            Err(e) => {
+                println!("[ERR ] ReqID {}: screen start failed: {}", req_id, e);
+                ScreenStartResponse::Failed(e)
+            }
+        };
+
+        if let Ok(pkt) = response.into_packet(req_id) {
+            let _ = self.conn.send(pkt).await;
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    async fn handle_screen_stop(&mut self, req_id: u64, payload: &[u8]) -> Result<(), TixError> {
+        let stopping: Vec<ScreenSession> = match ScreenStopRequest::from_payload(payload).session {
+            Some(id) => self.screens.remove(&id).into_iter().collect(),
+            None => std::mem::take(&mut self.screens).into_values().collect(),
+        };
+        let msg = match stopping.len() {
+            0 => "No screen session running".to_string(),
+            1 => "Screen session stopped".to_string(),
+            n => format!("{} screen sessions stopped", n),
+        };
+        for session in stopping {
+            session.stop().await;
+        }
+        println!("[SCRN] ReqID {}: {}", req_id, msg);
+
+        if let Ok(pkt) =
+            tix_core::Packet::new_response(req_id, Command::ScreenStop, msg.into_bytes())
+        {
+            let _ = self.conn.send(pkt).await;
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    /// Drop sessions whose capture loop has ended, so they do not count
+    /// against the limit.
+    async fn prune_screens(&mut self) {
+        let ended: Vec<u8> = self
+            .screens
+            .iter()
+            .filter(|(_, s)| !s.is_running())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ended {
+            if let Some(session) = self.screens.remove(&id) {
+                session.stop().await;
+            }
+        }
+    }
+
+    /// A live session to take input through. Every session shares the
+    /// slave's virtual desktop (the viewer offsets pointer positions by
+    /// the monitor's origin) and they switch modes together, so any
+    /// running one will do.
+    fn input_session(&self) -> Option<&ScreenSession> {
+        self.screens.values().find(|s| s.is_running())
+    }
+
+    /// Inject a mouse or keyboard event. Input is fire-and-forget: no
+    /// response is sent, so a fast-moving mouse does not flood the link.
+    /// The exception is a view-only session, which answers every event
+    /// with an `InputRejection`.
+    fn handle_input(&mut self, cmd: Command, req_id: u64, payload: &[u8]) {
+        self.state.complete_task(req_id);
+
+        let Some(session) = self.input_session() else {
+            return;
+        };
+        if let Err(rejection) = session.gate().admit() {
+            if session.gate().rejected() == 1 {
+                println!("[SCRN] ReqID {}: refusing {:?}: {}", req_id, cmd, rejection);
+            }
+            if let Ok(pkt) = rejection.into_packet(req_id, cmd) {
+                let _ = self.conn.sender().try_send(pkt);
+            }
+            return;
+        }
+        if session.timed_input() {
+            let input = match cmd {
+                Command::InputMouse => TimedMouseEvent::from_bytes(payload).map(TimedInput::Mouse),
+                _ => TimedKeyEvent::from_bytes(payload).map(TimedInput::Key),
+            };
+            match input {
+                Ok(input) => {
+                    self.input_burst.push(input);
+                    if self.input_burst.is_full() {
+                        self.apply_input();
+                    }
+                }
+                Err(e) => println!("[WARN] ReqID {}: bad {:?} payload: {}", req_id, cmd, e),
+            }
+            return;
+        }
+        let result =
+            match cmd {
+                Command::InputMouse => MouseEvent::from_bytes(payload)
+                    .and_then(|ev| session.injector().inject_mouse(&ev)),
+                _ => KeyEvent::from_bytes(payload)
+                    .and_then(|ev| session.injector().inject_keyboard(&ev)),
+            };
+        match result {
+            // The screen service tells the viewer; one line per event
+            // would flood the log.
+            Ok(()) | Err(TixError::InputBlocked(_)) => {}
+            Err(e) => println!("[WARN] ReqID {}: {:?} injection failed: {}", req_id, cmd, e),
+        }
+    }
+
+    /// Inject the stamped input waiting in the burst, minus the pointer
+    /// moves later input has overtaken. Every event in it already
+    /// passed the gate.
+    fn apply_input(&mut self) {
+        if self.input_burst.is_empty() {
+            return;
+        }
+        let dropped = self.input_burst.dropped();
+        let inputs = self.input_burst.take();
+        if dropped == 0 && self.input_burst.dropped() > 0 {
+            println!("[SCRN] Input is backing up; dropping pointer moves it has overtaken");
+        }
+        let Some(session) = self.input_session() else {
+            return;
+        };
+        for input in inputs {
+            let result = match input {
+                TimedInput::Mouse(mouse) => session.injector().inject_mouse(&mouse.event),
+                TimedInput::Key(key) => session.injector().inject_keyboard(&key.event),
+            };
+            match result {
+                Ok(()) | Err(TixError::InputBlocked(_)) => {}
+                Err(e) => println!("[WARN] Input injection failed: {}", e),
+            }
+        }
+    }
+
+    async fn handle_hello(&mut self, req_id: u64, payload: &[u8]) -> Result<(), TixError> {
+        let wire = match HelloInfo::from_bytes(payload) {
+            Ok(master) => {
+                // The reply and all after it carry the master's nonce.
+                self.follow_session(master.session);
+                let wire = master.negotiated_version();
+                let caps = self.state.negotiate_capabilities(&master.capabilities());
+                println!(
+                    "[CONN] Hello from {} {} on {} ({}, chunks {})",
+                    master.product,
+                    master.version,
+                    master.hostname,
+                    wire,
+                    if caps.compression {
+                        "compressed"
+                    } else {
+                        "raw"
+                    }
+                );
+                wire
+            }
+            Err(e) => {
+                println!("[WARN] ReqID {}: bad Hello payload: {}", req_id, e);
+                WireVersion::V1
+            }
+        };
+        let mut info = HelloInfo::local("tix-slave", selfupdate::CURRENT_VERSION)
+            .with_slave_id(self.slave_id.clone())
+            .with_compression(self.compressor.is_enabled());
+        if let Some(features) = &self.state.local_capabilities().features {
+            info = info.with_features(features);
+        }
+        if let Ok(pkt) = info.into_response_packet(req_id) {
+            let _ = self.conn.send(pkt).await;
+        }
+        // The master reads either version, so the reply may go out in
+        // the new one.
+        self.conn.set_wire_version(wire);
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    /// Swap in a staged build and, if asked, restart into it. The
+    /// response goes out before the restart so the master learns the
+    /// outcome even though the connection is about to drop. Runs as a
+    /// pooled task, since the staged file or the running binary may be
+    /// locked by a transfer.
+    fn handle_update_apply(&mut self, req_id: u64, payload: &[u8]) {
+        let tx: ConnectionSender = self.conn.sender();
+        let payload = payload.to_vec();
+        let locks = self.path_locks.clone();
+
+        let options = self.task_options("UpdateApply");
+        println!("[TASK] Spawning UpdateApply task for ReqID: {}", req_id);
+        if let Err(e) = self.task_pool.spawn_with_options(
+            tx,
+            req_id,
+            payload,
+            |tx, req_id, payload| async move {
+                let req = match UpdateApplyRequest::from_bytes(&payload) {
+                    Ok(req) => req,
+                    Err(e) => {
+                        let response = UpdateApplyResponse::Rejected(UpdateError::Io(format!(
+                            "bad request: {e}"
+                        )));
+                        if let Ok(pkt) = response.into_packet(req_id) {
+                            let _ = tx.send(pkt).await;
+                        }
+                        return;
+                    }
+                };
+                println!(
+                    "[UPDT] ReqID {}: update {} -> {} from {}",
+                    req_id,
+                    selfupdate::CURRENT_VERSION,
+                    req.version,
+                    req.staged_path
+                );
+
+                let result = match std::env::current_exe() {
+                    Ok(exe) => {
+                        let paths = [
+                            (Path::new(&req.staged_path), LockAccess::Exclusive),
+                            (exe.as_path(), LockAccess::Exclusive),
+                        ];
+                        match locks.acquire(req_id, &paths).await {
+                            Ok(_guard) => {
+                                let req = req.clone();
+                                let target = exe.clone();
+                                tokio::task::spawn_blocking(move || {
+                                    selfupdate::apply(&req, selfupdate::CURRENT_VERSION, &target)
+                                })
+                                .await
+                                .unwrap_or_else(|e| Err(UpdateError::Io(e.to_string())))
+                                .map(|()| exe)
+                            }
+                            Err(busy) => Err(UpdateError::Locked(busy.to_string())),
+                        }
+                    }
+                    Err(e) => Err(UpdateError::Io(format!(
+                        "cannot locate running executable: {e}"
+                    ))),
+                };
+
+                let (response, restart_into) = match result {
+                    Ok(exe) => {
+                        println!(
+                            "[UPDT] ReqID {}: {} is now {}",
+                            req_id,
+                            exe.display(),
+                            req.version
+                        );
+                        let response = UpdateApplyResponse::Accepted {
+                            previous_version: selfupdate::CURRENT_VERSION.to_string(),
+                            new_version: req.version.clone(),
+                            restarting: req.restart,
+                        };
+                        (response, req.restart.then_some(exe))
+                    }
+                    Err(e) => {
+                        println!("[ERR ] ReqID {}: update refused: {}", req_id, e);
+                        (UpdateApplyResponse::Rejected(e), None)
+                    }
+                };
+                if let Ok(pkt) = response.into_packet(req_id) {
+                    let _ = tx.send(pkt).await;
+                }
+
+                if let Some(exe) = restart_into {
+                    tokio::time::sleep(UPDATE_RESTART_DELAY).await;
+                    match selfupdate::spawn_replacement(&exe) {
+                        Ok(()) => {
+                            println!("[UPDT] Restarting into {}", exe.display());
+                            std::process::exit(0);
+                        }
+                        Err(e) => println!(
+                            "[ERR ] Could not start the new binary ({}); it will run on next start",
+                            e
+                        ),
+                    }
+                }
+            },
+            options,
+        ) {
+            println!("[ERR ] ReqID {}: {}", req_id, e);
+        }
+    }
+
+    async fn handle_screen_mode(&mut self, req_id: u64, payload: &[u8]) -> Result<(), TixError> {
+        let response = match (ScreenModeRequest::from_bytes(payload), self.input_session()) {
+            (Ok(req), Some(_)) => {
+                for session in self.screens.values() {
+                    session.gate().set_control(req.control);
+                }
+                println!(
+                    "[SCRN] ReqID {}: session is now {}",
+                    req_id,
+                    if req.control {
+                        "in control"
+                    } else {
+                        "view-only"
+                    }
+                );
+                ScreenModeResponse::Applied {
+                    control: req.control,
+                }
+            }
+            (Ok(_), _) => ScreenModeResponse::NoSession,
+            (Err(e), _) => {
+                println!("[WARN] ReqID {}: bad ScreenMode payload: {}", req_id, e);
+                ScreenModeResponse::NoSession
+            }
+        };
+        if let Ok(pkt) = response.into_packet(req_id) {
+            let _ = self.conn.send(pkt).await;
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    /// Switch a session's frames to the dictionary its ack offered.
+    async fn handle_screen_dictionary(
+        &mut self,
+        req_id: u64,
+        payload: &[u8],
+    ) -> Result<(), TixError> {
+        let result = match ScreenDictionaryRequest::from_bytes(payload) {
+            Ok(req) => match self.screens.get(&req.session) {
+                Some(session) => session.enable_dictionary(req.hash).map(|()| req),
+                None => Err(format!("no screen session {}", req.session)),
+            },
+            Err(e) => Err(format!("bad ScreenDictionary payload: {}", e)),
+        };
+        let response = match result {
+            Ok(req) => {
+                println!(
+                    "[SCRN] ReqID {}: session {} frames use dictionary {:016x}",
+                    req_id, req.session, req.hash
+                );
+                ScreenDictionaryResponse::Enabled
+            }
+            Err(e) => {
+                println!("[WARN] ReqID {}: screen dictionary refused: {}", req_id, e);
+                ScreenDictionaryResponse::Refused(e)
+            }
+        };
+        if let Ok(pkt) = response.into_packet(req_id) {
+            let _ = self.conn.send(pkt).await;
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    /// Log the viewer's side of a UDP preflight, and stop the session if
+    /// none of our probes reached it: its frames would not either.
+    async fn handle_screen_preflight(
+        &mut self,
+        req_id: u64,
+        payload: &[u8],
+    ) -> Result<(), TixError> {
+        match ScreenPreflightRequest::from_bytes(payload) {
+            Ok(req) if req.received == 0 => {
+                println!(
+                    "[WARN] ReqID {}: no UDP reached the viewer of session {}, stopping it",
+                    req_id, req.session
+                );
+                if let Some(session) = self.screens.remove(&req.session) {
+                    session.stop().await;
+                }
+            }
+            Ok(req) => println!(
+                "[SCRN] ReqID {}: viewer of session {} got {} probes{}",
+                req_id,
+                req.session,
+                req.received,
+                req.source
+                    .map(|from| format!(" from {}", from))
+                    .unwrap_or_default()
+            ),
+            Err(e) => println!(
+                "[WARN] ReqID {}: bad ScreenPreflight payload: {}",
+                req_id, e
+            ),
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    /// Switch a session between frames and thumbnails. There is no
+    /// reply; the stream shows the switch.
+    fn handle_screen_sharing(&mut self, req_id: u64, payload: &[u8]) {
+        match ScreenSharingRequest::from_bytes(payload) {
+            Ok(req) => match self.screens.get(&req.session) {
+                Some(session) => {
+                    session.switch_sharing(req.low_bandwidth, req.fallback);
+                    println!(
+                        "[SCRN] ReqID {}: session {} switched to {}",
+                        req_id,
+                        req.session,
+                        if req.low_bandwidth {
+                            "thumbnails"
+                        } else {
+                            "frames"
+                        }
+                    );
+                }
+                None => println!(
+                    "[WARN] ReqID {}: no screen session {} to switch",
+                    req_id, req.session
+                ),
+            },
+            Err(e) => println!("[WARN] ReqID {}: bad ScreenSharing payload: {}", req_id, e),
+        }
+        self.state.complete_task(req_id);
+    }
+
+    /// Raise Ctrl+Alt+Del for a viewer in control, or say why not.
+    async fn handle_send_sas(&mut self, req_id: u64) -> Result<(), TixError> {
+        let control = self.input_session().map(|s| s.gate().allows_control());
+        let allowed = self.allow_sas;
+        let response = tokio::task::spawn_blocking(move || sas::send(allowed, control))
+            .await
+            .unwrap_or_else(|e| SasResponse::Refused(SasRefusal::Failed(e.to_string())));
+        match &response {
+            SasResponse::Sent => println!("[SCRN] ReqID {}: sent Ctrl+Alt+Del", req_id),
+            SasResponse::Refused(refusal) => {
+                println!(
+                    "[WARN] ReqID {}: refusing Ctrl+Alt+Del: {}",
+                    req_id, refusal
+                )
+            }
+        }
+        if let Ok(pkt) = response.into_packet(req_id) {
+            let _ = self.conn.send(pkt).await;
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+
+    async fn handle_ping(&mut self, req_id: u64) -> Result<(), TixError> {
+        println!("[PING] Received Ping, sending Pong for ReqID: {}", req_id);
+        let tx: ConnectionSender = self.conn.sender();
+        if let Ok(pkt) = tix_core::Packet::new_response(req_id, Command::Ping, b"Pong".to_vec()) {
+            if let Err(e) = tx.send(pkt).await {
+                println!("[ERR ] ReqID {} failed to send Pong: {}", req_id, e);
+            } else {
+                println!("[SEND] ReqID {} Pong sent", req_id);
+            }
+        }
+        self.state.complete_task(req_id);
+        Ok(())
+    }
+}
+
+// ── Reconnection loop ────────────────────────────────────────────
+
+/// Connect to the master with exponential backoff, then run the main
+/// loop.  On disconnect, reconnect automatically until
+/// `MAX_RECONNECT_ATTEMPTS` consecutive failures. Every connection
+/// serves the same `scheduler`, when there is one.
+pub async fn run_with_reconnect(
+    conn_info: &ConnectionInfo,
+    config: &SlaveConfig,
+    slave_id: &str,
+    audit: &Audit,
+    scheduler: Option<&Scheduler>,
+) -> Result<(), TixError> {
+    let mut consecutive_failures: u32 = 0;
+
+    loop {
+        println!("[INIT] Connecting to Master at {}...", conn_info);
+
+        match TixSlave::connect(conn_info, config).await {
+            Ok(slave) => {
+                let mut slave = slave.with_slave_id(slave_id).with_audit(audit.clone());
+                if let Some(scheduler) = scheduler {
+                    slave = slave.with_scheduler(scheduler.clone());
+                }
+                println!("[CONN] Successfully connected to Master");
+                consecutive_failures = 0;
+
+                // On a task of its own, so a panic in a handler ends
+                // this connection rather than the slave.
+                match tokio::spawn(async move { slave.run().await }).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => println!("[ERR ] Connection loop error: {}", e),
+                    Err(e) if e.is_panic() => println!(
+                        "[CRSH] Connection loop panicked: {}",
+                        crash::panic_message(e.into_panic().as_ref())
+                    ),
+                    Err(e) => println!("[ERR ] Connection loop stopped: {}", e),
+                }
+                // run() returned — connection was lost
+            }
+            Err(e) => {
+                consecutive_failures += 1;
+                println!(
+                    "[FAIL] Connection attempt {}/{} failed: {}",
+                    consecutive_failures, MAX_RECONNECT_ATTEMPTS, e
+                );
+
+                if consecutive_failures >= MAX_RECONNECT_ATTEMPTS {
+                    println!("[FATAL] Max reconnection attempts reached — exiting");
+                    return Err(e);
+                }
+            }
+        }
+
+        // Exponential backoff with cap
+        let backoff = std::cmp::min(
+            RECONNECT_BASE_DELAY * 2u32.saturating_pow(consecutive_failures.min(5)),
+            RECONNECT_MAX_DELAY,
+        );
+        println!("[WAIT] Reconnecting in {:.1}s...", backoff.as_secs_f64());
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Send `packet` to a slave and wait for its `Copy` reply.
+    async fn copy_roundtrip(master: &mut Connection, packet: Packet) -> String {
+        let id = packet.request_id();
+        master.send(packet).await.unwrap();
+        let wait = async {
+            loop {
+                let reply = master.recv().await.expect("slave hung up");
+                if reply.request_id() == id && reply.command().ok() == Some(Command::Copy) {
+                    return String::from_utf8_lossy(reply.payload()).into_owned();
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), wait)
+            .await
+            .expect("no Copy reply")
+    }
+
+    /// Send `request` to a slave and wait for its final reply.
+    async fn shell_roundtrip(
+        master: &mut Connection,
+        request: ShellExecuteRequest,
+        id: u64,
+    ) -> Packet {
+        master.send(request.into_packet(id).unwrap()).await.unwrap();
+        let wait = async {
+            loop {
+                let reply = master.recv().await.expect("slave hung up");
+                if reply.request_id() == id && !reply.flags().contains(ProtocolFlags::STREAMING) {
+                    return reply;
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), wait)
+            .await
+            .expect("no ShellExecute reply")
+    }
+
+    #[tokio::test]
+    async fn copies_paths_with_spaces_over_loopback() {
+        let root = std::env::temp_dir().join(format!("tix copy e2e {}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let src = root.join("Program Files").join("my app.txt");
+        let dest = root.join("new home");
+        std::fs::create_dir_all(src.parent().unwrap()).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(&src, b"hello world").unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let slave = tokio::spawn(async move {
+            let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+            let mut slave = TixSlave::connect(&info, &SlaveConfig::default())
+                .await
+                .unwrap();
+            let _ = slave.run().await;
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut master = Connection::new(stream);
+
+        let req = CopyRequest::new(src.display().to_string(), dest.display().to_string());
+        let reply = copy_roundtrip(&mut master, req.clone().into_packet(1).unwrap()).await;
+        assert!(reply.contains("(11 bytes)"), "{reply}");
+        let copied = dest.join("my app.txt");
+        assert_eq!(std::fs::read(&copied).unwrap(), b"hello world");
+
+        // The copy exists now: refused unless overwriting.
+        std::fs::write(&copied, b"edited").unwrap();
+        let reply = copy_roundtrip(&mut master, req.clone().into_packet(2).unwrap()).await;
+        assert!(reply.contains("already exists"), "{reply}");
+        assert_eq!(std::fs::read(&copied).unwrap(), b"edited");
+
+        let forced = req.with_overwrite(true).into_packet(3).unwrap();
+        let reply = copy_roundtrip(&mut master, forced).await;
+        assert!(reply.starts_with("File"), "{reply}");
+        assert_eq!(std::fs::read(&copied).unwrap(), b"hello world");
+
+        slave.abort();
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn timed_out_shell_is_answered_with_an_error() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let slave = tokio::spawn(async move {
+            let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+            let mut config = SlaveConfig::default();
+            config.tasks.timeout_ms = 1;
+            let mut slave = TixSlave::connect(&info, &config).await.unwrap();
+            let _ = slave.run().await;
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut master = Connection::new(stream);
+
+        #[cfg(windows)]
+        let command = "ping -n 3 127.0.0.1";
+        #[cfg(not(windows))]
+        let command = "sleep 2";
+        let request = Packet::new_command(7, Command::ShellExecute, command.into()).unwrap();
+        master.send(request).await.unwrap();
+
+        let wait = async {
+            loop {
+                let reply = master.recv().await.expect("slave hung up");
+                if reply.request_id() == 7 {
+                    return reply;
+                }
+            }
+        };
+        let reply = tokio::time::timeout(Duration::from_secs(1), wait)
+            .await
+            .expect("no error response");
+        assert!(reply.flags().contains(ProtocolFlags::ERROR));
+        assert_eq!(reply.command().unwrap(), Command::ShellExecute);
+        assert_eq!(
+            TaskFailure::from_payload(reply.payload()),
+            TaskFailure::Timeout { after_ms: 1 }
+        );
+
+        // The shell's own output never follows.
+        let late = async {
+            loop {
+                let pkt = master.recv().await.expect("slave hung up");
+                if pkt.request_id() == 7 {
+                    return pkt;
+                }
+            }
+        };
+        assert!(
+            tokio::time::timeout(Duration::from_millis(2500), late)
+                .await
+                .is_err()
+        );
+
+        slave.abort();
+    }
+
+    #[tokio::test]
+    async fn shell_reports_its_interpreter_and_refuses_unlisted_ones() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let slave = tokio::spawn(async move {
+            let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+            let mut slave = TixSlave::connect(&info, &SlaveConfig::default())
+                .await
+                .unwrap();
+            let _ = slave.run().await;
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut master = Connection::new(stream);
+
+        let reply = shell_roundtrip(&mut master, ShellExecuteRequest::new("echo hi"), 1).await;
+        let exit = ShellExitStatus::from_bytes(reply.payload()).unwrap();
+        assert_eq!(exit.exit_code, 0);
+        assert_eq!(
+            exit.shell,
+            if cfg!(windows) { "cmd /s /c" } else { "sh -c" }
+        );
+
+        let zsh = ShellExecuteRequest::new("echo hi")
+            .with_shell(tix_core::protocol::ShellKind::Custom("zsh".into()));
+        let reply = shell_roundtrip(&mut master, zsh, 2).await;
+        assert!(reply.flags().contains(ProtocolFlags::ERROR));
+        let TaskFailure::Failed(msg) = TaskFailure::from_payload(reply.payload()) else {
+            panic!("{:?}", reply);
+        };
+        assert!(msg.contains("shell `zsh` is not allowed"), "{msg}");
+
+        slave.abort();
+    }
+
+    #[tokio::test]
+    async fn panicking_task_is_answered_and_the_slave_carries_on() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let slave = tokio::spawn(async move {
+            let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+            let mut slave = TixSlave::connect(&info, &SlaveConfig::default())
+                .await
+                .unwrap();
+            // A handler with a parser bug, as ListDir's task.
+            let tx = slave.conn.sender();
+            slave
+                .task_pool
+                .spawn(tx, 9, vec![1, 2, 3], |_tx, _req_id, payload| async move {
+                    let _ = payload[7];
+                })
+                .unwrap();
+            slave.task_commands.insert(9, Command::ListDir);
+            let _ = slave.run().await;
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut master = Connection::new(stream);
+
+        let reply_to = async |master: &mut Connection, id: u64| loop {
+            let reply = master.recv().await.expect("slave hung up");
+            if reply.request_id() == id {
+                return reply;
+            }
+        };
+        let reply = tokio::time::timeout(Duration::from_secs(5), reply_to(&mut master, 9))
+            .await
+            .expect("no error response");
+        assert!(reply.flags().contains(ProtocolFlags::ERROR));
+        assert_eq!(reply.command().unwrap(), Command::ListDir);
+        match TaskFailure::from_payload(reply.payload()) {
+            TaskFailure::Panicked(msg) => assert!(msg.contains("out of bounds"), "{msg}"),
+            other => panic!("expected a panic report, got {other:?}"),
+        }
+
+        // The next command is served as usual.
+        let ping = Packet::new_command(10, Command::Ping, Vec::new()).unwrap();
+        master.send(ping).await.unwrap();
+        let reply = tokio::time::timeout(Duration::from_secs(5), reply_to(&mut master, 10))
+            .await
+            .expect("no Ping reply");
+        assert_eq!(reply.command().unwrap(), Command::Ping);
+        assert!(!reply.flags().contains(ProtocolFlags::ERROR));
+
+        slave.abort();
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn a_new_session_is_echoed_and_drops_the_old_sessions_tasks() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let slave = tokio::spawn(async move {
+            let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+            let mut slave = TixSlave::connect(&info, &SlaveConfig::default())
+                .await
+                .unwrap();
+            slave.conn.set_wire_version(WireVersion::V2);
+            let _ = slave.run().await;
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut master = Connection::new(stream);
+        master.set_wire_version(WireVersion::V2);
+
+        let next_reply = async |master: &mut Connection| {
+            tokio::time::timeout(Duration::from_secs(5), master.recv())
+                .await
+                .expect("no reply")
+                .expect("slave hung up")
+        };
+        // Session 0xA: a shell command that outlives it, then a ping.
+        master.set_session(0xA);
+        let sleep = ShellExecuteRequest::new("sleep 1");
+        master.send(sleep.into_packet(7).unwrap()).await.unwrap();
+        master
+            .send(Packet::new_command(8, Command::Ping, Vec::new()).unwrap())
+            .await
+            .unwrap();
+        let reply = loop {
+            let reply = next_reply(&mut master).await;
+            if reply.request_id() == 8 {
+                break reply;
+            }
+        };
+        assert_eq!(reply.session(), 0xA);
+
+        // Session 0xB: the ping is answered under its nonce and the
+        // shell command is gone.
+        master.set_session(0xB);
+        master
+            .send(Packet::new_command(9, Command::Ping, Vec::new()).unwrap())
+            .await
+            .unwrap();
+        let reply = loop {
+            let reply = next_reply(&mut master).await;
+            assert_ne!(
+                reply.request_id(),
+                7,
+                "old session's task answered: {reply:?}"
+            );
+            if reply.request_id() == 9 {
+                break reply;
+            }
+        };
+        assert_eq!(reply.session(), 0xB);
+        let _ = tokio::time::timeout(Duration::from_millis(1500), async {
+            while let Some(reply) = master.recv().await {
+                assert_ne!(
+                    reply.request_id(),
+                    7,
+                    "old session's task answered: {reply:?}"
+                );
+            }
+        })
+        .await;
+
+        slave.abort();
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn shell_output_reaches_the_master_byte_for_byte() {
+        use futures::StreamExt;
+        use tix_core::client::TixClient;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let slave = tokio::spawn(async move {
+            let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+            let mut slave = TixSlave::connect(&info, &SlaveConfig::default())
+                .await
+                .unwrap();
+            let _ = slave.run().await;
+        });
+        let client = TixClient::accept(&listener).await.unwrap();
+
+        // CP850 "é", a NUL and a byte that is never UTF-8.
+        let mut shell = client
+            .shell("printf '\\202\\000\\377'; printf 'oops' >&2")
+            .await
+            .unwrap();
+        let stdout = shell.next().await.unwrap();
+        assert!(stdout.is_stdout);
+        assert_eq!(stdout.data, [0x82, 0x00, 0xFF]);
+        let stderr = shell.next().await.unwrap();
+        assert!(!stderr.is_stdout);
+        assert_eq!(stderr.data, b"oops");
+        let exit = shell.wait().await.unwrap();
+        assert_eq!(exit.exit_code, 0);
+        assert_eq!(exit.total_chunks, 2);
+        assert_eq!(exit.codepage, 65001);
+
+        slave.abort();
+    }
+
+    #[tokio::test]
+    async fn client_drives_slave_over_loopback() {
+        use futures::StreamExt;
+        use tix_core::client::TixClient;
+
+        let root = std::env::temp_dir().join(format!("tix client e2e {}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        let original = root.join("original.bin");
+        let contents: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&original, &contents).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let slave = tokio::spawn(async move {
+            let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+            let mut slave = TixSlave::connect(&info, &SlaveConfig::default())
+                .await
+                .unwrap();
+            let _ = slave.run().await;
+        });
+        let client = TixClient::accept(&listener).await.unwrap();
+
+        client.ping().await.unwrap();
+
+        let remote = root.join("uploaded.bin").display().to_string();
+        let up = client.upload(&original, &remote).await.unwrap();
+        assert_eq!(
+            (up.path.as_str(), up.bytes),
+            (remote.as_str(), contents.len() as u64)
+        );
+
+        // Listing, shell and download all in flight together.
+        let back = root.join("sub").join("downloaded.bin");
+        let dir = root.display().to_string();
+        let (listing, shell, down) = tokio::join!(
+            client.list_dir(&dir),
+            client.shell("echo hello"),
+            client.download(&remote, &back),
+        );
+        let mut names: Vec<String> = listing.unwrap().into_iter().map(|e| e.name).collect();
+        names.sort();
+        assert_eq!(names, ["original.bin", "sub", "uploaded.bin"]);
+        let down = down.unwrap();
+        assert_eq!(down.hash, up.hash);
+        assert_eq!(std::fs::read(&back).unwrap(), contents);
+
+        let mut shell = shell.unwrap();
+        let first = shell.next().await.unwrap();
+        assert!(first.is_stdout);
+        assert!(String::from_utf8_lossy(&first.data).contains("hello"));
+        assert_eq!(shell.wait().await.unwrap().exit_code, 0);
+
+        let missing = root.join("missing.bin").display().to_string();
+        assert!(
+            client
+                .download(&missing, root.join("never.bin"))
+                .await
+                .is_err()
+        );
+        assert!(!root.join("never.bin").exists());
+        assert_eq!(client.pending(), 0);
+
+        slave.abort();
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn recursive_copy_does_not_follow_a_link_loop() {
+        let root = std::env::temp_dir().join(format!("tix-copy-loop-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let src = root.join("src");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("sub").join("a.txt"), b"abc").unwrap();
+
+        // A link from inside the tree back to its top.
+        let link = src.join("sub").join("loop");
+        #[cfg(unix)]
+        let linked = std::os::unix::fs::symlink(&src, &link).is_ok();
+        #[cfg(windows)]
+        let linked = std::process::Command::new("cmd")
+            .args(["/c", "mklink", "/J"])
+            .arg(&link)
+            .arg(&src)
+            .status()
+            .is_ok_and(|s| s.success());
+        if !linked {
+            eprintln!("skipped: cannot create a directory link here");
+            let _ = std::fs::remove_dir_all(&root);
+            return;
+        }
+
+        let dest = root.join("dest");
+        let req = CopyRequest::new(src.display().to_string(), dest.display().to_string())
+            .with_recursive(true);
+        let reply = perform_robust_copy(&req).await.unwrap();
+        assert!(
+            reply.contains("(3 bytes, 1 linked folder(s) skipped)"),
+            "{reply}"
+        );
+        assert_eq!(
+            std::fs::read(dest.join("sub").join("a.txt")).unwrap(),
+            b"abc"
+        );
+        assert!(std::fs::symlink_metadata(dest.join("sub").join("loop")).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn empty_directories_survive_a_copy() {
+        let root = std::env::temp_dir().join(format!("tix empty copy {}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let src = root.join("skeleton");
+        std::fs::create_dir_all(src.join("a").join("deeper")).unwrap();
+        std::fs::create_dir_all(src.join("b")).unwrap();
+        let lonely = root.join("lonely");
+        std::fs::create_dir_all(&lonely).unwrap();
+        let shelf = root.join("shelf");
+        std::fs::create_dir_all(&shelf).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let slave = tokio::spawn(async move {
+            let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+            let mut slave = TixSlave::connect(&info, &SlaveConfig::default())
+                .await
+                .unwrap();
+            let _ = slave.run().await;
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut master = Connection::new(stream);
+
+        // Into a new path: the copy takes that name.
+        let fresh = root.join("fresh");
+        let req = CopyRequest::new(src.display().to_string(), fresh.display().to_string())
+            .with_recursive(true);
+        let reply = copy_roundtrip(&mut master, req.into_packet(1).unwrap()).await;
+        assert!(reply.contains("(0 bytes)"), "{reply}");
+        assert!(fresh.join("a").join("deeper").is_dir());
+        assert!(fresh.join("b").is_dir());
+
+        // Into an existing directory: the copy lands inside it.
+        for (id, dir) in [(2, &src), (3, &lonely)] {
+            let req = CopyRequest::new(dir.display().to_string(), shelf.display().to_string())
+                .with_recursive(true);
+            let reply = copy_roundtrip(&mut master, req.into_packet(id).unwrap()).await;
+            assert!(reply.starts_with("Directory"), "{reply}");
+        }
+        assert!(shelf.join("skeleton").join("a").join("deeper").is_dir());
+        assert!(shelf.join("lonely").is_dir());
+        assert_eq!(std::fs::read_dir(shelf.join("lonely")).unwrap().count(), 0);
+
+        slave.abort();
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn zero_byte_files_and_empty_listings_over_loopback() {
+        use tix_core::client::TixClient;
+
+        let root = std::env::temp_dir().join(format!("tix empty e2e {}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("nothing here")).unwrap();
+        let empty = root.join("empty.txt");
+        std::fs::write(&empty, b"").unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let slave = tokio::spawn(async move {
+            let info = ConnectionInfo::new("127.0.0.1".to_string(), port);
+            let mut slave = TixSlave::connect(&info, &SlaveConfig::default())
+                .await
+                .unwrap();
+            let _ = slave.run().await;
+        });
+        let client = TixClient::accept(&listener).await.unwrap();
+
+        let remote = root.join("uploaded.txt").display().to_string();
+        let up = client.upload(&empty, &remote).await.unwrap();
+        assert_eq!(up.bytes, 0);
+        assert_eq!(std::fs::read(&remote).unwrap(), b"");
+
+        let back = root.join("downloaded.txt");
+        let down = client.download(&remote, &back).await.unwrap();
+        assert_eq!((down.bytes, down.hash), (0, up.hash));
+        assert_eq!(std::fs::read(&back).unwrap(), b"");
+
+        let listing = client
+            .list_dir(&root.join("nothing here").display().to_string())
+            .await
+            .unwrap();
+        assert!(listing.is_empty(), "{listing:?}");
+        assert_eq!(client.pending(), 0);
+
+        slave.abort();
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn legacy_text_payload_still_parses() {
+        let req = parse_copy_payload(br#""C:\a.txt" C:\backup"#).unwrap();
+        assert_eq!(req.src, r"C:\a.txt");
+        assert_eq!(req.dest, r"C:\backup");
+        assert!(req.overwrite && req.recursive);
+        assert!(parse_copy_payload(b"lonely").is_none());
+    }
+}
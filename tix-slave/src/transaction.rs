@@ -0,0 +1,509 @@
+//! `FileTransaction`: run a list of file operations as one, undoing the
+//! finished ones when a later one fails.
+//!
+//! Each step that succeeds leaves an [`Undo`] in the journal: the
+//! directories it created, the copy it made, where it moved something
+//! from, or the Recycle Bin item it made. When a step fails, whatever
+//! it had half done is cleaned up and the journal is played back last
+//! first. Copies and moves refuse to replace anything, so undoing them
+//! never has to bring back what was overwritten. A delete that went
+//! past the Recycle Bin cannot be undone and is reported as such.
+//!
+//! The caller holds the path locks (see [`crate::locks`]); everything
+//! here blocks on the filesystem and is meant for
+//! `tokio::task::spawn_blocking`.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tix_core::protocol::{
+    DeleteOutcome, FileDeleteRequest, FileTransactionRequest, FileTransactionResponse, StepOutcome,
+    StepResult, TransactionOp, TrashRestoreResponse,
+};
+
+use crate::{locks, slave, trash};
+
+/// Run every step of `req`, or as few as could be undone.
+pub fn run(req: &FileTransactionRequest) -> FileTransactionResponse {
+    if let Err(e) = req.validate() {
+        return FileTransactionResponse::refused(&req.ops, e);
+    }
+
+    let mut outcomes = vec![StepOutcome::Skipped; req.ops.len()];
+    let mut journal = Vec::with_capacity(req.ops.len());
+    for (i, op) in req.ops.iter().enumerate() {
+        match apply(op) {
+            Ok(undo) => {
+                outcomes[i] = StepOutcome::Done;
+                journal.push(undo);
+            }
+            Err(e) => {
+                outcomes[i] = StepOutcome::Failed(e);
+                for (j, undo) in journal.into_iter().enumerate().rev() {
+                    outcomes[j] = undo.revert();
+                }
+                break;
+            }
+        }
+    }
+
+    FileTransactionResponse {
+        steps: req
+            .ops
+            .iter()
+            .cloned()
+            .zip(outcomes)
+            .map(|(op, outcome)| StepResult { op, outcome })
+            .collect(),
+        error: None,
+    }
+}
+
+// ── Steps ────────────────────────────────────────────────────────
+
+/// How to take back one finished step.
+#[derive(Debug)]
+enum Undo {
+    /// Remove the directories a mkdir created, outermost first in the
+    /// list, so they are removed from the end.
+    RemoveDirs(Vec<PathBuf>),
+    /// Remove what a copy created.
+    RemoveCopy(PathBuf),
+    /// Move `to` back to `from`.
+    MoveBack { from: PathBuf, to: PathBuf },
+    /// Take the item recycled from this path back out of the bin.
+    Restore(String),
+    /// Deleted for good; nothing to do.
+    Irreversible,
+}
+
+impl Undo {
+    fn revert(self) -> StepOutcome {
+        let result = match self {
+            Undo::RemoveDirs(dirs) => dirs
+                .iter()
+                .rev()
+                .try_for_each(|dir| tolerate_missing(std::fs::remove_dir(dir), dir)),
+            Undo::RemoveCopy(path) => tolerate_missing(remove(&path), &path),
+            Undo::MoveBack { from, to } => {
+                if exists(&from) {
+                    Err(format!("'{}' exists again", from.display()))
+                } else {
+                    move_path(&to, &from).map_err(|e| describe(&to, e))
+                }
+            }
+            Undo::Restore(path) => match trash::restore(&path) {
+                TrashRestoreResponse::Restored { .. } => Ok(()),
+                TrashRestoreResponse::NotFound(e)
+                | TrashRestoreResponse::Unsupported(e)
+                | TrashRestoreResponse::Failed(e) => Err(e),
+            },
+            Undo::Irreversible => return StepOutcome::Irreversible,
+        };
+        match result {
+            Ok(()) => StepOutcome::RolledBack,
+            Err(e) => StepOutcome::RollbackFailed(e),
+        }
+    }
+}
+
+/// Run one step, returning how to undo it.
+fn apply(op: &TransactionOp) -> Result<Undo, String> {
+    match op {
+        TransactionOp::Mkdir { path } => mkdir(Path::new(path)),
+        TransactionOp::Copy { src, dest } => copy(Path::new(src), Path::new(dest)),
+        TransactionOp::Move { src, dest } => {
+            let (src, dest) = (Path::new(src), Path::new(dest));
+            check_source_and_target(src, dest)?;
+            move_path(src, dest).map_err(|e| describe(src, e))?;
+            Ok(Undo::MoveBack {
+                from: src.to_path_buf(),
+                to: dest.to_path_buf(),
+            })
+        }
+        TransactionOp::Delete { path, mode } => {
+            let req = FileDeleteRequest::new(vec![path.clone()]).with_mode(*mode);
+            let response = trash::delete(&req);
+            match response.items.into_iter().next().map(|item| item.outcome) {
+                Some(DeleteOutcome::Recycled) => Ok(Undo::Restore(path.clone())),
+                Some(DeleteOutcome::Deleted) => Ok(Undo::Irreversible),
+                Some(DeleteOutcome::Failed(e)) => Err(format!("{}: {}", path, e)),
+                None => Err(format!("{}: nothing was deleted", path)),
+            }
+        }
+    }
+}
+
+fn mkdir(path: &Path) -> Result<Undo, String> {
+    if path.is_dir() {
+        return Ok(Undo::RemoveDirs(Vec::new()));
+    }
+    if exists(path) {
+        return Err(format!(
+            "'{}' already exists and is not a directory",
+            path.display()
+        ));
+    }
+    // Outermost first: the missing ancestors, then the path itself.
+    let mut created: Vec<PathBuf> = path
+        .ancestors()
+        .take_while(|dir| !dir.as_os_str().is_empty() && !exists(dir))
+        .map(Path::to_path_buf)
+        .collect();
+    created.reverse();
+    for (i, dir) in created.iter().enumerate() {
+        if let Err(e) = std::fs::create_dir(dir) {
+            for made in created[..i].iter().rev() {
+                let _ = std::fs::remove_dir(made);
+            }
+            return Err(describe(dir, e));
+        }
+    }
+    Ok(Undo::RemoveDirs(created))
+}
+
+fn copy(src: &Path, dest: &Path) -> Result<Undo, String> {
+    check_source_and_target(src, dest)?;
+    if src.is_dir() {
+        if locks::normalize(dest).starts_with(locks::normalize(src)) {
+            return Err(format!("cannot copy '{}' into itself", src.display()));
+        }
+        std::fs::create_dir(dest).map_err(|e| describe(dest, e))?;
+        if let Err(e) = slave::copy_tree(src, dest, false) {
+            let _ = std::fs::remove_dir_all(dest);
+            return Err(describe(src, e));
+        }
+    } else if let Err(e) = std::fs::copy(src, dest) {
+        let _ = std::fs::remove_file(dest);
+        return Err(describe(src, e));
+    }
+    Ok(Undo::RemoveCopy(dest.to_path_buf()))
+}
+
+/// `src` must be there and `dest` must not, so nothing is replaced.
+fn check_source_and_target(src: &Path, dest: &Path) -> Result<(), String> {
+    if !exists(src) {
+        return Err(format!("'{}' does not exist", src.display()));
+    }
+    if exists(dest) {
+        return Err(format!("'{}' already exists", dest.display()));
+    }
+    Ok(())
+}
+
+// ── Filesystem ───────────────────────────────────────────────────
+
+/// Rename `src` to `dest`, copying and removing when they are on
+/// different volumes.
+fn move_path(src: &Path, dest: &Path) -> io::Result<()> {
+    match std::fs::rename(src, dest) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        result => return result,
+    }
+    // The original goes only once the copy is whole.
+    let copied = if src.is_dir() {
+        std::fs::create_dir(dest).and_then(|()| slave::copy_tree(src, dest, false).map(|_| ()))
+    } else {
+        std::fs::copy(src, dest).map(|_| ())
+    };
+    if let Err(e) = copied {
+        let _ = remove(dest);
+        return Err(e);
+    }
+    remove(src)
+}
+
+/// Remove a file, or a directory and everything in it.
+fn remove(path: &Path) -> io::Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+/// Whether anything is at `path`, a dangling link included.
+fn exists(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok()
+}
+
+/// An undo whose target is already gone has nothing left to do.
+fn tolerate_missing(result: io::Result<()>, path: &Path) -> Result<(), String> {
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(describe(path, e)),
+        _ => Ok(()),
+    }
+}
+
+fn describe(path: &Path, e: io::Error) -> String {
+    format!("{}: {}", path.display(), e)
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use tix_core::protocol::{DeleteMode, MAX_TRANSACTION_OPS};
+    use tix_core::testing::temp_dir;
+
+    use super::*;
+
+    /// A scratch directory holding `a.txt`, `b.txt` and `old/c.txt`.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = temp_dir(&format!("tx-{name}"));
+        std::fs::create_dir(dir.join("old")).unwrap();
+        std::fs::write(dir.join("a.txt"), b"alpha").unwrap();
+        std::fs::write(dir.join("b.txt"), b"beta").unwrap();
+        std::fs::write(dir.join("old").join("c.txt"), b"gamma").unwrap();
+        dir
+    }
+
+    /// Every path under `dir` with the contents of its files.
+    fn snapshot(dir: &Path) -> BTreeMap<PathBuf, Option<Vec<u8>>> {
+        let mut out = BTreeMap::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(next) = pending.pop() {
+            for entry in std::fs::read_dir(&next).unwrap() {
+                let path = entry.unwrap().path();
+                let rel = path.strip_prefix(dir).unwrap().to_path_buf();
+                if path.is_dir() {
+                    out.insert(rel, None);
+                    pending.push(path);
+                } else {
+                    out.insert(rel, Some(std::fs::read(&path).unwrap()));
+                }
+            }
+        }
+        out
+    }
+
+    fn s(path: PathBuf) -> String {
+        path.display().to_string()
+    }
+
+    /// Create `new/inner`, copy `b.txt` into it, move `a.txt` and
+    /// `old/c.txt` there, and recycle the emptied `old`.
+    fn migration(dir: &Path) -> Vec<TransactionOp> {
+        let new = dir.join("new").join("inner");
+        vec![
+            TransactionOp::Mkdir {
+                path: s(new.clone()),
+            },
+            TransactionOp::Copy {
+                src: s(dir.join("b.txt")),
+                dest: s(new.join("b.txt")),
+            },
+            TransactionOp::Move {
+                src: s(dir.join("a.txt")),
+                dest: s(new.join("a.txt")),
+            },
+            TransactionOp::Move {
+                src: s(dir.join("old").join("c.txt")),
+                dest: s(new.join("c.txt")),
+            },
+            TransactionOp::Copy {
+                src: s(dir.join("new")),
+                dest: s(dir.join("backup")),
+            },
+        ]
+    }
+
+    /// A step that always fails: moving something that is not there.
+    fn doomed(dir: &Path) -> TransactionOp {
+        TransactionOp::Move {
+            src: s(dir.join("missing")),
+            dest: s(dir.join("anywhere")),
+        }
+    }
+
+    #[test]
+    fn every_step_runs_when_none_fails() {
+        let dir = scratch("commit");
+        let new = dir.join("new").join("inner");
+        let response = run(&FileTransactionRequest::new(migration(&dir)));
+        assert!(response.committed(), "{:?}", response);
+        assert_eq!(std::fs::read(new.join("a.txt")).unwrap(), b"alpha");
+        assert_eq!(std::fs::read(new.join("b.txt")).unwrap(), b"beta");
+        assert_eq!(std::fs::read(new.join("c.txt")).unwrap(), b"gamma");
+        assert!(dir.join("b.txt").exists());
+        assert!(!dir.join("a.txt").exists());
+        assert!(dir.join("backup").join("inner").join("c.txt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_failure_at_any_step_leaves_the_tree_as_it_was() {
+        let steps = migration(Path::new("/")).len();
+        for at in 0..=steps {
+            let dir = scratch(&format!("fail{}", at));
+            let before = snapshot(&dir);
+            let mut ops = migration(&dir);
+            ops.insert(at, doomed(&dir));
+
+            let response = run(&FileTransactionRequest::new(ops));
+            assert!(!response.committed());
+            assert_eq!(response.failed_step().map(|(i, _)| i), Some(at));
+            assert!(
+                response.failed_step().unwrap().1.contains("does not exist"),
+                "{:?}",
+                response
+            );
+            for (i, step) in response.steps.iter().enumerate() {
+                let expected = match i {
+                    i if i < at => StepOutcome::RolledBack,
+                    i if i > at => StepOutcome::Skipped,
+                    _ => continue,
+                };
+                assert_eq!(step.outcome, expected, "step {} failing at {}", i, at);
+            }
+            assert_eq!(response.left_in_effect(), 0);
+            assert_eq!(snapshot(&dir), before, "failing at {}", at);
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn a_step_whose_target_is_taken_undoes_the_ones_before_it() {
+        let steps = migration(Path::new("/")).len();
+        for at in 0..steps {
+            let dir = scratch(&format!("taken{}", at));
+            let ops = migration(&dir);
+            // Put a file where step `at` wants to create something.
+            let target = match &ops[at] {
+                TransactionOp::Mkdir { path } => path,
+                TransactionOp::Copy { dest, .. } | TransactionOp::Move { dest, .. } => dest,
+                TransactionOp::Delete { .. } => unreachable!(),
+            };
+            std::fs::create_dir_all(Path::new(target).parent().unwrap()).unwrap();
+            std::fs::write(target, b"taken").unwrap();
+            let before = snapshot(&dir);
+
+            let response = run(&FileTransactionRequest::new(ops));
+            assert_eq!(response.failed_step().map(|(i, _)| i), Some(at));
+            assert_eq!(response.left_in_effect(), 0, "{:?}", response);
+            assert_eq!(snapshot(&dir), before, "failing at {}", at);
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn steps_refuse_to_replace_anything() {
+        let dir = scratch("replace");
+        let before = snapshot(&dir);
+        for op in [
+            TransactionOp::Move {
+                src: s(dir.join("a.txt")),
+                dest: s(dir.join("b.txt")),
+            },
+            TransactionOp::Copy {
+                src: s(dir.join("a.txt")),
+                dest: s(dir.join("b.txt")),
+            },
+            TransactionOp::Copy {
+                src: s(dir.join("old")),
+                dest: s(dir.join("old").join("nested")),
+            },
+            TransactionOp::Mkdir {
+                path: s(dir.join("a.txt")),
+            },
+        ] {
+            let response = run(&FileTransactionRequest::new(vec![op.clone()]));
+            assert_eq!(response.failed_step().map(|(i, _)| i), Some(0), "{}", op);
+        }
+        assert_eq!(snapshot(&dir), before);
+
+        // A directory that is already there is fine, and stays on undo.
+        let ops = vec![
+            TransactionOp::Mkdir {
+                path: s(dir.join("old")),
+            },
+            doomed(&dir),
+        ];
+        let response = run(&FileTransactionRequest::new(ops));
+        assert_eq!(response.steps[0].outcome, StepOutcome::RolledBack);
+        assert_eq!(snapshot(&dir), before);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn permanent_deletes_are_reported_not_undone() {
+        let dir = scratch("delete");
+        let ops = vec![
+            TransactionOp::Move {
+                src: s(dir.join("a.txt")),
+                dest: s(dir.join("old").join("a.txt")),
+            },
+            TransactionOp::Delete {
+                path: s(dir.join("b.txt")),
+                mode: DeleteMode::Permanent,
+            },
+            TransactionOp::Delete {
+                path: s(dir.join("old")),
+                mode: DeleteMode::Permanent,
+            },
+            doomed(&dir),
+        ];
+        let response = run(&FileTransactionRequest::new(ops));
+        assert_eq!(response.steps[2].outcome, StepOutcome::Irreversible);
+        assert_eq!(response.steps[1].outcome, StepOutcome::Irreversible);
+        // The moved file went with `old`, so there is nothing to move back.
+        assert!(matches!(
+            response.steps[0].outcome,
+            StepOutcome::RollbackFailed(_)
+        ));
+        assert_eq!(response.left_in_effect(), 3);
+        assert!(!dir.join("b.txt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn recycled_deletes_come_back_where_there_is_a_bin() {
+        let dir = scratch("recycle");
+        let before = snapshot(&dir);
+        let ops = vec![
+            TransactionOp::Delete {
+                path: s(dir.join("old")),
+                mode: DeleteMode::Recycle,
+            },
+            doomed(&dir),
+        ];
+        let response = run(&FileTransactionRequest::new(ops));
+        if cfg!(windows) {
+            assert_eq!(response.steps[0].outcome, StepOutcome::RolledBack);
+            assert_eq!(snapshot(&dir), before);
+        } else {
+            // No Recycle Bin: the delete fell back to a permanent one.
+            assert_eq!(response.steps[0].outcome, StepOutcome::Irreversible);
+            assert!(!dir.join("old").exists());
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn oversized_and_empty_transactions_are_refused_whole() {
+        let dir = scratch("limits");
+        let before = snapshot(&dir);
+        let mkdirs: Vec<_> = (0..=MAX_TRANSACTION_OPS)
+            .map(|i| TransactionOp::Mkdir {
+                path: s(dir.join(format!("d{}", i))),
+            })
+            .collect();
+        let response = run(&FileTransactionRequest::new(mkdirs));
+        assert!(response.error.is_some());
+        assert!(
+            response
+                .steps
+                .iter()
+                .all(|step| step.outcome == StepOutcome::Skipped)
+        );
+        assert_eq!(snapshot(&dir), before);
+        assert!(
+            run(&FileTransactionRequest::new(Vec::new()))
+                .error
+                .is_some()
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
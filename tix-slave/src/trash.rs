@@ -0,0 +1,358 @@
+//! Remote delete with Recycle Bin support, and restore from it.
+//!
+//! Recycling goes through `SHFileOperationW` with `FOF_ALLOWUNDO`, the
+//! same path Explorer's Delete key takes. Restoring reads the bin's
+//! `$I` metadata files directly to find the most recent item that came
+//! from a given path and moves its `$R` twin back. Both block on the
+//! filesystem and are meant for `tokio::task::spawn_blocking`.
+
+use std::path::Path;
+
+use tix_core::protocol::{
+    DeleteMode, DeleteOutcome, DeletedItem, FileDeleteRequest, FileDeleteResponse,
+    TrashRestoreResponse,
+};
+
+// ── Delete ───────────────────────────────────────────────────────
+
+/// Delete every path in `req`, recycling unless the master asked for a
+/// permanent delete.
+pub fn delete(req: &FileDeleteRequest) -> FileDeleteResponse {
+    let mut note = None;
+
+    #[cfg(not(windows))]
+    if req.mode == DeleteMode::Recycle {
+        note = Some(
+            "Recycle Bin is not available on this platform; items were deleted permanently"
+                .to_string(),
+        );
+    }
+
+    let items = req
+        .paths
+        .iter()
+        .map(|path| DeletedItem {
+            path: path.clone(),
+            outcome: delete_one(Path::new(path), req.mode, &mut note),
+        })
+        .collect();
+
+    FileDeleteResponse {
+        requested: req.mode,
+        items,
+        note,
+    }
+}
+
+fn delete_one(path: &Path, mode: DeleteMode, note: &mut Option<String>) -> DeleteOutcome {
+    if std::fs::symlink_metadata(path).is_err() {
+        return DeleteOutcome::Failed("path does not exist".to_string());
+    }
+
+    #[cfg(windows)]
+    if mode == DeleteMode::Recycle {
+        return match win::recycle(path) {
+            Ok(()) => DeleteOutcome::Recycled,
+            Err(e) => DeleteOutcome::Failed(e),
+        };
+    }
+
+    // Only reachable for recycle requests off Windows; the note is
+    // already set by `delete`.
+    let _ = (mode, note);
+
+    match remove_permanently(path) {
+        Ok(()) => DeleteOutcome::Deleted,
+        Err(e) => DeleteOutcome::Failed(e.to_string()),
+    }
+}
+
+fn remove_permanently(path: &Path) -> std::io::Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+// ── Restore ──────────────────────────────────────────────────────
+
+/// Put back the most recently recycled item that lived at `original`.
+#[cfg(windows)]
+pub fn restore(original: &str) -> TrashRestoreResponse {
+    let Some(found) = win::find_latest(original) else {
+        return TrashRestoreResponse::NotFound(format!(
+            "nothing from '{}' in the Recycle Bin",
+            original
+        ));
+    };
+
+    let target = Path::new(original);
+    if target.exists() {
+        return TrashRestoreResponse::Failed(format!(
+            "'{}' already exists; move it away first",
+            original
+        ));
+    }
+    if let Some(parent) = target.parent()
+        && let Err(e) = std::fs::create_dir_all(parent)
+    {
+        return TrashRestoreResponse::Failed(format!("{}: {}", parent.display(), e));
+    }
+    if let Err(e) = std::fs::rename(&found.data_file, target) {
+        return TrashRestoreResponse::Failed(format!("{}: {}", found.data_file.display(), e));
+    }
+    // The item is back either way; a stale $I only leaves a ghost entry.
+    let _ = std::fs::remove_file(&found.info_file);
+
+    TrashRestoreResponse::Restored {
+        path: original.to_string(),
+    }
+}
+
+/// Put back the most recently recycled item that lived at `original`.
+#[cfg(not(windows))]
+pub fn restore(original: &str) -> TrashRestoreResponse {
+    TrashRestoreResponse::Unsupported(format!(
+        "cannot restore '{}': Recycle Bin is only available on Windows",
+        original
+    ))
+}
+
+// ── $I metadata ──────────────────────────────────────────────────
+
+/// Contents of a Recycle Bin `$I` file.
+#[cfg_attr(not(windows), allow(dead_code))]
+#[derive(Debug, PartialEq)]
+struct RecycleInfo {
+    /// Path the item was deleted from.
+    original_path: String,
+    /// Deletion time as a Windows FILETIME.
+    deleted_at: u64,
+}
+
+/// Parse a `$I` file: Vista–8.1 use version 1 with a fixed 260-wchar
+/// path, Windows 10+ use version 2 with a length-prefixed path.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_info(bytes: &[u8]) -> Option<RecycleInfo> {
+    let read_u64 = |at: usize| -> Option<u64> {
+        Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+    };
+
+    let version = read_u64(0)?;
+    let deleted_at = read_u64(16)?;
+    let name_bytes = match version {
+        1 => bytes.get(24..24 + 520)?,
+        2 => {
+            let chars = u32::from_le_bytes(bytes.get(24..28)?.try_into().ok()?) as usize;
+            bytes.get(28..28 + chars * 2)?
+        }
+        _ => return None,
+    };
+
+    let wide: Vec<u16> = name_bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&c| c != 0)
+        .collect();
+
+    Some(RecycleInfo {
+        original_path: String::from_utf16(&wide).ok()?,
+        deleted_at,
+    })
+}
+
+/// Windows paths compare case-insensitively and accept either slash.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn same_path(a: &str, b: &str) -> bool {
+    let norm = |s: &str| s.replace('/', "\\").trim_end_matches('\\').to_lowercase();
+    norm(a) == norm(b)
+}
+
+// ── Windows shell access ─────────────────────────────────────────
+
+#[cfg(windows)]
+mod win {
+    use std::path::{Path, PathBuf};
+
+    use windows::Win32::UI::Shell::{
+        FO_DELETE, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI, FOF_SILENT, SHFILEOPSTRUCTW,
+        SHFileOperationW,
+    };
+    use windows::core::PCWSTR;
+
+    use super::{RecycleInfo, parse_info, same_path};
+
+    /// Send `path` to the Recycle Bin.
+    pub fn recycle(path: &Path) -> Result<(), String> {
+        // pFrom is a list of NUL-terminated names ending in an empty one.
+        let mut from: Vec<u16> = path.to_string_lossy().encode_utf16().collect();
+        from.extend([0, 0]);
+
+        let mut op = SHFILEOPSTRUCTW {
+            wFunc: FO_DELETE,
+            pFrom: PCWSTR(from.as_ptr()),
+            fFlags: (FOF_ALLOWUNDO.0 | FOF_NOCONFIRMATION.0 | FOF_SILENT.0 | FOF_NOERRORUI.0)
+                as u16,
+            ..Default::default()
+        };
+
+        let status = unsafe { SHFileOperationW(&mut op) };
+        if status != 0 {
+            return Err(format!("SHFileOperation failed with code {:#x}", status));
+        }
+        if op.fAnyOperationsAborted.as_bool() {
+            return Err("operation was aborted".to_string());
+        }
+        Ok(())
+    }
+
+    /// A recycled item: its `$I` metadata file and `$R` data file.
+    pub struct Found {
+        pub info_file: PathBuf,
+        pub data_file: PathBuf,
+        info: RecycleInfo,
+    }
+
+    /// Newest Recycle Bin entry whose original path is `original`.
+    ///
+    /// Every volume keeps its own `$Recycle.Bin` with one folder per user
+    /// SID; folders we cannot read (other users) are skipped.
+    pub fn find_latest(original: &str) -> Option<Found> {
+        let root: String = original.chars().take(3).collect();
+        let bin = Path::new(&root).join("$Recycle.Bin");
+
+        let mut best: Option<Found> = None;
+        for sid_dir in std::fs::read_dir(bin).ok()?.flatten() {
+            let Ok(entries) = std::fs::read_dir(sid_dir.path()) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let Some(suffix) = name.strip_prefix("$I") else {
+                    continue;
+                };
+                let Some(info) = std::fs::read(entry.path())
+                    .ok()
+                    .and_then(|b| parse_info(&b))
+                else {
+                    continue;
+                };
+                if !same_path(&info.original_path, original) {
+                    continue;
+                }
+                let data_file = sid_dir.path().join(format!("$R{}", suffix));
+                if !data_file.exists() {
+                    continue;
+                }
+                if best
+                    .as_ref()
+                    .is_none_or(|b| info.deleted_at > b.info.deleted_at)
+                {
+                    best = Some(Found {
+                        info_file: entry.path(),
+                        data_file,
+                        info,
+                    });
+                }
+            }
+        }
+        best
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info_v2(path: &str, deleted_at: u64) -> Vec<u8> {
+        let wide: Vec<u16> = path.encode_utf16().chain(std::iter::once(0)).collect();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+        bytes.extend_from_slice(&1234u64.to_le_bytes());
+        bytes.extend_from_slice(&deleted_at.to_le_bytes());
+        bytes.extend_from_slice(&(wide.len() as u32).to_le_bytes());
+        bytes.extend(wide.iter().flat_map(|c| c.to_le_bytes()));
+        bytes
+    }
+
+    #[test]
+    fn parse_info_v2() {
+        let info = parse_info(&info_v2("C:\\Users\\me\\notes.txt", 42)).unwrap();
+        assert_eq!(info.original_path, "C:\\Users\\me\\notes.txt");
+        assert_eq!(info.deleted_at, 42);
+    }
+
+    #[test]
+    fn parse_info_v1() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&7u64.to_le_bytes());
+        let mut name = [0u16; 260];
+        for (slot, c) in name.iter_mut().zip("D:\\old.log".encode_utf16()) {
+            *slot = c;
+        }
+        bytes.extend(name.iter().flat_map(|c| c.to_le_bytes()));
+
+        let info = parse_info(&bytes).unwrap();
+        assert_eq!(info.original_path, "D:\\old.log");
+        assert_eq!(info.deleted_at, 7);
+    }
+
+    #[test]
+    fn parse_info_rejects_truncated_or_unknown() {
+        let full = info_v2("C:\\a", 1);
+        assert!(parse_info(&full[..full.len() - 2]).is_none());
+        let mut unknown = full.clone();
+        unknown[0] = 9;
+        assert!(parse_info(&unknown).is_none());
+    }
+
+    #[test]
+    fn same_path_ignores_case_and_slashes() {
+        assert!(same_path("C:\\Users\\Me\\File.txt", "c:/users/me/file.TXT"));
+        assert!(!same_path("C:\\a\\b", "C:\\a\\c"));
+    }
+
+    #[test]
+    fn permanent_delete_removes_tree() {
+        let dir = std::env::temp_dir().join(format!("tix-trash-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested").join("f.txt"), b"x").unwrap();
+        let missing = dir.join("missing");
+
+        let req = FileDeleteRequest::new(vec![
+            dir.to_string_lossy().to_string(),
+            missing.to_string_lossy().to_string(),
+        ])
+        .with_mode(DeleteMode::Permanent);
+        let resp = delete(&req);
+
+        assert!(!dir.exists());
+        assert_eq!(resp.items[0].outcome, DeleteOutcome::Deleted);
+        assert!(matches!(resp.items[1].outcome, DeleteOutcome::Failed(_)));
+        assert!(resp.note.is_none());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn recycle_falls_back_off_windows() {
+        let file = std::env::temp_dir().join(format!("tix-trash-{}.txt", std::process::id()));
+        std::fs::write(&file, b"x").unwrap();
+
+        let resp = delete(&FileDeleteRequest::new(vec![
+            file.to_string_lossy().to_string(),
+        ]));
+
+        assert!(!file.exists());
+        assert!(resp.fell_back_to_permanent());
+        assert!(resp.note.unwrap().contains("permanently"));
+        assert!(matches!(
+            restore(&file.to_string_lossy()),
+            TrashRestoreResponse::Unsupported(_)
+        ));
+    }
+}
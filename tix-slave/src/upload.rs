@@ -0,0 +1,624 @@
+//! Receiving side of chunked `FileWrite` uploads.
+//!
+//! An upload is a `FileTransferHeader`, any number of `FileChunk`s and a
+//! closing `FileHashVerification`, all under one request ID (see the
+//! wire diagram in `tix_core::protocol::file`). [`UploadSink`] writes the
+//! chunks as they arrive and checks the hash at the end; whatever goes
+//! wrong, the partial file is removed and the master gets exactly one
+//! [`FileTransferAck`]. Senders that ask for it also get
+//! [`FileWriteProgress`] along the way.
+//!
+//! Each upload runs in a task of its own ([`receive`]): it may wait for
+//! its path lock, and the writes stay off the main loop, which only
+//! passes the packets on.
+
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use tix_core::protocol::file::{ACK_EVERY_CHUNKS, ACK_INTERVAL, DEFAULT_UPLOAD_WINDOW};
+use tix_core::protocol::{
+    FileChunk, FileHashVerification, FileTransferAck, FileTransferHeader, FileWriteProgress,
+};
+use tix_core::{ConnectionSender, Packet, ProtocolFlags};
+use tokio::sync::mpsc;
+
+use crate::locks::{PathGuard, PathLocks};
+
+/// Packets an upload task may fall behind by before the main loop waits
+/// for it. A windowed sender never gets this far ahead.
+pub const UPLOAD_QUEUE: usize = 2 * DEFAULT_UPLOAD_WINDOW as usize;
+
+/// Where uploads with a relative path land: the user's Downloads folder
+/// if there is one, otherwise the working directory.
+pub fn default_upload_dir() -> PathBuf {
+    let home = std::env::var_os("USERPROFILE").or_else(|| std::env::var_os("HOME"));
+    if let Some(home) = home {
+        let downloads = Path::new(&home).join("Downloads");
+        if downloads.is_dir() {
+            return downloads;
+        }
+    }
+    std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+}
+
+/// Resolve the header path against `base`. Relative paths keep only their
+/// file name so an upload cannot climb out of `base`; an existing file is
+/// never overwritten, a ` (n)` suffix is added instead.
+fn destination(requested: &str, base: &Path) -> Option<PathBuf> {
+    let requested = Path::new(requested);
+    let path = if requested.is_absolute() {
+        requested.to_path_buf()
+    } else {
+        base.join(requested.file_name()?)
+    };
+    if !path.exists() {
+        return Some(path);
+    }
+
+    let stem = path.file_stem()?.to_string_lossy().to_string();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..1000)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|p| !p.exists())
+}
+
+/// One upload in progress.
+pub struct UploadSink {
+    path: PathBuf,
+    file: Option<File>,
+    hasher: blake3::Hasher,
+    written: u64,
+    expected_size: u64,
+    /// Chunks written so far.
+    chunks: u64,
+    expected_chunks: u64,
+    /// Largest chunk the header announced, the cap on what a
+    /// compressed chunk may inflate to.
+    chunk_size: u32,
+    /// Chunk count and time of the last progress report, when the
+    /// sender asked for reports.
+    reported: Option<(u64, Instant)>,
+    /// Exclusive lock on `path` for as long as the upload runs.
+    _lock: PathGuard,
+}
+
+impl UploadSink {
+    /// Lock and open the destination for `header`, waiting up to
+    /// `locks.timeout_secs` for a task busy with the path. On failure
+    /// the returned ack is the master's answer and no sink exists.
+    pub async fn begin(
+        header: &FileTransferHeader,
+        base: &Path,
+        locks: &PathLocks,
+        request_id: u64,
+    ) -> Result<Self, FileTransferAck> {
+        let reject = |path: String, error: String| FileTransferAck {
+            path,
+            bytes_written: 0,
+            error: Some(error),
+        };
+
+        if header.is_directory {
+            return Err(reject(
+                header.path.clone(),
+                "directory uploads are not supported".to_string(),
+            ));
+        }
+        let Some(path) = destination(&header.path, base) else {
+            return Err(reject(
+                header.path.clone(),
+                "no usable destination name".to_string(),
+            ));
+        };
+        let lock = locks
+            .write(request_id, &path)
+            .await
+            .map_err(|busy| reject(path.display().to_string(), busy.to_string()))?;
+        // A folder upload sends each file under its own subdirectory.
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| reject(path.display().to_string(), e.to_string()))?;
+        }
+        let file =
+            File::create(&path).map_err(|e| reject(path.display().to_string(), e.to_string()))?;
+
+        Ok(Self {
+            path,
+            file: Some(file),
+            hasher: blake3::Hasher::new(),
+            written: 0,
+            expected_size: header.size,
+            chunks: 0,
+            expected_chunks: header.total_chunks,
+            chunk_size: header.chunk_size,
+            reported: None,
+            _lock: lock,
+        })
+    }
+
+    /// Report progress to the sender; the first report is the one
+    /// returned, for the header.
+    pub fn with_progress(mut self) -> (Self, FileWriteProgress) {
+        self.reported = Some((self.chunks, Instant::now()));
+        let progress = self.progress();
+        (self, progress)
+    }
+
+    /// Destination on disk.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// What has been written so far. Chunks are written in order, so
+    /// there are never gaps to report.
+    pub fn progress(&self) -> FileWriteProgress {
+        FileWriteProgress::new(self.chunks, self.written)
+    }
+
+    /// The next progress report, if one is due at `now`: after
+    /// [`ACK_EVERY_CHUNKS`] new chunks, or after [`ACK_INTERVAL`] when
+    /// any chunk at all has landed since the last one.
+    pub fn progress_due(&mut self, now: Instant) -> Option<FileWriteProgress> {
+        let (chunks, at) = self.reported?;
+        let new = self.chunks - chunks;
+        let due = new >= ACK_EVERY_CHUNKS || (new > 0 && now.duration_since(at) >= ACK_INTERVAL);
+        if !due {
+            return None;
+        }
+        self.reported = Some((self.chunks, now));
+        Some(self.progress())
+    }
+
+    /// Write `chunk` at its offset. Chunks must arrive in order, since
+    /// the hash is computed as the data streams in. A compressed chunk
+    /// is inflated first; one that will not inflate aborts the upload.
+    pub fn write_chunk(&mut self, chunk: &FileChunk) -> Result<(), FileTransferAck> {
+        let inflated;
+        let chunk = if chunk.compressed {
+            inflated = match chunk.clone().unpack(self.chunk_size) {
+                Ok(inflated) => inflated,
+                Err(e) => return Err(self.abort(e.to_string())),
+            };
+            &inflated
+        } else {
+            chunk
+        };
+        if chunk.offset != self.written {
+            return Err(self.abort(format!(
+                "chunk {} starts at {}, expected {}",
+                chunk.chunk_index, chunk.offset, self.written
+            )));
+        }
+        if self.written + chunk.data.len() as u64 > self.expected_size {
+            return Err(self.abort("more data than the header announced".to_string()));
+        }
+
+        let Some(file) = self.file.as_mut() else {
+            return Err(self.abort("upload already closed".to_string()));
+        };
+        let result = file
+            .seek(SeekFrom::Start(chunk.offset))
+            .and_then(|_| file.write_all(&chunk.data));
+        if let Err(e) = result {
+            return Err(self.abort(e.to_string()));
+        }
+
+        self.hasher.update(&chunk.data);
+        self.written += chunk.data.len() as u64;
+        self.chunks += 1;
+        Ok(())
+    }
+
+    /// Check the closing verification and report the outcome. A
+    /// zero-byte file has no chunks at all: the header and this
+    /// verification are the whole upload.
+    pub fn finish(mut self, verification: &FileHashVerification) -> FileTransferAck {
+        if let Some(file) = self.file.take()
+            && let Err(e) = file.sync_all()
+        {
+            return self.abort(e.to_string());
+        }
+
+        if verification.total_bytes != self.written || self.written != self.expected_size {
+            let msg = format!(
+                "size mismatch: received {} of {} bytes",
+                self.written, verification.total_bytes
+            );
+            return self.abort(msg);
+        }
+        if verification.total_chunks != self.chunks || self.chunks != self.expected_chunks {
+            let msg = format!(
+                "chunk count mismatch: received {} of {}",
+                self.chunks, self.expected_chunks
+            );
+            return self.abort(msg);
+        }
+        if *self.hasher.finalize().as_bytes() != verification.blake3_hash {
+            return self.abort("hash mismatch".to_string());
+        }
+
+        FileTransferAck {
+            path: self.path.display().to_string(),
+            bytes_written: self.written,
+            error: None,
+        }
+    }
+
+    /// Drop the partial file and describe why.
+    pub fn abort(&mut self, error: String) -> FileTransferAck {
+        self.file = None;
+        let _ = std::fs::remove_file(&self.path);
+        FileTransferAck {
+            path: self.path.display().to_string(),
+            bytes_written: self.written,
+            error: Some(error),
+        }
+    }
+}
+
+/// Run the upload that `header` opens, fed the rest of its packets
+/// through `packets`, and answer it on `tx`. The upload is dropped,
+/// unanswered, if `packets` closes before the last packet.
+pub async fn receive(
+    request_id: u64,
+    header: Packet,
+    mut packets: mpsc::Receiver<Packet>,
+    base: PathBuf,
+    locks: PathLocks,
+    tx: ConnectionSender,
+) {
+    let ack = match FileTransferHeader::from_bytes(header.payload()) {
+        Ok(parsed) => match UploadSink::begin(&parsed, &base, &locks, request_id).await {
+            Ok(mut sink) => {
+                println!(
+                    "[UPLD] ReqID {}: receiving {} bytes into {}",
+                    request_id,
+                    parsed.size,
+                    sink.path().display()
+                );
+                if header.flags().contains(ProtocolFlags::ACK_REQUESTED) {
+                    let (windowed, progress) = sink.with_progress();
+                    sink = windowed;
+                    if let Ok(pkt) = progress.into_packet(request_id) {
+                        let _ = tx.send(pkt).await;
+                    }
+                }
+                match stream(request_id, sink, &mut packets, &tx).await {
+                    Some(ack) => ack,
+                    None => return,
+                }
+            }
+            Err(ack) => ack,
+        },
+        Err(e) => FileTransferAck {
+            path: String::new(),
+            bytes_written: 0,
+            error: Some(format!("expected an upload header: {}", e)),
+        },
+    };
+
+    match &ack.error {
+        None => println!(
+            "[UPLD] ReqID {}: {} bytes written to {}",
+            request_id, ack.bytes_written, ack.path
+        ),
+        Some(e) => println!("[ERR ] ReqID {}: upload failed: {}", request_id, e),
+    }
+    if let Ok(pkt) = ack.into_packet(request_id) {
+        let _ = tx.send(pkt).await;
+    }
+}
+
+/// Write chunks from `packets` until the closing one, and return the
+/// ack, or `None` once `packets` closes early.
+async fn stream(
+    request_id: u64,
+    mut sink: UploadSink,
+    packets: &mut mpsc::Receiver<Packet>,
+    tx: &ConnectionSender,
+) -> Option<FileTransferAck> {
+    while let Some(packet) = packets.recv().await {
+        if packet.flags().contains(ProtocolFlags::FINAL_FRAGMENT) {
+            if packet.payload().is_empty() {
+                println!("[UPLD] ReqID {}: cancelled by the master", request_id);
+                return Some(sink.abort("cancelled by the sender".to_string()));
+            }
+            return Some(match FileHashVerification::from_bytes(packet.payload()) {
+                Ok(verification) => sink.finish(&verification),
+                Err(e) => sink.abort(format!("invalid verification: {}", e)),
+            });
+        }
+        let result = FileChunk::from_bytes(packet.payload())
+            .map_err(|e| sink.abort(format!("invalid chunk: {}", e)))
+            .and_then(|chunk| sink.write_chunk(&chunk));
+        if let Err(ack) = result {
+            return Some(ack);
+        }
+        if let Some(progress) = sink.progress_due(Instant::now())
+            && let Ok(pkt) = progress.into_packet(request_id)
+        {
+            let _ = tx.send(pkt).await;
+        }
+    }
+    sink.abort("connection closed".to_string());
+    None
+}
+
+// ── Tests ────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tix_core::protocol::ChunkCompressor;
+    use tix_core::testing::temp_dir;
+
+    fn header(path: &str, size: u64) -> FileTransferHeader {
+        FileTransferHeader {
+            path: path.to_string(),
+            size,
+            modified: 0,
+            permissions: 0,
+            is_directory: false,
+            total_chunks: FileTransferHeader::compute_total_chunks(size, 4),
+            chunk_size: 4,
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_and_verifies() {
+        let dir = temp_dir("upload-ok");
+        let mut sink = UploadSink::begin(
+            &header("../../notes.txt", 6),
+            &dir,
+            &PathLocks::default(),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(sink.path(), dir.join("notes.txt"));
+
+        sink.write_chunk(&FileChunk::new(0, 0, b"hell".to_vec()))
+            .unwrap();
+        sink.write_chunk(&FileChunk::new(4, 1, b"o!".to_vec()))
+            .unwrap();
+        let hash = *blake3::hash(b"hello!").as_bytes();
+        let ack = sink.finish(&FileHashVerification::new(hash, 6, 2));
+
+        assert!(ack.is_ok(), "{:?}", ack.error);
+        assert_eq!(ack.bytes_written, 6);
+        assert_eq!(std::fs::read(dir.join("notes.txt")).unwrap(), b"hello!");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn progress_is_reported_every_few_chunks_or_on_a_timer() {
+        let dir = temp_dir("upload-progress");
+        let size = 4 * (ACK_EVERY_CHUNKS + 2);
+        let sink = UploadSink::begin(&header("big.bin", size), &dir, &PathLocks::default(), 1)
+            .await
+            .unwrap();
+        let (mut sink, first) = sink.with_progress();
+        assert_eq!(first, FileWriteProgress::new(0, 0));
+
+        let start = Instant::now();
+        let mut reports = Vec::new();
+        for i in 0..ACK_EVERY_CHUNKS + 1 {
+            sink.write_chunk(&FileChunk::new(i * 4, i, vec![0; 4]))
+                .unwrap();
+            reports.extend(sink.progress_due(start));
+        }
+        assert_eq!(
+            reports,
+            vec![FileWriteProgress::new(
+                ACK_EVERY_CHUNKS,
+                4 * ACK_EVERY_CHUNKS
+            )]
+        );
+
+        // One chunk since the last report: only the timer sends it.
+        assert_eq!(sink.progress_due(start), None);
+        let later = start + ACK_INTERVAL;
+        let report = sink.progress_due(later).unwrap();
+        assert_eq!(report.chunks_written, ACK_EVERY_CHUNKS + 1);
+        assert!(!report.has_gaps());
+        assert_eq!(sink.progress_due(later + ACK_INTERVAL), None);
+
+        let mut quiet = UploadSink::begin(&header("quiet.bin", 4), &dir, &PathLocks::default(), 2)
+            .await
+            .unwrap();
+        quiet
+            .write_chunk(&FileChunk::new(0, 0, vec![0; 4]))
+            .unwrap();
+        assert_eq!(quiet.progress_due(later), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn zero_byte_upload_is_header_and_verification() {
+        let dir = temp_dir("upload-empty");
+        let sink = UploadSink::begin(&header("empty.txt", 0), &dir, &PathLocks::default(), 1)
+            .await
+            .unwrap();
+        let hash = *blake3::hash(b"").as_bytes();
+        let ack = sink.finish(&FileHashVerification::new(hash, 0, 0));
+
+        assert!(ack.is_ok(), "{:?}", ack.error);
+        assert_eq!(ack.bytes_written, 0);
+        assert_eq!(std::fs::read(dir.join("empty.txt")).unwrap(), b"");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn missing_chunk_is_a_mismatch() {
+        let dir = temp_dir("upload-short");
+        let mut h = header("c.bin", 0);
+        h.total_chunks = 1;
+        let sink = UploadSink::begin(&h, &dir, &PathLocks::default(), 1)
+            .await
+            .unwrap();
+        let hash = *blake3::hash(b"").as_bytes();
+        let ack = sink.finish(&FileHashVerification::new(hash, 0, 0));
+        assert!(ack.error.unwrap().contains("chunk count"));
+        assert!(!dir.join("c.bin").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn bad_hash_removes_partial_file() {
+        let dir = temp_dir("upload-hash");
+        let sink = {
+            let mut sink = UploadSink::begin(&header("a.bin", 4), &dir, &PathLocks::default(), 1)
+                .await
+                .unwrap();
+            sink.write_chunk(&FileChunk::new(0, 0, b"abcd".to_vec()))
+                .unwrap();
+            sink
+        };
+        let ack = sink.finish(&FileHashVerification::new([0; 32], 4, 1));
+
+        assert_eq!(ack.error.as_deref(), Some("hash mismatch"));
+        assert!(!dir.join("a.bin").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn cancelled_upload_removes_partial_file() {
+        let dir = temp_dir("upload-cancel");
+        let mut sink = UploadSink::begin(&header("d.bin", 8), &dir, &PathLocks::default(), 1)
+            .await
+            .unwrap();
+        sink.write_chunk(&FileChunk::new(0, 0, b"abcd".to_vec()))
+            .unwrap();
+        assert!(dir.join("d.bin").exists());
+
+        let ack = sink.abort("cancelled by the sender".to_string());
+        assert_eq!(ack.error.as_deref(), Some("cancelled by the sender"));
+        assert_eq!(ack.bytes_written, 4);
+        assert!(!dir.join("d.bin").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn missing_parent_directories_are_created() {
+        let dir = temp_dir("upload-nested");
+        let path = dir.join("photos").join("2024").join("e.jpg");
+        let sink = UploadSink::begin(
+            &header(&path.display().to_string(), 0),
+            &std::env::temp_dir(),
+            &PathLocks::default(),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(sink.path(), path);
+        assert!(path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn out_of_order_chunk_aborts() {
+        let dir = temp_dir("upload-order");
+        let mut sink = UploadSink::begin(&header("b.bin", 8), &dir, &PathLocks::default(), 1)
+            .await
+            .unwrap();
+        let ack = sink
+            .write_chunk(&FileChunk::new(4, 1, b"efgh".to_vec()))
+            .unwrap_err();
+        assert!(ack.error.unwrap().contains("expected 0"));
+        assert!(!dir.join("b.bin").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn compressed_chunks_are_inflated_and_bombs_refused() {
+        let dir = temp_dir("upload-zstd");
+        let mut h = header("z.txt", 4096);
+        h.chunk_size = 4096;
+        h.total_chunks = 1;
+        let mut sink = UploadSink::begin(&h, &dir, &PathLocks::default(), 1)
+            .await
+            .unwrap();
+        let text = b"line\n".repeat(1024)[..4096].to_vec();
+        let chunk = ChunkCompressor::new(3).pack(0, 0, text.clone());
+        assert!(chunk.compressed);
+        sink.write_chunk(&chunk).unwrap();
+        let hash = *blake3::hash(&text).as_bytes();
+        let ack = sink.finish(&FileHashVerification::new(hash, 4096, 1));
+        assert!(ack.is_ok(), "{:?}", ack.error);
+        assert_eq!(std::fs::read(dir.join("z.txt")).unwrap(), text);
+
+        // Announced 4 KiB chunks, sent one that inflates to 1 MiB.
+        let mut sink = UploadSink::begin(&h, &dir, &PathLocks::default(), 2)
+            .await
+            .unwrap();
+        let bomb = ChunkCompressor::new(3).pack(0, 0, vec![0; 1 << 20]);
+        let ack = sink.write_chunk(&bomb).unwrap_err();
+        assert!(ack.error.unwrap().contains("does not inflate"));
+        assert!(!sink.path().exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn existing_file_is_not_overwritten() {
+        let dir = temp_dir("upload-dup");
+        std::fs::write(dir.join("report.pdf"), b"old").unwrap();
+        let sink = UploadSink::begin(&header("report.pdf", 0), &dir, &PathLocks::default(), 1)
+            .await
+            .unwrap();
+        assert_eq!(sink.path(), dir.join("report (1).pdf"));
+        assert_eq!(std::fs::read(dir.join("report.pdf")).unwrap(), b"old");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn locked_destination_is_refused_after_the_lock_timeout() {
+        let dir = temp_dir("upload-busy");
+        let locks = PathLocks::new(Duration::from_millis(50));
+        let _held = locks.write(1, &dir).await.unwrap();
+        let ack = UploadSink::begin(&header("c.bin", 4), &dir, &locks, 2)
+            .await
+            .err()
+            .unwrap();
+        assert!(ack.error.unwrap().starts_with("resource busy"));
+        assert!(!dir.join("c.bin").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn upload_waits_for_a_busy_destination() {
+        let dir = temp_dir("upload-wait");
+        let locks = PathLocks::new(Duration::from_secs(5));
+        let held = locks.write(1, &dir.join("c.bin")).await.unwrap();
+        let upload = {
+            let (dir, locks) = (dir.clone(), locks.clone());
+            tokio::spawn(async move {
+                UploadSink::begin(&header("c.bin", 4), &dir, &locks, 2)
+                    .await
+                    .map(|sink| sink.path().to_path_buf())
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!upload.is_finished());
+        drop(held);
+        let path = upload.await.unwrap().ok().unwrap();
+        assert_eq!(path, dir.join("c.bin"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn directories_are_rejected() {
+        let mut h = header("photos", 0);
+        h.is_directory = true;
+        let ack = UploadSink::begin(&h, &std::env::temp_dir(), &PathLocks::default(), 1)
+            .await
+            .err()
+            .unwrap();
+        assert!(ack.error.unwrap().contains("directory"));
+    }
+}
@@ -0,0 +1,425 @@
+//! End-to-end scenarios over loopback: the real slave loop driven by a
+//! [`HeadlessMaster`], and the screen pipeline fed by a
+//! [`SyntheticSource`]. Nothing here needs a display, admin rights or a
+//! second machine.
+
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use tix_core::network::wiretap::{CaptureReader, Direction};
+use tix_core::protocol::file::{
+    DEFAULT_CHUNK_SIZE, DeltaSyncRequest, FileChunk, FileDigest, FileHashVerification, apply_delta,
+};
+use tix_core::protocol::system::TaskFailure;
+use tix_core::protocol::{
+    NetDiagRequest, NetDiagResponse, PortCheckOutcome, ShellExecuteRequest, ShellExitStatus,
+    ShellOutputChunk, WireTapResponse, WireTapSettings,
+};
+use tix_core::rdp::client::ScreenClient;
+use tix_core::rdp::service::{ScreenService, ScreenServiceConfig};
+use tix_core::rdp::transport::ScreenTransport;
+use tix_core::rdp::types::PixelFormat;
+use tix_core::testing::{HeadlessMaster, SyntheticSource};
+use tix_core::{Command, FragmentReassembler, MAX_PAYLOAD_SIZE, Packet, ProtocolFlags, TixError};
+use tix_slave::audit::Audit;
+use tix_slave::config::SlaveConfig;
+use tix_slave::run_with_reconnect;
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+
+/// Start a slave dialling `master` and wait until it is connected.
+async fn connect_slave(master: &mut HeadlessMaster) -> JoinHandle<Result<(), TixError>> {
+    let info = master.info().clone();
+    let slave = tokio::spawn(async move {
+        run_with_reconnect(
+            &info,
+            &SlaveConfig::default(),
+            "e2e",
+            &Audit::default(),
+            None,
+        )
+        .await
+    });
+    master.accept().await.expect("slave never connected");
+    slave
+}
+
+async fn ping(master: &mut HeadlessMaster) {
+    let pong = master.request(Command::Ping, Vec::new()).await.unwrap();
+    assert_eq!(pong.payload(), b"Pong");
+}
+
+/// Run a shell request and collect its stdout and exit status.
+async fn shell(master: &mut HeadlessMaster, payload: Vec<u8>) -> (String, ShellExitStatus) {
+    let id = master
+        .send_command(Command::ShellExecute, payload)
+        .await
+        .unwrap();
+    let mut packets = master.stream(id).await.unwrap();
+    let last = packets.pop().unwrap();
+    assert_eq!(last.command().unwrap(), Command::ShellExecute);
+    let exit = ShellExitStatus::from_bytes(last.payload()).unwrap();
+    let mut stdout = Vec::new();
+    for packet in packets {
+        let chunk = ShellOutputChunk::from_bytes(packet.payload()).unwrap();
+        if chunk.is_stdout {
+            stdout.extend(chunk.data);
+        }
+    }
+    (String::from_utf8_lossy(&stdout).into_owned(), exit)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shell_round_trip() {
+    let mut master = HeadlessMaster::bind().await.unwrap();
+    let slave = connect_slave(&mut master).await;
+
+    let (text, exit) = shell(&mut master, b"echo hello".to_vec()).await;
+    assert!(text.contains("hello"), "{text}");
+    assert_eq!(exit.exit_code, 0);
+
+    slave.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shell_runs_in_the_requested_directory() {
+    let dir = std::env::temp_dir().join(format!("tix-e2e-cwd-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // `pwd` prints the resolved path (macOS puts temp under a symlink).
+    #[cfg(not(windows))]
+    let dir = dir.canonicalize().unwrap();
+
+    let mut master = HeadlessMaster::bind().await.unwrap();
+    let slave = connect_slave(&mut master).await;
+
+    #[cfg(windows)]
+    let command = "echo %CD% %TIX_E2E%";
+    #[cfg(not(windows))]
+    let command = "echo \"$(pwd) $TIX_E2E\"";
+    let request = ShellExecuteRequest::new(command)
+        .with_working_dir(dir.to_string_lossy())
+        .with_env("TIX_E2E", "marker");
+    let (text, _) = shell(&mut master, request.to_bytes().unwrap()).await;
+    assert!(text.contains(&*dir.to_string_lossy()), "{text}");
+    assert!(text.contains("marker"), "{text}");
+
+    // A directory that is not there fails the task instead of running
+    // the command somewhere else.
+    let missing =
+        ShellExecuteRequest::new(command).with_working_dir(dir.join("missing").to_string_lossy());
+    let reply = master
+        .request(Command::ShellExecute, missing.to_bytes().unwrap())
+        .await
+        .unwrap();
+    assert!(reply.flags().contains(ProtocolFlags::ERROR));
+    match TaskFailure::from_payload(reply.payload()) {
+        TaskFailure::Io(msg) => assert!(msg.contains("does not exist"), "{msg}"),
+        other => panic!("unexpected failure {other:?}"),
+    }
+
+    slave.abort();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn chunked_download_matches_the_file_hash() {
+    let path = std::env::temp_dir().join(format!("tix-e2e-download-{}", std::process::id()));
+    let data: Vec<u8> = (0..5 * 1024 * 1024u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect();
+    std::fs::write(&path, &data).unwrap();
+
+    let mut master = HeadlessMaster::bind().await.unwrap();
+    let slave = connect_slave(&mut master).await;
+
+    // Syncing against an empty copy downloads every chunk.
+    let empty = FileDigest::of(&[], DEFAULT_CHUNK_SIZE as u32);
+    let id = master.next_id();
+    let request = DeltaSyncRequest::new(path.to_string_lossy(), &empty);
+    master.send(request.into_packet(id).unwrap()).await.unwrap();
+    let packets = master.stream(id).await.unwrap();
+
+    let (last, chunks) = packets.split_last().unwrap();
+    assert!(last.flags().contains(ProtocolFlags::FINAL_FRAGMENT));
+    let verification = FileHashVerification::from_bytes(last.payload()).unwrap();
+    let chunks: Vec<FileChunk> = chunks
+        .iter()
+        .map(|p| FileChunk::from_bytes(p.payload()).unwrap())
+        .collect();
+    assert_eq!(chunks.len(), data.len().div_ceil(DEFAULT_CHUNK_SIZE));
+    assert_eq!(verification.total_chunks, chunks.len() as u64);
+
+    let received = apply_delta(&[], &chunks, verification.total_bytes);
+    assert_eq!(received.len(), data.len());
+    assert_eq!(
+        *blake3::hash(&received).as_bytes(),
+        verification.blake3_hash
+    );
+    assert_eq!(verification.blake3_hash, *blake3::hash(&data).as_bytes());
+
+    slave.abort();
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn huge_directory_listing_arrives_in_fragments() {
+    let dir = std::env::temp_dir().join(format!("tix-e2e-listing-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // Long names, so the listing outgrows a single packet.
+    for i in 0..2500 {
+        std::fs::write(dir.join(format!("{:0>120}", i)), b"").unwrap();
+    }
+
+    let mut master = HeadlessMaster::bind().await.unwrap();
+    let slave = connect_slave(&mut master).await;
+
+    let id = master
+        .send_command(Command::ListDir, dir.to_string_lossy().as_bytes().to_vec())
+        .await
+        .unwrap();
+    let mut reassembler = FragmentReassembler::new();
+    let listing = loop {
+        let packet = master.reply(id).await.unwrap();
+        assert!(packet.is_fragment());
+        if let Some(whole) = reassembler.push(&packet).unwrap() {
+            break String::from_utf8(whole.payload).unwrap();
+        }
+    };
+    assert!(listing.len() > MAX_PAYLOAD_SIZE);
+    // The `PATH|` header, then one entry per file.
+    assert!(listing.starts_with("PATH|"));
+    assert_eq!(listing.split(';').count(), 2501);
+
+    slave.abort();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancelled_task_reports_cancellation() {
+    let mut master = HeadlessMaster::bind().await.unwrap();
+    let slave = connect_slave(&mut master).await;
+
+    #[cfg(windows)]
+    let long = b"ping -n 11 127.0.0.1".to_vec();
+    #[cfg(not(windows))]
+    let long = b"sleep 10".to_vec();
+    let started = Instant::now();
+    let task = master
+        .send_command(Command::ShellExecute, long)
+        .await
+        .unwrap();
+    // Let the pool pick the task up before cancelling it.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let reply = master
+        .request(Command::ShellCancel, task.to_le_bytes().to_vec())
+        .await
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(reply.payload()),
+        format!("Cancelled ReqID {task}")
+    );
+
+    let failure = master.reply(task).await.unwrap();
+    assert!(failure.flags().contains(ProtocolFlags::ERROR));
+    assert_eq!(
+        TaskFailure::from_payload(failure.payload()),
+        TaskFailure::Cancelled
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    slave.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reused_request_id_runs_once() {
+    let marker = std::env::temp_dir().join(format!("tix-e2e-dupl-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+
+    let mut master = HeadlessMaster::bind().await.unwrap();
+    let slave = connect_slave(&mut master).await;
+
+    #[cfg(windows)]
+    let command = format!("echo run>> \"{}\" & ping -n 11 127.0.0.1", marker.display());
+    #[cfg(not(windows))]
+    let command = format!("echo run >> '{}' && sleep 10", marker.display());
+    let id = master.next_id();
+    for _ in 0..2 {
+        let packet = Packet::new_command(id, Command::ShellExecute, command.clone().into_bytes());
+        master.send(packet.unwrap()).await.unwrap();
+    }
+
+    let refusal = master.reply(id).await.unwrap();
+    assert!(refusal.flags().contains(ProtocolFlags::ERROR));
+    assert_eq!(
+        TaskFailure::from_payload(refusal.payload()),
+        TaskFailure::DuplicateRequest
+    );
+
+    // The first request is still the one running, and cancel reaches it.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let reply = master
+        .request(Command::ShellCancel, id.to_le_bytes().to_vec())
+        .await
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(reply.payload()),
+        format!("Cancelled ReqID {id}")
+    );
+    let failure = master.reply(id).await.unwrap();
+    assert_eq!(
+        TaskFailure::from_payload(failure.payload()),
+        TaskFailure::Cancelled
+    );
+    let runs = std::fs::read_to_string(&marker).unwrap_or_default();
+    assert_eq!(runs.lines().count(), 1, "{runs:?}");
+
+    // Once finished, the ID is free again.
+    let ping = Packet::new_command(id, Command::Ping, Vec::new()).unwrap();
+    master.send(ping).await.unwrap();
+    assert_eq!(master.reply(id).await.unwrap().payload(), b"Pong");
+
+    slave.abort();
+    let _ = std::fs::remove_file(&marker);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn port_check_reaches_the_masters_own_listener() {
+    let mut master = HeadlessMaster::bind().await.unwrap();
+    let slave = connect_slave(&mut master).await;
+    let listener = master.info().clone();
+
+    let req = NetDiagRequest::port_check(listener.ip(), listener.port());
+    let reply = master
+        .request(Command::NetDiag, req.to_bytes().unwrap())
+        .await
+        .unwrap();
+    let NetDiagResponse::PortCheck(result) = NetDiagResponse::from_bytes(reply.payload()).unwrap()
+    else {
+        panic!("not a port check reply");
+    };
+    assert_eq!(result.outcome, PortCheckOutcome::Open, "{:?}", result);
+    assert_eq!(result.address.unwrap().port(), listener.port());
+
+    slave.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn wire_tap_captures_the_slaves_traffic() {
+    let mut master = HeadlessMaster::bind().await.unwrap();
+    let slave = connect_slave(&mut master).await;
+    let capture = std::env::temp_dir().join(format!("tix-e2e-wire-{}.cap", std::process::id()));
+    let _ = std::fs::remove_file(&capture);
+
+    let mut settings = WireTapSettings {
+        enabled: true,
+        dump_bytes: 16,
+        capture: Some(capture.display().to_string()),
+    };
+    let reply = master
+        .request(Command::WireTap, settings.to_bytes().unwrap())
+        .await
+        .unwrap();
+    let applied = WireTapResponse::from_bytes(reply.payload()).unwrap();
+    assert_eq!(applied, WireTapResponse::Applied(settings.clone()));
+    ping(&mut master).await;
+
+    settings.enabled = false;
+    settings.capture = None;
+    master
+        .request(Command::WireTap, settings.to_bytes().unwrap())
+        .await
+        .unwrap();
+
+    let pings: Vec<(Direction, Vec<u8>)> = CaptureReader::open(&capture)
+        .unwrap()
+        .map(Result::unwrap)
+        .map(|record| (record.direction, record.packet().unwrap()))
+        .filter(|(_, packet)| packet.command().unwrap() == Command::Ping)
+        .map(|(direction, packet)| (direction, packet.payload().to_vec()))
+        .collect();
+    std::fs::remove_file(&capture).unwrap();
+    assert_eq!(
+        pings,
+        [
+            (Direction::Received, Vec::new()),
+            (Direction::Sent, b"Pong".to_vec())
+        ]
+    );
+
+    slave.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn slave_reconnects_after_the_master_drops() {
+    let mut master = HeadlessMaster::bind().await.unwrap();
+    let slave = connect_slave(&mut master).await;
+    ping(&mut master).await;
+
+    master.disconnect();
+    // The slave waits out its base backoff (one second) before dialling.
+    master.accept().await.expect("slave did not come back");
+    ping(&mut master).await;
+
+    slave.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hundred_synthetic_frames_reach_the_viewer() {
+    const FRAMES: u64 = 100;
+    const WIDTH: u32 = 320;
+    const HEIGHT: u32 = 240;
+
+    let send_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let recv_sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let send_addr = send_sock.local_addr().unwrap();
+    let recv_addr = recv_sock.local_addr().unwrap();
+
+    let config = ScreenServiceConfig {
+        block_size: 16,
+        capture_timeout_ms: 10,
+        allow_format_downgrade: false,
+        stall_timeout: None,
+        ..ScreenServiceConfig::default()
+    };
+    let source = SyntheticSource::new(WIDTH, HEIGHT).with_limit(FRAMES);
+    let mut service =
+        ScreenService::with_source(ScreenTransport::new(send_sock, recv_addr), config, source);
+    let stop_service = service.stop_handle();
+    let service_task = tokio::spawn(async move { service.run().await });
+
+    let mut client = ScreenClient::new(
+        ScreenTransport::new(recv_sock, send_addr),
+        PixelFormat::Bgra8,
+    );
+    let mut stats = client.stats_receiver();
+    let frames = client.frame_receiver();
+    let stop_client = client.stop_handle();
+    let client_task = tokio::spawn(async move { client.run().await });
+
+    // Stop at the last frame, or once the stream has gone quiet.
+    let _ = tokio::time::timeout(Duration::from_secs(10), async {
+        while stats.borrow().frame_number < FRAMES - 1 {
+            if stats.changed().await.is_err() {
+                break;
+            }
+        }
+    })
+    .await;
+
+    stop_service.store(false, Ordering::SeqCst);
+    stop_client.store(false, Ordering::SeqCst);
+    service_task.await.unwrap().unwrap();
+    client_task.abort();
+
+    let received = stats.borrow().total_frames;
+    assert!(
+        received * 100 >= FRAMES * 99,
+        "{received} of {FRAMES} frames arrived"
+    );
+    let last = frames.borrow().clone();
+    if last.frame_number == FRAMES - 1 && received == FRAMES {
+        let expected = SyntheticSource::frame(WIDTH, HEIGHT, FRAMES - 1);
+        assert!(last.buffer == expected.data, "last frame decoded wrong");
+    }
+}